@@ -0,0 +1,45 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Feed raw bytes to the p2p receiver:  a preamble, then whatever frame follows it.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use blockstack_lib::codec::PREAMBLE_ENCODED_SIZE;
+use blockstack_lib::net::codec::validate_frame;
+use blockstack_lib::net::{ProtocolFamily, StacksP2P};
+
+fuzz_target!(|data: &[u8]| {
+    let mut protocol = StacksP2P::new();
+    let (preamble, _) = match protocol.read_preamble(data) {
+        Ok(res) => res,
+        Err(_) => {
+            return;
+        }
+    };
+    let frame = &data[(PREAMBLE_ENCODED_SIZE as usize)..];
+    if frame.len() < preamble.payload_len as usize {
+        return;
+    }
+    let frame = &frame[..(preamble.payload_len as usize)];
+
+    let valid = validate_frame(&preamble, frame).is_ok();
+    if protocol.read_payload(&preamble, frame).is_ok() {
+        assert!(valid, "read a frame that doesn't validate");
+    }
+});
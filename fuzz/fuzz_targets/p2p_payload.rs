@@ -0,0 +1,62 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Feed raw bytes to the p2p payload decoder as a frame with no relayers, and check that every
+//! payload it accepts re-encodes to a frame that passes `validate_frame()`.
+
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+
+use blockstack_lib::codec::{StacksMessageCodec, MAX_FRAME_LEN};
+use blockstack_lib::net::codec::validate_frame;
+use blockstack_lib::net::{Preamble, StacksMessageType};
+use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+use blockstack_lib::util::secp256k1::MessageSignature;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() > MAX_FRAME_LEN as usize {
+        return;
+    }
+    let payload = match StacksMessageType::consensus_deserialize(&mut Cursor::new(data)) {
+        Ok(payload) => payload,
+        Err(_) => {
+            return;
+        }
+    };
+
+    let mut frame = vec![0x00, 0x00, 0x00, 0x00];
+    payload
+        .consensus_serialize(&mut frame)
+        .expect("failed to re-encode a decoded payload");
+    let preamble = Preamble {
+        peer_version: 0,
+        network_id: 0,
+        seq: 0,
+        burn_block_height: 0,
+        burn_block_hash: BurnchainHeaderHash([0; 32]),
+        burn_stable_block_height: 0,
+        burn_stable_block_hash: BurnchainHeaderHash([0; 32]),
+        additional_data: 0,
+        signature: MessageSignature::empty(),
+        payload_len: frame.len() as u32,
+    };
+    if let Err(e) = validate_frame(&preamble, &frame) {
+        panic!("valid payload {:?} failed validation: {:?}", &payload, &e);
+    }
+});
@@ -32,6 +32,7 @@ use blockstack_lib::burnchains::bitcoin::address::{
     ADDRESS_VERSION_MAINNET_SINGLESIG, ADDRESS_VERSION_TESTNET_SINGLESIG,
 };
 use blockstack_lib::burnchains::Address;
+use blockstack_lib::chainstate::stacks::boot::cost_voting::CostVotingCall;
 use blockstack_lib::chainstate::stacks::{
     StacksBlock, StacksMicroblock, StacksPrivateKey, StacksPublicKey, StacksTransaction,
     StacksTransactionSigner, TokenTransferMemo, TransactionAnchorMode, TransactionAuth,
@@ -62,6 +63,7 @@ This CLI has these methods:
 
   publish            used to generate and sign a contract publish transaction
   contract-call      used to generate and sign a contract-call transaction
+  cost-vote          used to generate and sign a call to the cost-voting contract
   generate-sk        used to generate a secret key for transaction signing
   token-transfer     used to generate and sign a transfer transaction
   addresses          used to get both Bitcoin and Stacks addresses from a private key
@@ -119,6 +121,35 @@ e.g.,
                        -x 050011deadbeef11ababffff11deadbeef11ababffff
 ";
 
+const COST_VOTE_USAGE: &str = "blockstack-cli (options) cost-vote [origin-secret-key-hex] [fee-rate] [nonce] [action] [args...]
+
+The cost-vote command generates and signs a call to the cost-voting boot contract, which
+governs the cost functions of Clarity functions. If successful, this command outputs the
+hex string encoding of the transaction to stdout, and exits with code 0.
+
+The action is one of:
+
+  submit-proposal [function-contract] [function-name] [cost-function-contract] [cost-function-name]
+                   propose that [cost-function-name] in [cost-function-contract] cost
+                   [function-name] in [function-contract]
+  vote [proposal-id] [amount]
+                   lock [amount] uSTX in favor of a proposal
+  withdraw-votes [proposal-id] [amount]
+                   unlock [amount] previously voted uSTX
+  veto [proposal-id]
+                   veto a vote-confirmed proposal, as the miner of the previous block
+  confirm-votes [proposal-id]
+                   confirm that a proposal has enough votes
+  confirm-miners [proposal-id]
+                   confirm that a vote-confirmed proposal was not vetoed
+
+A cost-vote call can be mined in a Stacks block, a Stacks microblock, or either.  The default
+is that the miner chooses, but you can decide which with the following options:
+
+  --microblock-only  indicates to mine this transaction only in a microblock
+  --block-only       indicates to mine this transaction only in a block
+";
+
 const TOKEN_TRANSFER_USAGE: &str = "blockstack-cli (options) token-transfer [origin-secret-key-hex] [fee-rate] [nonce] [recipient-address] [amount] [memo] [args...]
 
 The transfer command generates and signs a STX transfer transaction. If successful,
@@ -498,6 +529,119 @@ fn handle_contract_call(
     Ok(to_hex(&signed_tx_bytes))
 }
 
+fn handle_cost_vote(
+    args_slice: &[String],
+    version: TransactionVersion,
+    chain_id: u32,
+) -> Result<String, CliError> {
+    let mut args = args_slice.to_vec();
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", COST_VOTE_USAGE)));
+    }
+    let anchor_mode = parse_anchor_mode(&mut args, COST_VOTE_USAGE)?;
+    if args.len() < 5 {
+        return Err(CliError::Message(format!(
+            "Incorrect argument count supplied \n\nUSAGE:\n {}",
+            COST_VOTE_USAGE
+        )));
+    }
+    let sk_origin = StacksPrivateKey::from_hex(&args[0])?;
+    let tx_fee = args[1].parse()?;
+    let nonce = args[2].parse()?;
+    let action = &args[3];
+    let action_args = &args[4..];
+
+    let expect_args = |count: usize| -> Result<(), CliError> {
+        if action_args.len() != count {
+            Err(CliError::Message(format!(
+                "Incorrect argument count supplied for {} \n\nUSAGE:\n {}",
+                action, COST_VOTE_USAGE
+            )))
+        } else {
+            Ok(())
+        }
+    };
+    let parse_principal = |principal: &str| -> Result<PrincipalData, CliError> {
+        PrincipalData::parse(principal)
+            .map_err(|_e| CliError::Message(format!("Failed to parse principal {}", principal)))
+    };
+
+    let call = match action.as_str() {
+        "submit-proposal" => {
+            expect_args(4)?;
+            CostVotingCall::SubmitProposal {
+                function_contract: parse_principal(&action_args[0])?,
+                function_name: action_args[1].clone(),
+                cost_function_contract: parse_principal(&action_args[2])?,
+                cost_function_name: action_args[3].clone(),
+            }
+        }
+        "vote" => {
+            expect_args(2)?;
+            CostVotingCall::Vote {
+                proposal_id: action_args[0].parse()?,
+                amount: action_args[1].parse()?,
+            }
+        }
+        "withdraw-votes" => {
+            expect_args(2)?;
+            CostVotingCall::WithdrawVotes {
+                proposal_id: action_args[0].parse()?,
+                amount: action_args[1].parse()?,
+            }
+        }
+        "veto" => {
+            expect_args(1)?;
+            CostVotingCall::Veto {
+                proposal_id: action_args[0].parse()?,
+            }
+        }
+        "confirm-votes" => {
+            expect_args(1)?;
+            CostVotingCall::ConfirmVotes {
+                proposal_id: action_args[0].parse()?,
+            }
+        }
+        "confirm-miners" => {
+            expect_args(1)?;
+            CostVotingCall::ConfirmMiners {
+                proposal_id: action_args[0].parse()?,
+            }
+        }
+        _ => {
+            return Err(CliError::Message(format!(
+                "Unknown cost-vote action {} \n\nUSAGE:\n {}",
+                action, COST_VOTE_USAGE
+            )))
+        }
+    };
+
+    let payload = call
+        .to_payload(version == TransactionVersion::Mainnet)
+        .map_err(|e| CliError::Message(format!("{}", e)))?;
+    let mut unsigned_tx = make_standard_single_sig_tx(
+        version,
+        chain_id,
+        payload,
+        &StacksPublicKey::from_private(&sk_origin),
+        nonce,
+        tx_fee,
+    );
+    unsigned_tx.anchor_mode = anchor_mode;
+
+    let mut unsigned_tx_bytes = vec![];
+    unsigned_tx
+        .consensus_serialize(&mut unsigned_tx_bytes)
+        .expect("FATAL: invalid transaction");
+    let signed_tx = sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), &sk_origin)?;
+
+    let mut signed_tx_bytes = vec![];
+    signed_tx
+        .consensus_serialize(&mut signed_tx_bytes)
+        .expect("FATAL: invalid signed transaction");
+    Ok(to_hex(&signed_tx_bytes))
+}
+
 fn handle_token_transfer(
     args_slice: &[String],
     version: TransactionVersion,
@@ -817,6 +961,7 @@ fn main_handler(mut argv: Vec<String>) -> Result<String, CliError> {
         match method.as_str() {
             "contract-call" => handle_contract_call(args, tx_version, chain_id),
             "publish" => handle_contract_publish(args, tx_version, chain_id),
+            "cost-vote" => handle_cost_vote(args, tx_version, chain_id),
             "token-transfer" => handle_token_transfer(args, tx_version, chain_id),
             "generate-sk" => generate_secret_key(args, tx_version),
             "addresses" => get_addresses(args, tx_version),
@@ -875,6 +1020,84 @@ mod test {
         .contains("IO error"));
     }
 
+    #[test]
+    fn simple_cost_vote() {
+        let sk = "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3";
+        let cv_args = [
+            "--testnet",
+            "cost-vote",
+            sk,
+            "1",
+            "0",
+            "submit-proposal",
+            "ST000000000000000000002AMW42H.pox",
+            "get-pox-info",
+            "ST000000000000000000002AMW42H.costs",
+            "cost_stx_balance",
+        ];
+
+        let tx_hex = main_handler(to_string_vec(&cv_args)).unwrap();
+        let tx = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(
+            &hex_bytes(&tx_hex).unwrap(),
+        ))
+        .unwrap();
+        match tx.payload {
+            TransactionPayload::ContractCall(ref call) => {
+                assert_eq!(call.address, StacksAddress::burn_address(false));
+                assert_eq!(call.contract_name.as_str(), "cost-voting");
+                assert_eq!(call.function_name.as_str(), "submit-proposal");
+                assert_eq!(call.function_args.len(), 4);
+            }
+            _ => panic!("Expected a contract call"),
+        }
+
+        let cv_args = [
+            "cost-vote",
+            sk,
+            "1",
+            "1",
+            "--block-only",
+            "vote",
+            "0",
+            "1000",
+        ];
+        let tx_hex = main_handler(to_string_vec(&cv_args)).unwrap();
+        let tx = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(
+            &hex_bytes(&tx_hex).unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(tx.anchor_mode, TransactionAnchorMode::OnChainOnly);
+        match tx.payload {
+            TransactionPayload::ContractCall(ref call) => {
+                assert_eq!(call.address, StacksAddress::burn_address(true));
+                assert_eq!(call.function_name.as_str(), "vote-proposal");
+                assert_eq!(call.function_args, vec![Value::UInt(0), Value::UInt(1000)]);
+            }
+            _ => panic!("Expected a contract call"),
+        }
+
+        for cv_args in [
+            vec!["cost-vote", sk, "1", "0", "vote", "0"],
+            vec!["cost-vote", sk, "1", "0", "confirm-votes", "-1"],
+            vec!["cost-vote", sk, "1", "0", "no-such-action", "0"],
+            vec![
+                "cost-vote",
+                sk,
+                "1",
+                "0",
+                "submit-proposal",
+                "not-a-principal",
+                "get-pox-info",
+                "ST000000000000000000002AMW42H.costs",
+                "cost_stx_balance",
+            ],
+        ]
+        .iter()
+        {
+            assert!(main_handler(to_string_vec(cv_args)).is_err());
+        }
+    }
+
     #[test]
     fn simple_token_transfer() {
         let tt_args = [
@@ -34,6 +34,12 @@ use chainstate::stacks::{
 pub enum BitcoinAddressType {
     PublicKeyHash,
     ScriptHash,
+    /// Native segwit v0 pay-to-witness-public-key-hash.  Its hash is the hash160 of a compressed
+    /// public key, just like a `PublicKeyHash`'s, so it has the same version byte.
+    WitnessPublicKeyHash,
+    /// Segwit v1 pay-to-taproot, with its 32-byte output key.  Its hash is the hash160 of the
+    /// output key, which identifies the output but can't be paid to as anything but taproot.
+    WitnessTaproot([u8; 32]),
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
@@ -53,14 +59,20 @@ pub fn address_type_to_version_byte(
     network_id: BitcoinNetworkType,
 ) -> u8 {
     match (addrtype, network_id) {
-        (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Mainnet) => {
+        (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Mainnet)
+        | (BitcoinAddressType::WitnessPublicKeyHash, BitcoinNetworkType::Mainnet)
+        | (BitcoinAddressType::WitnessTaproot(_), BitcoinNetworkType::Mainnet) => {
             ADDRESS_VERSION_MAINNET_SINGLESIG
         }
         (BitcoinAddressType::ScriptHash, BitcoinNetworkType::Mainnet) => {
             ADDRESS_VERSION_MAINNET_MULTISIG
         }
         (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Testnet)
-        | (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Regtest) => {
+        | (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Regtest)
+        | (BitcoinAddressType::WitnessPublicKeyHash, BitcoinNetworkType::Testnet)
+        | (BitcoinAddressType::WitnessPublicKeyHash, BitcoinNetworkType::Regtest)
+        | (BitcoinAddressType::WitnessTaproot(_), BitcoinNetworkType::Testnet)
+        | (BitcoinAddressType::WitnessTaproot(_), BitcoinNetworkType::Regtest) => {
             ADDRESS_VERSION_TESTNET_SINGLESIG
         }
         (BitcoinAddressType::ScriptHash, BitcoinNetworkType::Testnet)
@@ -92,6 +104,61 @@ pub fn version_byte_to_address_type(
     }
 }
 
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// BIP350 constant which a bech32m checksum is XOR'ed with (bech32's is 1)
+const BECH32M_CONST: u32 = 0x2bc830a3;
+
+/// BIP173 checksum over a human-readable part and 5-bit data values
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk: u32 = 1;
+    for value in values.iter() {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ (*value as u32);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+/// Encode a segwit program as a BIP173 bech32 address (version 0) or a BIP350 bech32m address
+/// (versions 1 and up)
+fn bech32_encode_segwit(hrp: &str, version: u8, program: &[u8]) -> String {
+    // witness version, then the program regrouped into 5-bit values
+    let mut data = vec![version];
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for byte in program.iter() {
+        acc = ((acc << 8) | (*byte as u32)) & 0xfff;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            data.push(((acc >> bits) & 0x1f) as u8);
+        }
+    }
+    if bits > 0 {
+        data.push(((acc << (5 - bits)) & 0x1f) as u8);
+    }
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 0x1f));
+    values.extend_from_slice(&data);
+    values.extend_from_slice(&[0u8; 6]);
+    let checksum = bech32_polymod(&values) ^ if version == 0 { 1 } else { BECH32M_CONST };
+    for i in 0..6 {
+        data.push(((checksum >> (5 * (5 - i))) & 0x1f) as u8);
+    }
+
+    let mut ret = format!("{}1", hrp);
+    ret.extend(data.iter().map(|d| BECH32_CHARSET[*d as usize] as char));
+    ret
+}
+
 pub fn to_c32_version_byte(version: u8) -> Option<u8> {
     match version {
         ADDRESS_VERSION_MAINNET_SINGLESIG => Some(C32_ADDRESS_VERSION_MAINNET_SINGLESIG),
@@ -214,11 +281,29 @@ impl BitcoinAddress {
         return ret;
     }
 
+    /// Base58check-encode this address.  Witness addresses have no base58 form, so they encode
+    /// as the p2pkh address of the same key hash.
     pub fn to_b58(&self) -> String {
         let versioned_bytes = self.to_versioned_bytes();
         base58::check_encode_slice(&versioned_bytes)
     }
 
+    /// Bech32m-encode this address as a p2tr address if it's one, or else bech32-encode its key
+    /// hash as a p2wpkh address
+    pub fn to_bech32(&self) -> String {
+        let hrp = match self.network_id {
+            BitcoinNetworkType::Mainnet => "bc",
+            BitcoinNetworkType::Testnet => "tb",
+            BitcoinNetworkType::Regtest => "bcrt",
+        };
+        match self.addrtype {
+            BitcoinAddressType::WitnessTaproot(ref output_key) => {
+                bech32_encode_segwit(hrp, 1, output_key)
+            }
+            _ => bech32_encode_segwit(hrp, 0, self.bytes.as_bytes()),
+        }
+    }
+
     pub fn to_c32(&self) -> String {
         let version_byte = address_type_to_version_byte(self.addrtype, self.network_id);
         let c32_address_byte = to_c32_version_byte(version_byte).unwrap(); // NOTE: should never panic, since (addrtype, network_id) always maps to a valid Bitcoin version byte
@@ -239,6 +324,44 @@ impl BitcoinAddress {
         }
     }
 
+    pub fn to_p2wpkh_tx_out(bytes: &Hash160, value: u64) -> TxOut {
+        let script_pubkey = BtcScriptBuilder::new()
+            .push_int(0)
+            .push_slice(&bytes.0)
+            .into_script();
+        TxOut {
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub fn to_p2tr_tx_out(output_key: &[u8; 32], value: u64) -> TxOut {
+        let script_pubkey = BtcScriptBuilder::new()
+            .push_int(1)
+            .push_slice(output_key)
+            .into_script();
+        TxOut {
+            value,
+            script_pubkey,
+        }
+    }
+
+    /// Make an output which pays `value` to this address
+    pub fn to_tx_out(&self, value: u64) -> TxOut {
+        match self.addrtype {
+            BitcoinAddressType::PublicKeyHash => {
+                BitcoinAddress::to_p2pkh_tx_out(&self.bytes, value)
+            }
+            BitcoinAddressType::ScriptHash => BitcoinAddress::to_p2sh_tx_out(&self.bytes, value),
+            BitcoinAddressType::WitnessPublicKeyHash => {
+                BitcoinAddress::to_p2wpkh_tx_out(&self.bytes, value)
+            }
+            BitcoinAddressType::WitnessTaproot(ref output_key) => {
+                BitcoinAddress::to_p2tr_tx_out(output_key, value)
+            }
+        }
+    }
+
     pub fn to_p2sh_tx_out(bytes: &Hash160, value: u64) -> TxOut {
         let script_pubkey = BtcScriptBuilder::new()
             .push_opcode(BtcOp::OP_HASH160)
@@ -271,7 +394,12 @@ impl Address for BitcoinAddress {
 
 impl std::fmt::Display for BitcoinAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.to_b58().fmt(f)
+        match self.addrtype {
+            BitcoinAddressType::WitnessPublicKeyHash | BitcoinAddressType::WitnessTaproot(_) => {
+                self.to_bech32().fmt(f)
+            }
+            _ => self.to_b58().fmt(f),
+        }
     }
 }
 
@@ -368,6 +496,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_to_bech32() {
+        // from BIP173
+        let bytes = Hash160::from_hex("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let addr = BitcoinAddress {
+            network_id: BitcoinNetworkType::Mainnet,
+            addrtype: BitcoinAddressType::WitnessPublicKeyHash,
+            bytes: bytes.clone(),
+        };
+        assert_eq!(
+            addr.to_bech32(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert_eq!(
+            addr.to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+
+        let addr = BitcoinAddress {
+            network_id: BitcoinNetworkType::Testnet,
+            addrtype: BitcoinAddressType::WitnessPublicKeyHash,
+            bytes,
+        };
+        assert_eq!(
+            addr.to_bech32(),
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+        );
+
+        assert_eq!(
+            BitcoinAddress::to_p2wpkh_tx_out(&addr.bytes, 1).script_pubkey,
+            hex_bytes("0014751e76e8199196d454941c45d1b3a323f1433bd6")
+                .unwrap()
+                .into()
+        );
+    }
+
+    #[test]
+    fn test_to_bech32m() {
+        // from BIP350
+        let mut output_key = [0u8; 32];
+        output_key.copy_from_slice(
+            &hex_bytes("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(),
+        );
+        let addr = BitcoinAddress {
+            network_id: BitcoinNetworkType::Mainnet,
+            addrtype: BitcoinAddressType::WitnessTaproot(output_key),
+            bytes: Hash160::from_data(&output_key),
+        };
+        assert_eq!(
+            addr.to_string(),
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+        );
+        assert_eq!(
+            addr.to_tx_out(1).script_pubkey,
+            hex_bytes("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .unwrap()
+                .into()
+        );
+    }
+
     #[test]
     fn test_from_scriptpubkey() {
         let fixtures = vec![
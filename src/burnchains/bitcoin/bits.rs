@@ -51,7 +51,11 @@ pub fn parse_script<'a>(script: &'a Script) -> Vec<Instruction<'a>> {
 impl BitcoinTxInput {
     pub fn to_address_bits(&self) -> Vec<u8> {
         let hash_mode = match self.in_type {
-            BitcoinInputType::Standard => {
+            BitcoinInputType::Taproot => {
+                // no public key to hash
+                return vec![0u8; 20];
+            }
+            BitcoinInputType::Standard | BitcoinInputType::SegwitNative => {
                 if self.keys.len() == 1 {
                     AddressHashMode::SerializeP2PKH
                 } else {
@@ -495,6 +499,55 @@ impl BitcoinTxInput {
         })
     }
 
+    /// Parse a native p2wpkh transaction input -- an empty scriptsig, and a witness of a
+    /// signature and a public key -- into a BitcoinTxInput.
+    pub fn from_bitcoin_witness_txin(txin: &BtcTxIn) -> Option<BitcoinTxInput> {
+        if !txin.script_sig.is_empty() || txin.witness.len() != 2 {
+            test_debug!("Not a native p2wpkh input");
+            return None;
+        }
+
+        let pubkey = match BitcoinPublicKey::from_slice(&txin.witness[1][..]) {
+            Ok(pubkey) => pubkey,
+            Err(_) => {
+                test_debug!("Not a native p2wpkh input: witness item 1 is not a public key");
+                return None;
+            }
+        };
+
+        Some(BitcoinTxInput {
+            tx_ref: to_txid(txin),
+            keys: vec![pubkey],
+            num_required: 1,
+            in_type: BitcoinInputType::SegwitNative,
+        })
+    }
+
+    /// Parse a p2tr key-path spend -- an empty scriptsig, and a witness of a Schnorr signature
+    /// and maybe an annex -- into a BitcoinTxInput.  Script-path spends are not supported.
+    pub fn from_bitcoin_taproot_txin(txin: &BtcTxIn) -> Option<BitcoinTxInput> {
+        if !txin.script_sig.is_empty() {
+            test_debug!("Not a p2tr input");
+            return None;
+        }
+
+        // BIP341: with two or more witness items, the last is an annex if it starts with 0x50
+        let has_annex =
+            txin.witness.len() >= 2 && txin.witness[txin.witness.len() - 1].first() == Some(&0x50);
+        let num_items = txin.witness.len() - if has_annex { 1 } else { 0 };
+        if num_items != 1 || (txin.witness[0].len() != 64 && txin.witness[0].len() != 65) {
+            test_debug!("Not a p2tr key-path spend");
+            return None;
+        }
+
+        Some(BitcoinTxInput {
+            tx_ref: to_txid(txin),
+            keys: vec![],
+            num_required: 0,
+            in_type: BitcoinInputType::Taproot,
+        })
+    }
+
     /// parse a Bitcoin transaction input into a BitcoinTxInput
     pub fn from_bitcoin_txin(txin: &BtcTxIn) -> Option<BitcoinTxInput> {
         let input_txid = to_txid(txin);
@@ -557,6 +610,46 @@ impl BitcoinTxOutput {
         }
     }
 
+    /// Parse a burnchain tx output from a native p2wpkh bitcoin output
+    pub fn from_bitcoin_witness_txout(
+        network_id: BitcoinNetworkType,
+        txout: &BtcTxOut,
+    ) -> Option<BitcoinTxOutput> {
+        if !txout.script_pubkey.is_v0_p2wpkh() {
+            return None;
+        }
+        let address = BitcoinAddress::from_bytes(
+            network_id,
+            BitcoinAddressType::WitnessPublicKeyHash,
+            &txout.script_pubkey.as_bytes()[2..22],
+        )
+        .ok()?;
+        Some(BitcoinTxOutput {
+            address,
+            units: txout.value,
+        })
+    }
+
+    /// Parse a burnchain tx output from a p2tr bitcoin output
+    pub fn from_bitcoin_taproot_txout(
+        network_id: BitcoinNetworkType,
+        txout: &BtcTxOut,
+    ) -> Option<BitcoinTxOutput> {
+        if !txout.script_pubkey.is_v1_p2tr() {
+            return None;
+        }
+        let mut output_key = [0u8; 32];
+        output_key.copy_from_slice(&txout.script_pubkey.as_bytes()[2..34]);
+        Some(BitcoinTxOutput {
+            address: BitcoinAddress {
+                network_id,
+                addrtype: BitcoinAddressType::WitnessTaproot(output_key),
+                bytes: Hash160::from_data(&output_key),
+            },
+            units: txout.value,
+        })
+    }
+
     /// Parse a burnchain tx output from a bitcoin output
     pub fn from_bitcoin_txout(
         network_id: BitcoinNetworkType,
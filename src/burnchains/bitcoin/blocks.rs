@@ -31,6 +31,7 @@ use burnchains::indexer::{
 };
 use burnchains::Error as burnchain_error;
 use burnchains::{BurnchainBlock, BurnchainTransaction, MagicBytes, Txid, MAGIC_BYTES_LENGTH};
+use core::{StacksEpoch, StacksEpochId};
 use deps;
 use deps::bitcoin::blockdata::block::{Block, LoneBlockHeader};
 use deps::bitcoin::blockdata::opcodes::All as btc_opcodes;
@@ -98,6 +99,9 @@ pub struct BitcoinBlockDownloader {
 pub struct BitcoinBlockParser {
     network_id: BitcoinNetworkType,
     magic_bytes: MagicBytes,
+    /// burnchain height at which native p2wpkh and p2tr inputs and outputs become acceptable (the
+    /// start of Stacks 2.1), if ever
+    witness_height: Option<u64>,
 }
 
 impl BitcoinBlockDownloader {
@@ -232,9 +236,27 @@ impl BitcoinBlockParser {
         BitcoinBlockParser {
             network_id: network_id,
             magic_bytes: magic_bytes.clone(),
+            witness_height: None,
         }
     }
 
+    /// Accept native p2wpkh and p2tr inputs and outputs in burnchain transactions from the start
+    /// of Stacks 2.1 onwards, if it's scheduled in `epochs`
+    pub fn with_stacks_epochs(mut self, epochs: &[StacksEpoch]) -> BitcoinBlockParser {
+        self.witness_height = epochs
+            .iter()
+            .find(|epoch| epoch.epoch_id >= StacksEpochId::Epoch21)
+            .map(|epoch| epoch.start_height);
+        self
+    }
+
+    /// Are native p2wpkh and p2tr inputs and outputs acceptable at this height?
+    fn allow_witness(&self, block_height: u64) -> bool {
+        self.witness_height
+            .map(|height| height <= block_height)
+            .unwrap_or(false)
+    }
+
     /// Verify that a block matches a header
     pub fn check_block(block: &Block, header: &LoneBlockHeader) -> bool {
         if header.header.bitcoin_hash() != block.bitcoin_hash() {
@@ -294,15 +316,19 @@ impl BitcoinBlockParser {
 
     /// Is this an acceptable transaction?  It must have
     /// * an OP_RETURN output at output 0
-    /// * only p2pkh or p2sh outputs for outputs 1...n
-    fn maybe_burnchain_tx(&self, tx: &Transaction) -> bool {
+    /// * only p2pkh or p2sh outputs for outputs 1...n (or p2wpkh or p2tr, if `allow_witness`)
+    fn maybe_burnchain_tx(&self, tx: &Transaction, allow_witness: bool) -> bool {
         if self.parse_data(&tx.output[0].script_pubkey).is_none() {
             test_debug!("Tx {:?} has no valid OP_RETURN", tx.txid());
             return false;
         }
 
         for i in 1..tx.output.len() {
-            if !tx.output[i].script_pubkey.is_p2pkh() && !tx.output[i].script_pubkey.is_p2sh() {
+            let script_pubkey = &tx.output[i].script_pubkey;
+            let recognized = script_pubkey.is_p2pkh()
+                || script_pubkey.is_p2sh()
+                || (allow_witness && (script_pubkey.is_v0_p2wpkh() || script_pubkey.is_v1_p2tr()));
+            if !recognized {
                 // unrecognized output type
                 test_debug!(
                     "Tx {:?} has unrecognized output type in output {}",
@@ -318,10 +344,16 @@ impl BitcoinBlockParser {
 
     /// Parse a transaction's inputs into burnchain tx inputs.
     /// Succeeds only if we can parse each input.
-    fn parse_inputs(&self, tx: &Transaction) -> Option<Vec<BitcoinTxInput>> {
+    fn parse_inputs(&self, tx: &Transaction, allow_witness: bool) -> Option<Vec<BitcoinTxInput>> {
         let mut ret = vec![];
         for inp in &tx.input {
-            match BitcoinTxInput::from_bitcoin_txin(&inp) {
+            let input = if allow_witness && inp.script_sig.is_empty() {
+                BitcoinTxInput::from_bitcoin_witness_txin(&inp)
+                    .or_else(|| BitcoinTxInput::from_bitcoin_taproot_txin(&inp))
+            } else {
+                BitcoinTxInput::from_bitcoin_txin(&inp)
+            };
+            match input {
                 None => {
                     test_debug!("Failed to parse input");
                     return None;
@@ -337,10 +369,17 @@ impl BitcoinBlockParser {
     /// Parse a transaction's outputs into burnchain tx outputs.
     /// Succeeds only if we can parse each output.
     /// Does not parse the first output -- this is the OP_RETURN
-    fn parse_outputs(&self, tx: &Transaction) -> Option<Vec<BitcoinTxOutput>> {
+    fn parse_outputs(&self, tx: &Transaction, allow_witness: bool) -> Option<Vec<BitcoinTxOutput>> {
         let mut ret = vec![];
         for outp in &tx.output[1..tx.output.len()] {
-            match BitcoinTxOutput::from_bitcoin_txout(self.network_id, &outp) {
+            let output = if allow_witness && outp.script_pubkey.is_v0_p2wpkh() {
+                BitcoinTxOutput::from_bitcoin_witness_txout(self.network_id, &outp)
+            } else if allow_witness && outp.script_pubkey.is_v1_p2tr() {
+                BitcoinTxOutput::from_bitcoin_taproot_txout(self.network_id, &outp)
+            } else {
+                BitcoinTxOutput::from_bitcoin_txout(self.network_id, &outp)
+            };
+            match output {
                 None => {
                     test_debug!("Failed to parse output");
                     return None;
@@ -353,9 +392,15 @@ impl BitcoinBlockParser {
         Some(ret)
     }
 
-    /// Parse a Bitcoin transaction into a Burnchain transaction
-    pub fn parse_tx(&self, tx: &Transaction, vtxindex: usize) -> Option<BitcoinTransaction> {
-        if !self.maybe_burnchain_tx(tx) {
+    /// Parse a Bitcoin transaction in the block at `block_height` into a Burnchain transaction
+    pub fn parse_tx(
+        &self,
+        tx: &Transaction,
+        vtxindex: usize,
+        block_height: u64,
+    ) -> Option<BitcoinTransaction> {
+        let allow_witness = self.allow_witness(block_height);
+        if !self.maybe_burnchain_tx(tx, allow_witness) {
             test_debug!("Not a burnchain tx");
             return None;
         }
@@ -369,8 +414,8 @@ impl BitcoinBlockParser {
         let data_amt = tx.output[0].value;
 
         let (opcode, data) = data_opt.unwrap();
-        let inputs_opt = self.parse_inputs(tx);
-        let outputs_opt = self.parse_outputs(tx);
+        let inputs_opt = self.parse_inputs(tx, allow_witness);
+        let outputs_opt = self.parse_outputs(tx, allow_witness);
 
         match (inputs_opt, outputs_opt) {
             (Some(inputs), Some(outputs)) => {
@@ -398,7 +443,7 @@ impl BitcoinBlockParser {
         let mut accepted_txs = vec![];
         for i in 0..block.txdata.len() {
             let tx = &block.txdata[i];
-            match self.parse_tx(tx, i) {
+            match self.parse_tx(tx, i, block_height) {
                 Some(bitcoin_tx) => {
                     accepted_txs.push(bitcoin_tx);
                 }
@@ -475,12 +520,20 @@ mod tests {
         BitcoinBlock, BitcoinInputType, BitcoinTransaction, BitcoinTxInput, BitcoinTxOutput,
     };
     use burnchains::{BurnchainBlock, BurnchainTransaction, MagicBytes, Txid};
+    use core::{
+        StacksEpoch, StacksEpochId, PEER_VERSION_EPOCH_2_0, PEER_VERSION_EPOCH_2_05,
+        STACKS_EPOCH_MAX,
+    };
     use deps::bitcoin::blockdata::block::{Block, LoneBlockHeader};
-    use deps::bitcoin::blockdata::transaction::Transaction;
+    use deps::bitcoin::blockdata::opcodes;
+    use deps::bitcoin::blockdata::script::{Builder, Script};
+    use deps::bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
     use deps::bitcoin::network::encodable::VarInt;
     use deps::bitcoin::network::serialize::deserialize;
-    use util::hash::hex_bytes;
+    use deps::bitcoin::util::hash::Sha256dHash;
+    use util::hash::{hex_bytes, Hash160};
     use util::log;
+    use vm::costs::ExecutionCost;
 
     use crate::types::chainstate::BurnchainHeaderHash;
 
@@ -562,7 +615,7 @@ mod tests {
         let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100])); // "id"
         for tx_fixture in tx_fixtures {
             let tx = make_tx(&tx_fixture.txstr).unwrap();
-            let res = parser.maybe_burnchain_tx(&tx);
+            let res = parser.maybe_burnchain_tx(&tx, false);
             assert_eq!(res, tx_fixture.result);
         }
     }
@@ -730,7 +783,7 @@ mod tests {
         let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100])); // "id"
         for tx_fixture in tx_fixtures {
             let tx = make_tx(&tx_fixture.txstr).unwrap();
-            let burnchain_tx = parser.parse_tx(&tx, vtxindex as usize);
+            let burnchain_tx = parser.parse_tx(&tx, vtxindex as usize, 0);
             assert!(burnchain_tx.is_some());
             assert_eq!(burnchain_tx, tx_fixture.result);
         }
@@ -756,7 +809,7 @@ mod tests {
         let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100])); // "id"
         for tx_fixture in tx_fixtures_strange {
             let tx = make_tx(&tx_fixture.txstr).unwrap();
-            let burnchain_tx = parser.parse_tx(&tx, vtxindex as usize);
+            let burnchain_tx = parser.parse_tx(&tx, vtxindex as usize, 0);
             assert!(burnchain_tx.is_none());
         }
     }
@@ -979,4 +1032,150 @@ mod tests {
             assert_eq!(parsed_block_opt, block_fixture.result);
         }
     }
+
+    #[test]
+    fn parse_witness_tx_test() {
+        // one native p2wpkh input, an OP_RETURN, and one native p2wpkh output
+        let pubkey =
+            hex_bytes("03ef2340518b5867b23598a9cf74611f8b98064f7d55cdb8c107c67b5efcbc5c77")
+                .unwrap();
+        let pubkey_hash = Hash160::from_data(&pubkey);
+
+        let mut sig = hex_bytes("3045022100be57031bf2c095945ba2876e97b3f86ee051643a29b908f22ed45ccf58620103022061e056e5f48c5a51c66604a1ca28e4bfaabab1478424c9bbb396cc6afe5c222e").unwrap();
+        sig.push(0x01);
+
+        let tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Sha256dHash([0x11; 32]),
+                    vout: 0,
+                },
+                script_sig: Script::new(),
+                sequence: 0xfffffffd,
+                witness: vec![sig, pubkey.clone()],
+            }],
+            output: vec![
+                TxOut {
+                    value: 0,
+                    script_pubkey: Builder::new()
+                        .push_opcode(opcodes::All::OP_RETURN)
+                        .push_slice(b"id+0123456789")
+                        .into_script(),
+                },
+                BitcoinAddress::to_p2wpkh_tx_out(&pubkey_hash, 12345),
+            ],
+        };
+
+        let epochs = vec![
+            StacksEpoch {
+                epoch_id: StacksEpochId::Epoch20,
+                start_height: 0,
+                end_height: 100,
+                block_limit: ExecutionCost::max_value(),
+                network_epoch: PEER_VERSION_EPOCH_2_0,
+            },
+            StacksEpoch {
+                epoch_id: StacksEpochId::Epoch21,
+                start_height: 100,
+                end_height: STACKS_EPOCH_MAX,
+                block_limit: ExecutionCost::max_value(),
+                network_epoch: PEER_VERSION_EPOCH_2_05,
+            },
+        ];
+
+        // never accepted without a 2.1 epoch
+        let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100]));
+        assert!(parser.parse_tx(&tx, 1, 100).is_none());
+
+        // only accepted once 2.1 begins
+        let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100]))
+            .with_stacks_epochs(&epochs);
+        assert!(parser.parse_tx(&tx, 1, 99).is_none());
+
+        let parsed = parser.parse_tx(&tx, 1, 100).unwrap();
+        assert_eq!(parsed.opcode, b'+');
+        assert_eq!(parsed.inputs.len(), 1);
+        assert_eq!(parsed.inputs[0].in_type, BitcoinInputType::SegwitNative);
+        assert_eq!(parsed.inputs[0].keys.len(), 1);
+        assert_eq!(parsed.inputs[0].keys[0].to_bytes_compressed(), pubkey);
+        assert_eq!(parsed.outputs.len(), 1);
+        assert_eq!(parsed.outputs[0].units, 12345);
+        assert_eq!(
+            parsed.outputs[0].address.addrtype,
+            BitcoinAddressType::WitnessPublicKeyHash
+        );
+        assert_eq!(parsed.outputs[0].address.bytes, pubkey_hash);
+    }
+
+    #[test]
+    fn parse_taproot_tx_test() {
+        // two p2tr key-path spends (one with an annex), an OP_RETURN, and one p2tr output
+        let mut output_key = [0u8; 32];
+        output_key.copy_from_slice(
+            &hex_bytes("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(),
+        );
+        let taproot_txin = |vout: u32, witness: Vec<Vec<u8>>| TxIn {
+            previous_output: OutPoint {
+                txid: Sha256dHash([0x11; 32]),
+                vout,
+            },
+            script_sig: Script::new(),
+            sequence: 0xfffffffd,
+            witness,
+        };
+
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![
+                taproot_txin(0, vec![vec![0x22; 64]]),
+                taproot_txin(1, vec![vec![0x22; 65], vec![0x50, 0x01]]),
+            ],
+            output: vec![
+                TxOut {
+                    value: 0,
+                    script_pubkey: Builder::new()
+                        .push_opcode(opcodes::All::OP_RETURN)
+                        .push_slice(b"id+0123456789")
+                        .into_script(),
+                },
+                BitcoinAddress::to_p2tr_tx_out(&output_key, 12345),
+            ],
+        };
+
+        let epochs = vec![StacksEpoch {
+            epoch_id: StacksEpochId::Epoch21,
+            start_height: 100,
+            end_height: STACKS_EPOCH_MAX,
+            block_limit: ExecutionCost::max_value(),
+            network_epoch: PEER_VERSION_EPOCH_2_05,
+        }];
+        let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100]))
+            .with_stacks_epochs(&epochs);
+        assert!(parser.parse_tx(&tx, 1, 99).is_none());
+
+        let parsed = parser.parse_tx(&tx, 1, 100).unwrap();
+        assert_eq!(parsed.inputs.len(), 2);
+        for (i, input) in parsed.inputs.iter().enumerate() {
+            assert_eq!(input.in_type, BitcoinInputType::Taproot);
+            assert!(input.keys.is_empty());
+            assert_eq!(input.tx_ref.1, i as u32);
+        }
+        assert_eq!(parsed.outputs.len(), 1);
+        assert_eq!(parsed.outputs[0].units, 12345);
+        assert_eq!(
+            parsed.outputs[0].address.addrtype,
+            BitcoinAddressType::WitnessTaproot(output_key)
+        );
+        assert_eq!(
+            parsed.outputs[0].address.bytes,
+            Hash160::from_data(&output_key)
+        );
+
+        // script-path spends aren't supported
+        tx.input[1] = taproot_txin(1, vec![vec![0x22; 64], vec![0x51], vec![0xc0; 33]]);
+        assert!(parser.parse_tx(&tx, 1, 100).is_none());
+    }
 }
@@ -18,6 +18,7 @@ use rand::{thread_rng, Rng};
 use std::fs;
 use std::net;
 use std::net::Shutdown;
+use std::net::ToSocketAddrs;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::path;
@@ -45,6 +46,7 @@ use deps::bitcoin::blockdata::block::LoneBlockHeader;
 use deps::bitcoin::network::message::NetworkMessage;
 use deps::bitcoin::network::serialize::BitcoinHash;
 use deps::bitcoin::network::serialize::Error as btc_serialization_err;
+use util::get_epoch_time_secs;
 use util::log;
 
 use core::{StacksEpoch, STACKS_EPOCHS_MAINNET, STACKS_EPOCHS_REGTEST, STACKS_EPOCHS_TESTNET};
@@ -99,6 +101,33 @@ fn get_bitcoin_stacks_epochs(network_id: BitcoinNetworkType) -> Vec<StacksEpoch>
     }
 }
 
+// how often, in seconds, we check whether a preferred bitcoind has come back while we're using
+// one of its backups
+pub const BITCOIN_ENDPOINT_FAILBACK_INTERVAL: u64 = 60;
+
+// how long, in seconds, we wait to connect to a bitcoind when checking whether it's back
+pub const BITCOIN_ENDPOINT_PROBE_TIMEOUT: u64 = 5;
+
+/// A bitcoind we can sync from
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BitcoinEndpoint {
+    pub host: String,
+    pub peer_port: u16,
+    pub rpc_port: u16,
+}
+
+impl BitcoinEndpoint {
+    /// Can we open a connection to its p2p port?
+    pub fn is_reachable(&self, timeout: Duration) -> bool {
+        match (self.host.as_str(), self.peer_port).to_socket_addrs() {
+            Ok(addrs) => addrs
+                .into_iter()
+                .any(|addr| net::TcpStream::connect_timeout(&addr, timeout).is_ok()),
+            Err(_) => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BitcoinIndexerConfig {
     // config fields
@@ -113,6 +142,8 @@ pub struct BitcoinIndexerConfig {
     pub first_block: u64,
     pub magic_bytes: MagicBytes,
     pub epochs: Option<Vec<StacksEpoch>>,
+    // bitcoinds to fail over to if peer_host goes down, in order of preference
+    pub backup_endpoints: Vec<BitcoinEndpoint>,
 }
 
 #[derive(Debug)]
@@ -126,6 +157,9 @@ pub struct BitcoinIndexerRuntime {
     pub last_getdata_send_time: u64,
     pub last_getheaders_send_time: u64,
     pub timeout: u64,
+    // which of the config's endpoints we're talking to (0 is peer_host)
+    pub endpoint_index: usize,
+    pub last_failback_check: u64,
 }
 
 pub struct BitcoinIndexer {
@@ -148,6 +182,7 @@ impl BitcoinIndexerConfig {
             first_block: 0,
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
             epochs: None,
+            backup_endpoints: vec![],
         }
     }
 }
@@ -165,6 +200,8 @@ impl BitcoinIndexerRuntime {
             last_getdata_send_time: 0,
             last_getheaders_send_time: 0,
             timeout: 300,
+            endpoint_index: 0,
+            last_failback_check: 0,
         }
     }
 }
@@ -184,12 +221,89 @@ impl BitcoinIndexer {
         }
     }
 
-    /// (re)connect to our configured network peer.
+    /// Our bitcoinds, in order of preference: the configured peer, then its backups
+    pub fn endpoints(&self) -> Vec<BitcoinEndpoint> {
+        let mut endpoints = vec![BitcoinEndpoint {
+            host: self.config.peer_host.clone(),
+            peer_port: self.config.peer_port,
+            rpc_port: self.config.rpc_port,
+        }];
+        endpoints.extend(self.config.backup_endpoints.iter().cloned());
+        endpoints
+    }
+
+    /// The bitcoind we're talking to
+    pub fn current_endpoint(&self) -> BitcoinEndpoint {
+        let mut endpoints = self.endpoints();
+        let index = self.runtime.endpoint_index.min(endpoints.len() - 1);
+        endpoints.swap_remove(index)
+    }
+
+    /// Close our connection to the bitcoind, if we have one
+    fn disconnect_peer(&mut self) {
+        if let Some(s) = self.runtime.sock.take() {
+            let _ = s.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Switch to the next bitcoind, wrapping around to the most preferred one.  The caller must
+    /// reconnect.  Returns false if there's no other bitcoind to switch to.
+    pub fn fail_over(&mut self) -> bool {
+        let num_endpoints = self.endpoints().len();
+        if num_endpoints < 2 {
+            return false;
+        }
+        let from = self.current_endpoint();
+        self.runtime.endpoint_index = (self.runtime.endpoint_index + 1) % num_endpoints;
+        self.disconnect_peer();
+
+        let to = self.current_endpoint();
+        warn!(
+            "Failing over from bitcoind {}:{} to {}:{}",
+            &from.host, from.peer_port, &to.host, to.peer_port
+        );
+        true
+    }
+
+    /// If we're using a backup bitcoind, and it's been a while since we checked, see if a more
+    /// preferred one is reachable again and switch back to it.  The caller must reconnect.
+    /// Returns true if we switched.
+    pub fn fail_back(&mut self) -> bool {
+        if self.runtime.endpoint_index == 0 {
+            return false;
+        }
+        let now = get_epoch_time_secs();
+        if self.runtime.last_failback_check + BITCOIN_ENDPOINT_FAILBACK_INTERVAL > now {
+            return false;
+        }
+        self.runtime.last_failback_check = now;
+
+        let timeout = Duration::from_secs(BITCOIN_ENDPOINT_PROBE_TIMEOUT);
+        let endpoints = self.endpoints();
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            if i >= self.runtime.endpoint_index {
+                break;
+            }
+            if endpoint.is_reachable(timeout) {
+                info!(
+                    "Failing back to bitcoind {}:{}",
+                    &endpoint.host, endpoint.peer_port
+                );
+                self.runtime.endpoint_index = i;
+                self.disconnect_peer();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// (re)connect to our current bitcoind.
     /// Sets self.runtime.sock to a new socket referring to our configured
     /// Bitcoin peer.  If we fail to connect, this method sets the socket
     /// to None.
     fn reconnect_peer(&mut self) -> Result<(), btc_error> {
-        match net::TcpStream::connect((self.config.peer_host.as_str(), self.config.peer_port)) {
+        let endpoint = self.current_endpoint();
+        match net::TcpStream::connect((endpoint.host.as_str(), endpoint.peer_port)) {
             Ok(s) => {
                 // Disable Nagle algorithm
                 s.set_nodelay(true).map_err(|_e| {
@@ -261,7 +375,10 @@ impl BitcoinIndexer {
         message_handler: &mut T,
         initial_handshake: bool,
     ) -> Result<(), btc_error> {
-        let mut do_handshake = initial_handshake || !self.is_connected();
+        // go back to a more preferred bitcoind if it's up again
+        let failed_back = self.fail_back();
+
+        let mut do_handshake = initial_handshake || failed_back || !self.is_connected();
         let mut keep_going = true;
         let mut initiated = false;
 
@@ -345,7 +462,9 @@ impl BitcoinIndexer {
                     debug!("Received unrecognized network command while receiving a message: {}, ignoring", s);
                 }
                 Err(e) => {
+                    // e.g. the bitcoind stopped answering; try another one
                     warn!("Unhandled error while receiving a message: {:?}", e);
+                    self.fail_over();
                     do_handshake = true;
                 }
             }
@@ -818,6 +937,7 @@ impl BurnchainIndexer for BitcoinIndexer {
 
     fn parser(&self) -> BitcoinBlockParser {
         BitcoinBlockParser::new(self.runtime.network_id, self.config.magic_bytes)
+            .with_stacks_epochs(&self.get_stacks_epochs())
     }
 }
 
@@ -1227,6 +1347,7 @@ mod test {
             first_block: 0,
             magic_bytes: MagicBytes([105, 100]),
             epochs: None,
+            backup_endpoints: vec![],
         };
 
         if fs::metadata(&indexer_conf.spv_headers_path).is_ok() {
@@ -1237,4 +1358,78 @@ mod test {
         let last_block = indexer.sync_headers(0, None).unwrap();
         eprintln!("sync'ed to block {}", last_block);
     }
+
+    #[test]
+    fn test_indexer_endpoint_failover() {
+        // one bitcoind that's up, and two that are down
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let up_port = listener.local_addr().unwrap().port();
+        let down_port_1 = net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let down_port_2 = net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut indexer_conf =
+            BitcoinIndexerConfig::test_default("/tmp/test_indexer_endpoint_failover".to_string());
+        indexer_conf.peer_port = down_port_1;
+        indexer_conf.backup_endpoints = vec![
+            BitcoinEndpoint {
+                host: "127.0.0.1".to_string(),
+                peer_port: down_port_2,
+                rpc_port: 18443,
+            },
+            BitcoinEndpoint {
+                host: "127.0.0.1".to_string(),
+                peer_port: up_port,
+                rpc_port: 18443,
+            },
+        ];
+        let mut indexer = BitcoinIndexer::new(
+            indexer_conf,
+            BitcoinIndexerRuntime::new(BitcoinNetworkType::Regtest),
+        );
+
+        assert_eq!(indexer.endpoints().len(), 3);
+        assert_eq!(indexer.current_endpoint().peer_port, down_port_1);
+        assert!(indexer.reconnect_peer().is_err());
+
+        // fail over until we reach a bitcoind that's up
+        assert!(indexer.fail_over());
+        assert_eq!(indexer.current_endpoint().peer_port, down_port_2);
+        assert!(indexer.reconnect_peer().is_err());
+
+        assert!(indexer.fail_over());
+        assert_eq!(indexer.current_endpoint().peer_port, up_port);
+        assert!(indexer.reconnect_peer().is_ok());
+        assert!(indexer.is_connected());
+
+        // nothing better is up
+        assert!(!indexer.fail_back());
+        assert_eq!(indexer.current_endpoint().peer_port, up_port);
+
+        // the most preferred bitcoind comes back, but we only check every so often
+        let _listener_1 = net::TcpListener::bind(("127.0.0.1", down_port_1)).unwrap();
+        assert!(!indexer.fail_back());
+
+        indexer.runtime.last_failback_check = 0;
+        assert!(indexer.fail_back());
+        assert_eq!(indexer.current_endpoint().peer_port, down_port_1);
+        assert!(!indexer.is_connected());
+
+        // failing over wraps around
+        indexer.runtime.endpoint_index = 2;
+        assert!(indexer.fail_over());
+        assert_eq!(indexer.runtime.endpoint_index, 0);
+
+        // nothing to fail over to
+        indexer.config.backup_endpoints.clear();
+        assert!(!indexer.fail_over());
+        assert_eq!(indexer.current_endpoint().peer_port, down_port_1);
+    }
 }
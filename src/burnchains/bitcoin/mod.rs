@@ -164,6 +164,10 @@ pub struct BitcoinTxOutput {
 pub enum BitcoinInputType {
     Standard,
     SegwitP2SH,
+    /// native segwit v0 p2wpkh, whose key hash is the same as the p2pkh one
+    SegwitNative,
+    /// segwit v1 p2tr key-path spend, which reveals no public key
+    Taproot,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -153,9 +153,10 @@ impl BitcoinIndexer {
     /// Do the initial handshake to the remote peer.
     /// Returns the remote peer's block height
     pub fn peer_handshake(&mut self) -> Result<u64, btc_error> {
+        let endpoint = self.current_endpoint();
         debug!(
             "Begin peer handshake to {}:{}",
-            self.config.peer_host, self.config.peer_port
+            &endpoint.host, endpoint.peer_port
         );
         self.send_version()?;
         let version_reply = self.recv_message()?;
@@ -166,20 +167,24 @@ impl BitcoinIndexer {
 
         debug!(
             "Established connection to {}:{}, who has {} blocks",
-            self.config.peer_host, self.config.peer_port, self.runtime.block_height
+            &endpoint.host, endpoint.peer_port, self.runtime.block_height
         );
         Ok(self.runtime.block_height)
     }
 
     /// Connect to a remote peer, do a handshake with the remote peer, and use exponential backoff until we
-    /// succeed in establishing a connection.
+    /// succeed in establishing a connection.  If we have backup bitcoinds, then each failure fails
+    /// over to the next one, and we only back off once we've tried them all.
     /// This method masks ConnectionBroken errors, but does not mask other network errors.
     /// Returns the remote peer's block height on success
     pub fn connect_handshake_backoff(&mut self) -> Result<u64, btc_error> {
         let mut backoff: f64 = 1.0;
         let mut rng = thread_rng();
+        let num_endpoints = self.endpoints().len();
+        let mut failures = 0;
 
         loop {
+            let endpoint = self.current_endpoint();
             let connection_result = self.connect();
             match connection_result {
                 Ok(()) => {
@@ -192,14 +197,15 @@ impl BitcoinIndexer {
                         }
                         Err(btc_error::ConnectionBroken) => {
                             // need to try again
-                            backoff = 2.0 * backoff + (backoff * rng.gen_range(0.0, 1.0));
                         }
                         Err(e) => {
-                            // propagate other network error
+                            // propagate other network error, but try a different bitcoind
+                            // next time
                             warn!(
                                 "Failed to handshake with {}:{}: {:?}",
-                                &self.config.peer_host, self.config.peer_port, &e
+                                &endpoint.host, endpoint.peer_port, &e
                             );
+                            self.fail_over();
                             return Err(e);
                         }
                     }
@@ -207,12 +213,18 @@ impl BitcoinIndexer {
                 Err(err_msg) => {
                     error!(
                         "Failed to connect to peer {}:{}: {}",
-                        &self.config.peer_host, self.config.peer_port, err_msg
+                        &endpoint.host, endpoint.peer_port, err_msg
                     );
-                    backoff = 2.0 * backoff + (backoff * rng.gen_range(0.0, 1.0));
                 }
             }
 
+            failures += 1;
+            if self.fail_over() && failures % num_endpoints != 0 {
+                // haven't tried every bitcoind yet
+                continue;
+            }
+            backoff = 2.0 * backoff + (backoff * rng.gen_range(0.0, 1.0));
+
             // don't sleep more than 60 seconds
             if backoff > 60.0 {
                 backoff = 60.0;
@@ -253,9 +265,10 @@ impl BitcoinIndexer {
             relay: false,
         };
 
+        let endpoint = self.current_endpoint();
         debug!(
             "Send version (nonce={}) to {}:{}",
-            self.runtime.version_nonce, self.config.peer_host, self.config.peer_port
+            self.runtime.version_nonce, &endpoint.host, endpoint.peer_port
         );
         self.send_message(btc_message::NetworkMessage::Version(payload))
     }
@@ -341,11 +354,12 @@ impl BitcoinIndexer {
             btc_message_blockdata::GetHeadersMessage::new(vec![prev_block_hash], prev_block_hash);
         let payload = btc_message::NetworkMessage::GetHeaders(getheaders);
 
+        let endpoint = self.current_endpoint();
         debug!(
             "Send GetHeaders {} for 2000 headers to {}:{}",
             prev_block_hash.be_hex_string(),
-            self.config.peer_host,
-            self.config.peer_port
+            &endpoint.host,
+            endpoint.peer_port
         );
 
         self.runtime.last_getheaders_send_time = get_epoch_time_secs();
@@ -366,12 +380,13 @@ impl BitcoinIndexer {
         let getdata = btc_message::NetworkMessage::GetData(getdata_invs);
 
         self.runtime.last_getdata_send_time = get_epoch_time_secs();
+        let endpoint = self.current_endpoint();
         debug!(
             "Send GetData {}-{} to {}:{}",
             block_hashes[0].be_hex_string(),
             block_hashes[block_hashes.len() - 1].be_hex_string(),
-            self.config.peer_host,
-            self.config.peer_port
+            &endpoint.host,
+            endpoint.peer_port
         );
         self.send_message(getdata)
     }
@@ -300,6 +300,11 @@ impl BurnchainSigner {
 
     pub fn from_bitcoin_input(inp: &BitcoinTxInput) -> BurnchainSigner {
         match inp.in_type {
+            BitcoinInputType::SegwitNative | BitcoinInputType::Taproot => BurnchainSigner {
+                hash_mode: AddressHashMode::SerializeP2PKH,
+                num_sigs: inp.num_required,
+                public_keys: inp.keys.clone(),
+            },
             BitcoinInputType::Standard => {
                 if inp.num_required == 1 && inp.keys.len() == 1 {
                     BurnchainSigner {
@@ -346,6 +351,10 @@ impl BurnchainSigner {
     }
 
     pub fn to_address_bits(&self) -> Vec<u8> {
+        if self.public_keys.is_empty() {
+            // a taproot key-path spend reveals no public key to hash
+            return vec![0u8; 20];
+        }
         let h = public_keys_to_address_hash(&self.hash_mode, self.num_sigs, &self.public_keys);
         h.as_bytes().to_vec()
     }
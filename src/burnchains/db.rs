@@ -471,7 +471,7 @@ mod tests {
 
         for (ix, tx_fixture) in fixtures.iter().enumerate() {
             let tx = make_tx(&tx_fixture.txstr);
-            let burnchain_tx = parser.parse_tx(&tx, ix + 1).unwrap();
+            let burnchain_tx = parser.parse_tx(&tx, ix + 1, noncanon_block_height).unwrap();
             if let Some(res) = &tx_fixture.result {
                 let mut res = res.clone();
                 res.vtxindex = (ix + 1).try_into().unwrap();
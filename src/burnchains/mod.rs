@@ -43,6 +43,7 @@ use crate::types::chainstate::PoxId;
 use crate::types::chainstate::StacksAddress;
 use crate::types::proof::TrieHash;
 
+use self::bitcoin::address::BitcoinAddressType;
 use self::bitcoin::indexer::{
     BITCOIN_MAINNET as BITCOIN_NETWORK_ID_MAINNET, BITCOIN_MAINNET_NAME,
     BITCOIN_REGTEST as BITCOIN_NETWORK_ID_REGTEST, BITCOIN_REGTEST_NAME,
@@ -260,6 +261,36 @@ impl BurnchainTransaction {
         }
     }
 
+    /// Does the recipient at `index` in `get_recipients()` pay to a witness program?
+    pub fn is_witness_recipient(&self, index: usize) -> bool {
+        match *self {
+            BurnchainTransaction::Bitcoin(ref btc) => btc
+                .outputs
+                .get(index)
+                .map(|o| match o.address.addrtype {
+                    BitcoinAddressType::WitnessPublicKeyHash
+                    | BitcoinAddressType::WitnessTaproot(_) => true,
+                    _ => false,
+                })
+                .unwrap_or(false),
+        }
+    }
+
+    /// Does the recipient at `index` in `get_recipients()` pay to a taproot output key?  Its
+    /// address is only the hash of the key, so nothing can be paid back to it.
+    pub fn is_taproot_recipient(&self, index: usize) -> bool {
+        match *self {
+            BurnchainTransaction::Bitcoin(ref btc) => btc
+                .outputs
+                .get(index)
+                .map(|o| match o.address.addrtype {
+                    BitcoinAddressType::WitnessTaproot(_) => true,
+                    _ => false,
+                })
+                .unwrap_or(false),
+        }
+    }
+
     pub fn get_burn_amount(&self) -> u64 {
         match *self {
             BurnchainTransaction::Bitcoin(ref btc) => btc.data_amt,
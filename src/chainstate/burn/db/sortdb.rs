@@ -2443,7 +2443,7 @@ impl SortitionDB {
         match epoch {
             StacksEpochId::Epoch10 => false,
             StacksEpochId::Epoch20 => (version == "1" || version == "2"),
-            StacksEpochId::Epoch2_05 => version == "2",
+            StacksEpochId::Epoch2_05 | StacksEpochId::Epoch21 => version == "2",
         }
     }
 
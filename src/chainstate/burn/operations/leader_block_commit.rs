@@ -298,7 +298,7 @@ impl LeaderBlockCommitOp {
             >= burnchain.pox_constants.sunset_end
         {
             // should be only one burn output
-            if !outputs[0].address.is_burn() {
+            if !outputs[0].address.is_burn() || tx.is_witness_recipient(0) {
                 return Err(op_error::BlockCommitBadOutputs);
             }
             let BurnchainRecipient { address, amount } = outputs.remove(0);
@@ -306,7 +306,7 @@ impl LeaderBlockCommitOp {
         // check if we're in a prepare phase
         } else if burnchain.is_in_prepare_phase(block_height) {
             // should be only one burn output
-            if !outputs[0].address.is_burn() {
+            if !outputs[0].address.is_burn() || tx.is_witness_recipient(0) {
                 return Err(op_error::BlockCommitBadOutputs);
             }
             let BurnchainRecipient { address, amount } = outputs.remove(0);
@@ -322,6 +322,11 @@ impl LeaderBlockCommitOp {
                 if ix >= OUTPUTS_PER_COMMIT {
                     break;
                 }
+                // PoX reward addresses are p2pkh or p2sh, so a witness output can't pay one
+                if tx.is_witness_recipient(ix) {
+                    warn!("Invalid commit tx: PoX output {} is a witness output", ix);
+                    return Err(op_error::BlockCommitBadOutputs);
+                }
                 // all pox outputs must have the same fee
                 if let Some(pox_fee) = pox_fee {
                     if output.amount != pox_fee {
@@ -832,7 +837,7 @@ impl LeaderBlockCommitOp {
                     );
                 }
             }
-            StacksEpochId::Epoch2_05 => {
+            StacksEpochId::Epoch2_05 | StacksEpochId::Epoch21 => {
                 if self.memo.len() < 1 {
                     debug!(
                         "Invalid block commit";
@@ -1468,8 +1473,11 @@ mod tests {
                     timestamp: get_epoch_time_secs(),
                 },
             };
-            let burnchain_tx =
-                BurnchainTransaction::Bitcoin(parser.parse_tx(&tx, vtxindex as usize).unwrap());
+            let burnchain_tx = BurnchainTransaction::Bitcoin(
+                parser
+                    .parse_tx(&tx, vtxindex as usize, header.block_height)
+                    .unwrap(),
+            );
 
             let mut burnchain = Burnchain::regtest("nope");
             burnchain.pox_constants.sunset_start = block_height;
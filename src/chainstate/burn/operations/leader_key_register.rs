@@ -384,8 +384,11 @@ pub mod tests {
                     timestamp: get_epoch_time_secs(),
                 },
             };
-            let burnchain_tx =
-                BurnchainTransaction::Bitcoin(parser.parse_tx(&tx, vtxindex as usize).unwrap());
+            let burnchain_tx = BurnchainTransaction::Bitcoin(
+                parser
+                    .parse_tx(&tx, vtxindex as usize, header.block_height)
+                    .unwrap(),
+            );
             let op = LeaderKeyRegisterOp::from_tx(&header, &burnchain_tx);
 
             match (op, tx_fixture.result) {
@@ -252,6 +252,12 @@ impl StackStxOp {
             return Err(op_error::InvalidInput);
         };
 
+        // PoX pays reward addresses as p2pkh or p2sh, so a taproot output can't be one
+        if tx.is_taproot_recipient(0) {
+            warn!("Invalid tx: reward address is a taproot output");
+            return Err(op_error::InvalidInput);
+        }
+
         let data = StackStxOp::parse_data(&tx.data()).ok_or_else(|| {
             warn!("Invalid tx data");
             op_error::ParseError
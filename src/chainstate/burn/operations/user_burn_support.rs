@@ -379,7 +379,7 @@ mod tests {
         for tx_fixture in tx_fixtures {
             let tx = make_tx(&tx_fixture.txstr).unwrap();
             let burnchain_tx =
-                BurnchainTransaction::Bitcoin(parser.parse_tx(&tx, vtxindex as usize).unwrap());
+                BurnchainTransaction::Bitcoin(parser.parse_tx(&tx, vtxindex as usize, 0).unwrap());
 
             let header = match tx_fixture.result {
                 Some(ref op) => BurnchainBlockHeader {
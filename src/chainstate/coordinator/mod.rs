@@ -392,6 +392,12 @@ impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T,
             atlas_config: AtlasConfig::default(false),
         }
     }
+
+    /// The coordinator's own chainstate handle, which processes blocks
+    #[cfg(test)]
+    pub fn chainstate_mut(&mut self) -> &mut StacksChainState {
+        &mut self.chain_state_db
+    }
 }
 
 pub fn get_next_recipients<U: RewardSetProvider>(
@@ -224,6 +224,12 @@ impl StacksAddress {
                 BitcoinAddress::to_p2pkh_tx_out(&self.bytes, value)
             }
             BitcoinAddressType::ScriptHash => BitcoinAddress::to_p2sh_tx_out(&self.bytes, value),
+            BitcoinAddressType::WitnessPublicKeyHash => {
+                BitcoinAddress::to_p2wpkh_tx_out(&self.bytes, value)
+            }
+            BitcoinAddressType::WitnessTaproot(_) => {
+                unreachable!("BUG: a Stacks address only holds the hash of a taproot output key")
+            }
         }
     }
 }
@@ -0,0 +1,330 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers for taking part in cost-function governance through the `cost-voting` boot contract:
+//! building the contract calls that propose, vote on, veto and confirm a cost function, and
+//! reading proposals and confirmed cost functions back out of the chainstate.
+
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::stacks::db::StacksChainState;
+use chainstate::stacks::Error;
+use chainstate::stacks::TransactionPayload;
+use vm::types::{PrincipalData, TupleData, Value};
+
+use crate::types::chainstate::StacksBlockId;
+use crate::util::boot::boot_code_addr;
+
+pub const COST_VOTING_NAME: &'static str = "cost-voting";
+
+/// A call to one of the public functions of the `cost-voting` contract
+#[derive(Debug, Clone, PartialEq)]
+pub enum CostVotingCall {
+    /// Propose that `function_name` in `function_contract` be costed by `cost_function_name` in
+    /// `cost_function_contract`
+    SubmitProposal {
+        function_contract: PrincipalData,
+        function_name: String,
+        cost_function_contract: PrincipalData,
+        cost_function_name: String,
+    },
+    /// Lock `amount` uSTX in favor of a proposal
+    Vote { proposal_id: u128, amount: u128 },
+    /// Unlock `amount` previously voted uSTX
+    WithdrawVotes { proposal_id: u128, amount: u128 },
+    /// Veto a vote-confirmed proposal (only the miner of the previous block may)
+    Veto { proposal_id: u128 },
+    /// Confirm that a proposal has received enough votes
+    ConfirmVotes { proposal_id: u128 },
+    /// Confirm that a vote-confirmed proposal outlasted its veto period
+    ConfirmMiners { proposal_id: u128 },
+}
+
+impl CostVotingCall {
+    pub fn function_name(&self) -> &'static str {
+        match self {
+            CostVotingCall::SubmitProposal { .. } => "submit-proposal",
+            CostVotingCall::Vote { .. } => "vote-proposal",
+            CostVotingCall::WithdrawVotes { .. } => "withdraw-votes",
+            CostVotingCall::Veto { .. } => "veto",
+            CostVotingCall::ConfirmVotes { .. } => "confirm-votes",
+            CostVotingCall::ConfirmMiners { .. } => "confirm-miners",
+        }
+    }
+
+    /// The arguments to pass to the contract function.  Fails if a function name does not fit in
+    /// a `(string-ascii 128)`.
+    pub fn args(&self) -> Result<Vec<Value>, Error> {
+        let ascii_128 = |name: &str| {
+            if name.len() > 128 {
+                return Err(Error::InvalidStacksTransaction(
+                    format!("Function name {} is longer than 128 characters", name),
+                    false,
+                ));
+            }
+            Value::string_ascii_from_bytes(name.as_bytes().to_vec()).map_err(|_| {
+                Error::InvalidStacksTransaction(
+                    format!("Function name {} is not an ASCII string", name),
+                    false,
+                )
+            })
+        };
+        let args = match self {
+            CostVotingCall::SubmitProposal {
+                function_contract,
+                function_name,
+                cost_function_contract,
+                cost_function_name,
+            } => vec![
+                Value::Principal(function_contract.clone()),
+                ascii_128(function_name)?,
+                Value::Principal(cost_function_contract.clone()),
+                ascii_128(cost_function_name)?,
+            ],
+            CostVotingCall::Vote {
+                proposal_id,
+                amount,
+            }
+            | CostVotingCall::WithdrawVotes {
+                proposal_id,
+                amount,
+            } => vec![Value::UInt(*proposal_id), Value::UInt(*amount)],
+            CostVotingCall::Veto { proposal_id }
+            | CostVotingCall::ConfirmVotes { proposal_id }
+            | CostVotingCall::ConfirmMiners { proposal_id } => vec![Value::UInt(*proposal_id)],
+        };
+        Ok(args)
+    }
+
+    /// The contract-call payload making this call to the `cost-voting` contract of mainnet or
+    /// testnet.
+    pub fn to_payload(&self, mainnet: bool) -> Result<TransactionPayload, Error> {
+        Ok(TransactionPayload::new_contract_call(
+            boot_code_addr(mainnet),
+            COST_VOTING_NAME,
+            self.function_name(),
+            self.args()?,
+        )
+        .expect("BUG: cost-voting contract and function names are valid Clarity names"))
+    }
+}
+
+/// A cost-function proposal in the `cost-voting` contract, and how far it has progressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostVotingProposal {
+    pub proposal_id: u128,
+    pub function_contract: PrincipalData,
+    pub function_name: String,
+    pub cost_function_contract: PrincipalData,
+    pub cost_function_name: String,
+    /// Votes must be cast and confirmed before this block height
+    pub expiration_block_height: u128,
+    pub votes: u128,
+    pub vetos: u128,
+    /// If the votes were confirmed, the block height the veto period ends at
+    pub veto_expiration_block_height: Option<u128>,
+    /// If the miners confirmed the proposal, its index among the confirmed proposals
+    pub confirmed_id: Option<u128>,
+}
+
+impl CostVotingProposal {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "proposal_id": self.proposal_id.to_string(),
+            "function_contract": self.function_contract.to_string(),
+            "function_name": self.function_name,
+            "cost_function_contract": self.cost_function_contract.to_string(),
+            "cost_function_name": self.cost_function_name,
+            "expiration_block_height": self.expiration_block_height.to_string(),
+            "votes": self.votes.to_string(),
+            "vetos": self.vetos.to_string(),
+            "veto_expiration_block_height": self.veto_expiration_block_height.map(|h| h.to_string()),
+            "confirmed_id": self.confirmed_id.map(|id| id.to_string()),
+        })
+    }
+}
+
+/// A cost function the miners confirmed, and which replaces the default cost of a function
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmedCostFunction {
+    pub confirmed_id: u128,
+    pub function_contract: PrincipalData,
+    pub function_name: String,
+    pub cost_function_contract: PrincipalData,
+    pub cost_function_name: String,
+    pub confirmed_height: u128,
+}
+
+impl ConfirmedCostFunction {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "confirmed_id": self.confirmed_id.to_string(),
+            "function_contract": self.function_contract.to_string(),
+            "function_name": self.function_name,
+            "cost_function_contract": self.cost_function_contract.to_string(),
+            "cost_function_name": self.cost_function_name,
+            "confirmed_height": self.confirmed_height.to_string(),
+        })
+    }
+}
+
+fn expect_field(tuple: &TupleData, name: &str) -> Value {
+    tuple
+        .get(name)
+        .expect(&format!("FATAL: no '{}' in cost-voting map entry", name))
+        .to_owned()
+}
+
+impl StacksChainState {
+    fn eval_cost_voting_read_only(
+        &mut self,
+        sortdb: &SortitionDB,
+        stacks_block_id: &StacksBlockId,
+        code: &str,
+    ) -> Result<Value, Error> {
+        self.eval_boot_code_read_only(sortdb, stacks_block_id, COST_VOTING_NAME, code)
+    }
+
+    /// Number of cost-function proposals ever submitted as of `stacks_block_id`
+    pub fn get_cost_voting_proposal_count(
+        &mut self,
+        sortdb: &SortitionDB,
+        stacks_block_id: &StacksBlockId,
+    ) -> Result<u128, Error> {
+        self.eval_cost_voting_read_only(sortdb, stacks_block_id, "(var-get proposal-count)")
+            .map(|value| value.expect_u128())
+    }
+
+    /// Look up a cost-function proposal, and its votes, vetos and confirmations, as of
+    /// `stacks_block_id`.  Returns None if no such proposal was submitted.
+    pub fn get_cost_voting_proposal(
+        &mut self,
+        sortdb: &SortitionDB,
+        stacks_block_id: &StacksBlockId,
+        proposal_id: u128,
+    ) -> Result<Option<CostVotingProposal>, Error> {
+        let proposal = match self
+            .eval_cost_voting_read_only(
+                sortdb,
+                stacks_block_id,
+                &format!("(get-proposal u{})", proposal_id),
+            )?
+            .expect_optional()
+        {
+            Some(proposal) => proposal.expect_tuple(),
+            None => return Ok(None),
+        };
+
+        let votes = self
+            .eval_cost_voting_read_only(
+                sortdb,
+                stacks_block_id,
+                &format!("(get-proposal-votes u{})", proposal_id),
+            )?
+            .expect_optional()
+            .map(|votes| votes.expect_u128())
+            .unwrap_or(0);
+        let vetos = self
+            .eval_cost_voting_read_only(
+                sortdb,
+                stacks_block_id,
+                &format!("(get-proposal-vetos u{})", proposal_id),
+            )?
+            .expect_optional()
+            .map(|vetos| vetos.expect_u128())
+            .unwrap_or(0);
+        let veto_expiration_block_height = self
+            .eval_cost_voting_read_only(
+                sortdb,
+                stacks_block_id,
+                &format!(
+                    "(get expiration-block-height (map-get? vote-confirmed-proposals {{ proposal-id: u{} }}))",
+                    proposal_id
+                ),
+            )?
+            .expect_optional()
+            .map(|height| height.expect_u128());
+        let confirmed_id = self
+            .eval_cost_voting_read_only(
+                sortdb,
+                stacks_block_id,
+                &format!(
+                    "(get confirmed-id (map-get? proposal-confirmed-id {{ proposal-id: u{} }}))",
+                    proposal_id
+                ),
+            )?
+            .expect_optional()
+            .map(|id| id.expect_u128());
+
+        Ok(Some(CostVotingProposal {
+            proposal_id,
+            function_contract: expect_field(&proposal, "function-contract").expect_principal(),
+            function_name: expect_field(&proposal, "function-name").expect_ascii(),
+            cost_function_contract: expect_field(&proposal, "cost-function-contract")
+                .expect_principal(),
+            cost_function_name: expect_field(&proposal, "cost-function-name").expect_ascii(),
+            expiration_block_height: expect_field(&proposal, "expiration-block-height")
+                .expect_u128(),
+            votes,
+            vetos,
+            veto_expiration_block_height,
+            confirmed_id,
+        }))
+    }
+
+    /// The cost functions the miners confirmed as of `stacks_block_id`, in the order they were
+    /// confirmed.
+    pub fn get_confirmed_cost_functions(
+        &mut self,
+        sortdb: &SortitionDB,
+        stacks_block_id: &StacksBlockId,
+    ) -> Result<Vec<ConfirmedCostFunction>, Error> {
+        let confirmed_count = self
+            .eval_cost_voting_read_only(
+                sortdb,
+                stacks_block_id,
+                "(var-get confirmed-proposal-count)",
+            )?
+            .expect_u128();
+
+        let mut confirmed = vec![];
+        for confirmed_id in 0..confirmed_count {
+            let entry = self
+                .eval_cost_voting_read_only(
+                    sortdb,
+                    stacks_block_id,
+                    &format!("(get-confirmed-proposal u{})", confirmed_id),
+                )?
+                .expect_optional()
+                .expect(&format!(
+                    "FATAL: missing confirmed proposal {} of {}",
+                    confirmed_id, confirmed_count
+                ))
+                .expect_tuple();
+
+            confirmed.push(ConfirmedCostFunction {
+                confirmed_id,
+                function_contract: expect_field(&entry, "function-contract").expect_principal(),
+                function_name: expect_field(&entry, "function-name").expect_ascii(),
+                cost_function_contract: expect_field(&entry, "cost-function-contract")
+                    .expect_principal(),
+                cost_function_name: expect_field(&entry, "cost-function-name").expect_ascii(),
+                confirmed_height: expect_field(&entry, "confirmed-height").expect_u128(),
+            });
+        }
+
+        Ok(confirmed)
+    }
+}
@@ -29,7 +29,7 @@ use chainstate::stacks::index::marf::MarfConnection;
 use chainstate::stacks::Error;
 use clarity_vm::clarity::ClarityConnection;
 use core::{POX_MAXIMAL_SCALING, POX_THRESHOLD_STEPS_USTX};
-use util::hash::Hash160;
+use util::hash::{to_hex, Hash160};
 use vm::contexts::ContractContext;
 use vm::costs::{
     cost_functions::ClarityCostFunction, ClarityCostFunctionReference, CostStateSummary,
@@ -57,11 +57,13 @@ const BOOT_CODE_LOCKUP: &'static str = std::include_str!("lockup.clar");
 pub const BOOT_CODE_COSTS: &'static str = std::include_str!("costs.clar");
 pub const BOOT_CODE_COSTS_2: &'static str = std::include_str!("costs-2.clar");
 pub const BOOT_CODE_COSTS_2_TESTNET: &'static str = std::include_str!("costs-2-testnet.clar");
+const BOOT_CODE_COSTS_3_ADDITIONS: &'static str = std::include_str!("costs-3.clar");
 const BOOT_CODE_COST_VOTING_MAINNET: &'static str = std::include_str!("cost-voting.clar");
 const BOOT_CODE_BNS: &'static str = std::include_str!("bns.clar");
 const BOOT_CODE_GENESIS: &'static str = std::include_str!("genesis.clar");
 pub const COSTS_1_NAME: &'static str = "costs";
 pub const COSTS_2_NAME: &'static str = "costs-2";
+pub const COSTS_3_NAME: &'static str = "costs-3";
 
 lazy_static! {
     static ref BOOT_CODE_POX_MAINNET: String =
@@ -69,6 +71,10 @@ lazy_static! {
     pub static ref BOOT_CODE_POX_TESTNET: String =
         format!("{}\n{}", BOOT_CODE_POX_TESTNET_CONSTS, BOOT_CODE_POX_BODY);
     pub static ref BOOT_CODE_COST_VOTING_TESTNET: String = make_testnet_cost_voting();
+    pub static ref BOOT_CODE_COSTS_3: String =
+        format!("{}\n{}", BOOT_CODE_COSTS_2, BOOT_CODE_COSTS_3_ADDITIONS);
+    pub static ref BOOT_CODE_COSTS_3_TESTNET: String =
+        format!("{}\n{}", BOOT_CODE_COSTS_2_TESTNET, BOOT_CODE_COSTS_3_ADDITIONS);
     pub static ref STACKS_BOOT_CODE_MAINNET: [(&'static str, &'static str); 6] = [
         ("pox", &BOOT_CODE_POX_MAINNET),
         ("lockup", BOOT_CODE_LOCKUP),
@@ -146,6 +152,35 @@ fn tuple_to_pox_addr(tuple_data: TupleData) -> (AddressHashMode, Hash160) {
     (version, hashbytes)
 }
 
+/// A principal's stacking state, as recorded in the PoX contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackerInfo {
+    pub amount_ustx: u128,
+    pub pox_address: StacksAddress,
+    pub first_reward_cycle: u64,
+    pub lock_period: u64,
+}
+
+/// A principal's unexpired delegation, as recorded in the PoX contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelegationInfo {
+    pub amount_ustx: u128,
+    pub delegated_to: PrincipalData,
+    pub until_burn_height: Option<u64>,
+    /// the PoX address the delegate must stack to, if the delegator chose one
+    pub pox_address: Option<StacksAddress>,
+}
+
+/// A BNS name which resolves, as recorded in the BNS contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct BNSNameInfo {
+    pub owner: PrincipalData,
+    pub zonefile_hash: Vec<u8>,
+    pub lease_started_at: u64,
+    /// None if names in the namespace never expire
+    pub lease_ending_at: Option<u64>,
+}
+
 impl StacksChainState {
     fn eval_boot_code_read_only(
         &mut self,
@@ -167,6 +202,16 @@ impl StacksChainState {
             .map_err(Error::ClarityError)
     }
 
+    /// Convert a PoX address tuple into the Stacks address with the same hash mode and bytes
+    fn pox_address_from_tuple(&self, pox_addr_tuple: TupleData) -> StacksAddress {
+        let (hash_mode, hash) = tuple_to_pox_addr(pox_addr_tuple);
+        let version = match self.mainnet {
+            true => hash_mode.to_version_mainnet(),
+            false => hash_mode.to_version_testnet(),
+        };
+        StacksAddress::new(version, hash)
+    }
+
     pub fn get_liquid_ustx(&mut self, stacks_block_id: &StacksBlockId) -> u128 {
         let mut connection = self.clarity_state.read_only_connection(
             stacks_block_id,
@@ -405,7 +450,7 @@ impl StacksChainState {
                 .to_owned()
                 .expect_tuple();
 
-            let (hash_mode, hash) = tuple_to_pox_addr(pox_addr_tuple);
+            let pox_address = self.pox_address_from_tuple(pox_addr_tuple);
 
             let total_ustx = tuple_data
                 .get("total-ustx")
@@ -413,23 +458,215 @@ impl StacksChainState {
                 .to_owned()
                 .expect_u128();
 
-            let version = match self.mainnet {
-                true => hash_mode.to_version_mainnet(),
-                false => hash_mode.to_version_testnet(),
-            };
-
             test_debug!(
                 "PoX reward address (for {} ustx): {:?}",
                 total_ustx,
-                &StacksAddress::new(version, hash)
+                &pox_address
             );
-            ret.push((StacksAddress::new(version, hash), total_ustx));
+            ret.push((pox_address, total_ustx));
         }
 
         Ok(ret)
     }
+
+    /// Get a principal's stacking state as of the given block.  Returns None if the principal
+    /// has no STX stacked, or if its lock has expired.
+    pub fn get_stacker_info(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        stacker: &PrincipalData,
+    ) -> Result<Option<StackerInfo>, Error> {
+        let stacking_state = match self
+            .eval_boot_code_read_only(
+                sortdb,
+                block_id,
+                "pox",
+                &format!("(get-stacker-info '{})", stacker),
+            )?
+            .expect_optional()
+        {
+            Some(value) => value.expect_tuple(),
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let pox_addr_tuple = stacking_state
+            .get("pox-addr")
+            .expect("FATAL: no 'pox-addr' in stacking state")
+            .to_owned()
+            .expect_tuple();
+
+        Ok(Some(StackerInfo {
+            amount_ustx: stacking_state
+                .get("amount-ustx")
+                .expect("FATAL: no 'amount-ustx' in stacking state")
+                .to_owned()
+                .expect_u128(),
+            pox_address: self.pox_address_from_tuple(pox_addr_tuple),
+            first_reward_cycle: stacking_state
+                .get("first-reward-cycle")
+                .expect("FATAL: no 'first-reward-cycle' in stacking state")
+                .to_owned()
+                .expect_u128() as u64,
+            lock_period: stacking_state
+                .get("lock-period")
+                .expect("FATAL: no 'lock-period' in stacking state")
+                .to_owned()
+                .expect_u128() as u64,
+        }))
+    }
+
+    /// Get a principal's delegation as of the given block.  Returns None if the principal has
+    /// not delegated its STX, or if its delegation has expired.
+    pub fn get_delegation_info(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        stacker: &PrincipalData,
+    ) -> Result<Option<DelegationInfo>, Error> {
+        let delegation_state = match self
+            .eval_boot_code_read_only(
+                sortdb,
+                block_id,
+                "pox",
+                &format!("(get-check-delegation '{})", stacker),
+            )?
+            .expect_optional()
+        {
+            Some(value) => value.expect_tuple(),
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let pox_address = delegation_state
+            .get("pox-addr")
+            .expect("FATAL: no 'pox-addr' in delegation state")
+            .to_owned()
+            .expect_optional()
+            .map(|pox_addr| self.pox_address_from_tuple(pox_addr.expect_tuple()));
+
+        Ok(Some(DelegationInfo {
+            amount_ustx: delegation_state
+                .get("amount-ustx")
+                .expect("FATAL: no 'amount-ustx' in delegation state")
+                .to_owned()
+                .expect_u128(),
+            delegated_to: delegation_state
+                .get("delegated-to")
+                .expect("FATAL: no 'delegated-to' in delegation state")
+                .to_owned()
+                .expect_principal(),
+            until_burn_height: delegation_state
+                .get("until-burn-ht")
+                .expect("FATAL: no 'until-burn-ht' in delegation state")
+                .to_owned()
+                .expect_optional()
+                .map(|height| height.expect_u128() as u64),
+            pox_address,
+        }))
+    }
+
+    /// Resolve a BNS name as of the given block.  Returns None if the name does not exist, or if
+    /// it does not resolve because it has been revoked, or its lease has expired.
+    pub fn get_bns_name_info(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<BNSNameInfo>, Error> {
+        let resolved = match self
+            .eval_boot_code_read_only(
+                sortdb,
+                block_id,
+                "bns",
+                &format!(
+                    "(name-resolve 0x{} 0x{})",
+                    to_hex(namespace.as_bytes()),
+                    to_hex(name.as_bytes())
+                ),
+            )?
+            .expect_result()
+        {
+            Ok(value) => value.expect_tuple(),
+            Err(_) => {
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(BNSNameInfo {
+            owner: resolved
+                .get("owner")
+                .expect("FATAL: no 'owner' in resolved name")
+                .to_owned()
+                .expect_principal(),
+            zonefile_hash: resolved
+                .get("zonefile-hash")
+                .expect("FATAL: no 'zonefile-hash' in resolved name")
+                .to_owned()
+                .expect_buff(20),
+            lease_started_at: resolved
+                .get("lease-started-at")
+                .expect("FATAL: no 'lease-started-at' in resolved name")
+                .to_owned()
+                .expect_u128() as u64,
+            lease_ending_at: resolved
+                .get("lease-ending-at")
+                .expect("FATAL: no 'lease-ending-at' in resolved name")
+                .to_owned()
+                .expect_optional()
+                .map(|height| height.expect_u128() as u64),
+        }))
+    }
+
+    /// Get the BNS name owned by a principal as of the given block, as (namespace, name).
+    /// Returns None if the principal does not own a name which resolves.
+    pub fn get_bns_principal_name(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        owner: &PrincipalData,
+    ) -> Result<Option<(String, String)>, Error> {
+        let fqn = match self
+            .eval_boot_code_read_only(
+                sortdb,
+                block_id,
+                "bns",
+                &format!("(resolve-principal '{})", owner),
+            )?
+            .expect_result()
+        {
+            Ok(value) => value.expect_tuple(),
+            Err(_) => {
+                return Ok(None);
+            }
+        };
+
+        let namespace = fqn
+            .get("namespace")
+            .expect("FATAL: no 'namespace' in resolved principal")
+            .to_owned()
+            .expect_buff(20);
+        let name = fqn
+            .get("name")
+            .expect("FATAL: no 'name' in resolved principal")
+            .to_owned()
+            .expect_buff(48);
+
+        // the BNS contract only admits names and namespaces made of ASCII characters
+        Ok(Some((
+            String::from_utf8_lossy(&namespace).to_string(),
+            String::from_utf8_lossy(&name).to_string(),
+        )))
+    }
 }
 
+pub mod cost_voting;
+pub mod overrides;
+
 #[cfg(test)]
 mod contract_tests;
 
@@ -461,6 +698,7 @@ pub mod test {
     use crate::util::boot::{boot_code_id, boot_code_test_addr};
     use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
 
+    use super::cost_voting::CostVotingCall;
     use super::*;
 
     pub const TESTNET_STACKING_THRESHOLD_25: u128 = 8000;
@@ -1158,6 +1396,122 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_cost_voting_proposal() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash::zero());
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+        burnchain.pox_constants.anchor_threshold = 1;
+
+        let (mut peer, keys) = instantiate_pox_peer(&burnchain, "test-cost-voting-proposal", 6010);
+
+        let submit_proposal = CostVotingCall::SubmitProposal {
+            function_contract: boot_code_id("pox", false).into(),
+            function_name: "get-pox-info".into(),
+            cost_function_contract: boot_code_id("costs", false).into(),
+            cost_function_name: "cost_stx_balance".into(),
+        };
+        let vote = CostVotingCall::Vote {
+            proposal_id: 0,
+            amount: 1000,
+        };
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let block_txs = vec![
+                    make_coinbase(miner, 0),
+                    make_tx(&keys[0], 0, 0, submit_proposal.to_payload(false).unwrap()),
+                    make_tx(&keys[0], 1, 0, vote.to_payload(false).unwrap()),
+                ];
+
+                let block_builder = StacksBlockBuilder::make_regtest_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        block_txs,
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let block_height = StacksChainState::get_anchored_block_header_info(
+                chainstate.db(),
+                &consensus_hash,
+                &block_bhh,
+            )
+            .unwrap()
+            .unwrap()
+            .block_height;
+
+            assert_eq!(
+                chainstate
+                    .get_cost_voting_proposal_count(sortdb, &stacks_block_id)
+                    .unwrap(),
+                1
+            );
+            let proposal = chainstate
+                .get_cost_voting_proposal(sortdb, &stacks_block_id, 0)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                proposal.function_contract,
+                boot_code_id("pox", false).into()
+            );
+            assert_eq!(proposal.function_name, "get-pox-info");
+            assert_eq!(
+                proposal.cost_function_contract,
+                boot_code_id("costs", false).into()
+            );
+            assert_eq!(proposal.cost_function_name, "cost_stx_balance");
+            assert_eq!(
+                proposal.expiration_block_height,
+                block_height as u128 + 2016
+            );
+            assert_eq!(proposal.votes, 1000);
+            assert_eq!(proposal.vetos, 0);
+            assert_eq!(proposal.veto_expiration_block_height, None);
+            assert_eq!(proposal.confirmed_id, None);
+
+            assert!(chainstate
+                .get_cost_voting_proposal(sortdb, &stacks_block_id, 1)
+                .unwrap()
+                .is_none());
+            assert!(chainstate
+                .get_confirmed_cost_functions(sortdb, &stacks_block_id)
+                .unwrap()
+                .is_empty());
+        });
+    }
+
     #[test]
     fn test_lockups() {
         let mut peer_config = TestPeerConfig::new("test_lockups", 2000, 2001);
@@ -1622,6 +1976,38 @@ pub mod test {
                 let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
                 assert_eq!(alice_balance, 0);
 
+                // ...and the chainstate reports her stacking state as the PoX contract has it
+                let alice_stacker_info =
+                    with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                        chainstate.get_stacker_info(
+                            sortdb,
+                            &tip_index_block,
+                            &key_to_stacks_addr(&alice).into(),
+                        )
+                    })
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    alice_stacker_info.amount_ustx,
+                    1024 * POX_THRESHOLD_STEPS_USTX
+                );
+                assert_eq!(alice_stacker_info.pox_address, key_to_stacks_addr(&alice));
+                assert_eq!(alice_stacker_info.lock_period, 12);
+                assert_eq!(
+                    alice_stacker_info.first_reward_cycle as u128,
+                    alice_reward_cycle
+                );
+                let alice_delegation_info =
+                    with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                        chainstate.get_delegation_info(
+                            sortdb,
+                            &tip_index_block,
+                            &key_to_stacks_addr(&alice).into(),
+                        )
+                    })
+                    .unwrap();
+                assert_eq!(alice_delegation_info, None);
+
                 let min_ustx = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
                     chainstate.get_stacking_minimum(sortdb, &tip_index_block)
                 })
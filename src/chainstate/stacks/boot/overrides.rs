@@ -0,0 +1,292 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Boot contract overrides for private networks.  An override whose name matches a stock boot
+//! contract (e.g. `pox`, `costs` or `bns`) replaces it at genesis; any other override is
+//! installed after the stock boot contracts.  Since the node calls into the stock contracts
+//! directly, a replacement must keep their interface: every public and read-only function, data
+//! map, data var and token of the stock contract must be declared with the same type.
+
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+use clarity_vm::database::MemoryBackingStore;
+use core::StacksEpochId;
+use util::strings::StacksString;
+use vm::analysis::{run_analysis, ContractAnalysis};
+use vm::ast::parse;
+use vm::costs::LimitedCostTracker;
+use vm::representations::{ClarityName, ContractName};
+
+use super::{STACKS_BOOT_CODE_MAINNET, STACKS_BOOT_CODE_TESTNET};
+use crate::util::boot::boot_code_id;
+
+/// The boot contracts to install at genesis: the stock boot contracts, with any overrides
+/// substituted in place, followed by the additional contracts among the overrides.
+pub fn boot_contracts_with_overrides(
+    overrides: &[(String, String)],
+    mainnet: bool,
+) -> Vec<(String, String)> {
+    let stock = if mainnet {
+        STACKS_BOOT_CODE_MAINNET.iter()
+    } else {
+        STACKS_BOOT_CODE_TESTNET.iter()
+    };
+    let mut contracts: Vec<(String, String)> = stock
+        .map(|(name, code)| (name.to_string(), code.to_string()))
+        .collect();
+    for (name, code) in overrides.iter() {
+        match contracts
+            .iter_mut()
+            .find(|(stock_name, _)| stock_name == name)
+        {
+            Some(contract) => contract.1 = code.clone(),
+            None => contracts.push((name.clone(), code.clone())),
+        }
+    }
+    contracts
+}
+
+/// Check that a set of boot contract overrides can be installed at genesis.  Overrides are only
+/// permitted off mainnet, must have distinct valid names, must type-check against the boot
+/// contracts installed before them, and must keep the interface of any stock contract they
+/// replace.
+pub fn validate_boot_contract_overrides(
+    overrides: &[(String, String)],
+    mainnet: bool,
+) -> Result<(), String> {
+    if overrides.is_empty() {
+        return Ok(());
+    }
+    if mainnet {
+        return Err("boot contract overrides are not permitted on mainnet".into());
+    }
+
+    let mut names = HashSet::new();
+    for (name, code) in overrides.iter() {
+        ContractName::try_from(name.clone())
+            .map_err(|_| format!("invalid boot contract name `{}`", name))?;
+        if StacksString::from_str(code).is_none() {
+            return Err(format!(
+                "boot contract `{}` contains characters not permitted in contract code",
+                name
+            ));
+        }
+        if !names.insert(name.as_str()) {
+            return Err(format!(
+                "boot contract `{}` is overridden more than once",
+                name
+            ));
+        }
+    }
+
+    let stock = analyze_boot_contracts(&boot_contracts_with_overrides(&[], mainnet), mainnet)?;
+    let installed =
+        analyze_boot_contracts(&boot_contracts_with_overrides(overrides, mainnet), mainnet)?;
+
+    for (name, stock_analysis) in stock.iter() {
+        if !names.contains(name.as_str()) {
+            continue;
+        }
+        let analysis = installed
+            .iter()
+            .find(|(installed_name, _)| installed_name == name)
+            .map(|(_, analysis)| analysis)
+            .expect("BUG: replaced boot contract was not analyzed");
+        check_interface(name, stock_analysis, analysis)?;
+    }
+    Ok(())
+}
+
+/// Type-check boot contracts in installation order, so each can refer to those before it.
+fn analyze_boot_contracts(
+    contracts: &[(String, String)],
+    mainnet: bool,
+) -> Result<Vec<(String, ContractAnalysis)>, String> {
+    let mut store = MemoryBackingStore::new();
+    let mut analyses = vec![];
+    for (name, code) in contracts.iter() {
+        let contract_id = boot_code_id(name, mainnet);
+        let mut expressions = parse(&contract_id, code)
+            .map_err(|e| format!("boot contract `{}` does not parse: {}", name, e))?;
+
+        // the analysis of a contract can only be stored once the contract exists
+        let mut clarity_db = store.as_clarity_db();
+        clarity_db.begin();
+        clarity_db
+            .insert_contract_hash(&contract_id, code)
+            .map_err(|e| format!("failed to store boot contract `{}`: {}", name, e))?;
+        clarity_db.commit();
+
+        let analysis = run_analysis(
+            &contract_id,
+            &mut expressions,
+            &mut store.as_analysis_db(),
+            true,
+            LimitedCostTracker::new_free(),
+            StacksEpochId::Epoch20,
+        )
+        .map_err(|(e, _)| format!("boot contract `{}` does not type-check: {}", name, e))?;
+        analyses.push((name.clone(), analysis));
+    }
+    Ok(analyses)
+}
+
+fn check_interface(
+    name: &str,
+    stock: &ContractAnalysis,
+    replacement: &ContractAnalysis,
+) -> Result<(), String> {
+    check_declarations(
+        name,
+        "public function",
+        &stock.public_function_types,
+        &replacement.public_function_types,
+    )?;
+    check_declarations(
+        name,
+        "read-only function",
+        &stock.read_only_function_types,
+        &replacement.read_only_function_types,
+    )?;
+    check_declarations(name, "data map", &stock.map_types, &replacement.map_types)?;
+    check_declarations(
+        name,
+        "data var",
+        &stock.persisted_variable_types,
+        &replacement.persisted_variable_types,
+    )?;
+    check_declarations(
+        name,
+        "non-fungible token",
+        &stock.non_fungible_tokens,
+        &replacement.non_fungible_tokens,
+    )?;
+    for token in stock.fungible_tokens.iter() {
+        if !replacement.fungible_tokens.contains(token) {
+            return Err(format!(
+                "boot contract `{}` override does not define fungible token `{}`",
+                name, token
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_declarations<T: PartialEq + Debug>(
+    name: &str,
+    kind: &str,
+    stock: &BTreeMap<ClarityName, T>,
+    replacement: &BTreeMap<ClarityName, T>,
+) -> Result<(), String> {
+    for (decl_name, stock_type) in stock.iter() {
+        match replacement.get(decl_name) {
+            None => {
+                return Err(format!(
+                    "boot contract `{}` override does not define {} `{}`",
+                    name, kind, decl_name
+                ));
+            }
+            Some(replacement_type) if replacement_type != stock_type => {
+                return Err(format!(
+                    "boot contract `{}` override declares {} `{}` as {:?}, but the node expects {:?}",
+                    name, kind, decl_name, replacement_type, stock_type
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::boot::BOOT_CODE_POX_TESTNET;
+
+    #[test]
+    fn test_boot_contracts_with_overrides() {
+        let overrides = vec![
+            ("costs".to_string(), "(define-read-only (f) u1)".to_string()),
+            (
+                "appchain".to_string(),
+                "(define-data-var x int 1)".to_string(),
+            ),
+        ];
+        let contracts = boot_contracts_with_overrides(&overrides, false);
+        assert_eq!(contracts.len(), STACKS_BOOT_CODE_TESTNET.len() + 1);
+        assert_eq!(contracts[2], overrides[0]);
+        assert_eq!(contracts.last().unwrap(), &overrides[1]);
+    }
+
+    #[test]
+    fn test_validate_boot_contract_overrides() {
+        assert!(validate_boot_contract_overrides(&[], true).is_ok());
+
+        // a pox contract with different constants but the same interface is fine, as is an
+        // additional contract which calls into it
+        let pox = BOOT_CODE_POX_TESTNET.replacen(
+            "(define-constant MIN_POX_REWARD_CYCLES u1)",
+            "(define-constant MIN_POX_REWARD_CYCLES u2)",
+            1,
+        );
+        assert_ne!(pox, *BOOT_CODE_POX_TESTNET);
+        let extra = "(define-read-only (minimum) (contract-call? .pox get-stacking-minimum))";
+        let overrides = vec![
+            ("pox".to_string(), pox.clone()),
+            ("appchain".to_string(), extra.to_string()),
+        ];
+        validate_boot_contract_overrides(&overrides, false).unwrap();
+
+        // not on mainnet
+        assert!(validate_boot_contract_overrides(&overrides, true).is_err());
+
+        // not twice
+        let duplicated = vec![
+            ("pox".to_string(), pox.clone()),
+            ("pox".to_string(), pox.clone()),
+        ];
+        assert!(validate_boot_contract_overrides(&duplicated, false)
+            .unwrap_err()
+            .contains("more than once"));
+
+        // must type-check
+        let broken = vec![("appchain".to_string(), "(+ 1 u1)".to_string())];
+        assert!(validate_boot_contract_overrides(&broken, false)
+            .unwrap_err()
+            .contains("does not type-check"));
+
+        // must keep the stock interface
+        let missing = vec![("costs".to_string(), "(define-read-only (f) u1)".to_string())];
+        assert!(validate_boot_contract_overrides(&missing, false)
+            .unwrap_err()
+            .contains("does not define read-only function"));
+
+        let retyped = vec![(
+            "pox".to_string(),
+            pox.replacen(
+                "(define-read-only (get-reward-set-pox-address (reward-cycle uint) (index uint))\n    (map-get? reward-cycle-pox-address-list { reward-cycle: reward-cycle, index: index }))",
+                "(define-read-only (get-reward-set-pox-address (reward-cycle uint) (index int))\n    (map-get? reward-cycle-pox-address-list { reward-cycle: reward-cycle, index: (to-uint index) }))",
+                1,
+            ),
+        )];
+        assert_ne!(retyped[0].1, pox);
+        assert!(validate_boot_contract_overrides(&retyped, false)
+            .unwrap_err()
+            .contains("declares read-only function `get-reward-set-pox-address`"));
+    }
+}
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::HashMap;
+use std::io::{Read, Write};
 
 use rusqlite::types::ToSql;
 use rusqlite::Row;
@@ -31,7 +32,46 @@ use vm::database::clarity_store::*;
 use vm::database::*;
 use vm::types::*;
 
-use crate::types::chainstate::{StacksAddress, StacksBlockHeader, StacksBlockId};
+use chainstate::stacks::index::marf::MarfConnection;
+use chainstate::stacks::index::node::TriePath;
+use chainstate::stacks::index::Error as MARFError;
+use codec::{read_next, write_next, Error as codec_error, StacksMessageCodec};
+
+use crate::types::chainstate::{MARFValue, StacksAddress, StacksBlockHeader, StacksBlockId};
+use crate::types::proof::{TrieHash, TrieMerkleProof};
+
+/// Version byte that starts an encoded `AccountProof`
+pub const ACCOUNT_PROOF_VERSION: u8 = 1;
+
+/// A block whose Clarity state trie an `AccountProof` passes through
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountProofAnchor {
+    pub index_block_hash: StacksBlockId,
+    /// root hash of the block's Clarity state trie
+    pub state_index_root: TrieHash,
+    /// the block's consensus hash and header, from which both of the above can be checked.  The
+    /// boot block has no header, so this is None for it.
+    pub header: Option<(ConsensusHash, StacksBlockHeader)>,
+}
+
+/// A Clarity MARF key for an account, with its stored value and proof of inclusion if it has
+/// one.  The MARF can't prove that a key is absent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountProofEntry {
+    pub key: String,
+    pub value: Option<(String, TrieMerkleProof<StacksBlockId>)>,
+}
+
+/// Proof of an account's STX balance and nonce as of a chain tip.  A light client that trusts
+/// the tip's header can check it without any other chain state (see verify()).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountProof {
+    pub tip: StacksBlockId,
+    /// every block the proofs pass through, starting with the tip
+    pub anchors: Vec<AccountProofAnchor>,
+    /// the account's balance entry, then its nonce entry
+    pub entries: Vec<AccountProofEntry>,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MinerReward {
@@ -94,6 +134,157 @@ impl FromRow<MinerPaymentSchedule> for MinerPaymentSchedule {
     }
 }
 
+impl StacksMessageCodec for AccountProofAnchor {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.index_block_hash)?;
+        write_next(fd, &self.state_index_root)?;
+        match self.header {
+            Some((ref consensus_hash, ref header)) => {
+                write_next(fd, &1u8)?;
+                write_next(fd, consensus_hash)?;
+                write_next(fd, header)?;
+            }
+            None => {
+                write_next(fd, &0u8)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<AccountProofAnchor, codec_error> {
+        let index_block_hash: StacksBlockId = read_next(fd)?;
+        let state_index_root: TrieHash = read_next(fd)?;
+        let has_header: u8 = read_next(fd)?;
+        let header = match has_header {
+            0 => None,
+            1 => {
+                let consensus_hash: ConsensusHash = read_next(fd)?;
+                let header: StacksBlockHeader = read_next(fd)?;
+                Some((consensus_hash, header))
+            }
+            _ => {
+                return Err(codec_error::DeserializeError(format!(
+                    "Invalid account proof anchor header flag {}",
+                    has_header
+                )));
+            }
+        };
+        Ok(AccountProofAnchor {
+            index_block_hash,
+            state_index_root,
+            header,
+        })
+    }
+}
+
+impl StacksMessageCodec for AccountProofEntry {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.key.as_bytes().to_vec())?;
+        match self.value {
+            Some((ref value, ref proof)) => {
+                write_next(fd, &1u8)?;
+                write_next(fd, &value.as_bytes().to_vec())?;
+                write_next(fd, proof)?;
+            }
+            None => {
+                write_next(fd, &0u8)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<AccountProofEntry, codec_error> {
+        let read_string = |fd: &mut R| -> Result<String, codec_error> {
+            let bytes: Vec<u8> = read_next(fd)?;
+            String::from_utf8(bytes).map_err(|_| {
+                codec_error::DeserializeError("Account proof string is not UTF-8".to_string())
+            })
+        };
+        let key = read_string(fd)?;
+        let has_value: u8 = read_next(fd)?;
+        let value = match has_value {
+            0 => None,
+            1 => {
+                let value = read_string(fd)?;
+                let proof: TrieMerkleProof<StacksBlockId> = read_next(fd)?;
+                Some((value, proof))
+            }
+            _ => {
+                return Err(codec_error::DeserializeError(format!(
+                    "Invalid account proof entry value flag {}",
+                    has_value
+                )));
+            }
+        };
+        Ok(AccountProofEntry { key, value })
+    }
+}
+
+impl StacksMessageCodec for AccountProof {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &ACCOUNT_PROOF_VERSION)?;
+        write_next(fd, &self.tip)?;
+        write_next(fd, &self.anchors)?;
+        write_next(fd, &self.entries)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<AccountProof, codec_error> {
+        let version: u8 = read_next(fd)?;
+        if version != ACCOUNT_PROOF_VERSION {
+            return Err(codec_error::DeserializeError(format!(
+                "Unsupported account proof version {}",
+                version
+            )));
+        }
+        let tip: StacksBlockId = read_next(fd)?;
+        let anchors: Vec<AccountProofAnchor> = read_next(fd)?;
+        let entries: Vec<AccountProofEntry> = read_next(fd)?;
+        Ok(AccountProof {
+            tip,
+            anchors,
+            entries,
+        })
+    }
+}
+
+impl AccountProof {
+    /// Check every entry's proof against the tip's state root, using only what's in this
+    /// proof.  Each anchor with a header must match it.  The caller still has to decide whether
+    /// it trusts the tip's header (and the boot block, if it's an anchor).
+    pub fn verify(&self) -> bool {
+        let mut root_to_block = HashMap::new();
+        for anchor in self.anchors.iter() {
+            if let Some((ref consensus_hash, ref header)) = anchor.header {
+                if header.index_block_hash(consensus_hash) != anchor.index_block_hash
+                    || header.state_index_root != anchor.state_index_root
+                {
+                    return false;
+                }
+            }
+            root_to_block.insert(
+                anchor.state_index_root.clone(),
+                anchor.index_block_hash.clone(),
+            );
+        }
+
+        let tip_root = match self.anchors.first() {
+            Some(anchor) if anchor.index_block_hash == self.tip => &anchor.state_index_root,
+            _ => return false,
+        };
+
+        self.entries.iter().all(|entry| match entry.value {
+            Some((ref value, ref proof)) => proof.verify(
+                &TriePath::from_key(&entry.key),
+                &MARFValue::from_value(value),
+                tip_root,
+                &root_to_block,
+            ),
+            None => true,
+        })
+    }
+}
+
 impl MinerReward {
     pub fn total(&self) -> u128 {
         self.coinbase
@@ -153,6 +344,77 @@ impl StacksChainState {
         })
     }
 
+    /// Get a proof of an account's STX balance and nonce as of the given chain tip, along with
+    /// the block headers needed to check it.  Returns Ok(None) if the tip has no Clarity state.
+    pub fn get_account_proof(
+        &mut self,
+        tip: &StacksBlockId,
+        principal: &PrincipalData,
+    ) -> Result<Option<AccountProof>, Error> {
+        let keys = vec![
+            ClarityDatabase::make_key_for_account_balance(principal),
+            ClarityDatabase::make_key_for_account_nonce(principal),
+        ];
+
+        let proofs = self.with_clarity_marf(|marf| {
+            let tip_root = match marf.get_root_hash_at(tip) {
+                Ok(root) => root,
+                Err(MARFError::NotFoundError) => return Ok(None),
+                Err(e) => return Err(Error::from(e)),
+            };
+
+            let mut roots = vec![(tip.clone(), tip_root)];
+            let mut entries = vec![];
+            for key in keys.into_iter() {
+                let value = match marf.get_with_proof_and_blocks(tip, &key) {
+                    Ok(Some((marf_value, proof, proof_blocks))) => {
+                        let value = SqliteConnection::get(marf.sqlite_conn(), &marf_value.to_hex())
+                            .ok_or(Error::DBError(db_error::Corruption))?;
+                        for block in proof_blocks.into_iter() {
+                            if !roots.iter().any(|(root_block, _)| *root_block == block) {
+                                let root = marf.get_root_hash_at(&block)?;
+                                roots.push((block, root));
+                            }
+                        }
+                        Some((value, proof))
+                    }
+                    Ok(None) | Err(MARFError::NotFoundError) => None,
+                    Err(e) => return Err(e.into()),
+                };
+                entries.push(AccountProofEntry { key, value });
+            }
+            Ok(Some((roots, entries)))
+        })?;
+
+        let (roots, entries) = match proofs {
+            Some(proofs) => proofs,
+            None => return Ok(None),
+        };
+
+        let mut anchors = vec![];
+        for (index_block_hash, state_index_root) in roots.into_iter() {
+            // the boot block's header doesn't commit to its state
+            let header = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                self.db(),
+                &index_block_hash,
+            )?
+            .filter(|header_info| header_info.block_height > 0)
+            .map(|header_info| (header_info.consensus_hash, header_info.anchored_header));
+
+            anchors.push(AccountProofAnchor {
+                index_block_hash,
+                state_index_root,
+                header,
+            });
+        }
+
+        Ok(Some(AccountProof {
+            tip: tip.clone(),
+            anchors,
+            entries,
+        }))
+    }
+
     pub fn get_account_ft<'a>(
         clarity_tx: &mut ClarityTx<'a>,
         contract_id: &QualifiedContractIdentifier,
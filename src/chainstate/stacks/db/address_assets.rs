@@ -0,0 +1,581 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Optional index of the fungible and non-fungible tokens each principal holds, built from the
+//! token events of processed blocks, so a node can serve a principal's holdings without a
+//! separate indexer.
+//!
+//! Each token event that credits or debits a principal is stored as a row.  A principal's
+//! holdings as of a chain tip are found by replaying its rows from the blocks on the tip's fork.
+//! The index is only maintained while `StacksChainState::address_asset_index` is set, so
+//! holdings are only complete if it was set when the node processed the chain.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::{
+    FTEventType, NFTEventType, StacksTransactionEvent, StacksTransactionReceipt,
+};
+use chainstate::stacks::Error;
+use util::db::Error as db_error;
+use util::db::{query_rows, u64_to_sql, FromColumn, FromRow};
+use vm::database::ClaritySerializable;
+use vm::types::{AssetIdentifier, PrincipalData, Value};
+
+/// How a token event changed a principal's holdings
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetChange {
+    /// `amount` of the token was credited to (`received`) or debited from the principal
+    FungibleToken {
+        asset_identifier: AssetIdentifier,
+        amount: u128,
+        received: bool,
+    },
+    /// The principal came to own (`received`) or stopped owning the token `value`
+    NonFungibleToken {
+        asset_identifier: AssetIdentifier,
+        value: Value,
+        received: bool,
+    },
+}
+
+/// A stored token event for one principal
+struct AddressAssetEvent {
+    index_block_hash: StacksBlockId,
+    block_height: u64,
+    asset_identifier: String,
+    /// decimal amount, for fungible tokens
+    amount: Option<String>,
+    /// hex-encoded consensus serialization of the token, for non-fungible tokens
+    nft_value: Option<String>,
+    received: bool,
+}
+
+impl FromRow<AddressAssetEvent> for AddressAssetEvent {
+    fn from_row<'a>(row: &'a Row) -> Result<AddressAssetEvent, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let asset_identifier: String = row.get_unwrap("asset_identifier");
+        let amount: Option<String> = row.get_unwrap("amount");
+        let nft_value: Option<String> = row.get_unwrap("nft_value");
+        let received: bool = row.get_unwrap("received");
+        Ok(AddressAssetEvent {
+            index_block_hash,
+            block_height,
+            asset_identifier,
+            amount,
+            nft_value,
+            received,
+        })
+    }
+}
+
+/// A principal's fungible and non-fungible token holdings, sorted by asset identifier (and,
+/// for non-fungible tokens, by serialized value)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AddressAssetHoldings {
+    /// (asset identifier, balance) of each token with a non-zero balance
+    pub fungible_tokens: Vec<(String, u128)>,
+    /// (asset identifier, token) of each owned token
+    pub non_fungible_tokens: Vec<(String, Value)>,
+}
+
+/// The changes a token event makes to the holdings of the principals it involves.  STX events
+/// and transfers of a token to its own owner make no changes.
+pub fn event_asset_changes(event: &StacksTransactionEvent) -> Vec<(PrincipalData, AssetChange)> {
+    let ft_change = |asset_identifier: &AssetIdentifier, amount: u128, received: bool| {
+        AssetChange::FungibleToken {
+            asset_identifier: asset_identifier.clone(),
+            amount,
+            received,
+        }
+    };
+    let nft_change = |asset_identifier: &AssetIdentifier, value: &Value, received: bool| {
+        AssetChange::NonFungibleToken {
+            asset_identifier: asset_identifier.clone(),
+            value: value.clone(),
+            received,
+        }
+    };
+
+    match event {
+        StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(data)) => {
+            if data.sender == data.recipient {
+                return vec![];
+            }
+            vec![
+                (
+                    data.sender.clone(),
+                    ft_change(&data.asset_identifier, data.amount, false),
+                ),
+                (
+                    data.recipient.clone(),
+                    ft_change(&data.asset_identifier, data.amount, true),
+                ),
+            ]
+        }
+        StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(data)) => vec![(
+            data.recipient.clone(),
+            ft_change(&data.asset_identifier, data.amount, true),
+        )],
+        StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(data)) => vec![(
+            data.sender.clone(),
+            ft_change(&data.asset_identifier, data.amount, false),
+        )],
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(data)) => {
+            if data.sender == data.recipient {
+                return vec![];
+            }
+            vec![
+                (
+                    data.sender.clone(),
+                    nft_change(&data.asset_identifier, &data.value, false),
+                ),
+                (
+                    data.recipient.clone(),
+                    nft_change(&data.asset_identifier, &data.value, true),
+                ),
+            ]
+        }
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(data)) => vec![(
+            data.recipient.clone(),
+            nft_change(&data.asset_identifier, &data.value, true),
+        )],
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(data)) => vec![(
+            data.sender.clone(),
+            nft_change(&data.asset_identifier, &data.value, false),
+        )],
+        StacksTransactionEvent::STXEvent(_) | StacksTransactionEvent::SmartContractEvent(_) => {
+            vec![]
+        }
+    }
+}
+
+impl StacksChainState {
+    /// Add the token events in a processed block's receipts to the address asset index.
+    /// Receipts of transactions aborted by post-conditions are skipped, since their effects
+    /// were rolled back.
+    pub fn index_address_assets(
+        tx: &DBTx,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let sql = "INSERT OR REPLACE INTO address_asset_events
+                   (principal, index_block_hash, block_height, receipt_index, event_index,
+                    asset_identifier, amount, nft_value, received)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+        for (receipt_index, receipt) in receipts.iter().enumerate() {
+            if receipt.post_condition_aborted {
+                continue;
+            }
+            for (event_index, event) in receipt.events.iter().enumerate() {
+                for (principal, change) in event_asset_changes(event).into_iter() {
+                    let (asset_identifier, amount, nft_value, received) = match change {
+                        AssetChange::FungibleToken {
+                            asset_identifier,
+                            amount,
+                            received,
+                        } => (asset_identifier, Some(amount.to_string()), None, received),
+                        AssetChange::NonFungibleToken {
+                            asset_identifier,
+                            value,
+                            received,
+                        } => (asset_identifier, None, Some(value.serialize()), received),
+                    };
+                    let args: &[&dyn ToSql] = &[
+                        &principal.to_string(),
+                        index_block_hash,
+                        &u64_to_sql(block_height)?,
+                        &(receipt_index as i64),
+                        &(event_index as i64),
+                        &asset_identifier.to_string(),
+                        &amount,
+                        &nft_value,
+                        &received,
+                    ];
+                    tx.execute(sql, args)
+                        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the tokens `principal` holds as of `tip`, by replaying its indexed token events
+    /// from the blocks on `tip`'s fork.
+    pub fn get_address_assets(
+        &self,
+        tip: &StacksBlockId,
+        principal: &PrincipalData,
+    ) -> Result<AddressAssetHoldings, Error> {
+        let index_conn = self.index_conn()?;
+        let tip_height = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            tip,
+        )? {
+            Some(header) => header.block_height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+
+        let sql = "SELECT * FROM address_asset_events
+                   WHERE principal = ?1 AND block_height <= ?2
+                   ORDER BY block_height, receipt_index, event_index";
+        let args: &[&dyn ToSql] = &[&principal.to_string(), &u64_to_sql(tip_height)?];
+        let events: Vec<AddressAssetEvent> = query_rows(self.db(), sql, args)?;
+
+        // height => block ID of the ancestor of `tip` at that height
+        let mut canonical: HashMap<u64, Option<StacksBlockId>> = HashMap::new();
+        let mut balances: BTreeMap<String, u128> = BTreeMap::new();
+        // (asset identifier, serialized token) => owned
+        let mut owned: BTreeMap<(String, String), bool> = BTreeMap::new();
+
+        for event in events.into_iter() {
+            let ancestor = match canonical.entry(event.block_height) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    index_conn
+                        .get_ancestor_block_hash(event.block_height, tip)
+                        .map_err(Error::DBError)?,
+                ),
+            };
+            if ancestor.as_ref() != Some(&event.index_block_hash) {
+                continue;
+            }
+
+            match (event.amount, event.nft_value) {
+                (Some(amount), None) => {
+                    let amount: u128 = amount.parse().map_err(|_| db_error::ParseError)?;
+                    let balance = balances.entry(event.asset_identifier).or_insert(0);
+                    // debits of credits from before the index was enabled are dropped
+                    *balance = if event.received {
+                        balance.saturating_add(amount)
+                    } else {
+                        balance.saturating_sub(amount)
+                    };
+                }
+                (None, Some(nft_value)) => {
+                    owned.insert((event.asset_identifier, nft_value), event.received);
+                }
+                _ => return Err(Error::DBError(db_error::Corruption)),
+            }
+        }
+
+        let fungible_tokens = balances
+            .into_iter()
+            .filter(|(_, balance)| *balance > 0)
+            .collect();
+        let mut non_fungible_tokens = vec![];
+        for ((asset_identifier, nft_value), is_owned) in owned.into_iter() {
+            if !is_owned {
+                continue;
+            }
+            let value =
+                Value::try_deserialize_hex_untyped(&nft_value).map_err(|_| db_error::ParseError)?;
+            non_fungible_tokens.push((asset_identifier, value));
+        }
+
+        Ok(AddressAssetHoldings {
+            fungible_tokens,
+            non_fungible_tokens,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainstate::burn::ConsensusHash;
+    use chainstate::stacks::db::test::*;
+    use chainstate::stacks::events::{
+        FTMintEventData, FTTransferEventData, NFTMintEventData, NFTTransferEventData,
+    };
+    use chainstate::stacks::*;
+    use util::hash::{Hash160, Sha512Trunc256Sum};
+    use vm::costs::ExecutionCost;
+    use vm::types::QualifiedContractIdentifier;
+
+    use crate::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+
+    use super::*;
+
+    fn make_child_tip(
+        chainstate: &mut StacksChainState,
+        parent: &StacksHeaderInfo,
+        fork: u8,
+    ) -> StacksHeaderInfo {
+        let mut new_tip = parent.clone();
+        new_tip.anchored_header.parent_block = parent.anchored_header.block_hash();
+        new_tip.anchored_header.total_work.work = parent.anchored_header.total_work.work + 1;
+        new_tip.block_height = parent.block_height + 1;
+        new_tip.consensus_hash = ConsensusHash(
+            Hash160::from_data(
+                &Sha512Trunc256Sum::from_data(&[&parent.consensus_hash.0[..], &[fork]].concat()).0,
+            )
+            .0,
+        );
+        new_tip.burn_header_hash =
+            BurnchainHeaderHash(Sha512Trunc256Sum::from_data(&new_tip.consensus_hash.0).0);
+        new_tip.burn_header_height = parent.burn_header_height + 1;
+
+        let mut block_reward = MinerPaymentSchedule::genesis(false);
+        block_reward.parent_consensus_hash = parent.consensus_hash;
+        block_reward.parent_block_hash = parent.anchored_header.block_hash();
+        block_reward.block_hash = new_tip.anchored_header.block_hash();
+        block_reward.consensus_hash = new_tip.consensus_hash;
+
+        let mut tx = chainstate.index_tx_begin().unwrap();
+        let tip = StacksChainState::advance_tip(
+            &mut tx,
+            &parent.anchored_header,
+            &parent.consensus_hash,
+            &new_tip.anchored_header,
+            &new_tip.consensus_hash,
+            &new_tip.burn_header_hash,
+            new_tip.burn_header_height,
+            new_tip.burn_header_timestamp,
+            None,
+            &block_reward,
+            &vec![],
+            &ExecutionCost::zero(),
+            123,
+            false,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    fn asset(name: &str) -> AssetIdentifier {
+        AssetIdentifier {
+            contract_identifier: QualifiedContractIdentifier::new(
+                StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                    .unwrap()
+                    .into(),
+                "tokens".into(),
+            ),
+            asset_name: name.into(),
+        }
+    }
+
+    fn ft_mint(recipient: &PrincipalData, amount: u128) -> StacksTransactionEvent {
+        StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(FTMintEventData {
+            asset_identifier: asset("coin"),
+            recipient: recipient.clone(),
+            amount,
+        }))
+    }
+
+    fn ft_transfer(
+        sender: &PrincipalData,
+        recipient: &PrincipalData,
+        amount: u128,
+    ) -> StacksTransactionEvent {
+        StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(FTTransferEventData {
+            asset_identifier: asset("coin"),
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            amount,
+        }))
+    }
+
+    fn nft_mint(recipient: &PrincipalData, id: u128) -> StacksTransactionEvent {
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(NFTMintEventData {
+            asset_identifier: asset("item"),
+            recipient: recipient.clone(),
+            value: Value::UInt(id),
+        }))
+    }
+
+    fn nft_transfer(
+        sender: &PrincipalData,
+        recipient: &PrincipalData,
+        id: u128,
+    ) -> StacksTransactionEvent {
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(NFTTransferEventData {
+            asset_identifier: asset("item"),
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            value: Value::UInt(id),
+        }))
+    }
+
+    fn make_receipt(events: Vec<StacksTransactionEvent>) -> StacksTransactionReceipt {
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&StacksPrivateKey::new()).unwrap(),
+            TransactionPayload::new_contract_call(
+                StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap(),
+                "tokens",
+                "f",
+                vec![],
+            )
+            .unwrap(),
+        );
+        StacksTransactionReceipt::from_contract_call(
+            tx,
+            events,
+            Value::okay_true(),
+            0,
+            ExecutionCost::zero(),
+        )
+    }
+
+    fn index_block(
+        chainstate: &mut StacksChainState,
+        tip: &StacksHeaderInfo,
+        receipts: &[StacksTransactionReceipt],
+    ) {
+        let tx = chainstate.index_tx_begin().unwrap();
+        StacksChainState::index_address_assets(
+            tx.tx(),
+            &tip.index_block_hash(),
+            tip.block_height,
+            receipts,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_event_asset_changes() {
+        let alice: PrincipalData =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into();
+        let bob: PrincipalData =
+            StacksAddress::from_string("STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW")
+                .unwrap()
+                .into();
+
+        assert_eq!(
+            event_asset_changes(&ft_transfer(&alice, &bob, 5)),
+            vec![
+                (
+                    alice.clone(),
+                    AssetChange::FungibleToken {
+                        asset_identifier: asset("coin"),
+                        amount: 5,
+                        received: false,
+                    }
+                ),
+                (
+                    bob.clone(),
+                    AssetChange::FungibleToken {
+                        asset_identifier: asset("coin"),
+                        amount: 5,
+                        received: true,
+                    }
+                ),
+            ]
+        );
+        assert!(event_asset_changes(&ft_transfer(&alice, &alice, 5)).is_empty());
+        assert!(event_asset_changes(&nft_transfer(&bob, &bob, 1)).is_empty());
+    }
+
+    #[test]
+    fn test_get_address_assets() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "test_get_address_assets");
+        let alice: PrincipalData =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into();
+        let bob: PrincipalData =
+            StacksAddress::from_string("STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW")
+                .unwrap()
+                .into();
+
+        // genesis <- block_1 <- block_2, and block_1 <- fork_2
+        let genesis = StacksHeaderInfo::regtest_genesis();
+        let block_1 = make_child_tip(&mut chainstate, &genesis, 0);
+        let block_2 = make_child_tip(&mut chainstate, &block_1, 0);
+        let fork_2 = make_child_tip(&mut chainstate, &block_1, 1);
+
+        let mut aborted = make_receipt(vec![ft_mint(&alice, 1000)]);
+        aborted.post_condition_aborted = true;
+        index_block(
+            &mut chainstate,
+            &block_1,
+            &[
+                make_receipt(vec![ft_mint(&alice, 100), nft_mint(&alice, 1)]),
+                make_receipt(vec![nft_mint(&alice, 2)]),
+                aborted,
+            ],
+        );
+        index_block(
+            &mut chainstate,
+            &block_2,
+            &[make_receipt(vec![
+                ft_transfer(&alice, &bob, 30),
+                nft_transfer(&alice, &bob, 1),
+            ])],
+        );
+        index_block(
+            &mut chainstate,
+            &fork_2,
+            &[make_receipt(vec![ft_transfer(&alice, &bob, 100)])],
+        );
+
+        assert_eq!(
+            chainstate
+                .get_address_assets(&block_2.index_block_hash(), &alice)
+                .unwrap(),
+            AddressAssetHoldings {
+                fungible_tokens: vec![(asset("coin").to_string(), 70)],
+                non_fungible_tokens: vec![(asset("item").to_string(), Value::UInt(2))],
+            }
+        );
+        assert_eq!(
+            chainstate
+                .get_address_assets(&block_2.index_block_hash(), &bob)
+                .unwrap(),
+            AddressAssetHoldings {
+                fungible_tokens: vec![(asset("coin").to_string(), 30)],
+                non_fungible_tokens: vec![(asset("item").to_string(), Value::UInt(1))],
+            }
+        );
+
+        // on the other fork, alice sent bob all her coins and kept both items
+        assert_eq!(
+            chainstate
+                .get_address_assets(&fork_2.index_block_hash(), &alice)
+                .unwrap(),
+            AddressAssetHoldings {
+                fungible_tokens: vec![],
+                non_fungible_tokens: vec![
+                    (asset("item").to_string(), Value::UInt(1)),
+                    (asset("item").to_string(), Value::UInt(2)),
+                ],
+            }
+        );
+
+        // as of block_1
+        assert_eq!(
+            chainstate
+                .get_address_assets(&block_1.index_block_hash(), &bob)
+                .unwrap(),
+            AddressAssetHoldings::default()
+        );
+
+        assert!(matches!(
+            chainstate.get_address_assets(&StacksBlockId([0x11; 32]), &alice),
+            Err(Error::NoSuchBlockError)
+        ));
+    }
+}
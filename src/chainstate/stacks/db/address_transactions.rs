@@ -0,0 +1,630 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Optional index of the transactions that involve each principal, so a node can serve an
+//! address's transaction history without a separate indexer.
+//!
+//! A transaction involves a principal if the principal
+//! * is its origin or sponsor;
+//! * receives its token transfer, or is the contract it calls or deploys;
+//! * is the sender, recipient, or locked address of one of its STX, fungible or non-fungible
+//!   token events, or the contract that emitted one of its `print` events.
+//!
+//! The index is only maintained while `StacksChainState::address_tx_index` is set.  Like the
+//! contract event index, it holds transactions from every fork, and queries only return
+//! transactions from blocks on the fork of the given tip.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use burnchains::Txid;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::{
+    FTEventType, NFTEventType, STXEventType, StacksTransactionEvent, StacksTransactionReceipt,
+    TransactionOrigin,
+};
+use chainstate::stacks::Error;
+use chainstate::stacks::TransactionPayload;
+use util::db::Error as db_error;
+use util::db::{query_rows, u64_to_sql, FromColumn, FromRow};
+use vm::types::{PrincipalData, QualifiedContractIdentifier};
+
+/// Position of a transaction in the chain: the height of its block, and the position of the
+/// transaction in the block's receipts (transactions from the confirmed microblock stream come
+/// first, in stream order).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AddressTransactionCursor {
+    pub block_height: u64,
+    pub receipt_index: u32,
+}
+
+impl fmt::Display for AddressTransactionCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.block_height, self.receipt_index)
+    }
+}
+
+impl FromStr for AddressTransactionCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AddressTransactionCursor, String> {
+        let parts: Vec<_> = s.split('-').collect();
+        if parts.len() != 2 {
+            return Err(format!("Invalid transaction cursor: {}", s));
+        }
+        let invalid = |_| format!("Invalid transaction cursor: {}", s);
+        Ok(AddressTransactionCursor {
+            block_height: parts[0].parse().map_err(invalid)?,
+            receipt_index: parts[1].parse().map_err(invalid)?,
+        })
+    }
+}
+
+/// A transaction involving a principal, as stored in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressTransaction {
+    pub index_block_hash: StacksBlockId,
+    pub txid: Txid,
+    pub cursor: AddressTransactionCursor,
+    /// The microblock the transaction was mined in, if it was not mined in the anchored block
+    pub microblock_hash: Option<BlockHeaderHash>,
+    pub microblock_sequence: Option<u16>,
+    /// Positions of the transaction's events that involve the principal
+    pub event_indexes: Vec<u32>,
+}
+
+impl FromRow<AddressTransaction> for AddressTransaction {
+    fn from_row<'a>(row: &'a Row) -> Result<AddressTransaction, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let txid = Txid::from_column(row, "txid")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let receipt_index: i64 = row.get_unwrap("receipt_index");
+        let microblock_hash: Option<String> = row.get_unwrap("microblock_hash");
+        let microblock_sequence: Option<i64> = row.get_unwrap("microblock_sequence");
+        let event_indexes_json: String = row.get_unwrap("event_indexes");
+
+        let microblock_hash = match microblock_hash {
+            Some(hash) => Some(BlockHeaderHash::from_hex(&hash).map_err(|_| db_error::ParseError)?),
+            None => None,
+        };
+        let microblock_sequence = match microblock_sequence {
+            Some(seq) => Some(u16::try_from(seq).map_err(|_| db_error::ParseError)?),
+            None => None,
+        };
+        let event_indexes: Vec<u32> =
+            serde_json::from_str(&event_indexes_json).map_err(|_| db_error::ParseError)?;
+
+        Ok(AddressTransaction {
+            index_block_hash,
+            txid,
+            cursor: AddressTransactionCursor {
+                block_height,
+                receipt_index: u32::try_from(receipt_index).map_err(|_| db_error::ParseError)?,
+            },
+            microblock_hash,
+            microblock_sequence,
+            event_indexes,
+        })
+    }
+}
+
+/// The principals an event involves
+fn event_principals(event: &StacksTransactionEvent) -> Vec<PrincipalData> {
+    match event {
+        StacksTransactionEvent::SmartContractEvent(data) => {
+            vec![PrincipalData::Contract(data.key.0.clone())]
+        }
+        StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(data)) => {
+            vec![data.sender.clone(), data.recipient.clone()]
+        }
+        StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(data)) => {
+            vec![data.recipient.clone()]
+        }
+        StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(data)) => {
+            vec![data.sender.clone()]
+        }
+        StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(data)) => {
+            vec![data.locked_address.clone()]
+        }
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(data)) => {
+            vec![data.sender.clone(), data.recipient.clone()]
+        }
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(data)) => {
+            vec![data.recipient.clone()]
+        }
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(data)) => {
+            vec![data.sender.clone()]
+        }
+        StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(data)) => {
+            vec![data.sender.clone(), data.recipient.clone()]
+        }
+        StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(data)) => {
+            vec![data.recipient.clone()]
+        }
+        StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(data)) => {
+            vec![data.sender.clone()]
+        }
+    }
+}
+
+/// The principals a receipt's transaction involves (see the module documentation), each with
+/// the positions of the transaction's events that involve it.  Principals are listed in the
+/// order they are first encountered.
+pub fn receipt_principals(receipt: &StacksTransactionReceipt) -> Vec<(PrincipalData, Vec<u32>)> {
+    let mut principals: Vec<(PrincipalData, Vec<u32>)> = vec![];
+    let mut positions: HashMap<PrincipalData, usize> = HashMap::new();
+    let mut add = |principal: PrincipalData, event_index: Option<u32>| {
+        let position = *positions.entry(principal.clone()).or_insert_with(|| {
+            principals.push((principal, vec![]));
+            principals.len() - 1
+        });
+        if let Some(event_index) = event_index {
+            let event_indexes = &mut principals[position].1;
+            if event_indexes.last() != Some(&event_index) {
+                event_indexes.push(event_index);
+            }
+        }
+    };
+
+    if let TransactionOrigin::Stacks(ref tx) = receipt.transaction {
+        add(tx.origin_address().into(), None);
+        if let Some(sponsor) = tx.sponsor_address() {
+            add(sponsor.into(), None);
+        }
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref recipient, ..) => add(recipient.clone(), None),
+            TransactionPayload::ContractCall(ref contract_call) => add(
+                PrincipalData::Contract(contract_call.to_clarity_contract_id()),
+                None,
+            ),
+            TransactionPayload::SmartContract(ref smart_contract) => add(
+                PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    tx.origin_address().into(),
+                    smart_contract.name.clone(),
+                )),
+                None,
+            ),
+            TransactionPayload::PoisonMicroblock(..) | TransactionPayload::Coinbase(..) => {}
+        }
+    }
+
+    for (event_index, event) in receipt.events.iter().enumerate() {
+        for principal in event_principals(event).into_iter() {
+            add(principal, Some(event_index as u32));
+        }
+    }
+
+    principals
+}
+
+impl StacksChainState {
+    /// Add the transactions in a processed block's receipts to the address transaction index
+    pub fn index_address_transactions(
+        tx: &DBTx,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let sql = "INSERT OR REPLACE INTO address_transactions
+                   (principal, index_block_hash, block_height, receipt_index, txid,
+                    microblock_hash, microblock_sequence, event_indexes)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+        for (receipt_index, receipt) in receipts.iter().enumerate() {
+            let txid = receipt.transaction.txid();
+            let microblock_hash = receipt
+                .microblock_header
+                .as_ref()
+                .map(|header| header.block_hash());
+            let microblock_sequence = receipt
+                .microblock_header
+                .as_ref()
+                .map(|header| header.sequence as i64);
+            for (principal, event_indexes) in receipt_principals(receipt).into_iter() {
+                let event_indexes_json = serde_json::to_string(&event_indexes)
+                    .expect("FATAL: failed to serialize event indexes");
+                let args: &[&dyn ToSql] = &[
+                    &principal.to_string(),
+                    index_block_hash,
+                    &u64_to_sql(block_height)?,
+                    &(receipt_index as i64),
+                    &txid,
+                    &microblock_hash,
+                    &microblock_sequence,
+                    &event_indexes_json,
+                ];
+                tx.execute(sql, args)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get up to `limit` of the transactions involving `principal` on the fork ending at `tip`,
+    /// newest first.  If `before` is given, only transactions before that cursor are returned.
+    pub fn get_address_transactions(
+        &self,
+        tip: &StacksBlockId,
+        principal: &PrincipalData,
+        before: Option<&AddressTransactionCursor>,
+        limit: u32,
+    ) -> Result<Vec<AddressTransaction>, Error> {
+        let index_conn = self.index_conn()?;
+        let tip_height = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            tip,
+        )? {
+            Some(header) => header.block_height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+
+        let principal_str = principal.to_string();
+        let tip_cursor = AddressTransactionCursor {
+            block_height: tip_height,
+            receipt_index: u32::MAX,
+        };
+        let (first_cursor, first_cmp) = match before {
+            Some(before) if *before <= tip_cursor => (*before, "<"),
+            _ => (tip_cursor, "<="),
+        };
+        // the last row examined.  Rows from different forks can share a cursor, so paging within
+        // this query also orders by block ID.
+        let mut last_row: Option<(AddressTransactionCursor, StacksBlockId)> = None;
+
+        // height => block ID of the ancestor of `tip` at that height
+        let mut canonical: HashMap<u64, Option<StacksBlockId>> = HashMap::new();
+        let mut transactions = vec![];
+
+        while (transactions.len() as u32) < limit {
+            // non-canonical transactions are filtered out below, so ask for more than we need
+            let batch_size = (limit as i64) * 2;
+            let position_clause = match last_row {
+                Some(_) => {
+                    "(block_height, receipt_index, index_block_hash) < (?2, ?3, ?5)".to_string()
+                }
+                None => format!("(block_height, receipt_index) {} (?2, ?3)", first_cmp),
+            };
+            let sql = format!(
+                "SELECT * FROM address_transactions
+                 WHERE principal = ?1 AND {}
+                 ORDER BY block_height DESC, receipt_index DESC, index_block_hash DESC
+                 LIMIT ?4",
+                position_clause
+            );
+            let (start, start_block) = match last_row {
+                Some((cursor, ref block_id)) => (cursor, Some(block_id.clone())),
+                None => (first_cursor, None),
+            };
+            let start_height = u64_to_sql(start.block_height)?;
+            let start_receipt_index = start.receipt_index as i64;
+            let mut args: Vec<&dyn ToSql> = vec![
+                &principal_str,
+                &start_height,
+                &start_receipt_index,
+                &batch_size,
+            ];
+            if let Some(ref start_block) = start_block {
+                args.push(start_block);
+            }
+
+            let batch: Vec<AddressTransaction> = query_rows(self.db(), &sql, args.as_slice())?;
+            let batch_len = batch.len() as i64;
+            for transaction in batch.into_iter() {
+                last_row = Some((transaction.cursor, transaction.index_block_hash.clone()));
+
+                let height = transaction.cursor.block_height;
+                let ancestor = match canonical.entry(height) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(
+                        index_conn
+                            .get_ancestor_block_hash(height, tip)
+                            .map_err(Error::DBError)?,
+                    ),
+                };
+                if ancestor.as_ref() != Some(&transaction.index_block_hash) {
+                    continue;
+                }
+
+                transactions.push(transaction);
+                if (transactions.len() as u32) >= limit {
+                    break;
+                }
+            }
+            if batch_len < batch_size {
+                break;
+            }
+        }
+
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainstate::burn::ConsensusHash;
+    use chainstate::stacks::db::test::*;
+    use chainstate::stacks::events::{STXTransferEventData, SmartContractEventData};
+    use chainstate::stacks::*;
+    use util::hash::{Hash160, Sha512Trunc256Sum};
+    use vm::costs::ExecutionCost;
+    use vm::types::Value;
+
+    use crate::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+
+    use super::*;
+
+    fn make_child_tip(
+        chainstate: &mut StacksChainState,
+        parent: &StacksHeaderInfo,
+        fork: u8,
+    ) -> StacksHeaderInfo {
+        let mut new_tip = parent.clone();
+        new_tip.anchored_header.parent_block = parent.anchored_header.block_hash();
+        new_tip.anchored_header.total_work.work = parent.anchored_header.total_work.work + 1;
+        new_tip.block_height = parent.block_height + 1;
+        new_tip.consensus_hash = ConsensusHash(
+            Hash160::from_data(
+                &Sha512Trunc256Sum::from_data(&[&parent.consensus_hash.0[..], &[fork]].concat()).0,
+            )
+            .0,
+        );
+        new_tip.burn_header_hash =
+            BurnchainHeaderHash(Sha512Trunc256Sum::from_data(&new_tip.consensus_hash.0).0);
+        new_tip.burn_header_height = parent.burn_header_height + 1;
+
+        let mut block_reward = MinerPaymentSchedule::genesis(false);
+        block_reward.parent_consensus_hash = parent.consensus_hash;
+        block_reward.parent_block_hash = parent.anchored_header.block_hash();
+        block_reward.block_hash = new_tip.anchored_header.block_hash();
+        block_reward.consensus_hash = new_tip.consensus_hash;
+
+        let mut tx = chainstate.index_tx_begin().unwrap();
+        let tip = StacksChainState::advance_tip(
+            &mut tx,
+            &parent.anchored_header,
+            &parent.consensus_hash,
+            &new_tip.anchored_header,
+            &new_tip.consensus_hash,
+            &new_tip.burn_header_hash,
+            new_tip.burn_header_height,
+            new_tip.burn_header_timestamp,
+            None,
+            &block_reward,
+            &vec![],
+            &ExecutionCost::zero(),
+            123,
+            false,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    fn address_of(privk: &StacksPrivateKey) -> StacksAddress {
+        StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(privk)],
+        )
+        .unwrap()
+    }
+
+    fn make_transfer_receipt(
+        privk: &StacksPrivateKey,
+        nonce: u64,
+        recipient: &StacksAddress,
+    ) -> StacksTransactionReceipt {
+        let mut tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(privk).unwrap(),
+            TransactionPayload::TokenTransfer(
+                recipient.clone().into(),
+                123,
+                TokenTransferMemo([0u8; 34]),
+            ),
+        );
+        tx.set_origin_nonce(nonce);
+        let event = StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(
+            STXTransferEventData {
+                sender: address_of(privk).into(),
+                recipient: recipient.clone().into(),
+                amount: 123,
+            },
+        ));
+        StacksTransactionReceipt::from_stx_transfer(
+            tx,
+            vec![event],
+            Value::okay_true(),
+            ExecutionCost::zero(),
+        )
+    }
+
+    fn index_block(
+        chainstate: &mut StacksChainState,
+        tip: &StacksHeaderInfo,
+        receipts: &[StacksTransactionReceipt],
+    ) {
+        let tx = chainstate.index_tx_begin().unwrap();
+        StacksChainState::index_address_transactions(
+            tx.tx(),
+            &tip.index_block_hash(),
+            tip.block_height,
+            receipts,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_receipt_principals() {
+        let privk = StacksPrivateKey::new();
+        let sender = address_of(&privk);
+        let contract = QualifiedContractIdentifier::new(sender.clone().into(), "token".into());
+
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk).unwrap(),
+            TransactionPayload::new_contract_call(sender.clone(), "token", "transfer", vec![])
+                .unwrap(),
+        );
+        let recipient =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap();
+        let events = vec![
+            StacksTransactionEvent::SmartContractEvent(SmartContractEventData {
+                key: (contract.clone(), "print".to_string()),
+                value: Value::Int(1),
+            }),
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(
+                STXTransferEventData {
+                    sender: sender.clone().into(),
+                    recipient: recipient.clone().into(),
+                    amount: 1,
+                },
+            )),
+        ];
+        let receipt = StacksTransactionReceipt::from_contract_call(
+            tx,
+            events,
+            Value::okay_true(),
+            0,
+            ExecutionCost::zero(),
+        );
+
+        assert_eq!(
+            receipt_principals(&receipt),
+            vec![
+                (sender.into(), vec![1]),
+                (PrincipalData::Contract(contract), vec![0]),
+                (recipient.into(), vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_address_transactions() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "test_get_address_transactions");
+        let alice_privk = StacksPrivateKey::new();
+        let alice: PrincipalData = address_of(&alice_privk).into();
+        let bob = StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap();
+
+        // genesis <- block_1 <- block_2, and genesis <- fork_1
+        let genesis = StacksHeaderInfo::regtest_genesis();
+        let block_1 = make_child_tip(&mut chainstate, &genesis, 0);
+        let block_2 = make_child_tip(&mut chainstate, &block_1, 0);
+        let fork_1 = make_child_tip(&mut chainstate, &genesis, 1);
+
+        let mut microblock_receipt = make_transfer_receipt(&alice_privk, 0, &bob);
+        microblock_receipt.microblock_header = Some(
+            StacksMicroblock::first_unsigned(&block_1.anchored_header.block_hash(), vec![]).header,
+        );
+        index_block(
+            &mut chainstate,
+            &block_1,
+            &[
+                microblock_receipt,
+                make_transfer_receipt(&alice_privk, 1, &bob),
+            ],
+        );
+        index_block(
+            &mut chainstate,
+            &block_2,
+            &[make_transfer_receipt(&alice_privk, 2, &bob)],
+        );
+        // same height and receipt index as block_1's transactions
+        index_block(
+            &mut chainstate,
+            &fork_1,
+            &[
+                make_transfer_receipt(&alice_privk, 10, &bob),
+                make_transfer_receipt(&alice_privk, 11, &bob),
+            ],
+        );
+
+        let positions = |transactions: &[AddressTransaction]| -> Vec<(u64, u32)> {
+            transactions
+                .iter()
+                .map(|tx| (tx.cursor.block_height, tx.cursor.receipt_index))
+                .collect()
+        };
+
+        let tip = block_2.index_block_hash();
+        let transactions = chainstate
+            .get_address_transactions(&tip, &alice, None, 10)
+            .unwrap();
+        assert_eq!(positions(&transactions), vec![(2, 0), (1, 1), (1, 0)]);
+        assert!(transactions
+            .iter()
+            .all(|tx| tx.index_block_hash != fork_1.index_block_hash()));
+        assert_eq!(transactions[1].microblock_hash, None);
+        assert!(transactions[2].microblock_hash.is_some());
+        assert_eq!(transactions[2].microblock_sequence, Some(0));
+        assert_eq!(transactions[2].event_indexes, vec![0]);
+
+        // page through one transaction at a time
+        let mut cursor = None;
+        let mut paged = vec![];
+        loop {
+            let page = chainstate
+                .get_address_transactions(&tip, &alice, cursor.as_ref(), 1)
+                .unwrap();
+            if page.is_empty() {
+                break;
+            }
+            assert_eq!(page.len(), 1);
+            cursor = Some(page[0].cursor);
+            paged.extend(page);
+        }
+        assert_eq!(paged, transactions);
+
+        // the recipient is indexed too
+        assert_eq!(
+            positions(
+                &chainstate
+                    .get_address_transactions(&fork_1.index_block_hash(), &bob.into(), None, 10)
+                    .unwrap()
+            ),
+            vec![(1, 1), (1, 0)]
+        );
+
+        assert!(matches!(
+            chainstate.get_address_transactions(&StacksBlockId([0x11; 32]), &alice, None, 10),
+            Err(Error::NoSuchBlockError)
+        ));
+    }
+
+    #[test]
+    fn test_address_transaction_cursor() {
+        let cursor: AddressTransactionCursor = "1234-5".parse().unwrap();
+        assert_eq!(
+            cursor,
+            AddressTransactionCursor {
+                block_height: 1234,
+                receipt_index: 5,
+            }
+        );
+        assert_eq!(cursor.to_string(), "1234-5");
+        assert!("1234".parse::<AddressTransactionCursor>().is_err());
+        assert!("1234-5-6".parse::<AddressTransactionCursor>().is_err());
+        assert!("a-5".parse::<AddressTransactionCursor>().is_err());
+    }
+}
@@ -37,6 +37,9 @@ use chainstate::burn::db::sortdb::*;
 use chainstate::burn::operations::*;
 use chainstate::burn::BlockSnapshot;
 use chainstate::stacks::db::accounts::MinerReward;
+use chainstate::stacks::db::parallel_analysis::{
+    ContractDeploy, ParallelAnalysis, PreAnalysisOutcome,
+};
 use chainstate::stacks::db::transactions::TransactionNonceMismatch;
 use chainstate::stacks::db::*;
 use chainstate::stacks::index::MarfTrieId;
@@ -53,6 +56,7 @@ use core::*;
 use cost_estimates::EstimatorError;
 use net::BlocksInvData;
 use net::Error as net_error;
+use net::ExportedBlockData;
 use net::ExtendedStacksHeader;
 use net::MemPoolSyncData;
 use util::db::u64_to_sql;
@@ -554,6 +558,58 @@ impl StreamCursor {
         })
     }
 
+    /// Stream the canonical blocks in the fork identified by `tip`, starting at `start_height`.
+    /// At most `count` blocks will be sent, and the stream will stop at `tip`.
+    pub fn new_block_export(
+        chainstate: &StacksChainState,
+        tip: &StacksBlockId,
+        start_height: u64,
+        count: u32,
+    ) -> Result<StreamCursor, Error> {
+        let tip_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.db(),
+            tip,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        // the boot block has no block data, so never send it
+        let start_height = cmp::max(start_height, 1);
+        let end_height = cmp::min(
+            tip_info.block_height + 1,
+            start_height.saturating_add(count as u64),
+        );
+
+        let index_conn = chainstate.index_conn()?;
+        let mut index_block_hashes = vec![];
+        for height in start_height..end_height {
+            match index_conn
+                .get_ancestor_block_hash(height, tip)
+                .map_err(Error::DBError)?
+            {
+                Some(index_block_hash) => index_block_hashes.push(index_block_hash),
+                None => break,
+            }
+        }
+
+        test_debug!(
+            "Request to export {} blocks from {} off of {}",
+            index_block_hashes.len(),
+            start_height,
+            tip
+        );
+
+        // need to send out the consensus_serialize()'ed array length before sending blocks.
+        let num_items_buf = (index_block_hashes.len() as u32).to_be_bytes();
+
+        Ok(StreamCursor::BlockExport(BlockExportStreamData {
+            index_block_hashes,
+            next_block_ptr: 0,
+            block_buf: num_items_buf.to_vec(),
+            block_buf_ptr: 0,
+            total_bytes: 0,
+        }))
+    }
+
     fn stream_one_byte<W: Write>(fd: &mut W, b: u8) -> Result<u64, Error> {
         loop {
             match fd.write(&[b]) {
@@ -586,6 +642,7 @@ impl StreamCursor {
             StreamCursor::Block(ref stream) => stream.offset(),
             StreamCursor::Microblocks(ref stream) => stream.offset(),
             StreamCursor::Headers(ref stream) => stream.offset(),
+            StreamCursor::BlockExport(ref stream) => stream.offset(),
             // no-op for mempool txs
             StreamCursor::MempoolTxs(..) => 0,
         }
@@ -596,6 +653,7 @@ impl StreamCursor {
             StreamCursor::Block(ref mut stream) => stream.add_bytes(nw),
             StreamCursor::Microblocks(ref mut stream) => stream.add_bytes(nw),
             StreamCursor::Headers(ref mut stream) => stream.add_bytes(nw),
+            StreamCursor::BlockExport(ref mut stream) => stream.add_bytes(nw),
             // no-op fo mempool txs
             StreamCursor::MempoolTxs(..) => (),
         }
@@ -650,6 +708,9 @@ impl StreamCursor {
                 Ok(num_written)
             }
             StreamCursor::Block(ref mut stream) => chainstate.stream_block(fd, stream, count),
+            StreamCursor::BlockExport(ref mut stream) => {
+                chainstate.stream_block_export(fd, stream, count)
+            }
         }
     }
 }
@@ -683,6 +744,15 @@ impl Streamer for BlockStreamData {
     }
 }
 
+impl Streamer for BlockExportStreamData {
+    fn offset(&self) -> u64 {
+        self.total_bytes
+    }
+    fn add_bytes(&mut self, nw: u64) {
+        self.total_bytes += nw;
+    }
+}
+
 impl Streamer for MicroblockStreamData {
     fn offset(&self) -> u64 {
         self.offset
@@ -2701,6 +2771,64 @@ impl StacksChainState {
             .and_then(|processed| Ok(processed.len() > 0))
     }
 
+    /// Load up to `limit` microblocks at or after `start_seq` from the stream built off of the
+    /// anchored block identified by the given parent_index_block_hash, in sequence order.  If a
+    /// child anchored block has confirmed the stream, the confirmed microblocks are loaded;
+    /// otherwise, the longest non-forked stream of unconfirmed microblocks is.
+    /// Returns whether or not the stream is confirmed, and the microblocks, or None if there are
+    /// no such microblocks.
+    /// Used for paging through microblock streams in the RPC interface.
+    ///
+    /// DO NOT USE IN CONSENSUS CODE.
+    pub fn load_microblock_stream_page(
+        &self,
+        parent_index_block_hash: &StacksBlockId,
+        start_seq: u16,
+        limit: u16,
+    ) -> Result<Option<(bool, Vec<StacksMicroblock>)>, Error> {
+        let unconfirmed = StacksChainState::read_i64s(self.db(), "SELECT processed FROM staging_microblocks WHERE index_block_hash = ?1 AND processed = 1 AND orphaned = 0 LIMIT 1", &[parent_index_block_hash])?.is_empty();
+
+        if unconfirmed {
+            let last_seq = start_seq.saturating_add(limit);
+            return Ok(StacksChainState::load_descendant_staging_microblock_stream(
+                self.db(),
+                parent_index_block_hash,
+                start_seq,
+                last_seq,
+            )?
+            .map(|microblocks| (false, microblocks)));
+        }
+
+        let sql = "SELECT * FROM staging_microblocks WHERE index_block_hash = ?1 AND sequence >= ?2 AND processed = 1 AND orphaned = 0 ORDER BY sequence ASC LIMIT ?3";
+        let args: &[&dyn ToSql] = &[parent_index_block_hash, &start_seq, &limit];
+        let staging_microblocks =
+            query_rows::<StagingMicroblock, _>(self.db(), sql, args).map_err(Error::DBError)?;
+
+        let mut ret: Vec<StacksMicroblock> = vec![];
+        for staging_microblock in staging_microblocks.iter() {
+            if let Some(last) = ret.last() {
+                if last.header.sequence == staging_microblock.sequence {
+                    // children in different forks confirmed different microblock forks
+                    continue;
+                }
+            }
+            let mblock_data = StacksChainState::load_staging_microblock_bytes(
+                self.db(),
+                &staging_microblock.microblock_hash,
+            )?
+            .ok_or(Error::NoSuchBlockError)?;
+            let mblock = StacksMicroblock::consensus_deserialize(&mut &mblock_data[..])
+                .map_err(Error::CodecError)?;
+            ret.push(mblock);
+        }
+
+        if ret.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some((true, ret)))
+        }
+    }
+
     /// Do we have a given microblock as a descendant of a given anchored block?
     /// Does not consider whether or not it has been processed or is orphaned.
     /// Used by the relayer to decide whether or not a microblock should be relayed.
@@ -3193,6 +3321,260 @@ impl StacksChainState {
         StacksChainState::stream_data_from_chunk_store(&self.blocks_path, fd, stream, count)
     }
 
+    /// Load an anchored block and the parent microblock stream it confirms, for export.
+    /// Returns Ok(None) if the block or any of its confirmed microblocks are not stored.
+    pub fn load_exported_block(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Option<ExportedBlockData>, Error> {
+        let block_info =
+            match StacksChainState::load_staging_block_info(&self.db(), index_block_hash)? {
+                Some(block_info) => block_info,
+                None => {
+                    return Ok(None);
+                }
+            };
+
+        let block = match StacksChainState::load_block(
+            &self.blocks_path,
+            &block_info.consensus_hash,
+            &block_info.anchored_block_hash,
+        )? {
+            Some(block) => block,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let parent_microblocks = if block.header.parent_microblock == EMPTY_MICROBLOCK_PARENT_HASH
+            && block.header.parent_microblock_sequence == 0
+        {
+            vec![]
+        } else {
+            match StacksChainState::load_processed_microblock_stream_fork(
+                &self.db(),
+                &block_info.parent_consensus_hash,
+                &block_info.parent_anchored_block_hash,
+                &block.header.parent_microblock,
+            )? {
+                Some(microblocks) => microblocks,
+                None => {
+                    return Ok(None);
+                }
+            }
+        };
+
+        Ok(Some(ExportedBlockData {
+            consensus_hash: block_info.consensus_hash,
+            block,
+            parent_consensus_hash: block_info.parent_consensus_hash,
+            parent_microblocks,
+        }))
+    }
+
+    /// Stream a sequence of canonical blocks and their confirmed microblocks.
+    /// Each block is loaded and serialized in its entirety before being sent.
+    /// Returns the number of bytes written, and updates `stream` to point to the next point to
+    /// read.  Writes the bytes streamed to `fd`.
+    pub fn stream_block_export<W: Write>(
+        &self,
+        fd: &mut W,
+        stream: &mut BlockExportStreamData,
+        count: u64,
+    ) -> Result<u64, Error> {
+        let mut num_written = 0;
+        while num_written < count {
+            if stream.block_buf_ptr >= stream.block_buf.len() {
+                // load next
+                let index_block_hash = match stream.index_block_hashes.get(stream.next_block_ptr) {
+                    Some(index_block_hash) => index_block_hash.clone(),
+                    None => {
+                        test_debug!("No more blocks to export");
+                        break;
+                    }
+                };
+                stream.next_block_ptr += 1;
+
+                let exported_block = self
+                    .load_exported_block(&index_block_hash)?
+                    .ok_or(Error::NoSuchBlockError)?;
+
+                stream.block_buf.clear();
+                stream.block_buf_ptr = 0;
+                exported_block
+                    .consensus_serialize(&mut stream.block_buf)
+                    .map_err(Error::CodecError)?;
+            }
+
+            // write out bufferred block
+            let start = stream.block_buf_ptr;
+            let end = cmp::min(
+                stream.block_buf.len(),
+                start + ((count - num_written) as usize),
+            );
+            fd.write_all(&stream.block_buf[start..end])
+                .map_err(Error::WriteError)?;
+
+            let nw = (end - start) as u64;
+            stream.block_buf_ptr = end;
+            stream.add_bytes(nw);
+            num_written += nw;
+        }
+        Ok(num_written)
+    }
+
+    /// Get the results of the transactions in a block's receipts, as logged when the block was
+    /// processed.  Empty unless the node ran with `STACKS_TRANSACTION_LOG=1`.
+    fn get_logged_transaction_results(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<HashMap<Txid, String>, Error> {
+        let sql = "SELECT txid, result FROM transactions WHERE index_block_hash = ?1";
+        let mut stmt = self
+            .db()
+            .prepare(sql)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        let rows = stmt
+            .query_map(&[index_block_hash], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        let mut results = HashMap::new();
+        for row in rows {
+            let (txid_hex, result) = row.map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            let txid =
+                Txid::from_hex(&txid_hex).map_err(|_| Error::DBError(db_error::ParseError))?;
+            results.insert(txid, result);
+        }
+        Ok(results)
+    }
+
+    /// Load a processed anchored block, the transactions of it and of the microblocks it
+    /// confirms, and what the node recorded of their receipts, as one JSON object.  Receipt
+    /// results are only known if the node logged them (see `get_logged_transaction_results()`);
+    /// receipt events are the block's indexed contract events.
+    /// Returns Ok(None) if the block was not processed or its data is not stored.
+    pub fn load_exported_block_json(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Option<serde_json::Value>, Error> {
+        let header_info = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            index_block_hash,
+        )? {
+            Some(header_info) => header_info,
+            None => {
+                return Ok(None);
+            }
+        };
+        let exported_block = match self.load_exported_block(index_block_hash)? {
+            Some(exported_block) => exported_block,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let results = self.get_logged_transaction_results(index_block_hash)?;
+        let mut events: HashMap<Txid, Vec<serde_json::Value>> = HashMap::new();
+        for event in self.get_block_contract_events(index_block_hash)?.iter() {
+            events
+                .entry(event.txid.clone())
+                .or_default()
+                .push(event.to_json());
+        }
+
+        // receipts list the confirmed microblock transactions first
+        let mut txs = vec![];
+        for microblock in exported_block.parent_microblocks.iter() {
+            for tx in microblock.txs.iter() {
+                txs.push((
+                    tx,
+                    Some((microblock.block_hash(), microblock.header.sequence)),
+                ));
+            }
+        }
+        for tx in exported_block.block.txs.iter() {
+            txs.push((tx, None));
+        }
+
+        let transactions: Vec<_> = txs
+            .into_iter()
+            .map(|(tx, microblock)| {
+                let txid = tx.txid();
+                json!({
+                    "txid": format!("0x{}", &txid),
+                    "tx": format!("0x{}", to_hex(&tx.serialize_to_vec())),
+                    "microblock_hash": microblock
+                        .as_ref()
+                        .map(|(hash, _)| format!("0x{}", hash)),
+                    "microblock_sequence": microblock.as_ref().map(|(_, seq)| *seq),
+                    "receipt": {
+                        "result": results.get(&txid),
+                        "events": events.remove(&txid).unwrap_or_default(),
+                    },
+                })
+            })
+            .collect();
+
+        Ok(Some(json!({
+            "block_height": header_info.block_height,
+            "index_block_hash": format!("0x{}", index_block_hash),
+            "block_hash": format!("0x{}", exported_block.block.block_hash()),
+            "consensus_hash": format!("0x{}", &exported_block.consensus_hash),
+            "parent_block_hash": format!("0x{}", &exported_block.block.header.parent_block),
+            "parent_consensus_hash": format!("0x{}", &exported_block.parent_consensus_hash),
+            "burn_block_height": header_info.burn_header_height,
+            "burn_block_hash": format!("0x{}", &header_info.burn_header_hash),
+            "burn_block_time": header_info.burn_header_timestamp,
+            "transactions": transactions,
+        })))
+    }
+
+    /// Write the canonical blocks in the fork identified by `tip` from `start_height` through
+    /// `end_height` (or `tip`) to `fd` as newline-delimited JSON, one block (see
+    /// `load_exported_block_json()`) per line.  Blocks are loaded one at a time and `fd` is
+    /// flushed after each, so a slow reader holds back the export instead of letting it buffer.
+    /// Returns the number of blocks written.
+    pub fn write_block_export_ndjson<W: Write>(
+        &self,
+        fd: &mut W,
+        tip: &StacksBlockId,
+        start_height: u64,
+        end_height: Option<u64>,
+    ) -> Result<u64, Error> {
+        let tip_info =
+            StacksChainState::get_stacks_block_header_info_by_index_block_hash(self.db(), tip)?
+                .ok_or(Error::NoSuchBlockError)?;
+        let end_height = match end_height {
+            Some(end_height) => cmp::min(end_height, tip_info.block_height),
+            None => tip_info.block_height,
+        };
+
+        let index_conn = self.index_conn()?;
+        let mut num_written = 0;
+        // the boot block has no block data, so never send it
+        for height in cmp::max(start_height, 1)..=end_height {
+            let index_block_hash = match index_conn
+                .get_ancestor_block_hash(height, tip)
+                .map_err(Error::DBError)?
+            {
+                Some(index_block_hash) => index_block_hash,
+                None => break,
+            };
+            let block_json = self
+                .load_exported_block_json(&index_block_hash)?
+                .ok_or(Error::NoSuchBlockError)?;
+
+            serde_json::to_writer(&mut *fd, &block_json)
+                .map_err(|e| Error::WriteError(e.into()))?;
+            fd.write_all(b"\n").map_err(Error::WriteError)?;
+            fd.flush().map_err(Error::WriteError)?;
+            num_written += 1;
+        }
+        Ok(num_written)
+    }
+
     /// Stream unconfirmed microblocks from the staging DB.  Pull only from the staging DB.
     /// Returns the number of bytes written, and updates `stream` to point to the next point to
     /// read.  Wrties the bytes streamed to `fd`.
@@ -4341,7 +4723,16 @@ impl StacksChainState {
                         applied = true;
                     }
                     StacksEpochId::Epoch2_05 => {
-                        panic!("No defined transition from Epoch2_05 forward")
+                        assert_eq!(
+                            sortition_epoch.epoch_id,
+                            StacksEpochId::Epoch21,
+                            "Should only transition from Epoch2_05 to Epoch21"
+                        );
+                        receipts.push(clarity_tx.block.initialize_epoch_2_1()?);
+                        applied = true;
+                    }
+                    StacksEpochId::Epoch21 => {
+                        panic!("No defined transition from Epoch21 forward")
                     }
                 }
             }
@@ -4408,6 +4799,7 @@ impl StacksChainState {
                             execution_cost,
                             microblock_header: None,
                             tx_index: 0,
+                            vm_error: None,
                         };
 
                         all_receipts.push(receipt);
@@ -4463,6 +4855,7 @@ impl StacksChainState {
                                 execution_cost: ExecutionCost::zero(),
                                 microblock_header: None,
                                 tx_index: 0,
+                                vm_error: None,
                             }),
                             Err(e) => {
                                 info!("TransferStx burn op processing error.";
@@ -4984,6 +5377,11 @@ impl StacksChainState {
         .expect("BUG: Failed to load snapshot for block snapshot during Stacks block processing")
         .parent_burn_header_hash;
 
+        let analysis_cache = clarity_instance.get_analysis_cache();
+        let clarity_state_blobs = clarity_instance.with_marf(|marf| marf.external_blobs().cloned());
+        let clarity_state_index_root =
+            StacksChainState::vm_state_index_root_path(PathBuf::from(&chainstate_tx.root_path));
+
         let SetupBlockResult {
             mut clarity_tx,
             mut tx_receipts,
@@ -5014,6 +5412,40 @@ impl StacksChainState {
             ExecutionCost::max_value()
         });
 
+        // analyze the block's contract deploys in parallel, so that processing its transactions
+        // can reuse the analyses (a lone deploy gains nothing from it)
+        let contract_deploys = ContractDeploy::from_transactions(&block.txs);
+        match (analysis_cache, clarity_state_index_root.to_str()) {
+            (Some(analysis_cache), Some(clarity_state_index_root))
+                if contract_deploys.len() > 1 =>
+            {
+                let outcomes = ParallelAnalysis::new(
+                    clarity_state_index_root,
+                    &StacksBlockHeader::make_index_block_hash(
+                        &parent_consensus_hash,
+                        &parent_block_hash,
+                    ),
+                    mainnet,
+                    evaluated_epoch,
+                    &block_limit,
+                    analysis_cache,
+                )
+                .with_external_blobs(clarity_state_blobs)
+                .with_cost_overrides(clarity_tx.cost_overrides())
+                .run(&contract_deploys);
+                debug!(
+                    "Analyzed {} of {} contract deploys in block {} ahead of processing",
+                    outcomes
+                        .iter()
+                        .filter(|outcome| **outcome == PreAnalysisOutcome::Analyzed)
+                        .count(),
+                    contract_deploys.len(),
+                    &block.block_hash()
+                );
+            }
+            _ => {}
+        }
+
         let (
             scheduled_miner_reward,
             block_execution_cost,
@@ -5256,6 +5688,53 @@ impl StacksChainState {
 
         chainstate_tx.log_transactions_processed(&new_tip.index_block_hash(), &tx_receipts);
 
+        StacksChainState::index_contract_events(
+            chainstate_tx.tx.tx(),
+            &new_tip.index_block_hash(),
+            new_tip.block_height,
+            &tx_receipts,
+        )
+        .expect("FATAL: failed to index contract events");
+
+        StacksChainState::record_contract_metrics(
+            chainstate_tx.tx.tx(),
+            &new_tip.index_block_hash(),
+            new_tip.block_height,
+            &tx_receipts,
+        )
+        .expect("FATAL: failed to record contract execution metrics");
+
+        if chainstate_tx.address_tx_index {
+            StacksChainState::index_address_transactions(
+                chainstate_tx.tx.tx(),
+                &new_tip.index_block_hash(),
+                new_tip.block_height,
+                &tx_receipts,
+            )
+            .expect("FATAL: failed to index address transactions");
+        }
+
+        if chainstate_tx.address_asset_index {
+            StacksChainState::index_address_assets(
+                chainstate_tx.tx.tx(),
+                &new_tip.index_block_hash(),
+                new_tip.block_height,
+                &tx_receipts,
+            )
+            .expect("FATAL: failed to index address assets");
+        }
+
+        if chainstate_tx.rosetta_index {
+            StacksChainState::index_rosetta_transactions(
+                chainstate_tx.tx.tx(),
+                &new_tip.index_block_hash(),
+                new_tip.block_height,
+                &tx_receipts,
+                &matured_rewards,
+            )
+            .expect("FATAL: failed to index Rosetta operations");
+        }
+
         set_last_execution_cost_observed(&block_execution_cost, &block_limit);
 
         let epoch_receipt = StacksEpochReceipt {
@@ -10188,6 +10667,118 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_write_block_export_ndjson() {
+        let peer_config = TestPeerConfig::new("test_write_block_export_ndjson", 21315, 21316);
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+
+        let num_blocks = 3;
+        let mut blocks = vec![];
+        for tenure_id in 0..num_blocks {
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let mut mempool =
+                        MemPoolDB::open_test(false, 0x80000000, &chainstate_path).unwrap();
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let anchored_block = StacksBlockBuilder::build_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mut mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        BlockBuilderSettings::max_value(),
+                        None,
+                    )
+                    .unwrap();
+                    (anchored_block.0, vec![])
+                },
+            );
+
+            let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+            blocks.push((consensus_hash, stacks_block));
+        }
+
+        let (ref tip_consensus_hash, ref tip_block) = blocks[num_blocks - 1];
+        let tip =
+            StacksBlockHeader::make_index_block_hash(tip_consensus_hash, &tip_block.block_hash());
+
+        let mut buf = vec![];
+        let num_written = peer
+            .chainstate()
+            .write_block_export_ndjson(&mut buf, &tip, 0, None)
+            .unwrap();
+        assert_eq!(num_written, num_blocks as u64);
+
+        let lines: Vec<&str> = std::str::from_utf8(&buf).unwrap().lines().collect();
+        assert_eq!(lines.len(), num_blocks);
+        for (i, line) in lines.iter().enumerate() {
+            let (ref consensus_hash, ref block) = blocks[i];
+            let block_json: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(block_json["block_height"], (i + 1) as u64);
+            assert_eq!(
+                block_json["index_block_hash"],
+                format!(
+                    "0x{}",
+                    StacksBlockHeader::make_index_block_hash(consensus_hash, &block.block_hash())
+                )
+            );
+            let txs = block_json["transactions"].as_array().unwrap();
+            assert_eq!(txs.len(), block.txs.len());
+            assert_eq!(txs[0]["txid"], format!("0x{}", block.txs[0].txid()));
+            assert!(txs[0]["microblock_hash"].is_null());
+            assert_eq!(txs[0]["receipt"]["events"], json!([]));
+        }
+
+        // a bounded range
+        let mut buf = vec![];
+        let num_written = peer
+            .chainstate()
+            .write_block_export_ndjson(&mut buf, &tip, 2, Some(2))
+            .unwrap();
+        assert_eq!(num_written, 1);
+        let block_json: serde_json::Value =
+            serde_json::from_str(std::str::from_utf8(&buf).unwrap().trim_end()).unwrap();
+        assert_eq!(block_json["block_height"], 2);
+    }
+
     #[test]
     fn stacks_db_staging_microblocks_fork() {
         // multiple anchored blocks build off of a forked microblock stream
@@ -0,0 +1,950 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Index of the events emitted by contracts, so they can be looked up by contract, event type
+//! and topic, like Ethereum logs.
+//!
+//! A contract's events are the `print` events it emits, and the fungible and non-fungible token
+//! events of the assets it defines.  STX events don't belong to any one contract, and are not
+//! indexed.
+//!
+//! Clarity events don't have topics of their own.  A token event's topic is the name of its
+//! asset, and a `print` event's topic is derived from the printed value:
+//! * a tuple with a string-valued `topic` or `event` field (the common convention, e.g.
+//!   `(print { event: "transfer", ... })`) has that string as its topic;
+//! * any other tuple has the name of its first field (in Clarity's sorted field order);
+//! * a string has itself as its topic;
+//! * anything else has no topic, and can only be found by contract.
+//!
+//! Events are indexed per Stacks block as it is processed, so the index holds events from every
+//! fork.  Queries only return events from blocks on the fork of the given tip.
+
+use std::cmp;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use burnchains::Txid;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::{
+    FTEventType, NFTEventType, StacksTransactionEvent, StacksTransactionReceipt,
+};
+use chainstate::stacks::Error;
+use util::db::Error as db_error;
+use util::db::{query_rows, u64_to_sql, FromColumn, FromRow};
+use vm::database::ClaritySerializable;
+use vm::types::serialization::SerializationError;
+use vm::types::{
+    CharType, PrincipalData, QualifiedContractIdentifier, SequenceData, UTF8Data, Value,
+};
+use vm::ClarityName;
+
+/// Position of an event in the chain: the height of its block, the position of its transaction
+/// in the block's receipts, and its position in the transaction's events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ContractEventCursor {
+    pub block_height: u64,
+    pub receipt_index: u32,
+    pub event_index: u32,
+}
+
+impl fmt::Display for ContractEventCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}",
+            self.block_height, self.receipt_index, self.event_index
+        )
+    }
+}
+
+impl FromStr for ContractEventCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ContractEventCursor, String> {
+        let parts: Vec<_> = s.split('-').collect();
+        if parts.len() != 3 {
+            return Err(format!("Invalid event cursor: {}", s));
+        }
+        let invalid = |_| format!("Invalid event cursor: {}", s);
+        Ok(ContractEventCursor {
+            block_height: parts[0].parse().map_err(invalid)?,
+            receipt_index: parts[1].parse().map_err(invalid)?,
+            event_index: parts[2].parse().map_err(invalid)?,
+        })
+    }
+}
+
+/// The type of an indexed event.  The names are the event observer's names for these events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractEventType {
+    Print,
+    FTTransfer,
+    FTMint,
+    FTBurn,
+    NFTTransfer,
+    NFTMint,
+    NFTBurn,
+}
+
+impl ContractEventType {
+    pub const ALL: [ContractEventType; 7] = [
+        ContractEventType::Print,
+        ContractEventType::FTTransfer,
+        ContractEventType::FTMint,
+        ContractEventType::FTBurn,
+        ContractEventType::NFTTransfer,
+        ContractEventType::NFTMint,
+        ContractEventType::NFTBurn,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContractEventType::Print => "contract_event",
+            ContractEventType::FTTransfer => "ft_transfer_event",
+            ContractEventType::FTMint => "ft_mint_event",
+            ContractEventType::FTBurn => "ft_burn_event",
+            ContractEventType::NFTTransfer => "nft_transfer_event",
+            ContractEventType::NFTMint => "nft_mint_event",
+            ContractEventType::NFTBurn => "nft_burn_event",
+        }
+    }
+}
+
+impl fmt::Display for ContractEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ContractEventType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ContractEventType, String> {
+        ContractEventType::ALL
+            .iter()
+            .find(|event_type| event_type.as_str() == s)
+            .cloned()
+            .ok_or_else(|| format!("Invalid event type: {}", s))
+    }
+}
+
+/// Which of a contract's events to return from a query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractEventFilter {
+    pub event_type: Option<ContractEventType>,
+    pub topic: Option<String>,
+    /// lowest block height to return events from
+    pub from_block: u64,
+    /// highest block height to return events from, if not the tip's
+    pub to_block: Option<u64>,
+}
+
+/// An event, as stored in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedContractEvent {
+    pub index_block_hash: StacksBlockId,
+    pub txid: Txid,
+    pub cursor: ContractEventCursor,
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub event_type: ContractEventType,
+    pub topic: Option<String>,
+    /// the printed value of a `print` event, the amount (as a `uint`) of a fungible token event,
+    /// or the asset of a non-fungible token event
+    pub value: Value,
+    pub sender: Option<PrincipalData>,
+    pub recipient: Option<PrincipalData>,
+}
+
+impl FromRow<IndexedContractEvent> for IndexedContractEvent {
+    fn from_row<'a>(row: &'a Row) -> Result<IndexedContractEvent, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let txid = Txid::from_column(row, "txid")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let receipt_index: i64 = row.get_unwrap("receipt_index");
+        let event_index: i64 = row.get_unwrap("event_index");
+        let contract_identifier_str: String = row.get_unwrap("contract_identifier");
+        let event_type_str: String = row.get_unwrap("event_type");
+        let topic: Option<String> = row.get_unwrap("topic");
+        let value_hex: String = row.get_unwrap("value");
+        let sender_str: Option<String> = row.get_unwrap("sender");
+        let recipient_str: Option<String> = row.get_unwrap("recipient");
+
+        let contract_identifier = QualifiedContractIdentifier::parse(&contract_identifier_str)
+            .map_err(|_| db_error::ParseError)?;
+        let event_type = event_type_str.parse().map_err(|_| db_error::ParseError)?;
+        let value = Value::try_deserialize_hex_untyped(&value_hex)
+            .map_err(|_: SerializationError| db_error::ParseError)?;
+        let parse_principal = |principal_str: Option<String>| match principal_str {
+            Some(principal_str) => PrincipalData::parse(&principal_str)
+                .map(Some)
+                .map_err(|_| db_error::ParseError),
+            None => Ok(None),
+        };
+        let sender = parse_principal(sender_str)?;
+        let recipient = parse_principal(recipient_str)?;
+
+        Ok(IndexedContractEvent {
+            index_block_hash,
+            txid,
+            cursor: ContractEventCursor {
+                block_height,
+                receipt_index: u32::try_from(receipt_index).map_err(|_| db_error::ParseError)?,
+                event_index: u32::try_from(event_index).map_err(|_| db_error::ParseError)?,
+            },
+            contract_identifier,
+            event_type,
+            topic,
+            value,
+            sender,
+            recipient,
+        })
+    }
+}
+
+fn clarity_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Sequence(SequenceData::String(CharType::ASCII(data))) => {
+            String::from_utf8(data.data.clone()).ok()
+        }
+        Value::Sequence(SequenceData::String(CharType::UTF8(UTF8Data { data }))) => {
+            String::from_utf8(data.concat()).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Derive the topic of a printed value (see the module documentation)
+pub fn derive_event_topic(value: &Value) -> Option<String> {
+    match value {
+        Value::Tuple(tuple) => {
+            for field in ["topic", "event"].iter() {
+                let field_name =
+                    ClarityName::try_from(field.to_string()).expect("BUG: invalid Clarity name");
+                if let Some(topic) = tuple.data_map.get(&field_name).and_then(clarity_string) {
+                    return Some(topic);
+                }
+            }
+            tuple
+                .data_map
+                .keys()
+                .next()
+                .map(|name| name.as_str().to_string())
+        }
+        _ => clarity_string(value),
+    }
+}
+
+/// The fields of an event, as indexed: its contract, type, topic, value, sender and recipient.
+/// Returns None for events that don't belong to a contract.
+type ContractEventFields<'a> = (
+    &'a QualifiedContractIdentifier,
+    ContractEventType,
+    Option<String>,
+    Value,
+    Option<&'a PrincipalData>,
+    Option<&'a PrincipalData>,
+);
+
+fn contract_event_fields(event: &StacksTransactionEvent) -> Option<ContractEventFields<'_>> {
+    let fields = match event {
+        StacksTransactionEvent::SmartContractEvent(data) => (
+            &data.key.0,
+            ContractEventType::Print,
+            derive_event_topic(&data.value),
+            data.value.clone(),
+            None,
+            None,
+        ),
+        StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(data)) => (
+            &data.asset_identifier.contract_identifier,
+            ContractEventType::FTTransfer,
+            Some(data.asset_identifier.asset_name.to_string()),
+            Value::UInt(data.amount),
+            Some(&data.sender),
+            Some(&data.recipient),
+        ),
+        StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(data)) => (
+            &data.asset_identifier.contract_identifier,
+            ContractEventType::FTMint,
+            Some(data.asset_identifier.asset_name.to_string()),
+            Value::UInt(data.amount),
+            None,
+            Some(&data.recipient),
+        ),
+        StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(data)) => (
+            &data.asset_identifier.contract_identifier,
+            ContractEventType::FTBurn,
+            Some(data.asset_identifier.asset_name.to_string()),
+            Value::UInt(data.amount),
+            Some(&data.sender),
+            None,
+        ),
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(data)) => (
+            &data.asset_identifier.contract_identifier,
+            ContractEventType::NFTTransfer,
+            Some(data.asset_identifier.asset_name.to_string()),
+            data.value.clone(),
+            Some(&data.sender),
+            Some(&data.recipient),
+        ),
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(data)) => (
+            &data.asset_identifier.contract_identifier,
+            ContractEventType::NFTMint,
+            Some(data.asset_identifier.asset_name.to_string()),
+            data.value.clone(),
+            None,
+            Some(&data.recipient),
+        ),
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(data)) => (
+            &data.asset_identifier.contract_identifier,
+            ContractEventType::NFTBurn,
+            Some(data.asset_identifier.asset_name.to_string()),
+            data.value.clone(),
+            Some(&data.sender),
+            None,
+        ),
+        StacksTransactionEvent::STXEvent(_) => return None,
+    };
+    Some(fields)
+}
+
+impl StacksChainState {
+    /// Add the contract events in a processed block's receipts to the contract event index
+    pub fn index_contract_events(
+        tx: &DBTx,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let sql = "INSERT OR REPLACE INTO contract_events
+                   (index_block_hash, block_height, receipt_index, event_index, txid, contract_identifier, topic, value, event_type, sender, recipient)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)";
+        for (receipt_index, receipt) in receipts.iter().enumerate() {
+            let txid = receipt.transaction.txid();
+            for (event_index, event) in receipt.events.iter().enumerate() {
+                let (contract_identifier, event_type, topic, value, sender, recipient) =
+                    match contract_event_fields(event) {
+                        Some(fields) => fields,
+                        None => continue,
+                    };
+                let args: &[&dyn ToSql] = &[
+                    index_block_hash,
+                    &u64_to_sql(block_height)?,
+                    &(receipt_index as i64),
+                    &(event_index as i64),
+                    &txid,
+                    &contract_identifier.to_string(),
+                    &topic,
+                    &value.serialize(),
+                    &event_type.as_str(),
+                    &sender.map(|sender| sender.to_string()),
+                    &recipient.map(|recipient| recipient.to_string()),
+                ];
+                tx.execute(sql, args)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get up to `limit` indexed events from `contract_identifier` that pass `filter` on the fork
+    /// ending at `tip`, continuing after `after`, if given.  Events are returned in chain order.
+    pub fn get_contract_events(
+        &self,
+        tip: &StacksBlockId,
+        contract_identifier: &QualifiedContractIdentifier,
+        filter: &ContractEventFilter,
+        after: Option<&ContractEventCursor>,
+        limit: u32,
+    ) -> Result<Vec<IndexedContractEvent>, Error> {
+        let from_block = filter.from_block;
+        let index_conn = self.index_conn()?;
+        let tip_height = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            tip,
+        )? {
+            Some(header) => header.block_height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+
+        let max_height = match filter.to_block {
+            Some(to_block) => cmp::min(to_block, tip_height),
+            None => tip_height,
+        };
+        let contract_identifier_str = contract_identifier.to_string();
+        let mut start = after.cloned().unwrap_or(ContractEventCursor {
+            block_height: from_block,
+            receipt_index: 0,
+            event_index: 0,
+        });
+        let mut include_start = after.is_none();
+        if start.block_height < from_block {
+            start = ContractEventCursor {
+                block_height: from_block,
+                receipt_index: 0,
+                event_index: 0,
+            };
+            include_start = true;
+        }
+
+        // height => block ID of the ancestor of `tip` at that height
+        let mut canonical: HashMap<u64, Option<StacksBlockId>> = HashMap::new();
+        let mut events = vec![];
+
+        while (events.len() as u32) < limit {
+            // non-canonical events are filtered out below, so ask for more than we need
+            let batch_size = (limit as i64) * 2;
+            let mut sql = format!(
+                "SELECT * FROM contract_events
+                 WHERE contract_identifier = ?1
+                 AND (block_height, receipt_index, event_index) {} (?2, ?3, ?4)
+                 AND block_height <= ?5",
+                if include_start { ">=" } else { ">" },
+            );
+            let mut args: Vec<&dyn ToSql> = vec![];
+            let start_height = u64_to_sql(start.block_height)?;
+            let start_receipt_index = start.receipt_index as i64;
+            let start_event_index = start.event_index as i64;
+            let max_height = u64_to_sql(max_height)?;
+            let event_type = filter.event_type.map(|event_type| event_type.as_str());
+            args.push(&contract_identifier_str);
+            args.push(&start_height);
+            args.push(&start_receipt_index);
+            args.push(&start_event_index);
+            args.push(&max_height);
+            if let Some(ref topic) = filter.topic {
+                args.push(topic);
+                sql.push_str(&format!(" AND topic = ?{}", args.len()));
+            }
+            if let Some(ref event_type) = event_type {
+                args.push(event_type);
+                sql.push_str(&format!(" AND event_type = ?{}", args.len()));
+            }
+            args.push(&batch_size);
+            sql.push_str(&format!(
+                " ORDER BY block_height, receipt_index, event_index LIMIT ?{}",
+                args.len()
+            ));
+
+            let batch: Vec<IndexedContractEvent> = query_rows(self.db(), &sql, args.as_slice())?;
+            let batch_len = batch.len() as i64;
+            for event in batch.into_iter() {
+                start = event.cursor;
+                include_start = false;
+
+                let height = event.cursor.block_height;
+                let ancestor = match canonical.entry(height) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(
+                        index_conn
+                            .get_ancestor_block_hash(height, tip)
+                            .map_err(Error::DBError)?,
+                    ),
+                };
+                if *ancestor != Some(event.index_block_hash) {
+                    continue;
+                }
+
+                events.push(event);
+                if (events.len() as u32) >= limit {
+                    break;
+                }
+            }
+            if batch_len < batch_size {
+                break;
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Get the indexed events of every contract from one block's receipts, in receipt order
+    pub fn get_block_contract_events(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Vec<IndexedContractEvent>, Error> {
+        let sql = "SELECT * FROM contract_events WHERE index_block_hash = ?1
+                   ORDER BY receipt_index, event_index";
+        let args: &[&dyn ToSql] = &[index_block_hash];
+        query_rows(self.db(), sql, args).map_err(Error::DBError)
+    }
+}
+
+impl IndexedContractEvent {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "event_index": self.cursor.event_index,
+            "contract_identifier": self.contract_identifier.to_string(),
+            "event_type": self.event_type.to_string(),
+            "topic": self.topic,
+            "value": format!("0x{}", self.value.serialize()),
+            "sender": self.sender.as_ref().map(|sender| sender.to_string()),
+            "recipient": self.recipient.as_ref().map(|recipient| recipient.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainstate::burn::ConsensusHash;
+    use chainstate::stacks::db::test::*;
+    use chainstate::stacks::events::{
+        FTMintEventData, FTTransferEventData, STXEventType, STXMintEventData,
+        SmartContractEventData,
+    };
+    use chainstate::stacks::*;
+    use util::hash::{Hash160, Sha512Trunc256Sum};
+    use vm::costs::ExecutionCost;
+    use vm::types::{AssetIdentifier, TupleData};
+
+    use crate::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+
+    use super::*;
+
+    fn make_child_tip(
+        chainstate: &mut StacksChainState,
+        parent: &StacksHeaderInfo,
+        fork: u8,
+    ) -> StacksHeaderInfo {
+        let mut new_tip = parent.clone();
+        new_tip.anchored_header.parent_block = parent.anchored_header.block_hash();
+        new_tip.anchored_header.total_work.work = parent.anchored_header.total_work.work + 1;
+        new_tip.block_height = parent.block_height + 1;
+        new_tip.consensus_hash = ConsensusHash(
+            Hash160::from_data(
+                &Sha512Trunc256Sum::from_data(&[&parent.consensus_hash.0[..], &[fork]].concat()).0,
+            )
+            .0,
+        );
+        new_tip.burn_header_hash =
+            BurnchainHeaderHash(Sha512Trunc256Sum::from_data(&new_tip.consensus_hash.0).0);
+        new_tip.burn_header_height = parent.burn_header_height + 1;
+
+        let mut block_reward = MinerPaymentSchedule::genesis(false);
+        block_reward.parent_consensus_hash = parent.consensus_hash;
+        block_reward.parent_block_hash = parent.anchored_header.block_hash();
+        block_reward.block_hash = new_tip.anchored_header.block_hash();
+        block_reward.consensus_hash = new_tip.consensus_hash;
+
+        let mut tx = chainstate.index_tx_begin().unwrap();
+        let tip = StacksChainState::advance_tip(
+            &mut tx,
+            &parent.anchored_header,
+            &parent.consensus_hash,
+            &new_tip.anchored_header,
+            &new_tip.consensus_hash,
+            &new_tip.burn_header_hash,
+            new_tip.burn_header_height,
+            new_tip.burn_header_timestamp,
+            None,
+            &block_reward,
+            &vec![],
+            &ExecutionCost::zero(),
+            123,
+            false,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    fn make_print_receipt(
+        contract_id: &QualifiedContractIdentifier,
+        values: Vec<Value>,
+    ) -> StacksTransactionReceipt {
+        let privk = StacksPrivateKey::new();
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk).unwrap(),
+            TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+        );
+        let events = values
+            .into_iter()
+            .map(|value| {
+                StacksTransactionEvent::SmartContractEvent(SmartContractEventData {
+                    key: (contract_id.clone(), "print".to_string()),
+                    value,
+                })
+            })
+            .collect();
+        StacksTransactionReceipt::from_contract_call(
+            tx,
+            events,
+            Value::okay_true(),
+            0,
+            ExecutionCost::zero(),
+        )
+    }
+
+    fn make_event_tuple(event: &str, amount: u128) -> Value {
+        Value::Tuple(
+            TupleData::from_data(vec![
+                ("amount".into(), Value::UInt(amount)),
+                (
+                    "event".into(),
+                    Value::string_ascii_from_bytes(event.as_bytes().to_vec()).unwrap(),
+                ),
+            ])
+            .unwrap(),
+        )
+    }
+
+    fn print_filter(topic: Option<&str>, from_block: u64) -> ContractEventFilter {
+        ContractEventFilter {
+            topic: topic.map(|topic| topic.to_string()),
+            from_block,
+            ..ContractEventFilter::default()
+        }
+    }
+
+    fn index_block(
+        chainstate: &mut StacksChainState,
+        tip: &StacksHeaderInfo,
+        receipts: &[StacksTransactionReceipt],
+    ) {
+        let tx = chainstate.index_tx_begin().unwrap();
+        StacksChainState::index_contract_events(
+            tx.tx(),
+            &tip.index_block_hash(),
+            tip.block_height,
+            receipts,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_derive_event_topic() {
+        assert_eq!(
+            derive_event_topic(&make_event_tuple("transfer", 1)),
+            Some("transfer".to_string())
+        );
+
+        // no topic or event field, so the first field name is used
+        let value = Value::Tuple(
+            TupleData::from_data(vec![
+                ("sender".into(), Value::UInt(1)),
+                ("amount".into(), Value::UInt(2)),
+            ])
+            .unwrap(),
+        );
+        assert_eq!(derive_event_topic(&value), Some("amount".to_string()));
+
+        // non-string topic fields are ignored
+        let value = Value::Tuple(
+            TupleData::from_data(vec![
+                ("topic".into(), Value::UInt(1)),
+                ("amount".into(), Value::UInt(2)),
+            ])
+            .unwrap(),
+        );
+        assert_eq!(derive_event_topic(&value), Some("amount".to_string()));
+
+        assert_eq!(
+            derive_event_topic(&Value::string_utf8_from_bytes("mint".as_bytes().to_vec()).unwrap()),
+            Some("mint".to_string())
+        );
+        assert_eq!(derive_event_topic(&Value::UInt(1)), None);
+    }
+
+    #[test]
+    fn test_contract_event_type_parse() {
+        for event_type in ContractEventType::ALL.iter() {
+            assert_eq!(
+                event_type.as_str().parse::<ContractEventType>().unwrap(),
+                *event_type
+            );
+        }
+        assert!("stx_transfer_event".parse::<ContractEventType>().is_err());
+    }
+
+    #[test]
+    fn test_contract_event_cursor_parse() {
+        let cursor = ContractEventCursor {
+            block_height: 12,
+            receipt_index: 3,
+            event_index: 4,
+        };
+        assert_eq!(cursor.to_string(), "12-3-4");
+        assert_eq!("12-3-4".parse::<ContractEventCursor>().unwrap(), cursor);
+        assert!("12-3".parse::<ContractEventCursor>().is_err());
+        assert!("12-3-x".parse::<ContractEventCursor>().is_err());
+    }
+
+    #[test]
+    fn test_get_contract_events() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "test_get_contract_events");
+        let contract_id = QualifiedContractIdentifier::new(
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into(),
+            "token".into(),
+        );
+        let other_contract_id = QualifiedContractIdentifier::new(
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into(),
+            "other".into(),
+        );
+
+        // genesis <- block_1 <- block_2, and genesis <- fork_1
+        let genesis = StacksHeaderInfo::regtest_genesis();
+        let block_1 = make_child_tip(&mut chainstate, &genesis, 0);
+        let block_2 = make_child_tip(&mut chainstate, &block_1, 0);
+        let fork_1 = make_child_tip(&mut chainstate, &genesis, 1);
+
+        index_block(
+            &mut chainstate,
+            &block_1,
+            &[
+                make_print_receipt(
+                    &contract_id,
+                    vec![make_event_tuple("mint", 1), make_event_tuple("transfer", 2)],
+                ),
+                make_print_receipt(&other_contract_id, vec![make_event_tuple("mint", 3)]),
+            ],
+        );
+        index_block(
+            &mut chainstate,
+            &block_2,
+            &[
+                make_print_receipt(&contract_id, vec![make_event_tuple("transfer", 4)]),
+                make_print_receipt(&contract_id, vec![make_event_tuple("transfer", 5)]),
+            ],
+        );
+        index_block(
+            &mut chainstate,
+            &fork_1,
+            &[make_print_receipt(
+                &contract_id,
+                vec![make_event_tuple("transfer", 6)],
+            )],
+        );
+
+        let amounts = |events: &[IndexedContractEvent]| -> Vec<u128> {
+            events
+                .iter()
+                .map(|event| {
+                    event
+                        .value
+                        .clone()
+                        .expect_tuple()
+                        .get("amount")
+                        .unwrap()
+                        .clone()
+                        .expect_u128()
+                })
+                .collect()
+        };
+
+        let tip = block_2.index_block_hash();
+        let events = chainstate
+            .get_contract_events(&tip, &contract_id, &print_filter(None, 0), None, 100)
+            .unwrap();
+        assert_eq!(amounts(&events), vec![1, 2, 4, 5]);
+
+        let events = chainstate
+            .get_contract_events(
+                &tip,
+                &contract_id,
+                &print_filter(Some("transfer"), 0),
+                None,
+                100,
+            )
+            .unwrap();
+        assert_eq!(amounts(&events), vec![2, 4, 5]);
+
+        let events = chainstate
+            .get_contract_events(
+                &tip,
+                &contract_id,
+                &print_filter(Some("transfer"), 2),
+                None,
+                100,
+            )
+            .unwrap();
+        assert_eq!(amounts(&events), vec![4, 5]);
+
+        // page through the events one at a time
+        let mut cursor = None;
+        let mut paged = vec![];
+        loop {
+            let page = chainstate
+                .get_contract_events(
+                    &tip,
+                    &contract_id,
+                    &print_filter(None, 0),
+                    cursor.as_ref(),
+                    1,
+                )
+                .unwrap();
+            if page.is_empty() {
+                break;
+            }
+            assert_eq!(page.len(), 1);
+            cursor = Some(page[0].cursor);
+            paged.extend(page);
+        }
+        assert_eq!(amounts(&paged), vec![1, 2, 4, 5]);
+
+        // only the fork's own events are visible from its tip
+        let events = chainstate
+            .get_contract_events(
+                &fork_1.index_block_hash(),
+                &contract_id,
+                &print_filter(None, 0),
+                None,
+                100,
+            )
+            .unwrap();
+        assert_eq!(amounts(&events), vec![6]);
+        assert_eq!(events[0].topic, Some("transfer".to_string()));
+
+        // the fork's events are skipped when filling a page on the canonical fork
+        let events = chainstate
+            .get_contract_events(
+                &tip,
+                &contract_id,
+                &print_filter(Some("transfer"), 0),
+                None,
+                2,
+            )
+            .unwrap();
+        assert_eq!(amounts(&events), vec![2, 4]);
+
+        match chainstate.get_contract_events(
+            &StacksBlockId([0x11; 32]),
+            &contract_id,
+            &ContractEventFilter::default(),
+            None,
+            100,
+        ) {
+            Err(Error::NoSuchBlockError) => {}
+            res => panic!("Expected NoSuchBlockError, got {:?}", res),
+        }
+    }
+    #[test]
+    fn test_get_contract_events_by_type() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "test_get_contract_events_by_type");
+        let contract_id = QualifiedContractIdentifier::new(
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into(),
+            "token".into(),
+        );
+        let alice: PrincipalData =
+            StacksAddress::from_string("ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM")
+                .unwrap()
+                .into();
+        let bob: PrincipalData =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into();
+        let asset_identifier = AssetIdentifier {
+            contract_identifier: contract_id.clone(),
+            asset_name: "coin".into(),
+        };
+
+        let genesis = StacksHeaderInfo::regtest_genesis();
+        let block_1 = make_child_tip(&mut chainstate, &genesis, 0);
+        let block_2 = make_child_tip(&mut chainstate, &block_1, 0);
+
+        let mut receipt = make_print_receipt(&contract_id, vec![make_event_tuple("mint", 10)]);
+        receipt
+            .events
+            .push(StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(
+                FTMintEventData {
+                    asset_identifier: asset_identifier.clone(),
+                    recipient: alice.clone(),
+                    amount: 10,
+                },
+            )));
+        receipt.events.push(StacksTransactionEvent::STXEvent(
+            STXEventType::STXMintEvent(STXMintEventData {
+                recipient: alice.clone(),
+                amount: 1,
+            }),
+        ));
+        index_block(&mut chainstate, &block_1, &[receipt]);
+
+        let mut receipt = make_print_receipt(&contract_id, vec![]);
+        receipt.events.push(StacksTransactionEvent::FTEvent(
+            FTEventType::FTTransferEvent(FTTransferEventData {
+                asset_identifier: asset_identifier.clone(),
+                sender: alice.clone(),
+                recipient: bob.clone(),
+                amount: 4,
+            }),
+        ));
+        index_block(&mut chainstate, &block_2, &[receipt]);
+
+        let tip = block_2.index_block_hash();
+
+        // the STX event isn't indexed
+        let events = chainstate
+            .get_contract_events(
+                &tip,
+                &contract_id,
+                &ContractEventFilter::default(),
+                None,
+                100,
+            )
+            .unwrap();
+        let event_types: Vec<_> = events.iter().map(|event| event.event_type).collect();
+        assert_eq!(
+            event_types,
+            vec![
+                ContractEventType::Print,
+                ContractEventType::FTMint,
+                ContractEventType::FTTransfer
+            ]
+        );
+
+        let filter = ContractEventFilter {
+            event_type: Some(ContractEventType::FTTransfer),
+            ..ContractEventFilter::default()
+        };
+        let events = chainstate
+            .get_contract_events(&tip, &contract_id, &filter, None, 100)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].topic, Some("coin".to_string()));
+        assert_eq!(events[0].value, Value::UInt(4));
+        assert_eq!(events[0].sender, Some(alice.clone()));
+        assert_eq!(events[0].recipient, Some(bob.clone()));
+
+        // token events have their asset name as their topic
+        let filter = ContractEventFilter {
+            topic: Some("coin".to_string()),
+            to_block: Some(block_1.block_height),
+            ..ContractEventFilter::default()
+        };
+        let events = chainstate
+            .get_contract_events(&tip, &contract_id, &filter, None, 100)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, ContractEventType::FTMint);
+        assert_eq!(events[0].sender, None);
+        assert_eq!(events[0].recipient, Some(alice));
+    }
+}
@@ -0,0 +1,526 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-contract rollup of the execution costs spent in each processed block, so operators can
+//! see which contracts dominate block capacity.
+//!
+//! Each contract-call or smart-contract transaction's execution cost is attributed to the
+//! contract it calls or deploys.  Costs of the contracts it calls in turn are included in the
+//! top-level contract's totals, since receipts don't break them out.
+//!
+//! Rollups are kept per Stacks block as it is processed, so the table holds rollups from every
+//! fork.  Queries only sum the rollups of blocks on the fork of the given tip.
+
+use std::collections::HashMap;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::{StacksTransactionReceipt, TransactionOrigin};
+use chainstate::stacks::Error;
+use chainstate::stacks::TransactionPayload;
+use util::db::Error as db_error;
+use util::db::{query_rows, u64_to_sql, FromColumn, FromRow};
+use vm::costs::ExecutionCost;
+use vm::types::QualifiedContractIdentifier;
+
+/// The execution costs spent in a contract, over one block or summed over several
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractExecutionMetrics {
+    pub contract_identifier: QualifiedContractIdentifier,
+    /// Number of contract-call transactions to the contract
+    pub calls: u64,
+    /// Number of transactions deploying the contract (0 or 1 on any one fork)
+    pub deploys: u64,
+    pub execution_cost: ExecutionCost,
+}
+
+/// A block's rollup row, as stored in the table
+struct BlockContractMetrics {
+    index_block_hash: StacksBlockId,
+    block_height: u64,
+    metrics: ContractExecutionMetrics,
+}
+
+impl FromRow<BlockContractMetrics> for BlockContractMetrics {
+    fn from_row<'a>(row: &'a Row) -> Result<BlockContractMetrics, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let contract_identifier_str: String = row.get_unwrap("contract_identifier");
+        let contract_identifier = QualifiedContractIdentifier::parse(&contract_identifier_str)
+            .map_err(|_| db_error::ParseError)?;
+
+        Ok(BlockContractMetrics {
+            index_block_hash,
+            block_height,
+            metrics: ContractExecutionMetrics {
+                contract_identifier,
+                calls: u64::from_column(row, "calls")?,
+                deploys: u64::from_column(row, "deploys")?,
+                execution_cost: ExecutionCost {
+                    runtime: u64::from_column(row, "runtime")?,
+                    read_count: u64::from_column(row, "read_count")?,
+                    read_length: u64::from_column(row, "read_length")?,
+                    write_count: u64::from_column(row, "write_count")?,
+                    write_length: u64::from_column(row, "write_length")?,
+                },
+            },
+        })
+    }
+}
+
+impl ContractExecutionMetrics {
+    fn new(contract_identifier: QualifiedContractIdentifier) -> ContractExecutionMetrics {
+        ContractExecutionMetrics {
+            contract_identifier,
+            calls: 0,
+            deploys: 0,
+            execution_cost: ExecutionCost::zero(),
+        }
+    }
+
+    fn add(&mut self, other: &ContractExecutionMetrics) {
+        self.calls = self.calls.saturating_add(other.calls);
+        self.deploys = self.deploys.saturating_add(other.deploys);
+        add_saturating(&mut self.execution_cost, &other.execution_cost);
+    }
+}
+
+fn add_saturating(total: &mut ExecutionCost, cost: &ExecutionCost) {
+    total.runtime = total.runtime.saturating_add(cost.runtime);
+    total.read_count = total.read_count.saturating_add(cost.read_count);
+    total.read_length = total.read_length.saturating_add(cost.read_length);
+    total.write_count = total.write_count.saturating_add(cost.write_count);
+    total.write_length = total.write_length.saturating_add(cost.write_length);
+}
+
+/// The contract a receipt's execution cost is attributed to, and whether the transaction
+/// deployed it (rather than calling it)
+fn receipt_contract(
+    receipt: &StacksTransactionReceipt,
+) -> Option<(QualifiedContractIdentifier, bool)> {
+    let tx = match receipt.transaction {
+        TransactionOrigin::Stacks(ref tx) => tx,
+        TransactionOrigin::Burn(_) => return None,
+    };
+    match tx.payload {
+        TransactionPayload::ContractCall(ref contract_call) => {
+            Some((contract_call.to_clarity_contract_id(), false))
+        }
+        TransactionPayload::SmartContract(ref smart_contract) => Some((
+            QualifiedContractIdentifier::new(
+                tx.origin_address().into(),
+                smart_contract.name.clone(),
+            ),
+            true,
+        )),
+        _ => None,
+    }
+}
+
+/// Sum up a block's receipts per contract
+pub fn rollup_contract_metrics(
+    receipts: &[StacksTransactionReceipt],
+) -> Vec<ContractExecutionMetrics> {
+    let mut rollup: HashMap<QualifiedContractIdentifier, ContractExecutionMetrics> = HashMap::new();
+    for receipt in receipts.iter() {
+        let (contract_identifier, deploy) = match receipt_contract(receipt) {
+            Some(contract) => contract,
+            None => continue,
+        };
+        let metrics = rollup
+            .entry(contract_identifier.clone())
+            .or_insert_with(|| ContractExecutionMetrics::new(contract_identifier));
+        if deploy {
+            metrics.deploys += 1;
+        } else {
+            metrics.calls += 1;
+        }
+        add_saturating(&mut metrics.execution_cost, &receipt.execution_cost);
+    }
+    let mut rollup: Vec<_> = rollup.into_iter().map(|(_, metrics)| metrics).collect();
+    rollup.sort_by(|a, b| {
+        a.contract_identifier
+            .to_string()
+            .cmp(&b.contract_identifier.to_string())
+    });
+    rollup
+}
+
+impl StacksChainState {
+    /// Add the per-contract rollup of a processed block's receipts to the metrics table
+    pub fn record_contract_metrics(
+        tx: &DBTx,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let sql = "INSERT OR REPLACE INTO contract_execution_metrics
+                   (index_block_hash, block_height, contract_identifier, calls, deploys,
+                    runtime, read_count, read_length, write_count, write_length)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
+        for metrics in rollup_contract_metrics(receipts).into_iter() {
+            let cost = &metrics.execution_cost;
+            let args: &[&dyn ToSql] = &[
+                index_block_hash,
+                &u64_to_sql(block_height)?,
+                &metrics.contract_identifier.to_string(),
+                &u64_to_sql(metrics.calls)?,
+                &u64_to_sql(metrics.deploys)?,
+                &u64_to_sql(cost.runtime)?,
+                &u64_to_sql(cost.read_count)?,
+                &u64_to_sql(cost.read_length)?,
+                &u64_to_sql(cost.write_count)?,
+                &u64_to_sql(cost.write_length)?,
+            ];
+            tx.execute(sql, args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Sum the per-contract metrics of the last `num_blocks` blocks on the fork ending at `tip`
+    /// (including `tip` itself), optionally for one contract only.  Contracts are returned in
+    /// decreasing order of runtime cost.
+    pub fn get_contract_metrics(
+        &self,
+        tip: &StacksBlockId,
+        num_blocks: u64,
+        contract_identifier: Option<&QualifiedContractIdentifier>,
+    ) -> Result<Vec<ContractExecutionMetrics>, Error> {
+        let tip_height = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            tip,
+        )? {
+            Some(header) => header.block_height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+        if num_blocks == 0 {
+            return Ok(vec![]);
+        }
+        let start_height = tip_height.saturating_sub(num_blocks - 1);
+
+        let index_conn = self.index_conn()?;
+        let mut canonical = HashMap::new();
+        for height in start_height..=tip_height {
+            if let Some(block_id) = index_conn
+                .get_ancestor_block_hash(height, tip)
+                .map_err(Error::DBError)?
+            {
+                canonical.insert(height, block_id);
+            }
+        }
+
+        let contract_identifier_str = contract_identifier.map(|id| id.to_string());
+        let sql = format!(
+            "SELECT * FROM contract_execution_metrics WHERE block_height >= ?1 AND block_height <= ?2 {}",
+            if contract_identifier_str.is_some() {
+                "AND contract_identifier = ?3"
+            } else {
+                ""
+            }
+        );
+        let start_height_sql = u64_to_sql(start_height)?;
+        let tip_height_sql = u64_to_sql(tip_height)?;
+        let mut args: Vec<&dyn ToSql> = vec![&start_height_sql, &tip_height_sql];
+        if let Some(ref contract_identifier_str) = contract_identifier_str {
+            args.push(contract_identifier_str);
+        }
+        let rows: Vec<BlockContractMetrics> = query_rows(self.db(), &sql, args.as_slice())?;
+
+        let mut totals: HashMap<QualifiedContractIdentifier, ContractExecutionMetrics> =
+            HashMap::new();
+        for row in rows.into_iter() {
+            if canonical.get(&row.block_height) != Some(&row.index_block_hash) {
+                continue;
+            }
+            totals
+                .entry(row.metrics.contract_identifier.clone())
+                .or_insert_with(|| {
+                    ContractExecutionMetrics::new(row.metrics.contract_identifier.clone())
+                })
+                .add(&row.metrics);
+        }
+
+        let mut totals: Vec<_> = totals.into_iter().map(|(_, metrics)| metrics).collect();
+        totals.sort_by(|a, b| {
+            b.execution_cost
+                .runtime
+                .cmp(&a.execution_cost.runtime)
+                .then_with(|| {
+                    a.contract_identifier
+                        .to_string()
+                        .cmp(&b.contract_identifier.to_string())
+                })
+        });
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainstate::burn::ConsensusHash;
+    use chainstate::stacks::db::test::*;
+    use chainstate::stacks::*;
+    use util::hash::{Hash160, Sha512Trunc256Sum};
+    use vm::types::Value;
+
+    use crate::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+
+    use super::*;
+
+    fn make_child_tip(
+        chainstate: &mut StacksChainState,
+        parent: &StacksHeaderInfo,
+        fork: u8,
+    ) -> StacksHeaderInfo {
+        let mut new_tip = parent.clone();
+        new_tip.anchored_header.parent_block = parent.anchored_header.block_hash();
+        new_tip.anchored_header.total_work.work = parent.anchored_header.total_work.work + 1;
+        new_tip.block_height = parent.block_height + 1;
+        new_tip.consensus_hash = ConsensusHash(
+            Hash160::from_data(
+                &Sha512Trunc256Sum::from_data(&[&parent.consensus_hash.0[..], &[fork]].concat()).0,
+            )
+            .0,
+        );
+        new_tip.burn_header_hash =
+            BurnchainHeaderHash(Sha512Trunc256Sum::from_data(&new_tip.consensus_hash.0).0);
+        new_tip.burn_header_height = parent.burn_header_height + 1;
+
+        let mut block_reward = MinerPaymentSchedule::genesis(false);
+        block_reward.parent_consensus_hash = parent.consensus_hash;
+        block_reward.parent_block_hash = parent.anchored_header.block_hash();
+        block_reward.block_hash = new_tip.anchored_header.block_hash();
+        block_reward.consensus_hash = new_tip.consensus_hash;
+
+        let mut tx = chainstate.index_tx_begin().unwrap();
+        let tip = StacksChainState::advance_tip(
+            &mut tx,
+            &parent.anchored_header,
+            &parent.consensus_hash,
+            &new_tip.anchored_header,
+            &new_tip.consensus_hash,
+            &new_tip.burn_header_hash,
+            new_tip.burn_header_height,
+            new_tip.burn_header_timestamp,
+            None,
+            &block_reward,
+            &vec![],
+            &ExecutionCost::zero(),
+            123,
+            false,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    fn make_cost(runtime: u64) -> ExecutionCost {
+        ExecutionCost {
+            runtime,
+            read_count: 1,
+            read_length: 10,
+            write_count: 2,
+            write_length: 20,
+        }
+    }
+
+    fn make_call_receipt(
+        privk: &StacksPrivateKey,
+        contract_id: &QualifiedContractIdentifier,
+        runtime: u64,
+    ) -> StacksTransactionReceipt {
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(privk).unwrap(),
+            TransactionPayload::new_contract_call(
+                StacksAddress::from(contract_id.issuer.clone()),
+                contract_id.name.as_str(),
+                "f",
+                vec![],
+            )
+            .unwrap(),
+        );
+        StacksTransactionReceipt::from_contract_call(
+            tx,
+            vec![],
+            Value::okay_true(),
+            0,
+            make_cost(runtime),
+        )
+    }
+
+    fn make_deploy_receipt(
+        privk: &StacksPrivateKey,
+        name: &str,
+        runtime: u64,
+    ) -> StacksTransactionReceipt {
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(privk).unwrap(),
+            TransactionPayload::new_smart_contract(name, "(define-data-var a int 0)").unwrap(),
+        );
+        StacksTransactionReceipt::from_contract_call(
+            tx,
+            vec![],
+            Value::okay_true(),
+            0,
+            make_cost(runtime),
+        )
+    }
+
+    fn record_block(
+        chainstate: &mut StacksChainState,
+        tip: &StacksHeaderInfo,
+        receipts: &[StacksTransactionReceipt],
+    ) {
+        let tx = chainstate.index_tx_begin().unwrap();
+        StacksChainState::record_contract_metrics(
+            tx.tx(),
+            &tip.index_block_hash(),
+            tip.block_height,
+            receipts,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_rollup_contract_metrics() {
+        let privk = StacksPrivateKey::new();
+        let deployer = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk)],
+        )
+        .unwrap();
+        let token = QualifiedContractIdentifier::new(deployer.into(), "token".into());
+
+        let rollup = rollup_contract_metrics(&[
+            make_deploy_receipt(&privk, "token", 100),
+            make_call_receipt(&privk, &token, 10),
+            make_call_receipt(&privk, &token, 20),
+        ]);
+        assert_eq!(
+            rollup,
+            vec![ContractExecutionMetrics {
+                contract_identifier: token,
+                calls: 2,
+                deploys: 1,
+                execution_cost: ExecutionCost {
+                    runtime: 130,
+                    read_count: 3,
+                    read_length: 30,
+                    write_count: 6,
+                    write_length: 60,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_get_contract_metrics() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "test_get_contract_metrics");
+        let privk = StacksPrivateKey::new();
+        let token = QualifiedContractIdentifier::new(
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into(),
+            "token".into(),
+        );
+        let pool = QualifiedContractIdentifier::new(
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                .unwrap()
+                .into(),
+            "pool".into(),
+        );
+
+        // genesis <- block_1 <- block_2, and genesis <- fork_1
+        let genesis = StacksHeaderInfo::regtest_genesis();
+        let block_1 = make_child_tip(&mut chainstate, &genesis, 0);
+        let block_2 = make_child_tip(&mut chainstate, &block_1, 0);
+        let fork_1 = make_child_tip(&mut chainstate, &genesis, 1);
+
+        record_block(
+            &mut chainstate,
+            &block_1,
+            &[
+                make_call_receipt(&privk, &token, 10),
+                make_call_receipt(&privk, &pool, 100),
+            ],
+        );
+        record_block(
+            &mut chainstate,
+            &block_2,
+            &[
+                make_call_receipt(&privk, &token, 20),
+                make_call_receipt(&privk, &token, 30),
+            ],
+        );
+        record_block(
+            &mut chainstate,
+            &fork_1,
+            &[make_call_receipt(&privk, &token, 1000)],
+        );
+
+        let summary = |metrics: Vec<ContractExecutionMetrics>| -> Vec<(String, u64, u64)> {
+            metrics
+                .into_iter()
+                .map(|m| {
+                    (
+                        m.contract_identifier.name.to_string(),
+                        m.calls,
+                        m.execution_cost.runtime,
+                    )
+                })
+                .collect()
+        };
+
+        let tip = block_2.index_block_hash();
+        assert_eq!(
+            summary(chainstate.get_contract_metrics(&tip, 10, None).unwrap()),
+            vec![("pool".to_string(), 1, 100), ("token".to_string(), 3, 60)]
+        );
+        assert_eq!(
+            summary(chainstate.get_contract_metrics(&tip, 1, None).unwrap()),
+            vec![("token".to_string(), 2, 50)]
+        );
+        assert_eq!(
+            summary(
+                chainstate
+                    .get_contract_metrics(&tip, 10, Some(&token))
+                    .unwrap()
+            ),
+            vec![("token".to_string(), 3, 60)]
+        );
+        assert_eq!(
+            summary(
+                chainstate
+                    .get_contract_metrics(&fork_1.index_block_hash(), 10, None)
+                    .unwrap()
+            ),
+            vec![("token".to_string(), 1, 1000)]
+        );
+
+        match chainstate.get_contract_metrics(&StacksBlockId([0x11; 32]), 10, None) {
+            Err(Error::NoSuchBlockError) => {}
+            res => panic!("Expected NoSuchBlockError, got {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,419 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Simulation of a contract upgrade: replay the calls a deployed contract has received against
+//! a proposed replacement, and report where it behaves differently from the original.
+//!
+//! The original and the replacement are each deployed in a speculative block on top of the
+//! block the original was published after, and the contract's call trace is replayed against
+//! each in order.  Nothing is ever committed to the chainstate.  Only calls made directly by
+//! transactions are in a trace -- calls from other contracts are not -- and the rest of the
+//! chain's state stays as it was when the contract was published, so a call that depended on
+//! anything the trace doesn't replay may not return what it did on-chain.  It will return the
+//! same thing against both codes, though, so any divergence is due to the replacement.
+
+use burnchains::Txid;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::StacksTransactionEvent;
+use chainstate::stacks::Error;
+use chainstate::stacks::{
+    StacksTransaction, TransactionPayload, MINER_BLOCK_CONSENSUS_HASH, MINER_BLOCK_HEADER_HASH,
+};
+use clarity_vm::clarity::Error as clarity_error;
+use core::EMPTY_MICROBLOCK_PARENT_HASH;
+use vm::costs::LimitedCostTracker;
+use vm::database::BurnStateDB;
+use vm::types::{PrincipalData, QualifiedContractIdentifier, Value};
+use vm::ClarityName;
+
+/// A call made to a contract by a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractCallRecord {
+    pub txid: Txid,
+    /// The anchored block that the transaction was mined in, or that confirmed the microblock
+    /// it was mined in
+    pub block_id: StacksBlockId,
+    pub sender: PrincipalData,
+    pub function_name: ClarityName,
+    pub args: Vec<Value>,
+}
+
+/// A deployed contract's source, and the calls it has received up to some chain tip, oldest
+/// first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractCallTrace {
+    pub contract_id: QualifiedContractIdentifier,
+    pub source: String,
+    /// The anchored block built on the state the contract was published on top of
+    pub parent_consensus_hash: ConsensusHash,
+    pub parent_block_hash: BlockHeaderHash,
+    pub calls: Vec<ContractCallRecord>,
+}
+
+/// What a replayed call did
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContractCallOutcome {
+    Returned {
+        result: Value,
+        events: Vec<StacksTransactionEvent>,
+    },
+    Failed(String),
+}
+
+/// A call that the original contract and its replacement handled differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractCallDivergence {
+    pub call_index: usize,
+    pub call: ContractCallRecord,
+    pub original: ContractCallOutcome,
+    pub replacement: ContractCallOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractUpgradeReport {
+    pub contract_id: QualifiedContractIdentifier,
+    pub calls_replayed: usize,
+    pub divergences: Vec<ContractCallDivergence>,
+}
+
+impl StacksChainState {
+    /// Load the transactions of an anchored block, preceded by those of the microblocks it
+    /// confirms.
+    fn load_block_and_confirmed_microblock_txs(
+        &self,
+        header_info: &StacksHeaderInfo,
+    ) -> Result<Vec<StacksTransaction>, Error> {
+        let block_hash = header_info.anchored_header.block_hash();
+        let mut txs = vec![];
+        if header_info.anchored_header.parent_microblock != EMPTY_MICROBLOCK_PARENT_HASH {
+            let parent_id =
+                StacksChainState::get_parent_block_id(self.db(), &header_info.index_block_hash())?
+                    .ok_or(Error::NoSuchBlockError)?;
+            let parent_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                self.db(),
+                &parent_id,
+            )?
+            .ok_or(Error::NoSuchBlockError)?;
+            let microblocks = StacksChainState::load_processed_microblock_stream_fork(
+                self.db(),
+                &parent_info.consensus_hash,
+                &parent_info.anchored_header.block_hash(),
+                &header_info.anchored_header.parent_microblock,
+            )?
+            .ok_or(Error::NoSuchBlockError)?;
+            for microblock in microblocks.into_iter() {
+                txs.extend(microblock.txs);
+            }
+        }
+        let block = StacksChainState::load_block(
+            &self.blocks_path,
+            &header_info.consensus_hash,
+            &block_hash,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+        txs.extend(block.txs);
+        Ok(txs)
+    }
+
+    /// Find the transaction that published a contract on the fork of `tip`, and the contract
+    /// calls that transactions have made to it since, up to and including `tip`.  Returns
+    /// Ok(None) if the contract was not published by a transaction on this fork (boot contracts
+    /// are not).
+    pub fn get_contract_call_trace(
+        &self,
+        tip: &StacksBlockId,
+        contract_id: &QualifiedContractIdentifier,
+    ) -> Result<Option<ContractCallTrace>, Error> {
+        let mut cursor =
+            StacksChainState::get_stacks_block_header_info_by_index_block_hash(self.db(), tip)?
+                .ok_or(Error::NoSuchBlockError)?;
+
+        // walk back from the tip until we find the publishing transaction
+        let mut calls = vec![];
+        loop {
+            if cursor.block_height == 0 {
+                return Ok(None);
+            }
+            let block_id = cursor.index_block_hash();
+            let txs = self.load_block_and_confirmed_microblock_txs(&cursor)?;
+            let parent_id = StacksChainState::get_parent_block_id(self.db(), &block_id)?
+                .ok_or(Error::NoSuchBlockError)?;
+            let parent = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                self.db(),
+                &parent_id,
+            )?
+            .ok_or(Error::NoSuchBlockError)?;
+
+            for tx in txs.into_iter().rev() {
+                match tx.payload {
+                    TransactionPayload::ContractCall(ref call)
+                        if call.to_clarity_contract_id() == *contract_id =>
+                    {
+                        calls.push(ContractCallRecord {
+                            txid: tx.txid(),
+                            block_id,
+                            sender: PrincipalData::from(tx.origin_address()),
+                            function_name: call.function_name.clone(),
+                            args: call.function_args.clone(),
+                        });
+                    }
+                    TransactionPayload::SmartContract(ref smart_contract)
+                        if smart_contract.name == contract_id.name
+                            && PrincipalData::from(tx.origin_address())
+                                == PrincipalData::Standard(contract_id.issuer.clone()) =>
+                    {
+                        calls.reverse();
+                        return Ok(Some(ContractCallTrace {
+                            contract_id: contract_id.clone(),
+                            source: smart_contract.code_body.to_string(),
+                            parent_consensus_hash: parent.consensus_hash,
+                            parent_block_hash: parent.anchored_header.block_hash(),
+                            calls,
+                        }));
+                    }
+                    _ => {}
+                }
+            }
+            cursor = parent;
+        }
+    }
+
+    /// Deploy `source` as the traced contract in a speculative block, and replay the trace's
+    /// calls against it.  The block is rolled back when done.
+    fn replay_contract_call_trace(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        trace: &ContractCallTrace,
+        source: &str,
+    ) -> Result<Vec<ContractCallOutcome>, Error> {
+        let mut clarity_tx = self.block_begin(
+            burn_dbconn,
+            &trace.parent_consensus_hash,
+            &trace.parent_block_hash,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+        // a long trace won't fit in one block's budget
+        clarity_tx
+            .connection()
+            .set_cost_tracker(LimitedCostTracker::new_free());
+
+        let deployed = clarity_tx.connection().as_transaction(|tx| {
+            let (contract_ast, contract_analysis) =
+                tx.analyze_smart_contract(&trace.contract_id, source)?;
+            tx.initialize_smart_contract(&trace.contract_id, &contract_ast, source, |_, _| false)?;
+            tx.save_analysis(&trace.contract_id, &contract_analysis)
+                .map_err(clarity_error::from)
+        });
+        if let Err(e) = deployed {
+            clarity_tx.rollback_block();
+            return Err(Error::ClarityError(e));
+        }
+
+        let mut outcomes = Vec::with_capacity(trace.calls.len());
+        for call in trace.calls.iter() {
+            let outcome = clarity_tx.connection().as_transaction(|tx| {
+                match tx.run_contract_call(
+                    &call.sender,
+                    &trace.contract_id,
+                    &call.function_name,
+                    &call.args,
+                    |_, _| false,
+                ) {
+                    Ok((result, _, events)) => ContractCallOutcome::Returned { result, events },
+                    Err(e) => ContractCallOutcome::Failed(e.to_string()),
+                }
+            });
+            outcomes.push(outcome);
+        }
+
+        clarity_tx.rollback_block();
+        Ok(outcomes)
+    }
+
+    /// Replay a contract's call trace against both its original source and `new_source`, and
+    /// report each call whose result or events differ.  Fails if either source can't be
+    /// deployed.
+    pub fn simulate_contract_upgrade(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        trace: &ContractCallTrace,
+        new_source: &str,
+    ) -> Result<ContractUpgradeReport, Error> {
+        let original = self.replay_contract_call_trace(burn_dbconn, trace, &trace.source)?;
+        let replacement = self.replay_contract_call_trace(burn_dbconn, trace, new_source)?;
+
+        let divergences = original
+            .into_iter()
+            .zip(replacement)
+            .enumerate()
+            .filter(|(_, (original, replacement))| original != replacement)
+            .map(
+                |(call_index, (original, replacement))| ContractCallDivergence {
+                    call_index,
+                    call: trace.calls[call_index].clone(),
+                    original,
+                    replacement,
+                },
+            )
+            .collect();
+
+        Ok(ContractUpgradeReport {
+            contract_id: trace.contract_id.clone(),
+            calls_replayed: trace.calls.len(),
+            divergences,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use crate::types::proof::ClarityMarfTrieId;
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use chainstate::stacks::StacksPrivateKey;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
+    use vm::database::NULL_BURN_STATE_DB;
+    use vm::types::StandardPrincipalData;
+
+    const COUNTER: &str = "
+        (define-data-var counter int 0)
+        (define-public (increment (by int))
+            (begin
+                (var-set counter (+ (var-get counter) by))
+                (print (var-get counter))
+                (ok (var-get counter))))
+        (define-read-only (get-counter) (var-get counter))";
+
+    fn trace_of(calls: &[(&str, Vec<Value>)]) -> ContractCallTrace {
+        let sender = PrincipalData::from(StandardPrincipalData::transient());
+        ContractCallTrace {
+            contract_id: QualifiedContractIdentifier::local("counter").unwrap(),
+            source: COUNTER.to_string(),
+            parent_consensus_hash: FIRST_BURNCHAIN_CONSENSUS_HASH,
+            parent_block_hash: FIRST_STACKS_BLOCK_HASH,
+            calls: calls
+                .iter()
+                .enumerate()
+                .map(|(i, (function_name, args))| ContractCallRecord {
+                    txid: Txid([i as u8; 32]),
+                    block_id: StacksBlockId::sentinel(),
+                    sender: sender.clone(),
+                    function_name: ClarityName::try_from(function_name.to_string()).unwrap(),
+                    args: args.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_simulate_contract_upgrade() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "simulate_contract_upgrade");
+        let trace = trace_of(&[
+            ("increment", vec![Value::Int(1)]),
+            ("increment", vec![Value::Int(-5)]),
+            ("get-counter", vec![]),
+            ("increment", vec![Value::Int(10)]),
+        ]);
+
+        // the same code never diverges
+        let report = chainstate
+            .simulate_contract_upgrade(&NULL_BURN_STATE_DB, &trace, COUNTER)
+            .unwrap();
+        assert_eq!(report.calls_replayed, 4);
+        assert!(report.divergences.is_empty());
+
+        // a replacement that refuses to decrement diverges from the second call on
+        let replacement = "
+            (define-data-var counter int 0)
+            (define-public (increment (by int))
+                (begin
+                    (asserts! (> by 0) (err 0))
+                    (var-set counter (+ (var-get counter) by))
+                    (print (var-get counter))
+                    (ok (var-get counter))))
+            (define-read-only (get-counter) (var-get counter))";
+        let report = chainstate
+            .simulate_contract_upgrade(&NULL_BURN_STATE_DB, &trace, replacement)
+            .unwrap();
+        assert_eq!(report.calls_replayed, 4);
+        let diverged: Vec<_> = report.divergences.iter().map(|d| d.call_index).collect();
+        assert_eq!(diverged, vec![1, 2, 3]);
+
+        match (
+            &report.divergences[0].original,
+            &report.divergences[0].replacement,
+        ) {
+            (
+                ContractCallOutcome::Returned {
+                    result: original,
+                    events: original_events,
+                },
+                ContractCallOutcome::Returned {
+                    result: replacement,
+                    events: replacement_events,
+                },
+            ) => {
+                assert_eq!(*original, Value::okay(Value::Int(-4)).unwrap());
+                assert_eq!(*replacement, Value::error(Value::Int(0)).unwrap());
+                assert_eq!(original_events.len(), 1);
+                assert!(replacement_events.is_empty());
+            }
+            _ => panic!("Expected both calls to return"),
+        }
+
+        // a replacement without a function fails calls to it
+        let replacement = "
+            (define-data-var counter int 0)
+            (define-public (increment (by int))
+                (begin
+                    (var-set counter (+ (var-get counter) by))
+                    (print (var-get counter))
+                    (ok (var-get counter))))";
+        let report = chainstate
+            .simulate_contract_upgrade(&NULL_BURN_STATE_DB, &trace, replacement)
+            .unwrap();
+        assert_eq!(report.divergences.len(), 1);
+        assert_eq!(report.divergences[0].call_index, 2);
+        assert!(matches!(
+            report.divergences[0].replacement,
+            ContractCallOutcome::Failed(_)
+        ));
+
+        // a replacement that doesn't deploy can't be simulated
+        assert!(chainstate
+            .simulate_contract_upgrade(&NULL_BURN_STATE_DB, &trace, "(define-public (oops)")
+            .is_err());
+
+        // nothing was written to the chainstate
+        let genesis = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        assert_eq!(
+            chainstate
+                .get_contract_call_trace(&genesis, &trace.contract_id)
+                .unwrap(),
+            None
+        );
+        assert!(chainstate
+            .get_contract_call_trace(&StacksBlockId([0x11; 32]), &trace.contract_id)
+            .is_err());
+    }
+}
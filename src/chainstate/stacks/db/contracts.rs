@@ -39,8 +39,9 @@ use net::Error as net_error;
 
 use vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData};
 
-use vm::contexts::{AssetMap, OwnedEnvironment};
+use vm::contexts::{AssetMap, ExecutionLimits, OwnedEnvironment};
 
+use vm::analysis::dependency_graph::DependencyGraph;
 use vm::analysis::run_analysis;
 use vm::ast::build_ast;
 use vm::types::{AssetIdentifier, Value};
@@ -48,10 +49,17 @@ use vm::types::{AssetIdentifier, Value};
 pub use vm::analysis::errors::CheckErrors;
 use vm::errors::Error as clarity_vm_error;
 
-use vm::database::ClarityDatabase;
+use vm::database::{ClarityDatabase, SqliteConnection, StoreType};
 
 use vm::contracts::Contract;
+use vm::costs::{ExecutionCost, LimitedCostTracker};
+use vm::database::BurnStateDB;
+use vm::errors::InterpreterError;
+use vm::representations::{ClarityName, SymbolicExpression};
+use vm::types::{FixedFunction, FunctionType, TypeSignature};
 
+use chainstate::stacks::index::marf::MarfConnection;
+use chainstate::stacks::index::Error as MARFError;
 use clarity_vm::clarity::ClarityConnection;
 
 impl StacksChainState {
@@ -85,4 +93,501 @@ impl StacksChainState {
             })
             .map_err(Error::ClarityError)
     }
+
+    /// Evaluate a contract function as `sender` without committing any writes, under the given
+    /// cost limit (whose write budget is ignored) and execution limits.  Public functions may be
+    /// called too, as long as they do not write; any attempt to write exceeds the cost limit.
+    pub fn eval_read_only_function<T: ClarityConnection>(
+        clarity_tx: &mut T,
+        mainnet: bool,
+        contract_id: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+        sender: &PrincipalData,
+        cost_limit: ExecutionCost,
+        limits: ExecutionLimits,
+    ) -> Result<Value, clarity_vm_error> {
+        let args: Vec<_> = args
+            .iter()
+            .map(|x| SymbolicExpression::atom_value(x.clone()))
+            .collect();
+
+        let mut cost_limit = cost_limit;
+        cost_limit.write_length = 0;
+        cost_limit.write_count = 0;
+
+        let epoch = clarity_tx.get_epoch();
+        let mut cost_track = clarity_tx
+            .with_clarity_db_readonly(|clarity_db| {
+                LimitedCostTracker::new_mid_block(mainnet, cost_limit, clarity_db, epoch)
+            })
+            .map_err(|_| clarity_vm_error::from(InterpreterError::CostContractLoadFailure))?;
+        if let Some(cost_overrides) = clarity_tx.cost_overrides() {
+            cost_track.set_cost_overrides(cost_overrides);
+        }
+
+        clarity_tx.with_readonly_clarity_env(mainnet, sender.clone(), cost_track, |env| {
+            // we want to execute any function as long as no actual writes are made as
+            // opposed to be limited to purely calling `define-read-only` functions,
+            // so use `read_only = false`.  This broadens the number of functions that
+            // can be called, and also circumvents limitations on `define-read-only`
+            // functions that can not use `contrac-call?`, even when calling other
+            // read-only functions
+            env.execute_contract_limited(contract_id, function, &args, false, limits)
+        })
+    }
+
+    /// Evaluate a contract function without committing any writes at the given chain tip.  If
+    /// `tip` is the unconfirmed chain tip, then the function is evaluated against the unconfirmed
+    /// microblock state.
+    /// Returns Ok(None) if the chain tip does not exist.
+    pub fn eval_read_only_function_at_tip(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksBlockId,
+        contract_id: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+        sender: &PrincipalData,
+        cost_limit: ExecutionCost,
+        limits: ExecutionLimits,
+    ) -> Result<Option<Result<Value, clarity_vm_error>>, Error> {
+        let mainnet = self.mainnet;
+        self.maybe_read_only_clarity_tx(burn_dbconn, tip, |clarity_tx| {
+            StacksChainState::eval_read_only_function(
+                clarity_tx,
+                mainnet,
+                contract_id,
+                function,
+                args,
+                sender,
+                cost_limit,
+                limits,
+            )
+        })
+    }
+
+    /// Evaluate a batch of contract functions without committing any writes, all against the
+    /// same snapshot of the chain state at `tip`, so that their results are consistent with one
+    /// another.  Each call is charged against its own `cost_limit` and `limits`, and each call's
+    /// result is returned in order.
+    /// Returns Ok(None) if the chain tip does not exist.
+    pub fn eval_read_only_function_batch_at_tip(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksBlockId,
+        calls: &[ReadOnlyCall],
+        sender: &PrincipalData,
+        cost_limit: ExecutionCost,
+        limits: ExecutionLimits,
+    ) -> Result<Option<Vec<Result<Value, clarity_vm_error>>>, Error> {
+        let mainnet = self.mainnet;
+        self.maybe_read_only_clarity_tx(burn_dbconn, tip, |clarity_tx| {
+            calls
+                .iter()
+                .map(|call| {
+                    StacksChainState::eval_read_only_function(
+                        clarity_tx,
+                        mainnet,
+                        &call.contract_id,
+                        call.function.as_str(),
+                        &call.args,
+                        sender,
+                        cost_limit.clone(),
+                        limits,
+                    )
+                })
+                .collect()
+        })
+    }
+
+    /// Evaluate a contract function without committing any writes against the unconfirmed
+    /// microblock state, so the result reflects transactions mined in microblocks on top of the
+    /// canonical chain tip.
+    /// Returns Ok(None) if there is no readable unconfirmed state.
+    pub fn eval_read_only_function_unconfirmed(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        contract_id: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+        sender: &PrincipalData,
+        cost_limit: ExecutionCost,
+        limits: ExecutionLimits,
+    ) -> Result<Option<Result<Value, clarity_vm_error>>, Error> {
+        let mainnet = self.mainnet;
+        self.with_read_only_unconfirmed_clarity_tx(burn_dbconn, |clarity_tx| {
+            StacksChainState::eval_read_only_function(
+                clarity_tx,
+                mainnet,
+                contract_id,
+                function,
+                args,
+                sender,
+                cost_limit,
+                limits,
+            )
+        })
+    }
+
+    /// Check a proposed argument list against the analyzed signature of a public function at
+    /// `tip`, without evaluating it.  This applies the same checks as mempool admission of a
+    /// contract-call, but reports every mismatched argument instead of just the first.
+    /// Returns Ok(Some(Err(..))) if the contract or function does not exist or the number of
+    /// arguments is wrong, and Ok(None) if the chain tip does not exist.
+    pub fn check_contract_call_args_at_tip(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksBlockId,
+        contract_id: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+    ) -> Result<Option<Result<Vec<ArgumentMismatch>, CheckErrors>>, Error> {
+        self.maybe_read_only_clarity_tx(burn_dbconn, tip, |clarity_tx| {
+            clarity_tx.with_analysis_db_readonly(|db| {
+                let function_type = db
+                    .get_public_function_type(contract_id, function)
+                    .map_err(|e| e.err)?
+                    .ok_or_else(|| {
+                        CheckErrors::NoSuchPublicFunction(
+                            contract_id.to_string(),
+                            function.to_string(),
+                        )
+                    })?;
+                let expected_args = match function_type {
+                    FunctionType::Fixed(FixedFunction { ref args, .. }) => args.clone(),
+                    _ => unreachable!("public functions always have a fixed signature"),
+                };
+                let mismatches = function_type
+                    .check_each_arg_by_allowing_trait_cast(db, args)
+                    .map_err(|e| e.err)?;
+                Ok(mismatches
+                    .into_iter()
+                    .map(|(index, error)| ArgumentMismatch {
+                        index,
+                        name: expected_args[index].name.clone(),
+                        expected: expected_args[index].signature.clone(),
+                        error,
+                    })
+                    .collect())
+            })
+        })
+    }
+
+    /// Get the dependency graph of the contracts deployed as of `tip`: of `contract_id` and the
+    /// contracts it (transitively) depends on if given, and of every contract otherwise.
+    /// Returns Ok(None) if the chain tip does not exist.
+    pub fn get_contract_dependency_graph(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksBlockId,
+        contract_id: Option<&QualifiedContractIdentifier>,
+    ) -> Result<Option<DependencyGraph>, Error> {
+        // contract metadata is kept for every fork, so check each one is deployed as of `tip`
+        let candidates: Vec<QualifiedContractIdentifier> = match contract_id {
+            Some(contract_id) => vec![contract_id.clone()],
+            None => {
+                let key = ClarityDatabase::make_metadata_key(StoreType::Contract, "contract");
+                self.clarity_state
+                    .with_marf(|marf| {
+                        SqliteConnection::get_contracts_with_metadata(marf.sqlite_conn(), &key)
+                    })
+                    .iter()
+                    .filter_map(|contract_id| QualifiedContractIdentifier::parse(contract_id).ok())
+                    .collect()
+            }
+        };
+
+        self.maybe_read_only_clarity_tx(burn_dbconn, tip, |clarity_tx| {
+            clarity_tx.with_clarity_db_readonly(|db| {
+                let roots: Vec<_> = candidates
+                    .into_iter()
+                    .filter(|contract_id| db.has_contract(contract_id))
+                    .collect();
+                DependencyGraph::from_clarity_db(db, &roots)
+            })
+        })
+    }
+
+    /// Get the Clarity state changed by the given block: each key it wrote a new value to, with
+    /// the key's value before and after the block.  Returns Ok(None) if the block has no Clarity
+    /// state (e.g. it was never processed).
+    pub fn get_block_state_diff(
+        &mut self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Option<Vec<StateDiffEntry>>, Error> {
+        self.with_clarity_marf(|marf| {
+            let diff = match marf.get_block_diff(index_block_hash) {
+                Ok(diff) => diff,
+                Err(MARFError::NotFoundError) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let side_store = marf.sqlite_conn();
+            let entries = diff
+                .into_iter()
+                .map(|entry| {
+                    let path = entry.path.to_hex();
+                    StateDiffEntry {
+                        key: SqliteConnection::get_key_preimage(side_store, &path),
+                        value: SqliteConnection::get(side_store, &entry.value.to_hex()),
+                        prior_value: entry
+                            .prior_value
+                            .and_then(|value| SqliteConnection::get(side_store, &value.to_hex())),
+                        path,
+                    }
+                })
+                .collect();
+            Ok(Some(entries))
+        })
+    }
+}
+
+/// A Clarity key written by a block, as found by `StacksChainState::get_block_state_diff()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateDiffEntry {
+    /// the key, if its preimage was recorded when it was written
+    pub key: Option<String>,
+    /// the key's MARF path, as hex
+    pub path: String,
+    /// the value the block wrote, as stored in the side store
+    pub value: Option<String>,
+    /// the key's value in the block's parent, if it had one
+    pub prior_value: Option<String>,
+}
+
+/// A proposed argument which does not fit the signature of the function it is passed to
+#[derive(Debug, PartialEq)]
+pub struct ArgumentMismatch {
+    /// position of the argument in the argument list
+    pub index: usize,
+    /// name of the parameter it was passed as
+    pub name: ClarityName,
+    pub expected: TypeSignature,
+    pub error: CheckErrors,
+}
+
+/// A contract function call to evaluate as part of a read-only batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadOnlyCall {
+    pub contract_id: QualifiedContractIdentifier,
+    pub function: ClarityName,
+    pub args: Vec<Value>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use chainstate::stacks::index::node::TriePath;
+    use util::boot::{boot_code_addr, boot_code_id};
+    use vm::database::clarity_store::make_contract_hash_key;
+    use vm::types::TupleData;
+
+    #[test]
+    fn test_get_contract_dependency_graph() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "test_get_contract_dependency_graph");
+        let genesis_tip = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+
+        let graph = chainstate
+            .get_contract_dependency_graph(&NULL_BURN_STATE_DB, &genesis_tip, None)
+            .unwrap()
+            .unwrap();
+        for name in ["pox", "costs", "bns", "cost-voting", "lockup"].iter() {
+            assert!(graph.contracts.contains_key(&boot_code_id(name, false)));
+        }
+        assert!(graph.missing.is_empty());
+        assert!(graph.find_cycles().is_empty());
+
+        let contract_id = boot_code_id("cost-voting", false);
+        let graph = chainstate
+            .get_contract_dependency_graph(&NULL_BURN_STATE_DB, &genesis_tip, Some(&contract_id))
+            .unwrap()
+            .unwrap();
+        assert!(graph.contracts.contains_key(&contract_id));
+        assert!(!graph.contracts.contains_key(&boot_code_id("pox", false)));
+
+        let unknown_tip = StacksBlockId([0x11; 32]);
+        assert!(chainstate
+            .get_contract_dependency_graph(&NULL_BURN_STATE_DB, &unknown_tip, None)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_get_block_state_diff() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "test_get_block_state_diff");
+        let genesis_tip = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+
+        // the genesis block writes the boot contracts
+        let diff = chainstate
+            .get_block_state_diff(&genesis_tip)
+            .unwrap()
+            .unwrap();
+        assert!(!diff.is_empty());
+        for entry in diff.iter() {
+            let key = entry.key.as_ref().unwrap();
+            assert_eq!(entry.path, TriePath::from_key(key).to_hex());
+            assert!(entry.value.is_some());
+        }
+        let pox_key = make_contract_hash_key(&boot_code_id("pox", false));
+        assert!(diff
+            .iter()
+            .any(|entry| entry.key.as_ref() == Some(&pox_key)));
+
+        let unknown_tip = StacksBlockId([0x11; 32]);
+        assert!(chainstate
+            .get_block_state_diff(&unknown_tip)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_eval_read_only_function_batch_at_tip() {
+        let mut chainstate = instantiate_chainstate(
+            false,
+            0x80000000,
+            "test_eval_read_only_function_batch_at_tip",
+        );
+        let genesis_tip = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let sender = PrincipalData::from(boot_code_addr(false));
+
+        let calls = vec![
+            ReadOnlyCall {
+                contract_id: boot_code_id("pox", false),
+                function: "get-total-ustx-stacked".into(),
+                args: vec![Value::UInt(1)],
+            },
+            ReadOnlyCall {
+                contract_id: boot_code_id("pox", false),
+                function: "no-such-function".into(),
+                args: vec![],
+            },
+            ReadOnlyCall {
+                contract_id: boot_code_id("pox", false),
+                function: "get-reward-set-size".into(),
+                args: vec![Value::UInt(1)],
+            },
+        ];
+        let results = chainstate
+            .eval_read_only_function_batch_at_tip(
+                &NULL_BURN_STATE_DB,
+                &genesis_tip,
+                &calls,
+                &sender,
+                ExecutionCost::max_value(),
+                ExecutionLimits::default(),
+            )
+            .unwrap()
+            .unwrap();
+
+        // a failing call does not affect the others
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], Ok(Value::UInt(0)));
+        assert!(results[1].is_err());
+        assert_eq!(results[2], Ok(Value::UInt(0)));
+
+        let unknown_tip = StacksBlockId([0x11; 32]);
+        assert!(chainstate
+            .eval_read_only_function_batch_at_tip(
+                &NULL_BURN_STATE_DB,
+                &unknown_tip,
+                &calls,
+                &sender,
+                ExecutionCost::max_value(),
+                ExecutionLimits::default(),
+            )
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_check_contract_call_args_at_tip() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "test_check_contract_call_args_at_tip");
+        let genesis_tip = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let pox = boot_code_id("pox", false);
+        let pox_addr = Value::Tuple(
+            TupleData::from_data(vec![
+                ("version".into(), Value::buff_from_byte(0)),
+                ("hashbytes".into(), Value::buff_from(vec![0; 20]).unwrap()),
+            ])
+            .unwrap(),
+        );
+
+        let mut check = |function: &str, args: Vec<Value>| {
+            chainstate
+                .check_contract_call_args_at_tip(
+                    &NULL_BURN_STATE_DB,
+                    &genesis_tip,
+                    &pox,
+                    function,
+                    &args,
+                )
+                .unwrap()
+                .unwrap()
+        };
+
+        let good_args = vec![
+            Value::UInt(1),
+            pox_addr.clone(),
+            Value::UInt(1),
+            Value::UInt(2),
+        ];
+        assert_eq!(check("stack-stx", good_args), Ok(vec![]));
+
+        // every mismatched argument is reported
+        let bad_args = vec![
+            Value::Int(1),
+            pox_addr.clone(),
+            Value::UInt(1),
+            Value::Int(2),
+        ];
+        let mismatches = check("stack-stx", bad_args).unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].index, 0);
+        assert_eq!(mismatches[0].name.as_str(), "amount-ustx");
+        assert_eq!(mismatches[0].expected, TypeSignature::UIntType);
+        assert_eq!(
+            mismatches[0].error,
+            CheckErrors::TypeError(TypeSignature::UIntType, TypeSignature::IntType)
+        );
+        assert_eq!(mismatches[1].index, 3);
+        assert_eq!(mismatches[1].name.as_str(), "lock-period");
+
+        assert_eq!(
+            check("stack-stx", vec![Value::UInt(1)]),
+            Err(CheckErrors::IncorrectArgumentCount(4, 1))
+        );
+
+        // read-only functions can't be called by a transaction
+        assert!(matches!(
+            check("get-pox-info", vec![]),
+            Err(CheckErrors::NoSuchPublicFunction(..))
+        ));
+
+        let unknown_tip = StacksBlockId([0x11; 32]);
+        assert!(chainstate
+            .check_contract_call_args_at_tip(
+                &NULL_BURN_STATE_DB,
+                &unknown_tip,
+                &pox,
+                "stack-stx",
+                &[]
+            )
+            .unwrap()
+            .is_none());
+    }
 }
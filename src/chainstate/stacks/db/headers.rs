@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::collections::HashMap;
 use std::fmt;
 use std::fs;
@@ -275,6 +276,62 @@ impl StacksChainState {
         }
     }
 
+    /// Get the highest block that is an ancestor of (or is) both of the given blocks.
+    /// Returns Ok(None) if either block is not known.
+    pub fn get_common_ancestor(
+        &self,
+        block_a: &StacksBlockId,
+        block_b: &StacksBlockId,
+    ) -> Result<Option<StacksHeaderInfo>, Error> {
+        let header_a = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            block_a,
+        )? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let header_b = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            block_b,
+        )? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        // once the two forks share an ancestor at some height, they share all lower ones, so
+        // binary-search for the highest height at which they agree.  All forks share the boot
+        // block.
+        let index_conn = self.index_conn()?;
+        let mut lo = 0;
+        let mut hi = cmp::min(header_a.block_height, header_b.block_height);
+        let mut ancestor = index_conn
+            .get_ancestor_block_hash(lo, block_a)
+            .map_err(Error::DBError)?;
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            let ancestor_a = index_conn
+                .get_ancestor_block_hash(mid, block_a)
+                .map_err(Error::DBError)?;
+            let ancestor_b = index_conn
+                .get_ancestor_block_hash(mid, block_b)
+                .map_err(Error::DBError)?;
+            if ancestor_a.is_some() && ancestor_a == ancestor_b {
+                lo = mid;
+                ancestor = ancestor_a;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        match ancestor {
+            Some(ancestor) => StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                self.db(),
+                &ancestor,
+            ),
+            None => Ok(None),
+        }
+    }
+
     /// Get a segment of headers from the canonical chain
     pub fn get_ancestors_headers(
         conn: &Connection,
@@ -0,0 +1,258 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checksum manifests of the chainstate.
+//!
+//! A manifest lists the digests of the canonical chainstate at every `interval`-th Stacks block
+//! height.  Two nodes that agree on the chain history produce identical checkpoints for the
+//! heights they have in common, so operators can compare their node's manifest against their
+//! peers' (or against a published one) to detect a node that has silently diverged.
+
+use std::cmp;
+
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::burn::ConsensusHash;
+use chainstate::stacks::db::*;
+use chainstate::stacks::Error;
+use util::hash::Sha512Trunc256Sum;
+
+use crate::types::chainstate::{StacksBlockHeader, StacksBlockId};
+use crate::types::proof::TrieHash;
+
+/// Default number of Stacks blocks between checkpoints
+pub const CHAINSTATE_MANIFEST_INTERVAL: u64 = 1000;
+
+/// Maximum number of checkpoints in a manifest
+pub const MAX_CHAINSTATE_MANIFEST_CHECKPOINTS: u64 = 100;
+
+/// Digests of the chainstate as of one canonical Stacks block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainstateCheckpoint {
+    pub block_height: u64,
+    /// Commits to the whole chain of Stacks block headers up to this block
+    pub index_block_hash: StacksBlockId,
+    /// Root of the chainstate index MARF as of this block
+    pub index_root: TrieHash,
+    /// Root of the Clarity state MARF as of this block
+    pub state_index_root: TrieHash,
+    /// Commits to the whole chain of sortitions up to the one that selected this block
+    pub consensus_hash: ConsensusHash,
+    /// Root of the sortition MARF as of the sortition that selected this block
+    pub sortition_root: TrieHash,
+    /// Digest over all of the above
+    pub digest: Sha512Trunc256Sum,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainstateManifest {
+    pub stacks_tip_height: u64,
+    pub interval: u64,
+    /// Checkpoints in ascending height order
+    pub checkpoints: Vec<ChainstateCheckpoint>,
+}
+
+impl ChainstateCheckpoint {
+    pub fn new(
+        block_height: u64,
+        index_block_hash: StacksBlockId,
+        index_root: TrieHash,
+        state_index_root: TrieHash,
+        consensus_hash: ConsensusHash,
+        sortition_root: TrieHash,
+    ) -> ChainstateCheckpoint {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&block_height.to_be_bytes());
+        bytes.extend_from_slice(index_block_hash.as_bytes());
+        bytes.extend_from_slice(index_root.as_bytes());
+        bytes.extend_from_slice(state_index_root.as_bytes());
+        bytes.extend_from_slice(consensus_hash.as_bytes());
+        bytes.extend_from_slice(sortition_root.as_bytes());
+
+        ChainstateCheckpoint {
+            block_height,
+            index_block_hash,
+            index_root,
+            state_index_root,
+            consensus_hash,
+            sortition_root,
+            digest: Sha512Trunc256Sum::from_data(&bytes),
+        }
+    }
+}
+
+impl StacksChainState {
+    /// Get the checkpoint at `block_height` on the fork ending at `tip`, if there is a block at
+    /// that height.
+    pub fn get_chainstate_checkpoint(
+        &self,
+        sortdb: &SortitionDB,
+        tip: &StacksBlockId,
+        block_height: u64,
+    ) -> Result<Option<ChainstateCheckpoint>, Error> {
+        let index_block_hash = match self
+            .index_conn()?
+            .get_ancestor_block_hash(block_height, tip)
+            .map_err(Error::DBError)?
+        {
+            Some(index_block_hash) => index_block_hash,
+            None => return Ok(None),
+        };
+        let header = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            &index_block_hash,
+        )? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let snapshot =
+            match SortitionDB::get_block_snapshot_consensus(sortdb.conn(), &header.consensus_hash)?
+            {
+                Some(snapshot) => snapshot,
+                None => return Ok(None),
+            };
+
+        Ok(Some(ChainstateCheckpoint::new(
+            block_height,
+            index_block_hash,
+            header.index_root,
+            header.anchored_header.state_index_root,
+            header.consensus_hash,
+            snapshot.index_root,
+        )))
+    }
+
+    /// Get the manifest of the most recent `max_checkpoints` checkpoints on the canonical Stacks
+    /// fork, taken every `interval` blocks.
+    pub fn get_chainstate_manifest(
+        &self,
+        sortdb: &SortitionDB,
+        interval: u64,
+        max_checkpoints: u64,
+    ) -> Result<ChainstateManifest, Error> {
+        assert!(interval > 0);
+
+        let (consensus_hash, block_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())?;
+        let tip = StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash);
+        let stacks_tip_height =
+            match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                self.db(),
+                &tip,
+            )? {
+                Some(header) => header.block_height,
+                None => return Err(Error::NoSuchBlockError),
+            };
+
+        let num_checkpoints = cmp::min(stacks_tip_height / interval, max_checkpoints);
+        let first_checkpoint = stacks_tip_height / interval - num_checkpoints + 1;
+
+        let mut checkpoints = vec![];
+        for i in first_checkpoint..(first_checkpoint + num_checkpoints) {
+            if let Some(checkpoint) = self.get_chainstate_checkpoint(sortdb, &tip, i * interval)? {
+                checkpoints.push(checkpoint);
+            }
+        }
+
+        Ok(ChainstateManifest {
+            stacks_tip_height,
+            interval,
+            checkpoints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_chainstate_checkpoint_digest() {
+        let checkpoint = ChainstateCheckpoint::new(
+            1000,
+            StacksBlockId([0x01; 32]),
+            TrieHash([0x02; 32]),
+            TrieHash([0x03; 32]),
+            ConsensusHash([0x04; 20]),
+            TrieHash([0x05; 32]),
+        );
+        assert_eq!(
+            checkpoint,
+            ChainstateCheckpoint::new(
+                1000,
+                StacksBlockId([0x01; 32]),
+                TrieHash([0x02; 32]),
+                TrieHash([0x03; 32]),
+                ConsensusHash([0x04; 20]),
+                TrieHash([0x05; 32]),
+            )
+        );
+
+        // every field is committed to
+        let others = [
+            ChainstateCheckpoint::new(
+                2000,
+                StacksBlockId([0x01; 32]),
+                TrieHash([0x02; 32]),
+                TrieHash([0x03; 32]),
+                ConsensusHash([0x04; 20]),
+                TrieHash([0x05; 32]),
+            ),
+            ChainstateCheckpoint::new(
+                1000,
+                StacksBlockId([0x11; 32]),
+                TrieHash([0x02; 32]),
+                TrieHash([0x03; 32]),
+                ConsensusHash([0x04; 20]),
+                TrieHash([0x05; 32]),
+            ),
+            ChainstateCheckpoint::new(
+                1000,
+                StacksBlockId([0x01; 32]),
+                TrieHash([0x12; 32]),
+                TrieHash([0x03; 32]),
+                ConsensusHash([0x04; 20]),
+                TrieHash([0x05; 32]),
+            ),
+            ChainstateCheckpoint::new(
+                1000,
+                StacksBlockId([0x01; 32]),
+                TrieHash([0x02; 32]),
+                TrieHash([0x13; 32]),
+                ConsensusHash([0x04; 20]),
+                TrieHash([0x05; 32]),
+            ),
+            ChainstateCheckpoint::new(
+                1000,
+                StacksBlockId([0x01; 32]),
+                TrieHash([0x02; 32]),
+                TrieHash([0x03; 32]),
+                ConsensusHash([0x14; 20]),
+                TrieHash([0x05; 32]),
+            ),
+            ChainstateCheckpoint::new(
+                1000,
+                StacksBlockId([0x01; 32]),
+                TrieHash([0x02; 32]),
+                TrieHash([0x03; 32]),
+                ConsensusHash([0x04; 20]),
+                TrieHash([0x15; 32]),
+            ),
+        ];
+        for other in others.iter() {
+            assert!(other.digest != checkpoint.digest);
+        }
+    }
+}
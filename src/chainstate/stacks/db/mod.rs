@@ -21,6 +21,7 @@ use std::io;
 use std::io::prelude::*;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use rusqlite::types::ToSql;
 use rusqlite::Connection;
@@ -40,6 +41,7 @@ use chainstate::stacks::db::accounts::*;
 use chainstate::stacks::db::blocks::*;
 use chainstate::stacks::db::unconfirmed::UnconfirmedState;
 use chainstate::stacks::events::*;
+use chainstate::stacks::index::blobs::ExternalTrieBlobs;
 use chainstate::stacks::index::marf::{
     MarfConnection, BLOCK_HASH_TO_HEIGHT_MAPPING_KEY, BLOCK_HEIGHT_TO_HASH_MAPPING_KEY, MARF,
 };
@@ -66,10 +68,11 @@ use util::db::{
 };
 use util::hash::to_hex;
 use vm::analysis::analysis_db::AnalysisDatabase;
+use vm::analysis::cache::AnalysisCache;
 use vm::analysis::run_analysis;
 use vm::ast::build_ast;
 use vm::contexts::OwnedEnvironment;
-use vm::costs::{ExecutionCost, LimitedCostTracker};
+use vm::costs::{CostOverrides, ExecutionCost, LimitedCostTracker};
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, STXBalance, SqliteConnection, NULL_BURN_STATE_DB,
 };
@@ -87,17 +90,30 @@ use crate::util::boot::{boot_code_acc, boot_code_addr, boot_code_id, boot_code_t
 use vm::Value;
 
 pub mod accounts;
+pub mod address_assets;
+pub mod address_transactions;
 pub mod blocks;
+pub mod contract_events;
+pub mod contract_metrics;
+pub mod contract_upgrades;
 pub mod contracts;
 pub mod headers;
+pub mod manifest;
+pub mod parallel_analysis;
+pub mod rosetta;
 pub mod transactions;
 pub mod unconfirmed;
 
 lazy_static! {
     pub static ref TRANSACTION_LOG: bool =
         std::env::var("STACKS_TRANSACTION_LOG") == Ok("1".into());
+    /// Analyses of recently-published contract sources, shared by every chainstate
+    static ref ANALYSIS_CACHE: Arc<AnalysisCache> = Arc::new(AnalysisCache::new(ANALYSIS_CACHE_CAPACITY));
 }
 
+/// Number of contract analyses kept in the process-wide analysis cache
+const ANALYSIS_CACHE_CAPACITY: usize = 256;
+
 pub struct StacksChainState {
     pub mainnet: bool,
     pub chain_id: u32,
@@ -108,6 +124,16 @@ pub struct StacksChainState {
     pub clarity_state_index_root: String, // path to dir containing clarity MARF and side-store
     pub root_path: String,
     pub unconfirmed_state: Option<UnconfirmedState>,
+    /// Whether to maintain the address transaction index as blocks are processed
+    pub address_tx_index: bool,
+    /// Whether to maintain the address asset index as blocks are processed
+    pub address_asset_index: bool,
+    /// Whether to maintain the Rosetta operations index as blocks are processed
+    pub rosetta_index: bool,
+    /// Where the Clarity state MARF's offloaded tries live, if it offloads any
+    clarity_state_blobs: Option<Arc<ExternalTrieBlobs>>,
+    /// Constant costs charged in place of the boot cost contracts' cost functions, if any
+    cost_overrides: Option<Arc<CostOverrides>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -183,8 +209,23 @@ impl DBConfig {
     pub fn supports_epoch(&self, epoch_id: StacksEpochId) -> bool {
         match epoch_id {
             StacksEpochId::Epoch10 => false,
-            StacksEpochId::Epoch20 => (self.version == "1" || self.version == "2"),
-            StacksEpochId::Epoch2_05 => self.version == "2",
+            StacksEpochId::Epoch20 => {
+                self.version == "1"
+                    || self.version == "2"
+                    || self.version == "3"
+                    || self.version == "4"
+                    || self.version == "5"
+                    || self.version == "6"
+                    || self.version == "7"
+            }
+            StacksEpochId::Epoch2_05 | StacksEpochId::Epoch21 => {
+                self.version == "2"
+                    || self.version == "3"
+                    || self.version == "4"
+                    || self.version == "5"
+                    || self.version == "6"
+                    || self.version == "7"
+            }
         }
     }
 }
@@ -308,6 +349,10 @@ impl ClarityConnection for ClarityTx<'_> {
     fn get_epoch(&self) -> StacksEpochId {
         self.block.get_epoch()
     }
+
+    fn cost_overrides(&self) -> Option<Arc<CostOverrides>> {
+        self.block.cost_overrides()
+    }
 }
 
 impl<'a> ClarityTx<'a> {
@@ -405,6 +450,12 @@ pub struct ChainstateTx<'a> {
     pub blocks_path: String,
     pub tx: StacksDBTx<'a>,
     pub root_path: String,
+    /// Whether to maintain the address transaction index
+    pub address_tx_index: bool,
+    /// Whether to maintain the address asset index
+    pub address_asset_index: bool,
+    /// Whether to maintain the Rosetta operations index
+    pub rosetta_index: bool,
 }
 
 impl<'a> ChainstateTx<'a> {
@@ -419,6 +470,9 @@ impl<'a> ChainstateTx<'a> {
             blocks_path,
             tx,
             root_path,
+            address_tx_index: false,
+            address_asset_index: false,
+            rosetta_index: false,
         }
     }
 
@@ -487,6 +541,7 @@ pub enum StreamCursor {
     Microblocks(MicroblockStreamData),
     Headers(HeaderStreamData),
     MempoolTxs(TxStreamData),
+    BlockExport(BlockExportStreamData),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -560,7 +615,20 @@ pub struct TxStreamData {
     pub corked: bool,
 }
 
-pub const CHAINSTATE_VERSION: &'static str = "2";
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockExportStreamData {
+    /// index block hashes of the canonical blocks to send, in ascending height order
+    pub index_block_hashes: Vec<StacksBlockId>,
+    /// index into index_block_hashes of the next block to load
+    pub next_block_ptr: usize,
+    /// serialized ExportedBlockData that's being sent (initially, the length prefix)
+    pub block_buf: Vec<u8>,
+    pub block_buf_ptr: usize,
+    /// total number of bytes sent
+    pub total_bytes: u64,
+}
+
+pub const CHAINSTATE_VERSION: &'static str = "8";
 
 const CHAINSTATE_INITIAL_SCHEMA: &'static [&'static str] = &[
     "PRAGMA foreign_keys = ON;",
@@ -713,6 +781,131 @@ const CHAINSTATE_SCHEMA_2: &'static [&'static str] = &[
     "#,
 ];
 
+const CHAINSTATE_SCHEMA_3: &'static [&'static str] = &[
+    // new in schema version 3
+    // index of contract `print` events, queryable by contract and derived topic
+    r#"
+    CREATE TABLE contract_events(
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        receipt_index INTEGER NOT NULL,
+        event_index INTEGER NOT NULL,
+        txid TEXT NOT NULL,
+        contract_identifier TEXT NOT NULL,
+        topic TEXT,
+        value TEXT NOT NULL,     -- hex-encoded consensus serialization of the printed value
+        PRIMARY KEY(index_block_hash,receipt_index,event_index)
+    );"#,
+    "CREATE INDEX contract_events_by_topic ON contract_events(contract_identifier,topic,block_height);",
+    "CREATE INDEX contract_events_by_contract ON contract_events(contract_identifier,block_height);",
+    r#"
+    UPDATE db_config SET version = "3";
+    "#,
+];
+
+const CHAINSTATE_SCHEMA_4: &'static [&'static str] = &[
+    // new in schema version 4
+    // per-block, per-contract rollup of the execution costs of contract calls and deploys
+    r#"
+    CREATE TABLE contract_execution_metrics(
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        contract_identifier TEXT NOT NULL,
+        calls INTEGER NOT NULL,
+        deploys INTEGER NOT NULL,
+        runtime INTEGER NOT NULL,
+        read_count INTEGER NOT NULL,
+        read_length INTEGER NOT NULL,
+        write_count INTEGER NOT NULL,
+        write_length INTEGER NOT NULL,
+        PRIMARY KEY(index_block_hash,contract_identifier)
+    );"#,
+    "CREATE INDEX contract_execution_metrics_by_height ON contract_execution_metrics(block_height,contract_identifier);",
+    r#"
+    UPDATE db_config SET version = "4";
+    "#,
+];
+
+const CHAINSTATE_SCHEMA_5: &'static [&'static str] = &[
+    // new in schema version 5
+    // optional index of the transactions involving each principal
+    r#"
+    CREATE TABLE address_transactions(
+        principal TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        receipt_index INTEGER NOT NULL,
+        txid TEXT NOT NULL,
+        microblock_hash TEXT,
+        microblock_sequence INTEGER,
+        event_indexes TEXT NOT NULL,     -- JSON list of the events that involve the principal
+        PRIMARY KEY(principal,index_block_hash,receipt_index)
+    );"#,
+    "CREATE INDEX address_transactions_by_height ON address_transactions(principal,block_height,receipt_index);",
+    r#"
+    UPDATE db_config SET version = "5";
+    "#,
+];
+
+const CHAINSTATE_SCHEMA_6: &'static [&'static str] = &[
+    // new in schema version 6
+    // optional index of the token events that credit or debit each principal
+    r#"
+    CREATE TABLE address_asset_events(
+        principal TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        receipt_index INTEGER NOT NULL,
+        event_index INTEGER NOT NULL,
+        asset_identifier TEXT NOT NULL,
+        amount TEXT,             -- decimal amount of a fungible token
+        nft_value TEXT,          -- hex-encoded consensus serialization of a non-fungible token
+        received INTEGER NOT NULL,
+        PRIMARY KEY(principal,index_block_hash,receipt_index,event_index)
+    );"#,
+    "CREATE INDEX address_asset_events_by_height ON address_asset_events(principal,block_height);",
+    r#"
+    UPDATE db_config SET version = "6";
+    "#,
+];
+
+const CHAINSTATE_SCHEMA_7: &'static [&'static str] = &[
+    // new in schema version 7
+    // the contract event index also holds the token events of the assets a contract defines
+    r#"
+    ALTER TABLE contract_events ADD COLUMN event_type TEXT NOT NULL DEFAULT 'contract_event';
+    "#,
+    r#"
+    ALTER TABLE contract_events ADD COLUMN sender TEXT;
+    "#,
+    r#"
+    ALTER TABLE contract_events ADD COLUMN recipient TEXT;
+    "#,
+    "CREATE INDEX contract_events_by_type ON contract_events(contract_identifier,event_type,block_height);",
+    r#"
+    UPDATE db_config SET version = "7";
+    "#,
+];
+
+const CHAINSTATE_SCHEMA_8: &'static [&'static str] = &[
+    // new in schema version 8
+    // optional index of the STX balance changes made by each transaction, for the Rosetta API
+    r#"
+    CREATE TABLE rosetta_transactions(
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        receipt_index INTEGER NOT NULL,
+        txid TEXT NOT NULL,
+        status TEXT NOT NULL,
+        operations TEXT NOT NULL,    -- JSON list of operations
+        PRIMARY KEY(index_block_hash,receipt_index)
+    );"#,
+    "CREATE INDEX rosetta_transactions_by_txid ON rosetta_transactions(index_block_hash,txid);",
+    r#"
+    UPDATE db_config SET version = "8";
+    "#,
+];
+
 const CHAINSTATE_INDEXES: &'static [&'static str] = &[
     "CREATE INDEX IF NOT EXISTS index_block_hash_to_primary_key ON block_headers(index_block_hash,consensus_hash,block_hash);",
     "CREATE INDEX IF NOT EXISTS block_headers_hash_index ON block_headers(block_hash,block_height);",
@@ -806,6 +999,9 @@ pub struct ChainStateBootData {
         Option<Box<dyn FnOnce() -> Box<dyn Iterator<Item = ChainstateBNSNamespace>>>>,
     pub get_bulk_initial_names:
         Option<Box<dyn FnOnce() -> Box<dyn Iterator<Item = ChainstateBNSName>>>>,
+    /// (name, code) of boot contracts to install in place of, or in addition to, the stock
+    /// boot contracts (private networks only; see `boot::overrides`)
+    pub boot_contract_overrides: Vec<(String, String)>,
 }
 
 impl ChainStateBootData {
@@ -825,6 +1021,7 @@ impl ChainStateBootData {
             get_bulk_initial_balances: None,
             get_bulk_initial_namespaces: None,
             get_bulk_initial_names: None,
+            boot_contract_overrides: vec![],
         }
     }
 }
@@ -922,6 +1119,48 @@ impl StacksChainState {
                             tx.execute_batch(cmd)?;
                         }
                     }
+                    "2" => {
+                        // migrate to 3
+                        info!("Migrating chainstate schema from version 2 to 3");
+                        for cmd in CHAINSTATE_SCHEMA_3.iter() {
+                            tx.execute_batch(cmd)?;
+                        }
+                    }
+                    "3" => {
+                        // migrate to 4
+                        info!("Migrating chainstate schema from version 3 to 4");
+                        for cmd in CHAINSTATE_SCHEMA_4.iter() {
+                            tx.execute_batch(cmd)?;
+                        }
+                    }
+                    "4" => {
+                        // migrate to 5
+                        info!("Migrating chainstate schema from version 4 to 5");
+                        for cmd in CHAINSTATE_SCHEMA_5.iter() {
+                            tx.execute_batch(cmd)?;
+                        }
+                    }
+                    "5" => {
+                        // migrate to 6
+                        info!("Migrating chainstate schema from version 5 to 6");
+                        for cmd in CHAINSTATE_SCHEMA_6.iter() {
+                            tx.execute_batch(cmd)?;
+                        }
+                    }
+                    "6" => {
+                        // migrate to 7
+                        info!("Migrating chainstate schema from version 6 to 7");
+                        for cmd in CHAINSTATE_SCHEMA_7.iter() {
+                            tx.execute_batch(cmd)?;
+                        }
+                    }
+                    "7" => {
+                        // migrate to 8
+                        info!("Migrating chainstate schema from version 7 to 8");
+                        for cmd in CHAINSTATE_SCHEMA_8.iter() {
+                            tx.execute_batch(cmd)?;
+                        }
+                    }
                     _ => {
                         error!(
                             "Invalid chain state database: expected version = {}, got {}",
@@ -1061,6 +1300,15 @@ impl StacksChainState {
         let mut initial_liquid_ustx = 0u128;
         let mut receipts = vec![];
 
+        if let Err(msg) = boot::overrides::validate_boot_contract_overrides(
+            &boot_data.boot_contract_overrides,
+            mainnet,
+        ) {
+            let msg = format!("Invalid boot contract override: {}", msg);
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg, false));
+        }
+
         {
             let mut clarity_tx = chainstate.genesis_block_begin(
                 &NULL_BURN_STATE_DB,
@@ -1069,11 +1317,10 @@ impl StacksChainState {
                 &FIRST_BURNCHAIN_CONSENSUS_HASH,
                 &FIRST_STACKS_BLOCK_HASH,
             );
-            let boot_code = if mainnet {
-                *boot::STACKS_BOOT_CODE_MAINNET
-            } else {
-                *boot::STACKS_BOOT_CODE_TESTNET
-            };
+            let boot_code = boot::overrides::boot_contracts_with_overrides(
+                &boot_data.boot_contract_overrides,
+                mainnet,
+            );
             for (boot_code_name, boot_code_contract) in boot_code.iter() {
                 debug!(
                     "Instantiate boot code contract '{}' ({} bytes)...",
@@ -1505,7 +1752,11 @@ impl StacksChainState {
     /// Re-open the chainstate -- i.e. to get a new handle to it using an existing chain state's
     /// parameters
     pub fn reopen(&self) -> Result<(StacksChainState, Vec<StacksTransactionReceipt>), Error> {
-        StacksChainState::open(self.mainnet, self.chain_id, &self.root_path)
+        let (mut chainstate, receipts) =
+            StacksChainState::open(self.mainnet, self.chain_id, &self.root_path)?;
+        chainstate.set_clarity_state_blobs(self.clarity_state_blobs.clone());
+        chainstate.set_cost_overrides(self.cost_overrides.clone());
+        Ok((chainstate, receipts))
     }
 
     pub fn open_testnet<F>(
@@ -1606,7 +1857,8 @@ impl StacksChainState {
         )
         .map_err(|e| Error::ClarityError(e.into()))?;
 
-        let clarity_state = ClarityInstance::new(mainnet, vm_state);
+        let mut clarity_state = ClarityInstance::new(mainnet, vm_state);
+        clarity_state.set_analysis_cache(Some(ANALYSIS_CACHE.clone()));
 
         let mut chainstate = StacksChainState {
             mainnet: mainnet,
@@ -1618,6 +1870,11 @@ impl StacksChainState {
             clarity_state_index_root: clarity_state_index_root,
             root_path: path_str.to_string(),
             unconfirmed_state: None,
+            address_tx_index: false,
+            address_asset_index: false,
+            rosetta_index: false,
+            clarity_state_blobs: None,
+            cost_overrides: None,
         };
 
         let mut receipts = vec![];
@@ -1646,6 +1903,54 @@ impl StacksChainState {
         }
     }
 
+    /// Read (and offload) the Clarity state MARF's buried tries through `blobs`.  Unconfirmed
+    /// state opened from this chainstate, and chainstates made from it with `reopen()`, use
+    /// them as well.
+    pub fn set_clarity_state_blobs(&mut self, blobs: Option<Arc<ExternalTrieBlobs>>) {
+        self.clarity_state
+            .with_marf(|marf| marf.set_external_blobs(blobs.clone()));
+        if let Some(ref mut unconfirmed_state) = self.unconfirmed_state {
+            unconfirmed_state
+                .clarity_inst
+                .with_marf(|marf| marf.set_external_blobs(blobs.clone()));
+        }
+        self.clarity_state_blobs = blobs;
+    }
+
+    pub fn clarity_state_blobs(&self) -> Option<&Arc<ExternalTrieBlobs>> {
+        self.clarity_state_blobs.as_ref()
+    }
+
+    /// Charge constant costs in place of the boot cost contracts' cost functions when processing
+    /// and mining blocks and microblocks.  This lets private chains tune their cost model without
+    /// the cost-voting contracts; every node on the chain must use the same overrides, or they
+    /// will disagree on which blocks are valid.  Ignored on mainnet.  Unconfirmed state opened
+    /// from this chainstate, and chainstates made from it with `reopen()`, use them as well.
+    pub fn set_cost_overrides(&mut self, cost_overrides: Option<Arc<CostOverrides>>) {
+        let cost_overrides = if self.mainnet { None } else { cost_overrides };
+        self.clarity_state
+            .set_cost_overrides(cost_overrides.clone());
+        if let Some(ref mut unconfirmed_state) = self.unconfirmed_state {
+            unconfirmed_state
+                .clarity_inst
+                .set_cost_overrides(cost_overrides.clone());
+        }
+        self.cost_overrides = cost_overrides;
+    }
+
+    pub fn cost_overrides(&self) -> Option<&Arc<CostOverrides>> {
+        self.cost_overrides.as_ref()
+    }
+
+    /// Move all but the `keep_local` most recent Clarity state tries out to the Clarity state
+    /// MARF's external blob store (see `set_clarity_state_blobs`).  Returns the number of tries
+    /// moved.
+    pub fn offload_clarity_state_tries(&mut self, keep_local: u32) -> Result<u32, Error> {
+        self.clarity_state
+            .with_marf(|marf| marf.offload_tries(keep_local))
+            .map_err(|e| Error::DBError(db_error::IndexError(e)))
+    }
+
     /// Begin a transaction against the (indexed) stacks chainstate DB.
     /// Does not create a Clarity instance.
     pub fn index_tx_begin<'a>(&'a mut self) -> Result<StacksDBTx<'a>, Error> {
@@ -1672,8 +1977,11 @@ impl StacksChainState {
         let clarity_instance = &mut self.clarity_state;
         let inner_tx = StacksDBTx::new(&mut self.state_index, ());
 
-        let chainstate_tx =
+        let mut chainstate_tx =
             ChainstateTx::new(inner_tx, blocks_path, self.root_path.clone(), config);
+        chainstate_tx.address_tx_index = self.address_tx_index;
+        chainstate_tx.address_asset_index = self.address_asset_index;
+        chainstate_tx.rosetta_index = self.rosetta_index;
 
         Ok((chainstate_tx, clarity_instance))
     }
@@ -2221,6 +2529,7 @@ pub mod test {
             get_bulk_initial_balances: None,
             get_bulk_initial_names: None,
             get_bulk_initial_namespaces: None,
+            boot_contract_overrides: vec![],
         };
 
         StacksChainState::open_and_exec(mainnet, chain_id, &path, Some(&mut boot_data))
@@ -2237,6 +2546,61 @@ pub mod test {
         format!("/tmp/blockstack-test-chainstate-{}", test_name)
     }
 
+    #[test]
+    fn test_instantiate_chainstate_with_boot_contract_overrides() {
+        let path = chainstate_path("instantiate-chainstate-boot-contract-overrides");
+        let _ = fs::remove_dir_all(&path);
+
+        let pox = boot::BOOT_CODE_POX_TESTNET.replacen(
+            "(define-constant MAX_POX_REWARD_CYCLES u12)",
+            "(define-constant MAX_POX_REWARD_CYCLES u24)",
+            1,
+        );
+        let appchain = "(define-read-only (minimum) (contract-call? .pox get-stacking-minimum))";
+        let mut boot_data = ChainStateBootData::new(&Burnchain::regtest(""), vec![], None);
+        boot_data.boot_contract_overrides = vec![
+            ("pox".to_string(), pox.clone()),
+            ("appchain".to_string(), appchain.to_string()),
+        ];
+
+        let (_, receipts) =
+            StacksChainState::open_and_exec(false, 0x80000000, &path, Some(&mut boot_data))
+                .unwrap();
+
+        // the replacement is installed in place of the stock contract, and the additional
+        // contract after all the stock ones
+        let installed: Vec<(String, String)> = receipts
+            .iter()
+            .filter_map(|receipt| match receipt.transaction {
+                TransactionOrigin::Stacks(ref tx) => match tx.payload {
+                    TransactionPayload::SmartContract(ref sc) => {
+                        Some((sc.name.to_string(), sc.code_body.to_string()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(installed.len(), STACKS_BOOT_CODE_TESTNET.len() + 1);
+        assert_eq!(installed[0], ("pox".to_string(), pox));
+        assert_eq!(
+            installed.last().unwrap(),
+            &("appchain".to_string(), appchain.to_string())
+        );
+        assert!(receipts.iter().all(|receipt| receipt.vm_error.is_none()));
+
+        // a replacement which breaks the stock interface is refused
+        let path = chainstate_path("instantiate-chainstate-bad-boot-contract-overrides");
+        let _ = fs::remove_dir_all(&path);
+        let mut boot_data = ChainStateBootData::new(&Burnchain::regtest(""), vec![], None);
+        boot_data.boot_contract_overrides =
+            vec![("costs".to_string(), "(define-read-only (f) u1)".to_string())];
+        assert!(
+            StacksChainState::open_and_exec(false, 0x80000000, &path, Some(&mut boot_data))
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_instantiate_chainstate() {
         let mut chainstate = instantiate_chainstate(false, 0x80000000, "instantiate-chainstate");
@@ -2313,6 +2677,7 @@ pub mod test {
                         }),
                 )
             })),
+            boot_contract_overrides: vec![],
         };
 
         let path = chainstate_path("genesis-consistency-chainstate-test");
@@ -2403,6 +2768,7 @@ pub mod test {
                         }),
                 )
             })),
+            boot_contract_overrides: vec![],
         };
 
         let path = chainstate_path("genesis-consistency-chainstate");
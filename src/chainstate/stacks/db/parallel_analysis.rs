@@ -0,0 +1,580 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Analysis of the contracts a block publishes on several threads, ahead of processing it.
+//!
+//! Processing a block type-checks its contract deploys one after the other, which leaves all
+//! but one core idle.  So before the block's transactions run, its deploys are analyzed in
+//! parallel against the parent block's state, and the analyses go into the analysis cache,
+//! from which the transactions reuse them (charging what the analysis was charged).  Block
+//! processing itself is unchanged, and analyzes anything the cache can't supply, so whether and
+//! how a deploy was analyzed ahead of time never changes what the block does.
+//!
+//! A deploy that calls, or uses a trait of, a contract published earlier in the same block is
+//! analyzed after that contract, with its analysis in view.  Deploys are grouped into levels by
+//! the length of their longest chain of such dependencies, each level is analyzed in parallel,
+//! and outcomes are reported in block order no matter which worker finishes first.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use chainstate::stacks::db::*;
+use chainstate::stacks::index::blobs::ExternalTrieBlobs;
+use chainstate::stacks::{StacksTransaction, TransactionPayload};
+use clarity_vm::database::marf::MarfedKV;
+use core::StacksEpochId;
+use vm::analysis::cache::AnalysisCache;
+use vm::analysis::dependency_graph::ContractDependencies;
+use vm::analysis::ContractAnalysis;
+use vm::ast::build_ast;
+use vm::costs::{CostOverrides, ExecutionCost, LimitedCostTracker};
+use vm::database::{NULL_BURN_STATE_DB, NULL_HEADER_DB};
+use vm::representations::SymbolicExpression;
+use vm::types::{QualifiedContractIdentifier, StandardPrincipalData};
+
+/// Most threads used to analyze one level of a block's deploys
+pub const MAX_ANALYSIS_WORKERS: usize = 8;
+
+/// A contract published by one of a block's transactions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractDeploy {
+    pub tx_index: usize,
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub source: String,
+}
+
+/// What came of analyzing a deploy ahead of time
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreAnalysisOutcome {
+    /// The contract passed analysis, and its analysis is cached
+    Analyzed,
+    /// The contract did not parse, or did not pass analysis against the parent block's state
+    Failed(String),
+    /// The deploy was left to block processing, e.g. because it publishes a contract an
+    /// earlier deploy already does, or depends on a deploy that failed
+    Skipped,
+}
+
+struct AnalysisJob {
+    position: usize,
+    contract_identifier: QualifiedContractIdentifier,
+    source: String,
+    expressions: Vec<SymbolicExpression>,
+    /// analyses of the earlier deploys this one depends on
+    dependencies: Vec<ContractAnalysis>,
+}
+
+type JobResult = (usize, PreAnalysisOutcome, Option<ContractAnalysis>);
+
+/// Analyzes the deploys of a block built on `parent_block_id` into `analysis_cache`
+#[derive(Clone)]
+pub struct ParallelAnalysis {
+    clarity_state_index_root: String,
+    parent_block_id: StacksBlockId,
+    mainnet: bool,
+    epoch: StacksEpochId,
+    block_limit: ExecutionCost,
+    analysis_cache: Arc<AnalysisCache>,
+    external_blobs: Option<Arc<ExternalTrieBlobs>>,
+    cost_overrides: Option<Arc<CostOverrides>>,
+}
+
+impl ContractDeploy {
+    /// The contract deploys among `txs`, in order
+    pub fn from_transactions(txs: &[StacksTransaction]) -> Vec<ContractDeploy> {
+        txs.iter()
+            .enumerate()
+            .filter_map(|(tx_index, tx)| match tx.payload {
+                TransactionPayload::SmartContract(ref smart_contract) => Some(ContractDeploy {
+                    tx_index,
+                    contract_identifier: QualifiedContractIdentifier::new(
+                        StandardPrincipalData::from(tx.origin_address()),
+                        smart_contract.name.clone(),
+                    ),
+                    source: smart_contract.code_body.to_string(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl ParallelAnalysis {
+    pub fn new(
+        clarity_state_index_root: &str,
+        parent_block_id: &StacksBlockId,
+        mainnet: bool,
+        epoch: StacksEpochId,
+        block_limit: &ExecutionCost,
+        analysis_cache: Arc<AnalysisCache>,
+    ) -> ParallelAnalysis {
+        ParallelAnalysis {
+            clarity_state_index_root: clarity_state_index_root.to_string(),
+            parent_block_id: *parent_block_id,
+            mainnet,
+            epoch,
+            block_limit: block_limit.clone(),
+            analysis_cache,
+            external_blobs: None,
+            cost_overrides: None,
+        }
+    }
+
+    /// Read the parent block's state through `external_blobs` if any of its tries were
+    /// offloaded (see `StacksChainState::set_clarity_state_blobs`)
+    pub fn with_external_blobs(
+        mut self,
+        external_blobs: Option<Arc<ExternalTrieBlobs>>,
+    ) -> ParallelAnalysis {
+        self.external_blobs = external_blobs;
+        self
+    }
+
+    /// Price the analyses with the chainstate's cost overrides, if it has any (see
+    /// `StacksChainState::set_cost_overrides`), so they can be reused when processing the block
+    pub fn with_cost_overrides(
+        mut self,
+        cost_overrides: Option<Arc<CostOverrides>>,
+    ) -> ParallelAnalysis {
+        self.cost_overrides = cost_overrides;
+        self
+    }
+
+    /// Analyze `deploys`, caching the analyses of those that pass.  Returns each deploy's
+    /// outcome, in the order of `deploys`.  Only as many deploys as the cache holds are
+    /// analyzed; the rest are skipped, since their analyses would be evicted before use.
+    pub fn run(&self, deploys: &[ContractDeploy]) -> Vec<PreAnalysisOutcome> {
+        let mut outcomes = vec![PreAnalysisOutcome::Skipped; deploys.len()];
+        let deploys = &deploys[..deploys.len().min(self.analysis_cache.capacity())];
+
+        // parse each deploy, and find the level it can be analyzed at
+        let mut positions: HashMap<&QualifiedContractIdentifier, usize> = HashMap::new();
+        let mut levels: Vec<Option<usize>> = Vec::with_capacity(deploys.len());
+        let mut parsed: Vec<Option<(Vec<SymbolicExpression>, Vec<usize>)>> =
+            Vec::with_capacity(deploys.len());
+        for (position, deploy) in deploys.iter().enumerate() {
+            if positions.contains_key(&deploy.contract_identifier) {
+                levels.push(None);
+                parsed.push(None);
+                continue;
+            }
+            positions.insert(&deploy.contract_identifier, position);
+
+            let expressions = match build_ast(&deploy.contract_identifier, &deploy.source, &mut ())
+            {
+                Ok(contract_ast) => contract_ast.expressions,
+                Err(e) => {
+                    outcomes[position] = PreAnalysisOutcome::Failed(e.to_string());
+                    levels.push(None);
+                    parsed.push(None);
+                    continue;
+                }
+            };
+            let dependencies: Vec<usize> =
+                ContractDependencies::from_expressions(&deploy.contract_identifier, &expressions)
+                    .depends_on()
+                    .iter()
+                    .filter_map(|dependency| positions.get(dependency).cloned())
+                    .collect();
+            let level = dependencies
+                .iter()
+                .map(|dependency| levels[*dependency].map(|level| level + 1))
+                .try_fold(0, |max_level, level| {
+                    level.map(|level| level.max(max_level))
+                });
+            levels.push(level);
+            parsed.push(Some((expressions, dependencies)));
+        }
+
+        let max_level = match levels.iter().flatten().max() {
+            Some(max_level) => *max_level,
+            None => return outcomes,
+        };
+        let mut analyses: HashMap<usize, ContractAnalysis> = HashMap::new();
+        for level in 0..=max_level {
+            let mut jobs = vec![];
+            for (position, deploy) in deploys.iter().enumerate() {
+                if levels[position] != Some(level) {
+                    continue;
+                }
+                let (expressions, dependencies) = parsed[position]
+                    .take()
+                    .expect("BUG: deploy scheduled twice");
+                // a dependency that failed will fail this deploy too
+                let dependencies: Option<Vec<ContractAnalysis>> = dependencies
+                    .iter()
+                    .map(|dependency| analyses.get(dependency).cloned())
+                    .collect();
+                if let Some(dependencies) = dependencies {
+                    jobs.push(AnalysisJob {
+                        position,
+                        contract_identifier: deploy.contract_identifier.clone(),
+                        source: deploy.source.clone(),
+                        expressions,
+                        dependencies,
+                    });
+                }
+            }
+
+            for (position, outcome, analysis) in self.run_level(jobs) {
+                outcomes[position] = outcome;
+                if let Some(analysis) = analysis {
+                    analyses.insert(position, analysis);
+                }
+            }
+        }
+        outcomes
+    }
+
+    /// Spread `jobs` over worker threads, and wait for all of them
+    fn run_level(&self, jobs: Vec<AnalysisJob>) -> Vec<JobResult> {
+        if jobs.is_empty() {
+            return vec![];
+        }
+        let num_workers = jobs.len().min(MAX_ANALYSIS_WORKERS);
+        let mut batches: Vec<Vec<AnalysisJob>> = (0..num_workers).map(|_| vec![]).collect();
+        for (i, job) in jobs.into_iter().enumerate() {
+            batches[i % num_workers].push(job);
+        }
+
+        let workers: Vec<_> = batches
+            .into_iter()
+            .map(|batch| {
+                let positions: Vec<usize> = batch.iter().map(|job| job.position).collect();
+                let parallel_analysis = self.clone();
+                let handle = thread::spawn(move || parallel_analysis.analyze_batch(batch));
+                (positions, handle)
+            })
+            .collect();
+
+        let mut results = vec![];
+        for (positions, handle) in workers.into_iter() {
+            match handle.join() {
+                Ok(batch_results) => results.extend(batch_results),
+                Err(_) => {
+                    warn!("Contract analysis worker panicked");
+                    results.extend(
+                        positions
+                            .into_iter()
+                            .map(|position| (position, PreAnalysisOutcome::Skipped, None)),
+                    );
+                }
+            }
+        }
+        results
+    }
+
+    /// Analyze `jobs` one after the other, over a connection of this thread's own to the
+    /// parent block's state
+    fn analyze_batch(&self, jobs: Vec<AnalysisJob>) -> Vec<JobResult> {
+        let skip_all = |jobs: Vec<AnalysisJob>| -> Vec<JobResult> {
+            jobs.into_iter()
+                .map(|job| (job.position, PreAnalysisOutcome::Skipped, None))
+                .collect()
+        };
+
+        let mut marf = match MarfedKV::open_readonly(&self.clarity_state_index_root, None) {
+            Ok(marf) => marf,
+            Err(e) => {
+                warn!(
+                    "Failed to open Clarity state for contract analysis: {:?}",
+                    &e
+                );
+                return skip_all(jobs);
+            }
+        };
+        marf.get_marf()
+            .set_external_blobs(self.external_blobs.clone());
+        let mut store = match marf.begin_read_only_checked(Some(&self.parent_block_id)) {
+            Ok(store) => store,
+            Err(e) => {
+                warn!(
+                    "Failed to open Clarity state at {} for contract analysis: {:?}",
+                    &self.parent_block_id, &e
+                );
+                return skip_all(jobs);
+            }
+        };
+        let mut cost_tracker = {
+            let mut clarity_db = store.as_clarity_db(&NULL_HEADER_DB, &NULL_BURN_STATE_DB);
+            match LimitedCostTracker::new(
+                self.mainnet,
+                self.block_limit.clone(),
+                &mut clarity_db,
+                self.epoch,
+            ) {
+                Ok(cost_tracker) => cost_tracker,
+                Err(e) => {
+                    debug!(
+                        "Failed to load cost functions at {} for contract analysis: {:?}",
+                        &self.parent_block_id, &e
+                    );
+                    return skip_all(jobs);
+                }
+            }
+        };
+        if let Some(ref cost_overrides) = self.cost_overrides {
+            cost_tracker.set_cost_overrides(cost_overrides.clone());
+        }
+
+        let mut analysis_db = store.as_analysis_db();
+        jobs.into_iter()
+            .map(|mut job| {
+                analysis_db.begin();
+                for dependency in job.dependencies.iter() {
+                    analysis_db
+                        .insert_contract(&dependency.contract_identifier, dependency)
+                        .expect("BUG: analyzed a deploy after another of the same contract");
+                }
+                let result = self.analysis_cache.run_analysis(
+                    &job.contract_identifier,
+                    &job.source,
+                    &mut job.expressions,
+                    &mut analysis_db,
+                    cost_tracker.clone(),
+                    self.epoch,
+                );
+                analysis_db.roll_back();
+
+                match result {
+                    Ok(mut analysis) => {
+                        analysis.take_contract_cost_tracker();
+                        (job.position, PreAnalysisOutcome::Analyzed, Some(analysis))
+                    }
+                    Err((e, _)) => (
+                        job.position,
+                        PreAnalysisOutcome::Failed(e.to_string()),
+                        None,
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use clarity_vm::clarity::ClarityConnection;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
+    use vm::costs::cost_functions::ClarityCostFunction;
+    use vm::types::StandardPrincipalData;
+
+    fn deploy(tx_index: usize, name: &str, source: &str) -> ContractDeploy {
+        ContractDeploy {
+            tx_index,
+            contract_identifier: QualifiedContractIdentifier::new(
+                StandardPrincipalData::transient(),
+                name.into(),
+            ),
+            source: source.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parallel_analysis() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "test_parallel_analysis");
+        let analysis_cache = Arc::new(AnalysisCache::new(16));
+        chainstate
+            .clarity_state
+            .set_analysis_cache(Some(analysis_cache.clone()));
+
+        let deploys = vec![
+            deploy(0, "token", "(define-read-only (supply) u100)"),
+            deploy(
+                1,
+                "wallet",
+                "(define-read-only (total) (contract-call? .token supply))",
+            ),
+            deploy(2, "broken", "(define-read-only (oops) (+ 1 u1))"),
+            deploy(
+                3,
+                "uses-broken",
+                "(define-read-only (go) (contract-call? .broken oops))",
+            ),
+            deploy(
+                4,
+                "vault",
+                "(define-read-only (both) (+ (contract-call? .token supply) (contract-call? .wallet total)))",
+            ),
+            deploy(5, "token", "(define-read-only (supply) u200)"),
+            deploy(6, "unparseable", "(define-read-only (oops)"),
+            deploy(7, "counter", "(define-data-var count uint u0)"),
+        ];
+
+        let genesis = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let outcomes = ParallelAnalysis::new(
+            &chainstate.clarity_state_index_root,
+            &genesis,
+            false,
+            StacksEpochId::Epoch20,
+            &ExecutionCost::max_value(),
+            analysis_cache.clone(),
+        )
+        .run(&deploys);
+
+        assert_eq!(outcomes[0], PreAnalysisOutcome::Analyzed);
+        assert_eq!(outcomes[1], PreAnalysisOutcome::Analyzed);
+        assert!(matches!(outcomes[2], PreAnalysisOutcome::Failed(_)));
+        assert_eq!(outcomes[3], PreAnalysisOutcome::Skipped);
+        assert_eq!(outcomes[4], PreAnalysisOutcome::Analyzed);
+        assert_eq!(outcomes[5], PreAnalysisOutcome::Skipped);
+        assert!(matches!(outcomes[6], PreAnalysisOutcome::Failed(_)));
+        assert_eq!(outcomes[7], PreAnalysisOutcome::Analyzed);
+
+        // publishing the analyzed contracts in order reuses every analysis
+        let mut clarity_tx = chainstate.block_begin(
+            &NULL_BURN_STATE_DB,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+        let hits = analysis_cache.hits();
+        for position in [0, 1, 4, 7].iter() {
+            let deploy = &deploys[*position];
+            clarity_tx.connection().as_transaction(|tx| {
+                let (contract_ast, contract_analysis) = tx
+                    .analyze_smart_contract(&deploy.contract_identifier, &deploy.source)
+                    .unwrap();
+                tx.initialize_smart_contract(
+                    &deploy.contract_identifier,
+                    &contract_ast,
+                    &deploy.source,
+                    |_, _| false,
+                )
+                .unwrap();
+                tx.save_analysis(&deploy.contract_identifier, &contract_analysis)
+                    .unwrap();
+            });
+        }
+        assert_eq!(analysis_cache.hits(), hits + 4);
+        clarity_tx.rollback_block();
+    }
+
+    #[test]
+    fn test_parallel_analysis_cost_overrides() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "test_parallel_analysis_cost_overrides");
+        let analysis_cache = Arc::new(AnalysisCache::new(16));
+        chainstate
+            .clarity_state
+            .set_analysis_cache(Some(analysis_cache.clone()));
+        let mut cost_overrides = CostOverrides::new();
+        cost_overrides.insert(
+            ClarityCostFunction::AnalysisTypeCheck,
+            ExecutionCost::zero(),
+        );
+        chainstate.set_cost_overrides(Some(Arc::new(cost_overrides)));
+
+        let deploys = vec![
+            deploy(0, "stock", "(define-read-only (one) u1)"),
+            deploy(1, "overridden", "(define-read-only (two) u2)"),
+        ];
+        let genesis = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let analyze = |deploy: &ContractDeploy, cost_overrides: Option<Arc<CostOverrides>>| {
+            ParallelAnalysis::new(
+                &chainstate.clarity_state_index_root,
+                &genesis,
+                false,
+                StacksEpochId::Epoch20,
+                &ExecutionCost::max_value(),
+                analysis_cache.clone(),
+            )
+            .with_cost_overrides(cost_overrides)
+            .run(&[deploy.clone()])
+        };
+        assert_eq!(
+            analyze(&deploys[0], None),
+            vec![PreAnalysisOutcome::Analyzed]
+        );
+        assert_eq!(
+            analyze(&deploys[1], chainstate.cost_overrides().cloned()),
+            vec![PreAnalysisOutcome::Analyzed]
+        );
+
+        // only the analysis priced with the chainstate's overrides is reused
+        let mut clarity_tx = chainstate.block_begin(
+            &NULL_BURN_STATE_DB,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+        let hits = analysis_cache.hits();
+        for deploy in deploys.iter() {
+            clarity_tx.connection().as_transaction(|tx| {
+                tx.analyze_smart_contract(&deploy.contract_identifier, &deploy.source)
+                    .unwrap();
+            });
+        }
+        assert_eq!(analysis_cache.hits(), hits + 1);
+        clarity_tx.rollback_block();
+    }
+
+    #[test]
+    fn test_parallel_analysis_skips() {
+        let chainstate = instantiate_chainstate(false, 0x80000000, "test_parallel_analysis_skips");
+        let analysis_cache = Arc::new(AnalysisCache::new(1));
+        let deploys = vec![
+            deploy(0, "first", "(define-read-only (one) u1)"),
+            deploy(1, "second", "(define-read-only (two) u2)"),
+        ];
+        let genesis = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+
+        // deploys that the cache can't hold are left alone
+        let outcomes = ParallelAnalysis::new(
+            &chainstate.clarity_state_index_root,
+            &genesis,
+            false,
+            StacksEpochId::Epoch20,
+            &ExecutionCost::max_value(),
+            analysis_cache,
+        )
+        .run(&deploys);
+        assert_eq!(
+            outcomes,
+            vec![PreAnalysisOutcome::Analyzed, PreAnalysisOutcome::Skipped]
+        );
+
+        // a state that doesn't exist skips everything
+        let outcomes = ParallelAnalysis::new(
+            &chainstate.clarity_state_index_root,
+            &StacksBlockId([0x11; 32]),
+            false,
+            StacksEpochId::Epoch20,
+            &ExecutionCost::max_value(),
+            Arc::new(AnalysisCache::new(16)),
+        )
+        .run(&deploys);
+        assert_eq!(
+            outcomes,
+            vec![PreAnalysisOutcome::Skipped, PreAnalysisOutcome::Skipped]
+        );
+    }
+}
@@ -0,0 +1,581 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Optional index of the STX balance changes made by each transaction, which is what the Rosetta
+//! Data API serves.  Block data alone isn't enough: whether a transaction succeeded, and which
+//! STX it moved, is only known once it has been executed.
+//!
+//! Each transaction is recorded as a list of operations:
+//! * a `fee` debiting the account that paid the transaction fee;
+//! * an operation for the transaction's payload (unless it is a token transfer that succeeded,
+//!   since its `token_transfer` event operations describe it);
+//! * one operation per STX event: `token_transfer` (a debit and a related credit), `stx_mint`,
+//!   `stx_burn` and `stx_lock` (which moves no STX out of the account, so it has no amount);
+//! * for a block's coinbase, a `miner_reward` crediting each miner reward that matured in the
+//!   block.
+//!
+//! The index is only maintained while `StacksChainState::rosetta_index` is set.  Rows are keyed
+//! by block, so every fork's transactions are kept.
+
+use std::convert::TryFrom;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use burnchains::Txid;
+use chainstate::stacks::db::accounts::MinerReward;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::{
+    STXEventType, StacksTransactionEvent, StacksTransactionReceipt, TransactionOrigin,
+};
+use chainstate::stacks::Error;
+use chainstate::stacks::TransactionPayload;
+use util::db::Error as db_error;
+use util::db::{query_row, query_rows, u64_to_sql, FromColumn, FromRow};
+use util::hash::to_hex;
+use vm::types::{PrincipalData, QualifiedContractIdentifier, Value};
+
+pub const ROSETTA_STATUS_SUCCESS: &'static str = "success";
+pub const ROSETTA_STATUS_ABORT_BY_RESPONSE: &'static str = "abort_by_response";
+pub const ROSETTA_STATUS_ABORT_BY_POST_CONDITION: &'static str = "abort_by_post_condition";
+
+/// Every status an indexed operation can have
+pub const ROSETTA_STATUSES: &'static [&'static str] = &[
+    ROSETTA_STATUS_SUCCESS,
+    ROSETTA_STATUS_ABORT_BY_RESPONSE,
+    ROSETTA_STATUS_ABORT_BY_POST_CONDITION,
+];
+
+/// Every type an indexed operation can have
+pub const ROSETTA_OPERATION_TYPES: &'static [&'static str] = &[
+    "fee",
+    "token_transfer",
+    "contract_call",
+    "smart_contract",
+    "coinbase",
+    "poison_microblock",
+    "stx_mint",
+    "stx_burn",
+    "stx_lock",
+    "miner_reward",
+];
+
+/// A balance-relevant operation in a transaction, as stored in the index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RosettaIndexedOperation {
+    #[serde(rename = "type")]
+    pub op_type: String,
+    pub status: String,
+    /// the principal whose balance this operation concerns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// signed change to the account's balance, in decimal microSTX
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    /// positions of the operations this one pairs with (the other side of a transfer)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A transaction's operations, as stored in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct RosettaIndexedTransaction {
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// position of the transaction in the block's receipts
+    pub receipt_index: u32,
+    pub txid: Txid,
+    pub status: String,
+    pub operations: Vec<RosettaIndexedOperation>,
+}
+
+impl FromRow<RosettaIndexedTransaction> for RosettaIndexedTransaction {
+    fn from_row<'a>(row: &'a Row) -> Result<RosettaIndexedTransaction, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let receipt_index: i64 = row.get_unwrap("receipt_index");
+        let txid = Txid::from_column(row, "txid")?;
+        let status: String = row.get_unwrap("status");
+        let operations_json: String = row.get_unwrap("operations");
+        let operations =
+            serde_json::from_str(&operations_json).map_err(|_| db_error::ParseError)?;
+
+        Ok(RosettaIndexedTransaction {
+            index_block_hash,
+            block_height,
+            receipt_index: u32::try_from(receipt_index).map_err(|_| db_error::ParseError)?,
+            txid,
+            status,
+            operations,
+        })
+    }
+}
+
+/// The status of a receipt's transaction
+pub fn receipt_status(receipt: &StacksTransactionReceipt) -> &'static str {
+    if receipt.post_condition_aborted {
+        return ROSETTA_STATUS_ABORT_BY_POST_CONDITION;
+    }
+    match receipt.result {
+        Value::Response(ref data) if !data.committed => ROSETTA_STATUS_ABORT_BY_RESPONSE,
+        _ => ROSETTA_STATUS_SUCCESS,
+    }
+}
+
+/// Name of a payload's operation type
+pub fn payload_operation_type(payload: &TransactionPayload) -> &'static str {
+    match payload {
+        TransactionPayload::TokenTransfer(..) => "token_transfer",
+        TransactionPayload::ContractCall(..) => "contract_call",
+        TransactionPayload::SmartContract(..) => "smart_contract",
+        TransactionPayload::Coinbase(..) => "coinbase",
+        TransactionPayload::PoisonMicroblock(..) => "poison_microblock",
+    }
+}
+
+fn make_operation(
+    op_type: &str,
+    status: &str,
+    account: Option<&PrincipalData>,
+    amount: Option<i128>,
+) -> RosettaIndexedOperation {
+    RosettaIndexedOperation {
+        op_type: op_type.to_string(),
+        status: status.to_string(),
+        account: account.map(|principal| principal.to_string()),
+        amount: amount.map(|amount| amount.to_string()),
+        related: vec![],
+        metadata: None,
+    }
+}
+
+/// Append a debit of `amount` from `sender` and a related credit to `recipient`
+fn push_transfer(
+    operations: &mut Vec<RosettaIndexedOperation>,
+    op_type: &str,
+    status: &str,
+    sender: &PrincipalData,
+    recipient: &PrincipalData,
+    amount: u128,
+    metadata: Option<serde_json::Value>,
+) {
+    let debit_index = operations.len() as u32;
+    let mut debit = make_operation(op_type, status, Some(sender), Some(-(amount as i128)));
+    let mut credit = make_operation(op_type, status, Some(recipient), Some(amount as i128));
+    debit.related = vec![debit_index + 1];
+    credit.related = vec![debit_index];
+    debit.metadata = metadata.clone();
+    credit.metadata = metadata;
+    operations.push(debit);
+    operations.push(credit);
+}
+
+/// The operations of a receipt's transaction (see the module documentation).  `matured_rewards`
+/// are credited if the transaction is a coinbase.
+pub fn receipt_operations(
+    receipt: &StacksTransactionReceipt,
+    matured_rewards: &[MinerReward],
+) -> Vec<RosettaIndexedOperation> {
+    let status = receipt_status(receipt);
+    let mut operations = vec![];
+
+    // the token transfer memo is attached to the transfer it describes
+    let mut memo = None;
+    if let TransactionOrigin::Stacks(ref tx) = receipt.transaction {
+        let origin: PrincipalData = tx.origin_address().into();
+        let payer: PrincipalData = tx
+            .sponsor_address()
+            .map(|sponsor| sponsor.into())
+            .unwrap_or_else(|| origin.clone());
+        if tx.get_tx_fee() > 0 {
+            operations.push(make_operation(
+                "fee",
+                ROSETTA_STATUS_SUCCESS,
+                Some(&payer),
+                Some(-(tx.get_tx_fee() as i128)),
+            ));
+        }
+
+        let mut payload_op = make_operation(
+            payload_operation_type(&tx.payload),
+            status,
+            Some(&origin),
+            None,
+        );
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref recipient, amount, ref transfer_memo) => {
+                let memo_hex = format!("0x{}", to_hex(&transfer_memo.0));
+                if status == ROSETTA_STATUS_SUCCESS {
+                    memo = Some(json!({ "memo": memo_hex }));
+                } else {
+                    payload_op.metadata = Some(json!({
+                        "recipient": recipient.to_string(),
+                        "amount": amount.to_string(),
+                        "memo": memo_hex,
+                    }));
+                    operations.push(payload_op);
+                }
+            }
+            TransactionPayload::ContractCall(ref contract_call) => {
+                payload_op.metadata = Some(json!({
+                    "contract_identifier": contract_call.to_clarity_contract_id().to_string(),
+                    "function_name": contract_call.function_name.to_string(),
+                }));
+                operations.push(payload_op);
+            }
+            TransactionPayload::SmartContract(ref smart_contract) => {
+                let contract_id = QualifiedContractIdentifier::new(
+                    tx.origin_address().into(),
+                    smart_contract.name.clone(),
+                );
+                payload_op.metadata = Some(json!({
+                    "contract_identifier": contract_id.to_string(),
+                }));
+                operations.push(payload_op);
+            }
+            TransactionPayload::Coinbase(..) | TransactionPayload::PoisonMicroblock(..) => {
+                operations.push(payload_op);
+            }
+        }
+    }
+
+    for event in receipt.events.iter() {
+        match event {
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(data)) => {
+                push_transfer(
+                    &mut operations,
+                    "token_transfer",
+                    status,
+                    &data.sender,
+                    &data.recipient,
+                    data.amount,
+                    memo.take(),
+                );
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(data)) => {
+                operations.push(make_operation(
+                    "stx_mint",
+                    status,
+                    Some(&data.recipient),
+                    Some(data.amount as i128),
+                ));
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(data)) => {
+                operations.push(make_operation(
+                    "stx_burn",
+                    status,
+                    Some(&data.sender),
+                    Some(-(data.amount as i128)),
+                ));
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(data)) => {
+                let mut op = make_operation("stx_lock", status, Some(&data.locked_address), None);
+                op.metadata = Some(json!({
+                    "locked_amount": data.locked_amount.to_string(),
+                    "unlock_height": data.unlock_height,
+                }));
+                operations.push(op);
+            }
+            _ => {}
+        }
+    }
+
+    if let TransactionOrigin::Stacks(ref tx) = receipt.transaction {
+        if let TransactionPayload::Coinbase(..) = tx.payload {
+            for reward in matured_rewards.iter() {
+                let mut op = make_operation(
+                    "miner_reward",
+                    ROSETTA_STATUS_SUCCESS,
+                    Some(&reward.address.clone().into()),
+                    Some(reward.total() as i128),
+                );
+                op.metadata = Some(json!({
+                    "coinbase": reward.coinbase.to_string(),
+                    "tx_fees_anchored": reward.tx_fees_anchored.to_string(),
+                    "tx_fees_streamed_produced": reward.tx_fees_streamed_produced.to_string(),
+                    "tx_fees_streamed_confirmed": reward.tx_fees_streamed_confirmed.to_string(),
+                }));
+                operations.push(op);
+            }
+        }
+    }
+
+    operations
+}
+
+impl StacksChainState {
+    /// Add the transactions in a processed block's receipts to the Rosetta index
+    pub fn index_rosetta_transactions(
+        tx: &DBTx,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+        matured_rewards: &[MinerReward],
+    ) -> Result<(), Error> {
+        let sql = "INSERT OR REPLACE INTO rosetta_transactions
+                   (index_block_hash, block_height, receipt_index, txid, status, operations)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
+        for (receipt_index, receipt) in receipts.iter().enumerate() {
+            let operations_json =
+                serde_json::to_string(&receipt_operations(receipt, matured_rewards))
+                    .expect("FATAL: failed to serialize Rosetta operations");
+            let args: &[&dyn ToSql] = &[
+                index_block_hash,
+                &u64_to_sql(block_height)?,
+                &(receipt_index as i64),
+                &receipt.transaction.txid(),
+                &receipt_status(receipt),
+                &operations_json,
+            ];
+            tx.execute(sql, args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Get the indexed transactions of a block, in block order
+    pub fn get_rosetta_block_transactions(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Vec<RosettaIndexedTransaction>, Error> {
+        let sql = "SELECT * FROM rosetta_transactions WHERE index_block_hash = ?1
+                   ORDER BY receipt_index ASC";
+        query_rows(self.db(), sql, &[index_block_hash]).map_err(Error::DBError)
+    }
+
+    /// Get an indexed transaction of a block
+    pub fn get_rosetta_transaction(
+        &self,
+        index_block_hash: &StacksBlockId,
+        txid: &Txid,
+    ) -> Result<Option<RosettaIndexedTransaction>, Error> {
+        let sql = "SELECT * FROM rosetta_transactions WHERE index_block_hash = ?1 AND txid = ?2
+                   ORDER BY receipt_index ASC LIMIT 1";
+        let args: &[&dyn ToSql] = &[index_block_hash, txid];
+        query_row(self.db(), sql, args).map_err(Error::DBError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainstate::stacks::db::test::*;
+    use chainstate::stacks::events::{STXLockEventData, STXTransferEventData};
+    use chainstate::stacks::*;
+    use vm::costs::ExecutionCost;
+
+    use crate::types::chainstate::StacksAddress;
+
+    use super::*;
+
+    fn address_of(privk: &StacksPrivateKey) -> StacksAddress {
+        StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(privk)],
+        )
+        .unwrap()
+    }
+
+    fn make_transfer_receipt(
+        privk: &StacksPrivateKey,
+        recipient: &StacksAddress,
+        result: Value,
+        post_condition_aborted: bool,
+    ) -> StacksTransactionReceipt {
+        let mut tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(privk).unwrap(),
+            TransactionPayload::TokenTransfer(
+                recipient.clone().into(),
+                123,
+                TokenTransferMemo([1u8; 34]),
+            ),
+        );
+        tx.set_tx_fee(10);
+        let events = if result == Value::okay_true() && !post_condition_aborted {
+            vec![StacksTransactionEvent::STXEvent(
+                STXEventType::STXTransferEvent(STXTransferEventData {
+                    sender: address_of(privk).into(),
+                    recipient: recipient.clone().into(),
+                    amount: 123,
+                }),
+            )]
+        } else {
+            vec![]
+        };
+        let mut receipt =
+            StacksTransactionReceipt::from_stx_transfer(tx, events, result, ExecutionCost::zero());
+        receipt.post_condition_aborted = post_condition_aborted;
+        receipt
+    }
+
+    #[test]
+    fn test_receipt_operations() {
+        let privk = StacksPrivateKey::new();
+        let sender = address_of(&privk).to_string();
+        let recipient =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap();
+
+        // a successful transfer is a fee and a debit/credit pair
+        let receipt = make_transfer_receipt(&privk, &recipient, Value::okay_true(), false);
+        assert_eq!(receipt_status(&receipt), ROSETTA_STATUS_SUCCESS);
+        let ops = receipt_operations(&receipt, &[]);
+        let summary: Vec<_> = ops
+            .iter()
+            .map(|op| {
+                (
+                    op.op_type.as_str(),
+                    op.account.clone().unwrap(),
+                    op.amount.clone().unwrap(),
+                    op.related.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("fee", sender.clone(), "-10".to_string(), vec![]),
+                (
+                    "token_transfer",
+                    sender.clone(),
+                    "-123".to_string(),
+                    vec![2]
+                ),
+                (
+                    "token_transfer",
+                    recipient.to_string(),
+                    "123".to_string(),
+                    vec![1]
+                ),
+            ]
+        );
+        assert_eq!(
+            ops[1].metadata,
+            Some(json!({ "memo": format!("0x{}", to_hex(&[1u8; 34])) }))
+        );
+
+        // failed transfers still pay their fee
+        let receipt = make_transfer_receipt(&privk, &recipient, Value::okay_true(), true);
+        assert_eq!(
+            receipt_status(&receipt),
+            ROSETTA_STATUS_ABORT_BY_POST_CONDITION
+        );
+        let ops = receipt_operations(&receipt, &[]);
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0].status, ROSETTA_STATUS_SUCCESS);
+        assert_eq!(ops[1].op_type, "token_transfer");
+        assert_eq!(ops[1].status, ROSETTA_STATUS_ABORT_BY_POST_CONDITION);
+        assert_eq!(ops[1].amount, None);
+
+        let receipt = make_transfer_receipt(
+            &privk,
+            &recipient,
+            Value::error(Value::UInt(1)).unwrap(),
+            false,
+        );
+        assert_eq!(receipt_status(&receipt), ROSETTA_STATUS_ABORT_BY_RESPONSE);
+
+        // locks move no STX, and coinbases carry matured rewards
+        let mut coinbase = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk).unwrap(),
+            TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+        );
+        coinbase.set_tx_fee(0);
+        let mut receipt = StacksTransactionReceipt::from_coinbase(coinbase);
+        receipt.events = vec![StacksTransactionEvent::STXEvent(
+            STXEventType::STXLockEvent(STXLockEventData {
+                locked_amount: 1000,
+                unlock_height: 50,
+                locked_address: recipient.clone().into(),
+            }),
+        )];
+        let reward = MinerReward {
+            address: recipient.clone(),
+            coinbase: 500,
+            tx_fees_anchored: 1,
+            tx_fees_streamed_produced: 2,
+            tx_fees_streamed_confirmed: 3,
+            vtxindex: 0,
+        };
+        let ops = receipt_operations(&receipt, &[reward]);
+        let types: Vec<_> = ops.iter().map(|op| op.op_type.as_str()).collect();
+        assert_eq!(types, vec!["coinbase", "stx_lock", "miner_reward"]);
+        assert_eq!(ops[1].amount, None);
+        assert_eq!(ops[2].amount, Some("506".to_string()));
+    }
+
+    #[test]
+    fn test_get_rosetta_transactions() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "test_get_rosetta_transactions");
+        let privk = StacksPrivateKey::new();
+        let recipient =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap();
+        let receipts = vec![
+            make_transfer_receipt(&privk, &recipient, Value::okay_true(), false),
+            make_transfer_receipt(
+                &StacksPrivateKey::new(),
+                &recipient,
+                Value::okay_true(),
+                true,
+            ),
+        ];
+        let block_id = StacksBlockId([0x22; 32]);
+
+        let tx = chainstate.index_tx_begin().unwrap();
+        StacksChainState::index_rosetta_transactions(tx.tx(), &block_id, 5, &receipts, &[])
+            .unwrap();
+        tx.commit().unwrap();
+
+        let transactions = chainstate
+            .get_rosetta_block_transactions(&block_id)
+            .unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].block_height, 5);
+        assert_eq!(transactions[0].receipt_index, 0);
+        assert_eq!(transactions[0].status, ROSETTA_STATUS_SUCCESS);
+        assert_eq!(
+            transactions[0].operations,
+            receipt_operations(&receipts[0], &[])
+        );
+        assert_eq!(
+            transactions[1].status,
+            ROSETTA_STATUS_ABORT_BY_POST_CONDITION
+        );
+
+        let txid = receipts[1].transaction.txid();
+        assert_eq!(
+            chainstate
+                .get_rosetta_transaction(&block_id, &txid)
+                .unwrap(),
+            Some(transactions[1].clone())
+        );
+        assert_eq!(
+            chainstate
+                .get_rosetta_transaction(&StacksBlockId([0x33; 32]), &txid)
+                .unwrap(),
+            None
+        );
+        assert!(chainstate
+            .get_rosetta_block_transactions(&StacksBlockId([0x33; 32]))
+            .unwrap()
+            .is_empty());
+    }
+}
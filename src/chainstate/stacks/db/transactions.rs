@@ -24,6 +24,7 @@ use std::path::{Path, PathBuf};
 
 use chainstate::burn::db::sortdb::*;
 use chainstate::stacks::db::*;
+use chainstate::stacks::postconditions::check_post_conditions;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
 use clarity_vm::clarity::{
@@ -47,7 +48,7 @@ use vm::costs::runtime_cost;
 use vm::costs::CostTracker;
 use vm::costs::ExecutionCost;
 use vm::database::ClarityDatabase;
-use vm::errors::Error as InterpreterError;
+use vm::errors::{Error as InterpreterError, ErrorPayload};
 use vm::representations::ClarityName;
 use vm::representations::ContractName;
 use vm::types::{
@@ -84,6 +85,7 @@ impl StacksTransactionReceipt {
             execution_cost: cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -104,6 +106,7 @@ impl StacksTransactionReceipt {
             execution_cost: cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -124,6 +127,7 @@ impl StacksTransactionReceipt {
             execution_cost: cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -144,6 +148,7 @@ impl StacksTransactionReceipt {
             execution_cost: cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -164,6 +169,7 @@ impl StacksTransactionReceipt {
             execution_cost: cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -178,6 +184,7 @@ impl StacksTransactionReceipt {
             execution_cost: ExecutionCost::zero(),
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -195,6 +202,7 @@ impl StacksTransactionReceipt {
             execution_cost: analysis_cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -213,6 +221,7 @@ impl StacksTransactionReceipt {
             execution_cost: cost,
             microblock_header: None,
             tx_index: 0,
+            vm_error: None,
         }
     }
 
@@ -276,7 +285,7 @@ enum ClarityRuntimeTxError {
 fn handle_clarity_runtime_error(error: clarity_error) -> ClarityRuntimeTxError {
     match error {
         // runtime errors are okay
-        clarity_error::Interpreter(InterpreterError::Runtime(_, _)) => {
+        clarity_error::Interpreter(InterpreterError::Runtime(..)) => {
             ClarityRuntimeTxError::Acceptable {
                 error,
                 err_type: "runtime error",
@@ -296,6 +305,13 @@ fn handle_clarity_runtime_error(error: clarity_error) -> ClarityRuntimeTxError {
     }
 }
 
+fn vm_error_payload(error: &clarity_error) -> Option<ErrorPayload> {
+    match error {
+        clarity_error::Interpreter(e) => Some(e.payload()),
+        _ => None,
+    }
+}
+
 impl StacksChainState {
     /// Get the payer account
     fn get_payer_account<T: ClarityConnection>(
@@ -448,187 +464,18 @@ impl StacksChainState {
         origin_account: &StacksAccount,
         asset_map: &AssetMap,
     ) -> bool {
-        let mut checked_fungible_assets: HashMap<PrincipalData, HashSet<AssetIdentifier>> =
-            HashMap::new();
-        let mut checked_nonfungible_assets: HashMap<
-            PrincipalData,
-            HashMap<AssetIdentifier, HashSet<Value>>,
-        > = HashMap::new();
-        let allow_unchecked_assets = *post_condition_mode == TransactionPostConditionMode::Allow;
-
-        for postcond in post_conditions {
-            match postcond {
-                TransactionPostCondition::STX(
-                    ref principal,
-                    ref condition_code,
-                    ref amount_sent_condition,
-                ) => {
-                    let account_principal = principal.to_principal_data(&origin_account.principal);
-
-                    let amount_transferred = asset_map.get_stx(&account_principal).unwrap_or(0);
-                    let amount_burned = asset_map.get_stx_burned(&account_principal).unwrap_or(0);
-
-                    let amount_sent = amount_transferred
-                        .checked_add(amount_burned)
-                        .expect("FATAL: sent waaaaay too much STX");
-
-                    if !condition_code.check(*amount_sent_condition as u128, amount_sent) {
-                        info!(
-                            "Post-condition check failure on STX owned by {}: {:?} {:?} {}",
-                            account_principal, amount_sent_condition, condition_code, amount_sent
-                        );
-                        return false;
-                    }
-
-                    if let Some(ref mut asset_ids) =
-                        checked_fungible_assets.get_mut(&account_principal)
-                    {
-                        if amount_transferred > 0 {
-                            asset_ids.insert(AssetIdentifier::STX());
-                        }
-                        if amount_burned > 0 {
-                            asset_ids.insert(AssetIdentifier::STX_burned());
-                        }
-                    } else {
-                        let mut h = HashSet::new();
-                        if amount_transferred > 0 {
-                            h.insert(AssetIdentifier::STX());
-                        }
-                        if amount_burned > 0 {
-                            h.insert(AssetIdentifier::STX_burned());
-                        }
-                        checked_fungible_assets.insert(account_principal, h);
-                    }
-                }
-                TransactionPostCondition::Fungible(
-                    ref principal,
-                    ref asset_info,
-                    ref condition_code,
-                    ref amount_sent_condition,
-                ) => {
-                    let account_principal = principal.to_principal_data(&origin_account.principal);
-                    let asset_id = AssetIdentifier {
-                        contract_identifier: QualifiedContractIdentifier::new(
-                            StandardPrincipalData::from(asset_info.contract_address.clone()),
-                            asset_info.contract_name.clone(),
-                        ),
-                        asset_name: asset_info.asset_name.clone(),
-                    };
-
-                    let amount_sent = asset_map
-                        .get_fungible_tokens(&account_principal, &asset_id)
-                        .unwrap_or(0);
-                    if !condition_code.check(*amount_sent_condition as u128, amount_sent) {
-                        info!("Post-condition check failure on fungible asset {} owned by {}: {} {:?} {}", &asset_id, account_principal, amount_sent_condition, condition_code, amount_sent);
-                        return false;
-                    }
-
-                    if let Some(ref mut asset_ids) =
-                        checked_fungible_assets.get_mut(&account_principal)
-                    {
-                        asset_ids.insert(asset_id);
-                    } else {
-                        let mut h = HashSet::new();
-                        h.insert(asset_id);
-                        checked_fungible_assets.insert(account_principal, h);
-                    }
-                }
-                TransactionPostCondition::Nonfungible(
-                    ref principal,
-                    ref asset_info,
-                    ref asset_value,
-                    ref condition_code,
-                ) => {
-                    let account_principal = principal.to_principal_data(&origin_account.principal);
-                    let asset_id = AssetIdentifier {
-                        contract_identifier: QualifiedContractIdentifier::new(
-                            StandardPrincipalData::from(asset_info.contract_address.clone()),
-                            asset_info.contract_name.clone(),
-                        ),
-                        asset_name: asset_info.asset_name.clone(),
-                    };
-
-                    let empty_assets = vec![];
-                    let assets_sent = asset_map
-                        .get_nonfungible_tokens(&account_principal, &asset_id)
-                        .unwrap_or(&empty_assets);
-                    if !condition_code.check(asset_value, assets_sent) {
-                        info!("Post-condition check failure on non-fungible asset {} owned by {}: {:?} {:?}", &asset_id, account_principal, &asset_value, condition_code);
-                        return false;
-                    }
-
-                    if let Some(ref mut asset_id_map) =
-                        checked_nonfungible_assets.get_mut(&account_principal)
-                    {
-                        if let Some(ref mut asset_values) = asset_id_map.get_mut(&asset_id) {
-                            asset_values.insert(asset_value.clone());
-                        } else {
-                            let mut asset_set = HashSet::new();
-                            asset_set.insert(asset_value.clone());
-                            asset_id_map.insert(asset_id, asset_set);
-                        }
-                    } else {
-                        let mut asset_id_map = HashMap::new();
-                        let mut asset_set = HashSet::new();
-                        asset_set.insert(asset_value.clone());
-                        asset_id_map.insert(asset_id, asset_set);
-                        checked_nonfungible_assets.insert(account_principal, asset_id_map);
-                    }
-                }
-            }
-        }
-
-        if !allow_unchecked_assets {
-            // make sure every asset transferred is covered by a postcondition
-            let asset_map_copy = (*asset_map).clone();
-            let mut all_assets_sent = asset_map_copy.to_table();
-            for (principal, mut assets) in all_assets_sent.drain() {
-                for (asset_identifier, asset_entry) in assets.drain() {
-                    match asset_entry {
-                        AssetMapEntry::Asset(values) => {
-                            // this is a NFT
-                            if let Some(ref checked_nft_asset_map) =
-                                checked_nonfungible_assets.get(&principal)
-                            {
-                                if let Some(ref nfts) = checked_nft_asset_map.get(&asset_identifier)
-                                {
-                                    // each value must be covered
-                                    for v in values {
-                                        if !nfts.contains(&v) {
-                                            info!("Post-condition check failure: Non-fungible asset {} value {:?} was moved by {} but not checked", &asset_identifier, &v, &principal);
-                                            return false;
-                                        }
-                                    }
-                                } else {
-                                    // no values covered
-                                    info!("Post-condition check failure: No checks for non-fungible asset type {} moved by {}", &asset_identifier, &principal);
-                                    return false;
-                                }
-                            } else {
-                                // no NFT for this principal
-                                info!("Post-condition check failure: No checks for any non-fungible assets, but moved {} by {}", &asset_identifier, &principal);
-                                return false;
-                            }
-                        }
-                        _ => {
-                            // This is STX or a fungible token
-                            if let Some(ref checked_ft_asset_ids) =
-                                checked_fungible_assets.get(&principal)
-                            {
-                                if !checked_ft_asset_ids.contains(&asset_identifier) {
-                                    info!("Post-condition check failure: checks did not cover transfer of {} by {}", &asset_identifier, &principal);
-                                    return false;
-                                }
-                            } else {
-                                info!("Post-condition check failure: No checks for fungible token type {} moved by {}", &asset_identifier, &principal);
-                                return false;
-                            }
-                        }
-                    }
-                }
+        match check_post_conditions(
+            post_conditions,
+            post_condition_mode,
+            &origin_account.principal,
+            asset_map,
+        ) {
+            Ok(()) => true,
+            Err(failure) => {
+                info!("Post-condition check failure: {}", &failure);
+                false
             }
         }
-        return true;
     }
 
     /// Given two microblock headers, were they signed by the same key?
@@ -895,6 +742,7 @@ impl StacksChainState {
                     .sub(&cost_before)
                     .expect("BUG: total block cost decreased");
 
+                let mut vm_error = None;
                 let (result, asset_map, events) = match contract_call_resp {
                     Ok((return_value, asset_map, events)) => {
                         info!("Contract-call successfully processed";
@@ -912,6 +760,7 @@ impl StacksChainState {
                                       "function_name" => %contract_call.function_name,
                                       "function_args" => %VecDisplay(&contract_call.function_args),
                                       "error" => ?error);
+                            vm_error = vm_error_payload(&error);
                             (Value::err_none(), AssetMap::new(), vec![])
                         }
                         ClarityRuntimeTxError::AbortedByCallback(value, assets, events) => {
@@ -942,13 +791,14 @@ impl StacksChainState {
                     },
                 };
 
-                let receipt = StacksTransactionReceipt::from_contract_call(
+                let mut receipt = StacksTransactionReceipt::from_contract_call(
                     tx.clone(),
                     events,
                     result,
                     asset_map.get_stx_burned_total(),
                     total_cost,
                 );
+                receipt.vm_error = vm_error;
                 Ok(receipt)
             }
             TransactionPayload::SmartContract(ref smart_contract) => {
@@ -1042,6 +892,7 @@ impl StacksChainState {
                     .sub(&cost_before)
                     .expect("BUG: total block cost decreased");
 
+                let mut vm_error = None;
                 let (asset_map, events) = match initialize_resp {
                     Ok(x) => {
                         // store analysis -- if this fails, then the have some pretty bad problems
@@ -1056,6 +907,7 @@ impl StacksChainState {
                                       "contract" => %contract_id,
                                       "code" => %contract_code_str,
                                       "error" => ?error);
+                            vm_error = vm_error_payload(&error);
                             (AssetMap::new(), vec![])
                         }
                         ClarityRuntimeTxError::AbortedByCallback(_, assets, events) => {
@@ -1086,13 +938,14 @@ impl StacksChainState {
                     },
                 };
 
-                let receipt = StacksTransactionReceipt::from_smart_contract(
+                let mut receipt = StacksTransactionReceipt::from_smart_contract(
                     tx.clone(),
                     events,
                     asset_map.get_stx_burned_total(),
                     contract_analysis,
                     total_cost,
                 );
+                receipt.vm_error = vm_error;
                 Ok(receipt)
             }
             TransactionPayload::PoisonMicroblock(ref mblock_header_1, ref mblock_header_2) => {
@@ -2116,8 +1969,13 @@ pub mod test {
 
         // contract-calls that don't commit
         let contract_calls = vec![
-            ("hello-world", "set-bar", vec![Value::Int(1), Value::Int(0)]), // divide-by-zero
-            ("hello-world", "return-error", vec![]),                        // returns an (err ...)
+            (
+                "hello-world",
+                "set-bar",
+                vec![Value::Int(1), Value::Int(0)],
+                Some(1005),
+            ), // divide-by-zero
+            ("hello-world", "return-error", vec![], None), // returns an (err ...)
         ];
 
         // do contract-calls
@@ -2131,7 +1989,8 @@ pub mod test {
         let mut next_nonce = 0;
 
         for contract_call in contract_calls {
-            let (contract_name, contract_function, contract_args) = contract_call;
+            let (contract_name, contract_function, contract_args, expected_error_code) =
+                contract_call;
             let mut tx_contract_call = StacksTransaction::new(
                 TransactionVersion::Testnet,
                 auth_2.clone(),
@@ -2157,9 +2016,19 @@ pub mod test {
                 StacksChainState::get_account(&mut conn, &addr_2.to_account_principal());
             assert_eq!(account_2.nonce, next_nonce);
 
-            let (_fee, _) =
+            let (_fee, receipt) =
                 StacksChainState::process_transaction(&mut conn, &signed_tx_2, false).unwrap();
 
+            // runtime errors are reported with their code and location
+            assert_eq!(
+                receipt.vm_error.as_ref().map(|e| e.code),
+                expected_error_code
+            );
+            if let Some(vm_error) = receipt.vm_error {
+                assert_eq!(vm_error.contract, Some(contract_id.to_string()));
+                assert_eq!(vm_error.function.as_deref(), Some("set-bar"));
+            }
+
             // nonce should have incremented
             next_nonce += 1;
             let account_2 =
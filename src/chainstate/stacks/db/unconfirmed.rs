@@ -93,9 +93,12 @@ impl UnconfirmedState {
     /// Make a new unconfirmed state, but don't do anything with it yet.  Caller should immediately
     /// call .refresh() to instatiate and store the underlying state trie.
     fn new(chainstate: &StacksChainState, tip: StacksBlockId) -> Result<UnconfirmedState, Error> {
-        let marf = MarfedKV::open_unconfirmed(&chainstate.clarity_state_index_root, None)?;
+        let mut marf = MarfedKV::open_unconfirmed(&chainstate.clarity_state_index_root, None)?;
+        marf.get_marf()
+            .set_external_blobs(chainstate.clarity_state_blobs().cloned());
 
-        let clarity_instance = ClarityInstance::new(chainstate.mainnet, marf);
+        let mut clarity_instance = ClarityInstance::new(chainstate.mainnet, marf);
+        clarity_instance.set_cost_overrides(chainstate.cost_overrides().cloned());
         let unconfirmed_tip = MARF::make_unconfirmed_chain_tip(&tip);
         let cost_so_far = StacksChainState::get_stacks_block_anchored_cost(chainstate.db(), &tip)?
             .ok_or(Error::NoSuchBlockError)?;
@@ -126,9 +129,12 @@ impl UnconfirmedState {
         chainstate: &StacksChainState,
         tip: StacksBlockId,
     ) -> Result<UnconfirmedState, Error> {
-        let marf = MarfedKV::open_unconfirmed(&chainstate.clarity_state_index_root, None)?;
+        let mut marf = MarfedKV::open_unconfirmed(&chainstate.clarity_state_index_root, None)?;
+        marf.get_marf()
+            .set_external_blobs(chainstate.clarity_state_blobs().cloned());
 
-        let clarity_instance = ClarityInstance::new(chainstate.mainnet, marf);
+        let mut clarity_instance = ClarityInstance::new(chainstate.mainnet, marf);
+        clarity_instance.set_cost_overrides(chainstate.cost_overrides().cloned());
         let unconfirmed_tip = MARF::make_unconfirmed_chain_tip(&tip);
         let cost_so_far = StacksChainState::get_stacks_block_anchored_cost(chainstate.db(), &tip)?
             .ok_or(Error::NoSuchBlockError)?;
@@ -589,7 +595,9 @@ mod test {
     use chainstate::stacks::index::marf::*;
     use chainstate::stacks::index::node::*;
     use chainstate::stacks::index::*;
-    use chainstate::stacks::miner::test::make_coinbase;
+    use chainstate::stacks::miner::test::{
+        make_coinbase, make_user_contract_publish, make_user_stacks_transfer,
+    };
     use chainstate::stacks::miner::*;
     use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
     use chainstate::stacks::*;
@@ -597,6 +605,7 @@ mod test {
     use core::*;
     use net::relay::*;
     use net::test::*;
+    use vm::contexts::ExecutionLimits;
 
     use super::*;
 
@@ -831,6 +840,201 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_unconfirmed_read_only_function_call() {
+        let privk = StacksPrivateKey::new();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk)],
+        )
+        .unwrap();
+
+        let initial_balance = 1000000000;
+        let mut peer_config =
+            TestPeerConfig::new("test_unconfirmed_read_only_function_call", 7020, 7021);
+        peer_config.initial_balances = vec![(addr.to_account_principal(), initial_balance)];
+
+        let mut peer = TestPeer::new(peer_config);
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        let contract = "(define-read-only (get-balance (who principal)) (stx-get-balance who))";
+
+        // anchored block publishes a contract that reads balances
+        let (burn_ops, stacks_block, _) = peer.make_tenure(
+            |ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, _, _| {
+                let parent_tip =
+                    StacksChainState::get_genesis_header_info(chainstate.db()).unwrap();
+                let block_builder = StacksBlockBuilder::make_regtest_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+
+                let coinbase_tx = make_coinbase(miner, 0);
+                let publish_tx = make_user_contract_publish(&privk, 0, 0, "balances", contract);
+                let (anchored_block, _, _) = StacksBlockBuilder::make_anchored_block_from_txs(
+                    block_builder,
+                    chainstate,
+                    &sortdb.index_conn(),
+                    vec![coinbase_tx, publish_tx],
+                )
+                .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
+
+        let canonical_tip =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block.block_hash());
+
+        let recv_addr =
+            StacksAddress::from_string("ST1H1B54MY50RMBRRKS7GV2ZWG79RZ1RQ1ETW4E01").unwrap();
+
+        // microblock sends some STX
+        let microblock = {
+            let sortdb = peer.sortdb.take().unwrap();
+            let sort_iconn = sortdb.index_conn();
+
+            peer.chainstate()
+                .reload_unconfirmed_state(&sort_iconn, canonical_tip.clone())
+                .unwrap();
+
+            let microblock = {
+                let mut microblock_builder = StacksMicroblockBuilder::new(
+                    stacks_block.block_hash(),
+                    consensus_hash.clone(),
+                    peer.chainstate(),
+                    &sort_iconn,
+                    BlockBuilderSettings::max_value(),
+                )
+                .unwrap();
+
+                let stx_transfer =
+                    make_user_stacks_transfer(&privk, 1, 0, &recv_addr.to_account_principal(), 123);
+                let stx_transfer_len = stx_transfer.serialize_to_vec().len() as u64;
+                microblock_builder
+                    .mine_next_microblock_from_txs(
+                        vec![(stx_transfer, stx_transfer_len)],
+                        &microblock_privkey,
+                    )
+                    .unwrap()
+            };
+
+            peer.sortdb = Some(sortdb);
+            microblock
+        };
+
+        peer.chainstate()
+            .preprocess_streamed_microblock(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+                &microblock,
+            )
+            .unwrap();
+
+        let sortdb = peer.sortdb.take().unwrap();
+        peer.chainstate()
+            .reload_unconfirmed_state(&sortdb.index_conn(), canonical_tip.clone())
+            .unwrap();
+
+        let contract_id = QualifiedContractIdentifier::new(
+            StandardPrincipalData::from(addr.clone()),
+            "balances".into(),
+        );
+        let args = vec![Value::from(PrincipalData::from(recv_addr.clone()))];
+        let sender = PrincipalData::from(addr.clone());
+
+        // unconfirmed state sees the microblock transaction
+        let unconfirmed_result = peer
+            .chainstate()
+            .eval_read_only_function_unconfirmed(
+                &sortdb.index_conn(),
+                &contract_id,
+                "get-balance",
+                &args,
+                &sender,
+                ExecutionCost::max_value(),
+                ExecutionLimits::default(),
+            )
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(unconfirmed_result, Value::UInt(123));
+
+        // ...and so does evaluating at the unconfirmed chain tip
+        let unconfirmed_tip = peer
+            .chainstate()
+            .unconfirmed_state
+            .as_ref()
+            .unwrap()
+            .unconfirmed_chain_tip
+            .clone();
+        let unconfirmed_tip_result = peer
+            .chainstate()
+            .eval_read_only_function_at_tip(
+                &sortdb.index_conn(),
+                &unconfirmed_tip,
+                &contract_id,
+                "get-balance",
+                &args,
+                &sender,
+                ExecutionCost::max_value(),
+                ExecutionLimits::default(),
+            )
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(unconfirmed_tip_result, Value::UInt(123));
+
+        // confirmed state does not
+        let confirmed_result = peer
+            .chainstate()
+            .eval_read_only_function_at_tip(
+                &sortdb.index_conn(),
+                &canonical_tip,
+                &contract_id,
+                "get-balance",
+                &args,
+                &sender,
+                ExecutionCost::max_value(),
+                ExecutionLimits::default(),
+            )
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(confirmed_result, Value::UInt(0));
+
+        // no such chain tip
+        assert!(peer
+            .chainstate()
+            .eval_read_only_function_at_tip(
+                &sortdb.index_conn(),
+                &StacksBlockId([0x11; 32]),
+                &contract_id,
+                "get-balance",
+                &args,
+                &sender,
+                ExecutionCost::max_value(),
+                ExecutionLimits::default(),
+            )
+            .unwrap()
+            .is_none());
+
+        peer.sortdb = Some(sortdb);
+    }
+
     #[test]
     fn test_unconfirmed_refresh_10_microblocks_10_stx_transfers() {
         let privk = StacksPrivateKey::new();
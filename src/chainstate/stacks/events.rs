@@ -20,6 +20,7 @@ use burnchains::Txid;
 use chainstate::stacks::StacksTransaction;
 use vm::analysis::ContractAnalysis;
 use vm::costs::ExecutionCost;
+use vm::errors::ErrorPayload;
 use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, Value,
 };
@@ -62,6 +63,8 @@ pub struct StacksTransactionReceipt {
     pub execution_cost: ExecutionCost,
     pub microblock_header: Option<StacksMicroblockHeader>,
     pub tx_index: u32,
+    /// Structured description of the runtime error that aborted this transaction, if any
+    pub vm_error: Option<ErrorPayload>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
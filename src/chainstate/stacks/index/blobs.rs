@@ -0,0 +1,941 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! External storage for MARF trie blobs.
+//!
+//! A MARF stores each block's trie as a blob in its `marf_data` table.  Once a block is buried,
+//! its trie is never written again, so an archival node can offload old tries to object storage
+//! and keep only recent tries -- plus a bounded cache of recently-read old tries -- on local
+//! disk.  An offloaded trie keeps its row in `marf_data` (so block IDs and back-pointers are
+//! unaffected), but its `data` is emptied and the blob's SHA-256 is recorded alongside it.
+//! Readers that find an empty blob fetch it by block hash from the MARF's `ExternalTrieBlobs`,
+//! and refuse it unless it still has the recorded hash.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerName, StreamOwned};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use chainstate::stacks::index::Error;
+use net::tls::load_certs;
+use util::hash::to_hex;
+
+/// How long to wait on an object store before giving up on a request
+pub const BLOB_STORE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// CA certificates used to authenticate an object store, unless its URL names others
+pub const DEFAULT_CA_FILE: &str = "/etc/ssl/certs/ca-certificates.crt";
+
+/// Hex SHA-256 of a trie blob, which is recorded when the blob is offloaded
+pub fn trie_blob_hash(data: &[u8]) -> String {
+    to_hex(Sha256::digest(data).as_slice())
+}
+
+/// A place to put trie blobs that are no longer needed locally.  Blobs are named by the hex
+/// block hash of their trie.
+pub trait TrieBlobStore: Send + Sync {
+    /// Store `data` as `name`, replacing any blob already stored under that name
+    fn put_blob(&self, name: &str, data: &[u8]) -> Result<(), Error>;
+
+    /// Load the blob stored as `name`.  Fails with `Error::NotFoundError` if there isn't one.
+    fn get_blob(&self, name: &str) -> Result<Vec<u8>, Error>;
+}
+
+/// Blob store backed by a directory, e.g. on a network filesystem or a large, slow disk
+pub struct DirectoryBlobStore {
+    root: PathBuf,
+}
+
+impl DirectoryBlobStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<DirectoryBlobStore, Error> {
+        fs::create_dir_all(&root)?;
+        Ok(DirectoryBlobStore {
+            root: root.as_ref().to_path_buf(),
+        })
+    }
+}
+
+impl TrieBlobStore for DirectoryBlobStore {
+    fn put_blob(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        // write-then-rename, so a reader never sees a partial blob
+        let tmp_path = self
+            .root
+            .join(format!("{}.{}.tmp", name, rand::random::<u64>()));
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, self.root.join(name))?;
+        Ok(())
+    }
+
+    fn get_blob(&self, name: &str) -> Result<Vec<u8>, Error> {
+        fs::read(self.root.join(name)).map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Error::NotFoundError
+            } else {
+                Error::IOError(e)
+            }
+        })
+    }
+}
+
+/// Blob store backed by an S3-compatible object store, addressed path-style
+/// (`https://host:port/bucket/prefix/name`).  Requests are signed with AWS Signature Version 4,
+/// and always go over TLS, since they carry the node's credentials and its state.
+pub struct S3BlobStore {
+    tls_config: Arc<ClientConfig>,
+    host: String,
+    port: u16,
+    bucket: String,
+    prefix: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3BlobStore {
+    pub fn new(
+        tls_config: Arc<ClientConfig>,
+        host: &str,
+        port: u16,
+        bucket: &str,
+        prefix: &str,
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> S3BlobStore {
+        let prefix = prefix.trim_matches('/');
+        S3BlobStore {
+            tls_config,
+            host: host.to_string(),
+            port,
+            bucket: bucket.to_string(),
+            prefix: if prefix.is_empty() {
+                "".to_string()
+            } else {
+                format!("{}/", prefix)
+            },
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+        }
+    }
+
+    fn object_path(&self, name: &str) -> String {
+        format!("/{}/{}{}", &self.bucket, &self.prefix, name)
+    }
+
+    /// Send a signed request for `name`, and return the response status and body
+    fn request(&self, method: &str, name: &str, body: &[u8]) -> Result<(u16, Vec<u8>), Error> {
+        let path = self.object_path(name);
+        let host = if self.port == 443 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", &self.host, self.port)
+        };
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = to_hex(Sha256::digest(body).as_slice());
+
+        let headers = [
+            ("host", host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        let (signed_headers, signature) = sigv4_signature(
+            method,
+            &path,
+            "",
+            &headers,
+            &payload_hash,
+            &self.region,
+            "s3",
+            &amz_date,
+            &self.secret_access_key,
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}/{}/s3/aws4_request, SignedHeaders={}, Signature={}",
+            &self.access_key_id,
+            &amz_date[0..8],
+            &self.region,
+            &signed_headers,
+            &signature
+        );
+
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nx-amz-content-sha256: {}\r\nx-amz-date: {}\r\nAuthorization: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            &path,
+            &host,
+            &payload_hash,
+            &amz_date,
+            &authorization,
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+
+        let server_name = ServerName::try_from(self.host.as_str()).map_err(|_| {
+            Error::IOError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid object store host name '{}'", &self.host),
+            ))
+        })?;
+        let session = ClientConnection::new(self.tls_config.clone(), server_name)
+            .map_err(|e| Error::IOError(io::Error::new(io::ErrorKind::Other, e)))?;
+
+        let sock = TcpStream::connect((self.host.as_str(), self.port))?;
+        sock.set_read_timeout(Some(BLOB_STORE_TIMEOUT))?;
+        sock.set_write_timeout(Some(BLOB_STORE_TIMEOUT))?;
+        let mut stream = StreamOwned::new(session, sock);
+        stream.write_all(&request)?;
+
+        let mut response = vec![];
+        match stream.read_to_end(&mut response) {
+            Ok(_) => {}
+            // some servers close without a TLS close_notify; a truncated body is still caught
+            // against the Content-Length
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+            Err(e) => return Err(e.into()),
+        }
+        parse_http_response(&response)
+    }
+
+    fn check_status(
+        &self,
+        method: &str,
+        name: &str,
+        status: u16,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        match status {
+            200..=299 => Ok(()),
+            404 => Err(Error::NotFoundError),
+            _ => Err(Error::IOError(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "S3 {} {} failed with status {}: {}",
+                    method,
+                    &self.object_path(name),
+                    status,
+                    String::from_utf8_lossy(&body[0..body.len().min(512)])
+                ),
+            ))),
+        }
+    }
+}
+
+impl TrieBlobStore for S3BlobStore {
+    fn put_blob(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let (status, body) = self.request("PUT", name, data)?;
+        self.check_status("PUT", name, status, &body)
+    }
+
+    fn get_blob(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let (status, body) = self.request("GET", name, &[])?;
+        self.check_status("GET", name, status, &body)?;
+        Ok(body)
+    }
+}
+
+/// Parse a complete HTTP/1.1 response into its status code and body.  The response must either
+/// have a Content-Length, or be terminated by the server closing the connection.
+fn parse_http_response(response: &[u8]) -> Result<(u16, Vec<u8>), Error> {
+    let bad_response = |msg: &str| {
+        Error::IOError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid HTTP response from blob store: {}", msg),
+        ))
+    };
+
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| bad_response("no end of headers"))?;
+    let preamble = std::str::from_utf8(&response[0..header_end])
+        .map_err(|_| bad_response("headers are not UTF-8"))?;
+    let mut body = response[(header_end + 4)..].to_vec();
+
+    let mut lines = preamble.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| bad_response("bad status line"))?;
+
+    for line in lines {
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim().to_lowercase();
+        let value = parts.next().unwrap_or("").trim();
+        if name == "content-length" {
+            let len = value
+                .parse::<usize>()
+                .map_err(|_| bad_response("bad Content-Length"))?;
+            if body.len() < len {
+                return Err(bad_response("truncated body"));
+            }
+            body.truncate(len);
+        } else if name == "transfer-encoding" && value.to_lowercase().contains("chunked") {
+            return Err(bad_response("chunked encoding is not supported"));
+        }
+    }
+
+    Ok((status, body))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block_key = [0u8; 64];
+    if key.len() > block_key.len() {
+        block_key[0..32].copy_from_slice(Sha256::digest(key).as_slice());
+    } else {
+        block_key[0..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.input(block_key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.input(data);
+
+    let mut outer = Sha256::new();
+    outer.input(block_key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.input(inner.result().as_slice());
+
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(outer.result().as_slice());
+    ret
+}
+
+/// Compute an AWS Signature Version 4 request signature.  `headers` are the (lowercase) names
+/// and values of the headers to sign, sorted by name; `path` and `query` must already be
+/// URI-encoded.  Returns the signed header list and the hex signature.
+fn sigv4_signature(
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(&str, String)],
+    payload_hash: &str,
+    region: &str,
+    service: &str,
+    amz_date: &str,
+    secret_access_key: &str,
+) -> (String, String) {
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method, path, query, &canonical_headers, &signed_headers, payload_hash
+    );
+
+    let date = &amz_date[0..8];
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        &scope,
+        to_hex(Sha256::digest(canonical_request.as_bytes()).as_slice())
+    );
+
+    let k_date = hmac_sha256(
+        format!("AWS4{}", secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    let k_region = hmac_sha256(&k_date, region.as_bytes());
+    let k_service = hmac_sha256(&k_region, service.as_bytes());
+    let k_signing = hmac_sha256(&k_service, b"aws4_request");
+    let signature = to_hex(&hmac_sha256(&k_signing, string_to_sign.as_bytes()));
+
+    (signed_headers, signature)
+}
+
+/// TLS settings for talking to an object store whose certificate chains to one of the CA
+/// certificates in the PEM file `ca_file`
+pub fn blob_store_tls_config(ca_file: &str) -> Result<Arc<ClientConfig>, Error> {
+    let tls_error = |msg: String| Error::IOError(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_file).map_err(|e| tls_error(format!("{:?}", &e)))? {
+        roots
+            .add(&cert)
+            .map_err(|e| tls_error(format!("Bad CA certificate in {}: {:?}", ca_file, &e)))?;
+    }
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Open the blob store at `url`.  Supported URLs are
+/// * `file:///path/to/dir`, for a `DirectoryBlobStore`, and
+/// * `s3+https://host:port/bucket/prefix?region=us-east-1&ca_file=/path/to/ca.pem`, for an
+///   `S3BlobStore`.  The server's certificate is checked against `ca_file` (by default,
+///   `DEFAULT_CA_FILE`).  Its credentials are taken from the `AWS_ACCESS_KEY_ID` and
+///   `AWS_SECRET_ACCESS_KEY` environment variables.
+pub fn open_blob_store(url: &str) -> Result<Box<dyn TrieBlobStore>, Error> {
+    let bad_url = |msg: &str| {
+        Error::IOError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid blob store URL '{}': {}", url, msg),
+        ))
+    };
+    let parsed = Url::parse(url).map_err(|e| bad_url(&format!("{}", e)))?;
+
+    match parsed.scheme() {
+        "file" => Ok(Box::new(DirectoryBlobStore::new(parsed.path())?)),
+        "s3+http" => Err(bad_url(
+            "object stores must be reached over TLS (use s3+https)",
+        )),
+        "s3+https" => {
+            let host = parsed.host_str().ok_or_else(|| bad_url("no host"))?;
+            let port = parsed.port().unwrap_or(443);
+            let mut segments = parsed
+                .path_segments()
+                .ok_or_else(|| bad_url("no bucket"))?
+                .filter(|s| !s.is_empty());
+            let bucket = segments.next().ok_or_else(|| bad_url("no bucket"))?;
+            let prefix = segments.collect::<Vec<_>>().join("/");
+            let region = parsed
+                .query_pairs()
+                .find(|(k, _)| k == "region")
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_else(|| "us-east-1".to_string());
+            let ca_file = parsed
+                .query_pairs()
+                .find(|(k, _)| k == "ca_file")
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_else(|| DEFAULT_CA_FILE.to_string());
+
+            let access_key_id = std::env::var("AWS_ACCESS_KEY_ID")
+                .map_err(|_| bad_url("AWS_ACCESS_KEY_ID is not set"))?;
+            let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY")
+                .map_err(|_| bad_url("AWS_SECRET_ACCESS_KEY is not set"))?;
+
+            Ok(Box::new(S3BlobStore::new(
+                blob_store_tls_config(&ca_file)?,
+                host,
+                port,
+                bucket,
+                &prefix,
+                &region,
+                &access_key_id,
+                &secret_access_key,
+            )))
+        }
+        scheme => Err(bad_url(&format!("unsupported scheme '{}'", scheme))),
+    }
+}
+
+struct CacheState {
+    /// blob name => (size, last use)
+    entries: HashMap<String, (u64, u64)>,
+    /// last use => blob name
+    by_use: BTreeMap<u64, String>,
+    clock: u64,
+    total_bytes: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, name: &str, size: u64) {
+        self.clock += 1;
+        if let Some((old_size, last_use)) =
+            self.entries.insert(name.to_string(), (size, self.clock))
+        {
+            self.by_use.remove(&last_use);
+            self.total_bytes -= old_size;
+        }
+        self.by_use.insert(self.clock, name.to_string());
+        self.total_bytes += size;
+    }
+
+    fn remove(&mut self, name: &str) -> bool {
+        match self.entries.remove(name) {
+            Some((size, last_use)) => {
+                self.by_use.remove(&last_use);
+                self.total_bytes -= size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Name of the least-recently-used blob other than `keep`
+    fn least_recently_used(&self, keep: &str) -> Option<String> {
+        self.by_use
+            .values()
+            .find(|name| name.as_str() != keep)
+            .cloned()
+    }
+}
+
+/// Least-recently-used cache of offloaded trie blobs, kept in a local directory.  Survives
+/// restarts: blobs already in the directory are picked up (oldest-modified first) when the cache
+/// is opened.
+pub struct TrieBlobCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Mutex<CacheState>,
+}
+
+impl TrieBlobCache {
+    pub fn new<P: AsRef<Path>>(dir: P, max_bytes: u64) -> Result<TrieBlobCache, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut existing = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("blob") => {}
+                Some("tmp") => {
+                    // left over from a crash
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+                _ => continue,
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let md = entry.metadata()?;
+            existing.push((md.modified()?, name, md.len()));
+        }
+        existing.sort();
+
+        let cache = TrieBlobCache {
+            dir,
+            max_bytes,
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                by_use: BTreeMap::new(),
+                clock: 0,
+                total_bytes: 0,
+            }),
+        };
+
+        {
+            let mut state = cache.state.lock().expect("BUG: blob cache lock poisoned");
+            for (_, name, size) in existing.into_iter() {
+                state.touch(&name, size);
+            }
+            cache.evict(&mut state, "");
+        }
+        Ok(cache)
+    }
+
+    fn blob_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.blob", name))
+    }
+
+    /// Remove least-recently-used blobs (other than `keep`) until the cache fits its budget
+    fn evict(&self, state: &mut CacheState, keep: &str) {
+        while state.total_bytes > self.max_bytes {
+            let name = match state.least_recently_used(keep) {
+                Some(name) => name,
+                None => break,
+            };
+            state.remove(&name);
+            if let Err(e) = fs::remove_file(self.blob_path(&name)) {
+                warn!("Failed to evict cached trie blob {}: {:?}", &name, &e);
+            }
+        }
+    }
+
+    /// Open the cached copy of `name`, if there is one
+    pub fn open(&self, name: &str) -> Option<fs::File> {
+        let mut state = self.state.lock().expect("BUG: blob cache lock poisoned");
+        let size = state.entries.get(name)?.0;
+        match fs::File::open(self.blob_path(name)) {
+            Ok(f) => {
+                state.touch(name, size);
+                Some(f)
+            }
+            Err(e) => {
+                warn!("Cached trie blob {} is unreadable: {:?}", name, &e);
+                state.remove(name);
+                None
+            }
+        }
+    }
+
+    /// Add a blob to the cache, evicting least-recently-used blobs to make room for it, and open it
+    pub fn insert(&self, name: &str, data: &[u8]) -> Result<fs::File, Error> {
+        let tmp_path = self
+            .dir
+            .join(format!("{}.{}.tmp", name, rand::random::<u64>()));
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, self.blob_path(name))?;
+        let f = fs::File::open(self.blob_path(name))?;
+
+        let mut state = self.state.lock().expect("BUG: blob cache lock poisoned");
+        state.touch(name, data.len() as u64);
+        self.evict(&mut state, name);
+        Ok(f)
+    }
+
+    /// Number of cached blobs
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .expect("BUG: blob cache lock poisoned")
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total size of the cached blobs
+    pub fn total_bytes(&self) -> u64 {
+        self.state
+            .lock()
+            .expect("BUG: blob cache lock poisoned")
+            .total_bytes
+    }
+}
+
+/// The offloaded tries of a MARF: where they're stored, and the local cache of hot ones
+pub struct ExternalTrieBlobs {
+    store: Box<dyn TrieBlobStore>,
+    cache: TrieBlobCache,
+}
+
+impl ExternalTrieBlobs {
+    pub fn new(store: Box<dyn TrieBlobStore>, cache: TrieBlobCache) -> ExternalTrieBlobs {
+        ExternalTrieBlobs { store, cache }
+    }
+
+    pub fn cache(&self) -> &TrieBlobCache {
+        &self.cache
+    }
+
+    /// Open the offloaded trie blob for the block with hex hash `name`, fetching it into the
+    /// cache if it isn't there already.  A fetched blob is only accepted (and cached) if its
+    /// hash is `expected_hash`, the hash recorded when it was offloaded.
+    pub fn open_blob(&self, name: &str, expected_hash: &str) -> Result<fs::File, Error> {
+        if let Some(f) = self.cache.open(name) {
+            return Ok(f);
+        }
+        debug!("Fetch offloaded trie blob {}", name);
+        let data = self.store.get_blob(name)?;
+        let hash = trie_blob_hash(&data);
+        if hash != expected_hash {
+            return Err(Error::CorruptionError(format!(
+                "Offloaded trie blob {} has hash {}, but {} was offloaded",
+                name, &hash, expected_hash
+            )));
+        }
+        self.cache.insert(name, &data)
+    }
+
+    /// Upload the trie blob for the block with hex hash `name`, and read it back to make sure
+    /// it was stored intact (since the local copy is about to be discarded)
+    pub fn offload_blob(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        self.store.put_blob(name, data)?;
+        let stored = self.store.get_blob(name)?;
+        if stored != data {
+            return Err(Error::CorruptionError(format!(
+                "Offloaded trie blob {} does not match the local copy ({} != {} bytes)",
+                name,
+                stored.len(),
+                data.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection};
+
+    use net::tls::pem_decode;
+    use net::tls::test::{TEST_CA_CERT, TEST_SERVER_CERT, TEST_SERVER_KEY};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = PathBuf::from(format!("/tmp/stacks-test-blobs-{}", name));
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_sigv4_signature() {
+        // `get-vanilla` from the AWS Signature Version 4 test suite
+        let (signed_headers, signature) = sigv4_signature(
+            "GET",
+            "/",
+            "",
+            &[
+                ("host", "example.amazonaws.com".to_string()),
+                ("x-amz-date", "20150830T123600Z".to_string()),
+            ],
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "us-east-1",
+            "service",
+            "20150830T123600Z",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+        );
+        assert_eq!(signed_headers, "host;x-amz-date");
+        assert_eq!(
+            signature,
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_parse_http_response() {
+        let (status, body) =
+            parse_http_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"hello".to_vec());
+
+        // no content-length -- read to close
+        let (status, body) =
+            parse_http_response(b"HTTP/1.1 404 Not Found\r\nServer: test\r\n\r\nnope").unwrap();
+        assert_eq!(status, 404);
+        assert_eq!(body, b"nope".to_vec());
+
+        assert!(
+            parse_http_response(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello").is_err()
+        );
+        assert!(parse_http_response(b"HTTP/1.1 200 OK\r\n").is_err());
+        assert!(parse_http_response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_directory_blob_store() {
+        let store = DirectoryBlobStore::new(test_dir("directory")).unwrap();
+        match store.get_blob("aa") {
+            Err(Error::NotFoundError) => {}
+            x => panic!("Expected NotFoundError, got {:?}", &x),
+        }
+        store.put_blob("aa", &[1, 2, 3]).unwrap();
+        assert_eq!(store.get_blob("aa").unwrap(), vec![1, 2, 3]);
+        store.put_blob("aa", &[4, 5]).unwrap();
+        assert_eq!(store.get_blob("aa").unwrap(), vec![4, 5]);
+    }
+
+    #[test]
+    fn test_s3_blob_store() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server_certs = pem_decode(TEST_SERVER_CERT, &["CERTIFICATE"])
+            .unwrap()
+            .into_iter()
+            .map(Certificate)
+            .collect();
+        let server_key =
+            PrivateKey(pem_decode(TEST_SERVER_KEY, &["PRIVATE KEY"]).unwrap()[0].clone());
+        let server_config = Arc::new(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(server_certs, server_key)
+                .unwrap(),
+        );
+
+        // minimal S3: one request per TLS connection, objects kept in memory
+        let server = thread::spawn(move || {
+            let mut objects: HashMap<String, Vec<u8>> = HashMap::new();
+            let mut requests = vec![];
+            for _ in 0..3 {
+                let (sock, _) = listener.accept().unwrap();
+                let session = ServerConnection::new(server_config.clone()).unwrap();
+                let mut sock = StreamOwned::new(session, sock);
+                let mut buf = vec![];
+                let mut chunk = [0u8; 4096];
+                let (preamble, body) = loop {
+                    let nr = sock.read(&mut chunk).unwrap();
+                    buf.extend_from_slice(&chunk[0..nr]);
+                    if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        let preamble = String::from_utf8(buf[0..end].to_vec()).unwrap();
+                        let len: usize = preamble
+                            .lines()
+                            .find(|l| l.starts_with("Content-Length:"))
+                            .map(|l| l["Content-Length:".len()..].trim().parse().unwrap())
+                            .unwrap();
+                        while buf.len() < end + 4 + len {
+                            let nr = sock.read(&mut chunk).unwrap();
+                            buf.extend_from_slice(&chunk[0..nr]);
+                        }
+                        break (preamble, buf[(end + 4)..(end + 4 + len)].to_vec());
+                    }
+                };
+
+                let mut request_line = preamble.lines().next().unwrap().split_whitespace();
+                let method = request_line.next().unwrap().to_string();
+                let path = request_line.next().unwrap().to_string();
+                let response = match (method.as_str(), objects.get(&path)) {
+                    ("PUT", _) => {
+                        objects.insert(path.clone(), body);
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".to_vec()
+                    }
+                    ("GET", Some(data)) => {
+                        let mut response =
+                            format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len())
+                                .into_bytes();
+                        response.extend_from_slice(data);
+                        response
+                    }
+                    _ => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                };
+                sock.write_all(&response).unwrap();
+                sock.conn.send_close_notify();
+                sock.flush().unwrap();
+                requests.push((method, path, preamble));
+            }
+            requests
+        });
+
+        let dir = test_dir("s3");
+        fs::create_dir_all(&dir).unwrap();
+        let ca_file = dir.join("ca.pem");
+        fs::write(&ca_file, TEST_CA_CERT).unwrap();
+
+        let store = S3BlobStore::new(
+            blob_store_tls_config(ca_file.to_str().unwrap()).unwrap(),
+            "localhost",
+            port,
+            "archive",
+            "/mainnet/",
+            "us-east-1",
+            "AKIDEXAMPLE",
+            "secret",
+        );
+        match store.get_blob("aabb") {
+            Err(Error::NotFoundError) => {}
+            x => panic!("Expected NotFoundError, got {:?}", &x),
+        }
+        store.put_blob("aabb", &[9u8; 10000]).unwrap();
+        assert_eq!(store.get_blob("aabb").unwrap(), vec![9u8; 10000]);
+
+        let requests = server.join().unwrap();
+        for (_, path, preamble) in requests.iter() {
+            assert_eq!(path, "/archive/mainnet/aabb");
+            assert!(preamble.contains("Authorization: AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+            assert!(preamble.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date"));
+        }
+        assert_eq!(requests[1].0, "PUT");
+    }
+
+    #[test]
+    fn test_open_blob_store() {
+        assert!(open_blob_store("s3+http://localhost:9000/archive").is_err());
+        assert!(open_blob_store("ftp://localhost/archive").is_err());
+
+        let dir = test_dir("open");
+        assert!(open_blob_store(&format!("file://{}", dir.to_str().unwrap())).is_ok());
+    }
+
+    #[test]
+    fn test_blob_cache_lru() {
+        let dir = test_dir("cache-lru");
+        let cache = TrieBlobCache::new(&dir, 250).unwrap();
+
+        cache.insert("a", &[1u8; 100]).unwrap();
+        cache.insert("b", &[2u8; 100]).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        // `a` is now more recently used than `b`, so `b` gets evicted
+        assert!(cache.open("a").is_some());
+        cache.insert("c", &[3u8; 100]).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.total_bytes(), 200);
+        assert!(cache.open("b").is_none());
+        assert!(fs::metadata(dir.join("b.blob")).is_err());
+
+        let mut data = vec![];
+        cache.open("a").unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1u8; 100]);
+
+        // a blob bigger than the cache is kept until the next insert
+        cache.insert("d", &[4u8; 300]).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(cache.open("d").is_some());
+
+        // reopening picks up what's on disk, within the new budget
+        drop(cache);
+        let cache = TrieBlobCache::new(&dir, 1000).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.total_bytes(), 300);
+        let cache = TrieBlobCache::new(&dir, 100).unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_external_blobs() {
+        let store = DirectoryBlobStore::new(test_dir("external-store")).unwrap();
+        let cache = TrieBlobCache::new(test_dir("external-cache"), 1000).unwrap();
+        let blobs = ExternalTrieBlobs::new(Box::new(store), cache);
+
+        blobs.offload_blob("aa", &[1, 2, 3]).unwrap();
+        blobs.offload_blob("cc", &[4, 5, 6]).unwrap();
+        assert!(blobs.cache().is_empty());
+
+        // a blob that doesn't match its recorded hash is refused, and not cached
+        match blobs.open_blob("cc", &trie_blob_hash(&[4, 5, 7])) {
+            Err(Error::CorruptionError(_)) => {}
+            x => panic!("Expected CorruptionError, got {:?}", &x.map(|_| ())),
+        }
+        assert!(blobs.cache().is_empty());
+
+        let mut data = vec![];
+        blobs
+            .open_blob("aa", &trie_blob_hash(&[1, 2, 3]))
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+        assert_eq!(blobs.cache().len(), 1);
+
+        match blobs.open_blob("bb", &trie_blob_hash(&[])) {
+            Err(Error::NotFoundError) => {}
+            x => panic!("Expected NotFoundError, got {:?}", &x.map(|_| ())),
+        }
+    }
+}
@@ -21,11 +21,13 @@ use std::io;
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::ops::DerefMut;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use rusqlite::{Connection, Transaction};
 use sha2::Digest;
 
 use chainstate::stacks::index::bits::{get_leaf_hash, get_node_hash, read_root_hash};
+use chainstate::stacks::index::blobs::ExternalTrieBlobs;
 use chainstate::stacks::index::node::{
     clear_backptr, is_backptr, set_backptr, CursorError, TrieCursor, TrieNode, TrieNode16,
     TrieNode256, TrieNode4, TrieNode48, TrieNodeID, TrieNodeType, TriePath, TriePtr, TRIEPTR_SIZE,
@@ -49,6 +51,15 @@ pub const BLOCK_HASH_TO_HEIGHT_MAPPING_KEY: &str = "__MARF_BLOCK_HASH_TO_HEIGHT"
 pub const BLOCK_HEIGHT_TO_HASH_MAPPING_KEY: &str = "__MARF_BLOCK_HEIGHT_TO_HASH";
 pub const OWN_BLOCK_HEIGHT_KEY: &str = "__MARF_BLOCK_HEIGHT_SELF";
 
+/// A key written by a block, as found by `MARF::get_block_diff()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MARFDiffEntry {
+    pub path: TriePath,
+    pub value: MARFValue,
+    /// The key's value in the block's parent, if it had one
+    pub prior_value: Option<MARFValue>,
+}
+
 /// Merklized Adaptive-Radix Forest -- a collection of Merklized Adaptive-Radix Tries.
 pub struct MARF<T: MarfTrieId> {
     storage: TrieFileStorage<T>,
@@ -105,6 +116,11 @@ pub trait MarfConnection<T: MarfTrieId> {
         self.with_conn(|c| MARF::get_block_height(c, ancestor, tip))
     }
 
+    /// Find the keys written by the given block
+    fn get_block_diff(&mut self, block_hash: &T) -> Result<Vec<MARFDiffEntry>, Error> {
+        self.with_conn(|c| MARF::get_block_diff(c, block_hash))
+    }
+
     /// Get the current root trie hash
     fn get_root_hash(&mut self) -> Result<TrieHash, Error> {
         self.with_conn(|c| read_root_hash(c))
@@ -1029,6 +1045,12 @@ impl<T: MarfTrieId> MARF<T> {
         Ok(MARF::from_storage(file_storage))
     }
 
+    /// Instantiate a read-only MARF from an existing database on disk, which is never written to.
+    pub fn from_path_readonly(path: &str) -> Result<MARF<T>, Error> {
+        let file_storage = TrieFileStorage::open_readonly(path)?;
+        Ok(MARF::from_storage(file_storage))
+    }
+
     /// Instantiate an unconfirmed MARF using a TrieFileStorage instance, from the given path on disk.
     /// This will have the side-effect of instantiating a new fork table from the tries encoded on
     /// disk. Performant code should call this method sparingly.
@@ -1137,6 +1159,116 @@ impl<T: MarfTrieId> MARF<T> {
             .map(|option_result| option_result.map(T::from))
     }
 
+    /// Find the leaves written by the given block's trie, i.e. the keys whose values the block
+    /// changed.  Leaves that the trie only copied forward from an ancestor (such as when a node is
+    /// split) and the MARF's own block-height bookkeeping keys are not reported.  Each entry
+    /// carries the value the key had in the block's parent, if any.  Entries are ordered by path.
+    pub fn get_block_diff(
+        storage: &mut TrieStorageConnection<T>,
+        block_hash: &T,
+    ) -> Result<Vec<MARFDiffEntry>, Error> {
+        let (cur_block_hash, cur_block_id) = storage.get_cur_block_and_id();
+
+        let result = MARF::inner_get_block_diff(storage, block_hash);
+
+        // restore
+        storage.open_block_maybe_id(&cur_block_hash, cur_block_id)?;
+
+        result
+    }
+
+    fn inner_get_block_diff(
+        storage: &mut TrieStorageConnection<T>,
+        block_hash: &T,
+    ) -> Result<Vec<MARFDiffEntry>, Error> {
+        let height =
+            MARF::get_block_height(storage, block_hash, block_hash)?.ok_or(Error::NotFoundError)?;
+        let parent_block_hash = if height > 0 {
+            MARF::get_block_at_height(storage, height - 1, block_hash)?
+        } else {
+            None
+        };
+
+        // paths of the keys set by set_block_heights() for this block
+        let mut bookkeeping_paths = vec![
+            TriePath::from_key(OWN_BLOCK_HEIGHT_KEY),
+            TriePath::from_key(&format!("{}::{}", BLOCK_HEIGHT_TO_HASH_MAPPING_KEY, height)),
+            TriePath::from_key(&format!(
+                "{}::{}",
+                BLOCK_HASH_TO_HEIGHT_MAPPING_KEY, block_hash
+            )),
+        ];
+        if let Some(ref parent_block_hash) = parent_block_hash {
+            bookkeeping_paths.push(TriePath::from_key(&format!(
+                "{}::{}",
+                BLOCK_HEIGHT_TO_HASH_MAPPING_KEY,
+                height - 1
+            )));
+            bookkeeping_paths.push(TriePath::from_key(&format!(
+                "{}::{}",
+                BLOCK_HASH_TO_HEIGHT_MAPPING_KEY, parent_block_hash
+            )));
+        }
+
+        // collect every leaf stored in this block's trie.  Back-pointers lead to nodes stored in
+        // ancestor tries, so they are not followed.
+        storage.open_block(block_hash)?;
+        let (root, _) = Trie::read_root(storage)?;
+        let mut leaves = vec![];
+        let mut frontier = vec![(root, vec![])];
+        while let Some((node, mut path_bytes)) = frontier.pop() {
+            path_bytes.extend_from_slice(node.path_bytes());
+            if let TrieNodeType::Leaf(leaf) = node {
+                let path = TriePath::from_bytes(&path_bytes).ok_or_else(|| {
+                    Error::CorruptionError(format!(
+                        "Leaf path has {} bytes, expected 32",
+                        path_bytes.len()
+                    ))
+                })?;
+                leaves.push((path, leaf.data));
+                continue;
+            }
+
+            for ptr in node.ptrs().iter() {
+                if ptr.id() == TrieNodeID::Empty as u8 || is_backptr(ptr.id()) {
+                    continue;
+                }
+                let (child, _) = storage.read_nodetype(ptr)?;
+                let mut child_path_bytes = path_bytes.clone();
+                child_path_bytes.push(ptr.chr());
+                frontier.push((child, child_path_bytes));
+            }
+        }
+        leaves.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+        let mut diff = vec![];
+        for (path, value) in leaves.into_iter() {
+            if bookkeeping_paths.contains(&path) {
+                continue;
+            }
+            let prior_value = match parent_block_hash {
+                Some(ref parent_block_hash) => {
+                    match MARF::get_path(storage, parent_block_hash, &path) {
+                        Ok(leaf_opt) => leaf_opt.map(|leaf| leaf.data),
+                        Err(Error::NotFoundError) => None,
+                        Err(e) => return Err(e),
+                    }
+                }
+                None => None,
+            };
+            if prior_value.as_ref() == Some(&value) {
+                // copied forward, not written
+                continue;
+            }
+            diff.push(MARFDiffEntry {
+                path,
+                value,
+                prior_value,
+            });
+        }
+        Ok(diff)
+    }
+
     /// Make an unconfirmed chain tip from an existing chain tip, so that it won't conflict with
     /// the "true" chain tip after the state it represents is later reprocessed and confirmed.
     pub fn make_unconfirmed_chain_tip(chain_tip: &T) -> T {
@@ -1234,6 +1366,24 @@ impl<T: MarfTrieId> MARF<T> {
         Ok(Some((marf_value, proof)))
     }
 
+    /// Like get_with_proof(), but also return the blocks whose tries the proof passes through,
+    /// starting with block_hash.  These are the blocks whose trie root hashes a verifier needs.
+    pub fn get_with_proof_and_blocks(
+        &mut self,
+        block_hash: &T,
+        key: &str,
+    ) -> Result<Option<(MARFValue, TrieMerkleProof<T>, Vec<T>)>, Error> {
+        let mut conn = self.storage.connection();
+        let marf_value = match MARF::get_by_key(&mut conn, block_hash, key)? {
+            None => return Ok(None),
+            Some(x) => x,
+        };
+        let path = TriePath::from_key(key);
+        let (proof, blocks) =
+            TrieMerkleProof::from_path_with_blocks(&mut conn, &path, &marf_value, block_hash)?;
+        Ok(Some((marf_value, proof, blocks)))
+    }
+
     pub fn get_bhh_at_height(&mut self, block_hash: &T, height: u32) -> Result<Option<T>, Error> {
         MARF::get_block_at_height(&mut self.storage.connection(), height, block_hash)
     }
@@ -1417,6 +1567,22 @@ impl<T: MarfTrieId> MARF<T> {
         self.storage.transaction().unwrap()
     }
 
+    /// Use `external_blobs` for this MARF's offloaded tries (see
+    /// `TrieFileStorage::set_external_blobs`)
+    pub fn set_external_blobs(&mut self, external_blobs: Option<Arc<ExternalTrieBlobs>>) {
+        self.storage.set_external_blobs(external_blobs)
+    }
+
+    pub fn external_blobs(&self) -> Option<&Arc<ExternalTrieBlobs>> {
+        self.storage.external_blobs()
+    }
+
+    /// Offload all but the `keep_local` most recently stored tries to this MARF's external blob
+    /// store.  Returns the number of tries offloaded.
+    pub fn offload_tries(&mut self, keep_local: u32) -> Result<u32, Error> {
+        self.storage.offload_tries(keep_local)
+    }
+
     /// Make a raw transaction to the underlying storage
     pub fn storage_tx<'a>(&'a mut self) -> Result<Transaction<'a>, db_error> {
         self.storage.sqlite_tx()
@@ -1465,12 +1631,14 @@ mod test {
     use std::io::Cursor;
 
     use chainstate::stacks::index::bits::*;
+    use chainstate::stacks::index::blobs::*;
     use chainstate::stacks::index::marf::*;
     use chainstate::stacks::index::node::*;
     use chainstate::stacks::index::proofs::*;
     use chainstate::stacks::index::storage::*;
     use chainstate::stacks::index::test::*;
     use chainstate::stacks::index::trie::*;
+    use chainstate::stacks::index::trie_sql;
     use util::get_epoch_time_ms;
     use util::hash::to_hex;
 
@@ -3612,4 +3780,182 @@ mod test {
             assert!(false);
         }
     }
+
+    #[test]
+    fn marf_offload_tries() {
+        let path = "/tmp/test-marf-offload.sqlite".to_string();
+        let store_dir = "/tmp/test-marf-offload-store";
+        let cache_dir = "/tmp/test-marf-offload-cache";
+        if fs::metadata(&path).is_ok() {
+            fs::remove_file(&path).unwrap();
+        }
+        for dir in [store_dir, cache_dir].iter() {
+            if fs::metadata(dir).is_ok() {
+                fs::remove_dir_all(dir).unwrap();
+            }
+        }
+
+        // small enough that reading every trie back will evict some of them
+        let cache = TrieBlobCache::new(cache_dir, 4096).unwrap();
+        let store = DirectoryBlobStore::new(store_dir).unwrap();
+
+        let mut marf = MARF::from_path(&path).unwrap();
+        marf.set_external_blobs(Some(Arc::new(ExternalTrieBlobs::new(
+            Box::new(store),
+            cache,
+        ))));
+        let mut block_hashes = vec![];
+        let mut root_hashes = vec![];
+        let mut parent = BlockHeaderHash::sentinel();
+        for i in 0..10u8 {
+            let block_hash = BlockHeaderHash([i + 1; 32]);
+            marf.begin(&parent, &block_hash).unwrap();
+            for j in 0..16 {
+                marf.insert(
+                    &format!("key-{}-{}", i, j),
+                    MARFValue::from_value(&format!("value-{}-{}", i, j)),
+                )
+                .unwrap();
+            }
+            marf.commit().unwrap();
+
+            root_hashes.push(marf.get_root_hash_at(&block_hash).unwrap());
+            block_hashes.push(block_hash);
+            parent = block_hash;
+        }
+
+        assert_eq!(marf.offload_tries(3).unwrap(), 7);
+        assert_eq!(marf.offload_tries(3).unwrap(), 0);
+        for (i, block_hash) in block_hashes.iter().enumerate() {
+            let offloaded = fs::metadata(format!("{}/{}", store_dir, block_hash)).is_ok();
+            assert_eq!(offloaded, i < 7);
+        }
+
+        // without the blob store, offloaded tries can't be read
+        let root_ptr = TriePtr::new(
+            TrieNodeID::Node256 as u8,
+            0,
+            TrieStorageConnection::<BlockHeaderHash>::root_ptr_disk(),
+        );
+        match trie_sql::get_node_hash_bytes_by_bhh(
+            marf.sqlite_conn(),
+            None,
+            &block_hashes[0],
+            &root_ptr,
+        ) {
+            Err(Error::NoBlobStoreError) => {}
+            x => panic!("Expected NoBlobStoreError, got {:?}", &x),
+        }
+
+        // with it, everything reads the same as before
+        for (i, block_hash) in block_hashes.iter().enumerate() {
+            assert_eq!(marf.get_root_hash_at(block_hash).unwrap(), root_hashes[i]);
+            for k in 0..=i {
+                for j in 0..16 {
+                    let (value, _) = marf
+                        .get_with_proof(block_hash, &format!("key-{}-{}", k, j))
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(value, MARFValue::from_value(&format!("value-{}-{}", k, j)));
+                }
+            }
+        }
+
+        // and the MARF can still be extended
+        let block_hash = BlockHeaderHash([11; 32]);
+        marf.begin(&parent, &block_hash).unwrap();
+        marf.insert("key-10", MARFValue::from_value("value-10"))
+            .unwrap();
+        marf.commit().unwrap();
+        let (value, _) = marf
+            .get_with_proof(&block_hash, "key-0-0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, MARFValue::from_value("value-0-0"));
+        drop(marf);
+
+        // a blob that changed in the store is refused when it is fetched back
+        let tampered_path = format!("{}/{}", store_dir, &block_hashes[0]);
+        let mut tampered = fs::read(&tampered_path).unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+        fs::write(&tampered_path, &tampered).unwrap();
+
+        let fresh_cache_dir = "/tmp/test-marf-offload-cache-fresh";
+        if fs::metadata(fresh_cache_dir).is_ok() {
+            fs::remove_dir_all(fresh_cache_dir).unwrap();
+        }
+        let mut marf: MARF<BlockHeaderHash> = MARF::from_path(&path).unwrap();
+        marf.set_external_blobs(Some(Arc::new(ExternalTrieBlobs::new(
+            Box::new(DirectoryBlobStore::new(store_dir).unwrap()),
+            TrieBlobCache::new(fresh_cache_dir, 4096).unwrap(),
+        ))));
+        match marf.get_root_hash_at(&block_hashes[0]) {
+            Err(Error::CorruptionError(_)) => {}
+            x => panic!("Expected CorruptionError, got {:?}", &x),
+        }
+        assert_eq!(
+            marf.get_root_hash_at(&block_hashes[1]).unwrap(),
+            root_hashes[1]
+        );
+    }
+
+    #[test]
+    fn test_marf_block_diff() {
+        let f = TrieFileStorage::new_memory().unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        let block_1 = BlockHeaderHash([1; 32]);
+        let block_2 = BlockHeaderHash([2; 32]);
+
+        let keys: Vec<String> = (0..64).map(|i| format!("key-{}", i)).collect();
+        let values: Vec<MARFValue> = (0..64)
+            .map(|i| MARFValue::from_value(&format!("value-{}", i)))
+            .collect();
+
+        marf.begin(&BlockHeaderHash::sentinel(), &block_1).unwrap();
+        marf.insert_batch(&keys, values.clone()).unwrap();
+        marf.commit().unwrap();
+
+        // rewriting a key with its existing value is not a change
+        marf.begin(&block_1, &block_2).unwrap();
+        marf.insert("key-1", MARFValue::from_value("value-1-changed"))
+            .unwrap();
+        marf.insert("key-2", MARFValue::from_value("value-2"))
+            .unwrap();
+        marf.insert("key-new", MARFValue::from_value("value-new"))
+            .unwrap();
+        marf.commit().unwrap();
+
+        let diff_1 = marf.get_block_diff(&block_1).unwrap();
+        assert_eq!(diff_1.len(), 64);
+        for (key, value) in keys.iter().zip(values.iter()) {
+            let entry = diff_1
+                .iter()
+                .find(|entry| entry.path == TriePath::from_key(key))
+                .unwrap();
+            assert_eq!(&entry.value, value);
+            assert_eq!(entry.prior_value, None);
+        }
+
+        let mut expected_2 = vec![
+            MARFDiffEntry {
+                path: TriePath::from_key("key-1"),
+                value: MARFValue::from_value("value-1-changed"),
+                prior_value: Some(MARFValue::from_value("value-1")),
+            },
+            MARFDiffEntry {
+                path: TriePath::from_key("key-new"),
+                value: MARFValue::from_value("value-new"),
+                prior_value: None,
+            },
+        ];
+        expected_2.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()));
+        assert_eq!(marf.get_block_diff(&block_2).unwrap(), expected_2);
+
+        match marf.get_block_diff(&BlockHeaderHash([3; 32])) {
+            Err(Error::NotFoundError) => {}
+            x => panic!("Expected NotFoundError, got {:?}", &x),
+        }
+    }
 }
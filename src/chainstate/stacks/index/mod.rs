@@ -35,6 +35,7 @@ use crate::types::chainstate::{BurnchainHeaderHash, MARFValue, MARF_VALUE_ENCODE
 use crate::types::proof::{ClarityMarfTrieId, TrieHash, TRIEHASH_ENCODED_SIZE};
 
 pub mod bits;
+pub mod blobs;
 pub mod marf;
 pub mod node;
 pub mod proofs;
@@ -250,6 +251,7 @@ pub enum Error {
     CursorError(node::CursorError),
     RestoreMarfBlockError(Box<Error>),
     NonMatchingForks([u8; 32], [u8; 32]),
+    NoBlobStoreError,
 }
 
 impl From<io::Error> for Error {
@@ -315,6 +317,9 @@ impl fmt::Display for Error {
             Error::RequestedIdentifierForExtensionTrie => {
                 write!(f, "BUG: MARF requested the identifier for a RAM trie")
             }
+            Error::NoBlobStoreError => {
+                write!(f, "No external blob store is configured for this MARF")
+            }
         }
     }
 }
@@ -117,6 +117,12 @@ define_u8_enum!( TrieMerkleProofTypeIndicator {
     Node4 = 0, Node16 = 1, Node48 = 2, Node256 = 3, Leaf = 4, Shunt = 5
 });
 
+impl<T: MarfTrieId> PartialEq for TrieMerkleProof<T> {
+    fn eq(&self, other: &TrieMerkleProof<T>) -> bool {
+        self.0 == other.0
+    }
+}
+
 impl<T: MarfTrieId> PartialEq for TrieMerkleProofType<T> {
     fn eq(&self, other: &TrieMerkleProofType<T>) -> bool {
         match (self, other) {
@@ -1370,14 +1376,30 @@ impl<T: MarfTrieId> TrieMerkleProof<T> {
         expected_value: &MARFValue,
         root_block_header: &T,
     ) -> Result<TrieMerkleProof<T>, Error> {
+        TrieMerkleProof::from_path_with_blocks(storage, path, expected_value, root_block_header)
+            .map(|(proof, _)| proof)
+    }
+
+    /// Make a merkle proof of inclusion from a path, and also return the blocks whose tries the
+    /// proof passes through, starting with root_block_header.  A verifier needs the trie root
+    /// hash of each of these blocks (see verify()).
+    /// If the path doesn't resolve, return an error (NotFoundError)
+    pub fn from_path_with_blocks(
+        storage: &mut TrieStorageConnection<T>,
+        path: &TriePath,
+        expected_value: &MARFValue,
+        root_block_header: &T,
+    ) -> Result<(TrieMerkleProof<T>, Vec<T>), Error> {
         // accumulate proofs in reverse order -- each proof will be from an earlier and earlier
         // trie, so we'll reverse them in the end so the proof starts with the latest trie.
         let mut segment_proofs = vec![];
         let mut shunt_proofs = vec![];
         let mut block_header = root_block_header.clone();
+        let mut blocks = vec![];
 
         loop {
             storage.open_block(&block_header)?;
+            blocks.push(block_header.clone());
 
             trace!(
                 "Walk {:?} path {:?} to leaf or backptr",
@@ -1478,7 +1500,7 @@ impl<T: MarfTrieId> TrieMerkleProof<T> {
             proof.append(&mut shunt_proofs[i]);
         }
 
-        Ok(TrieMerkleProof(proof))
+        Ok((TrieMerkleProof(proof), blocks))
     }
 
     /// Make a merkle proof of inclusion from a key/value pair.
@@ -26,6 +26,7 @@ use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::os;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{cmp, error};
 
 use regex::Regex;
@@ -39,6 +40,7 @@ use chainstate::stacks::index::bits::{
     get_node_byte_len, get_node_hash, read_block_identifier, read_hash_bytes, read_node_hash_bytes,
     read_nodetype, read_root_hash, write_nodetype_bytes,
 };
+use chainstate::stacks::index::blobs::{self, ExternalTrieBlobs};
 use chainstate::stacks::index::node::{
     clear_backptr, is_backptr, set_backptr, TrieNode, TrieNode16, TrieNode256, TrieNode4,
     TrieNode48, TrieNodeID, TrieNodeType, TriePath, TriePtr,
@@ -610,6 +612,7 @@ impl<T: MarfTrieId> NodeHashReader for TrieRAM<T> {
 
 pub struct TrieSqlCursor<'a> {
     db: &'a Connection,
+    external_blobs: Option<&'a ExternalTrieBlobs>,
     block_id: u32,
 }
 
@@ -620,7 +623,7 @@ pub struct TrieSqlHashMapCursor<'a, T: MarfTrieId> {
 
 impl NodeHashReader for TrieSqlCursor<'_> {
     fn read_node_hash_bytes<W: Write>(&mut self, ptr: &TriePtr, w: &mut W) -> Result<(), Error> {
-        trie_sql::read_node_hash_bytes(self.db, w, self.block_id, ptr)
+        trie_sql::read_node_hash_bytes(self.db, self.external_blobs, w, self.block_id, ptr)
     }
 }
 
@@ -671,6 +674,7 @@ pub struct TrieStorageConnection<'a, T: MarfTrieId> {
     pub db_path: &'a str,
     db: SqliteConnection<'a>,
     data: &'a mut TrieStorageTransientData<T>,
+    external_blobs: Option<Arc<ExternalTrieBlobs>>,
 
     // used in testing in order to short-circuit block-height lookups
     //   when the trie struct is tested outside of marf.rs usage
@@ -717,6 +721,9 @@ pub struct TrieFileStorage<T: MarfTrieId> {
     db: Connection,
     data: TrieStorageTransientData<T>,
 
+    /// where this MARF's offloaded tries are stored, if it has any
+    external_blobs: Option<Arc<ExternalTrieBlobs>>,
+
     // used in testing in order to short-circuit block-height lookups
     //   when the trie struct is tested outside of marf.rs usage
     #[cfg(test)]
@@ -739,6 +746,7 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
             db: SqliteConnection::ConnRef(&self.db),
             db_path: &self.db_path,
             data: &mut self.data,
+            external_blobs: self.external_blobs.clone(),
 
             #[cfg(test)]
             test_genesis_block: &mut self.test_genesis_block,
@@ -755,6 +763,7 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
             db: SqliteConnection::Tx(tx),
             db_path: &self.db_path,
             data: &mut self.data,
+            external_blobs: self.external_blobs.clone(),
 
             #[cfg(test)]
             test_genesis_block: &mut self.test_genesis_block,
@@ -814,6 +823,9 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
         if create_flag {
             trie_sql::create_tables_if_needed(&mut db)?;
         }
+        if !readonly {
+            trie_sql::migrate_tables_if_needed(&mut db)?;
+        }
 
         debug!("Opened TrieFileStorage {};", db_path);
 
@@ -842,6 +854,8 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
                 unconfirmed: unconfirmed,
             },
 
+            external_blobs: None,
+
             // used in testing in order to short-circuit block-height lookups
             //   when the trie struct is tested outside of marf.rs usage
             #[cfg(test)]
@@ -872,6 +886,16 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
         self.data.readonly
     }
 
+    /// Use `external_blobs` for this MARF's offloaded tries.  Read-only views made from this
+    /// storage with `reopen_readonly()` share it.
+    pub fn set_external_blobs(&mut self, external_blobs: Option<Arc<ExternalTrieBlobs>>) {
+        self.external_blobs = external_blobs;
+    }
+
+    pub fn external_blobs(&self) -> Option<&Arc<ExternalTrieBlobs>> {
+        self.external_blobs.as_ref()
+    }
+
     pub fn unconfirmed(&self) -> bool {
         self.data.unconfirmed
     }
@@ -907,6 +931,8 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
                 unconfirmed: true,
             },
 
+            external_blobs: self.external_blobs.clone(),
+
             // used in testing in order to short-circuit block-height lookups
             //   when the trie struct is tested outside of marf.rs usage
             #[cfg(test)]
@@ -915,6 +941,38 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
 
         Ok(ret)
     }
+
+    /// Move all but the `keep_local` most recently stored confirmed tries to this MARF's external
+    /// blob store, and discard their local copies.  Each trie is uploaded outside of any
+    /// transaction, and then discarded in its own short transaction, so this can run for a long
+    /// time without blocking writers.  The space freed in the database file is reused for new
+    /// tries.  Returns the number of tries offloaded.
+    pub fn offload_tries(&mut self, keep_local: u32) -> Result<u32, Error> {
+        if self.readonly() {
+            return Err(Error::ReadOnlyError);
+        }
+        let external_blobs = self.external_blobs.clone().ok_or(Error::NoBlobStoreError)?;
+
+        let tries = trie_sql::get_offloadable_tries(&self.db, keep_local)?;
+        let mut offloaded = 0;
+        for (block_id, block_hash) in tries.into_iter() {
+            let data = trie_sql::read_trie_blob(&self.db, block_id)?;
+            external_blobs.offload_blob(&block_hash, &data)?;
+
+            let tx = tx_begin_immediate(&mut self.db)?;
+            trie_sql::clear_trie_blob(&tx, block_id, &blobs::trie_blob_hash(&data))?;
+            tx.commit()?;
+
+            debug!(
+                "Offloaded trie {} ({}, {} bytes)",
+                &block_hash,
+                block_id,
+                data.len()
+            );
+            offloaded += 1;
+        }
+        Ok(offloaded)
+    }
 }
 
 impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
@@ -954,6 +1012,8 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
                 unconfirmed: true,
             },
 
+            external_blobs: self.external_blobs.clone(),
+
             // used in testing in order to short-circuit block-height lookups
             //   when the trie struct is tested outside of marf.rs usage
             #[cfg(test)]
@@ -1307,7 +1367,12 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
             0,
             TrieStorageConnection::<T>::root_ptr_disk(),
         );
-        trie_sql::get_node_hash_bytes_by_bhh(&self.db, bhh, &root_hash_ptr)
+        trie_sql::get_node_hash_bytes_by_bhh(
+            &self.db,
+            self.external_blobs.as_deref(),
+            bhh,
+            &root_hash_ptr,
+        )
     }
 
     /// Generate a mapping between Trie root hashes and the blocks that contain them
@@ -1533,6 +1598,7 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
         // otherwise, the current block is open as an FD
         let mut cursor = TrieSqlCursor {
             db: &self.db,
+            external_blobs: self.external_blobs.as_deref(),
             block_id: self.data.cur_block_id.ok_or_else(|| {
                 error!("Failed to get cur block as hash reader");
                 Error::NotFoundError
@@ -1578,7 +1644,12 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
 
         // some other block or ptr, or cache miss
         match self.data.cur_block_id {
-            Some(block_id) => trie_sql::get_node_hash_bytes(&self.db, block_id, ptr),
+            Some(block_id) => trie_sql::get_node_hash_bytes(
+                &self.db,
+                self.external_blobs.as_deref(),
+                block_id,
+                ptr,
+            ),
             None => {
                 error!("Not found (no file is open)");
                 Err(Error::NotFoundError)
@@ -1610,7 +1681,9 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
 
         // some other block
         match self.data.cur_block_id {
-            Some(id) => trie_sql::read_node_type(&self.db, id, &clear_ptr),
+            Some(id) => {
+                trie_sql::read_node_type(&self.db, self.external_blobs.as_deref(), id, &clear_ptr)
+            }
             None => {
                 debug!("Not found (no file is open)");
                 Err(Error::NotFoundError)
@@ -42,6 +42,7 @@ use chainstate::stacks::index::bits::{
     get_node_byte_len, get_node_hash, read_block_identifier, read_hash_bytes,
     read_node_hash_bytes as bits_read_node_hash_bytes, read_nodetype, write_nodetype_bytes,
 };
+use chainstate::stacks::index::blobs::ExternalTrieBlobs;
 use chainstate::stacks::index::node::{
     clear_backptr, is_backptr, set_backptr, TrieNode, TrieNode16, TrieNode256, TrieNode4,
     TrieNode48, TrieNodeID, TrieNodeType, TriePath, TriePtr,
@@ -62,7 +63,9 @@ CREATE TABLE IF NOT EXISTS marf_data (
    block_id INTEGER PRIMARY KEY, 
    block_hash TEXT UNIQUE NOT NULL,
    data BLOB NOT NULL,
-   unconfirmed INTEGER NOT NULL
+   unconfirmed INTEGER NOT NULL,
+   -- hex SHA-256 of the trie's blob, if it has been offloaded to an external blob store
+   external_hash TEXT
 );
 
 CREATE INDEX IF NOT EXISTS block_hash_marf_data ON marf_data(block_hash);
@@ -92,6 +95,19 @@ pub fn create_tables_if_needed(conn: &mut Connection) -> Result<(), Error> {
     tx.commit().map_err(|e| e.into())
 }
 
+/// Add the columns introduced since the MARF's tables were first created
+pub fn migrate_tables_if_needed(conn: &mut Connection) -> Result<(), Error> {
+    let has_external_hash = conn
+        .prepare("SELECT 1 FROM pragma_table_info('marf_data') WHERE name = 'external_hash'")?
+        .exists(NO_PARAMS)?;
+    if !has_external_hash {
+        let tx = tx_begin_immediate(conn)?;
+        tx.execute_batch("ALTER TABLE marf_data ADD COLUMN external_hash TEXT")?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
 pub fn get_block_identifier<T: MarfTrieId>(conn: &Connection, bhh: &T) -> Result<u32, Error> {
     conn.query_row(
         "SELECT block_id FROM marf_data WHERE block_hash = ?",
@@ -258,95 +274,162 @@ pub fn read_all_block_hashes_and_roots<T: MarfTrieId>(
     rows.collect()
 }
 
-pub fn read_node_hash_bytes<W: Write>(
-    conn: &Connection,
-    w: &mut W,
+/// A reader over a confirmed trie's blob, wherever it is stored
+pub enum TrieBlobReader<'a> {
+    Local(Blob<'a>),
+    External(fs::File),
+}
+
+impl Read for TrieBlobReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            TrieBlobReader::Local(blob) => blob.read(buf),
+            TrieBlobReader::External(f) => f.read(buf),
+        }
+    }
+}
+
+impl Seek for TrieBlobReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            TrieBlobReader::Local(blob) => blob.seek(pos),
+            TrieBlobReader::External(f) => f.seek(pos),
+        }
+    }
+}
+
+/// Open a trie's blob for reading.  If the trie has been offloaded (i.e. its blob in `marf_data`
+/// is empty), it is read from `external_blobs` instead.
+pub fn open_trie_blob_readonly<'a>(
+    conn: &'a Connection,
+    external_blobs: Option<&ExternalTrieBlobs>,
     block_id: u32,
-    ptr: &TriePtr,
-) -> Result<(), Error> {
-    let mut blob = conn.blob_open(
+) -> Result<TrieBlobReader<'a>, Error> {
+    let blob = conn.blob_open(
         rusqlite::DatabaseName::Main,
         "marf_data",
         "data",
         block_id.into(),
         true,
     )?;
+    if blob.size() > 0 {
+        return Ok(TrieBlobReader::Local(blob));
+    }
+
+    let (block_hash, external_hash): (String, Option<String>) = conn.query_row(
+        "SELECT block_hash, external_hash FROM marf_data WHERE block_id = ?",
+        &[block_id],
+        |row| Ok((row.get("block_hash")?, row.get("external_hash")?)),
+    )?;
+    let external_hash = external_hash.ok_or_else(|| {
+        Error::CorruptionError(format!(
+            "Trie {} ({}) is empty, but was never offloaded",
+            &block_hash, block_id
+        ))
+    })?;
+    match external_blobs {
+        Some(external_blobs) => Ok(TrieBlobReader::External(
+            external_blobs.open_blob(&block_hash, &external_hash)?,
+        )),
+        None => {
+            error!(
+                "Trie {} ({}) has been offloaded, but there is no external blob store",
+                &block_hash, block_id
+            );
+            Err(Error::NoBlobStoreError)
+        }
+    }
+}
+
+pub fn read_node_hash_bytes<W: Write>(
+    conn: &Connection,
+    external_blobs: Option<&ExternalTrieBlobs>,
+    w: &mut W,
+    block_id: u32,
+    ptr: &TriePtr,
+) -> Result<(), Error> {
+    let mut blob = open_trie_blob_readonly(conn, external_blobs, block_id)?;
     let hash_buff = bits_read_node_hash_bytes(&mut blob, ptr)?;
     w.write_all(&hash_buff).map_err(|e| e.into())
 }
 
 pub fn read_node_hash_bytes_by_bhh<W: Write, T: MarfTrieId>(
     conn: &Connection,
+    external_blobs: Option<&ExternalTrieBlobs>,
     w: &mut W,
     bhh: &T,
     ptr: &TriePtr,
 ) -> Result<(), Error> {
-    let row_id: i64 = conn.query_row(
-        "SELECT block_id FROM marf_data WHERE block_hash = ?",
-        &[bhh],
-        |r| r.get("block_id"),
-    )?;
-    let mut blob = conn.blob_open(
-        rusqlite::DatabaseName::Main,
-        "marf_data",
-        "data",
-        row_id,
-        true,
-    )?;
-    let hash_buff = bits_read_node_hash_bytes(&mut blob, ptr)?;
-    w.write_all(&hash_buff).map_err(|e| e.into())
+    let block_id = get_block_identifier(conn, bhh)?;
+    read_node_hash_bytes(conn, external_blobs, w, block_id, ptr)
 }
 
 pub fn read_node_type(
     conn: &Connection,
+    external_blobs: Option<&ExternalTrieBlobs>,
     block_id: u32,
     ptr: &TriePtr,
 ) -> Result<(TrieNodeType, TrieHash), Error> {
-    let mut blob = conn.blob_open(
-        rusqlite::DatabaseName::Main,
-        "marf_data",
-        "data",
-        block_id.into(),
-        true,
-    )?;
+    let mut blob = open_trie_blob_readonly(conn, external_blobs, block_id)?;
     read_nodetype(&mut blob, ptr)
 }
 
 pub fn get_node_hash_bytes(
     conn: &Connection,
+    external_blobs: Option<&ExternalTrieBlobs>,
     block_id: u32,
     ptr: &TriePtr,
 ) -> Result<TrieHash, Error> {
-    let mut blob = conn.blob_open(
-        rusqlite::DatabaseName::Main,
-        "marf_data",
-        "data",
-        block_id.into(),
-        true,
-    )?;
+    let mut blob = open_trie_blob_readonly(conn, external_blobs, block_id)?;
     let hash_buff = bits_read_node_hash_bytes(&mut blob, ptr)?;
     Ok(TrieHash(hash_buff))
 }
 
 pub fn get_node_hash_bytes_by_bhh<T: MarfTrieId>(
     conn: &Connection,
+    external_blobs: Option<&ExternalTrieBlobs>,
     bhh: &T,
     ptr: &TriePtr,
 ) -> Result<TrieHash, Error> {
-    let row_id: i64 = conn.query_row(
-        "SELECT block_id FROM marf_data WHERE block_hash = ?",
-        &[bhh],
-        |r| r.get("block_id"),
+    let block_id = get_block_identifier(conn, bhh)?;
+    get_node_hash_bytes(conn, external_blobs, block_id, ptr)
+}
+
+/// Get the confirmed tries that are still stored locally, except for the `keep_local` most
+/// recently stored confirmed tries, in the order they were stored.
+pub fn get_offloadable_tries(
+    conn: &Connection,
+    keep_local: u32,
+) -> Result<Vec<(u32, String)>, Error> {
+    let mut s = conn.prepare(
+        "SELECT block_id, block_hash FROM marf_data WHERE unconfirmed = 0 AND length(data) > 0 AND block_id NOT IN \
+         (SELECT block_id FROM marf_data WHERE unconfirmed = 0 ORDER BY block_id DESC LIMIT ?) \
+         ORDER BY block_id ASC",
     )?;
-    let mut blob = conn.blob_open(
-        rusqlite::DatabaseName::Main,
-        "marf_data",
-        "data",
-        row_id,
-        true,
+    let rows = s.query_and_then(&[keep_local], |row| -> Result<(u32, String), Error> {
+        Ok((row.get("block_id")?, row.get("block_hash")?))
+    })?;
+    rows.collect()
+}
+
+pub fn read_trie_blob(conn: &Connection, block_id: u32) -> Result<Vec<u8>, Error> {
+    let data = conn.query_row(
+        "SELECT data FROM marf_data WHERE block_id = ?",
+        &[block_id],
+        |row| row.get("data"),
     )?;
-    let hash_buff = bits_read_node_hash_bytes(&mut blob, ptr)?;
-    Ok(TrieHash(hash_buff))
+    Ok(data)
+}
+
+/// Discard the local copy of a confirmed trie which has been offloaded, and record the hash of
+/// the offloaded copy so it can be checked whenever it is fetched back
+pub fn clear_trie_blob(conn: &Connection, block_id: u32, external_hash: &str) -> Result<(), Error> {
+    let args: &[&dyn ToSql] = &[&external_hash, &block_id];
+    conn.execute(
+        "UPDATE marf_data SET data = zeroblob(0), external_hash = ? WHERE block_id = ? AND unconfirmed = 0",
+        args,
+    )?;
+    Ok(())
 }
 
 pub fn tx_lock_bhh_for_extension<T: MarfTrieId>(
@@ -0,0 +1,719 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Construction and verification of microblock streams, independent of a running node.
+//!
+//! A microblock stream hangs off of an anchored block.  The anchored block commits to the hash of
+//! the public key that must sign each microblock in the stream (its `microblock_pubkey_hash`).
+//! The first microblock has sequence number 0 and points to the anchored block's hash, and each
+//! subsequent microblock has the next sequence number and points to its parent microblock's hash.
+//! A later anchored block confirms a prefix of the stream by naming its last microblock's hash and
+//! sequence number.
+//!
+//! `MicroblockStreamBuilder` produces well-formed, signed microblocks off-node, and
+//! `verify_microblock_stream` and `verify_confirmed_microblock_stream` check a stream without
+//! needing access to the chainstate.  Neither checks that the transactions are valid against the
+//! chain state -- only a node can do that.
+
+use std::error;
+use std::fmt;
+
+use chainstate::stacks::*;
+use net::Error as net_error;
+use util::hash::MerkleTree;
+use util::hash::Sha512Trunc256Sum;
+
+use crate::codec::{StacksMessageCodec, MAX_MESSAGE_LEN};
+use crate::types::chainstate::{BlockHeaderHash, StacksBlockHeader, StacksMicroblockHeader};
+
+/// Reasons why a microblock can't be built, or why a microblock stream is invalid.
+/// The `usize` in each variant is the index of the offending microblock in the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MicroblockStreamError {
+    /// The stream is empty
+    EmptyStream,
+    /// The microblock has no transactions
+    NoTransactions(usize),
+    /// The microblock's transactions can't go into a microblock, or are on the wrong network
+    InvalidTransactions(usize, String),
+    /// The microblock is too big
+    TooBig(usize, u64),
+    /// The microblock's header does not commit to its transactions
+    MerkleRootMismatch(usize),
+    /// The microblock was not signed by the key the anchored block committed to
+    BadSignature(usize, String),
+    /// The microblock's sequence number is not the expected one
+    BadSequence(usize, u16, u16),
+    /// The microblock's parent is not the expected one
+    BadParent(usize, BlockHeaderHash, BlockHeaderHash),
+    /// The stream can't be extended, since its last sequence number is u16::MAX
+    SequenceOverflow,
+    /// The anchored block does not confirm any microblock in this stream
+    NotConfirmed(BlockHeaderHash, u16),
+}
+
+impl fmt::Display for MicroblockStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MicroblockStreamError::EmptyStream => write!(f, "Empty microblock stream"),
+            MicroblockStreamError::NoTransactions(i) => {
+                write!(f, "Microblock {} has no transactions", i)
+            }
+            MicroblockStreamError::InvalidTransactions(i, ref msg) => {
+                write!(f, "Microblock {} has invalid transactions: {}", i, msg)
+            }
+            MicroblockStreamError::TooBig(i, len) => {
+                write!(f, "Microblock {} is too big ({} bytes)", i, len)
+            }
+            MicroblockStreamError::MerkleRootMismatch(i) => {
+                write!(f, "Microblock {} has a tx Merkle root mismatch", i)
+            }
+            MicroblockStreamError::BadSignature(i, ref msg) => {
+                write!(f, "Microblock {} has a bad signature: {}", i, msg)
+            }
+            MicroblockStreamError::BadSequence(i, expected, actual) => write!(
+                f,
+                "Microblock {} has sequence {}, but expected {}",
+                i, actual, expected
+            ),
+            MicroblockStreamError::BadParent(i, ref expected, ref actual) => write!(
+                f,
+                "Microblock {} has parent {}, but expected {}",
+                i, actual, expected
+            ),
+            MicroblockStreamError::SequenceOverflow => {
+                write!(f, "Microblock stream sequence overflow")
+            }
+            MicroblockStreamError::NotConfirmed(ref hash, seq) => write!(
+                f,
+                "Microblock stream does not contain confirmed microblock {} (sequence {})",
+                hash, seq
+            ),
+        }
+    }
+}
+
+impl error::Error for MicroblockStreamError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+/// Builds a signed microblock stream on top of an anchored block, one microblock at a time.
+pub struct MicroblockStreamBuilder {
+    /// hash of the anchored block this stream builds on
+    anchored_block_hash: BlockHeaderHash,
+    /// key that signs each microblock.  Its public key hash must be the anchored block's
+    /// `microblock_pubkey_hash`.
+    privk: StacksPrivateKey,
+    mainnet: bool,
+    chain_id: u32,
+    /// header of the last microblock built, if any
+    tip: Option<StacksMicroblockHeader>,
+}
+
+impl MicroblockStreamBuilder {
+    /// Start a new stream on top of the given anchored block.
+    pub fn new(
+        anchored_block_hash: &BlockHeaderHash,
+        privk: &StacksPrivateKey,
+        mainnet: bool,
+        chain_id: u32,
+    ) -> MicroblockStreamBuilder {
+        MicroblockStreamBuilder {
+            anchored_block_hash: anchored_block_hash.clone(),
+            privk: privk.clone(),
+            mainnet,
+            chain_id,
+            tip: None,
+        }
+    }
+
+    /// Continue an existing stream on top of the given anchored block, whose last microblock has
+    /// the given header.
+    pub fn resume(
+        anchored_block_hash: &BlockHeaderHash,
+        tip: &StacksMicroblockHeader,
+        privk: &StacksPrivateKey,
+        mainnet: bool,
+        chain_id: u32,
+    ) -> MicroblockStreamBuilder {
+        let mut builder =
+            MicroblockStreamBuilder::new(anchored_block_hash, privk, mainnet, chain_id);
+        builder.tip = Some(tip.clone());
+        builder
+    }
+
+    /// Hash of the public key that signs this stream.  The anchored block this stream builds on
+    /// must commit to it.
+    pub fn microblock_pubkey_hash(&self) -> Hash160 {
+        let mut pubk = StacksPublicKey::from_private(&self.privk);
+        pubk.set_compressed(true);
+        StacksBlockHeader::pubkey_hash(&pubk)
+    }
+
+    /// Header of the last microblock built (or resumed from), if any
+    pub fn get_tip(&self) -> Option<&StacksMicroblockHeader> {
+        self.tip.as_ref()
+    }
+
+    /// Build and sign the next microblock in the stream from the given transactions.
+    pub fn next_microblock(
+        &mut self,
+        txs: Vec<StacksTransaction>,
+    ) -> Result<StacksMicroblock, MicroblockStreamError> {
+        let index = self
+            .tip
+            .as_ref()
+            .map(|hdr| hdr.sequence as usize + 1)
+            .unwrap_or(0);
+        if txs.is_empty() {
+            return Err(MicroblockStreamError::NoTransactions(index));
+        }
+
+        let mut microblock = match self.tip {
+            Some(ref tip) => StacksMicroblock::from_parent_unsigned(tip, txs)
+                .ok_or(MicroblockStreamError::SequenceOverflow)?,
+            None => StacksMicroblock::first_unsigned(&self.anchored_block_hash, txs),
+        };
+
+        check_microblock_transactions(index, &microblock, self.mainnet, self.chain_id)?;
+
+        microblock
+            .sign(&self.privk)
+            .map_err(|e| MicroblockStreamError::BadSignature(index, e.to_string()))?;
+
+        self.tip = Some(microblock.header.clone());
+        Ok(microblock)
+    }
+}
+
+/// Check that a microblock's transactions are well-formed, are allowed in a microblock, and
+/// are committed to by its header.
+fn check_microblock_transactions(
+    index: usize,
+    microblock: &StacksMicroblock,
+    mainnet: bool,
+    chain_id: u32,
+) -> Result<(), MicroblockStreamError> {
+    if microblock.txs.is_empty() {
+        return Err(MicroblockStreamError::NoTransactions(index));
+    }
+    if !StacksBlock::validate_transactions_unique(&microblock.txs) {
+        return Err(MicroblockStreamError::InvalidTransactions(
+            index,
+            "duplicate transaction".to_string(),
+        ));
+    }
+    if !StacksBlock::validate_transactions_network(&microblock.txs, mainnet) {
+        return Err(MicroblockStreamError::InvalidTransactions(
+            index,
+            "transaction is for the wrong network".to_string(),
+        ));
+    }
+    if !StacksBlock::validate_transactions_chain_id(&microblock.txs, chain_id) {
+        return Err(MicroblockStreamError::InvalidTransactions(
+            index,
+            "transaction has the wrong chain ID".to_string(),
+        ));
+    }
+    if !StacksBlock::validate_anchor_mode(&microblock.txs, false) {
+        return Err(MicroblockStreamError::InvalidTransactions(
+            index,
+            "transaction must be mined in an anchored block".to_string(),
+        ));
+    }
+    if !StacksBlock::validate_coinbase(&microblock.txs, false) {
+        return Err(MicroblockStreamError::InvalidTransactions(
+            index,
+            "coinbase transaction".to_string(),
+        ));
+    }
+
+    let txids = microblock
+        .txs
+        .iter()
+        .map(|tx| tx.txid().as_bytes().to_vec())
+        .collect();
+    let merkle_tree = MerkleTree::<Sha512Trunc256Sum>::new(&txids);
+    if merkle_tree.root() != microblock.header.tx_merkle_root {
+        return Err(MicroblockStreamError::MerkleRootMismatch(index));
+    }
+
+    let len = microblock.serialize_to_vec().len() as u64;
+    if len > MAX_MESSAGE_LEN as u64 {
+        return Err(MicroblockStreamError::TooBig(index, len));
+    }
+
+    Ok(())
+}
+
+/// Verify a microblock stream built on top of the anchored block with hash
+/// `anchored_block_hash`, whose header committed to `microblock_pubkey_hash`:
+/// * the stream starts at sequence 0 with the anchored block as its parent;
+/// * each microblock has the next sequence number, and its parent is the prior microblock;
+/// * each microblock is signed by the key the anchored block committed to;
+/// * each microblock's header commits to its transactions, and the transactions are allowed
+///   in a microblock on the given network.
+///
+/// The stream must be in order, and must not fork.
+pub fn verify_microblock_stream(
+    anchored_block_hash: &BlockHeaderHash,
+    microblock_pubkey_hash: &Hash160,
+    microblocks: &[StacksMicroblock],
+    mainnet: bool,
+    chain_id: u32,
+) -> Result<(), MicroblockStreamError> {
+    if microblocks.is_empty() {
+        return Err(MicroblockStreamError::EmptyStream);
+    }
+
+    let mut expected_parent = anchored_block_hash.clone();
+    for (i, microblock) in microblocks.iter().enumerate() {
+        let expected_sequence = if i <= u16::MAX as usize {
+            i as u16
+        } else {
+            return Err(MicroblockStreamError::SequenceOverflow);
+        };
+
+        if microblock.header.sequence != expected_sequence {
+            return Err(MicroblockStreamError::BadSequence(
+                i,
+                expected_sequence,
+                microblock.header.sequence,
+            ));
+        }
+        if microblock.header.prev_block != expected_parent {
+            return Err(MicroblockStreamError::BadParent(
+                i,
+                expected_parent,
+                microblock.header.prev_block.clone(),
+            ));
+        }
+
+        microblock
+            .header
+            .verify(microblock_pubkey_hash)
+            .map_err(|e| match e {
+                net_error::VerifyingError(msg) => MicroblockStreamError::BadSignature(i, msg),
+                e => MicroblockStreamError::BadSignature(i, e.to_string()),
+            })?;
+
+        check_microblock_transactions(i, microblock, mainnet, chain_id)?;
+
+        expected_parent = microblock.block_hash();
+    }
+
+    Ok(())
+}
+
+/// Verify the prefix of a microblock stream that `child_header` confirms.  The stream must build
+/// on `parent_header`, and is verified as with `verify_microblock_stream`.  Returns the
+/// confirmed prefix of the stream, which is empty if the child confirms no microblocks.
+pub fn verify_confirmed_microblock_stream<'a>(
+    parent_header: &StacksBlockHeader,
+    child_header: &StacksBlockHeader,
+    microblocks: &'a [StacksMicroblock],
+    mainnet: bool,
+    chain_id: u32,
+) -> Result<&'a [StacksMicroblock], MicroblockStreamError> {
+    if !child_header.has_microblock_parent() {
+        return Ok(&microblocks[0..0]);
+    }
+
+    let confirmed_len = child_header.parent_microblock_sequence as usize + 1;
+    if microblocks.len() < confirmed_len {
+        return Err(MicroblockStreamError::NotConfirmed(
+            child_header.parent_microblock.clone(),
+            child_header.parent_microblock_sequence,
+        ));
+    }
+
+    let confirmed = &microblocks[0..confirmed_len];
+    verify_microblock_stream(
+        &parent_header.block_hash(),
+        &parent_header.microblock_pubkey_hash,
+        confirmed,
+        mainnet,
+        chain_id,
+    )?;
+
+    if confirmed[confirmed_len - 1].block_hash() != child_header.parent_microblock {
+        return Err(MicroblockStreamError::NotConfirmed(
+            child_header.parent_microblock.clone(),
+            child_header.parent_microblock_sequence,
+        ));
+    }
+
+    Ok(confirmed)
+}
+
+#[cfg(test)]
+mod test {
+    use address::AddressHashMode;
+    use chainstate::stacks::*;
+    use core::{CHAIN_ID_TESTNET, EMPTY_MICROBLOCK_PARENT_HASH};
+
+    use crate::types::chainstate::StacksAddress;
+
+    use super::*;
+
+    fn make_transfers(
+        privk: &StacksPrivateKey,
+        start_nonce: u64,
+        count: u64,
+    ) -> Vec<StacksTransaction> {
+        let recipient = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&StacksPrivateKey::new())],
+        )
+        .unwrap();
+
+        let mut txs = vec![];
+        for nonce in start_nonce..(start_nonce + count) {
+            let auth = TransactionAuth::from_p2pkh(privk).unwrap();
+            let mut tx = StacksTransaction::new(
+                TransactionVersion::Testnet,
+                auth,
+                TransactionPayload::TokenTransfer(
+                    recipient.clone().into(),
+                    123,
+                    TokenTransferMemo([0u8; 34]),
+                ),
+            );
+            tx.chain_id = CHAIN_ID_TESTNET;
+            tx.anchor_mode = TransactionAnchorMode::OffChainOnly;
+            tx.set_origin_nonce(nonce);
+
+            let mut signer = StacksTransactionSigner::new(&tx);
+            signer.sign_origin(privk).unwrap();
+            txs.push(signer.get_tx().unwrap());
+        }
+        txs
+    }
+
+    fn make_stream(
+        anchored_block_hash: &BlockHeaderHash,
+        mblock_privk: &StacksPrivateKey,
+        len: u64,
+    ) -> Vec<StacksMicroblock> {
+        let privk = StacksPrivateKey::new();
+        let mut builder = MicroblockStreamBuilder::new(
+            anchored_block_hash,
+            mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        );
+        (0..len)
+            .map(|i| {
+                builder
+                    .next_microblock(make_transfers(&privk, 2 * i, 2))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_build_and_verify_microblock_stream() {
+        let anchored_block_hash = BlockHeaderHash([0x11; 32]);
+        let mblock_privk = StacksPrivateKey::new();
+        let builder = MicroblockStreamBuilder::new(
+            &anchored_block_hash,
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        );
+        let pubkh = builder.microblock_pubkey_hash();
+
+        let stream = make_stream(&anchored_block_hash, &mblock_privk, 5);
+        for (i, mblock) in stream.iter().enumerate() {
+            assert_eq!(mblock.header.sequence, i as u16);
+            if i == 0 {
+                assert_eq!(mblock.header.prev_block, anchored_block_hash);
+            } else {
+                assert_eq!(mblock.header.prev_block, stream[i - 1].block_hash());
+            }
+
+            // microblocks round-trip through the codec
+            let bytes = mblock.serialize_to_vec();
+            let decoded = StacksMicroblock::consensus_deserialize(&mut &bytes[..]).unwrap();
+            assert_eq!(&decoded, mblock);
+        }
+
+        verify_microblock_stream(
+            &anchored_block_hash,
+            &pubkh,
+            &stream,
+            false,
+            CHAIN_ID_TESTNET,
+        )
+        .unwrap();
+
+        // resuming continues the stream
+        let mut resumed = MicroblockStreamBuilder::resume(
+            &anchored_block_hash,
+            &stream[4].header,
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        );
+        let next = resumed
+            .next_microblock(make_transfers(&StacksPrivateKey::new(), 0, 1))
+            .unwrap();
+        let mut longer = stream.clone();
+        longer.push(next);
+        verify_microblock_stream(
+            &anchored_block_hash,
+            &pubkh,
+            &longer,
+            false,
+            CHAIN_ID_TESTNET,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_build_microblock_stream_errors() {
+        let anchored_block_hash = BlockHeaderHash([0x11; 32]);
+        let mblock_privk = StacksPrivateKey::new();
+        let mut builder = MicroblockStreamBuilder::new(
+            &anchored_block_hash,
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        );
+
+        assert_eq!(
+            builder.next_microblock(vec![]),
+            Err(MicroblockStreamError::NoTransactions(0))
+        );
+
+        let mut txs = make_transfers(&StacksPrivateKey::new(), 0, 1);
+        txs.push(txs[0].clone());
+        match builder.next_microblock(txs) {
+            Err(MicroblockStreamError::InvalidTransactions(0, _)) => {}
+            x => panic!("Expected invalid transactions, got {:?}", &x),
+        }
+
+        let mut txs = make_transfers(&StacksPrivateKey::new(), 0, 1);
+        txs[0].anchor_mode = TransactionAnchorMode::OnChainOnly;
+        match builder.next_microblock(txs) {
+            Err(MicroblockStreamError::InvalidTransactions(0, _)) => {}
+            x => panic!("Expected invalid transactions, got {:?}", &x),
+        }
+
+        // nothing was built
+        assert!(builder.get_tip().is_none());
+
+        let mut builder = MicroblockStreamBuilder::resume(
+            &anchored_block_hash,
+            &StacksMicroblockHeader {
+                sequence: u16::MAX,
+                ..StacksMicroblockHeader::first_empty_unsigned(&anchored_block_hash)
+            },
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        );
+        assert_eq!(
+            builder.next_microblock(make_transfers(&StacksPrivateKey::new(), 0, 1)),
+            Err(MicroblockStreamError::SequenceOverflow)
+        );
+    }
+
+    #[test]
+    fn test_verify_microblock_stream_errors() {
+        let anchored_block_hash = BlockHeaderHash([0x11; 32]);
+        let mblock_privk = StacksPrivateKey::new();
+        let pubkh = MicroblockStreamBuilder::new(
+            &anchored_block_hash,
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        )
+        .microblock_pubkey_hash();
+        let stream = make_stream(&anchored_block_hash, &mblock_privk, 4);
+
+        assert_eq!(
+            verify_microblock_stream(&anchored_block_hash, &pubkh, &[], false, CHAIN_ID_TESTNET),
+            Err(MicroblockStreamError::EmptyStream)
+        );
+
+        // wrong anchored block
+        match verify_microblock_stream(
+            &BlockHeaderHash([0x22; 32]),
+            &pubkh,
+            &stream,
+            false,
+            CHAIN_ID_TESTNET,
+        ) {
+            Err(MicroblockStreamError::BadParent(0, _, _)) => {}
+            x => panic!("Expected bad parent, got {:?}", &x),
+        }
+
+        // wrong signer
+        match verify_microblock_stream(
+            &anchored_block_hash,
+            &Hash160([0x33; 20]),
+            &stream,
+            false,
+            CHAIN_ID_TESTNET,
+        ) {
+            Err(MicroblockStreamError::BadSignature(0, _)) => {}
+            x => panic!("Expected bad signature, got {:?}", &x),
+        }
+
+        // wrong network
+        match verify_microblock_stream(
+            &anchored_block_hash,
+            &pubkh,
+            &stream,
+            true,
+            CHAIN_ID_TESTNET,
+        ) {
+            Err(MicroblockStreamError::InvalidTransactions(0, _)) => {}
+            x => panic!("Expected invalid transactions, got {:?}", &x),
+        }
+
+        // gap in the stream
+        let gapped = vec![stream[0].clone(), stream[2].clone()];
+        assert_eq!(
+            verify_microblock_stream(
+                &anchored_block_hash,
+                &pubkh,
+                &gapped,
+                false,
+                CHAIN_ID_TESTNET
+            ),
+            Err(MicroblockStreamError::BadSequence(1, 1, 2))
+        );
+
+        // tampered transactions
+        let mut tampered = stream.clone();
+        tampered[2].txs.pop();
+        assert_eq!(
+            verify_microblock_stream(
+                &anchored_block_hash,
+                &pubkh,
+                &tampered,
+                false,
+                CHAIN_ID_TESTNET
+            ),
+            Err(MicroblockStreamError::MerkleRootMismatch(2))
+        );
+
+        // fork: a different microblock at sequence 1 doesn't link to the rest of the stream
+        let mut fork_builder = MicroblockStreamBuilder::resume(
+            &anchored_block_hash,
+            &stream[0].header,
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        );
+        let mut forked = stream.clone();
+        forked[1] = fork_builder
+            .next_microblock(make_transfers(&StacksPrivateKey::new(), 0, 1))
+            .unwrap();
+        match verify_microblock_stream(
+            &anchored_block_hash,
+            &pubkh,
+            &forked,
+            false,
+            CHAIN_ID_TESTNET,
+        ) {
+            Err(MicroblockStreamError::BadParent(2, _, _)) => {}
+            x => panic!("Expected bad parent, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_verify_confirmed_microblock_stream() {
+        let mblock_privk = StacksPrivateKey::new();
+        let mut parent_header = StacksBlockHeader::genesis_block_header();
+        parent_header.microblock_pubkey_hash = MicroblockStreamBuilder::new(
+            &BlockHeaderHash([0x00; 32]),
+            &mblock_privk,
+            false,
+            CHAIN_ID_TESTNET,
+        )
+        .microblock_pubkey_hash();
+
+        let stream = make_stream(&parent_header.block_hash(), &mblock_privk, 4);
+
+        // child confirms the first three microblocks
+        let mut child_header = StacksBlockHeader::genesis_block_header();
+        child_header.parent_block = parent_header.block_hash();
+        child_header.parent_microblock = stream[2].block_hash();
+        child_header.parent_microblock_sequence = 2;
+
+        let confirmed = verify_confirmed_microblock_stream(
+            &parent_header,
+            &child_header,
+            &stream,
+            false,
+            CHAIN_ID_TESTNET,
+        )
+        .unwrap();
+        assert_eq!(confirmed, &stream[0..3]);
+
+        // child confirms a microblock that isn't in the stream
+        child_header.parent_microblock = BlockHeaderHash([0x44; 32]);
+        assert_eq!(
+            verify_confirmed_microblock_stream(
+                &parent_header,
+                &child_header,
+                &stream,
+                false,
+                CHAIN_ID_TESTNET
+            ),
+            Err(MicroblockStreamError::NotConfirmed(
+                BlockHeaderHash([0x44; 32]),
+                2
+            ))
+        );
+
+        // child confirms past the end of the stream
+        child_header.parent_microblock_sequence = 10;
+        assert_eq!(
+            verify_confirmed_microblock_stream(
+                &parent_header,
+                &child_header,
+                &stream,
+                false,
+                CHAIN_ID_TESTNET
+            ),
+            Err(MicroblockStreamError::NotConfirmed(
+                BlockHeaderHash([0x44; 32]),
+                10
+            ))
+        );
+
+        // child confirms no microblocks
+        child_header.parent_microblock = EMPTY_MICROBLOCK_PARENT_HASH.clone();
+        child_header.parent_microblock_sequence = 0;
+        let confirmed = verify_confirmed_microblock_stream(
+            &parent_header,
+            &child_header,
+            &stream,
+            false,
+            CHAIN_ID_TESTNET,
+        )
+        .unwrap();
+        assert_eq!(confirmed.len(), 0);
+    }
+}
@@ -18,7 +18,9 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::convert::From;
 use std::fs;
+use std::io;
 use std::mem;
+use std::path::Path;
 
 use crate::cost_estimates::metrics::CostMetric;
 use crate::cost_estimates::CostEstimator;
@@ -44,6 +46,7 @@ use serde::Deserialize;
 use util::get_epoch_time_ms;
 use util::hash::MerkleTree;
 use util::hash::Sha512Trunc256Sum;
+use util::hash::{hex_bytes, to_hex};
 use util::secp256k1::{MessageSignature, Secp256k1PrivateKey};
 use util::vrf::*;
 use vm::database::BurnStateDB;
@@ -59,6 +62,9 @@ use chainstate::stacks::db::blocks::SetupBlockResult;
 pub struct BlockBuilderSettings {
     pub max_miner_time_ms: u64,
     pub mempool_settings: MemPoolWalkSettings,
+    /// If set, write a `BlockAssemblyReplay` for each anchored block assembly attempt to this
+    /// directory.
+    pub replay_dir: Option<String>,
 }
 
 impl BlockBuilderSettings {
@@ -66,6 +72,7 @@ impl BlockBuilderSettings {
         BlockBuilderSettings {
             max_miner_time_ms: u64::max_value(),
             mempool_settings: MemPoolWalkSettings::default(),
+            replay_dir: None,
         }
     }
 
@@ -73,6 +80,7 @@ impl BlockBuilderSettings {
         BlockBuilderSettings {
             max_miner_time_ms: u64::max_value(),
             mempool_settings: MemPoolWalkSettings::zero(),
+            replay_dir: None,
         }
     }
 }
@@ -104,6 +112,165 @@ enum BlockLimitFunction {
     LIMIT_REACHED,
 }
 
+/// Transaction selection state for assembling an anchored block out of a stream of candidate
+/// transactions.  The candidates come from the mempool when mining, and from a
+/// `BlockAssemblyReplay` when replaying.
+struct AnchoredBlockSelection {
+    /// txids of all transactions we looked at
+    considered: HashSet<Txid>,
+    /// map addrs of mined transaction origins to the nonces we used
+    mined_origin_nonces: HashMap<StacksAddress, u64>,
+    /// map addrs of mined transaction sponsors to the nonces we used
+    mined_sponsor_nonces: HashMap<StacksAddress, u64>,
+    /// transactions that can never be mined, and should be dropped from the mempool
+    invalidated_txs: Vec<Txid>,
+    block_limit_hit: BlockLimitFunction,
+    num_txs: u64,
+    tx_events: Vec<TransactionEvent>,
+}
+
+impl AnchoredBlockSelection {
+    fn new(tx_events: Vec<TransactionEvent>) -> AnchoredBlockSelection {
+        AnchoredBlockSelection {
+            considered: HashSet::new(),
+            mined_origin_nonces: HashMap::new(),
+            mined_sponsor_nonces: HashMap::new(),
+            invalidated_txs: vec![],
+            block_limit_hit: BlockLimitFunction::NO_LIMIT_HIT,
+            num_txs: 0,
+            tx_events,
+        }
+    }
+
+    /// Consider a candidate transaction for inclusion in the block being built.
+    /// If `estimator` is given, it will be updated with the transaction's cost if it is mined.
+    /// Returns what was done with the transaction, and whether or not to keep considering
+    /// candidates.
+    fn consider_tx(
+        &mut self,
+        builder: &mut StacksBlockBuilder,
+        epoch_tx: &mut ClarityTx,
+        tx: &StacksTransaction,
+        tx_len: u64,
+        out_of_time: bool,
+        estimator: Option<&mut dyn CostEstimator>,
+        block_limit: &ExecutionCost,
+        stacks_epoch_id: &StacksEpochId,
+    ) -> (AssemblyDecision, bool) {
+        if self.block_limit_hit == BlockLimitFunction::LIMIT_REACHED {
+            return (AssemblyDecision::BlockFull, false);
+        }
+        if out_of_time {
+            return (AssemblyDecision::OutOfTime, false);
+        }
+
+        // skip transactions early if we can
+        let txid = tx.txid();
+        if self.considered.contains(&txid) {
+            return (AssemblyDecision::AlreadyConsidered, true);
+        }
+
+        if let Some(nonce) = self.mined_origin_nonces.get(&tx.origin_address()) {
+            if *nonce >= tx.get_origin_nonce() {
+                return (AssemblyDecision::NonceAlreadyMined, true);
+            }
+        }
+        if let Some(sponsor_addr) = tx.sponsor_address() {
+            if let Some(nonce) = self.mined_sponsor_nonces.get(&sponsor_addr) {
+                if let Some(sponsor_nonce) = tx.get_sponsor_nonce() {
+                    if *nonce >= sponsor_nonce {
+                        return (AssemblyDecision::NonceAlreadyMined, true);
+                    }
+                }
+            }
+        }
+
+        self.considered.insert(txid.clone());
+
+        let tx_result = builder.try_mine_tx_with_len(epoch_tx, tx, tx_len, &self.block_limit_hit);
+        self.tx_events.push(tx_result.convert_to_event());
+
+        let (decision, error_opt) = match tx_result {
+            TransactionResult::Success(TransactionSuccess { receipt, .. }) => {
+                self.num_txs += 1;
+                if let Some(estimator) = estimator {
+                    if let Err(e) = estimator.notify_event(
+                        &tx.payload,
+                        &receipt.execution_cost,
+                        block_limit,
+                        stacks_epoch_id,
+                    ) {
+                        warn!("Error updating estimator";
+                              "txid" => %txid,
+                              "error" => ?e);
+                    }
+                }
+                (AssemblyDecision::Mined, None)
+            }
+            TransactionResult::Skipped(TransactionSkipped { error, .. }) => (
+                AssemblyDecision::Skipped {
+                    reason: error.to_string(),
+                },
+                Some(error),
+            ),
+            TransactionResult::ProcessingError(TransactionError { error, .. }) => (
+                AssemblyDecision::Failed {
+                    reason: error.to_string(),
+                },
+                Some(error),
+            ),
+        };
+
+        if let Some(error) = error_opt {
+            match &error {
+                Error::StacksTransactionSkipped(_) => {}
+                Error::BlockTooBigError => {
+                    // done mining -- our execution budget is exceeded.
+                    // Make the block from the transactions we did manage to get
+                    debug!("Block budget exceeded on tx {}", &txid);
+                    if self.block_limit_hit == BlockLimitFunction::NO_LIMIT_HIT {
+                        debug!("Switch to mining stx-transfers only");
+                        self.block_limit_hit = BlockLimitFunction::CONTRACT_LIMIT_HIT;
+                    } else if self.block_limit_hit == BlockLimitFunction::CONTRACT_LIMIT_HIT {
+                        debug!("Stop mining anchored block due to limit exceeded");
+                        self.block_limit_hit = BlockLimitFunction::LIMIT_REACHED;
+                        return (decision, false);
+                    }
+                }
+                Error::TransactionTooBigError => {
+                    self.invalidated_txs.push(txid.clone());
+                    if self.block_limit_hit == BlockLimitFunction::NO_LIMIT_HIT {
+                        self.block_limit_hit = BlockLimitFunction::CONTRACT_LIMIT_HIT;
+                        debug!("Switch to mining stx-transfers only");
+                    } else if self.block_limit_hit == BlockLimitFunction::CONTRACT_LIMIT_HIT {
+                        debug!("Stop mining anchored block due to limit exceeded");
+                        self.block_limit_hit = BlockLimitFunction::LIMIT_REACHED;
+                        return (decision, false);
+                    }
+                }
+                Error::InvalidStacksTransaction(_, true) => {
+                    // if we have an invalid transaction that was quietly ignored, don't warn here either
+                }
+                e => {
+                    warn!("Failed to apply tx {}: {:?}", &txid, &e);
+                    return (decision, true);
+                }
+            }
+        }
+
+        self.mined_origin_nonces
+            .insert(tx.origin_address(), tx.get_origin_nonce());
+        if let (Some(sponsor_addr), Some(sponsor_nonce)) =
+            (tx.sponsor_address(), tx.get_sponsor_nonce())
+        {
+            self.mined_sponsor_nonces
+                .insert(sponsor_addr, sponsor_nonce);
+        }
+
+        (decision, true)
+    }
+}
+
 pub struct MinerEpochInfo<'a> {
     pub chainstate_tx: ChainstateTx<'a>,
     pub clarity_instance: &'a mut ClarityInstance,
@@ -193,6 +360,125 @@ fn hex_deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Txid, D::Er
     Txid::from_hex(&inst_str).map_err(serde::de::Error::custom)
 }
 
+/// What the anchored block assembler did with a candidate transaction from the mempool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum AssemblyDecision {
+    /// The transaction was included in the block.
+    Mined,
+    /// The transaction was tried, but was skipped.  It might be mined later.
+    Skipped { reason: String },
+    /// The transaction was tried, but failed to be processed.
+    Failed { reason: String },
+    /// The transaction was not tried, because it had been considered already.
+    AlreadyConsidered,
+    /// The transaction was not tried, because a transaction with the same (or later) origin or
+    /// sponsor nonce was already mined.
+    NonceAlreadyMined,
+    /// The transaction was not tried, because the block is full.
+    BlockFull,
+    /// The transaction was not tried, because the miner ran out of time.
+    OutOfTime,
+}
+
+/// A transaction that the mempool offered to the anchored block assembler, in the order it was
+/// offered, along with what the assembler did with it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssemblyCandidate {
+    #[serde(deserialize_with = "hex_deserialize", serialize_with = "hex_serialize")]
+    pub txid: Txid,
+    /// hex-encoded transaction
+    pub tx: String,
+    pub len: u64,
+    pub update_estimate: bool,
+    #[serde(flatten)]
+    pub decision: AssemblyDecision,
+}
+
+/// Everything needed to deterministically re-run an anchored block assembly attempt:  the
+/// parent chain state to build on, the mempool candidate stream as the assembler saw it, and
+/// the assembler's decisions and resulting block.  Written by the miner if
+/// `BlockBuilderSettings::replay_dir` is set, and consumed by
+/// `StacksBlockBuilder::replay_anchored_block`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockAssemblyReplay {
+    pub parent_consensus_hash: ConsensusHash,
+    pub parent_block_hash: BlockHeaderHash,
+    /// hex-encoded header of the parent microblock stream tail, if there is one
+    pub parent_microblock_tail: Option<String>,
+    /// hex-encoded burnchain tip hash, and burnchain tip height + 1
+    pub burn_tip: String,
+    pub burn_tip_height: u32,
+    pub total_burn: u64,
+    /// hex-encoded VRF proof
+    pub proof: String,
+    pub microblock_pubkey_hash: Hash160,
+    /// hex-encoded coinbase transaction
+    pub coinbase_tx: String,
+    pub max_miner_time_ms: u64,
+    pub candidates: Vec<AssemblyCandidate>,
+    /// hex-encoded assembled block, if assembly succeeded
+    pub block: Option<String>,
+    /// why assembly failed, if it did
+    pub error: Option<String>,
+}
+
+impl BlockAssemblyReplay {
+    /// Load a replay file
+    pub fn from_path(path: &str) -> Result<BlockAssemblyReplay, Error> {
+        let bytes = fs::read(path).map_err(Error::ReadError)?;
+        serde_json::from_slice(&bytes).map_err(|e| {
+            Error::InvalidStacksBlock(format!("Failed to parse replay file {}: {:?}", path, &e))
+        })
+    }
+
+    /// Decode the recorded block, if there is one
+    pub fn get_block(&self) -> Result<Option<StacksBlock>, Error> {
+        match self.block {
+            Some(ref block_hex) => Ok(Some(BlockAssemblyReplay::decode_hex(block_hex)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_hex<T: StacksMessageCodec>(hex_str: &str) -> Result<T, Error> {
+        let bytes = hex_bytes(hex_str)
+            .map_err(|_| Error::InvalidStacksBlock("Invalid hex string in replay".to_string()))?;
+        T::consensus_deserialize(&mut &bytes[..]).map_err(Error::CodecError)
+    }
+
+    /// Store this replay to `{dir}/{parent index block hash}-{timestamp}.json`.
+    /// Failure to do so is logged, but is otherwise not fatal to mining.
+    fn store(&self, dir: &str, timestamp: u128) {
+        match self.inner_store(dir, timestamp) {
+            Ok(path) => {
+                debug!("Miner: stored block assembly replay to {}", &path);
+            }
+            Err(e) => {
+                warn!(
+                    "Miner: failed to store block assembly replay to {}: {:?}",
+                    dir, &e
+                );
+            }
+        }
+    }
+
+    fn inner_store(&self, dir: &str, timestamp: u128) -> Result<String, io::Error> {
+        fs::create_dir_all(dir)?;
+        let parent_index_hash = StacksBlockHeader::make_index_block_hash(
+            &self.parent_consensus_hash,
+            &self.parent_block_hash,
+        );
+        let path = Path::new(dir)
+            .join(format!("{}-{}.json", &parent_index_hash, timestamp))
+            .to_string_lossy()
+            .to_string();
+        let bytes = serde_json::to_vec_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(&path, &bytes)?;
+        Ok(path)
+    }
+}
+
 /// `TransactionResult` represents the outcome of transaction processing.
 /// We use this enum to involve the compiler in forcing us to always clearly
 /// indicate the outcome of a transaction.
@@ -1829,6 +2115,26 @@ impl StacksBlockBuilder {
 
         let (mut chainstate, _) = chainstate_handle.reopen()?;
 
+        // if we're writing a replay, then remember everything needed to re-run this attempt
+        let mut replay = settings.replay_dir.as_ref().map(|_| BlockAssemblyReplay {
+            parent_consensus_hash: tip_consensus_hash.clone(),
+            parent_block_hash: tip_block_hash.clone(),
+            parent_microblock_tail: parent_stacks_header
+                .microblock_tail
+                .as_ref()
+                .map(|hdr| to_hex(&hdr.serialize_to_vec())),
+            burn_tip: "".to_string(),
+            burn_tip_height: 0,
+            total_burn,
+            proof: proof.to_hex(),
+            microblock_pubkey_hash: pubkey_hash.clone(),
+            coinbase_tx: to_hex(&coinbase_tx.serialize_to_vec()),
+            max_miner_time_ms,
+            candidates: vec![],
+            block: None,
+            error: None,
+        });
+
         let mut builder = StacksBlockBuilder::make_block_builder(
             chainstate.mainnet,
             parent_stacks_header,
@@ -1840,6 +2146,11 @@ impl StacksBlockBuilder {
         let ts_start = get_epoch_time_ms();
 
         let mut miner_epoch_info = builder.pre_epoch_begin(&mut chainstate, burn_dbconn)?;
+        if let Some(ref mut replay) = replay {
+            replay.burn_tip = miner_epoch_info.burn_tip.to_hex();
+            replay.burn_tip_height = miner_epoch_info.burn_tip_height;
+        }
+
         let (mut epoch_tx, confirmed_mblock_cost) =
             builder.epoch_begin(burn_dbconn, &mut miner_epoch_info)?;
         let stacks_epoch_id = epoch_tx.get_epoch();
@@ -1847,158 +2158,61 @@ impl StacksBlockBuilder {
             .block_limit()
             .expect("Failed to obtain block limit from miner's block connection");
 
-        let mut tx_events = Vec::new();
-        tx_events.push(
-            builder
-                .try_mine_tx(&mut epoch_tx, coinbase_tx)?
-                .convert_to_event(),
-        );
+        let mut selection = AnchoredBlockSelection::new(vec![builder
+            .try_mine_tx(&mut epoch_tx, coinbase_tx)?
+            .convert_to_event()]);
 
         mempool.reset_last_known_nonces()?;
 
         mempool.estimate_tx_rates(100, &block_limit, &stacks_epoch_id)?;
 
-        let mut considered = HashSet::new(); // txids of all transactions we looked at
-        let mut mined_origin_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction origins to the nonces we used
-        let mut mined_sponsor_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction sponsors to the nonces we used
-
-        let mut invalidated_txs = vec![];
-
-        let mut block_limit_hit = BlockLimitFunction::NO_LIMIT_HIT;
         let deadline = ts_start + (max_miner_time_ms as u128);
-        let mut num_txs = 0;
 
         debug!(
             "Anchored block transaction selection begins (child of {})",
             &parent_stacks_header.anchored_header.block_hash()
         );
         let result = {
-            let mut intermediate_result = Ok(0);
-            while block_limit_hit != BlockLimitFunction::LIMIT_REACHED {
-                let mut num_considered = 0;
+            let mut intermediate_result: Result<u64, Error> = Ok(0);
+            while selection.block_limit_hit != BlockLimitFunction::LIMIT_REACHED {
+                let num_considered_before = selection.considered.len();
                 intermediate_result = mempool.iterate_candidates(
                     &mut epoch_tx,
                     tip_height,
                     mempool_settings.clone(),
                     |epoch_tx, to_consider, estimator| {
                         let txinfo = &to_consider.tx;
-                        let update_estimator = to_consider.update_estimate;
-
-                        if block_limit_hit == BlockLimitFunction::LIMIT_REACHED {
-                            return Ok(false);
-                        }
-                        if get_epoch_time_ms() >= deadline {
+                        let out_of_time = get_epoch_time_ms() >= deadline;
+                        if out_of_time {
                             debug!("Miner mining time exceeded ({} ms)", max_miner_time_ms);
-                            return Ok(false);
-                        }
-
-                        // skip transactions early if we can
-                        if considered.contains(&txinfo.tx.txid()) {
-                            return Ok(true);
                         }
 
-                        if let Some(nonce) = mined_origin_nonces.get(&txinfo.tx.origin_address()) {
-                            if *nonce >= txinfo.tx.get_origin_nonce() {
-                                return Ok(true);
-                            }
-                        }
-                        if let Some(sponsor_addr) = txinfo.tx.sponsor_address() {
-                            if let Some(nonce) = mined_sponsor_nonces.get(&sponsor_addr) {
-                                if let Some(sponsor_nonce) = txinfo.tx.get_sponsor_nonce() {
-                                    if *nonce >= sponsor_nonce {
-                                        return Ok(true);
-                                    }
-                                }
-                            }
-                        }
-
-                        considered.insert(txinfo.tx.txid());
-                        num_considered += 1;
-
-                        let tx_result = builder.try_mine_tx_with_len(
+                        let (decision, keep_going) = selection.consider_tx(
+                            &mut builder,
                             epoch_tx,
                             &txinfo.tx,
                             txinfo.metadata.len,
-                            &block_limit_hit,
+                            out_of_time,
+                            if to_consider.update_estimate {
+                                Some(estimator)
+                            } else {
+                                None
+                            },
+                            &block_limit,
+                            &stacks_epoch_id,
                         );
-                        tx_events.push(tx_result.convert_to_event());
-
-                        match tx_result {
-                            TransactionResult::Success(TransactionSuccess { receipt, .. }) => {
-                                num_txs += 1;
-                                if update_estimator {
-                                    if let Err(e) = estimator.notify_event(
-                                        &txinfo.tx.payload,
-                                        &receipt.execution_cost,
-                                        &block_limit,
-                                        &stacks_epoch_id,
-                                    ) {
-                                        warn!("Error updating estimator";
-                                              "txid" => %txinfo.metadata.txid,
-                                              "error" => ?e);
-                                    }
-                                }
-                            }
-                            TransactionResult::Skipped(TransactionSkipped { error, .. })
-                            | TransactionResult::ProcessingError(TransactionError {
-                                error, ..
-                            }) => {
-                                match &error {
-                                    Error::StacksTransactionSkipped(_) => {}
-                                    Error::BlockTooBigError => {
-                                        // done mining -- our execution budget is exceeded.
-                                        // Make the block from the transactions we did manage to get
-                                        debug!("Block budget exceeded on tx {}", &txinfo.tx.txid());
-                                        if block_limit_hit == BlockLimitFunction::NO_LIMIT_HIT {
-                                            debug!("Switch to mining stx-transfers only");
-                                            block_limit_hit =
-                                                BlockLimitFunction::CONTRACT_LIMIT_HIT;
-                                        } else if block_limit_hit
-                                            == BlockLimitFunction::CONTRACT_LIMIT_HIT
-                                        {
-                                            debug!(
-                                                "Stop mining anchored block due to limit exceeded"
-                                            );
-                                            block_limit_hit = BlockLimitFunction::LIMIT_REACHED;
-                                            return Ok(false);
-                                        }
-                                    }
-                                    Error::TransactionTooBigError => {
-                                        invalidated_txs.push(txinfo.metadata.txid);
-                                        if block_limit_hit == BlockLimitFunction::NO_LIMIT_HIT {
-                                            block_limit_hit =
-                                                BlockLimitFunction::CONTRACT_LIMIT_HIT;
-                                            debug!("Switch to mining stx-transfers only");
-                                        } else if block_limit_hit
-                                            == BlockLimitFunction::CONTRACT_LIMIT_HIT
-                                        {
-                                            debug!(
-                                                "Stop mining anchored block due to limit exceeded"
-                                            );
-                                            block_limit_hit = BlockLimitFunction::LIMIT_REACHED;
-                                            return Ok(false);
-                                        }
-                                    }
-                                    Error::InvalidStacksTransaction(_, true) => {
-                                        // if we have an invalid transaction that was quietly ignored, don't warn here either
-                                    }
-                                    e => {
-                                        warn!("Failed to apply tx {}: {:?}", &txinfo.tx.txid(), &e);
-                                        return Ok(true);
-                                    }
-                                }
-                            }
-                        }
 
-                        mined_origin_nonces
-                            .insert(txinfo.tx.origin_address(), txinfo.tx.get_origin_nonce());
-                        if let (Some(sponsor_addr), Some(sponsor_nonce)) =
-                            (txinfo.tx.sponsor_address(), txinfo.tx.get_sponsor_nonce())
-                        {
-                            mined_sponsor_nonces.insert(sponsor_addr, sponsor_nonce);
+                        if let Some(ref mut replay) = replay {
+                            replay.candidates.push(AssemblyCandidate {
+                                txid: txinfo.metadata.txid.clone(),
+                                tx: to_hex(&txinfo.tx.serialize_to_vec()),
+                                len: txinfo.metadata.len,
+                                update_estimate: to_consider.update_estimate,
+                                decision,
+                            });
                         }
 
-                        Ok(true)
+                        Ok(keep_going)
                     },
                 );
 
@@ -2006,14 +2220,20 @@ impl StacksBlockBuilder {
                     break;
                 }
 
-                if num_considered == 0 {
+                if selection.considered.len() == num_considered_before {
                     break;
                 }
             }
-            debug!("Anchored block transaction selection finished (child of {}): {} transactions selected ({} considered)", &parent_stacks_header.anchored_header.block_hash(), num_txs, considered.len());
+            debug!("Anchored block transaction selection finished (child of {}): {} transactions selected ({} considered)", &parent_stacks_header.anchored_header.block_hash(), selection.num_txs, selection.considered.len());
             intermediate_result
         };
 
+        let AnchoredBlockSelection {
+            invalidated_txs,
+            tx_events,
+            ..
+        } = selection;
+
         mempool.drop_txs(&invalidated_txs)?;
         if let Some(observer) = event_observer {
             observer.mempool_txs_dropped(invalidated_txs, MemPoolDropReason::TOO_EXPENSIVE);
@@ -2024,6 +2244,10 @@ impl StacksBlockBuilder {
             Err(e) => {
                 warn!("Failure building block: {}", e);
                 epoch_tx.rollback_block();
+                if let (Some(replay_dir), Some(mut replay)) = (settings.replay_dir, replay) {
+                    replay.error = Some(e.to_string());
+                    replay.store(&replay_dir, ts_start);
+                }
                 return Err(e);
             }
         }
@@ -2038,6 +2262,11 @@ impl StacksBlockBuilder {
 
         let ts_end = get_epoch_time_ms();
 
+        if let (Some(replay_dir), Some(mut replay)) = (settings.replay_dir, replay) {
+            replay.block = Some(to_hex(&block.serialize_to_vec()));
+            replay.store(&replay_dir, ts_start);
+        }
+
         if let Some(observer) = event_observer {
             observer.mined_block_event(
                 SortitionDB::get_canonical_burn_chain_tip(burn_dbconn.conn())?.block_height + 1,
@@ -2067,6 +2296,102 @@ impl StacksBlockBuilder {
 
         Ok((block, consumed, size))
     }
+
+    /// Re-run a recorded anchored block assembly attempt against this node's chainstate.  The
+    /// assembler is fed the same candidate transactions in the same order, on top of the same
+    /// parent block, parent microblock stream, and burnchain tip, and runs out of time at the
+    /// same point as the original attempt.  The parent block must have been processed by this
+    /// node.  Nothing is written to the chainstate.
+    /// Returns the assembled block, and what the assembler did with each candidate.
+    pub fn replay_anchored_block(
+        chainstate_handle: &StacksChainState,
+        burn_dbconn: &SortitionDBConn,
+        replay: &BlockAssemblyReplay,
+    ) -> Result<(StacksBlock, Vec<AssemblyDecision>), Error> {
+        let proof = VRFProof::from_hex(&replay.proof).ok_or(Error::InvalidStacksBlock(
+            "Invalid VRF proof in replay".to_string(),
+        ))?;
+        let burn_tip = BurnchainHeaderHash::from_hex(&replay.burn_tip)
+            .map_err(|_| Error::InvalidStacksBlock("Invalid burn tip in replay".to_string()))?;
+        let coinbase_tx: StacksTransaction = BlockAssemblyReplay::decode_hex(&replay.coinbase_tx)?;
+        let mut candidate_txs = Vec::with_capacity(replay.candidates.len());
+        for candidate in replay.candidates.iter() {
+            let tx: StacksTransaction = BlockAssemblyReplay::decode_hex(&candidate.tx)?;
+            candidate_txs.push(tx);
+        }
+
+        let (mut chainstate, _) = chainstate_handle.reopen()?;
+
+        let mut parent_stacks_header =
+            if replay.parent_consensus_hash == FIRST_BURNCHAIN_CONSENSUS_HASH {
+                StacksChainState::get_genesis_header_info(chainstate.db())?
+            } else {
+                StacksChainState::get_anchored_block_header_info(
+                    chainstate.db(),
+                    &replay.parent_consensus_hash,
+                    &replay.parent_block_hash,
+                )?
+                .ok_or(Error::NoSuchBlockError)?
+            };
+        parent_stacks_header.microblock_tail = match replay.parent_microblock_tail {
+            Some(ref tail_hex) => Some(BlockAssemblyReplay::decode_hex(tail_hex)?),
+            None => None,
+        };
+
+        debug!(
+            "Replay anchored block assembly off of {}/{} height {} ({} candidates)",
+            &replay.parent_consensus_hash,
+            &replay.parent_block_hash,
+            parent_stacks_header.block_height,
+            replay.candidates.len()
+        );
+
+        let mut builder = StacksBlockBuilder::make_block_builder(
+            chainstate.mainnet,
+            &parent_stacks_header,
+            proof,
+            replay.total_burn,
+            replay.microblock_pubkey_hash.clone(),
+        )?;
+
+        let mut miner_epoch_info = builder.pre_epoch_begin(&mut chainstate, burn_dbconn)?;
+
+        // build on the burnchain tip the miner saw, not the one we see now
+        miner_epoch_info.burn_tip = burn_tip;
+        miner_epoch_info.burn_tip_height = replay.burn_tip_height;
+
+        let (mut epoch_tx, _) = builder.epoch_begin(burn_dbconn, &mut miner_epoch_info)?;
+        let stacks_epoch_id = epoch_tx.get_epoch();
+        let block_limit = epoch_tx
+            .block_limit()
+            .expect("Failed to obtain block limit from miner's block connection");
+
+        let mut selection = AnchoredBlockSelection::new(vec![builder
+            .try_mine_tx(&mut epoch_tx, &coinbase_tx)?
+            .convert_to_event()]);
+
+        let mut decisions = Vec::with_capacity(replay.candidates.len());
+        for (candidate, tx) in replay.candidates.iter().zip(candidate_txs.iter()) {
+            // the only input that can't be reproduced is the wall clock, so use the original
+            // attempt's timing
+            let (decision, _) = selection.consider_tx(
+                &mut builder,
+                &mut epoch_tx,
+                tx,
+                candidate.len,
+                candidate.decision == AssemblyDecision::OutOfTime,
+                None,
+                &block_limit,
+                &stacks_epoch_id,
+            );
+            decisions.push(decision);
+        }
+
+        let block = builder.mine_anchored_block(&mut epoch_tx);
+        epoch_tx.rollback_block();
+
+        Ok((block, decisions))
+    }
 }
 
 #[cfg(test)]
@@ -7084,6 +7409,173 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_build_anchored_blocks_replay_assembly() {
+        let privk = StacksPrivateKey::from_hex(
+            "42faca653724860da7a41bfcef7e6ba78db55146f6900de8cb2a9f760ffac70c01",
+        )
+        .unwrap();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk)],
+        )
+        .unwrap();
+
+        let mut peer_config =
+            TestPeerConfig::new("test_build_anchored_blocks_replay_assembly", 2040, 2041);
+        peer_config.initial_balances = vec![(addr.to_account_principal(), 1000000000)];
+
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+        let replay_dir = format!("{}.replay", &chainstate_path);
+        if fs::metadata(&replay_dir).is_ok() {
+            fs::remove_dir_all(&replay_dir).unwrap();
+        }
+
+        let num_blocks = 3;
+        let recipient_addr_str = "ST1RFD5Q2QPK3E0F08HG9XDX7SSC7CNRS0QR0SGEV";
+        let recipient = StacksAddress::from_string(recipient_addr_str).unwrap();
+        let mut sender_nonce = 0;
+
+        for tenure_id in 0..num_blocks {
+            // send transactions to the mempool
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let parent_header_hash = parent_tip.anchored_header.block_hash();
+                    let parent_consensus_hash = parent_tip.consensus_hash.clone();
+
+                    let mut mempool =
+                        MemPoolDB::open_test(false, 0x80000000, &chainstate_path).unwrap();
+
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    if tenure_id > 0 {
+                        for _ in 0..2 {
+                            let stx_transfer = make_user_stacks_transfer(
+                                &privk,
+                                sender_nonce,
+                                200,
+                                &recipient.to_account_principal(),
+                                1,
+                            );
+                            sender_nonce += 1;
+
+                            mempool
+                                .submit(
+                                    chainstate,
+                                    &parent_consensus_hash,
+                                    &parent_header_hash,
+                                    &stx_transfer,
+                                    None,
+                                    &ExecutionCost::max_value(),
+                                    &StacksEpochId::Epoch20,
+                                )
+                                .unwrap();
+                        }
+                    }
+
+                    let tenure_replay_dir = format!("{}/{}", &replay_dir, tenure_id);
+                    let anchored_block = StacksBlockBuilder::build_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mut mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        BlockBuilderSettings {
+                            replay_dir: Some(tenure_replay_dir.clone()),
+                            ..BlockBuilderSettings::max_value()
+                        },
+                        None,
+                    )
+                    .unwrap();
+
+                    // exactly one replay was written, and it records what happened
+                    let replay_paths: Vec<_> = fs::read_dir(&tenure_replay_dir)
+                        .unwrap()
+                        .map(|entry| entry.unwrap().path())
+                        .collect();
+                    assert_eq!(replay_paths.len(), 1);
+
+                    let replay =
+                        BlockAssemblyReplay::from_path(replay_paths[0].to_str().unwrap()).unwrap();
+                    assert_eq!(replay.get_block().unwrap(), Some(anchored_block.0.clone()));
+                    assert!(replay.error.is_none());
+                    if tenure_id > 0 {
+                        assert_eq!(replay.candidates.len(), 2);
+                        for candidate in replay.candidates.iter() {
+                            assert_eq!(candidate.decision, AssemblyDecision::Mined);
+                        }
+                    } else {
+                        assert_eq!(replay.candidates.len(), 0);
+                    }
+
+                    // replaying the assembly reproduces the block and the decisions
+                    let (replayed_block, decisions) = StacksBlockBuilder::replay_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &replay,
+                    )
+                    .unwrap();
+                    assert_eq!(replayed_block, anchored_block.0);
+                    assert_eq!(
+                        decisions,
+                        replay
+                            .candidates
+                            .iter()
+                            .map(|candidate| candidate.decision.clone())
+                            .collect::<Vec<_>>()
+                    );
+
+                    (anchored_block.0, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            if tenure_id > 0 {
+                assert_eq!(stacks_block.txs.len(), 3);
+            }
+        }
+    }
+
     #[test]
     fn test_build_anchored_blocks_empty_with_builder_timeout() {
         let privk = StacksPrivateKey::from_hex(
@@ -10072,6 +10564,7 @@ pub mod test {
             get_bulk_initial_balances: None,
             get_bulk_initial_names: None,
             get_bulk_initial_namespaces: None,
+            boot_contract_overrides: vec![],
         };
 
         StacksChainState::open_and_exec(mainnet, chain_id, &path, Some(&mut boot_data))
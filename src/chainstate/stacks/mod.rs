@@ -68,7 +68,9 @@ pub mod boot;
 pub mod db;
 pub mod events;
 pub mod index;
+pub mod microblock;
 pub mod miner;
+pub mod postconditions;
 pub mod transaction;
 
 pub type StacksPublicKey = secp256k1::Secp256k1PublicKey;
@@ -0,0 +1,686 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Evaluation of transaction post-conditions against the assets a transaction moved.  This is
+//! used by block processing, but does not depend on any chainstate, so the mempool and the RPC
+//! interface can use it to check post-conditions against the outflows they expect a transaction
+//! to have.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chainstate::stacks::events::{FTEventType, NFTEventType, STXEventType, StacksTransactionEvent};
+use chainstate::stacks::{
+    FungibleConditionCode, NonfungibleConditionCode, TransactionPostCondition,
+    TransactionPostConditionMode,
+};
+use vm::contexts::{AssetMap, AssetMapEntry};
+use vm::errors::InterpreterResult;
+use vm::types::{
+    AssetIdentifier, PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, Value,
+};
+
+/// Why a transaction's post-conditions did not hold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostConditionFailure {
+    /// The STX or fungible token post-condition at `index` did not hold.  STX post-conditions
+    /// report `AssetIdentifier::STX()` as their asset, and count burnt STX as sent.
+    FungibleConditionNotMet {
+        index: usize,
+        principal: PrincipalData,
+        asset: AssetIdentifier,
+        condition_code: FungibleConditionCode,
+        amount: u128,
+        amount_sent: u128,
+    },
+    /// The non-fungible token post-condition at `index` did not hold.
+    NonfungibleConditionNotMet {
+        index: usize,
+        principal: PrincipalData,
+        asset: AssetIdentifier,
+        value: Value,
+        condition_code: NonfungibleConditionCode,
+    },
+    /// The STX post-condition at `index` could not be checked, because the STX that `principal`
+    /// sent and burnt add up to more than a u128 can hold.
+    StxAmountOverflow {
+        index: usize,
+        principal: PrincipalData,
+    },
+    /// In deny mode, `principal` sent STX or a fungible token that no post-condition covers.
+    UncheckedFungibleTransfer {
+        principal: PrincipalData,
+        asset: AssetIdentifier,
+    },
+    /// In deny mode, `principal` sent a non-fungible token that no post-condition covers.
+    /// `value` is None if no post-condition covers this asset type at all.
+    UncheckedNonfungibleTransfer {
+        principal: PrincipalData,
+        asset: AssetIdentifier,
+        value: Option<Value>,
+    },
+}
+
+/// How an asset is named in a failure.  STX and burnt STX are tracked under placeholder asset
+/// identifiers that don't mean anything to a user.
+fn asset_name(asset: &AssetIdentifier) -> String {
+    if *asset == AssetIdentifier::STX() {
+        "STX".to_string()
+    } else if *asset == AssetIdentifier::STX_burned() {
+        "burned STX".to_string()
+    } else {
+        asset.to_string()
+    }
+}
+
+impl fmt::Display for PostConditionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PostConditionFailure::FungibleConditionNotMet {
+                index,
+                principal,
+                asset,
+                condition_code,
+                amount,
+                amount_sent,
+            } => write!(
+                f,
+                "post-condition {} on {} owned by {} failed: {:?} {}, but sent {}",
+                index,
+                asset_name(asset),
+                principal,
+                condition_code,
+                amount,
+                amount_sent
+            ),
+            PostConditionFailure::NonfungibleConditionNotMet {
+                index,
+                principal,
+                asset,
+                value,
+                condition_code,
+            } => write!(
+                f,
+                "post-condition {} on {} owned by {} failed: {:?} {}",
+                index,
+                asset_name(asset),
+                principal,
+                condition_code,
+                value
+            ),
+            PostConditionFailure::StxAmountOverflow { index, principal } => write!(
+                f,
+                "post-condition {} on STX owned by {} failed: the STX sent and burned overflows",
+                index, principal
+            ),
+            PostConditionFailure::UncheckedFungibleTransfer { principal, asset } => {
+                write!(
+                    f,
+                    "{} was moved by {} but not checked",
+                    asset_name(asset),
+                    principal
+                )
+            }
+            PostConditionFailure::UncheckedNonfungibleTransfer {
+                principal,
+                asset,
+                value: Some(value),
+            } => write!(
+                f,
+                "{} value {} was moved by {} but not checked",
+                asset_name(asset),
+                value,
+                principal
+            ),
+            PostConditionFailure::UncheckedNonfungibleTransfer {
+                principal,
+                asset,
+                value: None,
+            } => write!(
+                f,
+                "no checks for non-fungible asset type {} moved by {}",
+                asset_name(asset),
+                principal
+            ),
+        }
+    }
+}
+
+impl PostConditionFailure {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        match self {
+            PostConditionFailure::FungibleConditionNotMet {
+                index,
+                principal,
+                asset,
+                condition_code,
+                amount,
+                amount_sent,
+            } => json!({
+                "type": "fungible_condition_not_met",
+                "index": index,
+                "principal": principal.to_string(),
+                "asset": asset_name(asset),
+                "condition_code": format!("{:?}", condition_code),
+                "amount": amount.to_string(),
+                "amount_sent": amount_sent.to_string(),
+            }),
+            PostConditionFailure::NonfungibleConditionNotMet {
+                index,
+                principal,
+                asset,
+                value,
+                condition_code,
+            } => json!({
+                "type": "nonfungible_condition_not_met",
+                "index": index,
+                "principal": principal.to_string(),
+                "asset": asset_name(asset),
+                "value": value.to_string(),
+                "condition_code": format!("{:?}", condition_code),
+            }),
+            PostConditionFailure::StxAmountOverflow { index, principal } => json!({
+                "type": "stx_amount_overflow",
+                "index": index,
+                "principal": principal.to_string(),
+            }),
+            PostConditionFailure::UncheckedFungibleTransfer { principal, asset } => json!({
+                "type": "unchecked_fungible_transfer",
+                "principal": principal.to_string(),
+                "asset": asset_name(asset),
+            }),
+            PostConditionFailure::UncheckedNonfungibleTransfer {
+                principal,
+                asset,
+                value,
+            } => json!({
+                "type": "unchecked_nonfungible_transfer",
+                "principal": principal.to_string(),
+                "asset": asset_name(asset),
+                "value": value.as_ref().map(|v| v.to_string()),
+            }),
+        }
+    }
+}
+
+/// Build the asset map of everything sent by the principals in a list of transaction events.
+/// Mints, STX locks and contract events move nothing out of a principal's account, so they are
+/// ignored.  Burns are counted as sent by their owner, the same way the VM records them.
+pub fn asset_map_from_events(events: &[StacksTransactionEvent]) -> InterpreterResult<AssetMap> {
+    let mut asset_map = AssetMap::new();
+    for event in events.iter() {
+        match event {
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(data)) => {
+                asset_map.add_stx_transfer(&data.sender, data.amount)?;
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(data)) => {
+                asset_map.add_stx_burn(&data.sender, data.amount)?;
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(data)) => {
+                asset_map.add_token_transfer(
+                    &data.sender,
+                    data.asset_identifier.clone(),
+                    data.amount,
+                )?;
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(data)) => {
+                asset_map.add_token_transfer(
+                    &data.sender,
+                    data.asset_identifier.clone(),
+                    data.amount,
+                )?;
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(data)) => {
+                asset_map.add_asset_transfer(
+                    &data.sender,
+                    data.asset_identifier.clone(),
+                    data.value.clone(),
+                );
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(data)) => {
+                asset_map.add_asset_transfer(
+                    &data.sender,
+                    data.asset_identifier.clone(),
+                    data.value.clone(),
+                );
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
+            | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_))
+            | StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(_))
+            | StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(_))
+            | StacksTransactionEvent::SmartContractEvent(_) => {}
+        }
+    }
+    Ok(asset_map)
+}
+
+/// Check a transaction's post-conditions against the assets it moved.  `origin` is the
+/// transaction's origin account, which `PostConditionPrincipal::Origin` refers to.
+/// Returns the first post-condition failure found, if any.
+pub fn check_post_conditions(
+    post_conditions: &[TransactionPostCondition],
+    post_condition_mode: &TransactionPostConditionMode,
+    origin: &PrincipalData,
+    asset_map: &AssetMap,
+) -> Result<(), PostConditionFailure> {
+    let mut checked_fungible_assets: HashMap<PrincipalData, HashSet<AssetIdentifier>> =
+        HashMap::new();
+    let mut checked_nonfungible_assets: HashMap<
+        PrincipalData,
+        HashMap<AssetIdentifier, HashSet<Value>>,
+    > = HashMap::new();
+    let allow_unchecked_assets = *post_condition_mode == TransactionPostConditionMode::Allow;
+
+    for (index, postcond) in post_conditions.iter().enumerate() {
+        match postcond {
+            TransactionPostCondition::STX(
+                ref principal,
+                ref condition_code,
+                ref amount_sent_condition,
+            ) => {
+                let account_principal = principal.to_principal_data(origin);
+
+                let amount_transferred = asset_map.get_stx(&account_principal).unwrap_or(0);
+                let amount_burned = asset_map.get_stx_burned(&account_principal).unwrap_or(0);
+
+                // a block can't move this much STX, but a client-supplied event list can
+                let amount_sent = match amount_transferred.checked_add(amount_burned) {
+                    Some(amount_sent) => amount_sent,
+                    None => {
+                        return Err(PostConditionFailure::StxAmountOverflow {
+                            index,
+                            principal: account_principal,
+                        });
+                    }
+                };
+
+                if !condition_code.check(*amount_sent_condition as u128, amount_sent) {
+                    return Err(PostConditionFailure::FungibleConditionNotMet {
+                        index,
+                        principal: account_principal,
+                        asset: AssetIdentifier::STX(),
+                        condition_code: *condition_code,
+                        amount: *amount_sent_condition as u128,
+                        amount_sent,
+                    });
+                }
+
+                let asset_ids = checked_fungible_assets
+                    .entry(account_principal)
+                    .or_default();
+                if amount_transferred > 0 {
+                    asset_ids.insert(AssetIdentifier::STX());
+                }
+                if amount_burned > 0 {
+                    asset_ids.insert(AssetIdentifier::STX_burned());
+                }
+            }
+            TransactionPostCondition::Fungible(
+                ref principal,
+                ref asset_info,
+                ref condition_code,
+                ref amount_sent_condition,
+            ) => {
+                let account_principal = principal.to_principal_data(origin);
+                let asset_id = AssetIdentifier {
+                    contract_identifier: QualifiedContractIdentifier::new(
+                        StandardPrincipalData::from(asset_info.contract_address),
+                        asset_info.contract_name.clone(),
+                    ),
+                    asset_name: asset_info.asset_name.clone(),
+                };
+
+                let amount_sent = asset_map
+                    .get_fungible_tokens(&account_principal, &asset_id)
+                    .unwrap_or(0);
+                if !condition_code.check(*amount_sent_condition as u128, amount_sent) {
+                    return Err(PostConditionFailure::FungibleConditionNotMet {
+                        index,
+                        principal: account_principal,
+                        asset: asset_id,
+                        condition_code: *condition_code,
+                        amount: *amount_sent_condition as u128,
+                        amount_sent,
+                    });
+                }
+
+                checked_fungible_assets
+                    .entry(account_principal)
+                    .or_default()
+                    .insert(asset_id);
+            }
+            TransactionPostCondition::Nonfungible(
+                ref principal,
+                ref asset_info,
+                ref asset_value,
+                ref condition_code,
+            ) => {
+                let account_principal = principal.to_principal_data(origin);
+                let asset_id = AssetIdentifier {
+                    contract_identifier: QualifiedContractIdentifier::new(
+                        StandardPrincipalData::from(asset_info.contract_address),
+                        asset_info.contract_name.clone(),
+                    ),
+                    asset_name: asset_info.asset_name.clone(),
+                };
+
+                let empty_assets = vec![];
+                let assets_sent = asset_map
+                    .get_nonfungible_tokens(&account_principal, &asset_id)
+                    .unwrap_or(&empty_assets);
+                if !condition_code.check(asset_value, assets_sent) {
+                    return Err(PostConditionFailure::NonfungibleConditionNotMet {
+                        index,
+                        principal: account_principal,
+                        asset: asset_id,
+                        value: asset_value.clone(),
+                        condition_code: *condition_code,
+                    });
+                }
+
+                checked_nonfungible_assets
+                    .entry(account_principal)
+                    .or_default()
+                    .entry(asset_id)
+                    .or_default()
+                    .insert(asset_value.clone());
+            }
+        }
+    }
+
+    if !allow_unchecked_assets {
+        // make sure every asset transferred is covered by a postcondition
+        let all_assets_sent = asset_map.clone().to_table();
+        for (principal, assets) in all_assets_sent.into_iter() {
+            for (asset_identifier, asset_entry) in assets.into_iter() {
+                match asset_entry {
+                    AssetMapEntry::Asset(values) => {
+                        // this is a NFT; each value must be covered
+                        let nfts = match checked_nonfungible_assets
+                            .get(&principal)
+                            .and_then(|checked| checked.get(&asset_identifier))
+                        {
+                            Some(nfts) => nfts,
+                            None => {
+                                return Err(PostConditionFailure::UncheckedNonfungibleTransfer {
+                                    principal,
+                                    asset: asset_identifier,
+                                    value: None,
+                                });
+                            }
+                        };
+                        for v in values {
+                            if !nfts.contains(&v) {
+                                return Err(PostConditionFailure::UncheckedNonfungibleTransfer {
+                                    principal,
+                                    asset: asset_identifier,
+                                    value: Some(v),
+                                });
+                            }
+                        }
+                    }
+                    _ => {
+                        // This is STX or a fungible token
+                        let checked = checked_fungible_assets
+                            .get(&principal)
+                            .map(|asset_ids| asset_ids.contains(&asset_identifier))
+                            .unwrap_or(false);
+                        if !checked {
+                            return Err(PostConditionFailure::UncheckedFungibleTransfer {
+                                principal,
+                                asset: asset_identifier,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use chainstate::stacks::events::{NFTTransferEventData, STXBurnEventData, STXMintEventData};
+    use chainstate::stacks::events::{STXTransferEventData, StacksTransactionEvent};
+    use chainstate::stacks::{AssetInfo, PostConditionPrincipal};
+    use util::hash::Hash160;
+    use vm::representations::{ClarityName, ContractName};
+
+    use crate::types::chainstate::StacksAddress;
+
+    fn addr(byte: u8) -> StacksAddress {
+        StacksAddress {
+            version: 1,
+            bytes: Hash160([byte; 20]),
+        }
+    }
+
+    fn nft_asset() -> (AssetInfo, AssetIdentifier) {
+        let info = AssetInfo {
+            contract_address: addr(0xff),
+            contract_name: ContractName::try_from("names".to_string()).unwrap(),
+            asset_name: ClarityName::try_from("name".to_string()).unwrap(),
+        };
+        let id = AssetIdentifier {
+            contract_identifier: QualifiedContractIdentifier::new(
+                StandardPrincipalData::from(info.contract_address),
+                info.contract_name.clone(),
+            ),
+            asset_name: info.asset_name.clone(),
+        };
+        (info, id)
+    }
+
+    #[test]
+    fn test_asset_map_from_events() {
+        let origin = PrincipalData::from(addr(1));
+        let recipient = PrincipalData::from(addr(2));
+        let (_, nft_id) = nft_asset();
+
+        let events = vec![
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(
+                STXTransferEventData {
+                    sender: origin.clone(),
+                    recipient: recipient.clone(),
+                    amount: 100,
+                },
+            )),
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(
+                STXTransferEventData {
+                    sender: origin.clone(),
+                    recipient: recipient.clone(),
+                    amount: 23,
+                },
+            )),
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(STXBurnEventData {
+                sender: origin.clone(),
+                amount: 7,
+            })),
+            StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(STXMintEventData {
+                recipient: origin.clone(),
+                amount: 1000,
+            })),
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(
+                NFTTransferEventData {
+                    asset_identifier: nft_id.clone(),
+                    sender: recipient.clone(),
+                    recipient: origin.clone(),
+                    value: Value::Int(1),
+                },
+            )),
+        ];
+
+        let asset_map = asset_map_from_events(&events).unwrap();
+        assert_eq!(asset_map.get_stx(&origin), Some(123));
+        assert_eq!(asset_map.get_stx_burned(&origin), Some(7));
+        assert_eq!(asset_map.get_stx(&recipient), None);
+        assert_eq!(
+            asset_map.get_nonfungible_tokens(&recipient, &nft_id),
+            Some(&vec![Value::Int(1)])
+        );
+        assert_eq!(asset_map.get_nonfungible_tokens(&origin, &nft_id), None);
+    }
+
+    #[test]
+    fn test_check_post_conditions_stx_overflow() {
+        let origin = PrincipalData::from(addr(1));
+        let events = vec![
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(
+                STXTransferEventData {
+                    sender: origin.clone(),
+                    recipient: PrincipalData::from(addr(2)),
+                    amount: u128::MAX,
+                },
+            )),
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(STXBurnEventData {
+                sender: origin.clone(),
+                amount: 1,
+            })),
+        ];
+        let asset_map = asset_map_from_events(&events).unwrap();
+
+        let stx = TransactionPostCondition::STX(
+            PostConditionPrincipal::Origin,
+            FungibleConditionCode::SentGt,
+            0,
+        );
+        assert_eq!(
+            check_post_conditions(
+                &[stx],
+                &TransactionPostConditionMode::Allow,
+                &origin,
+                &asset_map
+            ),
+            Err(PostConditionFailure::StxAmountOverflow {
+                index: 0,
+                principal: origin.clone(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_post_conditions_verdicts() {
+        let origin = PrincipalData::from(addr(1));
+        let (nft_info, nft_id) = nft_asset();
+
+        let mut asset_map = AssetMap::new();
+        asset_map.add_stx_transfer(&origin, 100).unwrap();
+        asset_map.add_asset_transfer(&origin, nft_id.clone(), Value::Int(1));
+
+        let stx_ok = TransactionPostCondition::STX(
+            PostConditionPrincipal::Origin,
+            FungibleConditionCode::SentEq,
+            100,
+        );
+        let stx_bad = TransactionPostCondition::STX(
+            PostConditionPrincipal::Origin,
+            FungibleConditionCode::SentLt,
+            100,
+        );
+        let nft_ok = TransactionPostCondition::Nonfungible(
+            PostConditionPrincipal::Origin,
+            nft_info.clone(),
+            Value::Int(1),
+            NonfungibleConditionCode::Sent,
+        );
+        let nft_bad = TransactionPostCondition::Nonfungible(
+            PostConditionPrincipal::Standard(addr(1)),
+            nft_info.clone(),
+            Value::Int(1),
+            NonfungibleConditionCode::NotSent,
+        );
+
+        assert_eq!(
+            check_post_conditions(
+                &[stx_ok.clone(), nft_ok.clone()],
+                &TransactionPostConditionMode::Deny,
+                &origin,
+                &asset_map
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check_post_conditions(
+                std::slice::from_ref(&stx_ok),
+                &TransactionPostConditionMode::Allow,
+                &origin,
+                &asset_map
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check_post_conditions(
+                &[stx_ok.clone(), stx_bad],
+                &TransactionPostConditionMode::Allow,
+                &origin,
+                &asset_map
+            ),
+            Err(PostConditionFailure::FungibleConditionNotMet {
+                index: 1,
+                principal: origin.clone(),
+                asset: AssetIdentifier::STX(),
+                condition_code: FungibleConditionCode::SentLt,
+                amount: 100,
+                amount_sent: 100,
+            })
+        );
+        assert_eq!(
+            check_post_conditions(
+                &[nft_bad],
+                &TransactionPostConditionMode::Allow,
+                &origin,
+                &asset_map
+            ),
+            Err(PostConditionFailure::NonfungibleConditionNotMet {
+                index: 0,
+                principal: origin.clone(),
+                asset: nft_id.clone(),
+                value: Value::Int(1),
+                condition_code: NonfungibleConditionCode::NotSent,
+            })
+        );
+        assert_eq!(
+            check_post_conditions(
+                &[stx_ok],
+                &TransactionPostConditionMode::Deny,
+                &origin,
+                &asset_map
+            ),
+            Err(PostConditionFailure::UncheckedNonfungibleTransfer {
+                principal: origin.clone(),
+                asset: nft_id.clone(),
+                value: None,
+            })
+        );
+        assert_eq!(
+            check_post_conditions(
+                &[nft_ok],
+                &TransactionPostConditionMode::Deny,
+                &origin,
+                &asset_map
+            ),
+            Err(PostConditionFailure::UncheckedFungibleTransfer {
+                principal: origin.clone(),
+                asset: AssetIdentifier::STX(),
+            })
+        );
+    }
+}
@@ -32,25 +32,32 @@ use rusqlite::{Connection, OpenFlags, NO_PARAMS};
 use address::c32::c32_address;
 use chainstate::stacks::index::{storage::TrieFileStorage, MarfTrieId};
 use util::db::sqlite_open;
+use util::db::DBConn;
 use util::db::FromColumn;
 use util::hash::{bytes_to_hex, Sha512Trunc256Sum};
 
 use util::log;
-use vm::ContractName;
+use vm::{ClarityName, ContractName};
 
 use vm::analysis;
 use vm::analysis::contract_interface_builder::build_contract_interface;
+use vm::analysis::linter::{lint_contract, Lint, LintConfig, LintLevel, LintRule, Linter};
 use vm::analysis::{errors::CheckError, errors::CheckResult, AnalysisDatabase, ContractAnalysis};
-use vm::ast::build_ast;
-use vm::contexts::{AssetMap, OwnedEnvironment};
+use vm::ast::formatter::format_source;
+use vm::ast::json::contract_ast_to_json;
+use vm::ast::{build_ast, build_pre_ast};
+use vm::bindings::generator::generate_bindings;
+use vm::contexts::{AssetMap, Environment, LocalContext, OwnedEnvironment};
 use vm::costs::ExecutionCost;
 use vm::costs::LimitedCostTracker;
+use vm::coverage::CoverageReporter;
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, STXBalance, SqliteConnection, NULL_BURN_STATE_DB,
 };
 use vm::errors::{Error, InterpreterResult, RuntimeErrorType};
-use vm::types::{PrincipalData, QualifiedContractIdentifier};
-use vm::{SymbolicExpression, SymbolicExpressionType, Value};
+use vm::profiler::{CostDimension, CostProfiler};
+use vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData};
+use vm::{eval, SymbolicExpression, SymbolicExpressionType, Value};
 
 use burnchains::PoxConstants;
 use burnchains::Txid;
@@ -69,10 +76,24 @@ use codec::StacksMessageCodec;
 
 use std::convert::TryFrom;
 
+use crate::chainstate::stacks::db::blocks::MINIMUM_TX_FEE_RATE_PER_BYTE;
+use crate::chainstate::stacks::{
+    StacksPrivateKey, StacksTransaction, TransactionAuth, TransactionContractCall,
+    TransactionPayload, TransactionVersion, MAX_BLOCK_LEN,
+};
+use crate::clarity_vm::clarity::{ClarityConnection, ClarityInstance, SimulatedTransaction};
 use crate::clarity_vm::database::marf::MarfedKV;
 use crate::clarity_vm::database::marf::WritableMarfStore;
 use crate::clarity_vm::database::MemoryBackingStore;
 use crate::core::StacksEpochId;
+use crate::core::{STACKS_EPOCHS_MAINNET, STACKS_EPOCHS_TESTNET};
+use crate::cost_estimates::fee_medians::WeightedMedianFeeRateEstimator;
+use crate::cost_estimates::fee_percentiles::PercentileFeeRateEstimator;
+use crate::cost_estimates::fee_regression::RegressionFeeRateEstimator;
+use crate::cost_estimates::fee_scalar::ScalarFeeRateEstimator;
+use crate::cost_estimates::metrics::{CostMetric, ProportionalDotProduct};
+use crate::cost_estimates::{dimension_proportions, FeeEstimator};
+use crate::net::RPCFeeEstimate;
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::BurnchainHeaderHash;
 use crate::types::chainstate::StacksAddress;
@@ -112,7 +133,14 @@ where command is one of:
                      must be passed eval string via stdin.
   eval_raw           to typecheck and evaluate an expression without a contract or database context.
   repl               to typecheck and evaluate expressions in a stdin/stdout loop.
+  session            to evaluate expressions and read-only calls against a node's chainstate.
   execute            to execute a public function of a defined contract.
+  estimate_cost      to estimate the cost and fee of a public function call against a node's chainstate.
+  coverage           to produce a code coverage report from `launch` and `execute` runs.
+  ast                to print the JSON abstract syntax tree of a contract.
+  fmt                to format a contract's source canonically.
+  lint               to report likely mistakes in a contract, according to configurable rules.
+  bindings           to generate Rust bindings for calling a contract's functions.
   generate_address   to generate a random Stacks public address for testing purposes.
 ",
         invoked_by
@@ -138,6 +166,9 @@ fn friendly_expect_opt<A>(input: Option<A>, msg: &str) -> A {
 
 pub const DEFAULT_CLI_EPOCH: StacksEpochId = StacksEpochId::Epoch2_05;
 
+/// Number of blocks a node's default windowed fee estimators take fee rates over
+const FEE_RATE_WINDOW_SIZE: u32 = 5;
+
 struct EvalInput {
     marf_kv: MarfedKV,
     contract_identifier: QualifiedContractIdentifier,
@@ -202,6 +233,7 @@ fn run_analysis_free<C: ClarityStorage>(
         &mut marf_kv.get_analysis_db(),
         save_contract,
         LimitedCostTracker::new_free(),
+        DEFAULT_CLI_EPOCH,
     )
 }
 
@@ -230,9 +262,41 @@ fn run_analysis<C: ClarityStorage>(
         &mut marf_kv.get_analysis_db(),
         save_contract,
         cost_track,
+        DEFAULT_CLI_EPOCH,
     )
 }
 
+fn lint_to_json(lint: &Lint) -> serde_json::Value {
+    json!({
+        "rule": lint.rule.get_name_str(),
+        "level": lint.level,
+        "message": lint.message,
+        "line": lint.span.start_line,
+        "column": lint.span.start_column,
+    })
+}
+
+/// If `enabled`, add the opt-in `trait-call-before-write` warnings for `expressions` to the
+/// analysis output
+fn add_trait_call_warnings(
+    result: &mut serde_json::Value,
+    enabled: bool,
+    expressions: &[SymbolicExpression],
+) {
+    if enabled {
+        let mut config = LintConfig::default();
+        for rule in LintRule::ALL.iter() {
+            config.set_level(*rule, LintLevel::Allow);
+        }
+        config.set_level(LintRule::TraitCallBeforeWrite, LintLevel::Warn);
+        let warnings: Vec<_> = Linter::run(expressions, &config)
+            .iter()
+            .map(lint_to_json)
+            .collect();
+        result["warnings"] = serde_json::Value::Array(warnings);
+    }
+}
+
 fn create_or_open_db(path: &String) -> Connection {
     let open_flags = if path == ":memory:" {
         OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
@@ -408,7 +472,7 @@ where
 ///  for program evaluation, not by consensus critical code.
 pub fn vm_execute(program: &str) -> Result<Option<Value>, Error> {
     let contract_id = QualifiedContractIdentifier::transient();
-    let mut contract_context = ContractContext::new(contract_id.clone());
+    let mut contract_context = ContractContext::new(contract_id.clone(), DEFAULT_CLI_EPOCH);
     let mut marf = MemoryBackingStore::new();
     let conn = marf.as_clarity_db();
     let mut global_context = GlobalContext::new(
@@ -627,6 +691,359 @@ impl HeadersDB for CLIHeadersDB {
     }
 }
 
+const SESSION_HELP: &str =
+    "Enter a Clarity expression to evaluate it at the session's block, or one of:
+  :call CONTRACT FUNCTION ARGS...  call a read-only function of a contract
+  :in CONTRACT EXPRESSION          evaluate an expression in a contract's context
+  :at INDEX-BLOCK-HASH             move the session to another block
+  :tip                             print the block the session is at
+  :history                         print the session history
+  :help                            print this message
+  :quit                            end the session";
+
+/// Split the first whitespace-delimited word off of `input`
+fn split_word(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    match input.find(char::is_whitespace) {
+        Some(idx) => (&input[..idx], input[idx..].trim()),
+        None => (input, ""),
+    }
+}
+
+/// Lines entered in a session, optionally kept in a file across sessions
+struct SessionHistory {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl SessionHistory {
+    fn open(path: Option<PathBuf>) -> SessionHistory {
+        let entries = match path.as_ref().map(fs::read_to_string) {
+            Some(Ok(contents)) => contents.lines().map(|line| line.to_string()).collect(),
+            _ => vec![],
+        };
+        SessionHistory { path, entries }
+    }
+
+    fn push(&mut self, line: &str) {
+        if let Some(ref path) = self.path {
+            let appended = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", line));
+            if let Err(e) = appended {
+                eprintln!("Failed to save history to {:?}: {}", path, e);
+            }
+        }
+        self.entries.push(line.to_string());
+    }
+}
+
+/// A read-only view of a Stacks node's chainstate at a chosen block.  Nothing evaluated in a
+/// session is ever written back to the node's databases.
+struct NodeSession {
+    clarity_instance: ClarityInstance,
+    headers_db: DBConn,
+    mainnet: bool,
+    tip: StacksBlockId,
+    sender: PrincipalData,
+    history: SessionHistory,
+}
+
+impl NodeSession {
+    /// Open the chainstate at `chainstate_path` (e.g., `<working_dir>/mainnet/chainstate`).  If
+    /// no tip is given, the session starts at the highest block the node has processed.
+    fn open(
+        chainstate_path: &str,
+        tip: Option<&str>,
+        sender: PrincipalData,
+        history: SessionHistory,
+    ) -> Result<NodeSession, String> {
+        let mut vm_path = PathBuf::from(chainstate_path);
+        vm_path.push("vm");
+        let headers_path = vm_path.join("index.sqlite");
+        let clarity_path = vm_path.join("clarity");
+
+        if let Err(e) = fs::metadata(&headers_path) {
+            return Err(format!("Failed to access {:?}: {}", &headers_path, e));
+        }
+        let headers_db = sqlite_open(&headers_path, OpenFlags::SQLITE_OPEN_READ_ONLY, false)
+            .map_err(|e| format!("Failed to open {:?}: {:?}", &headers_path, e))?;
+        let mainnet: bool = headers_db
+            .query_row("SELECT mainnet FROM db_config LIMIT 1", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .map_err(|e| format!("Failed to read the chainstate config: {}", e))?;
+
+        let marf_kv = MarfedKV::open_readonly(
+            clarity_path
+                .to_str()
+                .ok_or_else(|| format!("Invalid path {:?}", &clarity_path))?,
+            None,
+        )
+        .map_err(|e| format!("Failed to open the Clarity state: {:?}", e))?;
+
+        let mut session = NodeSession {
+            clarity_instance: ClarityInstance::new(mainnet, marf_kv),
+            headers_db,
+            mainnet,
+            tip: StacksBlockId::sentinel(),
+            sender,
+            history,
+        };
+        match tip {
+            Some(tip) => session.set_tip(tip)?,
+            None => {
+                session.tip = session
+                    .headers_db
+                    .query_row(
+                        "SELECT index_block_hash FROM block_headers ORDER BY block_height DESC, index_block_hash ASC LIMIT 1",
+                        NO_PARAMS,
+                        |row| row.get(0),
+                    )
+                    .map_err(|e| format!("Failed to find the chain tip: {}", e))?;
+            }
+        }
+        Ok(session)
+    }
+
+    fn set_tip(&mut self, tip: &str) -> Result<(), String> {
+        let tip = StacksBlockId::from_hex(tip)
+            .map_err(|_| format!("Invalid index block hash: {}", tip))?;
+        if self
+            .headers_db
+            .get_burn_block_height_for_block(&tip)
+            .is_none()
+        {
+            return Err(format!("No such block: {}", tip));
+        }
+        self.tip = tip;
+        Ok(())
+    }
+
+    fn with_env<F>(&mut self, to_do: F) -> Result<Value, String>
+    where
+        F: FnOnce(&mut Environment) -> Result<Value, Error>,
+    {
+        let tip = &self.tip;
+        let mut conn = self
+            .clarity_instance
+            .read_only_connection_checked(tip, &self.headers_db, &NULL_BURN_STATE_DB)
+            .map_err(|e| format!("Failed to open block {}: {}", tip, e))?;
+        conn.with_readonly_clarity_env(
+            self.mainnet,
+            self.sender.clone(),
+            LimitedCostTracker::new_free(),
+            to_do,
+        )
+        .map_err(|e| format!("Execution error:\n{}", e))
+    }
+
+    fn call_read_only(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        function: &str,
+        args: &str,
+    ) -> Result<Value, String> {
+        let args = ast::build_ast(&QualifiedContractIdentifier::transient(), args, &mut ())
+            .map_err(|e| format!("Parse error:\n{}", e))?
+            .expressions;
+        self.with_env(|env| {
+            let context = LocalContext::new();
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, env, &context).map(SymbolicExpression::atom_value))
+                .collect::<Result<Vec<_>, _>>()?;
+            env.execute_contract(contract_identifier, function, &args, true)
+        })
+    }
+
+    /// Run a public function as though a transaction called it, charging what a block would,
+    /// and discard what it writes.  Returns what it did, and the block limit it was charged
+    /// against.
+    fn simulate_call(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+    ) -> Result<(SimulatedTransaction, ExecutionCost), String> {
+        let mainnet = self.mainnet;
+        let sender = self.sender.clone();
+        let tip = &self.tip;
+        let mut conn = self
+            .clarity_instance
+            .read_only_connection_checked(tip, &self.headers_db, &NULL_BURN_STATE_DB)
+            .map_err(|e| format!("Failed to open block {}: {}", tip, e))?;
+
+        let epoch_id = conn.get_epoch();
+        let epochs = if mainnet {
+            &*STACKS_EPOCHS_MAINNET
+        } else {
+            &*STACKS_EPOCHS_TESTNET
+        };
+        let block_limit = epochs
+            .iter()
+            .find(|epoch| epoch.epoch_id == epoch_id)
+            .map(|epoch| epoch.block_limit.clone())
+            .ok_or_else(|| format!("No block limit for epoch {}", epoch_id))?;
+
+        let cost_track = conn
+            .with_clarity_db_readonly(|clarity_db| {
+                LimitedCostTracker::new_mid_block(
+                    mainnet,
+                    block_limit.clone(),
+                    clarity_db,
+                    epoch_id,
+                )
+            })
+            .map_err(|e| format!("Failed to load cost functions: {:?}", e))?;
+        conn.simulate_transaction(
+            mainnet,
+            sender,
+            cost_track,
+            contract_identifier,
+            function,
+            args,
+        )
+        .map(|simulated| (simulated, block_limit))
+        .map_err(|e| format!("Execution error:\n{}", e))
+    }
+
+    /// Run one line of session input.  Returns the text to show, or `None` once the session
+    /// should end.
+    fn run_line(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Some("".to_string());
+        }
+        if line != ":history" {
+            self.history.push(line);
+        }
+
+        let (command, rest) = split_word(line);
+        let result = match command {
+            ":quit" | ":exit" => return None,
+            ":help" => Ok(SESSION_HELP.to_string()),
+            ":tip" => Ok(self.tip.to_string()),
+            ":history" => Ok(self.history.entries.join("\n")),
+            ":at" => self.set_tip(rest).map(|_| self.tip.to_string()),
+            ":call" => {
+                let (contract, rest) = split_word(rest);
+                let (function, args) = split_word(rest);
+                QualifiedContractIdentifier::parse(contract)
+                    .map_err(|e| format!("Invalid contract identifier: {}", e))
+                    .and_then(|contract| self.call_read_only(&contract, function, args))
+                    .map(|value| value.to_string())
+            }
+            ":in" => {
+                let (contract, program) = split_word(rest);
+                QualifiedContractIdentifier::parse(contract)
+                    .map_err(|e| format!("Invalid contract identifier: {}", e))
+                    .and_then(|contract| {
+                        self.with_env(|env| env.eval_read_only(&contract, program))
+                    })
+                    .map(|value| value.to_string())
+            }
+            _ if command.starts_with(':') => Err(format!("Unknown command {}. Try :help", command)),
+            _ => self
+                .with_env(|env| env.eval_raw(line))
+                .map(|value| value.to_string()),
+        };
+
+        Some(result.unwrap_or_else(|e| e))
+    }
+}
+
+/// Fee suggestions for a transaction of `tx_len` bytes that costs `cost`, from the fee
+/// estimates kept by the node whose chainstate is at `chainstate_path`.  Returns the
+/// transaction's scalar cost, and low, middle and high suggestions computed the way
+/// `POST /v2/fees/transaction` computes them.
+fn estimate_fees(
+    chainstate_path: &str,
+    cost: &ExecutionCost,
+    block_limit: &ExecutionCost,
+    tx_len: u64,
+) -> Result<(u64, Vec<RPCFeeEstimate>), String> {
+    let metric = ProportionalDotProduct::new(MAX_BLOCK_LEN as u64);
+    let scalar_cost = metric.from_cost_and_len(cost, block_limit, tx_len);
+
+    // only read the estimates a node has written: opening an estimator creates its database
+    let estimates_path = PathBuf::from(chainstate_path).join("estimates");
+    let scalar_path = estimates_path.join("fee_estimator_scalar_rate.sqlite");
+    let median_path = estimates_path.join("fee_fuzzed_weighted_median.sqlite");
+    let unfuzzed_median_path = estimates_path.join("fee_weighted_median.sqlite");
+    let regression_path = estimates_path.join("fee_estimator_regression.sqlite");
+    let percentile_path = estimates_path.join("fee_estimator_percentile.sqlite");
+    if percentile_path.exists() {
+        // this estimator tracks each dimension's fee rate, so apply those to the cost directly
+        let dimension_fee_rates = PercentileFeeRateEstimator::open(
+            &percentile_path,
+            ProportionalDotProduct::new(MAX_BLOCK_LEN as u64),
+            FEE_RATE_WINDOW_SIZE,
+        )
+        .map_err(|e| format!("Failed to open {:?}: {}", &percentile_path, e))?
+        .get_dimension_rate_estimates()
+        .map_err(|e| format!("Failed to estimate fee rates: {}", e))?;
+        let proportions = dimension_proportions(&metric, cost, block_limit, tx_len);
+        let estimations = RPCFeeEstimate::estimate_fees_by_dimension(
+            scalar_cost,
+            dimension_fee_rates.estimate_fees(&proportions),
+        );
+        return Ok((scalar_cost, with_minimum_fee(estimations, tx_len)));
+    }
+
+    let fee_rates = if scalar_path.exists() {
+        ScalarFeeRateEstimator::open(
+            &scalar_path,
+            ProportionalDotProduct::new(MAX_BLOCK_LEN as u64),
+        )
+        .map_err(|e| format!("Failed to open {:?}: {}", &scalar_path, e))?
+        .get_rate_estimates()
+    } else if median_path.exists() {
+        WeightedMedianFeeRateEstimator::open(
+            &median_path,
+            ProportionalDotProduct::new(MAX_BLOCK_LEN as u64),
+            FEE_RATE_WINDOW_SIZE,
+        )
+        .map_err(|e| format!("Failed to open {:?}: {}", &median_path, e))?
+        .get_rate_estimates()
+    } else if unfuzzed_median_path.exists() {
+        WeightedMedianFeeRateEstimator::open(
+            &unfuzzed_median_path,
+            ProportionalDotProduct::new(MAX_BLOCK_LEN as u64),
+            FEE_RATE_WINDOW_SIZE,
+        )
+        .map_err(|e| format!("Failed to open {:?}: {}", &unfuzzed_median_path, e))?
+        .get_rate_estimates()
+    } else if regression_path.exists() {
+        RegressionFeeRateEstimator::open(
+            &regression_path,
+            ProportionalDotProduct::new(MAX_BLOCK_LEN as u64),
+            FEE_RATE_WINDOW_SIZE,
+        )
+        .map_err(|e| format!("Failed to open {:?}: {}", &regression_path, e))?
+        .get_rate_estimates()
+    } else {
+        return Err(format!("No fee estimates in {:?}", &estimates_path));
+    }
+    .map_err(|e| format!("Failed to estimate fee rates: {}", e))?;
+
+    let estimations = RPCFeeEstimate::estimate_fees(scalar_cost, fee_rates);
+    Ok((scalar_cost, with_minimum_fee(estimations, tx_len)))
+}
+
+/// Raise each of `estimations` to at least the minimum relay fee for a `tx_len`-byte transaction.
+fn with_minimum_fee(mut estimations: Vec<RPCFeeEstimate>, tx_len: u64) -> Vec<RPCFeeEstimate> {
+    let minimum_fee = tx_len * MINIMUM_TX_FEE_RATE_PER_BYTE;
+    for estimate in estimations.iter_mut() {
+        if estimate.fee < minimum_fee {
+            estimate.fee = minimum_fee;
+        }
+    }
+    estimations
+}
+
 fn get_eval_input(invoked_by: &str, args: &[String]) -> EvalInput {
     if args.len() < 3 || args.len() > 4 {
         eprintln!(
@@ -784,6 +1201,54 @@ fn install_boot_code<C: ClarityStorage>(header_db: &CLIHeadersDB, marf: &mut C)
         .unwrap();
 }
 
+/// Register a contract's source with the coverage reporter, so its executable lines and
+/// branches are known when producing a report.
+fn register_coverage_src(
+    coverage_folder: &str,
+    contract_identifier: &QualifiedContractIdentifier,
+    src_file: &str,
+    ast: &[SymbolicExpression],
+) {
+    friendly_expect(
+        fs::create_dir_all(coverage_folder),
+        &format!("Failed to create coverage folder {}", coverage_folder),
+    );
+    let ref_file =
+        PathBuf::from(coverage_folder).join(format!("{}.clarcovref", contract_identifier));
+    friendly_expect(
+        CoverageReporter::register_src_file(contract_identifier, src_file, ast, &ref_file),
+        "Failed to write coverage reference file",
+    );
+}
+
+/// Write out the coverage recorded by `coverage_reporter` in a fresh coverage file
+fn save_coverage(coverage_folder: &str, coverage_reporter: Option<CoverageReporter>) {
+    if let Some(coverage_reporter) = coverage_reporter {
+        friendly_expect(
+            fs::create_dir_all(coverage_folder),
+            &format!("Failed to create coverage folder {}", coverage_folder),
+        );
+        let cov_file = PathBuf::from(coverage_folder)
+            .join(format!("{}.clarcov", rand::thread_rng().gen::<u64>()));
+        friendly_expect(
+            coverage_reporter.to_file(&cov_file),
+            "Failed to write coverage file",
+        );
+    }
+}
+
+/// Write the costs recorded by `profiler` to `profile_file` as folded stacks, for flame graphs
+fn save_costs_profile(profile_file: &str, profiler: &CostProfiler, dimension: CostDimension) {
+    let mut f = friendly_expect(
+        fs::File::create(profile_file),
+        &format!("Failed to create costs profile {}", profile_file),
+    );
+    friendly_expect(
+        profiler.write_folded(&mut f, dimension),
+        "Failed to write costs profile",
+    );
+}
+
 pub fn add_costs(result: &mut serde_json::Value, costs: bool, runtime: ExecutionCost) {
     if costs {
         result["costs"] = serde_json::to_value(runtime).unwrap();
@@ -918,24 +1383,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 )
             }
         }
-        "generate_address" => {
-            // random 20 bytes
-            let random_bytes = rand::thread_rng().gen::<[u8; 20]>();
-            // version = 22
-            let addr =
-                friendly_expect(c32_address(22, &random_bytes), "Failed to generate address");
-
-            (0, Some(json!({ "address": format!("{}", addr) })))
-        }
-        "check" => {
-            if args.len() < 2 {
-                eprintln!(
-                    "Usage: {} {} [program-file.clar] [--contract_id CONTRACT_ID] [--output_analysis] [--costs] [--testnet] (vm-state.db)",
-                    invoked_by, args[0]
-                );
-                panic_test!();
-            }
-
+        "ast" => {
             let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
             let contract_id = if let Ok(optarg) = consume_arg(&mut argv, &["--contract_id"], true) {
                 optarg
@@ -947,32 +1395,22 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                     })
                     .unwrap_or(QualifiedContractIdentifier::transient())
             } else {
-                eprintln!("Expected argument for --contract-id");
+                eprintln!("Expected argument for --contract_id");
                 panic_test!();
             };
-
-            let output_analysis =
-                if let Ok(optarg) = consume_arg(&mut argv, &["--output_analysis"], false) {
-                    optarg.is_some()
-                } else {
-                    eprintln!("BUG: failed to parse arguments for --output_analysis");
-                    panic_test!();
-                };
-
-            let costs = if let Ok(Some(_)) = consume_arg(&mut argv, &["--costs"], false) {
+            let parse_only = if let Ok(Some(_)) = consume_arg(&mut argv, &["--parse_only"], false) {
                 true
             } else {
                 false
             };
 
-            // NOTE: ignored if we're using a DB
-            let mut testnet_given = false;
-            let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
-                testnet_given = true;
-                false
-            } else {
-                true
-            };
+            if argv.len() != 2 {
+                eprintln!(
+                    "Usage: {} {} [--contract_id CONTRACT_ID] [--parse_only] [program-file.clar]",
+                    invoked_by, argv[0]
+                );
+                panic_test!();
+            }
 
             let content: String = if &argv[1] == "-" {
                 let mut buffer = String::new();
@@ -988,87 +1426,428 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 )
             };
 
-            let mut ast = friendly_expect(parse(&contract_id, &content), "Failed to parse program");
-
-            let contract_analysis_res = {
-                if argv.len() >= 3 {
-                    // use a persisted marf
-                    if testnet_given {
-                        eprintln!("WARN: ignoring --testnet in favor of DB state in {:?}. Re-instantiate the DB to change.", &argv[2]);
-                    }
-
-                    let vm_filename = &argv[2];
-                    let header_db =
-                        friendly_expect(CLIHeadersDB::resume(vm_filename), "Failed to open CLI DB");
-                    let marf_kv = friendly_expect(
-                        MarfedKV::open(vm_filename, None),
-                        "Failed to open VM database.",
-                    );
+            // `--parse_only` skips definition sorting, trait resolution and sugar expansion, so
+            // the AST mirrors the source text
+            let ast_res = if parse_only {
+                build_pre_ast(&contract_id, &content)
+            } else {
+                build_ast(&contract_id, &content, &mut ())
+            };
+            match ast_res {
+                Ok(ast) => (0, Some(contract_ast_to_json(&ast))),
+                Err(e) => (
+                    1,
+                    Some(json!({
+                        "message": "Failed to parse program.",
+                        "error": {
+                            "parse": serde_json::to_value(&e.diagnostic).unwrap(),
+                        }
+                    })),
+                ),
+            }
+        }
+        "fmt" => {
+            let mut argv = args.to_vec();
+            let check = matches!(consume_arg(&mut argv, &["--check"], false), Ok(Some(_)));
+            let write = matches!(consume_arg(&mut argv, &["--write"], false), Ok(Some(_)));
 
-                    let result = at_chaintip(&argv[2], marf_kv, |mut marf| {
-                        let result =
-                            run_analysis(&contract_id, &mut ast, &header_db, &mut marf, false);
-                        (marf, result)
-                    });
-                    result
-                } else {
-                    let header_db = CLIHeadersDB::new_memory(mainnet);
-                    let mut analysis_marf = MemoryBackingStore::new();
+            if argv.len() != 2 || (write && (check || &argv[1] == "-")) {
+                eprintln!(
+                    "Usage: {} {} [--check | --write] [program-file.clar]",
+                    invoked_by, argv[0]
+                );
+                eprintln!("   Prints the formatted program, unless --check is given, in which case it only reports");
+                eprintln!("   whether the program is already formatted, or --write is given, in which case the");
+                eprintln!("   program file is formatted in place.");
+                eprintln!("   If the provided filename is `-`, the program is read from stdin.");
+                panic_test!();
+            }
 
-                    install_boot_code(&header_db, &mut analysis_marf);
-                    run_analysis(
-                        &contract_id,
-                        &mut ast,
-                        &header_db,
-                        &mut analysis_marf,
-                        false,
-                    )
-                }
+            let content: String = if &argv[1] == "-" {
+                let mut buffer = String::new();
+                friendly_expect(
+                    io::stdin().read_to_string(&mut buffer),
+                    "Error reading from stdin.",
+                );
+                buffer
+            } else {
+                friendly_expect(
+                    fs::read_to_string(&argv[1]),
+                    &format!("Error reading file: {}", argv[1]),
+                )
             };
 
-            let mut contract_analysis = match contract_analysis_res {
-                Ok(contract_analysis) => contract_analysis,
-                Err((e, cost_tracker)) => {
-                    let mut result = json!({
-                        "message": "Checks failed.",
-                        "error": {
-                            "analysis": serde_json::to_value(&e.diagnostic).unwrap(),
-                        }
-                    });
-                    add_costs(&mut result, costs, cost_tracker.get_total());
-                    return (1, Some(result));
+            let formatted = match format_source(&content) {
+                Ok(formatted) => formatted,
+                Err(e) => {
+                    return (
+                        1,
+                        Some(json!({
+                            "message": "Failed to parse program.",
+                            "error": {
+                                "parse": serde_json::to_value(&e.diagnostic).unwrap(),
+                            }
+                        })),
+                    );
                 }
             };
 
-            let mut result = json!({
-                "message": "Checks passed."
-            });
-
-            add_costs(
-                &mut result,
-                costs,
-                contract_analysis.take_contract_cost_tracker().get_total(),
-            );
-
-            if output_analysis {
-                result["analysis"] =
-                    serde_json::to_value(&build_contract_interface(&contract_analysis)).unwrap();
+            if check {
+                let is_formatted = formatted == content;
+                (
+                    if is_formatted { 0 } else { 1 },
+                    Some(json!({ "formatted": is_formatted })),
+                )
+            } else if write {
+                let changed = formatted != content;
+                if changed {
+                    friendly_expect(
+                        fs::write(&argv[1], &formatted),
+                        &format!("Error writing file: {}", argv[1]),
+                    );
+                }
+                (
+                    0,
+                    Some(json!({ "message": "Formatted.", "changed": changed })),
+                )
+            } else {
+                print!("{}", formatted);
+                (0, None)
             }
-            (0, Some(result))
         }
-        "repl" => {
-            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
-            let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
-                false
-            } else {
-                true
+        "lint" => {
+            let mut argv = args.to_vec();
+            let mut config = match consume_arg(&mut argv, &["--config"], true) {
+                Ok(Some(config_file)) => friendly_expect(
+                    LintConfig::from_json(&friendly_expect(
+                        fs::read_to_string(&config_file),
+                        &format!("Error reading file: {}", config_file),
+                    )),
+                    &format!("Error parsing lint config: {}", config_file),
+                ),
+                Ok(None) => LintConfig::default(),
+                Err(_) => {
+                    eprintln!("Expected argument for --config");
+                    panic_test!();
+                }
             };
-            let mut marf = MemoryBackingStore::new();
-            let mut vm_env =
-                OwnedEnvironment::new_free(mainnet, marf.as_clarity_db(), DEFAULT_CLI_EPOCH);
-            let mut exec_env = vm_env.get_exec_environment(None);
-            let mut analysis_marf = MemoryBackingStore::new();
-
+            // rules given on the command line override the config file
+            for (flag, level) in [
+                ("--allow", LintLevel::Allow),
+                ("--warn", LintLevel::Warn),
+                ("--deny", LintLevel::Deny),
+            ]
+            .iter()
+            {
+                while let Ok(Some(rule_name)) = consume_arg(&mut argv, &[flag], true) {
+                    let rule = friendly_expect_opt(
+                        LintRule::lookup_by_name(&rule_name),
+                        &format!(
+                            "Unknown lint rule '{}'. Expected one of: {}",
+                            rule_name,
+                            LintRule::ALL_NAMES.join(", ")
+                        ),
+                    );
+                    config.set_level(rule, *level);
+                }
+            }
+
+            if argv.len() != 2 {
+                eprintln!(
+                    "Usage: {} {} [--config lint-config.json] [--allow RULE] [--warn RULE] [--deny RULE] [program-file.clar]",
+                    invoked_by, argv[0]
+                );
+                eprintln!("   Rules: {}", LintRule::ALL_NAMES.join(", "));
+                eprintln!("   Every rule is reported as a warning, unless the config file or the --allow, --warn");
+                eprintln!("   and --deny options (which may be repeated) say otherwise. The config file maps rule");
+                eprintln!("   names to levels, e.g. {{\"unwrap-panic\": \"deny\"}}. Exits with an error if any");
+                eprintln!("   denied rule is violated.");
+                eprintln!("   If the provided filename is `-`, the program is read from stdin.");
+                panic_test!();
+            }
+
+            let content: String = if &argv[1] == "-" {
+                let mut buffer = String::new();
+                friendly_expect(
+                    io::stdin().read_to_string(&mut buffer),
+                    "Error reading from stdin.",
+                );
+                buffer
+            } else {
+                friendly_expect(
+                    fs::read_to_string(&argv[1]),
+                    &format!("Error reading file: {}", argv[1]),
+                )
+            };
+
+            let lints =
+                match lint_contract(&QualifiedContractIdentifier::transient(), &content, &config) {
+                    Ok(lints) => lints,
+                    Err(e) => {
+                        return (
+                            1,
+                            Some(json!({
+                                "message": "Failed to parse program.",
+                                "error": {
+                                    "parse": serde_json::to_value(&e.diagnostic).unwrap(),
+                                }
+                            })),
+                        );
+                    }
+                };
+
+            let denied = lints.iter().any(|lint| lint.level == LintLevel::Deny);
+            let lints_json: Vec<_> = lints.iter().map(lint_to_json).collect();
+            (
+                if denied { 1 } else { 0 },
+                Some(json!({
+                    "message": if denied { "Lint failed." } else { "Lint passed." },
+                    "lints": lints_json,
+                })),
+            )
+        }
+        "generate_address" => {
+            // random 20 bytes
+            let random_bytes = rand::thread_rng().gen::<[u8; 20]>();
+            // version = 22
+            let addr =
+                friendly_expect(c32_address(22, &random_bytes), "Failed to generate address");
+
+            (0, Some(json!({ "address": format!("{}", addr) })))
+        }
+        "bindings" => {
+            let mut argv = args.to_vec();
+            let usage = format!(
+                "Usage: {} {} [--contract_id CONTRACT_ID] [--crate CRATE_NAME] [--testnet] [program-file.clar] (vm-state.db)",
+                invoked_by, &argv[0]
+            );
+            let contract_id =
+                friendly_expect(consume_arg(&mut argv, &["--contract_id"], true), &usage)
+                    .map(|contract_id| {
+                        friendly_expect(
+                            QualifiedContractIdentifier::parse(&contract_id),
+                            &format!("Error parsing contract identifier '{}'", &contract_id),
+                        )
+                    })
+                    .unwrap_or_else(QualifiedContractIdentifier::transient);
+            let crate_name = friendly_expect(consume_arg(&mut argv, &["--crate"], true), &usage)
+                .unwrap_or_else(|| "blockstack_lib".to_string());
+            let mainnet = !matches!(consume_arg(&mut argv, &["--testnet"], false), Ok(Some(_)));
+            if argv.len() != 2 && argv.len() != 3 {
+                eprintln!("{}", &usage);
+                eprintln!("   Prints Rust source for building calls to the contract's public and read-only");
+                eprintln!(
+                    "   functions. Contracts it depends on are looked up in vm-state.db, if given."
+                );
+                panic_test!();
+            }
+
+            let content = if &argv[1] == "-" {
+                let mut buffer = String::new();
+                friendly_expect(
+                    io::stdin().read_to_string(&mut buffer),
+                    "Error reading from stdin.",
+                );
+                buffer
+            } else {
+                friendly_expect(
+                    fs::read_to_string(&argv[1]),
+                    &format!("Error reading file: {}", argv[1]),
+                )
+            };
+            let mut ast = friendly_expect(parse(&contract_id, &content), "Failed to parse program");
+
+            let analysis_result = if argv.len() == 3 {
+                let vm_filename = &argv[2];
+                let header_db =
+                    friendly_expect(CLIHeadersDB::resume(vm_filename), "Failed to open CLI DB");
+                let marf_kv = friendly_expect(
+                    MarfedKV::open(vm_filename, None),
+                    "Failed to open VM database.",
+                );
+                at_chaintip(vm_filename, marf_kv, |mut marf| {
+                    let result = run_analysis(&contract_id, &mut ast, &header_db, &mut marf, false);
+                    (marf, result)
+                })
+            } else {
+                let header_db = CLIHeadersDB::new_memory(mainnet);
+                let mut analysis_marf = MemoryBackingStore::new();
+                install_boot_code(&header_db, &mut analysis_marf);
+                run_analysis(
+                    &contract_id,
+                    &mut ast,
+                    &header_db,
+                    &mut analysis_marf,
+                    false,
+                )
+            };
+
+            match analysis_result {
+                Ok(analysis) => {
+                    print!("{}", generate_bindings(&analysis, &crate_name));
+                    (0, None)
+                }
+                Err((e, _)) => (
+                    1,
+                    Some(json!({
+                        "message": "Checks failed.",
+                        "error": {
+                            "analysis": serde_json::to_value(&e.diagnostic).unwrap(),
+                        }
+                    })),
+                ),
+            }
+        }
+        "check" => {
+            if args.len() < 2 {
+                eprintln!(
+                    "Usage: {} {} [program-file.clar] [--contract_id CONTRACT_ID] [--output_analysis] [--costs] [--trait_call_warnings] [--testnet] (vm-state.db)",
+                    invoked_by, args[0]
+                );
+                panic_test!();
+            }
+
+            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
+            let contract_id = if let Ok(optarg) = consume_arg(&mut argv, &["--contract_id"], true) {
+                optarg
+                    .map(|optarg_str| {
+                        friendly_expect(
+                            QualifiedContractIdentifier::parse(&optarg_str),
+                            &format!("Error parsing contract identifier '{}", &optarg_str),
+                        )
+                    })
+                    .unwrap_or(QualifiedContractIdentifier::transient())
+            } else {
+                eprintln!("Expected argument for --contract-id");
+                panic_test!();
+            };
+
+            let output_analysis =
+                if let Ok(optarg) = consume_arg(&mut argv, &["--output_analysis"], false) {
+                    optarg.is_some()
+                } else {
+                    eprintln!("BUG: failed to parse arguments for --output_analysis");
+                    panic_test!();
+                };
+
+            let costs = if let Ok(Some(_)) = consume_arg(&mut argv, &["--costs"], false) {
+                true
+            } else {
+                false
+            };
+
+            let trait_call_warnings =
+                if let Ok(Some(_)) = consume_arg(&mut argv, &["--trait_call_warnings"], false) {
+                    true
+                } else {
+                    false
+                };
+
+            // NOTE: ignored if we're using a DB
+            let mut testnet_given = false;
+            let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
+                testnet_given = true;
+                false
+            } else {
+                true
+            };
+
+            let content: String = if &argv[1] == "-" {
+                let mut buffer = String::new();
+                friendly_expect(
+                    io::stdin().read_to_string(&mut buffer),
+                    "Error reading from stdin.",
+                );
+                buffer
+            } else {
+                friendly_expect(
+                    fs::read_to_string(&argv[1]),
+                    &format!("Error reading file: {}", argv[1]),
+                )
+            };
+
+            let mut ast = friendly_expect(parse(&contract_id, &content), "Failed to parse program");
+
+            let contract_analysis_res = {
+                if argv.len() >= 3 {
+                    // use a persisted marf
+                    if testnet_given {
+                        eprintln!("WARN: ignoring --testnet in favor of DB state in {:?}. Re-instantiate the DB to change.", &argv[2]);
+                    }
+
+                    let vm_filename = &argv[2];
+                    let header_db =
+                        friendly_expect(CLIHeadersDB::resume(vm_filename), "Failed to open CLI DB");
+                    let marf_kv = friendly_expect(
+                        MarfedKV::open(vm_filename, None),
+                        "Failed to open VM database.",
+                    );
+
+                    let result = at_chaintip(&argv[2], marf_kv, |mut marf| {
+                        let result =
+                            run_analysis(&contract_id, &mut ast, &header_db, &mut marf, false);
+                        (marf, result)
+                    });
+                    result
+                } else {
+                    let header_db = CLIHeadersDB::new_memory(mainnet);
+                    let mut analysis_marf = MemoryBackingStore::new();
+
+                    install_boot_code(&header_db, &mut analysis_marf);
+                    run_analysis(
+                        &contract_id,
+                        &mut ast,
+                        &header_db,
+                        &mut analysis_marf,
+                        false,
+                    )
+                }
+            };
+
+            let mut contract_analysis = match contract_analysis_res {
+                Ok(contract_analysis) => contract_analysis,
+                Err((e, cost_tracker)) => {
+                    let mut result = json!({
+                        "message": "Checks failed.",
+                        "error": {
+                            "analysis": serde_json::to_value(&e.diagnostic).unwrap(),
+                        }
+                    });
+                    add_costs(&mut result, costs, cost_tracker.get_total());
+                    return (1, Some(result));
+                }
+            };
+
+            let mut result = json!({
+                "message": "Checks passed."
+            });
+
+            add_costs(
+                &mut result,
+                costs,
+                contract_analysis.take_contract_cost_tracker().get_total(),
+            );
+            add_trait_call_warnings(&mut result, trait_call_warnings, &ast);
+
+            if output_analysis {
+                result["analysis"] =
+                    serde_json::to_value(&build_contract_interface(&contract_analysis)).unwrap();
+            }
+            (0, Some(result))
+        }
+        "repl" => {
+            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
+            let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
+                false
+            } else {
+                true
+            };
+            let mut marf = MemoryBackingStore::new();
+            let mut vm_env =
+                OwnedEnvironment::new_free(mainnet, marf.as_clarity_db(), DEFAULT_CLI_EPOCH);
+            let mut exec_env = vm_env.get_exec_environment(None);
+            let mut analysis_marf = MemoryBackingStore::new();
+
             let contract_id = QualifiedContractIdentifier::transient();
 
             let mut stdout = io::stdout();
@@ -1118,6 +1897,70 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 println!("{}", eval_result);
             }
         }
+        "session" => {
+            let mut argv: Vec<String> = args.to_vec();
+            let usage = format!(
+                "Usage: {} {} [--at index-block-hash] [--sender principal] [--history file] [chainstate dir]",
+                invoked_by, &argv[0]
+            );
+            let tip = friendly_expect(consume_arg(&mut argv, &["--at"], true), &usage);
+            let sender = friendly_expect(consume_arg(&mut argv, &["--sender"], true), &usage)
+                .map(|sender| {
+                    friendly_expect(
+                        PrincipalData::parse(&sender),
+                        &format!("Invalid sender: {}", &sender),
+                    )
+                })
+                .unwrap_or_else(|| StandardPrincipalData::transient().into());
+            let history_path =
+                friendly_expect(consume_arg(&mut argv, &["--history"], true), &usage)
+                    .map(PathBuf::from)
+                    .or_else(|| {
+                        env::var_os("HOME")
+                            .map(|home| PathBuf::from(home).join(".clarity_cli_history"))
+                    });
+            if argv.len() != 2 {
+                eprintln!("{}", &usage);
+                panic_test!();
+            }
+
+            let mut session = friendly_expect(
+                NodeSession::open(
+                    &argv[1],
+                    tip.as_deref(),
+                    sender,
+                    SessionHistory::open(history_path),
+                ),
+                "Failed to open the node's chainstate.",
+            );
+            println!("Session at block {}. Enter :help for help.", &session.tip);
+
+            let mut stdout = io::stdout();
+            loop {
+                let mut buffer = String::new();
+                stdout.write_all(b"> ").unwrap_or_else(|e| {
+                    panic!("Failed to write stdout prompt string:\n{}", e);
+                });
+                stdout.flush().unwrap_or_else(|e| {
+                    panic!("Failed to flush stdout prompt string:\n{}", e);
+                });
+                match io::stdin().read_line(&mut buffer) {
+                    // end of input
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(error) => {
+                        eprintln!("Error reading from stdin:\n{}", error);
+                        panic_test!();
+                    }
+                }
+                match session.run_line(&buffer) {
+                    Some(output) if output.is_empty() => {}
+                    Some(output) => println!("{}", output),
+                    None => break,
+                }
+            }
+            (0, None)
+        }
         "eval_raw" => {
             let content: String = {
                 let mut buffer = String::new();
@@ -1365,10 +2208,20 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 } else {
                     false
                 };
-            if argv.len() < 4 {
-                eprintln!(
-                    "Usage: {} {} [--costs] [--assets] [--output_analysis] [contract-identifier] [contract-definition.clar] [vm-state.db]",
-                    invoked_by, argv[0]
+            let trait_call_warnings =
+                if let Ok(Some(_)) = consume_arg(&mut argv, &["--trait_call_warnings"], false) {
+                    true
+                } else {
+                    false
+                };
+            let coverage_folder = friendly_expect(
+                consume_arg(&mut argv, &["--coverage"], true),
+                "Expected a coverage folder after --coverage",
+            );
+            if argv.len() < 4 {
+                eprintln!(
+                    "Usage: {} {} [--costs] [--assets] [--output_analysis] [--trait_call_warnings] [--coverage coverage-folder] [contract-identifier] [contract-definition.clar] [vm-state.db]",
+                    invoked_by, argv[0]
                 );
                 panic_test!();
             }
@@ -1388,6 +2241,9 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 parse(&contract_identifier, &contract_content),
                 "Failed to parse program.",
             );
+            if let Some(ref coverage_folder) = coverage_folder {
+                register_coverage_src(coverage_folder, &contract_identifier, &argv[2], &ast);
+            }
             let header_db =
                 friendly_expect(CLIHeadersDB::resume(vm_filename), "Failed to open CLI DB");
             let marf_kv = friendly_expect(
@@ -1405,8 +2261,20 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                         Ok(analysis) => {
                             let result_and_cost =
                                 with_env_costs(mainnet, &header_db, &mut marf, |vm_env| {
-                                    vm_env
-                                        .initialize_contract(contract_identifier, &contract_content)
+                                    if coverage_folder.is_some() {
+                                        vm_env.set_coverage_reporter(CoverageReporter::new());
+                                    }
+                                    let result = vm_env.initialize_contract(
+                                        contract_identifier,
+                                        &contract_content,
+                                    );
+                                    if let Some(ref coverage_folder) = coverage_folder {
+                                        save_coverage(
+                                            coverage_folder,
+                                            vm_env.take_coverage_reporter(),
+                                        );
+                                    }
+                                    result
                                 });
                             (header_db, marf, Ok((analysis, result_and_cost)))
                         }
@@ -1421,6 +2289,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
 
                     add_costs(&mut result, costs, cost);
                     add_assets(&mut result, assets, asset_map);
+                    add_trait_call_warnings(&mut result, trait_call_warnings, &ast);
 
                     if output_analysis {
                         result["analysis"] =
@@ -1470,8 +2339,27 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 false
             };
 
+            let coverage_folder = friendly_expect(
+                consume_arg(&mut argv, &["--coverage"], true),
+                "Expected a coverage folder after --coverage",
+            );
+            let costs_profile = friendly_expect(
+                consume_arg(&mut argv, &["--costs_profile"], true),
+                "Expected a file name after --costs_profile",
+            );
+            let costs_profile_dimension = match friendly_expect(
+                consume_arg(&mut argv, &["--costs_profile_dimension"], true),
+                "Expected one of runtime, read_count, read_length, write_count, or write_length after --costs_profile_dimension",
+            ) {
+                Some(dimension) => friendly_expect(
+                    dimension.parse::<CostDimension>(),
+                    "Failed to parse --costs_profile_dimension",
+                ),
+                None => CostDimension::Runtime,
+            };
+
             if argv.len() < 5 {
-                eprintln!("Usage: {} {} [--costs] [--assets] [vm-state.db] [contract-identifier] [public-function-name] [sender-address] [args...]", invoked_by, argv[0]);
+                eprintln!("Usage: {} {} [--costs] [--assets] [--coverage coverage-folder] [--costs_profile profile-file] [--costs_profile_dimension dimension] [vm-state.db] [contract-identifier] [public-function-name] [sender-address] [args...]", invoked_by, argv[0]);
                 panic_test!();
             }
 
@@ -1517,7 +2405,26 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
 
             let (_, _, result_and_cost) = in_block(header_db, marf_kv, |header_db, mut marf| {
                 let result_and_cost = with_env_costs(mainnet, &header_db, &mut marf, |vm_env| {
-                    vm_env.execute_transaction(sender, contract_identifier, &tx_name, &arguments)
+                    if coverage_folder.is_some() {
+                        vm_env.set_coverage_reporter(CoverageReporter::new());
+                    }
+                    let profiler = CostProfiler::new();
+                    if costs_profile.is_some() {
+                        vm_env.add_eval_hook(Box::new(profiler.clone()));
+                    }
+                    let result = vm_env.execute_transaction(
+                        sender,
+                        contract_identifier,
+                        &tx_name,
+                        &arguments,
+                    );
+                    if let Some(ref coverage_folder) = coverage_folder {
+                        save_coverage(coverage_folder, vm_env.take_coverage_reporter());
+                    }
+                    if let Some(ref costs_profile) = costs_profile {
+                        save_costs_profile(costs_profile, &profiler, costs_profile_dimension);
+                    }
+                    result
                 });
                 (header_db, marf, result_and_cost)
             });
@@ -1579,6 +2486,182 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 }
             }
         }
+        "estimate_cost" => {
+            let mut argv: Vec<String> = args.to_vec();
+            let usage = format!(
+                "Usage: {} {} [--at-tip | --at index-block-hash] [--sender principal] [chainstate dir] [contract-identifier] [public-function-name] [args...]",
+                invoked_by, &argv[0]
+            );
+            let at_tip = friendly_expect(consume_arg(&mut argv, &["--at-tip"], false), &usage);
+            let tip = friendly_expect(consume_arg(&mut argv, &["--at"], true), &usage);
+            let sender = friendly_expect(consume_arg(&mut argv, &["--sender"], true), &usage)
+                .map(|sender| {
+                    friendly_expect(
+                        PrincipalData::parse(&sender),
+                        &format!("Invalid sender: {}", &sender),
+                    )
+                })
+                .unwrap_or_else(|| StandardPrincipalData::transient().into());
+            if argv.len() < 4 || (at_tip.is_some() && tip.is_some()) {
+                eprintln!("{}", &usage);
+                panic_test!();
+            }
+
+            let chainstate_path = &argv[1];
+            let contract_identifier = friendly_expect(
+                QualifiedContractIdentifier::parse(&argv[2]),
+                "Failed to parse contract identifier.",
+            );
+            let function_name = friendly_expect(
+                ClarityName::try_from(argv[3].clone()),
+                "Failed to parse function name.",
+            );
+            let arguments: Vec<Value> = argv[4..]
+                .iter()
+                .map(|argument| {
+                    let argument_parsed = friendly_expect(
+                        vm_execute(argument),
+                        &format!("Error parsing argument \"{}\"", argument),
+                    );
+                    friendly_expect_opt(
+                        argument_parsed,
+                        &format!("Failed to parse a value from the argument: {}", argument),
+                    )
+                })
+                .collect();
+
+            let mut session = friendly_expect(
+                NodeSession::open(
+                    chainstate_path,
+                    tip.as_deref(),
+                    sender,
+                    SessionHistory::open(None),
+                ),
+                "Failed to open the node's chainstate.",
+            );
+            let (simulated, block_limit) =
+                match session.simulate_call(&contract_identifier, &function_name, &arguments) {
+                    Ok(simulated) => simulated,
+                    Err(e) => {
+                        let result = json!({
+                            "error": { "runtime": e },
+                            "success": false,
+                        });
+                        return (1, Some(result));
+                    }
+                };
+
+            // the length of a single-signature transaction making this call
+            let tx = StacksTransaction::new(
+                if session.mainnet {
+                    TransactionVersion::Mainnet
+                } else {
+                    TransactionVersion::Testnet
+                },
+                TransactionAuth::from_p2pkh(&StacksPrivateKey::new())
+                    .expect("FATAL: failed to make a single-signature spending condition"),
+                TransactionPayload::ContractCall(TransactionContractCall {
+                    address: StacksAddress::from(contract_identifier.issuer.clone()),
+                    contract_name: contract_identifier.name.clone(),
+                    function_name,
+                    function_args: arguments,
+                }),
+            );
+            let tx_len = tx.serialize_to_vec().len() as u64;
+
+            let committed = match simulated.result {
+                Value::Response(ref data) => data.committed,
+                _ => false,
+            };
+            let percent_of_block_limit = |used: u64, limit: u64| {
+                if limit == 0 {
+                    0.0
+                } else {
+                    100.0 * (used as f64) / (limit as f64)
+                }
+            };
+            let cost = &simulated.cost;
+            let mut result = json!({
+                "tip": session.tip.to_string(),
+                "output": serde_json::to_value(&simulated.result).unwrap(),
+                "success": committed,
+                "costs": serde_json::to_value(cost).unwrap(),
+                "block_limit": serde_json::to_value(&block_limit).unwrap(),
+                "percent_of_block_limit": {
+                    "runtime": percent_of_block_limit(cost.runtime, block_limit.runtime),
+                    "read_count": percent_of_block_limit(cost.read_count, block_limit.read_count),
+                    "read_length": percent_of_block_limit(cost.read_length, block_limit.read_length),
+                    "write_count": percent_of_block_limit(cost.write_count, block_limit.write_count),
+                    "write_length": percent_of_block_limit(cost.write_length, block_limit.write_length),
+                },
+                "estimated_len": tx_len,
+            });
+            add_serialized_output(&mut result, simulated.result);
+            match estimate_fees(chainstate_path, cost, &block_limit, tx_len) {
+                Ok((scalar_cost, estimations)) => {
+                    result["estimated_cost_scalar"] = json!(scalar_cost);
+                    result["estimations"] = serde_json::to_value(estimations).unwrap();
+                }
+                Err(e) => {
+                    result["estimations_error"] = json!(e);
+                }
+            }
+            (0, Some(result))
+        }
+        "coverage" => {
+            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
+            let json = if let Ok(Some(_)) = consume_arg(&mut argv, &["--json"], false) {
+                true
+            } else {
+                false
+            };
+
+            if (json && argv.len() != 2) || (!json && argv.len() != 3) {
+                eprintln!(
+                    "Usage: {} {} [--json] [coverage-folder] (lcov-output-file)",
+                    invoked_by, argv[0]
+                );
+                panic_test!();
+            }
+
+            let coverage_folder = &argv[1];
+            let mut register_files = vec![];
+            let mut coverage_files = vec![];
+            let entries = friendly_expect(
+                fs::read_dir(coverage_folder),
+                &format!("Failed to read coverage folder {}", coverage_folder),
+            );
+            for entry in entries {
+                let path = friendly_expect(entry, "Failed to read coverage folder entry").path();
+                match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("clarcovref") => register_files.push(path),
+                    Some("clarcov") => coverage_files.push(path),
+                    _ => {}
+                }
+            }
+            register_files.sort();
+            coverage_files.sort();
+
+            if json {
+                let report = friendly_expect(
+                    CoverageReporter::produce_json(&register_files, &coverage_files),
+                    "Failed to produce coverage report",
+                );
+                (0, Some(json!({ "coverage": report })))
+            } else {
+                friendly_expect(
+                    CoverageReporter::produce_lcov(&argv[2], &register_files, &coverage_files),
+                    "Failed to produce coverage report",
+                );
+                (
+                    0,
+                    Some(json!({
+                        "message": "Coverage report written",
+                        "lcov": argv[2],
+                    })),
+                )
+            }
+        }
         _ => {
             print_usage(invoked_by);
             (1, None)
@@ -1674,6 +2757,546 @@ mod test {
         assert!(!header_db.is_mainnet());
     }
 
+    #[test]
+    fn test_coverage() {
+        let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
+        let cov_dir = format!("/tmp/cov_{}", rand::thread_rng().gen::<i32>());
+        let clar_name = format!("/tmp/test-cov_{}.clar", rand::thread_rng().gen::<i32>());
+        let lcov_name = format!("/tmp/test-cov_{}.lcov", rand::thread_rng().gen::<i32>());
+
+        fs::write(
+            &clar_name,
+            "(define-public (check (x int))
+               (if (> x 0)
+                   (ok x)
+                   (err x)))",
+        )
+        .unwrap();
+
+        invoke_command("test", &["initialize".to_string(), db_name.clone()]);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "launch".to_string(),
+                "--coverage".to_string(),
+                cov_dir.clone(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.cov".to_string(),
+                clar_name.clone(),
+                db_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "execute".to_string(),
+                "--coverage".to_string(),
+                cov_dir.clone(),
+                db_name.clone(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.cov".to_string(),
+                "check".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5".to_string(),
+                "1".to_string(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "coverage".to_string(),
+                "--json".to_string(),
+                cov_dir.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        let result = invoked.1.unwrap();
+        let report = &result["coverage"][0];
+        assert_eq!(
+            report["contract"],
+            json!("S1G2081040G2081040G2081040G208105NK8PE5.cov")
+        );
+        // only the `(ok x)` branch of the `if` was taken
+        assert_eq!(report["branches_found"], json!(2));
+        assert_eq!(report["branches_hit"], json!(1));
+        assert!(report["lines_hit"].as_u64().unwrap() > 0);
+
+        let invoked = invoke_command(
+            "test",
+            &["coverage".to_string(), cov_dir.clone(), lcov_name.clone()],
+        );
+        assert_eq!(invoked.0, 0);
+        let lcov = fs::read_to_string(&lcov_name).unwrap();
+        assert!(lcov.contains(&format!("SF:{}", &clar_name)));
+        assert!(lcov.contains("BRF:2"));
+        assert!(lcov.contains("BRH:1"));
+        assert!(lcov.contains("end_of_record"));
+    }
+
+    #[test]
+    fn test_costs_profile() {
+        let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
+        let clar_name = format!("/tmp/test-prof_{}.clar", rand::thread_rng().gen::<i32>());
+        let profile_name = format!("/tmp/test-prof_{}.folded", rand::thread_rng().gen::<i32>());
+
+        fs::write(
+            &clar_name,
+            "(define-data-var total int 0)
+             (define-private (add (x int))
+               (var-set total (+ (var-get total) x)))
+             (define-public (add-twice (x int))
+               (begin (add x) (add x) (ok (var-get total))))",
+        )
+        .unwrap();
+
+        invoke_command("test", &["initialize".to_string(), db_name.clone()]);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "launch".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.prof".to_string(),
+                clar_name.clone(),
+                db_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "execute".to_string(),
+                "--costs_profile".to_string(),
+                profile_name.clone(),
+                "--costs_profile_dimension".to_string(),
+                "write_count".to_string(),
+                db_name.clone(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.prof".to_string(),
+                "add-twice".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5".to_string(),
+                "1".to_string(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap()["output"], json!({"Int": 2}));
+
+        // both writes happen in `var-set`, called from `add`, called from `begin`
+        let profile = fs::read_to_string(&profile_name).unwrap();
+        let lines: Vec<_> = profile.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let (stack, value) = lines[0].rsplit_once(' ').unwrap();
+        assert_eq!(value, "2");
+        let frames: Vec<_> = stack.split(';').collect();
+        assert_eq!(frames.len(), 3);
+        assert!(frames[0].starts_with("S1G2081040G2081040G2081040G208105NK8PE5.prof::begin"));
+        assert!(frames[1].starts_with("S1G2081040G2081040G2081040G208105NK8PE5.prof::add"));
+        assert!(frames[2].starts_with("S1G2081040G2081040G2081040G208105NK8PE5.prof::var-set"));
+    }
+
+    #[test]
+    fn test_ast() {
+        let clar_name = format!("/tmp/test-ast_{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(&clar_name, "(define-map m int { a: int })\n(map-get? m 1)").unwrap();
+
+        let invoked = invoke_command("test", &["ast".to_string(), clar_name.clone()]);
+        assert_eq!(invoked.0, 0);
+        let ast = invoked.1.unwrap();
+        assert_eq!(ast["version"], json!(1));
+        assert_eq!(
+            ast["contract_identifier"],
+            json!("S1G2081040G2081040G2081040G208105NK8PE5.__transient")
+        );
+        assert_eq!(ast["expressions"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            ast["expressions"][1]["children"][0]["name"],
+            json!("map-get?")
+        );
+        assert_eq!(
+            ast["expressions"][0]["children"][3]["children"][0]["name"],
+            json!("tuple")
+        );
+        assert_eq!(
+            ast["expressions"][1]["span"],
+            json!({"start_line": 2, "start_column": 1, "end_line": 2, "end_column": 14})
+        );
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "ast".to_string(),
+                "--parse_only".to_string(),
+                "--contract_id".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.ast".to_string(),
+                clar_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        let ast = invoked.1.unwrap();
+        assert_eq!(
+            ast["contract_identifier"],
+            json!("S1G2081040G2081040G2081040G208105NK8PE5.ast")
+        );
+        assert_eq!(ast["expressions"], json!([]));
+        assert_eq!(
+            ast["pre_expressions"][0]["children"][3]["kind"],
+            json!("tuple")
+        );
+
+        fs::write(&clar_name, "(map-get? m 1").unwrap();
+        let invoked = invoke_command("test", &["ast".to_string(), clar_name.clone()]);
+        assert_eq!(invoked.0, 1);
+        assert_eq!(
+            invoked.1.unwrap()["message"],
+            json!("Failed to parse program.")
+        );
+    }
+
+    #[test]
+    fn test_fmt() {
+        let clar_name = format!("/tmp/test-fmt_{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &clar_name,
+            ";; a map\n(define-map m int {a:   int})  (map-get? m 1)",
+        )
+        .unwrap();
+
+        let invoked = invoke_command(
+            "test",
+            &["fmt".to_string(), "--check".to_string(), clar_name.clone()],
+        );
+        assert_eq!(invoked.0, 1);
+        assert_eq!(invoked.1.unwrap(), json!({ "formatted": false }));
+
+        let invoked = invoke_command(
+            "test",
+            &["fmt".to_string(), "--write".to_string(), clar_name.clone()],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap()["changed"], json!(true));
+        assert_eq!(
+            fs::read_to_string(&clar_name).unwrap(),
+            ";; a map\n(define-map m int { a: int })\n(map-get? m 1)\n"
+        );
+
+        let invoked = invoke_command(
+            "test",
+            &["fmt".to_string(), "--check".to_string(), clar_name.clone()],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap(), json!({ "formatted": true }));
+
+        fs::write(&clar_name, "(map-get? m 1").unwrap();
+        let invoked = invoke_command("test", &["fmt".to_string(), clar_name.clone()]);
+        assert_eq!(invoked.0, 1);
+        assert_eq!(
+            invoked.1.unwrap()["message"],
+            json!("Failed to parse program.")
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        use chainstate::stacks::db::test::{chainstate_path, instantiate_chainstate};
+
+        let chainstate = instantiate_chainstate(false, 0x80000000, "clarity-cli-session");
+        drop(chainstate);
+        let path = chainstate_path("clarity-cli-session");
+        let history_path = PathBuf::from(format!("{}/session-history", &path));
+        let pox = boot_code_id("pox", false);
+
+        let mut session = NodeSession::open(
+            &path,
+            None,
+            StandardPrincipalData::transient().into(),
+            SessionHistory::open(Some(history_path.clone())),
+        )
+        .unwrap();
+        let genesis = session.tip.to_string();
+
+        assert_eq!(session.run_line("(+ 1 2)\n").unwrap(), "3");
+        assert_eq!(
+            session
+                .run_line(&format!("(stx-get-balance '{})", &pox))
+                .unwrap(),
+            "u0"
+        );
+        assert_eq!(
+            session
+                .run_line(&format!(":in {} (var-get configured)", &pox))
+                .unwrap(),
+            "true"
+        );
+        assert!(session
+            .run_line(&format!(":call {} get-pox-info", &pox))
+            .unwrap()
+            .starts_with("(ok (tuple"));
+        assert!(session
+            .run_line(&format!(
+                ":call {} get-stacker-info '{}",
+                &pox,
+                StandardPrincipalData::transient()
+            ))
+            .unwrap()
+            .starts_with("none"));
+        // only read-only functions can be called, and no state survives an evaluation
+        assert!(session
+            .run_line(&format!(
+                ":call {} set-burnchain-parameters u0 u1 u2 u3 u4",
+                &pox
+            ))
+            .unwrap()
+            .starts_with("Execution error"));
+        assert_eq!(
+            session.run_line("(stx-burn? u1 tx-sender)").unwrap(),
+            "(err u1)"
+        );
+
+        assert!(session
+            .run_line(&format!(":at {}", &StacksBlockId([9; 32])))
+            .unwrap()
+            .starts_with("No such block"));
+        assert_eq!(
+            session.run_line(&format!(":at {}", &genesis)).unwrap(),
+            genesis
+        );
+        assert!(session
+            .run_line(":nope")
+            .unwrap()
+            .starts_with("Unknown command"));
+        assert!(session.run_line(":quit").is_none());
+
+        // history persists across sessions
+        let mut session = NodeSession::open(
+            &path,
+            Some(&genesis),
+            StandardPrincipalData::transient().into(),
+            SessionHistory::open(Some(history_path)),
+        )
+        .unwrap();
+        let history = session.run_line(":history").unwrap();
+        assert_eq!(history.lines().count(), 11);
+        assert_eq!(history.lines().next().unwrap(), "(+ 1 2)");
+        assert_eq!(history.lines().last().unwrap(), ":quit");
+
+        assert!(NodeSession::open(
+            "/tmp/no-such-chainstate",
+            None,
+            StandardPrincipalData::transient().into(),
+            SessionHistory::open(None),
+        )
+        .is_err());
+        assert!(fs::metadata("/tmp/no-such-chainstate").is_err());
+    }
+
+    #[test]
+    fn test_estimate_cost() {
+        use chainstate::stacks::db::test::{chainstate_path, instantiate_chainstate};
+
+        let chainstate = instantiate_chainstate(false, 0x80000000, "clarity-cli-estimate-cost");
+        drop(chainstate);
+        let path = chainstate_path("clarity-cli-estimate-cost");
+        let pox = boot_code_id("pox", false).to_string();
+        let args = [
+            "estimate_cost".to_string(),
+            path.clone(),
+            pox.clone(),
+            "allow-contract-caller".to_string(),
+            "'S1G2081040G2081040G2081040G208105NK8PE5.names".to_string(),
+            "none".to_string(),
+        ];
+
+        // no fee estimates have been recorded yet
+        let (exit, result) = invoke_command("test", &args);
+        let result = result.unwrap();
+        assert_eq!(exit, 0);
+        assert_eq!(result["success"], json!(true));
+        assert_eq!(
+            result["output"],
+            json!({"Response": {"committed": true, "data": {"Bool": true}}})
+        );
+        assert!(result["costs"]["runtime"].as_u64().unwrap() > 0);
+        assert!(result["costs"]["write_count"].as_u64().unwrap() > 0);
+        assert!(result["estimated_len"].as_u64().unwrap() > 0);
+        assert!(result["estimations_error"].is_string());
+        assert!(result.get("estimations").is_none());
+
+        let estimates_path = PathBuf::from(&path).join("estimates");
+        fs::create_dir_all(&estimates_path).unwrap();
+        let scalar_path = estimates_path.join("fee_estimator_scalar_rate.sqlite");
+        drop(
+            ScalarFeeRateEstimator::open(
+                &scalar_path,
+                ProportionalDotProduct::new(MAX_BLOCK_LEN as u64),
+            )
+            .unwrap(),
+        );
+        Connection::open(&scalar_path)
+            .unwrap()
+            .execute(
+                "INSERT INTO scalar_fee_estimator (estimate_key, high, middle, low) VALUES (1, 30000.0, 20000.0, 10000.0)",
+                NO_PARAMS,
+            )
+            .unwrap();
+
+        let (exit, result) = invoke_command("test", &args);
+        let result = result.unwrap();
+        assert_eq!(exit, 0);
+        assert!(result.get("estimations_error").is_none());
+        let estimations = result["estimations"].as_array().unwrap();
+        assert_eq!(estimations.len(), 3);
+        let minimum_fee = result["estimated_len"].as_u64().unwrap() * MINIMUM_TX_FEE_RATE_PER_BYTE;
+        for estimation in estimations.iter() {
+            assert!(estimation["fee"].as_u64().unwrap() >= minimum_fee);
+        }
+
+        // a function that is not public cannot be estimated
+        let (exit, result) = invoke_command(
+            "test",
+            &[
+                "estimate_cost".to_string(),
+                path,
+                pox,
+                "no-such-function".to_string(),
+            ],
+        );
+        assert_eq!(exit, 1);
+        assert_eq!(result.unwrap()["success"], json!(false));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let contract = format!("/tmp/bindings-{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &contract,
+            "(define-public (mint! (amount uint)) (ok amount))
+             (define-read-only (get-pox) (contract-call? 'SP000000000000000000002Q6VF78.pox get-pox-info))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "bindings".to_string(),
+                "--contract_id".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.tokens".to_string(),
+                contract.clone(),
+            ],
+        );
+        assert_eq!(invoked, (0, None));
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "bindings".to_string(),
+                "--testnet".to_string(),
+                contract.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 1);
+        assert_eq!(invoked.1.unwrap()["message"], "Checks failed.");
+    }
+
+    #[test]
+    fn test_lint() {
+        let clar_name = format!("/tmp/test-lint_{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &clar_name,
+            "(define-read-only (f)\n  (let ((a 1)) (unwrap-panic (some 2))))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command("test", &["lint".to_string(), clar_name.clone()]);
+        assert_eq!(invoked.0, 0);
+        let result = invoked.1.unwrap();
+        assert_eq!(result["message"], json!("Lint passed."));
+        assert_eq!(
+            result["lints"][0],
+            json!({
+                "rule": "unused-let-binding",
+                "level": "warn",
+                "message": "let-binding `a` is never used",
+                "line": 2,
+                "column": 9,
+            })
+        );
+        assert_eq!(result["lints"][1]["rule"], json!("unwrap-panic"));
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "lint".to_string(),
+                "--allow".to_string(),
+                "unused-let-binding".to_string(),
+                "--deny".to_string(),
+                "unwrap-panic".to_string(),
+                clar_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 1);
+        let result = invoked.1.unwrap();
+        assert_eq!(result["message"], json!("Lint failed."));
+        assert_eq!(result["lints"].as_array().unwrap().len(), 1);
+        assert_eq!(result["lints"][0]["level"], json!("deny"));
+
+        let config_name = format!("{}.json", &clar_name);
+        fs::write(
+            &config_name,
+            r#"{"unused-let-binding": "allow", "unwrap-panic": "allow"}"#,
+        )
+        .unwrap();
+        let invoked = invoke_command(
+            "test",
+            &[
+                "lint".to_string(),
+                "--config".to_string(),
+                config_name.clone(),
+                clar_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap()["lints"], json!([]));
+    }
+
+    #[test]
+    fn test_check_trait_call_warnings() {
+        let clar_name = format!(
+            "/tmp/test-trait-call-warnings_{}.clar",
+            rand::thread_rng().gen::<i32>()
+        );
+        fs::write(
+            &clar_name,
+            "(define-trait token ((transfer (uint) (response bool uint))))
+(define-data-var total uint u0)
+(define-public (withdraw (t <token>) (amount uint))
+  (begin
+    (try! (contract-call? t transfer amount))
+    (ok (var-set total amount))))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command("test", &["check".to_string(), clar_name.clone()]);
+        assert_eq!(invoked.0, 0);
+        assert!(invoked.1.unwrap().get("warnings").is_none());
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "check".to_string(),
+                "--trait_call_warnings".to_string(),
+                clar_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        let result = invoked.1.unwrap();
+        assert_eq!(result["message"], json!("Checks passed."));
+        let warnings = result["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["rule"], json!("trait-call-before-write"));
+        assert_eq!(warnings[0]["line"], json!(6));
+    }
+
     #[test]
     fn test_samples() {
         let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
@@ -17,11 +17,13 @@
 use std::convert::TryFrom;
 use std::error;
 use std::fmt;
+use std::sync::Arc;
 use std::thread;
 
 use chainstate::stacks::boot::{
-    BOOT_CODE_COSTS, BOOT_CODE_COSTS_2, BOOT_CODE_COST_VOTING_TESTNET as BOOT_CODE_COST_VOTING,
-    BOOT_CODE_POX_TESTNET, COSTS_2_NAME,
+    BOOT_CODE_COSTS, BOOT_CODE_COSTS_2, BOOT_CODE_COSTS_3, BOOT_CODE_COSTS_3_TESTNET,
+    BOOT_CODE_COST_VOTING_TESTNET as BOOT_CODE_COST_VOTING, BOOT_CODE_POX_TESTNET, COSTS_2_NAME,
+    COSTS_3_NAME,
 };
 use chainstate::stacks::db::StacksAccount;
 use chainstate::stacks::events::{StacksTransactionEvent, StacksTransactionReceipt};
@@ -31,17 +33,19 @@ use chainstate::stacks::Error as ChainstateError;
 use chainstate::stacks::{SinglesigHashMode, SinglesigSpendingCondition, StacksTransaction};
 use util::strings::StacksString;
 use vm::analysis;
+use vm::analysis::cache::AnalysisCache;
 use vm::analysis::AnalysisDatabase;
 use vm::analysis::{errors::CheckError, errors::CheckErrors, ContractAnalysis};
 use vm::ast;
 use vm::ast::{errors::ParseError, errors::ParseErrors, ContractAST};
 use vm::contexts::{AssetMap, Environment, OwnedEnvironment};
-use vm::costs::{CostTracker, ExecutionCost, LimitedCostTracker};
+use vm::costs::{CostOverrides, CostTracker, ExecutionCost, LimitedCostTracker};
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, RollbackWrapper, RollbackWrapperPersistedLog,
     STXBalance, SqliteConnection, NULL_BURN_STATE_DB, NULL_HEADER_DB,
 };
 use vm::errors::Error as InterpreterError;
+use vm::pool::EvalPool;
 use vm::representations::SymbolicExpression;
 use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, TypeSignature, Value,
@@ -88,6 +92,8 @@ use types::chainstate::BurnchainHeaderHash;
 pub struct ClarityInstance {
     datastore: MarfedKV,
     mainnet: bool,
+    cost_overrides: Option<Arc<CostOverrides>>,
+    analysis_cache: Option<Arc<AnalysisCache>>,
 }
 
 ///
@@ -100,6 +106,10 @@ pub struct ClarityBlockConnection<'a> {
     cost_track: Option<LimitedCostTracker>,
     mainnet: bool,
     epoch: StacksEpochId,
+    cost_overrides: Option<Arc<CostOverrides>>,
+    analysis_cache: Option<Arc<AnalysisCache>>,
+    /// interpreter buffers, reused from one transaction to the next
+    eval_pool: EvalPool,
 }
 
 ///
@@ -115,6 +125,9 @@ pub struct ClarityTransactionConnection<'a, 'b> {
     cost_track: &'a mut Option<LimitedCostTracker>,
     mainnet: bool,
     epoch: StacksEpochId,
+    cost_overrides: Option<Arc<CostOverrides>>,
+    analysis_cache: Option<Arc<AnalysisCache>>,
+    eval_pool: &'a mut EvalPool,
 }
 
 pub struct ClarityReadOnlyConnection<'a> {
@@ -122,6 +135,7 @@ pub struct ClarityReadOnlyConnection<'a> {
     header_db: &'a dyn HeadersDB,
     burn_state_db: &'a dyn BurnStateDB,
     epoch: StacksEpochId,
+    cost_overrides: Option<Arc<CostOverrides>>,
 }
 
 #[derive(Debug)]
@@ -270,7 +284,33 @@ impl ClarityBlockConnection<'_> {
 
 impl ClarityInstance {
     pub fn new(mainnet: bool, datastore: MarfedKV) -> ClarityInstance {
-        ClarityInstance { datastore, mainnet }
+        ClarityInstance {
+            datastore,
+            mainnet,
+            cost_overrides: None,
+            analysis_cache: None,
+        }
+    }
+
+    /// Use these constant costs in place of the given cost functions in every block and
+    /// unconfirmed state opened from now on.  Only meant for private chains.
+    pub fn set_cost_overrides(&mut self, cost_overrides: Option<Arc<CostOverrides>>) {
+        self.cost_overrides = cost_overrides;
+    }
+
+    /// Reuse analyses of identical contract sources from this cache, in every block opened from
+    /// now on.
+    pub fn set_analysis_cache(&mut self, analysis_cache: Option<Arc<AnalysisCache>>) {
+        self.analysis_cache = analysis_cache;
+    }
+
+    /// The cache that blocks opened from now on will reuse analyses from, if any.  Analyses
+    /// are never reused under cost overrides, so there is none then.
+    pub fn get_analysis_cache(&self) -> Option<Arc<AnalysisCache>> {
+        match self.cost_overrides {
+            Some(_) => None,
+            None => self.analysis_cache.clone(),
+        }
     }
 
     pub fn with_marf<F, R>(&mut self, f: F) -> R
@@ -322,15 +362,17 @@ impl ClarityInstance {
         let epoch = Self::get_epoch_of(current, header_db, burn_state_db);
         let cost_track = {
             let mut clarity_db = datastore.as_clarity_db(&NULL_HEADER_DB, &NULL_BURN_STATE_DB);
-            Some(
-                LimitedCostTracker::new(
-                    self.mainnet,
-                    epoch.block_limit.clone(),
-                    &mut clarity_db,
-                    epoch.epoch_id,
-                )
-                .expect("FAIL: problem instantiating cost tracking"),
+            let mut cost_track = LimitedCostTracker::new(
+                self.mainnet,
+                epoch.block_limit.clone(),
+                &mut clarity_db,
+                epoch.epoch_id,
             )
+            .expect("FAIL: problem instantiating cost tracking");
+            if let Some(cost_overrides) = self.cost_overrides.as_ref() {
+                cost_track.set_cost_overrides(cost_overrides.clone());
+            }
+            Some(cost_track)
         };
 
         ClarityBlockConnection {
@@ -340,6 +382,9 @@ impl ClarityInstance {
             cost_track,
             mainnet: self.mainnet,
             epoch: epoch.epoch_id,
+            cost_overrides: self.cost_overrides.clone(),
+            analysis_cache: self.analysis_cache.clone(),
+            eval_pool: EvalPool::new(),
         }
     }
 
@@ -363,6 +408,9 @@ impl ClarityInstance {
             cost_track,
             mainnet: self.mainnet,
             epoch,
+            cost_overrides: self.cost_overrides.clone(),
+            analysis_cache: self.analysis_cache.clone(),
+            eval_pool: EvalPool::new(),
         }
     }
 
@@ -388,6 +436,9 @@ impl ClarityInstance {
             cost_track,
             mainnet: self.mainnet,
             epoch,
+            cost_overrides: self.cost_overrides.clone(),
+            analysis_cache: self.analysis_cache.clone(),
+            eval_pool: EvalPool::new(),
         };
 
         let use_mainnet = self.mainnet;
@@ -460,15 +511,17 @@ impl ClarityInstance {
 
         let cost_track = {
             let mut clarity_db = datastore.as_clarity_db(&NULL_HEADER_DB, &NULL_BURN_STATE_DB);
-            Some(
-                LimitedCostTracker::new(
-                    self.mainnet,
-                    epoch.block_limit.clone(),
-                    &mut clarity_db,
-                    epoch.epoch_id,
-                )
-                .expect("FAIL: problem instantiating cost tracking"),
+            let mut cost_track = LimitedCostTracker::new(
+                self.mainnet,
+                epoch.block_limit.clone(),
+                &mut clarity_db,
+                epoch.epoch_id,
             )
+            .expect("FAIL: problem instantiating cost tracking");
+            if let Some(cost_overrides) = self.cost_overrides.as_ref() {
+                cost_track.set_cost_overrides(cost_overrides.clone());
+            }
+            Some(cost_track)
         };
 
         ClarityBlockConnection {
@@ -478,6 +531,9 @@ impl ClarityInstance {
             cost_track,
             mainnet: self.mainnet,
             epoch: epoch.epoch_id,
+            cost_overrides: self.cost_overrides.clone(),
+            analysis_cache: self.analysis_cache.clone(),
+            eval_pool: EvalPool::new(),
         }
     }
 
@@ -515,6 +571,7 @@ impl ClarityInstance {
             header_db,
             burn_state_db,
             epoch,
+            cost_overrides: self.cost_overrides.clone(),
         })
     }
 
@@ -553,6 +610,23 @@ impl ClarityInstance {
     }
 }
 
+/// The outcome of a contract call that was evaluated against a speculative store and then
+/// discarded.
+#[derive(Debug)]
+pub struct SimulatedTransaction {
+    /// value returned by the called function
+    pub result: Value,
+    /// assets moved by the call
+    pub asset_map: AssetMap,
+    /// events the call emitted
+    pub events: Vec<StacksTransactionEvent>,
+    /// raw Clarity store keys the call wrote, and the serialized values it would have written.
+    ///   If the call returned an `err` response, its writes are rolled back and this is empty.
+    pub write_set: Vec<(String, String)>,
+    /// cost of the call
+    pub cost: ExecutionCost,
+}
+
 pub trait ClarityConnection {
     /// Do something to the underlying DB that involves only reading.
     fn with_clarity_db_readonly_owned<F, R>(&mut self, to_do: F) -> R
@@ -564,6 +638,12 @@ pub trait ClarityConnection {
 
     fn get_epoch(&self) -> StacksEpochId;
 
+    /// Constant costs which cost trackers made for this connection should charge in place of
+    /// evaluating the corresponding cost functions (see `LimitedCostTracker::set_cost_overrides`)
+    fn cost_overrides(&self) -> Option<Arc<CostOverrides>> {
+        None
+    }
+
     fn with_clarity_db_readonly<F, R>(&mut self, to_do: F) -> R
     where
         F: FnOnce(&mut ClarityDatabase) -> R,
@@ -594,6 +674,43 @@ pub trait ClarityConnection {
             (result, db)
         })
     }
+
+    /// Run a public function against the current state as though it were a transaction, and
+    /// report what it would do.  Nothing is committed, regardless of the outcome.
+    fn simulate_transaction(
+        &mut self,
+        mainnet: bool,
+        sender: PrincipalData,
+        cost_track: LimitedCostTracker,
+        contract: &QualifiedContractIdentifier,
+        public_function: &str,
+        args: &[Value],
+    ) -> Result<SimulatedTransaction, InterpreterError> {
+        let epoch_id = self.get_epoch();
+        let expr_args: Vec<_> = args
+            .iter()
+            .map(|x| SymbolicExpression::atom_value(x.clone()))
+            .collect();
+
+        self.with_clarity_db_readonly_owned(|clarity_db| {
+            let mut vm_env =
+                OwnedEnvironment::new_cost_limited(mainnet, clarity_db, cost_track, epoch_id);
+            let result =
+                vm_env.execute_transaction(sender, contract.clone(), public_function, &expr_args);
+            let (db, cost_track) = vm_env
+                .destruct()
+                .expect("Failed to recover database reference after executing transaction");
+
+            let result = result.map(|(result, asset_map, events)| SimulatedTransaction {
+                result,
+                asset_map,
+                events,
+                write_set: db.store.get_pending_writes(),
+                cost: cost_track.get_total(),
+            });
+            (result, db)
+        })
+    }
 }
 
 impl ClarityConnection for ClarityBlockConnection<'_> {
@@ -624,6 +741,10 @@ impl ClarityConnection for ClarityBlockConnection<'_> {
     fn get_epoch(&self) -> StacksEpochId {
         self.epoch
     }
+
+    fn cost_overrides(&self) -> Option<Arc<CostOverrides>> {
+        self.cost_overrides.clone()
+    }
 }
 
 impl ClarityConnection for ClarityReadOnlyConnection<'_> {
@@ -655,6 +776,10 @@ impl ClarityConnection for ClarityReadOnlyConnection<'_> {
     fn get_epoch(&self) -> StacksEpochId {
         self.epoch
     }
+
+    fn cost_overrides(&self) -> Option<Arc<CostOverrides>> {
+        self.cost_overrides.clone()
+    }
 }
 
 impl<'a> ClarityBlockConnection<'a> {
@@ -804,6 +929,80 @@ impl<'a> ClarityBlockConnection<'a> {
         })
     }
 
+    /// Epoch 2.1 adds Clarity natives, and the costs-3 contract to price them
+    pub fn initialize_epoch_2_1(&mut self) -> Result<StacksTransactionReceipt, Error> {
+        // use the `using!` statement to ensure that the old cost_tracker is placed
+        //  back in all branches after initialization
+        using!(self.cost_track, "cost tracker", |old_cost_tracker| {
+            // epoch initialization is *free*
+            self.cost_track.replace(LimitedCostTracker::new_free());
+
+            let mainnet = self.mainnet;
+
+            let tx_version = if mainnet {
+                TransactionVersion::Mainnet
+            } else {
+                TransactionVersion::Testnet
+            };
+
+            let boot_code_address = boot_code_addr(mainnet);
+
+            let boot_code_auth = boot_code_tx_auth(boot_code_address);
+
+            let boot_code_nonce = self.with_clarity_db_readonly(|db| {
+                db.get_account_nonce(&boot_code_address.clone().into())
+            });
+
+            let boot_code_account = boot_code_acc(boot_code_address, boot_code_nonce);
+
+            // instantiate costs 3 contract...
+            let cost_3_code = if mainnet {
+                &*BOOT_CODE_COSTS_3
+            } else {
+                &*BOOT_CODE_COSTS_3_TESTNET
+            };
+
+            let payload = TransactionPayload::SmartContract(TransactionSmartContract {
+                name: ContractName::try_from(COSTS_3_NAME)
+                    .expect("FATAL: invalid boot-code contract name"),
+                code_body: StacksString::from_str(cost_3_code)
+                    .expect("FATAL: invalid boot code body"),
+            });
+
+            let costs_3_contract_tx =
+                StacksTransaction::new(tx_version.clone(), boot_code_auth.clone(), payload);
+
+            let initialization_receipt = self.as_transaction(|tx_conn| {
+                // bump the epoch in the Clarity DB
+                tx_conn
+                    .with_clarity_db(|db| {
+                        db.set_clarity_epoch_version(StacksEpochId::Epoch21);
+                        Ok(())
+                    })
+                    .unwrap();
+
+                // initialize with a synthetic transaction
+                StacksChainState::process_transaction_payload(
+                    tx_conn,
+                    &costs_3_contract_tx,
+                    &boot_code_account,
+                )
+                .expect("FATAL: Failed to process costs 3 contract initialization")
+            });
+
+            if initialization_receipt.result != Value::okay_true()
+                || initialization_receipt.post_condition_aborted
+            {
+                panic!(
+                    "FATAL: Failure processing Costs 3 contract initialization: {:#?}",
+                    &initialization_receipt
+                );
+            }
+
+            (old_cost_tracker, Ok(initialization_receipt))
+        })
+    }
+
     pub fn start_transaction_processing<'b>(&'b mut self) -> ClarityTransactionConnection<'b, 'a> {
         let store = &mut self.datastore;
         let cost_track = &mut self.cost_track;
@@ -820,6 +1019,9 @@ impl<'a> ClarityBlockConnection<'a> {
             log: Some(log),
             mainnet,
             epoch: self.epoch,
+            cost_overrides: self.cost_overrides.clone(),
+            analysis_cache: self.analysis_cache.clone(),
+            eval_pool: &mut self.eval_pool,
         }
     }
 
@@ -878,6 +1080,10 @@ impl<'a, 'b> ClarityConnection for ClarityTransactionConnection<'a, 'b> {
     fn get_epoch(&self) -> StacksEpochId {
         self.epoch
     }
+
+    fn cost_overrides(&self) -> Option<Arc<CostOverrides>> {
+        self.cost_overrides.clone()
+    }
 }
 
 impl<'a, 'b> Drop for ClarityTransactionConnection<'a, 'b> {
@@ -952,6 +1158,8 @@ impl<'a, 'b> ClarityTransactionConnection<'a, 'b> {
         identifier: &QualifiedContractIdentifier,
         contract_content: &str,
     ) -> Result<(ContractAST, ContractAnalysis), Error> {
+        let epoch = self.epoch;
+        let analysis_cache = self.analysis_cache.clone();
         using!(self.cost_track, "cost tracker", |mut cost_track| {
             self.inner_with_analysis_db(|db| {
                 let ast_result = ast::build_ast(identifier, contract_content, &mut cost_track);
@@ -961,13 +1169,24 @@ impl<'a, 'b> ClarityTransactionConnection<'a, 'b> {
                     Err(e) => return (cost_track, Err(e.into())),
                 };
 
-                let result = analysis::run_analysis(
-                    identifier,
-                    &mut contract_ast.expressions,
-                    db,
-                    false,
-                    cost_track,
-                );
+                let result = match analysis_cache {
+                    Some(ref analysis_cache) => analysis_cache.run_analysis(
+                        identifier,
+                        contract_content,
+                        &mut contract_ast.expressions,
+                        db,
+                        cost_track,
+                        epoch,
+                    ),
+                    None => analysis::run_analysis(
+                        identifier,
+                        &mut contract_ast.expressions,
+                        db,
+                        false,
+                        cost_track,
+                        epoch,
+                    ),
+                };
 
                 match result {
                     Ok(mut contract_analysis) => {
@@ -1005,7 +1224,9 @@ impl<'a, 'b> ClarityTransactionConnection<'a, 'b> {
                 db.begin();
                 let mut vm_env =
                     OwnedEnvironment::new_cost_limited(self.mainnet, db, cost_track, self.epoch);
+                vm_env.set_eval_pool(std::mem::take(self.eval_pool));
                 let result = to_do(&mut vm_env);
+                *self.eval_pool = vm_env.take_eval_pool();
                 let (mut db, cost_track) = vm_env
                     .destruct()
                     .expect("Failed to recover database reference after executing transaction");
@@ -1220,7 +1441,7 @@ mod tests {
     use vm::types::{StandardPrincipalData, Value};
 
     use core::{PEER_VERSION_EPOCH_1_0, PEER_VERSION_EPOCH_2_0, PEER_VERSION_EPOCH_2_05};
-    use vm::tests::{TEST_BURN_STATE_DB, TEST_HEADER_DB};
+    use vm::tests::{TEST_BURN_STATE_DB, TEST_BURN_STATE_DB_21, TEST_HEADER_DB};
 
     use crate::clarity_vm::database::marf::MarfedKV;
     use crate::types::proof::ClarityMarfTrieId;
@@ -1781,6 +2002,122 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_simulate_transaction() {
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(false, marf);
+        let contract_identifier = QualifiedContractIdentifier::local("foo").unwrap();
+        let sender: PrincipalData = StandardPrincipalData::transient().into();
+        let recipient = PrincipalData::parse("SP3X6QWWETNBZWGBK6DRGTR1KX50S74D3433WDGJY").unwrap();
+
+        clarity_instance
+            .begin_test_genesis_block(
+                &StacksBlockId::sentinel(),
+                &StacksBlockId([0 as u8; 32]),
+                &TEST_HEADER_DB,
+                &TEST_BURN_STATE_DB,
+            )
+            .commit_block();
+
+        {
+            let mut conn = clarity_instance.begin_block(
+                &StacksBlockId([0 as u8; 32]),
+                &StacksBlockId([1 as u8; 32]),
+                &TEST_HEADER_DB,
+                &TEST_BURN_STATE_DB,
+            );
+
+            let contract = "
+            (define-fungible-token tok)
+            (define-data-var counter int 0)
+            (define-map seen principal int)
+            (unwrap-panic (ft-mint? tok u100 tx-sender))
+            (define-public (go (to principal))
+              (begin
+                (var-set counter (+ (var-get counter) 1))
+                (map-set seen to (var-get counter))
+                (print \"hello\")
+                (try! (ft-transfer? tok u10 tx-sender to))
+                (ok (var-get counter))))
+            (define-public (fail)
+              (begin (var-set counter 100) (err u1)))";
+
+            conn.as_transaction(|conn| {
+                let (ct_ast, ct_analysis) = conn
+                    .analyze_smart_contract(&contract_identifier, &contract)
+                    .unwrap();
+                conn.initialize_smart_contract(&contract_identifier, &ct_ast, &contract, |_, _| {
+                    false
+                })
+                .unwrap();
+                conn.save_analysis(&contract_identifier, &ct_analysis)
+                    .unwrap();
+            });
+
+            for _ in 0..2 {
+                let simulated = conn
+                    .simulate_transaction(
+                        false,
+                        sender.clone(),
+                        LimitedCostTracker::new_free(),
+                        &contract_identifier,
+                        "go",
+                        &[Value::Principal(recipient.clone())],
+                    )
+                    .unwrap();
+
+                // nothing is committed, so each simulation sees the same state
+                assert_eq!(simulated.result, Value::okay(Value::Int(1)).unwrap());
+                assert_eq!(simulated.events.len(), 2);
+                let asset_id = AssetIdentifier {
+                    contract_identifier: contract_identifier.clone(),
+                    asset_name: "tok".into(),
+                };
+                assert_eq!(
+                    simulated.asset_map.get_fungible_tokens(&sender, &asset_id),
+                    Some(10)
+                );
+                assert!(simulated
+                    .write_set
+                    .iter()
+                    .any(|(key, _)| key.ends_with("::counter")));
+                // var, map entry, and two token balances
+                assert_eq!(simulated.write_set.len(), 4);
+            }
+
+            let simulated = conn
+                .as_transaction(|tx| {
+                    tx.simulate_transaction(
+                        false,
+                        sender.clone(),
+                        LimitedCostTracker::new_free(),
+                        &contract_identifier,
+                        "fail",
+                        &[],
+                    )
+                })
+                .unwrap();
+            assert_eq!(simulated.result, Value::error(Value::UInt(1)).unwrap());
+            assert_eq!(simulated.write_set.len(), 0);
+
+            // simulations left no trace
+            assert_eq!(
+                conn.as_transaction(|tx| tx.run_contract_call(
+                    &sender,
+                    &contract_identifier,
+                    "go",
+                    &[Value::Principal(recipient.clone())],
+                    |_, _| false
+                ))
+                .unwrap()
+                .0,
+                Value::okay(Value::Int(1)).unwrap()
+            );
+
+            conn.commit_block();
+        }
+    }
+
     #[test]
     pub fn test_post_condition_failure_contract_publish() {
         use chainstate::stacks::db::*;
@@ -2011,4 +2348,420 @@ mod tests {
             conn.commit_block();
         }
     }
+
+    fn deploy_analyzed(
+        conn: &mut ClarityBlockConnection,
+        contract_identifier: &QualifiedContractIdentifier,
+        contract: &str,
+    ) -> Result<(), Error> {
+        conn.as_transaction(|conn| {
+            let (ct_ast, ct_analysis) =
+                conn.analyze_smart_contract(contract_identifier, contract)?;
+            conn.initialize_smart_contract(contract_identifier, &ct_ast, contract, |_, _| false)?;
+            conn.save_analysis(contract_identifier, &ct_analysis)
+                .expect("Failed to save analysis");
+            Ok(())
+        })
+    }
+
+    fn call_public(
+        conn: &mut ClarityBlockConnection,
+        contract_identifier: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+    ) -> Value {
+        conn.as_transaction(|tx| {
+            tx.run_contract_call(
+                &StandardPrincipalData::transient().into(),
+                contract_identifier,
+                function,
+                args,
+                |_, _| false,
+            )
+        })
+        .unwrap()
+        .0
+    }
+
+    #[test]
+    pub fn test_analysis_cache() {
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(false, marf);
+        let analysis_cache = Arc::new(AnalysisCache::new(16));
+        clarity_instance.set_analysis_cache(Some(analysis_cache.clone()));
+
+        clarity_instance
+            .begin_test_genesis_block(
+                &StacksBlockId::sentinel(),
+                &StacksBlockId([0; 32]),
+                &TEST_HEADER_DB,
+                &TEST_BURN_STATE_DB,
+            )
+            .commit_block();
+
+        let registry = QualifiedContractIdentifier::local("registry").unwrap();
+        let token_source = "(define-fungible-token token)
+            (define-public (register) (contract-call? .registry register tx-sender))
+            (define-read-only (get-registry) .registry)
+            (define-read-only (get-token-contract) .token-a)";
+        let analyze = |conn: &mut ClarityBlockConnection, name: &str| {
+            let contract_identifier = QualifiedContractIdentifier::local(name).unwrap();
+            conn.as_transaction(|tx| {
+                let cost_before = tx.cost_so_far();
+                let (ast, analysis) = tx
+                    .analyze_smart_contract(&contract_identifier, token_source)
+                    .unwrap();
+                let mut cost = tx.cost_so_far();
+                cost.sub(&cost_before).unwrap();
+                tx.initialize_smart_contract(&contract_identifier, &ast, token_source, |_, _| {
+                    false
+                })
+                .unwrap();
+                tx.save_analysis(&contract_identifier, &analysis).unwrap();
+                (analysis, cost)
+            })
+        };
+
+        let mut conn = clarity_instance.begin_block(
+            &StacksBlockId([0; 32]),
+            &StacksBlockId([1; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB,
+        );
+        deploy_analyzed(
+            &mut conn,
+            &registry,
+            "(define-public (register (who principal)) (ok true))",
+        )
+        .unwrap();
+        let hits = analysis_cache.hits();
+
+        let (analysis_b, cost_b) = analyze(&mut conn, "token-b");
+        assert_eq!(analysis_cache.hits(), hits);
+        let (analysis_c, cost_c) = analyze(&mut conn, "token-c");
+        assert_eq!(analysis_cache.hits(), hits + 1);
+        assert_eq!(cost_b, cost_c);
+        assert_eq!(
+            analysis_c.contract_identifier,
+            QualifiedContractIdentifier::local("token-c").unwrap()
+        );
+        assert_eq!(analysis_b.contract_interface, analysis_c.contract_interface);
+        assert_eq!(analysis_b.type_map, analysis_c.type_map);
+        assert_eq!(analysis_b.expressions, analysis_c.expressions);
+
+        // the same source refers to itself when published as `token-a`
+        let (analysis_a, _) = analyze(&mut conn, "token-a");
+        assert_eq!(analysis_cache.hits(), hits + 1);
+        assert_eq!(analysis_b.contract_interface, analysis_a.contract_interface);
+        conn.commit_block();
+
+        // in a fork where the registry is different, the cached analysis does not apply
+        let mut conn = clarity_instance.begin_block(
+            &StacksBlockId([0; 32]),
+            &StacksBlockId([2; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB,
+        );
+        deploy_analyzed(
+            &mut conn,
+            &registry,
+            "(define-public (register (who principal)) (ok u1))",
+        )
+        .unwrap();
+        analyze(&mut conn, "token-d");
+        assert_eq!(analysis_cache.hits(), hits + 1);
+        conn.commit_block();
+    }
+
+    #[test]
+    pub fn test_eval_pool_reused_across_transactions() {
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(false, marf);
+        let contract_identifier = QualifiedContractIdentifier::local("counter").unwrap();
+
+        let mut conn = clarity_instance.begin_test_genesis_block(
+            &StacksBlockId::sentinel(),
+            &StacksBlockId([0; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB,
+        );
+        deploy_analyzed(
+            &mut conn,
+            &contract_identifier,
+            "(define-data-var n int 0)
+             (define-private (next (x int)) (+ x 1))
+             (define-public (incr) (ok (var-set n (next (var-get n)))))
+             (define-read-only (get-n) (var-get n))",
+        )
+        .unwrap();
+
+        call_public(&mut conn, &contract_identifier, "incr", &[]);
+        let (reused, allocated) = conn.eval_pool.stats();
+        call_public(&mut conn, &contract_identifier, "incr", &[]);
+        let (reused_after, allocated_after) = conn.eval_pool.stats();
+        // the second call mostly reuses the buffers the first one left behind.  Variadic natives
+        // like `+` keep the argument vectors they are given, so those are allocated again.
+        assert!(allocated_after - allocated < reused_after - reused);
+
+        let n =
+            conn.as_transaction(|tx| tx.eval_read_only(&contract_identifier, "(get-n)").unwrap());
+        assert_eq!(n, Value::Int(2));
+        conn.commit_block();
+    }
+
+    #[test]
+    pub fn test_trait_introspection() {
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(false, marf);
+
+        clarity_instance
+            .begin_test_genesis_block(
+                &StacksBlockId::sentinel(),
+                &StacksBlockId([0; 32]),
+                &TEST_HEADER_DB,
+                &TEST_BURN_STATE_DB,
+            )
+            .commit_block();
+
+        let mut conn = clarity_instance.begin_block(
+            &StacksBlockId([0; 32]),
+            &StacksBlockId([1; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB_21,
+        );
+        conn.initialize_epoch_2_05().unwrap();
+        conn.initialize_epoch_2_1().unwrap();
+
+        let traits = QualifiedContractIdentifier::local("traits").unwrap();
+        let explicit = QualifiedContractIdentifier::local("explicit").unwrap();
+        let structural = QualifiedContractIdentifier::local("structural").unwrap();
+        let mismatched = QualifiedContractIdentifier::local("mismatched").unwrap();
+        let checker = QualifiedContractIdentifier::local("checker").unwrap();
+
+        deploy_analyzed(
+            &mut conn,
+            &traits,
+            "(define-trait token-trait
+                ((transfer? (principal uint) (response bool uint))
+                 (get-balance (principal) (response uint uint))))",
+        )
+        .unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &explicit,
+            "(impl-trait .traits.token-trait)
+             (define-public (transfer? (to principal) (amount uint)) (ok true))
+             (define-public (get-balance (who principal)) (ok u0))",
+        )
+        .unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &structural,
+            "(define-public (transfer? (to principal) (amount uint)) (ok true))
+             (define-read-only (get-balance (who principal)) (ok u0))
+             (define-private (helper) u1)
+             (define-public (mint (amount uint)) (ok amount))",
+        )
+        .unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &mismatched,
+            "(define-public (transfer? (to principal) (amount uint)) (ok u1))
+             (define-public (get-balance (who principal)) (ok u0))",
+        )
+        .unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &checker,
+            "(define-public (check (who principal))
+                (ok (implements-trait who .traits.token-trait)))
+             (define-public (public-functions (who principal))
+                (ok (get-public-functions? who)))",
+        )
+        .unwrap();
+
+        for (contract, expected) in [
+            (&explicit, true),
+            (&structural, true),
+            (&mismatched, false),
+            (&traits, false),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                call_public(
+                    &mut conn,
+                    &checker,
+                    "check",
+                    &[Value::from(PrincipalData::Contract((*contract).clone()))]
+                ),
+                Value::okay(Value::Bool(*expected)).unwrap()
+            );
+        }
+        assert_eq!(
+            call_public(
+                &mut conn,
+                &checker,
+                "check",
+                &[Value::from(StandardPrincipalData::transient())]
+            ),
+            Value::okay(Value::Bool(false)).unwrap()
+        );
+
+        assert_eq!(
+            call_public(
+                &mut conn,
+                &checker,
+                "public-functions",
+                &[Value::from(PrincipalData::Contract(structural.clone()))]
+            )
+            .to_string(),
+            "(ok (some (\"mint\" \"transfer?\")))"
+        );
+        assert_eq!(
+            call_public(
+                &mut conn,
+                &checker,
+                "public-functions",
+                &[Value::from(PrincipalData::Contract(
+                    QualifiedContractIdentifier::local("missing").unwrap()
+                ))]
+            ),
+            Value::okay(Value::none()).unwrap()
+        );
+
+        // the new natives are reserved names from 2.1 on
+        let shadowing = QualifiedContractIdentifier::local("shadowing").unwrap();
+        assert!(deploy_analyzed(
+            &mut conn,
+            &shadowing,
+            "(define-read-only (implements-trait) true)"
+        )
+        .is_err());
+
+        conn.commit_block();
+    }
+
+    #[test]
+    pub fn test_trait_introspection_unavailable_before_2_1() {
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(false, marf);
+
+        clarity_instance
+            .begin_test_genesis_block(
+                &StacksBlockId::sentinel(),
+                &StacksBlockId([0; 32]),
+                &TEST_HEADER_DB,
+                &TEST_BURN_STATE_DB,
+            )
+            .commit_block();
+
+        let mut conn = clarity_instance.begin_block(
+            &StacksBlockId([0; 32]),
+            &StacksBlockId([1; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB,
+        );
+        conn.initialize_epoch_2_05().unwrap();
+
+        let checker = QualifiedContractIdentifier::local("checker").unwrap();
+        match deploy_analyzed(
+            &mut conn,
+            &checker,
+            "(define-public (check (who principal)) (ok (get-public-functions? who)))",
+        ) {
+            Err(Error::Analysis(e)) => assert_eq!(
+                e.err,
+                CheckErrors::UnknownFunction("get-public-functions?".to_string())
+            ),
+            _ => panic!("Expected an unknown function error"),
+        };
+
+        // contracts published before 2.1 may use the names for their own definitions
+        let shadowing = QualifiedContractIdentifier::local("shadowing").unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &shadowing,
+            "(define-public (implements-trait) (ok true))
+             (define-data-var get-public-functions? int 1)",
+        )
+        .unwrap();
+        assert_eq!(
+            call_public(&mut conn, &shadowing, "implements-trait", &[]),
+            Value::okay(Value::Bool(true)).unwrap()
+        );
+
+        conn.commit_block();
+    }
+
+    #[test]
+    pub fn test_pre_2_1_definitions_shadow_natives_after_2_1() {
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(false, marf);
+
+        clarity_instance
+            .begin_test_genesis_block(
+                &StacksBlockId::sentinel(),
+                &StacksBlockId([0; 32]),
+                &TEST_HEADER_DB,
+                &TEST_BURN_STATE_DB,
+            )
+            .commit_block();
+
+        let shadowing = QualifiedContractIdentifier::local("shadowing").unwrap();
+        let mut conn = clarity_instance.begin_block(
+            &StacksBlockId([0; 32]),
+            &StacksBlockId([1; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB,
+        );
+        conn.initialize_epoch_2_05().unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &shadowing,
+            "(define-private (slice? (a int) (b int)) (+ a b))
+             (define-public (call-slice) (ok (slice? 1 2)))
+             (define-public (bind-slice) (ok (let ((implements-trait 3)) implements-trait)))
+             (define-read-only (string-to-int? (s (string-ascii 8))) 7)
+             (define-public (call-string-to-int) (ok (string-to-int? \"12\")))",
+        )
+        .unwrap();
+        conn.commit_block();
+
+        // once 2.1 activates, the contract still calls its own definitions
+        let mut conn = clarity_instance.begin_block(
+            &StacksBlockId([1; 32]),
+            &StacksBlockId([2; 32]),
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB_21,
+        );
+        conn.initialize_epoch_2_1().unwrap();
+        assert_eq!(
+            call_public(&mut conn, &shadowing, "call-slice", &[]),
+            Value::okay(Value::Int(3)).unwrap()
+        );
+        assert_eq!(
+            call_public(&mut conn, &shadowing, "bind-slice", &[]),
+            Value::okay(Value::Int(3)).unwrap()
+        );
+        assert_eq!(
+            call_public(&mut conn, &shadowing, "call-string-to-int", &[]),
+            Value::okay(Value::Int(7)).unwrap()
+        );
+
+        // ...including when called from a contract published in 2.1
+        let caller = QualifiedContractIdentifier::local("caller").unwrap();
+        deploy_analyzed(
+            &mut conn,
+            &caller,
+            "(define-public (call-through) (contract-call? .shadowing call-slice))",
+        )
+        .unwrap();
+        assert_eq!(
+            call_public(&mut conn, &caller, "call-through", &[]),
+            Value::okay(Value::Int(3)).unwrap()
+        );
+
+        conn.commit_block();
+    }
 }
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use rusqlite::Connection;
 
 use chainstate::stacks::index::marf::{MarfConnection, MarfTransaction, MARF};
+use chainstate::stacks::index::node::TriePath;
 use chainstate::stacks::index::{Error, MarfTrieId};
 use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
 use util::db::IndexDBConn;
@@ -52,7 +53,8 @@ impl MarfedKV {
         };
 
         if SqliteConnection::check_schema(&marf.sqlite_conn()).is_ok() {
-            // no need to initialize
+            // no need to initialize, but the key preimage table may postdate this database
+            SqliteConnection::initialize_key_preimages(marf.sqlite_conn())?;
             return Ok(marf);
         }
 
@@ -90,6 +92,25 @@ impl MarfedKV {
         Ok(MarfedKV { marf, chain_tip })
     }
 
+    /// Open an existing Clarity state database, such as a node's, without writing to it.  Only
+    /// read-only connections can be made to the returned store.
+    pub fn open_readonly(
+        path_str: &str,
+        miner_tip: Option<&StacksBlockId>,
+    ) -> InterpreterResult<MarfedKV> {
+        let mut path = PathBuf::from(path_str);
+        path.push("marf.sqlite");
+        let marf_path = path.to_str().ok_or_else(|| InterpreterError::BadFileName)?;
+
+        let marf = MARF::from_path_readonly(marf_path)
+            .map_err(|err| InterpreterError::MarfFailure(IncomparableError { err }))?;
+        SqliteConnection::check_schema(marf.sqlite_conn())?;
+
+        let chain_tip = miner_tip.cloned().unwrap_or_else(StacksBlockId::sentinel);
+
+        Ok(MarfedKV { marf, chain_tip })
+    }
+
     // used by benchmarks
     pub fn temporary() -> MarfedKV {
         use rand::Rng;
@@ -644,6 +665,11 @@ impl<'a> ClarityBackingStore for WritableMarfStore<'a> {
             trace!("MarfedKV put '{}' = '{}'", &key, &value);
             let marf_value = MARFValue::from_value(&value);
             SqliteConnection::put(self.get_side_store(), &marf_value.to_hex(), &value);
+            SqliteConnection::put_key_preimage(
+                self.get_side_store(),
+                &TriePath::from_key(&key).to_hex(),
+                &key,
+            );
             keys.push(key);
             values.push(marf_value);
         }
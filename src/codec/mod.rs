@@ -1,5 +1,5 @@
 use std::io::{Read, Write};
-use std::{error, fmt, io, mem};
+use std::{cmp, error, fmt, io, mem};
 
 use util::hash::HASH160_ENCODED_SIZE;
 use util::secp256k1::MESSAGE_SIGNATURE_ENCODED_SIZE;
@@ -119,7 +119,13 @@ fn read_next_vec<T: StacksMessageCodec + Sized, R: Read>(
         )));
     }
 
-    let mut ret = Vec::with_capacity(len as usize);
+    // the length prefix is only a claim, so only reserve a little memory up front and let the
+    // vector grow as its items actually arrive
+    let prealloc = cmp::min(
+        len as usize,
+        MAX_VEC_PREALLOC_BYTES / cmp::max(mem::size_of::<T>(), 1),
+    );
+    let mut ret = Vec::with_capacity(prealloc);
     for _i in 0..len {
         let next_item = T::consensus_deserialize(fd)?;
         ret.push(next_item);
@@ -128,6 +134,30 @@ fn read_next_vec<T: StacksMessageCodec + Sized, R: Read>(
     Ok(ret)
 }
 
+/// Audit a length prefix before reading or allocating anything for the items it claims.  It may
+/// claim at most `max_items` items, and since each item takes at least `min_item_size` bytes,
+/// they all have to fit in the `bytes_left` bytes that follow it.
+pub fn check_length_prefix(
+    len: u32,
+    max_items: u32,
+    min_item_size: u32,
+    bytes_left: usize,
+) -> Result<(), Error> {
+    if len > max_items {
+        return Err(Error::DeserializeError(format!(
+            "Length prefix claims too many items ({} > {})",
+            len, max_items
+        )));
+    }
+    if (len as u128) * (min_item_size as u128) > bytes_left as u128 {
+        return Err(Error::DeserializeError(format!(
+            "Length prefix claims {} items of at least {} bytes, but only {} bytes follow",
+            len, min_item_size, bytes_left
+        )));
+    }
+    Ok(())
+}
+
 pub fn read_next_at_most<R: Read, T: StacksMessageCodec + Sized>(
     fd: &mut R,
     max_items: u32,
@@ -165,6 +195,13 @@ pub const MAX_PAYLOAD_LEN: u32 = 1 + 16 * 1024 * 1024;
 pub const MAX_MESSAGE_LEN: u32 =
     MAX_PAYLOAD_LEN + (PREAMBLE_ENCODED_SIZE + MAX_RELAYERS_LEN * RELAY_DATA_ENCODED_SIZE);
 
+/// What follows a p2p preamble -- the length-prefixed relayers and the payload -- can't be
+/// bigger than this
+pub const MAX_FRAME_LEN: u32 = 4 + MAX_RELAYERS_LEN * RELAY_DATA_ENCODED_SIZE + MAX_PAYLOAD_LEN;
+
+/// How much memory to reserve for a decoded vector before its items have been read
+pub const MAX_VEC_PREALLOC_BYTES: usize = 64 * 1024;
+
 /// P2P preamble length (addands correspond to fields above)
 pub const PREAMBLE_ENCODED_SIZE: u32 = 4
     + 4
@@ -16,12 +16,14 @@
 
 use std::cmp;
 use std::collections::HashSet;
+use std::fmt;
 use std::fs;
 use std::hash::Hasher;
 use std::io::{Read, Write};
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use rand::distributions::Uniform;
 use rand::prelude::Distribution;
@@ -251,6 +253,69 @@ pub struct MemPoolTxMetadata {
     pub accept_time: u64,
 }
 
+/// The names of the transaction payload types, as used to filter listed mempool transactions
+pub const MEMPOOL_PAYLOAD_TYPES: &[&str] = &[
+    "token_transfer",
+    "contract_call",
+    "smart_contract",
+    "poison_microblock",
+    "coinbase",
+];
+
+/// Get the name of a transaction's payload type
+pub fn mempool_payload_type(payload: &TransactionPayload) -> &'static str {
+    match payload {
+        TransactionPayload::TokenTransfer(..) => "token_transfer",
+        TransactionPayload::ContractCall(..) => "contract_call",
+        TransactionPayload::SmartContract(..) => "smart_contract",
+        TransactionPayload::PoisonMicroblock(..) => "poison_microblock",
+        TransactionPayload::Coinbase(..) => "coinbase",
+    }
+}
+
+/// Filters on the mempool transactions to list.  Unset filters match everything.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemPoolTxFilter {
+    pub origin: Option<StacksAddress>,
+    /// the address paying the fee, which is the origin if the transaction is not sponsored
+    pub sponsor: Option<StacksAddress>,
+    pub min_fee: Option<u64>,
+    pub max_fee: Option<u64>,
+    /// one of MEMPOOL_PAYLOAD_TYPES
+    pub payload_type: Option<String>,
+}
+
+/// A position in the listing of mempool transactions, which are ordered by the time they were
+/// accepted, and then by txid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolTxCursor {
+    pub accept_time: u64,
+    pub txid: Txid,
+}
+
+impl fmt::Display for MemPoolTxCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.accept_time, &self.txid)
+    }
+}
+
+impl FromStr for MemPoolTxCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MemPoolTxCursor, String> {
+        let parts: Vec<_> = s.split('-').collect();
+        if parts.len() != 2 {
+            return Err(format!("Invalid mempool cursor: {}", s));
+        }
+        Ok(MemPoolTxCursor {
+            accept_time: parts[0]
+                .parse()
+                .map_err(|_| format!("Invalid mempool cursor: {}", s))?,
+            txid: Txid::from_hex(parts[1]).map_err(|_| format!("Invalid mempool cursor: {}", s))?,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MemPoolWalkSettings {
     /// Minimum transaction fee that will be considered
@@ -1130,6 +1195,93 @@ impl MemPoolDB {
         )
     }
 
+    /// Get the fee rate the cost estimator assigned to a transaction, if it has one
+    pub fn get_tx_fee_rate(conn: &DBConn, txid: &Txid) -> Result<Option<f64>, db_error> {
+        conn.query_row(
+            "SELECT fee_rate FROM fee_estimates WHERE txid = ?1",
+            &[txid as &dyn ToSql],
+            |row| row.get::<_, Option<f64>>(0),
+        )
+        .optional()
+        .map(|fee_rate_opt| fee_rate_opt.flatten())
+        .map_err(db_error::SqliteError)
+    }
+
+    /// Get a page of up to `limit` transactions matching `filter`, along with their estimated fee
+    /// rates, in the order they were accepted.  Pass the cursor of the last transaction on a page
+    /// to get the next page.
+    pub fn get_txs_page(
+        conn: &DBConn,
+        filter: &MemPoolTxFilter,
+        cursor: Option<&MemPoolTxCursor>,
+        limit: u32,
+    ) -> Result<Vec<(MemPoolTxInfo, Option<f64>)>, db_error> {
+        let mut clauses = vec![];
+        let mut args: Vec<Box<dyn ToSql>> = vec![];
+        if let Some(cursor) = cursor {
+            clauses.push("(m.accept_time > ? OR (m.accept_time = ? AND m.txid > ?))");
+            args.push(Box::new(u64_to_sql(cursor.accept_time)?));
+            args.push(Box::new(u64_to_sql(cursor.accept_time)?));
+            args.push(Box::new(cursor.txid.clone()));
+        }
+        if let Some(ref origin) = filter.origin {
+            clauses.push("m.origin_address = ?");
+            args.push(Box::new(origin.to_string()));
+        }
+        if let Some(ref sponsor) = filter.sponsor {
+            clauses.push("m.sponsor_address = ?");
+            args.push(Box::new(sponsor.to_string()));
+        }
+        if let Some(min_fee) = filter.min_fee {
+            clauses.push("m.tx_fee >= ?");
+            args.push(Box::new(u64_to_sql(min_fee)?));
+        }
+        if let Some(max_fee) = filter.max_fee {
+            clauses.push("m.tx_fee <= ?");
+            args.push(Box::new(u64_to_sql(max_fee)?));
+        }
+
+        let mut sql =
+            "SELECT m.*, f.fee_rate FROM mempool AS m LEFT JOIN fee_estimates AS f ON m.txid = f.txid"
+                .to_string();
+        if !clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&clauses.join(" AND "));
+        }
+        sql.push_str(" ORDER BY m.accept_time ASC, m.txid ASC");
+
+        // the payload type isn't stored in its own column, so filter on it as we go
+        let mut stmt = conn.prepare(&sql).map_err(db_error::SqliteError)?;
+        let mut rows = stmt
+            .query(args.iter().map(|arg| arg.as_ref()))
+            .map_err(db_error::SqliteError)?;
+
+        let mut txs = vec![];
+        while (txs.len() as u32) < limit {
+            let row = match rows.next().map_err(db_error::SqliteError)? {
+                Some(row) => row,
+                None => {
+                    break;
+                }
+            };
+            let txinfo = MemPoolTxInfo::from_row(row)?;
+            if let Some(ref payload_type) = filter.payload_type {
+                if mempool_payload_type(&txinfo.tx.payload) != payload_type {
+                    continue;
+                }
+            }
+            let fee_rate: Option<f64> = row.get("fee_rate").map_err(db_error::SqliteError)?;
+            txs.push((txinfo, fee_rate));
+        }
+        Ok(txs)
+    }
+
+    /// Get the IDs of all transactions across all tips
+    pub fn get_all_txids(conn: &DBConn) -> Result<Vec<Txid>, db_error> {
+        let sql = "SELECT txid FROM mempool";
+        query_row_columns(conn, sql, NO_PARAMS, "txid")
+    }
+
     /// Get all transactions across all tips
     #[cfg(test)]
     pub fn get_all_txs(conn: &DBConn) -> Result<Vec<MemPoolTxInfo>, db_error> {
@@ -1532,6 +1684,32 @@ impl MemPoolDB {
         Ok(())
     }
 
+    /// Estimate a transaction's execution cost with this mempool's cost estimator
+    pub fn estimate_tx_cost(
+        &self,
+        tx: &StacksTransaction,
+        stacks_epoch_id: &StacksEpochId,
+    ) -> Result<ExecutionCost, EstimatorError> {
+        self.cost_estimator
+            .estimate_cost(&tx.payload, stacks_epoch_id)
+    }
+
+    /// Estimate the fee rate a transaction pays, the same way `submit` does when it stores it
+    pub fn estimate_tx_fee_rate(
+        &self,
+        tx: &StacksTransaction,
+        block_limit: &ExecutionCost,
+        stacks_epoch_id: &StacksEpochId,
+    ) -> Result<f64, EstimatorError> {
+        cost_estimates::estimate_fee_rate(
+            tx,
+            self.cost_estimator.as_ref(),
+            self.metric.as_ref(),
+            block_limit,
+            stacks_epoch_id,
+        )
+    }
+
     /// One-shot submit
     pub fn submit(
         &mut self,
@@ -40,6 +40,7 @@ use chainstate::stacks::{
 };
 use core::mempool::MemPoolWalkSettings;
 use core::mempool::TxTag;
+use core::mempool::{mempool_payload_type, MemPoolTxCursor, MemPoolTxFilter};
 use core::mempool::{BLOOM_COUNTER_DEPTH, BLOOM_COUNTER_ERROR_RATE, MAX_BLOOM_COUNTER_TXS};
 use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use core::FIRST_STACKS_BLOCK_HASH;
@@ -2018,3 +2019,137 @@ fn test_decode_tx_stream() {
         }
     }
 }
+
+#[test]
+fn test_get_txs_page() {
+    let mut chainstate = instantiate_chainstate(false, 0x80000000, "test_get_txs_page");
+    let chainstate_path = chainstate_path("test_get_txs_page");
+    let mut mempool = MemPoolDB::open_test(false, 0x80000000, &chainstate_path).unwrap();
+
+    let mut txs = codec_all_transactions(
+        &TransactionVersion::Testnet,
+        0x80000000,
+        &TransactionAnchorMode::Any,
+        &TransactionPostConditionMode::Allow,
+    );
+    let num_txs = txs.len();
+    let mut origins = vec![];
+    let mut num_transfers = 0;
+
+    let mut mempool_tx = mempool.tx_begin().unwrap();
+    for (i, mut tx) in txs.drain(..).enumerate() {
+        let origin_address = StacksAddress {
+            version: 22,
+            bytes: Hash160::from_data(&i.to_be_bytes()),
+        };
+        let sponsor_address = StacksAddress {
+            version: 22,
+            bytes: Hash160::from_data(&(i + 1).to_be_bytes()),
+        };
+        tx.set_tx_fee(100 * (i as u64 + 1));
+        if mempool_payload_type(&tx.payload) == "token_transfer" {
+            num_transfers += 1;
+        }
+
+        let origin_nonce = tx.get_origin_nonce();
+        let sponsor_nonce = tx.get_sponsor_nonce().unwrap_or(origin_nonce);
+        MemPoolDB::try_add_tx(
+            &mut mempool_tx,
+            &mut chainstate,
+            &ConsensusHash([0x1; 20]),
+            &BlockHeaderHash([0x2; 32]),
+            tx.txid(),
+            tx.serialize_to_vec(),
+            tx.get_tx_fee(),
+            100,
+            &origin_address,
+            origin_nonce,
+            &sponsor_address,
+            sponsor_nonce,
+            None,
+        )
+        .unwrap();
+        origins.push(origin_address);
+    }
+    mempool_tx.commit().unwrap();
+
+    // page through everything
+    let mut listed = vec![];
+    let mut cursor = None;
+    loop {
+        let page = MemPoolDB::get_txs_page(
+            mempool.conn(),
+            &MemPoolTxFilter::default(),
+            cursor.as_ref(),
+            7,
+        )
+        .unwrap();
+        assert!(page.len() <= 7);
+        if page.is_empty() {
+            break;
+        }
+        let (last, fee_rate) = page.last().unwrap();
+        assert_eq!(*fee_rate, None);
+        cursor = Some(MemPoolTxCursor {
+            accept_time: last.metadata.accept_time,
+            txid: last.metadata.txid.clone(),
+        });
+        listed.extend(page.into_iter().map(|(txinfo, _)| txinfo));
+    }
+    assert_eq!(listed.len(), num_txs);
+    // ordered by acceptance time, then txid (as stored, i.e. as hex)
+    for pair in listed.windows(2) {
+        assert!(
+            (pair[0].metadata.accept_time, pair[0].metadata.txid.to_hex())
+                < (pair[1].metadata.accept_time, pair[1].metadata.txid.to_hex())
+        );
+    }
+
+    // cursors round-trip through strings
+    let cursor = cursor.unwrap();
+    assert_eq!(cursor.to_string().parse::<MemPoolTxCursor>(), Ok(cursor));
+    assert!("123".parse::<MemPoolTxCursor>().is_err());
+
+    // filters
+    let page = MemPoolDB::get_txs_page(
+        mempool.conn(),
+        &MemPoolTxFilter {
+            origin: Some(origins[3].clone()),
+            ..MemPoolTxFilter::default()
+        },
+        None,
+        num_txs as u32,
+    )
+    .unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].0.metadata.origin_address, origins[3]);
+
+    let page = MemPoolDB::get_txs_page(
+        mempool.conn(),
+        &MemPoolTxFilter {
+            min_fee: Some(200),
+            max_fee: Some(500),
+            ..MemPoolTxFilter::default()
+        },
+        None,
+        num_txs as u32,
+    )
+    .unwrap();
+    assert_eq!(page.len(), 4);
+    assert!(page
+        .iter()
+        .all(|(txinfo, _)| txinfo.metadata.tx_fee >= 200 && txinfo.metadata.tx_fee <= 500));
+
+    let page = MemPoolDB::get_txs_page(
+        mempool.conn(),
+        &MemPoolTxFilter {
+            payload_type: Some("token_transfer".to_string()),
+            ..MemPoolTxFilter::default()
+        },
+        None,
+        num_txs as u32,
+    )
+    .unwrap();
+    assert!(num_transfers > 0 && num_transfers < num_txs);
+    assert_eq!(page.len(), num_transfers);
+}
@@ -39,7 +39,7 @@ CREATE TABLE median_fee_estimator (
     low NUMBER NOT NULL
 )";
 
-const MINIMUM_TX_FEE_RATE: f64 = 1f64;
+pub const MINIMUM_TX_FEE_RATE: f64 = 1f64;
 
 /// FeeRateEstimator with the following properties:
 ///
@@ -197,29 +197,9 @@ impl<M: CostMetric> FeeEstimator for WeightedMedianFeeRateEstimator<M> {
         receipt: &StacksEpochReceipt,
         block_limit: &ExecutionCost,
     ) -> Result<(), EstimatorError> {
-        // Calculate sorted fee rate for each transaction in the block.
-        let mut working_fee_rates: Vec<FeeRateAndWeight> = receipt
-            .tx_receipts
-            .iter()
-            .filter_map(|tx_receipt| {
-                fee_rate_and_weight_from_receipt(&self.metric, &tx_receipt, block_limit)
-            })
-            .collect();
-
-        // If necessary, add the "minimum" fee rate to fill the block.
-        maybe_add_minimum_fee_rate(&mut working_fee_rates, self.full_block_weight);
-
-        // If fee rates non-empty, then compute an update.
-        if working_fee_rates.len() > 0 {
-            // Values must be sorted.
-            working_fee_rates.sort_by(|a, b| {
-                a.fee_rate
-                    .partial_cmp(&b.fee_rate)
-                    .unwrap_or(Ordering::Equal)
-            });
-
-            // Compute the estimate and update.
-            let block_estimate = fee_rate_estimate_from_sorted_weighted_fees(&working_fee_rates);
+        if let Some(block_estimate) =
+            fee_rate_estimate_from_block(&self.metric, receipt, block_limit, self.full_block_weight)
+        {
             self.update_estimate(block_estimate);
         }
 
@@ -231,6 +211,43 @@ impl<M: CostMetric> FeeEstimator for WeightedMedianFeeRateEstimator<M> {
     }
 }
 
+/// Computes the weighted percentile `FeeRateEstimate` of the transactions in a block, padding any
+/// space the block left unused with the minimum fee rate.
+///
+/// Returns None if there are no fee rates to compute an estimate from.
+pub fn fee_rate_estimate_from_block(
+    metric: &dyn CostMetric,
+    receipt: &StacksEpochReceipt,
+    block_limit: &ExecutionCost,
+    full_block_weight: u64,
+) -> Option<FeeRateEstimate> {
+    // Calculate sorted fee rate for each transaction in the block.
+    let mut working_fee_rates: Vec<FeeRateAndWeight> = receipt
+        .tx_receipts
+        .iter()
+        .filter_map(|tx_receipt| fee_rate_and_weight_from_receipt(metric, &tx_receipt, block_limit))
+        .collect();
+
+    // If necessary, add the "minimum" fee rate to fill the block.
+    maybe_add_minimum_fee_rate(&mut working_fee_rates, full_block_weight);
+
+    // If fee rates non-empty, then compute an estimate.
+    if working_fee_rates.len() > 0 {
+        // Values must be sorted.
+        working_fee_rates.sort_by(|a, b| {
+            a.fee_rate
+                .partial_cmp(&b.fee_rate)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        Some(fee_rate_estimate_from_sorted_weighted_fees(
+            &working_fee_rates,
+        ))
+    } else {
+        None
+    }
+}
+
 /// Computes a `FeeRateEstimate` based on `sorted_fee_rates` using a "weighted percentile" method
 /// described in https://en.wikipedia.org/wiki/Percentile#Weighted_percentile
 ///
@@ -290,7 +307,10 @@ pub fn fee_rate_estimate_from_sorted_weighted_fees(
 
 /// If the weights in `working_rates` do not add up to `full_block_weight`, add a new entry **in
 /// place** that takes up the remaining space.
-fn maybe_add_minimum_fee_rate(working_rates: &mut Vec<FeeRateAndWeight>, full_block_weight: u64) {
+pub fn maybe_add_minimum_fee_rate(
+    working_rates: &mut Vec<FeeRateAndWeight>,
+    full_block_weight: u64,
+) {
     let mut total_weight = 0u64;
     for rate_and_weight in working_rates.into_iter() {
         total_weight = match total_weight.checked_add(rate_and_weight.weight) {
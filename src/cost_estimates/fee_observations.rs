@@ -0,0 +1,364 @@
+use std::cmp;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rusqlite::Transaction as SqlTransaction;
+use rusqlite::{Connection, Error as SqliteError, OptionalExtension, Row, NO_PARAMS};
+use serde_json::Value as JsonValue;
+
+use burnchains::Txid;
+use chainstate::stacks::db::StacksEpochReceipt;
+use chainstate::stacks::events::TransactionOrigin;
+use chainstate::stacks::TransactionPayload;
+use types::chainstate::StacksBlockId;
+use util::db::sqlite_open;
+use util::db::tx_begin_immediate_sqlite;
+use util::db::u64_to_sql;
+use vm::costs::ExecutionCost;
+
+use crate::util::db::table_exists;
+
+use super::metrics::CostMetric;
+use super::{DimensionFeeRateEstimates, FeeRateEstimate};
+use super::{EstimatorError, FeeEstimator};
+
+const CREATE_TABLE: &'static str = "
+CREATE TABLE fee_observations (
+    observation_key INTEGER PRIMARY KEY AUTOINCREMENT,
+    txid TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    index_block_hash TEXT NOT NULL,
+    payload_type TEXT NOT NULL,
+    tx_len INTEGER NOT NULL,
+    runtime INTEGER NOT NULL,
+    read_count INTEGER NOT NULL,
+    read_length INTEGER NOT NULL,
+    write_count INTEGER NOT NULL,
+    write_length INTEGER NOT NULL,
+    scalar_cost INTEGER NOT NULL,
+    fee INTEGER NOT NULL,
+    -- when the node's mempool first saw the transaction, if it did
+    accept_time INTEGER,
+    -- the timestamp of the burnchain block the transaction's block was mined in
+    confirm_time INTEGER NOT NULL
+)";
+
+const CREATE_INDEX: &'static str =
+    "CREATE INDEX IF NOT EXISTS fee_observations_by_height ON fee_observations(block_height)";
+
+/// One transaction the fee estimator saw confirmed: what it cost, what it paid, and how long it
+/// waited to be mined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeObservation {
+    pub txid: Txid,
+    pub block_height: u64,
+    pub index_block_hash: StacksBlockId,
+    pub payload_type: String,
+    pub tx_len: u64,
+    pub execution_cost: ExecutionCost,
+    /// `execution_cost` and `tx_len` as a scalar, under the recorder's cost metric
+    pub scalar_cost: u64,
+    pub fee: u64,
+    /// Seconds since the epoch at which the node's mempool accepted the transaction.  None if
+    /// the transaction never passed through this node's mempool.
+    pub accept_time: Option<u64>,
+    /// Seconds since the epoch of the burnchain block the transaction was confirmed in.
+    pub confirm_time: u64,
+}
+
+impl FeeObservation {
+    pub const CSV_HEADER: &'static str = "txid,block_height,index_block_hash,payload_type,tx_len,runtime,read_count,read_length,write_count,write_length,scalar_cost,fee,fee_rate,accept_time,confirm_time,confirmation_delay";
+
+    /// The fee paid per unit of scalar cost
+    pub fn fee_rate(&self) -> f64 {
+        self.fee as f64 / cmp::max(self.scalar_cost, 1) as f64
+    }
+
+    /// Seconds between the mempool accepting the transaction and its confirmation, if the
+    /// mempool saw it.
+    pub fn confirmation_delay(&self) -> Option<u64> {
+        self.accept_time
+            .map(|accept_time| self.confirm_time.saturating_sub(accept_time))
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "txid": self.txid.to_hex(),
+            "block_height": self.block_height,
+            "index_block_hash": self.index_block_hash.to_hex(),
+            "payload_type": self.payload_type,
+            "tx_len": self.tx_len,
+            "execution_cost": self.execution_cost,
+            "scalar_cost": self.scalar_cost,
+            "fee": self.fee,
+            "fee_rate": self.fee_rate(),
+            "accept_time": self.accept_time,
+            "confirm_time": self.confirm_time,
+            "confirmation_delay": self.confirmation_delay(),
+        })
+    }
+
+    /// A row matching `CSV_HEADER`.  Unknown values are left empty.
+    pub fn to_csv_row(&self) -> String {
+        let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.txid.to_hex(),
+            self.block_height,
+            self.index_block_hash.to_hex(),
+            self.payload_type,
+            self.tx_len,
+            self.execution_cost.runtime,
+            self.execution_cost.read_count,
+            self.execution_cost.read_length,
+            self.execution_cost.write_count,
+            self.execution_cost.write_length,
+            self.scalar_cost,
+            self.fee,
+            self.fee_rate(),
+            optional(self.accept_time),
+            self.confirm_time,
+            optional(self.confirmation_delay())
+        )
+    }
+
+    fn from_row(row: &Row) -> Result<FeeObservation, SqliteError> {
+        let get_u64 = |column: &str| -> Result<u64, SqliteError> {
+            let value: i64 = row.get(column)?;
+            Ok(value as u64)
+        };
+        let accept_time: Option<i64> = row.get("accept_time")?;
+        Ok(FeeObservation {
+            txid: row.get("txid")?,
+            block_height: get_u64("block_height")?,
+            index_block_hash: row.get("index_block_hash")?,
+            payload_type: row.get("payload_type")?,
+            tx_len: get_u64("tx_len")?,
+            execution_cost: ExecutionCost {
+                runtime: get_u64("runtime")?,
+                read_count: get_u64("read_count")?,
+                read_length: get_u64("read_length")?,
+                write_count: get_u64("write_count")?,
+                write_length: get_u64("write_length")?,
+            },
+            scalar_cost: get_u64("scalar_cost")?,
+            fee: get_u64("fee")?,
+            accept_time: accept_time.map(|t| t as u64),
+            confirm_time: get_u64("confirm_time")?,
+        })
+    }
+}
+
+/// The FeeObservationRecorder wraps an underlying FeeEstimator, and records every fee-paying
+/// transaction in the blocks it is notified of as a `FeeObservation`, so that operators can
+/// evaluate and tune estimators offline on the data the node saw.
+pub struct FeeObservationRecorder {
+    db: Connection,
+    /// Receives every `notify_block` and answers every `get_rate_estimates`
+    underlying: Box<dyn FeeEstimator>,
+    metric: Box<dyn CostMetric>,
+    /// Path to the node's mempool database, for looking up when transactions were accepted
+    mempool_path: Option<PathBuf>,
+}
+
+impl FeeObservationRecorder {
+    /// Open a fee observation recorder at the given db path. Creates if not existent.
+    pub fn open(
+        p: &Path,
+        underlying: Box<dyn FeeEstimator>,
+        metric: Box<dyn CostMetric>,
+        mempool_path: Option<PathBuf>,
+    ) -> Result<Self, SqliteError> {
+        let db = Self::open_db(p)?;
+        Ok(Self {
+            db,
+            underlying,
+            metric,
+            mempool_path,
+        })
+    }
+
+    fn open_db(p: &Path) -> Result<Connection, SqliteError> {
+        let mut db = sqlite_open(
+            p,
+            rusqlite::OpenFlags::SQLITE_OPEN_CREATE | rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+            false,
+        )?;
+
+        let tx = tx_begin_immediate_sqlite(&mut db)?;
+        Self::instantiate_db(&tx)?;
+        tx.commit()?;
+        Ok(db)
+    }
+
+    fn instantiate_db(tx: &SqlTransaction) -> Result<(), SqliteError> {
+        if !table_exists(tx, "fee_observations")? {
+            tx.execute(CREATE_TABLE, NO_PARAMS)?;
+            tx.execute(CREATE_INDEX, NO_PARAMS)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load the observations recorded at `p` for blocks at or above `since_height`, in the order
+    /// they were recorded.
+    pub fn get_observations(
+        p: &Path,
+        since_height: u64,
+    ) -> Result<Vec<FeeObservation>, SqliteError> {
+        let db = sqlite_open(p, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY, false)?;
+        let sql =
+            "SELECT * FROM fee_observations WHERE block_height >= ? ORDER BY observation_key ASC";
+        let mut stmt = db.prepare(sql)?;
+        let since_height = u64_to_sql(since_height).unwrap_or(i64::MAX);
+        let rows = stmt.query_and_then(&[since_height], |row| FeeObservation::from_row(row))?;
+        rows.collect()
+    }
+
+    /// Write `observations` as CSV, with a header line.
+    pub fn write_csv<W: Write>(
+        observations: &[FeeObservation],
+        fd: &mut W,
+    ) -> Result<(), std::io::Error> {
+        writeln!(fd, "{}", FeeObservation::CSV_HEADER)?;
+        for observation in observations.iter() {
+            writeln!(fd, "{}", observation.to_csv_row())?;
+        }
+        Ok(())
+    }
+
+    /// When the node's mempool accepted `txid`, if it did and still has it.
+    fn get_accept_time(&self, mempool: &Connection, txid: &Txid) -> Option<u64> {
+        let accept_time: Option<i64> = mempool
+            .query_row(
+                "SELECT accept_time FROM mempool WHERE txid = ?",
+                &[txid],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or_else(|e| {
+                warn!("Failed to query mempool accept time"; "txid" => %txid, "error" => %e);
+                None
+            });
+        accept_time.map(|t| t as u64)
+    }
+
+    fn record_block(
+        &mut self,
+        receipt: &StacksEpochReceipt,
+        block_limit: &ExecutionCost,
+    ) -> Result<(), SqliteError> {
+        let mempool = match self.mempool_path.as_ref() {
+            Some(path) if path.exists() => Some(sqlite_open(
+                path,
+                rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+                false,
+            )?),
+            _ => None,
+        };
+
+        let block_height = receipt.header.block_height;
+        let index_block_hash = receipt.header.index_block_hash();
+        let confirm_time = receipt.header.burn_header_timestamp;
+
+        let mut observations = vec![];
+        for tx_receipt in receipt.tx_receipts.iter() {
+            let tx = match tx_receipt.transaction {
+                TransactionOrigin::Stacks(ref tx) => tx,
+                TransactionOrigin::Burn(_) => continue,
+            };
+            let tx_len = tx.tx_len();
+            let (payload_type, scalar_cost) = match tx.payload {
+                TransactionPayload::TokenTransfer(..) => {
+                    ("token_transfer", self.metric.from_len(tx_len))
+                }
+                // Coinbase txs are "free", so they don't factor into the fee market.
+                TransactionPayload::Coinbase(_) => continue,
+                TransactionPayload::PoisonMicroblock(..) => (
+                    "poison_microblock",
+                    self.metric
+                        .from_cost_and_len(&tx_receipt.execution_cost, block_limit, tx_len),
+                ),
+                TransactionPayload::ContractCall(_) => (
+                    "contract_call",
+                    self.metric
+                        .from_cost_and_len(&tx_receipt.execution_cost, block_limit, tx_len),
+                ),
+                TransactionPayload::SmartContract(_) => (
+                    "smart_contract",
+                    self.metric
+                        .from_cost_and_len(&tx_receipt.execution_cost, block_limit, tx_len),
+                ),
+            };
+            let txid = tx.txid();
+            let accept_time = mempool
+                .as_ref()
+                .and_then(|mempool| self.get_accept_time(mempool, &txid));
+            observations.push(FeeObservation {
+                txid,
+                block_height,
+                index_block_hash: index_block_hash.clone(),
+                payload_type: payload_type.to_string(),
+                tx_len,
+                execution_cost: tx_receipt.execution_cost.clone(),
+                scalar_cost,
+                fee: tx.get_tx_fee(),
+                accept_time,
+                confirm_time,
+            });
+        }
+
+        let sql = "INSERT INTO fee_observations
+                   (txid, block_height, index_block_hash, payload_type, tx_len, runtime, read_count,
+                    read_length, write_count, write_length, scalar_cost, fee, accept_time, confirm_time)
+                   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+        let to_sql = |x: u64| u64_to_sql(x).unwrap_or(i64::MAX);
+        let tx = tx_begin_immediate_sqlite(&mut self.db)?;
+        for observation in observations.iter() {
+            tx.execute(
+                sql,
+                rusqlite::params![
+                    observation.txid,
+                    to_sql(observation.block_height),
+                    observation.index_block_hash,
+                    observation.payload_type,
+                    to_sql(observation.tx_len),
+                    to_sql(observation.execution_cost.runtime),
+                    to_sql(observation.execution_cost.read_count),
+                    to_sql(observation.execution_cost.read_length),
+                    to_sql(observation.execution_cost.write_count),
+                    to_sql(observation.execution_cost.write_length),
+                    to_sql(observation.scalar_cost),
+                    to_sql(observation.fee),
+                    observation.accept_time.map(to_sql),
+                    to_sql(observation.confirm_time),
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+}
+
+impl FeeEstimator for FeeObservationRecorder {
+    /// Records the block's observations, then passes the block to `underlying`.
+    fn notify_block(
+        &mut self,
+        receipt: &StacksEpochReceipt,
+        block_limit: &ExecutionCost,
+    ) -> Result<(), EstimatorError> {
+        if let Err(e) = self.record_block(receipt, block_limit) {
+            warn!("Failed to record fee observations";
+                  "stacks_height" => receipt.header.block_height,
+                  "error" => %e);
+        }
+        self.underlying.notify_block(receipt, block_limit)
+    }
+
+    fn get_rate_estimates(&self) -> Result<FeeRateEstimate, EstimatorError> {
+        self.underlying.get_rate_estimates()
+    }
+
+    fn get_dimension_rate_estimates(&self) -> Result<DimensionFeeRateEstimates, EstimatorError> {
+        self.underlying.get_dimension_rate_estimates()
+    }
+}
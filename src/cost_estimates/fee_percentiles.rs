@@ -0,0 +1,305 @@
+use std::cmp;
+use std::cmp::Ordering;
+use std::path::Path;
+
+use rusqlite::Transaction as SqlTransaction;
+use rusqlite::{Connection, Error as SqliteError};
+
+use util::db::sqlite_open;
+use util::db::tx_begin_immediate_sqlite;
+
+use vm::costs::ExecutionCost;
+
+use chainstate::stacks::db::StacksEpochReceipt;
+use chainstate::stacks::events::TransactionOrigin;
+use chainstate::stacks::TransactionPayload;
+
+use crate::util::db::table_exists;
+
+use super::fee_medians::{
+    fee_rate_estimate_from_block, fee_rate_estimate_from_sorted_weighted_fees,
+    maybe_add_minimum_fee_rate, FeeRateAndWeight, MINIMUM_TX_FEE_RATE,
+};
+use super::metrics::CostMetric;
+use super::metrics::PROPORTION_RESOLUTION;
+use super::{dimension_proportions, DimensionFeeRateEstimates, FeeRateEstimate};
+use super::{EstimatorError, FeeEstimator, FEE_RATE_DIMENSIONS};
+
+const CREATE_TABLE: &'static str = "
+CREATE TABLE percentile_fee_estimator (
+    block_key INTEGER NOT NULL,
+    -- 'scalar', or one of FEE_RATE_DIMENSIONS
+    dimension TEXT NOT NULL,
+    high NUMBER NOT NULL,
+    middle NUMBER NOT NULL,
+    low NUMBER NOT NULL,
+    PRIMARY KEY (block_key, dimension)
+)";
+
+/// The `dimension` under which the scalar (whole block limit) measures are stored.
+const SCALAR_DIMENSION: &'static str = "scalar";
+
+/// FeeRateEstimator which measures the fee rates paid for each dimension of the block limit:
+///
+/// 1) Each block is measured the way `WeightedMedianFeeRateEstimator` measures it: the weighted
+///    5th, 50th and 95th percentile fee rates of the block, padded with the minimum fee rate.
+///    This measure answers `get_rate_estimates()`.
+/// 2) Each block is *also* measured once per dimension (runtime, read count, read length,
+///    write count, write length and tx length). In a dimension's measure, each transaction's fee
+///    rate is weighted by the share of that dimension it occupied, and unused space in that
+///    dimension is padded with the minimum fee rate. A dimension that blocks fill up therefore
+///    measures higher than one that they leave mostly empty.
+/// 3) The estimate for each dimension is the median of its last `window_size` measures.
+pub struct PercentileFeeRateEstimator<M: CostMetric> {
+    db: Connection,
+    /// We only look back `window_size` block measures.
+    window_size: u32,
+    /// The weight of a "full block" in abstract scalar cost units. See
+    /// `WeightedMedianFeeRateEstimator`.
+    full_block_weight: u64,
+    /// Use this cost metric in fee rate calculations.
+    metric: M,
+}
+
+impl<M: CostMetric> PercentileFeeRateEstimator<M> {
+    /// Open a fee rate estimator at the given db path. Creates if not existent.
+    pub fn open(p: &Path, metric: M, window_size: u32) -> Result<Self, SqliteError> {
+        let mut db = sqlite_open(
+            p,
+            rusqlite::OpenFlags::SQLITE_OPEN_CREATE | rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+            false,
+        )?;
+
+        // check if the db needs to be instantiated regardless of whether or not
+        //  it was newly created: the db itself may be shared with other fee estimators,
+        //  which would not have created the necessary table for this estimator.
+        let tx = tx_begin_immediate_sqlite(&mut db)?;
+        Self::instantiate_db(&tx)?;
+        tx.commit()?;
+
+        Ok(Self {
+            db,
+            metric,
+            window_size,
+            full_block_weight: PROPORTION_RESOLUTION,
+        })
+    }
+
+    /// Check if the SQL database was already created. Necessary to avoid races if
+    ///  different threads open an estimator at the same time.
+    fn db_already_instantiated(tx: &SqlTransaction) -> Result<bool, SqliteError> {
+        table_exists(tx, "percentile_fee_estimator")
+    }
+
+    fn instantiate_db(tx: &SqlTransaction) -> Result<(), SqliteError> {
+        if !Self::db_already_instantiated(tx)? {
+            tx.execute(CREATE_TABLE, rusqlite::NO_PARAMS)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_rate_estimate_from_sql(
+        conn: &Connection,
+        dimension: &str,
+        window_size: u32,
+    ) -> Result<FeeRateEstimate, EstimatorError> {
+        let sql = "SELECT high, middle, low FROM percentile_fee_estimator
+                   WHERE dimension = ? ORDER BY block_key DESC LIMIT ?";
+        let mut stmt = conn.prepare(sql).expect("SQLite failure");
+
+        let mut highs = Vec::with_capacity(window_size as usize);
+        let mut mids = Vec::with_capacity(window_size as usize);
+        let mut lows = Vec::with_capacity(window_size as usize);
+        let results = stmt
+            .query_and_then::<_, SqliteError, _, _>(
+                rusqlite::params![dimension, window_size],
+                |row| {
+                    let high: f64 = row.get("high")?;
+                    let middle: f64 = row.get("middle")?;
+                    let low: f64 = row.get("low")?;
+                    Ok((low, middle, high))
+                },
+            )
+            .expect("SQLite failure");
+
+        for result in results {
+            let (low, middle, high) = result.expect("SQLite failure");
+            highs.push(high);
+            mids.push(middle);
+            lows.push(low);
+        }
+
+        if highs.is_empty() || mids.is_empty() || lows.is_empty() {
+            return Err(EstimatorError::NoEstimateAvailable);
+        }
+
+        Ok(FeeRateEstimate {
+            high: median(highs),
+            middle: median(mids),
+            low: median(lows),
+        })
+    }
+
+    fn update_estimates(&mut self, new_measures: Vec<(&str, FeeRateEstimate)>) {
+        let tx = tx_begin_immediate_sqlite(&mut self.db).expect("SQLite failure");
+        let block_key: i64 = tx
+            .query_row(
+                "SELECT IFNULL(MAX(block_key), 0) + 1 FROM percentile_fee_estimator",
+                rusqlite::NO_PARAMS,
+                |row| row.get(0),
+            )
+            .expect("SQLite failure");
+        let insert_sql = "INSERT INTO percentile_fee_estimator
+                          (block_key, dimension, high, middle, low) VALUES (?, ?, ?, ?, ?)";
+        let deletion_sql = "DELETE FROM percentile_fee_estimator WHERE block_key <= ? - ?";
+        for (dimension, new_measure) in new_measures.iter() {
+            tx.execute(
+                insert_sql,
+                rusqlite::params![
+                    block_key,
+                    dimension,
+                    new_measure.high,
+                    new_measure.middle,
+                    new_measure.low,
+                ],
+            )
+            .expect("SQLite failure");
+        }
+        tx.execute(deletion_sql, rusqlite::params![block_key, self.window_size])
+            .expect("SQLite failure");
+
+        let estimate = Self::get_rate_estimate_from_sql(&tx, SCALAR_DIMENSION, self.window_size);
+        tx.commit().expect("SQLite failure");
+        if let Ok(next_estimate) = estimate {
+            debug!("Updating percentile fee rate estimate for new block";
+                   "new_estimate_high" => next_estimate.high,
+                   "new_estimate_middle" => next_estimate.middle,
+                   "new_estimate_low" => next_estimate.low);
+        }
+    }
+}
+
+impl<M: CostMetric> FeeEstimator for PercentileFeeRateEstimator<M> {
+    fn notify_block(
+        &mut self,
+        receipt: &StacksEpochReceipt,
+        block_limit: &ExecutionCost,
+    ) -> Result<(), EstimatorError> {
+        if let Some(scalar_estimate) =
+            fee_rate_estimate_from_block(&self.metric, receipt, block_limit, self.full_block_weight)
+        {
+            let mut measures = vec![(SCALAR_DIMENSION, scalar_estimate)];
+            let dimension_estimates = dimension_fee_rate_estimates_from_block(
+                &self.metric,
+                receipt,
+                block_limit,
+                self.full_block_weight,
+            );
+            measures.extend(
+                FEE_RATE_DIMENSIONS
+                    .iter()
+                    .cloned()
+                    .zip(dimension_estimates.to_vec().into_iter()),
+            );
+            self.update_estimates(measures);
+        }
+
+        Ok(())
+    }
+
+    fn get_rate_estimates(&self) -> Result<FeeRateEstimate, EstimatorError> {
+        Self::get_rate_estimate_from_sql(&self.db, SCALAR_DIMENSION, self.window_size)
+    }
+
+    fn get_dimension_rate_estimates(&self) -> Result<DimensionFeeRateEstimates, EstimatorError> {
+        let estimates = FEE_RATE_DIMENSIONS
+            .iter()
+            .map(|dimension| {
+                Self::get_rate_estimate_from_sql(&self.db, dimension, self.window_size)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DimensionFeeRateEstimates::from_vec(estimates))
+    }
+}
+
+/// `values` must be non-empty.
+fn median(mut values: Vec<f64>) -> f64 {
+    // For float values that do not compare easily, treat them as equals.
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let len = values.len();
+    if len % 2 == 1 {
+        values[len / 2]
+    } else {
+        (values[len / 2] + values[len / 2 - 1]) / 2f64
+    }
+}
+
+/// Computes the weighted percentile `FeeRateEstimate` of each dimension of the block limit, for
+/// the transactions in a block. Each transaction's fee rate (its fee per unit of scalar cost) is
+/// weighted by the share of the dimension it occupied, and each dimension's unused space is
+/// padded with the minimum fee rate.
+pub fn dimension_fee_rate_estimates_from_block(
+    metric: &dyn CostMetric,
+    receipt: &StacksEpochReceipt,
+    block_limit: &ExecutionCost,
+    full_block_weight: u64,
+) -> DimensionFeeRateEstimates {
+    let mut working_fee_rates: Vec<Vec<FeeRateAndWeight>> =
+        FEE_RATE_DIMENSIONS.iter().map(|_| vec![]).collect();
+
+    for tx_receipt in receipt.tx_receipts.iter() {
+        let tx = match tx_receipt.transaction {
+            TransactionOrigin::Stacks(ref tx) => tx,
+            TransactionOrigin::Burn(_) => continue,
+        };
+        let tx_len = tx.tx_len();
+        let (execution_cost, scalar_cost) = match tx.payload {
+            // TokenTransfers *only* contribute tx_len.
+            TransactionPayload::TokenTransfer(..) => {
+                (ExecutionCost::zero(), metric.from_len(tx_len))
+            }
+            // Coinbase txs are "free", so they don't factor into the fee market.
+            TransactionPayload::Coinbase(_) => continue,
+            TransactionPayload::PoisonMicroblock(..)
+            | TransactionPayload::ContractCall(_)
+            | TransactionPayload::SmartContract(_) => (
+                tx_receipt.execution_cost.clone(),
+                metric.from_cost_and_len(&tx_receipt.execution_cost, block_limit, tx_len),
+            ),
+        };
+
+        let fee_rate = tx.get_tx_fee() as f64 / cmp::max(scalar_cost, 1) as f64;
+        if fee_rate.is_infinite() {
+            warn!("fee_rate is infinite for {:?}", tx_receipt);
+            continue;
+        }
+        let fee_rate = fee_rate.max(MINIMUM_TX_FEE_RATE);
+
+        let proportions = dimension_proportions(metric, &execution_cost, block_limit, tx_len);
+        for (rates, proportion) in working_fee_rates.iter_mut().zip(proportions.iter()) {
+            if *proportion > 0 {
+                rates.push(FeeRateAndWeight {
+                    fee_rate,
+                    weight: *proportion,
+                });
+            }
+        }
+    }
+
+    let estimates = working_fee_rates
+        .into_iter()
+        .map(|mut rates| {
+            // Padding makes `rates` non-empty even if no transaction used this dimension.
+            maybe_add_minimum_fee_rate(&mut rates, full_block_weight);
+            rates.sort_by(|a, b| {
+                a.fee_rate
+                    .partial_cmp(&b.fee_rate)
+                    .unwrap_or(Ordering::Equal)
+            });
+            fee_rate_estimate_from_sorted_weighted_fees(&rates)
+        })
+        .collect();
+
+    DimensionFeeRateEstimates::from_vec(estimates)
+}
@@ -1,6 +1,6 @@
 use vm::costs::ExecutionCost;
 
-use super::FeeRateEstimate;
+use super::{DimensionFeeRateEstimates, FeeRateEstimate};
 use super::{EstimatorError, FeeEstimator};
 use chainstate::stacks::db::StacksEpochReceipt;
 use rand::distributions::{Distribution, Uniform};
@@ -89,4 +89,9 @@ impl<T: FeeEstimator> FeeEstimator for FeeRateFuzzer<T> {
         let underlying_estimate = self.underlying.get_rate_estimates()?;
         Ok(self.fuzz_estimate(underlying_estimate))
     }
+
+    /// Just passes the dimension estimates straight from `underlying`.
+    fn get_dimension_rate_estimates(&self) -> Result<DimensionFeeRateEstimates, EstimatorError> {
+        self.underlying.get_dimension_rate_estimates()
+    }
 }
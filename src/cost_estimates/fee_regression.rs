@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use rusqlite::Transaction as SqlTransaction;
+use rusqlite::{Connection, Error as SqliteError};
+
+use util::db::sqlite_open;
+use util::db::tx_begin_immediate_sqlite;
+
+use vm::costs::ExecutionCost;
+
+use chainstate::stacks::db::StacksEpochReceipt;
+
+use crate::util::db::table_exists;
+
+use super::fee_medians::{fee_rate_estimate_from_block, MINIMUM_TX_FEE_RATE};
+use super::metrics::CostMetric;
+use super::metrics::PROPORTION_RESOLUTION;
+use super::FeeRateEstimate;
+use super::{EstimatorError, FeeEstimator};
+
+const CREATE_TABLE: &'static str = "
+CREATE TABLE regression_fee_estimator (
+    measure_key INTEGER PRIMARY KEY AUTOINCREMENT,
+    high NUMBER NOT NULL,
+    middle NUMBER NOT NULL,
+    low NUMBER NOT NULL
+)";
+
+/// FeeRateEstimator which follows the trend of fee rates over recent blocks:
+///
+/// 1) Each block is measured the way `WeightedMedianFeeRateEstimator` measures it: the weighted
+///    5th, 50th and 95th percentile fee rates of the block, padded with the minimum fee rate.
+/// 2) The last `window_size` measures are kept, and a least-squares line is fit through each of
+///    the low, middle and high measures against block order.
+/// 3) The estimate is each line's value at the *next* block. While fee rates rise block over
+///    block (e.g., under a burst of load) the estimate leads the measures rather than lagging
+///    behind them the way averaging estimators do.
+///
+/// Estimates never drop below the minimum fee rate, and low <= middle <= high always holds.
+pub struct RegressionFeeRateEstimator<M: CostMetric> {
+    db: Connection,
+    /// We only fit the last `window_size` block measures.
+    window_size: u32,
+    /// The weight of a "full block" in abstract scalar cost units. See
+    /// `WeightedMedianFeeRateEstimator`.
+    full_block_weight: u64,
+    /// Use this cost metric in fee rate calculations.
+    metric: M,
+}
+
+impl<M: CostMetric> RegressionFeeRateEstimator<M> {
+    /// Open a fee rate estimator at the given db path. Creates if not existent.
+    pub fn open(p: &Path, metric: M, window_size: u32) -> Result<Self, SqliteError> {
+        let mut db = sqlite_open(
+            p,
+            rusqlite::OpenFlags::SQLITE_OPEN_CREATE | rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+            false,
+        )?;
+
+        // check if the db needs to be instantiated regardless of whether or not
+        //  it was newly created: the db itself may be shared with other fee estimators,
+        //  which would not have created the necessary table for this estimator.
+        let tx = tx_begin_immediate_sqlite(&mut db)?;
+        Self::instantiate_db(&tx)?;
+        tx.commit()?;
+
+        Ok(Self {
+            db,
+            metric,
+            window_size,
+            full_block_weight: PROPORTION_RESOLUTION,
+        })
+    }
+
+    /// Check if the SQL database was already created. Necessary to avoid races if
+    ///  different threads open an estimator at the same time.
+    fn db_already_instantiated(tx: &SqlTransaction) -> Result<bool, SqliteError> {
+        table_exists(tx, "regression_fee_estimator")
+    }
+
+    fn instantiate_db(tx: &SqlTransaction) -> Result<(), SqliteError> {
+        if !Self::db_already_instantiated(tx)? {
+            tx.execute(CREATE_TABLE, rusqlite::NO_PARAMS)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_rate_estimates_from_sql(
+        conn: &Connection,
+        window_size: u32,
+    ) -> Result<FeeRateEstimate, EstimatorError> {
+        let sql = "SELECT high, middle, low FROM regression_fee_estimator ORDER BY measure_key DESC LIMIT ?";
+        let mut stmt = conn.prepare(sql).expect("SQLite failure");
+
+        let mut highs = Vec::with_capacity(window_size as usize);
+        let mut mids = Vec::with_capacity(window_size as usize);
+        let mut lows = Vec::with_capacity(window_size as usize);
+        let results = stmt
+            .query_and_then::<_, SqliteError, _, _>(&[window_size], |row| {
+                let high: f64 = row.get("high")?;
+                let middle: f64 = row.get("middle")?;
+                let low: f64 = row.get("low")?;
+                Ok((low, middle, high))
+            })
+            .expect("SQLite failure");
+
+        for result in results {
+            let (low, middle, high) = result.expect("SQLite failure");
+            highs.push(high);
+            mids.push(middle);
+            lows.push(low);
+        }
+
+        if highs.is_empty() || mids.is_empty() || lows.is_empty() {
+            return Err(EstimatorError::NoEstimateAvailable);
+        }
+
+        // measures were read newest first
+        highs.reverse();
+        mids.reverse();
+        lows.reverse();
+
+        let mut estimates = vec![
+            predict_next_fee_rate(&lows),
+            predict_next_fee_rate(&mids),
+            predict_next_fee_rate(&highs),
+        ];
+        estimates.sort_by(|a, b| {
+            a.partial_cmp(b)
+                .expect("BUG: Fee rates should be orderable: predictions are finite")
+        });
+
+        Ok(FeeRateEstimate {
+            high: estimates[2],
+            middle: estimates[1],
+            low: estimates[0],
+        })
+    }
+
+    fn update_estimate(&mut self, new_measure: FeeRateEstimate) {
+        let tx = tx_begin_immediate_sqlite(&mut self.db).expect("SQLite failure");
+        let insert_sql = "INSERT INTO regression_fee_estimator
+                          (high, middle, low) VALUES (?, ?, ?)";
+        let deletion_sql = "DELETE FROM regression_fee_estimator
+                            WHERE measure_key <= (
+                               SELECT MAX(measure_key) - ?
+                               FROM regression_fee_estimator )";
+        tx.execute(
+            insert_sql,
+            rusqlite::params![new_measure.high, new_measure.middle, new_measure.low,],
+        )
+        .expect("SQLite failure");
+        tx.execute(deletion_sql, rusqlite::params![self.window_size])
+            .expect("SQLite failure");
+
+        let estimate = Self::get_rate_estimates_from_sql(&tx, self.window_size);
+        tx.commit().expect("SQLite failure");
+        if let Ok(next_estimate) = estimate {
+            debug!("Updating fee rate estimate for new block";
+                   "new_measure_high" => new_measure.high,
+                   "new_measure_middle" => new_measure.middle,
+                   "new_measure_low" => new_measure.low,
+                   "new_estimate_high" => next_estimate.high,
+                   "new_estimate_middle" => next_estimate.middle,
+                   "new_estimate_low" => next_estimate.low);
+        }
+    }
+}
+
+impl<M: CostMetric> FeeEstimator for RegressionFeeRateEstimator<M> {
+    fn notify_block(
+        &mut self,
+        receipt: &StacksEpochReceipt,
+        block_limit: &ExecutionCost,
+    ) -> Result<(), EstimatorError> {
+        if let Some(block_estimate) =
+            fee_rate_estimate_from_block(&self.metric, receipt, block_limit, self.full_block_weight)
+        {
+            self.update_estimate(block_estimate);
+        }
+
+        Ok(())
+    }
+
+    fn get_rate_estimates(&self) -> Result<FeeRateEstimate, EstimatorError> {
+        Self::get_rate_estimates_from_sql(&self.db, self.window_size)
+    }
+}
+
+/// Fits a least-squares line through `measures`, taken to be at block offsets 0, 1, 2, ..., and
+/// returns its value at the block after the last measure, saturating at the minimum fee rate.
+///
+/// `measures` must be non-empty.
+pub fn predict_next_fee_rate(measures: &[f64]) -> f64 {
+    assert!(!measures.is_empty());
+
+    let n = measures.len() as f64;
+    let mean_x = (n - 1f64) / 2f64;
+    let mean_y = measures.iter().sum::<f64>() / n;
+
+    let mut covariance = 0f64;
+    let mut variance = 0f64;
+    for (x, y) in measures.iter().enumerate() {
+        let dx = x as f64 - mean_x;
+        covariance += dx * (y - mean_y);
+        variance += dx * dx;
+    }
+
+    // a single measure has no trend
+    let slope = if variance > 0f64 {
+        covariance / variance
+    } else {
+        0f64
+    };
+    let prediction = mean_y + slope * (n - mean_x);
+
+    if prediction.is_finite() && prediction >= MINIMUM_TX_FEE_RATE {
+        prediction
+    } else if prediction.is_infinite() && prediction.is_sign_positive() {
+        f64::MAX
+    } else {
+        MINIMUM_TX_FEE_RATE
+    }
+}
@@ -14,7 +14,10 @@ use burnchains::Txid;
 use chainstate::stacks::db::StacksEpochReceipt;
 
 pub mod fee_medians;
+pub mod fee_observations;
+pub mod fee_percentiles;
 pub mod fee_rate_fuzzer;
+pub mod fee_regression;
 pub mod fee_scalar;
 pub mod metrics;
 pub mod pessimistic;
@@ -25,7 +28,7 @@ pub mod tests;
 use crate::chainstate::stacks::StacksTransaction;
 use core::StacksEpochId;
 
-use self::metrics::CostMetric;
+use self::metrics::{CostMetric, PROPORTION_RESOLUTION};
 pub use self::pessimistic::PessimisticEstimator;
 
 /// This trait is for implementation of *fee rate* estimation: estimators should
@@ -49,6 +52,11 @@ pub trait FeeEstimator {
     ) -> Result<(), EstimatorError>;
     /// Get the current estimates for fee rate
     fn get_rate_estimates(&self) -> Result<FeeRateEstimate, EstimatorError>;
+    /// Get the current estimates for the fee rate paid for each dimension of the block limit.
+    ///  Estimators that only track a scalar fee rate need not implement this.
+    fn get_dimension_rate_estimates(&self) -> Result<DimensionFeeRateEstimates, EstimatorError> {
+        Err(EstimatorError::NoEstimateAvailable)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
@@ -78,6 +86,100 @@ impl FeeRateEstimate {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+/// This struct is returned from fee rate estimators that track the fee rates paid for each
+/// dimension of the block limit separately. Each rate is in microstx per unit of the block
+/// limit occupied in that dimension, where the whole dimension is `PROPORTION_RESOLUTION` units.
+pub struct DimensionFeeRateEstimates {
+    pub runtime: FeeRateEstimate,
+    pub read_count: FeeRateEstimate,
+    pub read_length: FeeRateEstimate,
+    pub write_count: FeeRateEstimate,
+    pub write_length: FeeRateEstimate,
+    pub tx_len: FeeRateEstimate,
+}
+
+/// The dimensions of the block limit that `DimensionFeeRateEstimates` covers, in the order used
+/// by `DimensionFeeRateEstimates::to_vec()` and `dimension_proportions()`.
+pub const FEE_RATE_DIMENSIONS: [&str; 6] = [
+    "runtime",
+    "read_count",
+    "read_length",
+    "write_count",
+    "write_length",
+    "tx_len",
+];
+
+impl DimensionFeeRateEstimates {
+    /// `estimates` must be ordered as `FEE_RATE_DIMENSIONS`.
+    pub fn from_vec(estimates: Vec<FeeRateEstimate>) -> DimensionFeeRateEstimates {
+        assert_eq!(estimates.len(), FEE_RATE_DIMENSIONS.len());
+        let mut estimates = estimates.into_iter();
+        let mut next = || estimates.next().expect("BUG: checked length above");
+        DimensionFeeRateEstimates {
+            runtime: next(),
+            read_count: next(),
+            read_length: next(),
+            write_count: next(),
+            write_length: next(),
+            tx_len: next(),
+        }
+    }
+
+    pub fn to_vec(self) -> Vec<FeeRateEstimate> {
+        vec![
+            self.runtime,
+            self.read_count,
+            self.read_length,
+            self.write_count,
+            self.write_length,
+            self.tx_len,
+        ]
+    }
+
+    /// The total fees that a transaction occupying `proportions` of the block limit (as
+    ///  returned by `dimension_proportions()`) should pay: the sum, over each dimension, of the
+    ///  share occupied times the fee rate estimated for that dimension.
+    pub fn estimate_fees(&self, proportions: &[u64; 6]) -> FeeRateEstimate {
+        self.clone()
+            .to_vec()
+            .into_iter()
+            .zip(proportions.iter())
+            .fold(
+                FeeRateEstimate {
+                    high: 0f64,
+                    middle: 0f64,
+                    low: 0f64,
+                },
+                |total, (rates, proportion)| total + rates * (*proportion as f64),
+            )
+    }
+}
+
+/// The share of each dimension of the block limit that a transaction with the given cost and
+///  length occupies, in units where a whole dimension is `PROPORTION_RESOLUTION`. The length
+///  share is the one `metric` assigns to `tx_len`. Ordered as `FEE_RATE_DIMENSIONS`.
+pub fn dimension_proportions<CM: CostMetric + ?Sized>(
+    metric: &CM,
+    cost: &ExecutionCost,
+    block_limit: &ExecutionCost,
+    tx_len: u64,
+) -> [u64; 6] {
+    //  use MAX(1, block_limit) to guard against divide by zero
+    //  use MIN(1, self/block_limit) to guard against self > block_limit
+    let proportion = |used: u64, limit: u64| {
+        (PROPORTION_RESOLUTION as f64 * 1_f64.min(used as f64 / 1_f64.max(limit as f64))) as u64
+    };
+    [
+        proportion(cost.runtime, block_limit.runtime),
+        proportion(cost.read_count, block_limit.read_count),
+        proportion(cost.read_length, block_limit.read_length),
+        proportion(cost.write_count, block_limit.write_count),
+        proportion(cost.write_length, block_limit.write_length),
+        metric.from_len(tx_len),
+    ]
+}
+
 impl Mul<f64> for FeeRateEstimate {
     type Output = FeeRateEstimate;
 
@@ -230,6 +230,7 @@ impl PessimisticEstimator {
                     StacksEpochId::Epoch10 => "",
                     StacksEpochId::Epoch20 => "",
                     StacksEpochId::Epoch2_05 => ":2.05",
+                    StacksEpochId::Epoch21 => ":2.1",
                 };
                 format!(
                     "cc{}:{}:{}.{}",
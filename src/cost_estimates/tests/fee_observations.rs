@@ -0,0 +1,252 @@
+use std::env;
+use std::fs;
+
+use rand::Rng;
+
+use cost_estimates::fee_observations::{FeeObservation, FeeObservationRecorder};
+use cost_estimates::{EstimatorError, FeeEstimator, FeeRateEstimate};
+use vm::costs::ExecutionCost;
+
+use chainstate::stacks::db::StacksEpochReceipt;
+use chainstate::stacks::events::StacksTransactionReceipt;
+use core::mempool::MemPoolDB;
+use util::db::sqlite_open;
+use util::hash::{to_hex, Hash160};
+
+use crate::chainstate::stacks::{
+    CoinbasePayload, StacksTransaction, TokenTransferMemo, TransactionAuth,
+    TransactionContractCall, TransactionPayload, TransactionSpendingCondition, TransactionVersion,
+};
+use crate::cost_estimates::metrics::UnitMetric;
+use crate::types::chainstate::StacksAddress;
+use crate::vm::types::{PrincipalData, StandardPrincipalData};
+use crate::vm::Value;
+use cost_estimates::tests::common::*;
+
+/// A FeeEstimator which counts the blocks it is notified of
+struct CountingEstimator {
+    blocks: u64,
+}
+
+impl FeeEstimator for CountingEstimator {
+    fn notify_block(
+        &mut self,
+        _receipt: &StacksEpochReceipt,
+        _block_limit: &ExecutionCost,
+    ) -> Result<(), EstimatorError> {
+        self.blocks += 1;
+        Ok(())
+    }
+
+    fn get_rate_estimates(&self) -> Result<FeeRateEstimate, EstimatorError> {
+        Ok(FeeRateEstimate {
+            high: self.blocks as f64,
+            middle: self.blocks as f64,
+            low: self.blocks as f64,
+        })
+    }
+}
+
+fn temp_db_path(prefix: &str) -> std::path::PathBuf {
+    let mut path = env::temp_dir();
+    let random_bytes = rand::thread_rng().gen::<[u8; 32]>();
+    path.push(&format!(
+        "{}_{}.sqlite",
+        prefix,
+        &to_hex(&random_bytes)[0..8]
+    ));
+    path
+}
+
+fn make_dummy_coinbase_tx() -> StacksTransaction {
+    StacksTransaction::new(
+        TransactionVersion::Mainnet,
+        TransactionAuth::Standard(TransactionSpendingCondition::new_initial_sighash()),
+        TransactionPayload::Coinbase(CoinbasePayload([0; 32])),
+    )
+}
+
+fn make_dummy_transfer_tx(fee: u64) -> StacksTransactionReceipt {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Mainnet,
+        TransactionAuth::Standard(TransactionSpendingCondition::new_initial_sighash()),
+        TransactionPayload::TokenTransfer(
+            PrincipalData::Standard(StandardPrincipalData(0, [0; 20])),
+            1,
+            TokenTransferMemo([0; 34]),
+        ),
+    );
+    tx.set_tx_fee(fee);
+    StacksTransactionReceipt::from_stx_transfer(
+        tx,
+        vec![],
+        Value::okay(Value::Bool(true)).unwrap(),
+        ExecutionCost::zero(),
+    )
+}
+
+fn make_dummy_cc_tx(fee: u64, execution_cost: &ExecutionCost) -> StacksTransactionReceipt {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Mainnet,
+        TransactionAuth::Standard(TransactionSpendingCondition::new_initial_sighash()),
+        TransactionPayload::ContractCall(TransactionContractCall {
+            address: StacksAddress::new(0, Hash160([0; 20])),
+            contract_name: "cc-dummy".into(),
+            function_name: "func-name".into(),
+            function_args: vec![],
+        }),
+    );
+    tx.set_tx_fee(fee);
+    StacksTransactionReceipt::from_contract_call(
+        tx,
+        vec![],
+        Value::okay(Value::Bool(true)).unwrap(),
+        0,
+        execution_cost.clone(),
+    )
+}
+
+const block_limit: ExecutionCost = ExecutionCost {
+    write_length: 100,
+    write_count: 100,
+    read_length: 100,
+    read_count: 100,
+    runtime: 100,
+};
+
+#[test]
+fn test_record_and_export_observations() {
+    let observations_path = temp_db_path("fee_observations");
+    let mut recorder = FeeObservationRecorder::open(
+        &observations_path,
+        Box::new(CountingEstimator { blocks: 0 }),
+        Box::new(UnitMetric),
+        None,
+    )
+    .expect("Test failure: could not open fee observation DB");
+
+    let cc_cost = ExecutionCost {
+        write_length: 1,
+        write_count: 2,
+        read_length: 3,
+        read_count: 4,
+        runtime: 5,
+    };
+    let mut block_receipt = make_block_receipt(vec![
+        StacksTransactionReceipt::from_coinbase(make_dummy_coinbase_tx()),
+        make_dummy_transfer_tx(180),
+        make_dummy_cc_tx(300, &cc_cost),
+    ]);
+    block_receipt.header.block_height = 10;
+    block_receipt.header.burn_header_timestamp = 1000;
+    recorder
+        .notify_block(&block_receipt, &block_limit)
+        .expect("Should be able to process block receipt");
+
+    block_receipt.header.block_height = 11;
+    recorder
+        .notify_block(&block_receipt, &block_limit)
+        .expect("Should be able to process block receipt");
+
+    // the underlying estimator sees every block
+    assert_eq!(recorder.get_rate_estimates().unwrap().middle, 2f64);
+
+    // coinbases are not observed
+    let observations = FeeObservationRecorder::get_observations(&observations_path, 0).unwrap();
+    assert_eq!(observations.len(), 4);
+
+    let transfer = &observations[0];
+    assert_eq!(transfer.block_height, 10);
+    assert_eq!(transfer.payload_type, "token_transfer");
+    assert_eq!(transfer.fee, 180);
+    // the unit metric scores every transaction as 1
+    assert_eq!(transfer.scalar_cost, 1);
+    assert_eq!(transfer.fee_rate(), 180f64);
+    assert_eq!(transfer.accept_time, None);
+    assert_eq!(transfer.confirm_time, 1000);
+    assert_eq!(transfer.confirmation_delay(), None);
+
+    let contract_call = &observations[1];
+    assert_eq!(contract_call.payload_type, "contract_call");
+    assert_eq!(contract_call.execution_cost, cc_cost);
+
+    let observations = FeeObservationRecorder::get_observations(&observations_path, 11).unwrap();
+    assert_eq!(observations.len(), 2);
+    assert!(observations.iter().all(|o| o.block_height == 11));
+
+    let mut csv = vec![];
+    FeeObservationRecorder::write_csv(&observations, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], FeeObservation::CSV_HEADER);
+    assert_eq!(
+        lines[0].split(',').count(),
+        lines[1].split(',').count(),
+        "Every row should have a column per header"
+    );
+    assert!(lines[1].ends_with(",1000,"));
+
+    let json = observations[1].to_json();
+    assert_eq!(json["payload_type"], "contract_call");
+    assert_eq!(json["execution_cost"]["runtime"], 5);
+    assert_eq!(json["fee_rate"], 300f64);
+    assert!(json["confirmation_delay"].is_null());
+}
+
+#[test]
+fn test_confirmation_delay_from_mempool() {
+    let chainstate_path = env::temp_dir().join(format!(
+        "fee_observations_mempool_{}",
+        &to_hex(&rand::thread_rng().gen::<[u8; 32]>())[0..8]
+    ));
+    fs::create_dir_all(&chainstate_path).unwrap();
+    let mempool_path = MemPoolDB::db_path(chainstate_path.to_str().unwrap()).unwrap();
+
+    let transfer = make_dummy_transfer_tx(180);
+    let txid = transfer.transaction.txid();
+    {
+        // just enough of the mempool schema to look up accept times
+        let conn = sqlite_open(
+            &mempool_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_CREATE | rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+            false,
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE mempool (txid TEXT NOT NULL, accept_time INTEGER NOT NULL)",
+            rusqlite::NO_PARAMS,
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO mempool (txid, accept_time) VALUES (?, ?)",
+            rusqlite::params![txid, 940],
+        )
+        .unwrap();
+    }
+
+    let observations_path = temp_db_path("fee_observations");
+    let mut recorder = FeeObservationRecorder::open(
+        &observations_path,
+        Box::new(CountingEstimator { blocks: 0 }),
+        Box::new(UnitMetric),
+        Some(mempool_path.into()),
+    )
+    .unwrap();
+
+    let mut block_receipt = make_block_receipt(vec![
+        transfer,
+        make_dummy_cc_tx(300, &ExecutionCost::zero()),
+    ]);
+    block_receipt.header.burn_header_timestamp = 1000;
+    recorder
+        .notify_block(&block_receipt, &block_limit)
+        .expect("Should be able to process block receipt");
+
+    let observations = FeeObservationRecorder::get_observations(&observations_path, 0).unwrap();
+    assert_eq!(observations.len(), 2);
+    assert_eq!(observations[0].accept_time, Some(940));
+    assert_eq!(observations[0].confirmation_delay(), Some(60));
+    // the mempool never saw the contract call
+    assert_eq!(observations[1].confirmation_delay(), None);
+}
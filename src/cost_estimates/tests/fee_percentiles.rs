@@ -0,0 +1,200 @@
+use std::env;
+
+use rand::Rng;
+
+use cost_estimates::metrics::CostMetric;
+use cost_estimates::{EstimatorError, FeeEstimator};
+use vm::costs::ExecutionCost;
+
+use chainstate::stacks::events::StacksTransactionReceipt;
+use util::hash::{to_hex, Hash160};
+
+use crate::chainstate::stacks::{
+    StacksTransaction, TransactionAuth, TransactionContractCall, TransactionPayload,
+    TransactionSpendingCondition, TransactionVersion,
+};
+use crate::cost_estimates::dimension_proportions;
+use crate::cost_estimates::fee_percentiles::PercentileFeeRateEstimator;
+use crate::cost_estimates::metrics::ProportionalDotProduct;
+use crate::types::chainstate::StacksAddress;
+use crate::vm::Value;
+use cost_estimates::tests::common::*;
+
+/// Returns true iff `b` is within `0.1%` of `a`.
+fn is_close_f64(a: f64, b: f64) -> bool {
+    let error = (a - b).abs() / a.abs();
+    error < 0.001
+}
+
+fn instantiate_test_db<CM: CostMetric>(m: CM) -> PercentileFeeRateEstimator<CM> {
+    let mut path = env::temp_dir();
+    let random_bytes = rand::thread_rng().gen::<[u8; 32]>();
+    path.push(&format!("fee_db_{}.sqlite", &to_hex(&random_bytes)[0..8]));
+
+    let window_size = 5;
+    PercentileFeeRateEstimator::open(&path, m, window_size)
+        .expect("Test failure: could not open fee rate DB")
+}
+
+fn make_dummy_cc_tx(fee: u64, execution_cost: &ExecutionCost) -> StacksTransactionReceipt {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Mainnet,
+        TransactionAuth::Standard(TransactionSpendingCondition::new_initial_sighash()),
+        TransactionPayload::ContractCall(TransactionContractCall {
+            address: StacksAddress::new(0, Hash160([0; 20])),
+            contract_name: "cc-dummy".into(),
+            function_name: "func-name".into(),
+            function_args: vec![],
+        }),
+    );
+    tx.set_tx_fee(fee);
+    StacksTransactionReceipt::from_contract_call(
+        tx,
+        vec![],
+        Value::okay(Value::Bool(true)).unwrap(),
+        0,
+        execution_cost.clone(),
+    )
+}
+
+const block_limit: ExecutionCost = ExecutionCost {
+    write_length: 100,
+    write_count: 100,
+    read_length: 100,
+    read_count: 100,
+    runtime: 100,
+};
+
+const half_operation_cost: ExecutionCost = ExecutionCost {
+    write_length: 0,
+    write_count: 0,
+    read_length: 0,
+    read_count: 0,
+    runtime: 50,
+};
+
+// The scalar cost of `make_dummy_cc_tx(_, &half_operation_cost)`.
+const half_operation_cost_basis: u64 = 5164;
+
+#[test]
+fn test_empty_fee_estimator() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let estimator = instantiate_test_db(metric);
+    assert_eq!(
+        estimator
+            .get_rate_estimates()
+            .expect_err("Empty rate estimator should error."),
+        EstimatorError::NoEstimateAvailable
+    );
+    assert_eq!(
+        estimator
+            .get_dimension_rate_estimates()
+            .expect_err("Empty rate estimator should error."),
+        EstimatorError::NoEstimateAvailable
+    );
+}
+
+/// Blocks whose runtime is filled at a fee rate of 10 should measure that rate for runtime, but
+/// only the minimum fee rate for the dimensions they leave empty.
+#[test]
+fn test_congested_dimension_measures_higher() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let mut estimator = instantiate_test_db(metric);
+
+    for _ in 0..5 {
+        let block_receipt = make_block_receipt(vec![
+            make_dummy_cc_tx(10 * half_operation_cost_basis, &half_operation_cost),
+            make_dummy_cc_tx(10 * half_operation_cost_basis, &half_operation_cost),
+        ]);
+
+        estimator
+            .notify_block(&block_receipt, &block_limit)
+            .expect("Should be able to process block receipt");
+    }
+
+    let estimate = estimator
+        .get_rate_estimates()
+        .expect("Should be able to create estimate now");
+    assert!(is_close_f64(estimate.high, 10f64));
+    assert!(is_close_f64(estimate.middle, 10f64));
+
+    let dimension_estimates = estimator
+        .get_dimension_rate_estimates()
+        .expect("Should be able to create dimension estimates now");
+    assert!(is_close_f64(dimension_estimates.runtime.high, 10f64));
+    assert!(is_close_f64(dimension_estimates.runtime.middle, 10f64));
+    assert!(is_close_f64(dimension_estimates.runtime.low, 10f64));
+
+    // nothing was read or written
+    assert_eq!(dimension_estimates.read_count.high, 1f64);
+    assert_eq!(dimension_estimates.read_length.middle, 1f64);
+    assert_eq!(dimension_estimates.write_count.low, 1f64);
+    assert_eq!(dimension_estimates.write_length.high, 1f64);
+
+    // the transactions took up a little of the block length
+    assert_eq!(dimension_estimates.tx_len.low, 1f64);
+    assert!(dimension_estimates.tx_len.middle < 2f64);
+}
+
+/// Two transactions with the same scalar cost are quoted different fees if one is heavy in a
+/// congested dimension and the other is heavy in an empty one.
+#[test]
+fn test_dimension_fees_price_congestion() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let mut estimator = instantiate_test_db(ProportionalDotProduct::new(10_000));
+
+    let block_receipt = make_block_receipt(vec![
+        make_dummy_cc_tx(10 * half_operation_cost_basis, &half_operation_cost),
+        make_dummy_cc_tx(10 * half_operation_cost_basis, &half_operation_cost),
+    ]);
+    estimator
+        .notify_block(&block_receipt, &block_limit)
+        .expect("Should be able to process block receipt");
+
+    let dimension_estimates = estimator
+        .get_dimension_rate_estimates()
+        .expect("Should be able to create dimension estimates now");
+
+    let runtime_heavy = ExecutionCost {
+        runtime: 20,
+        ..ExecutionCost::zero()
+    };
+    let reads_heavy = ExecutionCost {
+        read_count: 20,
+        ..ExecutionCost::zero()
+    };
+    assert_eq!(
+        metric.from_cost_and_len(&runtime_heavy, &block_limit, 100),
+        metric.from_cost_and_len(&reads_heavy, &block_limit, 100)
+    );
+
+    let runtime_proportions = dimension_proportions(&metric, &runtime_heavy, &block_limit, 100);
+    let reads_proportions = dimension_proportions(&metric, &reads_heavy, &block_limit, 100);
+    assert_eq!(runtime_proportions, [2000, 0, 0, 0, 0, 100]);
+    assert_eq!(reads_proportions, [0, 2000, 0, 0, 0, 100]);
+
+    let runtime_fees = dimension_estimates.estimate_fees(&runtime_proportions);
+    let reads_fees = dimension_estimates.estimate_fees(&reads_proportions);
+    let len_fee = 100f64 * dimension_estimates.tx_len.middle;
+    assert!(is_close_f64(runtime_fees.middle, 2000f64 * 10f64 + len_fee));
+    assert!(is_close_f64(reads_fees.middle, 2000f64 * 1f64 + len_fee));
+    assert!(runtime_fees.low > reads_fees.low);
+    assert!(runtime_fees.high > reads_fees.high);
+}
+
+/// Costs beyond the block limit occupy at most the whole dimension.
+#[test]
+fn test_dimension_proportions_saturate() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let cost = ExecutionCost {
+        write_length: 500,
+        write_count: 50,
+        read_length: 25,
+        read_count: 0,
+        runtime: 100,
+    };
+    assert_eq!(
+        dimension_proportions(&metric, &cost, &block_limit, 20_000),
+        [10_000, 0, 2500, 5000, 10_000, 10_000]
+    );
+}
@@ -0,0 +1,162 @@
+use std::env;
+
+use rand::Rng;
+
+use cost_estimates::metrics::CostMetric;
+use cost_estimates::{EstimatorError, FeeEstimator};
+use vm::costs::ExecutionCost;
+
+use chainstate::stacks::events::StacksTransactionReceipt;
+use util::hash::{to_hex, Hash160};
+
+use crate::chainstate::stacks::{
+    StacksTransaction, TransactionAuth, TransactionContractCall, TransactionPayload,
+    TransactionSpendingCondition, TransactionVersion,
+};
+use crate::cost_estimates::fee_regression::{predict_next_fee_rate, RegressionFeeRateEstimator};
+use crate::cost_estimates::metrics::ProportionalDotProduct;
+use crate::cost_estimates::FeeRateEstimate;
+use crate::types::chainstate::StacksAddress;
+use crate::vm::Value;
+use cost_estimates::tests::common::*;
+
+/// Returns true iff `b` is within `0.1%` of `a`.
+fn is_close_f64(a: f64, b: f64) -> bool {
+    let error = (a - b).abs() / a.abs();
+    error < 0.001
+}
+
+fn instantiate_test_db<CM: CostMetric>(m: CM) -> RegressionFeeRateEstimator<CM> {
+    let mut path = env::temp_dir();
+    let random_bytes = rand::thread_rng().gen::<[u8; 32]>();
+    path.push(&format!("fee_db_{}.sqlite", &to_hex(&random_bytes)[0..8]));
+
+    let window_size = 5;
+    RegressionFeeRateEstimator::open(&path, m, window_size)
+        .expect("Test failure: could not open fee rate DB")
+}
+
+fn make_dummy_cc_tx(fee: u64, execution_cost: &ExecutionCost) -> StacksTransactionReceipt {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Mainnet,
+        TransactionAuth::Standard(TransactionSpendingCondition::new_initial_sighash()),
+        TransactionPayload::ContractCall(TransactionContractCall {
+            address: StacksAddress::new(0, Hash160([0; 20])),
+            contract_name: "cc-dummy".into(),
+            function_name: "func-name".into(),
+            function_args: vec![],
+        }),
+    );
+    tx.set_tx_fee(fee);
+    StacksTransactionReceipt::from_contract_call(
+        tx,
+        vec![],
+        Value::okay(Value::Bool(true)).unwrap(),
+        0,
+        execution_cost.clone(),
+    )
+}
+
+const block_limit: ExecutionCost = ExecutionCost {
+    write_length: 100,
+    write_count: 100,
+    read_length: 100,
+    read_count: 100,
+    runtime: 100,
+};
+
+const half_operation_cost: ExecutionCost = ExecutionCost {
+    write_length: 0,
+    write_count: 0,
+    read_length: 0,
+    read_count: 0,
+    runtime: 50,
+};
+
+// The scalar cost of `make_dummy_cc_tx(_, &half_operation_cost)`.
+const half_operation_cost_basis: u64 = 5164;
+
+#[test]
+fn test_predict_next_fee_rate() {
+    // no trend: a single measure, or equal measures
+    assert_eq!(predict_next_fee_rate(&[10f64]), 10f64);
+    assert!(is_close_f64(
+        predict_next_fee_rate(&[7f64, 7f64, 7f64]),
+        7f64
+    ));
+    // a rising trend is extrapolated one block ahead
+    assert!(is_close_f64(
+        predict_next_fee_rate(&[10f64, 20f64, 30f64, 40f64]),
+        50f64
+    ));
+    // a falling trend saturates at the minimum fee rate
+    assert_eq!(predict_next_fee_rate(&[30f64, 20f64, 10f64]), 1f64);
+}
+
+#[test]
+fn test_empty_fee_estimator() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let estimator = instantiate_test_db(metric);
+    assert_eq!(
+        estimator
+            .get_rate_estimates()
+            .expect_err("Empty rate estimator should error."),
+        EstimatorError::NoEstimateAvailable
+    );
+}
+
+/// Full blocks whose fee rates rise by 10 each block should produce an estimate of the next
+/// block's fee rate, not an average of the past ones.
+#[test]
+fn test_rising_fee_rates_lead_estimate() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let mut estimator = instantiate_test_db(metric);
+
+    for i in 1..6 {
+        let block_receipt = make_block_receipt(vec![
+            make_dummy_cc_tx(i * 10 * half_operation_cost_basis, &half_operation_cost),
+            make_dummy_cc_tx(i * 10 * half_operation_cost_basis, &half_operation_cost),
+        ]);
+
+        estimator
+            .notify_block(&block_receipt, &block_limit)
+            .expect("Should be able to process block receipt");
+    }
+
+    let estimate = estimator
+        .get_rate_estimates()
+        .expect("Should be able to create estimate now");
+    assert!(is_close_f64(estimate.high, 60f64));
+    assert!(is_close_f64(estimate.middle, 60f64));
+    assert!(is_close_f64(estimate.low, 60f64));
+}
+
+/// Only the last `window_size` blocks are fit: after a burst subsides, the estimate returns to the
+/// prevailing fee rate.
+#[test]
+fn test_window_size_forgets_burst() {
+    let metric = ProportionalDotProduct::new(10_000);
+    let mut estimator = instantiate_test_db(metric);
+
+    for fee_rate in [100, 100, 100, 10, 10, 10, 10, 10].iter() {
+        let block_receipt = make_block_receipt(vec![
+            make_dummy_cc_tx(fee_rate * half_operation_cost_basis, &half_operation_cost),
+            make_dummy_cc_tx(fee_rate * half_operation_cost_basis, &half_operation_cost),
+        ]);
+
+        estimator
+            .notify_block(&block_receipt, &block_limit)
+            .expect("Should be able to process block receipt");
+    }
+
+    assert_eq!(
+        estimator
+            .get_rate_estimates()
+            .expect("Should be able to create estimate now"),
+        FeeRateEstimate {
+            high: 10f64,
+            middle: 10f64,
+            low: 10f64,
+        }
+    );
+}
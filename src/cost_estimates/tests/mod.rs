@@ -3,7 +3,10 @@ use cost_estimates::FeeRateEstimate;
 pub mod common;
 pub mod cost_estimators;
 pub mod fee_medians;
+pub mod fee_observations;
+pub mod fee_percentiles;
 pub mod fee_rate_fuzzer;
+pub mod fee_regression;
 pub mod fee_scalar;
 pub mod metrics;
 
@@ -373,6 +373,14 @@ impl Script {
             && self.0[1] == opcodes::All::OP_PUSHBYTES_20 as u8
     }
 
+    /// Checks whether a script pubkey is a p2tr output
+    #[inline]
+    pub fn is_v1_p2tr(&self) -> bool {
+        self.0.len() == 34
+            && self.0[0] == opcodes::All::OP_PUSHNUM_1 as u8
+            && self.0[1] == opcodes::All::OP_PUSHBYTES_32 as u8
+    }
+
     /// Check if this is an OP_RETURN output
     pub fn is_op_return(&self) -> bool {
         !self.0.is_empty() && (opcodes::All::from(self.0[0]) == opcodes::All::OP_RETURN)
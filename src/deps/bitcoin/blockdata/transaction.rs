@@ -280,6 +280,74 @@ impl Transaction {
         Sha256dHash::from_data(&raw_vec)
     }
 
+    /// Computes the BIP143 signature hash for a segwit v0 input, given the `script_code` it
+    /// executes (for p2wpkh, the p2pkh script of its key hash) and the `value` of the output it
+    /// spends.
+    ///
+    /// # Panics
+    /// Panics if `input_index` is greater than or equal to `self.input.len()`
+    ///
+    pub fn segwit_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_u32: u32,
+    ) -> Sha256dHash {
+        assert!(input_index < self.input.len()); // Panic on OOB
+
+        let (sighash, anyone_can_pay) =
+            SigHashType::from_u32(sighash_u32).split_anyonecanpay_flag();
+        let zero_hash = Sha256dHash::from(&[0u8; 32][..]);
+
+        let hash_prevouts = if !anyone_can_pay {
+            let mut raw_vec = vec![];
+            for input in self.input.iter() {
+                raw_vec.extend_from_slice(&serialize(&input.previous_output).unwrap());
+            }
+            Sha256dHash::from_data(&raw_vec)
+        } else {
+            zero_hash
+        };
+
+        let hash_sequence =
+            if !anyone_can_pay && sighash != SigHashType::Single && sighash != SigHashType::None {
+                let mut raw_vec = vec![];
+                for input in self.input.iter() {
+                    raw_vec.extend_from_slice(&input.sequence.to_le_bytes());
+                }
+                Sha256dHash::from_data(&raw_vec)
+            } else {
+                zero_hash
+            };
+
+        let hash_outputs = if sighash != SigHashType::Single && sighash != SigHashType::None {
+            let mut raw_vec = vec![];
+            for output in self.output.iter() {
+                raw_vec.extend_from_slice(&serialize(output).unwrap());
+            }
+            Sha256dHash::from_data(&raw_vec)
+        } else if sighash == SigHashType::Single && input_index < self.output.len() {
+            Sha256dHash::from_data(&serialize(&self.output[input_index]).unwrap())
+        } else {
+            zero_hash
+        };
+
+        let input = &self.input[input_index];
+        let mut raw_vec = vec![];
+        raw_vec.extend_from_slice(&self.version.to_le_bytes());
+        raw_vec.extend_from_slice(hash_prevouts.as_bytes());
+        raw_vec.extend_from_slice(hash_sequence.as_bytes());
+        raw_vec.extend_from_slice(&serialize(&input.previous_output).unwrap());
+        raw_vec.extend_from_slice(&serialize(script_code).unwrap());
+        raw_vec.extend_from_slice(&value.to_le_bytes());
+        raw_vec.extend_from_slice(&input.sequence.to_le_bytes());
+        raw_vec.extend_from_slice(hash_outputs.as_bytes());
+        raw_vec.extend_from_slice(&self.lock_time.to_le_bytes());
+        raw_vec.extend_from_slice(&sighash_u32.to_le_bytes());
+        Sha256dHash::from_data(&raw_vec)
+    }
+
     /// Gets the "weight" of this transaction, as defined by BIP141. For transactions with an empty
     /// witness, this is simply the consensus-serialized size times 4. For transactions with a
     /// witness, this is the non-witness consensus-serialized size multiplied by 3 plus the
@@ -663,6 +731,22 @@ mod tests {
         assert_eq!(actual_result, expected_result);
     }
 
+    #[test]
+    fn test_segwit_sighash() {
+        // native p2wpkh example from BIP143
+        let tx: Transaction = deserialize(&hex_bytes("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap()[..]).unwrap();
+        let script_code =
+            Script::from(hex_bytes("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+        let expected = Sha256dHash::from(
+            &hex_bytes("c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670").unwrap()
+                [..],
+        );
+        assert_eq!(
+            tx.segwit_signature_hash(1, &script_code, 600000000, 1),
+            expected
+        );
+    }
+
     // These test vectors were stolen from libbtc, which is Copyright 2014 Jonas Schnelli MIT
     // They were transformed by replacing {...} with run_test_sighash(...), then the ones containing
     // OP_CODESEPARATOR in their pubkeys were removed
@@ -21,6 +21,7 @@
 #![allow(non_upper_case_globals)]
 #![cfg_attr(test, allow(unused_variables, unused_assignments))]
 
+extern crate base64;
 extern crate curve25519_dalek;
 extern crate ed25519_dalek;
 extern crate rand;
@@ -30,14 +31,20 @@ extern crate secp256k1;
 extern crate serde;
 #[macro_use]
 extern crate lazy_static;
+extern crate igd;
 extern crate integer_sqrt;
+extern crate libflate;
 extern crate mio;
+extern crate net2;
 extern crate percent_encoding;
 extern crate regex;
 extern crate ripemd160;
+extern crate rustls;
+extern crate sha1;
 extern crate sha2;
 extern crate sha3;
 extern crate siphasher;
+extern crate snow;
 extern crate time;
 extern crate url;
 
@@ -23,17 +23,20 @@
 #[macro_use]
 extern crate blockstack_lib;
 extern crate rusqlite;
+extern crate serde_json;
 
 #[macro_use(o, slog_log, slog_trace, slog_debug, slog_info, slog_warn, slog_error)]
 extern crate slog;
 
 use std::io;
 use std::io::prelude::*;
+use std::path::PathBuf;
 use std::process;
 use std::{collections::HashMap, env};
 use std::{convert::TryFrom, fs};
 
 use blockstack_lib::burnchains::BLOCKSTACK_MAGIC_MAINNET;
+use blockstack_lib::cost_estimates::fee_observations::FeeObservationRecorder;
 use blockstack_lib::cost_estimates::UnitEstimator;
 use cost_estimates::metrics::UnitMetric;
 use rusqlite::types::ToSql;
@@ -57,6 +60,7 @@ use blockstack_lib::util::get_epoch_time_ms;
 use blockstack_lib::util::hash::{hex_bytes, to_hex};
 use blockstack_lib::util::log;
 use blockstack_lib::util::retry::LogReader;
+use blockstack_lib::vm::types::QualifiedContractIdentifier;
 use blockstack_lib::*;
 use blockstack_lib::{
     burnchains::{db::BurnchainBlockData, PoxConstants},
@@ -545,6 +549,243 @@ simulating a miner.
         process::exit(0);
     }
 
+    if argv[1] == "simulate-contract-upgrade" {
+        if argv.len() < 5 {
+            eprintln!(
+                "Usage: {} simulate-contract-upgrade <working-dir> <contract-id> <new-source-file> [index-block-hash]
+
+Given a <working-dir>, replay every contract call that transactions have made to <contract-id> up
+to the given block (or the canonical chain tip) against both the deployed code and the code in
+<new-source-file>, and print each call that behaves differently.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+        let contract_id = QualifiedContractIdentifier::parse(&argv[3])
+            .expect("Failed to parse contract identifier");
+        let new_source =
+            fs::read_to_string(&argv[4]).expect(&format!("Failed to read {}", &argv[4]));
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let (mut chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+
+        let tip = if argv.len() >= 6 {
+            StacksBlockId::from_hex(&argv[5]).expect("Failed to parse index block hash")
+        } else {
+            let stacks_block = chain_state.get_stacks_chain_tip(&sort_db).unwrap().unwrap();
+            StacksBlockHeader::make_index_block_hash(
+                &stacks_block.consensus_hash,
+                &stacks_block.anchored_block_hash,
+            )
+        };
+
+        let trace = match chain_state
+            .get_contract_call_trace(&tip, &contract_id)
+            .expect("Failed to load contract call trace")
+        {
+            Some(trace) => trace,
+            None => {
+                eprintln!(
+                    "Contract {} was not published on the fork of {}",
+                    &contract_id, &tip
+                );
+                process::exit(1);
+            }
+        };
+
+        let report = chain_state
+            .simulate_contract_upgrade(&sort_db.index_conn(), &trace, &new_source)
+            .expect("Failed to simulate contract upgrade");
+
+        for divergence in report.divergences.iter() {
+            println!(
+                "Call {} (tx {} in {}): {} {:?} from {}",
+                divergence.call_index,
+                &divergence.call.txid,
+                &divergence.call.block_id,
+                &divergence.call.function_name,
+                &divergence.call.args,
+                &divergence.call.sender
+            );
+            println!("  original:    {:?}", &divergence.original);
+            println!("  replacement: {:?}", &divergence.replacement);
+        }
+        println!(
+            "Replayed {} calls to {}: {} diverged",
+            report.calls_replayed,
+            &report.contract_id,
+            report.divergences.len()
+        );
+
+        process::exit(0);
+    }
+
+    if argv[1] == "cost-voting-status" {
+        if argv.len() < 3 {
+            eprintln!(
+                "Usage: {} cost-voting-status <working-dir> [index-block-hash]
+
+Given a <working-dir>, print every proposal submitted to the cost-voting contract with its votes,
+vetos and confirmations, and the cost functions the miners have confirmed, as of the given block
+(or the canonical chain tip).
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let (mut chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+
+        let tip = if argv.len() >= 4 {
+            StacksBlockId::from_hex(&argv[3]).expect("Failed to parse index block hash")
+        } else {
+            let stacks_block = chain_state.get_stacks_chain_tip(&sort_db).unwrap().unwrap();
+            StacksBlockHeader::make_index_block_hash(
+                &stacks_block.consensus_hash,
+                &stacks_block.anchored_block_hash,
+            )
+        };
+
+        let proposal_count = chain_state
+            .get_cost_voting_proposal_count(&sort_db, &tip)
+            .expect("Failed to load cost-voting proposal count");
+        let mut proposals = vec![];
+        for proposal_id in 0..proposal_count {
+            let proposal = chain_state
+                .get_cost_voting_proposal(&sort_db, &tip, proposal_id)
+                .expect("Failed to load cost-voting proposal")
+                .expect(&format!("Missing cost-voting proposal {}", proposal_id));
+            proposals.push(proposal.to_json());
+        }
+        let confirmed: Vec<_> = chain_state
+            .get_confirmed_cost_functions(&sort_db, &tip)
+            .expect("Failed to load confirmed cost functions")
+            .iter()
+            .map(|confirmed| confirmed.to_json())
+            .collect();
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "tip": tip.to_hex(),
+                "proposals": proposals,
+                "confirmed_cost_functions": confirmed,
+            }))
+            .unwrap()
+        );
+        process::exit(0);
+    }
+
+    if argv[1] == "export-fee-observations" {
+        if argv.len() < 3 {
+            eprintln!(
+                "Usage: {} export-fee-observations <observations-db> [csv|json] [since-height]
+
+Print the transaction costs, fees and confirmation delays that a node configured with
+`record_observations = true` has recorded in <observations-db>, from blocks at or above
+[since-height] (default 0), as CSV (the default) or as a JSON array.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+
+        let format = argv.get(3).map(|s| s.as_str()).unwrap_or("csv");
+        let since_height = argv
+            .get(4)
+            .map(|s| s.parse::<u64>().expect("Failed to parse since-height"))
+            .unwrap_or(0);
+        let observations =
+            FeeObservationRecorder::get_observations(&PathBuf::from(&argv[2]), since_height)
+                .expect(&format!(
+                    "Failed to load fee observations from {}",
+                    &argv[2]
+                ));
+
+        match format {
+            "csv" => {
+                let stdout = io::stdout();
+                FeeObservationRecorder::write_csv(&observations, &mut stdout.lock())
+                    .expect("Failed to write fee observations");
+            }
+            "json" => {
+                let observations: Vec<_> = observations.iter().map(|o| o.to_json()).collect();
+                println!("{}", serde_json::Value::Array(observations));
+            }
+            _ => {
+                eprintln!("Unknown format {}: expected csv or json", format);
+                process::exit(1);
+            }
+        }
+
+        process::exit(0);
+    }
+
+    if argv[1] == "export-ndjson" {
+        if argv.len() < 3 {
+            eprintln!(
+                "Usage: {} export-ndjson <working-dir> [start-height] [end-height]
+
+Given a <working-dir>, print the canonical Stacks blocks from [start-height] (default 1) through
+[end-height] (default the chain tip) as newline-delimited JSON: one object per block, holding the
+block's header fields and its transactions (including those of the microblocks it confirms) with
+their receipts.  Receipt results are only present if the node ran with STACKS_TRANSACTION_LOG=1.
+Output is written as it is read, so a slow consumer slows the export down.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+        let start_height = argv
+            .get(3)
+            .map(|s| s.parse::<u64>().expect("Failed to parse start-height"))
+            .unwrap_or(1);
+        let end_height = argv
+            .get(4)
+            .map(|s| s.parse::<u64>().expect("Failed to parse end-height"));
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .unwrap_or_else(|_| panic!("Failed to open {}", &sort_db_path));
+        let (chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+
+        let stacks_block = chain_state.get_stacks_chain_tip(&sort_db).unwrap().unwrap();
+        let tip = StacksBlockHeader::make_index_block_hash(
+            &stacks_block.consensus_hash,
+            &stacks_block.anchored_block_hash,
+        );
+
+        let stdout = io::stdout();
+        if let Err(e) = chain_state.write_block_export_ndjson(
+            &mut stdout.lock(),
+            &tip,
+            start_height,
+            end_height,
+        ) {
+            eprintln!("Failed to export blocks: {:?}", &e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     if argv[1] == "decode-microblocks" {
         if argv.len() < 3 {
             eprintln!(
@@ -808,6 +1049,7 @@ simulating a miner.
             first_block: 0,
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
             epochs: None,
+            backup_endpoints: vec![],
         };
 
         let indexer = BitcoinIndexer::new(
@@ -845,6 +1087,7 @@ simulating a miner.
             get_bulk_initial_balances: None,
             get_bulk_initial_namespaces: None,
             get_bulk_initial_names: None,
+            boot_contract_overrides: vec![],
         };
 
         let (mut new_chainstate, _) = StacksChainState::open_and_exec(
@@ -31,7 +31,8 @@ use burnchains::BurnchainSigner;
 use std::convert::TryInto;
 use std::error::Error;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 use util::db::sqlite_open;
 use util::db::Error as DatabaseError;
 use util::uint::{Uint256, Uint512};
@@ -39,15 +40,68 @@ use vm::costs::ExecutionCost;
 
 #[cfg(feature = "monitoring_prom")]
 mod prometheus;
+pub mod statsd;
 
 #[cfg(feature = "monitoring_prom")]
 lazy_static! {
     static ref GLOBAL_BURNCHAIN_SIGNER: Mutex<Option<BurnchainSigner>> = Mutex::new(None);
 }
 
+lazy_static! {
+    static ref METRICS_BACKENDS: RwLock<Vec<Arc<dyn MetricsBackend>>> = RwLock::new(vec![]);
+}
+
+/// A destination for the node's metrics, in addition to the Prometheus registry (which is
+/// updated whenever the `monitoring_prom` feature is enabled).  Metrics are named as they are
+/// in Prometheus.
+pub trait MetricsBackend: Send + Sync {
+    /// Add `value` to a monotonic counter
+    fn counter_add(&self, name: &str, labels: &[(&str, &str)], value: u64);
+    fn gauge_set(&self, name: &str, value: f64);
+    fn gauge_add(&self, name: &str, delta: f64);
+    /// Record one sample of a distribution, such as a latency in seconds
+    fn histogram_observe(&self, name: &str, labels: &[(&str, &str)], value: f64);
+}
+
+/// Send all metrics recorded from now on to `backend` as well
+pub fn register_metrics_backend(backend: Arc<dyn MetricsBackend>) {
+    METRICS_BACKENDS.write().unwrap().push(backend);
+}
+
+fn metrics_backends_registered() -> bool {
+    !METRICS_BACKENDS.read().unwrap().is_empty()
+}
+
+fn for_each_metrics_backend<F: Fn(&dyn MetricsBackend)>(f: F) {
+    for backend in METRICS_BACKENDS.read().unwrap().iter() {
+        f(backend.as_ref());
+    }
+}
+
+fn counter_inc(name: &str) {
+    counter_add(name, 1);
+}
+
+fn counter_add(name: &str, value: u64) {
+    for_each_metrics_backend(|backend| backend.counter_add(name, &[], value));
+}
+
+fn gauge_set(name: &str, value: f64) {
+    for_each_metrics_backend(|backend| backend.gauge_set(name, value));
+}
+
+fn gauge_add(name: &str, delta: f64) {
+    for_each_metrics_backend(|backend| backend.gauge_add(name, delta));
+}
+
+fn histogram_observe(name: &str, labels: &[(&str, &str)], value: f64) {
+    for_each_metrics_backend(|backend| backend.histogram_observe(name, labels, value));
+}
+
 pub fn increment_rpc_calls_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::RPC_CALL_COUNTER.inc();
+    counter_inc("stacks_node_rpc_requests_total");
 }
 
 pub fn instrument_http_request_handler<F, R>(
@@ -57,99 +111,131 @@ pub fn instrument_http_request_handler<F, R>(
 where
     F: FnOnce(HttpRequestType) -> Result<R, net_error>,
 {
-    #[cfg(feature = "monitoring_prom")]
     increment_rpc_calls_counter();
 
     #[cfg(feature = "monitoring_prom")]
     let timer = prometheus::new_rpc_call_timer(req.get_path());
 
+    let path = if metrics_backends_registered() {
+        Some(req.get_path())
+    } else {
+        None
+    };
+    let start = Instant::now();
+
     let res = handler(req);
 
     #[cfg(feature = "monitoring_prom")]
     timer.stop_and_record();
 
+    if let Some(path) = path {
+        histogram_observe(
+            "stacks_node_rpc_call_latencies_histogram",
+            &[("path", path)],
+            start.elapsed().as_secs_f64(),
+        );
+    }
+
     res
 }
 
 pub fn increment_stx_blocks_received_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_BLOCKS_RECEIVED_COUNTER.inc();
+    counter_inc("stacks_node_stx_blocks_received_total");
 }
 
 pub fn increment_stx_micro_blocks_received_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_MICRO_BLOCKS_RECEIVED_COUNTER.inc();
+    counter_inc("stacks_node_stx_micro_blocks_received_total");
 }
 
 pub fn increment_stx_blocks_served_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_BLOCKS_SERVED_COUNTER.inc();
+    counter_inc("stacks_node_stx_blocks_served_total");
 }
 
 pub fn increment_stx_micro_blocks_served_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_MICRO_BLOCKS_SERVED_COUNTER.inc();
+    counter_inc("stacks_node_stx_micro_blocks_served_total");
 }
 
 pub fn increment_stx_confirmed_micro_blocks_served_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_CONFIRMED_MICRO_BLOCKS_SERVED_COUNTER.inc();
+    counter_inc("stacks_node_stx_confirmed_micro_blocks_served_total");
 }
 
 pub fn increment_txs_received_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::TXS_RECEIVED_COUNTER.inc();
+    counter_inc("stacks_node_transactions_received_total");
 }
 
 pub fn increment_btc_blocks_received_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::BTC_BLOCKS_RECEIVED_COUNTER.inc();
+    counter_inc("stacks_node_btc_blocks_received_total");
 }
 
 /// Log `execution_cost` as a ratio of `block_limit`.
-#[allow(unused_variables)]
 pub fn set_last_execution_cost_observed(
     execution_cost: &ExecutionCost,
     block_limit: &ExecutionCost,
 ) {
+    let read_count = execution_cost.read_count as f64 / block_limit.read_count as f64;
+    let write_count = execution_cost.write_count as f64 / block_limit.read_count as f64;
+    let read_length = execution_cost.read_length as f64 / block_limit.read_length as f64;
+    let write_length = execution_cost.write_length as f64 / block_limit.write_length as f64;
+    let runtime = execution_cost.runtime as f64 / block_limit.runtime as f64;
+
     #[cfg(feature = "monitoring_prom")]
     {
-        prometheus::LAST_BLOCK_READ_COUNT
-            .set(execution_cost.read_count as f64 / block_limit.read_count as f64);
-        prometheus::LAST_BLOCK_WRITE_COUNT
-            .set(execution_cost.write_count as f64 / block_limit.read_count as f64);
-        prometheus::LAST_BLOCK_READ_LENGTH
-            .set(execution_cost.read_length as f64 / block_limit.read_length as f64);
-        prometheus::LAST_BLOCK_WRITE_LENGTH
-            .set(execution_cost.write_length as f64 / block_limit.write_length as f64);
-        prometheus::LAST_BLOCK_RUNTIME
-            .set(execution_cost.runtime as f64 / block_limit.runtime as f64);
+        prometheus::LAST_BLOCK_READ_COUNT.set(read_count);
+        prometheus::LAST_BLOCK_WRITE_COUNT.set(write_count);
+        prometheus::LAST_BLOCK_READ_LENGTH.set(read_length);
+        prometheus::LAST_BLOCK_WRITE_LENGTH.set(write_length);
+        prometheus::LAST_BLOCK_RUNTIME.set(runtime);
     }
+
+    gauge_set("stacks_node_last_block_read_count", read_count);
+    gauge_set("stacks_node_last_block_write_count", write_count);
+    gauge_set("stacks_node_last_block_read_length", read_length);
+    gauge_set("stacks_node_last_block_write_length", write_length);
+    gauge_set("stacks_node_last_block_runtime", runtime);
 }
 
 pub fn increment_btc_ops_sent_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::BTC_OPS_SENT_COUNTER.inc();
+    counter_inc("stacks_node_btc_ops_sent_total");
 }
 
 pub fn increment_stx_blocks_processed_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_BLOCKS_PROCESSED_COUNTER.inc();
+    counter_inc("stacks_node_stx_blocks_processed_total");
 }
 
 pub fn increment_stx_blocks_mined_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_BLOCKS_MINED_COUNTER.inc();
+    counter_inc("stacks_node_stx_blocks_mined_total");
 }
 
 pub fn increment_warning_emitted_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::WARNING_EMITTED_COUNTER.inc();
+    counter_inc("stacks_node_warning_emitted_total");
 }
 
 pub fn increment_errors_emitted_counter() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::ERRORS_EMITTED_COUNTER.inc();
+    counter_inc("stacks_node_errors_emitted_total");
 }
 
 fn txid_tracking_db(chainstate_root_path: &str) -> Result<DBConn, DatabaseError> {
@@ -192,30 +278,35 @@ fn txid_tracking_db_contains(conn: &DBConn, txid: &Txid) -> Result<bool, Databas
     Ok(contains)
 }
 
-#[allow(unused_variables)]
+/// Whether or not any metrics are recorded at all
+fn metrics_enabled() -> bool {
+    cfg!(feature = "monitoring_prom") || metrics_backends_registered()
+}
+
 pub fn mempool_accepted(txid: &Txid, chainstate_root_path: &str) -> Result<(), DatabaseError> {
-    #[cfg(feature = "monitoring_prom")]
-    {
-        let tracking_db = txid_tracking_db(chainstate_root_path)?;
+    if !metrics_enabled() {
+        return Ok(());
+    }
 
-        if txid_tracking_db_contains(&tracking_db, txid)? {
-            // processed by a previous block, do not track again
-            return Ok(());
-        }
+    let tracking_db = txid_tracking_db(chainstate_root_path)?;
 
-        prometheus::MEMPOOL_OUTSTANDING_TXS.inc();
+    if txid_tracking_db_contains(&tracking_db, txid)? {
+        // processed by a previous block, do not track again
+        return Ok(());
     }
 
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MEMPOOL_OUTSTANDING_TXS.inc();
+    gauge_add("stacks_node_mempool_outstanding_txs", 1.0);
+
     Ok(())
 }
 
-#[allow(unused_variables)]
 pub fn log_transaction_processed(
     txid: &Txid,
     chainstate_root_path: &str,
 ) -> Result<(), DatabaseError> {
-    #[cfg(feature = "monitoring_prom")]
-    {
+    if metrics_enabled() {
         let mempool_db_path = MemPoolDB::db_path(chainstate_root_path)?;
         let mempool_conn = sqlite_open(&mempool_db_path, OpenFlags::SQLITE_OPEN_READ_ONLY, false)?;
         let tracking_db = txid_tracking_db(chainstate_root_path)?;
@@ -238,82 +329,216 @@ pub fn log_transaction_processed(
 
         let time_to_process = time_now - mempool_accept_time;
 
-        prometheus::MEMPOOL_OUTSTANDING_TXS.dec();
-        prometheus::MEMPOOL_TX_CONFIRM_TIME.observe(time_to_process as f64);
+        #[cfg(feature = "monitoring_prom")]
+        {
+            prometheus::MEMPOOL_OUTSTANDING_TXS.dec();
+            prometheus::MEMPOOL_TX_CONFIRM_TIME.observe(time_to_process as f64);
+        }
+        gauge_add("stacks_node_mempool_outstanding_txs", -1.0);
+        histogram_observe(
+            "stacks_node_mempool_tx_confirm_times",
+            &[],
+            time_to_process as f64,
+        );
     }
     Ok(())
 }
 
-#[allow(unused_variables)]
 pub fn update_active_miners_count_gauge(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::ACTIVE_MINERS_COUNT_GAUGE.set(value);
+    gauge_set("stacks_node_active_miners_total", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_stacks_tip_height(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STACKS_TIP_HEIGHT_GAUGE.set(value);
+    gauge_set("stacks_node_stacks_tip_height", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_burnchain_height(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::BURNCHAIN_HEIGHT_GAUGE.set(value);
+    gauge_set("stacks_node_burn_block_height", value as f64);
+}
+
+pub fn update_block_commit_fee_rate(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::BLOCK_COMMIT_FEE_RATE_GAUGE.set(value);
+    gauge_set("stacks_node_block_commit_fee_rate", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_inbound_neighbors(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::INBOUND_NEIGHBORS_GAUGE.set(value);
+    gauge_set("stacks_node_neighbors_inbound", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_outbound_neighbors(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::OUTBOUND_NEIGHBORS_GAUGE.set(value);
+    gauge_set("stacks_node_neighbors_outbound", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_inbound_bandwidth(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::INBOUND_BANDWIDTH_GAUGE.add(value);
+    gauge_add("stacks_node_bandwidth_inbound", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_outbound_bandwidth(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::OUTBOUND_BANDWIDTH_GAUGE.add(value);
+    gauge_add("stacks_node_bandwidth_outbound", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_inbound_rpc_bandwidth(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::INBOUND_RPC_BANDWIDTH_GAUGE.add(value);
+    gauge_add("stacks_node_rpc_bandwidth_inbound", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_outbound_rpc_bandwidth(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::OUTBOUND_RPC_BANDWIDTH_GAUGE.add(value);
+    gauge_add("stacks_node_rpc_bandwidth_outbound", value as f64);
 }
 
-#[allow(unused_variables)]
 pub fn increment_msg_counter(name: String) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::MSG_COUNTER_VEC
         .with_label_values(&[&name])
         .inc();
+    for_each_metrics_backend(|backend| {
+        backend.counter_add("stacks_node_message_count", &[("name", &name)], 1)
+    });
+}
+
+pub fn update_open_files(total: i64, sockets: i64, databases: i64, limit: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    {
+        prometheus::OPEN_FILES_GAUGE.set(total);
+        prometheus::OPEN_SOCKETS_GAUGE.set(sockets);
+        prometheus::OPEN_DATABASE_FILES_GAUGE.set(databases);
+        prometheus::OPEN_FILES_LIMIT_GAUGE.set(limit);
+    }
+    gauge_set("stacks_node_open_files", total as f64);
+    gauge_set("stacks_node_open_sockets", sockets as f64);
+    gauge_set("stacks_node_open_database_files", databases as f64);
+    gauge_set("stacks_node_open_files_limit", limit as f64);
+}
+
+pub fn increment_connections_shed_counter(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::CONNECTIONS_SHED_COUNTER.inc_by(value);
+    counter_add("stacks_node_connections_shed", value as u64);
+}
+
+pub fn increment_connections_refused_counter() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::CONNECTIONS_REFUSED_COUNTER.inc();
+    counter_inc("stacks_node_connections_refused");
+}
+
+pub fn increment_rpc_requests_refused_counter(reason: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::RPC_REQUESTS_REFUSED_COUNTER_VEC
+        .with_label_values(&[reason])
+        .inc();
+    for_each_metrics_backend(|backend| {
+        backend.counter_add("stacks_node_rpc_requests_refused", &[("reason", reason)], 1)
+    });
+}
+
+pub fn increment_p2p_peer_misbehavior_counter(reason: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::P2P_PEER_MISBEHAVIOR_COUNTER_VEC
+        .with_label_values(&[reason])
+        .inc();
+    for_each_metrics_backend(|backend| {
+        backend.counter_add("stacks_node_p2p_peer_misbehavior", &[("reason", reason)], 1)
+    });
+}
+
+/// Are per-p2p-neighbor metrics going anywhere?  They're labeled with the neighbor's address,
+/// which costs something to format.
+pub fn p2p_peer_metrics_enabled() -> bool {
+    cfg!(feature = "monitoring_prom") || metrics_backends_registered()
+}
+
+pub fn update_p2p_peer_rtt(peer: &str, rtt_secs: f64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::P2P_PEER_RTT_HISTOGRAM_VEC
+        .with_label_values(&[peer])
+        .observe(rtt_secs);
+    histogram_observe(
+        "stacks_node_p2p_peer_rtt_seconds",
+        &[("peer", peer)],
+        rtt_secs,
+    );
+}
+
+/// `result` is "reply" or "timeout"
+pub fn increment_p2p_peer_requests_counter(peer: &str, result: &str, value: u64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::P2P_PEER_REQUESTS_COUNTER_VEC
+        .with_label_values(&[peer, result])
+        .inc_by(value as i64);
+    for_each_metrics_backend(|backend| {
+        backend.counter_add(
+            "stacks_node_p2p_peer_requests",
+            &[("peer", peer), ("result", result)],
+            value,
+        )
+    });
+}
+
+/// `direction` is "tx" or "rx"
+pub fn increment_p2p_peer_bytes_counter(peer: &str, direction: &str, value: u64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::P2P_PEER_BYTES_COUNTER_VEC
+        .with_label_values(&[peer, direction])
+        .inc_by(value as i64);
+    for_each_metrics_backend(|backend| {
+        backend.counter_add(
+            "stacks_node_p2p_peer_bytes",
+            &[("peer", peer), ("direction", direction)],
+            value,
+        )
+    });
+}
+
+/// Stop exporting a disconnected p2p neighbor's metrics, so they don't pile up as neighbors come
+/// and go.  Only the Prometheus registry can forget them; other backends keep their last values.
+pub fn remove_p2p_peer_metrics(_peer: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    {
+        let _ = prometheus::P2P_PEER_RTT_HISTOGRAM_VEC.remove_label_values(&[_peer]);
+        for result in ["reply", "timeout"].iter() {
+            let _ = prometheus::P2P_PEER_REQUESTS_COUNTER_VEC.remove_label_values(&[_peer, result]);
+        }
+        for direction in ["tx", "rx"].iter() {
+            let _ = prometheus::P2P_PEER_BYTES_COUNTER_VEC.remove_label_values(&[_peer, direction]);
+        }
+    }
+}
+
+pub fn increment_p2p_peers_banned_counter() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::P2P_PEERS_BANNED_COUNTER.inc();
+    counter_inc("stacks_node_p2p_peers_banned");
 }
 
 pub fn increment_stx_mempool_gc() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::STX_MEMPOOL_GC.inc();
+    counter_inc("stacks_node_mempool_gc_count");
 }
 
 pub fn increment_contract_calls_processed() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::CONTRACT_CALLS_PROCESSED_COUNT.inc();
+    counter_inc("stacks_contract_calls_processed");
 }
 
 /// Given a value (type uint256), return value/uint256::max() as an f64 value.
@@ -373,35 +598,111 @@ pub fn test_convert_uint256_to_f64() {
     assert_approx_eq!(convert_uint256_to_f64_percentage(original, 1000), 12.234567);
 }
 
-#[allow(unused_variables)]
+#[cfg(test)]
+#[derive(Default)]
+struct RecordingBackend {
+    records: Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MetricsBackend for RecordingBackend {
+    fn counter_add(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.records
+            .lock()
+            .unwrap()
+            .push(format!("counter {} {:?} {}", name, labels, value));
+    }
+
+    fn gauge_set(&self, name: &str, value: f64) {
+        self.records
+            .lock()
+            .unwrap()
+            .push(format!("gauge {} {}", name, value));
+    }
+
+    fn gauge_add(&self, name: &str, delta: f64) {
+        self.records
+            .lock()
+            .unwrap()
+            .push(format!("gauge {} {:+}", name, delta));
+    }
+
+    fn histogram_observe(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.records
+            .lock()
+            .unwrap()
+            .push(format!("histogram {} {:?} {}", name, labels, value));
+    }
+}
+
+#[test]
+fn test_metrics_backend_receives_metrics() {
+    let backend = Arc::new(RecordingBackend::default());
+    register_metrics_backend(backend.clone());
+
+    // backends are global, so only look at metrics no other test records
+    increment_msg_counter("test_metrics_backend_receives_metrics".to_string());
+    update_computed_miner_commitment((3u128 << 64) + 4);
+
+    let records: Vec<_> = backend
+        .records
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|record| {
+            record.contains("test_metrics_backend_receives_metrics")
+                || record.contains("stacks_node_computed_miner_commitment")
+        })
+        .cloned()
+        .collect();
+    assert_eq!(
+        records,
+        vec![
+            "counter stacks_node_message_count [(\"name\", \"test_metrics_backend_receives_metrics\")] 1",
+            "gauge stacks_node_computed_miner_commitment_high 3",
+            "gauge stacks_node_computed_miner_commitment_low 4",
+        ]
+    );
+}
+
 pub fn update_computed_relative_miner_score(value: Uint256) {
+    let percentage = convert_uint256_to_f64_percentage(value, 7);
     #[cfg(feature = "monitoring_prom")]
-    {
-        let percentage = convert_uint256_to_f64_percentage(value, 7);
-        prometheus::COMPUTED_RELATIVE_MINER_SCORE.set(percentage);
-    }
+    prometheus::COMPUTED_RELATIVE_MINER_SCORE.set(percentage);
+    gauge_set("stacks_node_computed_relative_miner_score", percentage);
 }
 
-#[allow(unused_variables)]
 pub fn update_computed_miner_commitment(value: u128) {
+    let high_bits = (value >> 64) as u64;
+    let low_bits = value as u64;
     #[cfg(feature = "monitoring_prom")]
     {
-        let high_bits = (value >> 64) as u64;
-        let low_bits = value as u64;
         prometheus::COMPUTED_MINER_COMMITMENT_HIGH.set(high_bits as i64);
         prometheus::COMPUTED_MINER_COMMITMENT_LOW.set(low_bits as i64);
     }
+    gauge_set(
+        "stacks_node_computed_miner_commitment_high",
+        high_bits as f64,
+    );
+    gauge_set("stacks_node_computed_miner_commitment_low", low_bits as f64);
 }
 
-#[allow(unused_variables)]
 pub fn update_miner_current_median_commitment(value: u128) {
+    let high_bits = (value >> 64) as u64;
+    let low_bits = value as u64;
     #[cfg(feature = "monitoring_prom")]
     {
-        let high_bits = (value >> 64) as u64;
-        let low_bits = value as u64;
         prometheus::MINER_CURRENT_MEDIAN_COMMITMENT_HIGH.set(high_bits as i64);
         prometheus::MINER_CURRENT_MEDIAN_COMMITMENT_LOW.set(low_bits as i64);
     }
+    gauge_set(
+        "stacks_node_miner_current_median_commitment_high",
+        high_bits as f64,
+    );
+    gauge_set(
+        "stacks_node_miner_current_median_commitment_low",
+        low_bits as f64,
+    );
 }
 
 /// Function sets the global variable `GLOBAL_BURNCHAIN_SIGNER`.
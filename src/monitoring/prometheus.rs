@@ -131,6 +131,11 @@ lazy_static! {
         "Burnchain tip height"
     )).unwrap();
 
+    pub static ref BLOCK_COMMIT_FEE_RATE_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_block_commit_fee_rate",
+        "Fee rate, in sats per vbyte, of the last block-commit sent"
+    )).unwrap();
+
     pub static ref INBOUND_NEIGHBORS_GAUGE: IntGauge = register_int_gauge!(opts!(
         "stacks_node_neighbors_inbound",
         "Total count of current known inbound neighbors"
@@ -168,6 +173,70 @@ lazy_static! {
     ).unwrap();
 
 
+    pub static ref OPEN_FILES_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_open_files",
+        "Number of file descriptors the node has open"
+    )).unwrap();
+
+    pub static ref OPEN_SOCKETS_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_open_sockets",
+        "Number of open file descriptors that are sockets"
+    )).unwrap();
+
+    pub static ref OPEN_DATABASE_FILES_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_open_database_files",
+        "Number of open file descriptors that are SQLite databases, journals, or WALs"
+    )).unwrap();
+
+    pub static ref OPEN_FILES_LIMIT_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_open_files_limit",
+        "Maximum number of file descriptors the node may have open"
+    )).unwrap();
+
+    pub static ref CONNECTIONS_SHED_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_connections_shed",
+        "Total count of inbound connections closed because the node was running out of file descriptors"
+    )).unwrap();
+
+    pub static ref CONNECTIONS_REFUSED_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_connections_refused",
+        "Total count of inbound connections refused because the node was running out of file descriptors"
+    )).unwrap();
+
+    pub static ref RPC_REQUESTS_REFUSED_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_rpc_requests_refused",
+        "Total count of RPC requests refused for lacking a valid API key or exceeding a rate limit",
+        &["reason"]
+    ).unwrap();
+
+    pub static ref P2P_PEER_MISBEHAVIOR_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_p2p_peer_misbehavior",
+        "Total count of p2p peer misbehavior that cost the peer reputation",
+        &["reason"]
+    ).unwrap();
+
+    pub static ref P2P_PEER_RTT_HISTOGRAM_VEC: HistogramVec = register_histogram_vec!(histogram_opts!(
+        "stacks_node_p2p_peer_rtt_seconds",
+        "Time (seconds) each p2p neighbor took to answer our requests"
+    ), &["peer"]).unwrap();
+
+    pub static ref P2P_PEER_REQUESTS_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_p2p_peer_requests",
+        "Total count of requests to each p2p neighbor, by whether it replied or timed out",
+        &["peer", "result"]
+    ).unwrap();
+
+    pub static ref P2P_PEER_BYTES_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_p2p_peer_bytes",
+        "Total bytes sent to (tx) and received from (rx) each p2p neighbor",
+        &["peer", "direction"]
+    ).unwrap();
+
+    pub static ref P2P_PEERS_BANNED_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_p2p_peers_banned",
+        "Total count of p2p peers banned because their reputation fell too low"
+    )).unwrap();
+
     pub static ref STX_MEMPOOL_GC: IntCounter = register_int_counter!(opts!(
         "stacks_node_mempool_gc_count",
         "Total count of all mempool garbage collections"
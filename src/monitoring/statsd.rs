@@ -0,0 +1,137 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A metrics backend that pushes to a statsd (or DogStatsD-compatible) agent over UDP.
+//! Labels are sent as DogStatsD tags; plain statsd agents ignore them.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use super::MetricsBackend;
+
+pub struct StatsdBackend {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdBackend {
+    /// Connect to the statsd agent at `endpoint` (e.g. `127.0.0.1:8125`).  Every metric name
+    /// is prefixed with `prefix`, if given, followed by a `.`.
+    pub fn new<A: ToSocketAddrs>(endpoint: A, prefix: Option<&str>) -> io::Result<StatsdBackend> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(endpoint)?;
+        socket.set_nonblocking(true)?;
+        Ok(StatsdBackend {
+            socket,
+            prefix: prefix.map(|p| format!("{}.", p)).unwrap_or_default(),
+        })
+    }
+
+    fn send(&self, name: &str, value: &str, kind: &str, labels: &[(&str, &str)]) {
+        let line = format_line(&self.prefix, name, value, kind, labels);
+        // statsd is fire-and-forget; a dropped datagram only loses one sample
+        if let Err(e) = self.socket.send(line.as_bytes()) {
+            debug!("Failed to send metric to statsd: {:?}", &e);
+        }
+    }
+}
+
+fn format_line(
+    prefix: &str,
+    name: &str,
+    value: &str,
+    kind: &str,
+    labels: &[(&str, &str)],
+) -> String {
+    let mut line = format!("{}{}:{}|{}", prefix, name, value, kind);
+    if !labels.is_empty() {
+        let tags: Vec<_> = labels
+            .iter()
+            .map(|(key, value)| format!("{}:{}", key, value))
+            .collect();
+        line.push_str("|#");
+        line.push_str(&tags.join(","));
+    }
+    line
+}
+
+impl MetricsBackend for StatsdBackend {
+    fn counter_add(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.send(name, &value.to_string(), "c", labels);
+    }
+
+    fn gauge_set(&self, name: &str, value: f64) {
+        // a leading sign would be read as a relative update, so clamp to non-negative values
+        self.send(name, &value.max(0.0).to_string(), "g", &[]);
+    }
+
+    fn gauge_add(&self, name: &str, delta: f64) {
+        self.send(name, &format!("{:+}", delta), "g", &[]);
+    }
+
+    fn histogram_observe(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.send(name, &value.to_string(), "h", labels);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(format_line("", "a", "1", "c", &[]), "a:1|c");
+        assert_eq!(
+            format_line(
+                "stacks.",
+                "a",
+                "0.5",
+                "h",
+                &[("path", "/v2/info"), ("x", "y")]
+            ),
+            "stacks.a:0.5|h|#path:/v2/info,x:y"
+        );
+    }
+
+    #[test]
+    fn test_statsd_backend_sends_datagrams() {
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        agent
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let backend = StatsdBackend::new(agent.local_addr().unwrap(), Some("node")).unwrap();
+
+        backend.counter_add("blocks", &[], 2);
+        backend.gauge_add("outstanding", -1.0);
+        backend.gauge_set("height", 12.0);
+
+        let mut buf = [0u8; 512];
+        let mut received = vec![];
+        for _ in 0..3 {
+            let len = agent.recv(&mut buf).unwrap();
+            received.push(String::from_utf8(buf[..len].to_vec()).unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![
+                "node.blocks:2|c",
+                "node.outstanding:-1|g",
+                "node.height:12|g"
+            ]
+        );
+    }
+}
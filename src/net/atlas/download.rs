@@ -24,7 +24,7 @@ use std::net::{IpAddr, SocketAddr};
 use crate::types::chainstate::StacksBlockId;
 use chainstate::burn::ConsensusHash;
 use chainstate::stacks::db::StacksChainState;
-use net::atlas::MAX_RETRY_DELAY;
+use net::backoff::{PeerFailures, RetryPolicy};
 use net::connection::ConnectionOptions;
 use net::dns::*;
 use net::p2p::PeerNetwork;
@@ -56,6 +56,7 @@ pub struct AttachmentsDownloader {
     ongoing_batch: Option<AttachmentsBatchStateMachine>,
     processed_batches: Vec<AttachmentsBatch>,
     reliability_reports: HashMap<UrlString, ReliabilityReport>,
+    peer_failures: PeerFailures<UrlString>,
 }
 
 impl AttachmentsDownloader {
@@ -65,6 +66,7 @@ impl AttachmentsDownloader {
             ongoing_batch: None,
             processed_batches: vec![],
             reliability_reports: HashMap::new(),
+            peer_failures: PeerFailures::new(),
             initial_batch,
         }
     }
@@ -128,9 +130,20 @@ impl AttachmentsDownloader {
                     return Ok((vec![], vec![]));
                 }
 
+                let now = get_epoch_time_secs();
+                self.peer_failures
+                    .prune(&network.connection_opts.attachment_retry_policy(), now);
+
                 let mut peers = HashMap::new();
                 for peer in network.get_outbound_sync_peers() {
                     if let Some(peer_url) = network.get_data_url(&peer) {
+                        if let Some(retry_at) = self.peer_failures.retry_at(&peer_url, now) {
+                            debug!(
+                                "Atlas: will not ask {:?} for attachments until after {}",
+                                &peer_url, retry_at
+                            );
+                            continue;
+                        }
                         let report = match self.reliability_reports.get(&peer_url) {
                             Some(report) => report.clone(),
                             None => ReliabilityReport::empty(),
@@ -204,9 +217,29 @@ impl AttachmentsDownloader {
                     self.reliability_reports.insert(peer_url, report);
                 }
 
+                // Back off from the peers that failed us, and forgive the ones that didn't
+                let retry_policy = context.connection_options.attachment_retry_policy();
+                for peer_url in context.failed_peers.drain() {
+                    context.succeeded_peers.remove(&peer_url);
+                    let retry_at = self.peer_failures.record_failure(
+                        peer_url.clone(),
+                        &retry_policy,
+                        get_epoch_time_secs(),
+                    );
+                    debug!(
+                        "Atlas: {:?} failed {} time(s) in a row; will retry it after {}",
+                        &peer_url,
+                        self.peer_failures.failures(&peer_url),
+                        retry_at
+                    );
+                }
+                for peer_url in context.succeeded_peers.drain() {
+                    self.peer_failures.record_success(&peer_url);
+                }
+
                 // Re-insert AttachmentsBatch back to the queue if not fully processed
                 if !context.attachments_batch.has_fully_succeed() {
-                    context.attachments_batch.bump_retry_count();
+                    context.attachments_batch.bump_retry_count(&retry_policy);
                     // If max_attachment_retry_count not reached, we'll re-enqueue the batch
                     if context.attachments_batch.retry_count
                         < context.connection_options.max_attachment_retry_count
@@ -323,6 +356,9 @@ pub struct AttachmentsBatchStateContext {
     >,
     pub attachments: HashSet<Attachment>,
     pub events_to_deregister: Vec<usize>,
+    /// peers that failed or answered at least one request in this batch
+    pub failed_peers: HashSet<UrlString>,
+    pub succeeded_peers: HashSet<UrlString>,
 }
 
 impl AttachmentsBatchStateContext {
@@ -339,6 +375,8 @@ impl AttachmentsBatchStateContext {
             inventories: HashMap::new(),
             attachments: HashSet::new(),
             events_to_deregister: vec![],
+            failed_peers: HashSet::new(),
+            succeeded_peers: HashSet::new(),
         }
     }
 
@@ -486,10 +524,14 @@ impl AttachmentsBatchStateContext {
                     }
                 };
                 report.bump_successful_requests();
+                self.succeeded_peers.insert(request.get_url().clone());
             } else {
                 report.bump_failed_requests();
+                self.failed_peers.insert(request.get_url().clone());
             }
         }
+        self.failed_peers
+            .extend(results.faulty_peers.values().cloned());
         let mut events_ids = results
             .faulty_peers
             .iter()
@@ -512,10 +554,14 @@ impl AttachmentsBatchStateContext {
             if let Some(HttpResponseType::GetAttachment(_, response)) = response {
                 self.attachments.insert(response.attachment);
                 report.bump_successful_requests();
+                self.succeeded_peers.insert(request.get_url().clone());
             } else {
                 report.bump_failed_requests();
+                self.failed_peers.insert(request.get_url().clone());
             }
         }
+        self.failed_peers
+            .extend(results.faulty_peers.values().cloned());
         let mut events_ids = results
             .faulty_peers
             .iter()
@@ -1121,14 +1167,9 @@ impl AttachmentsBatch {
         };
     }
 
-    pub fn bump_retry_count(&mut self) {
+    pub fn bump_retry_count(&mut self, retry_policy: &RetryPolicy) {
         self.retry_count += 1;
-        let delay = cmp::min(
-            MAX_RETRY_DELAY,
-            2u64.saturating_pow(self.retry_count as u32).saturating_add(
-                thread_rng().gen::<u64>() % 2u64.saturating_pow((self.retry_count - 1) as u32),
-            ),
-        );
+        let delay = retry_policy.delay_for(self.retry_count);
 
         debug!("Atlas: Re-attempt download in {} seconds", delay);
         self.retry_deadline = get_epoch_time_secs() + delay;
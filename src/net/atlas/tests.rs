@@ -72,7 +72,7 @@ fn new_attachments_batch_from(
         attachments_batch.track_attachment(&attachment_instance);
     }
     for _ in 0..retry_count {
-        attachments_batch.bump_retry_count();
+        attachments_batch.bump_retry_count(&ConnectionOptions::default().attachment_retry_policy());
     }
     attachments_batch
 }
@@ -0,0 +1,202 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Retry backoff for the block and attachment downloaders.  Each time a peer fails us, we wait
+//! twice as long as last time before asking it again (up to a maximum), plus some random jitter
+//! so that many nodes that lost the same peer don't all come back to it at once.  A success
+//! resets the peer's failure count, so a peer that only hiccuped is used again right away.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::thread_rng;
+use rand::Rng;
+
+/// How long to wait before retrying something that has failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// how long to wait after the first failure, in seconds
+    pub base_delay: u64,
+    /// the longest we'll ever wait, in seconds
+    pub max_delay: u64,
+    /// up to this fraction of the delay is added as random jitter
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    pub fn new(base_delay: u64, max_delay: u64, jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            base_delay,
+            max_delay,
+            jitter,
+        }
+    }
+
+    /// The delay after this many consecutive failures, without jitter
+    pub fn base_delay_for(&self, failures: u64) -> u64 {
+        if failures == 0 {
+            return 0;
+        }
+        let exponent = cmp::min(failures - 1, 63) as u32;
+        cmp::min(
+            self.max_delay,
+            self.base_delay
+                .saturating_mul(2u64.saturating_pow(exponent)),
+        )
+    }
+
+    /// The delay after this many consecutive failures, with jitter
+    pub fn delay_for(&self, failures: u64) -> u64 {
+        let delay = self.base_delay_for(failures);
+        let max_jitter = ((delay as f64) * self.jitter.max(0.0)) as u64;
+        let jitter = if max_jitter > 0 {
+            thread_rng().gen_range(0, max_jitter + 1)
+        } else {
+            0
+        };
+        cmp::min(self.max_delay, delay.saturating_add(jitter))
+    }
+}
+
+/// A peer's run of consecutive failures
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureRecord {
+    pub failures: u64,
+    /// when we can try this peer again
+    pub retry_at: u64,
+}
+
+/// Consecutive failures of each peer, and when each one can be tried again
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerFailures<K: Hash + Eq> {
+    records: HashMap<K, FailureRecord>,
+}
+
+impl<K: Hash + Eq> Default for PeerFailures<K> {
+    fn default() -> PeerFailures<K> {
+        PeerFailures::new()
+    }
+}
+
+impl<K: Hash + Eq> PeerFailures<K> {
+    pub fn new() -> PeerFailures<K> {
+        PeerFailures {
+            records: HashMap::new(),
+        }
+    }
+
+    /// Record a failure, and return when the peer can be tried again
+    pub fn record_failure(&mut self, peer: K, policy: &RetryPolicy, now: u64) -> u64 {
+        let record = self.records.entry(peer).or_insert(FailureRecord {
+            failures: 0,
+            retry_at: 0,
+        });
+        record.failures += 1;
+        record.retry_at = now.saturating_add(policy.delay_for(record.failures));
+        record.retry_at
+    }
+
+    /// Record a success, which forgives the peer's failures
+    pub fn record_success(&mut self, peer: &K) {
+        self.records.remove(peer);
+    }
+
+    /// If this peer is still backed off, then when can it be tried again?
+    pub fn retry_at(&self, peer: &K, now: u64) -> Option<u64> {
+        self.records
+            .get(peer)
+            .map(|record| record.retry_at)
+            .filter(|retry_at| now < *retry_at)
+    }
+
+    pub fn failures(&self, peer: &K) -> u64 {
+        self.records
+            .get(peer)
+            .map(|record| record.failures)
+            .unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Forget peers that haven't failed since well before the longest backoff, so this doesn't
+    /// grow without bound.
+    pub fn prune(&mut self, policy: &RetryPolicy, now: u64) {
+        self.records
+            .retain(|_, record| record.retry_at.saturating_add(policy.max_delay) > now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::new(5, 60, 0.0);
+        assert_eq!(policy.base_delay_for(0), 0);
+        assert_eq!(policy.base_delay_for(1), 5);
+        assert_eq!(policy.base_delay_for(2), 10);
+        assert_eq!(policy.base_delay_for(4), 40);
+        assert_eq!(policy.base_delay_for(5), 60);
+        assert_eq!(policy.base_delay_for(u64::MAX), 60);
+        assert_eq!(policy.delay_for(3), 20);
+
+        let policy = RetryPolicy::new(8, 1000, 0.5);
+        for _ in 0..100 {
+            let delay = policy.delay_for(2);
+            assert!((16..=24).contains(&delay));
+        }
+        for _ in 0..100 {
+            assert_eq!(policy.delay_for(10), 1000);
+        }
+    }
+
+    #[test]
+    fn test_peer_failures() {
+        let policy = RetryPolicy::new(10, 100, 0.0);
+        let mut failures = PeerFailures::new();
+
+        assert_eq!(failures.record_failure("a", &policy, 1000), 1010);
+        assert_eq!(failures.retry_at(&"a", 1000), Some(1010));
+        assert_eq!(failures.retry_at(&"a", 1010), None);
+        assert_eq!(failures.retry_at(&"b", 1000), None);
+
+        // backs off further on each consecutive failure
+        assert_eq!(failures.record_failure("a", &policy, 1010), 1030);
+        assert_eq!(failures.record_failure("a", &policy, 1030), 1070);
+        assert_eq!(failures.failures(&"a"), 3);
+
+        // a success forgives it
+        failures.record_success(&"a");
+        assert_eq!(failures.failures(&"a"), 0);
+        assert_eq!(failures.retry_at(&"a", 1030), None);
+        assert_eq!(failures.record_failure("a", &policy, 2000), 2010);
+
+        failures.record_failure("b", &policy, 2100);
+        failures.prune(&policy, 2105);
+        assert_eq!(failures.len(), 2);
+        failures.prune(&policy, 2200);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures.failures(&"b"), 1);
+    }
+}
@@ -0,0 +1,348 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Anti-entropy block offers.  When the anti-entropy pass finds anchored blocks and confirmed
+//! microblock streams that a neighbor's inventory says it's missing (most-recently-processed
+//! first), it can offer them instead of pushing them outright.  The neighbor answers the
+//! `BlocksOffer` with a `BlocksOfferAccept` listing only the data it still doesn't have -- it may
+//! have fetched it with the block downloader since we last saw its inventory -- and we push just
+//! that.  Offers are only made to neighbors that advertise `ServiceFlags::BLOCK_OFFERS`; other
+//! neighbors get the data pushed to them as before.
+
+use std::collections::HashMap;
+
+use chainstate::burn::ConsensusHash;
+use chainstate::stacks::db::StacksChainState;
+use chainstate::stacks::{StacksBlock, StacksMicroblock};
+use net::capabilities::Capability;
+use net::p2p::PeerNetwork;
+use net::reputation::PeerMisbehavior;
+use net::Error as net_error;
+use net::*;
+use util::get_epoch_time_secs;
+
+use crate::types::chainstate::{StacksBlockHeader, StacksBlockId};
+
+/// Data we offered to a neighbor, held until it tells us which of it to push
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingBlocksOffer {
+    pub offered_at: u64,
+    /// offered anchored blocks, by index block hash
+    pub blocks: HashMap<StacksBlockId, (ConsensusHash, StacksBlock)>,
+    /// offered confirmed microblock streams, keyed by the index block hash of the anchored block
+    /// that confirms them.  Each value is the index block hash of the stream's parent anchored
+    /// block, and the stream itself.
+    pub microblocks: HashMap<StacksBlockId, (StacksBlockId, Vec<StacksMicroblock>)>,
+}
+
+impl PendingBlocksOffer {
+    pub fn new(
+        blocks: Vec<(ConsensusHash, StacksBlock)>,
+        microblocks: Vec<(StacksBlockId, StacksBlockId, Vec<StacksMicroblock>)>,
+    ) -> PendingBlocksOffer {
+        PendingBlocksOffer {
+            offered_at: get_epoch_time_secs(),
+            blocks: blocks
+                .into_iter()
+                .map(|(consensus_hash, block)| {
+                    (
+                        StacksBlockHeader::make_index_block_hash(
+                            &consensus_hash,
+                            &block.block_hash(),
+                        ),
+                        (consensus_hash, block),
+                    )
+                })
+                .collect(),
+            microblocks: microblocks
+                .into_iter()
+                .map(|(parent_block_id, child_block_id, mblocks)| {
+                    (child_block_id, (parent_block_id, mblocks))
+                })
+                .collect(),
+        }
+    }
+
+    /// The `BlocksOffer` to send for this data
+    pub fn to_offer(&self) -> BlocksOfferData {
+        BlocksOfferData {
+            blocks: self.blocks.keys().cloned().collect(),
+            microblocks: self.microblocks.keys().cloned().collect(),
+        }
+    }
+
+    /// Take the data a neighbor accepted.  Anything it asks for that we didn't offer is ignored.
+    pub fn take_accepted(
+        &mut self,
+        accepted: &BlocksOfferData,
+    ) -> (
+        Vec<(ConsensusHash, StacksBlock)>,
+        Vec<(StacksBlockId, Vec<StacksMicroblock>)>,
+    ) {
+        let blocks = accepted
+            .blocks
+            .iter()
+            .filter_map(|block_id| self.blocks.remove(block_id))
+            .collect();
+        let microblocks = accepted
+            .microblocks
+            .iter()
+            .filter_map(|child_block_id| self.microblocks.remove(child_block_id))
+            .collect();
+        (blocks, microblocks)
+    }
+}
+
+impl PeerNetwork {
+    /// Should we offer anti-entropy data to this neighbor, instead of pushing it?
+    pub fn can_offer_blocks(&self, nk: &NeighborKey) -> bool {
+        if !self.connection_opts.antientropy_offers {
+            return false;
+        }
+        self.events
+            .get(nk)
+            .and_then(|event_id| self.peers.get(event_id))
+            .map(|convo| convo.supports(Capability::BlockOffers))
+            .unwrap_or(false)
+    }
+
+    /// Offer a neighbor the anchored blocks and confirmed microblock streams it's missing.  Each
+    /// microblock stream is given as its parent anchored block's index hash, the index hash of the
+    /// anchored block that confirms it, and the stream.  Replaces any offer we already have
+    /// outstanding with this neighbor.
+    pub fn offer_blocks(
+        &mut self,
+        nk: &NeighborKey,
+        blocks: Vec<(ConsensusHash, StacksBlock)>,
+        microblocks: Vec<(StacksBlockId, StacksBlockId, Vec<StacksMicroblock>)>,
+    ) -> Result<(), net_error> {
+        let pending = PendingBlocksOffer::new(blocks, microblocks);
+        let offer = pending.to_offer();
+        debug!(
+            "{:?}: AntiEntropy: offer {} anchored blocks and {} microblock streams to {:?}",
+            &self.local_peer,
+            offer.blocks.len(),
+            offer.microblocks.len(),
+            nk
+        );
+
+        let msg = self.sign_for_peer(nk, StacksMessageType::BlocksOffer(offer))?;
+        self.relay_signed_message(nk, msg)?;
+        self.pending_block_offers.insert(nk.clone(), pending);
+        Ok(())
+    }
+
+    /// Which of the offered data don't we have yet?  We want an anchored block if we've never
+    /// stored it (processed or not), and a microblock stream if we haven't processed the
+    /// microblocks that the given anchored block confirms.
+    pub fn find_wanted_blocks(
+        chainstate: &StacksChainState,
+        offer: &BlocksOfferData,
+    ) -> Result<BlocksOfferData, net_error> {
+        let mut wanted = BlocksOfferData {
+            blocks: vec![],
+            microblocks: vec![],
+        };
+        for block_id in offer.blocks.iter() {
+            if StacksChainState::load_staging_block_info(chainstate.db(), block_id)?.is_none() {
+                wanted.blocks.push(block_id.clone());
+            }
+        }
+        for child_block_id in offer.microblocks.iter() {
+            if !chainstate.has_processed_microblocks(child_block_id)? {
+                wanted.microblocks.push(child_block_id.clone());
+            }
+        }
+        Ok(wanted)
+    }
+
+    /// Answer a neighbor's `BlocksOffer` with the data we want pushed to us.  We always answer,
+    /// even if we want nothing, so the neighbor can forget the offer.
+    fn handle_unsolicited_BlocksOffer(
+        &mut self,
+        chainstate: &StacksChainState,
+        nk: &NeighborKey,
+        offer: &BlocksOfferData,
+    ) -> Result<(), net_error> {
+        let wanted = PeerNetwork::find_wanted_blocks(chainstate, offer)?;
+        debug!(
+            "{:?}: Accept {} of {} offered anchored blocks and {} of {} offered microblock streams from {:?}",
+            &self.local_peer,
+            wanted.blocks.len(),
+            offer.blocks.len(),
+            wanted.microblocks.len(),
+            offer.microblocks.len(),
+            nk
+        );
+        let msg = self.sign_for_peer(nk, StacksMessageType::BlocksOfferAccept(wanted))?;
+        self.relay_signed_message(nk, msg)
+    }
+
+    /// Push the data a neighbor accepted from our outstanding offer to it.
+    fn handle_unsolicited_BlocksOfferAccept(
+        &mut self,
+        nk: &NeighborKey,
+        accepted: &BlocksOfferData,
+    ) -> Result<(), net_error> {
+        let mut pending = match self.pending_block_offers.remove(nk) {
+            Some(pending) => pending,
+            None => {
+                debug!(
+                    "{:?}: Drop BlocksOfferAccept from {:?}: we have no outstanding offer",
+                    &self.local_peer, nk
+                );
+                self.record_peer_misbehavior(nk, PeerMisbehavior::UnsolicitedSpam);
+                return Ok(());
+            }
+        };
+
+        let (blocks, microblocks) = pending.take_accepted(accepted);
+        debug!(
+            "{:?}: AntiEntropy: {:?} accepted {} anchored blocks and {} microblock streams",
+            &self.local_peer,
+            nk,
+            blocks.len(),
+            microblocks.len()
+        );
+
+        if !blocks.is_empty() {
+            self.broadcast_message(
+                vec![nk.clone()],
+                vec![],
+                StacksMessageType::Blocks(BlocksData { blocks }),
+            );
+        }
+        for (index_anchor_block, microblocks) in microblocks.into_iter() {
+            self.broadcast_message(
+                vec![nk.clone()],
+                vec![],
+                StacksMessageType::Microblocks(MicroblocksData {
+                    index_anchor_block,
+                    microblocks,
+                }),
+            );
+        }
+        Ok(())
+    }
+
+    /// Handle anti-entropy block offers from a neighbor, and its answers to ours.
+    /// Returns the message if it isn't part of the block offer protocol.
+    pub fn handle_unsolicited_block_offer_message(
+        &mut self,
+        chainstate: &StacksChainState,
+        event_id: usize,
+        message: StacksMessage,
+    ) -> Option<StacksMessage> {
+        match message.payload {
+            StacksMessageType::BlocksOffer(_) | StacksMessageType::BlocksOfferAccept(_) => {}
+            _ => {
+                return Some(message);
+            }
+        }
+
+        let nk = match self.peers.get(&event_id) {
+            Some(convo) if convo.is_authenticated() => convo.to_neighbor_key(),
+            _ => {
+                // drop -- a correct peer will have authenticated before sending this message
+                test_debug!(
+                    "{:?}: Drop {} from unauthenticated event {}",
+                    &self.local_peer,
+                    message.payload.get_message_name(),
+                    event_id
+                );
+                return None;
+            }
+        };
+
+        let res = match message.payload {
+            StacksMessageType::BlocksOffer(ref offer) => {
+                self.handle_unsolicited_BlocksOffer(chainstate, &nk, offer)
+            }
+            StacksMessageType::BlocksOfferAccept(ref accepted) => {
+                self.handle_unsolicited_BlocksOfferAccept(&nk, accepted)
+            }
+            _ => unreachable!(),
+        };
+        if let Err(e) = res {
+            warn!(
+                "{:?}: Failed to handle {} from {:?}: {:?}",
+                &self.local_peer,
+                message.payload.get_message_name(),
+                &nk,
+                &e
+            );
+        }
+        None
+    }
+
+    /// Forget offers that were never answered, or that were made to neighbors we're no longer
+    /// connected to.  The next anti-entropy pass will offer the data again if it's still missing.
+    pub fn prune_pending_block_offers(&mut self) {
+        let now = get_epoch_time_secs();
+        let timeout = self.connection_opts.timeout;
+        let events = &self.events;
+        self.pending_block_offers
+            .retain(|nk, pending| events.contains_key(nk) && pending.offered_at + timeout >= now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::test::make_codec_test_block;
+
+    #[test]
+    fn test_pending_blocks_offer_take_accepted() {
+        let block = make_codec_test_block(5);
+        let ch = ConsensusHash([0x11; 20]);
+        let block_id = StacksBlockHeader::make_index_block_hash(&ch, &block.block_hash());
+        let parent_id = StacksBlockId([0x22; 32]);
+        let child_id = StacksBlockId([0x33; 32]);
+
+        let mut pending = PendingBlocksOffer::new(
+            vec![(ch.clone(), block.clone())],
+            vec![(parent_id.clone(), child_id.clone(), vec![])],
+        );
+
+        let offer = pending.to_offer();
+        assert_eq!(offer.blocks, vec![block_id.clone()]);
+        assert_eq!(offer.microblocks, vec![child_id.clone()]);
+
+        // asking for data we didn't offer gets nothing
+        let (blocks, microblocks) = pending.take_accepted(&BlocksOfferData {
+            blocks: vec![StacksBlockId([0x44; 32])],
+            microblocks: vec![parent_id.clone()],
+        });
+        assert!(blocks.is_empty());
+        assert!(microblocks.is_empty());
+
+        // only the accepted data is taken, and microblocks are pushed on their parent
+        let (blocks, microblocks) = pending.take_accepted(&BlocksOfferData {
+            blocks: vec![],
+            microblocks: vec![child_id.clone()],
+        });
+        assert!(blocks.is_empty());
+        assert_eq!(microblocks, vec![(parent_id.clone(), vec![])]);
+
+        let (blocks, microblocks) = pending.take_accepted(&BlocksOfferData {
+            blocks: vec![block_id.clone(), block_id.clone()],
+            microblocks: vec![child_id.clone()],
+        });
+        assert_eq!(blocks, vec![(ch, block)]);
+        assert!(microblocks.is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Capability negotiation.  A peer advertises the optional p2p features it implements as
+//! `ServiceFlags` bits in its handshake.  Peers that both advertise `ServiceFlags::CAPABILITIES`
+//! also send each other a `Capabilities` message right after the handshake, which says which
+//! version of each advertised capability they implement.  A capability that a peer advertises
+//! but doesn't send a version for (including every capability of a peer that doesn't send
+//! `Capabilities` at all) is at version 1.
+//!
+//! New message types are gated on capabilities:  a message that only some peers understand is
+//! only sent to peers that advertise the capability it belongs to (see `Capability::required_by`
+//! and `ConversationP2P::can_send`).  This way, a new p2p feature can roll out one node at a
+//! time, and can change its wire format later by bumping its version.
+
+use burnchains::BurnchainView;
+use net::chat::ConversationP2P;
+use net::db::LocalPeer;
+use net::Error as net_error;
+use net::{
+    CapabilitiesData, CapabilityVersion, ServiceFlags, StacksMessageID, StacksMessageType,
+    MAX_CAPABILITIES,
+};
+
+/// An optional p2p feature that a peer can advertise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Relay,
+    Rpc,
+    Encryption,
+    CompactBlocks,
+    MempoolTxs,
+    Compression,
+    BlockOffers,
+    Headers,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 8] = [
+        Capability::Relay,
+        Capability::Rpc,
+        Capability::Encryption,
+        Capability::CompactBlocks,
+        Capability::MempoolTxs,
+        Capability::Compression,
+        Capability::BlockOffers,
+        Capability::Headers,
+    ];
+
+    /// The handshake service bit that advertises this capability
+    pub fn service_flag(&self) -> u16 {
+        match *self {
+            Capability::Relay => ServiceFlags::RELAY as u16,
+            Capability::Rpc => ServiceFlags::RPC as u16,
+            Capability::Encryption => ServiceFlags::ENCRYPTION as u16,
+            Capability::CompactBlocks => ServiceFlags::COMPACT_BLOCKS as u16,
+            Capability::MempoolTxs => ServiceFlags::MEMPOOL_TXS as u16,
+            Capability::Compression => ServiceFlags::COMPRESSION as u16,
+            Capability::BlockOffers => ServiceFlags::BLOCK_OFFERS as u16,
+            Capability::Headers => ServiceFlags::HEADERS as u16,
+        }
+    }
+
+    pub fn from_service_flag(service: u16) -> Option<Capability> {
+        Capability::ALL
+            .iter()
+            .find(|cap| cap.service_flag() == service)
+            .copied()
+    }
+
+    /// The version of this capability that this node implements
+    pub fn local_version(&self) -> u16 {
+        match *self {
+            Capability::Relay
+            | Capability::Rpc
+            | Capability::Encryption
+            | Capability::CompactBlocks
+            | Capability::MempoolTxs
+            | Capability::Compression
+            | Capability::BlockOffers
+            | Capability::Headers => 1,
+        }
+    }
+
+    /// Is this capability advertised in the given handshake services?
+    pub fn is_advertised(&self, services: u16) -> bool {
+        services & self.service_flag() != 0
+    }
+
+    /// Which capability does a peer need to understand this message?  Messages that every peer
+    /// understands don't need one.
+    pub fn required_by(msg_id: StacksMessageID) -> Option<Capability> {
+        match msg_id {
+            StacksMessageID::CompactBlock
+            | StacksMessageID::GetBlockTxs
+            | StacksMessageID::BlockTxs => Some(Capability::CompactBlocks),
+            StacksMessageID::GetMempoolTxs | StacksMessageID::MempoolTxs => {
+                Some(Capability::MempoolTxs)
+            }
+            StacksMessageID::Compressed => Some(Capability::Compression),
+            StacksMessageID::BlocksOffer | StacksMessageID::BlocksOfferAccept => {
+                Some(Capability::BlockOffers)
+            }
+            StacksMessageID::GetHeaders | StacksMessageID::Headers => Some(Capability::Headers),
+            _ => None,
+        }
+    }
+}
+
+impl CapabilitiesData {
+    /// The versions of the capabilities we advertise in our handshake
+    pub fn from_local_services(services: u16) -> CapabilitiesData {
+        CapabilitiesData {
+            versions: Capability::ALL
+                .iter()
+                .filter(|cap| cap.is_advertised(services))
+                .map(|cap| CapabilityVersion {
+                    service: cap.service_flag(),
+                    version: cap.local_version(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ConversationP2P {
+    /// Does the remote peer advertise this capability?
+    pub fn supports(&self, cap: Capability) -> bool {
+        cap.is_advertised(self.peer_services)
+    }
+
+    /// Which version of this capability does the remote peer implement?  None if it doesn't
+    /// advertise it at all.
+    pub fn capability_version(&self, cap: Capability) -> Option<u16> {
+        if !self.supports(cap) {
+            return None;
+        }
+        Some(
+            *self
+                .peer_capability_versions
+                .get(&cap.service_flag())
+                .unwrap_or(&1),
+        )
+    }
+
+    /// Does the remote peer implement at least this version of this capability?
+    pub fn supports_version(&self, cap: Capability, min_version: u16) -> bool {
+        self.capability_version(cap)
+            .map(|version| version >= min_version)
+            .unwrap_or(false)
+    }
+
+    /// Will the remote peer understand this message?
+    pub fn can_send(&self, payload: &StacksMessageType) -> bool {
+        Capability::required_by(payload.get_message_id())
+            .map(|cap| self.supports(cap))
+            .unwrap_or(true)
+    }
+
+    /// Should we exchange capability versions with this peer once we've handshaked?
+    fn exchanges_capabilities(&self, local_peer: &LocalPeer) -> bool {
+        let flag = ServiceFlags::CAPABILITIES as u16;
+        local_peer.services & flag != 0 && self.peer_services & flag != 0
+    }
+
+    /// Send our capability versions to the remote peer, if it wants them.  Called once the
+    /// handshake completes.
+    pub fn send_capabilities(
+        &mut self,
+        local_peer: &LocalPeer,
+        chain_view: &BurnchainView,
+    ) -> Result<(), net_error> {
+        if !self.exchanges_capabilities(local_peer) {
+            return Ok(());
+        }
+        let payload = StacksMessageType::Capabilities(CapabilitiesData::from_local_services(
+            local_peer.services,
+        ));
+        let msg = self.sign_message(chain_view, &local_peer.private_key, payload)?;
+        let handle = self.relay_signed_message(msg)?;
+        self.reply_handles.push_back(handle);
+        Ok(())
+    }
+
+    /// Remember the remote peer's capability versions.  Versions for capabilities it doesn't
+    /// advertise are ignored, and so are versions for service bits we don't know about yet.
+    pub fn handle_capabilities(&mut self, data: &CapabilitiesData) {
+        self.peer_capability_versions.clear();
+        for cap_version in data.versions.iter().take(MAX_CAPABILITIES as usize) {
+            match Capability::from_service_flag(cap_version.service) {
+                Some(cap) if self.supports(cap) => {
+                    self.peer_capability_versions
+                        .insert(cap_version.service, cap_version.version);
+                }
+                _ => {
+                    test_debug!(
+                        "{:?}: ignoring version {} of service {:#x}",
+                        &self,
+                        cap_version.version,
+                        cap_version.service
+                    );
+                }
+            }
+        }
+        debug!(
+            "{:?}: capability versions are {:?}",
+            &self, &self.peer_capability_versions
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use burnchains::Burnchain;
+    use core::StacksEpoch;
+    use net::connection::ConnectionOptions;
+    use net::{BlocksOfferData, CompressedData, GetHeadersData, MempoolTxsData};
+
+    use crate::types::chainstate::StacksBlockId;
+
+    fn make_convo(services: u16) -> ConversationP2P {
+        let burnchain = Burnchain::regtest(":memory:");
+        let mut convo = ConversationP2P::new(
+            123,
+            456,
+            &burnchain,
+            &"127.0.0.1:20443".parse().unwrap(),
+            &ConnectionOptions::default(),
+            true,
+            0,
+            StacksEpoch::unit_test_pre_2_05(0),
+        );
+        convo.peer_services = services;
+        convo
+    }
+
+    #[test]
+    fn test_capability_service_flags() {
+        for cap in Capability::ALL.iter() {
+            assert_eq!(
+                Capability::from_service_flag(cap.service_flag()),
+                Some(*cap)
+            );
+        }
+        assert_eq!(
+            Capability::from_service_flag(ServiceFlags::MINER as u16),
+            None
+        );
+        assert_eq!(
+            Capability::from_service_flag(ServiceFlags::CAPABILITIES as u16),
+            None
+        );
+
+        let services = ServiceFlags::RELAY as u16
+            | ServiceFlags::COMPRESSION as u16
+            | ServiceFlags::CAPABILITIES as u16;
+        assert_eq!(
+            CapabilitiesData::from_local_services(services),
+            CapabilitiesData {
+                versions: vec![
+                    CapabilityVersion {
+                        service: ServiceFlags::RELAY as u16,
+                        version: 1,
+                    },
+                    CapabilityVersion {
+                        service: ServiceFlags::COMPRESSION as u16,
+                        version: 1,
+                    },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn test_capability_versions() {
+        let mut convo = make_convo(
+            ServiceFlags::RELAY as u16
+                | ServiceFlags::COMPACT_BLOCKS as u16
+                | ServiceFlags::CAPABILITIES as u16,
+        );
+
+        // advertised capabilities default to version 1
+        assert_eq!(convo.capability_version(Capability::Relay), Some(1));
+        assert_eq!(convo.capability_version(Capability::CompactBlocks), Some(1));
+        assert_eq!(convo.capability_version(Capability::Compression), None);
+
+        convo.handle_capabilities(&CapabilitiesData {
+            versions: vec![
+                CapabilityVersion {
+                    service: ServiceFlags::COMPACT_BLOCKS as u16,
+                    version: 3,
+                },
+                // not advertised
+                CapabilityVersion {
+                    service: ServiceFlags::COMPRESSION as u16,
+                    version: 2,
+                },
+                // unknown
+                CapabilityVersion {
+                    service: 0x8000,
+                    version: 2,
+                },
+            ],
+        });
+
+        assert_eq!(convo.capability_version(Capability::Relay), Some(1));
+        assert_eq!(convo.capability_version(Capability::CompactBlocks), Some(3));
+        assert_eq!(convo.capability_version(Capability::Compression), None);
+        assert_eq!(convo.peer_capability_versions.len(), 1);
+
+        assert!(convo.supports_version(Capability::CompactBlocks, 2));
+        assert!(!convo.supports_version(Capability::CompactBlocks, 4));
+        assert!(!convo.supports_version(Capability::Compression, 1));
+    }
+
+    #[test]
+    fn test_capability_gated_messages() {
+        let convo = make_convo(ServiceFlags::RELAY as u16 | ServiceFlags::MEMPOOL_TXS as u16);
+
+        assert!(convo.can_send(&StacksMessageType::GetNeighbors));
+        assert!(
+            convo.can_send(&StacksMessageType::MempoolTxs(MempoolTxsData {
+                txs: vec![]
+            }))
+        );
+        assert!(
+            !convo.can_send(&StacksMessageType::BlocksOffer(BlocksOfferData {
+                blocks: vec![],
+                microblocks: vec![],
+            }))
+        );
+        assert!(
+            !convo.can_send(&StacksMessageType::Compressed(CompressedData {
+                data: vec![]
+            }))
+        );
+        assert!(
+            !convo.can_send(&StacksMessageType::GetHeaders(GetHeadersData {
+                tip: StacksBlockId([0u8; 32]),
+                start_height: 0,
+                max_headers: 1,
+            }))
+        );
+    }
+}
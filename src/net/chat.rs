@@ -19,6 +19,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::convert::TryFrom;
+use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::mem;
@@ -37,6 +38,7 @@ use chainstate::stacks::db::StacksChainState;
 use chainstate::stacks::StacksPublicKey;
 use monitoring;
 use net::asn::ASEntry4;
+use net::capabilities::Capability;
 use net::codec::*;
 use net::connection::ConnectionOptions;
 use net::connection::ConnectionP2P;
@@ -44,7 +46,9 @@ use net::connection::ReplyHandleP2P;
 use net::db::PeerDB;
 use net::db::*;
 use net::neighbors::MAX_NEIGHBOR_BLOCK_DELAY;
+use net::noise::P2PTransport;
 use net::relay::*;
+use net::reputation::{PeerMisbehavior, MAX_REPUTATION};
 use net::Error as net_error;
 use net::GetBlocksInv;
 use net::GetPoxInv;
@@ -57,6 +61,7 @@ use net::GETPOXINV_MAX_BITLEN;
 use net::*;
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::log;
@@ -92,6 +97,9 @@ pub const BLOCK_POINT_LIFETIME: u64 = 600;
 
 pub const MAX_PEER_HEARTBEAT_INTERVAL: usize = 3600 * 6; // 6 hours
 
+/// How much each new round-trip time sample moves a peer's smoothed RTT (as in TCP's SRTT)
+pub const RTT_SMOOTHING: f64 = 0.125;
+
 /// Statistics on relayer hints in Stacks messages.  Used to deduce network choke points.
 #[derive(Debug, Clone)]
 pub struct RelayStats {
@@ -139,6 +147,11 @@ pub struct NeighborStats {
     pub microblocks_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub transaction_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub relayed_messages: HashMap<NeighborAddress, RelayStats>,
+    pub misbehavior: Vec<PeerMisbehavior>, // not yet charged against the peer's reputation
+    pub reputation: f64,                   // as of the last time the PeerNetwork checked
+    pub rtt_ms: Option<f64>,               // smoothed round-trip time of our requests
+    pub replies_rx: u64,                   // requests the peer answered
+    pub requests_timed_out: u64,           // requests the peer didn't answer in time
 }
 
 impl NeighborStats {
@@ -162,6 +175,11 @@ impl NeighborStats {
             microblocks_push_rx_counts: VecDeque::new(),
             transaction_push_rx_counts: VecDeque::new(),
             relayed_messages: HashMap::new(),
+            misbehavior: vec![],
+            reputation: MAX_REPUTATION,
+            rtt_ms: None,
+            replies_rx: 0,
+            requests_timed_out: 0,
         }
     }
 
@@ -220,6 +238,35 @@ impl NeighborStats {
         ret
     }
 
+    /// Record how long the peer took to answer one of our requests
+    pub fn add_rtt(&mut self, rtt_ms: u64) {
+        let sample = rtt_ms as f64;
+        self.rtt_ms = Some(match self.rtt_ms {
+            Some(rtt) => rtt + RTT_SMOOTHING * (sample - rtt),
+            None => sample,
+        });
+        self.replies_rx += 1;
+    }
+
+    /// What fraction of our requests that have either been answered or timed out were
+    /// answered?  None if none have.
+    pub fn get_request_success_rate(&self) -> Option<f64> {
+        let total = self.replies_rx + self.requests_timed_out;
+        if total == 0 {
+            return None;
+        }
+        Some((self.replies_rx as f64) / (total as f64))
+    }
+
+    /// Log misbehavior for the PeerNetwork to charge against this peer's reputation
+    pub fn add_misbehavior(&mut self, misbehavior: PeerMisbehavior) {
+        self.misbehavior.push(misbehavior);
+    }
+
+    pub fn take_misbehavior(&mut self) -> Vec<PeerMisbehavior> {
+        mem::take(&mut self.misbehavior)
+    }
+
     /// Get a peer's perceived health -- the last $NUM_HEALTH_POINTS successful messages divided by
     /// the total.
     pub fn get_health_score(&self) -> f64 {
@@ -300,12 +347,13 @@ pub struct ConversationP2P {
     pub peer_network_id: u32,
     pub peer_version: u32,
     pub peer_services: u16,
-    pub peer_addrbytes: PeerAddress,      // from socketaddr
-    pub peer_port: u16,                   // from socketaddr
-    pub handshake_addrbytes: PeerAddress, // from handshake
-    pub handshake_port: u16,              // from handshake
-    pub peer_heartbeat: u32,              // how often do we need to ping the remote peer?
-    pub peer_expire_block_height: u64,    // when does the peer's key expire?
+    pub peer_capability_versions: HashMap<u16, u16>, // from the peer's Capabilities message
+    pub peer_addrbytes: PeerAddress,                 // from socketaddr
+    pub peer_port: u16,                              // from socketaddr
+    pub handshake_addrbytes: PeerAddress,            // from handshake
+    pub handshake_port: u16,                         // from handshake
+    pub peer_heartbeat: u32, // how often do we need to ping the remote peer?
+    pub peer_expire_block_height: u64, // when does the peer's key expire?
 
     pub data_url: UrlString, // where does this peer's data live?  Set to a 0-length string if not known.
 
@@ -317,9 +365,15 @@ pub struct ConversationP2P {
 
     pub stats: NeighborStats,
 
+    // when we sent each of our requests that hasn't been answered yet (in ms), and its ttl
+    pending_requests: HashMap<u32, (u128, u64)>,
+
     // outbound replies
     pub reply_handles: VecDeque<ReplyHandleP2P>,
 
+    // encryption for the bytes on the wire
+    pub transport: P2PTransport,
+
     // system epochs
     epochs: Vec<StacksEpoch>,
 }
@@ -503,6 +557,7 @@ impl ConversationP2P {
             handshake_port: 0,
             peer_heartbeat: 0,
             peer_services: 0,
+            peer_capability_versions: HashMap::new(),
             peer_expire_block_height: 0,
 
             data_url: UrlString::try_from("".to_string()).unwrap(),
@@ -513,8 +568,11 @@ impl ConversationP2P {
             burnchain_stable_tip_burn_header_hash: BurnchainHeaderHash::zero(),
 
             stats: NeighborStats::new(outbound),
+            pending_requests: HashMap::new(),
             reply_handles: VecDeque::new(),
 
+            transport: P2PTransport::plaintext(),
+
             epochs: epochs,
         }
     }
@@ -523,6 +581,11 @@ impl ConversationP2P {
         self.connection.set_public_key(pubkey_opt);
     }
 
+    /// Set how this conversation's bytes go over the wire.  Must be called before any are sent.
+    pub fn set_transport(&mut self, transport: P2PTransport) {
+        self.transport = transport;
+    }
+
     pub fn to_neighbor_key(&self) -> NeighborKey {
         NeighborKey {
             peer_version: self.peer_version,
@@ -624,8 +687,8 @@ impl ConversationP2P {
     /// Does this remote neighbor support the mempool query interface?  It will if it has both
     /// RELAY and RPC bits set.
     pub fn supports_mempool_query(peer_services: u16) -> bool {
-        let expected_bits = (ServiceFlags::RELAY as u16) | (ServiceFlags::RPC as u16);
-        (peer_services & expected_bits) == expected_bits
+        Capability::Relay.is_advertised(peer_services)
+            && Capability::Rpc.is_advertised(peer_services)
     }
 
     /// Determine whether or not a given (height, burn_header_hash) pair _disagrees_ with our
@@ -790,6 +853,51 @@ impl ConversationP2P {
         rng.gen::<u32>()
     }
 
+    /// Does this remote neighbor accept compressed payloads?
+    pub fn supports_compression(peer_services: u16) -> bool {
+        Capability::Compression.is_advertised(peer_services)
+    }
+
+    /// Compress a large payload if we and the remote peer both support it, and if it actually
+    /// gets smaller.  Otherwise, hand it back as-is.
+    fn compress_payload(&self, payload: StacksMessageType) -> StacksMessageType {
+        if !self.connection.options.p2p_compression
+            || !ConversationP2P::supports_compression(self.peer_services)
+            || !payload.is_compressible()
+        {
+            return payload;
+        }
+        let size = payload.serialize_to_vec().len();
+        if (size as u64) < self.connection.options.p2p_compression_min_size {
+            return payload;
+        }
+        match CompressedData::compress(&payload) {
+            Ok(compressed) => {
+                if compressed.data.len() < size {
+                    test_debug!(
+                        "{:?}: compressed {} from {} to {} bytes",
+                        &self,
+                        payload.get_message_name(),
+                        size,
+                        compressed.data.len()
+                    );
+                    StacksMessageType::Compressed(compressed)
+                } else {
+                    payload
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "{:?}: failed to compress {}: {:?}",
+                    &self,
+                    payload.get_message_name(),
+                    &e
+                );
+                payload
+            }
+        }
+    }
+
     /// Generate a signed message for this conversation
     pub fn sign_message(
         &mut self,
@@ -797,6 +905,7 @@ impl ConversationP2P {
         private_key: &Secp256k1PrivateKey,
         payload: StacksMessageType,
     ) -> Result<StacksMessage, net_error> {
+        let payload = self.compress_payload(payload);
         let mut msg =
             StacksMessage::from_chain_view(self.version, self.network_id, chain_view, payload);
         msg.sign(self.next_seq(), private_key)?;
@@ -812,6 +921,7 @@ impl ConversationP2P {
         mut relay_hints: Vec<RelayData>,
         payload: StacksMessageType,
     ) -> Result<StacksMessage, net_error> {
+        let payload = self.compress_payload(payload);
         let mut msg =
             StacksMessage::from_chain_view(self.version, self.network_id, chain_view, payload);
         msg.relayers.append(&mut relay_hints);
@@ -831,6 +941,7 @@ impl ConversationP2P {
         payload: StacksMessageType,
         seq: u32,
     ) -> Result<StacksMessage, net_error> {
+        let payload = self.compress_payload(payload);
         let mut msg =
             StacksMessage::from_chain_view(self.version, self.network_id, chain_view, payload);
         msg.sign(seq, private_key)?;
@@ -932,6 +1043,8 @@ impl ConversationP2P {
         msg.consensus_serialize(&mut handle)?;
 
         self.stats.msgs_tx += 1;
+        self.pending_requests
+            .insert(msg.request_id(), (get_epoch_time_ms(), ttl));
 
         debug!(
             "{:?}: request-send({}) {} seq {}",
@@ -1033,9 +1146,23 @@ impl ConversationP2P {
     ) -> Result<bool, net_error> {
         let pubk = handshake_data.node_public_key.to_public_key()?;
 
+        // an encrypted session only talks to the peer who signed it
+        if let Err(e) = self.transport.check_remote_public_key(&pubk) {
+            debug!(
+                "{:?}: handshake key {} is not the key the encrypted session was signed with",
+                &self,
+                &to_hex(&pubk.to_bytes_compressed())
+            );
+            return Err(e);
+        }
+
         self.peer_version = preamble.peer_version;
         self.peer_network_id = preamble.network_id;
         self.peer_services = handshake_data.services;
+        if self.peer_services & (ServiceFlags::CAPABILITIES as u16) == 0 {
+            // it won't tell us its capability versions
+            self.peer_capability_versions.clear();
+        }
         self.peer_expire_block_height = handshake_data.expire_block_height;
         self.handshake_addrbytes = handshake_data.addrbytes.clone();
         self.handshake_port = handshake_data.port;
@@ -1170,7 +1297,8 @@ impl ConversationP2P {
             );
         }
 
-        let accept_data = HandshakeAcceptData::new(local_peer, self.heartbeat);
+        let accept_data =
+            HandshakeAcceptData::new(local_peer, &self.peer_addrbytes, self.heartbeat);
         let accept = StacksMessage::from_chain_view(
             self.version,
             self.network_id,
@@ -1484,6 +1612,91 @@ impl ConversationP2P {
         self.sign_and_reply(local_peer, burnchain_view, preamble, response)
     }
 
+    /// Create a response to an inbound GetBlockTxs request, but unsigned.
+    /// Replies with a NACK if we don't have the block, or if an index is out of range.
+    pub fn make_getblocktxs_response(
+        chainstate: &StacksChainState,
+        get_block_txs: &GetBlockTxsData,
+    ) -> Result<StacksMessageType, net_error> {
+        let block = match StacksChainState::load_block(
+            &chainstate.blocks_path,
+            &get_block_txs.consensus_hash,
+            &get_block_txs.block_hash,
+        )? {
+            Some(block) => block,
+            None => {
+                return Ok(StacksMessageType::Nack(NackData::new(
+                    NackErrorCodes::NoSuchBlock,
+                )));
+            }
+        };
+
+        let mut txs = Vec::with_capacity(get_block_txs.indexes.len());
+        for index in get_block_txs.indexes.iter() {
+            match block.txs.get(*index as usize) {
+                Some(tx) => txs.push(tx.clone()),
+                None => {
+                    return Ok(StacksMessageType::Nack(NackData::new(
+                        NackErrorCodes::InvalidMessage,
+                    )));
+                }
+            }
+        }
+
+        Ok(StacksMessageType::BlockTxs(BlockTxsData {
+            consensus_hash: get_block_txs.consensus_hash.clone(),
+            block_hash: get_block_txs.block_hash.clone(),
+            txs,
+        }))
+    }
+
+    /// Handle an inbound GetBlockTxs request.
+    /// Returns a reply handle to the generated message (possibly a nack)
+    fn handle_getblocktxs(
+        &mut self,
+        local_peer: &LocalPeer,
+        chainstate: &StacksChainState,
+        burnchain_view: &BurnchainView,
+        preamble: &Preamble,
+        get_block_txs: &GetBlockTxsData,
+    ) -> Result<ReplyHandleP2P, net_error> {
+        monitoring::increment_msg_counter("p2p_get_block_txs".to_string());
+
+        let response = ConversationP2P::make_getblocktxs_response(chainstate, get_block_txs)?;
+        debug!(
+            "{:?}: Handled GetBlockTxs for {}/{}. Reply {}",
+            &local_peer,
+            &get_block_txs.consensus_hash,
+            &get_block_txs.block_hash,
+            response.get_message_name()
+        );
+        self.sign_and_reply(local_peer, burnchain_view, preamble, response)
+    }
+
+    /// Handle an inbound GetHeaders request.
+    /// Returns a reply handle to the generated message (possibly a nack)
+    fn handle_getheaders(
+        &mut self,
+        local_peer: &LocalPeer,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        burnchain_view: &BurnchainView,
+        preamble: &Preamble,
+        get_headers: &GetHeadersData,
+    ) -> Result<ReplyHandleP2P, net_error> {
+        monitoring::increment_msg_counter("p2p_get_headers".to_string());
+
+        let response = ConversationP2P::make_getheaders_response(sortdb, chainstate, get_headers)?;
+        debug!(
+            "{:?}: Handled GetHeaders for {} from height {}. Reply {}",
+            &local_peer,
+            &get_headers.tip,
+            get_headers.start_height,
+            response.get_message_description()
+        );
+        self.sign_and_reply(local_peer, burnchain_view, preamble, response)
+    }
+
     /// Create a response an inbound GetPoxInv request, but unsigned.
     /// Returns a reply handle to the generated message (possibly a nack)
     pub fn make_getpoxinv_response(
@@ -1670,6 +1883,7 @@ impl ConversationP2P {
         if !self.process_relayers(local_peer, preamble, &relayers) {
             debug!("Drop pushed blocks -- invalid relayers {:?}", &relayers);
             self.stats.msgs_err += 1;
+            self.stats.add_misbehavior(PeerMisbehavior::InvalidMessage);
             return Err(net_error::InvalidMessage);
         }
 
@@ -1685,6 +1899,7 @@ impl ConversationP2P {
                 self.connection.options.max_block_push_bandwidth,
                 self.stats.get_block_push_bandwidth()
             );
+            self.stats.add_misbehavior(PeerMisbehavior::UnsolicitedSpam);
             return self
                 .reply_nack(local_peer, chain_view, preamble, NackErrorCodes::Throttled)
                 .and_then(|handle| Ok(Some(handle)));
@@ -1710,6 +1925,7 @@ impl ConversationP2P {
                 &relayers
             );
             self.stats.msgs_err += 1;
+            self.stats.add_misbehavior(PeerMisbehavior::InvalidMessage);
             return Err(net_error::InvalidMessage);
         }
 
@@ -1721,6 +1937,7 @@ impl ConversationP2P {
                 > (self.connection.options.max_microblocks_push_bandwidth as f64)
         {
             debug!("Neighbor {:?} exceeded max microblocks-push bandwidth of {} bytes/sec (currently at {})", &self.to_neighbor_key(), self.connection.options.max_microblocks_push_bandwidth, self.stats.get_microblocks_push_bandwidth());
+            self.stats.add_misbehavior(PeerMisbehavior::UnsolicitedSpam);
             return self
                 .reply_nack(local_peer, chain_view, preamble, NackErrorCodes::Throttled)
                 .and_then(|handle| Ok(Some(handle)));
@@ -1745,6 +1962,7 @@ impl ConversationP2P {
                 &relayers
             );
             self.stats.msgs_err += 1;
+            self.stats.add_misbehavior(PeerMisbehavior::InvalidMessage);
             return Err(net_error::InvalidMessage);
         }
 
@@ -1756,6 +1974,7 @@ impl ConversationP2P {
                 > (self.connection.options.max_transaction_push_bandwidth as f64)
         {
             debug!("Neighbor {:?} exceeded max transaction-push bandwidth of {} bytes/sec (currently at {})", &self.to_neighbor_key(), self.connection.options.max_transaction_push_bandwidth, self.stats.get_transaction_push_bandwidth());
+            self.stats.add_misbehavior(PeerMisbehavior::UnsolicitedSpam);
             return self
                 .reply_nack(local_peer, chain_view, preamble, NackErrorCodes::Throttled)
                 .and_then(|handle| Ok(Some(handle)));
@@ -1797,6 +2016,36 @@ impl ConversationP2P {
                 &msg.preamble,
                 get_blocks_inv,
             ),
+            StacksMessageType::GetBlockTxs(ref get_block_txs) => self.handle_getblocktxs(
+                local_peer,
+                chainstate,
+                chain_view,
+                &msg.preamble,
+                get_block_txs,
+            ),
+            StacksMessageType::GetHeaders(ref get_headers) => self.handle_getheaders(
+                local_peer,
+                sortdb,
+                chainstate,
+                chain_view,
+                &msg.preamble,
+                get_headers,
+            ),
+            StacksMessageType::CompactBlock(_) | StacksMessageType::BlockTxs(_) => {
+                // not handled here, but these count towards the block-push bandwidth
+                match self.validate_blocks_push(
+                    local_peer,
+                    chain_view,
+                    &msg.preamble,
+                    msg.relayers.clone(),
+                )? {
+                    Some(handle) => Ok(handle),
+                    None => {
+                        // will forward upstream
+                        return Ok(Some(msg));
+                    }
+                }
+            }
             StacksMessageType::Blocks(_) => {
                 monitoring::increment_stx_blocks_received_counter();
 
@@ -1873,7 +2122,7 @@ impl ConversationP2P {
     pub fn recv<R: Read>(&mut self, r: &mut R) -> Result<usize, net_error> {
         let mut total_recved = 0;
         loop {
-            let res = self.connection.recv_data(r);
+            let res = self.connection.recv_data(&mut self.transport.stream(r));
             match res {
                 Ok(num_recved) => {
                     total_recved += num_recved;
@@ -1897,6 +2146,11 @@ impl ConversationP2P {
                 }
             }
         }
+        if total_recved > 0 {
+            if let Some(peer) = self.metrics_label() {
+                monitoring::increment_p2p_peer_bytes_counter(&peer, "rx", total_recved as u64);
+            }
+        }
         test_debug!("{:?}: received {} bytes", self, total_recved);
         Ok(total_recved)
     }
@@ -1908,7 +2162,7 @@ impl ConversationP2P {
             // queue next byte slice
             self.try_flush()?;
 
-            let res = self.connection.send_data(w);
+            let res = self.connection.send_data(&mut self.transport.stream(w));
             match res {
                 Ok(num_sent) => {
                     total_sent += num_sent;
@@ -1925,6 +2179,21 @@ impl ConversationP2P {
                 }
             }
         }
+
+        // the transport may have handshake messages or encrypted frames of its own to send
+        match self.transport.flush_to(w) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => {
+                info!("{:?}: failed to send on P2P conversation: {:?}", self, &e);
+                return Err(net_error::WriteError(e));
+            }
+        }
+        if total_sent > 0 {
+            if let Some(peer) = self.metrics_label() {
+                monitoring::increment_p2p_peer_bytes_counter(&peer, "tx", total_sent as u64);
+            }
+        }
         test_debug!("{:?}: sent {} bytes", self, total_sent);
         Ok(total_sent)
     }
@@ -1984,6 +2253,7 @@ impl ConversationP2P {
                     );
                     self.stats.msgs_err += 1;
                     self.stats.add_healthpoint(false);
+                    self.stats.add_misbehavior(PeerMisbehavior::InvalidMessage);
                     return Err(e);
                 }
                 _ => {
@@ -2024,8 +2294,15 @@ impl ConversationP2P {
             StacksMessageType::HandshakeAccept(ref data) => {
                 test_debug!("{:?}: Got HandshakeAccept", &self);
                 self.handle_handshake_accept(&msg.preamble, data)
+                    .and_then(|_| self.send_capabilities(local_peer, burnchain_view))
                     .and_then(|_| Ok(None))
             }
+            StacksMessageType::Capabilities(ref data) => {
+                test_debug!("{:?}: Got Capabilities", &self);
+                consume = true;
+                self.handle_capabilities(data);
+                Ok(None)
+            }
             StacksMessageType::Ping(_) => {
                 test_debug!("{:?}: Got Ping", &self);
 
@@ -2096,6 +2373,7 @@ impl ConversationP2P {
                 if solicited {
                     test_debug!("{:?}: Got unauthenticated HandshakeAccept", &self);
                     self.handle_handshake_accept(&msg.preamble, data)
+                        .and_then(|_| self.send_capabilities(local_peer, burnchain_view))
                         .and_then(|_| Ok(None))
                 } else {
                     test_debug!("{:?}: Unsolicited unauthenticated HandshakeAccept", &self);
@@ -2237,8 +2515,14 @@ impl ConversationP2P {
                         reply.payload.get_message_name()
                     );
                     reply.sign(msg.preamble.seq, &local_peer.private_key)?;
+                    let accepted = matches!(reply.payload, StacksMessageType::HandshakeAccept(_));
                     let reply_handle = self.relay_signed_message(reply)?;
                     self.reply_handles.push_back(reply_handle);
+
+                    if accepted {
+                        // the remote peer now knows our public key
+                        self.send_capabilities(local_peer, burnchain_view)?;
+                    }
                 }
             }
 
@@ -2304,6 +2588,7 @@ impl ConversationP2P {
                         "{:?}: Fulfilled pending message request (type {} seq {})",
                         &self, _msgtype, _seq
                     );
+                    self.record_reply(_seq);
                 }
                 Some(msg) => {
                     if consumed {
@@ -2352,6 +2637,49 @@ impl ConversationP2P {
         for _ in 0..num_drained {
             self.stats.add_healthpoint(false);
         }
+        if num_drained > 0 {
+            self.stats.requests_timed_out += num_drained as u64;
+            if let Some(peer) = self.metrics_label() {
+                monitoring::increment_p2p_peer_requests_counter(
+                    &peer,
+                    "timeout",
+                    num_drained as u64,
+                );
+            }
+        }
+
+        let now_ms = get_epoch_time_ms();
+        self.pending_requests
+            .retain(|_, (sent_ms, ttl)| *sent_ms + (*ttl as u128) * 1000 >= now_ms);
+    }
+
+    /// Note how long the remote peer took to answer one of our requests
+    fn record_reply(&mut self, seq: u32) {
+        let sent_ms = match self.pending_requests.remove(&seq) {
+            Some((sent_ms, _)) => sent_ms,
+            None => {
+                return;
+            }
+        };
+        let rtt_ms = get_epoch_time_ms().saturating_sub(sent_ms) as u64;
+        self.stats.add_rtt(rtt_ms);
+        if let Some(peer) = self.metrics_label() {
+            monitoring::update_p2p_peer_rtt(&peer, (rtt_ms as f64) / 1000.0);
+            monitoring::increment_p2p_peer_requests_counter(&peer, "reply", 1);
+        }
+    }
+
+    /// How this peer is identified in per-peer metrics -- its advertised address, since it
+    /// connects to us from a different port each time.  None if it hasn't handshaked yet, or if
+    /// per-peer metrics aren't being collected.
+    pub fn metrics_label(&self) -> Option<String> {
+        if !self.is_authenticated() || !monitoring::p2p_peer_metrics_enabled() {
+            return None;
+        }
+        Some(format!(
+            "{}",
+            self.handshake_addrbytes.to_socketaddr(self.handshake_port)
+        ))
     }
 
     /// Get a ref to the conversation stats
@@ -3343,6 +3671,30 @@ mod test {
                 assert!(false);
             }
         }
+
+        // convo 1 timed both replies
+        let stats = convo_1.get_stats();
+        assert_eq!(stats.replies_rx, 2);
+        assert_eq!(stats.requests_timed_out, 0);
+        assert!(stats.rtt_ms.is_some());
+        assert_eq!(stats.get_request_success_rate(), Some(1.0));
+    }
+
+    #[test]
+    fn neighbor_stats_rtt() {
+        let mut stats = NeighborStats::new(true);
+        assert_eq!(stats.rtt_ms, None);
+        assert_eq!(stats.get_request_success_rate(), None);
+
+        stats.add_rtt(100);
+        assert_eq!(stats.rtt_ms, Some(100.0));
+
+        // smoothed
+        stats.add_rtt(900);
+        assert_eq!(stats.rtt_ms, Some(200.0));
+
+        stats.requests_timed_out += 2;
+        assert_eq!(stats.get_request_success_rate(), Some(0.5));
     }
 
     #[test]
@@ -4517,6 +4869,104 @@ mod test {
             assert_eq!(stats.num_bytes, (msg.preamble.payload_len - 1) as u64);
         }
     }
+
+    #[test]
+    fn convo_compress_payload() {
+        let conn_opts = ConnectionOptions {
+            p2p_compression: true,
+            p2p_compression_min_size: 128,
+            ..ConnectionOptions::default()
+        };
+        let socketaddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8090);
+
+        let burnchain = testing_burnchain_config();
+
+        let mut chain_view = BurnchainView {
+            burn_block_height: 12348,
+            burn_block_hash: BurnchainHeaderHash([0x11; 32]),
+            burn_stable_block_height: 12341,
+            burn_stable_block_hash: BurnchainHeaderHash([0x22; 32]),
+            last_burn_block_hashes: HashMap::new(),
+        };
+        chain_view.make_test_data();
+
+        let local_peer = LocalPeer::new(
+            123,
+            burnchain.network_id,
+            PeerAddress::from_ipv4(127, 0, 0, 1),
+            NETWORK_P2P_PORT,
+            None,
+            get_epoch_time_secs() + 123456,
+            UrlString::try_from("http://foo.com").unwrap(),
+        );
+        let mut convo = ConversationP2P::new(
+            123,
+            456,
+            &burnchain,
+            &socketaddr,
+            &conn_opts,
+            true,
+            0,
+            StacksEpoch::unit_test_pre_2_05(0),
+        );
+
+        let big_inv = StacksMessageType::BlocksInv(BlocksInvData {
+            bitlen: 4096,
+            block_bitvec: vec![0xff; 512],
+            microblocks_bitvec: vec![0x00; 512],
+        });
+        let small_inv = StacksMessageType::BlocksInv(BlocksInvData {
+            bitlen: 8,
+            block_bitvec: vec![0xff],
+            microblocks_bitvec: vec![0x00],
+        });
+        let ping = StacksMessageType::Ping(PingData { nonce: 123 });
+
+        // peer hasn't said it supports compression
+        let msg = convo
+            .sign_reply(&chain_view, &local_peer.private_key, big_inv.clone(), 123)
+            .unwrap();
+        assert_eq!(msg.payload, big_inv);
+
+        convo.peer_services = (ServiceFlags::RELAY as u16) | (ServiceFlags::COMPRESSION as u16);
+
+        // big payloads get compressed, and decompress to the original
+        let msg = convo
+            .sign_reply(&chain_view, &local_peer.private_key, big_inv.clone(), 123)
+            .unwrap();
+        match msg.payload {
+            StacksMessageType::Compressed(ref data) => {
+                assert_eq!(data.decompress().unwrap(), big_inv);
+            }
+            _ => panic!("not compressed: {:?}", &msg.payload),
+        }
+        let msg_bytes = msg.serialize_to_vec();
+        let msg_2 = StacksMessage::consensus_deserialize(&mut &msg_bytes[..]).unwrap();
+        assert_eq!(msg_2.payload, big_inv);
+
+        let msg = convo
+            .sign_message(&chain_view, &local_peer.private_key, big_inv.clone())
+            .unwrap();
+        assert_eq!(msg.payload.get_message_id(), StacksMessageID::Compressed);
+
+        // small payloads and control messages are left alone
+        let msg = convo
+            .sign_reply(&chain_view, &local_peer.private_key, small_inv.clone(), 123)
+            .unwrap();
+        assert_eq!(msg.payload, small_inv);
+
+        let msg = convo
+            .sign_reply(&chain_view, &local_peer.private_key, ping.clone(), 123)
+            .unwrap();
+        assert_eq!(msg.payload, ping);
+
+        // nothing gets compressed if we turn it off
+        convo.connection.options.p2p_compression = false;
+        let msg = convo
+            .sign_reply(&chain_view, &local_peer.private_key, big_inv.clone(), 123)
+            .unwrap();
+        assert_eq!(msg.payload, big_inv);
+    }
 }
 
 // TODO: test bandwidth limits
@@ -21,6 +21,7 @@ use std::io::prelude::*;
 use std::io::Read;
 use std::mem;
 
+use libflate::zlib;
 use rand;
 use rand::Rng;
 use sha2::Digest;
@@ -51,12 +52,14 @@ use util::secp256k1::MESSAGE_SIGNATURE_ENCODED_SIZE;
 use util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
 
 use crate::codec::{
-    read_next, write_next, Error as codec_error, StacksMessageCodec, MAX_RELAYERS_LEN,
-    PREAMBLE_ENCODED_SIZE,
+    check_length_prefix, read_next, write_next, Error as codec_error, StacksMessageCodec,
+    MAX_FRAME_LEN, MAX_RELAYERS_LEN, NEIGHBOR_ADDRESS_ENCODED_SIZE, PREAMBLE_ENCODED_SIZE,
+    RELAY_DATA_ENCODED_SIZE,
 };
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::BurnchainHeaderHash;
 use crate::types::chainstate::StacksBlockHeader;
+use crate::types::chainstate::StacksBlockId;
 use crate::types::StacksPublicKeyBuffer;
 
 impl_stacks_message_codec_for_int!(u8; [0; 1]);
@@ -552,6 +555,37 @@ impl StacksMessageCodec for MicroblocksData {
     }
 }
 
+impl StacksMessageCodec for ExportedBlockData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.consensus_hash)?;
+        write_next(fd, &self.block)?;
+        write_next(fd, &self.parent_consensus_hash)?;
+        write_next(fd, &self.parent_microblocks)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<ExportedBlockData, codec_error> {
+        let consensus_hash = read_next(fd)?;
+        let block = {
+            let mut bound_read = BoundReader::from_reader(fd, MAX_BLOCK_LEN as u64);
+            read_next(&mut bound_read)
+        }?;
+        let parent_consensus_hash = read_next(fd)?;
+        let parent_microblocks: Vec<StacksMicroblock> = {
+            // loose upper-bound
+            let mut bound_read = BoundReader::from_reader(fd, MAX_MESSAGE_LEN as u64);
+            read_next(&mut bound_read)
+        }?;
+
+        Ok(ExportedBlockData {
+            consensus_hash,
+            block,
+            parent_consensus_hash,
+            parent_microblocks,
+        })
+    }
+}
+
 impl NeighborAddress {
     pub fn from_neighbor(n: &Neighbor) -> NeighborAddress {
         NeighborAddress {
@@ -599,10 +633,16 @@ impl StacksMessageCodec for NeighborsData {
 
 impl HandshakeData {
     pub fn from_local_peer(local_peer: &LocalPeer) -> HandshakeData {
-        let (addrbytes, port) = match local_peer.public_ip_address {
-            Some((ref public_addrbytes, ref port)) => (public_addrbytes.clone(), *port),
-            None => (local_peer.addrbytes.clone(), local_peer.port),
-        };
+        HandshakeData::from_local_peer_for(local_peer, None)
+    }
+
+    /// Make the handshake to send to the peer at `peer_addrbytes`, advertising our address in
+    /// its IP family if we have one
+    pub fn from_local_peer_for(
+        local_peer: &LocalPeer,
+        peer_addrbytes: Option<&PeerAddress>,
+    ) -> HandshakeData {
+        let (addrbytes, port) = local_peer.advertised_address(peer_addrbytes);
 
         // transmit the empty string if our data URL compels us to bind to the anynet address
         let data_url = if local_peer.data_url.has_routable_host() {
@@ -665,9 +705,13 @@ impl StacksMessageCodec for HandshakeData {
 }
 
 impl HandshakeAcceptData {
-    pub fn new(local_peer: &LocalPeer, heartbeat_interval: u32) -> HandshakeAcceptData {
+    pub fn new(
+        local_peer: &LocalPeer,
+        peer_addrbytes: &PeerAddress,
+        heartbeat_interval: u32,
+    ) -> HandshakeAcceptData {
         HandshakeAcceptData {
-            handshake: HandshakeData::from_local_peer(local_peer),
+            handshake: HandshakeData::from_local_peer_for(local_peer, Some(peer_addrbytes)),
             heartbeat_interval: heartbeat_interval,
         }
     }
@@ -801,6 +845,322 @@ impl StacksMessageCodec for MemPoolSyncData {
     }
 }
 
+impl StacksMessageCodec for ShortTxid {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        fd.write_all(&self.0).map_err(codec_error::WriteError)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<ShortTxid, codec_error> {
+        let mut bytes = [0u8; 6];
+        fd.read_exact(&mut bytes).map_err(codec_error::ReadError)?;
+        Ok(ShortTxid(bytes))
+    }
+}
+
+impl StacksMessageCodec for PrefilledTransaction {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.index)?;
+        write_next(fd, &self.tx)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<PrefilledTransaction, codec_error> {
+        let index: u16 = read_next(fd)?;
+        let tx: StacksTransaction = read_next(fd)?;
+        Ok(PrefilledTransaction { index, tx })
+    }
+}
+
+/// Are these transaction indexes strictly ascending?
+fn tx_indexes_ascending<I: Iterator<Item = u16>>(indexes: I) -> bool {
+    let mut last = None;
+    for index in indexes {
+        if let Some(last) = last {
+            if index <= last {
+                return false;
+            }
+        }
+        last = Some(index);
+    }
+    true
+}
+
+impl StacksMessageCodec for CompactBlockData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.consensus_hash)?;
+        write_next(fd, &self.header)?;
+        write_next(fd, &self.nonce)?;
+        write_next(fd, &self.short_txids)?;
+        write_next(fd, &self.prefilled_txs)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<CompactBlockData, codec_error> {
+        let consensus_hash: ConsensusHash = read_next(fd)?;
+        let header: StacksBlockHeader = read_next(fd)?;
+        let nonce: u64 = read_next(fd)?;
+        let short_txids: Vec<ShortTxid> = read_next_at_most(fd, u16::MAX as u32)?;
+        let prefilled_txs: Vec<PrefilledTransaction> = {
+            // loose upper-bound
+            let mut bound_read = BoundReader::from_reader(fd, MAX_MESSAGE_LEN as u64);
+            read_next_at_most(&mut bound_read, u16::MAX as u32)
+        }?;
+
+        let num_txs = short_txids.len() + prefilled_txs.len();
+        if num_txs > (u16::MAX as usize) + 1 {
+            return Err(codec_error::DeserializeError(
+                "Invalid CompactBlockData: too many transactions".to_string(),
+            ));
+        }
+        if !tx_indexes_ascending(prefilled_txs.iter().map(|ptx| ptx.index)) {
+            return Err(codec_error::DeserializeError(
+                "Invalid CompactBlockData: prefilled transactions out of order".to_string(),
+            ));
+        }
+        if let Some(last) = prefilled_txs.last() {
+            if (last.index as usize) >= num_txs {
+                return Err(codec_error::DeserializeError(
+                    "Invalid CompactBlockData: prefilled transaction index out of range"
+                        .to_string(),
+                ));
+            }
+        }
+
+        Ok(CompactBlockData {
+            consensus_hash,
+            header,
+            nonce,
+            short_txids,
+            prefilled_txs,
+        })
+    }
+}
+
+impl StacksMessageCodec for GetBlockTxsData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.consensus_hash)?;
+        write_next(fd, &self.block_hash)?;
+        write_next(fd, &self.indexes)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<GetBlockTxsData, codec_error> {
+        let consensus_hash: ConsensusHash = read_next(fd)?;
+        let block_hash: BlockHeaderHash = read_next(fd)?;
+        let indexes: Vec<u16> = read_next_at_most(fd, (u16::MAX as u32) + 1)?;
+
+        if !tx_indexes_ascending(indexes.iter().copied()) {
+            return Err(codec_error::DeserializeError(
+                "Invalid GetBlockTxsData: indexes out of order".to_string(),
+            ));
+        }
+
+        Ok(GetBlockTxsData {
+            consensus_hash,
+            block_hash,
+            indexes,
+        })
+    }
+}
+
+impl StacksMessageCodec for BlockTxsData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.consensus_hash)?;
+        write_next(fd, &self.block_hash)?;
+        write_next(fd, &self.txs)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<BlockTxsData, codec_error> {
+        let consensus_hash: ConsensusHash = read_next(fd)?;
+        let block_hash: BlockHeaderHash = read_next(fd)?;
+        let txs: Vec<StacksTransaction> = {
+            // loose upper-bound
+            let mut bound_read = BoundReader::from_reader(fd, MAX_MESSAGE_LEN as u64);
+            read_next_at_most(&mut bound_read, (u16::MAX as u32) + 1)
+        }?;
+
+        Ok(BlockTxsData {
+            consensus_hash,
+            block_hash,
+            txs,
+        })
+    }
+}
+
+impl StacksMessageCodec for GetMempoolTxsData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.filter)?;
+        write_next(fd, &self.max_txs)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<GetMempoolTxsData, codec_error> {
+        let filter: MemPoolSyncData = read_next(fd)?;
+        let max_txs: u32 = read_next(fd)?;
+        Ok(GetMempoolTxsData { filter, max_txs })
+    }
+}
+
+impl StacksMessageCodec for MempoolTxsData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.txs)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<MempoolTxsData, codec_error> {
+        let txs: Vec<StacksTransaction> = {
+            // loose upper-bound
+            let mut bound_read = BoundReader::from_reader(fd, MAX_MESSAGE_LEN as u64);
+            read_next_at_most(&mut bound_read, u32::MAX)
+        }?;
+        Ok(MempoolTxsData { txs })
+    }
+}
+
+impl StacksMessageCodec for BlocksOfferData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.blocks)?;
+        write_next(fd, &self.microblocks)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<BlocksOfferData, codec_error> {
+        let blocks: Vec<StacksBlockId> = read_next_at_most(fd, BLOCKS_PUSHED_MAX)?;
+        let microblocks: Vec<StacksBlockId> = read_next_at_most(fd, BLOCKS_PUSHED_MAX)?;
+        Ok(BlocksOfferData {
+            blocks,
+            microblocks,
+        })
+    }
+}
+
+impl StacksMessageCodec for CapabilityVersion {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.service)?;
+        write_next(fd, &self.version)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<CapabilityVersion, codec_error> {
+        let service: u16 = read_next(fd)?;
+        let version: u16 = read_next(fd)?;
+        Ok(CapabilityVersion { service, version })
+    }
+}
+
+impl StacksMessageCodec for CapabilitiesData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.versions)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<CapabilitiesData, codec_error> {
+        let versions: Vec<CapabilityVersion> = read_next_at_most(fd, MAX_CAPABILITIES)?;
+        Ok(CapabilitiesData { versions })
+    }
+}
+
+impl StacksMessageCodec for GetHeadersData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.tip)?;
+        write_next(fd, &self.start_height)?;
+        write_next(fd, &self.max_headers)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<GetHeadersData, codec_error> {
+        let tip: StacksBlockId = read_next(fd)?;
+        let start_height: u64 = read_next(fd)?;
+        let max_headers: u32 = read_next(fd)?;
+        Ok(GetHeadersData {
+            tip,
+            start_height,
+            max_headers,
+        })
+    }
+}
+
+impl StacksMessageCodec for HeadersData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.tip)?;
+        write_next(fd, &self.tip_height)?;
+        write_next(fd, &self.headers)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<HeadersData, codec_error> {
+        let tip: StacksBlockId = read_next(fd)?;
+        let tip_height: u64 = read_next(fd)?;
+        let headers: Vec<ExtendedStacksHeader> = read_next_at_most(fd, MAX_HEADERS as u32)?;
+        Ok(HeadersData {
+            tip,
+            tip_height,
+            headers,
+        })
+    }
+}
+
+impl StacksMessageCodec for CompressedData {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.data)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<CompressedData, codec_error> {
+        let data: Vec<u8> = read_next_at_most(fd, MAX_MESSAGE_LEN)?;
+        Ok(CompressedData { data })
+    }
+}
+
+impl CompressedData {
+    /// Compress a message payload
+    pub fn compress(payload: &StacksMessageType) -> Result<CompressedData, codec_error> {
+        let mut encoder = zlib::Encoder::new(vec![]).map_err(codec_error::WriteError)?;
+        payload.consensus_serialize(&mut encoder)?;
+        let data = encoder
+            .finish()
+            .into_result()
+            .map_err(codec_error::WriteError)?;
+        Ok(CompressedData { data })
+    }
+
+    /// Decompress the message payload.  The payload may not itself be compressed, and may not
+    /// decompress to more than MAX_MESSAGE_LEN bytes.
+    pub fn decompress(&self) -> Result<StacksMessageType, codec_error> {
+        let mut decoded = vec![];
+        zlib::Decoder::new(&self.data[..])
+            .and_then(|decoder| {
+                decoder
+                    .take((MAX_MESSAGE_LEN as u64) + 1)
+                    .read_to_end(&mut decoded)
+            })
+            .map_err(|e| {
+                codec_error::DeserializeError(format!("Failed to decompress payload: {:?}", &e))
+            })?;
+        if decoded.len() > MAX_MESSAGE_LEN as usize {
+            return Err(codec_error::DeserializeError(
+                "Decompressed payload is too big".to_string(),
+            ));
+        }
+        if decoded.first() == Some(&(StacksMessageID::Compressed as u8)) {
+            return Err(codec_error::DeserializeError(
+                "Compressed payload is itself compressed".to_string(),
+            ));
+        }
+
+        let mut cursor = io::Cursor::new(&decoded[..]);
+        let payload: StacksMessageType = read_next(&mut cursor)?;
+        if cursor.position() as usize != decoded.len() {
+            return Err(codec_error::DeserializeError(
+                "Decompressed payload has trailing bytes".to_string(),
+            ));
+        }
+        Ok(payload)
+    }
+}
+
 impl StacksMessageCodec for RelayData {
     fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
         write_next(fd, &self.peer)?;
@@ -839,9 +1199,35 @@ impl StacksMessageType {
             StacksMessageType::Pong(ref _m) => StacksMessageID::Pong,
             StacksMessageType::NatPunchRequest(ref _m) => StacksMessageID::NatPunchRequest,
             StacksMessageType::NatPunchReply(ref _m) => StacksMessageID::NatPunchReply,
+            StacksMessageType::CompactBlock(ref _m) => StacksMessageID::CompactBlock,
+            StacksMessageType::GetBlockTxs(ref _m) => StacksMessageID::GetBlockTxs,
+            StacksMessageType::BlockTxs(ref _m) => StacksMessageID::BlockTxs,
+            StacksMessageType::GetMempoolTxs(ref _m) => StacksMessageID::GetMempoolTxs,
+            StacksMessageType::MempoolTxs(ref _m) => StacksMessageID::MempoolTxs,
+            StacksMessageType::Compressed(ref _m) => StacksMessageID::Compressed,
+            StacksMessageType::BlocksOffer(ref _m) => StacksMessageID::BlocksOffer,
+            StacksMessageType::BlocksOfferAccept(ref _m) => StacksMessageID::BlocksOfferAccept,
+            StacksMessageType::Capabilities(ref _m) => StacksMessageID::Capabilities,
+            StacksMessageType::GetHeaders(ref _m) => StacksMessageID::GetHeaders,
+            StacksMessageType::Headers(ref _m) => StacksMessageID::Headers,
         }
     }
 
+    /// Is this the kind of payload that's worth compressing, if it's big enough?
+    pub fn is_compressible(&self) -> bool {
+        matches!(
+            *self,
+            StacksMessageType::Neighbors(_)
+                | StacksMessageType::BlocksInv(_)
+                | StacksMessageType::PoxInv(_)
+                | StacksMessageType::Blocks(_)
+                | StacksMessageType::Microblocks(_)
+                | StacksMessageType::BlockTxs(_)
+                | StacksMessageType::MempoolTxs(_)
+                | StacksMessageType::Headers(_)
+        )
+    }
+
     pub fn get_message_name(&self) -> &'static str {
         match *self {
             StacksMessageType::Handshake(ref _m) => "Handshake",
@@ -863,6 +1249,17 @@ impl StacksMessageType {
             StacksMessageType::Pong(ref _m) => "Pong",
             StacksMessageType::NatPunchRequest(ref _m) => "NatPunchRequest",
             StacksMessageType::NatPunchReply(ref _m) => "NatPunchReply",
+            StacksMessageType::CompactBlock(ref _m) => "CompactBlock",
+            StacksMessageType::GetBlockTxs(ref _m) => "GetBlockTxs",
+            StacksMessageType::BlockTxs(ref _m) => "BlockTxs",
+            StacksMessageType::GetMempoolTxs(ref _m) => "GetMempoolTxs",
+            StacksMessageType::MempoolTxs(ref _m) => "MempoolTxs",
+            StacksMessageType::Compressed(ref _m) => "Compressed",
+            StacksMessageType::BlocksOffer(ref _m) => "BlocksOffer",
+            StacksMessageType::BlocksOfferAccept(ref _m) => "BlocksOfferAccept",
+            StacksMessageType::Capabilities(ref _m) => "Capabilities",
+            StacksMessageType::GetHeaders(ref _m) => "GetHeaders",
+            StacksMessageType::Headers(ref _m) => "Headers",
         }
     }
 
@@ -921,6 +1318,53 @@ impl StacksMessageType {
             StacksMessageType::NatPunchReply(ref m) => {
                 format!("NatPunchReply({},{}:{})", m.nonce, &m.addrbytes, m.port)
             }
+            StacksMessageType::CompactBlock(ref m) => format!(
+                "CompactBlock({}/{},{},{})",
+                &m.consensus_hash,
+                &m.header.block_hash(),
+                m.short_txids.len(),
+                m.prefilled_txs.len()
+            ),
+            StacksMessageType::GetBlockTxs(ref m) => format!(
+                "GetBlockTxs({}/{},{:?})",
+                &m.consensus_hash, &m.block_hash, &m.indexes
+            ),
+            StacksMessageType::BlockTxs(ref m) => format!(
+                "BlockTxs({}/{},{})",
+                &m.consensus_hash,
+                &m.block_hash,
+                m.txs.len()
+            ),
+            StacksMessageType::GetMempoolTxs(ref m) => {
+                format!("GetMempoolTxs({})", m.max_txs)
+            }
+            StacksMessageType::MempoolTxs(ref m) => format!("MempoolTxs({})", m.txs.len()),
+            StacksMessageType::Compressed(ref m) => format!("Compressed({})", m.data.len()),
+            StacksMessageType::BlocksOffer(ref m) => {
+                format!("BlocksOffer({},{})", m.blocks.len(), m.microblocks.len())
+            }
+            StacksMessageType::BlocksOfferAccept(ref m) => format!(
+                "BlocksOfferAccept({},{})",
+                m.blocks.len(),
+                m.microblocks.len()
+            ),
+            StacksMessageType::Capabilities(ref m) => format!(
+                "Capabilities({})",
+                m.versions
+                    .iter()
+                    .map(|v| format!("{:#x}:{}", v.service, v.version))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            StacksMessageType::GetHeaders(ref m) => {
+                format!(
+                    "GetHeaders({},{},{})",
+                    &m.tip, m.start_height, m.max_headers
+                )
+            }
+            StacksMessageType::Headers(ref m) => {
+                format!("Headers({},{},{})", &m.tip, m.tip_height, m.headers.len())
+            }
         }
     }
 }
@@ -954,6 +1398,19 @@ impl StacksMessageCodec for StacksMessageID {
             x if x == StacksMessageID::Pong as u8 => StacksMessageID::Pong,
             x if x == StacksMessageID::NatPunchRequest as u8 => StacksMessageID::NatPunchRequest,
             x if x == StacksMessageID::NatPunchReply as u8 => StacksMessageID::NatPunchReply,
+            x if x == StacksMessageID::CompactBlock as u8 => StacksMessageID::CompactBlock,
+            x if x == StacksMessageID::GetBlockTxs as u8 => StacksMessageID::GetBlockTxs,
+            x if x == StacksMessageID::BlockTxs as u8 => StacksMessageID::BlockTxs,
+            x if x == StacksMessageID::GetMempoolTxs as u8 => StacksMessageID::GetMempoolTxs,
+            x if x == StacksMessageID::MempoolTxs as u8 => StacksMessageID::MempoolTxs,
+            x if x == StacksMessageID::Compressed as u8 => StacksMessageID::Compressed,
+            x if x == StacksMessageID::BlocksOffer as u8 => StacksMessageID::BlocksOffer,
+            x if x == StacksMessageID::BlocksOfferAccept as u8 => {
+                StacksMessageID::BlocksOfferAccept
+            }
+            x if x == StacksMessageID::Capabilities as u8 => StacksMessageID::Capabilities,
+            x if x == StacksMessageID::GetHeaders as u8 => StacksMessageID::GetHeaders,
+            x if x == StacksMessageID::Headers as u8 => StacksMessageID::Headers,
             _ => {
                 return Err(codec_error::DeserializeError(
                     "Unknown message ID".to_string(),
@@ -987,6 +1444,17 @@ impl StacksMessageCodec for StacksMessageType {
             StacksMessageType::Pong(ref m) => write_next(fd, m)?,
             StacksMessageType::NatPunchRequest(ref nonce) => write_next(fd, nonce)?,
             StacksMessageType::NatPunchReply(ref m) => write_next(fd, m)?,
+            StacksMessageType::CompactBlock(ref m) => write_next(fd, m)?,
+            StacksMessageType::GetBlockTxs(ref m) => write_next(fd, m)?,
+            StacksMessageType::BlockTxs(ref m) => write_next(fd, m)?,
+            StacksMessageType::GetMempoolTxs(ref m) => write_next(fd, m)?,
+            StacksMessageType::MempoolTxs(ref m) => write_next(fd, m)?,
+            StacksMessageType::Compressed(ref m) => write_next(fd, m)?,
+            StacksMessageType::BlocksOffer(ref m) => write_next(fd, m)?,
+            StacksMessageType::BlocksOfferAccept(ref m) => write_next(fd, m)?,
+            StacksMessageType::Capabilities(ref m) => write_next(fd, m)?,
+            StacksMessageType::GetHeaders(ref m) => write_next(fd, m)?,
+            StacksMessageType::Headers(ref m) => write_next(fd, m)?,
         }
         Ok(())
     }
@@ -1064,6 +1532,51 @@ impl StacksMessageCodec for StacksMessageType {
                 let m: NatPunchData = read_next(fd)?;
                 StacksMessageType::NatPunchReply(m)
             }
+            StacksMessageID::CompactBlock => {
+                let m: CompactBlockData = read_next(fd)?;
+                StacksMessageType::CompactBlock(m)
+            }
+            StacksMessageID::GetBlockTxs => {
+                let m: GetBlockTxsData = read_next(fd)?;
+                StacksMessageType::GetBlockTxs(m)
+            }
+            StacksMessageID::BlockTxs => {
+                let m: BlockTxsData = read_next(fd)?;
+                StacksMessageType::BlockTxs(m)
+            }
+            StacksMessageID::GetMempoolTxs => {
+                let m: GetMempoolTxsData = read_next(fd)?;
+                StacksMessageType::GetMempoolTxs(m)
+            }
+            StacksMessageID::MempoolTxs => {
+                let m: MempoolTxsData = read_next(fd)?;
+                StacksMessageType::MempoolTxs(m)
+            }
+            StacksMessageID::Compressed => {
+                // hand back what the sender compressed
+                let m: CompressedData = read_next(fd)?;
+                m.decompress()?
+            }
+            StacksMessageID::BlocksOffer => {
+                let m: BlocksOfferData = read_next(fd)?;
+                StacksMessageType::BlocksOffer(m)
+            }
+            StacksMessageID::BlocksOfferAccept => {
+                let m: BlocksOfferData = read_next(fd)?;
+                StacksMessageType::BlocksOfferAccept(m)
+            }
+            StacksMessageID::Capabilities => {
+                let m: CapabilitiesData = read_next(fd)?;
+                StacksMessageType::Capabilities(m)
+            }
+            StacksMessageID::GetHeaders => {
+                let m: GetHeadersData = read_next(fd)?;
+                StacksMessageType::GetHeaders(m)
+            }
+            StacksMessageID::Headers => {
+                let m: HeadersData = read_next(fd)?;
+                StacksMessageType::Headers(m)
+            }
             StacksMessageID::Reserved => {
                 return Err(codec_error::DeserializeError(
                     "Unsupported message ID 'reserved'".to_string(),
@@ -1256,6 +1769,99 @@ impl StacksP2P {
     }
 }
 
+// Lower bounds on the encoded sizes of the items in vector payloads, comfortably under the
+// smallest valid encodings.  Only used to check that length prefixes are plausible.
+const MIN_BLOCK_ENCODED_SIZE: u32 = 200;
+const MIN_MICROBLOCK_ENCODED_SIZE: u32 = 100;
+const MIN_TRANSACTION_ENCODED_SIZE: u32 = 40;
+const MIN_BLOCK_HEADER_ENCODED_SIZE: u32 = 200;
+
+/// Check a p2p frame -- the relayers and payload that follow a preamble -- against strict bounds
+/// before deserializing it.  Only the frame's length prefixes and message ID are read, so a peer
+/// can't make us allocate much for a frame that turns out to be invalid:
+/// * the frame can't be longer than `MAX_FRAME_LEN`, and all of it has to be in `bytes`
+/// * there can be at most `MAX_RELAYERS_LEN` relayers, and they have to fit in the frame
+/// * the message ID has to be one we know
+/// * if the payload is a vector, its length prefix can't claim more items than its message type
+///   allows, or than fit in the rest of the frame
+pub fn validate_frame(preamble: &Preamble, bytes: &[u8]) -> Result<(), codec_error> {
+    let frame_len = preamble.payload_len as usize;
+    if frame_len > MAX_FRAME_LEN as usize {
+        return Err(codec_error::OverflowError(format!(
+            "Frame is too big ({} > {})",
+            frame_len, MAX_FRAME_LEN
+        )));
+    }
+    if bytes.len() < frame_len {
+        return Err(codec_error::UnderflowError(format!(
+            "Frame is incomplete ({} < {})",
+            bytes.len(),
+            frame_len
+        )));
+    }
+
+    let frame = &bytes[0..frame_len];
+    let mut cursor = io::Cursor::new(frame);
+    let bytes_left = |cursor: &io::Cursor<&[u8]>| frame_len - (cursor.position() as usize);
+
+    let num_relayers: u32 = read_next(&mut cursor)?;
+    check_length_prefix(
+        num_relayers,
+        MAX_RELAYERS_LEN,
+        RELAY_DATA_ENCODED_SIZE,
+        bytes_left(&cursor),
+    )?;
+    cursor
+        .set_position(cursor.position() + (num_relayers as u64) * (RELAY_DATA_ENCODED_SIZE as u64));
+
+    let msg_id: StacksMessageID = read_next(&mut cursor)?;
+    let (skip, max_items, min_item_size) = match msg_id {
+        StacksMessageID::Reserved => {
+            return Err(codec_error::DeserializeError(
+                "Unsupported message ID 'reserved'".to_string(),
+            ));
+        }
+        StacksMessageID::Neighbors => (0, MAX_NEIGHBORS_DATA_LEN, NEIGHBOR_ADDRESS_ENCODED_SIZE),
+        StacksMessageID::BlocksAvailable | StacksMessageID::MicroblocksAvailable => {
+            // (ConsensusHash, BurnchainHeaderHash)
+            (0, BLOCKS_AVAILABLE_MAX_LEN, 20 + 32)
+        }
+        StacksMessageID::Blocks => (0, BLOCKS_PUSHED_MAX, 20 + MIN_BLOCK_ENCODED_SIZE),
+        StacksMessageID::Microblocks => {
+            // after the anchor block's StacksBlockId
+            (32, u32::MAX, MIN_MICROBLOCK_ENCODED_SIZE)
+        }
+        StacksMessageID::MempoolTxs => (0, u32::MAX, MIN_TRANSACTION_ENCODED_SIZE),
+        StacksMessageID::BlocksOffer | StacksMessageID::BlocksOfferAccept => {
+            (0, BLOCKS_PUSHED_MAX, 32)
+        }
+        StacksMessageID::Capabilities => (0, MAX_CAPABILITIES, 4),
+        StacksMessageID::Headers => {
+            // after the tip's StacksBlockId and height; each header comes with its consensus
+            // hash and parent's StacksBlockId
+            (
+                32 + 8,
+                MAX_HEADERS as u32,
+                20 + MIN_BLOCK_HEADER_ENCODED_SIZE + 32,
+            )
+        }
+        StacksMessageID::Compressed => (0, MAX_MESSAGE_LEN, 1),
+        _ => {
+            return Ok(());
+        }
+    };
+
+    if bytes_left(&cursor) < skip {
+        return Err(codec_error::UnderflowError(
+            "Not enough bytes to form the payload".to_string(),
+        ));
+    }
+    cursor.set_position(cursor.position() + (skip as u64));
+
+    let num_items: u32 = read_next(&mut cursor)?;
+    check_length_prefix(num_items, max_items, min_item_size, bytes_left(&cursor))
+}
+
 impl ProtocolFamily for StacksP2P {
     type Preamble = Preamble;
     type Message = StacksMessage;
@@ -1306,6 +1912,8 @@ impl ProtocolFamily for StacksP2P {
             ));
         }
 
+        validate_frame(preamble, bytes)?;
+
         let mut cursor = io::Cursor::new(&bytes[0..(preamble.payload_len as usize)]);
         let (relayers, payload) = StacksMessage::deserialize_body(&mut cursor)?;
         let message = StacksMessage {
@@ -1983,6 +2591,236 @@ pub mod test {
         check_codec_and_corruption::<NatPunchData>(&data, &bytes);
     }
 
+    #[test]
+    fn codec_GetBlockTxsData() {
+        let data = GetBlockTxsData {
+            consensus_hash: ConsensusHash([0x11; 20]),
+            block_hash: BlockHeaderHash([0x22; 32]),
+            indexes: vec![1, 0x0203],
+        };
+        let mut bytes = vec![0x11; 20];
+        bytes.extend_from_slice(&[0x22; 32]);
+        bytes.extend_from_slice(&[
+            // length
+            0x00, 0x00, 0x00, 0x02, // indexes
+            0x00, 0x01, 0x02, 0x03,
+        ]);
+
+        check_codec_and_corruption::<GetBlockTxsData>(&data, &bytes);
+
+        // indexes must be strictly ascending
+        let mut out_of_order = data.clone();
+        out_of_order.indexes = vec![0x0203, 1];
+        assert!(check_deserialize_failure(&out_of_order));
+
+        let mut duplicate = data.clone();
+        duplicate.indexes = vec![1, 1];
+        assert!(check_deserialize_failure(&duplicate));
+    }
+
+    #[test]
+    fn codec_GetMempoolTxsData() {
+        let data = GetMempoolTxsData {
+            filter: MemPoolSyncData::TxTags([0x11; 32], vec![TxTag([0x22; 8])]),
+            max_txs: 0x01020304,
+        };
+        let mut bytes = vec![MemPoolSyncDataID::TxTags.to_u8()];
+        bytes.extend_from_slice(&[0x11; 32]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        bytes.extend_from_slice(&[0x22; 8]);
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+
+        check_codec_and_corruption::<GetMempoolTxsData>(&data, &bytes);
+    }
+
+    #[test]
+    fn codec_BlocksOfferData() {
+        let data = BlocksOfferData {
+            blocks: vec![StacksBlockId([0x11; 32]), StacksBlockId([0x22; 32])],
+            microblocks: vec![StacksBlockId([0x33; 32])],
+        };
+        let mut bytes = vec![0x00, 0x00, 0x00, 0x02];
+        bytes.extend_from_slice(&[0x11; 32]);
+        bytes.extend_from_slice(&[0x22; 32]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        bytes.extend_from_slice(&[0x33; 32]);
+
+        check_codec_and_corruption::<BlocksOfferData>(&data, &bytes);
+
+        // can't offer more than a block push's worth
+        let too_many = BlocksOfferData {
+            blocks: vec![StacksBlockId([0x11; 32]); (BLOCKS_PUSHED_MAX + 1) as usize],
+            microblocks: vec![],
+        };
+        assert!(check_deserialize_failure(&too_many));
+    }
+
+    #[test]
+    fn codec_GetHeadersData() {
+        let data = GetHeadersData {
+            tip: StacksBlockId([0x11; 32]),
+            start_height: 0x0102030405060708,
+            max_headers: 0x090a0b0c,
+        };
+        let mut bytes = vec![0x11; 32];
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        bytes.extend_from_slice(&[0x09, 0x0a, 0x0b, 0x0c]);
+
+        check_codec_and_corruption::<GetHeadersData>(&data, &bytes);
+    }
+
+    #[test]
+    fn codec_HeadersData() {
+        let header = ExtendedStacksHeader {
+            consensus_hash: ConsensusHash([0x22; 20]),
+            header: StacksBlockHeader::genesis_block_header(),
+            parent_block_id: StacksBlockId([0x33; 32]),
+        };
+        let data = HeadersData {
+            tip: StacksBlockId([0x11; 32]),
+            tip_height: 0x0102030405060708,
+            headers: vec![header.clone()],
+        };
+        let mut bytes = vec![0x11; 32];
+        bytes.extend_from_slice(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        header.consensus_serialize(&mut bytes).unwrap();
+
+        check_codec_and_corruption::<HeadersData>(&data, &bytes);
+
+        // can't send more than MAX_HEADERS
+        let too_many = HeadersData {
+            tip: StacksBlockId([0x11; 32]),
+            tip_height: 0,
+            headers: vec![header; MAX_HEADERS + 1],
+        };
+        assert!(check_deserialize_failure(&too_many));
+    }
+
+    #[test]
+    fn codec_CapabilitiesData() {
+        let data = CapabilitiesData {
+            versions: vec![
+                CapabilityVersion {
+                    service: ServiceFlags::COMPACT_BLOCKS as u16,
+                    version: 2,
+                },
+                CapabilityVersion {
+                    service: 0x8000,
+                    version: 0x0102,
+                },
+            ],
+        };
+        let bytes = vec![
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x08, 0x00, 0x02, 0x80, 0x00, 0x01, 0x02,
+        ];
+
+        check_codec_and_corruption::<CapabilitiesData>(&data, &bytes);
+
+        let too_many = CapabilitiesData {
+            versions: vec![
+                CapabilityVersion {
+                    service: 0x01,
+                    version: 1,
+                };
+                (MAX_CAPABILITIES + 1) as usize
+            ],
+        };
+        assert!(check_deserialize_failure(&too_many));
+    }
+
+    #[test]
+    fn codec_CompactBlockData() {
+        let data = CompactBlockData {
+            consensus_hash: ConsensusHash([0x11; 20]),
+            header: StacksBlockHeader::genesis_block_header(),
+            nonce: 0x0102030405060708,
+            short_txids: vec![ShortTxid([0x33; 6]), ShortTxid([0x44; 6])],
+            prefilled_txs: vec![],
+        };
+        let mut bytes = vec![0x11; 20];
+        data.header.consensus_serialize(&mut bytes).unwrap();
+        bytes.extend_from_slice(&[
+            // nonce
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // short txids
+            0x00, 0x00, 0x00, 0x02, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x44, 0x44, 0x44, 0x44,
+            0x44, 0x44, // prefilled txs
+            0x00, 0x00, 0x00, 0x00,
+        ]);
+
+        check_codec_and_corruption::<CompactBlockData>(&data, &bytes);
+    }
+
+    #[test]
+    fn codec_CompressedData() {
+        let payload = StacksMessageType::BlocksInv(BlocksInvData {
+            bitlen: 4096,
+            block_bitvec: vec![0xff; 512],
+            microblocks_bitvec: vec![0x00; 512],
+        });
+        let payload_bytes = payload.serialize_to_vec();
+
+        let compressed = CompressedData::compress(&payload).unwrap();
+        assert!(compressed.data.len() < payload_bytes.len());
+        assert_eq!(compressed.decompress().unwrap(), payload);
+
+        // the codec hands back what was compressed
+        let compressed_bytes = StacksMessageType::Compressed(compressed.clone()).serialize_to_vec();
+        assert_eq!(compressed_bytes[0], StacksMessageID::Compressed as u8);
+        assert_eq!(
+            StacksMessageType::consensus_deserialize(&mut &compressed_bytes[..]).unwrap(),
+            payload
+        );
+
+        // signatures cover the compressed bytes
+        let privkey = Secp256k1PrivateKey::new();
+        let pubkey_buf =
+            StacksPublicKeyBuffer::from_public_key(&Secp256k1PublicKey::from_private(&privkey));
+        let mut msg = StacksMessage::new(
+            PEER_VERSION_TESTNET,
+            0x9abcdef0,
+            12345,
+            &BurnchainHeaderHash([0x11; 32]),
+            12339,
+            &BurnchainHeaderHash([0x22; 32]),
+            StacksMessageType::Compressed(compressed.clone()),
+        );
+        msg.sign(444, &privkey).unwrap();
+        msg.verify_secp256k1(&pubkey_buf).unwrap();
+        assert_eq!(
+            msg.preamble.payload_len as usize,
+            4 + compressed_bytes.len()
+        );
+
+        let msg_bytes = msg.serialize_to_vec();
+        let msg_2 = StacksMessage::consensus_deserialize(&mut &msg_bytes[..]).unwrap();
+        assert_eq!(msg_2.preamble, msg.preamble);
+        assert_eq!(msg_2.payload, payload);
+
+        // can't nest compressed payloads
+        let nested = CompressedData::compress(&StacksMessageType::Compressed(compressed)).unwrap();
+        assert!(check_deserialize(nested.decompress()));
+
+        // garbage doesn't decompress
+        let garbage = CompressedData {
+            data: vec![0x01, 0x02, 0x03, 0x04],
+        };
+        assert!(check_deserialize(garbage.decompress()));
+        let garbage_bytes = StacksMessageType::Compressed(garbage).serialize_to_vec();
+        assert!(check_deserialize(StacksMessageType::consensus_deserialize(
+            &mut &garbage_bytes[..]
+        )));
+
+        // trailing bytes are rejected
+        let mut encoder = zlib::Encoder::new(vec![]).unwrap();
+        encoder.write_all(&payload_bytes).unwrap();
+        encoder.write_all(&[0x00]).unwrap();
+        let trailing = CompressedData {
+            data: encoder.finish().into_result().unwrap(),
+        };
+        assert!(check_deserialize(trailing.decompress()));
+    }
+
     #[test]
     fn codec_StacksMessage() {
         let payloads: Vec<StacksMessageType> = vec![
@@ -2099,6 +2937,56 @@ pub mod test {
                 port: 12345,
                 nonce: 0x12345678,
             }),
+            StacksMessageType::CompactBlock(CompactBlockData {
+                consensus_hash: ConsensusHash([0x11; 20]),
+                header: StacksBlockHeader::genesis_block_header(),
+                nonce: 0x0102030405060708,
+                short_txids: vec![ShortTxid([0x33; 6])],
+                prefilled_txs: vec![],
+            }),
+            StacksMessageType::GetBlockTxs(GetBlockTxsData {
+                consensus_hash: ConsensusHash([0x11; 20]),
+                block_hash: BlockHeaderHash([0x22; 32]),
+                indexes: vec![0, 1, 2],
+            }),
+            StacksMessageType::BlockTxs(BlockTxsData {
+                consensus_hash: ConsensusHash([0x11; 20]),
+                block_hash: BlockHeaderHash([0x22; 32]),
+                txs: vec![],
+            }),
+            StacksMessageType::GetMempoolTxs(GetMempoolTxsData {
+                filter: MemPoolSyncData::TxTags([0x11; 32], vec![TxTag([0x22; 8])]),
+                max_txs: 128,
+            }),
+            StacksMessageType::MempoolTxs(MempoolTxsData { txs: vec![] }),
+            StacksMessageType::BlocksOffer(BlocksOfferData {
+                blocks: vec![StacksBlockId([0x11; 32])],
+                microblocks: vec![StacksBlockId([0x22; 32])],
+            }),
+            StacksMessageType::BlocksOfferAccept(BlocksOfferData {
+                blocks: vec![],
+                microblocks: vec![StacksBlockId([0x22; 32])],
+            }),
+            StacksMessageType::Capabilities(CapabilitiesData {
+                versions: vec![CapabilityVersion {
+                    service: ServiceFlags::BLOCK_OFFERS as u16,
+                    version: 1,
+                }],
+            }),
+            StacksMessageType::GetHeaders(GetHeadersData {
+                tip: StacksBlockId([0x11; 32]),
+                start_height: 1,
+                max_headers: 10,
+            }),
+            StacksMessageType::Headers(HeadersData {
+                tip: StacksBlockId([0x11; 32]),
+                tip_height: 1,
+                headers: vec![ExtendedStacksHeader {
+                    consensus_hash: ConsensusHash([0x22; 20]),
+                    header: StacksBlockHeader::genesis_block_header(),
+                    parent_block_id: StacksBlockId([0x33; 32]),
+                }],
+            }),
         ];
 
         let mut maximal_relayers: Vec<RelayData> = vec![];
@@ -2185,6 +3073,11 @@ pub mod test {
                 &payload
             );
             check_codec_and_corruption::<StacksMessage>(&stacks_message, &stacks_message_bytes);
+            validate_frame(
+                &preamble,
+                &stacks_message_bytes[(PREAMBLE_ENCODED_SIZE as usize)..],
+            )
+            .unwrap();
 
             // can't have too many relayers
             let mut preamble_too_many_relayers = preamble.clone();
@@ -2197,9 +3090,91 @@ pub mod test {
                 payload: payload.clone(),
             };
             assert!(check_deserialize_failure(&stacks_message_too_many_relayers));
+
+            let mut too_many_relayers_frame = too_many_relayer_bytes.clone();
+            too_many_relayers_frame.append(&mut payload_bytes.clone());
+            assert!(validate_frame(&preamble_too_many_relayers, &too_many_relayers_frame).is_err());
         }
     }
 
+    #[test]
+    fn codec_validate_frame() {
+        let make_preamble = |frame: &[u8]| Preamble {
+            peer_version: 0x01020304,
+            network_id: 0x05060708,
+            seq: 0x090a0b0c,
+            burn_block_height: 0x00001122,
+            burn_block_hash: BurnchainHeaderHash([0x11; 32]),
+            burn_stable_block_height: 0x00001111,
+            burn_stable_block_hash: BurnchainHeaderHash([0x22; 32]),
+            additional_data: 0x33333333,
+            signature: MessageSignature::from_raw(&vec![0x44; 65]),
+            payload_len: frame.len() as u32,
+        };
+        // no relayers, then the given payload
+        let make_frame = |payload: &[u8]| {
+            let mut frame = vec![0x00, 0x00, 0x00, 0x00];
+            frame.extend_from_slice(payload);
+            frame
+        };
+
+        let frame = make_frame(
+            &StacksMessageType::Neighbors(NeighborsData { neighbors: vec![] }).serialize_to_vec(),
+        );
+        validate_frame(&make_preamble(&frame), &frame).unwrap();
+
+        // the frame has to be all there
+        assert!(validate_frame(&make_preamble(&frame), &frame[..frame.len() - 1]).is_err());
+
+        // and not too big
+        let mut preamble = make_preamble(&frame);
+        preamble.payload_len = MAX_FRAME_LEN + 1;
+        assert!(validate_frame(&preamble, &frame).is_err());
+
+        // unknown and reserved message IDs
+        let frame = make_frame(&[0xfe]);
+        assert!(validate_frame(&make_preamble(&frame), &frame).is_err());
+        let frame = make_frame(&[StacksMessageID::Reserved as u8]);
+        assert!(validate_frame(&make_preamble(&frame), &frame).is_err());
+
+        // more neighbors than a Neighbors message can have
+        let mut payload = vec![StacksMessageID::Neighbors as u8];
+        payload.extend_from_slice(&(MAX_NEIGHBORS_DATA_LEN + 1).to_be_bytes());
+        payload.extend_from_slice(&vec![
+            0u8;
+            ((MAX_NEIGHBORS_DATA_LEN + 1) * NEIGHBOR_ADDRESS_ENCODED_SIZE)
+                as usize
+        ]);
+        let frame = make_frame(&payload);
+        assert!(validate_frame(&make_preamble(&frame), &frame).is_err());
+
+        // a few microblocks claiming to be billions
+        let mut payload = vec![StacksMessageID::Microblocks as u8];
+        payload.extend_from_slice(&[0x01; 32]);
+        payload.extend_from_slice(&u32::MAX.to_be_bytes());
+        payload.extend_from_slice(&vec![0u8; 1000]);
+        let frame = make_frame(&payload);
+        assert!(validate_frame(&make_preamble(&frame), &frame).is_err());
+
+        // a compressed payload longer than the frame
+        let mut payload = vec![StacksMessageID::Compressed as u8];
+        payload.extend_from_slice(&1000u32.to_be_bytes());
+        payload.extend_from_slice(&[0u8; 999]);
+        let frame = make_frame(&payload);
+        assert!(validate_frame(&make_preamble(&frame), &frame).is_err());
+
+        // a relayer that isn't there
+        let mut frame = vec![0x00, 0x00, 0x00, 0x01];
+        frame.extend_from_slice(&StacksMessageType::Ping(PingData { nonce: 1 }).serialize_to_vec());
+        assert!(validate_frame(&make_preamble(&frame), &frame).is_err());
+
+        // the p2p receiver checks frames before deserializing them
+        let mut protocol = StacksP2P::new();
+        assert!(protocol
+            .read_payload(&make_preamble(&frame), &frame)
+            .is_err());
+    }
+
     #[test]
     fn codec_sign_and_verify() {
         let privkey = Secp256k1PrivateKey::new();
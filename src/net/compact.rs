@@ -0,0 +1,764 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compact block relay.  Instead of pushing a whole anchored block to a peer, a node can push the
+//! block's header along with a 6-byte short ID for each of its transactions.  Most of those
+//! transactions will already be in the recipient's mempool, so the recipient rebuilds the block
+//! from there and only asks the sender for the ones it's missing (`GetBlockTxs` / `BlockTxs`).
+//!
+//! Short IDs are keyed by the block hash and a random nonce picked by the sender, so a peer
+//! can't grind out transactions whose short IDs collide in every block.  If a collision does
+//! make us rebuild the wrong block, the transaction Merkle root won't match the header, and we
+//! ask the sender for all of the transactions we filled in from the mempool.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::hash::Hasher;
+
+use siphasher::sip::SipHasher; // this is SipHash-2-4
+
+use burnchains::Txid;
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::burn::ConsensusHash;
+use chainstate::stacks::db::StacksChainState;
+use chainstate::stacks::{StacksBlock, StacksTransaction, TransactionPayload};
+use core::mempool::MemPoolDB;
+use net::p2p::PeerNetwork;
+use net::reputation::PeerMisbehavior;
+use net::Error as net_error;
+use net::*;
+use util::get_epoch_time_secs;
+use util::hash::{MerkleTree, Sha512Trunc256Sum};
+
+use crate::types::chainstate::{BlockHeaderHash, StacksBlockHeader, StacksBlockId};
+
+impl ShortTxid {
+    pub fn from_txid(keys: (u64, u64), txid: &Txid) -> ShortTxid {
+        let mut hasher = SipHasher::new_with_keys(keys.0, keys.1);
+        hasher.write(&txid.0);
+
+        let result_64 = hasher.finish().to_be_bytes();
+        let mut bytes = [0u8; 6];
+        bytes.copy_from_slice(&result_64[0..6]);
+        ShortTxid(bytes)
+    }
+}
+
+impl CompactBlockData {
+    /// Derive the SipHash keys for a block's short transaction IDs
+    pub fn short_txid_keys(block_hash: &BlockHeaderHash, nonce: u64) -> (u64, u64) {
+        let mut preimage = block_hash.as_bytes().to_vec();
+        preimage.extend_from_slice(&nonce.to_be_bytes());
+        let h = Sha512Trunc256Sum::from_data(&preimage);
+        let k0 = u64::from_le_bytes(h.0[0..8].try_into().expect("BUG: 8-byte slice"));
+        let k1 = u64::from_le_bytes(h.0[8..16].try_into().expect("BUG: 8-byte slice"));
+        (k0, k1)
+    }
+
+    /// Make a compact block out of a block.  The coinbase is always sent in full, since nobody
+    /// else has it, and so is any transaction whose short ID collides with an earlier one.
+    /// (A block is at most `MAX_BLOCK_LEN` bytes, so its transaction indexes fit in a u16)
+    pub fn from_block(
+        consensus_hash: &ConsensusHash,
+        block: &StacksBlock,
+        nonce: u64,
+    ) -> CompactBlockData {
+        let keys = CompactBlockData::short_txid_keys(&block.block_hash(), nonce);
+        let mut short_txids = vec![];
+        let mut prefilled_txs = vec![];
+        let mut seen = HashSet::new();
+        for (i, tx) in block.txs.iter().enumerate() {
+            let short_txid = ShortTxid::from_txid(keys, &tx.txid());
+            let is_coinbase = matches!(tx.payload, TransactionPayload::Coinbase(_));
+            if is_coinbase || !seen.insert(short_txid) {
+                prefilled_txs.push(PrefilledTransaction {
+                    index: i as u16,
+                    tx: tx.clone(),
+                });
+            } else {
+                short_txids.push(short_txid);
+            }
+        }
+        CompactBlockData {
+            consensus_hash: consensus_hash.clone(),
+            header: block.header.clone(),
+            nonce,
+            short_txids,
+            prefilled_txs,
+        }
+    }
+
+    pub fn num_txs(&self) -> usize {
+        self.short_txids.len() + self.prefilled_txs.len()
+    }
+}
+
+/// A compact block we're rebuilding.  Transactions we couldn't find in our mempool are requested
+/// from the peer that sent it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingCompactBlock {
+    pub consensus_hash: ConsensusHash,
+    pub header: StacksBlockHeader,
+    pub nonce: u64,
+    /// event ID of the peer that sent us the compact block
+    pub event_id: usize,
+    /// the compact block's relayers, to pass along with the rebuilt block
+    pub relayers: Vec<RelayData>,
+    /// short ID of each transaction, or None if it was prefilled
+    short_txids: Vec<Option<ShortTxid>>,
+    /// each transaction we have so far
+    txs: Vec<Option<StacksTransaction>>,
+    /// indexes we asked the sender for, and are waiting on
+    requested: Vec<u16>,
+    /// did we already ask for every transaction we didn't get in full?
+    requested_all: bool,
+    /// when we got the compact block
+    pub received_at: u64,
+}
+
+impl PendingCompactBlock {
+    pub fn new(
+        event_id: usize,
+        relayers: Vec<RelayData>,
+        data: CompactBlockData,
+        now: u64,
+    ) -> PendingCompactBlock {
+        let num_txs = data.num_txs();
+        let mut short_txids = Vec::with_capacity(num_txs);
+        let mut txs = Vec::with_capacity(num_txs);
+        let mut prefilled = data.prefilled_txs.into_iter().peekable();
+        let mut short = data.short_txids.into_iter();
+        for i in 0..num_txs {
+            if prefilled.peek().map(|ptx| ptx.index as usize) == Some(i) {
+                let ptx = prefilled.next().expect("BUG: peeked prefilled tx");
+                short_txids.push(None);
+                txs.push(Some(ptx.tx));
+            } else {
+                short_txids.push(short.next());
+                txs.push(None);
+            }
+        }
+        PendingCompactBlock {
+            consensus_hash: data.consensus_hash,
+            header: data.header,
+            nonce: data.nonce,
+            event_id,
+            relayers,
+            short_txids,
+            txs,
+            requested: vec![],
+            requested_all: false,
+            received_at: now,
+        }
+    }
+
+    pub fn index_block_hash(&self) -> StacksBlockId {
+        StacksBlockHeader::make_index_block_hash(&self.consensus_hash, &self.header.block_hash())
+    }
+
+    /// Fill in missing transactions from the given candidates (i.e. our mempool's transaction
+    /// IDs).  A short ID that matches more than one candidate is left for the sender to fill in.
+    pub fn fill_from<F>(&mut self, candidates: Vec<Txid>, mut load_tx: F) -> Result<(), net_error>
+    where
+        F: FnMut(&Txid) -> Result<Option<StacksTransaction>, net_error>,
+    {
+        let keys = CompactBlockData::short_txid_keys(&self.header.block_hash(), self.nonce);
+        let wanted: HashSet<ShortTxid> = self
+            .short_txids
+            .iter()
+            .zip(self.txs.iter())
+            .filter_map(|(short_txid_opt, tx_opt)| match (short_txid_opt, tx_opt) {
+                (Some(short_txid), None) => Some(*short_txid),
+                _ => None,
+            })
+            .collect();
+
+        let mut matches: HashMap<ShortTxid, Option<Txid>> = HashMap::new();
+        for txid in candidates.into_iter() {
+            let short_txid = ShortTxid::from_txid(keys, &txid);
+            if !wanted.contains(&short_txid) {
+                continue;
+            }
+            if matches.contains_key(&short_txid) {
+                // ambiguous
+                matches.insert(short_txid, None);
+            } else {
+                matches.insert(short_txid, Some(txid));
+            }
+        }
+
+        for i in 0..self.txs.len() {
+            if self.txs[i].is_some() {
+                continue;
+            }
+            let txid = match self.short_txids[i].and_then(|s| matches.get(&s).cloned().flatten()) {
+                Some(txid) => txid,
+                None => {
+                    continue;
+                }
+            };
+            self.txs[i] = load_tx(&txid)?;
+        }
+        Ok(())
+    }
+
+    /// Indexes of the transactions we don't have yet
+    pub fn missing(&self) -> Vec<u16> {
+        self.txs
+            .iter()
+            .enumerate()
+            .filter(|(_, tx_opt)| tx_opt.is_none())
+            .map(|(i, _)| i as u16)
+            .collect()
+    }
+
+    /// Forget every transaction we filled in ourselves, so we can ask the sender for all of them.
+    /// Returns false if we already did this once.
+    pub fn reset_filled(&mut self) -> bool {
+        if self.requested_all {
+            return false;
+        }
+        for i in 0..self.txs.len() {
+            if self.short_txids[i].is_some() {
+                self.txs[i] = None;
+            }
+        }
+        self.requested_all = true;
+        true
+    }
+
+    pub fn set_requested(&mut self, indexes: Vec<u16>) {
+        self.requested = indexes;
+    }
+
+    pub fn requested(&self) -> &[u16] {
+        &self.requested
+    }
+
+    /// Fill in the transactions we requested.
+    /// Returns false if the reply doesn't match the request.
+    pub fn fill_requested(&mut self, txs: Vec<StacksTransaction>) -> bool {
+        if txs.len() != self.requested.len() {
+            return false;
+        }
+        for (index, tx) in self.requested.iter().zip(txs.into_iter()) {
+            self.txs[*index as usize] = Some(tx);
+        }
+        self.requested.clear();
+        true
+    }
+
+    /// Assemble the block, if we have all of its transactions and they match the header
+    pub fn try_make_block(&self) -> Option<StacksBlock> {
+        let mut txs = Vec::with_capacity(self.txs.len());
+        for tx_opt in self.txs.iter() {
+            txs.push(tx_opt.clone()?);
+        }
+
+        let txid_vecs = txs.iter().map(|tx| tx.txid().as_bytes().to_vec()).collect();
+        let merkle_tree = MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs);
+        if merkle_tree.root() != self.header.tx_merkle_root {
+            return None;
+        }
+
+        Some(StacksBlock {
+            header: self.header.clone(),
+            txs,
+        })
+    }
+}
+
+impl PeerNetwork {
+    /// Ask a peer for some of a compact block's transactions
+    fn send_getblocktxs(
+        &mut self,
+        event_id: usize,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        indexes: Vec<u16>,
+    ) -> Result<(), net_error> {
+        let nk = match self.peers.get(&event_id) {
+            Some(convo) => convo.to_neighbor_key(),
+            None => {
+                return Err(net_error::PeerNotConnected);
+            }
+        };
+        debug!(
+            "{:?}: Request {} transactions of compact block {}/{} from {:?}",
+            &self.local_peer,
+            indexes.len(),
+            consensus_hash,
+            block_hash,
+            &nk
+        );
+        let payload = StacksMessageType::GetBlockTxs(GetBlockTxsData {
+            consensus_hash: consensus_hash.clone(),
+            block_hash: block_hash.clone(),
+            indexes,
+        });
+        let msg = self.sign_for_peer(&nk, payload)?;
+        self.relay_signed_message(&nk, msg)
+    }
+
+    /// Try to finish a compact block.  If we have all of its transactions, then return it as a
+    /// `Blocks` message so it can be handled like a pushed block.  Otherwise, ask the sender for
+    /// what we're missing and hold onto it.
+    fn try_finish_compact_block(
+        &mut self,
+        mut pending: PendingCompactBlock,
+        preamble: Preamble,
+    ) -> Option<StacksMessage> {
+        let mut missing = pending.missing();
+        if missing.is_empty() {
+            if let Some(block) = pending.try_make_block() {
+                debug!(
+                    "{:?}: Rebuilt compact block {}/{}",
+                    &self.local_peer,
+                    &pending.consensus_hash,
+                    &block.block_hash()
+                );
+                return Some(StacksMessage {
+                    preamble,
+                    relayers: pending.relayers,
+                    payload: StacksMessageType::Blocks(BlocksData {
+                        blocks: vec![(pending.consensus_hash, block)],
+                    }),
+                });
+            }
+
+            // short ID collision, or a bad sender
+            if !pending.reset_filled() {
+                info!(
+                    "{:?}: Compact block {}/{} does not match its header; dropping",
+                    &self.local_peer,
+                    &pending.consensus_hash,
+                    &pending.header.block_hash()
+                );
+                if let Some(nk) = self
+                    .peers
+                    .get(&pending.event_id)
+                    .map(|c| c.to_neighbor_key())
+                {
+                    self.record_peer_misbehavior(&nk, PeerMisbehavior::InvalidMessage);
+                }
+                return None;
+            }
+            debug!(
+                "{:?}: Compact block {}/{} does not match its header; requesting all transactions",
+                &self.local_peer,
+                &pending.consensus_hash,
+                &pending.header.block_hash()
+            );
+            missing = pending.missing();
+        }
+
+        if let Err(e) = self.send_getblocktxs(
+            pending.event_id,
+            &pending.consensus_hash,
+            &pending.header.block_hash(),
+            missing.clone(),
+        ) {
+            debug!(
+                "{:?}: Failed to request transactions for compact block {}/{}: {:?}",
+                &self.local_peer,
+                &pending.consensus_hash,
+                &pending.header.block_hash(),
+                &e
+            );
+            return None;
+        }
+        pending.set_requested(missing);
+        self.pending_compact_blocks
+            .insert(pending.index_block_hash(), pending);
+        None
+    }
+
+    /// Handle an unsolicited CompactBlock.  Returns the rebuilt block as a `Blocks` message if we
+    /// had all of its transactions.
+    fn handle_unsolicited_CompactBlock(
+        &mut self,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
+        event_id: usize,
+        message: StacksMessage,
+        buffer: bool,
+    ) -> Option<StacksMessage> {
+        let data = match message.payload {
+            StacksMessageType::CompactBlock(ref data) => data,
+            _ => {
+                return Some(message);
+            }
+        };
+        let block_hash = data.header.block_hash();
+        let index_block_hash =
+            StacksBlockHeader::make_index_block_hash(&data.consensus_hash, &block_hash);
+
+        let sn = match SortitionDB::get_block_snapshot_consensus(
+            &sortdb.conn(),
+            &data.consensus_hash,
+        ) {
+            Ok(Some(sn)) => sn,
+            Ok(None) => {
+                if buffer {
+                    debug!(
+                        "{:?}: Will buffer unsolicited CompactBlock({}) -- consensus hash not (yet) recognized",
+                        &self.local_peer, &index_block_hash
+                    );
+                    self.buffer_data_message(event_id, message);
+                } else {
+                    debug!(
+                        "{:?}: Will drop unsolicited CompactBlock({}) -- consensus hash not (yet) recognized",
+                        &self.local_peer, &index_block_hash
+                    );
+                }
+                return None;
+            }
+            Err(e) => {
+                info!(
+                    "{:?}: Failed to query block snapshot for {}: {:?}",
+                    &self.local_peer, &data.consensus_hash, &e
+                );
+                return None;
+            }
+        };
+
+        if !sn.pox_valid || sn.winning_stacks_block_hash != block_hash {
+            info!(
+                "{:?}: Ignoring compact block {} -- winning block was {} (sortition: {}, PoX-valid: {})",
+                &self.local_peer, &block_hash, &sn.winning_stacks_block_hash, sn.sortition, sn.pox_valid
+            );
+            return None;
+        }
+
+        if self.pending_compact_blocks.contains_key(&index_block_hash) {
+            debug!(
+                "{:?}: Already rebuilding compact block {}",
+                &self.local_peer, &index_block_hash
+            );
+            return None;
+        }
+
+        match StacksChainState::has_block_indexed(&chainstate.blocks_path, &index_block_hash) {
+            Ok(false) => {}
+            Ok(true) => {
+                debug!(
+                    "{:?}: Already have compact block {}",
+                    &self.local_peer, &index_block_hash
+                );
+                return None;
+            }
+            Err(e) => {
+                info!(
+                    "{:?}: Failed to check for block {}: {:?}",
+                    &self.local_peer, &index_block_hash, &e
+                );
+                return None;
+            }
+        }
+
+        let (preamble, relayers, data) = match message {
+            StacksMessage {
+                preamble,
+                relayers,
+                payload: StacksMessageType::CompactBlock(data),
+            } => (preamble, relayers, data),
+            _ => unreachable!(),
+        };
+
+        let mut pending = PendingCompactBlock::new(event_id, relayers, data, get_epoch_time_secs());
+        let candidates = match MemPoolDB::get_all_txids(mempool.conn()) {
+            Ok(txids) => txids,
+            Err(e) => {
+                warn!(
+                    "{:?}: Failed to load mempool transaction IDs: {:?}",
+                    &self.local_peer, &e
+                );
+                vec![]
+            }
+        };
+        let fill_res = pending.fill_from(candidates, |txid| {
+            MemPoolDB::get_tx(mempool.conn(), txid)
+                .map(|txinfo_opt| txinfo_opt.map(|txinfo| txinfo.tx))
+                .map_err(net_error::DBError)
+        });
+        if let Err(e) = fill_res {
+            warn!(
+                "{:?}: Failed to load mempool transactions for compact block {}: {:?}",
+                &self.local_peer, &index_block_hash, &e
+            );
+        }
+
+        debug!(
+            "{:?}: Compact block {} from event {} is missing {} of {} transactions",
+            &self.local_peer,
+            &index_block_hash,
+            event_id,
+            pending.missing().len(),
+            pending.txs.len()
+        );
+        self.try_finish_compact_block(pending, preamble)
+    }
+
+    /// Handle an unsolicited BlockTxs.  Returns the rebuilt block as a `Blocks` message if this
+    /// finished it.
+    fn handle_unsolicited_BlockTxs(
+        &mut self,
+        event_id: usize,
+        message: StacksMessage,
+    ) -> Option<StacksMessage> {
+        let (preamble, data) = match message {
+            StacksMessage {
+                preamble,
+                payload: StacksMessageType::BlockTxs(data),
+                ..
+            } => (preamble, data),
+            _ => {
+                return Some(message);
+            }
+        };
+        let index_block_hash =
+            StacksBlockHeader::make_index_block_hash(&data.consensus_hash, &data.block_hash);
+        let mut pending = match self.pending_compact_blocks.remove(&index_block_hash) {
+            Some(pending) => pending,
+            None => {
+                debug!(
+                    "{:?}: Drop BlockTxs for {} -- not rebuilding that block",
+                    &self.local_peer, &index_block_hash
+                );
+                return None;
+            }
+        };
+
+        if pending.event_id != event_id {
+            debug!(
+                "{:?}: Drop BlockTxs for {} from event {} -- requested from event {}",
+                &self.local_peer, &index_block_hash, event_id, pending.event_id
+            );
+            self.pending_compact_blocks
+                .insert(index_block_hash, pending);
+            return None;
+        }
+
+        if !pending.fill_requested(data.txs) {
+            info!(
+                "{:?}: BlockTxs for {} does not match our request for {} transactions",
+                &self.local_peer,
+                &index_block_hash,
+                pending.requested().len()
+            );
+            if let Some(nk) = self.peers.get(&event_id).map(|c| c.to_neighbor_key()) {
+                self.record_peer_misbehavior(&nk, PeerMisbehavior::InvalidMessage);
+            }
+            return None;
+        }
+
+        self.try_finish_compact_block(pending, preamble)
+    }
+
+    /// Handle an unsolicited compact-block relay message.
+    /// Returns the message that should be handled in its place, if any: a rebuilt block as a
+    /// `Blocks` message, or the message itself if it isn't part of compact block relay.
+    pub fn handle_unsolicited_compact_block_message(
+        &mut self,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
+        event_id: usize,
+        message: StacksMessage,
+        buffer: bool,
+    ) -> Option<StacksMessage> {
+        match message.payload {
+            StacksMessageType::CompactBlock(_) | StacksMessageType::BlockTxs(_) => {}
+            _ => {
+                return Some(message);
+            }
+        }
+
+        match self.peers.get(&event_id) {
+            Some(convo) if convo.is_authenticated() => {}
+            _ => {
+                // drop -- a correct peer will have authenticated before sending this message
+                test_debug!(
+                    "{:?}: Drop {} from unauthenticated event {}",
+                    &self.local_peer,
+                    message.payload.get_message_name(),
+                    event_id
+                );
+                return None;
+            }
+        }
+
+        match message.payload {
+            StacksMessageType::CompactBlock(_) => self.handle_unsolicited_CompactBlock(
+                sortdb, chainstate, mempool, event_id, message, buffer,
+            ),
+            _ => self.handle_unsolicited_BlockTxs(event_id, message),
+        }
+    }
+
+    /// Forget compact blocks whose missing transactions never arrived.  The downloader will fetch
+    /// them instead once our inventory says we don't have them.
+    pub fn prune_pending_compact_blocks(&mut self) {
+        let now = get_epoch_time_secs();
+        let timeout = self.connection_opts.compact_block_timeout;
+        let local_peer = &self.local_peer;
+        self.pending_compact_blocks
+            .retain(|index_block_hash, pending| {
+                if pending.received_at + timeout < now {
+                    debug!(
+                        "{:?}: Timed out rebuilding compact block {}",
+                        local_peer, index_block_hash
+                    );
+                    false
+                } else {
+                    true
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::*;
+    use util::secp256k1::Secp256k1PrivateKey;
+
+    use crate::types::chainstate::StacksAddress;
+
+    fn make_block(num_transfers: u64) -> StacksBlock {
+        let privk = Secp256k1PrivateKey::new();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let mut txs = vec![StacksTransaction::new(
+            TransactionVersion::Testnet,
+            auth.clone(),
+            TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+        )];
+        for i in 0..num_transfers {
+            let mut tx = StacksTransaction::new(
+                TransactionVersion::Testnet,
+                auth.clone(),
+                TransactionPayload::TokenTransfer(
+                    StacksAddress {
+                        version: 26,
+                        bytes: Hash160([0xff; 20]),
+                    }
+                    .to_account_principal(),
+                    123,
+                    TokenTransferMemo([0u8; 34]),
+                ),
+            );
+            tx.set_origin_nonce(i);
+            txs.push(tx);
+        }
+
+        let txid_vecs = txs.iter().map(|tx| tx.txid().as_bytes().to_vec()).collect();
+        let mut header = StacksBlockHeader::genesis_block_header();
+        header.tx_merkle_root = MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs).root();
+        StacksBlock { header, txs }
+    }
+
+    fn mempool_of(txs: &[StacksTransaction]) -> HashMap<Txid, StacksTransaction> {
+        txs.iter().map(|tx| (tx.txid(), tx.clone())).collect()
+    }
+
+    fn fill_from_mempool(
+        pending: &mut PendingCompactBlock,
+        mempool: &HashMap<Txid, StacksTransaction>,
+    ) {
+        pending
+            .fill_from(mempool.keys().cloned().collect(), |txid| {
+                Ok(mempool.get(txid).cloned())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_compact_block_prefills_coinbase() {
+        let block = make_block(5);
+        let ch = ConsensusHash([0x11; 20]);
+        let data = CompactBlockData::from_block(&ch, &block, 1234);
+
+        assert_eq!(data.num_txs(), 6);
+        assert_eq!(data.prefilled_txs.len(), 1);
+        assert_eq!(data.prefilled_txs[0].index, 0);
+        assert_eq!(data.prefilled_txs[0].tx, block.txs[0]);
+        assert_eq!(data.short_txids.len(), 5);
+
+        // short IDs depend on the nonce
+        let other = CompactBlockData::from_block(&ch, &block, 5678);
+        assert!(other.short_txids != data.short_txids);
+    }
+
+    #[test]
+    fn test_compact_block_rebuild_from_mempool() {
+        let block = make_block(5);
+        let ch = ConsensusHash([0x11; 20]);
+        let data = CompactBlockData::from_block(&ch, &block, 1234);
+
+        // have everything
+        let mut pending = PendingCompactBlock::new(1, vec![], data.clone(), 0);
+        fill_from_mempool(&mut pending, &mempool_of(&block.txs[1..]));
+        assert!(pending.missing().is_empty());
+        assert_eq!(pending.try_make_block(), Some(block.clone()));
+
+        // missing some
+        let mut pending = PendingCompactBlock::new(1, vec![], data.clone(), 0);
+        let mut mempool = mempool_of(&block.txs[1..]);
+        mempool.remove(&block.txs[2].txid());
+        mempool.remove(&block.txs[4].txid());
+        fill_from_mempool(&mut pending, &mempool);
+        assert_eq!(pending.missing(), vec![2, 4]);
+        assert_eq!(pending.try_make_block(), None);
+
+        pending.set_requested(pending.missing());
+
+        // wrong number of transactions
+        let mut bad = pending.clone();
+        assert!(!bad.fill_requested(vec![block.txs[2].clone()]));
+
+        assert!(pending.fill_requested(vec![block.txs[2].clone(), block.txs[4].clone()]));
+        assert!(pending.missing().is_empty());
+        assert_eq!(pending.try_make_block(), Some(block.clone()));
+    }
+
+    #[test]
+    fn test_compact_block_rebuild_wrong_txs() {
+        let block = make_block(3);
+        let ch = ConsensusHash([0x11; 20]);
+        let data = CompactBlockData::from_block(&ch, &block, 1234);
+
+        // a sender that gives us the wrong transaction produces a block that doesn't match its
+        // header
+        let mut pending = PendingCompactBlock::new(1, vec![], data, 0);
+        fill_from_mempool(&mut pending, &mempool_of(&block.txs[1..3]));
+        assert_eq!(pending.missing(), vec![3]);
+        pending.set_requested(pending.missing());
+        assert!(pending.fill_requested(vec![block.txs[1].clone()]));
+        assert_eq!(pending.try_make_block(), None);
+
+        // we can ask for everything we filled in once
+        assert!(pending.reset_filled());
+        assert_eq!(pending.missing(), vec![1, 2, 3]);
+        assert!(!pending.reset_filled());
+
+        pending.set_requested(pending.missing());
+        assert!(pending.fill_requested(block.txs[1..].to_vec()));
+        assert_eq!(pending.try_make_block(), Some(block));
+    }
+}
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::io;
@@ -38,7 +39,9 @@ use crate::codec::StacksMessageCodec;
 use crate::codec::MAX_MESSAGE_LEN;
 use core::mempool::MAX_BLOOM_COUNTER_TXS;
 use net::codec::*;
+use net::AddressFamilyPreference;
 use net::Error as net_error;
+use net::HttpCorsHeaders;
 use net::HttpRequestPreamble;
 use net::HttpResponsePreamble;
 use net::MessageSequence;
@@ -49,14 +52,17 @@ use net::RelayData;
 use net::StacksHttp;
 use net::StacksP2P;
 
+use net::backoff::RetryPolicy;
 use net::download::BLOCK_DOWNLOAD_INTERVAL;
 use net::inv::{INV_REWARD_CYCLES, INV_SYNC_INTERVAL};
 use net::neighbors::{
     NEIGHBOR_REQUEST_TIMEOUT, NEIGHBOR_WALK_INTERVAL, NUM_INITIAL_WALKS, WALK_MAX_DURATION,
     WALK_MIN_DURATION, WALK_RESET_INTERVAL, WALK_RESET_PROB, WALK_RETRY_COUNT, WALK_STATE_TIMEOUT,
 };
+use net::portmap::PortMappingMethod;
+use net::ratelimit::RPCRateLimit;
 
-use vm::{costs::ExecutionCost, types::BOUND_VALUE_SERIALIZATION_HEX};
+use vm::{costs::ExecutionCost, types::BOUND_VALUE_SERIALIZATION_HEX, ExecutionLimits};
 
 use chainstate::burn::ConsensusHash;
 
@@ -296,11 +302,18 @@ struct ConnectionInbox<P: ProtocolFamily> {
     inbox: VecDeque<P::Message>,
     inbox_maxlen: usize,
 
+    // if set, stop reading once the inbox is full instead of failing with InboxOverflow
+    pause_when_full: bool,
+    // reading stopped because the inbox filled up; there may be more data to read or parse
+    paused: bool,
+
     // partially-parsed incoming messages
     preamble: Option<P::Preamble>,
     buf: Vec<u8>,
     message_ptr: usize, // index into buf where the message begins
     payload_ptr: usize, // for payloads of unknown length, this points to where to read next
+    // when the remote peer started sending the partially-received message, if any
+    partial_since: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -322,6 +335,7 @@ pub struct ConnectionOptions {
     pub inbox_maxlen: usize,
     pub outbox_maxlen: usize,
     pub connect_timeout: u64,
+    /// the longest a p2p connection may stay open without handshaking, in seconds
     pub handshake_timeout: u64,
     pub timeout: u64,
     pub idle_timeout: u64,
@@ -336,8 +350,65 @@ pub struct ConnectionOptions {
     pub soft_max_neighbors_per_host: u64,
     pub soft_max_neighbors_per_org: u64,
     pub soft_max_clients_per_host: u64,
+    /// how many inbound connections from miners we can have, full-stop
+    pub max_inbound_miners: u64,
+    /// how many inbound connections from non-miners we can have, full-stop
+    pub max_inbound_followers: u64,
+    /// how many inbound connections that haven't handshaked yet we can have, full-stop
+    pub max_inbound_unknown: u64,
+    /// how many of our sockets inbound connections must leave free for outbound connections
+    pub reserved_outbound_slots: u64,
+    /// how often each IP address may open an inbound p2p connection, in connections per second
+    /// (None means no limit).  Each one costs us a handshake.
+    pub inbound_handshake_rate_limit: Option<RPCRateLimit>,
+    /// how long a p2p peer may take to finish sending a message it has started, in seconds (0
+    /// means no limit)
+    pub partial_message_timeout: u64,
     pub max_neighbors_of_neighbor: u64,
     pub max_http_clients: u64,
+    pub max_http_pipelined_requests: u64,
+    pub max_websocket_sessions: u64,
+    pub websocket_ping_interval: u64,
+    pub http_response_cache_size: u64,
+    /// how many of the most recent Stacks chain reorgs to report in /v2/info
+    pub max_recent_reorgs: u64,
+    /// how many burnchain blocks the Stacks tip may go without advancing before we reset block
+    /// sync and look for new neighbors (0 disables this)
+    pub stale_tip_burn_blocks: u64,
+    /// origins that browsers may call the RPC interface from.  "*" allows any origin, and an
+    /// empty list sends no CORS headers at all.
+    pub rpc_cors_allowed_origins: Vec<String>,
+    pub rpc_cors_allowed_methods: Vec<String>,
+    pub rpc_cors_allowed_headers: Vec<String>,
+    /// how long (in seconds) browsers may cache a CORS preflight response
+    pub rpc_cors_max_age: Option<u64>,
+    /// PEM certificate chain and private key to serve the RPC interface over TLS with.  Both must
+    /// be set to enable TLS.  The files are re-read whenever they change.
+    pub rpc_tls_cert_file: Option<String>,
+    pub rpc_tls_key_file: Option<String>,
+    /// PEM bundle of CAs whose client certificates the RPC interface accepts
+    pub rpc_tls_client_ca_file: Option<String>,
+    /// refuse write requests (transactions, blocks, microblocks) from RPC clients that did not
+    /// present a certificate signed by `rpc_tls_client_ca_file`
+    pub rpc_tls_require_client_cert: bool,
+    /// file permission bits to give the RPC interface's unix domain socket, if it is bound to one
+    pub rpc_unix_socket_mode: u32,
+    /// RPC API keys, and how fast each may send requests (None means no limit)
+    pub rpc_api_keys: HashMap<String, Option<RPCRateLimit>>,
+    /// refuse RPC requests that don't carry one of `rpc_api_keys`
+    pub rpc_require_api_key: bool,
+    /// how fast each client IP may send RPC requests without an API key (None means no limit)
+    pub rpc_ip_rate_limit: Option<RPCRateLimit>,
+    /// API key that /v2/admin endpoints require.  They're refused if this isn't set.
+    pub rpc_admin_api_key: Option<String>,
+    /// how many blocks the burnchain view may trail the burnchain tip before /v2/health/ready
+    /// reports the node as not ready
+    pub health_max_burnchain_lag: u64,
+    /// how old (in seconds) the Stacks chain tip's burn block may be before /v2/health/ready
+    /// reports the node as not ready
+    pub health_max_tip_age: u64,
+    /// how many authenticated p2p peers the node needs for /v2/health/ready to report it as ready
+    pub health_min_peers: u64,
     pub neighbor_request_timeout: u64,
     pub num_initial_walks: u64,
     pub walk_retry_count: u64,
@@ -355,14 +426,53 @@ pub struct ConnectionOptions {
     pub dns_timeout: u128,
     pub max_inflight_blocks: u64,
     pub max_inflight_attachments: u64,
+    /// how many block or microblock requests we'll have outstanding to any one neighbor
+    pub max_inflight_blocks_per_peer: u64,
+    /// how long (in seconds) a block or microblock request can go unanswered before an idle
+    /// neighbor is asked for the same data.  0 disables this.
+    pub block_download_steal_timeout: u64,
     pub max_attachment_retry_count: u64,
+    /// how long (in seconds) to stop asking a peer for blocks after it first fails us.  This
+    /// doubles with each consecutive failure, up to `download_retry_max_delay`.
+    pub download_retry_base_delay: u64,
+    pub download_retry_max_delay: u64,
+    /// up to this fraction of a download retry delay is added as random jitter
+    pub download_retry_jitter: f64,
+    /// how long (in seconds) to wait before retrying an attachments batch that didn't fully
+    /// download, and how long to stop asking a peer for attachments after it first fails us.
+    /// These double with each consecutive failure, up to `attachment_retry_max_delay`.
+    pub attachment_retry_base_delay: u64,
+    pub attachment_retry_max_delay: u64,
+    /// up to this fraction of an attachment retry delay is added as random jitter
+    pub attachment_retry_jitter: f64,
     pub read_only_call_limit: ExecutionCost,
+    /// how many expressions a read-only call may evaluate.  0 means no limit.
+    pub read_only_call_max_steps: u64,
+    /// how long a read-only call may run, in milliseconds.  0 means no limit.
+    pub read_only_call_timeout_ms: u64,
     pub maximum_call_argument_size: u32,
     pub max_block_push_bandwidth: u64,
     pub max_microblocks_push_bandwidth: u64,
     pub max_transaction_push_bandwidth: u64,
     pub max_sockets: usize,
+    /// maximum number of file descriptors the node may hold open.  0 means use the process limit.
+    pub max_open_files: u64,
+    /// how many file descriptors to hold back for databases and other files.  Once fewer than
+    /// this many remain, new inbound connections are refused and the least-valuable ones are shed.
+    pub open_files_reserve: u64,
     pub public_ip_address: Option<(PeerAddress, u16)>,
+    /// a dual-stack node's public address in the other IP family from `public_ip_address`.  It's
+    /// advertised to the peers that reach us over that family.
+    pub alt_public_ip_address: Option<(PeerAddress, u16)>,
+    /// which IP address families to walk to and connect out to
+    pub address_family: AddressFamilyPreference,
+    /// CIDR ranges (prefix and 128-bit mask) and AS numbers whose peers are always allowed, or
+    /// denied.  These are added to the peer DB's rules at startup; the rules can also be changed
+    /// at runtime through /v2/admin/peer_policy.
+    pub allow_cidrs: Vec<(PeerAddress, u32)>,
+    pub deny_cidrs: Vec<(PeerAddress, u32)>,
+    pub allow_asns: Vec<u32>,
+    pub deny_asns: Vec<u32>,
     pub public_ip_request_timeout: u64,
     pub public_ip_timeout: u64,
     pub public_ip_max_retries: u64,
@@ -370,6 +480,9 @@ pub struct ConnectionOptions {
     pub max_microblock_push: u64,
     pub antientropy_retry: u64,
     pub antientropy_public: bool,
+    /// offer anti-entropy data to neighbors that answer `BlocksOffer`s, and only push what they
+    /// accept, instead of pushing all of it (see `net::blockoffer`)
+    pub antientropy_offers: bool,
     pub max_buffered_blocks_available: u64,
     pub max_buffered_microblocks_available: u64,
     pub max_buffered_blocks: u64,
@@ -380,6 +493,84 @@ pub struct ConnectionOptions {
     pub mempool_max_tx_query: u64,
     /// how long a mempool sync is allowed to take, in total, before timing out
     pub mempool_sync_timeout: u64,
+    /// after booting, how many distinct peers to sync our mempool with back-to-back before
+    /// falling back to one sync every `mempool_sync_interval` seconds (0 disables this)
+    pub mempool_sync_bootstrap_peers: u64,
+    /// how long (in seconds) it takes a misbehaving peer to earn back half of its lost reputation
+    pub reputation_half_life: u64,
+    /// peers whose reputation falls to this score or below are banned
+    pub reputation_ban_threshold: f64,
+    /// peers whose reputation falls below this score are the last to be used for inventory sync
+    /// and the first to be pruned
+    pub reputation_deprioritize_threshold: f64,
+    /// how much reputation a peer loses for each kind of misbehavior, out of a possible 100
+    pub reputation_penalty_invalid_message: f64,
+    pub reputation_penalty_stale_inventory: f64,
+    pub reputation_penalty_slow_response: f64,
+    pub reputation_penalty_unsolicited_spam: f64,
+    /// advertise `ServiceFlags::ENCRYPTION`, and open outbound connections with a Noise handshake
+    pub p2p_encryption: bool,
+    /// refuse inbound connections that don't start a Noise handshake
+    pub p2p_require_encryption: bool,
+    /// advertise `ServiceFlags::COMPACT_BLOCKS`, and push new blocks as compact blocks to peers
+    /// that advertise it too
+    pub compact_blocks: bool,
+    /// advertise `ServiceFlags::COMPRESSION`, and compress large payloads sent to peers that
+    /// advertise it too
+    pub p2p_compression: bool,
+    /// don't bother compressing payloads smaller than this many bytes
+    pub p2p_compression_min_size: u64,
+    /// advertise `ServiceFlags::CAPABILITIES`, and exchange capability versions with peers that
+    /// advertise it too (see `net::capabilities`)
+    pub capability_exchange: bool,
+    /// how long to wait for the missing transactions of a compact block before giving up on it
+    pub compact_block_timeout: u64,
+    /// how many recently-seen txids to remember for each neighbor, so we don't relay a
+    /// transaction to a neighbor that already has it
+    pub tx_relay_filter_items: u32,
+    /// how often to ask a random outbound neighbor for the mempool transactions we're missing
+    /// with `GetMempoolTxs`, in seconds (0 disables this)
+    pub mempool_pull_interval: u64,
+    /// how often a single neighbor may ask us for mempool transactions, in seconds
+    pub mempool_txs_min_interval: u64,
+    /// how many bytes of pushed microblocks we forward per interval for the stream off of the
+    /// canonical Stacks tip (see `net::relaypolicy`)
+    pub microblock_relay_canonical_budget: u64,
+    /// how many bytes of pushed microblocks we forward per interval for each stream that isn't off
+    /// of the canonical Stacks tip
+    pub microblock_relay_stale_budget: u64,
+    /// how long each microblock stream's relay budget lasts before it is refilled, in seconds
+    pub microblock_relay_budget_interval: u64,
+    /// how many of the best-scoring peers from our last run to reconnect to as soon as we start,
+    /// before the first neighbor walk (see `net::warmstart`).  0 disables this.
+    pub warm_start_peers: u64,
+    /// how often to save our outbound neighbors' quality scores to the peer DB, in seconds
+    pub peer_score_save_interval: u64,
+    /// how long a saved peer score is good for, in seconds.  Peers we haven't scored since then
+    /// are not warm-started.
+    pub peer_score_max_age: u64,
+    /// open outbound p2p connections through this SOCKS5 proxy (see `net::socks`)
+    pub socks5_proxy: Option<net::SocketAddr>,
+    /// username and password for the SOCKS5 proxy, if it wants them
+    pub socks5_credentials: Option<(String, String)>,
+    /// how fast we may push data to each p2p neighbor, in bytes per second (None means no limit)
+    pub peer_egress_limit: Option<RPCRateLimit>,
+    /// how fast we may push data to all p2p neighbors together, in bytes per second (None means
+    /// no limit)
+    pub global_egress_limit: Option<RPCRateLimit>,
+    /// hostnames (`host[:port]`) that resolve to bootstrap peers (see `net::seeds`)
+    pub dns_seeds: Vec<String>,
+    /// ask DNS seeds only for peers with these service bits (0 means any peer)
+    pub dns_seed_services: u16,
+    /// how long to wait before looking up the DNS seeds again once we've tried all their peers,
+    /// in seconds
+    pub dns_seed_interval: u64,
+    /// ask the gateway to forward a public port to our p2p port (see `net::portmap`)
+    pub port_mapping: PortMappingMethod,
+    /// how long each port mapping lasts before it must be renewed, in seconds
+    pub port_mapping_lease: u64,
+    /// the NAT-PMP gateway to use, if not the default route's gateway
+    pub port_mapping_gateway: Option<net::Ipv4Addr>,
 
     // fault injection
     pub disable_neighbor_walk: bool,
@@ -419,8 +610,40 @@ impl std::default::Default for ConnectionOptions {
             soft_max_neighbors_per_host: 10, // how many outbound connections we can have per IP address, before we start pruning them
             soft_max_neighbors_per_org: 10, // how many outbound connections we can have per AS-owning organization, before we start pruning them
             soft_max_clients_per_host: 10, // how many inbound connections we can have per IP address, before we start pruning them,
+            max_inbound_miners: 32,
+            max_inbound_followers: 256,
+            max_inbound_unknown: 64,
+            reserved_outbound_slots: 32,
+            inbound_handshake_rate_limit: None,
+            partial_message_timeout: 0,
             max_neighbors_of_neighbor: 10,
             max_http_clients: 10,
+            max_http_pipelined_requests: 16, // how many requests a HTTP client can have in flight on one connection
+            max_websocket_sessions: 100, // maximum number of HTTP connections upgraded to WebSockets
+            websocket_ping_interval: 30, // how often to ping an otherwise-quiet WebSocket client
+            http_response_cache_size: 1024, // how many immutable RPC responses to keep in memory
+            max_recent_reorgs: 16,
+            stale_tip_burn_blocks: 0,
+            rpc_cors_allowed_origins: vec!["*".to_string()],
+            rpc_cors_allowed_methods: vec![
+                "POST".to_string(),
+                "GET".to_string(),
+                "OPTIONS".to_string(),
+            ],
+            rpc_cors_allowed_headers: vec!["origin".to_string(), "content-type".to_string()],
+            rpc_cors_max_age: None,
+            rpc_tls_cert_file: None,
+            rpc_tls_key_file: None,
+            rpc_tls_client_ca_file: None,
+            rpc_tls_require_client_cert: false,
+            rpc_unix_socket_mode: 0o600, // only the node's own user can use the RPC socket
+            rpc_api_keys: HashMap::new(),
+            rpc_require_api_key: false,
+            rpc_ip_rate_limit: None,
+            rpc_admin_api_key: None,
+            health_max_burnchain_lag: 6,
+            health_max_tip_age: 3600,
+            health_min_peers: 1,
             neighbor_request_timeout: NEIGHBOR_REQUEST_TIMEOUT, // how long to wait for a neighbor request
             num_initial_walks: NUM_INITIAL_WALKS,
             walk_retry_count: WALK_RETRY_COUNT,
@@ -435,10 +658,18 @@ impl std::default::Default for ConnectionOptions {
             inv_reward_cycles: INV_REWARD_CYCLES, // how many reward cycles of blocks to sync in a non-full inventory sync
             download_interval: BLOCK_DOWNLOAD_INTERVAL, // how often to scan for blocks to download
             pingback_timeout: 60,
-            dns_timeout: 15_000,            // DNS timeout, in millis
-            max_inflight_blocks: 6,         // number of parallel block downloads
-            max_inflight_attachments: 6,    // number of parallel attachments downloads
+            dns_timeout: 15_000,              // DNS timeout, in millis
+            max_inflight_blocks: 6,           // number of parallel block downloads
+            max_inflight_attachments: 6,      // number of parallel attachments downloads
+            max_inflight_blocks_per_peer: 2, // number of parallel block downloads from one neighbor
+            block_download_steal_timeout: 15, // re-request a slow block from an idle neighbor after this many seconds
             max_attachment_retry_count: 32, // how many attempt to get an attachment before giving up
+            download_retry_base_delay: 60,
+            download_retry_max_delay: 3600,
+            download_retry_jitter: 0.25,
+            attachment_retry_base_delay: 2,
+            attachment_retry_max_delay: 600,
+            attachment_retry_jitter: 0.5,
             read_only_call_limit: ExecutionCost {
                 write_length: 0,
                 write_count: 0,
@@ -446,12 +677,22 @@ impl std::default::Default for ConnectionOptions {
                 read_count: 30,
                 runtime: 1_000_000_000,
             },
+            read_only_call_max_steps: 10_000_000,
+            read_only_call_timeout_ms: 5_000,
             maximum_call_argument_size: 20 * BOUND_VALUE_SERIALIZATION_HEX,
             max_block_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_microblocks_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_transaction_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_sockets: 800,            // maximum number of client sockets we'll ever register
+            max_open_files: 0,           // use the process's open file limit
+            open_files_reserve: 128,     // file descriptors held back for databases and other files
             public_ip_address: None,     // resolve it at runtime by default
+            alt_public_ip_address: None,
+            address_family: AddressFamilyPreference::Any,
+            allow_cidrs: vec![],
+            deny_cidrs: vec![],
+            allow_asns: vec![],
+            deny_asns: vec![],
             public_ip_request_timeout: 60, // how often we can attempt to look up our public IP address
             public_ip_timeout: 3600,       // re-learn the public IP ever hour, if it's not given
             public_ip_max_retries: 3, // maximum number of retries before self-throttling for $public_ip_timeout
@@ -459,6 +700,7 @@ impl std::default::Default for ConnectionOptions {
             max_microblock_push: 10, // maximum number of microblocks messages to push out via our anti-entropy protocol
             antientropy_retry: 60,   // retry pushing data once every minute
             antientropy_public: true, // run antientropy even if we're NOT NAT'ed
+            antientropy_offers: false,
             max_buffered_blocks_available: 1,
             max_buffered_microblocks_available: 1,
             max_buffered_blocks: 1,
@@ -466,6 +708,40 @@ impl std::default::Default for ConnectionOptions {
             mempool_sync_interval: 30, // number of seconds in-between mempool sync
             mempool_max_tx_query: 128, // maximum number of transactions to visit per mempool query
             mempool_sync_timeout: 180, // how long a mempool sync can go for (3 minutes)
+            mempool_sync_bootstrap_peers: 0,
+            reputation_half_life: 3600, // a peer earns back half its lost reputation in an hour
+            reputation_ban_threshold: 0.0,
+            reputation_deprioritize_threshold: 50.0,
+            reputation_penalty_invalid_message: 20.0,
+            reputation_penalty_stale_inventory: 5.0,
+            reputation_penalty_slow_response: 10.0,
+            reputation_penalty_unsolicited_spam: 10.0,
+            p2p_encryption: false,
+            p2p_require_encryption: false,
+            compact_blocks: false,
+            p2p_compression: false,
+            p2p_compression_min_size: 1024,
+            capability_exchange: false,
+            compact_block_timeout: 30,
+            tx_relay_filter_items: 4096,
+            mempool_pull_interval: 0,
+            mempool_txs_min_interval: 10,
+            microblock_relay_canonical_budget: u64::MAX,
+            microblock_relay_stale_budget: u64::MAX,
+            microblock_relay_budget_interval: 600,
+            warm_start_peers: 0,
+            peer_score_save_interval: 300,
+            peer_score_max_age: 7 * 24 * 3600,
+            socks5_proxy: None,
+            socks5_credentials: None,
+            peer_egress_limit: None,
+            global_egress_limit: None,
+            dns_seeds: vec![],
+            dns_seed_services: 0,
+            dns_seed_interval: 600,
+            port_mapping: PortMappingMethod::Disabled,
+            port_mapping_lease: 3600,
+            port_mapping_gateway: None,
 
             // no faults on by default
             disable_neighbor_walk: false,
@@ -487,6 +763,62 @@ impl std::default::Default for ConnectionOptions {
     }
 }
 
+impl ConnectionOptions {
+    /// How the block downloader backs off from peers that fail it
+    pub fn download_retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(
+            self.download_retry_base_delay,
+            self.download_retry_max_delay,
+            self.download_retry_jitter,
+        )
+    }
+
+    /// How the attachments downloader backs off from batches and peers that fail it
+    pub fn attachment_retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(
+            self.attachment_retry_base_delay,
+            self.attachment_retry_max_delay,
+            self.attachment_retry_jitter,
+        )
+    }
+
+    /// The execution limits read-only calls are evaluated under
+    pub fn read_only_call_execution_limits(&self) -> ExecutionLimits {
+        ExecutionLimits {
+            max_steps: Some(self.read_only_call_max_steps).filter(|steps| *steps > 0),
+            max_duration: Some(self.read_only_call_timeout_ms)
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+        }
+    }
+
+    /// The CORS headers to answer a request from the given origin with.  Preflight responses
+    /// also say how long they may be cached.
+    pub fn cors_headers(&self, origin: Option<&str>, preflight: bool) -> HttpCorsHeaders {
+        let allow_origin = if self.rpc_cors_allowed_origins.iter().any(|o| o == "*") {
+            Some("*".to_string())
+        } else {
+            origin
+                .filter(|origin| {
+                    self.rpc_cors_allowed_origins
+                        .iter()
+                        .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+                })
+                .map(|origin| origin.to_string())
+        };
+        HttpCorsHeaders {
+            allow_origin,
+            allow_methods: self.rpc_cors_allowed_methods.join(", "),
+            allow_headers: self.rpc_cors_allowed_headers.join(", "),
+            max_age: if preflight {
+                self.rpc_cors_max_age
+            } else {
+                None
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NetworkConnection<P: ProtocolFamily> {
     pub options: ConnectionOptions,
@@ -504,10 +836,13 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
             public_key: public_key_opt,
             inbox: VecDeque::with_capacity(max_messages),
             inbox_maxlen: max_messages,
+            pause_when_full: false,
+            paused: false,
             preamble: None,
             buf: vec![],
             message_ptr: 0,
             payload_ptr: 0,
+            partial_since: None,
         }
     }
 
@@ -772,11 +1107,19 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
     /// recv_bytes() will prevent more data from being read from the socket until the messages are
     /// dequeued.
     ///
+    /// If the inbox pauses when full, then whatever is left of `buf` once the inbox fills up is
+    /// held in the input buffer until consume_buffered_messages() is called.
+    ///
     /// Returns nothing on success, and enqueues zero or more messages into our inbox.
     /// Returns net_error::InvalidMessage if a message could not be parsed or authenticated.
     fn consume_messages(&mut self, protocol: &mut P, buf: &[u8]) -> Result<(), net_error> {
         let mut offset = 0;
         loop {
+            if self.pause_when_full && self.inbox.len() >= self.inbox_maxlen {
+                self.buf.extend_from_slice(&buf[offset..]);
+                self.paused = true;
+                return Ok(());
+            }
             if self.inbox.len() > self.inbox_maxlen {
                 return Err(net_error::InboxOverflow);
             }
@@ -818,6 +1161,7 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
                                 bytes_consumed
                             );
                             self.inbox.push_back(message);
+                            self.partial_since = None;
                             consumed_message = true;
                         }
                         None => {}
@@ -844,8 +1188,19 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
 
         // we can buffer bytes faster than we can process messages, so be sure to drain the buffer
         // before returning.
+        self.consume_buffered_messages(protocol)
+    }
+
+    /// Consume messages from data that has already been buffered.
+    /// If the inbox pauses when full, this stops once it fills up.
+    fn consume_buffered_messages(&mut self, protocol: &mut P) -> Result<(), net_error> {
         if self.buf.len() > 0 {
             loop {
+                if self.pause_when_full && self.inbox.len() >= self.inbox_maxlen {
+                    self.paused = true;
+                    break;
+                }
+
                 let mut consumed_message = false;
 
                 if self.preamble.is_none() {
@@ -869,6 +1224,7 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
                                 // queue up
                                 test_debug!("Consumed buffered message '{}' (request {}) from {} input buffer bytes", message.get_message_name(), message.request_id(), _bytes_consumed);
                                 self.inbox.push_back(message);
+                                self.partial_since = None;
                                 consumed_message = true;
                             }
                             None => {}
@@ -892,10 +1248,47 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
         Ok(())
     }
 
+    /// Note when the remote peer started sending the message we're partway through receiving,
+    /// if any.  Data we've buffered up because the inbox is full doesn't count, since we stopped
+    /// reading it, not the remote peer.
+    fn update_partial_since(&mut self) {
+        let partial = !self.paused && (self.preamble.is_some() || !self.buf.is_empty());
+        if !partial {
+            self.partial_since = None;
+        } else if self.partial_since.is_none() {
+            self.partial_since = Some(get_epoch_time_secs());
+        }
+    }
+
     /// Read bytes from an input stream, buffer them up, try to parse the buffer
     /// into messages, and enqueue the messages into the inbox.
+    /// If the inbox pauses when full, this stops reading once the inbox fills up, and picks up
+    /// where it left off once there's room again.
     /// Returns net_error::RecvError if we couldn't read from the fd
     fn recv_bytes<R: Read>(&mut self, protocol: &mut P, fd: &mut R) -> Result<usize, net_error> {
+        let res = self.recv_bytes_inner(protocol, fd);
+        self.update_partial_since();
+        res
+    }
+
+    fn recv_bytes_inner<R: Read>(
+        &mut self,
+        protocol: &mut P,
+        fd: &mut R,
+    ) -> Result<usize, net_error> {
+        if self.pause_when_full {
+            if self.inbox.len() >= self.inbox_maxlen {
+                self.paused = true;
+                return Ok(0);
+            }
+            if self.paused {
+                self.paused = false;
+                self.consume_buffered_messages(protocol)?;
+                if self.paused {
+                    return Ok(0);
+                }
+            }
+        }
         if self.inbox.len() > self.inbox_maxlen {
             return Err(net_error::InboxOverflow);
         }
@@ -947,6 +1340,10 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
             if num_read > 0 {
                 // decode into message stream
                 self.consume_messages(protocol, &buf[0..num_read])?;
+                if self.paused {
+                    // leave the rest in the socket until the inbox has room
+                    break;
+                }
             }
         }
 
@@ -1178,6 +1575,13 @@ impl<P: ProtocolFamily> ConnectionOutbox<P> {
     pub fn num_messages(&self) -> usize {
         self.outbox.len()
     }
+
+    /// Have all queued messages been completely written out to the socket?
+    pub fn is_flushed(&self) -> bool {
+        self.outbox.is_empty()
+            && self.pending_message_fd.is_none()
+            && self.socket_out_ptr >= self.socket_out_buf.len()
+    }
 }
 
 impl<P: ProtocolFamily + Clone> NetworkConnection<P> {
@@ -1339,16 +1743,40 @@ impl<P: ProtocolFamily + Clone> NetworkConnection<P> {
         self.inbox.recv_bytes(&mut self.protocol, fd)
     }
 
+    /// Cap the inbox at `maxlen` messages, and stop reading from the socket once it's full instead
+    /// of failing with InboxOverflow.  Reading resumes once messages are taken out of the inbox.
+    pub fn set_inbox_backpressure(&mut self, maxlen: usize) {
+        self.inbox.inbox_maxlen = maxlen;
+        self.inbox.pause_when_full = true;
+    }
+
+    /// Did we stop reading because the inbox filled up?  If so, there may be more data waiting
+    /// in the socket or in our buffer.
+    pub fn is_recv_paused(&self) -> bool {
+        self.inbox.paused
+    }
+
     /// how many inbox messages pending?
     pub fn inbox_len(&self) -> usize {
         self.inbox.num_messages()
     }
 
+    /// When did the remote peer start sending the message we've only received part of?  None if
+    /// we're not partway through a message.
+    pub fn partial_message_since(&self) -> Option<u64> {
+        self.inbox.partial_since
+    }
+
     /// how many outbox messages pending?
     pub fn outbox_len(&self) -> usize {
         self.outbox.num_messages()
     }
 
+    /// have all outbox messages been fully sent?
+    pub fn is_flushed(&self) -> bool {
+        self.outbox.is_flushed()
+    }
+
     /// get the next inbox message
     pub fn next_inbox_message(&mut self) -> Option<P::Message> {
         self.inbox.next_message()
@@ -2136,6 +2564,59 @@ mod test {
         })
     }
 
+    #[test]
+    fn connection_recv_partial_message() {
+        let privkey = Secp256k1PrivateKey::new();
+        let pubkey = Secp256k1PublicKey::from_private(&privkey);
+
+        let mut conn = ConnectionP2P::new(
+            StacksP2P::new(),
+            &ConnectionOptions::default(),
+            Some(pubkey),
+        );
+
+        let mut ping = StacksMessage::new(
+            0x12345678,
+            0x9abcdef0,
+            12345,
+            &BurnchainHeaderHash([0x11; 32]),
+            12339,
+            &BurnchainHeaderHash([0x22; 32]),
+            StacksMessageType::Ping(PingData { nonce: 0x01020304 }),
+        );
+        ping.sign(1, &privkey).unwrap();
+
+        let mut ping_bytes = vec![];
+        ping.consensus_serialize(&mut ping_bytes).unwrap();
+        let half = ping_bytes.len() / 2;
+
+        assert_eq!(conn.partial_message_since(), None);
+
+        // first half of the message
+        let mut first_half = ping_bytes[..half].to_vec();
+        let mut fd = NetCursor::new(first_half.as_mut_slice());
+        assert_eq!(conn.recv_data(&mut fd).unwrap(), half);
+        assert_eq!(conn.inbox_len(), 0);
+
+        let since = conn.partial_message_since().unwrap();
+        assert!(since <= get_epoch_time_secs());
+
+        // second half of the message, and the first half of the next
+        let mut rest = ping_bytes[half..].to_vec();
+        rest.extend_from_slice(&ping_bytes[..half]);
+        let mut fd = NetCursor::new(rest.as_mut_slice());
+        conn.recv_data(&mut fd).unwrap();
+        assert_eq!(conn.inbox_len(), 1);
+        assert!(conn.partial_message_since().is_some());
+
+        // the rest of the next message
+        let mut rest = ping_bytes[half..].to_vec();
+        let mut fd = NetCursor::new(rest.as_mut_slice());
+        conn.recv_data(&mut fd).unwrap();
+        assert_eq!(conn.inbox_len(), 2);
+        assert_eq!(conn.partial_message_since(), None);
+    }
+
     #[test]
     fn connection_send_recv_timeout() {
         let privkey = Secp256k1PrivateKey::new();
@@ -19,7 +19,7 @@ use std::fmt;
 use rusqlite::types::ToSql;
 use rusqlite::Row;
 use rusqlite::Transaction;
-use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, NO_PARAMS};
 
 use std::convert::From;
 use std::convert::TryFrom;
@@ -111,6 +111,8 @@ pub struct LocalPeer {
 
     // filled in and curated at runtime
     pub public_ip_address: Option<(PeerAddress, u16)>,
+    // our public address in the other IP family, if we're dual-stack
+    pub alt_public_ip_address: Option<(PeerAddress, u16)>,
 }
 
 impl fmt::Display for LocalPeer {
@@ -180,9 +182,27 @@ impl LocalPeer {
             services: services as u16,
             data_url: data_url,
             public_ip_address: None,
+            alt_public_ip_address: None,
         }
     }
 
+    /// The address and port to tell a peer we're reachable at.  If we know our public address
+    /// in the same IP family as the peer's address, then that's the one; otherwise it's our
+    /// public address, or our bind address if we don't know it yet.
+    pub fn advertised_address(&self, peer_addrbytes: Option<&PeerAddress>) -> (PeerAddress, u16) {
+        let public_addrs = [self.public_ip_address, self.alt_public_ip_address];
+        let same_family = peer_addrbytes.and_then(|peer_addrbytes| {
+            public_addrs
+                .iter()
+                .flatten()
+                .find(|(addrbytes, _)| addrbytes.is_ipv4() == peer_addrbytes.is_ipv4())
+        });
+        same_family
+            .or_else(|| public_addrs.iter().flatten().next())
+            .cloned()
+            .unwrap_or((self.addrbytes.clone(), self.port))
+    }
+
     pub fn to_neighbor_addr(&self) -> NeighborAddress {
         NeighborAddress {
             addrbytes: self.addrbytes.clone(),
@@ -232,6 +252,7 @@ impl FromRow<LocalPeer> for LocalPeer {
             services: services,
             data_url: data_url,
             public_ip_address: None,
+            alt_public_ip_address: None,
         })
     }
 }
@@ -358,6 +379,41 @@ const PEERDB_INITIAL_SCHEMA: &'static [&'static str] = &[
     );"#,
 ];
 
+// Hostnames of onion peers, keyed by the addrbytes they are stored under in the frontier (see
+// `PeerAddress::from_onion_host`).  Created when the DB is opened, so older DBs get it too.
+const PEERDB_ONION_HOSTS_SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS onion_hosts(
+        addrbytes TEXT NOT NULL,
+        host TEXT NOT NULL,
+
+        PRIMARY KEY(addrbytes)
+    );"#;
+
+// AS numbers whose peers are always allowed, or denied.  Like the CIDR prefix tables, these are
+// applied to the frontier's `allowed` and `denied` columns.  Created when the DB is opened, so
+// older DBs get them too.
+const PEERDB_ASN_FILTER_SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS allowed_asns(
+        asn INTEGER PRIMARY KEY NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS denied_asns(
+        asn INTEGER PRIMARY KEY NOT NULL
+    );"#;
+
+// How good each outbound neighbor was the last time we scored it (see `net::warmstart`), so we
+// can reconnect to the best ones first when we restart.  Created when the DB is opened, so older
+// DBs get it too.
+const PEERDB_PEER_SCORES_SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS peer_scores(
+        network_id INTEGER NOT NULL,
+        addrbytes TEXT NOT NULL,
+        port INTEGER NOT NULL,
+        score REAL NOT NULL,
+        scored_at INTEGER NOT NULL,
+
+        PRIMARY KEY(network_id,addrbytes,port)
+    );"#;
+
 const PEERDB_INDEXES: &'static [&'static str] =
     &["CREATE INDEX IF NOT EXISTS peer_address_index ON frontier(network_id,addrbytes,port);"];
 
@@ -441,10 +497,31 @@ impl PeerDB {
 
         tx.commit().map_err(db_error::SqliteError)?;
 
+        self.add_onion_hosts_table()?;
+        self.add_asn_filter_tables()?;
+        self.add_peer_scores_table()?;
         self.add_indexes()?;
         Ok(())
     }
 
+    fn add_onion_hosts_table(&mut self) -> Result<(), db_error> {
+        self.conn
+            .execute_batch(PEERDB_ONION_HOSTS_SCHEMA)
+            .map_err(db_error::SqliteError)
+    }
+
+    fn add_asn_filter_tables(&mut self) -> Result<(), db_error> {
+        self.conn
+            .execute_batch(PEERDB_ASN_FILTER_SCHEMA)
+            .map_err(db_error::SqliteError)
+    }
+
+    fn add_peer_scores_table(&mut self) -> Result<(), db_error> {
+        self.conn
+            .execute_batch(PEERDB_PEER_SCORES_SCHEMA)
+            .map_err(db_error::SqliteError)
+    }
+
     fn add_indexes(&mut self) -> Result<(), db_error> {
         let tx = self.tx_begin()?;
         for row_text in PEERDB_INDEXES {
@@ -494,6 +571,10 @@ impl PeerDB {
             debug!("Refresh deny {}/{}", &prefix, mask);
             PeerDB::apply_cidr_filter(tx, &prefix, mask, "denied", i64::MAX)?;
         }
+        for asn in PeerDB::get_denied_asns(tx)?.into_iter() {
+            debug!("Refresh deny AS{}", asn);
+            PeerDB::apply_asn_filter(tx, asn, "denied", i64::MAX)?;
+        }
         Ok(())
     }
 
@@ -504,6 +585,10 @@ impl PeerDB {
             debug!("Refresh allow {}/{}", &prefix, mask);
             PeerDB::apply_cidr_filter(tx, &prefix, mask, "allowed", i64::MAX)?;
         }
+        for asn in PeerDB::get_allowed_asns(tx)?.into_iter() {
+            debug!("Refresh allow AS{}", asn);
+            PeerDB::apply_asn_filter(tx, asn, "allowed", i64::MAX)?;
+        }
         Ok(())
     }
 
@@ -579,6 +664,7 @@ impl PeerDB {
             }
         } else {
             db.update_local_peer(network_id, parent_network_id, data_url, p2p_port)?;
+            db.add_asn_filter_tables()?;
 
             {
                 let mut tx = db.tx_begin()?;
@@ -601,6 +687,8 @@ impl PeerDB {
             }
         }
         if readwrite {
+            db.add_onion_hosts_table()?;
+            db.add_peer_scores_table()?;
             db.add_indexes()?;
         }
         Ok(db)
@@ -804,6 +892,15 @@ impl PeerDB {
         query_row::<Neighbor, _>(conn, &qry, &args)
     }
 
+    /// Get the peers that are denied right now, whether they're banned for a while or denied
+    /// for good (e.g. by a CIDR or AS-number deny rule)
+    pub fn get_denied_peers(conn: &DBConn, network_id: u32) -> Result<Vec<Neighbor>, db_error> {
+        let qry = "SELECT * FROM frontier WHERE network_id = ?1 AND (denied < 0 OR denied > ?2)"
+            .to_string();
+        let args: &[&dyn ToSql] = &[&network_id, &u64_to_sql(util::get_epoch_time_secs())?];
+        query_rows::<Neighbor, _>(conn, &qry, args)
+    }
+
     /// Is a peer denied?
     pub fn is_peer_denied(
         conn: &DBConn,
@@ -819,12 +916,20 @@ impl PeerDB {
                 if PeerDB::is_address_denied(conn, &neighbor.addr.addrbytes)? {
                     return Ok(true);
                 }
+                if neighbor.asn != 0 && PeerDB::is_asn_denied(conn, neighbor.asn)? {
+                    return Ok(true);
+                }
                 return Ok(false);
             }
             None => {
                 if PeerDB::is_address_denied(conn, &peer_addr)? {
                     return Ok(true);
                 }
+                if let Some(asn) = PeerDB::asn_lookup(conn, peer_addr)? {
+                    if PeerDB::is_asn_denied(conn, asn)? {
+                        return Ok(true);
+                    }
+                }
                 return Ok(false);
             }
         }
@@ -842,6 +947,9 @@ impl PeerDB {
                 if neighbor.allowed < 0 {
                     return Ok(true);
                 }
+                if neighbor.asn != 0 && PeerDB::is_asn_allowed(conn, neighbor.asn)? {
+                    return Ok(true);
+                }
                 return Ok(false);
             }
             None => {
@@ -959,6 +1067,110 @@ impl PeerDB {
         Ok(())
     }
 
+    /// Remember an onion peer's hostname.  Returns the address to store it under.
+    pub fn set_onion_host<'a>(
+        tx: &mut Transaction<'a>,
+        host: &str,
+    ) -> Result<PeerAddress, db_error> {
+        let host = host.to_lowercase();
+        let addrbytes = PeerAddress::from_onion_host(&host);
+        tx.execute(
+            "INSERT OR REPLACE INTO onion_hosts (addrbytes, host) VALUES (?1, ?2)",
+            &[&addrbytes.to_bin() as &dyn ToSql, &host],
+        )
+        .map_err(db_error::SqliteError)?;
+        Ok(addrbytes)
+    }
+
+    /// Get the hostname of an onion peer
+    pub fn get_onion_host(
+        conn: &DBConn,
+        addrbytes: &PeerAddress,
+    ) -> Result<Option<String>, db_error> {
+        conn.query_row(
+            "SELECT host FROM onion_hosts WHERE addrbytes = ?1",
+            &[&addrbytes.to_bin()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_error::SqliteError)
+    }
+
+    /// Remember how good a peer was as of `now`
+    pub fn set_peer_score<'a>(
+        tx: &mut Transaction<'a>,
+        nk: &NeighborKey,
+        score: f64,
+        now: u64,
+    ) -> Result<(), db_error> {
+        let args: &[&dyn ToSql] = &[
+            &nk.network_id,
+            &to_bin(nk.addrbytes.as_bytes()),
+            &nk.port,
+            &score,
+            &u64_to_sql(now)?,
+        ];
+        tx.execute(
+            "INSERT OR REPLACE INTO peer_scores (network_id, addrbytes, port, score, scored_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            args,
+        )
+        .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Get a peer's last score, and when it was scored
+    pub fn get_peer_score(conn: &DBConn, nk: &NeighborKey) -> Result<Option<(f64, u64)>, db_error> {
+        let args: &[&dyn ToSql] = &[&nk.network_id, &to_bin(nk.addrbytes.as_bytes()), &nk.port];
+        conn.query_row(
+            "SELECT score, scored_at FROM peer_scores WHERE network_id = ?1 AND addrbytes = ?2 AND port = ?3",
+            args,
+            |row| {
+                let score: f64 = row.get(0)?;
+                let scored_at: i64 = row.get(1)?;
+                Ok((score, scored_at as u64))
+            },
+        )
+        .optional()
+        .map_err(db_error::SqliteError)
+    }
+
+    /// Forget the scores of peers we haven't scored since `before`
+    pub fn prune_peer_scores<'a>(tx: &mut Transaction<'a>, before: u64) -> Result<(), db_error> {
+        tx.execute(
+            "DELETE FROM peer_scores WHERE scored_at < ?1",
+            &[&u64_to_sql(before)?],
+        )
+        .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Get up to `count` of the best-scoring peers in the frontier that were scored no earlier
+    /// than `scored_since`, best first.  Denied peers and peers whose keys have expired are
+    /// skipped.
+    pub fn get_best_scored_peers(
+        conn: &DBConn,
+        network_id: u32,
+        network_epoch: u8,
+        count: u32,
+        block_height: u64,
+        scored_since: u64,
+    ) -> Result<Vec<Neighbor>, db_error> {
+        let qry = "SELECT frontier.* FROM frontier JOIN peer_scores ON \
+                   frontier.network_id = peer_scores.network_id AND frontier.addrbytes = peer_scores.addrbytes AND frontier.port = peer_scores.port \
+                   WHERE frontier.network_id = ?1 AND ?2 < frontier.expire_block_height AND frontier.denied < ?3 AND \
+                   (frontier.peer_version & 0x000000ff) >= ?4 AND peer_scores.scored_at >= ?5 \
+                   ORDER BY peer_scores.score DESC, peer_scores.scored_at DESC LIMIT ?6";
+        let args: &[&dyn ToSql] = &[
+            &network_id,
+            &u64_to_sql(block_height)?,
+            &u64_to_sql(util::get_epoch_time_secs())?,
+            &network_epoch,
+            &u64_to_sql(scored_since)?,
+            &count,
+        ];
+        query_rows::<Neighbor, _>(conn, qry, args)
+    }
+
     /// Set/unset allow flag for a peer
     /// Pass -1 for "always"
     pub fn set_allow_peer<'a>(
@@ -1254,6 +1466,209 @@ impl PeerDB {
         Ok(())
     }
 
+    /// Clear the allow or deny flag that the removed rule set on the given CIDR prefix's
+    /// addresses, and re-apply the remaining rules (which may still cover some of them).
+    /// Bootstrap peers stay allowed, and bans keep their deadlines.
+    fn unapply_cidr_filter<'a>(
+        tx: &mut Transaction<'a>,
+        prefix: &PeerAddress,
+        mask: u32,
+        column: &str,
+    ) -> Result<(), db_error> {
+        assert!(mask > 0 && mask <= 128);
+        let prefix_txt = PeerDB::cidr_prefix_to_string(prefix, mask);
+        let args: &[&dyn ToSql] = &[&mask, &prefix_txt];
+        let sql = match column {
+            "denied" => format!(
+                "UPDATE frontier SET denied = 0 WHERE denied = {} AND SUBSTR(addrbytes,1,?1) = SUBSTR(?2,1,?1)",
+                i64::MAX
+            ),
+            _ => format!(
+                "UPDATE frontier SET allowed = 0 WHERE (allowed < 0 OR allowed = {}) AND initial = 0 AND SUBSTR(addrbytes,1,?1) = SUBSTR(?2,1,?1)",
+                i64::MAX
+            ),
+        };
+        tx.execute(&sql, args).map_err(db_error::SqliteError)?;
+        PeerDB::reapply_filters(tx, column)
+    }
+
+    /// Re-apply all CIDR and ASN rules for the given column
+    fn reapply_filters<'a>(tx: &mut Transaction<'a>, column: &str) -> Result<(), db_error> {
+        let (cidrs, asns, value) = match column {
+            "denied" => (
+                PeerDB::get_denied_cidrs(tx)?,
+                PeerDB::get_denied_asns(tx)?,
+                i64::MAX,
+            ),
+            _ => (
+                PeerDB::get_allowed_cidrs(tx)?,
+                PeerDB::get_allowed_asns(tx)?,
+                -1,
+            ),
+        };
+        for (prefix, mask) in cidrs.into_iter() {
+            PeerDB::apply_cidr_filter(tx, &prefix, mask, column, value)?;
+        }
+        for asn in asns.into_iter() {
+            PeerDB::apply_asn_filter(tx, asn, column, value)?;
+        }
+        Ok(())
+    }
+
+    /// Remove an allowed CIDR prefix
+    pub fn remove_allow_cidr<'a>(
+        tx: &mut Transaction<'a>,
+        prefix: &PeerAddress,
+        mask: u32,
+    ) -> Result<(), db_error> {
+        assert!(mask > 0 && mask <= 128);
+        PeerDB::remove_cidr_prefix(tx, "allowed_prefixes", prefix, mask)?;
+
+        debug!("Remove allow {}/{}", &prefix, mask);
+        PeerDB::unapply_cidr_filter(tx, prefix, mask, "allowed")
+    }
+
+    /// Remove a denied CIDR prefix
+    pub fn remove_deny_cidr<'a>(
+        tx: &mut Transaction<'a>,
+        prefix: &PeerAddress,
+        mask: u32,
+    ) -> Result<(), db_error> {
+        assert!(mask > 0 && mask <= 128);
+        PeerDB::remove_cidr_prefix(tx, "denied_prefixes", prefix, mask)?;
+
+        debug!("Remove deny {}/{}", &prefix, mask);
+        PeerDB::unapply_cidr_filter(tx, prefix, mask, "denied")
+    }
+
+    /// Get all AS numbers from a given table
+    fn get_asns(conn: &DBConn, table: &str) -> Result<Vec<u32>, db_error> {
+        let sql_query = format!("SELECT asn FROM {} ORDER BY asn", table);
+        let mut stmt = conn.prepare(&sql_query)?;
+        let rows_res_iter = stmt
+            .query_map(NO_PARAMS, |row| row.get::<_, u32>(0))
+            .map_err(db_error::SqliteError)?;
+
+        let mut ret = vec![];
+        for row_res in rows_res_iter {
+            ret.push(row_res.map_err(db_error::SqliteError)?);
+        }
+
+        Ok(ret)
+    }
+
+    /// Get all denied AS numbers
+    pub fn get_denied_asns(conn: &DBConn) -> Result<Vec<u32>, db_error> {
+        PeerDB::get_asns(conn, "denied_asns")
+    }
+
+    /// Get all allowed AS numbers
+    pub fn get_allowed_asns(conn: &DBConn) -> Result<Vec<u32>, db_error> {
+        PeerDB::get_asns(conn, "allowed_asns")
+    }
+
+    fn has_asn(conn: &DBConn, table: &str, asn: u32) -> Result<bool, db_error> {
+        let count = query_count(
+            conn,
+            &format!("SELECT COUNT(*) FROM {} WHERE asn = ?1", table),
+            &[&asn as &dyn ToSql],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Are the peers in this AS denied?
+    pub fn is_asn_denied(conn: &DBConn, asn: u32) -> Result<bool, db_error> {
+        PeerDB::has_asn(conn, "denied_asns", asn)
+    }
+
+    /// Are the peers in this AS always allowed?
+    pub fn is_asn_allowed(conn: &DBConn, asn: u32) -> Result<bool, db_error> {
+        PeerDB::has_asn(conn, "allowed_asns", asn)
+    }
+
+    /// Update the given column to be equal to the given value for all peers in the given AS
+    fn apply_asn_filter<'a>(
+        tx: &mut Transaction<'a>,
+        asn: u32,
+        column: &str,
+        value: i64,
+    ) -> Result<(), db_error> {
+        assert!(asn != 0);
+        let args: &[&dyn ToSql] = &[&value, &asn];
+        tx.execute(
+            &format!("UPDATE frontier SET {} = ?1 WHERE asn = ?2", column),
+            args,
+        )
+        .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Always allow the peers in an AS
+    pub fn add_allow_asn<'a>(tx: &mut Transaction<'a>, asn: u32) -> Result<(), db_error> {
+        assert!(asn != 0);
+        tx.execute(
+            "INSERT OR REPLACE INTO allowed_asns (asn) VALUES (?1)",
+            &[&asn as &dyn ToSql],
+        )
+        .map_err(db_error::SqliteError)?;
+
+        debug!("Apply allow AS{}", asn);
+        PeerDB::apply_asn_filter(tx, asn, "allowed", -1)
+    }
+
+    /// Deny the peers in an AS
+    pub fn add_deny_asn<'a>(tx: &mut Transaction<'a>, asn: u32) -> Result<(), db_error> {
+        assert!(asn != 0);
+        tx.execute(
+            "INSERT OR REPLACE INTO denied_asns (asn) VALUES (?1)",
+            &[&asn as &dyn ToSql],
+        )
+        .map_err(db_error::SqliteError)?;
+
+        debug!("Apply deny AS{}", asn);
+        PeerDB::apply_asn_filter(tx, asn, "denied", i64::MAX)
+    }
+
+    /// Stop always allowing the peers in an AS.  Bootstrap peers stay allowed.
+    pub fn remove_allow_asn<'a>(tx: &mut Transaction<'a>, asn: u32) -> Result<(), db_error> {
+        tx.execute(
+            "DELETE FROM allowed_asns WHERE asn = ?1",
+            &[&asn as &dyn ToSql],
+        )
+        .map_err(db_error::SqliteError)?;
+
+        debug!("Remove allow AS{}", asn);
+        tx.execute(
+            &format!(
+                "UPDATE frontier SET allowed = 0 WHERE (allowed < 0 OR allowed = {}) AND initial = 0 AND asn = ?1",
+                i64::MAX
+            ),
+            &[&asn as &dyn ToSql],
+        )
+        .map_err(db_error::SqliteError)?;
+        PeerDB::reapply_filters(tx, "allowed")
+    }
+
+    /// Stop denying the peers in an AS.  Bans keep their deadlines.
+    pub fn remove_deny_asn<'a>(tx: &mut Transaction<'a>, asn: u32) -> Result<(), db_error> {
+        tx.execute(
+            "DELETE FROM denied_asns WHERE asn = ?1",
+            &[&asn as &dyn ToSql],
+        )
+        .map_err(db_error::SqliteError)?;
+
+        debug!("Remove deny AS{}", asn);
+        tx.execute(
+            &format!(
+                "UPDATE frontier SET denied = 0 WHERE denied = {} AND asn = ?1",
+                i64::MAX
+            ),
+            &[&asn as &dyn ToSql],
+        )
+        .map_err(db_error::SqliteError)?;
+        PeerDB::reapply_filters(tx, "denied")
+    }
+
     /// Get random neighbors, optionally always including allowed neighbors
     pub fn get_random_neighbors(
         conn: &DBConn,
@@ -1445,6 +1860,45 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_local_peer_advertised_address() {
+        let db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+        let mut local_peer = PeerDB::get_local_peer(db.conn()).unwrap();
+
+        let ipv4_peer = PeerAddress::from_ipv4(1, 2, 3, 4);
+        let ipv6_peer = PeerAddress([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        let public_ipv4 = (PeerAddress::from_ipv4(5, 6, 7, 8), 20444);
+        let public_ipv6 = (
+            PeerAddress([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]),
+            20445,
+        );
+
+        // nothing learned yet, so advertise the bind address
+        let bind_addr = (local_peer.addrbytes.clone(), local_peer.port);
+        assert_eq!(local_peer.advertised_address(None), bind_addr);
+        assert_eq!(local_peer.advertised_address(Some(&ipv6_peer)), bind_addr);
+
+        // single-stack: everyone gets the one public address
+        local_peer.public_ip_address = Some(public_ipv4.clone());
+        assert_eq!(local_peer.advertised_address(None), public_ipv4);
+        assert_eq!(local_peer.advertised_address(Some(&ipv4_peer)), public_ipv4);
+        assert_eq!(local_peer.advertised_address(Some(&ipv6_peer)), public_ipv4);
+
+        // dual-stack: each peer gets the address in its own family
+        local_peer.alt_public_ip_address = Some(public_ipv6.clone());
+        assert_eq!(local_peer.advertised_address(None), public_ipv4);
+        assert_eq!(local_peer.advertised_address(Some(&ipv4_peer)), public_ipv4);
+        assert_eq!(local_peer.advertised_address(Some(&ipv6_peer)), public_ipv6);
+    }
+
     #[test]
     fn test_peer_insert_and_retrieval() {
         let neighbor = Neighbor {
@@ -1946,6 +2400,50 @@ mod test {
         assert_eq!(peer_allowed.allowed, 20000000);
     }
 
+    #[test]
+    fn test_get_denied_peers() {
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+        let now = util::get_epoch_time_secs();
+        {
+            let mut tx = db.tx_begin().unwrap();
+            // banned for a while
+            PeerDB::set_deny_peer(
+                &mut tx,
+                0x9abcdef0,
+                &PeerAddress([0x1; 16]),
+                12345,
+                now + 3600,
+            )
+            .unwrap();
+            // ban already lifted
+            PeerDB::set_deny_peer(&mut tx, 0x9abcdef0, &PeerAddress([0x2; 16]), 12345, now - 1)
+                .unwrap();
+            // some other network
+            PeerDB::set_deny_peer(
+                &mut tx,
+                0x9abcdef1,
+                &PeerAddress([0x3; 16]),
+                12345,
+                now + 3600,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+
+        let denied = PeerDB::get_denied_peers(db.conn(), 0x9abcdef0).unwrap();
+        assert_eq!(denied.len(), 1);
+        assert_eq!(denied[0].addr.addrbytes, PeerAddress([0x1; 16]));
+        assert_eq!(denied[0].denied, (now + 3600) as i64);
+    }
+
     #[test]
     fn test_peer_cidr_lists() {
         let mut db = PeerDB::connect_memory(
@@ -2323,4 +2821,269 @@ mod test {
         assert_eq!(n1.allowed, 0);
         assert_eq!(n2.allowed, 0);
     }
+
+    #[test]
+    fn test_peer_cidr_asn_rules() {
+        let make_neighbor = |addrbytes: PeerAddress, asn: u32| Neighbor {
+            addr: NeighborKey {
+                peer_version: 0x12345678,
+                network_id: 0x9abcdef0,
+                addrbytes,
+                port: 12345,
+            },
+            public_key: Secp256k1PublicKey::from_hex(
+                "02fa66b66f8971a8cd4d20ffded09674e030f0f33883f337f34b95ad4935bac0e3",
+            )
+            .unwrap(),
+            expire_block: 23456,
+            last_contact_time: 1552509642,
+            allowed: 0,
+            denied: 0,
+            asn,
+            org: 45678,
+            in_degree: 1,
+            out_degree: 1,
+        };
+        let neighbor_1 = make_neighbor(PeerAddress::from_ipv4(198, 51, 100, 1), 64496);
+        let neighbor_2 = make_neighbor(PeerAddress::from_ipv4(198, 51, 100, 2), 64497);
+        let bootstrap = make_neighbor(PeerAddress::from_ipv4(192, 0, 2, 1), 64498);
+
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![bootstrap.clone()],
+        )
+        .unwrap();
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::try_insert_peer(&mut tx, &neighbor_1).unwrap();
+            PeerDB::try_insert_peer(&mut tx, &neighbor_2).unwrap();
+            PeerDB::set_allow_peer(
+                &mut tx,
+                bootstrap.addr.network_id,
+                &bootstrap.addr.addrbytes,
+                bootstrap.addr.port,
+                -1,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+
+        let get = |db: &PeerDB, n: &Neighbor| {
+            PeerDB::get_peer(db.conn(), n.addr.network_id, &n.addr.addrbytes, n.addr.port)
+                .unwrap()
+                .unwrap()
+        };
+        let is_denied = |db: &PeerDB, n: &Neighbor| {
+            PeerDB::is_peer_denied(db.conn(), n.addr.network_id, &n.addr.addrbytes, n.addr.port)
+                .unwrap()
+        };
+
+        let (prefix, mask) = PeerAddress::from_cidr_str("198.51.100.0/24").unwrap();
+        assert_eq!(mask, 120);
+        assert_eq!(prefix.to_cidr_string(mask), "198.51.100.0/24");
+        let (prefix_6, mask_6) = PeerAddress::from_cidr_str("2001:db8::/32").unwrap();
+        assert_eq!(mask_6, 32);
+        assert_eq!(prefix_6.to_cidr_string(mask_6), "2001:db8::/32");
+        assert!(PeerAddress::from_cidr_str("198.51.100.0/33").is_none());
+        assert!(PeerAddress::from_cidr_str("2001:db8::/0").is_none());
+        assert!(PeerAddress::from_cidr_str("198.51.100.0").is_none());
+
+        // a CIDR deny covers both neighbors; removing it un-denies them
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::add_deny_cidr(&mut tx, &prefix, mask).unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(is_denied(&db, &neighbor_1));
+        assert!(is_denied(&db, &neighbor_2));
+
+        // ...unless an ASN deny still covers one of them
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::add_deny_asn(&mut tx, 64496).unwrap();
+            PeerDB::remove_deny_cidr(&mut tx, &prefix, mask).unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(PeerDB::get_denied_cidrs(db.conn()).unwrap().is_empty());
+        assert_eq!(PeerDB::get_denied_asns(db.conn()).unwrap(), vec![64496]);
+        assert!(is_denied(&db, &neighbor_1));
+        assert!(!is_denied(&db, &neighbor_2));
+        assert_eq!(get(&db, &neighbor_2).denied, 0);
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::remove_deny_asn(&mut tx, 64496).unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(!is_denied(&db, &neighbor_1));
+        assert_eq!(get(&db, &neighbor_1).denied, 0);
+
+        // removing an allow rule leaves bootstrap peers allowed
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::add_allow_asn(&mut tx, 64497).unwrap();
+            PeerDB::add_allow_asn(&mut tx, 64498).unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(get(&db, &neighbor_2).allowed < 0);
+        assert_eq!(
+            PeerDB::get_allowed_asns(db.conn()).unwrap(),
+            vec![64497, 64498]
+        );
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::remove_allow_asn(&mut tx, 64497).unwrap();
+            PeerDB::remove_allow_asn(&mut tx, 64498).unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(get(&db, &neighbor_2).allowed, 0);
+        assert!(get(&db, &bootstrap).allowed < 0);
+    }
+
+    #[test]
+    fn test_peer_onion_hosts() {
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+
+        let host = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+        let addrbytes = {
+            let mut tx = db.tx_begin().unwrap();
+            let addrbytes = PeerDB::set_onion_host(&mut tx, &host.to_uppercase()).unwrap();
+            tx.commit().unwrap();
+            addrbytes
+        };
+
+        assert!(addrbytes.is_onion());
+        assert_eq!(addrbytes, PeerAddress::from_onion_host(host));
+        assert_eq!(
+            PeerDB::get_onion_host(db.conn(), &addrbytes).unwrap(),
+            Some(host.to_string())
+        );
+        assert_eq!(
+            PeerDB::get_onion_host(db.conn(), &PeerAddress([0x1; 16])).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_peer_scores() {
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+
+        let neighbors: Vec<_> = (1..6)
+            .map(|i| Neighbor {
+                addr: NeighborKey {
+                    peer_version: 0x12345678,
+                    network_id: 0x9abcdef0,
+                    addrbytes: PeerAddress([i; 16]),
+                    port: 12345,
+                },
+                public_key: Secp256k1PublicKey::from_hex(
+                    "02fa66b66f8971a8cd4d20ffded09674e030f0f33883f337f34b95ad4935bac0e3",
+                )
+                .unwrap(),
+                expire_block: 23456,
+                last_contact_time: 1552509642,
+                allowed: 0,
+                denied: 0,
+                asn: 34567,
+                org: 45678,
+                in_degree: 1,
+                out_degree: 1,
+            })
+            .collect();
+
+        let now = util::get_epoch_time_secs();
+        {
+            let mut tx = db.tx_begin().unwrap();
+            for neighbor in neighbors.iter() {
+                assert!(PeerDB::try_insert_peer(&mut tx, neighbor).unwrap());
+            }
+            PeerDB::set_peer_score(&mut tx, &neighbors[0].addr, 50.0, now).unwrap();
+            PeerDB::set_peer_score(&mut tx, &neighbors[1].addr, 90.0, now).unwrap();
+            // scored too long ago
+            PeerDB::set_peer_score(&mut tx, &neighbors[2].addr, 70.0, now - 1000).unwrap();
+            // denied
+            PeerDB::set_peer_score(&mut tx, &neighbors[3].addr, 95.0, now).unwrap();
+            PeerDB::set_deny_peer(
+                &mut tx,
+                0x9abcdef0,
+                &neighbors[3].addr.addrbytes,
+                12345,
+                now + 3600,
+            )
+            .unwrap();
+            // neighbors[4] was never scored
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(
+            PeerDB::get_peer_score(db.conn(), &neighbors[1].addr).unwrap(),
+            Some((90.0, now))
+        );
+        assert_eq!(
+            PeerDB::get_peer_score(db.conn(), &neighbors[4].addr).unwrap(),
+            None
+        );
+
+        let best: Vec<_> =
+            PeerDB::get_best_scored_peers(db.conn(), 0x9abcdef0, 0x05, 10, 12345, now - 100)
+                .unwrap()
+                .into_iter()
+                .map(|n| n.addr)
+                .collect();
+        assert_eq!(
+            best,
+            vec![neighbors[1].addr.clone(), neighbors[0].addr.clone()]
+        );
+
+        let best: Vec<_> =
+            PeerDB::get_best_scored_peers(db.conn(), 0x9abcdef0, 0x05, 1, 12345, now - 10000)
+                .unwrap()
+                .into_iter()
+                .map(|n| n.addr)
+                .collect();
+        assert_eq!(best, vec![neighbors[1].addr.clone()]);
+
+        // expired keys
+        assert_eq!(
+            PeerDB::get_best_scored_peers(db.conn(), 0x9abcdef0, 0x05, 10, 23456, 0)
+                .unwrap()
+                .len(),
+            0
+        );
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::prune_peer_scores(&mut tx, now - 100).unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(
+            PeerDB::get_peer_score(db.conn(), &neighbors[2].addr).unwrap(),
+            None
+        );
+        assert_eq!(
+            PeerDB::get_peer_score(db.conn(), &neighbors[0].addr).unwrap(),
+            Some((50.0, now))
+        );
+    }
 }
@@ -24,6 +24,7 @@ use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
 use std::io::Read;
 use std::io::Write;
+use std::mem;
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::sync::mpsc::sync_channel;
@@ -51,6 +52,7 @@ use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use core::FIRST_STACKS_BLOCK_HASH;
 use net::asn::ASEntry4;
 use net::atlas::AttachmentsDownloader;
+use net::backoff::{PeerFailures, RetryPolicy};
 use net::codec::*;
 use net::connection::ConnectionOptions;
 use net::connection::ReplyHandleHttp;
@@ -86,12 +88,6 @@ pub const BLOCK_DOWNLOAD_INTERVAL: u64 = 180;
 #[cfg(test)]
 pub const BLOCK_DOWNLOAD_INTERVAL: u64 = 0;
 
-/// If a URL never connects, don't use it again for this many seconds
-#[cfg(not(test))]
-pub const BLOCK_DOWNLOAD_BAN_URL: u64 = 300;
-#[cfg(test)]
-pub const BLOCK_DOWNLOAD_BAN_URL: u64 = 60;
-
 /// If we created a request to download a block or microblock, don't do so again until this many
 /// seconds have passed.
 #[cfg(not(test))]
@@ -218,6 +214,12 @@ pub struct BlockDownloader {
     /// Maximum number of concurrent requests
     max_inflight_requests: u64,
 
+    /// Maximum number of concurrent requests to a single peer
+    max_inflight_requests_per_peer: u64,
+
+    /// How long a request can go unanswered before we ask an idle peer for the same data
+    steal_timeout: u64,
+
     /// Block requests to try, grouped by block, keyed by sortition height
     blocks_to_try: HashMap<u64, VecDeque<BlockRequestKey>>,
 
@@ -233,6 +235,8 @@ pub struct BlockDownloader {
     /// The key for each of these is the sortition height and _index_ block hash.
     getblock_requests: HashMap<BlockRequestKey, usize>,
     getmicroblocks_requests: HashMap<BlockRequestKey, usize>,
+    inflight_since: HashMap<BlockRequestKey, u64>,
+    cancelled_requests: Vec<usize>,
     blocks: HashMap<BlockRequestKey, StacksBlock>,
     microblocks: HashMap<BlockRequestKey, Vec<StacksMicroblock>>,
 
@@ -241,7 +245,8 @@ pub struct BlockDownloader {
     broken_peers: Vec<usize>,
     broken_neighbors: Vec<NeighborKey>, // disconnect peers who report invalid block inventories too
 
-    blocked_urls: HashMap<UrlString, u64>, // URLs that chronically don't work, and when we can try them again
+    url_failures: PeerFailures<UrlString>, // URLs that have been failing, and when we can try them again
+    retry_policy: RetryPolicy,
 
     /// how often to download
     download_interval: u64,
@@ -256,6 +261,9 @@ impl BlockDownloader {
         dns_timeout: u128,
         download_interval: u64,
         max_inflight_requests: u64,
+        max_inflight_requests_per_peer: u64,
+        steal_timeout: u64,
+        retry_policy: RetryPolicy,
     ) -> BlockDownloader {
         BlockDownloader {
             state: BlockDownloaderState::DNSLookupBegin,
@@ -274,6 +282,8 @@ impl BlockDownloader {
             last_inv_update_at: 0,
 
             max_inflight_requests: max_inflight_requests,
+            max_inflight_requests_per_peer,
+            steal_timeout,
             blocks_to_try: HashMap::new(),
             microblocks_to_try: HashMap::new(),
 
@@ -283,13 +293,16 @@ impl BlockDownloader {
 
             getblock_requests: HashMap::new(),
             getmicroblocks_requests: HashMap::new(),
+            inflight_since: HashMap::new(),
+            cancelled_requests: vec![],
             blocks: HashMap::new(),
             microblocks: HashMap::new(),
 
             dead_peers: vec![],
             broken_peers: vec![],
             broken_neighbors: vec![],
-            blocked_urls: HashMap::new(),
+            url_failures: PeerFailures::new(),
+            retry_policy,
 
             download_interval: download_interval,
             requested_blocks: HashMap::new(),
@@ -306,6 +319,7 @@ impl BlockDownloader {
 
         self.getblock_requests.clear();
         self.getmicroblocks_requests.clear();
+        self.inflight_since.clear();
         self.blocks_to_try.clear();
         self.microblocks_to_try.clear();
         self.blocks.clear();
@@ -314,6 +328,8 @@ impl BlockDownloader {
         self.dead_peers.clear();
         self.broken_peers.clear();
         self.broken_neighbors.clear();
+        self.url_failures
+            .prune(&self.retry_policy, get_epoch_time_secs());
 
         // perserve sortition height
         // preserve download accounting
@@ -471,17 +487,21 @@ impl BlockDownloader {
                                 debug!("Event {} ({:?}, {:?}) for block {} failed to connect. Temporarily blocking URL", event_id, &block_key.neighbor, &block_key.data_url, &block_key.index_block_hash);
 
                                 // don't try this again for a while
-                                self.blocked_urls.insert(
+                                self.url_failures.record_failure(
                                     block_key.data_url,
-                                    get_epoch_time_secs() + BLOCK_DOWNLOAD_BAN_URL,
+                                    &self.retry_policy,
+                                    get_epoch_time_secs(),
                                 );
                             } else {
                                 debug!("Event {} ({:?}, {:?}, always-allowed) for block {} failed to connect", event_id, &block_key.neighbor, &block_key.data_url, &block_key.index_block_hash);
 
                                 if cfg!(test) {
-                                    // just mark that we would have blocked it
-                                    self.blocked_urls
-                                        .insert(block_key.data_url, get_epoch_time_secs() + 10);
+                                    // just mark that we would have blocked it (but only briefly)
+                                    self.url_failures.record_failure(
+                                        block_key.data_url,
+                                        &RetryPolicy::new(10, 10, 0.0),
+                                        get_epoch_time_secs(),
+                                    );
                                 }
                             }
                         }
@@ -503,6 +523,21 @@ impl BlockDownloader {
                                         info!("Invalid block from {:?} ({:?}): did not ask for block {}/{}", &block_key.neighbor, &block_key.data_url, block_key.consensus_hash, block.block_hash());
                                         self.broken_peers.push(event_id);
                                         self.broken_neighbors.push(block_key.neighbor.clone());
+                                        self.url_failures.record_failure(
+                                            block_key.data_url.clone(),
+                                            &self.retry_policy,
+                                            get_epoch_time_secs(),
+                                        );
+                                    } else if self.blocks.keys().any(|key| {
+                                        key.index_block_hash == block_key.index_block_hash
+                                    }) {
+                                        // another peer beat this one to it
+                                        debug!(
+                                            "Already got block {}: {}/{}",
+                                            &block_key.sortition_height,
+                                            &block_key.consensus_hash,
+                                            block.block_hash()
+                                        );
                                     } else {
                                         // got the block
                                         debug!(
@@ -511,6 +546,7 @@ impl BlockDownloader {
                                             &block_key.consensus_hash,
                                             block.block_hash()
                                         );
+                                        self.url_failures.record_success(&block_key.data_url);
                                         self.blocks.insert(block_key, block);
                                     }
                                 }
@@ -523,6 +559,11 @@ impl BlockDownloader {
                                     // it was present, so the absence is the mark of a broken peer
                                     self.broken_peers.push(event_id);
                                     self.broken_neighbors.push(block_key.neighbor.clone());
+                                    self.url_failures.record_failure(
+                                        block_key.data_url.clone(),
+                                        &self.retry_policy,
+                                        get_epoch_time_secs(),
+                                    );
                                 }
                                 _ => {
                                     // wrong message response
@@ -532,6 +573,11 @@ impl BlockDownloader {
                                     );
                                     self.broken_peers.push(event_id);
                                     self.broken_neighbors.push(block_key.neighbor.clone());
+                                    self.url_failures.record_failure(
+                                        block_key.data_url.clone(),
+                                        &self.retry_policy,
+                                        get_epoch_time_secs(),
+                                    );
                                 }
                             },
                         }
@@ -540,6 +586,20 @@ impl BlockDownloader {
             }
         });
 
+        // stop waiting on slower peers for blocks we already have
+        let have_blocks: HashSet<StacksBlockId> = self
+            .blocks
+            .keys()
+            .map(|key| key.index_block_hash.clone())
+            .collect();
+        BlockDownloader::cancel_requests(
+            &mut pending_block_requests,
+            &have_blocks,
+            &mut self.cancelled_requests,
+        );
+        let inflight_since = &mut self.inflight_since;
+        inflight_since.retain(|key, _| pending_block_requests.contains_key(key));
+
         // are we done?
         if pending_block_requests.len() == 0 {
             self.state = BlockDownloaderState::GetMicroblocksBegin;
@@ -601,9 +661,10 @@ impl BlockDownloader {
                                 );
 
                                 // don't try this again for a while
-                                self.blocked_urls.insert(
+                                self.url_failures.record_failure(
                                     block_key.data_url,
-                                    get_epoch_time_secs() + BLOCK_DOWNLOAD_BAN_URL,
+                                    &self.retry_policy,
+                                    get_epoch_time_secs(),
                                 );
                             }
                         }
@@ -622,6 +683,19 @@ impl BlockDownloader {
                                         info!("Got unexpected zero-length microblock stream from {:?} ({:?})", &block_key.neighbor, &block_key.data_url);
                                         self.broken_peers.push(event_id);
                                         self.broken_neighbors.push(block_key.neighbor.clone());
+                                        self.url_failures.record_failure(
+                                            block_key.data_url.clone(),
+                                            &self.retry_policy,
+                                            get_epoch_time_secs(),
+                                        );
+                                    } else if self.microblocks.keys().any(|key| {
+                                        key.index_block_hash == block_key.index_block_hash
+                                    }) {
+                                        // another peer beat this one to it
+                                        debug!(
+                                            "Already got microblocks {}: {}",
+                                            block_key.sortition_height, &block_key.index_block_hash
+                                        );
                                     } else {
                                         // have microblocks (but we don't know yet if they're well-formed)
                                         debug!(
@@ -631,6 +705,7 @@ impl BlockDownloader {
                                             &block_key.index_block_hash,
                                             microblocks[0].block_hash()
                                         );
+                                        self.url_failures.record_success(&block_key.data_url);
                                         self.microblocks.insert(block_key, microblocks);
                                     }
                                 }
@@ -646,12 +721,22 @@ impl BlockDownloader {
                                     // invalid microblock streams as present, even though they are
                                     // truly absent.  Don't punish these peers with a ban; just don't
                                     // talk to them for a while.
+                                    self.url_failures.record_failure(
+                                        block_key.data_url.clone(),
+                                        &self.retry_policy,
+                                        get_epoch_time_secs(),
+                                    );
                                 }
                                 _ => {
                                     // wrong message response
                                     info!("Got bad HTTP response from {:?}", &block_key.data_url);
                                     self.broken_peers.push(event_id);
                                     self.broken_neighbors.push(block_key.neighbor.clone());
+                                    self.url_failures.record_failure(
+                                        block_key.data_url.clone(),
+                                        &self.retry_policy,
+                                        get_epoch_time_secs(),
+                                    );
                                 }
                             },
                         }
@@ -660,6 +745,20 @@ impl BlockDownloader {
             }
         });
 
+        // stop waiting on slower peers for microblocks we already have
+        let have_microblocks: HashSet<StacksBlockId> = self
+            .microblocks
+            .keys()
+            .map(|key| key.index_block_hash.clone())
+            .collect();
+        BlockDownloader::cancel_requests(
+            &mut pending_microblock_requests,
+            &have_microblocks,
+            &mut self.cancelled_requests,
+        );
+        let inflight_since = &mut self.inflight_since;
+        inflight_since.retain(|key, _| pending_microblock_requests.contains_key(key));
+
         // are we done?
         if pending_microblock_requests.len() == 0 {
             self.state = BlockDownloaderState::Done;
@@ -673,6 +772,167 @@ impl BlockDownloader {
         return Ok(false);
     }
 
+    /// Drop the pending requests for data we already got from another peer, and remember their
+    /// event IDs so their HTTP connections can be closed.
+    fn cancel_requests(
+        pending: &mut HashMap<BlockRequestKey, usize>,
+        have: &HashSet<StacksBlockId>,
+        cancelled: &mut Vec<usize>,
+    ) {
+        pending.retain(|key, event_id| {
+            if have.contains(&key.index_block_hash) {
+                debug!(
+                    "Cancel request for {} from {:?} ({:?}), since another peer sent it",
+                    &key.index_block_hash, &key.neighbor, &key.data_url
+                );
+                cancelled.push(*event_id);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Start as many of the requests we have yet to make as each peer's in-flight limit allows.
+    /// Consecutive sortitions go to the same peer until it is at its limit, so each peer works on
+    /// its own range of the inventory.  Then, any request that has gone unanswered for too long is
+    /// also sent to an idle peer that can serve it, and we keep whichever answer comes first.
+    fn schedule_requests(
+        &mut self,
+        network: &mut PeerNetwork,
+        microblocks: bool,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) {
+        let now = get_epoch_time_secs();
+        let done_heights: HashSet<u64> = if microblocks {
+            self.microblocks
+                .keys()
+                .map(|key| key.sortition_height)
+                .collect()
+        } else {
+            self.blocks.keys().map(|key| key.sortition_height).collect()
+        };
+        let (to_try, inflight) = if microblocks {
+            (
+                &mut self.microblocks_to_try,
+                &mut self.getmicroblocks_requests,
+            )
+        } else {
+            (&mut self.blocks_to_try, &mut self.getblock_requests)
+        };
+
+        // how busy is each peer, and which sortitions are being worked on?
+        let mut load: HashMap<UrlString, u64> = HashMap::new();
+        let mut busy_heights: HashMap<u64, u64> = HashMap::new();
+        for key in inflight.keys() {
+            *load.entry(key.data_url.clone()).or_insert(0) += 1;
+            *busy_heights.entry(key.sortition_height).or_insert(0) += 1;
+        }
+
+        // hand out the sortitions nobody is working on yet
+        let mut last_url: Option<UrlString> = None;
+        for height in PeerNetwork::prioritize_requests(to_try).into_iter() {
+            if busy_heights.contains_key(&height) || done_heights.contains(&height) {
+                continue;
+            }
+            let keys = match to_try.get_mut(&height) {
+                Some(keys) => keys,
+                None => {
+                    continue;
+                }
+            };
+
+            // prefer the peer that got the last sortition, and then the least-busy peers
+            let max_per_peer = self.max_inflight_requests_per_peer;
+            let (mut candidates, deferred): (Vec<BlockRequestKey>, Vec<BlockRequestKey>) =
+                keys.drain(..).partition(|key| {
+                    max_per_peer == 0 || *load.get(&key.data_url).unwrap_or(&0) < max_per_peer
+                });
+            candidates.sort_by_key(|key| {
+                (
+                    Some(&key.data_url) != last_url.as_ref(),
+                    *load.get(&key.data_url).unwrap_or(&0),
+                )
+            });
+
+            let mut candidates: VecDeque<BlockRequestKey> = candidates.into_iter().collect();
+            let started = PeerNetwork::begin_request(
+                network,
+                &self.dns_lookups,
+                &mut candidates,
+                mempool,
+                chainstate,
+            );
+            keys.extend(candidates);
+            keys.extend(deferred);
+
+            if let Some((key, event_id)) = started {
+                *load.entry(key.data_url.clone()).or_insert(0) += 1;
+                busy_heights.insert(height, 1);
+                last_url = Some(key.data_url.clone());
+                self.inflight_since.insert(key.clone(), now);
+                inflight.insert(key, event_id);
+            }
+        }
+
+        let steal_timeout = self.steal_timeout;
+        if steal_timeout == 0 {
+            return;
+        }
+
+        // have idle peers race the peers that are taking too long
+        let inflight_since = &self.inflight_since;
+        let mut slow_keys: Vec<BlockRequestKey> = inflight
+            .keys()
+            .filter(|key| {
+                *busy_heights.get(&key.sortition_height).unwrap_or(&0) == 1
+                    && inflight_since
+                        .get(key)
+                        .map(|since| since + steal_timeout <= now)
+                        .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        slow_keys.sort_by_key(|key| key.sortition_height);
+
+        for slow_key in slow_keys.into_iter() {
+            let keys = match to_try.get_mut(&slow_key.sortition_height) {
+                Some(keys) => keys,
+                None => {
+                    continue;
+                }
+            };
+            let idx = match keys
+                .iter()
+                .position(|key| *load.get(&key.data_url).unwrap_or(&0) == 0)
+            {
+                Some(idx) => idx,
+                None => {
+                    continue;
+                }
+            };
+
+            let mut candidates: VecDeque<BlockRequestKey> = keys.remove(idx).into_iter().collect();
+            if let Some((key, event_id)) = PeerNetwork::begin_request(
+                network,
+                &self.dns_lookups,
+                &mut candidates,
+                mempool,
+                chainstate,
+            ) {
+                debug!(
+                    "{:?}: {:?} is taking too long to send {}; also asking {:?}",
+                    &network.local_peer, &slow_key.data_url, &key.index_block_hash, &key.data_url
+                );
+                *load.entry(key.data_url.clone()).or_insert(0) += 1;
+                busy_heights.insert(key.sortition_height, 2);
+                self.inflight_since.insert(key.clone(), now);
+                inflight.insert(key, event_id);
+            }
+        }
+    }
+
     /// Get the availability of each block in the given sortition range, using the inv state.
     /// Return the local block headers, paired with the list of peers that can serve them.
     /// Possibly less than the given range request.
@@ -1457,8 +1717,11 @@ impl PeerNetwork {
                     continue;
                 }
 
-                let prev_blocked = match downloader.blocked_urls.get(&data_url) {
-                    Some(deadline) if get_epoch_time_secs() < *deadline => {
+                let prev_blocked = match downloader
+                    .url_failures
+                    .retry_at(&data_url, get_epoch_time_secs())
+                {
+                    Some(deadline) => {
                         debug!(
                             "{:?}: Will not request {} {}/{} from {:?} (of {:?}) until after {}",
                             &self.local_peer,
@@ -1548,13 +1811,14 @@ impl PeerNetwork {
         self.make_requests(sortdb, chainstate, downloader, start_sortition_height, true)
     }
 
-    /// Prioritize block requests -- ask for the rarest blocks first
+    /// Prioritize block requests -- ask for the rarest blocks first, and for equally-rare blocks
+    /// in sortition order
     fn prioritize_requests(requests: &HashMap<u64, VecDeque<BlockRequestKey>>) -> Vec<u64> {
         let mut ordered = vec![];
         for (block_height, requests) in requests.iter() {
             ordered.push((*block_height, requests.len()));
         }
-        ordered.sort_by(|(_, ref l1), (_, ref l2)| l1.cmp(l2));
+        ordered.sort_by(|(h1, ref l1), (h2, ref l2)| l1.cmp(l2).then(h1.cmp(h2)));
         ordered.iter().map(|(ref h, _)| *h).collect()
     }
 
@@ -2017,44 +2281,30 @@ impl PeerNetwork {
     ) -> Result<(), net_error> {
         test_debug!("{:?}: block_getblocks_begin", &self.local_peer);
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
-            let mut priority = PeerNetwork::prioritize_requests(&downloader.blocks_to_try);
-            let mut requests = HashMap::new();
-            for sortition_height in priority.drain(..) {
-                match downloader.blocks_to_try.get_mut(&sortition_height) {
-                    Some(ref mut keys) => {
-                        match PeerNetwork::begin_request(
-                            network,
-                            &downloader.dns_lookups,
-                            keys,
-                            mempool,
-                            chainstate,
-                        ) {
-                            Some((key, handle)) => {
-                                requests.insert(key.clone(), handle);
-                            }
-                            None => {}
-                        }
-                    }
-                    None => {
-                        debug!(
-                            "{:?}: No block at sortition height {}",
-                            &network.local_peer, sortition_height
-                        );
-                    }
-                }
-            }
-
-            downloader.getblocks_begin(requests);
+            downloader.getblocks_begin(HashMap::new());
+            downloader.schedule_requests(network, false, mempool, chainstate);
             Ok(())
         })
     }
 
-    /// Try to see if all blocks are finished downloading
-    pub fn block_getblocks_try_finish(&mut self) -> Result<bool, net_error> {
+    /// Try to see if all blocks are finished downloading.  Peers that finish their requests are
+    /// given more in the meantime.
+    pub fn block_getblocks_try_finish(
+        &mut self,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<bool, net_error> {
         test_debug!("{:?}: block_getblocks_try_finish", &self.local_peer);
-        PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
-            downloader.getblocks_try_finish(network)
-        })
+        let (done, cancelled) =
+            PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
+                let done = downloader.getblocks_try_finish(network)?;
+                if !done {
+                    downloader.schedule_requests(network, false, mempool, chainstate);
+                }
+                Ok((done, mem::take(&mut downloader.cancelled_requests)))
+            })?;
+        self.cancel_http_requests(cancelled);
+        Ok(done)
     }
 
     /// Proceed to get microblocks
@@ -2065,44 +2315,45 @@ impl PeerNetwork {
     ) -> Result<(), net_error> {
         test_debug!("{:?}: block_getmicroblocks_begin", &self.local_peer);
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
-            let mut priority = PeerNetwork::prioritize_requests(&downloader.microblocks_to_try);
-            let mut requests = HashMap::new();
-            for sortition_height in priority.drain(..) {
-                match downloader.microblocks_to_try.get_mut(&sortition_height) {
-                    Some(ref mut keys) => {
-                        match PeerNetwork::begin_request(
-                            network,
-                            &downloader.dns_lookups,
-                            keys,
-                            mempool,
-                            chainstate,
-                        ) {
-                            Some((key, handle)) => {
-                                requests.insert(key.clone(), handle);
-                            }
-                            None => {}
-                        }
-                    }
-                    None => {
-                        debug!(
-                            "{:?}: No microblocks at sortition height {}",
-                            &network.local_peer, sortition_height
-                        );
-                    }
-                }
-            }
-
-            downloader.getmicroblocks_begin(requests);
+            downloader.getmicroblocks_begin(HashMap::new());
+            downloader.schedule_requests(network, true, mempool, chainstate);
             Ok(())
         })
     }
 
-    /// Try to see if all microblocks are finished downloading
-    pub fn block_getmicroblocks_try_finish(&mut self) -> Result<bool, net_error> {
+    /// Try to see if all microblocks are finished downloading.  Peers that finish their requests
+    /// are given more in the meantime.
+    pub fn block_getmicroblocks_try_finish(
+        &mut self,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<bool, net_error> {
         test_debug!("{:?}: block_getmicroblocks_try_finish", &self.local_peer);
-        PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
-            downloader.getmicroblocks_try_finish(network)
-        })
+        let (done, cancelled) =
+            PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
+                let done = downloader.getmicroblocks_try_finish(network)?;
+                if !done {
+                    downloader.schedule_requests(network, true, mempool, chainstate);
+                }
+                Ok((done, mem::take(&mut downloader.cancelled_requests)))
+            })?;
+        self.cancel_http_requests(cancelled);
+        Ok(done)
+    }
+
+    /// Close the HTTP connections of block and microblock requests we no longer need answered
+    fn cancel_http_requests(&mut self, event_ids: Vec<usize>) {
+        if event_ids.is_empty() {
+            return;
+        }
+        let _ = PeerNetwork::with_network_state(self, |ref mut network, ref mut network_state| {
+            PeerNetwork::with_http(network, |_, ref mut http| {
+                for event_id in event_ids.into_iter() {
+                    http.deregister_http(network_state, event_id);
+                }
+            });
+            Ok(())
+        });
     }
 
     /// Process newly-fetched blocks and microblocks.
@@ -2364,6 +2615,9 @@ impl PeerNetwork {
             self.connection_opts.dns_timeout,
             self.connection_opts.download_interval,
             self.connection_opts.max_inflight_blocks,
+            self.connection_opts.max_inflight_blocks_per_peer,
+            self.connection_opts.block_download_steal_timeout,
+            self.connection_opts.download_retry_policy(),
         ));
     }
 
@@ -2490,13 +2744,13 @@ impl PeerNetwork {
                     self.block_getblocks_begin(mempool, chainstate)?;
                 }
                 BlockDownloaderState::GetBlocksFinish => {
-                    self.block_getblocks_try_finish()?;
+                    self.block_getblocks_try_finish(mempool, chainstate)?;
                 }
                 BlockDownloaderState::GetMicroblocksBegin => {
                     self.block_getmicroblocks_begin(mempool, chainstate)?;
                 }
                 BlockDownloaderState::GetMicroblocksFinish => {
-                    self.block_getmicroblocks_try_finish()?;
+                    self.block_getmicroblocks_try_finish(mempool, chainstate)?;
                 }
                 BlockDownloaderState::Done => {
                     // did a pass.
@@ -3532,7 +3786,7 @@ pub mod test {
                 let mut blocked = 0;
                 match peer.network.block_downloader {
                     Some(ref dl) => {
-                        blocked = dl.blocked_urls.len();
+                        blocked = dl.url_failures.len();
                     }
                     None => {}
                 }
@@ -3775,4 +4029,133 @@ pub mod test {
             );
         })
     }
+
+    fn make_block_request_key(height: u64, port: u16) -> BlockRequestKey {
+        let consensus_hash = ConsensusHash([height as u8; 20]);
+        let block_hash = BlockHeaderHash([height as u8; 32]);
+        let index_block_hash =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash);
+        BlockRequestKey::new(
+            NeighborKey {
+                peer_version: 0x18000000,
+                network_id: 0x80000000,
+                addrbytes: PeerAddress::from_ipv4(127, 0, 0, 1),
+                port,
+            },
+            UrlString::try_from(format!("http://127.0.0.1:{}", port)).unwrap(),
+            consensus_hash,
+            block_hash,
+            index_block_hash,
+            None,
+            None,
+            height,
+            BlockRequestKeyKind::Block,
+        )
+    }
+
+    #[test]
+    fn test_schedule_block_requests() {
+        use std::net::TcpListener;
+
+        let ports = [3280, 3282, 3284];
+        let _listeners: Vec<TcpListener> = ports
+            .iter()
+            .map(|port| TcpListener::bind(format!("127.0.0.1:{}", port)).unwrap())
+            .collect();
+
+        let mut peer = TestPeer::new(TestPeerConfig::new(
+            "test_schedule_block_requests",
+            3270,
+            3271,
+        ));
+        let mempool = peer.mempool.take().unwrap();
+        let mut stacks_node = peer.stacks_node.take().unwrap();
+
+        let mut downloader = BlockDownloader::new(
+            0,
+            0,
+            6,
+            2,
+            10,
+            ConnectionOptions::default().download_retry_policy(),
+        );
+        for port in ports.iter() {
+            downloader.dns_lookups.insert(
+                UrlString::try_from(format!("http://127.0.0.1:{}", port)).unwrap(),
+                Some(vec![format!("127.0.0.1:{}", port).parse().unwrap()]),
+            );
+        }
+
+        // every peer has every block
+        for height in 1..7 {
+            let keys = ports
+                .iter()
+                .map(|port| make_block_request_key(height, *port))
+                .collect();
+            downloader.blocks_to_try.insert(height, keys);
+        }
+
+        downloader.state = BlockDownloaderState::GetBlocksBegin;
+        downloader.getblocks_begin(HashMap::new());
+        downloader.schedule_requests(
+            &mut peer.network,
+            false,
+            &mempool,
+            &mut stacks_node.chainstate,
+        );
+
+        // each peer gets its own range of two blocks
+        assert_eq!(downloader.getblock_requests.len(), 6);
+        let mut urls_by_height = HashMap::new();
+        for key in downloader.getblock_requests.keys() {
+            urls_by_height.insert(key.sortition_height, key.data_url.clone());
+        }
+        assert_eq!(urls_by_height[&1], urls_by_height[&2]);
+        assert_eq!(urls_by_height[&3], urls_by_height[&4]);
+        assert_eq!(urls_by_height[&5], urls_by_height[&6]);
+        assert!(urls_by_height[&1] != urls_by_height[&3]);
+        assert!(urls_by_height[&1] != urls_by_height[&5]);
+        assert!(urls_by_height[&3] != urls_by_height[&5]);
+
+        // the other peers are still there to try if these requests fail
+        for height in 1..7 {
+            assert_eq!(downloader.blocks_to_try[&height].len(), 2);
+        }
+
+        // the first peer finishes its range, and the others are slow
+        let idle_url = urls_by_height[&1].clone();
+        downloader
+            .getblock_requests
+            .retain(|key, _| key.sortition_height > 2);
+        downloader.blocks_to_try.remove(&1);
+        downloader.blocks_to_try.remove(&2);
+        for since in downloader.inflight_since.values_mut() {
+            *since = 0;
+        }
+
+        // so the idle peer races the slow peer for the lowest block
+        downloader.schedule_requests(
+            &mut peer.network,
+            false,
+            &mempool,
+            &mut stacks_node.chainstate,
+        );
+        assert_eq!(downloader.getblock_requests.len(), 5);
+        let racing: Vec<&BlockRequestKey> = downloader
+            .getblock_requests
+            .keys()
+            .filter(|key| key.sortition_height == 3)
+            .collect();
+        assert_eq!(racing.len(), 2);
+        assert!(racing.iter().any(|key| key.data_url == idle_url));
+
+        // whoever loses the race is cancelled
+        let mut have = HashSet::new();
+        have.insert(racing[0].index_block_hash.clone());
+        let mut pending = downloader.getblock_requests.clone();
+        let mut cancelled = vec![];
+        BlockDownloader::cancel_requests(&mut pending, &have, &mut cancelled);
+        assert_eq!(pending.len(), 3);
+        assert_eq!(cancelled.len(), 2);
+    }
 }
@@ -0,0 +1,384 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bulk header sync.  A peer that advertises `ServiceFlags::HEADERS` answers `GetHeaders` with up
+//! to `MAX_HEADERS` consecutive block headers from one of its Stacks forks (its canonical fork,
+//! unless the request names another tip), each with the consensus hash of the sortition that
+//! chose it.  This lets a node check the shape of a neighbor's chain without downloading any
+//! block bodies.
+//!
+//! When our Stacks tip goes stale (see `net::watchdog`), we ask our outbound neighbors for their
+//! canonical headers from our tip's height up, which tells us whether they're ahead of us on our
+//! fork or on a different fork altogether.  The last valid `Headers` reply from each neighbor is
+//! kept in `PeerNetwork::peer_headers`.
+
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::stacks::db::StacksChainState;
+use net::capabilities::Capability;
+use net::chat::ConversationP2P;
+use net::p2p::PeerNetwork;
+use net::reputation::PeerMisbehavior;
+use net::Error as net_error;
+use net::*;
+use util::get_epoch_time_secs;
+
+use crate::types::chainstate::{StacksBlockHeader, StacksBlockId};
+
+impl ExtendedStacksHeader {
+    /// This header's index block hash
+    pub fn index_block_hash(&self) -> StacksBlockId {
+        StacksBlockHeader::make_index_block_hash(&self.consensus_hash, &self.header.block_hash())
+    }
+}
+
+impl HeadersData {
+    /// Do the headers form an unbroken chain, each the parent of the next?
+    pub fn is_contiguous(&self) -> bool {
+        self.headers
+            .windows(2)
+            .all(|pair| pair[1].parent_block_id == pair[0].index_block_hash())
+    }
+}
+
+impl ConversationP2P {
+    /// Create a response to an inbound GetHeaders request, but unsigned.
+    /// Replies with a NACK if we don't have the requested tip.
+    pub fn make_getheaders_response(
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        get_headers: &GetHeadersData,
+    ) -> Result<StacksMessageType, net_error> {
+        let tip = if get_headers.tip == StacksBlockId([0u8; 32]) {
+            let (consensus_hash, block_hash) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())?;
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash)
+        } else {
+            get_headers.tip.clone()
+        };
+
+        let tip_height = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.db(),
+            &tip,
+        )? {
+            Some(tip_info) => tip_info.block_height,
+            None => {
+                return Ok(StacksMessageType::Nack(NackData::new(
+                    NackErrorCodes::NoSuchBlock,
+                )));
+            }
+        };
+
+        let num_headers = (MAX_HEADERS as u64)
+            .min(get_headers.max_headers as u64)
+            .min((tip_height + 1).saturating_sub(get_headers.start_height));
+
+        let index_conn = chainstate.index_conn()?;
+        let mut headers = Vec::with_capacity(num_headers as usize);
+        for height in get_headers.start_height..(get_headers.start_height + num_headers) {
+            let index_block_hash = index_conn.get_ancestor_block_hash(height, &tip)?.ok_or(
+                net_error::ChainstateError(format!("No ancestor of {} at {}", &tip, height)),
+            )?;
+            let header_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                chainstate.db(),
+                &index_block_hash,
+            )?
+            .ok_or(net_error::ChainstateError(format!(
+                "No header for {}",
+                &index_block_hash
+            )))?;
+            let parent_block_id = chainstate.get_parent(&index_block_hash)?;
+            headers.push(ExtendedStacksHeader {
+                consensus_hash: header_info.consensus_hash,
+                header: header_info.anchored_header,
+                parent_block_id,
+            });
+        }
+
+        Ok(StacksMessageType::Headers(HeadersData {
+            tip,
+            tip_height,
+            headers,
+        }))
+    }
+}
+
+impl PeerNetwork {
+    /// Ask a neighbor for some of its headers.  Its answer ends up in `peer_headers`.
+    pub fn send_getheaders(
+        &mut self,
+        nk: &NeighborKey,
+        get_headers: GetHeadersData,
+    ) -> Result<(), net_error> {
+        let event_id = *self.events.get(nk).ok_or(net_error::PeerNotConnected)?;
+        match self.peers.get(&event_id) {
+            Some(convo) if convo.supports(Capability::Headers) => {}
+            _ => {
+                return Err(net_error::InvalidMessage);
+            }
+        }
+
+        debug!(
+            "{:?}: Ask {:?} for up to {} headers from {} at height {}",
+            &self.local_peer,
+            nk,
+            get_headers.max_headers,
+            &get_headers.tip,
+            get_headers.start_height
+        );
+        let msg = self.sign_for_peer(nk, StacksMessageType::GetHeaders(get_headers.clone()))?;
+        self.relay_signed_message(nk, msg)?;
+        self.pending_header_requests
+            .insert(event_id, (get_epoch_time_secs(), get_headers));
+        Ok(())
+    }
+
+    /// Ask each outbound neighbor that answers `GetHeaders` for its canonical headers from our
+    /// Stacks tip's height up, to find out where its chain goes from there.
+    pub fn request_tip_headers(&mut self) {
+        let start_height = self.burnchain_tip.canonical_stacks_tip_height;
+        let neighbors: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|(event_id, convo)| {
+                convo.is_outbound()
+                    && convo.is_authenticated()
+                    && convo.supports(Capability::Headers)
+                    && !self.pending_header_requests.contains_key(*event_id)
+            })
+            .map(|(_, convo)| convo.to_neighbor_key())
+            .collect();
+
+        for nk in neighbors.into_iter() {
+            let get_headers = GetHeadersData {
+                tip: StacksBlockId([0u8; 32]),
+                start_height,
+                max_headers: MAX_HEADERS as u32,
+            };
+            if let Err(e) = self.send_getheaders(&nk, get_headers) {
+                debug!(
+                    "{:?}: Failed to ask {:?} for headers: {:?}",
+                    &self.local_peer, &nk, &e
+                );
+            }
+        }
+    }
+
+    /// Check a neighbor's `Headers` against the request it answers, and remember it.
+    fn handle_unsolicited_Headers(&mut self, event_id: usize, nk: &NeighborKey, data: HeadersData) {
+        let (_, request) = match self.pending_header_requests.remove(&event_id) {
+            Some(pending) => pending,
+            None => {
+                debug!(
+                    "{:?}: Drop unrequested Headers from {:?}",
+                    &self.local_peer, nk
+                );
+                self.record_peer_misbehavior(nk, PeerMisbehavior::UnsolicitedSpam);
+                return;
+            }
+        };
+
+        let tip_mismatch = request.tip != StacksBlockId([0u8; 32]) && request.tip != data.tip;
+        let expected_headers = (MAX_HEADERS as u64)
+            .min(request.max_headers as u64)
+            .min((data.tip_height + 1).saturating_sub(request.start_height));
+        // if the headers run all the way up to the tip, then the last one is the tip
+        let reaches_tip =
+            expected_headers > 0 && request.start_height + expected_headers == data.tip_height + 1;
+        let ends_at_tip = !reaches_tip
+            || data.headers.last().map(|hdr| hdr.index_block_hash()) == Some(data.tip.clone());
+        if tip_mismatch
+            || (data.headers.len() as u64) != expected_headers
+            || !data.is_contiguous()
+            || !ends_at_tip
+        {
+            info!(
+                "{:?}: Invalid Headers from {:?} in reply to {}",
+                &self.local_peer,
+                nk,
+                StacksMessageType::GetHeaders(request).get_message_description()
+            );
+            self.record_peer_misbehavior(nk, PeerMisbehavior::InvalidMessage);
+            return;
+        }
+
+        if request.start_height == self.burnchain_tip.canonical_stacks_tip_height {
+            // see how this neighbor's chain compares to ours
+            let our_tip = self.get_canonical_stacks_tip_index_hash();
+            match data.headers.first() {
+                Some(first) if first.index_block_hash() == our_tip => {
+                    if data.tip_height > request.start_height {
+                        info!(
+                            "{:?}: {:?} is {} block(s) ahead of us on our fork, at {}",
+                            &self.local_peer,
+                            nk,
+                            data.tip_height - request.start_height,
+                            &data.tip
+                        );
+                    }
+                }
+                Some(_) => {
+                    info!(
+                        "{:?}: {:?} is on a different fork than us, with tip {} at height {}",
+                        &self.local_peer, nk, &data.tip, data.tip_height
+                    );
+                }
+                None => {
+                    debug!(
+                        "{:?}: {:?} is behind us, at height {}",
+                        &self.local_peer, nk, data.tip_height
+                    );
+                }
+            }
+        }
+
+        self.peer_headers.insert(nk.clone(), data);
+    }
+
+    /// Handle a neighbor's answer to our `GetHeaders`.  Returns the message if it isn't one.
+    pub fn handle_unsolicited_headers_message(
+        &mut self,
+        event_id: usize,
+        message: StacksMessage,
+    ) -> Option<StacksMessage> {
+        let data = match message.payload {
+            StacksMessageType::Headers(data) => data,
+            _ => {
+                return Some(message);
+            }
+        };
+
+        let nk = match self.peers.get(&event_id) {
+            Some(convo) if convo.is_authenticated() => convo.to_neighbor_key(),
+            _ => {
+                // drop -- a correct peer will have authenticated before sending this message
+                test_debug!(
+                    "{:?}: Drop Headers from unauthenticated event {}",
+                    &self.local_peer,
+                    event_id
+                );
+                return None;
+            }
+        };
+
+        self.handle_unsolicited_Headers(event_id, &nk, data);
+        None
+    }
+
+    /// Give up on header requests that were never answered, and forget the headers of neighbors
+    /// we're no longer connected to.
+    pub fn prune_header_sync_state(&mut self) {
+        let now = get_epoch_time_secs();
+        let timeout = self.connection_opts.timeout;
+        let events = &self.events;
+        let peers = &self.peers;
+        self.pending_header_requests
+            .retain(|event_id, (asked_at, _)| {
+                peers.contains_key(event_id) && *asked_at + timeout >= now
+            });
+        self.peer_headers.retain(|nk, _| events.contains_key(nk));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use net::test::*;
+
+    #[test]
+    fn test_make_getheaders_response() {
+        let peer_config = TestPeerConfig::new("test_make_getheaders_response", 2259, 2260);
+        let mut peer = TestPeer::new(peer_config);
+
+        let mut tips = vec![];
+        for _ in 0..3 {
+            let (burn_ops, stacks_block, microblocks) = peer.make_default_tenure();
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            let (consensus_hash, block_hash) = SortitionDB::get_canonical_stacks_chain_tip_hash(
+                peer.sortdb.as_ref().unwrap().conn(),
+            )
+            .unwrap();
+            tips.push(StacksBlockId::new(&consensus_hash, &block_hash));
+        }
+
+        let sortdb = peer.sortdb.as_ref().unwrap();
+        let chainstate = &peer.stacks_node.as_ref().unwrap().chainstate;
+        let get_headers = |tip: &StacksBlockId, start_height: u64, max_headers: u32| {
+            let response = ConversationP2P::make_getheaders_response(
+                sortdb,
+                chainstate,
+                &GetHeadersData {
+                    tip: tip.clone(),
+                    start_height,
+                    max_headers,
+                },
+            )
+            .unwrap();
+            match response {
+                StacksMessageType::Headers(data) => {
+                    assert!(data.is_contiguous());
+                    Some(data)
+                }
+                _ => None,
+            }
+        };
+
+        // canonical fork
+        let data = get_headers(&StacksBlockId([0u8; 32]), 1, 10).unwrap();
+        assert_eq!(data.tip, tips[2]);
+        assert_eq!(data.tip_height, 3);
+        assert_eq!(
+            data.headers
+                .iter()
+                .map(|hdr| hdr.index_block_hash())
+                .collect::<Vec<_>>(),
+            tips
+        );
+
+        // part of the fork
+        let data = get_headers(&StacksBlockId([0u8; 32]), 2, 1).unwrap();
+        assert_eq!(data.headers.len(), 1);
+        assert_eq!(data.headers[0].index_block_hash(), tips[1]);
+        assert_eq!(data.headers[0].parent_block_id, tips[0]);
+
+        // named tip
+        let data = get_headers(&tips[1], 1, 10).unwrap();
+        assert_eq!(data.tip, tips[1]);
+        assert_eq!(data.tip_height, 2);
+        assert_eq!(
+            data.headers
+                .iter()
+                .map(|hdr| hdr.index_block_hash())
+                .collect::<Vec<_>>(),
+            tips[0..2].to_vec()
+        );
+
+        // nothing above the tip
+        let data = get_headers(&tips[2], 4, 10).unwrap();
+        assert_eq!(data.tip_height, 3);
+        assert!(data.headers.is_empty());
+
+        // unknown tip
+        assert!(get_headers(&StacksBlockId([0x11; 32]), 1, 10).is_none());
+
+        // a gap in the headers is caught
+        let mut data = get_headers(&tips[2], 1, 10).unwrap();
+        data.headers.remove(1);
+        assert!(!data.is_contiguous());
+    }
+}
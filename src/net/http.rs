@@ -17,6 +17,7 @@
  along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
@@ -29,6 +30,7 @@ use std::str;
 use std::str::FromStr;
 use std::time::SystemTime;
 
+use libflate::{gzip, zlib};
 use percent_encoding::percent_decode_str;
 use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
@@ -38,14 +40,23 @@ use url::{form_urlencoded, Url};
 
 use burnchains::{Address, Txid};
 use chainstate::burn::ConsensusHash;
+use chainstate::stacks::db::accounts::AccountProof;
+use chainstate::stacks::db::contract_events::{ContractEventFilter, ContractEventType};
 use chainstate::stacks::{StacksBlock, StacksMicroblock, StacksPublicKey, StacksTransaction};
+use core::mempool::{MemPoolTxFilter, MEMPOOL_PAYLOAD_TYPES};
 use deps::httparse;
 use net::atlas::Attachment;
+use net::rosetta::RosettaEndpoint;
 use net::ClientError;
+use net::ContractEventsQuery;
+use net::ContractMetricsQuery;
 use net::Error as net_error;
 use net::Error::ClarityError;
+use net::ExportedBlockData;
 use net::ExtendedStacksHeader;
+use net::HttpContentEncoding;
 use net::HttpContentType;
+use net::HttpCorsHeaders;
 use net::HttpRequestMetadata;
 use net::HttpRequestPreamble;
 use net::HttpRequestType;
@@ -66,9 +77,26 @@ use net::UnconfirmedTransactionStatus;
 use net::HTTP_PREAMBLE_MAX_ENCODED_SIZE;
 use net::HTTP_PREAMBLE_MAX_NUM_HEADERS;
 use net::HTTP_REQUEST_ID_RESERVED;
+use net::MAX_BLOCK_EXPORT;
+use net::MAX_CONTRACT_EVENTS;
+use net::{AddressTransactionsQuery, MAX_ADDRESS_TRANSACTIONS};
+use net::{MemPoolTransactionsQuery, MAX_MEMPOOL_TRANSACTIONS};
+use net::{DEFAULT_CONTRACT_METRICS_BLOCKS, MAX_CONTRACT_METRICS, MAX_CONTRACT_METRICS_BLOCKS};
+
+use chainstate::stacks::db::contracts::ReadOnlyCall;
+use chainstate::stacks::db::manifest::{
+    CHAINSTATE_MANIFEST_INTERVAL, MAX_CHAINSTATE_MANIFEST_CHECKPOINTS,
+};
 use net::MAX_HEADERS;
+use net::MAX_MAP_ENTRY_BATCH;
 use net::MAX_MICROBLOCKS_UNCONFIRMED;
-use net::{CallReadOnlyRequestBody, TipRequest};
+use net::MAX_MICROBLOCK_STREAM_PAGE;
+use net::MAX_READ_ONLY_CALL_BATCH;
+use net::{
+    CallReadOnlyBatchEntry, CallReadOnlyBatchRequestBody, CallReadOnlyRequestBody,
+    CheckContractCallArgsRequestBody, MapEntryBatchRequestBody, MapEntryBatchRequestEntry,
+    MapEntryLookup, TipRequest,
+};
 use net::{GetAttachmentResponse, GetAttachmentsInvResponse, PostTransactionRequestBody};
 use util::hash::hex_bytes;
 use util::hash::to_hex;
@@ -76,7 +104,7 @@ use util::hash::Hash160;
 use util::log;
 use util::retry::BoundReader;
 use util::retry::RetryReader;
-use vm::types::{StandardPrincipalData, TraitIdentifier};
+use vm::types::{QualifiedContractIdentifier, StandardPrincipalData, TraitIdentifier};
 use vm::{
     ast::parser::{
         CLARITY_NAME_REGEX, CONTRACT_NAME_REGEX, PRINCIPAL_DATA_REGEX, STANDARD_PRINCIPAL_REGEX,
@@ -93,19 +121,41 @@ use crate::codec::{
 use crate::types::chainstate::{BlockHeaderHash, StacksAddress, StacksBlockHeader, StacksBlockId};
 
 use super::FeeRateEstimateRequestBody;
+use super::FeeRateEstimateSubject;
+use super::{PeerPolicyUpdate, PeerPolicyUpdateRequestBody};
+use super::{PostConditionCheckEvent, PostConditionCheckRequestBody};
 
 lazy_static! {
     static ref PATH_GETINFO: Regex = Regex::new(r#"^/v2/info$"#).unwrap();
+    static ref PATH_GET_WEBSOCKET: Regex = Regex::new(r#"^/v2/ws$"#).unwrap();
     static ref PATH_GETPOXINFO: Regex = Regex::new(r#"^/v2/pox$"#).unwrap();
+    static ref PATH_GET_POX_CYCLE: Regex =
+        Regex::new(r#"^/v2/pox/cycles/(?P<cycle>[0-9]{1,20}|current)$"#).unwrap();
+    static ref PATH_GET_POX_REWARD_SET: Regex =
+        Regex::new(r#"^/v2/pox/reward_set/(?P<cycle>[0-9]{1,20})$"#).unwrap();
+    static ref PATH_GET_POX_STACKER: Regex = Regex::new(&format!(
+        "^/v2/pox/stackers/(?P<principal>{})$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
     static ref PATH_GETNEIGHBORS: Regex = Regex::new(r#"^/v2/neighbors$"#).unwrap();
+    static ref PATH_GETNEIGHBORS_DETAIL: Regex =
+        Regex::new(r#"^/v2/neighbors/detail$"#).unwrap();
+    static ref PATH_GETTELEMETRY: Regex = Regex::new(r#"^/v2/telemetry$"#).unwrap();
     static ref PATH_GETHEADERS: Regex = Regex::new(r#"^/v2/headers/([0-9]+)$"#).unwrap();
     static ref PATH_GETBLOCK: Regex = Regex::new(r#"^/v2/blocks/([0-9a-f]{64})$"#).unwrap();
+    static ref PATH_GETBLOCK_EXPORT: Regex =
+        Regex::new(r#"^/v2/blocks/export/([0-9]+)$"#).unwrap();
+    static ref PATH_GETBLOCK_STATE_DIFF: Regex =
+        Regex::new(r#"^/v2/blocks/(?P<block_id>[0-9a-f]{64})/state-diff$"#).unwrap();
     static ref PATH_GETMICROBLOCKS_INDEXED: Regex =
         Regex::new(r#"^/v2/microblocks/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_GETMICROBLOCKS_CONFIRMED: Regex =
         Regex::new(r#"^/v2/microblocks/confirmed/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_GETMICROBLOCKS_UNCONFIRMED: Regex =
         Regex::new(r#"^/v2/microblocks/unconfirmed/([0-9a-f]{64})/([0-9]{1,5})$"#).unwrap();
+    static ref PATH_GETMICROBLOCK_STREAM: Regex =
+        Regex::new(r#"^/v2/microblocks/stream/(?P<block_id>[0-9a-f]{64})$"#).unwrap();
     static ref PATH_GETTRANSACTION_UNCONFIRMED: Regex =
         Regex::new(r#"^/v2/transactions/unconfirmed/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_POSTTRANSACTION: Regex = Regex::new(r#"^/v2/transactions$"#).unwrap();
@@ -117,6 +167,11 @@ lazy_static! {
         *PRINCIPAL_DATA_REGEX
     ))
     .unwrap();
+    static ref PATH_GET_ACCOUNT_PROOF: Regex = Regex::new(&format!(
+        "^/v2/accounts/(?P<principal>{})/proof$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
     static ref PATH_GET_DATA_VAR: Regex = Regex::new(&format!(
         "^/v2/data_var/(?P<address>{})/(?P<contract>{})/(?P<varname>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
@@ -127,11 +182,19 @@ lazy_static! {
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
     ))
     .unwrap();
+    static ref PATH_POST_MAP_ENTRIES: Regex = Regex::new(r#"^/v2/map_entries$"#).unwrap();
     static ref PATH_POST_CALL_READ_ONLY: Regex = Regex::new(&format!(
         "^/v2/contracts/call-read/(?P<address>{})/(?P<contract>{})/(?P<function>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
     ))
     .unwrap();
+    static ref PATH_POST_CHECK_CONTRACT_CALL_ARGS: Regex = Regex::new(&format!(
+        "^/v2/contracts/check-args/(?P<address>{})/(?P<contract>{})/(?P<function>{})$",
+        *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
+    ))
+    .unwrap();
+    static ref PATH_POST_CALL_READ_ONLY_BATCH: Regex =
+        Regex::new(r#"^/v2/contracts/call-read-batch$"#).unwrap();
     static ref PATH_GET_CONTRACT_SRC: Regex = Regex::new(&format!(
         "^/v2/contracts/source/(?P<address>{})/(?P<contract>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
@@ -147,12 +210,59 @@ lazy_static! {
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
     ))
     .unwrap();
+    static ref PATH_GET_CONTRACT_STORAGE: Regex = Regex::new(&format!(
+        "^/v2/contracts/storage/(?P<address>{})/(?P<contract>{})$",
+        *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
+    ))
+    .unwrap();
+    static ref PATH_POST_POSTCONDITIONS_CHECK: Regex =
+        Regex::new("^/v2/postconditions/check$").unwrap();
     static ref PATH_GET_TRANSFER_COST: Regex = Regex::new("^/v2/fees/transfer$").unwrap();
     static ref PATH_GET_ATTACHMENTS_INV: Regex = Regex::new("^/v2/attachments/inv$").unwrap();
     static ref PATH_GET_ATTACHMENT: Regex =
         Regex::new(r#"^/v2/attachments/([0-9a-f]{40})$"#).unwrap();
     static ref PATH_POST_MEMPOOL_QUERY: Regex =
         Regex::new(r#"^/v2/mempool/query$"#).unwrap();
+    static ref PATH_GET_CONTRACT_EVENTS: Regex = Regex::new(r#"^/v2/events$"#).unwrap();
+    static ref PATH_GET_CONTRACT_ID_EVENTS: Regex = Regex::new(&format!(
+        "^/v2/contracts/(?P<contract>{}\\.{})/events$",
+        *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_CONTRACT_METRICS: Regex =
+        Regex::new(r#"^/v2/contracts/metrics$"#).unwrap();
+    static ref PATH_GET_CHAINSTATE_MANIFEST: Regex =
+        Regex::new(r#"^/v2/chainstate/manifest$"#).unwrap();
+    static ref PATH_GET_MEMPOOL_TRANSACTIONS: Regex =
+        Regex::new(r#"^/v2/mempool/transactions$"#).unwrap();
+    static ref PATH_GET_MEMPOOL_TRANSACTION: Regex =
+        Regex::new(r#"^/v2/mempool/transactions/([0-9a-f]{64})$"#).unwrap();
+    static ref PATH_GET_ADDRESS_TRANSACTIONS: Regex = Regex::new(&format!(
+        "^/v2/addresses/(?P<principal>{})/transactions$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_ADDRESS_ASSETS: Regex = Regex::new(&format!(
+        "^/v2/addresses/(?P<principal>{})/assets$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_ADDRESS_NAMES: Regex = Regex::new(&format!(
+        "^/v2/addresses/(?P<principal>{})/names$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_NAME: Regex =
+        Regex::new(r#"^/v2/names/(?P<name>[a-z0-9_-]{1,48})\.(?P<namespace>[a-z0-9_-]{1,20})$"#)
+            .unwrap();
+    static ref PATH_POST_ROSETTA: Regex = Regex::new(
+        "^/rosetta/v1/(?P<endpoint>network/(list|options|status)|block(/transaction)?|account/balance|mempool(/transaction)?|construction/(derive|preprocess|metadata|payloads|combine|parse|hash|submit))$"
+    )
+    .unwrap();
+    static ref PATH_GET_HEALTH_LIVE: Regex = Regex::new(r#"^/v2/health/live$"#).unwrap();
+    static ref PATH_GET_HEALTH_READY: Regex = Regex::new(r#"^/v2/health/ready$"#).unwrap();
+    static ref PATH_ADMIN_PEER_POLICY: Regex =
+        Regex::new(r#"^/v2/admin/peer_policy$"#).unwrap();
     static ref PATH_OPTIONS_WILDCARD: Regex = Regex::new("^/v2/.{0,4096}$").unwrap();
 }
 
@@ -561,10 +671,55 @@ impl<'a, R: Read> Read for HttpChunkedTransferReader<'a, R> {
     }
 }
 
+/// Compressor for a chunk-encoded response body.  It buffers compressed data in memory, so the
+/// body can be compressed piecemeal as it gets streamed out.
+enum HttpContentEncoder {
+    Gzip(gzip::Encoder<Vec<u8>>),
+    Deflate(zlib::Encoder<Vec<u8>>),
+}
+
+impl HttpContentEncoder {
+    fn new(encoding: HttpContentEncoding) -> Option<HttpContentEncoder> {
+        match encoding {
+            HttpContentEncoding::Identity => None,
+            HttpContentEncoding::Gzip => Some(HttpContentEncoder::Gzip(
+                gzip::Encoder::new(vec![]).expect("BUG: failed to write gzip header to memory"),
+            )),
+            HttpContentEncoding::Deflate => Some(HttpContentEncoder::Deflate(
+                zlib::Encoder::new(vec![]).expect("BUG: failed to write zlib header to memory"),
+            )),
+        }
+    }
+
+    /// Compress `buf`, and return whatever compressed data is ready to send
+    fn encode(&mut self, buf: &[u8]) -> io::Result<Vec<u8>> {
+        let compressed = match self {
+            HttpContentEncoder::Gzip(ref mut encoder) => {
+                encoder.write_all(buf)?;
+                encoder.as_inner_mut()
+            }
+            HttpContentEncoder::Deflate(ref mut encoder) => {
+                encoder.write_all(buf)?;
+                encoder.as_inner_mut()
+            }
+        };
+        Ok(mem::take(compressed))
+    }
+
+    /// Finish compressing, and return the rest of the compressed data
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            HttpContentEncoder::Gzip(encoder) => encoder.finish().into_result(),
+            HttpContentEncoder::Deflate(encoder) => encoder.finish().into_result(),
+        }
+    }
+}
+
 pub struct HttpChunkedTransferWriterState {
     chunk_size: usize,
     chunk_buf: Vec<u8>,
     corked: bool,
+    encoder: Option<HttpContentEncoder>,
 }
 
 impl HttpChunkedTransferWriterState {
@@ -573,8 +728,18 @@ impl HttpChunkedTransferWriterState {
             chunk_size: chunk_size,
             chunk_buf: vec![],
             corked: false,
+            encoder: None,
         }
     }
+
+    /// Compress the body with `encoding` before chunking it
+    pub fn with_content_encoding(
+        mut self,
+        encoding: HttpContentEncoding,
+    ) -> HttpChunkedTransferWriterState {
+        self.encoder = HttpContentEncoder::new(encoding);
+        self
+    }
 }
 
 pub struct HttpChunkedTransferWriter<'a, 'state, W: Write> {
@@ -635,10 +800,8 @@ impl<'a, 'state, W: Write> HttpChunkedTransferWriter<'a, 'state, W> {
     pub fn corked(&self) -> bool {
         self.state.corked
     }
-}
 
-impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    fn write_chunks(&mut self, buf: &[u8]) -> io::Result<usize> {
         let mut written = 0;
         while written < buf.len() && !self.state.corked {
             if self.state.chunk_buf.len() > 0 {
@@ -665,10 +828,27 @@ impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
         }
         Ok(written)
     }
+}
+
+impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let compressed = match self.state.encoder {
+            Some(ref mut encoder) if !self.state.corked => encoder.encode(buf)?,
+            _ => {
+                return self.write_chunks(buf);
+            }
+        };
+        self.write_chunks(&compressed)?;
+        Ok(buf.len())
+    }
 
     fn flush(&mut self) -> io::Result<()> {
         // send out any buffered chunk data
         if !self.state.corked {
+            if let Some(encoder) = self.state.encoder.take() {
+                let compressed = encoder.finish()?;
+                self.write_chunks(&compressed)?;
+            }
             self.flush_chunk().and_then(|nw| {
                 if nw > 0 {
                     // send empty chunk
@@ -863,7 +1043,7 @@ fn empty_headers<W: Write>(_fd: &mut W) -> Result<(), codec_error> {
     Ok(())
 }
 
-fn keep_alive_headers<W: Write>(fd: &mut W, md: &HttpResponseMetadata) -> Result<(), codec_error> {
+fn metadata_headers<W: Write>(fd: &mut W, md: &HttpResponseMetadata) -> Result<(), codec_error> {
     match md.client_version {
         HttpVersion::Http10 => {
             // client expects explicit keep-alive
@@ -883,6 +1063,43 @@ fn keep_alive_headers<W: Write>(fd: &mut W, md: &HttpResponseMetadata) -> Result
             }
         }
     }
+    if let Some(ref etag) = md.etag {
+        fd.write_all(format!("ETag: \"{}\"\r\n", etag).as_bytes())
+            .map_err(codec_error::WriteError)?;
+    }
+    if md.content_encoding != HttpContentEncoding::Identity {
+        fd.write_all(
+            format!(
+                "Content-Encoding: {}\r\nVary: Accept-Encoding\r\n",
+                md.content_encoding
+            )
+            .as_bytes(),
+        )
+        .map_err(codec_error::WriteError)?;
+    }
+    if let Some(retry_after) = md.retry_after {
+        fd.write_all(format!("Retry-After: {}\r\n", retry_after).as_bytes())
+            .map_err(codec_error::WriteError)?;
+    }
+    if let Some(ref allow_origin) = md.cors.allow_origin {
+        fd.write_all(
+            format!(
+                "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Headers: {}\r\nAccess-Control-Allow-Methods: {}\r\n",
+                allow_origin, md.cors.allow_headers, md.cors.allow_methods
+            )
+            .as_bytes(),
+        )
+        .map_err(codec_error::WriteError)?;
+        if allow_origin != "*" {
+            // the response depends on who asked
+            fd.write_all("Vary: Origin\r\n".as_bytes())
+                .map_err(codec_error::WriteError)?;
+        }
+        if let Some(max_age) = md.cors.max_age {
+            fd.write_all(format!("Access-Control-Max-Age: {}\r\n", max_age).as_bytes())
+                .map_err(codec_error::WriteError)?;
+        }
+    }
     Ok(())
 }
 
@@ -1043,15 +1260,25 @@ impl StacksMessageCodec for HttpRequestPreamble {
                             Err(_) => None,
                         }
                     } else if key == "connection" {
-                        // parse
-                        if value.to_lowercase() == "close" {
-                            keep_alive = false;
-                        } else if value.to_lowercase() == "keep-alive" {
-                            keep_alive = true;
-                        } else {
-                            return Err(codec_error::DeserializeError(
-                                "Inavlid HTTP request: invalid Connection: header".to_string(),
-                            ));
+                        // parse.  A WebSocket client asks to upgrade the connection, which may be
+                        // listed alongside keep-alive.
+                        let mut upgrade = false;
+                        for token in value.split(',').map(|token| token.trim().to_lowercase()) {
+                            if token == "close" {
+                                keep_alive = false;
+                            } else if token == "keep-alive" {
+                                keep_alive = true;
+                            } else if token == "upgrade" {
+                                keep_alive = true;
+                                upgrade = true;
+                            } else {
+                                return Err(codec_error::DeserializeError(
+                                    "Inavlid HTTP request: invalid Connection: header".to_string(),
+                                ));
+                            }
+                        }
+                        if upgrade {
+                            headers.insert(key, value);
                         }
                     } else {
                         headers.insert(key, value);
@@ -1064,6 +1291,18 @@ impl StacksMessageCodec for HttpRequestPreamble {
                     ));
                 };
 
+                // the only protocol we upgrade to is WebSocket
+                if headers.contains_key("connection")
+                    && headers
+                        .get("upgrade")
+                        .map(|value| value.to_lowercase() != "websocket")
+                        .unwrap_or(true)
+                {
+                    return Err(codec_error::DeserializeError(
+                        "Invalid HTTP request: unsupported Connection: upgrade".to_string(),
+                    ));
+                }
+
                 Ok(HttpRequestPreamble {
                     version: version,
                     verb: verb,
@@ -1099,18 +1338,42 @@ impl HttpResponsePreamble {
         }
     }
 
+    /// Write the preamble of a `101 Switching Protocols` response which accepts a WebSocket
+    /// upgrade.  There is no response body, so there are no content headers.
+    pub fn websocket_accept_from_md<W: Write>(
+        fd: &mut W,
+        md: &HttpResponseMetadata,
+        accept_key: &str,
+    ) -> Result<(), codec_error> {
+        fd.write_all(
+            format!(
+                "HTTP/1.1 101 Switching Protocols\r\nServer: stacks/2.0\r\nDate: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\nX-Request-Id: {}\r\n\r\n",
+                rfc7231_now(),
+                accept_key,
+                md.request_id
+            )
+            .as_bytes(),
+        )
+        .map_err(codec_error::WriteError)
+    }
+
     pub fn ok_JSON_from_md<W: Write>(
         fd: &mut W,
         md: &HttpResponseMetadata,
     ) -> Result<(), codec_error> {
+        // compressed bodies are chunk-encoded, since we don't know their length up front
+        let content_length = match md.content_encoding {
+            HttpContentEncoding::Identity => md.content_length.clone(),
+            _ => None,
+        };
         HttpResponsePreamble::new_serialized(
             fd,
             200,
             "OK",
-            md.content_length.clone(),
+            content_length,
             &HttpContentType::JSON,
             md.request_id,
-            |ref mut fd| keep_alive_headers(fd, md),
+            |ref mut fd| metadata_headers(fd, md),
         )
     }
 
@@ -1134,12 +1397,6 @@ impl HttpResponsePreamble {
             .map_err(codec_error::WriteError)?;
         fd.write_all(rfc7231_now().as_bytes())
             .map_err(codec_error::WriteError)?;
-        fd.write_all("\r\nAccess-Control-Allow-Origin: *".as_bytes())
-            .map_err(codec_error::WriteError)?;
-        fd.write_all("\r\nAccess-Control-Allow-Headers: origin, content-type".as_bytes())
-            .map_err(codec_error::WriteError)?;
-        fd.write_all("\r\nAccess-Control-Allow-Methods: POST, GET, OPTIONS".as_bytes())
-            .map_err(codec_error::WriteError)?;
         fd.write_all("\r\nContent-Type: ".as_bytes())
             .map_err(codec_error::WriteError)?;
         fd.write_all(content_type.as_str().as_bytes())
@@ -1249,8 +1506,19 @@ impl HttpResponsePreamble {
     }
 
     pub fn add_CORS_headers(&mut self) -> () {
-        self.headers
-            .insert("Access-Control-Allow-Origin".to_string(), "*".to_string());
+        let cors = HttpCorsHeaders::default();
+        self.headers.insert(
+            "Access-Control-Allow-Origin".to_string(),
+            cors.allow_origin.unwrap_or_default(),
+        );
+        self.headers.insert(
+            "Access-Control-Allow-Headers".to_string(),
+            cors.allow_headers,
+        );
+        self.headers.insert(
+            "Access-Control-Allow-Methods".to_string(),
+            cors.allow_methods,
+        );
     }
 
     // do we have Transfer-Encoding: chunked?
@@ -1463,14 +1731,54 @@ impl HttpRequestType {
             ) -> Result<HttpRequestType, net_error>,
         )] = &[
             ("GET", &PATH_GETINFO, &HttpRequestType::parse_getinfo),
+            (
+                "GET",
+                &PATH_GET_WEBSOCKET,
+                &HttpRequestType::parse_websocket_upgrade,
+            ),
             ("GET", &PATH_GETPOXINFO, &HttpRequestType::parse_getpoxinfo),
+            (
+                "GET",
+                &PATH_GET_POX_CYCLE,
+                &HttpRequestType::parse_get_pox_cycle,
+            ),
+            (
+                "GET",
+                &PATH_GET_POX_REWARD_SET,
+                &HttpRequestType::parse_get_pox_reward_set,
+            ),
+            (
+                "GET",
+                &PATH_GET_POX_STACKER,
+                &HttpRequestType::parse_get_pox_stacker,
+            ),
             (
                 "GET",
                 &PATH_GETNEIGHBORS,
                 &HttpRequestType::parse_getneighbors,
             ),
+            (
+                "GET",
+                &PATH_GETNEIGHBORS_DETAIL,
+                &HttpRequestType::parse_getneighbors_detail,
+            ),
+            (
+                "GET",
+                &PATH_GETTELEMETRY,
+                &HttpRequestType::parse_gettelemetry,
+            ),
             ("GET", &PATH_GETHEADERS, &HttpRequestType::parse_getheaders),
             ("GET", &PATH_GETBLOCK, &HttpRequestType::parse_getblock),
+            (
+                "GET",
+                &PATH_GETBLOCK_EXPORT,
+                &HttpRequestType::parse_getblock_export,
+            ),
+            (
+                "GET",
+                &PATH_GETBLOCK_STATE_DIFF,
+                &HttpRequestType::parse_getblock_state_diff,
+            ),
             (
                 "GET",
                 &PATH_GETMICROBLOCKS_INDEXED,
@@ -1486,6 +1794,11 @@ impl HttpRequestType {
                 &PATH_GETMICROBLOCKS_UNCONFIRMED,
                 &HttpRequestType::parse_getmicroblocks_unconfirmed,
             ),
+            (
+                "GET",
+                &PATH_GETMICROBLOCK_STREAM,
+                &HttpRequestType::parse_getmicroblock_stream,
+            ),
             (
                 "GET",
                 &PATH_GETTRANSACTION_UNCONFIRMED,
@@ -1512,6 +1825,11 @@ impl HttpRequestType {
                 &PATH_GET_ACCOUNT,
                 &HttpRequestType::parse_get_account,
             ),
+            (
+                "GET",
+                &PATH_GET_ACCOUNT_PROOF,
+                &HttpRequestType::parse_get_account_proof,
+            ),
             (
                 "GET",
                 &PATH_GET_DATA_VAR,
@@ -1522,6 +1840,11 @@ impl HttpRequestType {
                 &PATH_GET_MAP_ENTRY,
                 &HttpRequestType::parse_get_map_entry,
             ),
+            (
+                "POST",
+                &PATH_POST_MAP_ENTRIES,
+                &HttpRequestType::parse_get_map_entry_batch,
+            ),
             (
                 "GET",
                 &PATH_GET_TRANSFER_COST,
@@ -1542,11 +1865,31 @@ impl HttpRequestType {
                 &PATH_GET_CONTRACT_ABI,
                 &HttpRequestType::parse_get_contract_abi,
             ),
+            (
+                "GET",
+                &PATH_GET_CONTRACT_STORAGE,
+                &HttpRequestType::parse_get_contract_storage,
+            ),
+            (
+                "POST",
+                &PATH_POST_POSTCONDITIONS_CHECK,
+                &HttpRequestType::parse_post_postconditions_check,
+            ),
             (
                 "POST",
                 &PATH_POST_CALL_READ_ONLY,
                 &HttpRequestType::parse_call_read_only,
             ),
+            (
+                "POST",
+                &PATH_POST_CALL_READ_ONLY_BATCH,
+                &HttpRequestType::parse_call_read_only_batch,
+            ),
+            (
+                "POST",
+                &PATH_POST_CHECK_CONTRACT_CALL_ARGS,
+                &HttpRequestType::parse_check_contract_call_args,
+            ),
             (
                 "OPTIONS",
                 &PATH_OPTIONS_WILDCARD,
@@ -1567,6 +1910,77 @@ impl HttpRequestType {
                 &PATH_POST_MEMPOOL_QUERY,
                 &HttpRequestType::parse_post_mempool_query,
             ),
+            (
+                "GET",
+                &PATH_GET_CONTRACT_EVENTS,
+                &HttpRequestType::parse_get_contract_events,
+            ),
+            (
+                "GET",
+                &PATH_GET_CONTRACT_ID_EVENTS,
+                &HttpRequestType::parse_get_contract_id_events,
+            ),
+            (
+                "GET",
+                &PATH_GET_CONTRACT_METRICS,
+                &HttpRequestType::parse_get_contract_metrics,
+            ),
+            (
+                "GET",
+                &PATH_GET_CHAINSTATE_MANIFEST,
+                &HttpRequestType::parse_get_chainstate_manifest,
+            ),
+            (
+                "GET",
+                &PATH_GET_MEMPOOL_TRANSACTIONS,
+                &HttpRequestType::parse_get_mempool_transactions,
+            ),
+            (
+                "GET",
+                &PATH_GET_MEMPOOL_TRANSACTION,
+                &HttpRequestType::parse_get_mempool_transaction,
+            ),
+            (
+                "GET",
+                &PATH_GET_ADDRESS_TRANSACTIONS,
+                &HttpRequestType::parse_get_address_transactions,
+            ),
+            (
+                "GET",
+                &PATH_GET_ADDRESS_ASSETS,
+                &HttpRequestType::parse_get_address_assets,
+            ),
+            (
+                "GET",
+                &PATH_GET_ADDRESS_NAMES,
+                &HttpRequestType::parse_get_address_names,
+            ),
+            ("GET", &PATH_GET_NAME, &HttpRequestType::parse_get_name),
+            (
+                "POST",
+                &PATH_POST_ROSETTA,
+                &HttpRequestType::parse_post_rosetta,
+            ),
+            (
+                "GET",
+                &PATH_GET_HEALTH_LIVE,
+                &HttpRequestType::parse_get_health_live,
+            ),
+            (
+                "GET",
+                &PATH_GET_HEALTH_READY,
+                &HttpRequestType::parse_get_health_ready,
+            ),
+            (
+                "GET",
+                &PATH_ADMIN_PEER_POLICY,
+                &HttpRequestType::parse_get_peer_policy,
+            ),
+            (
+                "POST",
+                &PATH_ADMIN_PEER_POLICY,
+                &HttpRequestType::parse_post_peer_policy,
+            ),
         ];
 
         // use url::Url to parse path and query string
@@ -1632,6 +2046,54 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_websocket_upgrade<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for WebSocketUpgrade".to_string(),
+            ));
+        }
+        let upgrade = preamble
+            .headers
+            .get("upgrade")
+            .map(|value| value.to_lowercase())
+            .unwrap_or_default();
+        if upgrade != "websocket" || !preamble.headers.contains_key("connection") {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected a WebSocket upgrade".to_string(),
+            ));
+        }
+        if preamble
+            .headers
+            .get("sec-websocket-version")
+            .map(|value| value.trim())
+            != Some("13")
+        {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: unsupported WebSocket version".to_string(),
+            ));
+        }
+        let key = preamble
+            .headers
+            .get("sec-websocket-key")
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| {
+                net_error::DeserializeError(
+                    "Invalid Http request: missing Sec-WebSocket-Key header".to_string(),
+                )
+            })?;
+        Ok(HttpRequestType::WebSocketUpgrade(
+            HttpRequestMetadata::from_preamble(preamble),
+            key,
+        ))
+    }
+
     fn parse_getpoxinfo<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -1653,56 +2115,181 @@ impl HttpRequestType {
         ))
     }
 
-    fn parse_getneighbors<R: Read>(
+    fn parse_get_pox_cycle<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        _regex: &Captures,
-        _query: Option<&str>,
+        captures: &Captures,
+        query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetNeighbors".to_string(),
+                "Invalid Http request: expected 0-length body for GetPoxCycle".to_string(),
             ));
         }
 
-        Ok(HttpRequestType::GetNeighbors(
+        let cycle = match &captures["cycle"] {
+            "current" => None,
+            cycle => Some(cycle.parse::<u64>().map_err(|_e| {
+                net_error::DeserializeError("Failed to parse reward cycle".into())
+            })?),
+        };
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetPoxCycle(
             HttpRequestMetadata::from_preamble(preamble),
+            cycle,
+            tip,
         ))
     }
 
-    fn parse_get_transfer_cost<R: Read>(
+    fn parse_get_pox_reward_set<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        _regex: &Captures,
+        captures: &Captures,
         _query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetTransferCost".to_string(),
+                "Invalid Http request: expected 0-length body for GetPoxRewardSet".to_string(),
             ));
         }
 
-        Ok(HttpRequestType::GetTransferCost(
+        let cycle = captures["cycle"]
+            .parse::<u64>()
+            .map_err(|_e| net_error::DeserializeError("Failed to parse reward cycle".into()))?;
+
+        Ok(HttpRequestType::GetPoxRewardSet(
             HttpRequestMetadata::from_preamble(preamble),
+            cycle,
         ))
     }
 
-    /// Check whether the given option query string sets proof=0 (setting proof to false).
-    /// Defaults to true.
-    fn get_proof_query(query: Option<&str>) -> bool {
-        let no_proof = if let Some(query_string) = query {
-            form_urlencoded::parse(query_string.as_bytes())
-                .find(|(key, _v)| key == "proof")
-                .map(|(_k, value)| value == "0")
-                .unwrap_or(false)
-        } else {
-            false
-        };
+    fn parse_get_pox_stacker<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetPoxStacker".to_string(),
+            ));
+        }
 
-        !no_proof
-    }
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse stacker principal".into())
+        })?;
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetPoxStacker(
+            HttpRequestMetadata::from_preamble(preamble),
+            principal,
+            tip,
+        ))
+    }
+
+    fn parse_getneighbors<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetNeighbors".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::GetNeighbors(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_getneighbors_detail<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetNeighborsDetail".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::GetNeighborsDetail(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_gettelemetry<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetTelemetry".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::GetTelemetry(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_get_transfer_cost<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetTransferCost".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::GetTransferCost(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    /// Check whether the given option query string sets proof=0 (setting proof to false).
+    /// Defaults to true.
+    fn get_proof_query(query: Option<&str>) -> bool {
+        let no_proof = if let Some(query_string) = query {
+            form_urlencoded::parse(query_string.as_bytes())
+                .find(|(key, _v)| key == "proof")
+                .map(|(_k, value)| value == "0")
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        !no_proof
+    }
+
+    /// Check whether the given option query string sets report=1, asking for an admission
+    /// report instead of a bare txid.  Defaults to false.
+    fn get_report_query(query: Option<&str>) -> bool {
+        if let Some(query_string) = query {
+            form_urlencoded::parse(query_string.as_bytes())
+                .find(|(key, _v)| key == "report")
+                .map(|(_k, value)| value == "1")
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    }
 
     /// get the chain tip optional query argument (`tip`)
     /// Take the first value we can parse.
@@ -1750,6 +2337,25 @@ impl HttpRequestType {
         }
     }
 
+    /// get the block export count optional query argument (`count`)
+    /// Take the first value we can parse.  Defaults to MAX_BLOCK_EXPORT.
+    fn get_block_export_count_query(query: Option<&str>) -> u32 {
+        match query {
+            Some(query_string) => {
+                for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+                    if key != "count" {
+                        continue;
+                    }
+                    if let Ok(count) = value.parse::<u32>() {
+                        return count;
+                    }
+                }
+                MAX_BLOCK_EXPORT
+            }
+            None => MAX_BLOCK_EXPORT,
+        }
+    }
+
     fn parse_get_account<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -1778,6 +2384,32 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_get_account_proof<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAccountProof".to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse account principal".into())
+        })?;
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetAccountProof(
+            HttpRequestMetadata::from_preamble(preamble),
+            principal,
+            tip,
+        ))
+    }
+
     fn parse_get_data_var<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -1920,13 +2552,24 @@ impl HttpRequestType {
         ))
     }
 
-    fn parse_get_contract_arguments(
+    fn parse_check_contract_call_args<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
-    ) -> Result<(HttpRequestMetadata, StacksAddress, ContractName), net_error> {
-        if preamble.get_content_length() != 0 {
+        query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for CheckContractCallArgs ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
@@ -1935,93 +2578,281 @@ impl HttpRequestType {
         })?;
         let contract_name = ContractName::try_from(captures["contract"].to_string())
             .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let func_name = ClarityName::try_from(captures["function"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse function name".into()))?;
 
-        Ok((
+        let body: CheckContractCallArgsRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
+
+        let arguments = body
+            .arguments
+            .into_iter()
+            .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
+            .collect::<Option<Vec<Value>>>()
+            .ok_or_else(|| {
+                net_error::DeserializeError("Failed to deserialize argument value".into())
+            })?;
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::CheckContractCallArgs(
             HttpRequestMetadata::from_preamble(preamble),
             contract_addr,
             contract_name,
+            func_name,
+            arguments,
+            tip,
         ))
     }
 
-    fn parse_get_contract_abi<R: Read>(
-        _protocol: &mut StacksHttp,
-        preamble: &HttpRequestPreamble,
-        captures: &Captures,
-        query: Option<&str>,
-        _fd: &mut R,
-    ) -> Result<HttpRequestType, net_error> {
-        let tip = HttpRequestType::get_chain_tip_query(query);
-        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
-            |(preamble, addr, name)| HttpRequestType::GetContractABI(preamble, addr, name, tip),
-        )
-    }
-
-    fn parse_get_contract_source<R: Read>(
-        _protocol: &mut StacksHttp,
+    fn parse_get_map_entry_batch<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        captures: &Captures,
+        _captures: &Captures,
         query: Option<&str>,
-        _fd: &mut R,
+        fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        let with_proof = HttpRequestType::get_proof_query(query);
-        let tip = HttpRequestType::get_chain_tip_query(query);
-        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
-            |(preamble, addr, name)| {
-                HttpRequestType::GetContractSrc(preamble, addr, name, tip, with_proof)
-            },
-        )
-    }
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for GetMapEntryBatch ({})",
+                content_len
+            )));
+        }
 
-    fn parse_get_is_trait_implemented<R: Read>(
-        _protocol: &mut StacksHttp,
-        preamble: &HttpRequestPreamble,
-        captures: &Captures,
-        query: Option<&str>,
-        _fd: &mut R,
-    ) -> Result<HttpRequestType, net_error> {
-        let tip = HttpRequestType::get_chain_tip_query(query);
-        if preamble.get_content_length() != 0 {
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
-        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
-            net_error::DeserializeError("Failed to parse contract address".into())
-        })?;
-        let contract_name = ContractName::try_from(captures["contract"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
-        let trait_name = ClarityName::try_from(captures["traitName"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse trait name".into()))?;
-        let trait_contract_addr = StacksAddress::from_string(&captures["traitContractAddr"])
-            .ok_or_else(|| net_error::DeserializeError("Failed to parse contract address".into()))?
-            .into();
-        let trait_contract_name = ContractName::try_from(captures["traitContractName"].to_string())
-            .map_err(|_e| {
-                net_error::DeserializeError("Failed to parse trait contract name".into())
-            })?;
-        let trait_id = TraitIdentifier::new(trait_contract_addr, trait_contract_name, trait_name);
+        let body: MapEntryBatchRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-        Ok(HttpRequestType::GetIsTraitImplemented(
+        let num_keys: usize = body.maps.iter().map(|entry| entry.keys.len()).sum();
+        if num_keys == 0 || num_keys > MAX_MAP_ENTRY_BATCH {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: a batch must have between 1 and {} keys",
+                MAX_MAP_ENTRY_BATCH
+            )));
+        }
+
+        let mut lookups = Vec::with_capacity(body.maps.len());
+        for entry in body.maps.into_iter() {
+            let contract_id =
+                QualifiedContractIdentifier::parse(&entry.contract).map_err(|_e| {
+                    net_error::DeserializeError("Failed to parse contract identifier".into())
+                })?;
+            let map_name = ClarityName::try_from(entry.map)
+                .map_err(|_e| net_error::DeserializeError("Failed to parse map name".into()))?;
+            let keys = entry
+                .keys
+                .into_iter()
+                .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
+                .collect::<Option<Vec<Value>>>()
+                .ok_or_else(|| {
+                    net_error::DeserializeError("Failed to deserialize key value".into())
+                })?;
+            lookups.push(MapEntryLookup {
+                contract_id,
+                map_name,
+                keys,
+            });
+        }
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        let with_proof = HttpRequestType::get_proof_query(query);
+
+        Ok(HttpRequestType::GetMapEntryBatch(
             HttpRequestMetadata::from_preamble(preamble),
-            contract_addr,
-            contract_name,
-            trait_id,
+            lookups,
             tip,
+            with_proof,
         ))
     }
 
-    fn parse_getheaders<R: Read>(
-        _protocol: &mut StacksHttp,
+    fn parse_call_read_only_batch<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        captures: &Captures,
+        _captures: &Captures,
         query: Option<&str>,
-        _fd: &mut R,
+        fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() != 0 {
-            return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetBlock".to_string(),
-            ));
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for CallReadOnlyBatch ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
+        }
+
+        let body: CallReadOnlyBatchRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
+
+        if body.calls.is_empty() || body.calls.len() > MAX_READ_ONLY_CALL_BATCH {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: a batch must have between 1 and {} calls",
+                MAX_READ_ONLY_CALL_BATCH
+            )));
+        }
+
+        let sender = PrincipalData::parse(&body.sender)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse sender principal".into()))?;
+
+        let mut calls = Vec::with_capacity(body.calls.len());
+        for call in body.calls.into_iter() {
+            let contract_id = QualifiedContractIdentifier::parse(&call.contract).map_err(|_e| {
+                net_error::DeserializeError("Failed to parse contract identifier".into())
+            })?;
+            let function = ClarityName::try_from(call.function).map_err(|_e| {
+                net_error::DeserializeError("Failed to parse function name".into())
+            })?;
+            let args = call
+                .arguments
+                .into_iter()
+                .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
+                .collect::<Option<Vec<Value>>>()
+                .ok_or_else(|| {
+                    net_error::DeserializeError("Failed to deserialize argument value".into())
+                })?;
+            calls.push(ReadOnlyCall {
+                contract_id,
+                function,
+                args,
+            });
+        }
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::CallReadOnlyFunctionBatch(
+            HttpRequestMetadata::from_preamble(preamble),
+            sender,
+            calls,
+            tip,
+        ))
+    }
+
+    fn parse_get_contract_arguments(
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+    ) -> Result<(HttpRequestMetadata, StacksAddress, ContractName), net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
+        }
+
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
+        })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+
+        Ok((
+            HttpRequestMetadata::from_preamble(preamble),
+            contract_addr,
+            contract_name,
+        ))
+    }
+
+    fn parse_get_contract_abi<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
+            |(preamble, addr, name)| HttpRequestType::GetContractABI(preamble, addr, name, tip),
+        )
+    }
+
+    fn parse_get_contract_storage<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
+            |(preamble, addr, name)| HttpRequestType::GetContractStorage(preamble, addr, name, tip),
+        )
+    }
+
+    fn parse_get_contract_source<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let with_proof = HttpRequestType::get_proof_query(query);
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
+            |(preamble, addr, name)| {
+                HttpRequestType::GetContractSrc(preamble, addr, name, tip, with_proof)
+            },
+        )
+    }
+
+    fn parse_get_is_trait_implemented<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
+        }
+
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
+        })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let trait_name = ClarityName::try_from(captures["traitName"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse trait name".into()))?;
+        let trait_contract_addr = StacksAddress::from_string(&captures["traitContractAddr"])
+            .ok_or_else(|| net_error::DeserializeError("Failed to parse contract address".into()))?
+            .into();
+        let trait_contract_name = ContractName::try_from(captures["traitContractName"].to_string())
+            .map_err(|_e| {
+                net_error::DeserializeError("Failed to parse trait contract name".into())
+            })?;
+        let trait_id = TraitIdentifier::new(trait_contract_addr, trait_contract_name, trait_name);
+
+        Ok(HttpRequestType::GetIsTraitImplemented(
+            HttpRequestMetadata::from_preamble(preamble),
+            contract_addr,
+            contract_name,
+            trait_id,
+            tip,
+        ))
+    }
+
+    fn parse_getheaders<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetBlock".to_string(),
+            ));
         }
 
         let quantity_str = captures
@@ -2073,6 +2904,61 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_getblock_export<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetBlockExport".to_string(),
+            ));
+        }
+
+        let height_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to block height group".to_string(),
+            ))?
+            .as_str();
+
+        let start_height: u64 = height_str
+            .parse()
+            .map_err(|_| net_error::DeserializeError("Failed to parse block height".to_string()))?;
+
+        let count = HttpRequestType::get_block_export_count_query(query);
+
+        Ok(HttpRequestType::GetBlockExport(
+            HttpRequestMetadata::from_preamble(preamble),
+            start_height,
+            count,
+        ))
+    }
+
+    fn parse_getblock_state_diff<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetBlockStateDiff".to_string(),
+            ));
+        }
+
+        let block_id = StacksBlockId::from_hex(&captures["block_id"])
+            .map_err(|_e| net_error::DeserializeError("Failed to parse block hash".to_string()))?;
+
+        Ok(HttpRequestType::GetBlockStateDiff(
+            HttpRequestMetadata::from_preamble(preamble),
+            block_id,
+        ))
+    }
+
     fn parse_getmicroblocks_indexed<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -2177,6 +3063,49 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_getmicroblock_stream<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMicroblockStream".to_string(),
+            ));
+        }
+
+        let block_id = StacksBlockId::from_hex(&captures["block_id"])
+            .map_err(|_e| net_error::DeserializeError("Failed to parse block hash".to_string()))?;
+
+        let mut start = 0;
+        let mut limit = MAX_MICROBLOCK_STREAM_PAGE;
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            match key.as_ref() {
+                "start" => {
+                    start = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse start".into())
+                    })?;
+                }
+                "limit" => {
+                    let requested: u16 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                    limit = requested.clamp(1, MAX_MICROBLOCK_STREAM_PAGE);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(HttpRequestType::GetMicroblockStream(
+            HttpRequestMetadata::from_preamble(preamble),
+            block_id,
+            start,
+            limit,
+        ))
+    }
+
     fn parse_gettransaction_unconfirmed<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -2240,70 +3169,145 @@ impl HttpRequestType {
             net_error::DeserializeError(format!("Failed to parse JSON body: {}", e))
         })?;
 
-        let payload_hex = if body.transaction_payload.starts_with("0x") {
-            &body.transaction_payload[2..]
-        } else {
-            &body.transaction_payload
-        };
-
-        let payload_data = hex_bytes(payload_hex).map_err(|_e| {
-            net_error::DeserializeError("Bad hex string supplied for transaction payload".into())
-        })?;
+        let payload_data = match body.transaction_payload {
+            Some(ref payload) => {
+                let payload_hex = payload.strip_prefix("0x").unwrap_or(payload);
 
-        let payload = TransactionPayload::consensus_deserialize(&mut payload_data.as_slice())
-            .map_err(|e| {
-                net_error::DeserializeError(format!(
-                    "Failed to deserialize transaction payload: {}",
-                    e
-                ))
-            })?;
+                Some(hex_bytes(payload_hex).map_err(|_e| {
+                    net_error::DeserializeError(
+                        "Bad hex string supplied for transaction payload".into(),
+                    )
+                })?)
+            }
+            None => None,
+        };
 
-        let estimated_len =
-            std::cmp::max(body.estimated_len.unwrap_or(0), payload_data.len() as u64);
+        let payload_len = payload_data
+            .as_ref()
+            .map(|data| data.len() as u64)
+            .unwrap_or(0);
+        let estimated_len = std::cmp::max(body.estimated_len.unwrap_or(0), payload_len);
+
+        let subject = match (body.estimated_cost, payload_data) {
+            (Some(cost), _) => FeeRateEstimateSubject::Cost(cost),
+            (None, Some(payload_data)) => FeeRateEstimateSubject::Payload(
+                TransactionPayload::consensus_deserialize(&mut payload_data.as_slice()).map_err(
+                    |e| {
+                        net_error::DeserializeError(format!(
+                            "Failed to deserialize transaction payload: {}",
+                            e
+                        ))
+                    },
+                )?,
+            ),
+            (None, None) => {
+                return Err(net_error::DeserializeError(
+                    "Expected either transaction_payload or estimated_cost".to_string(),
+                ));
+            }
+        };
 
         Ok(HttpRequestType::FeeRateEstimate(
             HttpRequestMetadata::from_preamble(preamble),
-            payload,
+            subject,
             estimated_len,
         ))
     }
 
-    fn parse_posttransaction<R: Read>(
+    fn parse_post_postconditions_check<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         _regex: &Captures,
         _query: Option<&str>,
         fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() == 0 {
-            return Err(net_error::DeserializeError(
-                "Invalid Http request: expected non-zero-length body for PostTransaction"
-                    .to_string(),
-            ));
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < MAX_PAYLOAD_LEN) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for PostConditionCheck ({})",
+                content_len
+            )));
         }
 
-        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: PostTransaction body is too big".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
-        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
+        let bound_fd = BoundReader::from_reader(fd, content_len as u64);
 
-        match preamble.content_type {
-            None => {
-                return Err(net_error::DeserializeError(
-                    "Missing Content-Type for transaction".to_string(),
-                ));
-            }
-            Some(HttpContentType::Bytes) => {
-                HttpRequestType::parse_posttransaction_octets(preamble, &mut bound_fd)
-            }
-            Some(HttpContentType::JSON) => {
-                HttpRequestType::parse_posttransaction_json(preamble, &mut bound_fd)
-            }
-            _ => {
-                return Err(net_error::DeserializeError(
+        let body: PostConditionCheckRequestBody =
+            serde_json::from_reader(bound_fd).map_err(|e| {
+                net_error::DeserializeError(format!("Failed to parse JSON body: {}", e))
+            })?;
+
+        let tx_hex = if body.transaction.starts_with("0x") {
+            &body.transaction[2..]
+        } else {
+            &body.transaction
+        };
+
+        let tx_data = hex_bytes(tx_hex).map_err(|_e| {
+            net_error::DeserializeError("Bad hex string supplied for transaction".into())
+        })?;
+
+        let tx =
+            StacksTransaction::consensus_deserialize(&mut tx_data.as_slice()).map_err(|e| {
+                net_error::DeserializeError(format!("Failed to deserialize transaction: {}", e))
+            })?;
+
+        let events = body
+            .events
+            .iter()
+            .map(|event| event.to_event())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(net_error::DeserializeError)?;
+
+        Ok(HttpRequestType::PostConditionCheck(
+            HttpRequestMetadata::from_preamble(preamble),
+            tx,
+            events,
+        ))
+    }
+
+    fn parse_posttransaction<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected non-zero-length body for PostTransaction"
+                    .to_string(),
+            ));
+        }
+
+        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: PostTransaction body is too big".to_string(),
+            ));
+        }
+
+        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
+        let with_report = HttpRequestType::get_report_query(query);
+
+        match preamble.content_type {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Missing Content-Type for transaction".to_string(),
+                ));
+            }
+            Some(HttpContentType::Bytes) => {
+                HttpRequestType::parse_posttransaction_octets(preamble, &mut bound_fd, with_report)
+            }
+            Some(HttpContentType::JSON) => {
+                HttpRequestType::parse_posttransaction_json(preamble, &mut bound_fd, with_report)
+            }
+            _ => {
+                return Err(net_error::DeserializeError(
                     "Wrong Content-Type for transaction; expected application/json".to_string(),
                 ));
             }
@@ -2313,6 +3317,7 @@ impl HttpRequestType {
     fn parse_posttransaction_octets<R: Read>(
         preamble: &HttpRequestPreamble,
         fd: &mut R,
+        with_report: bool,
     ) -> Result<HttpRequestType, net_error> {
         let tx = StacksTransaction::consensus_deserialize(fd).map_err(|e| {
             if let codec_error::DeserializeError(msg) = e {
@@ -2328,12 +3333,14 @@ impl HttpRequestType {
             HttpRequestMetadata::from_preamble(preamble),
             tx,
             None,
+            with_report,
         ))
     }
 
     fn parse_posttransaction_json<R: Read>(
         preamble: &HttpRequestPreamble,
         fd: &mut R,
+        with_report: bool,
     ) -> Result<HttpRequestType, net_error> {
         let body: PostTransactionRequestBody = serde_json::from_reader(fd)
             .map_err(|_e| net_error::DeserializeError("Failed to parse body".into()))?;
@@ -2367,6 +3374,7 @@ impl HttpRequestType {
             HttpRequestMetadata::from_preamble(preamble),
             tx,
             attachment,
+            with_report,
         ))
     }
 
@@ -2568,55 +3576,609 @@ impl HttpRequestType {
 
         Ok(HttpRequestType::GetAttachmentsInv(
             HttpRequestMetadata::from_preamble(preamble),
-            index_block_hash,
-            pages_indexes,
+            index_block_hash,
+            pages_indexes,
+        ))
+    }
+
+    fn parse_post_mempool_query<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected non-empty body".to_string(),
+            ));
+        }
+
+        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: MemPoolQuery body is too big".to_string(),
+            ));
+        }
+
+        // content-type must be given, and must be application/octet-stream
+        match preamble.content_type {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Missing Content-Type for MemPoolQuery".to_string(),
+                ));
+            }
+            Some(ref c) => {
+                if *c != HttpContentType::Bytes {
+                    return Err(net_error::DeserializeError(
+                        "Wrong Content-Type for MemPoolQuery; expected application/octet-stream"
+                            .to_string(),
+                    ));
+                }
+            }
+        };
+
+        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
+        let mempool_query = MemPoolSyncData::consensus_deserialize(&mut bound_fd)?;
+        let page_id_opt = HttpRequestType::get_mempool_page_id_query(query);
+
+        Ok(HttpRequestType::MemPoolQuery(
+            HttpRequestMetadata::from_preamble(preamble),
+            mempool_query,
+            page_id_opt,
+        ))
+    }
+
+    fn parse_get_contract_events<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        // this endpoint predates the other event types, so it only returns `print` events
+        // unless asked for another type
+        HttpRequestType::parse_contract_events_query(
+            preamble,
+            None,
+            Some(ContractEventType::Print),
+            query,
+        )
+    }
+
+    fn parse_get_contract_id_events<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let contract = QualifiedContractIdentifier::parse(&captures["contract"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse contract identifier".into())
+        })?;
+        HttpRequestType::parse_contract_events_query(preamble, Some(contract), None, query)
+    }
+
+    /// Parse the query string of a request for contract events.  `contract` is the contract from
+    /// the request path, if the path has one; otherwise it's taken from the query string.
+    fn parse_contract_events_query(
+        preamble: &HttpRequestPreamble,
+        mut contract: Option<QualifiedContractIdentifier>,
+        default_event_type: Option<ContractEventType>,
+        query: Option<&str>,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetContractEvents".to_string(),
+            ));
+        }
+
+        let in_path = contract.is_some();
+        let mut filter = ContractEventFilter {
+            event_type: default_event_type,
+            ..ContractEventFilter::default()
+        };
+        let mut cursor = None;
+        let mut limit = MAX_CONTRACT_EVENTS;
+
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            match key.as_ref() {
+                "contract" if !in_path => {
+                    contract = Some(QualifiedContractIdentifier::parse(&value).map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse contract identifier".into())
+                    })?);
+                }
+                "event_type" => {
+                    filter.event_type = Some(value.parse().map_err(net_error::DeserializeError)?);
+                }
+                "topic" => {
+                    filter.topic = Some(value.to_string());
+                }
+                "from_block" => {
+                    filter.from_block = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse from_block".into())
+                    })?;
+                }
+                "to_block" => {
+                    filter.to_block = Some(value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse to_block".into())
+                    })?);
+                }
+                "cursor" => {
+                    cursor = Some(value.parse().map_err(net_error::DeserializeError)?);
+                }
+                "limit" => {
+                    let requested: u32 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                    limit = requested.clamp(1, MAX_CONTRACT_EVENTS);
+                }
+                _ => {}
+            }
+        }
+
+        let contract = contract.ok_or(net_error::DeserializeError(
+            "Invalid Http request: expecting contract".to_string(),
+        ))?;
+
+        Ok(HttpRequestType::GetContractEvents(
+            HttpRequestMetadata::from_preamble(preamble),
+            ContractEventsQuery {
+                contract,
+                filter,
+                cursor,
+                limit,
+            },
+        ))
+    }
+
+    fn parse_get_contract_metrics<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetContractMetrics".to_string(),
+            ));
+        }
+
+        let mut contract = None;
+        let mut blocks = DEFAULT_CONTRACT_METRICS_BLOCKS;
+        let mut limit = MAX_CONTRACT_METRICS;
+
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            match key.as_ref() {
+                "contract" => {
+                    contract = Some(QualifiedContractIdentifier::parse(&value).map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse contract identifier".into())
+                    })?);
+                }
+                "blocks" => {
+                    let requested: u64 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse blocks".into())
+                    })?;
+                    blocks = requested.clamp(1, MAX_CONTRACT_METRICS_BLOCKS);
+                }
+                "limit" => {
+                    let requested: u32 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                    limit = requested.clamp(1, MAX_CONTRACT_METRICS);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(HttpRequestType::GetContractMetrics(
+            HttpRequestMetadata::from_preamble(preamble),
+            ContractMetricsQuery {
+                contract,
+                blocks,
+                limit,
+            },
+        ))
+    }
+
+    fn parse_get_chainstate_manifest<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetChainstateManifest"
+                    .to_string(),
+            ));
+        }
+
+        let mut interval = CHAINSTATE_MANIFEST_INTERVAL;
+        let mut count = MAX_CHAINSTATE_MANIFEST_CHECKPOINTS;
+
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            match key.as_ref() {
+                "interval" => {
+                    interval = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse interval".into())
+                    })?;
+                    if interval == 0 {
+                        return Err(net_error::DeserializeError(
+                            "Invalid Http request: interval must be positive".to_string(),
+                        ));
+                    }
+                }
+                "count" => {
+                    let requested: u64 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse count".into())
+                    })?;
+                    count = cmp::min(requested, MAX_CHAINSTATE_MANIFEST_CHECKPOINTS);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(HttpRequestType::GetChainstateManifest(
+            HttpRequestMetadata::from_preamble(preamble),
+            interval,
+            count,
+        ))
+    }
+
+    fn parse_get_mempool_transactions<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMemPoolTransactions"
+                    .to_string(),
+            ));
+        }
+
+        let mut filter = MemPoolTxFilter::default();
+        let mut cursor = None;
+        let mut limit = MAX_MEMPOOL_TRANSACTIONS;
+
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            match key.as_ref() {
+                "origin" => {
+                    filter.origin = Some(StacksAddress::from_string(&value).ok_or(
+                        net_error::DeserializeError("Failed to parse origin address".into()),
+                    )?);
+                }
+                "sponsor" => {
+                    filter.sponsor = Some(StacksAddress::from_string(&value).ok_or(
+                        net_error::DeserializeError("Failed to parse sponsor address".into()),
+                    )?);
+                }
+                "min_fee" => {
+                    filter.min_fee = Some(value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse min_fee".into())
+                    })?);
+                }
+                "max_fee" => {
+                    filter.max_fee = Some(value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse max_fee".into())
+                    })?);
+                }
+                "type" => {
+                    if !MEMPOOL_PAYLOAD_TYPES.contains(&value.as_ref()) {
+                        return Err(net_error::DeserializeError(format!(
+                            "Invalid transaction type: expected one of {}",
+                            MEMPOOL_PAYLOAD_TYPES.join(", ")
+                        )));
+                    }
+                    filter.payload_type = Some(value.to_string());
+                }
+                "cursor" => {
+                    cursor = Some(value.parse().map_err(net_error::DeserializeError)?);
+                }
+                "limit" => {
+                    let requested: u32 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                    limit = requested.clamp(1, MAX_MEMPOOL_TRANSACTIONS);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(HttpRequestType::GetMemPoolTransactions(
+            HttpRequestMetadata::from_preamble(preamble),
+            MemPoolTransactionsQuery {
+                filter,
+                cursor,
+                limit,
+            },
+        ))
+    }
+
+    fn parse_get_mempool_transaction<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMemPoolTransaction"
+                    .to_string(),
+            ));
+        }
+
+        let txid_hex = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to txid group".to_string(),
+            ))?
+            .as_str();
+        let txid = Txid::from_hex(txid_hex)
+            .map_err(|_e| net_error::DeserializeError("Failed to decode txid hex".to_string()))?;
+
+        Ok(HttpRequestType::GetMemPoolTransaction(
+            HttpRequestMetadata::from_preamble(preamble),
+            txid,
+        ))
+    }
+
+    fn parse_get_address_transactions<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAddressTransactions"
+                    .to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"])
+            .map_err(|_e| net_error::DeserializeError("Failed to parse principal".into()))?;
+
+        let mut cursor = None;
+        let mut limit = MAX_ADDRESS_TRANSACTIONS;
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            match key.as_ref() {
+                "cursor" => {
+                    cursor = Some(value.parse().map_err(net_error::DeserializeError)?);
+                }
+                "limit" => {
+                    let requested: u32 = value.parse().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                    limit = requested.clamp(1, MAX_ADDRESS_TRANSACTIONS);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(HttpRequestType::GetAddressTransactions(
+            HttpRequestMetadata::from_preamble(preamble),
+            AddressTransactionsQuery {
+                principal,
+                cursor,
+                limit,
+            },
+        ))
+    }
+
+    fn parse_get_address_assets<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAddressAssets".to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"])
+            .map_err(|_e| net_error::DeserializeError("Failed to parse principal".into()))?;
+
+        Ok(HttpRequestType::GetAddressAssets(
+            HttpRequestMetadata::from_preamble(preamble),
+            principal,
+        ))
+    }
+
+    fn parse_get_address_names<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAddressNames".to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"])
+            .map_err(|_e| net_error::DeserializeError("Failed to parse principal".into()))?;
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetAddressNames(
+            HttpRequestMetadata::from_preamble(preamble),
+            principal,
+            tip,
+        ))
+    }
+
+    fn parse_get_name<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetName".to_string(),
+            ));
+        }
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetName(
+            HttpRequestMetadata::from_preamble(preamble),
+            captures["name"].to_string(),
+            captures["namespace"].to_string(),
+            tip,
+        ))
+    }
+
+    fn parse_post_rosetta<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let endpoint = RosettaEndpoint::from_name(&captures["endpoint"])
+            .ok_or_else(|| net_error::DeserializeError("Unknown Rosetta endpoint".to_string()))?;
+
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < MAX_PAYLOAD_LEN) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for Rosetta ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
+        }
+
+        let bound_fd = BoundReader::from_reader(fd, content_len as u64);
+        let body: serde_json::Value = serde_json::from_reader(bound_fd).map_err(|e| {
+            net_error::DeserializeError(format!("Failed to parse JSON body: {}", e))
+        })?;
+
+        Ok(HttpRequestType::Rosetta(
+            HttpRequestMetadata::from_preamble(preamble),
+            endpoint,
+            body,
+        ))
+    }
+
+    fn parse_get_health_live<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetHealthLive".to_string(),
+            ));
+        }
+        Ok(HttpRequestType::GetHealthLive(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_get_health_ready<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetHealthReady".to_string(),
+            ));
+        }
+        Ok(HttpRequestType::GetHealthReady(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_get_peer_policy<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetPeerPolicy".to_string(),
+            ));
+        }
+        Ok(HttpRequestType::GetPeerPolicy(
+            HttpRequestMetadata::from_preamble(preamble),
         ))
     }
 
-    fn parse_post_mempool_query<R: Read>(
+    fn parse_post_peer_policy<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         _regex: &Captures,
-        query: Option<&str>,
+        _query: Option<&str>,
         fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() == 0 {
-            return Err(net_error::DeserializeError(
-                "Invalid Http request: expected non-empty body".to_string(),
-            ));
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < MAX_PAYLOAD_LEN) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for PostPeerPolicy ({})",
+                content_len
+            )));
         }
 
-        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: MemPoolQuery body is too big".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
-        // content-type must be given, and must be application/octet-stream
-        match preamble.content_type {
-            None => {
-                return Err(net_error::DeserializeError(
-                    "Missing Content-Type for MemPoolQuery".to_string(),
-                ));
-            }
-            Some(ref c) => {
-                if *c != HttpContentType::Bytes {
-                    return Err(net_error::DeserializeError(
-                        "Wrong Content-Type for MemPoolQuery; expected application/octet-stream"
-                            .to_string(),
-                    ));
-                }
-            }
-        };
+        let bound_fd = BoundReader::from_reader(fd, content_len as u64);
+        let body: PeerPolicyUpdateRequestBody = serde_json::from_reader(bound_fd).map_err(|e| {
+            net_error::DeserializeError(format!("Failed to parse JSON body: {}", e))
+        })?;
 
-        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
-        let mempool_query = MemPoolSyncData::consensus_deserialize(&mut bound_fd)?;
-        let page_id_opt = HttpRequestType::get_mempool_page_id_query(query);
+        let cidr = match body.cidr {
+            Some(ref cidr) => Some(PeerAddress::from_cidr_str(cidr).ok_or_else(|| {
+                net_error::DeserializeError(format!("Invalid CIDR range '{}'", cidr))
+            })?),
+            None => None,
+        };
+        if body.asn == Some(0) {
+            return Err(net_error::DeserializeError(
+                "Invalid AS number 0".to_string(),
+            ));
+        }
+        if cidr.is_none() && body.asn.is_none() {
+            return Err(net_error::DeserializeError(
+                "Peer policy update needs a `cidr` or an `asn`".to_string(),
+            ));
+        }
 
-        Ok(HttpRequestType::MemPoolQuery(
+        Ok(HttpRequestType::PostPeerPolicy(
             HttpRequestMetadata::from_preamble(preamble),
-            mempool_query,
-            page_id_opt,
+            PeerPolicyUpdate {
+                list: body.list,
+                remove: body.remove,
+                cidr,
+                asn: body.asn,
+            },
         ))
     }
 
@@ -2636,29 +4198,58 @@ impl HttpRequestType {
     pub fn metadata(&self) -> &HttpRequestMetadata {
         match *self {
             HttpRequestType::GetInfo(ref md) => md,
+            HttpRequestType::WebSocketUpgrade(ref md, _) => md,
             HttpRequestType::GetPoxInfo(ref md, ..) => md,
+            HttpRequestType::GetPoxCycle(ref md, ..) => md,
+            HttpRequestType::GetPoxRewardSet(ref md, _) => md,
+            HttpRequestType::GetPoxStacker(ref md, ..) => md,
             HttpRequestType::GetNeighbors(ref md) => md,
+            HttpRequestType::GetNeighborsDetail(ref md) => md,
+            HttpRequestType::GetTelemetry(ref md) => md,
             HttpRequestType::GetHeaders(ref md, ..) => md,
             HttpRequestType::GetBlock(ref md, _) => md,
+            HttpRequestType::GetBlockExport(ref md, ..) => md,
+            HttpRequestType::GetBlockStateDiff(ref md, _) => md,
             HttpRequestType::GetMicroblocksIndexed(ref md, _) => md,
             HttpRequestType::GetMicroblocksConfirmed(ref md, _) => md,
             HttpRequestType::GetMicroblocksUnconfirmed(ref md, _, _) => md,
+            HttpRequestType::GetMicroblockStream(ref md, ..) => md,
             HttpRequestType::GetTransactionUnconfirmed(ref md, _) => md,
-            HttpRequestType::PostTransaction(ref md, _, _) => md,
+            HttpRequestType::PostTransaction(ref md, ..) => md,
             HttpRequestType::PostBlock(ref md, ..) => md,
             HttpRequestType::PostMicroblock(ref md, ..) => md,
             HttpRequestType::GetAccount(ref md, ..) => md,
+            HttpRequestType::GetAccountProof(ref md, ..) => md,
             HttpRequestType::GetDataVar(ref md, ..) => md,
             HttpRequestType::GetMapEntry(ref md, ..) => md,
+            HttpRequestType::GetMapEntryBatch(ref md, ..) => md,
             HttpRequestType::GetTransferCost(ref md) => md,
             HttpRequestType::GetContractABI(ref md, ..) => md,
+            HttpRequestType::GetContractStorage(ref md, ..) => md,
+            HttpRequestType::PostConditionCheck(ref md, ..) => md,
             HttpRequestType::GetContractSrc(ref md, ..) => md,
             HttpRequestType::GetIsTraitImplemented(ref md, ..) => md,
             HttpRequestType::CallReadOnlyFunction(ref md, ..) => md,
+            HttpRequestType::CallReadOnlyFunctionBatch(ref md, ..) => md,
+            HttpRequestType::CheckContractCallArgs(ref md, ..) => md,
             HttpRequestType::OptionsPreflight(ref md, ..) => md,
             HttpRequestType::GetAttachmentsInv(ref md, ..) => md,
             HttpRequestType::GetAttachment(ref md, ..) => md,
             HttpRequestType::MemPoolQuery(ref md, ..) => md,
+            HttpRequestType::GetContractEvents(ref md, _) => md,
+            HttpRequestType::GetContractMetrics(ref md, _) => md,
+            HttpRequestType::GetChainstateManifest(ref md, ..) => md,
+            HttpRequestType::GetMemPoolTransactions(ref md, _) => md,
+            HttpRequestType::GetMemPoolTransaction(ref md, _) => md,
+            HttpRequestType::GetAddressTransactions(ref md, _) => md,
+            HttpRequestType::GetAddressAssets(ref md, _) => md,
+            HttpRequestType::GetAddressNames(ref md, ..) => md,
+            HttpRequestType::GetName(ref md, ..) => md,
+            HttpRequestType::Rosetta(ref md, ..) => md,
+            HttpRequestType::GetHealthLive(ref md) => md,
+            HttpRequestType::GetHealthReady(ref md) => md,
+            HttpRequestType::GetPeerPolicy(ref md) => md,
+            HttpRequestType::PostPeerPolicy(ref md, _) => md,
             HttpRequestType::FeeRateEstimate(ref md, _, _) => md,
             HttpRequestType::ClientError(ref md, ..) => md,
         }
@@ -2667,34 +4258,84 @@ impl HttpRequestType {
     pub fn metadata_mut(&mut self) -> &mut HttpRequestMetadata {
         match *self {
             HttpRequestType::GetInfo(ref mut md) => md,
+            HttpRequestType::WebSocketUpgrade(ref mut md, _) => md,
             HttpRequestType::GetPoxInfo(ref mut md, ..) => md,
+            HttpRequestType::GetPoxCycle(ref mut md, ..) => md,
+            HttpRequestType::GetPoxRewardSet(ref mut md, _) => md,
+            HttpRequestType::GetPoxStacker(ref mut md, ..) => md,
             HttpRequestType::GetNeighbors(ref mut md) => md,
+            HttpRequestType::GetNeighborsDetail(ref mut md) => md,
+            HttpRequestType::GetTelemetry(ref mut md) => md,
             HttpRequestType::GetHeaders(ref mut md, ..) => md,
             HttpRequestType::GetBlock(ref mut md, _) => md,
+            HttpRequestType::GetBlockExport(ref mut md, ..) => md,
+            HttpRequestType::GetBlockStateDiff(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksIndexed(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksConfirmed(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksUnconfirmed(ref mut md, _, _) => md,
+            HttpRequestType::GetMicroblockStream(ref mut md, ..) => md,
             HttpRequestType::GetTransactionUnconfirmed(ref mut md, _) => md,
-            HttpRequestType::PostTransaction(ref mut md, _, _) => md,
+            HttpRequestType::PostTransaction(ref mut md, ..) => md,
             HttpRequestType::PostBlock(ref mut md, ..) => md,
             HttpRequestType::PostMicroblock(ref mut md, ..) => md,
             HttpRequestType::GetAccount(ref mut md, ..) => md,
+            HttpRequestType::GetAccountProof(ref mut md, ..) => md,
             HttpRequestType::GetDataVar(ref mut md, ..) => md,
             HttpRequestType::GetMapEntry(ref mut md, ..) => md,
+            HttpRequestType::GetMapEntryBatch(ref mut md, ..) => md,
             HttpRequestType::GetTransferCost(ref mut md) => md,
             HttpRequestType::GetContractABI(ref mut md, ..) => md,
+            HttpRequestType::GetContractStorage(ref mut md, ..) => md,
+            HttpRequestType::PostConditionCheck(ref mut md, ..) => md,
             HttpRequestType::GetContractSrc(ref mut md, ..) => md,
             HttpRequestType::GetIsTraitImplemented(ref mut md, ..) => md,
             HttpRequestType::CallReadOnlyFunction(ref mut md, ..) => md,
+            HttpRequestType::CallReadOnlyFunctionBatch(ref mut md, ..) => md,
+            HttpRequestType::CheckContractCallArgs(ref mut md, ..) => md,
             HttpRequestType::OptionsPreflight(ref mut md, ..) => md,
             HttpRequestType::GetAttachmentsInv(ref mut md, ..) => md,
             HttpRequestType::GetAttachment(ref mut md, ..) => md,
             HttpRequestType::MemPoolQuery(ref mut md, ..) => md,
+            HttpRequestType::GetContractEvents(ref mut md, _) => md,
+            HttpRequestType::GetContractMetrics(ref mut md, _) => md,
+            HttpRequestType::GetChainstateManifest(ref mut md, ..) => md,
+            HttpRequestType::GetMemPoolTransactions(ref mut md, _) => md,
+            HttpRequestType::GetMemPoolTransaction(ref mut md, _) => md,
+            HttpRequestType::GetAddressTransactions(ref mut md, _) => md,
+            HttpRequestType::GetAddressAssets(ref mut md, _) => md,
+            HttpRequestType::GetAddressNames(ref mut md, ..) => md,
+            HttpRequestType::GetName(ref mut md, ..) => md,
+            HttpRequestType::Rosetta(ref mut md, ..) => md,
+            HttpRequestType::GetHealthLive(ref mut md) => md,
+            HttpRequestType::GetHealthReady(ref mut md) => md,
+            HttpRequestType::GetPeerPolicy(ref mut md) => md,
+            HttpRequestType::PostPeerPolicy(ref mut md, _) => md,
             HttpRequestType::FeeRateEstimate(ref mut md, _, _) => md,
             HttpRequestType::ClientError(ref mut md, ..) => md,
         }
     }
 
+    /// Does this request submit data to the node (as opposed to only reading from it)?
+    pub fn is_write(&self) -> bool {
+        matches!(
+            *self,
+            HttpRequestType::PostTransaction(..)
+                | HttpRequestType::PostBlock(..)
+                | HttpRequestType::PostMicroblock(..)
+                | HttpRequestType::Rosetta(_, RosettaEndpoint::ConstructionSubmit, _)
+        )
+    }
+
+    /// Is this a request for one of the endpoints that need the admin API key?
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            *self,
+            HttpRequestType::GetPeerPolicy(..)
+                | HttpRequestType::PostPeerPolicy(..)
+                | HttpRequestType::GetNeighborsDetail(..)
+        )
+    }
+
     fn make_tip_query_string(tip_req: &TipRequest, with_proof: bool) -> String {
         match tip_req {
             TipRequest::UseLatestUnconfirmedTip => {
@@ -2716,11 +4357,29 @@ impl HttpRequestType {
     pub fn request_path(&self) -> String {
         match self {
             HttpRequestType::GetInfo(_md) => "/v2/info".to_string(),
+            HttpRequestType::WebSocketUpgrade(..) => "/v2/ws".to_string(),
             HttpRequestType::GetPoxInfo(_md, tip_req) => format!(
                 "/v2/pox{}",
                 HttpRequestType::make_tip_query_string(tip_req, true)
             ),
+            HttpRequestType::GetPoxCycle(_md, cycle, tip_req) => format!(
+                "/v2/pox/cycles/{}{}",
+                cycle
+                    .map(|cycle| cycle.to_string())
+                    .unwrap_or_else(|| "current".to_string()),
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
+            HttpRequestType::GetPoxRewardSet(_md, cycle) => {
+                format!("/v2/pox/reward_set/{}", cycle)
+            }
+            HttpRequestType::GetPoxStacker(_md, principal, tip_req) => format!(
+                "/v2/pox/stackers/{}{}",
+                principal,
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
             HttpRequestType::GetNeighbors(_md) => "/v2/neighbors".to_string(),
+            HttpRequestType::GetNeighborsDetail(_md) => "/v2/neighbors/detail".to_string(),
+            HttpRequestType::GetTelemetry(_md) => "/v2/telemetry".to_string(),
             HttpRequestType::GetHeaders(_md, quantity, tip_req) => format!(
                 "/v2/headers/{}{}",
                 quantity,
@@ -2729,6 +4388,12 @@ impl HttpRequestType {
             HttpRequestType::GetBlock(_md, block_hash) => {
                 format!("/v2/blocks/{}", block_hash.to_hex())
             }
+            HttpRequestType::GetBlockExport(_md, start_height, count) => {
+                format!("/v2/blocks/export/{}?count={}", start_height, count)
+            }
+            HttpRequestType::GetBlockStateDiff(_md, block_id) => {
+                format!("/v2/blocks/{}/state-diff", block_id.to_hex())
+            }
             HttpRequestType::GetMicroblocksIndexed(_md, block_hash) => {
                 format!("/v2/microblocks/{}", block_hash.to_hex())
             }
@@ -2740,10 +4405,22 @@ impl HttpRequestType {
                 block_hash.to_hex(),
                 min_seq
             ),
+            HttpRequestType::GetMicroblockStream(_md, block_id, start, limit) => format!(
+                "/v2/microblocks/stream/{}?start={}&limit={}",
+                block_id.to_hex(),
+                start,
+                limit
+            ),
             HttpRequestType::GetTransactionUnconfirmed(_md, txid) => {
                 format!("/v2/transactions/unconfirmed/{}", txid)
             }
-            HttpRequestType::PostTransaction(_md, ..) => "/v2/transactions".to_string(),
+            HttpRequestType::PostTransaction(_md, _, _, with_report) => {
+                if *with_report {
+                    "/v2/transactions?report=1".to_string()
+                } else {
+                    "/v2/transactions".to_string()
+                }
+            }
             HttpRequestType::PostBlock(_md, ch, ..) => format!("/v2/blocks/upload/{}", &ch),
             HttpRequestType::PostMicroblock(_md, _, tip_req) => format!(
                 "/v2/microblocks{}",
@@ -2756,6 +4433,11 @@ impl HttpRequestType {
                     HttpRequestType::make_tip_query_string(tip_req, *with_proof,)
                 )
             }
+            HttpRequestType::GetAccountProof(_md, principal, tip_req) => format!(
+                "/v2/accounts/{}/proof{}",
+                &principal.to_string(),
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
             HttpRequestType::GetDataVar(
                 _md,
                 contract_addr,
@@ -2785,6 +4467,10 @@ impl HttpRequestType {
                 map_name.as_str(),
                 HttpRequestType::make_tip_query_string(tip_req, *with_proof)
             ),
+            HttpRequestType::GetMapEntryBatch(_md, _lookups, tip_req, with_proof) => format!(
+                "/v2/map_entries{}",
+                HttpRequestType::make_tip_query_string(tip_req, *with_proof)
+            ),
             HttpRequestType::GetTransferCost(_md) => "/v2/fees/transfer".into(),
             HttpRequestType::GetContractABI(_, contract_addr, contract_name, tip_req) => format!(
                 "/v2/contracts/interface/{}/{}{}",
@@ -2792,6 +4478,14 @@ impl HttpRequestType {
                 contract_name.as_str(),
                 HttpRequestType::make_tip_query_string(tip_req, true,)
             ),
+            HttpRequestType::GetContractStorage(_, contract_addr, contract_name, tip_req) => {
+                format!(
+                    "/v2/contracts/storage/{}/{}{}",
+                    contract_addr,
+                    contract_name.as_str(),
+                    HttpRequestType::make_tip_query_string(tip_req, true,)
+                )
+            }
             HttpRequestType::GetContractSrc(
                 _,
                 contract_addr,
@@ -2834,6 +4528,24 @@ impl HttpRequestType {
                 func_name.as_str(),
                 HttpRequestType::make_tip_query_string(tip_req, true)
             ),
+            HttpRequestType::CheckContractCallArgs(
+                _,
+                contract_addr,
+                contract_name,
+                func_name,
+                _,
+                tip_req,
+            ) => format!(
+                "/v2/contracts/check-args/{}/{}/{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                func_name.as_str(),
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
+            HttpRequestType::CallReadOnlyFunctionBatch(_, _, _, tip_req) => format!(
+                "/v2/contracts/call-read-batch{}",
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
             HttpRequestType::OptionsPreflight(_md, path) => path.to_string(),
             HttpRequestType::GetAttachmentsInv(_md, index_block_hash, pages_indexes) => {
                 let pages_query = match pages_indexes.len() {
@@ -2859,7 +4571,97 @@ impl HttpRequestType {
                 }
                 None => "/v2/mempool/query".to_string(),
             },
+            HttpRequestType::GetContractEvents(_, query) => {
+                let mut args = form_urlencoded::Serializer::new(String::new());
+                if let Some(ref event_type) = query.filter.event_type {
+                    args.append_pair("event_type", event_type.as_str());
+                }
+                if let Some(ref topic) = query.filter.topic {
+                    args.append_pair("topic", topic);
+                }
+                args.append_pair("from_block", &query.filter.from_block.to_string());
+                if let Some(ref to_block) = query.filter.to_block {
+                    args.append_pair("to_block", &to_block.to_string());
+                }
+                if let Some(ref cursor) = query.cursor {
+                    args.append_pair("cursor", &cursor.to_string());
+                }
+                args.append_pair("limit", &query.limit.to_string());
+                format!("/v2/contracts/{}/events?{}", &query.contract, args.finish())
+            }
+            HttpRequestType::GetContractMetrics(_, query) => {
+                let mut args = form_urlencoded::Serializer::new(String::new());
+                if let Some(ref contract) = query.contract {
+                    args.append_pair("contract", &contract.to_string());
+                }
+                args.append_pair("blocks", &query.blocks.to_string());
+                args.append_pair("limit", &query.limit.to_string());
+                format!("/v2/contracts/metrics?{}", args.finish())
+            }
+            HttpRequestType::GetChainstateManifest(_, interval, count) => format!(
+                "/v2/chainstate/manifest?interval={}&count={}",
+                interval, count
+            ),
+            HttpRequestType::GetMemPoolTransactions(_, query) => {
+                let mut args = form_urlencoded::Serializer::new(String::new());
+                if let Some(ref origin) = query.filter.origin {
+                    args.append_pair("origin", &origin.to_string());
+                }
+                if let Some(ref sponsor) = query.filter.sponsor {
+                    args.append_pair("sponsor", &sponsor.to_string());
+                }
+                if let Some(min_fee) = query.filter.min_fee {
+                    args.append_pair("min_fee", &min_fee.to_string());
+                }
+                if let Some(max_fee) = query.filter.max_fee {
+                    args.append_pair("max_fee", &max_fee.to_string());
+                }
+                if let Some(ref payload_type) = query.filter.payload_type {
+                    args.append_pair("type", payload_type);
+                }
+                if let Some(ref cursor) = query.cursor {
+                    args.append_pair("cursor", &cursor.to_string());
+                }
+                args.append_pair("limit", &query.limit.to_string());
+                format!("/v2/mempool/transactions?{}", args.finish())
+            }
+            HttpRequestType::GetMemPoolTransaction(_, txid) => {
+                format!("/v2/mempool/transactions/{}", txid)
+            }
+            HttpRequestType::GetAddressTransactions(_, query) => {
+                let mut args = form_urlencoded::Serializer::new(String::new());
+                if let Some(ref cursor) = query.cursor {
+                    args.append_pair("cursor", &cursor.to_string());
+                }
+                args.append_pair("limit", &query.limit.to_string());
+                format!(
+                    "/v2/addresses/{}/transactions?{}",
+                    &query.principal,
+                    args.finish()
+                )
+            }
+            HttpRequestType::GetAddressAssets(_, principal) => {
+                format!("/v2/addresses/{}/assets", principal)
+            }
+            HttpRequestType::GetAddressNames(_, principal, tip_req) => format!(
+                "/v2/addresses/{}/names{}",
+                principal,
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
+            HttpRequestType::GetName(_, name, namespace, tip_req) => format!(
+                "/v2/names/{}.{}{}",
+                name,
+                namespace,
+                HttpRequestType::make_tip_query_string(tip_req, true)
+            ),
+            HttpRequestType::Rosetta(_, endpoint, _) => endpoint.path(),
+            HttpRequestType::GetHealthLive(_) => "/v2/health/live".to_string(),
+            HttpRequestType::GetHealthReady(_) => "/v2/health/ready".to_string(),
+            HttpRequestType::GetPeerPolicy(_) | HttpRequestType::PostPeerPolicy(..) => {
+                "/v2/admin/peer_policy".to_string()
+            }
             HttpRequestType::FeeRateEstimate(_, _, _) => self.get_path().to_string(),
+            HttpRequestType::PostConditionCheck(..) => self.get_path().to_string(),
             HttpRequestType::ClientError(_md, e) => match e {
                 ClientError::NotFound(path) => path.to_string(),
                 _ => "error path unknown".into(),
@@ -2870,34 +4672,68 @@ impl HttpRequestType {
     pub fn get_path(&self) -> &'static str {
         match self {
             HttpRequestType::GetInfo(..) => "/v2/info",
+            HttpRequestType::WebSocketUpgrade(..) => "/v2/ws",
             HttpRequestType::GetPoxInfo(..) => "/v2/pox",
+            HttpRequestType::GetPoxCycle(..) => "/v2/pox/cycles/:cycle",
+            HttpRequestType::GetPoxRewardSet(..) => "/v2/pox/reward_set/:cycle",
+            HttpRequestType::GetPoxStacker(..) => "/v2/pox/stackers/:principal",
             HttpRequestType::GetNeighbors(..) => "/v2/neighbors",
+            HttpRequestType::GetNeighborsDetail(..) => "/v2/neighbors/detail",
+            HttpRequestType::GetTelemetry(..) => "/v2/telemetry",
             HttpRequestType::GetHeaders(..) => "/v2/headers/:height",
             HttpRequestType::GetBlock(..) => "/v2/blocks/:hash",
+            HttpRequestType::GetBlockExport(..) => "/v2/blocks/export/:height",
+            HttpRequestType::GetBlockStateDiff(..) => "/v2/blocks/:hash/state-diff",
             HttpRequestType::GetMicroblocksIndexed(..) => "/v2/microblocks/:hash",
             HttpRequestType::GetMicroblocksConfirmed(..) => "/v2/microblocks/confirmed/:hash",
             HttpRequestType::GetMicroblocksUnconfirmed(..) => {
                 "/v2/microblocks/unconfirmed/:hash/:seq"
             }
+            HttpRequestType::GetMicroblockStream(..) => "/v2/microblocks/stream/:hash",
             HttpRequestType::GetTransactionUnconfirmed(..) => "/v2/transactions/unconfirmed/:txid",
             HttpRequestType::PostTransaction(..) => "/v2/transactions",
             HttpRequestType::PostBlock(..) => "/v2/blocks/upload/:block",
             HttpRequestType::PostMicroblock(..) => "/v2/microblocks",
             HttpRequestType::GetAccount(..) => "/v2/accounts/:principal",
+            HttpRequestType::GetAccountProof(..) => "/v2/accounts/:principal/proof",
             HttpRequestType::GetDataVar(..) => "/v2/data_var/:principal/:contract_name/:var_name",
             HttpRequestType::GetMapEntry(..) => "/v2/map_entry/:principal/:contract_name/:map_name",
+            HttpRequestType::GetMapEntryBatch(..) => "/v2/map_entries",
             HttpRequestType::GetTransferCost(..) => "/v2/fees/transfer",
             HttpRequestType::GetContractABI(..) => {
                 "/v2/contracts/interface/:principal/:contract_name"
             }
+            HttpRequestType::GetContractStorage(..) => {
+                "/v2/contracts/storage/:principal/:contract_name"
+            }
+            HttpRequestType::PostConditionCheck(..) => "/v2/postconditions/check",
             HttpRequestType::GetContractSrc(..) => "/v2/contracts/source/:principal/:contract_name",
             HttpRequestType::CallReadOnlyFunction(..) => {
                 "/v2/contracts/call-read/:principal/:contract_name/:func_name"
             }
+            HttpRequestType::CallReadOnlyFunctionBatch(..) => "/v2/contracts/call-read-batch",
+            HttpRequestType::CheckContractCallArgs(..) => {
+                "/v2/contracts/check-args/:principal/:contract_name/:func_name"
+            }
             HttpRequestType::GetAttachmentsInv(..) => "/v2/attachments/inv",
             HttpRequestType::GetAttachment(..) => "/v2/attachments/:hash",
             HttpRequestType::GetIsTraitImplemented(..) => "/v2/traits/:principal/:contract_name",
             HttpRequestType::MemPoolQuery(..) => "/v2/mempool/query",
+            HttpRequestType::GetContractEvents(..) => "/v2/contracts/:contract_id/events",
+            HttpRequestType::GetContractMetrics(..) => "/v2/contracts/metrics",
+            HttpRequestType::GetChainstateManifest(..) => "/v2/chainstate/manifest",
+            HttpRequestType::GetMemPoolTransactions(..) => "/v2/mempool/transactions",
+            HttpRequestType::GetMemPoolTransaction(..) => "/v2/mempool/transactions/:txid",
+            HttpRequestType::GetAddressTransactions(..) => "/v2/addresses/:principal/transactions",
+            HttpRequestType::GetAddressAssets(..) => "/v2/addresses/:principal/assets",
+            HttpRequestType::GetAddressNames(..) => "/v2/addresses/:principal/names",
+            HttpRequestType::GetName(..) => "/v2/names/:name",
+            HttpRequestType::Rosetta(..) => "/rosetta/v1/:endpoint",
+            HttpRequestType::GetHealthLive(..) => "/v2/health/live",
+            HttpRequestType::GetHealthReady(..) => "/v2/health/ready",
+            HttpRequestType::GetPeerPolicy(..) | HttpRequestType::PostPeerPolicy(..) => {
+                "/v2/admin/peer_policy"
+            }
             HttpRequestType::FeeRateEstimate(_, _, _) => "/v2/fees/transaction",
             HttpRequestType::OptionsPreflight(..) | HttpRequestType::ClientError(..) => "/",
         }
@@ -2905,7 +4741,7 @@ impl HttpRequestType {
 
     pub fn send<W: Write>(&self, _protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
         match self {
-            HttpRequestType::PostTransaction(md, tx, attachment) => {
+            HttpRequestType::PostTransaction(md, tx, attachment, _) => {
                 let mut tx_bytes = vec![];
                 write_next(&mut tx_bytes, tx)?;
                 let tx_hex = to_hex(&tx_bytes[..]);
@@ -3010,6 +4846,47 @@ impl HttpRequestType {
                 fd.write_all(&request_json.as_bytes())
                     .map_err(net_error::WriteError)?;
             }
+            HttpRequestType::GetMapEntryBatch(md, lookups, ..) => {
+                let mut entries = vec![];
+                for lookup in lookups.iter() {
+                    let mut keys = vec![];
+                    for key in lookup.keys.iter() {
+                        let mut key_bytes = vec![];
+                        key.serialize_write(&mut key_bytes)
+                            .map_err(net_error::WriteError)?;
+                        keys.push(to_hex(&key_bytes));
+                    }
+                    entries.push(MapEntryBatchRequestEntry {
+                        contract: lookup.contract_id.to_string(),
+                        map: lookup.map_name.to_string(),
+                        keys,
+                    });
+                }
+
+                let request_body = MapEntryBatchRequestBody { maps: entries };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize map entry batch to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
             HttpRequestType::CallReadOnlyFunction(
                 md,
                 _contract_addr,
@@ -3035,7 +4912,156 @@ impl HttpRequestType {
                 let mut request_body_bytes = vec![];
                 serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
                     net_error::SerializeError(format!(
-                        "Failed to serialize read-only call to JSON: {:?}",
+                        "Failed to serialize read-only call to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::CheckContractCallArgs(md, _, _, _, func_args, ..) => {
+                let mut args = vec![];
+                for arg in func_args.iter() {
+                    let mut arg_bytes = vec![];
+                    arg.serialize_write(&mut arg_bytes)
+                        .map_err(net_error::WriteError)?;
+                    args.push(to_hex(&arg_bytes));
+                }
+
+                let request_body = CheckContractCallArgsRequestBody { arguments: args };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize contract-call arguments to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::CallReadOnlyFunctionBatch(md, sender, calls, ..) => {
+                let mut entries = vec![];
+                for call in calls.iter() {
+                    let mut args = vec![];
+                    for arg in call.args.iter() {
+                        let mut arg_bytes = vec![];
+                        arg.serialize_write(&mut arg_bytes)
+                            .map_err(net_error::WriteError)?;
+                        args.push(to_hex(&arg_bytes));
+                    }
+                    entries.push(CallReadOnlyBatchEntry {
+                        contract: call.contract_id.to_string(),
+                        function: call.function.to_string(),
+                        arguments: args,
+                    });
+                }
+
+                let request_body = CallReadOnlyBatchRequestBody {
+                    sender: sender.to_string(),
+                    calls: entries,
+                };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize read-only call batch to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::PostConditionCheck(md, tx, events) => {
+                let mut tx_bytes = vec![];
+                write_next(&mut tx_bytes, tx)?;
+
+                let request_body = PostConditionCheckRequestBody {
+                    transaction: to_hex(&tx_bytes),
+                    events: events
+                        .iter()
+                        .filter_map(PostConditionCheckEvent::from_event)
+                        .collect(),
+                };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize post-condition check to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::MemPoolQuery(md, query, ..) => {
+                let request_body_bytes = query.serialize_to_vec();
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::Bytes),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::Rosetta(md, _, body) => {
+                let request_body_bytes = serde_json::to_vec(body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize Rosetta request to JSON: {:?}",
                         &e
                     ))
                 })?;
@@ -3054,8 +5080,23 @@ impl HttpRequestType {
                 fd.write_all(&request_body_bytes)
                     .map_err(net_error::WriteError)?;
             }
-            HttpRequestType::MemPoolQuery(md, query, ..) => {
-                let request_body_bytes = query.serialize_to_vec();
+            HttpRequestType::PostPeerPolicy(md, update) => {
+                let request_body = PeerPolicyUpdateRequestBody {
+                    list: update.list,
+                    remove: update.remove,
+                    cidr: update
+                        .cidr
+                        .as_ref()
+                        .map(|(prefix, mask)| prefix.to_cidr_string(*mask)),
+                    asn: update.asn,
+                };
+                let request_body_bytes = serde_json::to_vec(&request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize peer policy update to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
                 HttpRequestPreamble::new_serialized(
                     fd,
                     &md.version,
@@ -3064,12 +5105,40 @@ impl HttpRequestType {
                     &md.peer,
                     md.keep_alive,
                     Some(request_body_bytes.len() as u32),
-                    Some(&HttpContentType::Bytes),
-                    empty_headers,
+                    Some(&HttpContentType::JSON),
+                    |ref mut fd| {
+                        if let Some(ref api_key) = md.api_key {
+                            fd.write_all(format!("X-API-Key: {}\r\n", api_key).as_bytes())
+                                .map_err(codec_error::WriteError)?;
+                        }
+                        Ok(())
+                    },
                 )?;
                 fd.write_all(&request_body_bytes)
                     .map_err(net_error::WriteError)?;
             }
+            HttpRequestType::WebSocketUpgrade(md, key) => {
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "GET",
+                    &self.request_path(),
+                    &md.peer,
+                    true,
+                    None,
+                    None,
+                    |ref mut fd| {
+                        fd.write_all(
+                            format!(
+                                "Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {}\r\n",
+                                key
+                            )
+                            .as_bytes(),
+                        )
+                        .map_err(codec_error::WriteError)
+                    },
+                )?;
+            }
             other_type => {
                 let md = other_type.metadata();
                 let request_path = other_type.request_path();
@@ -3082,7 +5151,23 @@ impl HttpRequestType {
                     md.keep_alive,
                     None,
                     None,
-                    empty_headers,
+                    |ref mut fd| {
+                        if let Some(ref etags) = md.if_none_match {
+                            fd.write_all(format!("If-None-Match: {}\r\n", etags).as_bytes())
+                                .map_err(codec_error::WriteError)?;
+                        }
+                        if let Some(ref api_key) = md.api_key {
+                            fd.write_all(format!("X-API-Key: {}\r\n", api_key).as_bytes())
+                                .map_err(codec_error::WriteError)?;
+                        }
+                        if md.accept_encoding != HttpContentEncoding::Identity {
+                            fd.write_all(
+                                format!("Accept-Encoding: {}\r\n", md.accept_encoding).as_bytes(),
+                            )
+                            .map_err(codec_error::WriteError)?;
+                        }
+                        Ok(())
+                    },
                 )?;
             }
         }
@@ -3150,6 +5235,7 @@ impl HttpResponseType {
             402 => HttpResponseType::PaymentRequired(md, error_text),
             403 => HttpResponseType::Forbidden(md, error_text),
             404 => HttpResponseType::NotFound(md, error_text),
+            429 => HttpResponseType::TooManyRequests(md, error_text),
             500 => HttpResponseType::ServerError(md, error_text),
             503 => HttpResponseType::ServiceUnavailable(md, error_text),
             _ => HttpResponseType::Error(md, preamble.status_code, error_text),
@@ -3300,10 +5386,144 @@ impl HttpResponseType {
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
+        if preamble.status_code == 500
+            && preamble.content_type == HttpContentType::JSON
+            && PATH_POST_ROSETTA.is_match(&request_path)
+        {
+            // Rosetta errors are JSON objects, not text
+            let error =
+                HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+            return Ok(HttpResponseType::RosettaError(
+                HttpResponseMetadata::from_preamble(request_version, preamble),
+                error,
+            ));
+        }
+        if preamble.status_code == 503
+            && preamble.content_type == HttpContentType::JSON
+            && PATH_GET_HEALTH_READY.is_match(&request_path)
+        {
+            // a node that isn't ready still says why
+            return HttpResponseType::parse_get_health_ready(
+                protocol,
+                request_version,
+                preamble,
+                fd,
+                len_hint,
+            );
+        }
         if preamble.status_code >= 400 {
             return HttpResponseType::parse_error(protocol, request_version, preamble, fd);
         }
+        if preamble.status_code == 304 {
+            return Ok(HttpResponseType::NotModified(
+                HttpResponseMetadata::from_preamble(request_version, preamble),
+            ));
+        }
+
+        let encoding = match preamble.headers.get("content-encoding") {
+            Some(value) => value.parse::<HttpContentEncoding>()?,
+            None => HttpContentEncoding::Identity,
+        };
+        if encoding != HttpContentEncoding::Identity {
+            // decompress the body, and parse it as if it had been sent as-is
+            let body = HttpResponseType::read_decoded_body(
+                preamble,
+                encoding,
+                fd,
+                len_hint,
+                MAX_MESSAGE_LEN as u64,
+            )?;
+            let mut decoded_preamble = preamble.clone();
+            decoded_preamble.content_length = Some(body.len() as u32);
+            return HttpResponseType::parse_decoded(
+                protocol,
+                request_version,
+                &decoded_preamble,
+                request_path,
+                &mut &body[..],
+                Some(body.len()),
+            );
+        }
+
+        HttpResponseType::parse_decoded(
+            protocol,
+            request_version,
+            preamble,
+            request_path,
+            fd,
+            len_hint,
+        )
+    }
+
+    /// Read a compressed response body, and decompress it
+    fn read_decoded_body<R: Read>(
+        preamble: &HttpResponsePreamble,
+        encoding: HttpContentEncoding,
+        fd: &mut R,
+        len_hint: Option<usize>,
+        max_len: u64,
+    ) -> Result<Vec<u8>, net_error> {
+        let mut encoded = vec![];
+        if preamble.is_chunked() && len_hint.is_none() {
+            let mut chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
+            chunked_fd
+                .read_to_end(&mut encoded)
+                .map_err(net_error::ReadError)?;
+        } else {
+            let content_length_opt = match (preamble.content_length, len_hint) {
+                (Some(l), _) => Some(l as u64),
+                (None, Some(l)) => Some(l as u64),
+                (None, None) => None,
+            };
+            if let Some(content_length) = content_length_opt {
+                if content_length > max_len {
+                    return Err(net_error::DeserializeError(
+                        "Invalid Content-Length header: too long".to_string(),
+                    ));
+                }
+                let mut bound_fd = BoundReader::from_reader(fd, content_length);
+                bound_fd
+                    .read_to_end(&mut encoded)
+                    .map_err(net_error::ReadError)?;
+            } else {
+                // unsupported headers
+                trace!("preamble: {:?}", preamble);
+                return Err(net_error::DeserializeError(
+                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
+                ));
+            }
+        }
+
+        // don't let a small body decompress into an enormous one
+        let mut decoded = vec![];
+        let decode_res = match encoding {
+            HttpContentEncoding::Identity => {
+                return Ok(encoded);
+            }
+            HttpContentEncoding::Gzip => gzip::Decoder::new(&encoded[..])
+                .and_then(|decoder| decoder.take(max_len + 1).read_to_end(&mut decoded)),
+            HttpContentEncoding::Deflate => zlib::Decoder::new(&encoded[..])
+                .and_then(|decoder| decoder.take(max_len + 1).read_to_end(&mut decoded)),
+        };
+        decode_res.map_err(|e| {
+            net_error::DeserializeError(format!("Failed to decompress {} body: {:?}", encoding, &e))
+        })?;
+        if decoded.len() as u64 > max_len {
+            return Err(net_error::DeserializeError(
+                "Invalid response: decompressed body is too long".to_string(),
+            ));
+        }
+        Ok(decoded)
+    }
 
+    fn parse_decoded<R: Read>(
+        protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        request_path: String,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
         // TODO: make this static somehow
         let RESPONSE_METHODS: &[(
             &Regex,
@@ -3317,11 +5537,34 @@ impl HttpResponseType {
         )] = &[
             (&PATH_GETINFO, &HttpResponseType::parse_peerinfo),
             (&PATH_GETPOXINFO, &HttpResponseType::parse_poxinfo),
+            (&PATH_GET_POX_CYCLE, &HttpResponseType::parse_get_pox_cycle),
+            (
+                &PATH_GET_POX_REWARD_SET,
+                &HttpResponseType::parse_get_pox_reward_set,
+            ),
+            (
+                &PATH_GET_POX_STACKER,
+                &HttpResponseType::parse_get_pox_stacker,
+            ),
             (&PATH_GETNEIGHBORS, &HttpResponseType::parse_neighbors),
+            (
+                &PATH_GETNEIGHBORS_DETAIL,
+                &HttpResponseType::parse_neighbors_detail,
+            ),
+            (&PATH_GETTELEMETRY, &HttpResponseType::parse_telemetry),
             (&PATH_GETHEADERS, &HttpResponseType::parse_headers),
             (&PATH_GETBLOCK, &HttpResponseType::parse_block),
+            (&PATH_GETBLOCK_EXPORT, &HttpResponseType::parse_block_export),
+            (
+                &PATH_GETBLOCK_STATE_DIFF,
+                &HttpResponseType::parse_block_state_diff,
+            ),
             (&PATH_GET_DATA_VAR, &HttpResponseType::parse_get_data_var),
             (&PATH_GET_MAP_ENTRY, &HttpResponseType::parse_get_map_entry),
+            (
+                &PATH_POST_MAP_ENTRIES,
+                &HttpResponseType::parse_get_map_entry_batch,
+            ),
             (
                 &PATH_GETMICROBLOCKS_INDEXED,
                 &HttpResponseType::parse_microblocks,
@@ -3334,6 +5577,10 @@ impl HttpResponseType {
                 &PATH_GETMICROBLOCKS_UNCONFIRMED,
                 &HttpResponseType::parse_microblocks_unconfirmed,
             ),
+            (
+                &PATH_GETMICROBLOCK_STREAM,
+                &HttpResponseType::parse_microblock_stream_page,
+            ),
             (
                 &PATH_GETTRANSACTION_UNCONFIRMED,
                 &HttpResponseType::parse_transaction_unconfirmed,
@@ -3348,6 +5595,10 @@ impl HttpResponseType {
                 &HttpResponseType::parse_microblock_hash,
             ),
             (&PATH_GET_ACCOUNT, &HttpResponseType::parse_get_account),
+            (
+                &PATH_GET_ACCOUNT_PROOF,
+                &HttpResponseType::parse_get_account_proof,
+            ),
             (
                 &PATH_GET_CONTRACT_SRC,
                 &HttpResponseType::parse_get_contract_src,
@@ -3360,10 +5611,26 @@ impl HttpResponseType {
                 &PATH_GET_CONTRACT_ABI,
                 &HttpResponseType::parse_get_contract_abi,
             ),
+            (
+                &PATH_GET_CONTRACT_STORAGE,
+                &HttpResponseType::parse_get_contract_storage,
+            ),
+            (
+                &PATH_POST_POSTCONDITIONS_CHECK,
+                &HttpResponseType::parse_post_postconditions_check,
+            ),
             (
                 &PATH_POST_CALL_READ_ONLY,
                 &HttpResponseType::parse_call_read_only,
             ),
+            (
+                &PATH_POST_CALL_READ_ONLY_BATCH,
+                &HttpResponseType::parse_call_read_only_batch,
+            ),
+            (
+                &PATH_POST_CHECK_CONTRACT_CALL_ARGS,
+                &HttpResponseType::parse_check_contract_call_args,
+            ),
             (
                 &PATH_GET_ATTACHMENT,
                 &HttpResponseType::parse_get_attachment,
@@ -3376,6 +5643,56 @@ impl HttpResponseType {
                 &PATH_POST_MEMPOOL_QUERY,
                 &HttpResponseType::parse_post_mempool_query,
             ),
+            (
+                &PATH_GET_CONTRACT_EVENTS,
+                &HttpResponseType::parse_get_contract_events,
+            ),
+            (
+                &PATH_GET_CONTRACT_ID_EVENTS,
+                &HttpResponseType::parse_get_contract_events,
+            ),
+            (
+                &PATH_GET_CONTRACT_METRICS,
+                &HttpResponseType::parse_get_contract_metrics,
+            ),
+            (
+                &PATH_GET_CHAINSTATE_MANIFEST,
+                &HttpResponseType::parse_get_chainstate_manifest,
+            ),
+            (
+                &PATH_GET_MEMPOOL_TRANSACTIONS,
+                &HttpResponseType::parse_get_mempool_transactions,
+            ),
+            (
+                &PATH_GET_MEMPOOL_TRANSACTION,
+                &HttpResponseType::parse_get_mempool_transaction,
+            ),
+            (
+                &PATH_GET_ADDRESS_TRANSACTIONS,
+                &HttpResponseType::parse_get_address_transactions,
+            ),
+            (
+                &PATH_GET_ADDRESS_ASSETS,
+                &HttpResponseType::parse_get_address_assets,
+            ),
+            (
+                &PATH_GET_ADDRESS_NAMES,
+                &HttpResponseType::parse_get_address_names,
+            ),
+            (&PATH_GET_NAME, &HttpResponseType::parse_get_name),
+            (&PATH_POST_ROSETTA, &HttpResponseType::parse_rosetta),
+            (
+                &PATH_GET_HEALTH_LIVE,
+                &HttpResponseType::parse_get_health_live,
+            ),
+            (
+                &PATH_GET_HEALTH_READY,
+                &HttpResponseType::parse_get_health_ready,
+            ),
+            (
+                &PATH_ADMIN_PEER_POLICY,
+                &HttpResponseType::parse_peer_policy,
+            ),
         ];
 
         // use url::Url to parse path and query string
@@ -3450,52 +5767,173 @@ impl HttpResponseType {
             HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
         Ok(HttpResponseType::PoxInfo(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            pox_info,
+            pox_info,
+        ))
+    }
+
+    fn parse_get_pox_cycle<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let cycle = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PoxCycle(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            cycle,
+        ))
+    }
+
+    fn parse_get_pox_reward_set<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let reward_set =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PoxRewardSet(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            reward_set,
+        ))
+    }
+
+    fn parse_get_pox_stacker<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let stacker = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PoxStacker(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            stacker,
+        ))
+    }
+
+    fn parse_neighbors<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let neighbors_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Neighbors(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            neighbors_data,
+        ))
+    }
+
+    fn parse_neighbors_detail<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let detail_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::NeighborsDetail(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            detail_data,
+        ))
+    }
+
+    fn parse_telemetry<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let telemetry_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Telemetry(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            telemetry_data,
+        ))
+    }
+
+    fn parse_headers<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let headers: Vec<ExtendedStacksHeader> =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Headers(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            headers,
+        ))
+    }
+
+    fn parse_block<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let block: StacksBlock =
+            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Block(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            block,
         ))
     }
 
-    fn parse_neighbors<R: Read>(
+    fn parse_block_export<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let neighbors_data =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::Neighbors(
+        let blocks: Vec<ExportedBlockData> = HttpResponseType::parse_bytestream(
+            preamble,
+            fd,
+            len_hint,
+            (MAX_BLOCK_EXPORT as u64) * (MAX_MESSAGE_LEN as u64),
+        )?;
+        Ok(HttpResponseType::BlockExport(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            neighbors_data,
+            blocks,
         ))
     }
 
-    fn parse_headers<R: Read>(
+    fn parse_block_state_diff<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let headers: Vec<ExtendedStacksHeader> =
+        let state_diff =
             HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::Headers(
+        Ok(HttpResponseType::BlockStateDiff(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            headers,
+            state_diff,
         ))
     }
 
-    fn parse_block<R: Read>(
+    fn parse_microblock_stream_page<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let block: StacksBlock =
-            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::Block(
+        let page = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::MicroblockStreamPage(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            block,
+            page,
         ))
     }
 
@@ -3529,6 +5967,21 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_get_account_proof<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let proof: AccountProof =
+            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::AccountProof(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            proof,
+        ))
+    }
+
     fn parse_get_data_var<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3559,6 +6012,21 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_get_map_entry_batch<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let batch_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetMapEntryBatch(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            batch_data,
+        ))
+    }
+
     fn parse_get_contract_src<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3603,6 +6071,34 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_get_contract_storage<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let storage = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetContractStorage(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            storage,
+        ))
+    }
+
+    fn parse_post_postconditions_check<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let verdict = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PostConditionCheck(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            verdict,
+        ))
+    }
+
     fn parse_call_read_only<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3618,6 +6114,36 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_call_read_only_batch<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let batch_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::CallReadOnlyFunctionBatch(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            batch_data,
+        ))
+    }
+
+    fn parse_check_contract_call_args<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let check_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::CheckContractCallArgs(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            check_data,
+        ))
+    }
+
     fn parse_microblocks_unconfirmed<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3682,6 +6208,8 @@ impl HttpResponseType {
         ))
     }
 
+    /// A POSTed transaction is answered with either a bare txid, or an admission report if
+    /// the request asked for one.
     fn parse_txid<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3689,7 +6217,22 @@ impl HttpResponseType {
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let txid_hex: String = HttpResponseType::parse_json(preamble, fd, len_hint, 66)?;
+        let body: serde_json::Value =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        let txid_hex = match body {
+            serde_json::Value::String(txid_hex) => txid_hex,
+            report => {
+                let report = serde_json::from_value(report).map_err(|_e| {
+                    net_error::DeserializeError(
+                        "Failed to decode transaction admission report".to_string(),
+                    )
+                })?;
+                return Ok(HttpResponseType::TransactionAdmissionReport(
+                    HttpResponseMetadata::from_preamble(request_version, preamble),
+                    report,
+                ));
+            }
+        };
         if txid_hex.len() != 64 {
             return Err(net_error::DeserializeError(
                 "Invalid txid: expected 64 bytes".to_string(),
@@ -3736,6 +6279,192 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_get_contract_events<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let events = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::ContractEvents(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            events,
+        ))
+    }
+
+    fn parse_get_contract_metrics<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let metrics = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::ContractMetrics(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            metrics,
+        ))
+    }
+
+    fn parse_get_chainstate_manifest<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let manifest =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::ChainstateManifest(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            manifest,
+        ))
+    }
+
+    fn parse_get_mempool_transactions<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let transactions =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::MemPoolTransactions(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            transactions,
+        ))
+    }
+
+    fn parse_get_mempool_transaction<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let transaction =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::MemPoolTransaction(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            transaction,
+        ))
+    }
+
+    fn parse_get_address_transactions<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let transactions =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::AddressTransactions(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            transactions,
+        ))
+    }
+
+    fn parse_get_address_assets<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let assets = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::AddressAssets(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            assets,
+        ))
+    }
+
+    fn parse_get_address_names<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let names = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::AddressNames(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            names,
+        ))
+    }
+
+    fn parse_get_name<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let name = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Name(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            name,
+        ))
+    }
+
+    fn parse_rosetta<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let body = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Rosetta(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            body,
+        ))
+    }
+
+    fn parse_get_health_live<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let live = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::HealthLive(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            live,
+        ))
+    }
+
+    fn parse_get_health_ready<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let ready = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::HealthReady(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            ready,
+        ))
+    }
+
+    fn parse_peer_policy<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let policy = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PeerPolicy(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            policy,
+        ))
+    }
+
     fn parse_stacks_block_accepted<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3918,6 +6647,7 @@ impl HttpResponseType {
             402 => "Payment Required",
             403 => "Forbidden",
             404 => "Not Found",
+            429 => "Too Many Requests",
             500 => "Internal Server Error",
             503 => "Service Temporarily Unavailable",
             _ => "Error",
@@ -3938,7 +6668,7 @@ impl HttpResponseType {
             Some(message.len() as u32),
             &HttpContentType::Text,
             md.request_id,
-            |ref mut fd| keep_alive_headers(fd, md),
+            |ref mut fd| metadata_headers(fd, md),
         )?;
         fd.write_all(message.as_bytes())
             .map_err(net_error::WriteError)?;
@@ -3949,31 +6679,63 @@ impl HttpResponseType {
         match *self {
             HttpResponseType::PeerInfo(ref md, _) => md,
             HttpResponseType::PoxInfo(ref md, _) => md,
+            HttpResponseType::PoxCycle(ref md, _) => md,
+            HttpResponseType::PoxRewardSet(ref md, _) => md,
+            HttpResponseType::PoxStacker(ref md, _) => md,
             HttpResponseType::Neighbors(ref md, _) => md,
+            HttpResponseType::NeighborsDetail(ref md, _) => md,
+            HttpResponseType::Telemetry(ref md, _) => md,
             HttpResponseType::HeaderStream(ref md) => md,
             HttpResponseType::Headers(ref md, _) => md,
             HttpResponseType::Block(ref md, _) => md,
             HttpResponseType::BlockStream(ref md) => md,
+            HttpResponseType::BlockExport(ref md, _) => md,
+            HttpResponseType::BlockExportStream(ref md) => md,
+            HttpResponseType::BlockStateDiff(ref md, _) => md,
             HttpResponseType::Microblocks(ref md, _) => md,
             HttpResponseType::MicroblockStream(ref md) => md,
+            HttpResponseType::MicroblockStreamPage(ref md, _) => md,
             HttpResponseType::TransactionID(ref md, _) => md,
+            HttpResponseType::TransactionAdmissionReport(ref md, _) => md,
             HttpResponseType::StacksBlockAccepted(ref md, ..) => md,
             HttpResponseType::MicroblockHash(ref md, _) => md,
             HttpResponseType::TokenTransferCost(ref md, _) => md,
             HttpResponseType::GetDataVar(ref md, _) => md,
             HttpResponseType::GetMapEntry(ref md, _) => md,
+            HttpResponseType::GetMapEntryBatch(ref md, _) => md,
             HttpResponseType::GetAccount(ref md, _) => md,
+            HttpResponseType::AccountProof(ref md, _) => md,
             HttpResponseType::GetContractABI(ref md, _) => md,
+            HttpResponseType::GetContractStorage(ref md, _) => md,
+            HttpResponseType::PostConditionCheck(ref md, _) => md,
             HttpResponseType::GetContractSrc(ref md, _) => md,
             HttpResponseType::GetIsTraitImplemented(ref md, _) => md,
             HttpResponseType::CallReadOnlyFunction(ref md, _) => md,
+            HttpResponseType::CallReadOnlyFunctionBatch(ref md, _) => md,
+            HttpResponseType::WebSocketAccepted(ref md, _) => md,
+            HttpResponseType::CheckContractCallArgs(ref md, _) => md,
             HttpResponseType::UnconfirmedTransaction(ref md, _) => md,
             HttpResponseType::GetAttachment(ref md, _) => md,
             HttpResponseType::GetAttachmentsInv(ref md, _) => md,
             HttpResponseType::MemPoolTxStream(ref md) => md,
             HttpResponseType::MemPoolTxs(ref md, ..) => md,
             HttpResponseType::OptionsPreflight(ref md) => md,
+            HttpResponseType::NotModified(ref md) => md,
             HttpResponseType::TransactionFeeEstimation(ref md, _) => md,
+            HttpResponseType::ContractEvents(ref md, _) => md,
+            HttpResponseType::ContractMetrics(ref md, _) => md,
+            HttpResponseType::ChainstateManifest(ref md, _) => md,
+            HttpResponseType::MemPoolTransactions(ref md, _) => md,
+            HttpResponseType::MemPoolTransaction(ref md, _) => md,
+            HttpResponseType::AddressTransactions(ref md, _) => md,
+            HttpResponseType::AddressAssets(ref md, _) => md,
+            HttpResponseType::AddressNames(ref md, _) => md,
+            HttpResponseType::Name(ref md, _) => md,
+            HttpResponseType::Rosetta(ref md, _) => md,
+            HttpResponseType::RosettaError(ref md, _) => md,
+            HttpResponseType::HealthLive(ref md, _) => md,
+            HttpResponseType::HealthReady(ref md, _) => md,
+            HttpResponseType::PeerPolicy(ref md, _) => md,
             // errors
             HttpResponseType::BadRequestJSON(ref md, _) => md,
             HttpResponseType::BadRequest(ref md, _) => md,
@@ -3982,6 +6744,7 @@ impl HttpResponseType {
             HttpResponseType::Forbidden(ref md, _) => md,
             HttpResponseType::NotFound(ref md, _) => md,
             HttpResponseType::ServerError(ref md, _) => md,
+            HttpResponseType::TooManyRequests(ref md, _) => md,
             HttpResponseType::ServiceUnavailable(ref md, _) => md,
             HttpResponseType::Error(ref md, _, _) => md,
         }
@@ -4031,13 +6794,15 @@ impl HttpResponseType {
         fd: &mut W,
         message: &T,
     ) -> Result<(), net_error> {
-        if md.content_length.is_some() {
+        if md.content_length.is_some() && md.content_encoding == HttpContentEncoding::Identity {
             // have explicit content-length, so we can send as-is
             serde_json::to_writer(fd, message)
                 .map_err(|e| net_error::SerializeError(format!("Failed to send as JSON: {:?}", &e)))
         } else {
-            // no content-length, so send as chunk-encoded
-            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
+            // no content-length (or we don't know the compressed length), so send as
+            // chunk-encoded
+            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size)
+                .with_content_encoding(md.content_encoding);
             let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
             serde_json::to_writer(&mut encoder, message).map_err(|e| {
                 net_error::SerializeError(format!("Failed to send as chunk-encoded JSON: {:?}", &e))
@@ -4051,16 +6816,112 @@ impl HttpResponseType {
         match *self {
             HttpResponseType::GetAccount(ref md, ref account_data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, account_data)?;
+                HttpResponseType::send_json(protocol, md, fd, account_data)?;
+            }
+            HttpResponseType::AccountProof(ref md, ref proof) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| metadata_headers(fd, md),
+                )?;
+                HttpResponseType::send_bytestream(protocol, md, fd, proof)?;
+            }
+            HttpResponseType::TransactionFeeEstimation(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::ContractEvents(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::ContractMetrics(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::ChainstateManifest(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::MemPoolTransactions(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::MemPoolTransaction(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::AddressTransactions(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
             }
-            HttpResponseType::TransactionFeeEstimation(ref md, ref data) => {
+            HttpResponseType::AddressAssets(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::AddressNames(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::Name(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::Rosetta(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::RosettaError(ref md, ref error) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    500,
+                    HttpResponseType::error_reason(500),
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| metadata_headers(fd, md),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, error)?;
+            }
+            HttpResponseType::HealthLive(ref md, ref data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
+            HttpResponseType::PeerPolicy(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::HealthReady(ref md, ref data) => {
+                if data.ready {
+                    HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                } else {
+                    HttpResponsePreamble::new_serialized(
+                        fd,
+                        503,
+                        HttpResponseType::error_reason(503),
+                        md.content_length.clone(),
+                        &HttpContentType::JSON,
+                        md.request_id,
+                        |ref mut fd| metadata_headers(fd, md),
+                    )?;
+                }
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
             HttpResponseType::GetContractABI(ref md, ref data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
+            HttpResponseType::GetContractStorage(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::PostConditionCheck(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
             HttpResponseType::GetContractSrc(ref md, ref data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
@@ -4077,6 +6938,17 @@ impl HttpResponseType {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
+            HttpResponseType::CallReadOnlyFunctionBatch(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::WebSocketAccepted(ref md, ref accept_key) => {
+                HttpResponsePreamble::websocket_accept_from_md(fd, md, accept_key)?;
+            }
+            HttpResponseType::CheckContractCallArgs(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
             HttpResponseType::GetDataVar(ref md, ref var_data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, var_data)?;
@@ -4085,6 +6957,10 @@ impl HttpResponseType {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, map_data)?;
             }
+            HttpResponseType::GetMapEntryBatch(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
             HttpResponseType::PeerInfo(ref md, ref peer_info) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, peer_info)?;
@@ -4093,10 +6969,30 @@ impl HttpResponseType {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, pox_info)?;
             }
+            HttpResponseType::PoxCycle(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::PoxRewardSet(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::PoxStacker(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
             HttpResponseType::Neighbors(ref md, ref neighbor_data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, neighbor_data)?;
             }
+            HttpResponseType::NeighborsDetail(ref md, ref detail_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, detail_data)?;
+            }
+            HttpResponseType::Telemetry(ref md, ref telemetry_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, telemetry_data)?;
+            }
             HttpResponseType::GetAttachment(ref md, ref zonefile_data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, zonefile_data)?;
@@ -4113,7 +7009,7 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, headers)?;
             }
@@ -4127,7 +7023,7 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
             }
             HttpResponseType::Block(ref md, ref block) => {
@@ -4138,7 +7034,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_bytestream(protocol, md, fd, block)?;
             }
@@ -4152,9 +7048,42 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
+                )?;
+            }
+            HttpResponseType::BlockExport(ref md, ref blocks) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| metadata_headers(fd, md),
+                )?;
+                HttpResponseType::send_bytestream(protocol, md, fd, blocks)?;
+            }
+            HttpResponseType::BlockExportStream(ref md) => {
+                // only send the preamble.  The caller will need to figure out how to send along
+                // the exported blocks themselves.
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    None,
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
             }
+            HttpResponseType::BlockStateDiff(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::MicroblockStreamPage(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
             HttpResponseType::Microblocks(ref md, ref microblocks) => {
                 HttpResponsePreamble::new_serialized(
                     fd,
@@ -4163,7 +7092,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_bytestream(protocol, md, fd, microblocks)?;
             }
@@ -4177,7 +7106,7 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
             }
             HttpResponseType::TransactionID(ref md, ref txid) => {
@@ -4189,10 +7118,14 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, &txid_bytes)?;
             }
+            HttpResponseType::TransactionAdmissionReport(ref md, ref report) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, report)?;
+            }
             HttpResponseType::StacksBlockAccepted(ref md, ref stacks_block_id, ref accepted) => {
                 let accepted_data = StacksBlockAcceptedData {
                     stacks_block_id: stacks_block_id.clone(),
@@ -4205,7 +7138,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, &accepted_data)?;
             }
@@ -4218,7 +7151,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, &mblock_bytes)?;
             }
@@ -4236,7 +7169,7 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
             }
             HttpResponseType::MemPoolTxs(ref md, ref page_id, ref txs) => {
@@ -4247,7 +7180,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 match page_id {
                     Some(txid) => {
@@ -4279,10 +7212,21 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::Text,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_text(protocol, md, fd, "".as_bytes())?;
             }
+            HttpResponseType::NotModified(ref md) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    304,
+                    "Not Modified",
+                    Some(0),
+                    &HttpContentType::Text,
+                    md.request_id,
+                    |ref mut fd| metadata_headers(fd, md),
+                )?;
+            }
             HttpResponseType::BadRequestJSON(ref md, ref data) => {
                 HttpResponsePreamble::new_serialized(
                     fd,
@@ -4291,7 +7235,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| metadata_headers(fd, md),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
@@ -4300,6 +7244,7 @@ impl HttpResponseType {
             HttpResponseType::PaymentRequired(_, ref msg) => self.error_response(fd, 402, msg)?,
             HttpResponseType::Forbidden(_, ref msg) => self.error_response(fd, 403, msg)?,
             HttpResponseType::NotFound(_, ref msg) => self.error_response(fd, 404, msg)?,
+            HttpResponseType::TooManyRequests(_, ref msg) => self.error_response(fd, 429, msg)?,
             HttpResponseType::ServerError(_, ref msg) => self.error_response(fd, 500, msg)?,
             HttpResponseType::ServiceUnavailable(_, ref msg) => {
                 self.error_response(fd, 503, msg)?
@@ -4362,32 +7307,61 @@ impl MessageSequence for StacksHttpMessage {
         match *self {
             StacksHttpMessage::Request(ref req) => match req {
                 HttpRequestType::GetInfo(_) => "HTTP(GetInfo)",
+                HttpRequestType::WebSocketUpgrade(..) => "HTTP(WebSocketUpgrade)",
                 HttpRequestType::GetPoxInfo(_, _) => "HTTP(GetPoxInfo)",
+                HttpRequestType::GetPoxCycle(..) => "HTTP(GetPoxCycle)",
+                HttpRequestType::GetPoxRewardSet(..) => "HTTP(GetPoxRewardSet)",
+                HttpRequestType::GetPoxStacker(..) => "HTTP(GetPoxStacker)",
                 HttpRequestType::GetNeighbors(_) => "HTTP(GetNeighbors)",
+                HttpRequestType::GetNeighborsDetail(_) => "HTTP(GetNeighborsDetail)",
+                HttpRequestType::GetTelemetry(_) => "HTTP(GetTelemetry)",
                 HttpRequestType::GetHeaders(..) => "HTTP(GetHeaders)",
                 HttpRequestType::GetBlock(_, _) => "HTTP(GetBlock)",
+                HttpRequestType::GetBlockExport(..) => "HTTP(GetBlockExport)",
+                HttpRequestType::GetBlockStateDiff(..) => "HTTP(GetBlockStateDiff)",
                 HttpRequestType::GetMicroblocksIndexed(_, _) => "HTTP(GetMicroblocksIndexed)",
                 HttpRequestType::GetMicroblocksConfirmed(_, _) => "HTTP(GetMicroblocksConfirmed)",
                 HttpRequestType::GetMicroblocksUnconfirmed(_, _, _) => {
                     "HTTP(GetMicroblocksUnconfirmed)"
                 }
+                HttpRequestType::GetMicroblockStream(..) => "HTTP(GetMicroblockStream)",
                 HttpRequestType::GetTransactionUnconfirmed(_, _) => {
                     "HTTP(GetTransactionUnconfirmed)"
                 }
-                HttpRequestType::PostTransaction(_, _, _) => "HTTP(PostTransaction)",
+                HttpRequestType::PostTransaction(..) => "HTTP(PostTransaction)",
                 HttpRequestType::PostBlock(..) => "HTTP(PostBlock)",
                 HttpRequestType::PostMicroblock(..) => "HTTP(PostMicroblock)",
                 HttpRequestType::GetAccount(..) => "HTTP(GetAccount)",
+                HttpRequestType::GetAccountProof(..) => "HTTP(GetAccountProof)",
                 HttpRequestType::GetDataVar(..) => "HTTP(GetDataVar)",
                 HttpRequestType::GetMapEntry(..) => "HTTP(GetMapEntry)",
+                HttpRequestType::GetMapEntryBatch(..) => "HTTP(GetMapEntryBatch)",
                 HttpRequestType::GetTransferCost(_) => "HTTP(GetTransferCost)",
                 HttpRequestType::GetContractABI(..) => "HTTP(GetContractABI)",
+                HttpRequestType::GetContractStorage(..) => "HTTP(GetContractStorage)",
+                HttpRequestType::PostConditionCheck(..) => "HTTP(PostConditionCheck)",
                 HttpRequestType::GetContractSrc(..) => "HTTP(GetContractSrc)",
                 HttpRequestType::GetIsTraitImplemented(..) => "HTTP(GetIsTraitImplemented)",
                 HttpRequestType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
+                HttpRequestType::CallReadOnlyFunctionBatch(..) => "HTTP(CallReadOnlyFunctionBatch)",
+                HttpRequestType::CheckContractCallArgs(..) => "HTTP(CheckContractCallArgs)",
                 HttpRequestType::GetAttachment(..) => "HTTP(GetAttachment)",
                 HttpRequestType::GetAttachmentsInv(..) => "HTTP(GetAttachmentsInv)",
                 HttpRequestType::MemPoolQuery(..) => "HTTP(MemPoolQuery)",
+                HttpRequestType::GetContractEvents(..) => "HTTP(GetContractEvents)",
+                HttpRequestType::GetContractMetrics(..) => "HTTP(GetContractMetrics)",
+                HttpRequestType::GetChainstateManifest(..) => "HTTP(GetChainstateManifest)",
+                HttpRequestType::GetMemPoolTransactions(..) => "HTTP(GetMemPoolTransactions)",
+                HttpRequestType::GetMemPoolTransaction(..) => "HTTP(GetMemPoolTransaction)",
+                HttpRequestType::GetAddressTransactions(..) => "HTTP(GetAddressTransactions)",
+                HttpRequestType::GetAddressAssets(..) => "HTTP(GetAddressAssets)",
+                HttpRequestType::GetAddressNames(..) => "HTTP(GetAddressNames)",
+                HttpRequestType::GetName(..) => "HTTP(GetName)",
+                HttpRequestType::Rosetta(..) => "HTTP(Rosetta)",
+                HttpRequestType::GetHealthLive(..) => "HTTP(GetHealthLive)",
+                HttpRequestType::GetHealthReady(..) => "HTTP(GetHealthReady)",
+                HttpRequestType::GetPeerPolicy(..) => "HTTP(GetPeerPolicy)",
+                HttpRequestType::PostPeerPolicy(..) => "HTTP(PostPeerPolicy)",
                 HttpRequestType::OptionsPreflight(..) => "HTTP(OptionsPreflight)",
                 HttpRequestType::ClientError(..) => "HTTP(ClientError)",
                 HttpRequestType::FeeRateEstimate(_, _, _) => "HTTP(FeeRateEstimate)",
@@ -4396,29 +7370,51 @@ impl MessageSequence for StacksHttpMessage {
                 HttpResponseType::TokenTransferCost(_, _) => "HTTP(TokenTransferCost)",
                 HttpResponseType::GetDataVar(_, _) => "HTTP(GetDataVar)",
                 HttpResponseType::GetMapEntry(_, _) => "HTTP(GetMapEntry)",
+                HttpResponseType::GetMapEntryBatch(_, _) => "HTTP(GetMapEntryBatch)",
                 HttpResponseType::GetAccount(_, _) => "HTTP(GetAccount)",
+                HttpResponseType::AccountProof(_, _) => "HTTP(AccountProof)",
                 HttpResponseType::GetContractABI(..) => "HTTP(GetContractABI)",
+                HttpResponseType::GetContractStorage(..) => "HTTP(GetContractStorage)",
+                HttpResponseType::PostConditionCheck(..) => "HTTP(PostConditionCheck)",
                 HttpResponseType::GetContractSrc(..) => "HTTP(GetContractSrc)",
                 HttpResponseType::GetIsTraitImplemented(..) => "HTTP(GetIsTraitImplemented)",
                 HttpResponseType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
+                HttpResponseType::CallReadOnlyFunctionBatch(..) => {
+                    "HTTP(CallReadOnlyFunctionBatch)"
+                }
+                HttpResponseType::CheckContractCallArgs(..) => "HTTP(CheckContractCallArgs)",
+                HttpResponseType::WebSocketAccepted(..) => "HTTP(WebSocketAccepted)",
                 HttpResponseType::GetAttachment(_, _) => "HTTP(GetAttachment)",
                 HttpResponseType::GetAttachmentsInv(_, _) => "HTTP(GetAttachmentsInv)",
                 HttpResponseType::PeerInfo(_, _) => "HTTP(PeerInfo)",
                 HttpResponseType::PoxInfo(_, _) => "HTTP(PeerInfo)",
+                HttpResponseType::PoxCycle(..) => "HTTP(PoxCycle)",
+                HttpResponseType::PoxRewardSet(..) => "HTTP(PoxRewardSet)",
+                HttpResponseType::PoxStacker(..) => "HTTP(PoxStacker)",
                 HttpResponseType::Neighbors(_, _) => "HTTP(Neighbors)",
+                HttpResponseType::NeighborsDetail(_, _) => "HTTP(NeighborsDetail)",
+                HttpResponseType::Telemetry(_, _) => "HTTP(Telemetry)",
                 HttpResponseType::Headers(..) => "HTTP(Headers)",
                 HttpResponseType::HeaderStream(..) => "HTTP(HeaderStream)",
                 HttpResponseType::Block(_, _) => "HTTP(Block)",
                 HttpResponseType::BlockStream(_) => "HTTP(BlockStream)",
+                HttpResponseType::BlockExport(_, _) => "HTTP(BlockExport)",
+                HttpResponseType::BlockExportStream(_) => "HTTP(BlockExportStream)",
+                HttpResponseType::BlockStateDiff(..) => "HTTP(BlockStateDiff)",
                 HttpResponseType::Microblocks(_, _) => "HTTP(Microblocks)",
                 HttpResponseType::MicroblockStream(_) => "HTTP(MicroblockStream)",
+                HttpResponseType::MicroblockStreamPage(..) => "HTTP(MicroblockStreamPage)",
                 HttpResponseType::TransactionID(_, _) => "HTTP(Transaction)",
+                HttpResponseType::TransactionAdmissionReport(..) => {
+                    "HTTP(TransactionAdmissionReport)"
+                }
                 HttpResponseType::StacksBlockAccepted(..) => "HTTP(StacksBlockAccepted)",
                 HttpResponseType::MicroblockHash(_, _) => "HTTP(MicroblockHash)",
                 HttpResponseType::UnconfirmedTransaction(_, _) => "HTTP(UnconfirmedTransaction)",
                 HttpResponseType::MemPoolTxStream(..) => "HTTP(MemPoolTxStream)",
                 HttpResponseType::MemPoolTxs(..) => "HTTP(MemPoolTxs)",
                 HttpResponseType::OptionsPreflight(_) => "HTTP(OptionsPreflight)",
+                HttpResponseType::NotModified(_) => "HTTP(NotModified)",
                 HttpResponseType::BadRequestJSON(..) | HttpResponseType::BadRequest(..) => {
                     "HTTP(400)"
                 }
@@ -4427,11 +7423,26 @@ impl MessageSequence for StacksHttpMessage {
                 HttpResponseType::Forbidden(_, _) => "HTTP(403)",
                 HttpResponseType::NotFound(_, _) => "HTTP(404)",
                 HttpResponseType::ServerError(_, _) => "HTTP(500)",
+                HttpResponseType::TooManyRequests(_, _) => "HTTP(429)",
                 HttpResponseType::ServiceUnavailable(_, _) => "HTTP(503)",
                 HttpResponseType::Error(_, _, _) => "HTTP(other)",
                 HttpResponseType::TransactionFeeEstimation(_, _) => {
                     "HTTP(TransactionFeeEstimation)"
                 }
+                HttpResponseType::ContractEvents(..) => "HTTP(ContractEvents)",
+                HttpResponseType::ContractMetrics(..) => "HTTP(ContractMetrics)",
+                HttpResponseType::ChainstateManifest(..) => "HTTP(ChainstateManifest)",
+                HttpResponseType::MemPoolTransactions(..) => "HTTP(MemPoolTransactions)",
+                HttpResponseType::MemPoolTransaction(..) => "HTTP(MemPoolTransaction)",
+                HttpResponseType::AddressTransactions(..) => "HTTP(AddressTransactions)",
+                HttpResponseType::AddressAssets(..) => "HTTP(AddressAssets)",
+                HttpResponseType::AddressNames(..) => "HTTP(AddressNames)",
+                HttpResponseType::Name(..) => "HTTP(Name)",
+                HttpResponseType::Rosetta(..) => "HTTP(Rosetta)",
+                HttpResponseType::RosettaError(..) => "HTTP(500)",
+                HttpResponseType::HealthLive(..) => "HTTP(HealthLive)",
+                HttpResponseType::HealthReady(..) => "HTTP(HealthReady)",
+                HttpResponseType::PeerPolicy(..) => "HTTP(PeerPolicy)",
             },
         }
     }
@@ -4678,7 +7689,6 @@ impl StacksHttp {
     }
 
     /// Given a HTTP request, serialize it out
-    #[cfg(test)]
     pub fn serialize_request(req: &HttpRequestType) -> Result<Vec<u8>, net_error> {
         let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
         let mut ret = vec![];
@@ -4687,7 +7697,6 @@ impl StacksHttp {
     }
 
     /// Given a fully-formed single HTTP response, parse it (used by clients).
-    #[cfg(test)]
     pub fn parse_response(
         request_path: &str,
         response_buf: &[u8],
@@ -4962,8 +7971,12 @@ mod test {
     use chainstate::stacks::TransactionPostConditionMode;
     use chainstate::stacks::TransactionVersion;
     use net::codec::test::check_codec_and_corruption;
+    use net::connection::ConnectionOptions;
     use net::test::*;
+    use net::ContractSrcResponse;
+    use net::PeerPolicyList;
     use net::RPCNeighbor;
+    use net::RPCNeighborQuality;
     use net::RPCNeighborsInfo;
     use util::hash::to_hex;
     use util::hash::Hash160;
@@ -5909,15 +8922,27 @@ mod test {
                 12345,
             ),
             keep_alive: true,
+            if_none_match: None,
+            accept_encoding: HttpContentEncoding::Identity,
+            api_key: None,
+            origin: None,
+            cors: HttpCorsHeaders::default(),
         };
         let http_request_metadata_dns = HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: PeerHost::DNS("www.foo.com".to_string(), 80),
             keep_alive: true,
+            if_none_match: None,
+            accept_encoding: HttpContentEncoding::Identity,
+            api_key: None,
+            origin: None,
+            cors: HttpCorsHeaders::default(),
         };
 
         let tests = vec![
             HttpRequestType::GetNeighbors(http_request_metadata_ip.clone()),
+            HttpRequestType::GetNeighborsDetail(http_request_metadata_ip.clone()),
+            HttpRequestType::GetTelemetry(http_request_metadata_dns.clone()),
             HttpRequestType::GetBlock(http_request_metadata_dns.clone(), StacksBlockId([2u8; 32])),
             HttpRequestType::GetMicroblocksIndexed(
                 http_request_metadata_ip.clone(),
@@ -5927,6 +8952,13 @@ mod test {
                 http_request_metadata_dns.clone(),
                 make_test_transaction(),
                 None,
+                false,
+            ),
+            HttpRequestType::PostTransaction(
+                http_request_metadata_dns.clone(),
+                make_test_transaction(),
+                None,
+                true,
             ),
             HttpRequestType::OptionsPreflight(http_request_metadata_ip.clone(), "/".to_string()),
         ];
@@ -5947,6 +8979,17 @@ mod test {
         post_transaction_preamble.set_content_type(HttpContentType::Bytes);
         post_transaction_preamble.set_content_length(tx_body.len() as u32);
 
+        let mut post_transaction_report_preamble = HttpRequestPreamble::new(
+            HttpVersion::Http11,
+            "POST".to_string(),
+            "/v2/transactions?report=1".to_string(),
+            http_request_metadata_dns.peer.hostname(),
+            http_request_metadata_dns.peer.port(),
+            http_request_metadata_dns.keep_alive,
+        );
+        post_transaction_report_preamble.set_content_type(HttpContentType::Bytes);
+        post_transaction_report_preamble.set_content_length(tx_body.len() as u32);
+
         // all of these should parse
         let expected_http_preambles = vec![
             HttpRequestPreamble::new(
@@ -5957,6 +9000,22 @@ mod test {
                 http_request_metadata_ip.peer.port(),
                 http_request_metadata_ip.keep_alive,
             ),
+            HttpRequestPreamble::new(
+                HttpVersion::Http11,
+                "GET".to_string(),
+                "/v2/neighbors/detail".to_string(),
+                http_request_metadata_ip.peer.hostname(),
+                http_request_metadata_ip.peer.port(),
+                http_request_metadata_ip.keep_alive,
+            ),
+            HttpRequestPreamble::new(
+                HttpVersion::Http11,
+                "GET".to_string(),
+                "/v2/telemetry".to_string(),
+                http_request_metadata_dns.peer.hostname(),
+                http_request_metadata_dns.peer.port(),
+                http_request_metadata_dns.keep_alive,
+            ),
             HttpRequestPreamble::new(
                 HttpVersion::Http11,
                 "GET".to_string(),
@@ -5974,6 +9033,7 @@ mod test {
                 http_request_metadata_ip.keep_alive,
             ),
             post_transaction_preamble,
+            post_transaction_report_preamble,
             HttpRequestPreamble::new(
                 HttpVersion::Http11,
                 "OPTIONS".to_string(),
@@ -5984,7 +9044,15 @@ mod test {
             ),
         ];
 
-        let expected_http_bodies = vec![vec![], vec![], vec![], tx_body];
+        let expected_http_bodies = vec![
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            tx_body.clone(),
+            tx_body,
+        ];
 
         for (test, (expected_http_preamble, expected_http_body)) in tests.iter().zip(
             expected_http_preambles
@@ -6077,6 +9145,7 @@ mod test {
                     )
                     .unwrap(),
                     authenticated: true,
+                    quality: None,
                 },
                 RPCNeighbor {
                     network_id: 3,
@@ -6091,6 +9160,12 @@ mod test {
                     )
                     .unwrap(),
                     authenticated: false,
+                    quality: Some(RPCNeighborQuality {
+                        rtt_ms: Some(150),
+                        request_success_rate: Some(0.75),
+                        bytes_tx: 1024,
+                        bytes_rx: 4096,
+                    }),
                 },
             ],
             inbound: vec![],
@@ -6508,6 +9583,209 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_http_etag_matches() {
+        let mut md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        assert!(!md.etag_matches("abcd"));
+
+        md.if_none_match = Some("\"abcd\"".to_string());
+        assert!(md.etag_matches("abcd"));
+        assert!(!md.etag_matches("abc"));
+
+        md.if_none_match = Some("\"1234\", W/\"abcd\"".to_string());
+        assert!(md.etag_matches("abcd"));
+        assert!(md.etag_matches("1234"));
+        assert!(!md.etag_matches("5678"));
+
+        md.if_none_match = Some("*".to_string());
+        assert!(md.etag_matches("5678"));
+    }
+
+    #[test]
+    fn test_http_response_not_modified() {
+        let response = HttpResponseType::NotModified(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(0), true)
+                .with_etag("abcd".to_string()),
+        );
+        let request_path =
+            "/v2/blocks/1111111111111111111111111111111111111111111111111111111111111111";
+
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let mut bytes = vec![];
+        http.begin_request(HttpVersion::Http11, request_path.to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        match preamble {
+            StacksHttpPreamble::Response(ref resp) => {
+                assert_eq!(resp.status_code, 304);
+                assert_eq!(resp.headers.get("etag"), Some(&"\"abcd\"".to_string()));
+            }
+            StacksHttpPreamble::Request(_) => {
+                panic!("parsed a request");
+            }
+        }
+
+        let (message, _) = http.read_payload(&preamble, &bytes[offset..]).unwrap();
+        assert_eq!(message, StacksHttpMessage::Response(response));
+        assert_eq!(http.num_pending(), 0);
+    }
+
+    #[test]
+    fn test_http_response_cors_policy() {
+        let mut opts = ConnectionOptions::default();
+
+        // any origin may call a stock node
+        assert_eq!(
+            opts.cors_headers(Some("https://wallet.example"), false),
+            HttpCorsHeaders::default()
+        );
+
+        opts.rpc_cors_allowed_origins = vec!["https://wallet.example/".to_string()];
+        opts.rpc_cors_allowed_methods = vec!["GET".to_string(), "OPTIONS".to_string()];
+        opts.rpc_cors_max_age = Some(600);
+
+        assert_eq!(
+            opts.cors_headers(Some("https://other.example"), true)
+                .allow_origin,
+            None
+        );
+        assert_eq!(opts.cors_headers(None, true).allow_origin, None);
+        assert_eq!(
+            opts.cors_headers(Some("https://wallet.example"), false)
+                .max_age,
+            None
+        );
+
+        let request_path =
+            "/v2/blocks/1111111111111111111111111111111111111111111111111111111111111111";
+        let mut md = HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(0), true)
+            .with_etag("abcd".to_string());
+        md.cors = opts.cors_headers(Some("https://wallet.example"), true);
+        let response = HttpResponseType::NotModified(md);
+
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let mut bytes = vec![];
+        http.begin_request(HttpVersion::Http11, request_path.to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        match preamble {
+            StacksHttpPreamble::Response(ref resp) => {
+                let header = |name: &str| resp.headers.get(name).map(|value| value.as_str());
+                assert_eq!(
+                    header("access-control-allow-origin"),
+                    Some("https://wallet.example")
+                );
+                assert_eq!(header("access-control-allow-methods"), Some("GET, OPTIONS"));
+                assert_eq!(header("access-control-max-age"), Some("600"));
+                assert_eq!(header("vary"), Some("Origin"));
+            }
+            StacksHttpPreamble::Request(_) => {
+                panic!("parsed a request");
+            }
+        }
+
+        let (message, _) = http.read_payload(&preamble, &bytes[offset..]).unwrap();
+        assert_eq!(message, StacksHttpMessage::Response(response));
+
+        // no CORS headers at all for origins that aren't allowed
+        let mut md = HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(0), true)
+            .with_etag("abcd".to_string());
+        md.cors = opts.cors_headers(Some("https://other.example"), true);
+        let mut bytes = vec![];
+        http.begin_request(HttpVersion::Http11, request_path.to_string());
+        http.write_message(
+            &mut bytes,
+            &StacksHttpMessage::Response(HttpResponseType::NotModified(md)),
+        )
+        .unwrap();
+        let txt = String::from_utf8(bytes).unwrap();
+        assert!(txt.find("Access-Control-").is_none());
+    }
+
+    #[test]
+    fn test_http_content_encoding_negotiate() {
+        let tests = vec![
+            ("", HttpContentEncoding::Identity),
+            ("identity", HttpContentEncoding::Identity),
+            ("br", HttpContentEncoding::Identity),
+            ("gzip", HttpContentEncoding::Gzip),
+            ("deflate", HttpContentEncoding::Deflate),
+            ("deflate, gzip", HttpContentEncoding::Gzip),
+            ("gzip;q=0.5, deflate", HttpContentEncoding::Deflate),
+            ("gzip;q=0, deflate;q=0", HttpContentEncoding::Identity),
+            ("*", HttpContentEncoding::Gzip),
+            ("gzip;q=0, *", HttpContentEncoding::Deflate),
+            ("br, GZIP ; q=0.8", HttpContentEncoding::Gzip),
+        ];
+        for (accept_encoding, expected) in tests {
+            assert_eq!(
+                HttpContentEncoding::negotiate(accept_encoding),
+                expected,
+                "Accept-Encoding: {}",
+                accept_encoding
+            );
+        }
+    }
+
+    #[test]
+    fn test_http_response_compressed() {
+        let contract_src = ContractSrcResponse {
+            source: "(define-read-only (foo) u1)\n".repeat(100),
+            publish_height: 123,
+            marf_proof: None,
+        };
+        let request_path =
+            "/v2/contracts/source/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R/hello-world?proof=0";
+
+        for encoding in [HttpContentEncoding::Gzip, HttpContentEncoding::Deflate].iter() {
+            let response = HttpResponseType::GetContractSrc(
+                HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true)
+                    .with_content_encoding(*encoding),
+                contract_src.clone(),
+            );
+
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            let mut bytes = vec![];
+            http.begin_request(HttpVersion::Http11, request_path.to_string());
+            http.write_message(&mut bytes, &StacksHttpMessage::Response(response))
+                .unwrap();
+
+            let uncompressed_len = serde_json::to_string(&contract_src).unwrap().len();
+            assert!(bytes.len() < uncompressed_len);
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            match preamble {
+                StacksHttpPreamble::Response(ref resp) => {
+                    assert!(resp.is_chunked());
+                    assert_eq!(
+                        resp.headers.get("content-encoding"),
+                        Some(&encoding.to_string())
+                    );
+                }
+                StacksHttpPreamble::Request(_) => {
+                    panic!("parsed a request");
+                }
+            }
+
+            let (message_opt, _) = http
+                .stream_payload(&preamble, &mut &bytes[offset..])
+                .unwrap();
+            match message_opt.unwrap().0 {
+                StacksHttpMessage::Response(HttpResponseType::GetContractSrc(md, data)) => {
+                    assert_eq!(md.content_encoding, *encoding);
+                    assert_eq!(data, contract_src);
+                }
+                msg => {
+                    panic!("Unexpected message {:?}", &msg);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_http_response_type_codec_err() {
         let request_paths = vec![
@@ -6832,7 +10110,7 @@ mod test {
                 None,
                 &HttpContentType::JSON,
                 123,
-                |ref mut fd| keep_alive_headers(fd, &md),
+                |ref mut fd| metadata_headers(fd, &md),
             )
             .unwrap();
             responses.push(String::from_utf8(bytes).unwrap());
@@ -6925,6 +10203,99 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_http_parse_contract_events_query() {
+        let parse = |request: &str| -> ContractEventsQuery {
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+            match http.read_payload(&preamble, &request.as_bytes()[offset..]) {
+                Ok((
+                    StacksHttpMessage::Request(HttpRequestType::GetContractEvents(_, query)),
+                    _,
+                )) => query,
+                res => panic!("Expected GetContractEvents, got {:?}", &res),
+            }
+        };
+        let contract = QualifiedContractIdentifier::parse(
+            "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world",
+        )
+        .unwrap();
+
+        // the contract's own events path returns every event type by default
+        let query = parse("GET /v2/contracts/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world/events?topic=coin&from_block=2&to_block=5 HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: localhost:6270\r\n\r\n");
+        assert_eq!(query.contract, contract);
+        assert_eq!(
+            query.filter,
+            ContractEventFilter {
+                event_type: None,
+                topic: Some("coin".to_string()),
+                from_block: 2,
+                to_block: Some(5),
+            }
+        );
+
+        let query = parse("GET /v2/contracts/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world/events?event_type=ft_mint_event HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: localhost:6270\r\n\r\n");
+        assert_eq!(query.filter.event_type, Some(ContractEventType::FTMint));
+
+        // /v2/events only returns `print` events, unless asked for another type
+        let query = parse("GET /v2/events?contract=ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: localhost:6270\r\n\r\n");
+        assert_eq!(query.contract, contract);
+        assert_eq!(query.filter.event_type, Some(ContractEventType::Print));
+
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let request = "GET /v2/contracts/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world/events?event_type=stx_transfer_event HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: localhost:6270\r\n\r\n";
+        let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+        assert!(http
+            .read_payload(&preamble, &request.as_bytes()[offset..])
+            .is_err());
+    }
+
+    #[test]
+    fn test_http_parse_peer_policy_update() {
+        let update = PeerPolicyUpdate {
+            list: PeerPolicyList::Deny,
+            remove: false,
+            cidr: Some(PeerAddress::from_cidr_str("198.51.100.0/24").unwrap()),
+            asn: Some(64496),
+        };
+        let mut md = HttpRequestMetadata::from_host(PeerHost::from_host_port(
+            "localhost".to_string(),
+            20443,
+        ));
+        md.api_key = Some("admin".to_string());
+        let request = HttpRequestType::PostPeerPolicy(md, update.clone());
+
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let mut bytes = vec![];
+        request.send(&mut http, &mut bytes).unwrap();
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        match http.read_payload(&preamble, &bytes[offset..]) {
+            Ok((StacksHttpMessage::Request(HttpRequestType::PostPeerPolicy(md, parsed)), _)) => {
+                assert_eq!(parsed, update);
+                assert_eq!(md.api_key, Some("admin".to_string()));
+            }
+            res => panic!("Expected PostPeerPolicy, got {:?}", &res),
+        }
+
+        let bad_bodies = [
+            r#"{"list":"deny"}"#,
+            r#"{"list":"deny","cidr":"198.51.100.0/33"}"#,
+            r#"{"list":"deny","asn":0}"#,
+            r#"{"list":"maybe","asn":64496}"#,
+        ];
+        for body in bad_bodies.iter() {
+            let request = format!("POST /v2/admin/peer_policy HTTP/1.1\r\nHost: localhost:20443\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+            assert!(
+                http.read_payload(&preamble, &request.as_bytes()[offset..])
+                    .is_err(),
+                "body should not have parsed: {}",
+                body
+            );
+        }
+    }
+
     #[test]
     fn test_http_live_headers() {
         // headers pulled from prod
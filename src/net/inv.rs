@@ -1142,6 +1142,17 @@ impl InvState {
         list
     }
 
+    /// Get the list of stale peers
+    pub fn get_stale_peers(&self) -> Vec<NeighborKey> {
+        let mut list = vec![];
+        for (nk, stats) in self.block_stats.iter() {
+            if stats.status == NodeStatus::Stale {
+                list.push(nk.clone());
+            }
+        }
+        list
+    }
+
     /// Get the list of dead
     pub fn get_dead_peers(&self) -> Vec<NeighborKey> {
         let mut list = vec![];
@@ -2435,6 +2446,9 @@ impl PeerNetwork {
                 let mut random_neighbor_list: Vec<_> = new_sync_peers.into_iter().collect();
                 random_neighbor_list.shuffle(&mut thread_rng());
 
+                // neighbors with bad reputations go last
+                random_neighbor_list.sort_by_key(|nk| network.is_peer_deprioritized(nk));
+
                 // always pick permanently-allowed peers
                 let mut good_sync_peers_set = HashSet::new();
                 let mut random_sync_peers_list = vec![];
@@ -3368,10 +3382,10 @@ mod test {
         };
 
         peer_1
-            .with_network_state(|sortdb, chainstate, network, _relayer, _mempool| {
+            .with_network_state(|sortdb, chainstate, network, _relayer, mempool| {
                 network.refresh_local_peer().unwrap();
                 network
-                    .refresh_burnchain_view(sortdb, chainstate, false)
+                    .refresh_burnchain_view(sortdb, chainstate, mempool, false)
                     .unwrap();
                 network.refresh_sortition_view(sortdb).unwrap();
                 Ok(())
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::borrow::Borrow;
+use std::cmp;
 use std::cmp::PartialEq;
 use std::collections::{HashMap, HashSet};
 use std::convert::From;
@@ -47,7 +48,17 @@ use crate::util::boot::boot_code_tx_auth;
 use burnchains::Txid;
 use chainstate::burn::ConsensusHash;
 use chainstate::coordinator::Error as coordinator_error;
+use chainstate::stacks::db::accounts::AccountProof;
+use chainstate::stacks::db::address_transactions::AddressTransactionCursor;
 use chainstate::stacks::db::blocks::MemPoolRejection;
+use chainstate::stacks::db::contract_events::{ContractEventCursor, ContractEventFilter};
+use chainstate::stacks::db::contracts::ReadOnlyCall;
+use chainstate::stacks::db::manifest::ChainstateManifest;
+use chainstate::stacks::events::{
+    FTBurnEventData, FTEventType, FTTransferEventData, NFTBurnEventData, NFTEventType,
+    NFTTransferEventData, STXBurnEventData, STXEventType, STXTransferEventData,
+    StacksTransactionEvent,
+};
 use chainstate::stacks::index::Error as marf_error;
 use chainstate::stacks::Error as chainstate_error;
 use chainstate::stacks::{
@@ -61,6 +72,7 @@ use codec::{read_next, write_next};
 use core::mempool::*;
 use core::POX_REWARD_CYCLE_LENGTH;
 use net::atlas::{Attachment, AttachmentInstance};
+use net::rosetta::{RosettaEndpoint, RosettaError};
 use util::bloom::{BloomFilter, BloomNodeHasher};
 use util::db::DBConn;
 use util::db::Error as db_error;
@@ -74,14 +86,14 @@ use util::secp256k1::MessageSignature;
 use util::secp256k1::Secp256k1PublicKey;
 use util::secp256k1::MESSAGE_SIGNATURE_ENCODED_SIZE;
 use util::strings::UrlString;
-use vm::types::TraitIdentifier;
+use vm::types::{AssetIdentifier, QualifiedContractIdentifier, TraitIdentifier};
 use vm::{
-    analysis::contract_interface_builder::ContractInterface, types::PrincipalData, ClarityName,
-    ContractName, Value,
+    analysis::contract_interface_builder::ContractInterface, analysis::ContractStorageLayout,
+    types::PrincipalData, ClarityName, ContractName, Value,
 };
 
 use crate::codec::BURNCHAIN_HEADER_HASH_ENCODED_SIZE;
-use crate::cost_estimates::FeeRateEstimate;
+use crate::cost_estimates::{DimensionFeeRateEstimates, FeeRateEstimate};
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::PoxId;
 use crate::types::chainstate::{
@@ -98,21 +110,43 @@ use core::StacksEpoch;
 
 pub mod asn;
 pub mod atlas;
+pub mod backoff;
+pub mod blockoffer;
+pub mod capabilities;
 pub mod chat;
 pub mod codec;
+pub mod compact;
 pub mod connection;
 pub mod db;
 pub mod dns;
 pub mod download;
+pub mod headers;
 pub mod http;
 pub mod inv;
 pub mod neighbors;
+pub mod noise;
 pub mod p2p;
 pub mod poll;
+pub mod portmap;
 pub mod prune;
+pub mod ratelimit;
 pub mod relay;
+pub mod relaypolicy;
+pub mod reputation;
+pub mod response_cache;
+pub mod rosetta;
 pub mod rpc;
+pub mod seeds;
 pub mod server;
+pub mod slots;
+pub mod socks;
+pub mod tls;
+pub mod txgossip;
+#[cfg(unix)]
+pub mod unix;
+pub mod warmstart;
+pub mod watchdog;
+pub mod ws;
 
 #[derive(Debug)]
 pub enum Error {
@@ -222,6 +256,14 @@ pub enum Error {
     Transient(String),
     /// Expected end-of-stream, but had more data
     ExpectedEndOfStream,
+    /// Failed to load or use TLS certificates or keys
+    TlsError(String),
+    /// Failed to set up or use an encrypted p2p session
+    NoiseError(String),
+    /// Failed to connect through a SOCKS5 proxy
+    ProxyError(String),
+    /// Failed to map a port on the gateway
+    PortMappingError(String),
 }
 
 impl From<codec_error> for Error {
@@ -320,6 +362,10 @@ impl fmt::Display for Error {
             Error::NotFoundError => write!(f, "Requested data not found"),
             Error::Transient(ref s) => write!(f, "Transient network error: {}", s),
             Error::ExpectedEndOfStream => write!(f, "Expected end-of-stream"),
+            Error::TlsError(ref s) => write!(f, "TLS error: {}", s),
+            Error::NoiseError(ref s) => write!(f, "Noise error: {}", s),
+            Error::ProxyError(ref s) => write!(f, "Proxy error: {}", s),
+            Error::PortMappingError(ref s) => write!(f, "Port mapping error: {}", s),
         }
     }
 }
@@ -380,6 +426,10 @@ impl error::Error for Error {
             Error::NotFoundError => None,
             Error::Transient(ref _s) => None,
             Error::ExpectedEndOfStream => None,
+            Error::TlsError(ref _s) => None,
+            Error::NoiseError(ref _s) => None,
+            Error::ProxyError(ref _s) => None,
+            Error::PortMappingError(ref _s) => None,
         }
     }
 }
@@ -605,6 +655,122 @@ impl PeerAddress {
             self.0[0] >= 0xfc
         }
     }
+
+    /// Unwrap an IPv4-mapped IPv6 socket address (which is how a dual-stack listener sees IPv4
+    /// clients) into its IPv4 form, so the same peer always has the same socket address.
+    pub fn canonical_socketaddr(addr: &SocketAddr) -> SocketAddr {
+        PeerAddress::from_socketaddr(addr).to_socketaddr(addr.port())
+    }
+
+    /// Parse a CIDR range like `10.0.0.0/8` or `2001:db8::/32` into its prefix and its mask,
+    /// where the mask counts bits of the 128-bit address (so an IPv4 `/8` has mask 104).
+    pub fn from_cidr_str(cidr: &str) -> Option<(PeerAddress, u32)> {
+        let mut parts = cidr.splitn(2, '/');
+        let ip = parts.next()?.parse::<IpAddr>().ok()?;
+        let bits = parts.next()?.parse::<u32>().ok()?;
+        let mask = match ip {
+            IpAddr::V4(_) if bits <= 32 => bits + 96,
+            IpAddr::V6(_) if bits > 0 && bits <= 128 => bits,
+            _ => return None,
+        };
+        Some((PeerAddress::from_ip(&ip), mask))
+    }
+
+    /// Render a CIDR prefix and 128-bit mask the way `from_cidr_str` parses it
+    pub fn to_cidr_string(&self, mask: u32) -> String {
+        let ip = self.to_socketaddr(0).ip();
+        if self.is_ipv4() && mask >= 96 {
+            format!("{}/{}", ip, mask - 96)
+        } else {
+            format!("{}/{}", ip, mask)
+        }
+    }
+}
+
+/// Which IP address families the node walks to and connects out to.  Inbound connections are
+/// accepted from either family regardless.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressFamilyPreference {
+    Any,
+    PreferIPv4,
+    PreferIPv6,
+    OnlyIPv4,
+    OnlyIPv6,
+}
+
+impl AddressFamilyPreference {
+    pub const ALL: &'static [AddressFamilyPreference] = &[
+        AddressFamilyPreference::Any,
+        AddressFamilyPreference::PreferIPv4,
+        AddressFamilyPreference::PreferIPv6,
+        AddressFamilyPreference::OnlyIPv4,
+        AddressFamilyPreference::OnlyIPv6,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AddressFamilyPreference::Any => "any",
+            AddressFamilyPreference::PreferIPv4 => "prefer_ipv4",
+            AddressFamilyPreference::PreferIPv6 => "prefer_ipv6",
+            AddressFamilyPreference::OnlyIPv4 => "ipv4_only",
+            AddressFamilyPreference::OnlyIPv6 => "ipv6_only",
+        }
+    }
+
+    /// Can we connect out to this address?  Onion addresses are reached through the proxy, so
+    /// they're always allowed.
+    pub fn allows(&self, addr: &PeerAddress) -> bool {
+        match *self {
+            AddressFamilyPreference::OnlyIPv4 => addr.is_ipv4() || addr.is_onion(),
+            AddressFamilyPreference::OnlyIPv6 => !addr.is_ipv4(),
+            _ => true,
+        }
+    }
+
+    /// Is this address in the family we'd rather connect to?
+    pub fn prefers(&self, addr: &PeerAddress) -> bool {
+        match *self {
+            AddressFamilyPreference::PreferIPv4 | AddressFamilyPreference::OnlyIPv4 => {
+                addr.is_ipv4()
+            }
+            AddressFamilyPreference::PreferIPv6 | AddressFamilyPreference::OnlyIPv6 => {
+                !addr.is_ipv4()
+            }
+            AddressFamilyPreference::Any => true,
+        }
+    }
+
+    /// Drop the items whose address we can't connect to, and move the ones in the preferred
+    /// family to the front (otherwise preserving their order).
+    pub fn sort<T, F>(&self, items: Vec<T>, addr_of: F) -> Vec<T>
+    where
+        F: Fn(&T) -> &PeerAddress,
+    {
+        let (mut preferred, mut rest): (Vec<T>, Vec<T>) = items
+            .into_iter()
+            .filter(|item| self.allows(addr_of(item)))
+            .partition(|item| self.prefers(addr_of(item)));
+        preferred.append(&mut rest);
+        preferred
+    }
+}
+
+impl fmt::Display for AddressFamilyPreference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for AddressFamilyPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AddressFamilyPreference, String> {
+        AddressFamilyPreference::ALL
+            .iter()
+            .find(|pref| pref.as_str() == s)
+            .cloned()
+            .ok_or_else(|| format!("Unknown address family preference: {}", s))
+    }
 }
 
 pub const STACKS_PUBLIC_KEY_ENCODED_SIZE: u32 = 33;
@@ -652,6 +818,105 @@ impl FromStr for HttpContentType {
     }
 }
 
+/// supported HTTP content encodings
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub enum HttpContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl fmt::Display for HttpContentEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl HttpContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            HttpContentEncoding::Identity => "identity",
+            HttpContentEncoding::Gzip => "gzip",
+            HttpContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Pick the encoding for a response, given the request's Accept-Encoding header.  Honors
+    /// q-values, and prefers gzip over deflate when the client likes both equally.
+    pub fn negotiate(accept_encoding: &str) -> HttpContentEncoding {
+        let mut gzip_q = None;
+        let mut deflate_q = None;
+        let mut any_q = None;
+        for item in accept_encoding.split(',') {
+            let mut parts = item.split(';');
+            let name = parts.next().unwrap_or("").trim().to_lowercase();
+            let mut q = 1.0f64;
+            for param in parts {
+                if let Some(value) = param.trim().strip_prefix("q=") {
+                    q = value.trim().parse().unwrap_or(0.0);
+                }
+            }
+            match name.as_str() {
+                "gzip" | "x-gzip" => gzip_q = Some(q),
+                "deflate" => deflate_q = Some(q),
+                "*" => any_q = Some(q),
+                _ => {}
+            }
+        }
+
+        let gzip_q = gzip_q.or(any_q).unwrap_or(0.0);
+        let deflate_q = deflate_q.or(any_q).unwrap_or(0.0);
+        if gzip_q > 0.0 && gzip_q >= deflate_q {
+            HttpContentEncoding::Gzip
+        } else if deflate_q > 0.0 {
+            HttpContentEncoding::Deflate
+        } else {
+            HttpContentEncoding::Identity
+        }
+    }
+}
+
+impl FromStr for HttpContentEncoding {
+    type Err = codec_error;
+
+    fn from_str(header: &str) -> Result<HttpContentEncoding, codec_error> {
+        let s = header.trim().to_lowercase();
+        if s == "identity" {
+            Ok(HttpContentEncoding::Identity)
+        } else if s == "gzip" || s == "x-gzip" {
+            Ok(HttpContentEncoding::Gzip)
+        } else if s == "deflate" {
+            Ok(HttpContentEncoding::Deflate)
+        } else {
+            Err(codec_error::DeserializeError(
+                "Unsupported HTTP content encoding".to_string(),
+            ))
+        }
+    }
+}
+
+/// The CORS headers to send with an HTTP response
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct HttpCorsHeaders {
+    /// the Access-Control-Allow-Origin value.  None means send no CORS headers.
+    pub allow_origin: Option<String>,
+    pub allow_methods: String,
+    pub allow_headers: String,
+    /// how long (in seconds) a browser may cache a preflight response
+    pub max_age: Option<u64>,
+}
+
+impl std::default::Default for HttpCorsHeaders {
+    fn default() -> HttpCorsHeaders {
+        HttpCorsHeaders {
+            allow_origin: Some("*".to_string()),
+            allow_methods: "POST, GET, OPTIONS".to_string(),
+            allow_headers: "origin, content-type".to_string(),
+            max_age: None,
+        }
+    }
+}
+
 /// HTTP request preamble
 #[derive(Debug, Clone, PartialEq)]
 pub struct HttpRequestPreamble {
@@ -741,6 +1006,133 @@ pub struct MicroblocksData {
     pub microblocks: Vec<StacksMicroblock>,
 }
 
+/// A canonical anchored block, along with the parent microblock stream it confirms.
+/// Sent in ascending height order by the block export stream, so a follower can replay the
+/// chain by storing the microblocks and then the block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedBlockData {
+    pub consensus_hash: ConsensusHash,
+    pub block: StacksBlock,
+    pub parent_consensus_hash: ConsensusHash,
+    pub parent_microblocks: Vec<StacksMicroblock>,
+}
+
+/// A 6-byte short transaction ID, as used in compact blocks.  It's the first 6 bytes of
+/// siphash(k0, k1, txid), where the keys are derived from the block hash and the sender's nonce
+/// (see `net::compact`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShortTxid(pub [u8; 6]);
+
+/// A transaction sent in full in a compact block, along with its index in the block
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefilledTransaction {
+    pub index: u16,
+    pub tx: StacksTransaction,
+}
+
+/// A new anchored block, announced as its header plus a short ID for each transaction the
+/// recipient probably already has in its mempool.  Transactions it probably doesn't have (like
+/// the coinbase) are sent in full.  The recipient rebuilds the block and asks for whatever it's
+/// missing with `GetBlockTxs`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactBlockData {
+    pub consensus_hash: ConsensusHash,
+    pub header: StacksBlockHeader,
+    pub nonce: u64,
+    /// short IDs of the transactions that were not prefilled, in block order
+    pub short_txids: Vec<ShortTxid>,
+    /// prefilled transactions, in ascending index order
+    pub prefilled_txs: Vec<PrefilledTransaction>,
+}
+
+/// Request for some of a block's transactions, by index
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetBlockTxsData {
+    pub consensus_hash: ConsensusHash,
+    pub block_hash: BlockHeaderHash,
+    /// transaction indexes, in ascending order
+    pub indexes: Vec<u16>,
+}
+
+/// Reply to `GetBlockTxs` -- the requested transactions, in the order they were requested
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTxsData {
+    pub consensus_hash: ConsensusHash,
+    pub block_hash: BlockHeaderHash,
+    pub txs: Vec<StacksTransaction>,
+}
+
+/// Request for mempool transactions that the sender does not have.  The filter describes the
+/// sender's mempool, in the same format as the RPC mempool query (see `MemPoolDB`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetMempoolTxsData {
+    pub filter: MemPoolSyncData,
+    /// the most transactions the sender wants back
+    pub max_txs: u32,
+}
+
+/// Reply to `GetMempoolTxs` -- transactions that were not in the requester's filter
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolTxsData {
+    pub txs: Vec<StacksTransaction>,
+}
+
+/// Anti-entropy offer of anchored blocks and confirmed microblock streams that the recipient's
+/// inventory says it's missing.  Both lists are index block hashes; a microblock stream is named
+/// by the anchored block that confirms it.  The same structure is used to accept an offer, in
+/// which case it lists the offered data the recipient wants pushed to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlocksOfferData {
+    pub blocks: Vec<StacksBlockId>,
+    pub microblocks: Vec<StacksBlockId>,
+}
+
+/// The version of one capability that a peer implements.  The capability is named by its
+/// `ServiceFlags` bit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityVersion {
+    pub service: u16,
+    pub version: u16,
+}
+
+/// The versions of the capabilities a peer advertises in its handshake.  Sent right after the
+/// handshake to peers that advertise `ServiceFlags::CAPABILITIES`.  A capability the peer
+/// advertises but doesn't list here is at version 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilitiesData {
+    pub versions: Vec<CapabilityVersion>,
+}
+
+/// Request for a contiguous run of block headers from one Stacks fork, in ascending height order.
+/// The fork is named by its tip; a tip of all zeros asks for the recipient's canonical fork.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetHeadersData {
+    pub tip: StacksBlockId,
+    /// height of the first header wanted
+    pub start_height: u64,
+    /// the most headers the sender wants back (the recipient sends at most `MAX_HEADERS`)
+    pub max_headers: u32,
+}
+
+/// Reply to `GetHeaders` -- the fork's headers from `start_height` up, each with the consensus
+/// hash of the sortition that chose it.  Also says which tip the headers were read from (the
+/// recipient's canonical tip, if that's what was asked for), and how high it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadersData {
+    pub tip: StacksBlockId,
+    pub tip_height: u64,
+    pub headers: Vec<ExtendedStacksHeader>,
+}
+
+/// A zlib-compressed message payload.  Large payloads (blocks, microblock streams, inventories,
+/// and so on) are sent this way to peers that advertise `ServiceFlags::COMPRESSION`.  The message
+/// signature covers the compressed bytes.  The codec decompresses these on receipt, so only the
+/// sender ever holds a `StacksMessageType::Compressed`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedData {
+    pub data: Vec<u8>,
+}
+
 /// Block available hint
 #[derive(Debug, Clone, PartialEq)]
 pub struct BlocksAvailableData {
@@ -815,10 +1207,26 @@ pub struct HandshakeData {
     pub data_url: UrlString,
 }
 
-#[repr(u8)]
+#[repr(u16)]
 pub enum ServiceFlags {
     RELAY = 0x01,
     RPC = 0x02,
+    /// accepts encrypted p2p connections (see `net::noise`)
+    ENCRYPTION = 0x04,
+    /// accepts compact blocks (see `net::compact`)
+    COMPACT_BLOCKS = 0x08,
+    /// answers `GetMempoolTxs` requests
+    MEMPOOL_TXS = 0x10,
+    /// accepts compressed message payloads (see `CompressedData`)
+    COMPRESSION = 0x20,
+    /// answers anti-entropy `BlocksOffer`s (see `net::blockoffer`)
+    BLOCK_OFFERS = 0x40,
+    /// mines blocks, and gets its own inbound connection slots (see `net::slots`)
+    MINER = 0x80,
+    /// sends and accepts `Capabilities` after handshaking (see `net::capabilities`)
+    CAPABILITIES = 0x100,
+    /// answers `GetHeaders` requests (see `net::headers`)
+    HEADERS = 0x200,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -837,6 +1245,7 @@ pub mod NackErrorCodes {
     pub const Throttled: u32 = 3;
     pub const InvalidPoxFork: u32 = 4;
     pub const InvalidMessage: u32 = 5;
+    pub const NoSuchBlock: u32 = 6;
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -895,6 +1304,17 @@ pub enum StacksMessageType {
     Pong(PongData),
     NatPunchRequest(u32),
     NatPunchReply(NatPunchData),
+    CompactBlock(CompactBlockData),
+    GetBlockTxs(GetBlockTxsData),
+    BlockTxs(BlockTxsData),
+    GetMempoolTxs(GetMempoolTxsData),
+    MempoolTxs(MempoolTxsData),
+    Compressed(CompressedData),
+    BlocksOffer(BlocksOfferData),
+    BlocksOfferAccept(BlocksOfferData),
+    Capabilities(CapabilitiesData),
+    GetHeaders(GetHeadersData),
+    Headers(HeadersData),
 }
 
 /// Peer address variants
@@ -1021,6 +1441,148 @@ pub struct RPCPeerInfoData {
     pub unanchored_tip: Option<StacksBlockId>,
     pub unanchored_seq: Option<u16>,
     pub exit_at_block_height: Option<u64>,
+    /// the most recent reorgs of the canonical Stacks chain, oldest first
+    #[serde(default)]
+    pub recent_reorgs: Vec<RPCChainReorgData>,
+}
+
+/// The canonical Stacks chain tip stopped advancing while the burnchain kept going, so the node
+/// is trying to recover (see `net::watchdog`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCStaleTipData {
+    pub stacks_tip: StacksBlockId,
+    pub stacks_tip_height: u64,
+    /// the burnchain block height at which `stacks_tip` became the tip
+    pub stale_since_burn_block_height: u64,
+    pub burn_block_height: u64,
+    /// how many times the node has tried to recover since `stacks_tip` became the tip
+    pub recovery_attempt: u64,
+}
+
+/// A change of the canonical Stacks chain tip to a block that does not descend from the old tip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCChainReorgData {
+    pub orphaned_tip: StacksBlockId,
+    pub orphaned_tip_height: u64,
+    pub new_tip: StacksBlockId,
+    pub new_tip_height: u64,
+    /// the highest block both tips descend from
+    pub common_ancestor: StacksBlockId,
+    pub common_ancestor_height: u64,
+    /// how many blocks of the old canonical fork were orphaned
+    pub depth: u64,
+    /// the burnchain block height at which the node switched to the new tip
+    pub burn_block_height: u64,
+}
+
+/// Anonymized node statistics reported by the (opt-in) telemetry beacon.  Nothing in here
+/// identifies the node's network address or p2p identity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTelemetryStats {
+    pub server_version: String,
+    pub network_id: u32,
+    pub burn_block_height: u64,
+    pub stacks_tip_height: u64,
+    pub num_peers: u64,
+    pub os: String,
+    pub arch: String,
+    pub timestamp: u64,
+}
+
+/// Telemetry beacon payload.  The signature is over the SHA256 hash of the JSON-encoded
+/// `stats`, and is made with a key generated when the node starts, so beacons from the same
+/// node process can be linked together (but not to the node's p2p identity).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTelemetryData {
+    pub stats: RPCTelemetryStats,
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// The data we return on GET /v2/health/live
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCHealthLiveData {
+    pub status: String,
+}
+
+/// How far the node's burnchain view trails the burnchain tip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCHealthBurnchainCheck {
+    pub ready: bool,
+    pub height: u64,
+    /// height of the burnchain tip, if the node has learned it yet
+    pub target_height: Option<u64>,
+    pub max_lag: u64,
+}
+
+/// How long ago the Stacks chain tip's burn block was mined
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCHealthStacksTipCheck {
+    pub ready: bool,
+    pub height: u64,
+    /// seconds since the tip's burn block timestamp, if the tip is known
+    pub age: Option<u64>,
+    pub max_age: u64,
+}
+
+/// How many authenticated p2p peers the node has
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCHealthPeersCheck {
+    pub ready: bool,
+    pub count: u64,
+    pub min_count: u64,
+}
+
+/// The data we return on GET /v2/health/ready.  Sent as a `200` if `ready`, and a `503`
+/// otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCHealthReadyData {
+    pub ready: bool,
+    pub burnchain: RPCHealthBurnchainCheck,
+    pub stacks_tip: RPCHealthStacksTipCheck,
+    pub peers: RPCHealthPeersCheck,
+}
+
+/// The node's CIDR and AS-number peer allow/deny rules.  Returned by GET and POST
+/// /v2/admin/peer_policy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPeerPolicyData {
+    pub allow_cidrs: Vec<String>,
+    pub deny_cidrs: Vec<String>,
+    pub allow_asns: Vec<u32>,
+    pub deny_asns: Vec<u32>,
+}
+
+/// Which peer policy list an update changes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerPolicyList {
+    Allow,
+    Deny,
+}
+
+/// The body of a POST /v2/admin/peer_policy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerPolicyUpdateRequestBody {
+    pub list: PeerPolicyList,
+    /// remove the rules instead of adding them
+    #[serde(default)]
+    pub remove: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cidr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+}
+
+/// A validated peer policy update: add (or remove) a CIDR range and/or an AS number to (or
+/// from) the allow or deny list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerPolicyUpdate {
+    pub list: PeerPolicyList,
+    pub remove: bool,
+    /// prefix and 128-bit mask
+    pub cidr: Option<(PeerAddress, u32)>,
+    pub asn: Option<u32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1067,6 +1629,117 @@ pub struct RPCPoxInfoData {
     pub next_reward_cycle_in: u64,
 }
 
+/// The data we return on GET /v2/pox/cycles/{cycle}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCRewardCycleData {
+    pub id: u64,
+    pub is_current: bool,
+    pub reward_phase_start_block_height: u64,
+    pub prepare_phase_start_block_height: u64,
+    pub end_block_height: u64,
+    pub reward_phase_block_length: u64,
+    pub prepare_phase_block_length: u64,
+    pub reward_slots: u64,
+    pub stacked_ustx: u64,
+    pub min_threshold_ustx: u64,
+    pub is_pox_active: bool,
+}
+
+/// A PoX address in a reward set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCRewardSetEntry {
+    /// the burnchain address that block commits pay
+    pub pox_address: String,
+    pub total_ustx: u64,
+    /// how many of the cycle's reward slots this address holds
+    pub slots: u64,
+}
+
+/// The data we return on GET /v2/pox/reward_set/{cycle}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCRewardSetData {
+    pub reward_cycle: u64,
+    /// the anchor block whose PoX contract state the reward set was read from.  If the cycle has
+    /// no anchor block, its block commits all burn.
+    pub anchor_block_hash: Option<BlockHeaderHash>,
+    pub anchor_index_block_hash: Option<StacksBlockId>,
+    pub liquid_ustx: u64,
+    pub stacked_ustx: u64,
+    pub threshold_ustx: u64,
+    /// if false, too little STX was stacked for PoX to pay out, so no address holds a slot
+    pub enough_participation: bool,
+    pub entries: Vec<RPCRewardSetEntry>,
+}
+
+/// A principal's locked STX, as the PoX contract records it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCStackingState {
+    pub amount_ustx: u64,
+    pub pox_address: String,
+    pub first_reward_cycle: u64,
+    pub lock_period: u64,
+}
+
+/// A principal's delegation to a stacking pool, as the PoX contract records it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCDelegationState {
+    pub amount_ustx: u64,
+    pub delegated_to: String,
+    pub until_burn_height: Option<u64>,
+    pub pox_address: Option<String>,
+}
+
+/// The data we return on GET /v2/pox/stackers/{principal}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCStackerData {
+    pub locked_ustx: u64,
+    pub unlock_height: u64,
+    pub stacking: Option<RPCStackingState>,
+    pub delegation: Option<RPCDelegationState>,
+}
+
+/// A Clarity key written by a block.  Values are as stored by the Clarity database: data vars
+/// and map entries are hex-encoded consensus serializations of Clarity values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCStateDiffEntry {
+    /// the key, if the node recorded it when the key was written
+    pub key: Option<String>,
+    /// the key's MARF path, as hex
+    pub path: String,
+    pub value: Option<String>,
+    /// the key's value before the block, if it had one
+    pub prior_value: Option<String>,
+}
+
+/// The data we return on GET /v2/blocks/{index_block_hash}/state-diff
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBlockStateDiffData {
+    pub index_block_hash: StacksBlockId,
+    pub entries: Vec<RPCStateDiffEntry>,
+}
+
+/// A microblock, as returned by the microblock stream endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCMicroblockEntry {
+    pub microblock_hash: BlockHeaderHash,
+    pub sequence: u16,
+    pub parent_microblock_hash: BlockHeaderHash,
+    pub txids: Vec<Txid>,
+    /// hex-encoded consensus serialization of the microblock
+    pub microblock: String,
+}
+
+/// The data we return on GET /v2/microblocks/stream/{anchor_index_block_hash}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCMicroblockStreamData {
+    pub anchor_block_id: StacksBlockId,
+    /// whether or not a child anchored block has confirmed the stream
+    pub confirmed: bool,
+    pub microblocks: Vec<RPCMicroblockEntry>,
+    /// pass this as `start` to get the next page, if there may be one
+    pub next_start: Option<u16>,
+}
+
 /// Headers response payload
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExtendedStacksHeader {
@@ -1142,6 +1815,27 @@ impl RPCFeeEstimate {
             },
         ]
     }
+
+    /// Like `estimate_fees()`, but from per-dimension fee rates: `fees` are the total fees
+    ///  computed by `DimensionFeeRateEstimates::estimate_fees()`, and each `fee_rate` is the
+    ///  resulting fee per unit of `scalar`.
+    pub fn estimate_fees_by_dimension(scalar: u64, fees: FeeRateEstimate) -> Vec<RPCFeeEstimate> {
+        let denominator = cmp::max(scalar, 1) as f64;
+        vec![
+            RPCFeeEstimate {
+                fee: fees.low as u64,
+                fee_rate: fees.low / denominator,
+            },
+            RPCFeeEstimate {
+                fee: fees.middle as u64,
+                fee_rate: fees.middle / denominator,
+            },
+            RPCFeeEstimate {
+                fee: fees.high as u64,
+                fee_rate: fees.high / denominator,
+            },
+        ]
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1150,6 +1844,10 @@ pub struct RPCFeeEstimateResponse {
     pub estimated_cost_scalar: u64,
     pub estimations: Vec<RPCFeeEstimate>,
     pub cost_scalar_change_by_byte: f64,
+    /// The fee rates for each dimension of the block limit that `estimations` were computed
+    ///  from, if the node's fee estimator tracks them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension_fee_rates: Option<DimensionFeeRateEstimates>,
 }
 
 #[derive(Debug, Clone, PartialEq, Copy, Hash)]
@@ -1164,6 +1862,16 @@ pub struct HttpRequestMetadata {
     pub version: HttpVersion,
     pub peer: PeerHost,
     pub keep_alive: bool,
+    /// the request's If-None-Match header, if it has one
+    pub if_none_match: Option<String>,
+    /// the encoding the client would most like compressible responses in
+    pub accept_encoding: HttpContentEncoding,
+    /// the RPC API key the client identified itself with, if any
+    pub api_key: Option<String>,
+    /// the request's Origin header, if it has one
+    pub origin: Option<String>,
+    /// the CORS headers to answer the request with, as the node's CORS policy dictates
+    pub cors: HttpCorsHeaders,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1184,6 +1892,24 @@ pub struct MapEntryResponse {
     pub marf_proof: Option<String>,
 }
 
+/// The entries looked up in one data map, as returned by the map entry batch endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapEntryBatchResult {
+    pub contract: String,
+    pub map: String,
+    /// the entry for each key, in request order
+    pub entries: Vec<MapEntryResponse>,
+}
+
+/// The data we return on POST /v2/map_entries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapEntryBatchResponse {
+    /// index block hash of the chain tip every entry was read at
+    pub tip: String,
+    /// the entries of each map, in request order
+    pub maps: Vec<MapEntryBatchResult>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractSrcResponse {
     pub source: String,
@@ -1210,6 +1936,40 @@ pub struct CallReadOnlyResponse {
     pub cause: Option<String>,
 }
 
+/// The data we return on POST /v2/contracts/call-read-batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallReadOnlyBatchResponse {
+    /// index block hash of the chain tip every call was evaluated at
+    pub tip: String,
+    /// the result of each call, in request order
+    pub results: Vec<CallReadOnlyResponse>,
+}
+
+/// An argument which does not fit the signature of the function it is passed to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArgumentMismatchEntry {
+    pub index: usize,
+    /// name of the function parameter
+    pub name: String,
+    /// the parameter's type, and the type of the value passed for it
+    pub expected: String,
+    pub found: String,
+    pub cause: String,
+}
+
+/// The data we return on POST /v2/contracts/check-args
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckContractCallArgsResponse {
+    pub okay: bool,
+    /// why the call can't be checked at all, e.g. because the function does not exist or is
+    /// passed the wrong number of arguments
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+    #[serde(default)]
+    pub mismatches: Vec<ArgumentMismatchEntry>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AccountEntryResponse {
     pub balance: String,
@@ -1224,6 +1984,246 @@ pub struct AccountEntryResponse {
     pub nonce_proof: Option<String>,
 }
 
+/// A contract event, as returned by the events endpoints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractEventEntry {
+    pub index_block_hash: String,
+    pub block_height: u64,
+    pub txid: String,
+    pub event_index: u32,
+    pub contract_identifier: String,
+    /// the event observer's name for the event type, e.g. `contract_event` or `ft_transfer_event`
+    pub event_type: String,
+    pub topic: Option<String>,
+    /// hex-encoded consensus serialization of the printed value, token amount, or NFT
+    pub value: String,
+    pub sender: Option<String>,
+    pub recipient: Option<String>,
+    pub cursor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractEventsResponse {
+    pub events: Vec<ContractEventEntry>,
+    /// pass this as `cursor` to get the next page, if there may be one
+    pub next_cursor: Option<String>,
+}
+
+/// A data map of a contract, as returned by the contract storage endpoint.  Types are
+///  Clarity type signatures, e.g. `(tuple (owner principal))`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractStorageMapEntry {
+    pub name: String,
+    pub key_type: String,
+    pub value_type: String,
+}
+
+/// A data var or non-fungible token of a contract, with its Clarity type signature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractStorageEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_signature: String,
+}
+
+/// The data we return on GET /v2/contracts/storage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractStorageResponse {
+    pub maps: Vec<ContractStorageMapEntry>,
+    pub data_vars: Vec<ContractStorageEntry>,
+    pub fungible_tokens: Vec<String>,
+    pub non_fungible_tokens: Vec<ContractStorageEntry>,
+}
+
+impl From<ContractStorageLayout> for ContractStorageResponse {
+    fn from(layout: ContractStorageLayout) -> ContractStorageResponse {
+        ContractStorageResponse {
+            maps: layout
+                .maps
+                .into_iter()
+                .map(|(name, (key_type, value_type))| ContractStorageMapEntry {
+                    name: name.to_string(),
+                    key_type: key_type.to_string(),
+                    value_type: value_type.to_string(),
+                })
+                .collect(),
+            data_vars: layout
+                .data_vars
+                .into_iter()
+                .map(|(name, var_type)| ContractStorageEntry {
+                    name: name.to_string(),
+                    type_signature: var_type.to_string(),
+                })
+                .collect(),
+            fungible_tokens: layout
+                .fungible_tokens
+                .into_iter()
+                .map(|name| name.to_string())
+                .collect(),
+            non_fungible_tokens: layout
+                .non_fungible_tokens
+                .into_iter()
+                .map(|(name, asset_type)| ContractStorageEntry {
+                    name: name.to_string(),
+                    type_signature: asset_type.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A contract's execution costs over the queried blocks, as returned by the contract metrics
+/// endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractMetricsEntry {
+    pub contract_identifier: String,
+    pub calls: u64,
+    pub deploys: u64,
+    pub runtime: u64,
+    pub read_count: u64,
+    pub read_length: u64,
+    pub write_count: u64,
+    pub write_length: u64,
+}
+
+/// The data we return on GET /v2/contracts/metrics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractMetricsResponse {
+    pub tip: String,
+    pub blocks: u64,
+    /// contracts in decreasing order of runtime cost
+    pub contracts: Vec<ContractMetricsEntry>,
+}
+
+/// Query arguments to the contract metrics endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractMetricsQuery {
+    pub contract: Option<QualifiedContractIdentifier>,
+    pub blocks: u64,
+    pub limit: u32,
+}
+
+/// Query arguments to the events endpoints
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractEventsQuery {
+    pub contract: QualifiedContractIdentifier,
+    pub filter: ContractEventFilter,
+    pub cursor: Option<ContractEventCursor>,
+    pub limit: u32,
+}
+
+/// A pending transaction, as returned by the mempool transaction endpoints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemPoolTransactionEntry {
+    pub txid: String,
+    pub origin_address: String,
+    pub origin_nonce: u64,
+    /// the address paying the fee, which is the origin if the transaction is not sponsored
+    pub sponsor_address: String,
+    pub sponsor_nonce: u64,
+    pub fee: u64,
+    /// the fee rate the node's cost estimator assigned the transaction, if it has one
+    pub fee_rate: Option<f64>,
+    pub tx_len: u64,
+    pub payload_type: String,
+    /// the Stacks block height of the chain tip when the transaction was accepted
+    pub block_height: u64,
+    pub accept_time: u64,
+    /// hex-encoded transaction
+    pub tx: String,
+    pub cursor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemPoolTransactionsResponse {
+    pub transactions: Vec<MemPoolTransactionEntry>,
+    /// pass this as `cursor` to get the next page, if there may be one
+    pub next_cursor: Option<String>,
+}
+
+/// A transaction involving a principal, as returned by the address transactions endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressTransactionEntry {
+    pub txid: String,
+    pub index_block_hash: String,
+    pub block_height: u64,
+    /// position of the transaction in the block's receipts
+    pub receipt_index: u32,
+    /// the microblock the transaction was mined in, if it was not mined in the anchored block
+    pub microblock_hash: Option<String>,
+    pub microblock_sequence: Option<u16>,
+    /// positions of the transaction's events that involve the principal
+    pub event_indexes: Vec<u32>,
+    pub cursor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressTransactionsResponse {
+    pub transactions: Vec<AddressTransactionEntry>,
+    /// pass this as `cursor` to get the next page, if there may be one
+    pub next_cursor: Option<String>,
+}
+
+/// A fungible token balance, as returned by the address assets endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FungibleTokenHoldingEntry {
+    pub asset_identifier: String,
+    /// decimal balance, since it may not fit in a JSON number
+    pub balance: String,
+}
+
+/// An owned non-fungible token, as returned by the address assets endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NonFungibleTokenHoldingEntry {
+    pub asset_identifier: String,
+    /// hex-encoded consensus serialization of the token
+    pub value: String,
+    /// the token as a Clarity literal, e.g. `u1`
+    pub repr: String,
+}
+
+/// The data we return on GET /v2/addresses/{principal}/assets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressAssetsResponse {
+    pub fungible_tokens: Vec<FungibleTokenHoldingEntry>,
+    pub non_fungible_tokens: Vec<NonFungibleTokenHoldingEntry>,
+}
+
+/// The data we return on GET /v2/names/{name}.{namespace}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNameData {
+    pub name: String,
+    pub namespace: String,
+    pub owner: String,
+    /// hex-encoded
+    pub zonefile_hash: String,
+    pub lease_started_at: u64,
+    pub lease_ending_at: Option<u64>,
+}
+
+/// The data we return on GET /v2/addresses/{principal}/names
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPrincipalNamesData {
+    /// fully-qualified names, as `name.namespace`
+    pub names: Vec<String>,
+}
+
+/// Query arguments to the address transactions endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressTransactionsQuery {
+    pub principal: PrincipalData,
+    pub cursor: Option<AddressTransactionCursor>,
+    pub limit: u32,
+}
+
+/// Query arguments to the mempool transactions endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolTransactionsQuery {
+    pub filter: MemPoolTxFilter,
+    pub cursor: Option<MemPoolTxCursor>,
+    pub limit: u32,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnconfirmedTransactionStatus {
     Microblock {
@@ -1245,6 +2245,49 @@ pub struct PostTransactionRequestBody {
     pub attachment: Option<String>,
 }
 
+/// Nonce of an account a posted transaction spends from, against the nonce the chain tip expects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTransactionNonceCheck {
+    pub address: String,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Fee a posted transaction pays, against the minimum the mempool admits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTransactionFeeCheck {
+    pub fee: u64,
+    pub minimum_fee: u64,
+    pub tx_len: u64,
+    pub estimated_fee_rate: Option<f64>,
+}
+
+/// Post-conditions of a posted transaction, checked against the outflows that can be known
+/// without running it.  Only STX transfers have such outflows; `passed` is None otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTransactionPostConditionCheck {
+    pub mode: String,
+    pub count: usize,
+    pub passed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<serde_json::Value>,
+}
+
+/// The data we return on POST /v2/transactions?report=1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTransactionAdmissionReport {
+    pub txid: Txid,
+    pub accepted: bool,
+    pub already_in_mempool: bool,
+    pub origin_nonce: Option<RPCTransactionNonceCheck>,
+    pub sponsor_nonce: Option<RPCTransactionNonceCheck>,
+    pub fee: RPCTransactionFeeCheck,
+    pub estimated_cost: Option<ExecutionCost>,
+    pub post_conditions: RPCTransactionPostConditionCheck,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<serde_json::Value>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct GetAttachmentResponse {
     pub attachment: Attachment,
@@ -1291,6 +2334,11 @@ impl HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: PeerHost::from_host_port(host, port),
             keep_alive: true,
+            if_none_match: None,
+            accept_encoding: HttpContentEncoding::Identity,
+            api_key: None,
+            origin: None,
+            cors: HttpCorsHeaders::default(),
         }
     }
 
@@ -1299,6 +2347,11 @@ impl HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: peer_host,
             keep_alive: true,
+            if_none_match: None,
+            accept_encoding: HttpContentEncoding::Identity,
+            api_key: None,
+            origin: None,
+            cors: HttpCorsHeaders::default(),
         }
     }
 
@@ -1307,6 +2360,34 @@ impl HttpRequestMetadata {
             version: preamble.version,
             peer: preamble.host.clone(),
             keep_alive: preamble.keep_alive,
+            if_none_match: preamble.headers.get("if-none-match").cloned(),
+            accept_encoding: preamble
+                .headers
+                .get("accept-encoding")
+                .map(|value| HttpContentEncoding::negotiate(value))
+                .unwrap_or(HttpContentEncoding::Identity),
+            api_key: preamble.headers.get("x-api-key").cloned().or_else(|| {
+                preamble
+                    .headers
+                    .get("authorization")
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .map(|key| key.trim().to_string())
+            }),
+            origin: preamble.headers.get("origin").cloned(),
+            cors: HttpCorsHeaders::default(),
+        }
+    }
+
+    /// Does the request's If-None-Match header match `etag` (given without quotes)?  Uses the
+    /// weak comparison RFC 7232 requires for If-None-Match, so `W/"x"` matches `x`.
+    pub fn etag_matches(&self, etag: &str) -> bool {
+        match self.if_none_match {
+            Some(ref if_none_match) => if_none_match.split(',').any(|tag| {
+                let tag = tag.trim();
+                let tag = tag.strip_prefix("W/").unwrap_or(tag);
+                tag == "*" || tag.trim_matches('"') == etag
+            }),
+            None => false,
         }
     }
 }
@@ -1317,11 +2398,281 @@ pub struct CallReadOnlyRequestBody {
     pub arguments: Vec<String>,
 }
 
+/// One call in a batch of read-only calls
+#[derive(Serialize, Deserialize)]
+pub struct CallReadOnlyBatchEntry {
+    /// fully-qualified contract identifier
+    pub contract: String,
+    pub function: String,
+    /// hex-encoded serialized Clarity values
+    pub arguments: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CallReadOnlyBatchRequestBody {
+    pub sender: String,
+    pub calls: Vec<CallReadOnlyBatchEntry>,
+}
+
+/// The keys to look up in one data map, in a batch of map entry lookups
+#[derive(Serialize, Deserialize)]
+pub struct MapEntryBatchRequestEntry {
+    /// fully-qualified contract identifier
+    pub contract: String,
+    pub map: String,
+    /// hex-encoded serialized Clarity values
+    pub keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MapEntryBatchRequestBody {
+    pub maps: Vec<MapEntryBatchRequestEntry>,
+}
+
+/// Keys to look up in one contract data map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapEntryLookup {
+    pub contract_id: QualifiedContractIdentifier,
+    pub map_name: ClarityName,
+    pub keys: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CheckContractCallArgsRequestBody {
+    /// hex-encoded serialized Clarity values
+    pub arguments: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FeeRateEstimateRequestBody {
     #[serde(default)]
     pub estimated_len: Option<u64>,
-    pub transaction_payload: String,
+    #[serde(default)]
+    pub transaction_payload: Option<String>,
+    /// Estimate fees for this execution cost, instead of estimating the cost of
+    ///  `transaction_payload`
+    #[serde(default)]
+    pub estimated_cost: Option<ExecutionCost>,
+}
+
+/// What POST /v2/fees/transaction estimates fees for
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeeRateEstimateSubject {
+    /// A transaction payload, whose execution cost the node's cost estimator estimates
+    Payload(TransactionPayload),
+    /// An execution cost the client already estimated
+    Cost(ExecutionCost),
+}
+
+/// An asset event sent to POST /v2/postconditions/check.  Its fields are named the same as those
+///  of the event observer's asset events, so a client can pass those events along as-is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostConditionCheckEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub sender: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_identifier: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_value: Option<String>,
+}
+
+impl PostConditionCheckEvent {
+    fn parse_principal(principal: &Option<String>, field: &str) -> Result<PrincipalData, String> {
+        let principal = principal
+            .as_ref()
+            .ok_or_else(|| format!("Missing {}", field))?;
+        PrincipalData::parse(principal).map_err(|_e| format!("Invalid {}: {}", field, principal))
+    }
+
+    fn parse_asset_identifier(&self) -> Result<AssetIdentifier, String> {
+        let asset_id = self
+            .asset_identifier
+            .as_ref()
+            .ok_or_else(|| "Missing asset_identifier".to_string())?;
+        let (contract, asset_name) = asset_id
+            .split_once("::")
+            .ok_or_else(|| format!("Invalid asset_identifier: {}", asset_id))?;
+        Ok(AssetIdentifier {
+            contract_identifier: QualifiedContractIdentifier::parse(contract)
+                .map_err(|_e| format!("Invalid asset_identifier: {}", asset_id))?,
+            asset_name: ClarityName::try_from(asset_name.to_string())
+                .map_err(|_e| format!("Invalid asset_identifier: {}", asset_id))?,
+        })
+    }
+
+    fn parse_amount(&self) -> Result<u128, String> {
+        let amount = self
+            .amount
+            .as_ref()
+            .ok_or_else(|| "Missing amount".to_string())?;
+        amount
+            .parse::<u128>()
+            .map_err(|_e| format!("Invalid amount: {}", amount))
+    }
+
+    fn parse_value(&self) -> Result<Value, String> {
+        let raw_value = self
+            .raw_value
+            .as_ref()
+            .ok_or_else(|| "Missing raw_value".to_string())?;
+        let value_hex = raw_value.strip_prefix("0x").unwrap_or(raw_value);
+        Value::try_deserialize_hex_untyped(value_hex)
+            .map_err(|_e| format!("Invalid raw_value: {}", raw_value))
+    }
+
+    /// Decode into the transaction event it describes
+    pub fn to_event(&self) -> Result<StacksTransactionEvent, String> {
+        let sender =
+            PostConditionCheckEvent::parse_principal(&Some(self.sender.clone()), "sender")?;
+        let event = match self.event_type.as_str() {
+            "stx_transfer_event" => StacksTransactionEvent::STXEvent(
+                STXEventType::STXTransferEvent(STXTransferEventData {
+                    sender,
+                    recipient: PostConditionCheckEvent::parse_principal(
+                        &self.recipient,
+                        "recipient",
+                    )?,
+                    amount: self.parse_amount()?,
+                }),
+            ),
+            "stx_burn_event" => {
+                StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(STXBurnEventData {
+                    sender,
+                    amount: self.parse_amount()?,
+                }))
+            }
+            "ft_transfer_event" => {
+                StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(FTTransferEventData {
+                    asset_identifier: self.parse_asset_identifier()?,
+                    sender,
+                    recipient: PostConditionCheckEvent::parse_principal(
+                        &self.recipient,
+                        "recipient",
+                    )?,
+                    amount: self.parse_amount()?,
+                }))
+            }
+            "ft_burn_event" => {
+                StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(FTBurnEventData {
+                    asset_identifier: self.parse_asset_identifier()?,
+                    sender,
+                    amount: self.parse_amount()?,
+                }))
+            }
+            "nft_transfer_event" => StacksTransactionEvent::NFTEvent(
+                NFTEventType::NFTTransferEvent(NFTTransferEventData {
+                    asset_identifier: self.parse_asset_identifier()?,
+                    sender,
+                    recipient: PostConditionCheckEvent::parse_principal(
+                        &self.recipient,
+                        "recipient",
+                    )?,
+                    value: self.parse_value()?,
+                }),
+            ),
+            "nft_burn_event" => {
+                StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(NFTBurnEventData {
+                    asset_identifier: self.parse_asset_identifier()?,
+                    sender,
+                    value: self.parse_value()?,
+                }))
+            }
+            other => return Err(format!("Unsupported event type: {}", other)),
+        };
+        Ok(event)
+    }
+
+    /// Encode an asset event that moves assets out of its sender's account.  Returns None for
+    ///  any other event.
+    pub fn from_event(event: &StacksTransactionEvent) -> Option<PostConditionCheckEvent> {
+        let hex_value = |value: &Value| {
+            let mut bytes = vec![];
+            value
+                .serialize_write(&mut bytes)
+                .expect("IOError filling byte buffer.");
+            format!("0x{}", to_hex(&bytes))
+        };
+        let (event_type, sender, recipient, asset_identifier, amount, raw_value) = match event {
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(data)) => (
+                "stx_transfer_event",
+                &data.sender,
+                Some(&data.recipient),
+                None,
+                Some(data.amount),
+                None,
+            ),
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(data)) => (
+                "stx_burn_event",
+                &data.sender,
+                None,
+                None,
+                Some(data.amount),
+                None,
+            ),
+            StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(data)) => (
+                "ft_transfer_event",
+                &data.sender,
+                Some(&data.recipient),
+                Some(&data.asset_identifier),
+                Some(data.amount),
+                None,
+            ),
+            StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(data)) => (
+                "ft_burn_event",
+                &data.sender,
+                None,
+                Some(&data.asset_identifier),
+                Some(data.amount),
+                None,
+            ),
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(data)) => (
+                "nft_transfer_event",
+                &data.sender,
+                Some(&data.recipient),
+                Some(&data.asset_identifier),
+                None,
+                Some(hex_value(&data.value)),
+            ),
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(data)) => (
+                "nft_burn_event",
+                &data.sender,
+                None,
+                Some(&data.asset_identifier),
+                None,
+                Some(hex_value(&data.value)),
+            ),
+            _ => return None,
+        };
+        Some(PostConditionCheckEvent {
+            event_type: event_type.to_string(),
+            sender: sender.to_string(),
+            recipient: recipient.map(|r| r.to_string()),
+            asset_identifier: asset_identifier.map(|a| a.to_string()),
+            amount: amount.map(|a| a.to_string()),
+            raw_value,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PostConditionCheckRequestBody {
+    pub transaction: String,
+    pub events: Vec<PostConditionCheckEvent>,
+}
+
+/// The data we return on POST /v2/postconditions/check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostConditionCheckResponse {
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<serde_json::Value>,
 }
 
 /// Items in the NeighborsInfo -- combines NeighborKey and NeighborAddress
@@ -1334,6 +2685,9 @@ pub struct RPCNeighbor {
     pub port: u16,
     pub public_key_hash: Hash160,
     pub authenticated: bool,
+    /// only given for neighbors we're connected to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<RPCNeighborQuality>,
 }
 
 impl RPCNeighbor {
@@ -1345,10 +2699,23 @@ impl RPCNeighbor {
             port: nk.port,
             public_key_hash: pkh,
             authenticated: auth,
+            quality: None,
         }
     }
 }
 
+/// How well a connected neighbor has been serving us
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNeighborQuality {
+    /// smoothed round-trip time of our requests, in milliseconds (None if it hasn't answered any)
+    pub rtt_ms: Option<u64>,
+    /// fraction of our requests it answered before they timed out (None if none have either
+    /// been answered or timed out)
+    pub request_success_rate: Option<f64>,
+    pub bytes_tx: u64,
+    pub bytes_rx: u64,
+}
+
 /// Struct given back from a call to `/v2/neighbors`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RPCNeighborsInfo {
@@ -1357,6 +2724,88 @@ pub struct RPCNeighborsInfo {
     pub outbound: Vec<RPCNeighbor>,
 }
 
+/// What we know about a connected neighbor's block inventory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNeighborInvData {
+    /// one of "online", "broken", "diverged", "stale" or "dead"
+    pub status: String,
+    pub num_sortitions: u64,
+    pub num_reward_cycles: u64,
+    pub last_updated_at: u64,
+    pub scans: u64,
+    pub done: bool,
+}
+
+/// A connected neighbor, with its conversation's health stats
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNeighborDetail {
+    pub neighbor: RPCNeighbor,
+    pub outbound: bool,
+    pub services: u16,
+    pub first_contact_time: u64,
+    pub last_contact_time: u64,
+    pub last_send_time: u64,
+    pub last_recv_time: u64,
+    pub last_handshake_time: u64,
+    pub bytes_tx: u64,
+    pub bytes_rx: u64,
+    pub msgs_tx: u64,
+    pub msgs_rx: u64,
+    pub msgs_rx_unsolicited: u64,
+    pub msgs_err: u64,
+    /// fraction of recent requests the neighbor answered
+    pub health_score: f64,
+    /// smoothed round-trip time of our requests, in milliseconds
+    pub rtt_ms: Option<u64>,
+    pub replies_rx: u64,
+    pub requests_timed_out: u64,
+    /// out of 100
+    pub reputation: f64,
+    /// None if we haven't started an inventory sync with this neighbor
+    pub inv: Option<RPCNeighborInvData>,
+}
+
+/// A neighbor we won't talk to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCDeniedNeighbor {
+    #[serde(rename = "ip")]
+    pub addrbytes: PeerAddress,
+    pub port: u16,
+    pub public_key_hash: Hash160,
+    /// when the ban lifts, or None if it never does (e.g. a deny rule covers the neighbor)
+    pub denied_until: Option<u64>,
+}
+
+/// The outcome of a finished neighbor walk.  Neighbors are given as `ip:port`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNeighborWalkData {
+    pub finished_at: u64,
+    pub new_connections: Vec<String>,
+    pub dead_connections: Vec<String>,
+    pub broken_connections: Vec<String>,
+    pub replaced_neighbors: Vec<String>,
+}
+
+/// The state of the neighbor walk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNeighborWalkStatus {
+    /// the walk's current state, or None if no walk is in progress
+    pub state: Option<String>,
+    pub walk_count: u64,
+    pub walk_attempts: u64,
+    pub walk_total_step_count: u64,
+    /// oldest first
+    pub recent_walks: Vec<RPCNeighborWalkData>,
+}
+
+/// Struct given back from a call to `/v2/neighbors/detail`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCNeighborsDetailData {
+    pub neighbors: Vec<RPCNeighborDetail>,
+    pub denied: Vec<RPCDeniedNeighbor>,
+    pub walk: RPCNeighborWalkStatus,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TipRequest {
     UseLatestAnchoredTip,
@@ -1368,18 +2817,35 @@ pub enum TipRequest {
 #[derive(Debug, Clone, PartialEq)]
 pub enum HttpRequestType {
     GetInfo(HttpRequestMetadata),
+    /// upgrade to a WebSocket, with the client's Sec-WebSocket-Key
+    WebSocketUpgrade(HttpRequestMetadata, String),
     GetPoxInfo(HttpRequestMetadata, TipRequest),
+    /// reward cycle parameters; None means the current reward cycle
+    GetPoxCycle(HttpRequestMetadata, Option<u64>, TipRequest),
+    GetPoxRewardSet(HttpRequestMetadata, u64),
+    GetPoxStacker(HttpRequestMetadata, PrincipalData, TipRequest),
     GetNeighbors(HttpRequestMetadata),
+    GetNeighborsDetail(HttpRequestMetadata),
+    GetTelemetry(HttpRequestMetadata),
     GetHeaders(HttpRequestMetadata, u64, TipRequest),
     GetBlock(HttpRequestMetadata, StacksBlockId),
+    GetBlockExport(HttpRequestMetadata, u64, u32),
+    GetBlockStateDiff(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksIndexed(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksConfirmed(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksUnconfirmed(HttpRequestMetadata, StacksBlockId, u16),
+    GetMicroblockStream(HttpRequestMetadata, StacksBlockId, u16, u16),
     GetTransactionUnconfirmed(HttpRequestMetadata, Txid),
-    PostTransaction(HttpRequestMetadata, StacksTransaction, Option<Attachment>),
+    PostTransaction(
+        HttpRequestMetadata,
+        StacksTransaction,
+        Option<Attachment>,
+        bool,
+    ),
     PostBlock(HttpRequestMetadata, ConsensusHash, StacksBlock),
     PostMicroblock(HttpRequestMetadata, StacksMicroblock, TipRequest),
     GetAccount(HttpRequestMetadata, PrincipalData, TipRequest, bool),
+    GetAccountProof(HttpRequestMetadata, PrincipalData, TipRequest),
     GetDataVar(
         HttpRequestMetadata,
         StacksAddress,
@@ -1397,7 +2863,8 @@ pub enum HttpRequestType {
         TipRequest,
         bool,
     ),
-    FeeRateEstimate(HttpRequestMetadata, TransactionPayload, u64),
+    GetMapEntryBatch(HttpRequestMetadata, Vec<MapEntryLookup>, TipRequest, bool),
+    FeeRateEstimate(HttpRequestMetadata, FeeRateEstimateSubject, u64),
     CallReadOnlyFunction(
         HttpRequestMetadata,
         StacksAddress,
@@ -1407,6 +2874,20 @@ pub enum HttpRequestType {
         Vec<Value>,
         TipRequest,
     ),
+    CheckContractCallArgs(
+        HttpRequestMetadata,
+        StacksAddress,
+        ContractName,
+        ClarityName,
+        Vec<Value>,
+        TipRequest,
+    ),
+    CallReadOnlyFunctionBatch(
+        HttpRequestMetadata,
+        PrincipalData,
+        Vec<ReadOnlyCall>,
+        TipRequest,
+    ),
     GetTransferCost(HttpRequestMetadata),
     GetContractSrc(
         HttpRequestMetadata,
@@ -1416,6 +2897,12 @@ pub enum HttpRequestType {
         bool,
     ),
     GetContractABI(HttpRequestMetadata, StacksAddress, ContractName, TipRequest),
+    GetContractStorage(HttpRequestMetadata, StacksAddress, ContractName, TipRequest),
+    PostConditionCheck(
+        HttpRequestMetadata,
+        StacksTransaction,
+        Vec<StacksTransactionEvent>,
+    ),
     OptionsPreflight(HttpRequestMetadata, String),
     GetAttachment(HttpRequestMetadata, Hash160),
     GetAttachmentsInv(HttpRequestMetadata, StacksBlockId, HashSet<u32>),
@@ -1427,6 +2914,20 @@ pub enum HttpRequestType {
         TipRequest,
     ),
     MemPoolQuery(HttpRequestMetadata, MemPoolSyncData, Option<Txid>),
+    GetContractEvents(HttpRequestMetadata, ContractEventsQuery),
+    GetContractMetrics(HttpRequestMetadata, ContractMetricsQuery),
+    GetChainstateManifest(HttpRequestMetadata, u64, u64),
+    GetMemPoolTransactions(HttpRequestMetadata, MemPoolTransactionsQuery),
+    GetMemPoolTransaction(HttpRequestMetadata, Txid),
+    GetAddressTransactions(HttpRequestMetadata, AddressTransactionsQuery),
+    GetAddressAssets(HttpRequestMetadata, PrincipalData),
+    GetAddressNames(HttpRequestMetadata, PrincipalData, TipRequest),
+    GetName(HttpRequestMetadata, String, String, TipRequest),
+    Rosetta(HttpRequestMetadata, RosettaEndpoint, serde_json::Value),
+    GetHealthLive(HttpRequestMetadata),
+    GetHealthReady(HttpRequestMetadata),
+    GetPeerPolicy(HttpRequestMetadata),
+    PostPeerPolicy(HttpRequestMetadata, PeerPolicyUpdate),
     /// catch-all for any errors we should surface from parsing
     ClientError(HttpRequestMetadata, ClientError),
 }
@@ -1438,6 +2939,13 @@ pub struct HttpResponseMetadata {
     pub client_keep_alive: bool,
     pub request_id: u32,
     pub content_length: Option<u32>,
+    /// strong entity tag of an immutable resource, without quotes
+    pub etag: Option<String>,
+    /// how the response body is compressed
+    pub content_encoding: HttpContentEncoding,
+    /// how many seconds the client should wait before trying again
+    pub retry_after: Option<u64>,
+    pub cors: HttpCorsHeaders,
 }
 
 impl HttpResponseMetadata {
@@ -1461,9 +2969,28 @@ impl HttpResponseMetadata {
             client_keep_alive: client_keep_alive,
             request_id: request_id,
             content_length: content_length,
+            etag: None,
+            content_encoding: HttpContentEncoding::Identity,
+            retry_after: None,
+            cors: HttpCorsHeaders::default(),
         }
     }
 
+    pub fn with_etag(mut self, etag: String) -> HttpResponseMetadata {
+        self.etag = Some(etag);
+        self
+    }
+
+    pub fn with_content_encoding(mut self, encoding: HttpContentEncoding) -> HttpResponseMetadata {
+        self.content_encoding = encoding;
+        self
+    }
+
+    pub fn with_retry_after(mut self, secs: u64) -> HttpResponseMetadata {
+        self.retry_after = Some(secs);
+        self
+    }
+
     pub fn from_preamble(
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
@@ -1473,6 +3000,36 @@ impl HttpResponseMetadata {
             client_keep_alive: preamble.keep_alive,
             request_id: preamble.request_id,
             content_length: preamble.content_length.clone(),
+            etag: preamble
+                .headers
+                .get("etag")
+                .map(|etag| etag.trim_matches('"').to_string()),
+            content_encoding: preamble
+                .headers
+                .get("content-encoding")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(HttpContentEncoding::Identity),
+            retry_after: preamble
+                .headers
+                .get("retry-after")
+                .and_then(|value| value.parse().ok()),
+            cors: HttpCorsHeaders {
+                allow_origin: preamble.headers.get("access-control-allow-origin").cloned(),
+                allow_methods: preamble
+                    .headers
+                    .get("access-control-allow-methods")
+                    .cloned()
+                    .unwrap_or_default(),
+                allow_headers: preamble
+                    .headers
+                    .get("access-control-allow-headers")
+                    .cloned()
+                    .unwrap_or_default(),
+                max_age: preamble
+                    .headers
+                    .get("access-control-max-age")
+                    .and_then(|value| value.parse().ok()),
+            },
         }
     }
 
@@ -1482,6 +3039,10 @@ impl HttpResponseMetadata {
             client_keep_alive: false,
             request_id: HttpResponseMetadata::make_request_id(),
             content_length: Some(0),
+            etag: None,
+            content_encoding: HttpContentEncoding::Identity,
+            retry_after: None,
+            cors: HttpCorsHeaders::default(),
         }
     }
 }
@@ -1489,12 +3050,14 @@ impl HttpResponseMetadata {
 impl From<&HttpRequestType> for HttpResponseMetadata {
     fn from(req: &HttpRequestType) -> HttpResponseMetadata {
         let metadata = req.metadata();
-        HttpResponseMetadata::new(
+        let mut response_metadata = HttpResponseMetadata::new(
             metadata.version,
             HttpResponseMetadata::make_request_id(),
             None,
             metadata.keep_alive,
-        )
+        );
+        response_metadata.cors = metadata.cors.clone();
+        response_metadata
     }
 }
 
@@ -1503,22 +3066,40 @@ impl From<&HttpRequestType> for HttpResponseMetadata {
 pub enum HttpResponseType {
     PeerInfo(HttpResponseMetadata, RPCPeerInfoData),
     PoxInfo(HttpResponseMetadata, RPCPoxInfoData),
+    PoxCycle(HttpResponseMetadata, RPCRewardCycleData),
+    PoxRewardSet(HttpResponseMetadata, RPCRewardSetData),
+    PoxStacker(HttpResponseMetadata, RPCStackerData),
     Neighbors(HttpResponseMetadata, RPCNeighborsInfo),
+    NeighborsDetail(HttpResponseMetadata, RPCNeighborsDetailData),
+    Telemetry(HttpResponseMetadata, RPCTelemetryData),
     Headers(HttpResponseMetadata, Vec<ExtendedStacksHeader>),
     HeaderStream(HttpResponseMetadata),
     Block(HttpResponseMetadata, StacksBlock),
     BlockStream(HttpResponseMetadata),
+    BlockExport(HttpResponseMetadata, Vec<ExportedBlockData>),
+    BlockExportStream(HttpResponseMetadata),
+    BlockStateDiff(HttpResponseMetadata, RPCBlockStateDiffData),
     Microblocks(HttpResponseMetadata, Vec<StacksMicroblock>),
     MicroblockStream(HttpResponseMetadata),
+    MicroblockStreamPage(HttpResponseMetadata, RPCMicroblockStreamData),
     TransactionID(HttpResponseMetadata, Txid),
+    TransactionAdmissionReport(HttpResponseMetadata, RPCTransactionAdmissionReport),
     StacksBlockAccepted(HttpResponseMetadata, StacksBlockId, bool),
     MicroblockHash(HttpResponseMetadata, BlockHeaderHash),
     TokenTransferCost(HttpResponseMetadata, u64),
     GetDataVar(HttpResponseMetadata, DataVarResponse),
     GetMapEntry(HttpResponseMetadata, MapEntryResponse),
+    GetMapEntryBatch(HttpResponseMetadata, MapEntryBatchResponse),
     CallReadOnlyFunction(HttpResponseMetadata, CallReadOnlyResponse),
+    CallReadOnlyFunctionBatch(HttpResponseMetadata, CallReadOnlyBatchResponse),
+    CheckContractCallArgs(HttpResponseMetadata, CheckContractCallArgsResponse),
+    /// `101 Switching Protocols`, with the Sec-WebSocket-Accept key
+    WebSocketAccepted(HttpResponseMetadata, String),
     GetAccount(HttpResponseMetadata, AccountEntryResponse),
+    AccountProof(HttpResponseMetadata, AccountProof),
     GetContractABI(HttpResponseMetadata, ContractInterface),
+    GetContractStorage(HttpResponseMetadata, ContractStorageResponse),
+    PostConditionCheck(HttpResponseMetadata, PostConditionCheckResponse),
     GetContractSrc(HttpResponseMetadata, ContractSrcResponse),
     GetIsTraitImplemented(HttpResponseMetadata, GetIsTraitImplementedResponse),
     UnconfirmedTransaction(HttpResponseMetadata, UnconfirmedTransactionResponse),
@@ -1528,6 +3109,24 @@ pub enum HttpResponseType {
     MemPoolTxs(HttpResponseMetadata, Option<Txid>, Vec<StacksTransaction>),
     OptionsPreflight(HttpResponseMetadata),
     TransactionFeeEstimation(HttpResponseMetadata, RPCFeeEstimateResponse),
+    ContractEvents(HttpResponseMetadata, ContractEventsResponse),
+    ContractMetrics(HttpResponseMetadata, ContractMetricsResponse),
+    ChainstateManifest(HttpResponseMetadata, ChainstateManifest),
+    MemPoolTransactions(HttpResponseMetadata, MemPoolTransactionsResponse),
+    MemPoolTransaction(HttpResponseMetadata, MemPoolTransactionEntry),
+    AddressTransactions(HttpResponseMetadata, AddressTransactionsResponse),
+    AddressAssets(HttpResponseMetadata, AddressAssetsResponse),
+    AddressNames(HttpResponseMetadata, RPCPrincipalNamesData),
+    Name(HttpResponseMetadata, RPCNameData),
+    Rosetta(HttpResponseMetadata, serde_json::Value),
+    /// a Rosetta error, sent as a `500` with the error as its JSON body
+    RosettaError(HttpResponseMetadata, RosettaError),
+    HealthLive(HttpResponseMetadata, RPCHealthLiveData),
+    /// sent as a `200` if the node is ready, and a `503` otherwise
+    HealthReady(HttpResponseMetadata, RPCHealthReadyData),
+    PeerPolicy(HttpResponseMetadata, RPCPeerPolicyData),
+    // the client's copy of an immutable resource is current
+    NotModified(HttpResponseMetadata),
     // peer-given error responses
     BadRequest(HttpResponseMetadata, String),
     BadRequestJSON(HttpResponseMetadata, serde_json::Value),
@@ -1536,6 +3135,7 @@ pub enum HttpResponseType {
     Forbidden(HttpResponseMetadata, String),
     NotFound(HttpResponseMetadata, String),
     ServerError(HttpResponseMetadata, String),
+    TooManyRequests(HttpResponseMetadata, String),
     ServiceUnavailable(HttpResponseMetadata, String),
     Error(HttpResponseMetadata, u16, String),
 }
@@ -1568,6 +3168,17 @@ pub enum StacksMessageID {
     Pong = 16,
     NatPunchRequest = 17,
     NatPunchReply = 18,
+    CompactBlock = 19,
+    GetBlockTxs = 20,
+    BlockTxs = 21,
+    GetMempoolTxs = 22,
+    MempoolTxs = 23,
+    Compressed = 24,
+    BlocksOffer = 25,
+    BlocksOfferAccept = 26,
+    Capabilities = 27,
+    GetHeaders = 28,
+    Headers = 29,
     // reserved
     Reserved = 255,
 }
@@ -1678,6 +3289,9 @@ pub const GETPOXINV_MAX_BITLEN: u64 = 8;
 // message.
 pub const BLOCKS_PUSHED_MAX: u32 = 32;
 
+// maximum number of capability versions a peer can send
+pub const MAX_CAPABILITIES: u32 = 16;
+
 impl_byte_array_message_codec!(ConsensusHash, 20);
 impl_byte_array_message_codec!(Hash160, 20);
 impl_byte_array_message_codec!(BurnchainHeaderHash, 32);
@@ -1820,6 +3434,36 @@ pub const MAX_MICROBLOCKS_UNCONFIRMED: usize = 1024;
 // maximum number of block headers we'll get streamed to us
 pub const MAX_HEADERS: usize = 2100;
 
+// maximum number of anchored blocks (with their parent microblocks) in one block export
+pub const MAX_BLOCK_EXPORT: u32 = 8;
+
+// maximum number of microblocks in one page of the microblock stream endpoint
+pub const MAX_MICROBLOCK_STREAM_PAGE: u16 = 100;
+
+// maximum number of contract events in one page of the events endpoint
+pub const MAX_CONTRACT_EVENTS: u32 = 200;
+
+// maximum number of transactions in one page of the address transactions endpoint
+pub const MAX_ADDRESS_TRANSACTIONS: u32 = 200;
+
+// maximum number of transactions in one page of the mempool transactions endpoint
+pub const MAX_MEMPOOL_TRANSACTIONS: u32 = 200;
+
+// maximum number of trailing blocks the contract metrics endpoint sums over (about a month)
+pub const MAX_CONTRACT_METRICS_BLOCKS: u64 = 4320;
+
+// default number of trailing blocks the contract metrics endpoint sums over (about a day)
+pub const DEFAULT_CONTRACT_METRICS_BLOCKS: u64 = 144;
+
+// maximum number of contracts the contract metrics endpoint returns
+pub const MAX_CONTRACT_METRICS: u32 = 200;
+
+// maximum number of calls in one batch of read-only calls
+pub const MAX_READ_ONLY_CALL_BATCH: usize = 32;
+
+// maximum number of keys, across all maps, in one batch of map entry lookups
+pub const MAX_MAP_ENTRY_BATCH: usize = 256;
+
 // how long a peer will be denied for if it misbehaves
 #[cfg(test)]
 pub const DENY_BAN_DURATION: u64 = 30; // seconds
@@ -1845,6 +3489,8 @@ pub struct NetworkResult {
     pub num_state_machine_passes: u64,
     pub num_inv_sync_passes: u64,
     pub num_download_passes: u64,
+    pub reorgs: Vec<RPCChainReorgData>, // canonical Stacks chain reorgs we noticed
+    pub stale_tip: Option<RPCStaleTipData>, // set if our Stacks tip is stuck and we're recovering
 }
 
 impl NetworkResult {
@@ -1869,6 +3515,8 @@ impl NetworkResult {
             num_state_machine_passes: num_state_machine_passes,
             num_inv_sync_passes: num_inv_sync_passes,
             num_download_passes: num_download_passes,
+            reorgs: vec![],
+            stale_tip: None,
         }
     }
 
@@ -1892,6 +3540,14 @@ impl NetworkResult {
         self.attachments.len() > 0
     }
 
+    pub fn has_reorgs(&self) -> bool {
+        !self.reorgs.is_empty()
+    }
+
+    pub fn has_stale_tip(&self) -> bool {
+        self.stale_tip.is_some()
+    }
+
     pub fn transactions(&self) -> Vec<StacksTransaction> {
         self.pushed_transactions
             .values()
@@ -1941,6 +3597,10 @@ impl NetworkResult {
                                 .insert(neighbor_key.clone(), vec![(message.relayers, tx_data)]);
                         }
                     }
+                    StacksMessageType::MempoolTxs(mut txs_data) => {
+                        // pulled, not pushed -- store these, but don't relay them
+                        self.synced_transactions.append(&mut txs_data.txs);
+                    }
                     _ => {
                         // forward along
                         if let Some(messages) = self.unhandled_messages.get_mut(&neighbor_key) {
@@ -2330,6 +3990,8 @@ pub mod test {
         pub asn4_entries: Vec<ASEntry4>,
         pub burnchain: Burnchain,
         pub connection_opts: ConnectionOptions,
+        /// loopback address the peer is reached at
+        pub addrbytes: PeerAddress,
         pub server_port: u16,
         pub http_port: u16,
         pub asn: u32,
@@ -2340,6 +4002,8 @@ pub mod test {
         pub test_name: String,
         pub initial_balances: Vec<(PrincipalData, u64)>,
         pub initial_lockups: Vec<ChainstateAccountLockup>,
+        pub initial_namespaces: Vec<ChainstateBNSNamespace>,
+        pub initial_names: Vec<ChainstateBNSName>,
         pub spending_account: TestMiner,
         pub setup_code: String,
         pub epochs: Option<Vec<StacksEpoch>>,
@@ -2347,7 +4011,10 @@ pub mod test {
 
     impl TestPeerConfig {
         pub fn default() -> TestPeerConfig {
-            let conn_opts = ConnectionOptions::default();
+            let mut conn_opts = ConnectionOptions::default();
+            // lots of peers run at once in the test process, so don't let them shed connections
+            // because of each other's open files
+            conn_opts.open_files_reserve = 0;
             let start_block = 0;
             let mut burnchain = Burnchain::default_unittest(
                 start_block,
@@ -2378,6 +4045,7 @@ pub mod test {
                 connection_opts: conn_opts,
                 server_port: 32000,
                 http_port: 32001,
+                addrbytes: PeerAddress::from_ipv4(127, 0, 0, 1),
                 asn: 0,
                 org: 0,
                 allowed: 0,
@@ -2386,6 +4054,8 @@ pub mod test {
                 test_name: "".into(),
                 initial_balances: vec![],
                 initial_lockups: vec![],
+                initial_namespaces: vec![],
+                initial_names: vec![],
                 spending_account: spending_account,
                 setup_code: "".into(),
                 epochs: None,
@@ -2417,6 +4087,14 @@ pub mod test {
             config
         }
 
+        /// Reach this peer over the IPv6 loopback instead
+        pub fn use_ipv6_loopback(&mut self) {
+            self.addrbytes = PeerAddress::from_ip(&IpAddr::V6(Ipv6Addr::LOCALHOST));
+            self.data_url = format!("http://{}", self.addrbytes.to_socketaddr(self.http_port))
+                .as_str()
+                .into();
+        }
+
         pub fn add_neighbor(&mut self, n: &Neighbor) -> () {
             self.initial_neighbors.push(n.clone());
         }
@@ -2426,9 +4104,7 @@ pub mod test {
                 addr: NeighborKey {
                     peer_version: self.peer_version,
                     network_id: self.network_id,
-                    addrbytes: PeerAddress([
-                        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 127, 0, 0, 1,
-                    ]),
+                    addrbytes: self.addrbytes.clone(),
                     port: self.server_port,
                 },
                 public_key: Secp256k1PublicKey::from_private(&self.private_key),
@@ -2446,10 +4122,7 @@ pub mod test {
         }
 
         pub fn to_peer_host(&self) -> PeerHost {
-            PeerHost::IP(
-                PeerAddress([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 127, 0, 0, 1]),
-                self.http_port,
-            )
+            PeerHost::IP(self.addrbytes.clone(), self.http_port)
         }
     }
 
@@ -2549,7 +4222,7 @@ pub mod test {
                 config.burnchain.network_id,
                 None,
                 config.private_key_expire,
-                PeerAddress::from_ipv4(127, 0, 0, 1),
+                config.addrbytes.clone(),
                 config.server_port,
                 config.data_url.clone(),
                 &config.asn4_entries,
@@ -2634,6 +4307,18 @@ pub mod test {
                     Some(Box::new(move || Box::new(lockups.into_iter().map(|e| e))));
             }
 
+            if !config.initial_namespaces.is_empty() {
+                let namespaces = config.initial_namespaces.clone();
+                boot_data.get_bulk_initial_namespaces =
+                    Some(Box::new(move || Box::new(namespaces.into_iter())));
+            }
+
+            if !config.initial_names.is_empty() {
+                let names = config.initial_names.clone();
+                boot_data.get_bulk_initial_names =
+                    Some(Box::new(move || Box::new(names.into_iter())));
+            }
+
             let (chainstate, _) = StacksChainState::open_and_exec(
                 false,
                 config.network_id,
@@ -2678,22 +4363,17 @@ pub mod test {
                 }
             }
 
-            let local_addr =
-                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), config.server_port);
-            let http_local_addr =
-                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), config.http_port);
+            let any_addr = if config.addrbytes.is_ipv4() {
+                IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))
+            } else {
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+            };
+            let local_addr = SocketAddr::new(any_addr, config.server_port);
+            let http_local_addr = SocketAddr::new(any_addr, config.http_port);
 
             {
                 let mut tx = peerdb.tx_begin().unwrap();
-                PeerDB::set_local_ipaddr(
-                    &mut tx,
-                    &PeerAddress::from_socketaddr(&SocketAddr::new(
-                        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
-                        config.server_port,
-                    )),
-                    config.server_port,
-                )
-                .unwrap();
+                PeerDB::set_local_ipaddr(&mut tx, &config.addrbytes, config.server_port).unwrap();
                 PeerDB::set_local_private_key(
                     &mut tx,
                     &config.private_key,
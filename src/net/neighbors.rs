@@ -37,6 +37,7 @@ use util::db::DBConn;
 use util::db::DBTx;
 use util::db::Error as db_error;
 
+use util::secp256k1::Secp256k1PrivateKey;
 use util::secp256k1::Secp256k1PublicKey;
 
 use std::cmp;
@@ -70,6 +71,7 @@ pub const WALK_MIN_DURATION: u64 = 20; // minimum number of steps a walk will ta
 pub const WALK_MAX_DURATION: u64 = 40; // maximum number of steps a walk will take before we do a hard reset
 pub const WALK_RESET_PROB: f64 = 0.05; // probability of a walk reset in-between the minimum and maximum duration
 pub const WALK_STATE_TIMEOUT: u64 = 60; // how long the walk can remain in a single state before being reset
+pub const MAX_RECENT_NEIGHBOR_WALKS: usize = 16; // how many finished walks to report in /v2/neighbors/detail
 
 #[cfg(test)]
 pub const WALK_RESET_INTERVAL: u64 = 60; // how long a walk can last
@@ -277,6 +279,7 @@ pub struct NeighborWalk {
     next_neighbor: Option<Neighbor>,
     next_walk_outbound: bool,
     walk_outbound: bool, // whether or not we have an outbound connection to cur_neighbor
+    cur_neighbor_from_dns_seed: bool, // whether or not cur_neighbor came from a DNS seed, and isn't in the peer DB yet
     neighbor_from_handshake: NeighborKey,
 
     // current neighbor's frontier
@@ -352,6 +355,7 @@ impl NeighborWalk {
             next_neighbor: None,
             next_walk_outbound: true,
             walk_outbound: outbound,
+            cur_neighbor_from_dns_seed: false,
             neighbor_from_handshake: NeighborKey::empty(),
 
             frontier: HashMap::new(),
@@ -415,6 +419,7 @@ impl NeighborWalk {
         if self.cur_neighbor != next_neighbor {
             // moving on -- clear frontier
             self.frontier.clear();
+            self.cur_neighbor_from_dns_seed = false;
         }
 
         self.prev_neighbor = Some(self.cur_neighbor.clone());
@@ -579,10 +584,16 @@ impl NeighborWalk {
 
                                     Err(net_error::PeerNotConnected)
                                 } else {
-                                    // this is indeed cur_neighbor
+                                    // this is indeed cur_neighbor.  If it came from a DNS
+                                    // seed, we only just learned its public key, so add it.
                                     self.cur_neighbor
                                         .handshake_update(&mut tx, &data.handshake)?;
-                                    self.cur_neighbor.save_update(&mut tx)?;
+                                    if self.cur_neighbor_from_dns_seed {
+                                        self.cur_neighbor.save(&mut tx)?;
+                                        self.cur_neighbor_from_dns_seed = false;
+                                    } else {
+                                        self.cur_neighbor.save_update(&mut tx)?;
+                                    }
 
                                     debug!(
                                         "{:?}: Connected with {:?}",
@@ -740,9 +751,13 @@ impl NeighborWalk {
         Ok((resolved, to_resolve))
     }
 
-    /// Select neighbors that are routable, and ignore ones that are not.
+    /// Select neighbors that are routable and in an address family we connect to, and ignore
+    /// ones that are not.  Neighbors in the preferred address family come first.
     /// TODO: expand if we ever want to filter by unroutable network class or something
-    fn filter_sensible_neighbors(neighbors: Vec<NeighborAddress>) -> Vec<NeighborAddress> {
+    fn filter_sensible_neighbors(
+        neighbors: Vec<NeighborAddress>,
+        address_family: AddressFamilyPreference,
+    ) -> Vec<NeighborAddress> {
         let mut ret = vec![];
         for neighbor in neighbors.into_iter() {
             if neighbor.addrbytes.is_anynet() {
@@ -750,7 +765,7 @@ impl NeighborWalk {
             }
             ret.push(neighbor);
         }
-        ret
+        address_family.sort(ret, |neighbor| &neighbor.addrbytes)
     }
 
     /// Try to finish the getneighbors request to cur_neighbor
@@ -793,8 +808,10 @@ impl NeighborWalk {
                             "{:?}: Got Neighbors from {:?}: {:?}",
                             &self.local_peer, &self.cur_neighbor.addr, data.neighbors
                         );
-                        let neighbors =
-                            NeighborWalk::filter_sensible_neighbors(data.neighbors.clone());
+                        let neighbors = NeighborWalk::filter_sensible_neighbors(
+                            data.neighbors.clone(),
+                            network.connection_opts.address_family,
+                        );
                         let (mut found, to_resolve) = NeighborWalk::lookup_stale_neighbors(
                             network.peerdb.conn(),
                             message.preamble.network_id,
@@ -1187,8 +1204,10 @@ impl NeighborWalk {
                                     "{:?}: Got Neighbors from {:?}: {:?}",
                                     &self.local_peer, &nkey, &data.neighbors
                                 );
-                                let neighbors =
-                                    NeighborWalk::filter_sensible_neighbors(data.neighbors.clone());
+                                let neighbors = NeighborWalk::filter_sensible_neighbors(
+                                    data.neighbors.clone(),
+                                    network.connection_opts.address_family,
+                                );
                                 self.resolved_getneighbors_neighbors.insert(nkey, neighbors);
                             }
                             StacksMessageType::Nack(ref data) => {
@@ -1717,14 +1736,27 @@ impl PeerNetwork {
         block_height: u64,
     ) -> Result<Vec<Neighbor>, net_error> {
         let cur_epoch = self.get_current_epoch();
-        let neighbors = PeerDB::get_random_walk_neighbors(
-            &self.peerdb.conn(),
-            self.local_peer.network_id,
-            cur_epoch.network_epoch,
-            num_neighbors as u32,
-            block_height,
-        )
-        .map_err(net_error::DBError)?;
+        let address_family = self.connection_opts.address_family;
+
+        // if we're picky about address families, then sample more neighbors than we need so we
+        // can still find enough in the family we want
+        let num_samples = if address_family == AddressFamilyPreference::Any {
+            num_neighbors
+        } else {
+            num_neighbors * 4
+        };
+        let mut neighbors = address_family.sort(
+            PeerDB::get_random_walk_neighbors(
+                self.peerdb.conn(),
+                self.local_peer.network_id,
+                cur_epoch.network_epoch,
+                num_samples as u32,
+                block_height,
+            )
+            .map_err(net_error::DBError)?,
+            |neighbor| &neighbor.addr.addrbytes,
+        );
+        neighbors.truncate(num_neighbors as usize);
 
         if neighbors.len() == 0 {
             debug!(
@@ -1743,7 +1775,8 @@ impl PeerNetwork {
         nk: &NeighborKey,
     ) -> Result<ReplyHandleP2P, net_error> {
         // send handshake.
-        let handshake_data = HandshakeData::from_local_peer(&self.local_peer);
+        let handshake_data =
+            HandshakeData::from_local_peer_for(&self.local_peer, Some(&nk.addrbytes));
 
         debug!("{:?}: send Handshake to {:?}", &self.local_peer, &nk);
         walk.connecting.remove(nk);
@@ -1875,6 +1908,40 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Instantiate the neighbor walk from a peer that one of our DNS seeds gave us.  We don't
+    /// know its public key until it handshakes with us, so it gets a throwaway one until then.
+    fn instantiate_walk_from_dns_seed(&mut self) -> Result<(), net_error> {
+        while let Some(nk) = self.dns_seed_peers.pop_front() {
+            if self.events.contains_key(&nk) || self.is_bound(&nk) {
+                continue;
+            }
+            if PeerDB::is_peer_denied(self.peerdb.conn(), nk.network_id, &nk.addrbytes, nk.port)? {
+                debug!("{:?}: DNS seed peer {:?} is denied", &self.local_peer, &nk);
+                continue;
+            }
+
+            let placeholder_key = Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new());
+            let seed_neighbor = Neighbor::empty(&nk, &placeholder_key, 0);
+            let mut w = NeighborWalk::new(
+                self.local_peer.clone(),
+                self.chain_view.clone(),
+                &seed_neighbor,
+                true,
+                self.walk_pingbacks.clone(),
+                &self.connection_opts,
+            );
+            w.cur_neighbor_from_dns_seed = true;
+
+            debug!(
+                "{:?}: instantiated neighbor walk to DNS seed peer {:?}",
+                &self.local_peer, &nk
+            );
+            self.walk = Some(w);
+            return Ok(());
+        }
+        Err(net_error::NoSuchNeighbor)
+    }
+
     /// Is the network connected to always-allowed peers?
     /// Returns (count, total)
     fn count_connected_always_allowed_peers(&self) -> Result<(u64, u64), net_error> {
@@ -2680,7 +2747,8 @@ impl PeerNetwork {
                     nk
                 );
 
-                let handshake_data = HandshakeData::from_local_peer(&walk.local_peer);
+                let handshake_data =
+                    HandshakeData::from_local_peer_for(&walk.local_peer, Some(&nk.addrbytes));
                 let msg =
                     network.sign_for_peer(nk, StacksMessageType::Handshake(handshake_data))?;
                 let req_res = network.send_message(nk, msg, network.connection_opts.timeout);
@@ -2807,6 +2875,16 @@ impl PeerNetwork {
             // always ensure we're connected to always-allowed outbound peers
             let walk_res = match self.instantiate_walk_to_always_allowed(ibd) {
                 Ok(x) => Ok(x),
+                Err(net_error::NotFoundError)
+                    if PeerNetwork::count_outbound_conversations(&self.peers) == 0
+                        && !self.dns_seed_peers.is_empty() =>
+                {
+                    // bootstrap from our DNS seeds before falling back to our initial peers
+                    match self.instantiate_walk_from_dns_seed() {
+                        Err(net_error::NoSuchNeighbor) => self.instantiate_walk(),
+                        res => res,
+                    }
+                }
                 Err(net_error::NotFoundError) => {
                     if self.walk_attempts % (self.connection_opts.walk_inbound_ratio + 1) == 0 {
                         self.instantiate_walk()
@@ -3097,8 +3175,56 @@ mod test {
     use util::sleep_ms;
     use util::test::*;
 
+    use std::net::{IpAddr, Ipv6Addr};
+
     const TEST_IN_OUT_DEGREES: u64 = 0x1;
 
+    #[test]
+    fn test_filter_sensible_neighbors_address_family() {
+        let ipv4 = NeighborAddress {
+            addrbytes: PeerAddress::from_ipv4(1, 2, 3, 4),
+            port: 20444,
+            public_key_hash: Hash160([0x11; 20]),
+        };
+        let ipv6 = NeighborAddress {
+            addrbytes: PeerAddress::from_ip(&IpAddr::V6(Ipv6Addr::new(
+                0x2001, 0xdb8, 0, 0, 0, 0, 0, 1,
+            ))),
+            port: 20444,
+            public_key_hash: Hash160([0x22; 20]),
+        };
+        let anynet = NeighborAddress {
+            addrbytes: PeerAddress([0u8; 16]),
+            port: 20444,
+            public_key_hash: Hash160([0x33; 20]),
+        };
+        let neighbors = vec![ipv4.clone(), anynet.clone(), ipv6.clone()];
+
+        let filter = |address_family| {
+            NeighborWalk::filter_sensible_neighbors(neighbors.clone(), address_family)
+        };
+        assert_eq!(
+            filter(AddressFamilyPreference::Any),
+            vec![ipv4.clone(), ipv6.clone()]
+        );
+        assert_eq!(
+            filter(AddressFamilyPreference::PreferIPv4),
+            vec![ipv4.clone(), ipv6.clone()]
+        );
+        assert_eq!(
+            filter(AddressFamilyPreference::PreferIPv6),
+            vec![ipv6.clone(), ipv4.clone()]
+        );
+        assert_eq!(
+            filter(AddressFamilyPreference::OnlyIPv4),
+            vec![ipv4.clone()]
+        );
+        assert_eq!(
+            filter(AddressFamilyPreference::OnlyIPv6),
+            vec![ipv6.clone()]
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_step_walk_1_neighbor_plain() {
@@ -3215,6 +3341,52 @@ mod test {
         })
     }
 
+    #[test]
+    #[ignore]
+    fn test_step_walk_1_neighbor_dns_seed() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(33000);
+            let peer_2_config = TestPeerConfig::from_port(33002);
+
+            // peer 1 knows no one, but its DNS seed points at peer 2
+            peer_1_config.connection_opts.dns_seeds =
+                vec![format!("127.0.0.1:{}", peer_2_config.server_port)];
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+
+            let (mut dns_client, dns_thread_handle) = dns_thread_start(100);
+
+            let neighbor_2 = peer_2.to_neighbor();
+            let mut i = 0;
+            loop {
+                let _ = peer_1.step_dns(&mut dns_client);
+                let _ = peer_2.step();
+
+                // peer 2 is in peer 1's frontier DB, with its real public key
+                if let Some(p) = PeerDB::get_peer(
+                    peer_1.get_peerdb_conn(),
+                    neighbor_2.addr.network_id,
+                    &neighbor_2.addr.addrbytes,
+                    neighbor_2.addr.port,
+                )
+                .unwrap()
+                {
+                    assert_eq!(p.public_key, neighbor_2.public_key);
+                    assert_eq!(p.expire_block, neighbor_2.expire_block);
+                    break;
+                }
+
+                i += 1;
+            }
+
+            debug!("Bootstrapped from DNS seed after {} step(s)", i);
+            assert!(peer_1.network.dns_seed_peers.is_empty());
+
+            dns_thread_shutdown(dns_client, dns_thread_handle);
+        })
+    }
+
     #[test]
     #[ignore]
     fn test_step_walk_1_neighbor_plain_no_natpunch() {
@@ -3324,6 +3496,63 @@ mod test {
         })
     }
 
+    #[test]
+    #[ignore]
+    fn test_step_walk_1_neighbor_ipv6() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(32750);
+            let mut peer_2_config = TestPeerConfig::from_port(32752);
+            peer_1_config.use_ipv6_loopback();
+            peer_2_config.use_ipv6_loopback();
+
+            // peer 1 only talks IPv6, and crawls peer 2
+            peer_1_config.connection_opts.address_family = AddressFamilyPreference::OnlyIPv6;
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+
+            let mut i = 0;
+            while !peer_1.network.public_ip_confirmed
+                || peer_1
+                    .network
+                    .get_neighbor_stats(&peer_2.to_neighbor().addr)
+                    .is_none()
+            {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+                i += 1;
+            }
+
+            debug!("Completed walk round {} step(s)", i);
+
+            peer_1.dump_frontier();
+            peer_2.dump_frontier();
+
+            // peer 2 is in peer 1's frontier DB under its IPv6 address
+            let neighbor_2 = peer_2.to_neighbor();
+            assert!(!neighbor_2.addr.addrbytes.is_ipv4());
+            let p = PeerDB::get_peer(
+                peer_1.get_peerdb_conn(),
+                neighbor_2.addr.network_id,
+                &neighbor_2.addr.addrbytes,
+                neighbor_2.addr.port,
+            )
+            .unwrap()
+            .unwrap();
+            assert_eq!(p.public_key, neighbor_2.public_key);
+
+            // peer 1 learned and confirmed its public IPv6 address from peer 2
+            assert_eq!(
+                peer_1.network.local_peer.public_ip_address.clone().unwrap(),
+                (
+                    PeerAddress::from_ip(&IpAddr::V6(Ipv6Addr::LOCALHOST)),
+                    peer_1.config.server_port
+                )
+            );
+        })
+    }
+
     #[test]
     #[ignore]
     fn test_step_walk_1_neighbor_denied() {
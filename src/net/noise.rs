@@ -0,0 +1,740 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encrypted p2p transport.  A peer that opts in opens its outbound connections with a Noise
+//! handshake, prefixed with `NOISE_MAGIC` so the remote peer can tell it apart from a plaintext
+//! preamble.  Every byte after the handshake is sent in encrypted, authenticated frames.
+//!
+//! Noise alone can't tell who is on the other end, so the first frame each side sends signs the
+//! Noise handshake hash with its node key.  A conversation then only accepts a Stacks handshake
+//! made with that same key, which ties the encrypted channel to the peer it authenticated.
+
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+
+use burnchains::PrivateKey;
+use net::socks::Socks5Handshake;
+use net::Error as net_error;
+use util::secp256k1::{MessageSignature, Secp256k1PrivateKey, Secp256k1PublicKey};
+
+/// First bytes an encrypting initiator sends.  No valid preamble starts with a zero major version.
+pub const NOISE_MAGIC: [u8; 4] = [0x00, 0x4e, 0x5a, 0x01];
+
+const NOISE_PARAMS: &str = "Noise_NN_25519_ChaChaPoly_BLAKE2s";
+
+/// Largest Noise message, including the authentication tag
+const MAX_NOISE_MESSAGE_LEN: usize = 65535;
+const NOISE_TAG_LEN: usize = 16;
+const MAX_FRAME_PAYLOAD_LEN: usize = MAX_NOISE_MESSAGE_LEN - NOISE_TAG_LEN;
+
+enum NoiseState {
+    /// bytes go over the wire as-is
+    Plaintext,
+    /// waiting to see whether or not an inbound connection starts with `NOISE_MAGIC`
+    Detecting,
+    Handshaking(Box<snow::HandshakeState>),
+    Transport(Box<snow::TransportState>),
+}
+
+/// Encryption state for one p2p connection
+pub struct P2PTransport {
+    state: NoiseState,
+    /// SOCKS5 handshake with our proxy, which has to finish before anything else is sent
+    socks: Option<Box<Socks5Handshake>>,
+    private_key: Option<Secp256k1PrivateKey>,
+    require_encryption: bool,
+    handshake_hash: Vec<u8>,
+    remote_public_key: Option<Secp256k1PublicKey>,
+    remote_public_key_checked: bool,
+    /// bytes read from the socket, but not yet decrypted
+    recv_buf: Vec<u8>,
+    /// decrypted bytes not yet consumed
+    plaintext: Vec<u8>,
+    plaintext_ptr: usize,
+    /// bytes not yet written to the socket
+    send_buf: Vec<u8>,
+    eof: bool,
+}
+
+impl fmt::Debug for P2PTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.state {
+            NoiseState::Plaintext => "plaintext",
+            NoiseState::Detecting => "detecting",
+            NoiseState::Handshaking(_) => "handshaking",
+            NoiseState::Transport(_) => "encrypted",
+        };
+        if self.socks.is_some() {
+            write!(f, "P2PTransport(socks5,{})", state)
+        } else {
+            write!(f, "P2PTransport({})", state)
+        }
+    }
+}
+
+fn noise_error(e: snow::Error) -> net_error {
+    net_error::NoiseError(format!("{}", &e))
+}
+
+/// Append a length-prefixed frame to `buf`
+fn push_frame(buf: &mut Vec<u8>, frame: &[u8]) {
+    buf.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+    buf.extend_from_slice(frame);
+}
+
+/// Take the next whole length-prefixed frame off the front of `buf`, if there is one
+fn take_frame(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    if buf.len() < 2 {
+        return None;
+    }
+    let len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+    if buf.len() < 2 + len {
+        return None;
+    }
+    let frame = buf[2..2 + len].to_vec();
+    buf.drain(0..2 + len);
+    Some(frame)
+}
+
+impl P2PTransport {
+    fn new(state: NoiseState, private_key: Option<Secp256k1PrivateKey>) -> P2PTransport {
+        P2PTransport {
+            state,
+            socks: None,
+            private_key,
+            require_encryption: false,
+            handshake_hash: vec![],
+            remote_public_key: None,
+            remote_public_key_checked: false,
+            recv_buf: vec![],
+            plaintext: vec![],
+            plaintext_ptr: 0,
+            send_buf: vec![],
+            eof: false,
+        }
+    }
+
+    /// A connection that isn't encrypted
+    pub fn plaintext() -> P2PTransport {
+        P2PTransport::new(NoiseState::Plaintext, None)
+    }
+
+    /// An outbound connection that starts with a Noise handshake
+    pub fn initiator(private_key: &Secp256k1PrivateKey) -> Result<P2PTransport, net_error> {
+        let mut handshake = snow::Builder::new(NOISE_PARAMS.parse().map_err(noise_error)?)
+            .prologue(&NOISE_MAGIC)
+            .build_initiator()
+            .map_err(noise_error)?;
+
+        let mut msg = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        let len = handshake
+            .write_message(&[], &mut msg)
+            .map_err(noise_error)?;
+
+        let mut transport = P2PTransport::new(
+            NoiseState::Handshaking(Box::new(handshake)),
+            Some(private_key.clone()),
+        );
+        transport.send_buf.extend_from_slice(&NOISE_MAGIC);
+        push_frame(&mut transport.send_buf, &msg[0..len]);
+        Ok(transport)
+    }
+
+    /// An inbound connection, which is encrypted if the remote peer starts a Noise handshake.
+    /// If `require_encryption` is set, connections that don't are refused.
+    pub fn responder(private_key: &Secp256k1PrivateKey, require_encryption: bool) -> P2PTransport {
+        let mut transport = P2PTransport::new(NoiseState::Detecting, Some(private_key.clone()));
+        transport.require_encryption = require_encryption;
+        transport
+    }
+
+    /// Go through a SOCKS5 proxy first.  Nothing else is sent until the proxy has connected us.
+    pub fn with_socks5(mut self, handshake: Socks5Handshake) -> P2PTransport {
+        self.socks = Some(Box::new(handshake));
+        self
+    }
+
+    /// Are we still waiting for the proxy to connect us?
+    pub fn is_proxy_handshaking(&self) -> bool {
+        self.socks.is_some()
+    }
+
+    /// Is this connection sent in the clear?
+    pub fn is_plaintext(&self) -> bool {
+        match self.state {
+            NoiseState::Plaintext => true,
+            _ => false,
+        }
+    }
+
+    /// Has the Noise handshake finished?
+    pub fn is_encrypted(&self) -> bool {
+        match self.state {
+            NoiseState::Transport(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Is the Noise handshake still under way?
+    pub fn is_handshaking(&self) -> bool {
+        match self.state {
+            NoiseState::Handshaking(_) => self.socks.is_none(),
+            _ => false,
+        }
+    }
+
+    /// The node key the remote peer signed the Noise handshake with
+    pub fn remote_public_key(&self) -> Option<&Secp256k1PublicKey> {
+        self.remote_public_key.as_ref()
+    }
+
+    /// Make sure the remote peer's first Stacks handshake is made with the key it signed the
+    /// Noise handshake with.  Later handshakes are re-keys, which the old key authenticates.
+    pub fn check_remote_public_key(
+        &mut self,
+        public_key: &Secp256k1PublicKey,
+    ) -> Result<(), net_error> {
+        if self.is_plaintext() || self.remote_public_key_checked {
+            return Ok(());
+        }
+        match self.remote_public_key {
+            Some(ref bound_key)
+                if bound_key.to_bytes_compressed() == public_key.to_bytes_compressed() =>
+            {
+                self.remote_public_key_checked = true;
+                Ok(())
+            }
+            _ => Err(net_error::InvalidHandshake),
+        }
+    }
+
+    /// Finish the handshake, and sign it with our node key
+    fn begin_transport(
+        &mut self,
+        handshake: snow::HandshakeState,
+    ) -> Result<NoiseState, net_error> {
+        self.handshake_hash = handshake.get_handshake_hash().to_vec();
+        let mut transport = handshake.into_transport_mode().map_err(noise_error)?;
+
+        let private_key = self
+            .private_key
+            .as_ref()
+            .ok_or_else(|| net_error::NoiseError("No node key to sign with".to_string()))?;
+        let sig = private_key
+            .sign(&self.handshake_hash)
+            .map_err(|e| net_error::NoiseError(e.to_string()))?;
+
+        let mut msg = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        let len = transport
+            .write_message(sig.as_bytes(), &mut msg)
+            .map_err(noise_error)?;
+        push_frame(&mut self.send_buf, &msg[0..len]);
+
+        Ok(NoiseState::Transport(Box::new(transport)))
+    }
+
+    /// Advance the state machine with whatever is in `recv_buf`
+    fn process_input(&mut self) -> Result<(), net_error> {
+        let mut msg = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        loop {
+            let state = mem::replace(&mut self.state, NoiseState::Plaintext);
+            self.state = match state {
+                NoiseState::Plaintext => {
+                    self.plaintext.append(&mut self.recv_buf);
+                    return Ok(());
+                }
+                NoiseState::Detecting => {
+                    if self.recv_buf.len() < NOISE_MAGIC.len() {
+                        self.state = NoiseState::Detecting;
+                        return Ok(());
+                    }
+                    if self.recv_buf[0..NOISE_MAGIC.len()] == NOISE_MAGIC {
+                        self.recv_buf.drain(0..NOISE_MAGIC.len());
+                        let handshake =
+                            snow::Builder::new(NOISE_PARAMS.parse().map_err(noise_error)?)
+                                .prologue(&NOISE_MAGIC)
+                                .build_responder()
+                                .map_err(noise_error)?;
+                        NoiseState::Handshaking(Box::new(handshake))
+                    } else if self.require_encryption {
+                        debug!("Refusing plaintext p2p connection");
+                        return Err(net_error::NoiseError(
+                            "Peer did not start an encrypted session".to_string(),
+                        ));
+                    } else {
+                        NoiseState::Plaintext
+                    }
+                }
+                NoiseState::Handshaking(mut handshake) => {
+                    let frame = match take_frame(&mut self.recv_buf) {
+                        Some(frame) => frame,
+                        None => {
+                            self.state = NoiseState::Handshaking(handshake);
+                            return Ok(());
+                        }
+                    };
+                    handshake
+                        .read_message(&frame, &mut msg)
+                        .map_err(noise_error)?;
+                    if !handshake.is_initiator() {
+                        let len = handshake
+                            .write_message(&[], &mut msg)
+                            .map_err(noise_error)?;
+                        push_frame(&mut self.send_buf, &msg[0..len]);
+                    }
+                    if handshake.is_handshake_finished() {
+                        self.begin_transport(*handshake)?
+                    } else {
+                        NoiseState::Handshaking(handshake)
+                    }
+                }
+                NoiseState::Transport(mut transport) => {
+                    let frame = match take_frame(&mut self.recv_buf) {
+                        Some(frame) => frame,
+                        None => {
+                            self.state = NoiseState::Transport(transport);
+                            return Ok(());
+                        }
+                    };
+                    let len = transport
+                        .read_message(&frame, &mut msg)
+                        .map_err(noise_error)?;
+                    if self.remote_public_key.is_none() {
+                        // the first frame is the remote peer's signature over the handshake
+                        let sig = MessageSignature::from_bytes(&msg[0..len]).ok_or_else(|| {
+                            net_error::NoiseError("Invalid handshake signature".to_string())
+                        })?;
+                        let public_key =
+                            Secp256k1PublicKey::recover_to_pubkey(&self.handshake_hash, &sig)
+                                .map_err(|e| net_error::NoiseError(e.to_string()))?;
+                        self.remote_public_key = Some(public_key);
+                    } else {
+                        self.plaintext.extend_from_slice(&msg[0..len]);
+                    }
+                    NoiseState::Transport(transport)
+                }
+            };
+        }
+    }
+
+    /// Write out as much of `send_buf` as the socket will take
+    pub fn flush_to<W: Write>(&mut self, sock: &mut W) -> io::Result<()> {
+        if let Some(ref mut socks) = self.socks {
+            // everything else waits for the proxy
+            return socks.flush_to(sock);
+        }
+        while !self.send_buf.is_empty() {
+            let len = sock.write(&self.send_buf)?;
+            if len == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero));
+            }
+            self.send_buf.drain(0..len);
+        }
+        Ok(())
+    }
+
+    /// Read decrypted bytes from the socket
+    fn read_from<R: Read>(&mut self, sock: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(ref mut socks) = self.socks {
+                let mut sock_buf = [0u8; 512];
+                let len = sock.read(&mut sock_buf)?;
+                if len == 0 {
+                    return Ok(0);
+                }
+                socks
+                    .recv(&sock_buf[0..len])
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", &e)))?;
+                if !socks.is_connected() {
+                    continue;
+                }
+                debug!("Proxy connected us to {}", socks.target());
+                self.recv_buf.append(&mut socks.take_leftover());
+            }
+            if self.socks.take().is_some() {
+                self.process_input()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", &e)))?;
+                continue;
+            }
+
+            if self.plaintext_ptr < self.plaintext.len() {
+                let len = cmp::min(buf.len(), self.plaintext.len() - self.plaintext_ptr);
+                buf[0..len]
+                    .copy_from_slice(&self.plaintext[self.plaintext_ptr..self.plaintext_ptr + len]);
+                self.plaintext_ptr += len;
+                if self.plaintext_ptr >= self.plaintext.len() {
+                    self.plaintext.clear();
+                    self.plaintext_ptr = 0;
+                }
+                return Ok(len);
+            }
+            if self.is_plaintext() {
+                return sock.read(buf);
+            }
+            if self.eof {
+                return Ok(0);
+            }
+
+            let mut sock_buf = [0u8; 4096];
+            let len = sock.read(&mut sock_buf)?;
+            if len == 0 {
+                self.eof = true;
+                continue;
+            }
+            self.recv_buf.extend_from_slice(&sock_buf[0..len]);
+            self.process_input()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", &e)))?;
+        }
+    }
+
+    /// Encrypt and write bytes to the socket
+    fn write_to<W: Write>(&mut self, sock: &mut W, buf: &[u8]) -> io::Result<usize> {
+        self.flush_to(sock)?;
+        if self.socks.is_some() {
+            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        }
+        let transport = match self.state {
+            NoiseState::Plaintext => {
+                return sock.write(buf);
+            }
+            NoiseState::Detecting | NoiseState::Handshaking(_) => {
+                // nothing can be sent until we know how to send it
+                return Err(io::Error::from(io::ErrorKind::WouldBlock));
+            }
+            NoiseState::Transport(ref mut transport) => transport,
+        };
+
+        let len = cmp::min(buf.len(), MAX_FRAME_PAYLOAD_LEN);
+        let mut msg = vec![0u8; MAX_NOISE_MESSAGE_LEN];
+        let msg_len = transport
+            .write_message(&buf[0..len], &mut msg)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", &e)))?;
+        push_frame(&mut self.send_buf, &msg[0..msg_len]);
+
+        // the frame is ours to send now, even if the socket is full
+        match self.flush_to(sock) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => {
+                return Err(e);
+            }
+        }
+        Ok(len)
+    }
+
+    /// Read and write plaintext over `sock` through this transport
+    pub fn stream<'a, S>(&'a mut self, sock: &'a mut S) -> P2PTransportStream<'a, S> {
+        P2PTransportStream {
+            transport: self,
+            sock,
+        }
+    }
+}
+
+/// A socket, as seen through a `P2PTransport`
+pub struct P2PTransportStream<'a, S> {
+    transport: &'a mut P2PTransport,
+    sock: &'a mut S,
+}
+
+impl<'a, S: Read> Read for P2PTransportStream<'a, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.transport.read_from(self.sock, buf)
+    }
+}
+
+impl<'a, S: Write> Write for P2PTransportStream<'a, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.transport.write_to(self.sock, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.transport.flush_to(self.sock)?;
+        self.sock.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::socks::Socks5Target;
+    use std::collections::VecDeque;
+
+    /// One direction of an in-memory, non-blocking socket
+    #[derive(Default)]
+    struct Pipe {
+        bytes: VecDeque<u8>,
+        closed: bool,
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.bytes.is_empty() {
+                if self.closed {
+                    return Ok(0);
+                }
+                return Err(io::Error::from(io::ErrorKind::WouldBlock));
+            }
+            let len = cmp::min(buf.len(), self.bytes.len());
+            for (i, b) in self.bytes.drain(0..len).enumerate() {
+                buf[i] = b;
+            }
+            Ok(len)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.bytes.extend(buf.iter());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Move data back and forth until both sides have nothing more to say
+    fn exchange(
+        initiator: &mut P2PTransport,
+        responder: &mut P2PTransport,
+        to_responder: &mut Pipe,
+        to_initiator: &mut Pipe,
+    ) {
+        let mut buf = [0u8; 1024];
+        for _ in 0..4 {
+            initiator.flush_to(to_responder).unwrap();
+            let _ = responder.read_from(to_responder, &mut buf[0..0]);
+            responder.flush_to(to_initiator).unwrap();
+            let _ = initiator.read_from(to_initiator, &mut buf[0..0]);
+        }
+    }
+
+    fn read_all<R: Read>(r: &mut R) -> Vec<u8> {
+        let mut out = vec![];
+        let mut buf = [0u8; 1000];
+        loop {
+            match r.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => out.extend_from_slice(&buf[0..len]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => panic!("{:?}", &e),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_noise_transport() {
+        let initiator_key = Secp256k1PrivateKey::new();
+        let responder_key = Secp256k1PrivateKey::new();
+        let mut initiator = P2PTransport::initiator(&initiator_key).unwrap();
+        let mut responder = P2PTransport::responder(&responder_key, false);
+        let mut to_responder = Pipe::default();
+        let mut to_initiator = Pipe::default();
+
+        // nothing goes out until the handshake is done
+        assert_eq!(
+            initiator
+                .stream(&mut to_responder)
+                .write(b"hello")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(
+            &to_responder
+                .bytes
+                .iter()
+                .take(4)
+                .cloned()
+                .collect::<Vec<u8>>()[..],
+            &NOISE_MAGIC[..]
+        );
+
+        exchange(
+            &mut initiator,
+            &mut responder,
+            &mut to_responder,
+            &mut to_initiator,
+        );
+        assert!(initiator.is_encrypted());
+        assert!(responder.is_encrypted());
+
+        // each side learned the other's node key
+        assert_eq!(
+            initiator.remote_public_key(),
+            Some(&Secp256k1PublicKey::from_private(&responder_key))
+        );
+        assert_eq!(
+            responder.remote_public_key(),
+            Some(&Secp256k1PublicKey::from_private(&initiator_key))
+        );
+        assert!(responder
+            .check_remote_public_key(&Secp256k1PublicKey::from_private(&responder_key))
+            .is_err());
+        responder
+            .check_remote_public_key(&Secp256k1PublicKey::from_private(&initiator_key))
+            .unwrap();
+
+        // data goes both ways, and is not sent in the clear
+        let big: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let mut written = 0;
+        while written < big.len() {
+            written += initiator
+                .stream(&mut to_responder)
+                .write(&big[written..])
+                .unwrap();
+        }
+        responder
+            .stream(&mut to_initiator)
+            .write_all(b"hello back")
+            .unwrap();
+        assert!(!to_initiator
+            .bytes
+            .iter()
+            .cloned()
+            .collect::<Vec<u8>>()
+            .windows(10)
+            .any(|w| w == b"hello back"));
+
+        assert_eq!(read_all(&mut responder.stream(&mut to_responder)), big);
+        assert_eq!(
+            read_all(&mut initiator.stream(&mut to_initiator)),
+            b"hello back".to_vec()
+        );
+
+        // tampering is detected
+        responder
+            .stream(&mut to_initiator)
+            .write_all(b"tamper")
+            .unwrap();
+        let last = to_initiator.bytes.len() - 1;
+        to_initiator.bytes[last] ^= 0x01;
+        assert_eq!(
+            initiator
+                .stream(&mut to_initiator)
+                .read(&mut [0u8; 100])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_noise_transport_through_socks5() {
+        let initiator_key = Secp256k1PrivateKey::new();
+        let responder_key = Secp256k1PrivateKey::new();
+        let target = Socks5Target::Domain("example.onion".to_string(), 20444);
+        let mut initiator = P2PTransport::initiator(&initiator_key)
+            .unwrap()
+            .with_socks5(Socks5Handshake::new(target, None).unwrap());
+        let mut responder = P2PTransport::responder(&responder_key, false);
+        let mut to_responder = Pipe::default();
+        let mut to_initiator = Pipe::default();
+        let mut buf = [0u8; 1024];
+
+        // only the proxy greeting goes out at first
+        assert!(initiator.is_proxy_handshaking());
+        assert!(!initiator.is_handshaking());
+        assert_eq!(
+            initiator
+                .stream(&mut to_responder)
+                .write(b"hello")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(read_all(&mut to_responder), vec![0x05, 0x01, 0x00]);
+
+        // proxy picks no auth, and the initiator asks for the target
+        to_initiator.write_all(&[0x05, 0x00]).unwrap();
+        let _ = initiator.read_from(&mut to_initiator, &mut buf[0..0]);
+        initiator.flush_to(&mut to_responder).unwrap();
+        let mut connect = vec![0x05, 0x01, 0x00, 0x03, 13];
+        connect.extend_from_slice(b"example.onion");
+        connect.extend_from_slice(&20444u16.to_be_bytes());
+        assert_eq!(read_all(&mut to_responder), connect);
+
+        // proxy connects us, and the Noise handshake runs over the spliced connection
+        to_initiator
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        let _ = initiator.read_from(&mut to_initiator, &mut buf[0..0]);
+        assert!(!initiator.is_proxy_handshaking());
+
+        exchange(
+            &mut initiator,
+            &mut responder,
+            &mut to_responder,
+            &mut to_initiator,
+        );
+        assert!(initiator.is_encrypted());
+        assert!(responder.is_encrypted());
+
+        initiator
+            .stream(&mut to_responder)
+            .write_all(b"hello")
+            .unwrap();
+        assert_eq!(
+            read_all(&mut responder.stream(&mut to_responder)),
+            b"hello".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_noise_transport_plaintext_fallback() {
+        let responder_key = Secp256k1PrivateKey::new();
+
+        // a legacy peer's bytes are passed through as-is
+        let mut responder = P2PTransport::responder(&responder_key, false);
+        let mut to_responder = Pipe::default();
+        to_responder
+            .write_all(&[0x18, 0x00, 0x00, 0x00, 0x01, 0x02])
+            .unwrap();
+        to_responder.closed = true;
+        assert_eq!(
+            read_all(&mut responder.stream(&mut to_responder)),
+            vec![0x18, 0x00, 0x00, 0x00, 0x01, 0x02]
+        );
+        assert!(responder.is_plaintext());
+
+        let mut to_initiator = Pipe::default();
+        responder
+            .stream(&mut to_initiator)
+            .write_all(b"hello")
+            .unwrap();
+        assert_eq!(read_all(&mut to_initiator), b"hello".to_vec());
+
+        // ...unless encryption is required
+        let mut responder = P2PTransport::responder(&responder_key, true);
+        let mut to_responder = Pipe::default();
+        to_responder
+            .write_all(&[0x18, 0x00, 0x00, 0x00, 0x01, 0x02])
+            .unwrap();
+        assert_eq!(
+            responder
+                .stream(&mut to_responder)
+                .read(&mut [0u8; 100])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -24,6 +24,8 @@ use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::Path;
 use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::RecvError;
@@ -47,12 +49,18 @@ use chainstate::burn::db::sortdb::{BlockHeaderCache, SortitionDB};
 use chainstate::burn::BlockSnapshot;
 use chainstate::stacks::db::StacksChainState;
 use chainstate::stacks::{MAX_BLOCK_LEN, MAX_TRANSACTION_LEN};
-use monitoring::{update_inbound_neighbors, update_outbound_neighbors};
+use monitoring::{
+    increment_connections_refused_counter, remove_p2p_peer_metrics, update_inbound_neighbors,
+    update_outbound_neighbors,
+};
 use net::asn::ASEntry4;
 use net::atlas::AtlasDB;
 use net::atlas::{AttachmentInstance, AttachmentsDownloader};
+use net::blockoffer::PendingBlocksOffer;
+use net::capabilities::Capability;
 use net::chat::ConversationP2P;
 use net::chat::NeighborStats;
+use net::compact::PendingCompactBlock;
 use net::connection::ConnectionOptions;
 use net::connection::NetworkReplyHandle;
 use net::connection::ReplyHandleP2P;
@@ -61,21 +69,33 @@ use net::db::PeerDB;
 use net::download::BlockDownloader;
 use net::inv::*;
 use net::neighbors::*;
+use net::noise::P2PTransport;
 use net::poll::NetworkPollState;
 use net::poll::NetworkState;
+use net::portmap::{PortMapper, PortMappingMethod};
 use net::prune::*;
+use net::ratelimit::{EgressLimiter, HandshakeRateLimiter, RPCRateLimiter};
 use net::relay::RelayerStats;
 use net::relay::*;
 use net::relay::*;
+use net::relaypolicy::MicroblockRelayPolicy;
+use net::reputation::{PeerMisbehavior, PeerReputations};
+use net::response_cache::ResponseCache;
 use net::rpc::RPCHandlerArgs;
 use net::server::*;
+use net::socks::{Socks5Handshake, Socks5Target};
+use net::warmstart::PeerWarmStart;
+use net::watchdog::StaleTipWatchdog;
+use net::ws::WebSocketEventReceiver;
 use net::Error as net_error;
 use net::Neighbor;
 use net::NeighborKey;
 use net::PeerAddress;
 use net::*;
+use util::bloom::{BloomNodeHasher, RollingBloomFilter};
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::fdlimit::FileDescriptorUsage;
 use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
@@ -280,6 +300,7 @@ pub struct PeerNetwork {
     pub walk_total_step_count: u64,
     pub walk_pingbacks: HashMap<NeighborAddress, NeighborPingback>, // inbound peers for us to try to ping back and add to our frontier, mapped to (peer_version, network_id, timeout, pubkey)
     pub walk_result: NeighborWalkResult, // last successful neighbor walk result
+    pub recent_walks: VecDeque<RPCNeighborWalkData>, // last few walk results, for /v2/neighbors/detail
 
     // peer block inventory state
     pub inv_state: Option<InvState>,
@@ -305,6 +326,11 @@ pub struct PeerNetwork {
     mempool_state: MempoolSyncState,
     mempool_sync_deadline: u64,
     mempool_sync_timeout: u64,
+    // data URLs of the peers we've completed a mempool sync with since we booted.  Until we've
+    // synced with mempool_sync_bootstrap_peers of them (or run out of peers to try), we don't
+    // wait in-between mempool syncs.
+    mempool_sync_bootstrap_urls: HashSet<UrlString>,
+    mempool_sync_bootstrapped: bool,
 
     // how often we pruned a given inbound/outbound peer
     pub prune_outbound_counts: HashMap<NeighborKey, u64>,
@@ -324,6 +350,15 @@ pub struct PeerNetwork {
     public_ip_reply_handle: Option<ReplyHandleP2P>,
     public_ip_retries: u64,
 
+    // bootstrap peers from our DNS seeds that we haven't tried yet, our lookups of those seeds
+    // that are still in-flight, and when we last looked them up
+    pub dns_seed_peers: VecDeque<NeighborKey>,
+    pub dns_seed_requests: Vec<DNSRequest>,
+    pub dns_seeds_resolved_at: u64,
+
+    // keeps our p2p port mapped on the gateway, if we were asked to
+    port_mapper: Option<PortMapper>,
+
     // how many loops of the state-machine have occured?
     // Used to coordinate with the chain synchronization logic to ensure that the node has at least
     // begun to download blocks after fetching the next reward cycles' sortitions.
@@ -346,6 +381,66 @@ pub struct PeerNetwork {
     // can't process yet, but might be able to process on the next chain view update
     pub pending_messages: HashMap<usize, Vec<StacksMessage>>,
 
+    // open file descriptors as of the last check, and how many we're allowed to have
+    pub open_files: FileDescriptorUsage,
+    pub open_files_limit: u64,
+    pub open_files_last_checked_ms: u128,
+
+    // recently-served contract sources, keyed by ETag
+    pub http_response_cache: ResponseCache<ContractSrcResponse>,
+
+    // API-key authentication and rate limiting for RPC requests
+    pub rpc_rate_limiter: RPCRateLimiter,
+
+    // how well each neighbor has behaved recently
+    pub reputations: PeerReputations,
+
+    // how fast we may push data to our neighbors
+    pub egress_limiter: EgressLimiter,
+
+    // how often each IP address may connect to us
+    pub handshake_limiter: HandshakeRateLimiter,
+
+    // neighbors that hung up on our Noise handshake, which we talk to in plaintext instead
+    pub plaintext_peers: HashSet<NeighborKey>,
+
+    // compact blocks we're rebuilding, waiting on their missing transactions
+    pub pending_compact_blocks: HashMap<StacksBlockId, PendingCompactBlock>,
+
+    // outbound connections still being made through our SOCKS5 proxy: the address we are really
+    // connecting to, and the proxy handshake that gets us there
+    connecting_via_proxy: HashMap<usize, (SocketAddr, Socks5Handshake)>,
+
+    // txids each neighbor is known to have, so we don't relay them back
+    pub tx_relay_filters: HashMap<NeighborKey, RollingBloomFilter<BloomNodeHasher>>,
+    // how much of each pushed microblock stream we may still forward
+    pub microblock_relay_policy: MicroblockRelayPolicy,
+    // outstanding GetMempoolTxs requests, and when we sent them
+    pub pending_mempool_pulls: HashMap<usize, u64>,
+    // when we last answered each neighbor's GetMempoolTxs
+    pub mempool_txs_answered: HashMap<usize, u64>,
+    pub last_mempool_pull: u64,
+
+    // anti-entropy offers we've made and are waiting on answers for
+    pub pending_block_offers: HashMap<NeighborKey, PendingBlocksOffer>,
+
+    // outstanding GetHeaders requests, when we sent them, and what they asked for
+    pub pending_header_requests: HashMap<usize, (u64, GetHeadersData)>,
+    // each neighbor's last valid answer to our GetHeaders
+    pub peer_headers: HashMap<NeighborKey, HeadersData>,
+
+    // connections to our best peers from the last run, while we set them up
+    pub warm_start: Option<PeerWarmStart>,
+    pub warm_start_begun: bool,
+    // when we last saved our neighbors' scores to the peer DB
+    pub peer_scores_saved_at: u64,
+
+    // the most recent reorgs of the canonical Stacks chain, for /v2/info
+    pub recent_reorgs: VecDeque<RPCChainReorgData>,
+
+    // notices when our Stacks tip stops advancing
+    pub stale_tip_watchdog: StaleTipWatchdog,
+
     // fault injection -- force disconnects
     fault_last_disconnect: u64,
 }
@@ -362,9 +457,34 @@ impl PeerNetwork {
         epochs: Vec<StacksEpoch>,
     ) -> PeerNetwork {
         let http = HttpPeer::new(connection_opts.clone(), 0);
+        let http_response_cache =
+            ResponseCache::new(connection_opts.http_response_cache_size as usize);
+        let rpc_rate_limiter = RPCRateLimiter::from_connection_options(&connection_opts);
+        let reputations = PeerReputations::from_connection_options(&connection_opts);
+        let egress_limiter =
+            EgressLimiter::from_connection_options(&connection_opts, get_epoch_time_ms());
+        let handshake_limiter = HandshakeRateLimiter::from_connection_options(&connection_opts);
+        let microblock_relay_policy =
+            MicroblockRelayPolicy::from_connection_options(&connection_opts);
         let pub_ip = connection_opts.public_ip_address.clone();
         let pub_ip_learned = pub_ip.is_none();
         local_peer.public_ip_address = pub_ip.clone();
+        local_peer.alt_public_ip_address = connection_opts.alt_public_ip_address.clone();
+        if connection_opts.p2p_encryption {
+            local_peer.services |= ServiceFlags::ENCRYPTION as u16;
+        }
+        if connection_opts.compact_blocks {
+            local_peer.services |= ServiceFlags::COMPACT_BLOCKS as u16;
+        }
+        if connection_opts.p2p_compression {
+            local_peer.services |= ServiceFlags::COMPRESSION as u16;
+        }
+        if connection_opts.capability_exchange {
+            local_peer.services |= ServiceFlags::CAPABILITIES as u16;
+        }
+        local_peer.services |= ServiceFlags::MEMPOOL_TXS as u16;
+        local_peer.services |= ServiceFlags::BLOCK_OFFERS as u16;
+        local_peer.services |= ServiceFlags::HEADERS as u16;
 
         if connection_opts.disable_inbound_handshakes {
             debug!("{:?}: disable inbound handshakes", &local_peer);
@@ -422,6 +542,7 @@ impl PeerNetwork {
             walk_total_step_count: 0,
             walk_pingbacks: HashMap::new(),
             walk_result: NeighborWalkResult::new(),
+            recent_walks: VecDeque::new(),
 
             inv_state: None,
             pox_id: PoxId::initial(),
@@ -434,6 +555,8 @@ impl PeerNetwork {
             mempool_state: MempoolSyncState::PickOutboundPeer,
             mempool_sync_deadline: 0,
             mempool_sync_timeout: 0,
+            mempool_sync_bootstrap_urls: HashSet::new(),
+            mempool_sync_bootstrapped: false,
 
             prune_outbound_counts: HashMap::new(),
             prune_inbound_counts: HashMap::new(),
@@ -453,6 +576,12 @@ impl PeerNetwork {
             public_ip_reply_handle: None,
             public_ip_retries: 0,
 
+            dns_seed_peers: VecDeque::new(),
+            dns_seed_requests: vec![],
+            dns_seeds_resolved_at: 0,
+
+            port_mapper: None,
+
             num_state_machine_passes: 0,
             num_inv_sync_passes: 0,
             num_downloader_passes: 0,
@@ -464,6 +593,36 @@ impl PeerNetwork {
 
             pending_messages: HashMap::new(),
 
+            open_files: FileDescriptorUsage::default(),
+            open_files_limit: u64::MAX,
+            open_files_last_checked_ms: 0,
+
+            http_response_cache,
+            rpc_rate_limiter,
+            reputations,
+            egress_limiter,
+            handshake_limiter,
+            plaintext_peers: HashSet::new(),
+            pending_compact_blocks: HashMap::new(),
+            connecting_via_proxy: HashMap::new(),
+            tx_relay_filters: HashMap::new(),
+            microblock_relay_policy,
+            pending_mempool_pulls: HashMap::new(),
+            mempool_txs_answered: HashMap::new(),
+            last_mempool_pull: 0,
+
+            pending_block_offers: HashMap::new(),
+
+            pending_header_requests: HashMap::new(),
+            peer_headers: HashMap::new(),
+
+            warm_start: None,
+            warm_start_begun: false,
+            peer_scores_saved_at: get_epoch_time_secs(),
+
+            recent_reorgs: VecDeque::new(),
+            stale_tip_watchdog: StaleTipWatchdog::new(),
+
             fault_last_disconnect: 0,
         };
 
@@ -500,6 +659,14 @@ impl PeerNetwork {
         res
     }
 
+    /// Publish the chain and mempool events received on `receiver` to the RPC server's WebSocket
+    /// subscribers.
+    pub fn set_websocket_event_receiver(&mut self, receiver: WebSocketEventReceiver) {
+        PeerNetwork::with_http(self, |_, ref mut http| {
+            http.websocket_events = Some(receiver);
+        });
+    }
+
     /// start serving.
     pub fn bind(&mut self, my_addr: &SocketAddr, http_addr: &SocketAddr) -> Result<(), net_error> {
         let mut net = NetworkState::new(self.connection_opts.max_sockets)?;
@@ -514,6 +681,41 @@ impl PeerNetwork {
             http_addr
         );
 
+        self.set_network(net, my_addr, p2p_handle, http_handle);
+        Ok(())
+    }
+
+    /// start serving, with the RPC interface on a unix domain socket instead of a TCP port.  The
+    /// socket file gets the permission bits in `connection_opts.rpc_unix_socket_mode`.
+    #[cfg(unix)]
+    pub fn bind_unix_http(
+        &mut self,
+        my_addr: &SocketAddr,
+        http_path: &Path,
+    ) -> Result<(), net_error> {
+        let mut net = NetworkState::new(self.connection_opts.max_sockets)?;
+
+        let p2p_handle = net.bind(my_addr)?;
+        let http_handle = net.bind_unix(http_path, self.connection_opts.rpc_unix_socket_mode)?;
+
+        test_debug!(
+            "{:?}: bound on p2p {:?}, http {}",
+            &self.local_peer,
+            my_addr,
+            http_path.display()
+        );
+
+        self.set_network(net, my_addr, p2p_handle, http_handle);
+        Ok(())
+    }
+
+    fn set_network(
+        &mut self,
+        net: NetworkState,
+        my_addr: &SocketAddr,
+        p2p_handle: usize,
+        http_handle: usize,
+    ) {
         self.network = Some(net);
         self.p2p_network_handle = p2p_handle;
         self.http_network_handle = http_handle;
@@ -529,7 +731,24 @@ impl PeerNetwork {
             port: my_addr.port(),
         };
 
-        Ok(())
+        if self.connection_opts.port_mapping != PortMappingMethod::Disabled {
+            match PortMapper::spawn(
+                self.connection_opts.port_mapping,
+                my_addr.port(),
+                self.connection_opts.port_mapping_lease,
+                self.connection_opts.port_mapping_gateway,
+            ) {
+                Ok(port_mapper) => {
+                    self.port_mapper = Some(port_mapper);
+                }
+                Err(e) => {
+                    warn!(
+                        "{:?}: Failed to start port mapping: {:?}",
+                        &self.local_peer, &e
+                    );
+                }
+            }
+        }
     }
 
     /// Run a closure with the network state
@@ -813,7 +1032,9 @@ impl PeerNetwork {
         for (_, socket) in sockets.iter() {
             match socket.peer_addr() {
                 Ok(addr) => {
-                    if addr.ip() == ipaddr.ip() {
+                    if PeerAddress::canonical_socketaddr(&addr).ip()
+                        == PeerAddress::canonical_socketaddr(ipaddr).ip()
+                    {
                         ret += 1;
                     }
                 }
@@ -863,6 +1084,19 @@ impl PeerNetwork {
                 );
                 return Err(net_error::Denied);
             }
+
+            // don't talk if we can't (or won't) reach its address family
+            if !self
+                .connection_opts
+                .address_family
+                .allows(&neighbor.addrbytes)
+            {
+                debug!(
+                    "{:?}: Neighbor {:?} is not in an address family we connect to ({})",
+                    &self.local_peer, neighbor, self.connection_opts.address_family
+                );
+                return Err(net_error::Denied);
+            }
         }
 
         // already connected?
@@ -874,19 +1108,32 @@ impl PeerNetwork {
             return Ok(event_id);
         }
 
+        let proxy_opt = self.make_proxy_handshake(neighbor)?;
+
         let next_event_id = match self.network {
             None => {
                 test_debug!("{:?}: network not connected", &self.local_peer);
                 return Err(net_error::NotConnected);
             }
             Some(ref mut network) => {
-                let sock = NetworkState::connect(&neighbor.addrbytes.to_socketaddr(neighbor.port))?;
+                let sock = match proxy_opt {
+                    Some((ref proxy_addr, _)) => NetworkState::connect(proxy_addr)?,
+                    None => {
+                        NetworkState::connect(&neighbor.addrbytes.to_socketaddr(neighbor.port))?
+                    }
+                };
                 let hint_event_id = network.next_event_id()?;
                 let registered_event_id =
                     network.register(self.p2p_network_handle, hint_event_id, &sock)?;
 
                 self.connecting
                     .insert(registered_event_id, (sock, true, get_epoch_time_secs()));
+                if let Some((_, handshake)) = proxy_opt {
+                    self.connecting_via_proxy.insert(
+                        registered_event_id,
+                        (neighbor.addrbytes.to_socketaddr(neighbor.port), handshake),
+                    );
+                }
                 registered_event_id
             }
         };
@@ -894,6 +1141,49 @@ impl PeerNetwork {
         Ok(next_event_id)
     }
 
+    /// If we go through a SOCKS5 proxy, then get its address, and the handshake that asks it to
+    /// connect us to the neighbor.  Onion peers can't be reached any other way.
+    fn make_proxy_handshake(
+        &self,
+        neighbor: &NeighborKey,
+    ) -> Result<Option<(SocketAddr, Socks5Handshake)>, net_error> {
+        let proxy_addr = match self.connection_opts.socks5_proxy {
+            Some(ref addr) => addr.clone(),
+            None => {
+                if neighbor.addrbytes.is_onion() {
+                    debug!(
+                        "{:?}: Cannot reach onion peer {:?} without a proxy",
+                        &self.local_peer, neighbor
+                    );
+                    return Err(net_error::ProxyError(
+                        "No proxy configured to reach onion peers".to_string(),
+                    ));
+                }
+                return Ok(None);
+            }
+        };
+
+        let target = if neighbor.addrbytes.is_onion() {
+            match PeerDB::get_onion_host(self.peerdb.conn(), &neighbor.addrbytes)? {
+                Some(host) => Socks5Target::Domain(host, neighbor.port),
+                None => {
+                    debug!("{:?}: No onion host for {:?}", &self.local_peer, neighbor);
+                    return Err(net_error::NotFoundError);
+                }
+            }
+        } else {
+            Socks5Target::Addr(neighbor.addrbytes.to_socketaddr(neighbor.port))
+        };
+
+        debug!(
+            "{:?}: connect to {:?} through proxy {} as {}",
+            &self.local_peer, neighbor, &proxy_addr, &target
+        );
+        let handshake =
+            Socks5Handshake::new(target, self.connection_opts.socks5_credentials.clone())?;
+        Ok(Some((proxy_addr, handshake)))
+    }
+
     /// Given a list of neighbors keys, find the _set_ of neighbor keys that represent unique
     /// connections.  This is used by the broadcast logic to ensure that we only send a message to
     /// a peer once, even if we have both an inbound and outbound connection to it.
@@ -1047,6 +1337,17 @@ impl PeerNetwork {
                 .relay_signed_message(&neighbor_key, msg)
                 .and_then(|_| Ok(())),
             NetworkRequest::Broadcast(relay_hints, msg) => {
+                // don't forward more of a microblock stream than its relay budget allows
+                let msg = match msg {
+                    StacksMessageType::Microblocks(mut data) => {
+                        if !self.admit_microblocks_broadcast(&mut data) {
+                            return Ok(());
+                        }
+                        StacksMessageType::Microblocks(data)
+                    }
+                    msg => msg,
+                };
+
                 // pick some neighbors. Note that only some messages can be broadcasted.
                 let neighbor_keys = match msg {
                     StacksMessageType::Blocks(ref data) => {
@@ -1073,7 +1374,12 @@ impl PeerNetwork {
                         Ok(all_neighbors.into_iter().collect())
                     }
                     StacksMessageType::Transaction(ref data) => {
-                        self.sample_broadcast_peers(&relay_hints, data)
+                        match self.sample_broadcast_peers(&relay_hints, data) {
+                            Ok(neighbor_keys) => {
+                                Ok(self.filter_tx_broadcast_peers(neighbor_keys, &data.txid()))
+                            }
+                            Err(e) => Err(e),
+                        }
                     }
                     _ => {
                         // not suitable for broadcast
@@ -1218,6 +1524,54 @@ impl PeerNetwork {
         Ok(disconnect)
     }
 
+    /// Apply a peer policy update from the admin RPC.  A new deny rule takes effect right away:
+    /// we disconnect from the peers it covers.
+    pub fn update_peer_policy(&mut self, update: &PeerPolicyUpdate) -> Result<(), net_error> {
+        let mut tx = self.peerdb.tx_begin()?;
+        if let Some((ref prefix, mask)) = update.cidr {
+            match (update.list, update.remove) {
+                (PeerPolicyList::Allow, false) => PeerDB::add_allow_cidr(&mut tx, prefix, mask)?,
+                (PeerPolicyList::Allow, true) => PeerDB::remove_allow_cidr(&mut tx, prefix, mask)?,
+                (PeerPolicyList::Deny, false) => PeerDB::add_deny_cidr(&mut tx, prefix, mask)?,
+                (PeerPolicyList::Deny, true) => PeerDB::remove_deny_cidr(&mut tx, prefix, mask)?,
+            }
+        }
+        if let Some(asn) = update.asn {
+            match (update.list, update.remove) {
+                (PeerPolicyList::Allow, false) => PeerDB::add_allow_asn(&mut tx, asn)?,
+                (PeerPolicyList::Allow, true) => PeerDB::remove_allow_asn(&mut tx, asn)?,
+                (PeerPolicyList::Deny, false) => PeerDB::add_deny_asn(&mut tx, asn)?,
+                (PeerPolicyList::Deny, true) => PeerDB::remove_deny_asn(&mut tx, asn)?,
+            }
+        }
+        tx.commit()?;
+
+        info!("{:?}: Updated peer policy: {:?}", &self.local_peer, update);
+        if update.list == PeerPolicyList::Deny && !update.remove {
+            self.disconnect_denied_peers()?;
+        }
+        Ok(())
+    }
+
+    /// Disconnect from the peers that are now denied
+    fn disconnect_denied_peers(&mut self) -> Result<(), net_error> {
+        let mut denied = vec![];
+        for (event_id, convo) in self.peers.iter() {
+            let nk = convo.to_neighbor_key();
+            if PeerDB::is_peer_denied(self.peerdb.conn(), nk.network_id, &nk.addrbytes, nk.port)? {
+                denied.push((*event_id, nk));
+            }
+        }
+        for (event_id, nk) in denied.into_iter() {
+            info!(
+                "{:?}: Peer {:?} is now denied; disconnecting",
+                &self.local_peer, &nk
+            );
+            self.deregister_peer(event_id);
+        }
+        Ok(())
+    }
+
     /// Get the neighbor if we know of it and it's public key is unexpired.
     fn lookup_peer(
         &self,
@@ -1289,9 +1643,15 @@ impl PeerNetwork {
             && self.bind_nk.port == neighbor_key.port
     }
 
+    /// The port our p2p interface is bound to
+    pub fn bind_port(&self) -> u16 {
+        self.bind_nk.port
+    }
+
     /// Check to see if we can register the given socket
     /// * we can't have registered this neighbor already
-    /// * if this is inbound, we can't add more than self.num_clients
+    /// * if this is inbound, its IP address can't be connecting too often, and there has to be
+    ///   a connection slot for it (see `net::slots`)
     pub fn can_register_peer(
         &mut self,
         neighbor_key: &NeighborKey,
@@ -1320,6 +1680,21 @@ impl PeerNetwork {
             return Err(net_error::Denied);
         }
 
+        // connecting too often?
+        if !outbound {
+            if let Err(wait) = self
+                .handshake_limiter
+                .check(&neighbor_key.addrbytes, get_epoch_time_ms())
+            {
+                info!(
+                    "{:?}: Peer {:?} is connecting too often; refusing inbound connection for {}s",
+                    &self.local_peer, neighbor_key, wait
+                );
+                increment_connections_refused_counter();
+                return Err(net_error::PeerThrottled);
+            }
+        }
+
         // already connected?
         if let Some(event_id) = self.get_event_id(&neighbor_key) {
             test_debug!(
@@ -1331,15 +1706,21 @@ impl PeerNetwork {
             return Err(net_error::AlreadyConnected(event_id, neighbor_key.clone()));
         }
 
-        // consider rate-limits on in-bound peers
-        let num_outbound = PeerNetwork::count_outbound_conversations(&self.peers);
-        if !outbound && (self.peers.len() as u64) - num_outbound >= self.connection_opts.num_clients
-        {
-            // too many inbounds
-            info!("{:?}: Too many inbound connections", &self.local_peer);
+        // running out of file descriptors?
+        if !outbound && self.is_short_on_files() {
+            info!(
+                "{:?}: Running out of file descriptors; refusing inbound connection from {:?}",
+                &self.local_peer, neighbor_key
+            );
+            increment_connections_refused_counter();
             return Err(net_error::TooManyPeers);
         }
 
+        // consider connection slots for in-bound peers
+        if !outbound {
+            self.check_inbound_slots()?;
+        }
+
         Ok(())
     }
 
@@ -1388,16 +1769,20 @@ impl PeerNetwork {
         socket: mio_net::TcpStream,
         outbound: bool,
     ) -> Result<(), net_error> {
-        let client_addr = match socket.peer_addr() {
-            Ok(addr) => addr,
-            Err(e) => {
-                debug!(
-                    "{:?}: Failed to get peer address of {:?}: {:?}",
-                    &self.local_peer, &socket, &e
-                );
-                self.deregister_socket(event_id, socket);
-                return Err(net_error::SocketError);
-            }
+        // if we went through a proxy, then the socket's peer is the proxy, not the neighbor
+        let (client_addr, proxy_handshake_opt) = match self.connecting_via_proxy.remove(&event_id) {
+            Some((addr, handshake)) => (addr, Some(handshake)),
+            None => match socket.peer_addr() {
+                Ok(addr) => (PeerAddress::canonical_socketaddr(&addr), None),
+                Err(e) => {
+                    debug!(
+                        "{:?}: Failed to get peer address of {:?}: {:?}",
+                        &self.local_peer, &socket, &e
+                    );
+                    self.deregister_socket(event_id, socket);
+                    return Err(net_error::SocketError);
+                }
+            },
         };
 
         let neighbor_opt = match self.lookup_peer(self.chain_view.burn_block_height, &client_addr) {
@@ -1449,6 +1834,11 @@ impl PeerNetwork {
             self.epochs.clone(),
         );
         new_convo.set_public_key(pubkey_opt);
+        let mut transport = self.make_transport(&neighbor_key, outbound)?;
+        if let Some(handshake) = proxy_handshake_opt {
+            transport = transport.with_socks5(handshake);
+        }
+        new_convo.set_transport(transport);
 
         debug!(
             "{:?}: Registered {} as event {} ({:?},outbound={})",
@@ -1465,6 +1855,34 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Decide how a new conversation's bytes go over the wire.  Outbound connections start a Noise
+    /// handshake, unless the neighbor hung up on the last one we tried and we don't insist on it.
+    fn make_transport(
+        &self,
+        neighbor_key: &NeighborKey,
+        outbound: bool,
+    ) -> Result<P2PTransport, net_error> {
+        if !self.connection_opts.p2p_encryption {
+            return Ok(P2PTransport::plaintext());
+        }
+        if !outbound {
+            return Ok(P2PTransport::responder(
+                &self.local_peer.private_key,
+                self.connection_opts.p2p_require_encryption,
+            ));
+        }
+        if self.plaintext_peers.contains(neighbor_key)
+            && !self.connection_opts.p2p_require_encryption
+        {
+            debug!(
+                "{:?}: will not encrypt connection to {:?}",
+                &self.local_peer, neighbor_key
+            );
+            return Ok(P2PTransport::plaintext());
+        }
+        P2PTransport::initiator(&self.local_peer.private_key)
+    }
+
     /// Are we connected to a remote host already?
     pub fn is_registered(&self, neighbor_key: &NeighborKey) -> bool {
         self.events.contains_key(neighbor_key)
@@ -1506,6 +1924,23 @@ impl PeerNetwork {
     pub fn deregister_peer(&mut self, event_id: usize) -> () {
         debug!("{:?}: Disconnect event {}", &self.local_peer, event_id);
 
+        // don't let a peer escape its misbehavior by disconnecting
+        self.collect_peer_misbehavior(event_id);
+
+        // stop exporting its metrics, unless it's still connected in the other direction
+        if let Some(peer) = self
+            .peers
+            .get(&event_id)
+            .and_then(|convo| convo.metrics_label())
+        {
+            let still_connected = self.peers.iter().any(|(other_event_id, convo)| {
+                *other_event_id != event_id && convo.metrics_label().as_ref() == Some(&peer)
+            });
+            if !still_connected {
+                remove_p2p_peer_metrics(&peer);
+            }
+        }
+
         let mut nk_remove: Vec<NeighborKey> = vec![];
         for (neighbor_key, ev_id) in self.events.iter() {
             if *ev_id == event_id {
@@ -1514,6 +1949,15 @@ impl PeerNetwork {
         }
 
         for nk in nk_remove.into_iter() {
+            // remember which neighbors can't talk Noise, so we don't keep trying
+            if let Some(convo) = self.peers.get(&event_id) {
+                if convo.is_outbound() && convo.transport.is_handshaking() {
+                    self.plaintext_peers.insert(nk.clone());
+                } else if convo.supports(Capability::Encryption) {
+                    self.plaintext_peers.remove(&nk);
+                }
+            }
+
             // remove event state
             self.events.remove(&nk);
 
@@ -1541,10 +1985,12 @@ impl PeerNetwork {
                 if let Some((socket, ..)) = self.connecting.remove(&event_id) {
                     let _ = network.deregister(event_id, &socket);
                 }
+                self.connecting_via_proxy.remove(&event_id);
             }
         }
 
         self.relay_handles.remove(&event_id);
+        self.egress_limiter.remove_peer(event_id);
         self.peers.remove(&event_id);
         self.pending_messages.remove(&event_id);
     }
@@ -1868,6 +2314,14 @@ impl PeerNetwork {
         }
 
         // store for later
+        self.recent_walks
+            .push_back(RPCNeighborWalkData::from_walk_result(
+                &walk_result,
+                get_epoch_time_secs(),
+            ));
+        while self.recent_walks.len() > MAX_RECENT_NEIGHBOR_WALKS {
+            self.recent_walks.pop_front();
+        }
         self.walk_result = walk_result;
     }
 
@@ -1944,6 +2398,7 @@ impl PeerNetwork {
                         now
                     );
                     to_remove.push(*event_id);
+                    continue;
                 }
             } else {
                 // have not handshaked with this remote peer
@@ -1957,6 +2412,24 @@ impl PeerNetwork {
                         now
                     );
                     to_remove.push(*event_id);
+                    continue;
+                }
+            }
+
+            // started a message, but hasn't finished it
+            if self.connection_opts.partial_message_timeout > 0 {
+                if let Some(since) = convo.connection.partial_message_since() {
+                    if since + self.connection_opts.partial_message_timeout < now {
+                        debug!(
+                            "{:?}: Disconnect peer {:?}: took too long to send a message ({} + {} < {})",
+                            &self.local_peer,
+                            &convo,
+                            since,
+                            self.connection_opts.partial_message_timeout,
+                            now
+                        );
+                        to_remove.push(*event_id);
+                    }
                 }
             }
         }
@@ -2008,6 +2481,11 @@ impl PeerNetwork {
             }
         }
 
+        // don't prune connections to warm-start peers before we've handshaked with them
+        for event_id in self.get_warm_start_events().into_iter() {
+            safe.insert(event_id);
+        }
+
         // if we're in the middle of a peer walk, then don't prune any outbound connections it established
         // (yet)
         match self.walk {
@@ -2031,7 +2509,8 @@ impl PeerNetwork {
         let mut msgs = HashMap::new();
         for (event_id, convo) in self.peers.iter_mut() {
             let nk = convo.to_neighbor_key();
-            let handshake_data = HandshakeData::from_local_peer(&self.local_peer);
+            let handshake_data =
+                HandshakeData::from_local_peer_for(&self.local_peer, Some(&convo.peer_addrbytes));
             let handshake = StacksMessageType::Handshake(handshake_data);
 
             debug!(
@@ -2068,11 +2547,14 @@ impl PeerNetwork {
     }
 
     /// Flush relayed message handles, but don't block.
+    /// Neighbors we've pushed too much data to recently are skipped until their egress limit
+    /// allows more.
     /// Drop broken handles.
     /// Return the list of broken conversation event IDs
     fn flush_relay_handles(&mut self) -> Vec<usize> {
         let mut broken = vec![];
         let mut drained = vec![];
+        let now_ms = get_epoch_time_ms();
 
         // flush each outgoing conversation
         for (event_id, handle_list) in self.relay_handles.iter_mut() {
@@ -2085,6 +2567,11 @@ impl PeerNetwork {
                 (self.sockets.get_mut(event_id), self.peers.get_mut(event_id))
             {
                 while handle_list.len() > 0 {
+                    if !self.egress_limiter.can_send(*event_id, now_ms) {
+                        debug!("Egress to event {} is throttled", event_id);
+                        break;
+                    }
+
                     let handle = handle_list.front_mut().unwrap();
 
                     debug!("Flush relay handle to {:?} ({:?})", socket, convo);
@@ -2097,6 +2584,8 @@ impl PeerNetwork {
                                 break;
                             }
                         };
+                    self.egress_limiter
+                        .record_sent(*event_id, num_sent as u64, now_ms);
 
                     if flushed && num_sent == 0 {
                         // message fully sent
@@ -2173,8 +2662,13 @@ impl PeerNetwork {
                         txs.len()
                     );
 
-                    self.mempool_sync_deadline =
-                        get_epoch_time_secs() + self.connection_opts.mempool_sync_interval;
+                    if self.is_mempool_sync_bootstrapping() {
+                        // go straight on to the next peer
+                        self.mempool_sync_deadline = 0;
+                    } else {
+                        self.mempool_sync_deadline =
+                            get_epoch_time_secs() + self.connection_opts.mempool_sync_interval;
+                    }
                     return Ok(Some(txs));
                 } else {
                     return Ok(None);
@@ -2425,6 +2919,51 @@ impl PeerNetwork {
         }
     }
 
+    /// Advertise the gateway's public address and port once our p2p port is mapped, unless we
+    /// were given a public address to use.  If the mapping is lost, go back to learning our
+    /// public IP address from our neighbors.
+    fn poll_port_mapping(&mut self) {
+        let update = match self
+            .port_mapper
+            .as_ref()
+            .and_then(|mapper| mapper.try_recv())
+        {
+            Some(update) => update,
+            None => {
+                return;
+            }
+        };
+        if self.connection_opts.public_ip_address.is_some() {
+            debug!(
+                "{:?}: port mapping changed to {:?}, but our public IP address was given to us",
+                &self.local_peer, &update
+            );
+            return;
+        }
+        match update {
+            Some(mapping) => {
+                let public_addr = Some((mapping.external_addr, mapping.external_port));
+                if self.local_peer.public_ip_address != public_addr {
+                    info!(
+                        "{:?}: advertise mapped address {:?}",
+                        &self.local_peer, &public_addr
+                    );
+                }
+                self.local_peer.public_ip_address = public_addr;
+                self.public_ip_learned = false;
+                self.public_ip_confirmed = true;
+            }
+            None => {
+                info!(
+                    "{:?}: lost port mapping; will re-learn our public IP address",
+                    &self.local_peer
+                );
+                self.public_ip_learned = true;
+                self.public_ip_reset();
+            }
+        }
+    }
+
     /// Learn our publicly-routable IP address
     fn do_get_public_ip(&mut self) -> Result<bool, net_error> {
         if !self.need_public_ip() {
@@ -2499,8 +3038,20 @@ impl PeerNetwork {
             self.deregister_and_ban_neighbor(&broken);
         }
 
+        // peers that are behind us lose a little reputation for each inventory sync
+        if done {
+            let stale_neighbors = match self.inv_state {
+                Some(ref inv_state) => inv_state.get_stale_peers(),
+                None => vec![],
+            };
+            for stale in stale_neighbors.iter() {
+                self.record_peer_misbehavior(stale, PeerMisbehavior::StaleInventory);
+            }
+        }
+
         // disconnect from dead connections
         for dead in dead_neighbors.into_iter() {
+            self.record_peer_misbehavior(&dead, PeerMisbehavior::SlowResponse);
             self.deregister_neighbor(&dead);
         }
 
@@ -2684,7 +3235,15 @@ impl PeerNetwork {
         chainstate: &StacksChainState,
         local_blocks_inv: &BlocksInvData,
         block_stats: &NeighborBlockStats,
-    ) -> Result<Option<(ConsensusHash, BlockHeaderHash, Vec<StacksMicroblock>)>, net_error> {
+    ) -> Result<
+        Option<(
+            ConsensusHash,
+            BlockHeaderHash,
+            StacksBlockId,
+            Vec<StacksMicroblock>,
+        )>,
+        net_error,
+    > {
         let start_block_height = self.burnchain.reward_cycle_to_block_height(reward_cycle);
         if !local_blocks_inv.has_ith_microblock_stream((height - start_block_height) as u16) {
             return Ok(None);
@@ -2774,6 +3333,10 @@ impl PeerNetwork {
             return Ok(Some((
                 block_info.parent_consensus_hash,
                 block_info.parent_anchored_block_hash,
+                StacksBlockHeader::make_index_block_hash(
+                    &block_info.consensus_hash,
+                    &block_info.anchored_block_hash,
+                ),
                 microblocks,
             )));
         } else {
@@ -2965,7 +3528,7 @@ impl PeerNetwork {
                             if total_microblocks_to_broadcast
                                 < network.connection_opts.max_microblock_push
                             {
-                                if let Some((parent_consensus_hash, parent_block_hash, microblocks)) = network
+                                if let Some((parent_consensus_hash, parent_block_hash, child_block_id, microblocks)) = network
                                     .find_next_push_microblocks(
                                         nk,
                                         reward_cycle,
@@ -3012,7 +3575,7 @@ impl PeerNetwork {
                                         network.antientropy_microblocks.insert(nk.clone(), pushed);
                                     }
 
-                                    local_microblocks.push((index_block_hash, microblocks));
+                                    local_microblocks.push((index_block_hash, child_block_id, microblocks));
 
                                     if !lowest_reward_cycle_with_missing_block.contains_key(nk) {
                                         lowest_reward_cycle_with_missing_block
@@ -3049,6 +3612,26 @@ impl PeerNetwork {
                 &self.local_peer, total_blocks_to_broadcast, total_microblocks_to_broadcast
             );
 
+            // neighbors that take offers get to pick what they still need
+            let offer_neighbors: Vec<NeighborKey> = blocks_to_broadcast
+                .keys()
+                .filter(|nk| self.can_offer_blocks(nk))
+                .cloned()
+                .collect();
+            for nk in offer_neighbors.into_iter() {
+                let blocks = blocks_to_broadcast.remove(&nk).unwrap_or(vec![]);
+                let microblocks = microblocks_to_broadcast.remove(&nk).unwrap_or(vec![]);
+                if blocks.is_empty() && microblocks.is_empty() {
+                    continue;
+                }
+                if let Err(e) = self.offer_blocks(&nk, blocks, microblocks) {
+                    debug!(
+                        "{:?}: AntiEntropy: Failed to offer blocks to {:?}: {:?}",
+                        &self.local_peer, &nk, &e
+                    );
+                }
+            }
+
             for (nk, blocks) in blocks_to_broadcast.into_iter() {
                 let num_blocks = blocks.len();
                 if num_blocks == 0 {
@@ -3074,7 +3657,7 @@ impl PeerNetwork {
             }
 
             for (nk, microblock_datas) in microblocks_to_broadcast.into_iter() {
-                for (anchor_block_id, microblocks) in microblock_datas.into_iter() {
+                for (anchor_block_id, _, microblocks) in microblock_datas.into_iter() {
                     let num_microblocks = microblocks.len();
                     if num_microblocks == 0 {
                         continue;
@@ -3154,6 +3737,31 @@ impl PeerNetwork {
         }
     }
 
+    /// Are we still catching up on the network's mempool after booting?  If so, we do mempool
+    /// syncs back-to-back, each with a peer we haven't synced with yet.
+    pub fn is_mempool_sync_bootstrapping(&self) -> bool {
+        !self.mempool_sync_bootstrapped && self.connection_opts.mempool_sync_bootstrap_peers > 0
+    }
+
+    /// Remember that we've completed a mempool sync with the peer at this data URL
+    fn mempool_sync_finished_with(&mut self, url: &UrlString) {
+        if !self.is_mempool_sync_bootstrapping() {
+            return;
+        }
+        self.mempool_sync_bootstrap_urls.insert(url.clone());
+        if (self.mempool_sync_bootstrap_urls.len() as u64)
+            >= self.connection_opts.mempool_sync_bootstrap_peers
+        {
+            debug!(
+                "{:?}: Mempool sync bootstrapped from {} peers",
+                &self.local_peer,
+                self.mempool_sync_bootstrap_urls.len()
+            );
+            self.mempool_sync_bootstrapped = true;
+            self.mempool_sync_bootstrap_urls.clear();
+        }
+    }
+
     /// Reset a mempool sync
     fn mempool_sync_reset(&mut self) {
         self.mempool_state = MempoolSyncState::PickOutboundPeer;
@@ -3197,6 +3805,12 @@ impl PeerNetwork {
                 if convo.data_url.len() == 0 {
                     continue;
                 }
+                if self.is_mempool_sync_bootstrapping()
+                    && self.mempool_sync_bootstrap_urls.contains(&convo.data_url)
+                {
+                    // already synced with this one
+                    continue;
+                }
                 let url = convo.data_url.clone();
                 if dns_client_opt.is_none() {
                     if let Ok(Some(_)) = PeerNetwork::try_get_url_ip(&url) {
@@ -3213,6 +3827,17 @@ impl PeerNetwork {
 
         if let Some(url) = mempool_sync_data_url {
             self.mempool_sync_begin_resolve_data_url(url, dns_client_opt, page_id)
+        } else if self.is_mempool_sync_bootstrapping()
+            && !self.mempool_sync_bootstrap_urls.is_empty()
+        {
+            debug!(
+                "{:?}: No more peers to bootstrap our mempool from; synced with {}",
+                &self.local_peer,
+                self.mempool_sync_bootstrap_urls.len()
+            );
+            self.mempool_sync_bootstrapped = true;
+            self.mempool_sync_bootstrap_urls.clear();
+            Ok(None)
         } else {
             debug!("No peer has a data URL, so no mempool sync can happen");
             Ok(None)
@@ -3534,6 +4159,7 @@ impl PeerNetwork {
                                 }
                                 None => {
                                     // done
+                                    self.mempool_sync_finished_with(url);
                                     self.mempool_sync_reset();
                                     true
                                 }
@@ -4101,7 +4727,7 @@ impl PeerNetwork {
     }
 
     /// Buffer a message for re-processing once the burnchain view updates
-    fn buffer_data_message(&mut self, event_id: usize, msg: StacksMessage) -> () {
+    pub fn buffer_data_message(&mut self, event_id: usize, msg: StacksMessage) {
         if let Some(msgs) = self.pending_messages.get_mut(&event_id) {
             // check limits:
             // at most 1 BlocksAvailable
@@ -4120,7 +4746,7 @@ impl PeerNetwork {
                     StacksMessageType::MicroblocksAvailable(_) => {
                         microblocks_available += 1;
                     }
-                    StacksMessageType::Blocks(_) => {
+                    StacksMessageType::Blocks(_) | StacksMessageType::CompactBlock(_) => {
                         blocks_data += 1;
                     }
                     StacksMessageType::Microblocks(_) => {
@@ -4149,11 +4775,15 @@ impl PeerNetwork {
                     return;
                 }
             }
-            if let StacksMessageType::Blocks(_) = &msg.payload {
+            if let StacksMessageType::Blocks(_) | StacksMessageType::CompactBlock(_) = &msg.payload
+            {
                 if blocks_data >= self.connection_opts.max_buffered_blocks {
                     debug!(
-                        "{:?}: Drop BlocksData from event {} -- already have {} buffered",
-                        &self.local_peer, event_id, blocks_data
+                        "{:?}: Drop {} from event {} -- already have {} buffered",
+                        &self.local_peer,
+                        msg.payload.get_message_name(),
+                        event_id,
+                        blocks_data
                     );
                     return;
                 }
@@ -4673,6 +5303,7 @@ impl PeerNetwork {
         &mut self,
         sortdb: &SortitionDB,
         chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
         unsolicited: HashMap<usize, Vec<StacksMessage>>,
         ibd: bool,
         buffer: bool,
@@ -4707,17 +5338,57 @@ impl PeerNetwork {
                         &neighbor_key
                     );
                 }
-                let (to_buffer, relay) = self.handle_unsolicited_message(
-                    sortdb,
-                    chainstate,
-                    event_id,
-                    &message.payload,
-                    ibd,
-                    buffer,
-                );
-                if buffer && to_buffer {
-                    self.buffer_data_message(event_id, message);
-                } else if relay {
+
+                // compact blocks and their missing transactions are handled here, and turn into
+                // pushed blocks once they're rebuilt
+                let message = match self.handle_unsolicited_compact_block_message(
+                    sortdb, chainstate, mempool, event_id, message, buffer,
+                ) {
+                    Some(message) => message,
+                    None => {
+                        continue;
+                    }
+                };
+
+                // anti-entropy block offers, and neighbors' answers to ours
+                let message = match self
+                    .handle_unsolicited_block_offer_message(chainstate, event_id, message)
+                {
+                    Some(message) => message,
+                    None => {
+                        continue;
+                    }
+                };
+
+                // answers to our header requests
+                let message = match self.handle_unsolicited_headers_message(event_id, message) {
+                    Some(message) => message,
+                    None => {
+                        continue;
+                    }
+                };
+
+                // remember which transactions this neighbor has, and handle mempool pulls
+                let message = match self.handle_unsolicited_tx_gossip_message(
+                    sortdb, chainstate, mempool, event_id, message,
+                ) {
+                    Some(message) => message,
+                    None => {
+                        continue;
+                    }
+                };
+
+                let (to_buffer, relay) = self.handle_unsolicited_message(
+                    sortdb,
+                    chainstate,
+                    event_id,
+                    &message.payload,
+                    ibd,
+                    buffer,
+                );
+                if buffer && to_buffer {
+                    self.buffer_data_message(event_id, message);
+                } else if relay {
                     // forward to relayer for processing
                     debug!(
                         "{:?}: Will forward message {} from {:?} to relayer",
@@ -4878,6 +5549,22 @@ impl PeerNetwork {
     pub fn load_local_peer(&self) -> Result<LocalPeer, net_error> {
         let mut lp = PeerDB::get_local_peer(&self.peerdb.conn())?;
         lp.public_ip_address = self.local_peer.public_ip_address.clone();
+        lp.alt_public_ip_address = self.local_peer.alt_public_ip_address.clone();
+        if self.connection_opts.p2p_encryption {
+            lp.services |= ServiceFlags::ENCRYPTION as u16;
+        }
+        if self.connection_opts.compact_blocks {
+            lp.services |= ServiceFlags::COMPACT_BLOCKS as u16;
+        }
+        if self.connection_opts.p2p_compression {
+            lp.services |= ServiceFlags::COMPRESSION as u16;
+        }
+        if self.connection_opts.capability_exchange {
+            lp.services |= ServiceFlags::CAPABILITIES as u16;
+        }
+        lp.services |= ServiceFlags::MEMPOOL_TXS as u16;
+        lp.services |= ServiceFlags::BLOCK_OFFERS as u16;
+        lp.services |= ServiceFlags::HEADERS as u16;
         Ok(lp)
     }
 
@@ -4899,6 +5586,7 @@ impl PeerNetwork {
         &mut self,
         sortdb: &SortitionDB,
         chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
         ibd: bool,
     ) -> Result<HashMap<NeighborKey, Vec<StacksMessage>>, net_error> {
         // update burnchain snapshot if we need to (careful -- it's expensive)
@@ -4949,6 +5637,7 @@ impl PeerNetwork {
             ret = self.handle_unsolicited_messages(
                 sortdb,
                 chainstate,
+                mempool,
                 buffered_messages,
                 ibd,
                 false,
@@ -4961,6 +5650,75 @@ impl PeerNetwork {
         Ok(ret)
     }
 
+    /// Did the canonical Stacks chain tip just move to a block that does not descend from the tip
+    /// in `prior_burnchain_tip`?  If so, remember the reorg for /v2/info and return it.
+    /// Best-effort: failing to look up either tip just means no reorg is reported.
+    fn check_stacks_reorg(
+        &mut self,
+        chainstate: &StacksChainState,
+        prior_burnchain_tip: &BlockSnapshot,
+    ) -> Option<RPCChainReorgData> {
+        let old_tip = StacksBlockHeader::make_index_block_hash(
+            &prior_burnchain_tip.canonical_stacks_tip_consensus_hash,
+            &prior_burnchain_tip.canonical_stacks_tip_hash,
+        );
+        let new_tip = StacksBlockHeader::make_index_block_hash(
+            &self.burnchain_tip.canonical_stacks_tip_consensus_hash,
+            &self.burnchain_tip.canonical_stacks_tip_hash,
+        );
+        if old_tip == new_tip {
+            return None;
+        }
+
+        let ancestor = match chainstate.get_common_ancestor(&old_tip, &new_tip) {
+            Ok(Some(ancestor)) => ancestor,
+            Ok(None) => {
+                return None;
+            }
+            Err(e) => {
+                warn!(
+                    "{:?}: Failed to find common ancestor of {} and {}: {:?}",
+                    &self.local_peer, &old_tip, &new_tip, &e
+                );
+                return None;
+            }
+        };
+        if ancestor.index_block_hash() == old_tip {
+            // the chain just grew
+            return None;
+        }
+
+        let reorg = RPCChainReorgData {
+            orphaned_tip: old_tip,
+            orphaned_tip_height: prior_burnchain_tip.canonical_stacks_tip_height,
+            new_tip,
+            new_tip_height: self.burnchain_tip.canonical_stacks_tip_height,
+            common_ancestor: ancestor.index_block_hash(),
+            common_ancestor_height: ancestor.block_height,
+            depth: prior_burnchain_tip
+                .canonical_stacks_tip_height
+                .saturating_sub(ancestor.block_height),
+            burn_block_height: self.burnchain_tip.block_height,
+        };
+        info!(
+            "{:?}: Stacks chain reorg of depth {}: {} (height {}) replaced by {} (height {}), common ancestor {} (height {})",
+            &self.local_peer,
+            reorg.depth,
+            &reorg.orphaned_tip,
+            reorg.orphaned_tip_height,
+            &reorg.new_tip,
+            reorg.new_tip_height,
+            &reorg.common_ancestor,
+            reorg.common_ancestor_height
+        );
+
+        self.recent_reorgs.push_back(reorg.clone());
+        while self.recent_reorgs.len() as u64 > self.connection_opts.max_recent_reorgs {
+            self.recent_reorgs.pop_front();
+        }
+        Some(reorg)
+    }
+
     /// Update p2p networking state.
     /// -- accept new connections
     /// -- send data on ready sockets
@@ -5001,13 +5759,22 @@ impl PeerNetwork {
             );
             self.deregister_peer(error_event);
         }
-        let unhandled_messages =
-            self.handle_unsolicited_messages(sortdb, chainstate, unsolicited_messages, ibd, true)?;
+        let unhandled_messages = self.handle_unsolicited_messages(
+            sortdb,
+            chainstate,
+            mempool,
+            unsolicited_messages,
+            ibd,
+            true,
+        )?;
         network_result.consume_unsolicited(unhandled_messages);
 
         // schedule now-authenticated inbound convos for pingback
         self.schedule_network_pingbacks(unauthenticated_inbounds)?;
 
+        // find bootstrap peers, if we have DNS seeds
+        self.resolve_dns_seeds(&mut dns_client_opt);
+
         // do some Actual Work(tm)
         // do this _after_ processing new sockets, so the act of opening a socket doesn't trample
         // an already-used network ID.
@@ -5020,6 +5787,37 @@ impl PeerNetwork {
             ibd,
             network_result,
         )?;
+
+        // schedule neighbors with bad reputations to be banned
+        self.update_peer_reputations();
+
+        // advertise our mapped p2p port, if the gateway gave us one
+        self.poll_port_mapping();
+
+        // give up on compact blocks that we couldn't finish
+        self.prune_pending_compact_blocks();
+
+        // give up on block offers that were never answered
+        self.prune_pending_block_offers();
+
+        // give up on header requests that were never answered
+        self.prune_header_sync_state();
+
+        // remember how good our outbound neighbors are, in case we restart
+        self.do_save_peer_scores();
+
+        // disconnect inbound peers that handshaked into a class that's out of slots
+        self.prune_peer_class_slots();
+
+        // pull missing mempool transactions from a neighbor, if it's time to
+        self.prune_tx_gossip_state();
+        if let Err(e) = self.do_mempool_pull(mempool, ibd) {
+            warn!(
+                "{:?}: Failed to pull mempool transactions: {:?}",
+                &self.local_peer, &e
+            );
+        }
+
         if do_prune {
             // prune back our connections if it's been a while
             // (only do this if we're done with all other tasks).
@@ -5035,6 +5833,9 @@ impl PeerNetwork {
             self.prune_connections();
         }
 
+        // reconnect to our best peers from the last run before the neighbor walk finds new ones
+        self.do_peer_warm_start();
+
         // In parallel, do a neighbor walk
         self.do_network_neighbor_walk(ibd)?;
 
@@ -5262,8 +6063,14 @@ impl PeerNetwork {
         self.refresh_local_peer()?;
 
         // update burnchain view, before handling any HTTP connections
-        let unsolicited_buffered_messages = self.refresh_burnchain_view(sortdb, chainstate, ibd)?;
+        let prior_burnchain_tip = self.burnchain_tip.clone();
+        let unsolicited_buffered_messages =
+            self.refresh_burnchain_view(sortdb, chainstate, mempool, ibd)?;
         network_result.consume_unsolicited(unsolicited_buffered_messages);
+        if let Some(reorg) = self.check_stacks_reorg(chainstate, &prior_burnchain_tip) {
+            network_result.reorgs.push(reorg);
+        }
+        network_result.stale_tip = self.check_stale_tip(ibd);
 
         // update PoX view, before handling any HTTP connections
         self.refresh_sortition_view(sortdb)?;
@@ -5286,6 +6093,10 @@ impl PeerNetwork {
             }
         }
 
+        // if we're running out of file descriptors, make room before handling any new connections
+        self.refresh_open_files();
+        self.shed_connections();
+
         PeerNetwork::with_network_state(self, |ref mut network, ref mut network_state| {
             let http_stacks_msgs = PeerNetwork::with_http(network, |ref mut net, ref mut http| {
                 http.run(
@@ -5346,6 +6157,7 @@ mod test {
     use crate::types::chainstate::BurnchainHeaderHash;
 
     use super::*;
+    use net::reputation::MAX_REPUTATION;
 
     fn make_random_peer_address() -> PeerAddress {
         let mut rng = rand::thread_rng();
@@ -6149,4 +6961,509 @@ mod test {
             }
         });
     }
+
+    #[test]
+    #[ignore]
+    fn test_mempool_pull_2_peers() {
+        with_timeout(600, || {
+            // peer 1 gets some transactions; verify peer 2 pulls them all over p2p
+            let mut peer_1_config = TestPeerConfig::new("test_mempool_pull_2_peers", 2245, 2246);
+            let mut peer_2_config = TestPeerConfig::new("test_mempool_pull_2_peers", 2247, 2248);
+
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+            peer_2_config.add_neighbor(&peer_1_config.to_neighbor());
+
+            peer_1_config.connection_opts.mempool_txs_min_interval = 0;
+            peer_2_config.connection_opts.mempool_pull_interval = 1;
+
+            let num_txs = 300;
+            let pks: Vec<_> = (0..num_txs).map(|_| StacksPrivateKey::new()).collect();
+            let addrs: Vec<_> = pks.iter().map(to_addr).collect();
+            let initial_balances: Vec<_> = addrs
+                .iter()
+                .map(|a| (a.to_account_principal(), 1000000000))
+                .collect();
+
+            peer_1_config.initial_balances = initial_balances.clone();
+            peer_2_config.initial_balances = initial_balances.clone();
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+
+            // no HTTP mempool sync
+            peer_1.network.mempool_sync_deadline = u64::MAX;
+            peer_2.network.mempool_sync_deadline = u64::MAX;
+
+            let num_blocks = 10;
+            for i in 0..num_blocks {
+                let (burn_ops, stacks_block, microblocks) = peer_2.make_default_tenure();
+
+                peer_1.next_burnchain_block(burn_ops.clone());
+                peer_2.next_burnchain_block(burn_ops.clone());
+
+                peer_1.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+                peer_2.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+            }
+
+            let addr = StacksAddress {
+                version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                bytes: Hash160([0xff; 20]),
+            };
+
+            // fill peer 1 with lots of transactions
+            let mut txs = HashMap::new();
+            let mut peer_1_mempool = peer_1.mempool.take().unwrap();
+            let mut mempool_tx = peer_1_mempool.tx_begin().unwrap();
+            for (i, pk) in pks.iter().enumerate() {
+                let mut tx = StacksTransaction {
+                    version: TransactionVersion::Testnet,
+                    chain_id: 0x80000000,
+                    auth: TransactionAuth::from_p2pkh(pk).unwrap(),
+                    anchor_mode: TransactionAnchorMode::Any,
+                    post_condition_mode: TransactionPostConditionMode::Allow,
+                    post_conditions: vec![],
+                    payload: TransactionPayload::TokenTransfer(
+                        addr.to_account_principal(),
+                        123,
+                        TokenTransferMemo([0u8; 34]),
+                    ),
+                };
+                tx.set_tx_fee(1000);
+                tx.set_origin_nonce(0);
+
+                let mut tx_signer = StacksTransactionSigner::new(&tx);
+                tx_signer.sign_origin(pk).unwrap();
+
+                let tx = tx_signer.get_tx().unwrap();
+
+                let txid = tx.txid();
+                let tx_bytes = tx.serialize_to_vec();
+                let origin_addr = tx.origin_address();
+                let origin_nonce = tx.get_origin_nonce();
+                let sponsor_addr = tx.sponsor_address().unwrap_or(origin_addr.clone());
+                let sponsor_nonce = tx.get_sponsor_nonce().unwrap_or(origin_nonce);
+                let tx_fee = tx.get_tx_fee();
+
+                txs.insert(tx.txid(), tx.clone());
+
+                // should succeed
+                MemPoolDB::try_add_tx(
+                    &mut mempool_tx,
+                    peer_1.chainstate(),
+                    &ConsensusHash([0x1 + (num_blocks as u8); 20]),
+                    &BlockHeaderHash([0x2 + (num_blocks as u8); 32]),
+                    txid.clone(),
+                    tx_bytes,
+                    tx_fee,
+                    num_blocks,
+                    &origin_addr,
+                    origin_nonce,
+                    &sponsor_addr,
+                    sponsor_nonce,
+                    None,
+                )
+                .unwrap();
+
+                eprintln!("Added {} {}", i, &txid);
+            }
+            mempool_tx.commit().unwrap();
+            peer_1.mempool = Some(peer_1_mempool);
+
+            let mut round = 0;
+            let mut peer_1_mempool_txs = 0;
+            let mut peer_2_mempool_txs = 0;
+
+            while peer_1_mempool_txs < num_txs || peer_2_mempool_txs < num_txs {
+                if let Ok(mut result) = peer_1.step() {
+                    let lp = peer_1.network.local_peer.clone();
+                    peer_1
+                        .with_db_state(|sortdb, chainstate, relayer, mempool| {
+                            relayer.process_network_result(
+                                &lp,
+                                &mut result,
+                                sortdb,
+                                chainstate,
+                                mempool,
+                                false,
+                                None,
+                                None,
+                            )
+                        })
+                        .unwrap();
+                }
+
+                if let Ok(mut result) = peer_2.step() {
+                    let lp = peer_2.network.local_peer.clone();
+                    peer_2
+                        .with_db_state(|sortdb, chainstate, relayer, mempool| {
+                            relayer.process_network_result(
+                                &lp,
+                                &mut result,
+                                sortdb,
+                                chainstate,
+                                mempool,
+                                false,
+                                None,
+                                None,
+                            )
+                        })
+                        .unwrap();
+                }
+
+                round += 1;
+
+                let mp = peer_1.mempool.take().unwrap();
+                peer_1_mempool_txs = MemPoolDB::get_all_txs(mp.conn()).unwrap().len();
+                peer_1.mempool.replace(mp);
+
+                let mp = peer_2.mempool.take().unwrap();
+                peer_2_mempool_txs = MemPoolDB::get_all_txs(mp.conn()).unwrap().len();
+                peer_2.mempool.replace(mp);
+
+                info!(
+                    "Peer 1: {}, Peer 2: {}",
+                    peer_1_mempool_txs, peer_2_mempool_txs
+                );
+            }
+
+            info!("Completed mempool pull in {} step(s)", round);
+
+            let mp = peer_2.mempool.take().unwrap();
+            let peer_2_mempool_txs = MemPoolDB::get_all_txs(mp.conn()).unwrap();
+            peer_2.mempool.replace(mp);
+
+            for tx in peer_2_mempool_txs {
+                assert_eq!(&tx.tx, txs.get(&tx.tx.txid()).unwrap());
+            }
+
+            // peer 1 remembers that peer 2 has them all now, so it won't relay them back
+            assert!(peer_1
+                .network
+                .tx_relay_filters
+                .values()
+                .any(|filter| txs.keys().all(|txid| filter.contains_raw(&txid.0))));
+        });
+    }
+
+    #[test]
+    fn test_mempool_sync_bootstrap_peers() {
+        let mut peer_config = TestPeerConfig::new("test_mempool_sync_bootstrap_peers", 2249, 2250);
+        peer_config.connection_opts.mempool_sync_bootstrap_peers = 2;
+        let mut peer = TestPeer::new(peer_config);
+
+        let url_1 = UrlString::from("http://127.0.0.1:20443");
+        let url_2 = UrlString::from("http://127.0.0.1:20444");
+
+        assert!(peer.network.is_mempool_sync_bootstrapping());
+
+        // syncing with the same peer twice only counts once
+        peer.network.mempool_sync_finished_with(&url_1);
+        peer.network.mempool_sync_finished_with(&url_1);
+        assert!(peer.network.is_mempool_sync_bootstrapping());
+
+        peer.network.mempool_sync_finished_with(&url_2);
+        assert!(!peer.network.is_mempool_sync_bootstrapping());
+        assert!(peer.network.mempool_sync_bootstrap_urls.is_empty());
+
+        // not having any peers yet doesn't end the bootstrap
+        let mut peer_config = TestPeerConfig::new("test_mempool_sync_bootstrap_peers", 2251, 2252);
+        peer_config.connection_opts.mempool_sync_bootstrap_peers = 2;
+        let mut peer = TestPeer::new(peer_config);
+
+        peer.network.mempool_sync_finished_with(&url_1);
+        assert!(peer
+            .network
+            .mempool_sync_pick_outbound_peer(&mut None, &Txid([0u8; 32]))
+            .unwrap()
+            .is_none());
+        assert!(peer.network.is_mempool_sync_bootstrapping());
+
+        // disabled by default
+        let peer_config = TestPeerConfig::new("test_mempool_sync_bootstrap_peers", 2253, 2254);
+        let peer = TestPeer::new(peer_config);
+        assert!(!peer.network.is_mempool_sync_bootstrapping());
+    }
+
+    #[test]
+    fn test_no_reorg_on_chain_extension() {
+        let peer_config = TestPeerConfig::new("test_no_reorg_on_chain_extension", 2230, 2231);
+        let mut peer = TestPeer::new(peer_config);
+
+        let mut tips = vec![];
+        for _ in 0..3 {
+            let (burn_ops, stacks_block, microblocks) = peer.make_default_tenure();
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            let network_result = peer.step().unwrap();
+            assert!(!network_result.has_reorgs());
+            assert!(peer.network.recent_reorgs.is_empty());
+
+            let (consensus_hash, block_hash) = SortitionDB::get_canonical_stacks_chain_tip_hash(
+                peer.sortdb.as_ref().unwrap().conn(),
+            )
+            .unwrap();
+            tips.push(StacksBlockId::new(&consensus_hash, &block_hash));
+        }
+
+        let chainstate = &peer.stacks_node.as_ref().unwrap().chainstate;
+
+        // a tip's common ancestor with any of its ancestors is that ancestor
+        for i in 0..tips.len() {
+            for j in i..tips.len() {
+                let ancestor = chainstate
+                    .get_common_ancestor(&tips[i], &tips[j])
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(ancestor.index_block_hash(), tips[i]);
+
+                let ancestor = chainstate
+                    .get_common_ancestor(&tips[j], &tips[i])
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(ancestor.index_block_hash(), tips[i]);
+            }
+        }
+
+        // unknown blocks have no common ancestor
+        assert!(chainstate
+            .get_common_ancestor(&tips[0], &StacksBlockId([0x11; 32]))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_ban_neighbor_with_bad_reputation() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(2232);
+            let peer_2_config = TestPeerConfig::from_port(2234);
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+            let neighbor_2 = peer_2.to_neighbor();
+
+            // bootstrap peers are never banned, so make peer 2 an ordinary neighbor
+            let mut tx = peer_1.network.peerdb.tx_begin().unwrap();
+            PeerDB::set_allow_peer(
+                &mut tx,
+                neighbor_2.addr.network_id,
+                &neighbor_2.addr.addrbytes,
+                neighbor_2.addr.port,
+                0,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+
+            // wait for peer 1 to handshake with peer 2
+            while peer_1
+                .network
+                .get_neighbor_stats(&neighbor_2.addr)
+                .map(|stats| stats.last_handshake_time == 0)
+                .unwrap_or(true)
+            {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+            }
+            assert_eq!(
+                peer_1.network.get_peer_reputation(&neighbor_2.addr),
+                MAX_REPUTATION
+            );
+
+            // peer 2 sends a few invalid messages
+            let event_id = *peer_1.network.events.get(&neighbor_2.addr).unwrap();
+            for _ in 0..3 {
+                peer_1
+                    .network
+                    .peers
+                    .get_mut(&event_id)
+                    .unwrap()
+                    .stats
+                    .add_misbehavior(PeerMisbehavior::InvalidMessage);
+            }
+            peer_1.network.update_peer_reputations();
+            assert_eq!(peer_1.network.get_peer_reputation(&neighbor_2.addr), 40.0);
+            assert!(peer_1.network.is_peer_deprioritized(&neighbor_2.addr));
+            assert!(peer_1.network.bans.is_empty());
+            assert_eq!(
+                peer_1
+                    .network
+                    .get_neighbor_stats(&neighbor_2.addr)
+                    .unwrap()
+                    .reputation,
+                40.0
+            );
+
+            // and then a few more, and gets banned
+            for _ in 0..2 {
+                peer_1
+                    .network
+                    .record_peer_misbehavior(&neighbor_2.addr, PeerMisbehavior::InvalidMessage);
+            }
+            peer_1.network.update_peer_reputations();
+            assert!(peer_1.network.bans.contains(&event_id));
+
+            loop {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+
+                let denied = PeerDB::get_peer(
+                    peer_1.network.peerdb.conn(),
+                    neighbor_2.addr.network_id,
+                    &neighbor_2.addr.addrbytes,
+                    neighbor_2.addr.port,
+                )
+                .unwrap()
+                .unwrap()
+                .denied;
+                if denied > (get_epoch_time_secs() as i64) {
+                    break;
+                }
+            }
+        })
+    }
+
+    #[test]
+    #[ignore]
+    fn test_encrypted_p2p_handshake() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(2236);
+            let mut peer_2_config = TestPeerConfig::from_port(2238);
+            peer_1_config.connection_opts.p2p_encryption = true;
+            peer_2_config.connection_opts.p2p_encryption = true;
+            peer_2_config.connection_opts.p2p_require_encryption = true;
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+            let neighbor_2 = peer_2.to_neighbor();
+
+            assert!(peer_1.network.local_peer.services & (ServiceFlags::ENCRYPTION as u16) != 0);
+
+            while peer_1
+                .network
+                .get_neighbor_stats(&neighbor_2.addr)
+                .map(|stats| stats.last_handshake_time == 0)
+                .unwrap_or(true)
+            {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+            }
+
+            let convo_1 = peer_1.network.get_convo(&neighbor_2.addr).unwrap();
+            assert!(convo_1.transport.is_encrypted());
+            assert!(convo_1.peer_services & (ServiceFlags::ENCRYPTION as u16) != 0);
+            assert_eq!(
+                convo_1
+                    .transport
+                    .remote_public_key()
+                    .unwrap()
+                    .to_bytes_compressed(),
+                Secp256k1PublicKey::from_private(&peer_2.network.local_peer.private_key)
+                    .to_bytes_compressed()
+            );
+
+            let convo_2 = peer_2.network.peers.values().next().unwrap();
+            assert!(convo_2.transport.is_encrypted());
+        })
+    }
+
+    #[test]
+    #[ignore]
+    fn test_capability_exchange_handshake() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(2256);
+            let mut peer_2_config = TestPeerConfig::from_port(2258);
+            peer_1_config.connection_opts.capability_exchange = true;
+            peer_2_config.connection_opts.capability_exchange = true;
+            peer_2_config.connection_opts.compact_blocks = true;
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+            let neighbor_2 = peer_2.to_neighbor();
+
+            assert!(peer_1.network.local_peer.services & (ServiceFlags::CAPABILITIES as u16) != 0);
+
+            // both sides send their capability versions once the handshake completes
+            loop {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+
+                let recv_1 = peer_1
+                    .network
+                    .get_convo(&neighbor_2.addr)
+                    .map(|convo| {
+                        convo
+                            .stats
+                            .get_message_recv_count(StacksMessageID::Capabilities)
+                    })
+                    .unwrap_or(0);
+                let recv_2 = peer_2
+                    .network
+                    .peers
+                    .values()
+                    .map(|convo| {
+                        convo
+                            .stats
+                            .get_message_recv_count(StacksMessageID::Capabilities)
+                    })
+                    .sum::<u64>();
+                if recv_1 > 0 && recv_2 > 0 {
+                    break;
+                }
+            }
+
+            let convo_1 = peer_1.network.get_convo(&neighbor_2.addr).unwrap();
+            assert!(convo_1.supports(Capability::CompactBlocks));
+            assert_eq!(
+                convo_1.capability_version(Capability::CompactBlocks),
+                Some(Capability::CompactBlocks.local_version())
+            );
+            assert_eq!(
+                convo_1
+                    .peer_capability_versions
+                    .get(&(ServiceFlags::COMPACT_BLOCKS as u16)),
+                Some(&Capability::CompactBlocks.local_version())
+            );
+
+            let convo_2 = peer_2.network.peers.values().next().unwrap();
+            assert!(!convo_2.supports(Capability::CompactBlocks));
+            assert_eq!(convo_2.capability_version(Capability::CompactBlocks), None);
+            assert_eq!(
+                convo_2.capability_version(Capability::MempoolTxs),
+                Some(Capability::MempoolTxs.local_version())
+            );
+        })
+    }
+
+    #[test]
+    #[ignore]
+    fn test_encrypted_p2p_handshake_plaintext_fallback() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(2240);
+            let peer_2_config = TestPeerConfig::from_port(2242);
+            peer_1_config.connection_opts.p2p_encryption = true;
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+            let neighbor_2 = peer_2.to_neighbor();
+
+            // peer 2 doesn't speak Noise, so peer 1 gives up on it and talks in plaintext
+            while peer_1
+                .network
+                .get_neighbor_stats(&neighbor_2.addr)
+                .map(|stats| stats.last_handshake_time == 0)
+                .unwrap_or(true)
+            {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+            }
+
+            assert!(peer_1.network.plaintext_peers.contains(&neighbor_2.addr));
+            let convo_1 = peer_1.network.get_convo(&neighbor_2.addr).unwrap();
+            assert!(convo_1.transport.is_plaintext());
+        })
+    }
 }
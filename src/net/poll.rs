@@ -19,11 +19,16 @@ use net::Neighbor;
 use net::NeighborKey;
 use net::PeerAddress;
 
+#[cfg(unix)]
+use net::unix::{UnixSocketListener, UnixSocketStream};
+
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::fdlimit;
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
 use std::io;
 use std::io::Error as io_error;
 use std::io::ErrorKind;
@@ -31,6 +36,8 @@ use std::io::Read;
 use std::io::Write;
 use std::net;
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::Path;
 use std::time;
 use std::time::Duration;
 
@@ -39,19 +46,26 @@ use util::sleep_ms;
 
 use mio;
 use mio::net as mio_net;
+use mio::Evented;
 use mio::PollOpt;
 use mio::Ready;
 use mio::Token;
+use net2::TcpBuilder;
 
 use std::net::Shutdown;
 
 use rand;
 use rand::RngCore;
 
+use monitoring::increment_connections_refused_counter;
+
 const SERVER: Token = mio::Token(0);
 
 pub struct NetworkPollState {
     pub new: HashMap<usize, mio_net::TcpStream>,
+    /// new connections on a unix domain socket server
+    #[cfg(unix)]
+    pub new_unix: HashMap<usize, UnixSocketStream>,
     pub ready: Vec<usize>,
 }
 
@@ -59,16 +73,73 @@ impl NetworkPollState {
     pub fn new() -> NetworkPollState {
         NetworkPollState {
             new: HashMap::new(),
+            #[cfg(unix)]
+            new_unix: HashMap::new(),
             ready: vec![],
         }
     }
 }
 
+/// A connected socket that can be registered with the poller
+pub trait NetworkSocket: Evented + fmt::Debug {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+}
+
+impl NetworkSocket for mio_net::TcpStream {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        mio_net::TcpStream::shutdown(self, how)
+    }
+}
+
+#[cfg(unix)]
+impl NetworkSocket for UnixSocketStream {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        UnixSocketStream::shutdown(self, how)
+    }
+}
+
+/// The listening socket of a network server
+#[derive(Debug)]
+enum NetworkListener {
+    Tcp(mio_net::TcpListener),
+    #[cfg(unix)]
+    Unix(UnixSocketListener),
+}
+
+/// A connection accepted by a network server
+#[derive(Debug)]
+enum AcceptedSocket {
+    Tcp(mio_net::TcpStream),
+    #[cfg(unix)]
+    Unix(UnixSocketStream),
+}
+
+impl NetworkListener {
+    fn accept(&self) -> io::Result<AcceptedSocket> {
+        match self {
+            NetworkListener::Tcp(listener) => {
+                listener.accept().map(|(sock, _)| AcceptedSocket::Tcp(sock))
+            }
+            #[cfg(unix)]
+            NetworkListener::Unix(listener) => listener.accept().map(AcceptedSocket::Unix),
+        }
+    }
+}
+
+impl AcceptedSocket {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            AcceptedSocket::Tcp(sock) => sock.shutdown(how),
+            #[cfg(unix)]
+            AcceptedSocket::Unix(sock) => sock.shutdown(how),
+        }
+    }
+}
+
 // state for a single network server
 #[derive(Debug)]
 pub struct NetworkServerState {
-    addr: SocketAddr,
-    server_socket: mio_net::TcpListener,
+    server_socket: NetworkListener,
     server_event: mio::Token,
 }
 
@@ -106,9 +177,32 @@ impl NetworkState {
         self.event_map.len()
     }
 
+    /// How many more sockets can be registered
+    pub fn num_free_events(&self) -> usize {
+        (self.event_capacity + self.servers.len()).saturating_sub(self.event_map.len())
+    }
+
+    /// Make a listening socket.  An IPv6 socket also accepts IPv4 connections (as IPv4-mapped
+    /// addresses) regardless of the host's default, so binding to `[::]` listens on both families.
+    fn listen(addr: &SocketAddr) -> io::Result<mio_net::TcpListener> {
+        let builder = match addr {
+            SocketAddr::V4(_) => TcpBuilder::new_v4()?,
+            SocketAddr::V6(_) => {
+                let builder = TcpBuilder::new_v6()?;
+                builder.only_v6(false)?;
+                builder
+            }
+        };
+        if cfg!(unix) {
+            builder.reuse_address(true)?;
+        }
+        let listener = builder.bind(addr)?.listen(1024)?;
+        mio_net::TcpListener::from_std(listener)
+    }
+
     fn bind_address(addr: &SocketAddr) -> Result<mio_net::TcpListener, net_error> {
         if !cfg!(test) {
-            mio_net::TcpListener::bind(addr).map_err(|e| {
+            NetworkState::listen(addr).map_err(|e| {
                 error!("Failed to bind to {:?}: {:?}", addr, e);
                 net_error::BindError
             })
@@ -117,7 +211,7 @@ impl NetworkState {
             let mut rng = rand::thread_rng();
             let mut count = 1000;
             loop {
-                match mio_net::TcpListener::bind(addr) {
+                match NetworkState::listen(addr) {
                     Ok(server) => {
                         return Ok(server);
                     }
@@ -146,22 +240,42 @@ impl NetworkState {
     /// Returns the handle to the poll state, used to key network poll events.
     pub fn bind(&mut self, addr: &SocketAddr) -> Result<usize, net_error> {
         let server = NetworkState::bind_address(addr)?;
+        self.add_server(NetworkListener::Tcp(server))
+    }
+
+    /// Bind to a unix domain socket at the given path, with the given file permission bits.
+    /// New connections show up in `NetworkPollState::new_unix`.
+    /// Returns the handle to the poll state, used to key network poll events.
+    #[cfg(unix)]
+    pub fn bind_unix(&mut self, path: &Path, mode: u32) -> Result<usize, net_error> {
+        let server = UnixSocketListener::bind(path, mode)?;
+        self.add_server(NetworkListener::Unix(server))
+    }
+
+    fn add_server(&mut self, server: NetworkListener) -> Result<usize, net_error> {
         let next_server_event = self.next_event_id()?;
 
-        self.poll
-            .register(
-                &server,
+        let res = match server {
+            NetworkListener::Tcp(ref listener) => self.poll.register(
+                listener,
                 mio::Token(next_server_event),
                 Ready::all(),
                 PollOpt::edge(),
-            )
-            .map_err(|e| {
-                error!("Failed to register server socket: {:?}", &e);
-                net_error::BindError
-            })?;
+            ),
+            #[cfg(unix)]
+            NetworkListener::Unix(ref listener) => self.poll.register(
+                listener,
+                mio::Token(next_server_event),
+                Ready::all(),
+                PollOpt::edge(),
+            ),
+        };
+        res.map_err(|e| {
+            error!("Failed to register server socket: {:?}", &e);
+            net_error::BindError
+        })?;
 
         let network_server = NetworkServerState {
-            addr: addr.clone(),
             server_socket: server,
             server_event: mio::Token(next_server_event),
         };
@@ -181,11 +295,11 @@ impl NetworkState {
     /// Try to use the given hint_event_id value, but generate a different event ID if it's been
     /// taken.
     /// Return the actual event ID used (it may be different than hint_event_id)
-    pub fn register(
+    pub fn register<S: NetworkSocket>(
         &mut self,
         server_event_id: usize,
         hint_event_id: usize,
-        sock: &mio_net::TcpStream,
+        sock: &S,
     ) -> Result<usize, net_error> {
         let hint_event_id = hint_event_id % (self.event_capacity + self.servers.len());
         if let Some(x) = self.event_map.get(&server_event_id) {
@@ -242,10 +356,10 @@ impl NetworkState {
     }
 
     /// Deregister a socket event
-    pub fn deregister(
+    pub fn deregister<S: NetworkSocket>(
         &mut self,
         event_id: usize,
-        sock: &mio_net::TcpStream,
+        sock: &S,
     ) -> Result<(), net_error> {
         assert!(
             self.event_map.contains_key(&event_id),
@@ -386,12 +500,22 @@ impl NetworkState {
                     ));
 
                     loop {
-                        let (client_sock, client_addr) = match server.server_socket.accept() {
-                            Ok((client_sock, client_addr)) => (client_sock, client_addr),
+                        let client_sock = match server.server_socket.accept() {
+                            Ok(client_sock) => client_sock,
                             Err(e) => match e.kind() {
                                 ErrorKind::WouldBlock => {
                                     break;
                                 }
+                                _ if fdlimit::is_out_of_files(&e) => {
+                                    // leave the rest in the listen backlog until we free up
+                                    // some file descriptors
+                                    warn!(
+                                        "Out of file descriptors; not accepting connections on {:?}",
+                                        &server.server_socket
+                                    );
+                                    increment_connections_refused_counter();
+                                    break;
+                                }
                                 _ => {
                                     error!("Network error: {}", e);
                                     return Err(net_error::AcceptError);
@@ -417,16 +541,23 @@ impl NetworkState {
                         new_events.insert(next_event_id);
 
                         debug!(
-                            "New socket event: {}, {:?} addr={:?} (Events total: {}, max: {}) on server {:?}",
+                            "New socket event: {}, {:?} (Events total: {}, max: {}) on server {:?}",
                             next_event_id,
                             &client_sock,
-                            &client_addr,
                             self.event_map.len(),
                             self.event_capacity,
                             &server.server_socket
                         );
 
-                        poll_state.new.insert(next_event_id, client_sock);
+                        match client_sock {
+                            AcceptedSocket::Tcp(sock) => {
+                                poll_state.new.insert(next_event_id, sock);
+                            }
+                            #[cfg(unix)]
+                            AcceptedSocket::Unix(sock) => {
+                                poll_state.new_unix.insert(next_event_id, sock);
+                            }
+                        }
                     }
 
                     is_server_event = true;
@@ -488,6 +619,29 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_bind_dual_stack() {
+        let addr = "[::]:49045".parse::<SocketAddr>().unwrap();
+        let listener = NetworkState::listen(&addr).unwrap();
+        for client_addr in ["127.0.0.1:49045", "[::1]:49045"].iter() {
+            let client_addr = client_addr.parse::<SocketAddr>().unwrap();
+            let _client = net::TcpStream::connect(client_addr).unwrap();
+            let (_sock, peer_addr) = loop {
+                match listener.accept() {
+                    Ok(accepted) => break accepted,
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => sleep_ms(10),
+                    Err(e) => panic!("{:?}", &e),
+                }
+            };
+
+            // IPv4 clients show up as IPv4-mapped IPv6 addresses
+            assert_eq!(
+                PeerAddress::canonical_socketaddr(&peer_addr).ip(),
+                client_addr.ip()
+            );
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_register_deregister() {
@@ -622,4 +776,35 @@ mod test {
             assert!(ns.make_next_event_id(count, &in_use).is_none());
         }
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_bind_unix() {
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("test_bind_unix.{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut ns = NetworkState::new(100).unwrap();
+        let server_event_id = ns.bind_unix(&path, 0o600).unwrap();
+
+        let _client = UnixStream::connect(&path).unwrap();
+
+        let mut accepted = None;
+        for _ in 0..10 {
+            let mut poll_states = ns.poll(100).unwrap();
+            let poll_state = poll_states.get_mut(&server_event_id).unwrap();
+            assert!(poll_state.new.is_empty());
+            if let Some(eid) = poll_state.new_unix.keys().next().cloned() {
+                accepted = poll_state.new_unix.remove(&eid).map(|sock| (eid, sock));
+                break;
+            }
+        }
+
+        let (hint_event_id, sock) = accepted.expect("no connection on the unix socket");
+        let event_id = ns.register(server_event_id, hint_event_id, &sock).unwrap();
+        assert_eq!(ns.num_events(), 2);
+        ns.deregister(event_id, &sock).unwrap();
+        assert_eq!(ns.num_events(), 1);
+    }
 }
@@ -0,0 +1,641 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Port mapping on home routers, so that a node behind NAT can accept inbound p2p connections.
+//! A node with `port_mapping` set asks its gateway -- over UPnP IGD, or NAT-PMP (RFC 6886) -- to
+//! forward a public TCP port to its p2p port, and advertises the gateway's public address and
+//! that port in its handshakes.
+//!
+//! Gateways talk slowly and mappings expire, so a `PortMapper` runs on its own thread: it maps
+//! the port, renews the mapping halfway through its lifetime, and reports each new (or lost)
+//! mapping to the peer network over a channel.
+
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use igd;
+
+use net::Error as net_error;
+use net::PeerAddress;
+
+/// The port a NAT-PMP gateway listens on
+pub const NATPMP_PORT: u16 = 5351;
+
+const NATPMP_VERSION: u8 = 0;
+const NATPMP_OP_EXTERNAL_ADDRESS: u8 = 0;
+const NATPMP_OP_MAP_TCP: u8 = 2;
+const NATPMP_OP_REPLY: u8 = 0x80;
+
+/// NAT-PMP requests are retransmitted after 250ms, then after doubling intervals (RFC 6886,
+/// section 3.1)
+const NATPMP_INITIAL_RETRY_MS: u64 = 250;
+
+/// How long to wait before trying again after failing to map the port
+const PORT_MAPPING_RETRY_SECS: u64 = 300;
+
+/// How long to wait for the gateway to answer
+const PORT_MAPPING_TIMEOUT_SECS: u64 = 10;
+
+const PORT_MAPPING_DESCRIPTION: &str = "stacks-node p2p";
+
+/// How (or whether) to map the p2p port on the node's gateway
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortMappingMethod {
+    Disabled,
+    UPnP,
+    NatPmp,
+    /// try UPnP, then NAT-PMP
+    Any,
+}
+
+impl PortMappingMethod {
+    pub const ALL: &'static [PortMappingMethod] = &[
+        PortMappingMethod::Disabled,
+        PortMappingMethod::UPnP,
+        PortMappingMethod::NatPmp,
+        PortMappingMethod::Any,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            PortMappingMethod::Disabled => "none",
+            PortMappingMethod::UPnP => "upnp",
+            PortMappingMethod::NatPmp => "natpmp",
+            PortMappingMethod::Any => "any",
+        }
+    }
+}
+
+impl fmt::Display for PortMappingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for PortMappingMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PortMappingMethod, String> {
+        PortMappingMethod::ALL
+            .iter()
+            .find(|method| method.as_str() == s)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<_> = PortMappingMethod::ALL.iter().map(|m| m.as_str()).collect();
+                format!(
+                    "Unknown port mapping method '{}'; expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// A public port that the gateway forwards to our p2p port
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortMapping {
+    /// UPnP or NAT-PMP
+    pub method: PortMappingMethod,
+    pub gateway: SocketAddr,
+    pub external_addr: PeerAddress,
+    pub external_port: u16,
+    /// seconds until the gateway drops the mapping
+    pub lifetime: u64,
+}
+
+fn port_mapping_error(msg: &str) -> net_error {
+    net_error::PortMappingError(msg.to_string())
+}
+
+/// Encode a NAT-PMP request for the gateway's external address
+pub fn natpmp_external_address_request() -> Vec<u8> {
+    vec![NATPMP_VERSION, NATPMP_OP_EXTERNAL_ADDRESS]
+}
+
+/// Encode a NAT-PMP request to map a TCP port.  A lifetime of 0 removes the mapping.
+pub fn natpmp_map_request(internal_port: u16, external_port: u16, lifetime: u32) -> Vec<u8> {
+    let mut req = vec![NATPMP_VERSION, NATPMP_OP_MAP_TCP, 0, 0];
+    req.extend_from_slice(&internal_port.to_be_bytes());
+    req.extend_from_slice(&external_port.to_be_bytes());
+    req.extend_from_slice(&lifetime.to_be_bytes());
+    req
+}
+
+/// Check a NAT-PMP reply's header, and return its body (after the seconds-since-epoch field)
+fn natpmp_reply_body(reply: &[u8], op: u8, len: usize) -> Result<&[u8], net_error> {
+    if reply.len() < len || reply[0] != NATPMP_VERSION || reply[1] != NATPMP_OP_REPLY | op {
+        return Err(port_mapping_error("Malformed NAT-PMP reply"));
+    }
+    let result_code = u16::from_be_bytes([reply[2], reply[3]]);
+    if result_code != 0 {
+        return Err(net_error::PortMappingError(format!(
+            "NAT-PMP gateway refused the request (result code {})",
+            result_code
+        )));
+    }
+    Ok(&reply[8..len])
+}
+
+/// Decode a NAT-PMP external address reply
+pub fn natpmp_parse_external_address(reply: &[u8]) -> Result<Ipv4Addr, net_error> {
+    let body = natpmp_reply_body(reply, NATPMP_OP_EXTERNAL_ADDRESS, 12)?;
+    Ok(Ipv4Addr::new(body[0], body[1], body[2], body[3]))
+}
+
+/// Decode a NAT-PMP TCP mapping reply into the mapped external port and the mapping's lifetime
+pub fn natpmp_parse_map_reply(reply: &[u8], internal_port: u16) -> Result<(u16, u32), net_error> {
+    let body = natpmp_reply_body(reply, NATPMP_OP_MAP_TCP, 16)?;
+    if u16::from_be_bytes([body[0], body[1]]) != internal_port {
+        return Err(port_mapping_error(
+            "NAT-PMP reply is for a different internal port",
+        ));
+    }
+    let external_port = u16::from_be_bytes([body[2], body[3]]);
+    let lifetime = u32::from_be_bytes([body[4], body[5], body[6], body[7]]);
+    Ok((external_port, lifetime))
+}
+
+/// Send a NAT-PMP request, retransmitting it with exponential backoff until the gateway answers
+/// or the timeout passes.
+fn natpmp_request(
+    gateway: &SocketAddr,
+    request: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>, net_error> {
+    let bind_addr: SocketAddr = if gateway.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind_addr).map_err(|_e| net_error::BindError)?;
+    socket
+        .connect(gateway)
+        .map_err(|_e| net_error::ConnectionError)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut retry = Duration::from_millis(NATPMP_INITIAL_RETRY_MS);
+    let mut buf = [0u8; 16];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(net_error::PortMappingError(format!(
+                "NAT-PMP gateway {} did not answer",
+                gateway
+            )));
+        }
+        socket
+            .send(request)
+            .map_err(|e| net_error::SendError(format!("{:?}", &e)))?;
+        socket
+            .set_read_timeout(Some(retry.min(deadline - now)))
+            .map_err(|_e| net_error::SocketError)?;
+        match socket.recv(&mut buf) {
+            Ok(len) => {
+                return Ok(buf[..len].to_vec());
+            }
+            Err(_) => {
+                // timed out (or ICMP port unreachable); try again
+                retry *= 2;
+            }
+        }
+    }
+}
+
+/// Map our p2p port with NAT-PMP
+pub fn natpmp_map_port(
+    gateway: &SocketAddr,
+    local_port: u16,
+    lifetime: u32,
+    timeout: Duration,
+) -> Result<PortMapping, net_error> {
+    let reply = natpmp_request(gateway, &natpmp_external_address_request(), timeout)?;
+    let external_addr = natpmp_parse_external_address(&reply)?;
+
+    let reply = natpmp_request(
+        gateway,
+        &natpmp_map_request(local_port, local_port, lifetime),
+        timeout,
+    )?;
+    let (external_port, lifetime) = natpmp_parse_map_reply(&reply, local_port)?;
+
+    Ok(PortMapping {
+        method: PortMappingMethod::NatPmp,
+        gateway: gateway.clone(),
+        external_addr: PeerAddress::from_ip(&IpAddr::V4(external_addr)),
+        external_port,
+        lifetime: lifetime as u64,
+    })
+}
+
+/// Our address on the network that the gateway is on
+fn local_ipv4_for(gateway: &SocketAddrV4) -> Result<Ipv4Addr, net_error> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|_e| net_error::BindError)?;
+    socket
+        .connect(gateway)
+        .map_err(|_e| net_error::ConnectionError)?;
+    match socket.local_addr().map_err(|_e| net_error::SocketError)? {
+        SocketAddr::V4(addr) => Ok(*addr.ip()),
+        SocketAddr::V6(_) => Err(port_mapping_error("No local IPv4 address")),
+    }
+}
+
+/// Map our p2p port with UPnP IGD.  We ask for the same public port as our p2p port, and take
+/// any port the gateway offers if that one is taken.
+pub fn upnp_map_port(
+    local_port: u16,
+    lifetime: u32,
+    timeout: Duration,
+) -> Result<PortMapping, net_error> {
+    let gateway = igd::search_gateway(igd::SearchOptions {
+        timeout: Some(timeout),
+        ..Default::default()
+    })
+    .map_err(|e| net_error::PortMappingError(format!("No UPnP gateway found: {}", e)))?;
+
+    let local_addr = SocketAddrV4::new(local_ipv4_for(&gateway.addr)?, local_port);
+    let external_port = match gateway.add_port(
+        igd::PortMappingProtocol::TCP,
+        local_port,
+        local_addr,
+        lifetime,
+        PORT_MAPPING_DESCRIPTION,
+    ) {
+        Ok(()) => local_port,
+        Err(e) => {
+            debug!(
+                "UPnP gateway {} would not map port {}: {}",
+                &gateway.addr, local_port, e
+            );
+            gateway
+                .add_any_port(
+                    igd::PortMappingProtocol::TCP,
+                    local_addr,
+                    lifetime,
+                    PORT_MAPPING_DESCRIPTION,
+                )
+                .map_err(|e| {
+                    net_error::PortMappingError(format!("UPnP gateway refused mapping: {}", e))
+                })?
+        }
+    };
+    let external_addr = gateway.get_external_ip().map_err(|e| {
+        net_error::PortMappingError(format!("UPnP gateway has no external address: {}", e))
+    })?;
+
+    Ok(PortMapping {
+        method: PortMappingMethod::UPnP,
+        gateway: SocketAddr::V4(gateway.addr),
+        external_addr: PeerAddress::from_ip(&IpAddr::V4(external_addr)),
+        external_port,
+        lifetime: lifetime as u64,
+    })
+}
+
+/// Find the default gateway in the contents of `/proc/net/route`
+pub fn parse_default_gateway(route_table: &str) -> Option<Ipv4Addr> {
+    for line in route_table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            continue;
+        }
+        // the kernel prints the address's network-order bytes as a host-order integer
+        if let Ok(gateway) = u32::from_str_radix(fields[2], 16) {
+            if gateway != 0 {
+                return Some(Ipv4Addr::from(u32::from_be(gateway)));
+            }
+        }
+    }
+    None
+}
+
+/// Find the default gateway.  Only Linux has a place to look this up.
+fn default_gateway() -> Option<Ipv4Addr> {
+    fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|route_table| parse_default_gateway(&route_table))
+}
+
+/// Map our p2p port with the given method
+pub fn map_port(
+    method: PortMappingMethod,
+    local_port: u16,
+    lifetime: u32,
+    natpmp_gateway: Option<Ipv4Addr>,
+) -> Result<PortMapping, net_error> {
+    let timeout = Duration::from_secs(PORT_MAPPING_TIMEOUT_SECS);
+    let mapping = match method {
+        PortMappingMethod::Disabled => {
+            return Err(port_mapping_error("Port mapping is disabled"));
+        }
+        PortMappingMethod::UPnP => upnp_map_port(local_port, lifetime, timeout)?,
+        PortMappingMethod::NatPmp | PortMappingMethod::Any => {
+            let upnp_res = if method == PortMappingMethod::Any {
+                upnp_map_port(local_port, lifetime, timeout)
+            } else {
+                Err(port_mapping_error("UPnP not tried"))
+            };
+            match upnp_res {
+                Ok(mapping) => mapping,
+                Err(e) => {
+                    if method == PortMappingMethod::Any {
+                        debug!("Failed to map port over UPnP, will try NAT-PMP: {:?}", &e);
+                    }
+                    let gateway = natpmp_gateway
+                        .or_else(default_gateway)
+                        .ok_or_else(|| port_mapping_error("No default gateway for NAT-PMP"))?;
+                    natpmp_map_port(
+                        &SocketAddr::new(IpAddr::V4(gateway), NATPMP_PORT),
+                        local_port,
+                        lifetime,
+                        timeout,
+                    )?
+                }
+            }
+        }
+    };
+
+    if mapping.external_addr.is_in_private_range() {
+        // we're behind another NAT too, so the mapping won't make us reachable
+        return Err(net_error::PortMappingError(format!(
+            "Gateway {} has a private external address {}",
+            &mapping.gateway, &mapping.external_addr
+        )));
+    }
+    Ok(mapping)
+}
+
+/// Remove a mapping we made.  Best-effort.
+fn unmap_port(mapping: &PortMapping, local_port: u16) {
+    let res = match mapping.method {
+        PortMappingMethod::UPnP => igd::search_gateway(igd::SearchOptions {
+            timeout: Some(Duration::from_secs(PORT_MAPPING_TIMEOUT_SECS)),
+            ..Default::default()
+        })
+        .map_err(|e| format!("{}", e))
+        .and_then(|gateway| {
+            gateway
+                .remove_port(igd::PortMappingProtocol::TCP, mapping.external_port)
+                .map_err(|e| format!("{}", e))
+        }),
+        _ => natpmp_request(
+            &mapping.gateway,
+            &natpmp_map_request(local_port, 0, 0),
+            Duration::from_secs(PORT_MAPPING_TIMEOUT_SECS),
+        )
+        .map(|_| ())
+        .map_err(|e| format!("{:?}", &e)),
+    };
+    if let Err(e) = res {
+        debug!("Failed to remove port mapping {:?}: {}", mapping, &e);
+    }
+}
+
+/// Keeps the p2p port mapped from a background thread.  The thread stops (and removes its
+/// mapping) once this is dropped.
+pub struct PortMapper {
+    /// `Some(..)` for each new or changed mapping, and `None` when the mapping is lost
+    receiver: Receiver<Option<PortMapping>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl PortMapper {
+    pub fn spawn(
+        method: PortMappingMethod,
+        local_port: u16,
+        lifetime: u64,
+        natpmp_gateway: Option<Ipv4Addr>,
+    ) -> Result<PortMapper, net_error> {
+        let (sender, receiver) = sync_channel(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        thread::Builder::new()
+            .name("port-mapper".to_string())
+            .spawn(move || {
+                PortMapper::run(
+                    method,
+                    local_port,
+                    lifetime,
+                    natpmp_gateway,
+                    sender,
+                    thread_stop,
+                )
+            })
+            .map_err(|e| net_error::PortMappingError(format!("{:?}", &e)))?;
+        Ok(PortMapper { receiver, stop })
+    }
+
+    fn run(
+        method: PortMappingMethod,
+        local_port: u16,
+        lifetime: u64,
+        natpmp_gateway: Option<Ipv4Addr>,
+        sender: SyncSender<Option<PortMapping>>,
+        stop: Arc<AtomicBool>,
+    ) {
+        let lifetime = lifetime.min(u32::MAX as u64) as u32;
+        let mut current: Option<PortMapping> = None;
+        while !stop.load(Ordering::SeqCst) {
+            let wait = match map_port(method, local_port, lifetime, natpmp_gateway) {
+                Ok(mapping) => {
+                    let renew = (mapping.lifetime / 2).max(1);
+                    if current.as_ref() != Some(&mapping) {
+                        info!(
+                            "Mapped p2p port {} to {}:{} over {} (gateway {})",
+                            local_port,
+                            &mapping.external_addr,
+                            mapping.external_port,
+                            mapping.method,
+                            &mapping.gateway
+                        );
+                        current = Some(mapping.clone());
+                        if sender.send(Some(mapping)).is_err() {
+                            break;
+                        }
+                    }
+                    renew
+                }
+                Err(e) => {
+                    warn!("Failed to map p2p port {}: {:?}", local_port, &e);
+                    if current.take().is_some() && sender.send(None).is_err() {
+                        break;
+                    }
+                    PORT_MAPPING_RETRY_SECS
+                }
+            };
+
+            let deadline = Instant::now() + Duration::from_secs(wait);
+            while !stop.load(Ordering::SeqCst) && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(500));
+            }
+        }
+
+        if let Some(mapping) = current {
+            unmap_port(&mapping, local_port);
+        }
+    }
+
+    /// Get the latest change to the mapping since we last asked, if there was one
+    pub fn try_recv(&self) -> Option<Option<PortMapping>> {
+        let mut latest = None;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(update) => latest = Some(update),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => {
+                    return latest;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for PortMapper {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl fmt::Debug for PortMapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PortMapper")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_natpmp_codec() {
+        assert_eq!(natpmp_external_address_request(), vec![0, 0]);
+        assert_eq!(
+            natpmp_map_request(20444, 20444, 3600),
+            vec![0, 2, 0, 0, 0x4f, 0xdc, 0x4f, 0xdc, 0x00, 0x00, 0x0e, 0x10]
+        );
+
+        let reply = [0, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7];
+        assert_eq!(
+            natpmp_parse_external_address(&reply).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+
+        let reply = [
+            0, 130, 0, 0, 0, 0, 0, 1, 0x4f, 0xdc, 0x4f, 0xdd, 0x00, 0x00, 0x07, 0x08,
+        ];
+        assert_eq!(
+            natpmp_parse_map_reply(&reply, 20444).unwrap(),
+            (20445, 1800)
+        );
+
+        // wrong internal port
+        assert!(natpmp_parse_map_reply(&reply, 20443).is_err());
+
+        // gateway said no (result code 2: not authorized)
+        let reply = [0, 130, 0, 2, 0, 0, 0, 1, 0x4f, 0xdc, 0, 0, 0, 0, 0, 0];
+        assert!(natpmp_parse_map_reply(&reply, 20444).is_err());
+
+        // wrong opcode
+        let reply = [0, 129, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7];
+        assert!(natpmp_parse_external_address(&reply).is_err());
+
+        // truncated
+        assert!(natpmp_parse_external_address(&[0, 128, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_natpmp_map_port() {
+        // a fake gateway that maps every request to the next port up
+        let gateway = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let gateway_addr = gateway.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 16];
+            for _ in 0..2 {
+                let (len, from) = gateway.recv_from(&mut buf).unwrap();
+                let reply = match buf[1] {
+                    0 => {
+                        assert_eq!(len, 2);
+                        vec![0, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7]
+                    }
+                    2 => {
+                        assert_eq!(len, 12);
+                        let external_port = u16::from_be_bytes([buf[6], buf[7]]) + 1;
+                        let mut reply = vec![0, 130, 0, 0, 0, 0, 0, 1, buf[4], buf[5]];
+                        reply.extend_from_slice(&external_port.to_be_bytes());
+                        reply.extend_from_slice(&buf[8..12]);
+                        reply
+                    }
+                    _ => panic!("unexpected opcode {}", buf[1]),
+                };
+                gateway.send_to(&reply, from).unwrap();
+            }
+        });
+
+        let mapping = natpmp_map_port(&gateway_addr, 20444, 3600, Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(mapping.method, PortMappingMethod::NatPmp);
+        assert_eq!(mapping.gateway, gateway_addr);
+        assert_eq!(
+            mapping.external_addr,
+            PeerAddress::from_ipv4(203, 0, 113, 7)
+        );
+        assert_eq!(mapping.external_port, 20445);
+        assert_eq!(mapping.lifetime, 3600);
+    }
+
+    #[test]
+    fn test_natpmp_gateway_timeout() {
+        // nobody answers
+        let gateway = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let res = natpmp_map_port(
+            &gateway.local_addr().unwrap(),
+            20444,
+            3600,
+            Duration::from_millis(600),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_default_gateway() {
+        let route_table =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                           eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                           eth0\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+        assert_eq!(
+            parse_default_gateway(route_table),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(parse_default_gateway("Iface\tDestination\tGateway\n"), None);
+    }
+
+    #[test]
+    fn test_port_mapping_method_from_str() {
+        for method in PortMappingMethod::ALL.iter() {
+            assert_eq!(
+                method.as_str().parse::<PortMappingMethod>().unwrap(),
+                *method
+            );
+        }
+        assert!("pcp".parse::<PortMappingMethod>().is_err());
+    }
+}
@@ -38,14 +38,23 @@ use util::db::Error as db_error;
 use std::net::Shutdown;
 use std::net::SocketAddr;
 
+use std::cmp;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 
+use util::fdlimit;
+use util::fdlimit::FileDescriptorUsage;
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::log;
 
+use monitoring::{increment_connections_shed_counter, update_open_files};
+
+/// how often to re-count our open file descriptors
+const OPEN_FILES_REFRESH_INTERVAL_MS: u128 = 1_000;
+
 use rand::prelude::*;
 use rand::thread_rng;
 
@@ -120,7 +129,21 @@ impl PeerNetwork {
     /// likely to be up for X more seconds, so we only really want to distinguish between nodes that
     /// have wildly different uptimes.
     /// Within uptime buckets, sort by health.
-    fn compare_neighbor_uptime_health(stats1: &NeighborStats, stats2: &NeighborStats) -> Ordering {
+    /// Neighbors whose reputation is below `deprioritize_threshold` sort before everyone else.
+    fn compare_neighbor_uptime_health(
+        stats1: &NeighborStats,
+        stats2: &NeighborStats,
+        deprioritize_threshold: f64,
+    ) -> Ordering {
+        let deprioritized_1 = stats1.reputation < deprioritize_threshold;
+        let deprioritized_2 = stats2.reputation < deprioritize_threshold;
+        if deprioritized_1 && !deprioritized_2 {
+            return Ordering::Less;
+        }
+        if !deprioritized_1 && deprioritized_2 {
+            return Ordering::Greater;
+        }
+
         let now = get_epoch_time_secs();
         let uptime_1 = (now - stats1.first_contact_time) as f64;
         let uptime_2 = (now - stats2.first_contact_time) as f64;
@@ -201,6 +224,7 @@ impl PeerNetwork {
             })
             .collect();
 
+        let deprioritize_threshold = self.connection_opts.reputation_deprioritize_threshold;
         for org in orgs.iter() {
             // sort each neighbor list by uptime and health.
             // bucket uptime geometrically by powers of 2 -- a node that's been up for X seconds is
@@ -211,7 +235,11 @@ impl PeerNetwork {
                 None => {}
                 Some(ref mut neighbor_infos) => {
                     neighbor_infos.sort_by(|&(ref _nk1, ref stats1), &(ref _nk2, ref stats2)| {
-                        PeerNetwork::compare_neighbor_uptime_health(stats1, stats2)
+                        PeerNetwork::compare_neighbor_uptime_health(
+                            stats1,
+                            stats2,
+                            deprioritize_threshold,
+                        )
                     });
                 }
             }
@@ -409,6 +437,175 @@ impl PeerNetwork {
         (inbound, outbound)
     }
 
+    /// Refresh our view of how many file descriptors we have open, and how many we're allowed.
+    /// Listing open descriptors isn't free, so this is done at most once a second.
+    pub fn refresh_open_files(&mut self) {
+        let now = get_epoch_time_ms();
+        if self.open_files_last_checked_ms + OPEN_FILES_REFRESH_INTERVAL_MS > now {
+            return;
+        }
+        self.open_files_last_checked_ms = now;
+
+        self.open_files = match fdlimit::get_open_files() {
+            Some(usage) => usage,
+            None => {
+                // can't see them, so just count our own sockets
+                let num_http = self
+                    .http
+                    .as_ref()
+                    .map(|http| http.sockets.len() + http.connecting.len())
+                    .unwrap_or(0);
+                let sockets = (self.sockets.len() + self.connecting.len() + num_http) as u64 + 2;
+                FileDescriptorUsage {
+                    total: sockets,
+                    sockets,
+                    databases: 0,
+                }
+            }
+        };
+
+        let process_limit = match fdlimit::get_open_files_limit() {
+            Ok((soft, _)) => soft,
+            Err(_) => u64::MAX,
+        };
+        self.open_files_limit = if self.connection_opts.max_open_files > 0 {
+            cmp::min(self.connection_opts.max_open_files, process_limit)
+        } else {
+            process_limit
+        };
+
+        update_open_files(
+            self.open_files.total as i64,
+            self.open_files.sockets as i64,
+            self.open_files.databases as i64,
+            cmp::min(self.open_files_limit, i64::MAX as u64) as i64,
+        );
+    }
+
+    /// How many file descriptors do we need to free up to get back out of the reserve?
+    pub fn open_files_deficit(&self) -> u64 {
+        let headroom = self.open_files_limit.saturating_sub(self.open_files.total);
+        self.connection_opts
+            .open_files_reserve
+            .saturating_sub(headroom)
+    }
+
+    /// Are we low enough on file descriptors that we should refuse new inbound connections?
+    pub fn is_short_on_files(&self) -> bool {
+        self.open_files_deficit() > 0
+    }
+
+    /// Find inbound connections to close in order to free up `count` file descriptors.
+    /// Returns (HTTP event IDs, p2p event IDs), in order from least to most valuable: idle HTTP
+    /// clients first, then unauthenticated p2p peers, and then authenticated p2p peers with the
+    /// least uptime and health.  Outbound connections are never shed.
+    fn find_connections_to_shed(&self, count: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut http_events = vec![];
+        if let Some(ref http) = self.http {
+            let mut http_inbound: Vec<_> = http
+                .peers
+                .iter()
+                .filter(|(_, convo)| convo.get_url().is_none())
+                .map(|(event_id, convo)| {
+                    let last_active = cmp::max(
+                        convo.get_connection_time(),
+                        cmp::max(
+                            convo.get_last_request_time(),
+                            convo.get_last_response_time(),
+                        ),
+                    );
+                    (*event_id, last_active)
+                })
+                .collect();
+            http_inbound.sort_by_key(|(event_id, last_active)| (*last_active, *event_id));
+            http_events = http_inbound
+                .into_iter()
+                .take(count)
+                .map(|(event_id, _)| event_id)
+                .collect();
+        }
+
+        let mut p2p_events = vec![];
+        let remaining = count - http_events.len();
+        if remaining > 0 {
+            let mut unauthenticated = vec![];
+            let mut authenticated = vec![];
+            for (event_id, convo) in self.peers.iter() {
+                if convo.is_outbound() {
+                    continue;
+                }
+                if convo.is_authenticated() {
+                    authenticated.push((*event_id, convo.stats.clone()));
+                } else {
+                    unauthenticated.push(*event_id);
+                }
+            }
+            unauthenticated.sort();
+            authenticated.sort_by(|(_, stats1), (_, stats2)| {
+                PeerNetwork::compare_neighbor_uptime_health(
+                    stats1,
+                    stats2,
+                    self.connection_opts.reputation_deprioritize_threshold,
+                )
+            });
+
+            p2p_events = unauthenticated
+                .into_iter()
+                .chain(authenticated.into_iter().map(|(event_id, _)| event_id))
+                .take(remaining)
+                .collect();
+        }
+
+        (http_events, p2p_events)
+    }
+
+    /// If we're running out of file descriptors, close our least-valuable inbound connections
+    /// until we're back out of the reserve.  Returns the number of connections closed.
+    pub fn shed_connections(&mut self) -> usize {
+        let deficit = self.open_files_deficit();
+        if deficit == 0 {
+            return 0;
+        }
+
+        let (http_events, p2p_events) = self.find_connections_to_shed(deficit as usize);
+        let num_shed = http_events.len() + p2p_events.len();
+        if num_shed == 0 {
+            debug!(
+                "{:?}: Running out of file descriptors, but have no inbound connections to shed",
+                &self.local_peer
+            );
+            return 0;
+        }
+
+        warn!(
+            "{:?}: Running out of file descriptors ({} open, limit {}, reserve {}); shedding {} HTTP and {} p2p inbound connection(s)",
+            &self.local_peer,
+            self.open_files.total,
+            self.open_files_limit,
+            self.connection_opts.open_files_reserve,
+            http_events.len(),
+            p2p_events.len()
+        );
+
+        if let (Some(ref mut http), Some(ref mut network)) =
+            (self.http.as_mut(), self.network.as_mut())
+        {
+            for event_id in http_events.into_iter() {
+                http.deregister_http(network, event_id);
+            }
+        }
+        for event_id in p2p_events.into_iter() {
+            self.deregister_peer(event_id);
+        }
+
+        // count these as closed until we look again
+        self.open_files.total = self.open_files.total.saturating_sub(num_shed as u64);
+        self.open_files.sockets = self.open_files.sockets.saturating_sub(num_shed as u64);
+
+        increment_connections_shed_counter(num_shed as i64);
+        num_shed
+    }
+
     /// Prune our frontier.  Ignore connections in the preserve set.
     pub fn prune_frontier(&mut self, preserve: &HashSet<usize>) -> () {
         let num_outbound = PeerNetwork::count_outbound_conversations(&self.peers);
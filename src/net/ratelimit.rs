@@ -0,0 +1,825 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! API-key authentication and rate limiting for the RPC interface.  Requests that carry a known
+//! API key draw from that key's token bucket; all other requests, including those with an
+//! unknown API key, draw from a bucket for the client's IP address.  IPv6 clients share a bucket
+//! with the rest of their /64.  Admin requests are not rate limited, but each wrong admin API key
+//! costs the client's IP address a token from a small, slow bucket, and an IP address with
+//! none left is turned away until it refills.
+//!
+//! The same token buckets bound how fast we push data (blocks, microblocks, transactions) to
+//! our p2p neighbors, both per neighbor and in total.  There, a token is a byte.  They also bound
+//! how often each IP address may open an inbound p2p connection, each of which costs us a
+//! handshake.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+
+use sha2::{Digest, Sha256};
+
+use net::connection::ConnectionOptions;
+use net::PeerAddress;
+
+/// Most client IPs we keep a bucket for.  Past this, new clients share one bucket until some of
+/// the tracked ones go idle.
+const MAX_TRACKED_IPS: usize = 4096;
+
+/// How many unknown API keys an IP address may present if there's no per-IP rate limit: a few
+/// at once, then one a second
+const UNKNOWN_KEY_LIMIT: RPCRateLimit = RPCRateLimit {
+    rate: 1.0,
+    burst: 10,
+};
+
+/// How many wrong admin API keys an IP address may present: a few at once, then one a minute
+const ADMIN_AUTH_FAILURE_LIMIT: RPCRateLimit = RPCRateLimit {
+    rate: 1.0 / 60.0,
+    burst: 5,
+};
+
+/// Does the API key a client presented match `expected`?  Compares digests of the two, in
+/// constant time, so how long this takes says nothing about `expected`.
+pub fn api_key_matches(presented: Option<&str>, expected: &str) -> bool {
+    let presented = match presented {
+        Some(presented) => presented,
+        None => {
+            return false;
+        }
+    };
+    let presented_digest = Sha256::digest(presented.as_bytes());
+    let expected_digest = Sha256::digest(expected.as_bytes());
+    presented_digest
+        .iter()
+        .zip(expected_digest.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// The address a client is rate limited as.  An IPv6 host can usually pick any address in its
+/// /64, so IPv6 clients are limited by their /64 prefix.
+pub fn client_prefix(addr: &PeerAddress) -> PeerAddress {
+    if addr.is_ipv4() {
+        return *addr;
+    }
+    let mut bytes = addr.0;
+    for byte in bytes[8..16].iter_mut() {
+        *byte = 0;
+    }
+    PeerAddress(bytes)
+}
+
+/// How fast a client may send requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RPCRateLimit {
+    /// sustained requests per second
+    pub rate: f64,
+    /// how many requests may be sent at once after a quiet period
+    pub burst: u64,
+}
+
+/// Holds up to `burst` tokens, and gains `rate` tokens per second.  Each request takes one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBucket {
+    limit: RPCRateLimit,
+    tokens: f64,
+    last_refill_ms: u128,
+}
+
+impl TokenBucket {
+    /// Make a full bucket
+    pub fn new(limit: RPCRateLimit, now_ms: u128) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill_ms: now_ms,
+        }
+    }
+
+    fn refill(&mut self, now_ms: u128) {
+        let elapsed_ms = now_ms.saturating_sub(self.last_refill_ms);
+        self.tokens = (self.tokens + (elapsed_ms as f64) * self.limit.rate / 1000.0)
+            .min(self.limit.burst as f64);
+        self.last_refill_ms = now_ms;
+    }
+
+    /// Take a token.  If there isn't one, return how many seconds until there will be.
+    pub fn try_take(&mut self, now_ms: u128) -> Result<(), u64> {
+        self.peek(now_ms)?;
+        self.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Is there a token to take?  If not, return how many seconds until there will be.
+    pub fn peek(&mut self, now_ms: u128) -> Result<(), u64> {
+        self.refill(now_ms);
+        if self.tokens >= 1.0 {
+            Ok(())
+        } else if self.limit.rate <= 0.0 {
+            // never refills
+            Err(u64::MAX)
+        } else {
+            Err(((1.0 - self.tokens) / self.limit.rate).ceil() as u64)
+        }
+    }
+
+    /// Are there any tokens left?
+    pub fn has_tokens(&mut self, now_ms: u128) -> bool {
+        self.refill(now_ms);
+        self.tokens > 0.0
+    }
+
+    /// Take `amount` tokens, going into debt if there aren't enough.  The debt is paid off by
+    /// refills before `has_tokens()` is true again.
+    pub fn consume(&mut self, amount: u64, now_ms: u128) {
+        self.refill(now_ms);
+        self.tokens -= amount as f64;
+    }
+
+    /// Would this bucket be full by now?  A full bucket carries no state, so it can be forgotten.
+    pub fn is_full(&self, now_ms: u128) -> bool {
+        let mut bucket = self.clone();
+        bucket.refill(now_ms);
+        bucket.tokens >= self.limit.burst as f64
+    }
+}
+
+/// Token buckets for up to `capacity` clients.  When a new client would go over, clients whose
+/// buckets have refilled are forgotten to make room.  If none have, the new client isn't
+/// tracked, and draws from a single bucket shared by all such clients instead.  Busy clients are
+/// never forgotten, so nobody can get a fresh bucket by crowding theirs out.
+#[derive(Debug, Clone)]
+struct BoundedBuckets<K: Hash + Eq + Clone> {
+    capacity: usize,
+    buckets: HashMap<K, TokenBucket>,
+    /// shared by the clients we had no room to track
+    overflow: Option<TokenBucket>,
+}
+
+impl<K: Hash + Eq + Clone> BoundedBuckets<K> {
+    fn new(capacity: usize) -> BoundedBuckets<K> {
+        BoundedBuckets {
+            capacity,
+            buckets: HashMap::new(),
+            overflow: None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Make room for one more client, if we can.  Only clients with full buckets are forgotten,
+    /// since they carry no state.
+    fn make_room(&mut self, now_ms: u128) -> bool {
+        if self.buckets.len() < self.capacity {
+            return true;
+        }
+        self.buckets.retain(|_, bucket| !bucket.is_full(now_ms));
+        self.buckets.len() < self.capacity
+    }
+
+    /// Is there a token in `key`'s bucket?  If not, return how many seconds until there will be.
+    /// Clients without a bucket have a full one, unless there's no room to give them one.
+    fn peek(&mut self, key: &K, now_ms: u128) -> Result<(), u64> {
+        if let Some(bucket) = self.buckets.get_mut(key) {
+            return bucket.peek(now_ms);
+        }
+        if self.make_room(now_ms) {
+            return Ok(());
+        }
+        match self.overflow {
+            Some(ref mut bucket) => bucket.peek(now_ms),
+            None => Ok(()),
+        }
+    }
+
+    /// Get `key`'s bucket, giving it a full one with `limit` if it doesn't have one.  If there's
+    /// no room for it, get the overflow bucket.
+    fn get(&mut self, key: &K, limit: RPCRateLimit, now_ms: u128) -> &mut TokenBucket {
+        if !self.buckets.contains_key(key) && !self.make_room(now_ms) {
+            return self
+                .overflow
+                .get_or_insert_with(|| TokenBucket::new(limit, now_ms));
+        }
+        self.buckets
+            .entry(key.clone())
+            .or_insert_with(|| TokenBucket::new(limit, now_ms))
+    }
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum RPCRefusal {
+    /// the request had no API key, but one is required
+    MissingKey,
+    /// the request's API key isn't one of ours (and it has been charged to the client IP)
+    UnknownKey,
+    /// the API key has used up its requests; retry after this many seconds
+    KeyRateLimited(u64),
+    /// the client IP has used up its requests; retry after this many seconds
+    IPRateLimited(u64),
+}
+
+impl RPCRefusal {
+    /// Label for this refusal in metrics
+    pub fn reason(&self) -> &'static str {
+        match *self {
+            RPCRefusal::MissingKey => "missing_key",
+            RPCRefusal::UnknownKey => "unknown_key",
+            RPCRefusal::KeyRateLimited(_) => "key_rate_limited",
+            RPCRefusal::IPRateLimited(_) => "ip_rate_limited",
+        }
+    }
+}
+
+/// Decides whether or not to serve each RPC request
+#[derive(Debug, Clone)]
+pub struct RPCRateLimiter {
+    /// API key => its rate limit (None means unlimited)
+    api_keys: HashMap<String, Option<RPCRateLimit>>,
+    require_api_key: bool,
+    ip_limit: Option<RPCRateLimit>,
+    key_buckets: HashMap<String, TokenBucket>,
+    /// client IP prefix => its bucket
+    ip_buckets: BoundedBuckets<PeerAddress>,
+    /// client IP prefix => its allowance of wrong admin API keys
+    admin_failure_buckets: BoundedBuckets<PeerAddress>,
+}
+
+impl RPCRateLimiter {
+    pub fn new(
+        api_keys: HashMap<String, Option<RPCRateLimit>>,
+        require_api_key: bool,
+        ip_limit: Option<RPCRateLimit>,
+    ) -> RPCRateLimiter {
+        RPCRateLimiter {
+            api_keys,
+            require_api_key,
+            ip_limit,
+            key_buckets: HashMap::new(),
+            ip_buckets: BoundedBuckets::new(MAX_TRACKED_IPS),
+            admin_failure_buckets: BoundedBuckets::new(MAX_TRACKED_IPS),
+        }
+    }
+
+    pub fn from_connection_options(opts: &ConnectionOptions) -> RPCRateLimiter {
+        RPCRateLimiter::new(
+            opts.rpc_api_keys.clone(),
+            opts.rpc_require_api_key,
+            opts.rpc_ip_rate_limit,
+        )
+    }
+
+    /// Find the API key that `presented` matches.  Every key is compared in the same way, so how
+    /// long this takes says nothing about which keys we have.
+    fn find_api_key(&self, presented: &str) -> Option<(String, Option<RPCRateLimit>)> {
+        let mut found = None;
+        for (key, limit) in self.api_keys.iter() {
+            if api_key_matches(Some(presented), key) {
+                found = Some((key.clone(), *limit));
+            }
+        }
+        found
+    }
+
+    /// Decide whether or not to serve a request from `client_ip` carrying `api_key`, and charge
+    /// it against the relevant bucket if so.  An unknown API key is charged against the client
+    /// IP's bucket before it is refused, so clients can't guess keys any faster than they could
+    /// send requests without one.
+    pub fn check(
+        &mut self,
+        client_ip: &IpAddr,
+        api_key: Option<&str>,
+        now_ms: u128,
+    ) -> Result<(), RPCRefusal> {
+        let client = client_prefix(&PeerAddress::from_ip(client_ip));
+        match api_key {
+            Some(key) => match self.find_api_key(key) {
+                Some((key, Some(limit))) => self
+                    .key_buckets
+                    .entry(key)
+                    .or_insert_with(|| TokenBucket::new(limit, now_ms))
+                    .try_take(now_ms)
+                    .map_err(RPCRefusal::KeyRateLimited),
+                Some((_, None)) => Ok(()),
+                None => {
+                    let limit = self.ip_limit.unwrap_or(UNKNOWN_KEY_LIMIT);
+                    self.ip_buckets
+                        .get(&client, limit, now_ms)
+                        .try_take(now_ms)
+                        .map_err(RPCRefusal::IPRateLimited)?;
+                    Err(RPCRefusal::UnknownKey)
+                }
+            },
+            None => {
+                if self.require_api_key {
+                    return Err(RPCRefusal::MissingKey);
+                }
+                let limit = match self.ip_limit {
+                    Some(limit) => limit,
+                    None => {
+                        return Ok(());
+                    }
+                };
+                self.ip_buckets
+                    .get(&client, limit, now_ms)
+                    .try_take(now_ms)
+                    .map_err(RPCRefusal::IPRateLimited)
+            }
+        }
+    }
+}
+
+impl RPCRateLimiter {
+    /// Decide whether or not to look at the admin API key of a request from `client_ip`.  If
+    /// it has presented too many wrong ones lately, return how many seconds until it may try
+    /// again.
+    pub fn check_admin(&mut self, client_ip: &IpAddr, now_ms: u128) -> Result<(), u64> {
+        let client = client_prefix(&PeerAddress::from_ip(client_ip));
+        self.admin_failure_buckets.peek(&client, now_ms)
+    }
+
+    /// Charge a wrong admin API key presented by `client_ip`
+    pub fn record_admin_auth_failure(&mut self, client_ip: &IpAddr, now_ms: u128) {
+        let client = client_prefix(&PeerAddress::from_ip(client_ip));
+        let _ = self
+            .admin_failure_buckets
+            .get(&client, ADMIN_AUTH_FAILURE_LIMIT, now_ms)
+            .try_take(now_ms);
+    }
+}
+
+/// Decides when we may push more data to each p2p neighbor, so a node on a metered connection
+/// can bound its egress.  Writes to a socket can't be taken back, so a neighbor whose bucket
+/// runs dry is allowed to finish its current write and then waits out the debt.
+#[derive(Debug, Clone)]
+pub struct EgressLimiter {
+    /// bytes per second to each neighbor (None means no limit)
+    peer_limit: Option<RPCRateLimit>,
+    /// bytes per second to all neighbors together (None means no limit)
+    global_bucket: Option<TokenBucket>,
+    /// event ID => its bucket
+    peer_buckets: HashMap<usize, TokenBucket>,
+}
+
+impl EgressLimiter {
+    pub fn new(
+        peer_limit: Option<RPCRateLimit>,
+        global_limit: Option<RPCRateLimit>,
+        now_ms: u128,
+    ) -> EgressLimiter {
+        EgressLimiter {
+            peer_limit,
+            global_bucket: global_limit.map(|limit| TokenBucket::new(limit, now_ms)),
+            peer_buckets: HashMap::new(),
+        }
+    }
+
+    pub fn from_connection_options(opts: &ConnectionOptions, now_ms: u128) -> EgressLimiter {
+        EgressLimiter::new(opts.peer_egress_limit, opts.global_egress_limit, now_ms)
+    }
+
+    /// May we send more data to the neighbor on `event_id`?
+    pub fn can_send(&mut self, event_id: usize, now_ms: u128) -> bool {
+        if let Some(ref mut bucket) = self.global_bucket {
+            if !bucket.has_tokens(now_ms) {
+                return false;
+            }
+        }
+        match self.peer_buckets.get_mut(&event_id) {
+            Some(bucket) => bucket.has_tokens(now_ms),
+            None => true,
+        }
+    }
+
+    /// Charge `num_bytes` sent to the neighbor on `event_id`
+    pub fn record_sent(&mut self, event_id: usize, num_bytes: u64, now_ms: u128) {
+        if num_bytes == 0 {
+            return;
+        }
+        if let Some(ref mut bucket) = self.global_bucket {
+            bucket.consume(num_bytes, now_ms);
+        }
+        if let Some(limit) = self.peer_limit {
+            self.peer_buckets
+                .entry(event_id)
+                .or_insert_with(|| TokenBucket::new(limit, now_ms))
+                .consume(num_bytes, now_ms);
+        }
+    }
+
+    /// Forget a disconnected neighbor
+    pub fn remove_peer(&mut self, event_id: usize) {
+        self.peer_buckets.remove(&event_id);
+    }
+}
+
+/// Decides whether or not to accept an inbound p2p connection from an address, based on how
+/// often that address (or, for IPv6, its /64) has been connecting.  Every inbound connection has
+/// to handshake, so this bounds how many handshakes a single host can make us verify.
+#[derive(Debug, Clone)]
+pub struct HandshakeRateLimiter {
+    /// connections per second from each address (None means no limit)
+    limit: Option<RPCRateLimit>,
+    /// address prefix => its bucket
+    buckets: BoundedBuckets<PeerAddress>,
+}
+
+impl HandshakeRateLimiter {
+    pub fn new(limit: Option<RPCRateLimit>) -> HandshakeRateLimiter {
+        HandshakeRateLimiter {
+            limit,
+            buckets: BoundedBuckets::new(MAX_TRACKED_IPS),
+        }
+    }
+
+    pub fn from_connection_options(opts: &ConnectionOptions) -> HandshakeRateLimiter {
+        HandshakeRateLimiter::new(opts.inbound_handshake_rate_limit)
+    }
+
+    /// Charge an inbound connection from `addr`.  If it's over its limit, return how many seconds
+    /// until it can connect again.
+    pub fn check(&mut self, addr: &PeerAddress, now_ms: u128) -> Result<(), u64> {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => {
+                return Ok(());
+            }
+        };
+        self.buckets
+            .get(&client_prefix(addr), limit, now_ms)
+            .try_take(now_ms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_token_bucket() {
+        let limit = RPCRateLimit {
+            rate: 2.0,
+            burst: 3,
+        };
+        let mut bucket = TokenBucket::new(limit, 1000);
+        assert!(bucket.is_full(1000));
+
+        // burst, then wait
+        for _ in 0..3 {
+            bucket.try_take(1000).unwrap();
+        }
+        assert_eq!(bucket.try_take(1000), Err(1));
+        assert!(!bucket.is_full(1000));
+
+        // half a second later, there's one more token
+        bucket.try_take(1500).unwrap();
+        assert_eq!(bucket.try_take(1500), Err(1));
+
+        // never refills past the burst size
+        assert!(bucket.is_full(100_000));
+        for _ in 0..3 {
+            bucket.try_take(100_000).unwrap();
+        }
+        assert!(bucket.try_take(100_000).is_err());
+
+        // slow buckets report longer waits
+        let mut slow = TokenBucket::new(
+            RPCRateLimit {
+                rate: 0.1,
+                burst: 1,
+            },
+            0,
+        );
+        slow.try_take(0).unwrap();
+        assert_eq!(slow.try_take(0), Err(10));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut api_keys = HashMap::new();
+        api_keys.insert("unlimited".to_string(), None);
+        api_keys.insert(
+            "limited".to_string(),
+            Some(RPCRateLimit {
+                rate: 1.0,
+                burst: 2,
+            }),
+        );
+        let ip_limit = RPCRateLimit {
+            rate: 1.0,
+            burst: 1,
+        };
+        let ip_1: IpAddr = "1.2.3.4".parse().unwrap();
+        let ip_2: IpAddr = "5.6.7.8".parse().unwrap();
+
+        let mut limiter = RPCRateLimiter::new(api_keys.clone(), false, Some(ip_limit));
+
+        // anonymous clients are limited per IP
+        limiter.check(&ip_1, None, 0).unwrap();
+        assert_eq!(
+            limiter.check(&ip_1, None, 0),
+            Err(RPCRefusal::IPRateLimited(1))
+        );
+        limiter.check(&ip_2, None, 0).unwrap();
+        limiter.check(&ip_1, None, 1000).unwrap();
+
+        // keyed clients are limited per key, regardless of IP
+        limiter.check(&ip_1, Some("limited"), 0).unwrap();
+        limiter.check(&ip_2, Some("limited"), 0).unwrap();
+        assert_eq!(
+            limiter.check(&ip_1, Some("limited"), 0),
+            Err(RPCRefusal::KeyRateLimited(1))
+        );
+        for _ in 0..100 {
+            limiter.check(&ip_1, Some("unlimited"), 0).unwrap();
+        }
+
+        // unknown keys cost the client IP a request
+        assert_eq!(
+            limiter.check(&ip_1, Some("nope"), 2000),
+            Err(RPCRefusal::UnknownKey)
+        );
+        assert_eq!(
+            limiter.check(&ip_1, Some("nope"), 2000),
+            Err(RPCRefusal::IPRateLimited(1))
+        );
+        assert_eq!(
+            limiter.check(&ip_1, None, 2000),
+            Err(RPCRefusal::IPRateLimited(1))
+        );
+
+        // keys can be mandatory
+        let mut limiter = RPCRateLimiter::new(api_keys, true, None);
+        assert_eq!(limiter.check(&ip_1, None, 0), Err(RPCRefusal::MissingKey));
+        limiter.check(&ip_1, Some("unlimited"), 0).unwrap();
+
+        // unknown keys are still throttled without a per-IP limit
+        for _ in 0..UNKNOWN_KEY_LIMIT.burst {
+            assert_eq!(
+                limiter.check(&ip_1, Some("nope"), 0),
+                Err(RPCRefusal::UnknownKey)
+            );
+        }
+        assert_eq!(
+            limiter.check(&ip_1, Some("nope"), 0),
+            Err(RPCRefusal::IPRateLimited(1))
+        );
+        limiter.check(&ip_1, Some("unlimited"), 0).unwrap();
+
+        // no limits at all by default
+        let mut limiter = RPCRateLimiter::from_connection_options(&ConnectionOptions::default());
+        for _ in 0..100 {
+            limiter.check(&ip_1, None, 0).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_api_key_matches() {
+        assert!(api_key_matches(Some("admin"), "admin"));
+        assert!(!api_key_matches(Some("admin "), "admin"));
+        assert!(!api_key_matches(Some("nope"), "admin"));
+        assert!(!api_key_matches(Some(""), "admin"));
+        assert!(!api_key_matches(None, "admin"));
+    }
+
+    #[test]
+    fn test_admin_auth_failures() {
+        let ip_1: IpAddr = "1.2.3.4".parse().unwrap();
+        let ip_2: IpAddr = "5.6.7.8".parse().unwrap();
+        let mut limiter = RPCRateLimiter::from_connection_options(&ConnectionOptions::default());
+
+        // a few wrong keys are tolerated
+        for _ in 0..ADMIN_AUTH_FAILURE_LIMIT.burst {
+            limiter.check_admin(&ip_1, 0).unwrap();
+            limiter.record_admin_auth_failure(&ip_1, 0);
+        }
+
+        // then the client is turned away, and only gets one more try a minute
+        assert_eq!(limiter.check_admin(&ip_1, 0), Err(60));
+        assert_eq!(limiter.check_admin(&ip_1, 30_000), Err(30));
+        limiter.check_admin(&ip_1, 60_000).unwrap();
+        limiter.record_admin_auth_failure(&ip_1, 60_000);
+        assert!(limiter.check_admin(&ip_1, 60_000).is_err());
+
+        // other clients are unaffected, as are the ordinary rate limits
+        limiter.check_admin(&ip_2, 0).unwrap();
+        limiter.check(&ip_1, None, 60_000).unwrap();
+    }
+
+    #[test]
+    fn test_rate_limiter_prunes_idle_ips() {
+        let ip_limit = RPCRateLimit {
+            rate: 1.0,
+            burst: 1,
+        };
+        let mut limiter = RPCRateLimiter::new(HashMap::new(), false, Some(ip_limit));
+        for i in 0..MAX_TRACKED_IPS {
+            let ip = IpAddr::from([10, 0, (i / 256) as u8, (i % 256) as u8]);
+            limiter.check(&ip, None, 0).unwrap();
+        }
+        assert_eq!(limiter.ip_buckets.len(), MAX_TRACKED_IPS);
+
+        // everyone's bucket has refilled a second later
+        limiter
+            .check(&"1.2.3.4".parse().unwrap(), None, 1000)
+            .unwrap();
+        assert_eq!(limiter.ip_buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_rate_limiter_bounds_busy_ips() {
+        let ip_limit = RPCRateLimit {
+            rate: 1.0,
+            burst: 1,
+        };
+        let ip = |i: usize| IpAddr::from([10, (i / 65536) as u8, (i / 256) as u8, i as u8]);
+        let mut limiter = RPCRateLimiter::new(HashMap::new(), false, Some(ip_limit));
+
+        // nobody's bucket refills, so there's nothing idle to forget
+        for i in 0..MAX_TRACKED_IPS {
+            limiter.check(&ip(i), None, 0).unwrap();
+        }
+        assert_eq!(limiter.ip_buckets.len(), MAX_TRACKED_IPS);
+
+        // new clients share one bucket, and the busy ones are not forgotten
+        limiter.check(&ip(MAX_TRACKED_IPS), None, 0).unwrap();
+        for i in (MAX_TRACKED_IPS + 1)..(2 * MAX_TRACKED_IPS) {
+            assert_eq!(
+                limiter.check(&ip(i), None, 0),
+                Err(RPCRefusal::IPRateLimited(1))
+            );
+        }
+        assert_eq!(limiter.ip_buckets.len(), MAX_TRACKED_IPS);
+        assert_eq!(
+            limiter.check(&ip(0), None, 0),
+            Err(RPCRefusal::IPRateLimited(1))
+        );
+
+        // once the tracked clients go idle, new ones get their own buckets again
+        limiter.check(&ip(2 * MAX_TRACKED_IPS), None, 1000).unwrap();
+        assert_eq!(limiter.ip_buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_rate_limiter_ipv6_prefix() {
+        let ip_limit = RPCRateLimit {
+            rate: 1.0,
+            burst: 2,
+        };
+        let mut limiter = RPCRateLimiter::new(HashMap::new(), false, Some(ip_limit));
+
+        // cycling through addresses in one /64 doesn't get a client any more requests
+        let ip = |i: u64| {
+            IpAddr::from(Ipv6Addr::from(
+                (0x2001_0db8_0000_0001u128 << 64) | i as u128,
+            ))
+        };
+        limiter.check(&ip(1), None, 0).unwrap();
+        limiter.check(&ip(2), None, 0).unwrap();
+        for i in 3..(2 * MAX_TRACKED_IPS as u64) {
+            assert_eq!(
+                limiter.check(&ip(i), None, 0),
+                Err(RPCRefusal::IPRateLimited(1))
+            );
+        }
+        assert_eq!(limiter.ip_buckets.len(), 1);
+
+        // other /64s are limited separately
+        limiter
+            .check(&"2001:db8:0:2::1".parse().unwrap(), None, 0)
+            .unwrap();
+
+        // IPv4 clients are still limited by address
+        limiter.check(&"1.2.3.4".parse().unwrap(), None, 0).unwrap();
+        limiter.check(&"1.2.3.5".parse().unwrap(), None, 0).unwrap();
+    }
+
+    #[test]
+    fn test_handshake_rate_limiter() {
+        let mut unlimited = HandshakeRateLimiter::new(None);
+        for _ in 0..100 {
+            unlimited.check(&PeerAddress([0x01; 16]), 0).unwrap();
+        }
+
+        let mut limiter = HandshakeRateLimiter::new(Some(RPCRateLimit {
+            rate: 0.5,
+            burst: 2,
+        }));
+        let addr = PeerAddress([0x01; 16]);
+        limiter.check(&addr, 0).unwrap();
+        limiter.check(&addr, 0).unwrap();
+        assert_eq!(limiter.check(&addr, 0), Err(2));
+
+        // other addresses are unaffected
+        limiter.check(&PeerAddress([0x02; 16]), 0).unwrap();
+
+        // one connection every two seconds
+        assert_eq!(limiter.check(&addr, 1000), Err(1));
+        limiter.check(&addr, 2000).unwrap();
+        assert!(limiter.check(&addr, 2000).is_err());
+    }
+
+    #[test]
+    fn test_handshake_rate_limiter_bounds_busy_addrs() {
+        let mut limiter = HandshakeRateLimiter::new(Some(RPCRateLimit {
+            rate: 1.0,
+            burst: 1,
+        }));
+        let addr = |prefix: u64, host: u64| {
+            let mut bytes = [0u8; 16];
+            bytes[0..8].copy_from_slice(&(0x2001_0db8_0000_0000 | prefix).to_be_bytes());
+            bytes[8..16].copy_from_slice(&host.to_be_bytes());
+            PeerAddress(bytes)
+        };
+
+        // a host cycling through the addresses in its /64 is limited as one address
+        limiter.check(&addr(0, 0), 0).unwrap();
+        for i in 1..(2 * MAX_TRACKED_IPS as u64) {
+            assert_eq!(limiter.check(&addr(0, i), 0), Err(1));
+        }
+        assert_eq!(limiter.buckets.len(), 1);
+
+        // nobody's bucket refills, so there's nothing idle to forget
+        for i in 1..(MAX_TRACKED_IPS as u64) {
+            limiter.check(&addr(i, 0), 0).unwrap();
+        }
+        assert_eq!(limiter.buckets.len(), MAX_TRACKED_IPS);
+
+        // a host cycling through more prefixes than we track shares one bucket with every
+        // other untracked prefix, and can't crowd out anyone's bucket to reset its own
+        let first_untracked = MAX_TRACKED_IPS as u64;
+        limiter.check(&addr(first_untracked, 0), 0).unwrap();
+        for i in (first_untracked + 1)..(2 * first_untracked) {
+            assert_eq!(limiter.check(&addr(i, 0), 0), Err(1));
+        }
+        assert_eq!(limiter.buckets.len(), MAX_TRACKED_IPS);
+        assert_eq!(limiter.check(&addr(0, 0), 0), Err(1));
+        assert_eq!(limiter.check(&addr(1, 0), 0), Err(1));
+
+        // idle addresses are forgotten to make room
+        limiter.check(&addr(2 * first_untracked, 0), 1000).unwrap();
+        assert_eq!(limiter.buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_egress_limiter() {
+        let peer_limit = RPCRateLimit {
+            rate: 1000.0,
+            burst: 1000,
+        };
+        let global_limit = RPCRateLimit {
+            rate: 1500.0,
+            burst: 1500,
+        };
+        let mut limiter = EgressLimiter::new(Some(peer_limit), Some(global_limit), 0);
+
+        // a neighbor may overdraw its bucket with one write, but then has to wait it out
+        assert!(limiter.can_send(1, 0));
+        limiter.record_sent(1, 1500, 0);
+        assert!(!limiter.can_send(1, 0));
+
+        // the global bucket was drained too, so nobody else may send either
+        assert!(!limiter.can_send(2, 0));
+        assert!(limiter.can_send(2, 1));
+
+        assert!(!limiter.can_send(1, 500));
+        assert!(limiter.can_send(1, 501));
+
+        // neighbors are limited separately
+        let mut limiter = EgressLimiter::new(Some(peer_limit), None, 0);
+        limiter.record_sent(1, 1000, 0);
+        assert!(!limiter.can_send(1, 0));
+        assert!(limiter.can_send(2, 0));
+        limiter.record_sent(2, 999, 0);
+        assert!(limiter.can_send(2, 0));
+
+        // forgotten neighbors start over
+        limiter.remove_peer(1);
+        assert!(limiter.can_send(1, 0));
+
+        // no limits at all by default
+        let mut limiter = EgressLimiter::from_connection_options(&ConnectionOptions::default(), 0);
+        for i in 0..100 {
+            limiter.record_sent(1, 1_000_000, i);
+            assert!(limiter.can_send(1, i));
+        }
+    }
+}
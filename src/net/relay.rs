@@ -38,12 +38,14 @@ use chainstate::stacks::db::{StacksChainState, StacksEpochReceipt, StacksHeaderI
 use chainstate::stacks::events::StacksTransactionReceipt;
 use core::mempool::MemPoolDB;
 use core::mempool::*;
+use net::capabilities::Capability;
 use net::chat::*;
 use net::connection::*;
 use net::db::*;
 use net::http::*;
 use net::p2p::*;
 use net::poll::*;
+use net::relaypolicy::MicroblockRelayPolicy;
 use net::rpc::*;
 use net::Error as net_error;
 use net::*;
@@ -1274,8 +1276,23 @@ impl Relayer {
                     }
                 }
 
-                // have the p2p thread forward all new unconfirmed microblocks
+                // have the p2p thread forward all new unconfirmed microblocks, starting with the
+                // ones that extend the canonical tip
                 if new_microblocks.len() > 0 {
+                    let new_microblocks =
+                        match SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()) {
+                            Ok((canonical_ch, canonical_bhh)) => {
+                                let canonical_tip = StacksBlockHeader::make_index_block_hash(
+                                    &canonical_ch,
+                                    &canonical_bhh,
+                                );
+                                MicroblockRelayPolicy::prioritize(&canonical_tip, new_microblocks)
+                            }
+                            Err(e) => {
+                                warn!("Failed to load canonical Stacks tip: {:?}", &e);
+                                new_microblocks
+                            }
+                        };
                     debug!(
                         "{:?}: Unconfirmed microblocks: {}",
                         &_local_peer,
@@ -1462,10 +1479,26 @@ impl PeerNetwork {
     ) -> () {
         let blk_hash = block.block_hash();
         let ch = consensus_hash.clone();
-        let payload = BlocksData {
-            blocks: vec![(consensus_hash, block)],
+
+        // send just the header and short transaction IDs if the recipient can rebuild the block
+        // from its mempool
+        let compact = self.connection_opts.compact_blocks
+            && self
+                .get_convo(recipient)
+                .map(|convo| convo.supports(Capability::CompactBlocks))
+                .unwrap_or(false);
+        let payload = if compact {
+            StacksMessageType::CompactBlock(CompactBlockData::from_block(
+                &consensus_hash,
+                &block,
+                thread_rng().gen(),
+            ))
+        } else {
+            StacksMessageType::Blocks(BlocksData {
+                blocks: vec![(consensus_hash, block)],
+            })
         };
-        let message = match self.sign_for_peer(recipient, StacksMessageType::Blocks(payload)) {
+        let message = match self.sign_for_peer(recipient, payload) {
             Ok(m) => m,
             Err(e) => {
                 warn!(
@@ -1477,8 +1510,12 @@ impl PeerNetwork {
         };
 
         debug!(
-            "{:?}: Push block {}/{} to {:?}",
-            &self.local_peer, &ch, &blk_hash, recipient
+            "{:?}: Push {}block {}/{} to {:?}",
+            &self.local_peer,
+            if compact { "compact " } else { "" },
+            &ch,
+            &blk_hash,
+            recipient
         );
 
         // absorb errors
@@ -2963,6 +3000,204 @@ mod test {
         test_get_blocks_and_microblocks_2_peers_push_blocks_and_microblocks(false, false)
     }
 
+    /// Push a block as a compact block that sends none of its transactions in full, so the
+    /// recipient has to ask for whatever isn't in its mempool
+    fn push_compact_block(
+        peer: &mut TestPeer,
+        dest: &NeighborKey,
+        consensus_hash: ConsensusHash,
+        block: StacksBlock,
+    ) -> bool {
+        test_debug!(
+            "{:?}: Push compact block {}/{} to {:?}",
+            peer.to_neighbor().addr,
+            &consensus_hash,
+            block.block_hash(),
+            dest
+        );
+
+        let nonce = thread_rng().gen();
+        let keys = CompactBlockData::short_txid_keys(&block.block_hash(), nonce);
+        let msg = StacksMessageType::CompactBlock(CompactBlockData {
+            consensus_hash,
+            header: block.header.clone(),
+            nonce,
+            short_txids: block
+                .txs
+                .iter()
+                .map(|tx| ShortTxid::from_txid(keys, &tx.txid()))
+                .collect(),
+            prefilled_txs: vec![],
+        });
+        push_message(peer, dest, vec![], msg)
+    }
+
+    #[test]
+    #[ignore]
+    fn test_get_blocks_and_microblocks_2_peers_push_compact_blocks() {
+        // node 0 pushes compact blocks to node 1, which has none of their transactions and has
+        // to ask node 0 for them
+        with_timeout(600, move || {
+            let original_blocks_and_microblocks = RefCell::new(vec![]);
+            let blocks_and_microblocks = RefCell::new(vec![]);
+            let sent_blocks = RefCell::new(false);
+
+            let peers = run_get_blocks_and_microblocks(
+                "test_get_blocks_and_microblocks_2_peers_push_compact_blocks",
+                4260,
+                2,
+                |ref mut peer_configs| {
+                    // build initial network topology.
+                    assert_eq!(peer_configs.len(), 2);
+
+                    // peer 0 produces the blocks and pushes them to peer 1.
+                    // peer 1 doesn't download them, nor does it try to get invs
+                    peer_configs[0].connection_opts.disable_block_advertisement = true;
+                    peer_configs[0].connection_opts.compact_blocks = true;
+
+                    peer_configs[1].connection_opts.disable_inv_sync = true;
+                    peer_configs[1].connection_opts.disable_block_download = true;
+                    peer_configs[1].connection_opts.disable_block_advertisement = true;
+                    peer_configs[1].connection_opts.compact_blocks = true;
+
+                    // disable nat punches -- disconnect/reconnect
+                    // clears inv state
+                    peer_configs[0].connection_opts.disable_natpunch = true;
+                    peer_configs[1].connection_opts.disable_natpunch = true;
+
+                    let peer_0 = peer_configs[0].to_neighbor();
+                    let peer_1 = peer_configs[1].to_neighbor();
+
+                    peer_configs[0].add_neighbor(&peer_1);
+                    peer_configs[1].add_neighbor(&peer_0);
+                },
+                |num_blocks, ref mut peers| {
+                    let tip = SortitionDB::get_canonical_burn_chain_tip(
+                        peers[0].sortdb.as_ref().unwrap().conn(),
+                    )
+                    .unwrap();
+                    let this_reward_cycle = peers[0]
+                        .config
+                        .burnchain
+                        .block_height_to_reward_cycle(tip.block_height)
+                        .unwrap();
+
+                    // build up block data to replicate
+                    let mut block_data = vec![];
+                    for _ in 0..num_blocks {
+                        let tip = SortitionDB::get_canonical_burn_chain_tip(
+                            peers[0].sortdb.as_ref().unwrap().conn(),
+                        )
+                        .unwrap();
+                        if peers[0]
+                            .config
+                            .burnchain
+                            .block_height_to_reward_cycle(tip.block_height)
+                            .unwrap()
+                            != this_reward_cycle
+                        {
+                            continue;
+                        }
+                        let (mut burn_ops, stacks_block, microblocks) =
+                            peers[0].make_default_tenure();
+
+                        let (_, burn_header_hash, consensus_hash) =
+                            peers[0].next_burnchain_block(burn_ops.clone());
+                        peers[0].process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+                        TestPeer::set_ops_burn_header_hash(&mut burn_ops, &burn_header_hash);
+
+                        for i in 1..peers.len() {
+                            peers[i].next_burnchain_block_raw(burn_ops.clone());
+                        }
+
+                        let sn = SortitionDB::get_canonical_burn_chain_tip(
+                            peers[0].sortdb.as_ref().unwrap().conn(),
+                        )
+                        .unwrap();
+                        block_data.push((
+                            sn.consensus_hash.clone(),
+                            Some(stacks_block),
+                            Some(microblocks),
+                        ));
+                    }
+                    let saved_copy: Vec<(ConsensusHash, StacksBlock, Vec<StacksMicroblock>)> =
+                        block_data
+                            .clone()
+                            .drain(..)
+                            .map(|(ch, blk_opt, mblocks_opt)| {
+                                (ch, blk_opt.unwrap(), mblocks_opt.unwrap())
+                            })
+                            .collect();
+                    *blocks_and_microblocks.borrow_mut() = saved_copy.clone();
+                    *original_blocks_and_microblocks.borrow_mut() = saved_copy;
+                    block_data
+                },
+                |ref mut peers| {
+                    for peer in peers.iter_mut() {
+                        // force peers to keep trying to process buffered data
+                        peer.network.burnchain_tip.burn_header_hash =
+                            BurnchainHeaderHash([0u8; 32]);
+                    }
+
+                    let peer_1_nk = peers[1].to_neighbor().addr;
+                    if !is_peer_connected(&peers[0], &peer_1_nk) {
+                        return;
+                    }
+
+                    // push each block in order, followed by its microblocks
+                    let mut block_data = blocks_and_microblocks.borrow_mut();
+                    if block_data.is_empty() {
+                        // start over (can happen if a message gets dropped due to a timeout)
+                        test_debug!("Reset block transmission (possible timeout)");
+                        *block_data = original_blocks_and_microblocks.borrow().clone();
+                    }
+
+                    let (consensus_hash, block, microblocks) = block_data[0].clone();
+                    let block_hash = block.block_hash();
+                    let mut sent_blocks = sent_blocks.borrow_mut();
+                    if !*sent_blocks {
+                        *sent_blocks = push_compact_block(
+                            &mut peers[0],
+                            &peer_1_nk,
+                            consensus_hash.clone(),
+                            block,
+                        );
+                    }
+                    if *sent_blocks
+                        && push_microblocks(
+                            &mut peers[0],
+                            &peer_1_nk,
+                            vec![],
+                            consensus_hash,
+                            block_hash,
+                            microblocks,
+                        )
+                    {
+                        block_data.remove(0);
+                        *sent_blocks = false;
+                    }
+                },
+                |_| true,
+                |_| true,
+            );
+
+            // peer 1 had to ask for the blocks' transactions
+            let num_block_txs: u64 = peers[1]
+                .network
+                .peers
+                .values()
+                .map(|convo| {
+                    convo
+                        .stats
+                        .get_message_recv_count(StacksMessageID::BlockTxs)
+                })
+                .sum();
+            assert!(num_block_txs > 0);
+            assert!(peers[1].network.pending_compact_blocks.is_empty());
+        })
+    }
+
     #[test]
     #[ignore]
     fn test_get_blocks_and_microblocks_upload_blocks_http() {
@@ -4062,6 +4297,144 @@ mod test {
         })
     }
 
+    #[test]
+    #[ignore]
+    fn test_get_blocks_and_microblocks_2_peers_antientropy_offers() {
+        with_timeout(600, move || {
+            run_get_blocks_and_microblocks(
+                "test_get_blocks_and_microblocks_2_peers_antientropy_offers",
+                4270,
+                2,
+                |ref mut peer_configs| {
+                    // build initial network topology.
+                    assert_eq!(peer_configs.len(), 2);
+
+                    // peer 0 mines blocks, but does not advertize them nor announce them as
+                    // available via its inventory.  It only uses its anti-entropy protocol to
+                    // discover that peer 1 doesn't have them, and offers them to peer 1 that way.
+                    peer_configs[0].connection_opts.disable_block_advertisement = true;
+                    peer_configs[0].connection_opts.disable_block_download = true;
+
+                    peer_configs[1].connection_opts.disable_block_download = true;
+                    peer_configs[1].connection_opts.disable_block_advertisement = true;
+
+                    // disable nat punches -- disconnect/reconnect
+                    // clears inv state
+                    peer_configs[0].connection_opts.disable_natpunch = true;
+                    peer_configs[1].connection_opts.disable_natpunch = true;
+
+                    // permit anti-entropy protocol even if nat'ed
+                    peer_configs[0].connection_opts.antientropy_public = true;
+                    peer_configs[1].connection_opts.antientropy_public = true;
+                    peer_configs[0].connection_opts.antientropy_retry = 1;
+                    peer_configs[1].connection_opts.antientropy_retry = 1;
+
+                    // peer 0 offers the blocks, and only pushes the ones peer 1 accepts
+                    peer_configs[0].connection_opts.antientropy_offers = true;
+                    peer_configs[1].connection_opts.antientropy_offers = true;
+
+                    // make peer 0 go slowly
+                    peer_configs[0].connection_opts.max_block_push = 2;
+                    peer_configs[0].connection_opts.max_microblock_push = 2;
+
+                    let peer_0 = peer_configs[0].to_neighbor();
+                    let peer_1 = peer_configs[1].to_neighbor();
+
+                    // peer 0 is inbound to peer 1
+                    peer_configs[0].add_neighbor(&peer_1);
+                    peer_configs[1].add_neighbor(&peer_0);
+                },
+                |num_blocks, ref mut peers| {
+                    let tip = SortitionDB::get_canonical_burn_chain_tip(
+                        &peers[0].sortdb.as_ref().unwrap().conn(),
+                    )
+                    .unwrap();
+                    let this_reward_cycle = peers[0]
+                        .config
+                        .burnchain
+                        .block_height_to_reward_cycle(tip.block_height)
+                        .unwrap();
+
+                    // build up block data to replicate
+                    let mut block_data = vec![];
+                    for _ in 0..num_blocks {
+                        let tip = SortitionDB::get_canonical_burn_chain_tip(
+                            &peers[0].sortdb.as_ref().unwrap().conn(),
+                        )
+                        .unwrap();
+                        if peers[0]
+                            .config
+                            .burnchain
+                            .block_height_to_reward_cycle(tip.block_height)
+                            .unwrap()
+                            != this_reward_cycle
+                        {
+                            continue;
+                        }
+                        let (mut burn_ops, stacks_block, microblocks) =
+                            peers[0].make_default_tenure();
+
+                        let (_, burn_header_hash, consensus_hash) =
+                            peers[0].next_burnchain_block(burn_ops.clone());
+                        peers[0].process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+                        TestPeer::set_ops_burn_header_hash(&mut burn_ops, &burn_header_hash);
+
+                        for i in 1..peers.len() {
+                            peers[i].next_burnchain_block_raw(burn_ops.clone());
+                        }
+
+                        let sn = SortitionDB::get_canonical_burn_chain_tip(
+                            &peers[0].sortdb.as_ref().unwrap().conn(),
+                        )
+                        .unwrap();
+                        block_data.push((
+                            sn.consensus_hash.clone(),
+                            Some(stacks_block),
+                            Some(microblocks),
+                        ));
+                    }
+
+                    // cap with an empty sortition, so the antientropy protocol picks up all stacks
+                    // blocks
+                    let (_, burn_header_hash, consensus_hash) =
+                        peers[0].next_burnchain_block(vec![]);
+                    for i in 1..peers.len() {
+                        peers[i].next_burnchain_block_raw(vec![]);
+                    }
+                    let sn = SortitionDB::get_canonical_burn_chain_tip(
+                        &peers[0].sortdb.as_ref().unwrap().conn(),
+                    )
+                    .unwrap();
+                    block_data.push((sn.consensus_hash.clone(), None, None));
+
+                    block_data
+                },
+                |ref mut peers| {
+                    for peer in peers.iter_mut() {
+                        // force peers to keep trying to process buffered data
+                        peer.network.burnchain_tip.burn_header_hash =
+                            BurnchainHeaderHash([0u8; 32]);
+                    }
+
+                    let tip_opt = peers[1]
+                        .with_db_state(|sortdb, chainstate, _, _| {
+                            let tip_opt = chainstate.get_stacks_chain_tip(sortdb).unwrap();
+                            Ok(tip_opt)
+                        })
+                        .unwrap();
+                },
+                |ref peer| {
+                    // check peer health
+                    // nothing should break
+                    // TODO
+                    true
+                },
+                |_| true,
+            );
+        })
+    }
+
     #[test]
     #[ignore]
     fn test_get_blocks_and_microblocks_2_peers_buffered_messages() {
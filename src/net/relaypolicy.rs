@@ -0,0 +1,320 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Microblock relay policy.  While the Stacks chain is forked, neighbors can push us unconfirmed
+//! microblocks for several streams at once, but only the stream off of the canonical tip is
+//! likely to get confirmed.  So we forward the microblocks that extend the canonical tip first,
+//! and we give each stream a budget of how many bytes of microblocks we'll forward for it per
+//! interval.  Stale streams get a much smaller budget than the canonical one.
+
+use std::collections::HashMap;
+
+use codec::StacksMessageCodec;
+use net::connection::ConnectionOptions;
+use net::p2p::PeerNetwork;
+use net::*;
+use util::get_epoch_time_secs;
+
+use crate::types::chainstate::{StacksBlockHeader, StacksBlockId};
+
+/// Most microblock streams whose relay budgets we track at once
+pub const MAX_TRACKED_MICROBLOCK_STREAMS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MicroblockStreamPriority {
+    /// The stream extends the canonical Stacks chain tip
+    Canonical,
+    /// The stream is off of some other anchored block
+    Stale,
+}
+
+/// How much of a stream's microblocks we've forwarded in the current interval
+#[derive(Debug, Clone, PartialEq)]
+struct StreamBudget {
+    window_start: u64,
+    bytes_relayed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MicroblockRelayPolicy {
+    canonical_budget: u64,
+    stale_budget: u64,
+    interval: u64,
+    streams: HashMap<StacksBlockId, StreamBudget>,
+}
+
+impl MicroblockRelayPolicy {
+    pub fn new(canonical_budget: u64, stale_budget: u64, interval: u64) -> MicroblockRelayPolicy {
+        MicroblockRelayPolicy {
+            canonical_budget,
+            stale_budget,
+            interval,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn from_connection_options(opts: &ConnectionOptions) -> MicroblockRelayPolicy {
+        MicroblockRelayPolicy::new(
+            opts.microblock_relay_canonical_budget,
+            opts.microblock_relay_stale_budget,
+            opts.microblock_relay_budget_interval,
+        )
+    }
+
+    /// Does a stream off of the given anchored block extend the canonical tip?
+    pub fn classify(
+        canonical_tip: &StacksBlockId,
+        index_anchor_block: &StacksBlockId,
+    ) -> MicroblockStreamPriority {
+        if canonical_tip == index_anchor_block {
+            MicroblockStreamPriority::Canonical
+        } else {
+            MicroblockStreamPriority::Stale
+        }
+    }
+
+    /// Order MicroblocksData messages so that the ones that extend the canonical tip get sent
+    /// first.  Messages of the same priority keep their order.
+    pub fn prioritize(
+        canonical_tip: &StacksBlockId,
+        mut mblocks_msgs: Vec<(Vec<RelayData>, MicroblocksData)>,
+    ) -> Vec<(Vec<RelayData>, MicroblocksData)> {
+        mblocks_msgs.sort_by_key(|(_, mblocks_data)| {
+            MicroblockRelayPolicy::classify(canonical_tip, &mblocks_data.index_anchor_block)
+        });
+        mblocks_msgs
+    }
+
+    fn budget(&self, priority: MicroblockStreamPriority) -> u64 {
+        match priority {
+            MicroblockStreamPriority::Canonical => self.canonical_budget,
+            MicroblockStreamPriority::Stale => self.stale_budget,
+        }
+    }
+
+    /// Forget streams whose budget intervals have passed, since they'd get a full budget anyway
+    fn prune(&mut self, now: u64) {
+        let interval = self.interval;
+        self.streams
+            .retain(|_, budget| budget.window_start + interval > now);
+    }
+
+    /// How many more bytes of microblocks can we forward for this stream right now?
+    pub fn remaining(
+        &self,
+        canonical_tip: &StacksBlockId,
+        index_anchor_block: &StacksBlockId,
+        now: u64,
+    ) -> u64 {
+        let budget = self.budget(MicroblockRelayPolicy::classify(
+            canonical_tip,
+            index_anchor_block,
+        ));
+        match self.streams.get(index_anchor_block) {
+            Some(stream) if stream.window_start + self.interval > now => {
+                budget.saturating_sub(stream.bytes_relayed)
+            }
+            _ => budget,
+        }
+    }
+
+    /// Trim a batch of microblocks we're about to forward to what's left of its stream's budget,
+    /// and charge the stream for what we keep.  Microblocks are kept in order, since a neighbor
+    /// can't use a microblock without its parent.  Returns how many microblocks were dropped.
+    pub fn admit(
+        &mut self,
+        canonical_tip: &StacksBlockId,
+        mblocks_data: &mut MicroblocksData,
+        now: u64,
+    ) -> usize {
+        let remaining = self.remaining(canonical_tip, &mblocks_data.index_anchor_block, now);
+
+        let mut used = 0;
+        let mut keep = 0;
+        for mblock in mblocks_data.microblocks.iter() {
+            let mut mblock_bytes = vec![];
+            mblock
+                .consensus_serialize(&mut mblock_bytes)
+                .expect("BUG: failed to serialize microblock");
+            if used + (mblock_bytes.len() as u64) > remaining {
+                break;
+            }
+            used += mblock_bytes.len() as u64;
+            keep += 1;
+        }
+
+        let dropped = mblocks_data.microblocks.len() - keep;
+        mblocks_data.microblocks.truncate(keep);
+        if used == 0 {
+            return dropped;
+        }
+
+        if !self.streams.contains_key(&mblocks_data.index_anchor_block)
+            && self.streams.len() >= MAX_TRACKED_MICROBLOCK_STREAMS
+        {
+            self.prune(now);
+        }
+        let interval = self.interval;
+        let stream = self
+            .streams
+            .entry(mblocks_data.index_anchor_block.clone())
+            .or_insert(StreamBudget {
+                window_start: now,
+                bytes_relayed: 0,
+            });
+        if stream.window_start + interval <= now {
+            stream.window_start = now;
+            stream.bytes_relayed = 0;
+        }
+        stream.bytes_relayed += used;
+        dropped
+    }
+}
+
+impl PeerNetwork {
+    /// Index block hash of the canonical Stacks chain tip, as of the last burnchain view
+    pub fn get_canonical_stacks_tip_index_hash(&self) -> StacksBlockId {
+        StacksBlockHeader::make_index_block_hash(
+            &self.burnchain_tip.canonical_stacks_tip_consensus_hash,
+            &self.burnchain_tip.canonical_stacks_tip_hash,
+        )
+    }
+
+    /// Trim a batch of pushed microblocks we're about to broadcast to its stream's relay budget.
+    /// Returns false if there's nothing left to send.
+    pub fn admit_microblocks_broadcast(&mut self, mblocks_data: &mut MicroblocksData) -> bool {
+        let canonical_tip = self.get_canonical_stacks_tip_index_hash();
+        let dropped =
+            self.microblock_relay_policy
+                .admit(&canonical_tip, mblocks_data, get_epoch_time_secs());
+        if dropped > 0 {
+            debug!(
+                "{:?}: Will not forward {} microblock(s) off of {} (canonical tip is {}): out of relay budget",
+                &self.local_peer, dropped, &mblocks_data.index_anchor_block, &canonical_tip
+            );
+        }
+        mblocks_data.microblocks.len() > 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::*;
+    use util::hash::Sha512Trunc256Sum;
+    use util::secp256k1::MessageSignature;
+
+    use crate::types::chainstate::{BlockHeaderHash, StacksMicroblockHeader};
+
+    fn make_microblocks(count: usize) -> Vec<StacksMicroblock> {
+        let mut mblocks = vec![];
+        let mut prev_block = BlockHeaderHash([0u8; 32]);
+        for i in 0..count {
+            let header = StacksMicroblockHeader {
+                version: 0,
+                sequence: i as u16,
+                prev_block: prev_block.clone(),
+                tx_merkle_root: Sha512Trunc256Sum([i as u8; 32]),
+                signature: MessageSignature::empty(),
+            };
+            prev_block = header.block_hash();
+            mblocks.push(StacksMicroblock {
+                header,
+                txs: vec![],
+            });
+        }
+        mblocks
+    }
+
+    fn mblock_len(mblock: &StacksMicroblock) -> u64 {
+        let mut bytes = vec![];
+        mblock.consensus_serialize(&mut bytes).unwrap();
+        bytes.len() as u64
+    }
+
+    #[test]
+    fn test_prioritize_canonical_microblocks() {
+        let canonical_tip = StacksBlockId([0x01; 32]);
+        let msgs: Vec<_> = [0x02, 0x01, 0x03, 0x01]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                (
+                    vec![],
+                    MicroblocksData {
+                        index_anchor_block: StacksBlockId([*b; 32]),
+                        microblocks: make_microblocks(i + 1),
+                    },
+                )
+            })
+            .collect();
+
+        let order: Vec<_> = MicroblockRelayPolicy::prioritize(&canonical_tip, msgs)
+            .into_iter()
+            .map(|(_, data)| (data.index_anchor_block.0[0], data.microblocks.len()))
+            .collect();
+        assert_eq!(order, vec![(0x01, 2), (0x01, 4), (0x02, 1), (0x03, 3)]);
+    }
+
+    #[test]
+    fn test_microblock_relay_budgets() {
+        let mblocks = make_microblocks(10);
+        let len = mblock_len(&mblocks[0]);
+        let canonical_tip = StacksBlockId([0x01; 32]);
+        let stale_tip = StacksBlockId([0x02; 32]);
+
+        let mut policy = MicroblockRelayPolicy::new(8 * len, 2 * len, 600);
+
+        // canonical stream gets the bigger budget
+        let mut data = MicroblocksData {
+            index_anchor_block: canonical_tip.clone(),
+            microblocks: mblocks.clone(),
+        };
+        assert_eq!(policy.admit(&canonical_tip, &mut data, 1000), 2);
+        assert_eq!(data.microblocks, mblocks[0..8].to_vec());
+        assert_eq!(policy.remaining(&canonical_tip, &canonical_tip, 1000), 0);
+
+        // stale stream gets the smaller one
+        let mut data = MicroblocksData {
+            index_anchor_block: stale_tip.clone(),
+            microblocks: mblocks[0..3].to_vec(),
+        };
+        assert_eq!(policy.admit(&canonical_tip, &mut data, 1000), 1);
+        assert_eq!(data.microblocks, mblocks[0..2].to_vec());
+
+        // out of budget until the interval passes
+        let mut data = MicroblocksData {
+            index_anchor_block: stale_tip.clone(),
+            microblocks: mblocks[2..3].to_vec(),
+        };
+        assert_eq!(policy.admit(&canonical_tip, &mut data, 1599), 1);
+        assert_eq!(data.microblocks.len(), 0);
+
+        let mut data = MicroblocksData {
+            index_anchor_block: stale_tip.clone(),
+            microblocks: mblocks[2..3].to_vec(),
+        };
+        assert_eq!(policy.admit(&canonical_tip, &mut data, 1600), 0);
+        assert_eq!(data.microblocks, mblocks[2..3].to_vec());
+        assert_eq!(policy.remaining(&canonical_tip, &stale_tip, 1600), len);
+
+        // once the tip moves, the formerly-stale stream is the canonical one
+        assert_eq!(policy.remaining(&stale_tip, &stale_tip, 1600), 7 * len);
+        assert_eq!(policy.remaining(&stale_tip, &canonical_tip, 1000), 0);
+        assert_eq!(policy.remaining(&stale_tip, &canonical_tip, 1600), 2 * len);
+    }
+}
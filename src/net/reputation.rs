@@ -0,0 +1,323 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Peer reputation.  Every neighbor starts out with a perfect score, loses points whenever it
+//! misbehaves, and earns them back over time.  Neighbors with a low score are the last to be used
+//! for inventory sync and the first to be pruned, and neighbors whose score bottoms out are
+//! banned.
+
+use std::collections::HashMap;
+
+use net::connection::ConnectionOptions;
+use net::p2p::PeerNetwork;
+use net::NeighborKey;
+
+use util::get_epoch_time_secs;
+
+use monitoring::{increment_p2p_peer_misbehavior_counter, increment_p2p_peers_banned_counter};
+
+/// The score of a peer that has never misbehaved
+pub const MAX_REPUTATION: f64 = 100.0;
+
+/// Once we're tracking this many peers, forget the ones that have earned back their reputation
+const MAX_TRACKED_PEERS: usize = 4096;
+
+/// Things a peer can do to lose reputation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerMisbehavior {
+    /// sent a message that failed validation
+    InvalidMessage,
+    /// answered an inventory query with an inventory that is behind our view of the chain
+    StaleInventory,
+    /// did not answer a request before it timed out
+    SlowResponse,
+    /// pushed us more data than we allow
+    UnsolicitedSpam,
+}
+
+impl PeerMisbehavior {
+    /// Label for this misbehavior in metrics
+    pub fn reason(&self) -> &'static str {
+        match *self {
+            PeerMisbehavior::InvalidMessage => "invalid_message",
+            PeerMisbehavior::StaleInventory => "stale_inventory",
+            PeerMisbehavior::SlowResponse => "slow_response",
+            PeerMisbehavior::UnsolicitedSpam => "unsolicited_spam",
+        }
+    }
+}
+
+/// A peer's score.  Whatever it has lost below `MAX_REPUTATION` halves every `half_life` seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerReputation {
+    score: f64,
+    last_update: u64,
+}
+
+impl PeerReputation {
+    pub fn new(now: u64) -> PeerReputation {
+        PeerReputation {
+            score: MAX_REPUTATION,
+            last_update: now,
+        }
+    }
+
+    /// What the score has recovered to by `now`
+    pub fn get_score(&self, half_life: u64, now: u64) -> f64 {
+        if half_life == 0 {
+            return MAX_REPUTATION;
+        }
+        let elapsed = now.saturating_sub(self.last_update) as f64;
+        let deficit = MAX_REPUTATION - self.score;
+        MAX_REPUTATION - deficit * (0.5f64).powf(elapsed / (half_life as f64))
+    }
+
+    /// Take `penalty` points away, and return the new score
+    pub fn penalize(&mut self, penalty: f64, half_life: u64, now: u64) -> f64 {
+        self.score = (self.get_score(half_life, now) - penalty).max(0.0);
+        self.last_update = now;
+        self.score
+    }
+}
+
+/// Scores for all the peers we've seen misbehave
+#[derive(Debug, Clone)]
+pub struct PeerReputations {
+    half_life: u64,
+    ban_threshold: f64,
+    deprioritize_threshold: f64,
+    penalties: HashMap<PeerMisbehavior, f64>,
+    scores: HashMap<NeighborKey, PeerReputation>,
+}
+
+impl PeerReputations {
+    pub fn from_connection_options(opts: &ConnectionOptions) -> PeerReputations {
+        let mut penalties = HashMap::new();
+        penalties.insert(
+            PeerMisbehavior::InvalidMessage,
+            opts.reputation_penalty_invalid_message,
+        );
+        penalties.insert(
+            PeerMisbehavior::StaleInventory,
+            opts.reputation_penalty_stale_inventory,
+        );
+        penalties.insert(
+            PeerMisbehavior::SlowResponse,
+            opts.reputation_penalty_slow_response,
+        );
+        penalties.insert(
+            PeerMisbehavior::UnsolicitedSpam,
+            opts.reputation_penalty_unsolicited_spam,
+        );
+        PeerReputations {
+            half_life: opts.reputation_half_life,
+            ban_threshold: opts.reputation_ban_threshold,
+            deprioritize_threshold: opts.reputation_deprioritize_threshold,
+            penalties,
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Forget peers that have earned back their reputation
+    fn prune(&mut self, now: u64) {
+        if self.scores.len() >= MAX_TRACKED_PEERS {
+            let half_life = self.half_life;
+            self.scores
+                .retain(|_, rep| rep.get_score(half_life, now) < MAX_REPUTATION - 1.0);
+        }
+    }
+
+    /// Charge a peer for misbehaving, and return its new score
+    pub fn record(&mut self, nk: &NeighborKey, misbehavior: PeerMisbehavior, now: u64) -> f64 {
+        self.prune(now);
+        let penalty = *self.penalties.get(&misbehavior).unwrap_or(&0.0);
+        let half_life = self.half_life;
+        self.scores
+            .entry(nk.clone())
+            .or_insert_with(|| PeerReputation::new(now))
+            .penalize(penalty, half_life, now)
+    }
+
+    /// Get a peer's current score
+    pub fn get_score(&self, nk: &NeighborKey, now: u64) -> f64 {
+        self.scores
+            .get(nk)
+            .map(|rep| rep.get_score(self.half_life, now))
+            .unwrap_or(MAX_REPUTATION)
+    }
+
+    /// Should we avoid relying on this peer?
+    pub fn is_deprioritized(&self, nk: &NeighborKey, now: u64) -> bool {
+        self.get_score(nk, now) < self.deprioritize_threshold
+    }
+
+    /// Should we ban this peer?
+    pub fn is_banned(&self, nk: &NeighborKey, now: u64) -> bool {
+        self.get_score(nk, now) <= self.ban_threshold
+    }
+
+    /// Forget a peer's score.  Done once it is banned, since the peer DB tracks how long it stays
+    /// banned.
+    pub fn forget(&mut self, nk: &NeighborKey) {
+        self.scores.remove(nk);
+    }
+}
+
+impl PeerNetwork {
+    /// Charge a neighbor for misbehaving.  It will be banned on the next call to
+    /// `update_peer_reputations()` if its score falls too low.
+    pub fn record_peer_misbehavior(&mut self, nk: &NeighborKey, misbehavior: PeerMisbehavior) {
+        let score = self
+            .reputations
+            .record(nk, misbehavior, get_epoch_time_secs());
+        debug!(
+            "{:?}: neighbor {:?} misbehaved ({}); reputation is now {}",
+            &self.local_peer,
+            nk,
+            misbehavior.reason(),
+            score
+        );
+        increment_p2p_peer_misbehavior_counter(misbehavior.reason());
+    }
+
+    /// Get a neighbor's current reputation score, out of `MAX_REPUTATION`
+    pub fn get_peer_reputation(&self, nk: &NeighborKey) -> f64 {
+        self.reputations.get_score(nk, get_epoch_time_secs())
+    }
+
+    /// Is this neighbor's reputation low enough that we should avoid relying on it?
+    pub fn is_peer_deprioritized(&self, nk: &NeighborKey) -> bool {
+        self.reputations.is_deprioritized(nk, get_epoch_time_secs())
+    }
+
+    /// Charge a conversation's neighbor for the misbehavior it logged since we last looked
+    pub(crate) fn collect_peer_misbehavior(&mut self, event_id: usize) {
+        let (nk, misbehavior) = match self.peers.get_mut(&event_id) {
+            Some(convo) => (convo.to_neighbor_key(), convo.stats.take_misbehavior()),
+            None => {
+                return;
+            }
+        };
+        for m in misbehavior.into_iter() {
+            self.record_peer_misbehavior(&nk, m);
+        }
+    }
+
+    /// Collect all conversations' misbehavior, refresh their reputation scores, and schedule the
+    /// neighbors whose reputation fell too low to be banned.
+    pub fn update_peer_reputations(&mut self) {
+        let event_ids: Vec<usize> = self.peers.keys().map(|event_id| *event_id).collect();
+        let now = get_epoch_time_secs();
+        for event_id in event_ids.into_iter() {
+            self.collect_peer_misbehavior(event_id);
+
+            let convo = match self.peers.get_mut(&event_id) {
+                Some(convo) => convo,
+                None => {
+                    continue;
+                }
+            };
+            let nk = convo.to_neighbor_key();
+            convo.stats.reputation = self.reputations.get_score(&nk, now);
+
+            if self.reputations.is_banned(&nk, now) && !self.bans.contains(&event_id) {
+                info!(
+                    "{:?}: will ban neighbor {:?} with reputation {}",
+                    &self.local_peer, &nk, convo.stats.reputation
+                );
+                self.bans.insert(event_id);
+                self.reputations.forget(&nk);
+                increment_p2p_peers_banned_counter();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::PeerAddress;
+
+    fn make_nk(port: u16) -> NeighborKey {
+        NeighborKey {
+            peer_version: 0x18000000,
+            network_id: 0x80000000,
+            addrbytes: PeerAddress([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 127, 0, 0, 1]),
+            port,
+        }
+    }
+
+    #[test]
+    fn test_peer_reputation_decay() {
+        let mut rep = PeerReputation::new(1000);
+        assert_eq!(rep.get_score(3600, 1000), MAX_REPUTATION);
+
+        assert_eq!(rep.penalize(40.0, 3600, 1000), 60.0);
+        assert_eq!(rep.get_score(3600, 1000), 60.0);
+
+        // half of the lost reputation comes back every half-life
+        assert_eq!(rep.get_score(3600, 1000 + 3600), 80.0);
+        assert_eq!(rep.get_score(3600, 1000 + 7200), 90.0);
+
+        // penalties apply to the recovered score, and never go below 0
+        assert_eq!(rep.penalize(30.0, 3600, 1000 + 3600), 50.0);
+        assert_eq!(rep.penalize(1000.0, 3600, 1000 + 3600), 0.0);
+
+        // no half-life means instant forgiveness
+        assert_eq!(rep.get_score(0, 1000 + 3600), MAX_REPUTATION);
+    }
+
+    #[test]
+    fn test_peer_reputations() {
+        let mut opts = ConnectionOptions::default();
+        opts.reputation_half_life = 100;
+        opts.reputation_ban_threshold = 10.0;
+        opts.reputation_deprioritize_threshold = 50.0;
+        opts.reputation_penalty_invalid_message = 30.0;
+        opts.reputation_penalty_stale_inventory = 5.0;
+        opts.reputation_penalty_slow_response = 0.0;
+
+        let nk_1 = make_nk(1);
+        let nk_2 = make_nk(2);
+        let mut reps = PeerReputations::from_connection_options(&opts);
+
+        // well-behaved peers have full marks
+        assert_eq!(reps.get_score(&nk_1, 0), MAX_REPUTATION);
+        assert!(!reps.is_deprioritized(&nk_1, 0));
+        assert!(!reps.is_banned(&nk_1, 0));
+
+        // penalties differ by misbehavior, and may be turned off
+        assert_eq!(reps.record(&nk_1, PeerMisbehavior::StaleInventory, 0), 95.0);
+        assert_eq!(reps.record(&nk_1, PeerMisbehavior::SlowResponse, 0), 95.0);
+        assert_eq!(reps.record(&nk_1, PeerMisbehavior::InvalidMessage, 0), 65.0);
+        assert_eq!(reps.get_score(&nk_2, 0), MAX_REPUTATION);
+
+        // enough misbehavior gets a peer deprioritized...
+        assert_eq!(reps.record(&nk_1, PeerMisbehavior::InvalidMessage, 0), 35.0);
+        assert!(reps.is_deprioritized(&nk_1, 0));
+        assert!(!reps.is_banned(&nk_1, 0));
+
+        // ...until it earns its reputation back
+        assert!(!reps.is_deprioritized(&nk_1, 100));
+
+        // and yet more gets it banned
+        assert_eq!(reps.record(&nk_1, PeerMisbehavior::InvalidMessage, 0), 5.0);
+        assert!(reps.is_banned(&nk_1, 0));
+
+        reps.forget(&nk_1);
+        assert_eq!(reps.get_score(&nk_1, 0), MAX_REPUTATION);
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! In-process cache of RPC responses for immutable resources.  Entries are keyed by the
+//! resource's strong ETag, so a cached response never goes stale: if the resource changed, so
+//! would its ETag.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+/// Least-recently-used cache of response payloads, keyed by ETag
+#[derive(Debug, Clone)]
+pub struct ResponseCache<V: Clone> {
+    capacity: usize,
+    /// ETag => (payload, last-used tick)
+    entries: HashMap<String, (V, u64)>,
+    /// last-used tick => ETag, for finding the least-recently-used entry
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl<V: Clone> ResponseCache<V> {
+    /// Make a cache of up to `capacity` responses.  A capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> ResponseCache<V> {
+        ResponseCache {
+            capacity,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn touch(&mut self, etag: &str) {
+        if let Some((_, last_used)) = self.entries.get_mut(etag) {
+            self.lru.remove(last_used);
+            self.tick += 1;
+            *last_used = self.tick;
+            self.lru.insert(self.tick, etag.to_string());
+        }
+    }
+
+    /// Get the cached response for `etag`, if there is one
+    pub fn get(&mut self, etag: &str) -> Option<V> {
+        self.touch(etag);
+        self.entries.get(etag).map(|(value, _)| value.clone())
+    }
+
+    /// Cache the response for `etag`, evicting the least-recently-used response if the cache is
+    /// full
+    pub fn insert(&mut self, etag: String, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((_, last_used)) = self.entries.remove(&etag) {
+            self.lru.remove(&last_used);
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest = match self.lru.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(evicted) = self.lru.remove(&oldest) {
+                self.entries.remove(&evicted);
+            }
+        }
+        self.tick += 1;
+        self.lru.insert(self.tick, etag.clone());
+        self.entries.insert(etag, (value, self.tick));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_response_cache_lru() {
+        let mut cache = ResponseCache::new(2);
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // using "a" makes "b" the least-recently-used entry
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c".to_string(), 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+
+        // replacing an entry doesn't evict anything
+        cache.insert("c".to_string(), 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(4));
+
+        let mut disabled = ResponseCache::new(0);
+        disabled.insert("a".to_string(), 1);
+        assert!(disabled.is_empty());
+        assert_eq!(disabled.get("a"), None);
+    }
+}
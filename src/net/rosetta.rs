@@ -0,0 +1,1584 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Rosetta Data and Construction APIs (https://www.rosetta-api.org), served under
+//! `/rosetta/v1` straight from the chainstate and mempool.
+//!
+//! The Data API reads transactions' operations from the chainstate's Rosetta index, so it only
+//! describes blocks processed while the index was enabled.  The Construction API builds, signs
+//! and submits single-signature STX token transfers.
+
+use std::convert::TryFrom;
+
+use address::AddressHashMode;
+use burnchains::{Address, Txid};
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::stacks::db::blocks::MINIMUM_TX_FEE_RATE_PER_BYTE;
+use chainstate::stacks::db::rosetta::{
+    payload_operation_type, RosettaIndexedOperation, RosettaIndexedTransaction,
+    ROSETTA_OPERATION_TYPES, ROSETTA_STATUSES, ROSETTA_STATUS_SUCCESS,
+};
+use chainstate::stacks::db::{StacksChainState, StacksHeaderInfo};
+use chainstate::stacks::Error as chain_error;
+use chainstate::stacks::*;
+use clarity_vm::clarity::ClarityConnection;
+use core::mempool::{MemPoolDB, MemPoolEventDispatcher, MemPoolTxFilter};
+use util::hash::{hex_bytes, to_hex};
+use util::secp256k1::MessageSignature;
+use vm::database::BurnStateDB;
+use vm::types::PrincipalData;
+
+use crate::codec::StacksMessageCodec;
+use crate::types::chainstate::{StacksAddress, StacksBlockId};
+
+/// Version of the Rosetta specification implemented here
+pub const ROSETTA_VERSION: &'static str = "1.4.10";
+pub const ROSETTA_BLOCKCHAIN: &'static str = "stacks";
+pub const STX_SYMBOL: &'static str = "STX";
+pub const STX_DECIMALS: u32 = 6;
+
+/// Most mempool transaction IDs returned by `/mempool`
+pub const MAX_ROSETTA_MEMPOOL_TXS: u32 = 10_000;
+
+/// Encoded length of a single-signature token transfer, for suggesting fees
+const TOKEN_TRANSFER_TX_LEN: u64 = 180;
+
+/// Sub-account holding an account's locked STX
+const LOCKED_SUB_ACCOUNT: &'static str = "locked";
+
+pub const ERR_NETWORK: u32 = 1;
+pub const ERR_REQUEST: u32 = 2;
+pub const ERR_BLOCK_NOT_FOUND: u32 = 3;
+pub const ERR_TRANSACTION_NOT_FOUND: u32 = 4;
+pub const ERR_ACCOUNT: u32 = 5;
+pub const ERR_PUBLIC_KEY: u32 = 6;
+pub const ERR_OPERATIONS: u32 = 7;
+pub const ERR_TRANSACTION: u32 = 8;
+pub const ERR_SIGNATURE: u32 = 9;
+pub const ERR_REJECTED: u32 = 10;
+pub const ERR_INTERNAL: u32 = 11;
+
+/// Every error this API returns: (code, message, retriable)
+pub const ROSETTA_ERRORS: &'static [(u32, &'static str, bool)] = &[
+    (ERR_NETWORK, "Invalid network identifier", false),
+    (ERR_REQUEST, "Invalid request", false),
+    (ERR_BLOCK_NOT_FOUND, "Block not found", true),
+    (ERR_TRANSACTION_NOT_FOUND, "Transaction not found", true),
+    (ERR_ACCOUNT, "Invalid account identifier", false),
+    (ERR_PUBLIC_KEY, "Invalid public key", false),
+    (ERR_OPERATIONS, "Unsupported operations", false),
+    (ERR_TRANSACTION, "Invalid transaction", false),
+    (ERR_SIGNATURE, "Invalid signature", false),
+    (ERR_REJECTED, "Transaction rejected", false),
+    (ERR_INTERNAL, "Internal error", true),
+];
+
+/// A Rosetta endpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RosettaEndpoint {
+    NetworkList,
+    NetworkOptions,
+    NetworkStatus,
+    Block,
+    BlockTransaction,
+    AccountBalance,
+    Mempool,
+    MempoolTransaction,
+    ConstructionDerive,
+    ConstructionPreprocess,
+    ConstructionMetadata,
+    ConstructionPayloads,
+    ConstructionCombine,
+    ConstructionParse,
+    ConstructionHash,
+    ConstructionSubmit,
+}
+
+impl RosettaEndpoint {
+    pub const ALL: &'static [RosettaEndpoint] = &[
+        RosettaEndpoint::NetworkList,
+        RosettaEndpoint::NetworkOptions,
+        RosettaEndpoint::NetworkStatus,
+        RosettaEndpoint::Block,
+        RosettaEndpoint::BlockTransaction,
+        RosettaEndpoint::AccountBalance,
+        RosettaEndpoint::Mempool,
+        RosettaEndpoint::MempoolTransaction,
+        RosettaEndpoint::ConstructionDerive,
+        RosettaEndpoint::ConstructionPreprocess,
+        RosettaEndpoint::ConstructionMetadata,
+        RosettaEndpoint::ConstructionPayloads,
+        RosettaEndpoint::ConstructionCombine,
+        RosettaEndpoint::ConstructionParse,
+        RosettaEndpoint::ConstructionHash,
+        RosettaEndpoint::ConstructionSubmit,
+    ];
+
+    /// The endpoint's path, relative to `/rosetta/v1`
+    pub fn name(&self) -> &'static str {
+        match self {
+            RosettaEndpoint::NetworkList => "network/list",
+            RosettaEndpoint::NetworkOptions => "network/options",
+            RosettaEndpoint::NetworkStatus => "network/status",
+            RosettaEndpoint::Block => "block",
+            RosettaEndpoint::BlockTransaction => "block/transaction",
+            RosettaEndpoint::AccountBalance => "account/balance",
+            RosettaEndpoint::Mempool => "mempool",
+            RosettaEndpoint::MempoolTransaction => "mempool/transaction",
+            RosettaEndpoint::ConstructionDerive => "construction/derive",
+            RosettaEndpoint::ConstructionPreprocess => "construction/preprocess",
+            RosettaEndpoint::ConstructionMetadata => "construction/metadata",
+            RosettaEndpoint::ConstructionPayloads => "construction/payloads",
+            RosettaEndpoint::ConstructionCombine => "construction/combine",
+            RosettaEndpoint::ConstructionParse => "construction/parse",
+            RosettaEndpoint::ConstructionHash => "construction/hash",
+            RosettaEndpoint::ConstructionSubmit => "construction/submit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RosettaEndpoint> {
+        RosettaEndpoint::ALL
+            .iter()
+            .find(|endpoint| endpoint.name() == name)
+            .copied()
+    }
+
+    pub fn path(&self) -> String {
+        format!("/rosetta/v1/{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RosettaError {
+    pub code: u32,
+    pub message: String,
+    pub retriable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl RosettaError {
+    pub fn new(code: u32) -> RosettaError {
+        let (code, message, retriable) = ROSETTA_ERRORS
+            .iter()
+            .find(|(known, ..)| *known == code)
+            .copied()
+            .unwrap_or((ERR_INTERNAL, "Internal error", true));
+        RosettaError {
+            code,
+            message: message.to_string(),
+            retriable,
+            details: None,
+        }
+    }
+
+    pub fn with_message(code: u32, message: &str) -> RosettaError {
+        let mut error = RosettaError::new(code);
+        error.details = Some(json!({ "message": message }));
+        error
+    }
+}
+
+impl From<chain_error> for RosettaError {
+    fn from(e: chain_error) -> RosettaError {
+        RosettaError::with_message(ERR_INTERNAL, &format!("{:?}", &e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkIdentifier {
+    pub blockchain: String,
+    pub network: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockIdentifier {
+    pub index: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct PartialBlockIdentifier {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionIdentifier {
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubAccountIdentifier {
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountIdentifier {
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_account: Option<SubAccountIdentifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Currency {
+    pub symbol: String,
+    pub decimals: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Amount {
+    pub value: String,
+    pub currency: Currency,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationIdentifier {
+    pub index: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Operation {
+    pub operation_identifier: OperationIdentifier,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub related_operations: Option<Vec<OperationIdentifier>>,
+    #[serde(rename = "type")]
+    pub op_type: String,
+    /// absent for operations that haven't been executed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<AccountIdentifier>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub transaction_identifier: TransactionIdentifier,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    pub block_identifier: BlockIdentifier,
+    pub parent_block_identifier: BlockIdentifier,
+    /// milliseconds since the epoch
+    pub timestamp: u64,
+    pub transactions: Vec<Transaction>,
+    pub metadata: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicKey {
+    pub hex_bytes: String,
+    pub curve_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningPayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_identifier: Option<AccountIdentifier>,
+    pub hex_bytes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub signing_payload: SigningPayload,
+    pub public_key: PublicKey,
+    pub signature_type: String,
+    pub hex_bytes: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct NetworkRequest {
+    network_identifier: NetworkIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BlockRequest {
+    network_identifier: NetworkIdentifier,
+    block_identifier: PartialBlockIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BlockTransactionRequest {
+    network_identifier: NetworkIdentifier,
+    block_identifier: BlockIdentifier,
+    transaction_identifier: TransactionIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AccountBalanceRequest {
+    network_identifier: NetworkIdentifier,
+    account_identifier: AccountIdentifier,
+    #[serde(default)]
+    block_identifier: Option<PartialBlockIdentifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MempoolTransactionRequest {
+    network_identifier: NetworkIdentifier,
+    transaction_identifier: TransactionIdentifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ConstructionDeriveRequest {
+    network_identifier: NetworkIdentifier,
+    public_key: PublicKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ConstructionPreprocessRequest {
+    network_identifier: NetworkIdentifier,
+    operations: Vec<Operation>,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ConstructionMetadataRequest {
+    network_identifier: NetworkIdentifier,
+    options: TransferOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ConstructionPayloadsRequest {
+    network_identifier: NetworkIdentifier,
+    operations: Vec<Operation>,
+    #[serde(default)]
+    metadata: Option<TransferMetadata>,
+    #[serde(default)]
+    public_keys: Vec<PublicKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ConstructionCombineRequest {
+    network_identifier: NetworkIdentifier,
+    unsigned_transaction: String,
+    signatures: Vec<Signature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ConstructionParseRequest {
+    network_identifier: NetworkIdentifier,
+    signed: bool,
+    transaction: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SignedTransactionRequest {
+    network_identifier: NetworkIdentifier,
+    signed_transaction: String,
+}
+
+/// What `/construction/preprocess` tells `/construction/metadata` about a transfer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransferOptions {
+    sender_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+}
+
+/// What `/construction/metadata` tells `/construction/payloads` about a transfer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransferMetadata {
+    nonce: u64,
+    fee: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+}
+
+/// A token transfer described by a list of operations
+#[derive(Debug, Clone, PartialEq)]
+struct TransferIntent {
+    sender: StacksAddress,
+    recipient: PrincipalData,
+    amount: u64,
+    fee: Option<u64>,
+}
+
+/// Node state that isn't in the chainstate or mempool
+#[derive(Debug, Clone, PartialEq)]
+pub struct RosettaNodeInfo {
+    pub server_version: String,
+    /// IDs of the peers this node is talking to
+    pub peers: Vec<String>,
+}
+
+fn stx_currency() -> Currency {
+    Currency {
+        symbol: STX_SYMBOL.to_string(),
+        decimals: STX_DECIMALS,
+    }
+}
+
+fn stx_amount(value: i128) -> Amount {
+    Amount {
+        value: value.to_string(),
+        currency: stx_currency(),
+    }
+}
+
+fn network_identifier(mainnet: bool) -> NetworkIdentifier {
+    NetworkIdentifier {
+        blockchain: ROSETTA_BLOCKCHAIN.to_string(),
+        network: if mainnet { "mainnet" } else { "testnet" }.to_string(),
+    }
+}
+
+fn check_network(mainnet: bool, network: &NetworkIdentifier) -> Result<(), RosettaError> {
+    if *network == network_identifier(mainnet) {
+        Ok(())
+    } else {
+        Err(RosettaError::with_message(
+            ERR_NETWORK,
+            &format!(
+                "This node serves {}/{}",
+                ROSETTA_BLOCKCHAIN,
+                network_identifier(mainnet).network
+            ),
+        ))
+    }
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &serde_json::Value) -> Result<T, RosettaError> {
+    serde_json::from_value(body.clone())
+        .map_err(|e| RosettaError::with_message(ERR_REQUEST, &e.to_string()))
+}
+
+fn strip_hex_prefix(hex: &str) -> &str {
+    hex.strip_prefix("0x").unwrap_or(hex)
+}
+
+fn block_identifier(header: &StacksHeaderInfo) -> BlockIdentifier {
+    BlockIdentifier {
+        index: header.block_height,
+        hash: format!("0x{}", &header.index_block_hash()),
+    }
+}
+
+fn parse_txid(id: &TransactionIdentifier) -> Result<Txid, RosettaError> {
+    Txid::from_hex(strip_hex_prefix(&id.hash))
+        .map_err(|_| RosettaError::with_message(ERR_REQUEST, "Invalid transaction hash"))
+}
+
+fn decode_transaction(hex: &str) -> Result<StacksTransaction, RosettaError> {
+    let bytes = hex_bytes(strip_hex_prefix(hex))
+        .map_err(|_| RosettaError::with_message(ERR_TRANSACTION, "Invalid hex"))?;
+    StacksTransaction::consensus_deserialize(&mut &bytes[..])
+        .map_err(|e| RosettaError::with_message(ERR_TRANSACTION, &e.to_string()))
+}
+
+fn encode_transaction(tx: &StacksTransaction) -> String {
+    to_hex(&tx.serialize_to_vec())
+}
+
+fn canonical_tip(
+    sortdb: &SortitionDB,
+    chainstate: &StacksChainState,
+) -> Result<StacksHeaderInfo, RosettaError> {
+    let (consensus_hash, block_hash) =
+        SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())
+            .map_err(|e| RosettaError::with_message(ERR_INTERNAL, &format!("{:?}", &e)))?;
+    StacksChainState::get_anchored_block_header_info(chainstate.db(), &consensus_hash, &block_hash)?
+        .ok_or_else(|| RosettaError::new(ERR_BLOCK_NOT_FOUND))
+}
+
+/// Find the block a partial identifier names on the canonical fork.  An empty identifier names
+/// the canonical tip.
+fn resolve_block(
+    sortdb: &SortitionDB,
+    chainstate: &StacksChainState,
+    id: &PartialBlockIdentifier,
+) -> Result<StacksHeaderInfo, RosettaError> {
+    let block_id = match (id.index, id.hash.as_ref()) {
+        (_, Some(hash)) => StacksBlockId::from_hex(strip_hex_prefix(hash))
+            .map_err(|_| RosettaError::with_message(ERR_REQUEST, "Invalid block hash"))?,
+        (Some(index), None) => {
+            let tip = canonical_tip(sortdb, chainstate)?;
+            chainstate
+                .index_conn()?
+                .get_ancestor_block_hash(index, &tip.index_block_hash())
+                .map_err(chain_error::DBError)?
+                .ok_or_else(|| RosettaError::new(ERR_BLOCK_NOT_FOUND))?
+        }
+        (None, None) => return canonical_tip(sortdb, chainstate),
+    };
+    let header = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+        chainstate.db(),
+        &block_id,
+    )?
+    .ok_or_else(|| RosettaError::new(ERR_BLOCK_NOT_FOUND))?;
+    match id.index {
+        Some(index) if index != header.block_height => Err(RosettaError::with_message(
+            ERR_BLOCK_NOT_FOUND,
+            "Block index and hash do not match",
+        )),
+        _ => Ok(header),
+    }
+}
+
+fn indexed_operation(
+    index: usize,
+    op: &RosettaIndexedOperation,
+) -> Result<Operation, RosettaError> {
+    let amount = match op.amount {
+        Some(ref amount) => Some(stx_amount(amount.parse().map_err(|_| {
+            RosettaError::with_message(ERR_INTERNAL, "Corrupt Rosetta index")
+        })?)),
+        None => None,
+    };
+    Ok(Operation {
+        operation_identifier: OperationIdentifier {
+            index: index as u64,
+        },
+        related_operations: if op.related.is_empty() {
+            None
+        } else {
+            Some(
+                op.related
+                    .iter()
+                    .map(|related| OperationIdentifier {
+                        index: *related as u64,
+                    })
+                    .collect(),
+            )
+        },
+        op_type: op.op_type.clone(),
+        status: Some(op.status.clone()),
+        account: op.account.as_ref().map(|address| AccountIdentifier {
+            address: address.clone(),
+            sub_account: None,
+        }),
+        amount,
+        metadata: op.metadata.clone(),
+    })
+}
+
+fn indexed_transaction(tx: &RosettaIndexedTransaction) -> Result<Transaction, RosettaError> {
+    Ok(Transaction {
+        transaction_identifier: TransactionIdentifier {
+            hash: format!("0x{}", &tx.txid),
+        },
+        operations: tx
+            .operations
+            .iter()
+            .enumerate()
+            .map(|(i, op)| indexed_operation(i, op))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// The operations a transaction will perform if it succeeds, without statuses.  Used for
+/// transactions that haven't been mined.
+fn transaction_operations(tx: &StacksTransaction, with_fee: bool) -> Vec<Operation> {
+    let origin = tx.origin_address().to_string();
+    let mut operations = vec![];
+    let mut push = |op_type: &str, address: String, amount: Option<i128>| {
+        operations.push(Operation {
+            operation_identifier: OperationIdentifier {
+                index: operations.len() as u64,
+            },
+            related_operations: None,
+            op_type: op_type.to_string(),
+            status: None,
+            account: Some(AccountIdentifier {
+                address,
+                sub_account: None,
+            }),
+            amount: amount.map(stx_amount),
+            metadata: None,
+        });
+    };
+
+    match tx.payload {
+        TransactionPayload::TokenTransfer(ref recipient, amount, _) => {
+            push("token_transfer", origin, Some(-(amount as i128)));
+            push(
+                "token_transfer",
+                recipient.to_string(),
+                Some(amount as i128),
+            );
+        }
+        ref payload => push(payload_operation_type(payload), origin, None),
+    }
+    if with_fee && tx.get_tx_fee() > 0 {
+        let payer = tx
+            .sponsor_address()
+            .unwrap_or_else(|| tx.origin_address())
+            .to_string();
+        push("fee", payer, Some(-(tx.get_tx_fee() as i128)));
+    }
+
+    if let TransactionPayload::TokenTransfer(..) = tx.payload {
+        operations[0].related_operations = Some(vec![OperationIdentifier { index: 1 }]);
+        operations[1].related_operations = Some(vec![OperationIdentifier { index: 0 }]);
+    }
+    operations
+}
+
+fn parse_amount(amount: &Option<Amount>) -> Result<i128, RosettaError> {
+    let amount = amount
+        .as_ref()
+        .ok_or_else(|| RosettaError::with_message(ERR_OPERATIONS, "Operation has no amount"))?;
+    if amount.currency != stx_currency() {
+        return Err(RosettaError::with_message(
+            ERR_OPERATIONS,
+            "Only STX can be transferred",
+        ));
+    }
+    amount
+        .value
+        .parse()
+        .map_err(|_| RosettaError::with_message(ERR_OPERATIONS, "Invalid amount"))
+}
+
+fn operation_address(op: &Operation) -> Result<&str, RosettaError> {
+    op.account
+        .as_ref()
+        .map(|account| account.address.as_str())
+        .ok_or_else(|| RosettaError::with_message(ERR_OPERATIONS, "Operation has no account"))
+}
+
+/// Interpret operations as a token transfer: a `token_transfer` debit of the sender, a matching
+/// `token_transfer` credit of the recipient, and optionally a `fee` debit of the sender.
+fn parse_transfer_intent(operations: &[Operation]) -> Result<TransferIntent, RosettaError> {
+    let mut debit = None;
+    let mut credit = None;
+    let mut fee = None;
+    for op in operations.iter() {
+        let amount = parse_amount(&op.amount)?;
+        let address = operation_address(op)?;
+        match (op.op_type.as_str(), amount < 0) {
+            ("token_transfer", true) if debit.is_none() => debit = Some((address, -amount)),
+            ("token_transfer", false) if credit.is_none() => credit = Some((address, amount)),
+            ("fee", true) if fee.is_none() => fee = Some((address, -amount)),
+            _ => {
+                return Err(RosettaError::with_message(
+                    ERR_OPERATIONS,
+                    "Only token transfers are supported",
+                ));
+            }
+        }
+    }
+
+    let (sender, amount) = debit
+        .ok_or_else(|| RosettaError::with_message(ERR_OPERATIONS, "Transfer has no sender"))?;
+    let (recipient, credited) = credit
+        .ok_or_else(|| RosettaError::with_message(ERR_OPERATIONS, "Transfer has no recipient"))?;
+    if amount != credited {
+        return Err(RosettaError::with_message(
+            ERR_OPERATIONS,
+            "Transfer debit and credit do not match",
+        ));
+    }
+    let fee = match fee {
+        Some((payer, _)) if payer != sender => {
+            return Err(RosettaError::with_message(
+                ERR_OPERATIONS,
+                "The sender must pay the fee",
+            ));
+        }
+        Some((_, fee)) => Some(
+            u64::try_from(fee)
+                .map_err(|_| RosettaError::with_message(ERR_OPERATIONS, "Invalid fee"))?,
+        ),
+        None => None,
+    };
+
+    Ok(TransferIntent {
+        sender: StacksAddress::from_string(sender).ok_or_else(|| RosettaError::new(ERR_ACCOUNT))?,
+        recipient: PrincipalData::parse(recipient).map_err(|_| RosettaError::new(ERR_ACCOUNT))?,
+        amount: u64::try_from(amount)
+            .map_err(|_| RosettaError::with_message(ERR_OPERATIONS, "Invalid amount"))?,
+        fee,
+    })
+}
+
+fn parse_public_key(key: &PublicKey) -> Result<StacksPublicKey, RosettaError> {
+    if key.curve_type != "secp256k1" {
+        return Err(RosettaError::with_message(
+            ERR_PUBLIC_KEY,
+            "Only secp256k1 keys are supported",
+        ));
+    }
+    let bytes = hex_bytes(strip_hex_prefix(&key.hex_bytes))
+        .map_err(|_| RosettaError::new(ERR_PUBLIC_KEY))?;
+    StacksPublicKey::from_slice(&bytes).map_err(|_| RosettaError::new(ERR_PUBLIC_KEY))
+}
+
+fn public_key_address(mainnet: bool, key: &StacksPublicKey) -> Option<StacksAddress> {
+    let version = if mainnet {
+        C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+    } else {
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+    };
+    StacksAddress::from_public_keys(
+        version,
+        &AddressHashMode::SerializeP2PKH,
+        1,
+        &vec![key.clone()],
+    )
+}
+
+fn read_account(
+    sortdb: &SortitionDB,
+    chainstate: &mut StacksChainState,
+    block_id: &StacksBlockId,
+    principal: &PrincipalData,
+) -> Result<(u128, u128, u64, u64), RosettaError> {
+    chainstate
+        .maybe_read_only_clarity_tx(&sortdb.index_conn(), block_id, |clarity_tx| {
+            clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                let burn_block_height = clarity_db.get_current_burnchain_block_height() as u64;
+                let balance = clarity_db.get_account_stx_balance(principal);
+                let nonce = clarity_db.get_account_nonce(principal);
+                let (locked, unlock_height) =
+                    balance.get_locked_balance_at_burn_block(burn_block_height);
+                (balance.get_total_balance(), locked, unlock_height, nonce)
+            })
+        })?
+        .ok_or_else(|| RosettaError::new(ERR_BLOCK_NOT_FOUND))
+}
+
+fn network_options(node: &RosettaNodeInfo) -> serde_json::Value {
+    let statuses: Vec<_> = ROSETTA_STATUSES
+        .iter()
+        .map(|status| json!({ "status": status, "successful": *status == ROSETTA_STATUS_SUCCESS }))
+        .collect();
+    let errors: Vec<_> = ROSETTA_ERRORS
+        .iter()
+        .map(|(code, ..)| RosettaError::new(*code))
+        .collect();
+    json!({
+        "version": {
+            "rosetta_version": ROSETTA_VERSION,
+            "node_version": node.server_version,
+        },
+        "allow": {
+            "operation_statuses": statuses,
+            "operation_types": ROSETTA_OPERATION_TYPES,
+            "errors": errors,
+            "historical_balance_lookup": true,
+            "mempool_coins": false,
+        },
+    })
+}
+
+fn network_status(
+    node: &RosettaNodeInfo,
+    sortdb: &SortitionDB,
+    chainstate: &StacksChainState,
+) -> Result<serde_json::Value, RosettaError> {
+    let tip = canonical_tip(sortdb, chainstate)?;
+    let genesis = StacksChainState::get_genesis_header_info(chainstate.db())?;
+    let peers: Vec<_> = node
+        .peers
+        .iter()
+        .map(|peer_id| json!({ "peer_id": peer_id }))
+        .collect();
+    Ok(json!({
+        "current_block_identifier": block_identifier(&tip),
+        "current_block_timestamp": tip.burn_header_timestamp * 1000,
+        "genesis_block_identifier": block_identifier(&genesis),
+        "peers": peers,
+    }))
+}
+
+fn get_block(
+    sortdb: &SortitionDB,
+    chainstate: &StacksChainState,
+    id: &PartialBlockIdentifier,
+) -> Result<serde_json::Value, RosettaError> {
+    let header = resolve_block(sortdb, chainstate, id)?;
+    let block_id = header.index_block_hash();
+    let parent = if header.block_height == 0 {
+        header.clone()
+    } else {
+        let parent_id = StacksChainState::get_parent_block_id(chainstate.db(), &block_id)?
+            .ok_or_else(|| RosettaError::new(ERR_BLOCK_NOT_FOUND))?;
+        StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.db(),
+            &parent_id,
+        )?
+        .ok_or_else(|| RosettaError::new(ERR_BLOCK_NOT_FOUND))?
+    };
+    let transactions = chainstate
+        .get_rosetta_block_transactions(&block_id)?
+        .iter()
+        .map(indexed_transaction)
+        .collect::<Result<_, _>>()?;
+
+    let block = Block {
+        block_identifier: block_identifier(&header),
+        parent_block_identifier: block_identifier(&parent),
+        timestamp: header.burn_header_timestamp * 1000,
+        transactions,
+        metadata: json!({
+            "block_hash": format!("0x{}", &header.anchored_header.block_hash()),
+            "consensus_hash": format!("0x{}", &header.consensus_hash),
+            "burn_block_hash": format!("0x{}", &header.burn_header_hash),
+            "burn_block_height": header.burn_header_height,
+        }),
+    };
+    Ok(json!({ "block": block }))
+}
+
+fn get_block_transaction(
+    sortdb: &SortitionDB,
+    chainstate: &StacksChainState,
+    request: &BlockTransactionRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let header = resolve_block(
+        sortdb,
+        chainstate,
+        &PartialBlockIdentifier {
+            index: Some(request.block_identifier.index),
+            hash: Some(request.block_identifier.hash.clone()),
+        },
+    )?;
+    let txid = parse_txid(&request.transaction_identifier)?;
+    let transaction = chainstate
+        .get_rosetta_transaction(&header.index_block_hash(), &txid)?
+        .ok_or_else(|| RosettaError::new(ERR_TRANSACTION_NOT_FOUND))?;
+    Ok(json!({ "transaction": indexed_transaction(&transaction)? }))
+}
+
+fn get_account_balance(
+    sortdb: &SortitionDB,
+    chainstate: &mut StacksChainState,
+    request: &AccountBalanceRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let principal = PrincipalData::parse(&request.account_identifier.address)
+        .map_err(|_| RosettaError::new(ERR_ACCOUNT))?;
+    let locked_only = match request.account_identifier.sub_account {
+        None => false,
+        Some(ref sub_account) if sub_account.address == LOCKED_SUB_ACCOUNT => true,
+        Some(_) => {
+            return Err(RosettaError::with_message(
+                ERR_ACCOUNT,
+                "The only sub-account is \"locked\"",
+            ));
+        }
+    };
+    let header = resolve_block(
+        sortdb,
+        chainstate,
+        &request.block_identifier.clone().unwrap_or_default(),
+    )?;
+    let (total, locked, unlock_height, nonce) =
+        read_account(sortdb, chainstate, &header.index_block_hash(), &principal)?;
+
+    let balance = if locked_only { locked } else { total };
+    Ok(json!({
+        "block_identifier": block_identifier(&header),
+        "balances": [stx_amount(balance as i128)],
+        "metadata": {
+            "sequence_number": nonce,
+            "locked": locked.to_string(),
+            "unlock_height": unlock_height,
+        },
+    }))
+}
+
+fn get_mempool(mempool: &MemPoolDB) -> Result<serde_json::Value, RosettaError> {
+    let txs = MemPoolDB::get_txs_page(
+        mempool.conn(),
+        &MemPoolTxFilter::default(),
+        None,
+        MAX_ROSETTA_MEMPOOL_TXS,
+    )
+    .map_err(chain_error::DBError)?;
+    let ids: Vec<_> = txs
+        .iter()
+        .map(|(txinfo, _)| TransactionIdentifier {
+            hash: format!("0x{}", &txinfo.metadata.txid),
+        })
+        .collect();
+    Ok(json!({ "transaction_identifiers": ids }))
+}
+
+fn get_mempool_transaction(
+    mempool: &MemPoolDB,
+    request: &MempoolTransactionRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let txid = parse_txid(&request.transaction_identifier)?;
+    let txinfo = MemPoolDB::get_tx(mempool.conn(), &txid)
+        .map_err(chain_error::DBError)?
+        .ok_or_else(|| RosettaError::new(ERR_TRANSACTION_NOT_FOUND))?;
+    let transaction = Transaction {
+        transaction_identifier: request.transaction_identifier.clone(),
+        operations: transaction_operations(&txinfo.tx, true),
+    };
+    Ok(json!({ "transaction": transaction }))
+}
+
+fn construction_derive(
+    mainnet: bool,
+    request: &ConstructionDeriveRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let key = parse_public_key(&request.public_key)?;
+    let address =
+        public_key_address(mainnet, &key).ok_or_else(|| RosettaError::new(ERR_PUBLIC_KEY))?;
+    Ok(json!({ "account_identifier": { "address": address.to_string() } }))
+}
+
+fn construction_preprocess(
+    request: &ConstructionPreprocessRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let intent = parse_transfer_intent(&request.operations)?;
+    let memo = match request.metadata {
+        Some(ref metadata) => match metadata.get("memo") {
+            Some(serde_json::Value::String(memo)) => Some(memo.clone()),
+            Some(_) => {
+                return Err(RosettaError::with_message(
+                    ERR_REQUEST,
+                    "The memo must be a string",
+                ));
+            }
+            None => None,
+        },
+        None => None,
+    };
+    let options = TransferOptions {
+        sender_address: intent.sender.to_string(),
+        fee: intent.fee.map(|fee| fee.to_string()),
+        memo,
+    };
+    Ok(json!({
+        "options": options,
+        "required_public_keys": [{ "address": intent.sender.to_string() }],
+    }))
+}
+
+fn construction_metadata(
+    sortdb: &SortitionDB,
+    chainstate: &mut StacksChainState,
+    request: &ConstructionMetadataRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let sender = StacksAddress::from_string(&request.options.sender_address)
+        .ok_or_else(|| RosettaError::new(ERR_ACCOUNT))?;
+    let fee = match request.options.fee {
+        Some(ref fee) => fee
+            .parse::<u64>()
+            .map_err(|_| RosettaError::with_message(ERR_REQUEST, "Invalid fee"))?,
+        None => TOKEN_TRANSFER_TX_LEN * MINIMUM_TX_FEE_RATE_PER_BYTE,
+    };
+    let tip = canonical_tip(sortdb, chainstate)?;
+    let (_, _, _, nonce) =
+        read_account(sortdb, chainstate, &tip.index_block_hash(), &sender.into())?;
+
+    let metadata = TransferMetadata {
+        nonce,
+        fee: fee.to_string(),
+        memo: request.options.memo.clone(),
+    };
+    Ok(json!({
+        "metadata": metadata,
+        "suggested_fee": [stx_amount(fee as i128)],
+    }))
+}
+
+/// The hash the origin signs to authorize an unsigned, single-signature transaction
+fn origin_sighash(tx: &StacksTransaction) -> Txid {
+    let mut initial = tx.clone();
+    initial.auth = initial.auth.into_initial_sighash_auth();
+    TransactionSpendingCondition::make_sighash_presign(
+        &initial.txid(),
+        &TransactionAuthFlags::AuthStandard,
+        tx.get_tx_fee(),
+        tx.get_origin_nonce(),
+    )
+}
+
+fn construction_payloads(
+    mainnet: bool,
+    chain_id: u32,
+    request: &ConstructionPayloadsRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let intent = parse_transfer_intent(&request.operations)?;
+    let metadata = request.metadata.as_ref().ok_or_else(|| {
+        RosettaError::with_message(ERR_REQUEST, "Missing metadata from /construction/metadata")
+    })?;
+    let fee = match intent.fee {
+        Some(fee) => fee,
+        None => metadata
+            .fee
+            .parse()
+            .map_err(|_| RosettaError::with_message(ERR_REQUEST, "Invalid fee"))?,
+    };
+    let memo = match metadata.memo {
+        Some(ref memo) if memo.len() > 34 => {
+            return Err(RosettaError::with_message(
+                ERR_REQUEST,
+                "Memos are at most 34 bytes",
+            ));
+        }
+        Some(ref memo) => {
+            let mut bytes = [0u8; 34];
+            bytes[..memo.len()].copy_from_slice(memo.as_bytes());
+            TokenTransferMemo(bytes)
+        }
+        None => TokenTransferMemo([0u8; 34]),
+    };
+
+    // the sender's key is the one that hashes to the sender's address
+    let mut sender_key = None;
+    for key in request.public_keys.iter() {
+        let key = parse_public_key(key)?;
+        if public_key_address(mainnet, &key).as_ref() == Some(&intent.sender) {
+            sender_key = Some(key);
+        }
+    }
+    let sender_key = sender_key.ok_or_else(|| {
+        RosettaError::with_message(ERR_PUBLIC_KEY, "No public key for the sender")
+    })?;
+    let origin = TransactionSpendingCondition::new_singlesig_p2pkh(sender_key)
+        .ok_or_else(|| RosettaError::new(ERR_PUBLIC_KEY))?;
+
+    let mut tx = StacksTransaction::new(
+        if mainnet {
+            TransactionVersion::Mainnet
+        } else {
+            TransactionVersion::Testnet
+        },
+        TransactionAuth::Standard(origin),
+        TransactionPayload::TokenTransfer(intent.recipient, intent.amount, memo),
+    );
+    tx.chain_id = chain_id;
+    tx.post_condition_mode = TransactionPostConditionMode::Allow;
+    tx.set_tx_fee(fee);
+    tx.set_origin_nonce(metadata.nonce);
+
+    let payload = SigningPayload {
+        account_identifier: Some(AccountIdentifier {
+            address: intent.sender.to_string(),
+            sub_account: None,
+        }),
+        hex_bytes: to_hex(origin_sighash(&tx).as_bytes()),
+        signature_type: Some("ecdsa_recovery".to_string()),
+    };
+    Ok(json!({
+        "unsigned_transaction": encode_transaction(&tx),
+        "payloads": [payload],
+    }))
+}
+
+fn construction_combine(
+    mainnet: bool,
+    request: &ConstructionCombineRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let mut tx = decode_transaction(&request.unsigned_transaction)?;
+    let signature = match request.signatures.as_slice() {
+        [signature] => signature,
+        _ => {
+            return Err(RosettaError::with_message(
+                ERR_SIGNATURE,
+                "Expected exactly one signature",
+            ));
+        }
+    };
+    if signature.signature_type != "ecdsa_recovery" {
+        return Err(RosettaError::with_message(
+            ERR_SIGNATURE,
+            "Only ecdsa_recovery signatures are supported",
+        ));
+    }
+    let key = parse_public_key(&signature.public_key)?;
+    if public_key_address(mainnet, &key) != Some(tx.origin_address()) {
+        return Err(RosettaError::with_message(
+            ERR_SIGNATURE,
+            "The signer is not the transaction's origin",
+        ));
+    }
+
+    // Rosetta recoverable signatures are r || s || v; Stacks signatures are v || r || s
+    let bytes = hex_bytes(strip_hex_prefix(&signature.hex_bytes))
+        .map_err(|_| RosettaError::new(ERR_SIGNATURE))?;
+    if bytes.len() != 65 {
+        return Err(RosettaError::with_message(
+            ERR_SIGNATURE,
+            "Expected a 65-byte signature",
+        ));
+    }
+    let mut sig_bytes = [0u8; 65];
+    sig_bytes[0] = bytes[64];
+    sig_bytes[1..].copy_from_slice(&bytes[..64]);
+
+    match tx.auth {
+        TransactionAuth::Standard(TransactionSpendingCondition::Singlesig(ref mut origin)) => {
+            origin.set_signature(MessageSignature(sig_bytes));
+        }
+        _ => {
+            return Err(RosettaError::with_message(
+                ERR_TRANSACTION,
+                "Only single-signature transactions are supported",
+            ));
+        }
+    }
+    tx.verify()
+        .map_err(|e| RosettaError::with_message(ERR_SIGNATURE, &format!("{:?}", &e)))?;
+
+    Ok(json!({ "signed_transaction": encode_transaction(&tx) }))
+}
+
+fn construction_parse(
+    request: &ConstructionParseRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let tx = decode_transaction(&request.transaction)?;
+    let signers = if request.signed {
+        vec![AccountIdentifier {
+            address: tx.origin_address().to_string(),
+            sub_account: None,
+        }]
+    } else {
+        vec![]
+    };
+    Ok(json!({
+        "operations": transaction_operations(&tx, false),
+        "account_identifier_signers": signers,
+        "metadata": {
+            "fee": tx.get_tx_fee().to_string(),
+            "nonce": tx.get_origin_nonce(),
+        },
+    }))
+}
+
+fn construction_hash(
+    request: &SignedTransactionRequest,
+) -> Result<serde_json::Value, RosettaError> {
+    let tx = decode_transaction(&request.signed_transaction)?;
+    Ok(json!({ "transaction_identifier": { "hash": format!("0x{}", &tx.txid()) } }))
+}
+
+/// Submit a signed transaction to the mempool.  Returns the transaction if it is new and should
+/// be forwarded to peers.
+fn construction_submit(
+    sortdb: &SortitionDB,
+    chainstate: &mut StacksChainState,
+    mempool: &mut MemPoolDB,
+    event_observer: Option<&dyn MemPoolEventDispatcher>,
+    request: &SignedTransactionRequest,
+) -> Result<(serde_json::Value, Option<StacksTransaction>), RosettaError> {
+    let tx = decode_transaction(&request.signed_transaction)?;
+    let txid = tx.txid();
+    let response = json!({ "transaction_identifier": { "hash": format!("0x{}", &txid) } });
+    if mempool.has_tx(&txid) {
+        return Ok((response, None));
+    }
+
+    let tip = canonical_tip(sortdb, chainstate)?;
+    let burn_tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+        .map_err(|e| RosettaError::with_message(ERR_INTERNAL, &format!("{:?}", &e)))?;
+    let stacks_epoch = sortdb
+        .index_conn()
+        .get_stacks_epoch(burn_tip.block_height as u32)
+        .ok_or_else(|| {
+            RosettaError::with_message(ERR_INTERNAL, "Could not load the current Stacks epoch")
+        })?;
+
+    match mempool.submit(
+        chainstate,
+        &tip.consensus_hash,
+        &tip.anchored_header.block_hash(),
+        &tx,
+        event_observer,
+        &stacks_epoch.block_limit,
+        &stacks_epoch.epoch_id,
+    ) {
+        Ok(_) => {
+            debug!("Mempool accepted Rosetta transaction {}", &txid);
+            Ok((response, Some(tx)))
+        }
+        Err(e) => {
+            debug!("Mempool rejected Rosetta transaction {}: {:?}", &txid, &e);
+            let mut error = RosettaError::new(ERR_REJECTED);
+            error.details = Some(e.into_json(&txid));
+            Err(error)
+        }
+    }
+}
+
+/// Answer a Rosetta request.  Returns the response body, and the transaction to forward to peers
+/// if one was submitted.
+pub fn handle_rosetta_request(
+    endpoint: RosettaEndpoint,
+    body: &serde_json::Value,
+    node: &RosettaNodeInfo,
+    sortdb: &SortitionDB,
+    chainstate: &mut StacksChainState,
+    mempool: &mut MemPoolDB,
+    event_observer: Option<&dyn MemPoolEventDispatcher>,
+) -> Result<(serde_json::Value, Option<StacksTransaction>), RosettaError> {
+    let mainnet = chainstate.mainnet;
+    if endpoint == RosettaEndpoint::NetworkList {
+        let response = json!({ "network_identifiers": [network_identifier(mainnet)] });
+        return Ok((response, None));
+    }
+    let network: NetworkRequest = parse_body(body)?;
+    check_network(mainnet, &network.network_identifier)?;
+
+    let response = match endpoint {
+        RosettaEndpoint::NetworkList => unreachable!(),
+        RosettaEndpoint::NetworkOptions => network_options(node),
+        RosettaEndpoint::NetworkStatus => network_status(node, sortdb, chainstate)?,
+        RosettaEndpoint::Block => {
+            let request: BlockRequest = parse_body(body)?;
+            get_block(sortdb, chainstate, &request.block_identifier)?
+        }
+        RosettaEndpoint::BlockTransaction => {
+            get_block_transaction(sortdb, chainstate, &parse_body(body)?)?
+        }
+        RosettaEndpoint::AccountBalance => {
+            get_account_balance(sortdb, chainstate, &parse_body(body)?)?
+        }
+        RosettaEndpoint::Mempool => get_mempool(mempool)?,
+        RosettaEndpoint::MempoolTransaction => {
+            get_mempool_transaction(mempool, &parse_body(body)?)?
+        }
+        RosettaEndpoint::ConstructionDerive => construction_derive(mainnet, &parse_body(body)?)?,
+        RosettaEndpoint::ConstructionPreprocess => construction_preprocess(&parse_body(body)?)?,
+        RosettaEndpoint::ConstructionMetadata => {
+            construction_metadata(sortdb, chainstate, &parse_body(body)?)?
+        }
+        RosettaEndpoint::ConstructionPayloads => {
+            construction_payloads(mainnet, chainstate.chain_id, &parse_body(body)?)?
+        }
+        RosettaEndpoint::ConstructionCombine => construction_combine(mainnet, &parse_body(body)?)?,
+        RosettaEndpoint::ConstructionParse => construction_parse(&parse_body(body)?)?,
+        RosettaEndpoint::ConstructionHash => construction_hash(&parse_body(body)?)?,
+        RosettaEndpoint::ConstructionSubmit => {
+            return construction_submit(
+                sortdb,
+                chainstate,
+                mempool,
+                event_observer,
+                &parse_body(body)?,
+            );
+        }
+    };
+    Ok((response, None))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use burnchains::{PrivateKey, PublicKey as PublicKeyTrait};
+    use chainstate::stacks::StacksPrivateKey;
+    use util::secp256k1::Secp256k1PublicKey;
+
+    fn transfer_operations(sender: &str, recipient: &str, amount: i128) -> serde_json::Value {
+        json!([
+            {
+                "operation_identifier": { "index": 0 },
+                "type": "token_transfer",
+                "account": { "address": sender },
+                "amount": { "value": format!("{}", -amount), "currency": stx_currency() },
+            },
+            {
+                "operation_identifier": { "index": 1 },
+                "type": "token_transfer",
+                "account": { "address": recipient },
+                "amount": { "value": format!("{}", amount), "currency": stx_currency() },
+            },
+        ])
+    }
+
+    #[test]
+    fn test_endpoint_names() {
+        for endpoint in RosettaEndpoint::ALL.iter() {
+            assert_eq!(RosettaEndpoint::from_name(endpoint.name()), Some(*endpoint));
+            assert!(endpoint.path().starts_with("/rosetta/v1/"));
+        }
+        assert_eq!(RosettaEndpoint::from_name("network"), None);
+        assert_eq!(RosettaEndpoint::from_name("construction/sign"), None);
+    }
+
+    #[test]
+    fn test_parse_transfer_intent() {
+        let sender = "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R";
+        let recipient = "ST1SJ3DTE5DN7X54YDH5D64R3BCB6A2AG2ZQ8YPD5";
+
+        let operations: Vec<Operation> =
+            serde_json::from_value(transfer_operations(sender, recipient, 123)).unwrap();
+        let intent = parse_transfer_intent(&operations).unwrap();
+        assert_eq!(intent.sender.to_string(), sender);
+        assert_eq!(intent.recipient.to_string(), recipient);
+        assert_eq!(intent.amount, 123);
+        assert_eq!(intent.fee, None);
+
+        // a fee paid by the sender is accepted
+        let mut with_fee = operations.clone();
+        with_fee.push(Operation {
+            operation_identifier: OperationIdentifier { index: 2 },
+            related_operations: None,
+            op_type: "fee".to_string(),
+            status: None,
+            account: operations[0].account.clone(),
+            amount: Some(stx_amount(-180)),
+            metadata: None,
+        });
+        assert_eq!(parse_transfer_intent(&with_fee).unwrap().fee, Some(180));
+
+        // ...but not one paid by someone else
+        with_fee[2].account = operations[1].account.clone();
+        assert_eq!(
+            parse_transfer_intent(&with_fee).unwrap_err().code,
+            ERR_OPERATIONS
+        );
+
+        // debit and credit must match
+        let mut mismatched = operations.clone();
+        mismatched[1].amount = Some(stx_amount(124));
+        assert_eq!(
+            parse_transfer_intent(&mismatched).unwrap_err().code,
+            ERR_OPERATIONS
+        );
+
+        // a transfer needs both sides
+        assert_eq!(
+            parse_transfer_intent(&operations[..1]).unwrap_err().code,
+            ERR_OPERATIONS
+        );
+
+        // only STX can be moved
+        let mut other_currency = operations.clone();
+        for op in other_currency.iter_mut() {
+            op.amount.as_mut().unwrap().currency.symbol = "BTC".to_string();
+        }
+        assert_eq!(
+            parse_transfer_intent(&other_currency).unwrap_err().code,
+            ERR_OPERATIONS
+        );
+
+        // other operation types are rejected
+        let mut other_type = operations.clone();
+        other_type[0].op_type = "contract_call".to_string();
+        assert_eq!(
+            parse_transfer_intent(&other_type).unwrap_err().code,
+            ERR_OPERATIONS
+        );
+
+        // bad addresses are rejected
+        let mut bad_sender = operations.clone();
+        bad_sender[0].account.as_mut().unwrap().address = "not-an-address".to_string();
+        assert_eq!(
+            parse_transfer_intent(&bad_sender).unwrap_err().code,
+            ERR_ACCOUNT
+        );
+    }
+
+    #[test]
+    fn test_construction_flow() {
+        let network = network_identifier(false);
+        let chain_id = 0x80000000;
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let pubk = Secp256k1PublicKey::from_private(&privk);
+        let public_key =
+            json!({ "hex_bytes": to_hex(&pubk.to_bytes()), "curve_type": "secp256k1" });
+        let recipient = "ST1SJ3DTE5DN7X54YDH5D64R3BCB6A2AG2ZQ8YPD5";
+
+        // derive
+        let derived = construction_derive(
+            false,
+            &parse_body(&json!({ "network_identifier": network, "public_key": public_key }))
+                .unwrap(),
+        )
+        .unwrap();
+        let sender = derived["account_identifier"]["address"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            sender,
+            public_key_address(false, &pubk).unwrap().to_string()
+        );
+        assert!(sender.starts_with("ST"));
+
+        // preprocess
+        let operations = transfer_operations(&sender, recipient, 1000);
+        let preprocessed = construction_preprocess(
+            &parse_body(&json!({
+                "network_identifier": network,
+                "operations": operations,
+                "metadata": { "memo": "hello" },
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let options: TransferOptions =
+            serde_json::from_value(preprocessed["options"].clone()).unwrap();
+        assert_eq!(options.sender_address, sender);
+        assert_eq!(options.memo, Some("hello".to_string()));
+        assert_eq!(
+            preprocessed["required_public_keys"][0]["address"],
+            json!(sender)
+        );
+
+        // payloads, with the metadata /construction/metadata would have produced
+        let metadata = TransferMetadata {
+            nonce: 7,
+            fee: "180".to_string(),
+            memo: options.memo.clone(),
+        };
+        let payloads = construction_payloads(
+            false,
+            chain_id,
+            &parse_body(&json!({
+                "network_identifier": network,
+                "operations": operations,
+                "metadata": metadata,
+                "public_keys": [public_key],
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let unsigned_tx = payloads["unsigned_transaction"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let payload: SigningPayload =
+            serde_json::from_value(payloads["payloads"][0].clone()).unwrap();
+        assert_eq!(payload.signature_type, Some("ecdsa_recovery".to_string()));
+
+        // the unsigned transaction parses back to the same operations
+        let parsed = construction_parse(
+            &parse_body(&json!({
+                "network_identifier": network,
+                "signed": false,
+                "transaction": unsigned_tx,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let parsed_ops: Vec<Operation> =
+            serde_json::from_value(parsed["operations"].clone()).unwrap();
+        assert_eq!(parsed_ops.len(), 2);
+        assert_eq!(parsed_ops[0].amount, Some(stx_amount(-1000)));
+        assert_eq!(parsed_ops[1].amount, Some(stx_amount(1000)));
+        assert_eq!(parsed["metadata"]["nonce"], json!(7));
+        assert_eq!(parsed["metadata"]["fee"], json!("180"));
+        assert!(parsed["account_identifier_signers"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+
+        // sign the payload, and hand the signature over as r || s || v
+        let sig = privk.sign(&hex_bytes(&payload.hex_bytes).unwrap()).unwrap();
+        let mut rosetta_sig = sig.0[1..].to_vec();
+        rosetta_sig.push(sig.0[0]);
+        let signature = json!({
+            "signing_payload": payload,
+            "public_key": public_key,
+            "signature_type": "ecdsa_recovery",
+            "hex_bytes": to_hex(&rosetta_sig),
+        });
+
+        // a truncated signature is rejected
+        let mut truncated = signature.clone();
+        truncated["hex_bytes"] = json!(to_hex(&rosetta_sig[..64]));
+        let err = construction_combine(
+            false,
+            &parse_body(&json!({
+                "network_identifier": network,
+                "unsigned_transaction": unsigned_tx,
+                "signatures": [truncated],
+            }))
+            .unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ERR_SIGNATURE);
+
+        // combine
+        let combined = construction_combine(
+            false,
+            &parse_body(&json!({
+                "network_identifier": network,
+                "unsigned_transaction": unsigned_tx,
+                "signatures": [signature],
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let signed_tx = combined["signed_transaction"].as_str().unwrap().to_string();
+        let tx = decode_transaction(&signed_tx).unwrap();
+        tx.verify().unwrap();
+        assert_eq!(tx.chain_id, chain_id);
+        assert_eq!(tx.get_tx_fee(), 180);
+        assert_eq!(tx.get_origin_nonce(), 7);
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref to, amount, ref memo) => {
+                assert_eq!(to.to_string(), recipient);
+                assert_eq!(amount, 1000);
+                assert_eq!(&memo.0[..5], b"hello");
+            }
+            _ => panic!("Not a token transfer"),
+        }
+
+        // the signed transaction names its signer
+        let parsed = construction_parse(
+            &parse_body(&json!({
+                "network_identifier": network,
+                "signed": true,
+                "transaction": signed_tx,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            parsed["account_identifier_signers"][0]["address"],
+            json!(sender)
+        );
+
+        // hash
+        let hashed = construction_hash(
+            &parse_body(&json!({
+                "network_identifier": network,
+                "signed_transaction": signed_tx,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            hashed["transaction_identifier"]["hash"],
+            json!(format!("0x{}", &tx.txid()))
+        );
+    }
+
+    #[test]
+    fn test_check_network() {
+        assert!(check_network(true, &network_identifier(true)).is_ok());
+        assert!(check_network(false, &network_identifier(false)).is_ok());
+        assert_eq!(
+            check_network(true, &network_identifier(false))
+                .unwrap_err()
+                .code,
+            ERR_NETWORK
+        );
+    }
+}
@@ -17,6 +17,7 @@
  along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
@@ -32,9 +33,11 @@ use rand::thread_rng;
 use rusqlite::{DatabaseName, NO_PARAMS};
 
 use crate::codec::StacksMessageCodec;
+use crate::cost_estimates::dimension_proportions;
 use crate::cost_estimates::metrics::CostMetric;
 use crate::cost_estimates::CostEstimator;
 use crate::cost_estimates::FeeEstimator;
+use crate::net::FeeRateEstimateSubject;
 use crate::net::RPCFeeEstimate;
 use crate::net::RPCFeeEstimateResponse;
 use burnchains::Burnchain;
@@ -42,24 +45,34 @@ use burnchains::BurnchainView;
 use burnchains::*;
 use chainstate::burn::db::sortdb::SortitionDB;
 use chainstate::burn::ConsensusHash;
-use chainstate::stacks::db::blocks::CheckError;
+use chainstate::stacks::db::blocks::{CheckError, MemPoolRejection};
+use chainstate::stacks::db::contracts::ReadOnlyCall;
 use chainstate::stacks::db::{
-    blocks::MINIMUM_TX_FEE_RATE_PER_BYTE, StacksChainState, StreamCursor,
+    blocks::MINIMUM_TX_FEE, blocks::MINIMUM_TX_FEE_RATE_PER_BYTE, StacksChainState, StreamCursor,
 };
+use chainstate::stacks::events::StacksTransactionEvent;
+use chainstate::stacks::postconditions;
 use chainstate::stacks::Error as chain_error;
 use chainstate::stacks::*;
 use clarity_vm::clarity::ClarityConnection;
 use core::mempool::*;
 use monitoring;
 use net::atlas::{AtlasDB, Attachment, MAX_ATTACHMENT_INV_PAGES_PER_REQUEST};
+use net::chat::NeighborStats;
 use net::connection::ConnectionHttp;
 use net::connection::ConnectionOptions;
 use net::connection::ReplyHandleHttp;
 use net::db::PeerDB;
 use net::http::*;
+use net::inv::{NeighborBlockStats, NodeStatus};
+use net::neighbors::NeighborWalkResult;
 use net::p2p::PeerMap;
 use net::p2p::PeerNetwork;
+use net::ratelimit::{api_key_matches, RPCRefusal};
 use net::relay::Relayer;
+use net::response_cache::ResponseCache;
+use net::rosetta::{handle_rosetta_request, RosettaEndpoint, RosettaNodeInfo};
+use net::ws::{websocket_accept_key, WebSocketSession};
 use net::Error as net_error;
 use net::HttpRequestMetadata;
 use net::HttpRequestType;
@@ -68,9 +81,11 @@ use net::HttpResponseType;
 use net::MemPoolSyncData;
 use net::MicroblocksData;
 use net::NeighborAddress;
+use net::NeighborKey;
 use net::NeighborsData;
 use net::PeerAddress;
 use net::PeerHost;
+use net::PostConditionCheckResponse;
 use net::ProtocolFamily;
 use net::StacksHttp;
 use net::StacksHttpMessage;
@@ -79,23 +94,63 @@ use net::UnconfirmedTransactionResponse;
 use net::UnconfirmedTransactionStatus;
 use net::UrlString;
 use net::HTTP_REQUEST_ID_RESERVED;
+use net::MAX_BLOCK_EXPORT;
+use net::MAX_CONTRACT_EVENTS;
 use net::MAX_HEADERS;
 use net::MAX_NEIGHBORS_DATA_LEN;
 use net::{
-    AccountEntryResponse, AttachmentPage, CallReadOnlyResponse, ContractSrcResponse,
-    DataVarResponse, GetAttachmentResponse, GetAttachmentsInvResponse, MapEntryResponse,
+    AccountEntryResponse, ArgumentMismatchEntry, AttachmentPage, CallReadOnlyBatchResponse,
+    CallReadOnlyResponse, CheckContractCallArgsResponse, ContractSrcResponse, DataVarResponse,
+    GetAttachmentResponse, GetAttachmentsInvResponse, MapEntryResponse,
+};
+use net::{AddressAssetsResponse, FungibleTokenHoldingEntry, NonFungibleTokenHoldingEntry};
+use net::{
+    AddressTransactionEntry, AddressTransactionsQuery, AddressTransactionsResponse,
+    MAX_ADDRESS_TRANSACTIONS,
 };
 use net::{BlocksData, GetIsTraitImplementedResponse};
 use net::{ClientError, TipRequest};
-use net::{RPCNeighbor, RPCNeighborsInfo};
+use net::{ContractEventEntry, ContractEventsQuery, ContractEventsResponse};
+use net::{ContractMetricsEntry, ContractMetricsQuery, ContractMetricsResponse};
+use net::{MapEntryBatchResponse, MapEntryBatchResult, MapEntryLookup};
+use net::{
+    MemPoolTransactionEntry, MemPoolTransactionsQuery, MemPoolTransactionsResponse,
+    MAX_MEMPOOL_TRANSACTIONS,
+};
+use net::{PeerPolicyUpdate, RPCPeerPolicyData};
+use net::{RPCBlockStateDiffData, RPCStateDiffEntry};
+use net::{
+    RPCDelegationState, RPCRewardCycleData, RPCRewardSetData, RPCRewardSetEntry, RPCStackerData,
+    RPCStackingState,
+};
+use net::{RPCDeniedNeighbor, RPCNeighborDetail, RPCNeighborInvData, RPCNeighborWalkData};
+use net::{
+    RPCHealthBurnchainCheck, RPCHealthLiveData, RPCHealthPeersCheck, RPCHealthReadyData,
+    RPCHealthStacksTipCheck,
+};
+use net::{RPCMicroblockEntry, RPCMicroblockStreamData, MAX_MICROBLOCK_STREAM_PAGE};
+use net::{RPCNameData, RPCPrincipalNamesData};
+use net::{
+    RPCNeighbor, RPCNeighborQuality, RPCNeighborWalkStatus, RPCNeighborsDetailData,
+    RPCNeighborsInfo,
+};
 use net::{RPCPeerInfoData, RPCPoxInfoData};
+use net::{RPCTelemetryData, RPCTelemetryStats};
+use net::{
+    RPCTransactionAdmissionReport, RPCTransactionFeeCheck, RPCTransactionNonceCheck,
+    RPCTransactionPostConditionCheck,
+};
+use net::{MAX_CONTRACT_METRICS, MAX_CONTRACT_METRICS_BLOCKS};
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::Hash160;
 use util::hash::{hex_bytes, to_hex};
+use util::secp256k1::{MessageSignature, Secp256k1PrivateKey, Secp256k1PublicKey};
+use vm::contexts::AssetMap;
 use vm::database::clarity_store::make_contract_hash_key;
-use vm::types::TraitIdentifier;
+use vm::types::{TraitIdentifier, TypeSignature};
 use vm::{
     analysis::errors::CheckErrors,
     costs::{ExecutionCost, LimitedCostTracker},
@@ -132,6 +187,11 @@ pub struct RPCHandlerArgs<'a> {
     pub cost_estimator: Option<&'a dyn CostEstimator>,
     pub fee_estimator: Option<&'a dyn FeeEstimator>,
     pub cost_metric: Option<&'a dyn CostMetric>,
+    /// key used to sign telemetry beacons.  If not set, telemetry is disabled.
+    pub telemetry_key: Option<&'a Secp256k1PrivateKey>,
+    /// height of the burnchain tip as reported by the burnchain node, if known.  /v2/health/ready
+    /// compares the node's burnchain view against it.
+    pub burnchain_target_height: Option<u64>,
 }
 
 pub struct ConversationHttp {
@@ -159,6 +219,20 @@ pub struct ConversationHttp {
     pending_request: Option<ReplyHandleHttp>,
     pending_response: Option<HttpResponseType>,
     pending_error_response: Option<HttpResponseType>,
+
+    // the client asked to switch to the WebSocket protocol, and we're sending the 101
+    websocket_upgrade: bool,
+    // once the upgrade completes, this conversation carries a WebSocket session
+    websocket: Option<WebSocketSession>,
+
+    // the client presented a TLS certificate signed by one of our client CAs
+    client_authenticated: bool,
+
+    // the client has as many replies outstanding as it may pipeline, so we've stopped reading
+    // its requests until it catches up
+    recv_backlogged: bool,
+    // the client sent "Connection: close", so any requests pipelined after it are ignored
+    closing: bool,
 }
 
 impl fmt::Display for ConversationHttp {
@@ -242,10 +316,76 @@ impl RPCPeerInfoData {
             unanchored_seq: unconfirmed_seq,
             exit_at_block_height: exit_at_block_height.cloned(),
             genesis_chainstate_hash: genesis_chainstate_hash.clone(),
+            recent_reorgs: network.recent_reorgs.iter().cloned().collect(),
         }
     }
 }
 
+impl RPCTelemetryData {
+    pub fn from_network(
+        network: &PeerNetwork,
+        telemetry_key: &Secp256k1PrivateKey,
+    ) -> Result<RPCTelemetryData, net_error> {
+        let server_version = version_string(
+            "stacks-node",
+            option_env!("STACKS_NODE_VERSION")
+                .or(option_env!("CARGO_PKG_VERSION"))
+                .unwrap_or("0.0.0.0"),
+        );
+
+        let stats = RPCTelemetryStats {
+            server_version,
+            network_id: network.local_peer.network_id,
+            burn_block_height: network.chain_view.burn_block_height,
+            stacks_tip_height: network.burnchain_tip.canonical_stacks_tip_height,
+            num_peers: network.peers.len() as u64,
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            timestamp: get_epoch_time_secs(),
+        };
+
+        let signature = telemetry_key
+            .sign(RPCTelemetryData::stats_hash(&stats)?.as_bytes())
+            .map_err(|e| net_error::SigningError(e.to_string()))?;
+
+        Ok(RPCTelemetryData {
+            stats,
+            public_key: Secp256k1PublicKey::from_private(telemetry_key).to_hex(),
+            signature: signature.to_hex(),
+        })
+    }
+
+    fn stats_hash(stats: &RPCTelemetryStats) -> Result<Sha256Sum, net_error> {
+        let stats_json = serde_json::to_vec(stats)
+            .map_err(|e| net_error::SerializeError(format!("{:?}", &e)))?;
+        Ok(Sha256Sum::from_data(&stats_json))
+    }
+
+    /// Check that the stats were signed by the public key in this payload
+    pub fn verify(&self) -> bool {
+        let pubk = match Secp256k1PublicKey::from_hex(&self.public_key) {
+            Ok(pubk) => pubk,
+            Err(_) => {
+                return false;
+            }
+        };
+        let signature = match MessageSignature::from_hex(&self.signature) {
+            Ok(signature) => signature,
+            Err(_) => {
+                return false;
+            }
+        };
+        let stats_hash = match RPCTelemetryData::stats_hash(&self.stats) {
+            Ok(stats_hash) => stats_hash,
+            Err(_) => {
+                return false;
+            }
+        };
+        pubk.verify(stats_hash.as_bytes(), &signature)
+            .unwrap_or(false)
+    }
+}
+
 impl RPCPoxInfoData {
     pub fn from_db(
         sortdb: &SortitionDB,
@@ -422,6 +562,233 @@ impl RPCPoxInfoData {
     }
 }
 
+/// Get the burnchain height at which the given reward cycle starts, if it's representable
+fn reward_cycle_start_height(burnchain: &Burnchain, reward_cycle: u64) -> Option<u64> {
+    reward_cycle
+        .checked_mul(burnchain.pox_constants.reward_cycle_length as u64)?
+        .checked_add(burnchain.first_block_height + 1)
+}
+
+impl RPCRewardCycleData {
+    /// Load the parameters of a reward cycle (the current one, if `reward_cycle` is None) as of
+    /// the given chain tip
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        burnchain: &Burnchain,
+        reward_cycle: Option<u64>,
+    ) -> Result<RPCRewardCycleData, net_error> {
+        let burnchain_tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        let current_cycle = burnchain
+            .block_height_to_reward_cycle(burnchain_tip.block_height)
+            .ok_or(net_error::NotFoundError)?;
+        let id = reward_cycle.unwrap_or(current_cycle);
+
+        let pox_consts = &burnchain.pox_constants;
+        let reward_phase_start_block_height =
+            reward_cycle_start_height(burnchain, id).ok_or(net_error::NotFoundError)?;
+        let end_block_height = id
+            .checked_add(1)
+            .and_then(|next_id| reward_cycle_start_height(burnchain, next_id))
+            .ok_or(net_error::NotFoundError)?
+            - 1;
+        let prepare_phase_start_block_height =
+            end_block_height + 1 - (pox_consts.prepare_length as u64);
+
+        let stacked_ustx = chainstate.get_total_ustx_stacked(sortdb, tip, id as u128)?;
+        let liquid_ustx = chainstate.get_liquid_ustx(tip);
+        let reward_slots = pox_consts.reward_slots() as u64;
+        let min_threshold_ustx = StacksChainState::get_threshold_from_participation(
+            liquid_ustx,
+            stacked_ustx,
+            reward_slots as u128,
+        ) as u64;
+        let is_pox_active = chainstate.is_pox_active(sortdb, tip, id as u128)?;
+
+        Ok(RPCRewardCycleData {
+            id,
+            is_current: id == current_cycle,
+            reward_phase_start_block_height,
+            prepare_phase_start_block_height,
+            end_block_height,
+            reward_phase_block_length: (pox_consts.reward_cycle_length - pox_consts.prepare_length)
+                as u64,
+            prepare_phase_block_length: pox_consts.prepare_length as u64,
+            reward_slots,
+            stacked_ustx: stacked_ustx as u64,
+            min_threshold_ustx,
+            is_pox_active,
+        })
+    }
+}
+
+impl RPCRewardSetData {
+    /// Load the reward set of a reward cycle on the canonical burnchain fork.  The reward set is
+    /// read from the PoX contract as of the anchor block chosen in the prior cycle's prepare
+    /// phase, just as the chains coordinator reads it.  Fails with NotFoundError if that prepare
+    /// phase hasn't ended yet, or if its anchor block hasn't been processed.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<RPCRewardSetData, net_error> {
+        let cycle_start_height =
+            reward_cycle_start_height(burnchain, reward_cycle).ok_or(net_error::NotFoundError)?;
+        let prepare_end_height = cycle_start_height - 1;
+
+        let burnchain_tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        if prepare_end_height > burnchain_tip.block_height {
+            return Err(net_error::NotFoundError);
+        }
+
+        let anchor_block = if reward_cycle == 0
+            || cycle_start_height >= burnchain.pox_constants.sunset_end
+        {
+            None
+        } else {
+            let prepare_end = SortitionDB::get_ancestor_snapshot(
+                &sortdb.index_conn(),
+                prepare_end_height,
+                &burnchain_tip.sortition_id,
+            )?
+            .ok_or(net_error::NotFoundError)?;
+            sortdb
+                .index_handle(&burnchain_tip.sortition_id)
+                .get_chosen_pox_anchor(&prepare_end.burn_header_hash, &burnchain.pox_constants)
+                .map_err(|e| {
+                    net_error::ChainstateError(format!("Failed to find PoX anchor block: {:?}", &e))
+                })?
+        };
+
+        let (consensus_hash, anchor_block_hash) = match anchor_block {
+            Some(anchor_block) => anchor_block,
+            None => {
+                return Ok(RPCRewardSetData {
+                    reward_cycle,
+                    anchor_block_hash: None,
+                    anchor_index_block_hash: None,
+                    liquid_ustx: 0,
+                    stacked_ustx: 0,
+                    threshold_ustx: 0,
+                    enough_participation: false,
+                    entries: vec![],
+                });
+            }
+        };
+
+        if !StacksChainState::is_stacks_block_processed(
+            chainstate.db(),
+            &consensus_hash,
+            &anchor_block_hash,
+        )? {
+            return Err(net_error::NotFoundError);
+        }
+        let anchor_index_block_hash =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchor_block_hash);
+
+        let mut registered_addrs = chainstate.get_reward_addresses(
+            burnchain,
+            sortdb,
+            cycle_start_height,
+            &anchor_index_block_hash,
+        )?;
+        let liquid_ustx = chainstate.get_liquid_ustx(&anchor_index_block_hash);
+        let stacked_ustx = registered_addrs
+            .iter()
+            .fold(0, |total, (_, stacked_amt)| total + stacked_amt);
+        let threshold_ustx = StacksChainState::get_threshold_from_participation(
+            liquid_ustx,
+            stacked_ustx,
+            burnchain.pox_constants.reward_slots() as u128,
+        );
+        let enough_participation = burnchain
+            .pox_constants
+            .enough_participation(stacked_ustx, liquid_ustx);
+
+        // an address may have been registered more than once, so sum its amounts, as
+        // make_reward_set() does
+        registered_addrs.sort_by_key(|(address, _)| address.bytes.0);
+        let mut entries: Vec<(StacksAddress, u128)> = vec![];
+        for (address, stacked_amt) in registered_addrs.into_iter() {
+            match entries.last_mut() {
+                Some((last_address, total_ustx)) if *last_address == address => {
+                    *total_ustx += stacked_amt;
+                }
+                _ => entries.push((address, stacked_amt)),
+            }
+        }
+
+        Ok(RPCRewardSetData {
+            reward_cycle,
+            anchor_block_hash: Some(anchor_block_hash),
+            anchor_index_block_hash: Some(anchor_index_block_hash),
+            liquid_ustx: liquid_ustx as u64,
+            stacked_ustx: stacked_ustx as u64,
+            threshold_ustx: threshold_ustx as u64,
+            enough_participation,
+            entries: entries
+                .into_iter()
+                .map(|(address, total_ustx)| RPCRewardSetEntry {
+                    pox_address: address.to_b58(),
+                    total_ustx: total_ustx as u64,
+                    slots: if enough_participation {
+                        (total_ustx / threshold_ustx) as u64
+                    } else {
+                        0
+                    },
+                })
+                .collect(),
+        })
+    }
+}
+
+impl RPCStackerData {
+    /// Load a principal's locked STX, stacking state and delegation as of the given chain tip
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        stacker: &PrincipalData,
+    ) -> Result<RPCStackerData, net_error> {
+        let (locked_ustx, unlock_height) = chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let burn_block_height = clarity_db.get_current_burnchain_block_height() as u64;
+                    clarity_db
+                        .get_account_stx_balance(stacker)
+                        .get_locked_balance_at_burn_block(burn_block_height)
+                })
+            })?
+            .ok_or(net_error::NotFoundError)?;
+
+        let stacking = chainstate
+            .get_stacker_info(sortdb, tip, stacker)?
+            .map(|info| RPCStackingState {
+                amount_ustx: info.amount_ustx as u64,
+                pox_address: info.pox_address.to_b58(),
+                first_reward_cycle: info.first_reward_cycle,
+                lock_period: info.lock_period,
+            });
+        let delegation = chainstate
+            .get_delegation_info(sortdb, tip, stacker)?
+            .map(|info| RPCDelegationState {
+                amount_ustx: info.amount_ustx as u64,
+                delegated_to: info.delegated_to.to_string(),
+                until_burn_height: info.until_burn_height,
+                pox_address: info.pox_address.map(|address| address.to_b58()),
+            });
+
+        Ok(RPCStackerData {
+            locked_ustx: locked_ustx as u64,
+            unlock_height,
+            stacking,
+            delegation,
+        })
+    }
+}
+
 impl RPCNeighborsInfo {
     /// Load neighbor address information from the peer network
     pub fn from_p2p(
@@ -457,18 +824,16 @@ impl RPCNeighborsInfo {
         for (_, convo) in peers.iter() {
             let nk = convo.to_neighbor_key();
             let naddr = convo.to_neighbor_address();
+            let mut neighbor = RPCNeighbor::from_neighbor_key_and_pubkh(
+                nk,
+                naddr.public_key_hash,
+                convo.is_authenticated(),
+            );
+            neighbor.quality = Some(RPCNeighborQuality::from_stats(convo.get_stats()));
             if convo.is_outbound() {
-                outbound.push(RPCNeighbor::from_neighbor_key_and_pubkh(
-                    nk,
-                    naddr.public_key_hash,
-                    convo.is_authenticated(),
-                ));
+                outbound.push(neighbor);
             } else {
-                inbound.push(RPCNeighbor::from_neighbor_key_and_pubkh(
-                    nk,
-                    naddr.public_key_hash,
-                    convo.is_authenticated(),
-                ));
+                inbound.push(neighbor);
             }
         }
 
@@ -480,6 +845,149 @@ impl RPCNeighborsInfo {
     }
 }
 
+impl RPCNeighborWalkData {
+    /// Summarize a finished neighbor walk
+    pub fn from_walk_result(walk_result: &NeighborWalkResult, now: u64) -> RPCNeighborWalkData {
+        let addrs = |nks: &HashSet<NeighborKey>| -> Vec<String> {
+            let mut addrs: Vec<String> = nks
+                .iter()
+                .map(|nk| format!("{}", nk.addrbytes.to_socketaddr(nk.port)))
+                .collect();
+            addrs.sort();
+            addrs
+        };
+        RPCNeighborWalkData {
+            finished_at: now,
+            new_connections: addrs(&walk_result.new_connections),
+            dead_connections: addrs(&walk_result.dead_connections),
+            broken_connections: addrs(&walk_result.broken_connections),
+            replaced_neighbors: addrs(&walk_result.replaced_neighbors),
+        }
+    }
+}
+
+impl RPCNeighborQuality {
+    pub fn from_stats(stats: &NeighborStats) -> RPCNeighborQuality {
+        RPCNeighborQuality {
+            rtt_ms: stats.rtt_ms.map(|rtt| rtt.round() as u64),
+            request_success_rate: stats.get_request_success_rate(),
+            bytes_tx: stats.bytes_tx,
+            bytes_rx: stats.bytes_rx,
+        }
+    }
+}
+
+impl RPCNeighborInvData {
+    pub fn from_block_stats(stats: &NeighborBlockStats) -> RPCNeighborInvData {
+        let status = match stats.status {
+            NodeStatus::Online => "online",
+            NodeStatus::Broken => "broken",
+            NodeStatus::Diverged => "diverged",
+            NodeStatus::Stale => "stale",
+            NodeStatus::Dead => "dead",
+        };
+        RPCNeighborInvData {
+            status: status.to_string(),
+            num_sortitions: stats.inv.num_sortitions,
+            num_reward_cycles: stats.inv.num_reward_cycles,
+            last_updated_at: stats.inv.last_updated_at,
+            scans: stats.scans,
+            done: stats.done,
+        }
+    }
+}
+
+impl RPCNeighborsDetailData {
+    /// Describe our neighbors, the peers we won't talk to, and the neighbor walk
+    pub fn from_p2p(network: &PeerNetwork) -> Result<RPCNeighborsDetailData, net_error> {
+        let mut neighbors = vec![];
+        for (_, convo) in network.peers.iter() {
+            let nk = convo.to_neighbor_key();
+            let stats = convo.get_stats();
+            let inv = network
+                .inv_state
+                .as_ref()
+                .and_then(|inv_state| inv_state.get_stats(&nk))
+                .map(RPCNeighborInvData::from_block_stats);
+            neighbors.push(RPCNeighborDetail {
+                neighbor: RPCNeighbor::from_neighbor_key_and_pubkh(
+                    nk.clone(),
+                    convo.to_neighbor_address().public_key_hash,
+                    convo.is_authenticated(),
+                ),
+                outbound: convo.is_outbound(),
+                services: convo.peer_services,
+                first_contact_time: stats.first_contact_time,
+                last_contact_time: stats.last_contact_time,
+                last_send_time: stats.last_send_time,
+                last_recv_time: stats.last_recv_time,
+                last_handshake_time: stats.last_handshake_time,
+                bytes_tx: stats.bytes_tx,
+                bytes_rx: stats.bytes_rx,
+                msgs_tx: stats.msgs_tx,
+                msgs_rx: stats.msgs_rx,
+                msgs_rx_unsolicited: stats.msgs_rx_unsolicited,
+                msgs_err: stats.msgs_err,
+                health_score: stats.get_health_score(),
+                rtt_ms: stats.rtt_ms.map(|rtt| rtt.round() as u64),
+                replies_rx: stats.replies_rx,
+                requests_timed_out: stats.requests_timed_out,
+                reputation: network.get_peer_reputation(&nk),
+                inv,
+            });
+        }
+
+        let denied =
+            PeerDB::get_denied_peers(network.peerdb.conn(), network.local_peer.network_id)?
+                .into_iter()
+                .map(|neighbor| RPCDeniedNeighbor {
+                    addrbytes: neighbor.addr.addrbytes.clone(),
+                    port: neighbor.addr.port,
+                    public_key_hash: Hash160::from_node_public_key(&neighbor.public_key),
+                    denied_until: if neighbor.denied < 0 || neighbor.denied == i64::MAX {
+                        None
+                    } else {
+                        Some(neighbor.denied as u64)
+                    },
+                })
+                .collect();
+
+        Ok(RPCNeighborsDetailData {
+            neighbors,
+            denied,
+            walk: RPCNeighborWalkStatus {
+                state: network
+                    .walk
+                    .as_ref()
+                    .map(|walk| format!("{:?}", &walk.state)),
+                walk_count: network.walk_count,
+                walk_attempts: network.walk_attempts,
+                walk_total_step_count: network.walk_total_step_count,
+                recent_walks: network.recent_walks.iter().cloned().collect(),
+            },
+        })
+    }
+}
+
+impl RPCPeerPolicyData {
+    /// Load the CIDR and AS-number allow/deny rules from the peer DB
+    pub fn from_peerdb(peerdb: &PeerDB) -> Result<RPCPeerPolicyData, net_error> {
+        let conn = peerdb.conn();
+        let cidr_strings = |cidrs: Vec<(PeerAddress, u32)>| -> Vec<String> {
+            cidrs
+                .into_iter()
+                .map(|(prefix, mask)| prefix.to_cidr_string(mask))
+                .collect()
+        };
+        Ok(RPCPeerPolicyData {
+            allow_cidrs: cidr_strings(PeerDB::get_allowed_cidrs(conn)?),
+            deny_cidrs: cidr_strings(PeerDB::get_denied_cidrs(conn)?),
+            allow_asns: PeerDB::get_allowed_asns(conn)?,
+            deny_asns: PeerDB::get_denied_asns(conn)?,
+        })
+    }
+}
+
 impl ConversationHttp {
     pub fn new(
         peer_addr: SocketAddr,
@@ -490,8 +998,10 @@ impl ConversationHttp {
     ) -> ConversationHttp {
         let mut stacks_http = StacksHttp::new(peer_addr.clone());
         stacks_http.maximum_call_argument_size = conn_opts.maximum_call_argument_size;
+        let mut connection = ConnectionHttp::new(stacks_http, conn_opts, None);
+        connection.set_inbox_backpressure(conn_opts.max_http_pipelined_requests as usize);
         ConversationHttp {
-            connection: ConnectionHttp::new(stacks_http, conn_opts, None),
+            connection,
             conn_id: conn_id,
             timeout: conn_opts.timeout,
             reply_streams: VecDeque::new(),
@@ -501,6 +1011,11 @@ impl ConversationHttp {
             pending_request: None,
             pending_response: None,
             pending_error_response: None,
+            websocket_upgrade: false,
+            websocket: None,
+            client_authenticated: false,
+            recv_backlogged: false,
+            closing: false,
             keep_alive: true,
             total_request_count: 0,
             total_reply_count: 0,
@@ -627,6 +1142,95 @@ impl ConversationHttp {
         response.send(http, fd)
     }
 
+    /// Handle a GET for the node's liveness.  If the node can answer at all, it's live.
+    fn handle_get_health_live<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let response = HttpResponseType::HealthLive(
+            response_metadata,
+            RPCHealthLiveData {
+                status: "ok".to_string(),
+            },
+        );
+        response.send(http, fd)
+    }
+
+    /// Handle a GET for the node's readiness to serve traffic.  The node is ready once its
+    /// burnchain view is within `health_max_burnchain_lag` blocks of the burnchain tip, its Stacks
+    /// chain tip's burn block is no older than `health_max_tip_age` seconds, and it has at least
+    /// `health_min_peers` authenticated p2p peers.
+    fn handle_get_health_ready<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        network: &PeerNetwork,
+        chainstate: &StacksChainState,
+        options: &ConnectionOptions,
+        burnchain_target_height: Option<u64>,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let burnchain_height = network.burnchain_tip.block_height;
+        let burnchain = RPCHealthBurnchainCheck {
+            ready: burnchain_target_height
+                .map(|target| {
+                    target.saturating_sub(burnchain_height) <= options.health_max_burnchain_lag
+                })
+                .unwrap_or(false),
+            height: burnchain_height,
+            target_height: burnchain_target_height,
+            max_lag: options.health_max_burnchain_lag,
+        };
+
+        let tip_age = match StacksChainState::get_anchored_block_header_info(
+            chainstate.db(),
+            &network.burnchain_tip.canonical_stacks_tip_consensus_hash,
+            &network.burnchain_tip.canonical_stacks_tip_hash,
+        ) {
+            Ok(Some(header)) => {
+                Some(get_epoch_time_secs().saturating_sub(header.burn_header_timestamp))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Failed to load canonical Stacks chain tip: {:?}", &e);
+                None
+            }
+        };
+        let stacks_tip = RPCHealthStacksTipCheck {
+            ready: tip_age
+                .map(|age| age <= options.health_max_tip_age)
+                .unwrap_or(false),
+            height: network.burnchain_tip.canonical_stacks_tip_height,
+            age: tip_age,
+            max_age: options.health_max_tip_age,
+        };
+
+        let num_peers = network
+            .peers
+            .values()
+            .filter(|convo| convo.is_authenticated())
+            .count() as u64;
+        let peers = RPCHealthPeersCheck {
+            ready: num_peers >= options.health_min_peers,
+            count: num_peers,
+            min_count: options.health_min_peers,
+        };
+
+        let response = HttpResponseType::HealthReady(
+            response_metadata,
+            RPCHealthReadyData {
+                ready: burnchain.ready && stacks_tip.ready && peers.ready,
+                burnchain,
+                stacks_tip,
+                peers,
+            },
+        );
+        response.send(http, fd)
+    }
+
     /// Handle a GET pox info.
     /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
     fn handle_getpoxinfo<W: Write>(
@@ -664,6 +1268,93 @@ impl ConversationHttp {
         }
     }
 
+    /// Handle a GET for a reward cycle's parameters
+    fn handle_get_pox_cycle<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        burnchain: &Burnchain,
+        reward_cycle: Option<u64>,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response =
+            match RPCRewardCycleData::from_db(sortdb, chainstate, tip, burnchain, reward_cycle) {
+                Ok(data) => HttpResponseType::PoxCycle(response_metadata, data),
+                Err(net_error::NotFoundError) => {
+                    HttpResponseType::NotFound(response_metadata, "No such reward cycle".into())
+                }
+                Err(e) => {
+                    warn!("Failed to get reward cycle {:?}: {:?}", req, &e);
+                    HttpResponseType::ServerError(
+                        response_metadata,
+                        "Failed to query reward cycle".to_string(),
+                    )
+                }
+            };
+        response.send(http, fd)
+    }
+
+    /// Handle a GET for a reward cycle's reward set
+    fn handle_get_pox_reward_set<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response = match RPCRewardSetData::from_db(sortdb, chainstate, burnchain, reward_cycle)
+        {
+            Ok(data) => HttpResponseType::PoxRewardSet(response_metadata, data),
+            Err(net_error::NotFoundError) => {
+                HttpResponseType::NotFound(response_metadata, "Reward set is not known yet".into())
+            }
+            Err(e) => {
+                warn!("Failed to get reward set {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query reward set".to_string(),
+                )
+            }
+        };
+        response.send(http, fd)
+    }
+
+    /// Handle a GET for a principal's stacking state
+    fn handle_get_pox_stacker<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        stacker: &PrincipalData,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response = match RPCStackerData::from_db(sortdb, chainstate, tip, stacker) {
+            Ok(data) => HttpResponseType::PoxStacker(response_metadata, data),
+            Err(net_error::NotFoundError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to get stacking state {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query stacking state".to_string(),
+                )
+            }
+        };
+        response.send(http, fd)
+    }
+
     fn handle_getattachmentsinv<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
@@ -776,16 +1467,80 @@ impl ConversationHttp {
         response.send(http, fd)
     }
 
-    /// Handle a not-found
-    fn handle_notfound<W: Write>(
+    /// Handle a GET neighbors detail
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_getneighbors_detail<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
-        response_metadata: HttpResponseMetadata,
-        msg: String,
-    ) -> Result<Option<StreamCursor>, net_error> {
-        let response = HttpResponseType::NotFound(response_metadata, msg);
-        return response.send(http, fd).and_then(|_| Ok(None));
-    }
+        req: &HttpRequestType,
+        network: &PeerNetwork,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let detail_data = RPCNeighborsDetailData::from_p2p(network)?;
+        let response = HttpResponseType::NeighborsDetail(response_metadata, detail_data);
+        response.send(http, fd)
+    }
+
+    /// Handle a GET or POST for the peer policy.  A POST applies its update first, and both
+    /// answer with the resulting rules.
+    fn handle_peer_policy<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        network: &mut PeerNetwork,
+        update_opt: Option<&PeerPolicyUpdate>,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        if let Some(update) = update_opt {
+            if let Err(e) = network.update_peer_policy(update) {
+                warn!("Failed to update peer policy: {:?}", &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to update peer policy: {:?}", &e),
+                );
+                return response.send(http, fd);
+            }
+        }
+        let policy = RPCPeerPolicyData::from_peerdb(&network.peerdb)?;
+        let response = HttpResponseType::PeerPolicy(response_metadata, policy);
+        response.send(http, fd)
+    }
+
+    /// Handle a GET telemetry.  Shows the payload the telemetry beacon would submit right now.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_gettelemetry<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        network: &PeerNetwork,
+        handler_args: &RPCHandlerArgs,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let telemetry_key = match handler_args.telemetry_key {
+            Some(telemetry_key) => telemetry_key,
+            None => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Telemetry is not enabled".to_string(),
+                );
+                return response.send(http, fd);
+            }
+        };
+        let telemetry_data = RPCTelemetryData::from_network(network, telemetry_key)?;
+        let response = HttpResponseType::Telemetry(response_metadata, telemetry_data);
+        response.send(http, fd)
+    }
+
+    /// Handle a not-found
+    fn handle_notfound<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        response_metadata: HttpResponseMetadata,
+        msg: String,
+    ) -> Result<Option<StreamCursor>, net_error> {
+        let response = HttpResponseType::NotFound(response_metadata, msg);
+        return response.send(http, fd).and_then(|_| Ok(None));
+    }
 
     /// Handle a server error
     fn handle_server_error<W: Write>(
@@ -800,6 +1555,17 @@ impl ConversationHttp {
         return response.send(http, fd).and_then(|_| Ok(None));
     }
 
+    /// Handle a conditional GET for an immutable resource that the client already has
+    fn handle_not_modified<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        response_metadata: HttpResponseMetadata,
+        etag: String,
+    ) -> Result<Option<StreamCursor>, net_error> {
+        let response = HttpResponseType::NotModified(response_metadata.with_etag(etag));
+        response.send(http, fd).map(|_| None)
+    }
+
     /// Handle a GET headers. Start streaming the reply.
     /// The response's preamble (but not the headers list) will be synchronously written to the fd
     /// (so use a fd that can buffer!)
@@ -845,7 +1611,9 @@ impl ConversationHttp {
                     return response.send(http, fd).and_then(|_| Ok(None));
                 }
             };
-            let response = HttpResponseType::HeaderStream(response_metadata);
+            let response = HttpResponseType::HeaderStream(
+                response_metadata.with_content_encoding(req.metadata().accept_encoding),
+            );
             response.send(http, fd).and_then(|_| Ok(Some(stream)))
         }
     }
@@ -885,14 +1653,81 @@ impl ConversationHttp {
                 response.send(http, fd).and_then(|_| Ok(None))
             }
             Ok(true) => {
+                // blocks are immutable, so the index block hash is a strong ETag
+                let etag = index_block_hash.to_hex();
+                if req.metadata().etag_matches(&etag) {
+                    return ConversationHttp::handle_not_modified(
+                        http,
+                        fd,
+                        response_metadata,
+                        etag,
+                    );
+                }
+
                 // yup! start streaming it back
                 let stream = StreamCursor::new_block(index_block_hash.clone());
-                let response = HttpResponseType::BlockStream(response_metadata);
+                let response = HttpResponseType::BlockStream(
+                    response_metadata
+                        .with_etag(etag)
+                        .with_content_encoding(req.metadata().accept_encoding),
+                );
                 response.send(http, fd).and_then(|_| Ok(Some(stream)))
             }
         }
     }
 
+    /// Handle a GET block export.  Start streaming the reply.
+    /// The response's preamble (but not the block data) will be synchronously written to the fd
+    /// (so use a fd that can buffer!)
+    /// Return a StreamCursor struct for the run of blocks we're sending, so we can continue to
+    /// make progress sending it.
+    fn handle_getblock_export<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tip: &StacksBlockId,
+        start_height: u64,
+        count: u32,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<StreamCursor>, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        if count > MAX_BLOCK_EXPORT {
+            let response = HttpResponseType::BadRequestJSON(
+                response_metadata,
+                serde_json::Value::String(format!(
+                    "Invalid request: requested more than {} blocks",
+                    MAX_BLOCK_EXPORT
+                )),
+            );
+            return response.send(http, fd).and_then(|_| Ok(None));
+        }
+
+        let stream = match StreamCursor::new_block_export(chainstate, tip, start_height, count) {
+            Ok(stream) => stream,
+            Err(chain_error::NoSuchBlockError) => {
+                return ConversationHttp::handle_notfound(
+                    http,
+                    fd,
+                    response_metadata,
+                    format!("No blocks at or above height {}", start_height),
+                );
+            }
+            Err(e) => {
+                warn!("Failed to export blocks {:?}: {:?}", req, &e);
+                return ConversationHttp::handle_server_error(
+                    http,
+                    fd,
+                    response_metadata,
+                    format!("Failed to export blocks from height {}", start_height),
+                );
+            }
+        };
+        let response = HttpResponseType::BlockExportStream(
+            response_metadata.with_content_encoding(req.metadata().accept_encoding),
+        );
+        response.send(http, fd).and_then(|_| Ok(Some(stream)))
+    }
+
     /// Handle a GET confirmed microblock stream, by _anchor block hash_.  Start streaming the reply.
     /// The response's preamble (but not the block data) will be synchronously written to the fd
     /// (so use a fd that can buffer!)
@@ -958,12 +1793,27 @@ impl ConversationHttp {
                 );
             }
             Ok(Some(tail_index_microblock_hash)) => {
+                // a confirmed stream is identified by its last microblock
+                let etag = tail_index_microblock_hash.to_hex();
+                if req.metadata().etag_matches(&etag) {
+                    return ConversationHttp::handle_not_modified(
+                        http,
+                        fd,
+                        response_metadata,
+                        etag,
+                    );
+                }
+
                 let (response, stream_opt) = match StreamCursor::new_microblock_confirmed(
                     chainstate,
                     tail_index_microblock_hash.clone(),
                 ) {
                     Ok(stream) => (
-                        HttpResponseType::MicroblockStream(response_metadata),
+                        HttpResponseType::MicroblockStream(
+                            response_metadata
+                                .with_etag(etag)
+                                .with_content_encoding(req.metadata().accept_encoding),
+                        ),
                         Some(stream),
                     ),
                     Err(chain_error::NoSuchBlockError) => (
@@ -998,6 +1848,109 @@ impl ConversationHttp {
         }
     }
 
+    /// Handle a GET for a page of the microblock stream built off of an anchored block
+    fn handle_getmicroblock_stream<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        index_anchor_block_hash: &StacksBlockId,
+        start_seq: u16,
+        limit: u16,
+        chainstate: &StacksChainState,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let limit = cmp::min(limit, MAX_MICROBLOCK_STREAM_PAGE);
+        let response =
+            match chainstate.load_microblock_stream_page(index_anchor_block_hash, start_seq, limit)
+            {
+                Ok(Some((confirmed, microblocks))) => {
+                    let next_start = match microblocks.last() {
+                        Some(last) if microblocks.len() as u16 >= limit => {
+                            last.header.sequence.checked_add(1)
+                        }
+                        _ => None,
+                    };
+                    let microblocks = microblocks
+                        .into_iter()
+                        .map(|mblock| RPCMicroblockEntry {
+                            microblock_hash: mblock.block_hash(),
+                            sequence: mblock.header.sequence,
+                            parent_microblock_hash: mblock.header.prev_block.clone(),
+                            txids: mblock.txs.iter().map(|tx| tx.txid()).collect(),
+                            microblock: to_hex(&mblock.serialize_to_vec()),
+                        })
+                        .collect();
+                    HttpResponseType::MicroblockStreamPage(
+                        response_metadata,
+                        RPCMicroblockStreamData {
+                            anchor_block_id: index_anchor_block_hash.clone(),
+                            confirmed,
+                            microblocks,
+                            next_start,
+                        },
+                    )
+                }
+                Ok(None) => HttpResponseType::NotFound(
+                    response_metadata,
+                    format!(
+                        "No microblocks built on {} at or after {}",
+                        index_anchor_block_hash, start_seq
+                    ),
+                ),
+                Err(e) => {
+                    warn!(
+                        "Failed to load microblock stream for {}: {:?}",
+                        index_anchor_block_hash, &e
+                    );
+                    HttpResponseType::ServerError(
+                        response_metadata,
+                        "Failed to query microblock stream".to_string(),
+                    )
+                }
+            };
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET for the Clarity state written by a block
+    fn handle_getblock_state_diff<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        index_block_hash: &StacksBlockId,
+        chainstate: &mut StacksChainState,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let response = match chainstate.get_block_state_diff(index_block_hash) {
+            Ok(Some(entries)) => HttpResponseType::BlockStateDiff(
+                response_metadata,
+                RPCBlockStateDiffData {
+                    index_block_hash: index_block_hash.clone(),
+                    entries: entries
+                        .into_iter()
+                        .map(|entry| RPCStateDiffEntry {
+                            key: entry.key,
+                            path: entry.path,
+                            value: entry.value,
+                            prior_value: entry.prior_value,
+                        })
+                        .collect(),
+                },
+            ),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No state found for block {}", index_block_hash),
+            ),
+            Err(e) => {
+                warn!("Failed to get state diff of {}: {:?}", index_block_hash, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query block state diff".to_string(),
+                )
+            }
+        };
+        response.send(http, fd)
+    }
+
     /// Handle a GET confirmed microblock stream, by last _index microblock hash_ in the stream.  Start streaming the reply.
     /// The response's preamble (but not the block data) will be synchronously written to the fd
     /// (so use a fd that can buffer!)
@@ -1044,12 +1997,27 @@ impl ConversationHttp {
             }
             Ok(true) => {
                 // yup! start streaming it back
+                // a confirmed stream is identified by its last microblock
+                let etag = tail_index_microblock_hash.to_hex();
+                if req.metadata().etag_matches(&etag) {
+                    return ConversationHttp::handle_not_modified(
+                        http,
+                        fd,
+                        response_metadata,
+                        etag,
+                    );
+                }
+
                 let (response, stream_opt) = match StreamCursor::new_microblock_confirmed(
                     chainstate,
                     tail_index_microblock_hash.clone(),
                 ) {
                     Ok(stream) => (
-                        HttpResponseType::MicroblockStream(response_metadata),
+                        HttpResponseType::MicroblockStream(
+                            response_metadata
+                                .with_etag(etag)
+                                .with_content_encoding(req.metadata().accept_encoding),
+                        ),
                         Some(stream),
                     ),
                     Err(chain_error::NoSuchBlockError) => (
@@ -1171,2698 +2139,7030 @@ impl ConversationHttp {
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET on a smart contract's data var, given the current chain tip.  Optionally
-    /// supplies a MARF proof for the value.
-    fn handle_get_data_var<W: Write>(
+    /// Handle a GET for the proof of an account's balance and nonce at the given chain tip.
+    fn handle_get_account_proof<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        var_name: &ClarityName,
-        with_proof: bool,
+        account: &PrincipalData,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
-
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|clarity_db| {
-                    let key = ClarityDatabase::make_key_for_trip(
-                        &contract_identifier,
-                        StoreType::Variable,
-                        var_name,
-                    );
-
-                    let (value, marf_proof) = if with_proof {
-                        clarity_db
-                            .get_with_proof::<Value>(&key)
-                            .map(|(a, b)| (a, Some(format!("0x{}", b.to_hex()))))?
-                    } else {
-                        clarity_db.get::<Value>(&key).map(|a| (a, None))?
-                    };
+        let response = match chainstate.get_account_proof(tip, account) {
+            Ok(Some(proof)) => HttpResponseType::AccountProof(response_metadata, proof),
+            Ok(None) => HttpResponseType::NotFound(response_metadata, "Chain tip not found".into()),
+            Err(e) => {
+                warn!("Failed to prove account {} at {}: {:?}", account, tip, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load account proof".to_string(),
+                )
+            }
+        };
+        response.send(http, fd).map(|_| ())
+    }
 
-                    let data = format!("0x{}", value.serialize());
-                    Some(DataVarResponse { data, marf_proof })
-                })
-            }) {
-                Ok(Some(Some(data))) => HttpResponseType::GetDataVar(response_metadata, data),
-                Ok(Some(None)) => {
-                    HttpResponseType::NotFound(response_metadata, "Data var not found".into())
-                }
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+    /// Handle a GET for a page of a contract's indexed events on the fork ending at the given
+    /// chain tip.
+    fn handle_get_contract_events<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
+        tip: &StacksBlockId,
+        query: &ContractEventsQuery,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let limit = cmp::min(query.limit, MAX_CONTRACT_EVENTS);
+
+        let response = match chainstate.get_contract_events(
+            tip,
+            &query.contract,
+            &query.filter,
+            query.cursor.as_ref(),
+            limit,
+        ) {
+            Ok(events) => {
+                let next_cursor = if events.len() as u32 >= limit {
+                    events.last().map(|event| event.cursor.to_string())
+                } else {
+                    None
+                };
+                let events = events
+                    .into_iter()
+                    .map(|event| ContractEventEntry {
+                        index_block_hash: format!("0x{}", &event.index_block_hash),
+                        block_height: event.cursor.block_height,
+                        txid: format!("0x{}", &event.txid),
+                        event_index: event.cursor.event_index,
+                        contract_identifier: event.contract_identifier.to_string(),
+                        event_type: event.event_type.to_string(),
+                        topic: event.topic,
+                        value: format!("0x{}", event.value.serialize()),
+                        sender: event.sender.map(|sender| sender.to_string()),
+                        recipient: event.recipient.map(|recipient| recipient.to_string()),
+                        cursor: event.cursor.to_string(),
+                    })
+                    .collect();
+                HttpResponseType::ContractEvents(
+                    response_metadata,
+                    ContractEventsResponse {
+                        events,
+                        next_cursor,
+                    },
+                )
+            }
+            Err(chain_error::NoSuchBlockError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to load contract events: {:?}", &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load contract events".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET on a smart contract's data map, given the current chain tip.  Optionally
-    /// supplies a MARF proof for the value.
-    fn handle_get_map_entry<W: Write>(
+    /// Handle a GET for a page of the indexed transactions involving a principal on the fork
+    /// ending at the given chain tip.
+    fn handle_get_address_transactions<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
-        chainstate: &mut StacksChainState,
+        chainstate: &StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        map_name: &ClarityName,
-        key: &Value,
-        with_proof: bool,
+        query: &AddressTransactionsQuery,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+        if !chainstate.address_tx_index {
+            return HttpResponseType::NotFound(
+                response_metadata,
+                "Address transaction index is not enabled on this node".into(),
+            )
+            .send(http, fd)
+            .map(|_| ());
+        }
+        let limit = cmp::min(query.limit, MAX_ADDRESS_TRANSACTIONS);
 
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|clarity_db| {
-                    let key = ClarityDatabase::make_key_for_data_map_entry(
-                        &contract_identifier,
-                        map_name,
-                        key,
-                    );
-                    let (value, marf_proof) = if with_proof {
-                        clarity_db
-                            .get_with_proof::<Value>(&key)
-                            .map(|(a, b)| (a, Some(format!("0x{}", b.to_hex()))))
-                            .unwrap_or_else(|| {
-                                test_debug!("No value for '{}' in {}", &key, tip);
-                                (Value::none(), Some("".into()))
-                            })
-                    } else {
-                        clarity_db
-                            .get::<Value>(&key)
-                            .map(|a| (a, None))
-                            .unwrap_or_else(|| {
-                                test_debug!("No value for '{}' in {}", &key, tip);
-                                (Value::none(), None)
-                            })
-                    };
-
-                    let data = format!("0x{}", value.serialize());
-                    MapEntryResponse { data, marf_proof }
-                })
-            }) {
-                Ok(Some(data)) => HttpResponseType::GetMapEntry(response_metadata, data),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+        let response = match chainstate.get_address_transactions(
+            tip,
+            &query.principal,
+            query.cursor.as_ref(),
+            limit,
+        ) {
+            Ok(transactions) => {
+                let next_cursor = if transactions.len() as u32 >= limit {
+                    transactions.last().map(|tx| tx.cursor.to_string())
+                } else {
+                    None
+                };
+                let transactions = transactions
+                    .into_iter()
+                    .map(|tx| AddressTransactionEntry {
+                        txid: format!("0x{}", &tx.txid),
+                        index_block_hash: format!("0x{}", &tx.index_block_hash),
+                        block_height: tx.cursor.block_height,
+                        receipt_index: tx.cursor.receipt_index,
+                        microblock_hash: tx.microblock_hash.map(|hash| format!("0x{}", &hash)),
+                        microblock_sequence: tx.microblock_sequence,
+                        event_indexes: tx.event_indexes,
+                        cursor: tx.cursor.to_string(),
+                    })
+                    .collect();
+                HttpResponseType::AddressTransactions(
+                    response_metadata,
+                    AddressTransactionsResponse {
+                        transactions,
+                        next_cursor,
+                    },
+                )
+            }
+            Err(chain_error::NoSuchBlockError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to load address transactions: {:?}", &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load address transactions".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a POST to run a read-only function call with the given parameters on the given chain
-    /// tip.  Returns the result of the function call.  Returns a CallReadOnlyResponse on success.
-    fn handle_readonly_function_call<W: Write>(
+    /// Handle a GET for the fungible and non-fungible tokens a principal holds as of the given
+    /// chain tip, according to the address asset index.
+    fn handle_get_address_assets<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
-        chainstate: &mut StacksChainState,
+        chainstate: &StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        function: &ClarityName,
-        sender: &PrincipalData,
-        args: &[Value],
-        options: &ConnectionOptions,
+        principal: &PrincipalData,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
-
-        let args: Vec<_> = args
-            .iter()
-            .map(|x| SymbolicExpression::atom_value(x.clone()))
-            .collect();
-        let mainnet = chainstate.mainnet;
-        let mut cost_limit = options.read_only_call_limit.clone();
-        cost_limit.write_length = 0;
-        cost_limit.write_count = 0;
-
-        let data_opt_res =
-            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                let epoch = clarity_tx.get_epoch();
-                let cost_track = clarity_tx
-                    .with_clarity_db_readonly(|clarity_db| {
-                        LimitedCostTracker::new_mid_block(mainnet, cost_limit, clarity_db, epoch)
-                    })
-                    .map_err(|_| {
-                        ClarityRuntimeError::from(InterpreterError::CostContractLoadFailure)
-                    })?;
-
-                clarity_tx.with_readonly_clarity_env(mainnet, sender.clone(), cost_track, |env| {
-                    // we want to execute any function as long as no actual writes are made as
-                    // opposed to be limited to purely calling `define-read-only` functions,
-                    // so use `read_only = false`.  This broadens the number of functions that
-                    // can be called, and also circumvents limitations on `define-read-only`
-                    // functions that can not use `contrac-call?`, even when calling other
-                    // read-only functions
-                    env.execute_contract(&contract_identifier, function.as_str(), &args, false)
-                })
-            });
+        if !chainstate.address_asset_index {
+            return HttpResponseType::NotFound(
+                response_metadata,
+                "Address asset index is not enabled on this node".into(),
+            )
+            .send(http, fd)
+            .map(|_| ());
+        }
 
-        let response = match data_opt_res {
-            Ok(Some(Ok(data))) => HttpResponseType::CallReadOnlyFunction(
+        let response = match chainstate.get_address_assets(tip, principal) {
+            Ok(holdings) => HttpResponseType::AddressAssets(
                 response_metadata,
-                CallReadOnlyResponse {
-                    okay: true,
-                    result: Some(format!("0x{}", data.serialize())),
-                    cause: None,
+                AddressAssetsResponse {
+                    fungible_tokens: holdings
+                        .fungible_tokens
+                        .into_iter()
+                        .map(|(asset_identifier, balance)| FungibleTokenHoldingEntry {
+                            asset_identifier,
+                            balance: balance.to_string(),
+                        })
+                        .collect(),
+                    non_fungible_tokens: holdings
+                        .non_fungible_tokens
+                        .into_iter()
+                        .map(|(asset_identifier, value)| NonFungibleTokenHoldingEntry {
+                            asset_identifier,
+                            value: format!("0x{}", value.serialize()),
+                            repr: value.to_string(),
+                        })
+                        .collect(),
                 },
             ),
-            Ok(Some(Err(e))) => match e {
-                Unchecked(CheckErrors::CostBalanceExceeded(actual_cost, _))
-                    if actual_cost.write_count > 0 =>
-                {
-                    HttpResponseType::CallReadOnlyFunction(
-                        response_metadata,
-                        CallReadOnlyResponse {
-                            okay: false,
-                            result: None,
-                            cause: Some("NotReadOnly".to_string()),
-                        },
-                    )
-                }
-                _ => HttpResponseType::CallReadOnlyFunction(
-                    response_metadata,
-                    CallReadOnlyResponse {
-                        okay: false,
-                        result: None,
-                        cause: Some(e.to_string()),
-                    },
-                ),
-            },
-            Ok(None) | Err(_) => {
+            Err(chain_error::NoSuchBlockError) => {
                 HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
+            Err(e) => {
+                warn!("Failed to load address assets: {:?}", &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load address assets".to_string(),
+                )
+            }
         };
+
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET to fetch a contract's source code, given the chain tip.  Optionally returns a
-    /// MARF proof as well.
-    fn handle_get_contract_src<W: Write>(
+    /// Handle a GET for the BNS names a principal owns, according to the BNS contract
+    fn handle_get_address_names<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        with_proof: bool,
+        principal: &PrincipalData,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
-
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|db| {
-                    let source = db.get_contract_src(&contract_identifier)?;
-                    let contract_commit_key = make_contract_hash_key(&contract_identifier);
-                    let (contract_commit, proof) = if with_proof {
-                        db.get_with_proof::<ContractCommitment>(&contract_commit_key)
-                            .map(|(a, b)| (a, Some(format!("0x{}", &b.to_hex()))))
-                            .expect("BUG: obtained source, but couldn't get contract commit")
-                    } else {
-                        db.get::<ContractCommitment>(&contract_commit_key)
-                            .map(|a| (a, None))
-                            .expect("BUG: obtained source, but couldn't get contract commit")
-                    };
 
-                    let publish_height = contract_commit.block_height;
-                    Some(ContractSrcResponse {
-                        source,
-                        publish_height,
-                        marf_proof: proof,
-                    })
-                })
-            }) {
-                Ok(Some(Some(data))) => HttpResponseType::GetContractSrc(response_metadata, data),
-                Ok(Some(None)) => HttpResponseType::NotFound(
+        let response = match chainstate.get_bns_principal_name(sortdb, tip, principal) {
+            Ok(name_opt) => HttpResponseType::AddressNames(
+                response_metadata,
+                RPCPrincipalNamesData {
+                    names: name_opt
+                        .into_iter()
+                        .map(|(namespace, name)| format!("{}.{}", name, namespace))
+                        .collect(),
+                },
+            ),
+            Err(e) => {
+                warn!("Failed to resolve names owned by {}: {:?}", principal, &e);
+                HttpResponseType::ServerError(
                     response_metadata,
-                    "No contract source data found".into(),
-                ),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+                    "Failed to query BNS names".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET to fetch whether or not a contract implements a certain trait
-    fn handle_get_is_trait_implemented<W: Write>(
+    /// Handle a GET to resolve a BNS name to its owner and zonefile hash, according to the BNS
+    /// contract
+    fn handle_get_name<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        trait_id: &TraitIdentifier,
+        name: &str,
+        namespace: &str,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|db| {
-                    let analysis = db.load_contract_analysis(&contract_identifier)?;
-                    if analysis.implemented_traits.contains(trait_id) {
-                        Some(GetIsTraitImplementedResponse {
-                            is_implemented: true,
-                        })
-                    } else {
-                        let trait_defining_contract =
-                            db.load_contract_analysis(&trait_id.contract_identifier)?;
-                        let trait_definition =
-                            trait_defining_contract.get_defined_trait(&trait_id.name)?;
-                        let is_implemented = analysis
-                            .check_trait_compliance(trait_id, trait_definition)
-                            .is_ok();
-                        Some(GetIsTraitImplementedResponse { is_implemented })
-                    }
-                })
-            }) {
-                Ok(Some(Some(data))) => {
-                    HttpResponseType::GetIsTraitImplemented(response_metadata, data)
-                }
-                Ok(Some(None)) => HttpResponseType::NotFound(
+        let response = match chainstate.get_bns_name_info(sortdb, tip, namespace, name) {
+            Ok(Some(info)) => HttpResponseType::Name(
+                response_metadata,
+                RPCNameData {
+                    name: name.to_string(),
+                    namespace: namespace.to_string(),
+                    owner: info.owner.to_string(),
+                    zonefile_hash: to_hex(&info.zonefile_hash),
+                    lease_started_at: info.lease_started_at,
+                    lease_ending_at: info.lease_ending_at,
+                },
+            ),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No such name: {}.{}", name, namespace),
+            ),
+            Err(e) => {
+                warn!("Failed to resolve {}.{}: {:?}", name, namespace, &e);
+                HttpResponseType::ServerError(
                     response_metadata,
-                    "No contract analysis found or trait definition not found".into(),
-                ),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+                    "Failed to query BNS name".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET to fetch a contract's analysis data, given the chain tip.  Note that this isn't
-    /// something that's anchored to the blockchain, and can be different across different versions
-    /// of Stacks -- callers must trust the Stacks node to return correct analysis data.
-    /// Callers who don't trust the Stacks node should just fetch the contract source
-    /// code and analyze it offline.
-    fn handle_get_contract_abi<W: Write>(
+    /// Handle a Rosetta API request.  Returns the transaction to forward to peers, if the request
+    /// submitted a new one.
+    fn handle_rosetta<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
+        endpoint: RosettaEndpoint,
+        body: &serde_json::Value,
+        network: &PeerNetwork,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        event_observer: Option<&dyn MemPoolEventDispatcher>,
+    ) -> Result<Option<StacksTransaction>, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        if !chainstate.rosetta_index {
+            return HttpResponseType::NotFound(
+                response_metadata,
+                "Rosetta API is not enabled on this node".into(),
+            )
+            .send(http, fd)
+            .map(|_| None);
+        }
+
+        let node = RosettaNodeInfo {
+            server_version: version_string(
+                "stacks-node",
+                option_env!("STACKS_NODE_VERSION")
+                    .or(option_env!("CARGO_PKG_VERSION"))
+                    .unwrap_or("0.0.0.0"),
+            ),
+            peers: network
+                .peers
+                .values()
+                .filter(|convo| convo.is_authenticated())
+                .map(|convo| format!("0x{}", &convo.to_neighbor_address().public_key_hash))
+                .collect(),
+        };
+        let (response, forward) = match handle_rosetta_request(
+            endpoint,
+            body,
+            &node,
+            sortdb,
+            chainstate,
+            mempool,
+            event_observer,
+        ) {
+            Ok((data, forward)) => (HttpResponseType::Rosetta(response_metadata, data), forward),
+            Err(error) => {
+                debug!("Rosetta {} request failed: {:?}", endpoint.name(), &error);
+                (
+                    HttpResponseType::RosettaError(response_metadata, error),
+                    None,
+                )
+            }
+        };
+
+        response.send(http, fd).map(|_| forward)
+    }
+
+    /// Handle a GET for the per-contract execution costs summed over the last blocks of the
+    /// fork ending at the given chain tip.
+    fn handle_get_contract_metrics<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
+        query: &ContractMetricsQuery,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
-
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_analysis_db_readonly(|db| {
-                    let contract = db.load_contract(&contract_identifier)?;
-                    contract.contract_interface
-                })
-            }) {
-                Ok(Some(Some(data))) => HttpResponseType::GetContractABI(response_metadata, data),
-                Ok(Some(None)) => HttpResponseType::NotFound(
+        let blocks = cmp::min(query.blocks, MAX_CONTRACT_METRICS_BLOCKS);
+        let limit = cmp::min(query.limit, MAX_CONTRACT_METRICS);
+
+        let response = match chainstate.get_contract_metrics(tip, blocks, query.contract.as_ref()) {
+            Ok(metrics) => {
+                let contracts = metrics
+                    .into_iter()
+                    .take(limit as usize)
+                    .map(|metrics| ContractMetricsEntry {
+                        contract_identifier: metrics.contract_identifier.to_string(),
+                        calls: metrics.calls,
+                        deploys: metrics.deploys,
+                        runtime: metrics.execution_cost.runtime,
+                        read_count: metrics.execution_cost.read_count,
+                        read_length: metrics.execution_cost.read_length,
+                        write_count: metrics.execution_cost.write_count,
+                        write_length: metrics.execution_cost.write_length,
+                    })
+                    .collect();
+                HttpResponseType::ContractMetrics(
                     response_metadata,
-                    "No contract interface data found".into(),
-                ),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+                    ContractMetricsResponse {
+                        tip: format!("0x{}", tip),
+                        blocks,
+                        contracts,
+                    },
+                )
+            }
+            Err(chain_error::NoSuchBlockError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to load contract metrics: {:?}", &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load contract metrics".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET unconfirmed microblock stream.  Start streaming the reply.
-    /// The response's preamble (but not the block data) will be synchronously written to the fd
-    /// (so use a fd that can buffer!)
-    /// Return a StreamCursor struct for the block that we're sending, so we can continue to
-    /// make progress sending it.
-    fn handle_getmicroblocks_unconfirmed<W: Write>(
+    /// Handle a GET for the checksum manifest of the canonical chainstate.
+    fn handle_get_chainstate_manifest<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        index_anchor_block_hash: &StacksBlockId,
-        min_seq: u16,
+        sortdb: &SortitionDB,
         chainstate: &StacksChainState,
-    ) -> Result<Option<StreamCursor>, net_error> {
+        interval: u64,
+        count: u64,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-
-        // do we have this unconfirmed microblock stream?
-        match chainstate.has_any_staging_microblock_indexed(index_anchor_block_hash, min_seq) {
-            Ok(false) => {
-                // nope
-                let response = HttpResponseType::NotFound(
-                    response_metadata,
-                    format!(
-                        "No such unconfirmed microblock stream for {} at or after {}",
-                        index_anchor_block_hash.to_hex(),
-                        min_seq
-                    ),
-                );
-                response.send(http, fd).and_then(|_| Ok(None))
+        let response = match chainstate.get_chainstate_manifest(sortdb, interval, count) {
+            Ok(manifest) => HttpResponseType::ChainstateManifest(response_metadata, manifest),
+            Err(chain_error::NoSuchBlockError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
             Err(e) => {
-                // nope
-                warn!(
-                    "Failed to serve confirmed microblock stream {:?}: {:?}",
-                    req, &e
-                );
-                let response = HttpResponseType::ServerError(
+                warn!("Failed to compute chainstate manifest: {:?}", &e);
+                HttpResponseType::ServerError(
                     response_metadata,
-                    format!(
-                        "Failed to query unconfirmed microblock stream for {} at or after {}",
-                        index_anchor_block_hash.to_hex(),
-                        min_seq
-                    ),
-                );
-                response.send(http, fd).and_then(|_| Ok(None))
-            }
-            Ok(true) => {
-                // yup! start streaming it back
-                let (response, stream_opt) = match StreamCursor::new_microblock_unconfirmed(
-                    chainstate,
-                    index_anchor_block_hash.clone(),
-                    min_seq,
-                ) {
-                    Ok(stream) => (
-                        HttpResponseType::MicroblockStream(response_metadata),
-                        Some(stream),
-                    ),
-                    Err(chain_error::NoSuchBlockError) => (
-                        HttpResponseType::NotFound(
-                            response_metadata,
-                            format!(
-                                "No such unconfirmed microblock stream starting with {}",
-                                index_anchor_block_hash.to_hex()
-                            ),
-                        ),
-                        None,
-                    ),
-                    Err(_e) => {
-                        debug!(
-                            "Failed to load unconfirmed microblock stream {}: {:?}",
-                            &index_anchor_block_hash, &_e
-                        );
-                        (
-                            HttpResponseType::ServerError(
-                                response_metadata,
-                                format!(
-                                    "Failed to query unconfirmed microblock stream {}",
-                                    index_anchor_block_hash.to_hex()
-                                ),
-                            ),
-                            None,
-                        )
-                    }
-                };
-                response.send(http, fd).and_then(|_| Ok(stream_opt))
+                    "Failed to compute chainstate manifest".to_string(),
+                )
             }
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Describe a pending transaction for the mempool transaction endpoints
+    fn make_mempool_transaction_entry(
+        txinfo: MemPoolTxInfo,
+        fee_rate: Option<f64>,
+    ) -> MemPoolTransactionEntry {
+        let cursor = MemPoolTxCursor {
+            accept_time: txinfo.metadata.accept_time,
+            txid: txinfo.metadata.txid.clone(),
+        };
+        MemPoolTransactionEntry {
+            txid: format!("0x{}", &txinfo.metadata.txid),
+            origin_address: txinfo.metadata.origin_address.to_string(),
+            origin_nonce: txinfo.metadata.origin_nonce,
+            sponsor_address: txinfo.metadata.sponsor_address.to_string(),
+            sponsor_nonce: txinfo.metadata.sponsor_nonce,
+            fee: txinfo.metadata.tx_fee,
+            fee_rate,
+            tx_len: txinfo.metadata.len,
+            payload_type: mempool_payload_type(&txinfo.tx.payload).to_string(),
+            block_height: txinfo.metadata.block_height,
+            accept_time: txinfo.metadata.accept_time,
+            tx: format!("0x{}", to_hex(&txinfo.tx.serialize_to_vec())),
+            cursor: cursor.to_string(),
         }
     }
 
-    /// Handle a GET unconfirmed transaction.
-    /// The response will be synchronously written to the fd.
-    fn handle_gettransaction_unconfirmed<W: Write>(
+    /// Handle a GET for a page of the mempool's pending transactions
+    fn handle_get_mempool_transactions<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        chainstate: &StacksChainState,
         mempool: &MemPoolDB,
-        txid: &Txid,
+        query: &MemPoolTransactionsQuery,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
+        let limit = cmp::min(query.limit, MAX_MEMPOOL_TRANSACTIONS);
 
-        // present in the unconfirmed state?
-        if let Some(ref unconfirmed) = chainstate.unconfirmed_state.as_ref() {
-            if let Some((transaction, mblock_hash, seq)) =
-                unconfirmed.get_unconfirmed_transaction(txid)
-            {
-                let response = HttpResponseType::UnconfirmedTransaction(
+        let response = match MemPoolDB::get_txs_page(
+            mempool.conn(),
+            &query.filter,
+            query.cursor.as_ref(),
+            limit,
+        ) {
+            Ok(txs) => {
+                let transactions: Vec<_> = txs
+                    .into_iter()
+                    .map(|(txinfo, fee_rate)| {
+                        ConversationHttp::make_mempool_transaction_entry(txinfo, fee_rate)
+                    })
+                    .collect();
+                let next_cursor = if transactions.len() as u32 >= limit {
+                    transactions.last().map(|entry| entry.cursor.clone())
+                } else {
+                    None
+                };
+                HttpResponseType::MemPoolTransactions(
                     response_metadata,
-                    UnconfirmedTransactionResponse {
-                        status: UnconfirmedTransactionStatus::Microblock {
-                            block_hash: mblock_hash,
-                            seq: seq,
-                        },
-                        tx: to_hex(&transaction.serialize_to_vec()),
+                    MemPoolTransactionsResponse {
+                        transactions,
+                        next_cursor,
                     },
-                );
-                return response.send(http, fd).map(|_| ());
+                )
             }
-        }
-
-        // present in the mempool?
-        if let Some(txinfo) = MemPoolDB::get_tx(mempool.conn(), txid)? {
-            let response = HttpResponseType::UnconfirmedTransaction(
-                response_metadata,
-                UnconfirmedTransactionResponse {
-                    status: UnconfirmedTransactionStatus::Mempool,
-                    tx: to_hex(&txinfo.tx.serialize_to_vec()),
-                },
-            );
-            return response.send(http, fd).map(|_| ());
-        }
+            Err(e) => {
+                warn!("Failed to list mempool transactions: {:?}", &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to list mempool transactions".to_string(),
+                )
+            }
+        };
 
-        // not found
-        let response = HttpResponseType::NotFound(
-            response_metadata,
-            format!("No such unconfirmed transaction {}", txid),
-        );
-        return response.send(http, fd).map(|_| ());
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Load up the canonical Stacks chain tip.  Note that this is subject to both burn chain block
-    /// Stacks block availability -- different nodes with different partial replicas of the Stacks chain state
-    /// will return different values here.
-    ///
-    /// # Warn
-    /// - There is a potential race condition. If this function is loading the latest unconfirmed
-    /// tip, that tip may get invalidated by the time it is used in `maybe_read_only_clarity_tx`,
-    /// which is used to load clarity state at a particular tip (which would lead to a 404 error).
-    /// If this race condition occurs frequently, we can modify `maybe_read_only_clarity_tx` to
-    /// re-load the unconfirmed chain tip. Refer to issue #2997.
-    ///
-    /// # Inputs
-    /// - `tip_req` is given by the HTTP request as the optional query parameter for the chain tip
-    /// hash.  It will be UseLatestAnchoredTip if there was no parameter given. If it is set to
-    /// `latest`, the parameter will be set to UseLatestUnconfirmedTip.
-    fn handle_load_stacks_chain_tip<W: Write>(
+    /// Handle a GET for a pending transaction in the mempool
+    fn handle_get_mempool_transaction<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        tip_req: &TipRequest,
-        sortdb: &SortitionDB,
-        chainstate: &mut StacksChainState,
-    ) -> Result<Option<StacksBlockId>, net_error> {
-        match tip_req {
-            TipRequest::UseLatestUnconfirmedTip => {
-                let unconfirmed_chain_tip_opt = match &mut chainstate.unconfirmed_state {
-                    Some(unconfirmed_state) => {
-                        match unconfirmed_state.get_unconfirmed_state_if_exists() {
-                            Ok(res) => res,
-                            Err(msg) => {
-                                let response_metadata = HttpResponseMetadata::from(req);
-                                let response = HttpResponseType::NotFound(response_metadata, msg);
-                                return response.send(http, fd).and_then(|_| Ok(None));
-                            }
-                        }
-                    }
-                    None => None,
-                };
-
-                if let Some(unconfirmed_chain_tip) = unconfirmed_chain_tip_opt {
-                    Ok(Some(unconfirmed_chain_tip))
-                } else {
-                    match chainstate.get_stacks_chain_tip(sortdb)? {
-                        Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
-                            &tip.consensus_hash,
-                            &tip.anchored_block_hash,
-                        ))),
-                        None => {
-                            let response_metadata = HttpResponseMetadata::from(req);
-                            warn!("Failed to load Stacks chain tip");
-                            let response = HttpResponseType::NotFound(
-                                response_metadata,
-                                format!("Failed to load Stacks chain tip"),
-                            );
-                            response.send(http, fd).and_then(|_| Ok(None))
-                        }
-                    }
-                }
-            }
-            TipRequest::SpecificTip(tip) => Ok(Some(*tip).clone()),
-            TipRequest::UseLatestAnchoredTip => match chainstate.get_stacks_chain_tip(sortdb)? {
-                Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
-                    &tip.consensus_hash,
-                    &tip.anchored_block_hash,
+        mempool: &MemPoolDB,
+        txid: &Txid,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let txinfo_res =
+            MemPoolDB::get_tx(mempool.conn(), txid).and_then(|txinfo_opt| match txinfo_opt {
+                Some(txinfo) => Ok(Some((
+                    txinfo,
+                    MemPoolDB::get_tx_fee_rate(mempool.conn(), txid)?,
                 ))),
-                None => {
-                    let response_metadata = HttpResponseMetadata::from(req);
-                    warn!("Failed to load Stacks chain tip");
-                    let response = HttpResponseType::ServerError(
-                        response_metadata,
-                        format!("Failed to load Stacks chain tip"),
-                    );
-                    response.send(http, fd).and_then(|_| Ok(None))
-                }
-            },
-        }
-    }
+                None => Ok(None),
+            });
 
-    fn handle_load_stacks_chain_tip_hashes<W: Write>(
-        http: &mut StacksHttp,
-        fd: &mut W,
-        req: &HttpRequestType,
-        tip: StacksBlockId,
-        chainstate: &StacksChainState,
-    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, net_error> {
-        match chainstate.get_block_header_hashes(&tip)? {
-            Some((ch, bl)) => {
-                return Ok(Some((ch, bl)));
+        let response = match txinfo_res {
+            Ok(Some((txinfo, fee_rate))) => HttpResponseType::MemPoolTransaction(
+                response_metadata,
+                ConversationHttp::make_mempool_transaction_entry(txinfo, fee_rate),
+            ),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No such mempool transaction {}", txid),
+            ),
+            Err(e) => {
+                warn!("Failed to load mempool transaction {}: {:?}", txid, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load mempool transaction".to_string(),
+                )
             }
-            None => {}
-        }
+        };
 
-        let response_metadata = HttpResponseMetadata::from(req);
-        warn!("Failed to load Stacks chain tip");
-        let response = HttpResponseType::ServerError(
-            response_metadata,
-            format!("Failed to load Stacks chain tip"),
-        );
-        response.send(http, fd).and_then(|_| Ok(None))
+        response.send(http, fd).map(|_| ())
     }
 
-    fn handle_post_fee_rate_estimate<W: Write>(
+    /// Handle a GET on a smart contract's data var, given the current chain tip.  Optionally
+    /// supplies a MARF proof for the value.
+    fn handle_get_data_var<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        handler_args: &RPCHandlerArgs,
         sortdb: &SortitionDB,
-        tx: &TransactionPayload,
-        estimated_len: u64,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        var_name: &ClarityName,
+        with_proof: bool,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
-        let stacks_epoch = SortitionDB::get_stacks_epoch(sortdb.conn(), tip.block_height)?
-                .ok_or_else(|| {
-                    warn!(
-                        "Failed to get fee rate estimate because could not load Stacks epoch for canonical burn height = {}",
-                        tip.block_height
-                    );
-                    net_error::ChainstateError("Could not load Stacks epoch for canonical burn height".into())
-                })?;
-        if let Some((cost_estimator, fee_estimator, metric)) = handler_args.get_estimators_ref() {
-            let estimated_cost = match cost_estimator.estimate_cost(tx, &stacks_epoch.epoch_id) {
-                Ok(x) => x,
-                Err(e) => {
-                    debug!(
-                        "Estimator RPC endpoint failed to estimate tx: {}",
-                        tx.name()
-                    );
-                    return HttpResponseType::BadRequestJSON(response_metadata, e.into_json())
-                        .send(http, fd);
-                }
-            };
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
-            let scalar_cost =
-                metric.from_cost_and_len(&estimated_cost, &stacks_epoch.block_limit, estimated_len);
-            let fee_rates = match fee_estimator.get_rate_estimates() {
-                Ok(x) => x,
-                Err(e) => {
-                    debug!(
-                        "Estimator RPC endpoint failed to estimate fees for tx: {}",
-                        tx.name()
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let key = ClarityDatabase::make_key_for_trip(
+                        &contract_identifier,
+                        StoreType::Variable,
+                        var_name,
                     );
-                    return HttpResponseType::BadRequestJSON(response_metadata, e.into_json())
-                        .send(http, fd);
-                }
-            };
-
-            let mut estimations = RPCFeeEstimate::estimate_fees(scalar_cost, fee_rates).to_vec();
-
-            let minimum_fee = estimated_len * MINIMUM_TX_FEE_RATE_PER_BYTE;
 
-            for estimate in estimations.iter_mut() {
-                if estimate.fee < minimum_fee {
-                    estimate.fee = minimum_fee;
+                    let (value, marf_proof) = if with_proof {
+                        clarity_db
+                            .get_with_proof::<Value>(&key)
+                            .map(|(a, b)| (a, Some(format!("0x{}", b.to_hex()))))?
+                    } else {
+                        clarity_db.get::<Value>(&key).map(|a| (a, None))?
+                    };
+
+                    let data = format!("0x{}", value.serialize());
+                    Some(DataVarResponse { data, marf_proof })
+                })
+            }) {
+                Ok(Some(Some(data))) => HttpResponseType::GetDataVar(response_metadata, data),
+                Ok(Some(None)) => {
+                    HttpResponseType::NotFound(response_metadata, "Data var not found".into())
                 }
-            }
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
+            };
 
-            let response = HttpResponseType::TransactionFeeEstimation(
-                response_metadata,
-                RPCFeeEstimateResponse {
-                    estimated_cost,
-                    estimations,
-                    estimated_cost_scalar: scalar_cost,
-                    cost_scalar_change_by_byte: metric.change_per_byte(),
-                },
-            );
-            response.send(http, fd)
-        } else {
-            debug!("Fee and cost estimation not configured on this stacks node");
-            let response = HttpResponseType::BadRequestJSON(
-                response_metadata,
-                json!({
-                    "error": "Fee and Cost Estimation not configured on this Stacks node",
-                    "reason": "CostEstimationDisabled",
-                }),
-            );
-            response.send(http, fd)
-        }
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a transaction.  Directly submit it to the mempool so the client can see any
-    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
-    /// whether or not the transaction was accepted (and thus needs to be forwarded) in the return
-    /// value.
-    fn handle_post_transaction<W: Write>(
+    /// Handle a GET on a smart contract's data map, given the current chain tip.  Optionally
+    /// supplies a MARF proof for the value.
+    fn handle_get_map_entry<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        chainstate: &mut StacksChainState,
         sortdb: &SortitionDB,
-        consensus_hash: ConsensusHash,
-        block_hash: BlockHeaderHash,
-        mempool: &mut MemPoolDB,
-        tx: StacksTransaction,
-        atlasdb: &mut AtlasDB,
-        attachment: Option<Attachment>,
-        event_observer: Option<&dyn MemPoolEventDispatcher>,
-    ) -> Result<bool, net_error> {
-        let txid = tx.txid();
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        map_name: &ClarityName,
+        key: &Value,
+        with_proof: bool,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let (response, accepted) = if mempool.has_tx(&txid) {
-            debug!("Mempool already has POSTed transaction {}", &txid);
-            (
-                HttpResponseType::TransactionID(response_metadata, txid),
-                false,
-            )
-        } else {
-            let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
-            let stacks_epoch = sortdb
-                .index_conn()
-                .get_stacks_epoch(tip.block_height as u32)
-                .ok_or_else(|| {
-                    warn!(
-                        "Failed to store transaction because could not load Stacks epoch for canonical burn height = {}",
-                        tip.block_height
-                    );
-                    net_error::ChainstateError("Could not load Stacks epoch for canonical burn height".into())
-                })?;
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
-            match mempool.submit(
-                chainstate,
-                &consensus_hash,
-                &block_hash,
-                &tx,
-                event_observer,
-                &stacks_epoch.block_limit,
-                &stacks_epoch.epoch_id,
-            ) {
-                Ok(_) => {
-                    debug!("Mempool accepted POSTed transaction {}", &txid);
-                    (
-                        HttpResponseType::TransactionID(response_metadata, txid),
-                        true,
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    ConversationHttp::read_map_entry(
+                        clarity_db,
+                        tip,
+                        &contract_identifier,
+                        map_name,
+                        key,
+                        with_proof,
                     )
+                })
+            }) {
+                Ok(Some(data)) => HttpResponseType::GetMapEntry(
+                    response_metadata.with_content_encoding(req.metadata().accept_encoding),
+                    data,
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
                 }
-                Err(e) => {
-                    debug!("Mempool rejected POSTed transaction {}: {:?}", &txid, &e);
-                    (
-                        HttpResponseType::BadRequestJSON(response_metadata, e.into_json(&txid)),
-                        false,
-                    )
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Read one entry of a smart contract's data map, optionally with a MARF proof.  A missing
+    /// entry is reported as `none` (with an empty proof, if a proof was requested).
+    fn read_map_entry(
+        clarity_db: &mut ClarityDatabase,
+        _tip: &StacksBlockId,
+        contract_identifier: &QualifiedContractIdentifier,
+        map_name: &ClarityName,
+        key: &Value,
+        with_proof: bool,
+    ) -> MapEntryResponse {
+        let key = ClarityDatabase::make_key_for_data_map_entry(contract_identifier, map_name, key);
+        let (value, marf_proof) = if with_proof {
+            clarity_db
+                .get_with_proof::<Value>(&key)
+                .map(|(a, b)| (a, Some(format!("0x{}", b.to_hex()))))
+                .unwrap_or_else(|| {
+                    test_debug!("No value for '{}' in {}", &key, _tip);
+                    (Value::none(), Some("".into()))
+                })
+        } else {
+            clarity_db
+                .get::<Value>(&key)
+                .map(|a| (a, None))
+                .unwrap_or_else(|| {
+                    test_debug!("No value for '{}' in {}", &key, _tip);
+                    (Value::none(), None)
+                })
+        };
+
+        let data = format!("0x{}", value.serialize());
+        MapEntryResponse { data, marf_proof }
+    }
+
+    /// Handle a POST to look up a batch of data map entries, possibly across several maps and
+    /// contracts.  All entries are read in one read-only Clarity connection at the same chain tip.
+    /// Returns a MapEntryBatchResponse with one entry per key, in request order.
+    fn handle_get_map_entry_batch<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        lookups: &[MapEntryLookup],
+        with_proof: bool,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    lookups
+                        .iter()
+                        .map(|lookup| MapEntryBatchResult {
+                            contract: lookup.contract_id.to_string(),
+                            map: lookup.map_name.to_string(),
+                            entries: lookup
+                                .keys
+                                .iter()
+                                .map(|key| {
+                                    ConversationHttp::read_map_entry(
+                                        clarity_db,
+                                        tip,
+                                        &lookup.contract_id,
+                                        &lookup.map_name,
+                                        key,
+                                        with_proof,
+                                    )
+                                })
+                                .collect(),
+                        })
+                        .collect::<Vec<_>>()
+                })
+            }) {
+                Ok(Some(maps)) => HttpResponseType::GetMapEntryBatch(
+                    response_metadata.with_content_encoding(req.metadata().accept_encoding),
+                    MapEntryBatchResponse {
+                        tip: tip.to_hex(),
+                        maps,
+                    },
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
                 }
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a POST to run a read-only function call with the given parameters on the given chain
+    /// tip.  Returns the result of the function call.  Returns a CallReadOnlyResponse on success.
+    fn handle_readonly_function_call<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        function: &ClarityName,
+        sender: &PrincipalData,
+        args: &[Value],
+        options: &ConnectionOptions,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let data_opt_res = chainstate.eval_read_only_function_at_tip(
+            &sortdb.index_conn(),
+            tip,
+            &contract_identifier,
+            function.as_str(),
+            args,
+            sender,
+            options.read_only_call_limit.clone(),
+            options.read_only_call_execution_limits(),
+        );
+
+        let response = match data_opt_res {
+            Ok(Some(result)) => HttpResponseType::CallReadOnlyFunction(
+                response_metadata,
+                ConversationHttp::make_call_read_only_response(result),
+            ),
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
         };
+        response.send(http, fd).map(|_| ())
+    }
 
-        if let Some(ref attachment) = attachment {
-            if let TransactionPayload::ContractCall(ref contract_call) = tx.payload {
-                if atlasdb
-                    .should_keep_attachment(&contract_call.to_clarity_contract_id(), &attachment)
-                {
-                    atlasdb
-                        .insert_uninstantiated_attachment(attachment)
-                        .map_err(|e| net_error::DBError(e))?;
+    /// Handle a POST to run a batch of read-only function calls against the same chain tip.  All
+    /// calls are evaluated in one read-only Clarity connection, so they observe the same state.
+    /// Returns a CallReadOnlyBatchResponse with one result per call, in order.
+    fn handle_readonly_function_call_batch<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        sender: &PrincipalData,
+        calls: &[ReadOnlyCall],
+        options: &ConnectionOptions,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let data_opt_res = chainstate.eval_read_only_function_batch_at_tip(
+            &sortdb.index_conn(),
+            tip,
+            calls,
+            sender,
+            options.read_only_call_limit.clone(),
+            options.read_only_call_execution_limits(),
+        );
+
+        let response = match data_opt_res {
+            Ok(Some(results)) => HttpResponseType::CallReadOnlyFunctionBatch(
+                response_metadata,
+                CallReadOnlyBatchResponse {
+                    tip: tip.to_hex(),
+                    results: results
+                        .into_iter()
+                        .map(ConversationHttp::make_call_read_only_response)
+                        .collect(),
+                },
+            ),
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+        };
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a POST to check a proposed argument list against a public function's signature on
+    /// the given chain tip, without running the function.  Returns a
+    /// CheckContractCallArgsResponse listing every argument that does not fit.
+    fn handle_check_contract_call_args<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        function: &ClarityName,
+        args: &[Value],
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response = match chainstate.check_contract_call_args_at_tip(
+            &sortdb.index_conn(),
+            tip,
+            &contract_identifier,
+            function.as_str(),
+            args,
+        ) {
+            Ok(Some(Ok(mismatches))) => HttpResponseType::CheckContractCallArgs(
+                response_metadata,
+                CheckContractCallArgsResponse {
+                    okay: mismatches.is_empty(),
+                    cause: None,
+                    mismatches: mismatches
+                        .into_iter()
+                        .map(|mismatch| ArgumentMismatchEntry {
+                            index: mismatch.index,
+                            name: mismatch.name.to_string(),
+                            expected: mismatch.expected.to_string(),
+                            found: TypeSignature::type_of(&args[mismatch.index]).to_string(),
+                            cause: mismatch.error.to_string(),
+                        })
+                        .collect(),
+                },
+            ),
+            Ok(Some(Err(e))) => HttpResponseType::CheckContractCallArgs(
+                response_metadata,
+                CheckContractCallArgsResponse {
+                    okay: false,
+                    cause: Some(e.to_string()),
+                    mismatches: vec![],
+                },
+            ),
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+        };
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Convert the outcome of a read-only function call into its RPC representation.  A call that
+    /// ran out of its (zero) write budget is reported as `NotReadOnly`.
+    fn make_call_read_only_response(
+        result: Result<Value, ClarityRuntimeError>,
+    ) -> CallReadOnlyResponse {
+        match result {
+            Ok(data) => CallReadOnlyResponse {
+                okay: true,
+                result: Some(format!("0x{}", data.serialize())),
+                cause: None,
+            },
+            Err(Unchecked(CheckErrors::CostBalanceExceeded(actual_cost, _)))
+                if actual_cost.write_count > 0 =>
+            {
+                CallReadOnlyResponse {
+                    okay: false,
+                    result: None,
+                    cause: Some("NotReadOnly".to_string()),
                 }
             }
+            Err(e) => CallReadOnlyResponse {
+                okay: false,
+                result: None,
+                cause: Some(e.to_string()),
+            },
         }
-
-        response.send(http, fd).and_then(|_| Ok(accepted))
     }
 
-    /// Handle a block.  Directly submit a Stacks block to this node's chain state.
-    /// Indicate whether or not the block was accepted (i.e. it was new, and valid)
-    fn handle_post_block<W: Write>(
+    /// Handle a GET to fetch a contract's source code, given the chain tip.  Optionally returns a
+    /// MARF proof as well.  Published contracts never change, so the response carries a strong
+    /// ETag and is served from `cache` when possible.
+    fn handle_get_contract_src<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
-        consensus_hash: &ConsensusHash,
-        block: &StacksBlock,
-    ) -> Result<bool, net_error> {
+        cache: &mut ResponseCache<ContractSrcResponse>,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        with_proof: bool,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        // is this a consensus hash we recognize?
-        let (response, accepted) =
-            match SortitionDB::get_sortition_id_by_consensus(&sortdb.conn(), consensus_hash) {
-                Ok(Some(_)) => {
-                    // we recognize this consensus hash
-                    let ic = sortdb.index_conn();
-                    match Relayer::process_new_anchored_block(
-                        &ic,
-                        chainstate,
-                        consensus_hash,
-                        block,
-                        0,
-                    ) {
-                        Ok(true) => {
-                            debug!(
-                                "Accepted Stacks block {}/{}",
-                                consensus_hash,
-                                &block.block_hash()
-                            );
-                            (
-                                HttpResponseType::StacksBlockAccepted(
-                                    response_metadata,
-                                    StacksBlockHeader::make_index_block_hash(
-                                        consensus_hash,
-                                        &block.block_hash(),
-                                    ),
-                                    true,
-                                ),
-                                true,
-                            )
-                        }
-                        Ok(false) => {
-                            debug!(
-                                "Did not accept Stacks block {}/{}",
-                                consensus_hash,
-                                &block.block_hash()
-                            );
-                            (
-                                HttpResponseType::StacksBlockAccepted(
-                                    response_metadata,
-                                    StacksBlockHeader::make_index_block_hash(
-                                        consensus_hash,
-                                        &block.block_hash(),
-                                    ),
-                                    false,
-                                ),
-                                false,
-                            )
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to process anchored block {}/{}: {:?}",
-                                consensus_hash,
-                                &block.block_hash(),
-                                &e
-                            );
-                            (
-                                HttpResponseType::ServerError(
-                                    response_metadata,
-                                    format!(
-                                        "Failed to process anchored block {}/{}: {:?}",
-                                        consensus_hash,
-                                        &block.block_hash(),
-                                        &e
-                                    ),
-                                ),
-                                false,
-                            )
-                        }
-                    }
-                }
-                Ok(None) => {
-                    debug!(
-                        "Unrecognized consensus hash {} for block {}",
-                        consensus_hash,
-                        &block.block_hash()
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|db| {
+                    let contract_commit_key = make_contract_hash_key(&contract_identifier);
+                    let contract_commit = db.get::<ContractCommitment>(&contract_commit_key)?;
+
+                    // the source hash and publish height identify the contract, but a proof is
+                    // only good for the tip it was generated against
+                    let mut etag = format!(
+                        "{}-{}",
+                        contract_commit.hash.to_hex(),
+                        contract_commit.block_height
                     );
-                    (
-                        HttpResponseType::NotFound(
-                            response_metadata,
-                            format!("No such consensus hash '{}'", consensus_hash),
-                        ),
-                        false,
-                    )
+                    if with_proof {
+                        etag.push_str(&format!("-{}", tip.to_hex()));
+                    }
+
+                    if req.metadata().etag_matches(&etag) {
+                        return Some((etag, None));
+                    }
+                    if let Some(data) = cache.get(&etag) {
+                        return Some((etag, Some(data)));
+                    }
+
+                    let source = db.get_contract_src(&contract_identifier)?;
+                    let proof = if with_proof {
+                        db.get_with_proof::<ContractCommitment>(&contract_commit_key)
+                            .map(|(_, b)| Some(format!("0x{}", &b.to_hex())))
+                            .expect("BUG: obtained contract commit, but couldn't get its proof")
+                    } else {
+                        None
+                    };
+
+                    let data = ContractSrcResponse {
+                        source,
+                        publish_height: contract_commit.block_height,
+                        marf_proof: proof,
+                    };
+                    cache.insert(etag.clone(), data.clone());
+                    Some((etag, Some(data)))
+                })
+            }) {
+                Ok(Some(Some((etag, Some(data))))) => HttpResponseType::GetContractSrc(
+                    response_metadata
+                        .with_etag(etag)
+                        .with_content_encoding(req.metadata().accept_encoding),
+                    data,
+                ),
+                Ok(Some(Some((etag, None)))) => {
+                    HttpResponseType::NotModified(response_metadata.with_etag(etag))
                 }
-                Err(e) => {
-                    error!(
-                        "Failed to query sortition ID by consensus '{}'",
-                        consensus_hash
-                    );
-                    (
-                        HttpResponseType::ServerError(
-                            response_metadata,
-                            format!(
-                                "Failed to query sortition ID for consensus hash '{}': {:?}",
-                                consensus_hash, &e
-                            ),
-                        ),
-                        false,
-                    )
+                Ok(Some(None)) => HttpResponseType::NotFound(
+                    response_metadata,
+                    "No contract source data found".into(),
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
                 }
             };
 
-        response.send(http, fd).and_then(|_| Ok(accepted))
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a microblock.  Directly submit it to the microblock store so the client can see any
-    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
-    /// whether or not the microblock was accepted (and thus needs to be forwarded) in the return
-    /// value.
-    fn handle_post_microblock<W: Write>(
+    /// Handle a GET to fetch whether or not a contract implements a certain trait
+    fn handle_get_is_trait_implemented<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        consensus_hash: &ConsensusHash,
-        block_hash: &BlockHeaderHash,
+        sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
-        microblock: &StacksMicroblock,
-    ) -> Result<bool, net_error> {
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        trait_id: &TraitIdentifier,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let (response, accepted) =
-            match chainstate.preprocess_streamed_microblock(consensus_hash, block_hash, microblock)
-            {
-                Ok(accepted) => {
-                    if accepted {
-                        debug!(
-                            "Accepted uploaded microblock {}/{}-{}",
-                            &consensus_hash,
-                            &block_hash,
-                            &microblock.block_hash()
-                        );
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|db| {
+                    let analysis = db.load_contract_analysis(&contract_identifier)?;
+                    if analysis.implemented_traits.contains(trait_id) {
+                        Some(GetIsTraitImplementedResponse {
+                            is_implemented: true,
+                        })
                     } else {
-                        debug!(
-                            "Did not accept microblock {}/{}-{}",
-                            &consensus_hash,
-                            &block_hash,
-                            &microblock.block_hash()
-                        );
+                        let trait_defining_contract =
+                            db.load_contract_analysis(&trait_id.contract_identifier)?;
+                        let trait_definition =
+                            trait_defining_contract.get_defined_trait(&trait_id.name)?;
+                        let is_implemented = analysis
+                            .check_trait_compliance(trait_id, trait_definition)
+                            .is_ok();
+                        Some(GetIsTraitImplementedResponse { is_implemented })
                     }
-
-                    (
-                        HttpResponseType::MicroblockHash(
-                            response_metadata,
-                            microblock.block_hash(),
-                        ),
-                        accepted,
-                    )
+                })
+            }) {
+                Ok(Some(Some(data))) => {
+                    HttpResponseType::GetIsTraitImplemented(response_metadata, data)
                 }
-                Err(e) => (
-                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json()),
-                    false,
+                Ok(Some(None)) => HttpResponseType::NotFound(
+                    response_metadata,
+                    "No contract analysis found or trait definition not found".into(),
                 ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
             };
 
-        response.send(http, fd).and_then(|_| Ok(accepted))
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a request for mempool transactions in bulk
-    fn handle_mempool_query<W: Write>(
+    /// Handle a GET to fetch a contract's analysis data, given the chain tip.  Note that this isn't
+    /// something that's anchored to the blockchain, and can be different across different versions
+    /// of Stacks -- callers must trust the Stacks node to return correct analysis data.
+    /// Callers who don't trust the Stacks node should just fetch the contract source
+    /// code and analyze it offline.
+    fn handle_get_contract_abi<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
-        chainstate: &StacksChainState,
-        query: MemPoolSyncData,
-        max_txs: u64,
-        page_id: Option<Txid>,
-    ) -> Result<StreamCursor, net_error> {
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let response = HttpResponseType::MemPoolTxStream(response_metadata);
-        let height = chainstate
-            .get_stacks_chain_tip(sortdb)?
-            .map(|blk| blk.height)
-            .unwrap_or(0);
-
-        debug!(
-            "Begin mempool query";
-            "page_id" => %page_id.map(|txid| format!("{}", &txid)).unwrap_or("(none".to_string()),
-            "block_height" => height,
-            "max_txs" => max_txs
-        );
-
-        let stream = StreamCursor::new_tx_stream(query, max_txs, height, page_id);
-        response.send(http, fd).and_then(|_| Ok(stream))
-    }
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
-    /// Handle an external HTTP request.
-    /// Some requests, such as those for blocks, will create new reply streams.  This method adds
-    /// those new streams into the `reply_streams` set.
-    /// Returns a StacksMessageType option -- it's Some(...) if we need to forward a message to the
-    /// peer network (like a transaction or a block or microblock)
-    pub fn handle_request(
-        &mut self,
-        req: HttpRequestType,
-        network: &mut PeerNetwork,
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_analysis_db_readonly(|db| {
+                    let contract = db.load_contract(&contract_identifier)?;
+                    contract.contract_interface
+                })
+            }) {
+                Ok(Some(Some(data))) => HttpResponseType::GetContractABI(response_metadata, data),
+                Ok(Some(None)) => HttpResponseType::NotFound(
+                    response_metadata,
+                    "No contract interface data found".into(),
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    fn handle_get_contract_storage<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
-        mempool: &mut MemPoolDB,
-        handler_opts: &RPCHandlerArgs,
-    ) -> Result<Option<StacksMessageType>, net_error> {
-        let mut reply = self.connection.make_relay_handle(self.conn_id)?;
-        let keep_alive = req.metadata().keep_alive;
-        let mut ret = None;
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new((*contract_addr).into(), contract_name.clone());
 
-        let stream_opt = match req {
-            HttpRequestType::GetInfo(ref _md) => {
-                ConversationHttp::handle_getinfo(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    network,
-                    chainstate,
-                    handler_opts,
-                )?;
-                None
-            }
-            HttpRequestType::GetPoxInfo(ref _md, ref tip_req) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_getpoxinfo(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        &network.burnchain,
-                    )?;
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_analysis_db_readonly(|db| {
+                    db.get_storage_layout(&contract_identifier).ok()
+                })
+            }) {
+                Ok(Some(Some(layout))) => {
+                    HttpResponseType::GetContractStorage(response_metadata, layout.into())
                 }
-                None
-            }
-            HttpRequestType::GetNeighbors(ref _md) => {
-                ConversationHttp::handle_getneighbors(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    network,
-                )?;
-                None
-            }
-            HttpRequestType::GetHeaders(ref _md, ref quantity, ref tip_req) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_getheaders(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        &tip,
-                        *quantity,
-                        chainstate,
-                    )?
-                } else {
-                    None
+                Ok(Some(None)) => {
+                    HttpResponseType::NotFound(response_metadata, "No contract found".into())
                 }
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a POST to check a transaction's post-conditions against the asset events it
+    /// (would) emit.  This needs no chain state, so it works just as well on events from a
+    /// simulated run as on those from a mined transaction.
+    fn handle_post_postconditions_check<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tx: &StacksTransaction,
+        events: &[StacksTransactionEvent],
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let response = match postconditions::asset_map_from_events(events) {
+            Ok(asset_map) => {
+                let verdict = postconditions::check_post_conditions(
+                    &tx.post_conditions,
+                    &tx.post_condition_mode,
+                    &PrincipalData::from(tx.origin_address()),
+                    &asset_map,
+                );
+                HttpResponseType::PostConditionCheck(
+                    response_metadata,
+                    PostConditionCheckResponse {
+                        passed: verdict.is_ok(),
+                        reason: verdict.as_ref().err().map(|failure| failure.to_string()),
+                        failure: verdict.err().map(|failure| failure.json_serialize()),
+                    },
+                )
             }
-            HttpRequestType::GetBlock(ref _md, ref index_block_hash) => {
-                ConversationHttp::handle_getblock(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    index_block_hash,
-                    chainstate,
-                )?
-            }
-            HttpRequestType::GetMicroblocksIndexed(ref _md, ref index_head_hash) => {
-                ConversationHttp::handle_getmicroblocks_indexed(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    index_head_hash,
-                    chainstate,
-                )?
+            Err(e) => HttpResponseType::BadRequest(
+                response_metadata,
+                format!("Failed to total asset events: {}", e),
+            ),
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET unconfirmed microblock stream.  Start streaming the reply.
+    /// The response's preamble (but not the block data) will be synchronously written to the fd
+    /// (so use a fd that can buffer!)
+    /// Return a StreamCursor struct for the block that we're sending, so we can continue to
+    /// make progress sending it.
+    fn handle_getmicroblocks_unconfirmed<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        index_anchor_block_hash: &StacksBlockId,
+        min_seq: u16,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<StreamCursor>, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        // do we have this unconfirmed microblock stream?
+        match chainstate.has_any_staging_microblock_indexed(index_anchor_block_hash, min_seq) {
+            Ok(false) => {
+                // nope
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    format!(
+                        "No such unconfirmed microblock stream for {} at or after {}",
+                        index_anchor_block_hash.to_hex(),
+                        min_seq
+                    ),
+                );
+                response.send(http, fd).and_then(|_| Ok(None))
             }
-            HttpRequestType::GetMicroblocksConfirmed(ref _md, ref anchor_index_block_hash) => {
-                ConversationHttp::handle_getmicroblocks_confirmed(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    anchor_index_block_hash,
-                    chainstate,
-                )?
+            Err(e) => {
+                // nope
+                warn!(
+                    "Failed to serve confirmed microblock stream {:?}: {:?}",
+                    req, &e
+                );
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    format!(
+                        "Failed to query unconfirmed microblock stream for {} at or after {}",
+                        index_anchor_block_hash.to_hex(),
+                        min_seq
+                    ),
+                );
+                response.send(http, fd).and_then(|_| Ok(None))
             }
-            HttpRequestType::GetMicroblocksUnconfirmed(
-                ref _md,
-                ref index_anchor_block_hash,
-                ref min_seq,
-            ) => ConversationHttp::handle_getmicroblocks_unconfirmed(
-                &mut self.connection.protocol,
-                &mut reply,
-                &req,
-                index_anchor_block_hash,
-                *min_seq,
-                chainstate,
-            )?,
-            HttpRequestType::GetTransactionUnconfirmed(ref _md, ref txid) => {
-                ConversationHttp::handle_gettransaction_unconfirmed(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
+            Ok(true) => {
+                // yup! start streaming it back
+                let (response, stream_opt) = match StreamCursor::new_microblock_unconfirmed(
                     chainstate,
-                    mempool,
-                    txid,
-                )?;
-                None
+                    index_anchor_block_hash.clone(),
+                    min_seq,
+                ) {
+                    Ok(stream) => (
+                        HttpResponseType::MicroblockStream(
+                            response_metadata.with_content_encoding(req.metadata().accept_encoding),
+                        ),
+                        Some(stream),
+                    ),
+                    Err(chain_error::NoSuchBlockError) => (
+                        HttpResponseType::NotFound(
+                            response_metadata,
+                            format!(
+                                "No such unconfirmed microblock stream starting with {}",
+                                index_anchor_block_hash.to_hex()
+                            ),
+                        ),
+                        None,
+                    ),
+                    Err(_e) => {
+                        debug!(
+                            "Failed to load unconfirmed microblock stream {}: {:?}",
+                            &index_anchor_block_hash, &_e
+                        );
+                        (
+                            HttpResponseType::ServerError(
+                                response_metadata,
+                                format!(
+                                    "Failed to query unconfirmed microblock stream {}",
+                                    index_anchor_block_hash.to_hex()
+                                ),
+                            ),
+                            None,
+                        )
+                    }
+                };
+                response.send(http, fd).and_then(|_| Ok(stream_opt))
             }
-            HttpRequestType::GetAccount(ref _md, ref principal, ref tip_req, ref with_proof) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
+        }
+    }
+
+    /// Handle a GET unconfirmed transaction.
+    /// The response will be synchronously written to the fd.
+    fn handle_gettransaction_unconfirmed<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
+        txid: &Txid,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        // present in the unconfirmed state?
+        if let Some(ref unconfirmed) = chainstate.unconfirmed_state.as_ref() {
+            if let Some((transaction, mblock_hash, seq)) =
+                unconfirmed.get_unconfirmed_transaction(txid)
+            {
+                let response = HttpResponseType::UnconfirmedTransaction(
+                    response_metadata,
+                    UnconfirmedTransactionResponse {
+                        status: UnconfirmedTransactionStatus::Microblock {
+                            block_hash: mblock_hash,
+                            seq: seq,
+                        },
+                        tx: to_hex(&transaction.serialize_to_vec()),
+                    },
+                );
+                return response.send(http, fd).map(|_| ());
+            }
+        }
+
+        // present in the mempool?
+        if let Some(txinfo) = MemPoolDB::get_tx(mempool.conn(), txid)? {
+            let response = HttpResponseType::UnconfirmedTransaction(
+                response_metadata,
+                UnconfirmedTransactionResponse {
+                    status: UnconfirmedTransactionStatus::Mempool,
+                    tx: to_hex(&txinfo.tx.serialize_to_vec()),
+                },
+            );
+            return response.send(http, fd).map(|_| ());
+        }
+
+        // not found
+        let response = HttpResponseType::NotFound(
+            response_metadata,
+            format!("No such unconfirmed transaction {}", txid),
+        );
+        return response.send(http, fd).map(|_| ());
+    }
+
+    /// Load up the canonical Stacks chain tip.  Note that this is subject to both burn chain block
+    /// Stacks block availability -- different nodes with different partial replicas of the Stacks chain state
+    /// will return different values here.
+    ///
+    /// # Warn
+    /// - There is a potential race condition. If this function is loading the latest unconfirmed
+    /// tip, that tip may get invalidated by the time it is used in `maybe_read_only_clarity_tx`,
+    /// which is used to load clarity state at a particular tip (which would lead to a 404 error).
+    /// If this race condition occurs frequently, we can modify `maybe_read_only_clarity_tx` to
+    /// re-load the unconfirmed chain tip. Refer to issue #2997.
+    ///
+    /// # Inputs
+    /// - `tip_req` is given by the HTTP request as the optional query parameter for the chain tip
+    /// hash.  It will be UseLatestAnchoredTip if there was no parameter given. If it is set to
+    /// `latest`, the parameter will be set to UseLatestUnconfirmedTip.
+    fn handle_load_stacks_chain_tip<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tip_req: &TipRequest,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<Option<StacksBlockId>, net_error> {
+        match tip_req {
+            TipRequest::UseLatestUnconfirmedTip => {
+                let unconfirmed_chain_tip_opt = match &mut chainstate.unconfirmed_state {
+                    Some(unconfirmed_state) => {
+                        match unconfirmed_state.get_unconfirmed_state_if_exists() {
+                            Ok(res) => res,
+                            Err(msg) => {
+                                let response_metadata = HttpResponseMetadata::from(req);
+                                let response = HttpResponseType::NotFound(response_metadata, msg);
+                                return response.send(http, fd).and_then(|_| Ok(None));
+                            }
+                        }
+                    }
+                    None => None,
+                };
+
+                if let Some(unconfirmed_chain_tip) = unconfirmed_chain_tip_opt {
+                    Ok(Some(unconfirmed_chain_tip))
+                } else {
+                    match chainstate.get_stacks_chain_tip(sortdb)? {
+                        Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
+                            &tip.consensus_hash,
+                            &tip.anchored_block_hash,
+                        ))),
+                        None => {
+                            let response_metadata = HttpResponseMetadata::from(req);
+                            warn!("Failed to load Stacks chain tip");
+                            let response = HttpResponseType::NotFound(
+                                response_metadata,
+                                format!("Failed to load Stacks chain tip"),
+                            );
+                            response.send(http, fd).and_then(|_| Ok(None))
+                        }
+                    }
+                }
+            }
+            TipRequest::SpecificTip(tip) => Ok(Some(*tip).clone()),
+            TipRequest::UseLatestAnchoredTip => match chainstate.get_stacks_chain_tip(sortdb)? {
+                Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
+                ))),
+                None => {
+                    let response_metadata = HttpResponseMetadata::from(req);
+                    warn!("Failed to load Stacks chain tip");
+                    let response = HttpResponseType::ServerError(
+                        response_metadata,
+                        format!("Failed to load Stacks chain tip"),
+                    );
+                    response.send(http, fd).and_then(|_| Ok(None))
+                }
+            },
+        }
+    }
+
+    fn handle_load_stacks_chain_tip_hashes<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tip: StacksBlockId,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, net_error> {
+        match chainstate.get_block_header_hashes(&tip)? {
+            Some((ch, bl)) => {
+                return Ok(Some((ch, bl)));
+            }
+            None => {}
+        }
+
+        let response_metadata = HttpResponseMetadata::from(req);
+        warn!("Failed to load Stacks chain tip");
+        let response = HttpResponseType::ServerError(
+            response_metadata,
+            format!("Failed to load Stacks chain tip"),
+        );
+        response.send(http, fd).and_then(|_| Ok(None))
+    }
+
+    fn handle_post_fee_rate_estimate<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        handler_args: &RPCHandlerArgs,
+        sortdb: &SortitionDB,
+        subject: &FeeRateEstimateSubject,
+        estimated_len: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        let stacks_epoch = SortitionDB::get_stacks_epoch(sortdb.conn(), tip.block_height)?
+                .ok_or_else(|| {
+                    warn!(
+                        "Failed to get fee rate estimate because could not load Stacks epoch for canonical burn height = {}",
+                        tip.block_height
+                    );
+                    net_error::ChainstateError("Could not load Stacks epoch for canonical burn height".into())
+                })?;
+        if let Some((cost_estimator, fee_estimator, metric)) = handler_args.get_estimators_ref() {
+            let estimated_cost = match subject {
+                FeeRateEstimateSubject::Payload(tx) => {
+                    match cost_estimator.estimate_cost(tx, &stacks_epoch.epoch_id) {
+                        Ok(x) => x,
+                        Err(e) => {
+                            debug!(
+                                "Estimator RPC endpoint failed to estimate tx: {}",
+                                tx.name()
+                            );
+                            return HttpResponseType::BadRequestJSON(
+                                response_metadata,
+                                e.into_json(),
+                            )
+                            .send(http, fd);
+                        }
+                    }
+                }
+                FeeRateEstimateSubject::Cost(cost) => cost.clone(),
+            };
+
+            let scalar_cost =
+                metric.from_cost_and_len(&estimated_cost, &stacks_epoch.block_limit, estimated_len);
+            let fee_rates = match fee_estimator.get_rate_estimates() {
+                Ok(x) => x,
+                Err(e) => {
+                    debug!(
+                        "Estimator RPC endpoint failed to estimate fees for cost: {}",
+                        &estimated_cost
+                    );
+                    return HttpResponseType::BadRequestJSON(response_metadata, e.into_json())
+                        .send(http, fd);
+                }
+            };
+
+            // prefer the per-dimension fee rates, if the fee estimator tracks them
+            let dimension_fee_rates = fee_estimator.get_dimension_rate_estimates().ok();
+            let mut estimations = match dimension_fee_rates {
+                Some(ref dimension_fee_rates) => {
+                    let proportions = dimension_proportions(
+                        metric,
+                        &estimated_cost,
+                        &stacks_epoch.block_limit,
+                        estimated_len,
+                    );
+                    RPCFeeEstimate::estimate_fees_by_dimension(
+                        scalar_cost,
+                        dimension_fee_rates.estimate_fees(&proportions),
+                    )
+                }
+                None => RPCFeeEstimate::estimate_fees(scalar_cost, fee_rates),
+            };
+
+            let minimum_fee = estimated_len * MINIMUM_TX_FEE_RATE_PER_BYTE;
+
+            for estimate in estimations.iter_mut() {
+                if estimate.fee < minimum_fee {
+                    estimate.fee = minimum_fee;
+                }
+            }
+
+            let response = HttpResponseType::TransactionFeeEstimation(
+                response_metadata,
+                RPCFeeEstimateResponse {
+                    estimated_cost,
+                    estimations,
+                    estimated_cost_scalar: scalar_cost,
+                    cost_scalar_change_by_byte: metric.change_per_byte(),
+                    dimension_fee_rates,
+                },
+            );
+            response.send(http, fd)
+        } else {
+            debug!("Fee and cost estimation not configured on this stacks node");
+            let response = HttpResponseType::BadRequestJSON(
+                response_metadata,
+                json!({
+                    "error": "Fee and Cost Estimation not configured on this Stacks node",
+                    "reason": "CostEstimationDisabled",
+                }),
+            );
+            response.send(http, fd)
+        }
+    }
+
+    /// Build the admission report for a POSTed transaction.  `rejection` is the mempool's verdict
+    /// on it, and decides whether it was accepted; the rest of the report lays out the nonces it
+    /// uses against the chain tip, its fee against the mempool minimum, its estimated cost and
+    /// fee rate, and its post-conditions against the outflows known without running it.
+    fn make_transaction_admission_report(
+        chainstate: &mut StacksChainState,
+        sortdb: &SortitionDB,
+        mempool: &MemPoolDB,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        tx: &StacksTransaction,
+        already_in_mempool: bool,
+        rejection: Option<MemPoolRejection>,
+    ) -> Result<RPCTransactionAdmissionReport, net_error> {
+        let txid = tx.txid();
+        let tip = StacksBlockHeader::make_index_block_hash(consensus_hash, block_hash);
+        let accounts =
+            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), &tip, |clarity_tx| {
+                let origin = StacksChainState::get_account(clarity_tx, &tx.origin_address().into());
+                let sponsor = tx
+                    .sponsor_address()
+                    .map(|addr| StacksChainState::get_account(clarity_tx, &addr.into()));
+                (origin, sponsor)
+            })?;
+
+        let (origin_nonce, sponsor_nonce) = match accounts {
+            Some((origin, sponsor)) => (
+                Some(RPCTransactionNonceCheck {
+                    address: origin.principal.to_string(),
+                    expected: origin.nonce,
+                    actual: tx.get_origin().nonce(),
+                }),
+                sponsor.map(|sponsor| RPCTransactionNonceCheck {
+                    address: sponsor.principal.to_string(),
+                    expected: sponsor.nonce,
+                    actual: tx.get_payer().nonce(),
+                }),
+            ),
+            None => (None, None),
+        };
+
+        let tx_len = tx.tx_len();
+        let fee = tx.get_tx_fee();
+        let burn_tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        let stacks_epoch = sortdb
+            .index_conn()
+            .get_stacks_epoch(burn_tip.block_height as u32);
+
+        let (estimated_cost, estimated_fee_rate) = match stacks_epoch {
+            Some(stacks_epoch) => (
+                mempool.estimate_tx_cost(tx, &stacks_epoch.epoch_id).ok(),
+                mempool
+                    .estimate_tx_fee_rate(tx, &stacks_epoch.block_limit, &stacks_epoch.epoch_id)
+                    .ok(),
+            ),
+            None => (None, None),
+        };
+
+        // only an STX transfer's outflow is known before it runs
+        let origin = PrincipalData::from(tx.origin_address());
+        let verdict = match tx.payload {
+            TransactionPayload::TokenTransfer(_, amount, _) => {
+                let mut asset_map = AssetMap::new();
+                asset_map
+                    .add_stx_transfer(&origin, amount as u128)
+                    .ok()
+                    .map(|_| {
+                        postconditions::check_post_conditions(
+                            &tx.post_conditions,
+                            &tx.post_condition_mode,
+                            &origin,
+                            &asset_map,
+                        )
+                    })
+            }
+            _ => None,
+        };
+
+        Ok(RPCTransactionAdmissionReport {
+            txid: txid.clone(),
+            accepted: rejection.is_none(),
+            already_in_mempool,
+            origin_nonce,
+            sponsor_nonce,
+            fee: RPCTransactionFeeCheck {
+                fee,
+                minimum_fee: cmp::max(MINIMUM_TX_FEE, tx_len * MINIMUM_TX_FEE_RATE_PER_BYTE),
+                tx_len,
+                estimated_fee_rate,
+            },
+            estimated_cost,
+            post_conditions: RPCTransactionPostConditionCheck {
+                mode: match tx.post_condition_mode {
+                    TransactionPostConditionMode::Allow => "allow".to_string(),
+                    TransactionPostConditionMode::Deny => "deny".to_string(),
+                },
+                count: tx.post_conditions.len(),
+                passed: verdict.as_ref().map(|verdict| verdict.is_ok()),
+                failure: verdict
+                    .and_then(|verdict| verdict.err())
+                    .map(|failure| failure.json_serialize()),
+            },
+            rejection: rejection.map(|e| e.into_json(&txid)),
+        })
+    }
+
+    /// Handle a transaction.  Directly submit it to the mempool so the client can see any
+    /// rejection reasons up-front (different from how the peer network handles it).  If
+    /// `with_report` is set, answer with an admission report instead of a bare txid or error.
+    /// Indicate whether or not the transaction was accepted (and thus needs to be forwarded) in
+    /// the return value.
+    fn handle_post_transaction<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &mut StacksChainState,
+        sortdb: &SortitionDB,
+        consensus_hash: ConsensusHash,
+        block_hash: BlockHeaderHash,
+        mempool: &mut MemPoolDB,
+        tx: StacksTransaction,
+        atlasdb: &mut AtlasDB,
+        attachment: Option<Attachment>,
+        event_observer: Option<&dyn MemPoolEventDispatcher>,
+        with_report: bool,
+    ) -> Result<bool, net_error> {
+        let txid = tx.txid();
+        let response_metadata = HttpResponseMetadata::from(req);
+        let already_in_mempool = mempool.has_tx(&txid);
+        let submit_result = if already_in_mempool {
+            debug!("Mempool already has POSTed transaction {}", &txid);
+            Ok(())
+        } else {
+            let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+            let stacks_epoch = sortdb
+                .index_conn()
+                .get_stacks_epoch(tip.block_height as u32)
+                .ok_or_else(|| {
+                    warn!(
+                        "Failed to store transaction because could not load Stacks epoch for canonical burn height = {}",
+                        tip.block_height
+                    );
+                    net_error::ChainstateError("Could not load Stacks epoch for canonical burn height".into())
+                })?;
+
+            let result = mempool.submit(
+                chainstate,
+                &consensus_hash,
+                &block_hash,
+                &tx,
+                event_observer,
+                &stacks_epoch.block_limit,
+                &stacks_epoch.epoch_id,
+            );
+            match result {
+                Ok(_) => debug!("Mempool accepted POSTed transaction {}", &txid),
+                Err(ref e) => debug!("Mempool rejected POSTed transaction {}: {:?}", &txid, e),
+            }
+            result
+        };
+        let accepted = !already_in_mempool && submit_result.is_ok();
+
+        let response = if with_report {
+            let report = ConversationHttp::make_transaction_admission_report(
+                chainstate,
+                sortdb,
+                mempool,
+                &consensus_hash,
+                &block_hash,
+                &tx,
+                already_in_mempool,
+                submit_result.err(),
+            )?;
+            HttpResponseType::TransactionAdmissionReport(response_metadata, report)
+        } else {
+            match submit_result {
+                Ok(_) => HttpResponseType::TransactionID(response_metadata, txid),
+                Err(e) => HttpResponseType::BadRequestJSON(response_metadata, e.into_json(&txid)),
+            }
+        };
+
+        if let Some(ref attachment) = attachment {
+            if let TransactionPayload::ContractCall(ref contract_call) = tx.payload {
+                if atlasdb
+                    .should_keep_attachment(&contract_call.to_clarity_contract_id(), &attachment)
+                {
+                    atlasdb
+                        .insert_uninstantiated_attachment(attachment)
+                        .map_err(|e| net_error::DBError(e))?;
+                }
+            }
+        }
+
+        response.send(http, fd).and_then(|_| Ok(accepted))
+    }
+
+    /// Handle a block.  Directly submit a Stacks block to this node's chain state.
+    /// Indicate whether or not the block was accepted (i.e. it was new, and valid)
+    fn handle_post_block<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        consensus_hash: &ConsensusHash,
+        block: &StacksBlock,
+    ) -> Result<bool, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        // is this a consensus hash we recognize?
+        let (response, accepted) =
+            match SortitionDB::get_sortition_id_by_consensus(&sortdb.conn(), consensus_hash) {
+                Ok(Some(_)) => {
+                    // we recognize this consensus hash
+                    let ic = sortdb.index_conn();
+                    match Relayer::process_new_anchored_block(
+                        &ic,
+                        chainstate,
+                        consensus_hash,
+                        block,
+                        0,
+                    ) {
+                        Ok(true) => {
+                            debug!(
+                                "Accepted Stacks block {}/{}",
+                                consensus_hash,
+                                &block.block_hash()
+                            );
+                            (
+                                HttpResponseType::StacksBlockAccepted(
+                                    response_metadata,
+                                    StacksBlockHeader::make_index_block_hash(
+                                        consensus_hash,
+                                        &block.block_hash(),
+                                    ),
+                                    true,
+                                ),
+                                true,
+                            )
+                        }
+                        Ok(false) => {
+                            debug!(
+                                "Did not accept Stacks block {}/{}",
+                                consensus_hash,
+                                &block.block_hash()
+                            );
+                            (
+                                HttpResponseType::StacksBlockAccepted(
+                                    response_metadata,
+                                    StacksBlockHeader::make_index_block_hash(
+                                        consensus_hash,
+                                        &block.block_hash(),
+                                    ),
+                                    false,
+                                ),
+                                false,
+                            )
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to process anchored block {}/{}: {:?}",
+                                consensus_hash,
+                                &block.block_hash(),
+                                &e
+                            );
+                            (
+                                HttpResponseType::ServerError(
+                                    response_metadata,
+                                    format!(
+                                        "Failed to process anchored block {}/{}: {:?}",
+                                        consensus_hash,
+                                        &block.block_hash(),
+                                        &e
+                                    ),
+                                ),
+                                false,
+                            )
+                        }
+                    }
+                }
+                Ok(None) => {
+                    debug!(
+                        "Unrecognized consensus hash {} for block {}",
+                        consensus_hash,
+                        &block.block_hash()
+                    );
+                    (
+                        HttpResponseType::NotFound(
+                            response_metadata,
+                            format!("No such consensus hash '{}'", consensus_hash),
+                        ),
+                        false,
+                    )
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to query sortition ID by consensus '{}'",
+                        consensus_hash
+                    );
+                    (
+                        HttpResponseType::ServerError(
+                            response_metadata,
+                            format!(
+                                "Failed to query sortition ID for consensus hash '{}': {:?}",
+                                consensus_hash, &e
+                            ),
+                        ),
+                        false,
+                    )
+                }
+            };
+
+        response.send(http, fd).and_then(|_| Ok(accepted))
+    }
+
+    /// Handle a microblock.  Directly submit it to the microblock store so the client can see any
+    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
+    /// whether or not the microblock was accepted (and thus needs to be forwarded) in the return
+    /// value.
+    fn handle_post_microblock<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        chainstate: &mut StacksChainState,
+        microblock: &StacksMicroblock,
+    ) -> Result<bool, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let (response, accepted) =
+            match chainstate.preprocess_streamed_microblock(consensus_hash, block_hash, microblock)
+            {
+                Ok(accepted) => {
+                    if accepted {
+                        debug!(
+                            "Accepted uploaded microblock {}/{}-{}",
+                            &consensus_hash,
+                            &block_hash,
+                            &microblock.block_hash()
+                        );
+                    } else {
+                        debug!(
+                            "Did not accept microblock {}/{}-{}",
+                            &consensus_hash,
+                            &block_hash,
+                            &microblock.block_hash()
+                        );
+                    }
+
+                    (
+                        HttpResponseType::MicroblockHash(
+                            response_metadata,
+                            microblock.block_hash(),
+                        ),
+                        accepted,
+                    )
+                }
+                Err(e) => (
+                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json()),
+                    false,
+                ),
+            };
+
+        response.send(http, fd).and_then(|_| Ok(accepted))
+    }
+
+    /// Handle a request for mempool transactions in bulk
+    fn handle_mempool_query<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        query: MemPoolSyncData,
+        max_txs: u64,
+        page_id: Option<Txid>,
+    ) -> Result<StreamCursor, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let response = HttpResponseType::MemPoolTxStream(
+            response_metadata.with_content_encoding(req.metadata().accept_encoding),
+        );
+        let height = chainstate
+            .get_stacks_chain_tip(sortdb)?
+            .map(|blk| blk.height)
+            .unwrap_or(0);
+
+        debug!(
+            "Begin mempool query";
+            "page_id" => %page_id.map(|txid| format!("{}", &txid)).unwrap_or("(none".to_string()),
+            "block_height" => height,
+            "max_txs" => max_txs
+        );
+
+        let stream = StreamCursor::new_tx_stream(query, max_txs, height, page_id);
+        response.send(http, fd).and_then(|_| Ok(stream))
+    }
+
+    /// Handle an external HTTP request.
+    /// Some requests, such as those for blocks, will create new reply streams.  This method adds
+    /// those new streams into the `reply_streams` set.
+    /// Returns a StacksMessageType option -- it's Some(...) if we need to forward a message to the
+    /// peer network (like a transaction or a block or microblock)
+    pub fn handle_request(
+        &mut self,
+        req: HttpRequestType,
+        network: &mut PeerNetwork,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        handler_opts: &RPCHandlerArgs,
+    ) -> Result<Option<StacksMessageType>, net_error> {
+        let mut req = req;
+        let preflight = matches!(req, HttpRequestType::OptionsPreflight(..));
+        let cors = self
+            .connection
+            .options
+            .cors_headers(req.metadata().origin.as_deref(), preflight);
+        req.metadata_mut().cors = cors;
+
+        let mut reply = self.connection.make_relay_handle(self.conn_id)?;
+        let keep_alive = req.metadata().keep_alive;
+        let stream_encoding = req.metadata().accept_encoding;
+        let mut ret = None;
+
+        // admin endpoints answer only to the admin API key, so they skip the rate limiter.
+        // Guessing at the key is throttled instead.
+        if req.is_admin() {
+            let client_ip = self.peer_addr.ip();
+            let now_ms = get_epoch_time_ms();
+            let refusal = match self.connection.options.rpc_admin_api_key {
+                None => Some(HttpResponseType::Forbidden(
+                    HttpResponseMetadata::from(&req),
+                    "Admin endpoints are disabled".to_string(),
+                )),
+                Some(ref admin_key) => {
+                    match network.rpc_rate_limiter.check_admin(&client_ip, now_ms) {
+                        Err(retry_after) => Some(HttpResponseType::TooManyRequests(
+                            HttpResponseMetadata::from(&req).with_retry_after(retry_after),
+                            "Too many failed admin requests".to_string(),
+                        )),
+                        Ok(())
+                            if !api_key_matches(req.metadata().api_key.as_deref(), admin_key) =>
+                        {
+                            network
+                                .rpc_rate_limiter
+                                .record_admin_auth_failure(&client_ip, now_ms);
+                            Some(HttpResponseType::Unauthorized(
+                                HttpResponseMetadata::from(&req),
+                                "The admin API key is required".to_string(),
+                            ))
+                        }
+                        Ok(()) => None,
+                    }
+                }
+            };
+            if let Some(response) = refusal {
+                debug!("Refusing admin HTTP request from {:?}", &self.peer_addr);
+                monitoring::increment_rpc_requests_refused_counter("admin");
+                response.send(&mut self.connection.protocol, &mut reply)?;
+                self.reply_streams.push_back((reply, None, keep_alive));
+                return Ok(None);
+            }
+        }
+
+        // CORS preflights can't carry an API key, and neither can most load balancers' health
+        // probes.  All of these are cheap to answer.
+        let admission = match req {
+            HttpRequestType::OptionsPreflight(..)
+            | HttpRequestType::GetHealthLive(..)
+            | HttpRequestType::GetHealthReady(..) => Ok(()),
+            _ if req.is_admin() => Ok(()),
+            _ => network.rpc_rate_limiter.check(
+                &self.peer_addr.ip(),
+                req.metadata().api_key.as_deref(),
+                get_epoch_time_ms(),
+            ),
+        };
+        if let Err(refusal) = admission {
+            debug!(
+                "Refusing HTTP request from {:?}: {:?}",
+                &self.peer_addr, &refusal
+            );
+            monitoring::increment_rpc_requests_refused_counter(refusal.reason());
+            let response_metadata = HttpResponseMetadata::from(&req);
+            let response = match refusal {
+                RPCRefusal::MissingKey => HttpResponseType::Unauthorized(
+                    response_metadata,
+                    "An API key is required".to_string(),
+                ),
+                RPCRefusal::UnknownKey => {
+                    HttpResponseType::Unauthorized(response_metadata, "Unknown API key".to_string())
+                }
+                RPCRefusal::KeyRateLimited(retry_after)
+                | RPCRefusal::IPRateLimited(retry_after) => HttpResponseType::TooManyRequests(
+                    response_metadata.with_retry_after(retry_after),
+                    "Too many requests".to_string(),
+                ),
+            };
+            response.send(&mut self.connection.protocol, &mut reply)?;
+            self.reply_streams.push_back((reply, None, keep_alive));
+            return Ok(None);
+        }
+
+        if req.is_write()
+            && self.connection.options.rpc_tls_require_client_cert
+            && !self.client_authenticated
+        {
+            let response = HttpResponseType::Forbidden(
+                HttpResponseMetadata::from(&req),
+                "A client certificate is required to submit data".to_string(),
+            );
+            response.send(&mut self.connection.protocol, &mut reply)?;
+            self.reply_streams.push_back((reply, None, keep_alive));
+            return Ok(None);
+        }
+
+        let stream_opt = match req {
+            HttpRequestType::GetInfo(ref _md) => {
+                ConversationHttp::handle_getinfo(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                    chainstate,
+                    handler_opts,
+                )?;
+                None
+            }
+            HttpRequestType::GetPoxInfo(ref _md, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_getpoxinfo(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        &network.burnchain,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetPoxCycle(ref _md, reward_cycle, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_pox_cycle(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        &network.burnchain,
+                        reward_cycle,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetPoxRewardSet(ref _md, reward_cycle) => {
+                ConversationHttp::handle_get_pox_reward_set(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    &network.burnchain,
+                    reward_cycle,
+                )?;
+                None
+            }
+            HttpRequestType::GetPoxStacker(ref _md, ref stacker, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_pox_stacker(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        stacker,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetNeighbors(ref _md) => {
+                ConversationHttp::handle_getneighbors(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                )?;
+                None
+            }
+            HttpRequestType::GetTelemetry(ref _md) => {
+                ConversationHttp::handle_gettelemetry(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                    handler_opts,
+                )?;
+                None
+            }
+            HttpRequestType::GetHeaders(ref _md, ref quantity, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_getheaders(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        &tip,
+                        *quantity,
+                        chainstate,
+                    )?
+                } else {
+                    None
+                }
+            }
+            HttpRequestType::GetBlock(ref _md, ref index_block_hash) => {
+                ConversationHttp::handle_getblock(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    index_block_hash,
+                    chainstate,
+                )?
+            }
+            HttpRequestType::GetBlockExport(ref _md, ref start_height, ref count) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &TipRequest::UseLatestAnchoredTip,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_getblock_export(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        &tip,
+                        *start_height,
+                        *count,
+                        chainstate,
+                    )?
+                } else {
+                    None
+                }
+            }
+            HttpRequestType::GetBlockStateDiff(ref _md, ref index_block_hash) => {
+                ConversationHttp::handle_getblock_state_diff(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    index_block_hash,
+                    chainstate,
+                )?;
+                None
+            }
+            HttpRequestType::GetMicroblocksIndexed(ref _md, ref index_head_hash) => {
+                ConversationHttp::handle_getmicroblocks_indexed(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    index_head_hash,
+                    chainstate,
+                )?
+            }
+            HttpRequestType::GetMicroblocksConfirmed(ref _md, ref anchor_index_block_hash) => {
+                ConversationHttp::handle_getmicroblocks_confirmed(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    anchor_index_block_hash,
+                    chainstate,
+                )?
+            }
+            HttpRequestType::GetMicroblocksUnconfirmed(
+                ref _md,
+                ref index_anchor_block_hash,
+                ref min_seq,
+            ) => ConversationHttp::handle_getmicroblocks_unconfirmed(
+                &mut self.connection.protocol,
+                &mut reply,
+                &req,
+                index_anchor_block_hash,
+                *min_seq,
+                chainstate,
+            )?,
+            HttpRequestType::GetMicroblockStream(
+                ref _md,
+                ref index_anchor_block_hash,
+                ref start_seq,
+                ref limit,
+            ) => {
+                ConversationHttp::handle_getmicroblock_stream(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    index_anchor_block_hash,
+                    *start_seq,
+                    *limit,
+                    chainstate,
+                )?;
+                None
+            }
+            HttpRequestType::GetTransactionUnconfirmed(ref _md, ref txid) => {
+                ConversationHttp::handle_gettransaction_unconfirmed(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    chainstate,
+                    mempool,
+                    txid,
+                )?;
+                None
+            }
+            HttpRequestType::GetAccount(ref _md, ref principal, ref tip_req, ref with_proof) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_account_entry(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        principal,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetAccountProof(ref _md, ref principal, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_account_proof(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        principal,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetDataVar(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref var_name,
+                ref tip_req,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_data_var(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        var_name,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetMapEntry(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref map_name,
+                ref key,
+                ref tip_req,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_map_entry(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        map_name,
+                        key,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetMapEntryBatch(
+                ref _md,
+                ref lookups,
+                ref tip_req,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_map_entry_batch(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        lookups,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetTransferCost(ref _md) => {
+                ConversationHttp::handle_token_transfer_cost(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                )?;
+                None
+            }
+            HttpRequestType::GetContractABI(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_abi(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetContractStorage(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_storage(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::PostConditionCheck(ref _md, ref tx, ref events) => {
+                ConversationHttp::handle_post_postconditions_check(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tx,
+                    events,
+                )?;
+                None
+            }
+            HttpRequestType::FeeRateEstimate(ref _md, ref subject, estimated_len) => {
+                ConversationHttp::handle_post_fee_rate_estimate(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    sortdb,
+                    subject,
+                    estimated_len,
+                )?;
+                None
+            }
+            HttpRequestType::CallReadOnlyFunction(
+                ref _md,
+                ref ctrct_addr,
+                ref ctrct_name,
+                ref as_sender,
+                ref func_name,
+                ref args,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_readonly_function_call(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        ctrct_addr,
+                        ctrct_name,
+                        func_name,
+                        as_sender,
+                        args,
+                        &self.connection.options,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::CheckContractCallArgs(
+                ref _md,
+                ref ctrct_addr,
+                ref ctrct_name,
+                ref func_name,
+                ref args,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_check_contract_call_args(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        ctrct_addr,
+                        ctrct_name,
+                        func_name,
+                        args,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::CallReadOnlyFunctionBatch(
+                ref _md,
+                ref as_sender,
+                ref calls,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_readonly_function_call_batch(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        as_sender,
+                        calls,
+                        &self.connection.options,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetContractSrc(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref tip_req,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_src(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &mut network.http_response_cache,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::PostTransaction(ref _md, ref tx, ref attachment, with_report) => {
+                match chainstate.get_stacks_chain_tip(sortdb)? {
+                    Some(tip) => {
+                        let accepted = ConversationHttp::handle_post_transaction(
+                            &mut self.connection.protocol,
+                            &mut reply,
+                            &req,
+                            chainstate,
+                            sortdb,
+                            tip.consensus_hash,
+                            tip.anchored_block_hash,
+                            mempool,
+                            tx.clone(),
+                            &mut network.atlasdb,
+                            attachment.clone(),
+                            handler_opts.event_observer.as_deref(),
+                            with_report,
+                        )?;
+                        if accepted {
+                            // forward to peer network
+                            ret = Some(StacksMessageType::Transaction(tx.clone()));
+                        }
+                    }
+                    None => {
+                        let response_metadata = HttpResponseMetadata::from(&req);
+                        warn!("Failed to load Stacks chain tip");
+                        let response = HttpResponseType::ServerError(
+                            response_metadata,
+                            format!("Failed to load Stacks chain tip"),
+                        );
+                        response.send(&mut self.connection.protocol, &mut reply)?;
+                    }
+                }
+                None
+            }
+            HttpRequestType::GetAttachment(ref _md, ref content_hash) => {
+                ConversationHttp::handle_getattachment(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &mut network.atlasdb,
+                    content_hash.clone(),
+                )?;
+                None
+            }
+            HttpRequestType::GetAttachmentsInv(
+                ref _md,
+                ref index_block_hash,
+                ref pages_indexes,
+            ) => {
+                ConversationHttp::handle_getattachmentsinv(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &mut network.atlasdb,
+                    &index_block_hash,
+                    pages_indexes,
+                    &self.connection.options,
+                )?;
+                None
+            }
+            HttpRequestType::PostBlock(ref _md, ref consensus_hash, ref block) => {
+                let accepted = ConversationHttp::handle_post_block(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    consensus_hash,
+                    block,
+                )?;
+                if accepted {
+                    // inform the peer network so it can announce its presence
+                    ret = Some(StacksMessageType::Blocks(BlocksData {
+                        blocks: vec![(consensus_hash.clone(), block.clone())],
+                    }));
+                }
+                None
+            }
+            HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    if let Some((consensus_hash, block_hash)) =
+                        ConversationHttp::handle_load_stacks_chain_tip_hashes(
+                            &mut self.connection.protocol,
+                            &mut reply,
+                            &req,
+                            tip,
+                            chainstate,
+                        )?
+                    {
+                        let accepted = ConversationHttp::handle_post_microblock(
+                            &mut self.connection.protocol,
+                            &mut reply,
+                            &req,
+                            &consensus_hash,
+                            &block_hash,
+                            chainstate,
+                            mblock,
+                        )?;
+                        if accepted {
+                            // forward to peer network
+                            let tip = StacksBlockHeader::make_index_block_hash(
+                                &consensus_hash,
+                                &block_hash,
+                            );
+                            ret = Some(StacksMessageType::Microblocks(MicroblocksData {
+                                index_anchor_block: tip,
+                                microblocks: vec![(*mblock).clone()],
+                            }));
+                        }
+                    }
+                }
+                None
+            }
+            HttpRequestType::MemPoolQuery(ref _md, ref query, ref page_id_opt) => {
+                Some(ConversationHttp::handle_mempool_query(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    query.clone(),
+                    network.connection_opts.mempool_max_tx_query,
+                    page_id_opt.clone(),
+                )?)
+            }
+            HttpRequestType::GetContractEvents(ref _md, ref query) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &TipRequest::UseLatestAnchoredTip,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_events(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        query,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetContractMetrics(ref _md, ref query) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &TipRequest::UseLatestAnchoredTip,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_metrics(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        query,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetChainstateManifest(ref _md, interval, count) => {
+                ConversationHttp::handle_get_chainstate_manifest(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    interval,
+                    count,
+                )?;
+                None
+            }
+            HttpRequestType::GetMemPoolTransactions(ref _md, ref query) => {
+                ConversationHttp::handle_get_mempool_transactions(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    mempool,
+                    query,
+                )?;
+                None
+            }
+            HttpRequestType::GetAddressTransactions(ref _md, ref query) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &TipRequest::UseLatestAnchoredTip,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_address_transactions(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        query,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetAddressAssets(ref _md, ref principal) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &TipRequest::UseLatestAnchoredTip,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_address_assets(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        principal,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetAddressNames(ref _md, ref principal, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_address_names(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        principal,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetName(ref _md, ref name, ref namespace, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_name(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        name,
+                        namespace,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::Rosetta(ref _md, endpoint, ref body) => {
+                let forward = ConversationHttp::handle_rosetta(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    endpoint,
+                    body,
+                    network,
+                    sortdb,
+                    chainstate,
+                    mempool,
+                    handler_opts.event_observer,
+                )?;
+                if let Some(tx) = forward {
+                    ret = Some(StacksMessageType::Transaction(tx));
+                }
+                None
+            }
+            HttpRequestType::GetHealthLive(ref _md) => {
+                ConversationHttp::handle_get_health_live(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                )?;
+                None
+            }
+            HttpRequestType::GetHealthReady(ref _md) => {
+                ConversationHttp::handle_get_health_ready(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                    chainstate,
+                    &self.connection.options,
+                    handler_opts.burnchain_target_height,
+                )?;
+                None
+            }
+            HttpRequestType::GetNeighborsDetail(ref _md) => {
+                ConversationHttp::handle_getneighbors_detail(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                )?;
+                None
+            }
+            HttpRequestType::GetPeerPolicy(ref _md) => {
+                ConversationHttp::handle_peer_policy(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                    None,
+                )?;
+                None
+            }
+            HttpRequestType::PostPeerPolicy(ref _md, ref update) => {
+                ConversationHttp::handle_peer_policy(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    network,
+                    Some(update),
+                )?;
+                None
+            }
+            HttpRequestType::GetMemPoolTransaction(ref _md, ref txid) => {
+                ConversationHttp::handle_get_mempool_transaction(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    mempool,
+                    txid,
+                )?;
+                None
+            }
+            HttpRequestType::OptionsPreflight(ref _md, ref _path) => {
+                let response_metadata = HttpResponseMetadata::from(&req);
+                let response = HttpResponseType::OptionsPreflight(response_metadata);
+                response
+                    .send(&mut self.connection.protocol, &mut reply)
+                    .map(|_| ())?;
+                None
+            }
+            HttpRequestType::GetIsTraitImplemented(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref trait_id,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
                     &req,
                     tip_req,
                     sortdb,
                     chainstate,
                 )? {
-                    ConversationHttp::handle_get_account_entry(
+                    ConversationHttp::handle_get_is_trait_implemented(
                         &mut self.connection.protocol,
                         &mut reply,
                         &req,
                         sortdb,
                         chainstate,
-                        &tip,
-                        principal,
-                        *with_proof,
-                    )?;
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        trait_id,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::WebSocketUpgrade(ref _md, ref key) => {
+                let response_metadata = HttpResponseMetadata::from(&req);
+                let response = HttpResponseType::WebSocketAccepted(
+                    response_metadata,
+                    websocket_accept_key(key),
+                );
+                response
+                    .send(&mut self.connection.protocol, &mut reply)
+                    .map(|_| ())?;
+                self.websocket_upgrade = true;
+                None
+            }
+            HttpRequestType::ClientError(ref _md, ref err) => {
+                let response_metadata = HttpResponseMetadata::from(&req);
+                let response = match err {
+                    ClientError::Message(s) => HttpResponseType::BadRequestJSON(
+                        response_metadata,
+                        serde_json::Value::String(s.to_string()),
+                    ),
+                    ClientError::NotFound(path) => {
+                        HttpResponseType::NotFound(response_metadata, path.clone())
+                    }
+                };
+
+                response
+                    .send(&mut self.connection.protocol, &mut reply)
+                    .map(|_| ())?;
+                None
+            }
+        };
+
+        match stream_opt {
+            None => {
+                self.reply_streams.push_back((reply, None, keep_alive));
+            }
+            Some(stream) => {
+                // streamed responses are all compressed the way the client asked
+                self.reply_streams.push_back((
+                    reply,
+                    Some((
+                        HttpChunkedTransferWriterState::new(STREAM_CHUNK_SIZE as usize)
+                            .with_content_encoding(stream_encoding),
+                        stream,
+                    )),
+                    keep_alive,
+                ));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Make progress on outbound requests.
+    fn send_outbound_responses(
+        &mut self,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<(), net_error> {
+        // send out streamed responses in the order they were requested
+        let mut drained_handle = false;
+        let mut drained_stream = false;
+        let mut broken = false;
+        let mut do_keep_alive = true;
+
+        test_debug!(
+            "{:?}: {} HTTP replies pending",
+            &self,
+            self.reply_streams.len()
+        );
+        let _self_str = format!("{}", &self);
+
+        match self.reply_streams.front_mut() {
+            Some((ref mut reply, ref mut stream_opt, ref keep_alive)) => {
+                do_keep_alive = *keep_alive;
+
+                // if we're streaming, make some progress on the stream
+                match stream_opt {
+                    Some((ref mut http_chunk_state, ref mut stream)) => {
+                        let mut encoder =
+                            HttpChunkedTransferWriter::from_writer_state(reply, http_chunk_state);
+                        match stream.stream_to(mempool, chainstate, &mut encoder, STREAM_CHUNK_SIZE)
+                        {
+                            Ok(nw) => {
+                                test_debug!("{}: Streamed {} bytes", &_self_str, nw);
+                                if nw == 0 {
+                                    // EOF -- finish chunk and stop sending.
+                                    if !encoder.corked() {
+                                        encoder.flush().map_err(|e| {
+                                            test_debug!(
+                                                "{}: Write error on encoder flush: {:?}",
+                                                &_self_str,
+                                                &e
+                                            );
+                                            net_error::WriteError(e)
+                                        })?;
+
+                                        encoder.cork();
+
+                                        test_debug!("{}: Stream indicates EOF", &_self_str);
+                                    }
+
+                                    // try moving some data to the connection only once we're done
+                                    // streaming
+                                    match reply.try_flush() {
+                                        Ok(res) => {
+                                            test_debug!(
+                                                "{}: Streamed reply is drained?: {}",
+                                                &_self_str,
+                                                res
+                                            );
+                                            drained_handle = res;
+                                        }
+                                        Err(e) => {
+                                            // dead
+                                            warn!(
+                                                "{}: Broken HTTP connection: {:?}",
+                                                &_self_str, &e
+                                            );
+                                            broken = true;
+                                        }
+                                    }
+                                    drained_stream = true;
+                                }
+                            }
+                            Err(e) => {
+                                // broken -- terminate the stream.
+                                // For example, if we're streaming an unconfirmed block or
+                                // microblock, the data can get moved to the chunk store out from
+                                // under the stream.
+                                warn!(
+                                    "{}: Failed to send to HTTP connection: {:?}",
+                                    &_self_str, &e
+                                );
+                                broken = true;
+                            }
+                        }
+                    }
+                    None => {
+                        // not streamed; all data is buffered
+                        drained_stream = true;
+
+                        // try moving some data to the connection
+                        match reply.try_flush() {
+                            Ok(res) => {
+                                test_debug!("{}: Reply is drained", &_self_str);
+                                drained_handle = res;
+                            }
+                            Err(e) => {
+                                // dead
+                                warn!("{}: Broken HTTP connection: {:?}", &_self_str, &e);
+                                broken = true;
+                            }
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+
+        if broken || (drained_handle && drained_stream) {
+            // done with this stream
+            test_debug!(
+                "{:?}: done with stream (broken={}, drained_handle={}, drained_stream={})",
+                &self,
+                broken,
+                drained_handle,
+                drained_stream
+            );
+            self.total_reply_count += 1;
+            self.reply_streams.pop_front();
+
+            if !do_keep_alive {
+                // encountered "Connection: close"
+                self.keep_alive = false;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn try_send_recv_response(
+        req: ReplyHandleHttp,
+    ) -> Result<HttpResponseType, Result<ReplyHandleHttp, net_error>> {
+        match req.try_send_recv() {
+            Ok(message) => match message {
+                StacksHttpMessage::Request(_) => {
+                    warn!("Received response: not a HTTP response");
+                    return Err(Err(net_error::InvalidMessage));
+                }
+                StacksHttpMessage::Response(http_response) => Ok(http_response),
+            },
+            Err(res) => Err(res),
+        }
+    }
+
+    /// Make progress on our request/response
+    fn recv_inbound_response(&mut self) -> Result<(), net_error> {
+        // make progress on our pending request (if it exists).
+        let inprogress = self.pending_request.is_some();
+        let is_pending = self.pending_response.is_none();
+
+        let pending_request = self.pending_request.take();
+        let response = match pending_request {
+            None => Ok(self.pending_response.take()),
+            Some(req) => match ConversationHttp::try_send_recv_response(req) {
+                Ok(response) => Ok(Some(response)),
+                Err(res) => match res {
+                    Ok(handle) => {
+                        // try again
+                        self.pending_request = Some(handle);
+                        Ok(self.pending_response.take())
+                    }
+                    Err(e) => Err(e),
+                },
+            },
+        }?;
+
+        self.pending_response = response;
+
+        if inprogress && self.pending_request.is_none() {
+            test_debug!(
+                "{:?},id={}: HTTP request finished",
+                &self.peer_host,
+                self.conn_id
+            );
+        }
+
+        if is_pending && self.pending_response.is_some() {
+            test_debug!(
+                "{:?},id={}: HTTP response finished",
+                &self.peer_host,
+                self.conn_id
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Try to get our response
+    pub fn try_get_response(&mut self) -> Option<HttpResponseType> {
+        self.pending_response.take()
+    }
+
+    /// Make progress on in-flight messages.
+    pub fn try_flush(
+        &mut self,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<(), net_error> {
+        self.send_outbound_responses(mempool, chainstate)?;
+        self.recv_inbound_response()?;
+        Ok(())
+    }
+
+    /// Is the connection idle?
+    pub fn is_idle(&self) -> bool {
+        self.pending_response.is_none()
+            && self.connection.inbox_len() == 0
+            && self.connection.outbox_len() == 0
+            && self.reply_streams.len() == 0
+    }
+
+    /// Is the conversation out of pending data?
+    /// Don't consider it drained if we haven't received anything yet, or if it has been (or is
+    /// being) upgraded to a WebSocket.
+    pub fn is_drained(&self) -> bool {
+        !self.websocket_upgrade
+            && ((self.total_request_count > 0 && self.total_reply_count > 0)
+                || self.pending_error_response.is_some())
+            && self.is_idle()
+    }
+
+    /// Should the connection be kept alive even if drained?
+    pub fn is_keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// Did we stop reading the client's pipelined requests, either because it has too many
+    /// outstanding or because it isn't reading its replies?  If so, there may be requests waiting
+    /// to be read even though the socket won't signal that it's readable.
+    pub fn is_recv_paused(&self) -> bool {
+        self.recv_backlogged || self.connection.is_recv_paused()
+    }
+
+    /// Record whether or not the client authenticated itself with a TLS client certificate
+    pub fn set_client_authenticated(&mut self, authenticated: bool) {
+        self.client_authenticated = authenticated;
+    }
+
+    /// Has this conversation been upgraded to a WebSocket?
+    pub fn is_websocket(&self) -> bool {
+        self.websocket.is_some()
+    }
+
+    /// Get the WebSocket session this conversation carries, if it has been upgraded
+    pub fn websocket_mut(&mut self) -> Option<&mut WebSocketSession> {
+        self.websocket.as_mut()
+    }
+
+    /// Finish upgrading to a WebSocket, once the 101 response has been completely sent.
+    /// Returns true if the conversation became a WebSocket just now.
+    pub fn try_upgrade_websocket(&mut self) -> bool {
+        if self.websocket_upgrade
+            && self.websocket.is_none()
+            && self.is_idle()
+            && self.connection.is_flushed()
+        {
+            debug!("{:?}: upgraded to WebSocket", &self);
+            self.websocket = Some(WebSocketSession::new());
+            return true;
+        }
+        false
+    }
+
+    /// When was the last time we got an inbound request?
+    pub fn get_last_request_time(&self) -> u64 {
+        self.last_request_timestamp
+    }
+
+    /// When was the last time we sent data as part of an outbound response?
+    pub fn get_last_response_time(&self) -> u64 {
+        self.last_response_timestamp
+    }
+
+    /// When was this converation conencted?
+    pub fn get_connection_time(&self) -> u64 {
+        self.connection_time
+    }
+
+    /// Make progress on in-flight requests and replies.
+    /// Returns the list of transactions we'll need to forward to the peer network
+    pub fn chat(
+        &mut self,
+        network: &mut PeerNetwork,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        handler_args: &RPCHandlerArgs,
+    ) -> Result<Vec<StacksMessageType>, net_error> {
+        // if we have an in-flight error, then don't take any more requests.
+        if self.pending_error_response.is_some() {
+            return Ok(vec![]);
+        }
+
+        // handle in-bound HTTP request(s)
+        let num_inbound = self.connection.inbox_len();
+        let mut ret = vec![];
+        test_debug!("{:?}: {} HTTP requests pending", &self, num_inbound);
+
+        for _i in 0..num_inbound {
+            let msg = match self.connection.next_inbox_message() {
+                None => {
+                    continue;
+                }
+                Some(m) => m,
+            };
+
+            match msg {
+                StacksHttpMessage::Request(req) => {
+                    if self.closing {
+                        test_debug!("{:?}: Ignoring HTTP request pipelined after close", &self);
+                        continue;
+                    }
+                    if !req.metadata().keep_alive {
+                        self.closing = true;
+                    }
+
+                    // new request
+                    self.total_request_count += 1;
+                    self.last_request_timestamp = get_epoch_time_secs();
+                    let start_time = Instant::now();
+                    let path = req.get_path();
+                    let msg_opt = monitoring::instrument_http_request_handler(req, |req| {
+                        self.handle_request(req, network, sortdb, chainstate, mempool, handler_args)
+                    })?;
+
+                    debug!("Processed HTTPRequest"; "path" => %path, "processing_time_ms" => start_time.elapsed().as_millis(), "conn_id" => self.conn_id, "peer_addr" => &self.peer_addr);
+
+                    if let Some(msg) = msg_opt {
+                        ret.push(msg);
+                    }
+                }
+                StacksHttpMessage::Response(resp) => {
+                    // Is there someone else waiting for this message?  If so, pass it along.
+                    // (this _should_ be our pending_request handle)
+                    match self
+                        .connection
+                        .fulfill_request(StacksHttpMessage::Response(resp))
+                    {
+                        None => {
+                            test_debug!("{:?}: Fulfilled pending HTTP request", &self);
+                        }
+                        Some(_msg) => {
+                            // unsolicited; discard
+                            test_debug!("{:?}: Dropping unsolicited HTTP response", &self);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Remove all timed-out messages, and ding the remote peer as unhealthy
+    pub fn clear_timeouts(&mut self) -> () {
+        self.connection.drain_timeouts();
+    }
+
+    /// Load data into our HTTP connection
+    pub fn recv<R: Read>(&mut self, r: &mut R) -> Result<usize, net_error> {
+        if let Some(ref mut session) = self.websocket {
+            let nrecv = session.recv(r)?;
+            if nrecv > 0 {
+                self.last_request_timestamp = get_epoch_time_secs();
+            }
+            monitoring::update_inbound_rpc_bandwidth(nrecv as i64);
+            return Ok(nrecv);
+        }
+        if self.websocket_upgrade {
+            // the client may not speak until it has our 101
+            return Ok(0);
+        }
+        if (self.reply_streams.len() as u64) >= self.connection.options.max_http_pipelined_requests
+        {
+            // don't take more requests until the client reads some of its replies
+            self.recv_backlogged = true;
+            return Ok(0);
+        }
+        self.recv_backlogged = false;
+
+        let mut total_recv = 0;
+        loop {
+            let nrecv = match self.connection.recv_data(r) {
+                Ok(nr) => nr,
+                Err(e) => {
+                    debug!("{:?}: failed to recv: {:?}", self, &e);
+                    return Err(e);
+                }
+            };
+
+            total_recv += nrecv;
+            if nrecv > 0 {
+                self.last_request_timestamp = get_epoch_time_secs();
+            } else {
+                break;
+            }
+        }
+        monitoring::update_inbound_rpc_bandwidth(total_recv as i64);
+        Ok(total_recv)
+    }
+
+    /// Write data out of our HTTP connection.  Write as much as we can
+    pub fn send<W: Write>(
+        &mut self,
+        w: &mut W,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<usize, net_error> {
+        if let Some(ref mut session) = self.websocket {
+            let sz = session.send(w)?;
+            if sz > 0 {
+                self.last_response_timestamp = get_epoch_time_secs();
+            }
+            monitoring::update_inbound_rpc_bandwidth(sz as i64);
+            return Ok(sz);
+        }
+
+        let mut total_sz = 0;
+        loop {
+            // prime the Write
+            self.try_flush(mempool, chainstate)?;
+
+            let sz = match self.connection.send_data(w) {
+                Ok(sz) => sz,
+                Err(e) => {
+                    info!("{:?}: failed to send on HTTP conversation: {:?}", self, &e);
+                    return Err(e);
+                }
+            };
+
+            total_sz += sz;
+            if sz > 0 {
+                self.last_response_timestamp = get_epoch_time_secs();
+            } else {
+                break;
+            }
+        }
+        monitoring::update_inbound_rpc_bandwidth(total_sz as i64);
+        Ok(total_sz)
+    }
+
+    /// Make a new getinfo request to this endpoint
+    pub fn new_getinfo(&self) -> HttpRequestType {
+        HttpRequestType::GetInfo(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new getinfo request to this endpoint
+    pub fn new_getpoxinfo(&self, tip_req: TipRequest) -> HttpRequestType {
+        HttpRequestType::GetPoxInfo(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tip_req,
+        )
+    }
+
+    /// Make a new request for a reward cycle's parameters (the current cycle's, if None)
+    pub fn new_get_pox_cycle(
+        &self,
+        reward_cycle: Option<u64>,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetPoxCycle(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            reward_cycle,
+            tip_req,
+        )
+    }
+
+    /// Make a new request for a reward cycle's reward set
+    pub fn new_get_pox_reward_set(&self, reward_cycle: u64) -> HttpRequestType {
+        HttpRequestType::GetPoxRewardSet(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            reward_cycle,
+        )
+    }
+
+    /// Make a new request for a principal's stacking state
+    pub fn new_get_pox_stacker(
+        &self,
+        stacker: PrincipalData,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetPoxStacker(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            stacker,
+            tip_req,
+        )
+    }
+
+    /// Make a new getneighbors request to this endpoint
+    pub fn new_getneighbors(&self) -> HttpRequestType {
+        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new gettelemetry request to this endpoint
+    pub fn new_gettelemetry(&self) -> HttpRequestType {
+        HttpRequestType::GetTelemetry(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new getheaders request to this endpoint
+    pub fn new_getheaders(&self, quantity: u64, tip_req: TipRequest) -> HttpRequestType {
+        HttpRequestType::GetHeaders(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            quantity,
+            tip_req,
+        )
+    }
+
+    /// Make a new getblock request to this endpoint
+    pub fn new_getblock(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
+        HttpRequestType::GetBlock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_block_hash,
+        )
+    }
+
+    /// Make a new block export request to this endpoint
+    pub fn new_getblock_export(&self, start_height: u64, count: u32) -> HttpRequestType {
+        HttpRequestType::GetBlockExport(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            start_height,
+            count,
+        )
+    }
+
+    /// Make a new request for the Clarity state written by a block
+    pub fn new_getblock_state_diff(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
+        HttpRequestType::GetBlockStateDiff(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_block_hash,
+        )
+    }
+
+    /// Make a new get-microblocks request to this endpoint
+    pub fn new_getmicroblocks_indexed(
+        &self,
+        index_microblock_hash: StacksBlockId,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblocksIndexed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_microblock_hash,
+        )
+    }
+
+    /// Make a new get-microblocks-confirmed request to this endpoint
+    pub fn new_getmicroblocks_confirmed(
+        &self,
+        index_anchor_block_hash: StacksBlockId,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblocksConfirmed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_anchor_block_hash,
+        )
+    }
+
+    /// Make a new get-microblocks request for unconfirmed microblocks
+    pub fn new_getmicroblocks_unconfirmed(
+        &self,
+        anchored_index_block_hash: StacksBlockId,
+        min_seq: u16,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblocksUnconfirmed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            anchored_index_block_hash,
+            min_seq,
+        )
+    }
+
+    /// Make a new request for a page of the microblock stream built off of an anchored block
+    pub fn new_getmicroblock_stream(
+        &self,
+        anchored_index_block_hash: StacksBlockId,
+        start_seq: u16,
+        limit: u16,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblockStream(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            anchored_index_block_hash,
+            start_seq,
+            limit,
+        )
+    }
+
+    /// Make a new get-unconfirmed-tx request
+    pub fn new_gettransaction_unconfirmed(&self, txid: Txid) -> HttpRequestType {
+        HttpRequestType::GetTransactionUnconfirmed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            txid,
+        )
+    }
+
+    /// Make a new post-transaction request
+    pub fn new_post_transaction(&self, tx: StacksTransaction) -> HttpRequestType {
+        HttpRequestType::PostTransaction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tx,
+            None,
+            false,
+        )
+    }
+
+    /// Make a new post-transaction request that asks for an admission report
+    pub fn new_post_transaction_with_report(&self, tx: StacksTransaction) -> HttpRequestType {
+        HttpRequestType::PostTransaction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tx,
+            None,
+            true,
+        )
+    }
+
+    /// Make a new post-block request
+    pub fn new_post_block(&self, ch: ConsensusHash, block: StacksBlock) -> HttpRequestType {
+        HttpRequestType::PostBlock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            ch,
+            block,
+        )
+    }
+
+    /// Make a new post-microblock request
+    pub fn new_post_microblock(
+        &self,
+        mblock: StacksMicroblock,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::PostMicroblock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            mblock,
+            tip_req,
+        )
+    }
+
+    /// Make a new request for an account
+    pub fn new_getaccount(
+        &self,
+        principal: PrincipalData,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAccount(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request for the proof of an account's balance and nonce
+    pub fn new_get_account_proof(
+        &self,
+        principal: PrincipalData,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAccountProof(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            tip_req,
+        )
+    }
+
+    /// Make a new request for a data var
+    pub fn new_getdatavar(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        var_name: ClarityName,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetDataVar(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            var_name,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request for a data map
+    pub fn new_getmapentry(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        map_name: ClarityName,
+        key: Value,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMapEntry(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            map_name,
+            key,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request for a batch of data map entries
+    pub fn new_get_map_entry_batch(
+        &self,
+        lookups: Vec<MapEntryLookup>,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMapEntryBatch(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            lookups,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request to get a contract's source
+    pub fn new_getcontractsrc(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetContractSrc(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request to get a contract's ABI
+    pub fn new_getcontractabi(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetContractABI(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            tip_req,
+        )
+    }
+
+    /// Make a new request to get the maps, data vars, and tokens a contract defines
+    pub fn new_getcontractstorage(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetContractStorage(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            tip_req,
+        )
+    }
+
+    /// Make a new request to check a transaction's post-conditions against a list of asset events
+    pub fn new_postconditionscheck(
+        &self,
+        tx: StacksTransaction,
+        events: Vec<StacksTransactionEvent>,
+    ) -> HttpRequestType {
+        HttpRequestType::PostConditionCheck(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tx,
+            events,
+        )
+    }
+
+    /// Make a new request to run a read-only function
+    pub fn new_callreadonlyfunction(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        sender: PrincipalData,
+        function_name: ClarityName,
+        function_args: Vec<Value>,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::CallReadOnlyFunction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            sender,
+            function_name,
+            function_args,
+            tip_req,
+        )
+    }
+
+    /// Make a new request to check a contract-call's arguments without running it
+    pub fn new_check_contract_call_args(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        function_name: ClarityName,
+        function_args: Vec<Value>,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::CheckContractCallArgs(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            function_name,
+            function_args,
+            tip_req,
+        )
+    }
+
+    /// Make a new request to run a batch of read-only functions against one chain tip
+    pub fn new_call_read_only_batch(
+        &self,
+        sender: PrincipalData,
+        calls: Vec<ReadOnlyCall>,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::CallReadOnlyFunctionBatch(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            sender,
+            calls,
+            tip_req,
+        )
+    }
+
+    /// Make a new request for attachment inventory page
+    pub fn new_getattachmentsinv(
+        &self,
+        index_block_hash: StacksBlockId,
+        pages_indexes: HashSet<u32>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAttachmentsInv(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_block_hash,
+            pages_indexes,
+        )
+    }
+
+    /// Make a new request for mempool contents
+    pub fn new_mempool_query(
+        &self,
+        query: MemPoolSyncData,
+        page_id_opt: Option<Txid>,
+    ) -> HttpRequestType {
+        HttpRequestType::MemPoolQuery(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            query,
+            page_id_opt,
+        )
+    }
+
+    /// Make a new request for the chainstate checksum manifest
+    pub fn new_get_chainstate_manifest(&self, interval: u64, count: u64) -> HttpRequestType {
+        HttpRequestType::GetChainstateManifest(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            interval,
+            count,
+        )
+    }
+
+    /// Make a new request for a page of the mempool's pending transactions
+    pub fn new_get_mempool_transactions(&self, query: MemPoolTransactionsQuery) -> HttpRequestType {
+        HttpRequestType::GetMemPoolTransactions(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            query,
+        )
+    }
+
+    /// Make a new request for a pending transaction in the mempool
+    pub fn new_get_mempool_transaction(&self, txid: Txid) -> HttpRequestType {
+        HttpRequestType::GetMemPoolTransaction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            txid,
+        )
+    }
+
+    /// Make a new request for a page of the transactions involving a principal
+    pub fn new_get_address_transactions(&self, query: AddressTransactionsQuery) -> HttpRequestType {
+        HttpRequestType::GetAddressTransactions(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            query,
+        )
+    }
+
+    /// Make a new request for the tokens a principal holds
+    pub fn new_get_address_assets(&self, principal: PrincipalData) -> HttpRequestType {
+        HttpRequestType::GetAddressAssets(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+        )
+    }
+
+    /// Make a new request for the BNS names a principal owns
+    pub fn new_get_address_names(
+        &self,
+        principal: PrincipalData,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAddressNames(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            tip_req,
+        )
+    }
+
+    /// Make a new request to resolve a BNS name
+    pub fn new_get_name(
+        &self,
+        name: String,
+        namespace: String,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::GetName(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            name,
+            namespace,
+            tip_req,
+        )
+    }
+
+    /// Make a new Rosetta API request
+    pub fn new_rosetta(
+        &self,
+        endpoint: RosettaEndpoint,
+        body: serde_json::Value,
+    ) -> HttpRequestType {
+        HttpRequestType::Rosetta(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            endpoint,
+            body,
+        )
+    }
+
+    /// Make a new liveness probe
+    pub fn new_get_health_live(&self) -> HttpRequestType {
+        HttpRequestType::GetHealthLive(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new readiness probe
+    pub fn new_get_health_ready(&self) -> HttpRequestType {
+        HttpRequestType::GetHealthReady(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new request for the neighbors detail
+    pub fn new_getneighbors_detail(&self, admin_api_key: &str) -> HttpRequestType {
+        let mut md = HttpRequestMetadata::from_host(self.peer_host.clone());
+        md.api_key = Some(admin_api_key.to_string());
+        HttpRequestType::GetNeighborsDetail(md)
+    }
+
+    /// Make a new request for the peer policy
+    pub fn new_get_peer_policy(&self, admin_api_key: &str) -> HttpRequestType {
+        let mut md = HttpRequestMetadata::from_host(self.peer_host.clone());
+        md.api_key = Some(admin_api_key.to_string());
+        HttpRequestType::GetPeerPolicy(md)
+    }
+
+    /// Make a new peer policy update
+    pub fn new_post_peer_policy(
+        &self,
+        admin_api_key: &str,
+        update: PeerPolicyUpdate,
+    ) -> HttpRequestType {
+        let mut md = HttpRequestMetadata::from_host(self.peer_host.clone());
+        md.api_key = Some(admin_api_key.to_string());
+        HttpRequestType::PostPeerPolicy(md, update)
+    }
+
+    /// Make a new request for a page of contract events
+    pub fn new_get_contract_events(&self, query: ContractEventsQuery) -> HttpRequestType {
+        HttpRequestType::GetContractEvents(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            query,
+        )
+    }
+
+    /// Make a new request for per-contract execution metrics
+    pub fn new_get_contract_metrics(&self, query: ContractMetricsQuery) -> HttpRequestType {
+        HttpRequestType::GetContractMetrics(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            query,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::convert::TryInto;
+    use std::iter::FromIterator;
+
+    use address::*;
+    use burnchains::Burnchain;
+    use burnchains::BurnchainView;
+    use burnchains::*;
+    use chainstate::burn::ConsensusHash;
+    use chainstate::stacks::db::address_transactions::AddressTransactionCursor;
+    use chainstate::stacks::db::blocks::test::*;
+    use chainstate::stacks::db::contract_events::{ContractEventFilter, ContractEventType};
+    use chainstate::stacks::db::StacksChainState;
+    use chainstate::stacks::db::StreamCursor;
+    use chainstate::stacks::db::{ChainstateBNSName, ChainstateBNSNamespace};
+    use chainstate::stacks::miner::*;
+    use chainstate::stacks::test::*;
+    use chainstate::stacks::Error as chain_error;
+    use chainstate::stacks::*;
+    use net::codec::*;
+    use net::http::*;
+    use net::test::*;
+    use net::*;
+    use util::get_epoch_time_secs;
+    use util::hash::hex_bytes;
+    use util::pipe::*;
+    use vm::types::*;
+
+    use crate::types::chainstate::BlockHeaderHash;
+    use crate::types::chainstate::BurnchainHeaderHash;
+    use crate::types::proof::TrieHash;
+    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+
+    use core::mempool::{MemPoolTxFilter, BLOOM_COUNTER_ERROR_RATE, MAX_BLOOM_COUNTER_TXS};
+
+    use super::*;
+    use net::neighbors::MAX_RECENT_NEIGHBOR_WALKS;
+    use net::ratelimit::{RPCRateLimit, RPCRateLimiter};
+    use net::rosetta::ERR_NETWORK;
+
+    const TEST_CONTRACT: &'static str = "
+        (define-data-var bar int 0)
+        (define-map unit-map { account: principal } { units: int })
+        (define-public (get-bar) (ok (var-get bar)))
+        (define-public (set-bar (x int) (y int))
+          (begin (var-set bar (/ x y)) (ok (var-get bar))))
+        (define-public (add-unit)
+          (begin
+            (map-set unit-map { account: tx-sender } { units: 1 } )
+            (var-set bar 1)
+            (ok 1)))
+        (begin
+          (map-set unit-map { account: 'ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R } { units: 123 })
+          (print { event: \"init\", units: 123 }))";
+
+    const TEST_CONTRACT_UNCONFIRMED: &'static str = "(define-read-only (ro-test) (ok 1))";
+
+    fn convo_send_recv(
+        sender: &mut ConversationHttp,
+        sender_mempool: &MemPoolDB,
+        sender_chainstate: &mut StacksChainState,
+        receiver: &mut ConversationHttp,
+        receiver_mempool: &MemPoolDB,
+        receiver_chainstate: &mut StacksChainState,
+    ) -> () {
+        let (mut pipe_read, mut pipe_write) = Pipe::new();
+        pipe_read.set_nonblocking(true);
+
+        loop {
+            let res = true;
+
+            sender.try_flush(sender_mempool, sender_chainstate).unwrap();
+            receiver
+                .try_flush(sender_mempool, receiver_chainstate)
+                .unwrap();
+
+            pipe_write.try_flush().unwrap();
+
+            let all_relays_flushed =
+                receiver.num_pending_outbound() == 0 && sender.num_pending_outbound() == 0;
+
+            let nw = sender
+                .send(&mut pipe_write, sender_mempool, sender_chainstate)
+                .unwrap();
+            let nr = receiver.recv(&mut pipe_read).unwrap();
+
+            test_debug!(
+                "res = {}, all_relays_flushed = {} ({},{}), nr = {}, nw = {}",
+                res,
+                all_relays_flushed,
+                receiver.num_pending_outbound(),
+                sender.num_pending_outbound(),
+                nr,
+                nw
+            );
+            if res && all_relays_flushed && nr == 0 && nw == 0 {
+                test_debug!("Breaking send_recv");
+                break;
+            }
+        }
+    }
+
+    /// General testing function to test RPC calls.
+    /// This function sets up two peers, a client and a server.
+    /// It takes in a function of type F that generates the request to be sent to the server
+    /// It takes in another function of type C that verifies that the result from
+    /// the server is as expected.
+    /// The parameter `include_microblocks` determines whether a microblock stream is mined or not.
+    fn test_rpc<F, C>(
+        test_name: &str,
+        peer_1_p2p: u16,
+        peer_1_http: u16,
+        peer_2_p2p: u16,
+        peer_2_http: u16,
+        include_microblocks: bool,
+        make_request: F,
+        check_result: C,
+    ) -> ()
+    where
+        F: FnOnce(
+            &mut TestPeer,
+            &mut ConversationHttp,
+            &mut TestPeer,
+            &mut ConversationHttp,
+        ) -> HttpRequestType,
+        C: FnOnce(&HttpRequestType, &HttpResponseType, &mut TestPeer, &mut TestPeer) -> bool,
+    {
+        let mut peer_1_config = TestPeerConfig::new(test_name, peer_1_p2p, peer_1_http);
+        let mut peer_2_config = TestPeerConfig::new(test_name, peer_2_p2p, peer_2_http);
+
+        // ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R
+        let privk1 = StacksPrivateKey::from_hex(
+            "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
+        )
+        .unwrap();
+
+        // STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW
+        let privk2 = StacksPrivateKey::from_hex(
+            "94c319327cc5cd04da7147d32d836eb2e4c44f4db39aa5ede7314a761183d0c701",
+        )
+        .unwrap();
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+
+        let addr1 = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk1)],
+        )
+        .unwrap();
+        let addr2 = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk2)],
+        )
+        .unwrap();
+
+        peer_1_config.initial_balances = vec![
+            (addr1.to_account_principal(), 1000000000),
+            (addr2.to_account_principal(), 1000000000),
+        ];
+
+        peer_2_config.initial_balances = vec![
+            (addr1.to_account_principal(), 1000000000),
+            (addr2.to_account_principal(), 1000000000),
+        ];
+
+        // addr1 owns the BNS name alice.test, in a namespace whose names never expire
+        for config in [&mut peer_1_config, &mut peer_2_config].iter_mut() {
+            config.initial_namespaces = vec![ChainstateBNSNamespace {
+                namespace_id: "test".to_string(),
+                importer: addr2.to_string(),
+                buckets: "1;1;1;1;1;1;1;1;1;1;1;1;1;1;1;1".to_string(),
+                base: 1,
+                coeff: 1,
+                nonalpha_discount: 1,
+                no_vowel_discount: 1,
+                lifetime: 0,
+            }];
+            config.initial_names = vec![ChainstateBNSName {
+                fully_qualified_name: "alice.test".to_string(),
+                owner: addr1.to_string(),
+                zonefile_hash: "0102030405060708091011121314151617181920".to_string(),
+            }];
+        }
+
+        peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+        peer_2_config.add_neighbor(&peer_1_config.to_neighbor());
+
+        let mut peer_1 = TestPeer::new(peer_1_config);
+        let mut peer_2 = TestPeer::new(peer_2_config);
+
+        for peer in [&mut peer_1, &mut peer_2].iter_mut() {
+            peer.chainstate().address_tx_index = true;
+            peer.chainstate().address_asset_index = true;
+            peer.coord.chainstate_mut().address_tx_index = true;
+            peer.coord.chainstate_mut().address_asset_index = true;
+            peer.chainstate().rosetta_index = true;
+            peer.coord.chainstate_mut().rosetta_index = true;
+        }
+
+        // mine one block with a contract in it
+        // first the coinbase
+        // make a coinbase for this miner
+        let mut tx_coinbase = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::Coinbase(CoinbasePayload([0x00; 32])),
+        );
+        tx_coinbase.chain_id = 0x80000000;
+        tx_coinbase.anchor_mode = TransactionAnchorMode::OnChainOnly;
+        tx_coinbase.auth.set_origin_nonce(0);
+
+        let mut tx_signer = StacksTransactionSigner::new(&tx_coinbase);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_coinbase_signed = tx_signer.get_tx().unwrap();
+
+        // next the contract
+        let contract = TEST_CONTRACT.clone();
+        let mut tx_contract = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::new_smart_contract(&format!("hello-world"), &contract.to_string())
+                .unwrap(),
+        );
+
+        tx_contract.chain_id = 0x80000000;
+        tx_contract.auth.set_origin_nonce(1);
+        tx_contract.set_tx_fee(0);
+
+        let mut tx_signer = StacksTransactionSigner::new(&tx_contract);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_contract_signed = tx_signer.get_tx().unwrap();
+
+        // update account and state in a microblock that will be unconfirmed
+        let mut tx_cc = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::new_contract_call(addr1.clone(), "hello-world", "add-unit", vec![])
+                .unwrap(),
+        );
+
+        tx_cc.chain_id = 0x80000000;
+        tx_cc.auth.set_origin_nonce(2);
+        tx_cc.set_tx_fee(123);
+
+        let mut tx_signer = StacksTransactionSigner::new(&tx_cc);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_cc_signed = tx_signer.get_tx().unwrap();
+        let tx_cc_len = {
+            let mut bytes = vec![];
+            tx_cc_signed.consensus_serialize(&mut bytes).unwrap();
+            bytes.len() as u64
+        };
+
+        // make an unconfirmed contract
+        let unconfirmed_contract = TEST_CONTRACT_UNCONFIRMED.clone();
+        let mut tx_unconfirmed_contract = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::new_smart_contract(
+                &format!("hello-world-unconfirmed"),
+                &unconfirmed_contract.to_string(),
+            )
+            .unwrap(),
+        );
+
+        tx_unconfirmed_contract.chain_id = 0x80000000;
+        tx_unconfirmed_contract.auth.set_origin_nonce(3);
+        tx_unconfirmed_contract.set_tx_fee(0);
+
+        let mut tx_signer = StacksTransactionSigner::new(&tx_unconfirmed_contract);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_unconfirmed_contract_signed = tx_signer.get_tx().unwrap();
+        let tx_unconfirmed_contract_len = {
+            let mut bytes = vec![];
+            tx_unconfirmed_contract_signed
+                .consensus_serialize(&mut bytes)
+                .unwrap();
+            bytes.len() as u64
+        };
+
+        let tip =
+            SortitionDB::get_canonical_burn_chain_tip(&peer_1.sortdb.as_ref().unwrap().conn())
+                .unwrap();
+        let mut anchor_cost = ExecutionCost::zero();
+        let mut anchor_size = 0;
+
+        // make a block and a microblock.
+        // Put the coinbase and smart-contract in the anchored block.
+        // Put the contract-call in the microblock
+        let (burn_ops, stacks_block, microblocks) = peer_1.make_tenure(
+            |ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, ref parent_opt, _| {
+                let parent_tip = match parent_opt {
+                    None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                    Some(block) => {
+                        let ic = sortdb.index_conn();
+                        let snapshot = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                            &ic,
+                            &tip.sortition_id,
+                            &block.block_hash(),
+                        )
+                        .unwrap()
+                        .unwrap(); // succeeds because we don't fork
+                        StacksChainState::get_anchored_block_header_info(
+                            chainstate.db(),
+                            &snapshot.consensus_hash,
+                            &snapshot.winning_stacks_block_hash,
+                        )
+                        .unwrap()
+                        .unwrap()
+                    }
+                };
+
+                let block_builder = StacksBlockBuilder::make_regtest_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, anchored_block_size, anchored_block_cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![tx_coinbase_signed.clone(), tx_contract_signed.clone()],
+                    )
+                    .unwrap();
+
+                anchor_size = anchored_block_size;
+                anchor_cost = anchored_block_cost;
+
+                (anchored_block, vec![])
+            },
+        );
+
+        let (_, _, consensus_hash) = peer_1.next_burnchain_block(burn_ops.clone());
+        peer_2.next_burnchain_block(burn_ops.clone());
+
+        peer_1.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
+        peer_2.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
+
+        // begin microblock section
+        if include_microblocks {
+            // build 1-block microblock stream with the contract-call and the unconfirmed contract
+            let microblock = {
+                let sortdb = peer_1.sortdb.take().unwrap();
+                Relayer::setup_unconfirmed_state(peer_1.chainstate(), &sortdb).unwrap();
+                let mblock = {
+                    let sort_iconn = sortdb.index_conn();
+                    let mut microblock_builder = StacksMicroblockBuilder::new(
+                        stacks_block.block_hash(),
+                        consensus_hash.clone(),
+                        peer_1.chainstate(),
+                        &sort_iconn,
+                        BlockBuilderSettings::max_value(),
+                    )
+                    .unwrap();
+                    let microblock = microblock_builder
+                        .mine_next_microblock_from_txs(
+                            vec![
+                                (tx_cc_signed, tx_cc_len),
+                                (tx_unconfirmed_contract_signed, tx_unconfirmed_contract_len),
+                            ],
+                            &microblock_privkey,
+                        )
+                        .unwrap();
+                    microblock
+                };
+                peer_1.sortdb = Some(sortdb);
+                mblock
+            };
+
+            // store microblock stream
+            peer_1
+                .chainstate()
+                .preprocess_streamed_microblock(
+                    &consensus_hash,
+                    &stacks_block.block_hash(),
+                    &microblock,
+                )
+                .unwrap();
+            peer_2
+                .chainstate()
+                .preprocess_streamed_microblock(
+                    &consensus_hash,
+                    &stacks_block.block_hash(),
+                    &microblock,
+                )
+                .unwrap();
+
+            // process microblock stream to generate unconfirmed state
+            let canonical_tip = StacksBlockHeader::make_index_block_hash(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+            );
+            let sortdb1 = peer_1.sortdb.take().unwrap();
+            let sortdb2 = peer_2.sortdb.take().unwrap();
+            peer_1
+                .chainstate()
+                .reload_unconfirmed_state(&sortdb1.index_conn(), canonical_tip.clone())
+                .unwrap();
+            peer_2
+                .chainstate()
+                .reload_unconfirmed_state(&sortdb2.index_conn(), canonical_tip.clone())
+                .unwrap();
+            peer_1.sortdb = Some(sortdb1);
+            peer_2.sortdb = Some(sortdb2);
+        }
+        // end microblock section
+
+        // stuff some transactions into peer_2's mempool
+        // (relates to mempool query tests)
+        let mut mempool = peer_2.mempool.take().unwrap();
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        for i in 0..10 {
+            let pk = StacksPrivateKey::new();
+            let addr = StacksAddress::from_public_keys(
+                C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                &AddressHashMode::SerializeP2PKH,
+                1,
+                &vec![StacksPublicKey::from_private(&StacksPrivateKey::new())],
+            )
+            .unwrap();
+            let mut tx = StacksTransaction {
+                version: TransactionVersion::Testnet,
+                chain_id: 0x80000000,
+                auth: TransactionAuth::from_p2pkh(&pk).unwrap(),
+                anchor_mode: TransactionAnchorMode::Any,
+                post_condition_mode: TransactionPostConditionMode::Allow,
+                post_conditions: vec![],
+                payload: TransactionPayload::TokenTransfer(
+                    addr.to_account_principal(),
+                    123,
+                    TokenTransferMemo([0u8; 34]),
+                ),
+            };
+            tx.set_tx_fee(1000);
+            tx.set_origin_nonce(0);
+
+            let txid = tx.txid();
+            let tx_bytes = tx.serialize_to_vec();
+            let origin_addr = tx.origin_address();
+            let origin_nonce = tx.get_origin_nonce();
+            let sponsor_addr = tx.sponsor_address().unwrap_or(origin_addr.clone());
+            let sponsor_nonce = tx.get_sponsor_nonce().unwrap_or(origin_nonce);
+            let tx_fee = tx.get_tx_fee();
+
+            // should succeed
+            MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                peer_1.chainstate(),
+                &consensus_hash,
+                &stacks_block.block_hash(),
+                txid.clone(),
+                tx_bytes,
+                tx_fee,
+                stacks_block.header.total_work.work,
+                &origin_addr,
+                origin_nonce,
+                &sponsor_addr,
+                sponsor_nonce,
+                None,
+            )
+            .unwrap();
+        }
+        mempool_tx.commit().unwrap();
+        peer_2.mempool.replace(mempool);
+
+        let view_1 = peer_1.get_burnchain_view().unwrap();
+        let view_2 = peer_2.get_burnchain_view().unwrap();
+
+        let mut convo_1 = ConversationHttp::new(
+            format!("127.0.0.1:{}", peer_1_http)
+                .parse::<SocketAddr>()
+                .unwrap(),
+            Some(UrlString::try_from(format!("http://peer1.com")).unwrap()),
+            peer_1.to_peer_host(),
+            &peer_1.config.connection_opts,
+            0,
+        );
+
+        let mut convo_2 = ConversationHttp::new(
+            format!("127.0.0.1:{}", peer_2_http)
+                .parse::<SocketAddr>()
+                .unwrap(),
+            Some(UrlString::try_from(format!("http://peer2.com")).unwrap()),
+            peer_2.to_peer_host(),
+            &peer_2.config.connection_opts,
+            1,
+        );
+
+        let req = make_request(&mut peer_1, &mut convo_1, &mut peer_2, &mut convo_2);
+
+        convo_1.send_request(req.clone()).unwrap();
+        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
+        let peer_2_mempool = peer_2.mempool.take().unwrap();
+
+        test_debug!("convo1 sends to convo2");
+        convo_send_recv(
+            &mut convo_1,
+            &peer_1_mempool,
+            peer_1.chainstate(),
+            &mut convo_2,
+            &peer_2_mempool,
+            peer_2.chainstate(),
+        );
+
+        // hack around the borrow-checker
+        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
+        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
+
+        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
+            .unwrap();
+
+        convo_1
+            .chat(
+                &mut peer_1.network,
+                &mut peer_1_sortdb,
+                &mut peer_1_stacks_node.chainstate,
+                &mut peer_1_mempool,
+                &RPCHandlerArgs::default(),
+            )
+            .unwrap();
+
+        peer_1.sortdb = Some(peer_1_sortdb);
+        peer_1.stacks_node = Some(peer_1_stacks_node);
+        peer_1.mempool = Some(peer_1_mempool);
+        peer_2.mempool = Some(peer_2_mempool);
+
+        test_debug!("convo2 sends to convo1");
+
+        // hack around the borrow-checker
+        let mut peer_2_sortdb = peer_2.sortdb.take().unwrap();
+        let mut peer_2_stacks_node = peer_2.stacks_node.take().unwrap();
+        let mut peer_2_mempool = peer_2.mempool.take().unwrap();
+
+        Relayer::setup_unconfirmed_state(&mut peer_2_stacks_node.chainstate, &peer_2_sortdb)
+            .unwrap();
+
+        convo_2
+            .chat(
+                &mut peer_2.network,
+                &mut peer_2_sortdb,
+                &mut peer_2_stacks_node.chainstate,
+                &mut peer_2_mempool,
+                &RPCHandlerArgs::default(),
+            )
+            .unwrap();
+
+        peer_2.sortdb = Some(peer_2_sortdb);
+        peer_2.stacks_node = Some(peer_2_stacks_node);
+        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
+
+        convo_send_recv(
+            &mut convo_2,
+            &peer_2_mempool,
+            peer_2.chainstate(),
+            &mut convo_1,
+            &peer_1_mempool,
+            peer_1.chainstate(),
+        );
+
+        test_debug!("flush convo1");
+
+        // hack around the borrow-checker
+        convo_send_recv(
+            &mut convo_1,
+            &peer_1_mempool,
+            peer_1.chainstate(),
+            &mut convo_2,
+            &peer_2_mempool,
+            peer_2.chainstate(),
+        );
+
+        peer_2.mempool = Some(peer_2_mempool);
+
+        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
+        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
+
+        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
+            .unwrap();
+
+        convo_1
+            .chat(
+                &mut peer_1.network,
+                &mut peer_1_sortdb,
+                &mut peer_1_stacks_node.chainstate,
+                &mut peer_1_mempool,
+                &RPCHandlerArgs::default(),
+            )
+            .unwrap();
+
+        convo_1
+            .try_flush(&peer_1_mempool, &mut peer_1_stacks_node.chainstate)
+            .unwrap();
+
+        peer_1.sortdb = Some(peer_1_sortdb);
+        peer_1.stacks_node = Some(peer_1_stacks_node);
+        peer_1.mempool = Some(peer_1_mempool);
+
+        // should have gotten a reply
+        let resp_opt = convo_1.try_get_response();
+        assert!(resp_opt.is_some());
+
+        let resp = resp_opt.unwrap();
+        assert!(check_result(&req, &resp, &mut peer_1, &mut peer_2));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getinfo() {
+        let peer_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getinfo",
+            40000,
+            40001,
+            50000,
+            50001,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_info = RPCPeerInfoData::from_network(
+                    &peer_server.network,
+                    &peer_server.stacks_node.as_ref().unwrap().chainstate,
+                    &None,
+                    &Sha256Sum::zero(),
+                );
+
+                *peer_server_info.borrow_mut() = Some(peer_info);
+
+                convo_client.new_getinfo()
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PeerInfo(response_md, peer_data) => {
+                        assert_eq!(Some((*peer_data).clone()), *peer_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_gettelemetry() {
+        let telemetry_data_cell = RefCell::new(None);
+        test_rpc(
+            "test_rpc_gettelemetry",
+            40210,
+            40211,
+            50210,
+            50211,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let telemetry_key = Secp256k1PrivateKey::new();
+                let telemetry_data =
+                    RPCTelemetryData::from_network(&peer_server.network, &telemetry_key).unwrap();
+
+                *telemetry_data_cell.borrow_mut() = Some(telemetry_data);
+
+                convo_client.new_gettelemetry()
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let mut telemetry_data = telemetry_data_cell.borrow().clone().unwrap();
+                assert!(telemetry_data.verify());
+                assert_eq!(telemetry_data.stats.os, std::env::consts::OS);
+                assert_eq!(telemetry_data.stats.arch, std::env::consts::ARCH);
+
+                telemetry_data.stats.stacks_tip_height += 1;
+                assert!(!telemetry_data.verify());
+
+                // telemetry is not enabled on the server
+                match http_response {
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "Telemetry is not enabled");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                None
-            }
-            HttpRequestType::GetDataVar(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref var_name,
-                ref tip_req,
-                ref with_proof,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getpoxinfo() {
+        // Test v2/pox (aka GetPoxInfo) endpoint.
+        // In this test, `tip_req` is set to UseLatestAnchoredTip.
+        // Thus, the query for pox info will be against the canonical Stacks tip, which we expect to succeed.
+        let pox_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getpoxinfo",
+            40002,
+            40003,
+            50002,
+            50003,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let stacks_block_id = {
+                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                    StacksBlockHeader::make_index_block_hash(
+                        &tip.consensus_hash,
+                        &tip.anchored_block_hash,
+                    )
+                };
+                let pox_info = RPCPoxInfoData::from_db(
+                    &mut sortdb,
                     chainstate,
-                )? {
-                    ConversationHttp::handle_get_data_var(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                        var_name,
-                        *with_proof,
-                    )?;
+                    &stacks_block_id,
+                    &peer_client.config.burnchain,
+                )
+                .unwrap();
+                *pox_server_info.borrow_mut() = Some(pox_info);
+                convo_client.new_getpoxinfo(TipRequest::UseLatestAnchoredTip)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PoxInfo(response_md, pox_data) => {
+                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                None
-            }
-            HttpRequestType::GetMapEntry(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref map_name,
-                ref key,
-                ref tip_req,
-                ref with_proof,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getpoxinfo_use_latest_tip() {
+        // Test v2/pox (aka GetPoxInfo) endpoint.
+        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying for pox
+        // info against the unconfirmed state will succeed.
+        let pox_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getpoxinfo_use_latest_tip",
+            40004,
+            40005,
+            50004,
+            50005,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let stacks_block_id = chainstate
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                let pox_info = RPCPoxInfoData::from_db(
+                    &mut sortdb,
                     chainstate,
-                )? {
-                    ConversationHttp::handle_get_map_entry(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                        map_name,
-                        key,
-                        *with_proof,
-                    )?;
+                    &stacks_block_id,
+                    &peer_client.config.burnchain,
+                )
+                .unwrap();
+                *pox_server_info.borrow_mut() = Some(pox_info);
+                convo_client.new_getpoxinfo(TipRequest::UseLatestUnconfirmedTip)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PoxInfo(response_md, pox_data) => {
+                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                None
-            }
-            HttpRequestType::GetTransferCost(ref _md) => {
-                ConversationHttp::handle_token_transfer_cost(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                )?;
-                None
-            }
-            HttpRequestType::GetContractABI(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref tip_req,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_contract_abi(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                    )?;
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getneighbors() {
+        test_rpc(
+            "test_rpc_getneighbors",
+            40010,
+            40011,
+            50010,
+            50011,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getneighbors() },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Neighbors(response_md, neighbor_info) => {
+                        assert_eq!(neighbor_info.sample.len(), 1);
+                        assert_eq!(neighbor_info.sample[0].port, peer_client.config.server_port); // we see ourselves as the neighbor
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_rpc_getneighbors_detail() {
+        test_rpc(
+            "test_rpc_getneighbors_detail",
+            40928,
+            40929,
+            50928,
+            50929,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_server.connection.options.rpc_admin_api_key = Some("admin".to_string());
+                convo_client.new_getneighbors_detail("admin")
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::NeighborsDetail(response_md, detail_data) => {
+                        assert_eq!(detail_data.neighbors.len(), peer_server.network.peers.len());
+                        for neighbor in detail_data.neighbors.iter() {
+                            assert!(neighbor.reputation <= 100.0);
+                        }
+                        assert!(detail_data.denied.is_empty());
+                        assert_eq!(detail_data.walk.walk_count, peer_server.network.walk_count);
+                        assert!(detail_data.walk.recent_walks.len() <= MAX_RECENT_NEIGHBOR_WALKS);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                None
-            }
-            HttpRequestType::FeeRateEstimate(ref _md, ref tx, estimated_len) => {
-                ConversationHttp::handle_post_fee_rate_estimate(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    handler_opts,
-                    sortdb,
-                    tx,
-                    estimated_len,
-                )?;
-                None
-            }
-            HttpRequestType::CallReadOnlyFunction(
-                ref _md,
-                ref ctrct_addr,
-                ref ctrct_name,
-                ref as_sender,
-                ref func_name,
-                ref args,
-                ref tip_req,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_readonly_function_call(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        ctrct_addr,
-                        ctrct_name,
-                        func_name,
-                        as_sender,
-                        args,
-                        &self.connection.options,
-                    )?;
+            },
+        );
+    }
+
+    #[test]
+    fn test_rpc_getneighbors_detail_unauthorized() {
+        test_rpc(
+            "test_rpc_getneighbors_detail_unauthorized",
+            40930,
+            40931,
+            50930,
+            50931,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_server.connection.options.rpc_admin_api_key = Some("admin".to_string());
+                convo_client.new_getneighbors_detail("not-the-admin")
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::Unauthorized(_, msg) => {
+                        assert_eq!(msg, "The admin API key is required");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                None
-            }
-            HttpRequestType::GetContractSrc(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref tip_req,
-                ref with_proof,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_contract_src(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                        *with_proof,
-                    )?;
+            },
+        );
+    }
+
+    #[test]
+    fn test_rpc_getneighbors_detail_throttled() {
+        // a client that keeps guessing at the admin API key is turned away, even with the right
+        // one, until it has waited a while
+        test_rpc(
+            "test_rpc_getneighbors_detail_throttled",
+            40932,
+            40933,
+            50932,
+            50933,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_server.connection.options.rpc_admin_api_key = Some("admin".to_string());
+                let client_ip = convo_server.peer_addr.ip();
+                for _ in 0..10 {
+                    peer_server
+                        .network
+                        .rpc_rate_limiter
+                        .record_admin_auth_failure(&client_ip, get_epoch_time_ms());
                 }
-                None
-            }
-            HttpRequestType::PostTransaction(ref _md, ref tx, ref attachment) => {
-                match chainstate.get_stacks_chain_tip(sortdb)? {
-                    Some(tip) => {
-                        let accepted = ConversationHttp::handle_post_transaction(
-                            &mut self.connection.protocol,
-                            &mut reply,
-                            &req,
-                            chainstate,
-                            sortdb,
-                            tip.consensus_hash,
-                            tip.anchored_block_hash,
-                            mempool,
-                            tx.clone(),
-                            &mut network.atlasdb,
-                            attachment.clone(),
-                            handler_opts.event_observer.as_deref(),
-                        )?;
-                        if accepted {
-                            // forward to peer network
-                            ret = Some(StacksMessageType::Transaction(tx.clone()));
+                convo_client.new_getneighbors_detail("admin")
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::TooManyRequests(response_md, msg) => {
+                        assert!(response_md.retry_after.is_some());
+                        assert_eq!(msg, "Too many failed admin requests");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getheaders() {
+        let server_blocks_cell = RefCell::new(None);
+
+        test_rpc(
+            "test_rpc_getheaders",
+            40012,
+            40013,
+            50012,
+            50013,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a few continuous block to staging
+                let mut blocks: Vec<StacksBlock> = vec![];
+                let mut index_block_hashes = vec![];
+                for i in 0..25 {
+                    let mut peer_server_block = make_codec_test_block(25);
+
+                    peer_server_block.header.total_work.work = (i + 1) as u64;
+                    peer_server_block.header.total_work.burn = (i + 1) as u64;
+                    peer_server_block.header.parent_block = blocks
+                        .last()
+                        .map(|blk| blk.block_hash())
+                        .unwrap_or(BlockHeaderHash([0u8; 32]));
+
+                    let peer_server_consensus_hash = ConsensusHash([(i + 1) as u8; 20]);
+                    let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                        &peer_server_consensus_hash,
+                        &peer_server_block.block_hash(),
+                    );
+
+                    test_debug!("Store peer server index block {:?}", &index_block_hash);
+                    store_staging_block(
+                        peer_server.chainstate(),
+                        &peer_server_consensus_hash,
+                        &peer_server_block,
+                        &ConsensusHash([i as u8; 20]),
+                        456,
+                        123,
+                    );
+                    set_block_processed(
+                        peer_server.chainstate(),
+                        &peer_server_consensus_hash,
+                        &peer_server_block.block_hash(),
+                        true,
+                    );
+
+                    index_block_hashes.push(index_block_hash);
+                    blocks.push(peer_server_block);
+                }
+
+                let rev_blocks: Vec<_> = blocks.into_iter().rev().collect();
+                let rev_ibhs: Vec<_> = index_block_hashes.into_iter().rev().collect();
+
+                let tip = rev_ibhs[0].clone();
+                *server_blocks_cell.borrow_mut() = Some((rev_blocks, rev_ibhs));
+
+                // now ask for it
+                convo_client.new_getheaders(25, TipRequest::SpecificTip(tip))
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Headers(response_md, headers) => {
+                        assert_eq!(headers.len(), 25);
+                        let expected = server_blocks_cell.borrow().clone().unwrap();
+                        for (i, h) in headers.iter().enumerate() {
+                            assert_eq!(h.header, expected.0[i].header);
+                            assert_eq!(h.consensus_hash, ConsensusHash([(25 - i) as u8; 20]));
+                            if i + 1 < headers.len() {
+                                assert_eq!(h.parent_block_id, expected.1[i + 1]);
+                            }
                         }
+                        true
                     }
-                    None => {
-                        let response_metadata = HttpResponseMetadata::from(&req);
-                        warn!("Failed to load Stacks chain tip");
-                        let response = HttpResponseType::ServerError(
-                            response_metadata,
-                            format!("Failed to load Stacks chain tip"),
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_events() {
+        test_rpc(
+            "test_rpc_get_contract_events",
+            40220,
+            40221,
+            50220,
+            50221,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_get_contract_events(ContractEventsQuery {
+                    contract: QualifiedContractIdentifier::new(
+                        StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                            .unwrap()
+                            .into(),
+                        "hello-world".into(),
+                    ),
+                    filter: ContractEventFilter {
+                        event_type: Some(ContractEventType::Print),
+                        topic: Some("init".to_string()),
+                        ..ContractEventFilter::default()
+                    },
+                    cursor: None,
+                    limit: MAX_CONTRACT_EVENTS,
+                })
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::ContractEvents(response_md, data) => {
+                        assert_eq!(data.events.len(), 1);
+                        assert_eq!(data.next_cursor, None);
+
+                        let event = &data.events[0];
+                        assert_eq!(event.topic, Some("init".to_string()));
+                        assert_eq!(
+                            event.contract_identifier,
+                            "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world"
+                        );
+                        let value = Value::try_deserialize_hex_untyped(&event.value[2..]).unwrap();
+                        assert_eq!(
+                            value.expect_tuple().get("units").unwrap().clone(),
+                            Value::Int(123)
                         );
-                        response.send(&mut self.connection.protocol, &mut reply)?;
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
                     }
                 }
-                None
-            }
-            HttpRequestType::GetAttachment(ref _md, ref content_hash) => {
-                ConversationHttp::handle_getattachment(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    &mut network.atlasdb,
-                    content_hash.clone(),
-                )?;
-                None
-            }
-            HttpRequestType::GetAttachmentsInv(
-                ref _md,
-                ref index_block_hash,
-                ref pages_indexes,
-            ) => {
-                ConversationHttp::handle_getattachmentsinv(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    &mut network.atlasdb,
-                    &index_block_hash,
-                    pages_indexes,
-                    &self.connection.options,
-                )?;
-                None
-            }
-            HttpRequestType::PostBlock(ref _md, ref consensus_hash, ref block) => {
-                let accepted = ConversationHttp::handle_post_block(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    sortdb,
-                    chainstate,
-                    consensus_hash,
-                    block,
-                )?;
-                if accepted {
-                    // inform the peer network so it can announce its presence
-                    ret = Some(StacksMessageType::Blocks(BlocksData {
-                        blocks: vec![(consensus_hash.clone(), block.clone())],
-                    }));
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_metrics() {
+        test_rpc(
+            "test_rpc_get_contract_metrics",
+            40830,
+            40831,
+            50830,
+            50831,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_get_contract_metrics(ContractMetricsQuery {
+                    contract: None,
+                    blocks: MAX_CONTRACT_METRICS_BLOCKS,
+                    limit: MAX_CONTRACT_METRICS,
+                })
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::ContractMetrics(response_md, data) => {
+                        assert_eq!(data.blocks, MAX_CONTRACT_METRICS_BLOCKS);
+                        let hello_world = data
+                            .contracts
+                            .iter()
+                            .find(|entry| {
+                                entry.contract_identifier
+                                    == "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world"
+                            })
+                            .unwrap();
+                        assert_eq!(hello_world.deploys, 1);
+                        assert!(hello_world.runtime > 0);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                None
-            }
-            HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_req) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    if let Some((consensus_hash, block_hash)) =
-                        ConversationHttp::handle_load_stacks_chain_tip_hashes(
-                            &mut self.connection.protocol,
-                            &mut reply,
-                            &req,
-                            tip,
-                            chainstate,
-                        )?
-                    {
-                        let accepted = ConversationHttp::handle_post_microblock(
-                            &mut self.connection.protocol,
-                            &mut reply,
-                            &req,
-                            &consensus_hash,
-                            &block_hash,
-                            chainstate,
-                            mblock,
-                        )?;
-                        if accepted {
-                            // forward to peer network
-                            let tip = StacksBlockHeader::make_index_block_hash(
-                                &consensus_hash,
-                                &block_hash,
-                            );
-                            ret = Some(StacksMessageType::Microblocks(MicroblocksData {
-                                index_anchor_block: tip,
-                                microblocks: vec![(*mblock).clone()],
-                            }));
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_get_chainstate_manifest() {
+        let server_tip_cell = RefCell::new(None);
+
+        test_rpc(
+            "test_rpc_get_chainstate_manifest",
+            40230,
+            40231,
+            50230,
+            50231,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let sortdb = peer_server.sortdb.take().unwrap();
+                let tip = peer_server
+                    .chainstate()
+                    .get_stacks_chain_tip(&sortdb)
+                    .unwrap()
+                    .unwrap();
+                peer_server.sortdb = Some(sortdb);
+
+                *server_tip_cell.borrow_mut() = Some(tip);
+
+                // a checkpoint at every block
+                convo_client.new_get_chainstate_manifest(1, 100)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::ChainstateManifest(response_md, manifest) => {
+                        let tip = server_tip_cell.borrow().clone().unwrap();
+                        assert_eq!(manifest.stacks_tip_height, tip.height);
+                        assert_eq!(manifest.interval, 1);
+                        assert_eq!(manifest.checkpoints.len(), tip.height as usize);
+
+                        for (i, checkpoint) in manifest.checkpoints.iter().enumerate() {
+                            assert_eq!(checkpoint.block_height, (i as u64) + 1);
                         }
+
+                        let last = manifest.checkpoints.last().unwrap();
+                        assert_eq!(last.consensus_hash, tip.consensus_hash);
+                        assert_eq!(
+                            last.index_block_hash,
+                            StacksBlockHeader::make_index_block_hash(
+                                &tip.consensus_hash,
+                                &tip.anchored_block_hash
+                            )
+                        );
+                        true
                     }
-                }
-                None
-            }
-            HttpRequestType::MemPoolQuery(ref _md, ref query, ref page_id_opt) => {
-                Some(ConversationHttp::handle_mempool_query(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    sortdb,
-                    chainstate,
-                    query.clone(),
-                    network.connection_opts.mempool_max_tx_query,
-                    page_id_opt.clone(),
-                )?)
-            }
-            HttpRequestType::OptionsPreflight(ref _md, ref _path) => {
-                let response_metadata = HttpResponseMetadata::from(&req);
-                let response = HttpResponseType::OptionsPreflight(response_metadata);
-                response
-                    .send(&mut self.connection.protocol, &mut reply)
-                    .map(|_| ())?;
-                None
-            }
-            HttpRequestType::GetIsTraitImplemented(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref trait_id,
-                ref tip_req,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_req,
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_is_trait_implemented(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                        trait_id,
-                    )?;
-                }
-                None
-            }
-            HttpRequestType::ClientError(ref _md, ref err) => {
-                let response_metadata = HttpResponseMetadata::from(&req);
-                let response = match err {
-                    ClientError::Message(s) => HttpResponseType::BadRequestJSON(
-                        response_metadata,
-                        serde_json::Value::String(s.to_string()),
-                    ),
-                    ClientError::NotFound(path) => {
-                        HttpResponseType::NotFound(response_metadata, path.clone())
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
                     }
-                };
+                }
+            },
+        );
+    }
 
-                response
-                    .send(&mut self.connection.protocol, &mut reply)
-                    .map(|_| ())?;
-                None
-            }
-        };
+    #[test]
+    #[ignore]
+    fn test_rpc_getblock_export() {
+        let server_tip_cell = RefCell::new(None);
 
-        match stream_opt {
-            None => {
-                self.reply_streams.push_back((reply, None, keep_alive));
-            }
-            Some(stream) => {
-                self.reply_streams.push_back((
-                    reply,
-                    Some((
-                        HttpChunkedTransferWriterState::new(STREAM_CHUNK_SIZE as usize),
-                        stream,
-                    )),
-                    keep_alive,
-                ));
-            }
-        }
-        Ok(ret)
-    }
+        test_rpc(
+            "test_rpc_getblock_export",
+            40200,
+            40201,
+            50200,
+            50201,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let sortdb = peer_server.sortdb.take().unwrap();
+                let tip = peer_server
+                    .chainstate()
+                    .get_stacks_chain_tip(&sortdb)
+                    .unwrap()
+                    .unwrap();
+                peer_server.sortdb = Some(sortdb);
 
-    /// Make progress on outbound requests.
-    fn send_outbound_responses(
-        &mut self,
-        mempool: &MemPoolDB,
-        chainstate: &mut StacksChainState,
-    ) -> Result<(), net_error> {
-        // send out streamed responses in the order they were requested
-        let mut drained_handle = false;
-        let mut drained_stream = false;
-        let mut broken = false;
-        let mut do_keep_alive = true;
+                *server_tip_cell.borrow_mut() = Some(tip);
 
-        test_debug!(
-            "{:?}: {} HTTP replies pending",
-            &self,
-            self.reply_streams.len()
+                // ask for everything from the first block on
+                convo_client.new_getblock_export(1, MAX_BLOCK_EXPORT)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::BlockExport(response_md, blocks) => {
+                        let tip = server_tip_cell.borrow().clone().unwrap();
+                        assert_eq!(blocks.len(), tip.height as usize);
+
+                        let last = blocks.last().unwrap();
+                        assert_eq!(last.consensus_hash, tip.consensus_hash);
+                        assert_eq!(last.block.block_hash(), tip.anchored_block_hash);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
-        let _self_str = format!("{}", &self);
+    }
 
-        match self.reply_streams.front_mut() {
-            Some((ref mut reply, ref mut stream_opt, ref keep_alive)) => {
-                do_keep_alive = *keep_alive;
+    #[test]
+    #[ignore]
+    fn test_rpc_unconfirmed_getblock() {
+        let server_block_cell = RefCell::new(None);
 
-                // if we're streaming, make some progress on the stream
-                match stream_opt {
-                    Some((ref mut http_chunk_state, ref mut stream)) => {
-                        let mut encoder =
-                            HttpChunkedTransferWriter::from_writer_state(reply, http_chunk_state);
-                        match stream.stream_to(mempool, chainstate, &mut encoder, STREAM_CHUNK_SIZE)
-                        {
-                            Ok(nw) => {
-                                test_debug!("{}: Streamed {} bytes", &_self_str, nw);
-                                if nw == 0 {
-                                    // EOF -- finish chunk and stop sending.
-                                    if !encoder.corked() {
-                                        encoder.flush().map_err(|e| {
-                                            test_debug!(
-                                                "{}: Write error on encoder flush: {:?}",
-                                                &_self_str,
-                                                &e
-                                            );
-                                            net_error::WriteError(e)
-                                        })?;
+        test_rpc(
+            "test_rpc_unconfirmed_getblock",
+            40020,
+            40021,
+            50020,
+            50021,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a block to staging
+                let peer_server_block = make_codec_test_block(25);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                );
 
-                                        encoder.cork();
+                test_debug!("Store peer server index block {:?}", &index_block_hash);
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block,
+                    &ConsensusHash([0x03; 20]),
+                    456,
+                    123,
+                );
 
-                                        test_debug!("{}: Stream indicates EOF", &_self_str);
-                                    }
+                *server_block_cell.borrow_mut() = Some(peer_server_block);
 
-                                    // try moving some data to the connection only once we're done
-                                    // streaming
-                                    match reply.try_flush() {
-                                        Ok(res) => {
-                                            test_debug!(
-                                                "{}: Streamed reply is drained?: {}",
-                                                &_self_str,
-                                                res
-                                            );
-                                            drained_handle = res;
-                                        }
-                                        Err(e) => {
-                                            // dead
-                                            warn!(
-                                                "{}: Broken HTTP connection: {:?}",
-                                                &_self_str, &e
-                                            );
-                                            broken = true;
-                                        }
-                                    }
-                                    drained_stream = true;
-                                }
-                            }
-                            Err(e) => {
-                                // broken -- terminate the stream.
-                                // For example, if we're streaming an unconfirmed block or
-                                // microblock, the data can get moved to the chunk store out from
-                                // under the stream.
-                                warn!(
-                                    "{}: Failed to send to HTTP connection: {:?}",
-                                    &_self_str, &e
-                                );
-                                broken = true;
-                            }
-                        }
+                // now ask for it
+                convo_client.new_getblock(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Block(response_md, block_info) => {
+                        assert_eq!(
+                            block_info.block_hash(),
+                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
+                        );
+                        true
                     }
-                    None => {
-                        // not streamed; all data is buffered
-                        drained_stream = true;
-
-                        // try moving some data to the connection
-                        match reply.try_flush() {
-                            Ok(res) => {
-                                test_debug!("{}: Reply is drained", &_self_str);
-                                drained_handle = res;
-                            }
-                            Err(e) => {
-                                // dead
-                                warn!("{}: Broken HTTP connection: {:?}", &_self_str, &e);
-                                broken = true;
-                            }
-                        }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
                     }
                 }
-            }
-            None => {}
-        }
+            },
+        );
+    }
 
-        if broken || (drained_handle && drained_stream) {
-            // done with this stream
-            test_debug!(
-                "{:?}: done with stream (broken={}, drained_handle={}, drained_stream={})",
-                &self,
-                broken,
-                drained_handle,
-                drained_stream
-            );
-            self.total_reply_count += 1;
-            self.reply_streams.pop_front();
+    #[test]
+    #[ignore]
+    fn test_rpc_confirmed_getblock() {
+        let server_block_cell = RefCell::new(None);
 
-            if !do_keep_alive {
-                // encountered "Connection: close"
-                self.keep_alive = false;
-            }
-        }
-        Ok(())
-    }
+        test_rpc(
+            "test_rpc_confirmed_getblock",
+            40030,
+            40031,
+            50030,
+            50031,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a block to staging
+                let peer_server_block = make_codec_test_block(25);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                );
 
-    pub fn try_send_recv_response(
-        req: ReplyHandleHttp,
-    ) -> Result<HttpResponseType, Result<ReplyHandleHttp, net_error>> {
-        match req.try_send_recv() {
-            Ok(message) => match message {
-                StacksHttpMessage::Request(_) => {
-                    warn!("Received response: not a HTTP response");
-                    return Err(Err(net_error::InvalidMessage));
+                test_debug!("Store peer server index block {:?}", &index_block_hash);
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block,
+                    &ConsensusHash([0x03; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                    true,
+                );
+
+                *server_block_cell.borrow_mut() = Some(peer_server_block);
+
+                // now ask for it
+                convo_client.new_getblock(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Block(response_md, block_info) => {
+                        assert_eq!(
+                            block_info.block_hash(),
+                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
-                StacksHttpMessage::Response(http_response) => Ok(http_response),
             },
-            Err(res) => Err(res),
-        }
+        );
     }
 
-    /// Make progress on our request/response
-    fn recv_inbound_response(&mut self) -> Result<(), net_error> {
-        // make progress on our pending request (if it exists).
-        let inprogress = self.pending_request.is_some();
-        let is_pending = self.pending_response.is_none();
+    #[test]
+    #[ignore]
+    fn test_rpc_confirmed_getblock_not_modified() {
+        let index_block_hash_cell = RefCell::new(None);
+
+        test_rpc(
+            "test_rpc_confirmed_getblock_not_modified",
+            40878,
+            40879,
+            50878,
+            50879,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a block to staging
+                let peer_server_block = make_codec_test_block(25);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                );
+
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block,
+                    &ConsensusHash([0x03; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                    true,
+                );
+
+                *index_block_hash_cell.borrow_mut() = Some(index_block_hash.clone());
 
-        let pending_request = self.pending_request.take();
-        let response = match pending_request {
-            None => Ok(self.pending_response.take()),
-            Some(req) => match ConversationHttp::try_send_recv_response(req) {
-                Ok(response) => Ok(Some(response)),
-                Err(res) => match res {
-                    Ok(handle) => {
-                        // try again
-                        self.pending_request = Some(handle);
-                        Ok(self.pending_response.take())
+                // ask for it as if we already had it
+                let mut request = convo_client.new_getblock(index_block_hash.clone());
+                request.metadata_mut().if_none_match =
+                    Some(format!("\"{}\"", index_block_hash.to_hex()));
+                request
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::NotModified(response_md) => {
+                        assert_eq!(
+                            response_md.etag,
+                            Some(index_block_hash_cell.borrow().as_ref().unwrap().to_hex())
+                        );
+                        true
                     }
-                    Err(e) => Err(e),
-                },
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
             },
-        }?;
+        );
+    }
 
-        self.pending_response = response;
+    #[test]
+    #[ignore]
+    fn test_rpc_confirmed_getblock_compressed() {
+        let server_block_cell = RefCell::new(None);
 
-        if inprogress && self.pending_request.is_none() {
-            test_debug!(
-                "{:?},id={}: HTTP request finished",
-                &self.peer_host,
-                self.conn_id
-            );
-        }
+        test_rpc(
+            "test_rpc_confirmed_getblock_compressed",
+            40882,
+            40883,
+            50882,
+            50883,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a block to staging
+                let peer_server_block = make_codec_test_block(25);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                );
 
-        if is_pending && self.pending_response.is_some() {
-            test_debug!(
-                "{:?},id={}: HTTP response finished",
-                &self.peer_host,
-                self.conn_id
-            );
-        }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block,
+                    &ConsensusHash([0x03; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                    true,
+                );
 
-        Ok(())
-    }
+                *server_block_cell.borrow_mut() = Some(peer_server_block);
 
-    /// Try to get our response
-    pub fn try_get_response(&mut self) -> Option<HttpResponseType> {
-        self.pending_response.take()
+                // ask for it gzipped
+                let mut request = convo_client.new_getblock(index_block_hash);
+                request.metadata_mut().accept_encoding = HttpContentEncoding::Gzip;
+                request
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::Block(response_md, block_info) => {
+                        assert_eq!(response_md.content_encoding, HttpContentEncoding::Gzip);
+                        assert_eq!(
+                            block_info.block_hash(),
+                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// Make progress on in-flight messages.
-    pub fn try_flush(
-        &mut self,
-        mempool: &MemPoolDB,
-        chainstate: &mut StacksChainState,
-    ) -> Result<(), net_error> {
-        self.send_outbound_responses(mempool, chainstate)?;
-        self.recv_inbound_response()?;
-        Ok(())
-    }
+    #[test]
+    #[ignore]
+    fn test_rpc_get_indexed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-    /// Is the connection idle?
-    pub fn is_idle(&self) -> bool {
-        self.pending_response.is_none()
-            && self.connection.inbox_len() == 0
-            && self.connection.outbox_len() == 0
-            && self.reply_streams.len() == 0
-    }
+        test_rpc(
+            "test_rpc_indexed_microblocks",
+            40040,
+            40041,
+            50040,
+            50041,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-    /// Is the conversation out of pending data?
-    /// Don't consider it drained if we haven't received anything yet
-    pub fn is_drained(&self) -> bool {
-        ((self.total_request_count > 0 && self.total_reply_count > 0)
-            || self.pending_error_response.is_some())
-            && self.is_idle()
-    }
+                let parent_block = make_codec_test_block(25);
+                let parent_consensus_hash = ConsensusHash([0x02; 20]);
+                let parent_index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                );
 
-    /// Should the connection be kept alive even if drained?
-    pub fn is_keep_alive(&self) -> bool {
-        self.keep_alive
-    }
+                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
+                mblocks.truncate(15);
 
-    /// When was the last time we got an inbound request?
-    pub fn get_last_request_time(&self) -> u64 {
-        self.last_request_timestamp
-    }
+                let mut child_block = make_codec_test_block(25);
+                let child_consensus_hash = ConsensusHash([0x03; 20]);
 
-    /// When was the last time we sent data as part of an outbound response?
-    pub fn get_last_response_time(&self) -> u64 {
-        self.last_response_timestamp
-    }
+                child_block.header.parent_block = parent_block.block_hash();
+                child_block.header.parent_microblock =
+                    mblocks.last().as_ref().unwrap().block_hash();
+                child_block.header.parent_microblock_sequence =
+                    mblocks.last().as_ref().unwrap().header.sequence;
 
-    /// When was this converation conencted?
-    pub fn get_connection_time(&self) -> u64 {
-        self.connection_time
-    }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block,
+                    &ConsensusHash([0x01; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                    true,
+                );
 
-    /// Make progress on in-flight requests and replies.
-    /// Returns the list of transactions we'll need to forward to the peer network
-    pub fn chat(
-        &mut self,
-        network: &mut PeerNetwork,
-        sortdb: &SortitionDB,
-        chainstate: &mut StacksChainState,
-        mempool: &mut MemPoolDB,
-        handler_args: &RPCHandlerArgs,
-    ) -> Result<Vec<StacksMessageType>, net_error> {
-        // if we have an in-flight error, then don't take any more requests.
-        if self.pending_error_response.is_some() {
-            return Ok(vec![]);
-        }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block,
+                    &parent_consensus_hash,
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    true,
+                );
 
-        // handle in-bound HTTP request(s)
-        let num_inbound = self.connection.inbox_len();
-        let mut ret = vec![];
-        test_debug!("{:?}: {} HTTP requests pending", &self, num_inbound);
+                let index_microblock_hash = StacksBlockHeader::make_index_block_hash(
+                    &parent_consensus_hash,
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-        for _i in 0..num_inbound {
-            let msg = match self.connection.next_inbox_message() {
-                None => {
-                    continue;
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &parent_consensus_hash,
+                        &parent_block.block_hash(),
+                        &mblock,
+                    );
                 }
-                Some(m) => m,
-            };
 
-            match msg {
-                StacksHttpMessage::Request(req) => {
-                    // new request
-                    self.total_request_count += 1;
-                    self.last_request_timestamp = get_epoch_time_secs();
-                    let start_time = Instant::now();
-                    let path = req.get_path();
-                    let msg_opt = monitoring::instrument_http_request_handler(req, |req| {
-                        self.handle_request(req, network, sortdb, chainstate, mempool, handler_args)
-                    })?;
+                set_microblocks_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-                    debug!("Processed HTTPRequest"; "path" => %path, "processing_time_ms" => start_time.elapsed().as_millis(), "conn_id" => self.conn_id, "peer_addr" => &self.peer_addr);
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-                    if let Some(msg) = msg_opt {
-                        ret.push(msg);
+                convo_client.new_getmicroblocks_indexed(index_microblock_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match (*http_response).clone() {
+                    HttpResponseType::Microblocks(_, mut microblocks) => {
+                        microblocks.reverse();
+                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
+                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                        true
                     }
-                }
-                StacksHttpMessage::Response(resp) => {
-                    // Is there someone else waiting for this message?  If so, pass it along.
-                    // (this _should_ be our pending_request handle)
-                    match self
-                        .connection
-                        .fulfill_request(StacksHttpMessage::Response(resp))
-                    {
-                        None => {
-                            test_debug!("{:?}: Fulfilled pending HTTP request", &self);
-                        }
-                        Some(_msg) => {
-                            // unsolicited; discard
-                            test_debug!("{:?}: Dropping unsolicited HTTP response", &self);
-                        }
+                    _ => {
+                        error!("Invalid response: {:?}", http_response);
+                        false
                     }
                 }
-            }
-        }
-
-        Ok(ret)
+            },
+        );
     }
 
-    /// Remove all timed-out messages, and ding the remote peer as unhealthy
-    pub fn clear_timeouts(&mut self) -> () {
-        self.connection.drain_timeouts();
-    }
+    #[test]
+    #[ignore]
+    fn test_rpc_get_confirmed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-    /// Load data into our HTTP connection
-    pub fn recv<R: Read>(&mut self, r: &mut R) -> Result<usize, net_error> {
-        let mut total_recv = 0;
-        loop {
-            let nrecv = match self.connection.recv_data(r) {
-                Ok(nr) => nr,
-                Err(e) => {
-                    debug!("{:?}: failed to recv: {:?}", self, &e);
-                    return Err(e);
-                }
-            };
+        test_rpc(
+            "test_rpc_confirmed_microblocks",
+            40042,
+            40043,
+            50042,
+            50043,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-            total_recv += nrecv;
-            if nrecv > 0 {
-                self.last_request_timestamp = get_epoch_time_secs();
-            } else {
-                break;
-            }
-        }
-        monitoring::update_inbound_rpc_bandwidth(total_recv as i64);
-        Ok(total_recv)
-    }
+                let parent_block = make_codec_test_block(25);
+                let parent_consensus_hash = ConsensusHash([0x02; 20]);
 
-    /// Write data out of our HTTP connection.  Write as much as we can
-    pub fn send<W: Write>(
-        &mut self,
-        w: &mut W,
-        mempool: &MemPoolDB,
-        chainstate: &mut StacksChainState,
-    ) -> Result<usize, net_error> {
-        let mut total_sz = 0;
-        loop {
-            // prime the Write
-            self.try_flush(mempool, chainstate)?;
+                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
+                mblocks.truncate(15);
 
-            let sz = match self.connection.send_data(w) {
-                Ok(sz) => sz,
-                Err(e) => {
-                    info!("{:?}: failed to send on HTTP conversation: {:?}", self, &e);
-                    return Err(e);
-                }
-            };
+                let mut child_block = make_codec_test_block(25);
+                let child_consensus_hash = ConsensusHash([0x03; 20]);
 
-            total_sz += sz;
-            if sz > 0 {
-                self.last_response_timestamp = get_epoch_time_secs();
-            } else {
-                break;
-            }
-        }
-        monitoring::update_inbound_rpc_bandwidth(total_sz as i64);
-        Ok(total_sz)
-    }
+                child_block.header.parent_block = parent_block.block_hash();
+                child_block.header.parent_microblock =
+                    mblocks.last().as_ref().unwrap().block_hash();
+                child_block.header.parent_microblock_sequence =
+                    mblocks.last().as_ref().unwrap().header.sequence;
 
-    /// Make a new getinfo request to this endpoint
-    pub fn new_getinfo(&self) -> HttpRequestType {
-        HttpRequestType::GetInfo(HttpRequestMetadata::from_host(self.peer_host.clone()))
-    }
+                let child_index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                );
 
-    /// Make a new getinfo request to this endpoint
-    pub fn new_getpoxinfo(&self, tip_req: TipRequest) -> HttpRequestType {
-        HttpRequestType::GetPoxInfo(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            tip_req,
-        )
-    }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block,
+                    &ConsensusHash([0x01; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                    true,
+                );
 
-    /// Make a new getneighbors request to this endpoint
-    pub fn new_getneighbors(&self) -> HttpRequestType {
-        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
-    }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block,
+                    &parent_consensus_hash,
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    true,
+                );
 
-    /// Make a new getheaders request to this endpoint
-    pub fn new_getheaders(&self, quantity: u64, tip_req: TipRequest) -> HttpRequestType {
-        HttpRequestType::GetHeaders(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            quantity,
-            tip_req,
-        )
-    }
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &parent_consensus_hash,
+                        &parent_block.block_hash(),
+                        &mblock,
+                    );
+                }
 
-    /// Make a new getblock request to this endpoint
-    pub fn new_getblock(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
-        HttpRequestType::GetBlock(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_block_hash,
-        )
-    }
+                set_microblocks_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-    /// Make a new get-microblocks request to this endpoint
-    pub fn new_getmicroblocks_indexed(
-        &self,
-        index_microblock_hash: StacksBlockId,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMicroblocksIndexed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_microblock_hash,
-        )
+                *server_microblocks_cell.borrow_mut() = mblocks;
+
+                convo_client.new_getmicroblocks_confirmed(child_index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match (*http_response).clone() {
+                    HttpResponseType::Microblocks(_, mut microblocks) => {
+                        microblocks.reverse();
+                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
+                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// Make a new get-microblocks-confirmed request to this endpoint
-    pub fn new_getmicroblocks_confirmed(
-        &self,
-        index_anchor_block_hash: StacksBlockId,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMicroblocksConfirmed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_anchor_block_hash,
-        )
-    }
+    #[test]
+    #[ignore]
+    fn test_rpc_unconfirmed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
+
+        test_rpc(
+            "test_rpc_unconfirmed_microblocks",
+            40050,
+            40051,
+            50050,
+            50051,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
+
+                let consensus_hash = ConsensusHash([0x02; 20]);
+                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
+                let index_block_hash =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
+
+                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
+                mblocks.truncate(15);
 
-    /// Make a new get-microblocks request for unconfirmed microblocks
-    pub fn new_getmicroblocks_unconfirmed(
-        &self,
-        anchored_index_block_hash: StacksBlockId,
-        min_seq: u16,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMicroblocksUnconfirmed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            anchored_index_block_hash,
-            min_seq,
-        )
-    }
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &consensus_hash,
+                        &anchored_block_hash,
+                        &mblock,
+                    );
+                }
 
-    /// Make a new get-unconfirmed-tx request
-    pub fn new_gettransaction_unconfirmed(&self, txid: Txid) -> HttpRequestType {
-        HttpRequestType::GetTransactionUnconfirmed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            txid,
-        )
-    }
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-    /// Make a new post-transaction request
-    pub fn new_post_transaction(&self, tx: StacksTransaction) -> HttpRequestType {
-        HttpRequestType::PostTransaction(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            tx,
-            None,
-        )
+                // start at seq 5
+                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 5)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Microblocks(response_md, microblocks) => {
+                        assert_eq!(microblocks.len(), 10);
+                        assert_eq!(
+                            *microblocks,
+                            (*server_microblocks_cell.borrow())[5..].to_vec()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// Make a new post-block request
-    pub fn new_post_block(&self, ch: ConsensusHash, block: StacksBlock) -> HttpRequestType {
-        HttpRequestType::PostBlock(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            ch,
-            block,
-        )
-    }
+    #[test]
+    #[ignore]
+    fn test_rpc_get_confirmed_microblock_stream_page() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-    /// Make a new post-microblock request
-    pub fn new_post_microblock(
-        &self,
-        mblock: StacksMicroblock,
-        tip_req: TipRequest,
-    ) -> HttpRequestType {
-        HttpRequestType::PostMicroblock(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            mblock,
-            tip_req,
-        )
-    }
+        test_rpc(
+            "test_rpc_get_confirmed_microblock_stream_page",
+            40914,
+            40915,
+            50914,
+            50915,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-    /// Make a new request for an account
-    pub fn new_getaccount(
-        &self,
-        principal: PrincipalData,
-        tip_req: TipRequest,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetAccount(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            principal,
-            tip_req,
-            with_proof,
-        )
-    }
+                let parent_block = make_codec_test_block(25);
+                let parent_consensus_hash = ConsensusHash([0x02; 20]);
+                let parent_index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                );
 
-    /// Make a new request for a data var
-    pub fn new_getdatavar(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        var_name: ClarityName,
-        tip_req: TipRequest,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetDataVar(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            var_name,
-            tip_req,
-            with_proof,
-        )
-    }
+                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
+                mblocks.truncate(15);
 
-    /// Make a new request for a data map
-    pub fn new_getmapentry(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        map_name: ClarityName,
-        key: Value,
-        tip_req: TipRequest,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMapEntry(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            map_name,
-            key,
-            tip_req,
-            with_proof,
-        )
-    }
+                let mut child_block = make_codec_test_block(25);
+                let child_consensus_hash = ConsensusHash([0x03; 20]);
 
-    /// Make a new request to get a contract's source
-    pub fn new_getcontractsrc(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        tip_req: TipRequest,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetContractSrc(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            tip_req,
-            with_proof,
-        )
-    }
+                child_block.header.parent_block = parent_block.block_hash();
+                child_block.header.parent_microblock =
+                    mblocks.last().as_ref().unwrap().block_hash();
+                child_block.header.parent_microblock_sequence =
+                    mblocks.last().as_ref().unwrap().header.sequence;
 
-    /// Make a new request to get a contract's ABI
-    pub fn new_getcontractabi(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        tip_req: TipRequest,
-    ) -> HttpRequestType {
-        HttpRequestType::GetContractABI(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            tip_req,
-        )
-    }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block,
+                    &ConsensusHash([0x01; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                    true,
+                );
 
-    /// Make a new request to run a read-only function
-    pub fn new_callreadonlyfunction(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        sender: PrincipalData,
-        function_name: ClarityName,
-        function_args: Vec<Value>,
-        tip_req: TipRequest,
-    ) -> HttpRequestType {
-        HttpRequestType::CallReadOnlyFunction(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            sender,
-            function_name,
-            function_args,
-            tip_req,
-        )
-    }
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block,
+                    &parent_consensus_hash,
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    true,
+                );
+
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &parent_consensus_hash,
+                        &parent_block.block_hash(),
+                        mblock,
+                    );
+                }
+
+                set_microblocks_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-    /// Make a new request for attachment inventory page
-    pub fn new_getattachmentsinv(
-        &self,
-        index_block_hash: StacksBlockId,
-        pages_indexes: HashSet<u32>,
-    ) -> HttpRequestType {
-        HttpRequestType::GetAttachmentsInv(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_block_hash,
-            pages_indexes,
-        )
-    }
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-    /// Make a new request for mempool contents
-    pub fn new_mempool_query(
-        &self,
-        query: MemPoolSyncData,
-        page_id_opt: Option<Txid>,
-    ) -> HttpRequestType {
-        HttpRequestType::MemPoolQuery(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            query,
-            page_id_opt,
-        )
+                // second page of four
+                convo_client.new_getmicroblock_stream(parent_index_block_hash, 4, 4)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::MicroblockStreamPage(_, page) => {
+                        let expected = (*server_microblocks_cell.borrow())[4..8].to_vec();
+                        assert!(page.confirmed);
+                        assert_eq!(page.next_start, Some(8));
+                        assert_eq!(page.microblocks.len(), expected.len());
+                        for (entry, mblock) in page.microblocks.iter().zip(expected.iter()) {
+                            assert_eq!(entry.microblock_hash, mblock.block_hash());
+                            assert_eq!(entry.sequence, mblock.header.sequence);
+                            assert_eq!(entry.parent_microblock_hash, mblock.header.prev_block);
+                            assert_eq!(entry.txids.len(), mblock.txs.len());
+                            let bytes = hex_bytes(&entry.microblock).unwrap();
+                            let decoded =
+                                StacksMicroblock::consensus_deserialize(&mut &bytes[..]).unwrap();
+                            assert_eq!(&decoded, mblock);
+                        }
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
-}
-
-#[cfg(test)]
-mod test {
-    use std::cell::RefCell;
-    use std::convert::TryInto;
-    use std::iter::FromIterator;
 
-    use address::*;
-    use burnchains::Burnchain;
-    use burnchains::BurnchainView;
-    use burnchains::*;
-    use chainstate::burn::ConsensusHash;
-    use chainstate::stacks::db::blocks::test::*;
-    use chainstate::stacks::db::StacksChainState;
-    use chainstate::stacks::db::StreamCursor;
-    use chainstate::stacks::miner::*;
-    use chainstate::stacks::test::*;
-    use chainstate::stacks::Error as chain_error;
-    use chainstate::stacks::*;
-    use net::codec::*;
-    use net::http::*;
-    use net::test::*;
-    use net::*;
-    use util::get_epoch_time_secs;
-    use util::hash::hex_bytes;
-    use util::pipe::*;
-    use vm::types::*;
+    #[test]
+    #[ignore]
+    fn test_rpc_get_unconfirmed_microblock_stream_page() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-    use crate::types::chainstate::BlockHeaderHash;
-    use crate::types::chainstate::BurnchainHeaderHash;
-    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+        test_rpc(
+            "test_rpc_get_unconfirmed_microblock_stream_page",
+            40916,
+            40917,
+            50916,
+            50917,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-    use core::mempool::{BLOOM_COUNTER_ERROR_RATE, MAX_BLOOM_COUNTER_TXS};
+                let consensus_hash = ConsensusHash([0x02; 20]);
+                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
+                let index_block_hash =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
 
-    use super::*;
+                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
+                mblocks.truncate(15);
 
-    const TEST_CONTRACT: &'static str = "
-        (define-data-var bar int 0)
-        (define-map unit-map { account: principal } { units: int })
-        (define-public (get-bar) (ok (var-get bar)))
-        (define-public (set-bar (x int) (y int))
-          (begin (var-set bar (/ x y)) (ok (var-get bar))))
-        (define-public (add-unit)
-          (begin
-            (map-set unit-map { account: tx-sender } { units: 1 } )
-            (var-set bar 1)
-            (ok 1)))
-        (begin
-          (map-set unit-map { account: 'ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R } { units: 123 }))";
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &consensus_hash,
+                        &anchored_block_hash,
+                        mblock,
+                    );
+                }
 
-    const TEST_CONTRACT_UNCONFIRMED: &'static str = "(define-read-only (ro-test) (ok 1))";
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-    fn convo_send_recv(
-        sender: &mut ConversationHttp,
-        sender_mempool: &MemPoolDB,
-        sender_chainstate: &mut StacksChainState,
-        receiver: &mut ConversationHttp,
-        receiver_mempool: &MemPoolDB,
-        receiver_chainstate: &mut StacksChainState,
-    ) -> () {
-        let (mut pipe_read, mut pipe_write) = Pipe::new();
-        pipe_read.set_nonblocking(true);
+                // the last page is short
+                convo_client.new_getmicroblock_stream(index_block_hash, 10, 8)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::MicroblockStreamPage(_, page) => {
+                        let expected = (*server_microblocks_cell.borrow())[10..].to_vec();
+                        assert!(!page.confirmed);
+                        assert_eq!(page.next_start, None);
+                        assert_eq!(
+                            page.microblocks
+                                .iter()
+                                .map(|entry| entry.microblock_hash.clone())
+                                .collect::<Vec<_>>(),
+                            expected
+                                .iter()
+                                .map(|mblock| mblock.block_hash())
+                                .collect::<Vec<_>>()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        loop {
-            let res = true;
+    #[test]
+    #[ignore]
+    fn test_rpc_unconfirmed_transaction() {
+        let last_txid = RefCell::new(Txid([0u8; 32]));
+        let last_mblock = RefCell::new(BlockHeaderHash([0u8; 32]));
 
-            sender.try_flush(sender_mempool, sender_chainstate).unwrap();
-            receiver
-                .try_flush(sender_mempool, receiver_chainstate)
+        test_rpc(
+            "test_rpc_unconfirmed_transaction",
+            40052,
+            40053,
+            50052,
+            50053,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
                 .unwrap();
 
-            pipe_write.try_flush().unwrap();
+                let sortdb = peer_server.sortdb.take().unwrap();
+                Relayer::setup_unconfirmed_state(peer_server.chainstate(), &sortdb).unwrap();
+                peer_server.sortdb = Some(sortdb);
 
-            let all_relays_flushed =
-                receiver.num_pending_outbound() == 0 && sender.num_pending_outbound() == 0;
+                assert!(peer_server.chainstate().unconfirmed_state.is_some());
+                let (txid, mblock_hash) = match peer_server.chainstate().unconfirmed_state {
+                    Some(ref unconfirmed) => {
+                        assert!(unconfirmed.mined_txs.len() > 0);
+                        let mut txid = Txid([0u8; 32]);
+                        let mut mblock_hash = BlockHeaderHash([0u8; 32]);
+                        for (next_txid, (_, mbh, ..)) in unconfirmed.mined_txs.iter() {
+                            txid = next_txid.clone();
+                            mblock_hash = mbh.clone();
+                            break;
+                        }
+                        (txid, mblock_hash)
+                    }
+                    None => {
+                        panic!("No unconfirmed state");
+                    }
+                };
 
-            let nw = sender
-                .send(&mut pipe_write, sender_mempool, sender_chainstate)
-                .unwrap();
-            let nr = receiver.recv(&mut pipe_read).unwrap();
+                *last_txid.borrow_mut() = txid.clone();
+                *last_mblock.borrow_mut() = mblock_hash.clone();
+
+                convo_client.new_gettransaction_unconfirmed(txid)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::UnconfirmedTransaction(response_md, unconfirmed_resp) => {
+                        assert_eq!(
+                            unconfirmed_resp.status,
+                            UnconfirmedTransactionStatus::Microblock {
+                                block_hash: (*last_mblock.borrow()).clone(),
+                                seq: 0
+                            }
+                        );
+                        let tx = StacksTransaction::consensus_deserialize(
+                            &mut &hex_bytes(&unconfirmed_resp.tx).unwrap()[..],
+                        )
+                        .unwrap();
+                        assert_eq!(tx.txid(), *last_txid.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_getblock() {
+        test_rpc(
+            "test_rpc_missing_getblock",
+            40060,
+            40061,
+            50060,
+            50061,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block_hash,
+                );
+
+                // now ask for it
+                convo_client.new_getblock(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-            test_debug!(
-                "res = {}, all_relays_flushed = {} ({},{}), nr = {}, nw = {}",
-                res,
-                all_relays_flushed,
-                receiver.num_pending_outbound(),
-                sender.num_pending_outbound(),
-                nr,
-                nw
-            );
-            if res && all_relays_flushed && nr == 0 && nw == 0 {
-                test_debug!("Breaking send_recv");
-                break;
-            }
-        }
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_index_getmicroblocks() {
+        test_rpc(
+            "test_rpc_missing_index_getmicroblocks",
+            40070,
+            40071,
+            50070,
+            50071,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block_hash,
+                );
+
+                // now ask for it
+                convo_client.new_getmicroblocks_indexed(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// General testing function to test RPC calls.
-    /// This function sets up two peers, a client and a server.
-    /// It takes in a function of type F that generates the request to be sent to the server
-    /// It takes in another function of type C that verifies that the result from
-    /// the server is as expected.
-    /// The parameter `include_microblocks` determines whether a microblock stream is mined or not.
-    fn test_rpc<F, C>(
-        test_name: &str,
-        peer_1_p2p: u16,
-        peer_1_http: u16,
-        peer_2_p2p: u16,
-        peer_2_http: u16,
-        include_microblocks: bool,
-        make_request: F,
-        check_result: C,
-    ) -> ()
-    where
-        F: FnOnce(
-            &mut TestPeer,
-            &mut ConversationHttp,
-            &mut TestPeer,
-            &mut ConversationHttp,
-        ) -> HttpRequestType,
-        C: FnOnce(&HttpRequestType, &HttpResponseType, &mut TestPeer, &mut TestPeer) -> bool,
-    {
-        let mut peer_1_config = TestPeerConfig::new(test_name, peer_1_p2p, peer_1_http);
-        let mut peer_2_config = TestPeerConfig::new(test_name, peer_2_p2p, peer_2_http);
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_confirmed_getmicroblocks() {
+        test_rpc(
+            "test_rpc_missing_confirmed_getmicroblocks",
+            40072,
+            40073,
+            50072,
+            50073,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block_hash,
+                );
 
-        // ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R
-        let privk1 = StacksPrivateKey::from_hex(
-            "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
-        )
-        .unwrap();
+                // now ask for it
+                convo_client.new_getmicroblocks_confirmed(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        // STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW
-        let privk2 = StacksPrivateKey::from_hex(
-            "94c319327cc5cd04da7147d32d836eb2e4c44f4db39aa5ede7314a761183d0c701",
-        )
-        .unwrap();
-        let microblock_privkey = StacksPrivateKey::new();
-        let microblock_pubkeyhash =
-            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_unconfirmed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-        let addr1 = StacksAddress::from_public_keys(
-            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
-            &AddressHashMode::SerializeP2PKH,
-            1,
-            &vec![StacksPublicKey::from_private(&privk1)],
-        )
-        .unwrap();
-        let addr2 = StacksAddress::from_public_keys(
-            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
-            &AddressHashMode::SerializeP2PKH,
-            1,
-            &vec![StacksPublicKey::from_private(&privk2)],
-        )
-        .unwrap();
+        test_rpc(
+            "test_rpc_missing_unconfirmed_microblocks",
+            40080,
+            40081,
+            50080,
+            50081,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-        peer_1_config.initial_balances = vec![
-            (addr1.to_account_principal(), 1000000000),
-            (addr2.to_account_principal(), 1000000000),
-        ];
+                let consensus_hash = ConsensusHash([0x02; 20]);
+                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
+                let index_block_hash =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
 
-        peer_2_config.initial_balances = vec![
-            (addr1.to_account_principal(), 1000000000),
-            (addr2.to_account_principal(), 1000000000),
-        ];
+                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
+                mblocks.truncate(15);
 
-        peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
-        peer_2_config.add_neighbor(&peer_1_config.to_neighbor());
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &consensus_hash,
+                        &anchored_block_hash,
+                        &mblock,
+                    );
+                }
 
-        let mut peer_1 = TestPeer::new(peer_1_config);
-        let mut peer_2 = TestPeer::new(peer_2_config);
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-        // mine one block with a contract in it
-        // first the coinbase
-        // make a coinbase for this miner
-        let mut tx_coinbase = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::Coinbase(CoinbasePayload([0x00; 32])),
+                // start at seq 16 (which doesn't exist)
+                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 16)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
-        tx_coinbase.chain_id = 0x80000000;
-        tx_coinbase.anchor_mode = TransactionAnchorMode::OnChainOnly;
-        tx_coinbase.auth.set_origin_nonce(0);
-
-        let mut tx_signer = StacksTransactionSigner::new(&tx_coinbase);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_coinbase_signed = tx_signer.get_tx().unwrap();
+    }
 
-        // next the contract
-        let contract = TEST_CONTRACT.clone();
-        let mut tx_contract = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::new_smart_contract(&format!("hello-world"), &contract.to_string())
-                .unwrap(),
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src() {
+        // Test v2/contracts/source (aka GetContractSrc) endpoint.
+        // In this test, we don't set any tip parameters, and allow the endpoint to execute against
+        // the canonical Stacks tip.
+        // The contract source we are querying for exists in the anchored state, so we expect the
+        // query to succeed.
+        test_rpc(
+            "test_rpc_get_contract_src",
+            40090,
+            40091,
+            50090,
+            50091,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetContractSrc(response_md, data) => {
+                        assert_eq!(data.source, TEST_CONTRACT);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        tx_contract.chain_id = 0x80000000;
-        tx_contract.auth.set_origin_nonce(1);
-        tx_contract.set_tx_fee(0);
-
-        let mut tx_signer = StacksTransactionSigner::new(&tx_contract);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_contract_signed = tx_signer.get_tx().unwrap();
-
-        // update account and state in a microblock that will be unconfirmed
-        let mut tx_cc = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::new_contract_call(addr1.clone(), "hello-world", "add-unit", vec![])
-                .unwrap(),
+    #[test]
+    #[ignore]
+    fn test_rpc_post_transaction_requires_client_cert() {
+        // Test v2/transactions (aka PostTransaction) on a node that only accepts writes from
+        // clients with a TLS client certificate.  This client doesn't have one, so we expect the
+        // node to refuse the transaction without looking at it.
+        test_rpc(
+            "test_rpc_post_transaction_requires_client_cert",
+            40884,
+            40885,
+            50884,
+            50885,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_server.connection.options.rpc_tls_require_client_cert = true;
+                let privk = StacksPrivateKey::new();
+                let tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::Coinbase(CoinbasePayload([0x00; 32])),
+                );
+                convo_client.new_post_transaction(tx)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::Forbidden(_, msg) => {
+                        assert_eq!(msg, "A client certificate is required to submit data");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        tx_cc.chain_id = 0x80000000;
-        tx_cc.auth.set_origin_nonce(2);
-        tx_cc.set_tx_fee(123);
-
-        let mut tx_signer = StacksTransactionSigner::new(&tx_cc);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_cc_signed = tx_signer.get_tx().unwrap();
-        let tx_cc_len = {
-            let mut bytes = vec![];
-            tx_cc_signed.consensus_serialize(&mut bytes).unwrap();
-            bytes.len() as u64
-        };
-
-        // make an unconfirmed contract
-        let unconfirmed_contract = TEST_CONTRACT_UNCONFIRMED.clone();
-        let mut tx_unconfirmed_contract = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::new_smart_contract(
-                &format!("hello-world-unconfirmed"),
-                &unconfirmed_contract.to_string(),
-            )
-            .unwrap(),
+    #[test]
+    #[ignore]
+    fn test_rpc_getinfo_rate_limited() {
+        // Test v2/info (aka GetInfo) from a client that has used up its IP's requests.  We expect
+        // the node to refuse with a 429 and tell the client when to try again.
+        test_rpc(
+            "test_rpc_getinfo_rate_limited",
+            40886,
+            40887,
+            50886,
+            50887,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                peer_server.network.rpc_rate_limiter = RPCRateLimiter::new(
+                    HashMap::new(),
+                    false,
+                    Some(RPCRateLimit {
+                        rate: 1.0,
+                        burst: 0,
+                    }),
+                );
+                convo_client.new_getinfo()
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::TooManyRequests(response_md, _) => {
+                        assert_eq!(response_md.retry_after, Some(1));
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        tx_unconfirmed_contract.chain_id = 0x80000000;
-        tx_unconfirmed_contract.auth.set_origin_nonce(3);
-        tx_unconfirmed_contract.set_tx_fee(0);
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src_not_modified() {
+        // Test v2/contracts/source (aka GetContractSrc) endpoint with an If-None-Match header.
+        // Any cached copy of a published contract's source is still good, so we expect the query
+        // to return 304 with the contract's ETag.
+        test_rpc(
+            "test_rpc_get_contract_src_not_modified",
+            40880,
+            40881,
+            50880,
+            50881,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let mut request = convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                );
+                request.metadata_mut().if_none_match = Some("*".to_string());
+                request
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::NotModified(response_md) => {
+                        assert!(response_md.etag.is_some());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        let mut tx_signer = StacksTransactionSigner::new(&tx_unconfirmed_contract);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_unconfirmed_contract_signed = tx_signer.get_tx().unwrap();
-        let tx_unconfirmed_contract_len = {
-            let mut bytes = vec![];
-            tx_unconfirmed_contract_signed
-                .consensus_serialize(&mut bytes)
-                .unwrap();
-            bytes.len() as u64
-        };
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src_unconfirmed_with_canonical_tip() {
+        // Test v2/contracts/source (aka GetContractSrc) endpoint.
+        // In this test, we don't set any tip parameters, and allow the endpoint to execute against
+        // the canonical Stacks tip.
+        // The contract source we are querying for only exists in the unconfirmed state, so we
+        // expect the query to fail.
+        test_rpc(
+            "test_rpc_get_contract_src_unconfirmed_with_canonical_tip",
+            40100,
+            40101,
+            50100,
+            50101,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(_, error_str) => {
+                        assert_eq!(error_str, "No contract source data found");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        let tip =
-            SortitionDB::get_canonical_burn_chain_tip(&peer_1.sortdb.as_ref().unwrap().conn())
-                .unwrap();
-        let mut anchor_cost = ExecutionCost::zero();
-        let mut anchor_size = 0;
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src_with_unconfirmed_tip() {
+        // Test v2/contracts/source (aka GetContractSrc) endpoint.
+        // In this test, we set `tip_req` to be the unconfirmed chain tip.
+        // The contract source we are querying for exists in the unconfirmed state, so we expect
+        // the query to succeed.
+        test_rpc(
+            "test_rpc_get_contract_src_with_unconfirmed_tip",
+            40102,
+            40103,
+            50102,
+            50103,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetContractSrc(response_md, data) => {
+                        assert_eq!(data.source, TEST_CONTRACT_UNCONFIRMED);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        // make a block and a microblock.
-        // Put the coinbase and smart-contract in the anchored block.
-        // Put the contract-call in the microblock
-        let (burn_ops, stacks_block, microblocks) = peer_1.make_tenure(
-            |ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, ref parent_opt, _| {
-                let parent_tip = match parent_opt {
-                    None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
-                    Some(block) => {
-                        let ic = sortdb.index_conn();
-                        let snapshot = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-                            &ic,
-                            &tip.sortition_id,
-                            &block.block_hash(),
-                        )
-                        .unwrap()
-                        .unwrap(); // succeeds because we don't fork
-                        StacksChainState::get_anchored_block_header_info(
-                            chainstate.db(),
-                            &snapshot.consensus_hash,
-                            &snapshot.winning_stacks_block_hash,
-                        )
-                        .unwrap()
-                        .unwrap()
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src_use_latest_tip() {
+        // Test v2/contracts/source (aka GetContractSrc) endpoint.
+        // In this test, we set `tip_req` to UseLatestUnconfirmedTip.
+        // The contract source we are querying for exists in the unconfirmed state, so we expect
+        // the query to succeed.
+        test_rpc(
+            "test_rpc_get_contract_src_use_latest_tip",
+            40104,
+            40105,
+            50104,
+            50105,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetContractSrc(response_md, data) => {
+                        assert_eq!(data.source, TEST_CONTRACT_UNCONFIRMED);
+                        true
                     }
-                };
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-                let block_builder = StacksBlockBuilder::make_regtest_block_builder(
-                    &parent_tip,
-                    vrf_proof,
-                    tip.total_burn,
-                    microblock_pubkeyhash,
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account() {
+        test_rpc(
+            "test_rpc_get_account",
+            40110,
+            40111,
+            50110,
+            50111,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
                 )
-                .unwrap();
-                let (anchored_block, anchored_block_size, anchored_block_cost) =
-                    StacksBlockBuilder::make_anchored_block_from_txs(
-                        block_builder,
-                        chainstate,
-                        &sortdb.index_conn(),
-                        vec![tx_coinbase_signed.clone(), tx_contract_signed.clone()],
-                    )
-                    .unwrap();
-
-                anchor_size = anchored_block_size;
-                anchor_cost = anchored_block_cost;
-
-                (anchored_block, vec![])
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 2);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 1000000000);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
             },
         );
+    }
 
-        let (_, _, consensus_hash) = peer_1.next_burnchain_block(burn_ops.clone());
-        peer_2.next_burnchain_block(burn_ops.clone());
-
-        peer_1.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
-        peer_2.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
-
-        // begin microblock section
-        if include_microblocks {
-            // build 1-block microblock stream with the contract-call and the unconfirmed contract
-            let microblock = {
-                let sortdb = peer_1.sortdb.take().unwrap();
-                Relayer::setup_unconfirmed_state(peer_1.chainstate(), &sortdb).unwrap();
-                let mblock = {
-                    let sort_iconn = sortdb.index_conn();
-                    let mut microblock_builder = StacksMicroblockBuilder::new(
-                        stacks_block.block_hash(),
-                        consensus_hash.clone(),
-                        peer_1.chainstate(),
-                        &sort_iconn,
-                        BlockBuilderSettings::max_value(),
-                    )
-                    .unwrap();
-                    let microblock = microblock_builder
-                        .mine_next_microblock_from_txs(
-                            vec![
-                                (tx_cc_signed, tx_cc_len),
-                                (tx_unconfirmed_contract_signed, tx_unconfirmed_contract_len),
-                            ],
-                            &microblock_privkey,
-                        )
-                        .unwrap();
-                    microblock
-                };
-                peer_1.sortdb = Some(sortdb);
-                mblock
-            };
-
-            // store microblock stream
-            peer_1
-                .chainstate()
-                .preprocess_streamed_microblock(
-                    &consensus_hash,
-                    &stacks_block.block_hash(),
-                    &microblock,
-                )
-                .unwrap();
-            peer_2
-                .chainstate()
-                .preprocess_streamed_microblock(
-                    &consensus_hash,
-                    &stacks_block.block_hash(),
-                    &microblock,
+    /// In this test, the query parameter `tip_req` is set to UseLatestUnconfirmedTip, and so we expect the
+    /// tip used for the query to be the latest microblock.
+    /// We check that the account state matches the state in the most recent microblock.
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account_use_latest_tip() {
+        test_rpc(
+            "test_rpc_get_account_use_latest_tip",
+            40112,
+            40113,
+            50112,
+            50113,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
                 )
-                .unwrap();
-
-            // process microblock stream to generate unconfirmed state
-            let canonical_tip = StacksBlockHeader::make_index_block_hash(
-                &consensus_hash,
-                &stacks_block.block_hash(),
-            );
-            let sortdb1 = peer_1.sortdb.take().unwrap();
-            let sortdb2 = peer_2.sortdb.take().unwrap();
-            peer_1
-                .chainstate()
-                .reload_unconfirmed_state(&sortdb1.index_conn(), canonical_tip.clone())
-                .unwrap();
-            peer_2
-                .chainstate()
-                .reload_unconfirmed_state(&sortdb2.index_conn(), canonical_tip.clone())
-                .unwrap();
-            peer_1.sortdb = Some(sortdb1);
-            peer_2.sortdb = Some(sortdb2);
-        }
-        // end microblock section
-
-        // stuff some transactions into peer_2's mempool
-        // (relates to mempool query tests)
-        let mut mempool = peer_2.mempool.take().unwrap();
-        let mut mempool_tx = mempool.tx_begin().unwrap();
-        for i in 0..10 {
-            let pk = StacksPrivateKey::new();
-            let addr = StacksAddress::from_public_keys(
-                C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
-                &AddressHashMode::SerializeP2PKH,
-                1,
-                &vec![StacksPublicKey::from_private(&StacksPrivateKey::new())],
-            )
-            .unwrap();
-            let mut tx = StacksTransaction {
-                version: TransactionVersion::Testnet,
-                chain_id: 0x80000000,
-                auth: TransactionAuth::from_p2pkh(&pk).unwrap(),
-                anchor_mode: TransactionAnchorMode::Any,
-                post_condition_mode: TransactionPostConditionMode::Allow,
-                post_conditions: vec![],
-                payload: TransactionPayload::TokenTransfer(
-                    addr.to_account_principal(),
-                    123,
-                    TokenTransferMemo([0u8; 34]),
-                ),
-            };
-            tx.set_tx_fee(1000);
-            tx.set_origin_nonce(0);
-
-            let txid = tx.txid();
-            let tx_bytes = tx.serialize_to_vec();
-            let origin_addr = tx.origin_address();
-            let origin_nonce = tx.get_origin_nonce();
-            let sponsor_addr = tx.sponsor_address().unwrap_or(origin_addr.clone());
-            let sponsor_nonce = tx.get_sponsor_nonce().unwrap_or(origin_nonce);
-            let tx_fee = tx.get_tx_fee();
-
-            // should succeed
-            MemPoolDB::try_add_tx(
-                &mut mempool_tx,
-                peer_1.chainstate(),
-                &consensus_hash,
-                &stacks_block.block_hash(),
-                txid.clone(),
-                tx_bytes,
-                tx_fee,
-                stacks_block.header.total_work.work,
-                &origin_addr,
-                origin_nonce,
-                &sponsor_addr,
-                sponsor_nonce,
-                None,
-            )
-            .unwrap();
-        }
-        mempool_tx.commit().unwrap();
-        peer_2.mempool.replace(mempool);
-
-        let view_1 = peer_1.get_burnchain_view().unwrap();
-        let view_2 = peer_2.get_burnchain_view().unwrap();
-
-        let mut convo_1 = ConversationHttp::new(
-            format!("127.0.0.1:{}", peer_1_http)
-                .parse::<SocketAddr>()
-                .unwrap(),
-            Some(UrlString::try_from(format!("http://peer1.com")).unwrap()),
-            peer_1.to_peer_host(),
-            &peer_1.config.connection_opts,
-            0,
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 4);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 999999877);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        let mut convo_2 = ConversationHttp::new(
-            format!("127.0.0.1:{}", peer_2_http)
-                .parse::<SocketAddr>()
-                .unwrap(),
-            Some(UrlString::try_from(format!("http://peer2.com")).unwrap()),
-            peer_2.to_peer_host(),
-            &peer_2.config.connection_opts,
-            1,
+    /// In this test, the query parameter `tip_req` is set to UseLatestUnconfirmedTip, but we did not generate
+    /// microblocks in the rpc test. Thus, we expect the tip used for the query to be the previous
+    /// anchor block (which is the latest tip).
+    /// We check that the account state matches the state in the previous anchor block.
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account_use_latest_tip_no_microblocks() {
+        test_rpc(
+            "test_rpc_get_account",
+            40114,
+            40115,
+            50114,
+            50115,
+            false,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 2);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 1000000000);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        let req = make_request(&mut peer_1, &mut convo_1, &mut peer_2, &mut convo_2);
-
-        convo_1.send_request(req.clone()).unwrap();
-        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
-        let peer_2_mempool = peer_2.mempool.take().unwrap();
-
-        test_debug!("convo1 sends to convo2");
-        convo_send_recv(
-            &mut convo_1,
-            &peer_1_mempool,
-            peer_1.chainstate(),
-            &mut convo_2,
-            &peer_2_mempool,
-            peer_2.chainstate(),
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account_unconfirmed() {
+        test_rpc(
+            "test_rpc_get_account_unconfirmed",
+            40120,
+            40121,
+            50120,
+            50121,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 4);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 1000000000 - 123);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        // hack around the borrow-checker
-        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
-        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
-
-        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
-            .unwrap();
-
-        convo_1
-            .chat(
-                &mut peer_1.network,
-                &mut peer_1_sortdb,
-                &mut peer_1_stacks_node.chainstate,
-                &mut peer_1_mempool,
-                &RPCHandlerArgs::default(),
-            )
-            .unwrap();
-
-        peer_1.sortdb = Some(peer_1_sortdb);
-        peer_1.stacks_node = Some(peer_1_stacks_node);
-        peer_1.mempool = Some(peer_1_mempool);
-        peer_2.mempool = Some(peer_2_mempool);
-
-        test_debug!("convo2 sends to convo1");
-
-        // hack around the borrow-checker
-        let mut peer_2_sortdb = peer_2.sortdb.take().unwrap();
-        let mut peer_2_stacks_node = peer_2.stacks_node.take().unwrap();
-        let mut peer_2_mempool = peer_2.mempool.take().unwrap();
-
-        Relayer::setup_unconfirmed_state(&mut peer_2_stacks_node.chainstate, &peer_2_sortdb)
-            .unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account_proof() {
+        test_rpc(
+            "test_rpc_get_account_proof",
+            40912,
+            40913,
+            50912,
+            50913,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_get_account_proof(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::UseLatestAnchoredTip,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::AccountProof(response_md, proof) => {
+                        assert_eq!(proof.entries.len(), 2);
+                        assert!(proof.entries.iter().all(|entry| entry.value.is_some()));
+                        assert_eq!(proof.anchors[0].index_block_hash, proof.tip);
+                        assert!(proof.anchors[0].header.is_some());
+                        assert!(proof.verify());
+
+                        // a different value doesn't verify
+                        let mut bad_proof = proof.clone();
+                        if let Some((ref mut value, _)) = bad_proof.entries[1].value {
+                            value.push_str("00");
+                        }
+                        assert!(!bad_proof.verify());
 
-        convo_2
-            .chat(
-                &mut peer_2.network,
-                &mut peer_2_sortdb,
-                &mut peer_2_stacks_node.chainstate,
-                &mut peer_2_mempool,
-                &RPCHandlerArgs::default(),
-            )
-            .unwrap();
+                        // nor does a different state root for the tip
+                        let mut bad_proof = proof.clone();
+                        bad_proof.anchors[0].state_index_root = TrieHash([0x01; 32]);
+                        assert!(!bad_proof.verify());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        peer_2.sortdb = Some(peer_2_sortdb);
-        peer_2.stacks_node = Some(peer_2_stacks_node);
-        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_data_var() {
+        test_rpc(
+            "test_rpc_get_data_var",
+            40122,
+            40123,
+            50122,
+            50123,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getdatavar(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "bar".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetDataVar(response_md, data) => {
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::Int(0)
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        convo_send_recv(
-            &mut convo_2,
-            &peer_2_mempool,
-            peer_2.chainstate(),
-            &mut convo_1,
-            &peer_1_mempool,
-            peer_1.chainstate(),
+    #[test]
+    #[ignore]
+    fn test_rpc_get_data_var_unconfirmed() {
+        test_rpc(
+            "test_rpc_get_data_var_unconfirmed",
+            40124,
+            40125,
+            50124,
+            50125,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getdatavar(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "bar".try_into().unwrap(),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetDataVar(response_md, data) => {
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::Int(1)
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        test_debug!("flush convo1");
-
-        // hack around the borrow-checker
-        convo_send_recv(
-            &mut convo_1,
-            &peer_1_mempool,
-            peer_1.chainstate(),
-            &mut convo_2,
-            &peer_2_mempool,
-            peer_2.chainstate(),
+    #[test]
+    #[ignore]
+    fn test_rpc_get_data_var_nonexistant() {
+        test_rpc(
+            "test_rpc_get_data_var_nonexistant",
+            40125,
+            40126,
+            50125,
+            50126,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getdatavar(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "bar-nonexistant".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "Data var not found");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        peer_2.mempool = Some(peer_2_mempool);
-
-        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
-        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
-
-        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
-            .unwrap();
-
-        convo_1
-            .chat(
-                &mut peer_1.network,
-                &mut peer_1_sortdb,
-                &mut peer_1_stacks_node.chainstate,
-                &mut peer_1_mempool,
-                &RPCHandlerArgs::default(),
-            )
-            .unwrap();
-
-        convo_1
-            .try_flush(&peer_1_mempool, &mut peer_1_stacks_node.chainstate)
-            .unwrap();
-
-        peer_1.sortdb = Some(peer_1_sortdb);
-        peer_1.stacks_node = Some(peer_1_stacks_node);
-        peer_1.mempool = Some(peer_1_mempool);
-
-        // should have gotten a reply
-        let resp_opt = convo_1.try_get_response();
-        assert!(resp_opt.is_some());
-
-        let resp = resp_opt.unwrap();
-        assert!(check_result(&req, &resp, &mut peer_1, &mut peer_2));
+    #[test]
+    #[ignore]
+    fn test_rpc_get_map_entry() {
+        // Test v2/map_entry (aka GetMapEntry) endpoint.
+        // In this test, we don't set any tip parameters, and we expect that querying for map data
+        // against the canonical Stacks tip will succeed.
+        test_rpc(
+            "test_rpc_get_map_entry",
+            40130,
+            40131,
+            50130,
+            50131,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                convo_client.new_getmapentry(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "unit-map".try_into().unwrap(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
+                            .unwrap(),
+                    ),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetMapEntry(response_md, data) => {
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(123))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
     #[test]
     #[ignore]
-    fn test_rpc_getinfo() {
-        let peer_server_info = RefCell::new(None);
+    fn test_rpc_get_map_entry_batch() {
+        // Test v2/map_entries (aka GetMapEntryBatch) endpoint.
+        // Present and missing keys each get an entry with a proof, in request order.
         test_rpc(
-            "test_rpc_getinfo",
-            40000,
-            40001,
-            50000,
-            50001,
+            "test_rpc_get_map_entry_batch",
+            40876,
+            40877,
+            50876,
+            50877,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_info = RPCPeerInfoData::from_network(
-                    &peer_server.network,
-                    &peer_server.stacks_node.as_ref().unwrap().chainstate,
-                    &None,
-                    &Sha256Sum::zero(),
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                let missing_principal =
+                    StacksAddress::from_string("ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM")
+                        .unwrap()
+                        .to_account_principal();
+                let contract_id = QualifiedContractIdentifier::new(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .into(),
+                    "hello-world".try_into().unwrap(),
                 );
-
-                *peer_server_info.borrow_mut() = Some(peer_info);
-
-                convo_client.new_getinfo()
+                convo_client.new_get_map_entry_batch(
+                    vec![MapEntryLookup {
+                        contract_id,
+                        map_name: "unit-map".try_into().unwrap(),
+                        keys: vec![
+                            Value::Tuple(
+                                TupleData::from_data(vec![(
+                                    "account".into(),
+                                    Value::Principal(principal),
+                                )])
+                                .unwrap(),
+                            ),
+                            Value::Tuple(
+                                TupleData::from_data(vec![(
+                                    "account".into(),
+                                    Value::Principal(missing_principal),
+                                )])
+                                .unwrap(),
+                            ),
+                        ],
+                    }],
+                    TipRequest::UseLatestAnchoredTip,
+                    true,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::PeerInfo(response_md, peer_data) => {
-                        assert_eq!(Some((*peer_data).clone()), *peer_server_info.borrow());
+                    HttpResponseType::GetMapEntryBatch(response_md, data) => {
+                        assert_eq!(data.maps.len(), 1);
+                        assert_eq!(data.maps[0].map, "unit-map");
+                        let entries = &data.maps[0].entries;
+                        assert_eq!(entries.len(), 2);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&entries[0].data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(123))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
+                        );
+                        assert!(entries[0].marf_proof.as_ref().unwrap().len() > 2);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&entries[1].data).unwrap(),
+                            Value::none()
+                        );
+                        assert_eq!(entries[1].marf_proof, Some("".to_string()));
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3872,50 +9172,61 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getpoxinfo() {
-        // Test v2/pox (aka GetPoxInfo) endpoint.
-        // In this test, `tip_req` is set to UseLatestAnchoredTip.
-        // Thus, the query for pox info will be against the canonical Stacks tip, which we expect to succeed.
-        let pox_server_info = RefCell::new(None);
+    fn test_rpc_get_map_entry_unconfirmed() {
+        // Test v2/map_entry (aka GetMapEntry) endpoint.
+        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying for map data
+        // against the unconfirmed state will succeed.
         test_rpc(
-            "test_rpc_getpoxinfo",
-            40002,
-            40003,
-            50002,
-            50003,
+            "test_rpc_get_map_entry_unconfirmed",
+            40140,
+            40141,
+            50140,
+            50141,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
-                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
-                let stacks_block_id = {
-                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
-                    StacksBlockHeader::make_index_block_hash(
-                        &tip.consensus_hash,
-                        &tip.anchored_block_hash,
-                    )
-                };
-                let pox_info = RPCPoxInfoData::from_db(
-                    &mut sortdb,
-                    chainstate,
-                    &stacks_block_id,
-                    &peer_client.config.burnchain,
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                convo_client.new_getmapentry(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "unit-map".try_into().unwrap(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
+                            .unwrap(),
+                    ),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                    false,
                 )
-                .unwrap();
-                *pox_server_info.borrow_mut() = Some(pox_info);
-                convo_client.new_getpoxinfo(TipRequest::UseLatestAnchoredTip)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::PoxInfo(response_md, pox_data) => {
-                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                    HttpResponseType::GetMapEntry(response_md, data) => {
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
+                        );
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3925,49 +9236,51 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getpoxinfo_use_latest_tip() {
-        // Test v2/pox (aka GetPoxInfo) endpoint.
-        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying for pox
-        // info against the unconfirmed state will succeed.
-        let pox_server_info = RefCell::new(None);
+    fn test_rpc_get_map_entry_use_latest_tip() {
         test_rpc(
-            "test_rpc_getpoxinfo_use_latest_tip",
-            40004,
-            40005,
-            50004,
-            50005,
+            "test_rpc_get_map_entry_use_latest_tip",
+            40142,
+            40143,
+            50142,
+            50143,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
-                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
-                let stacks_block_id = chainstate
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                let pox_info = RPCPoxInfoData::from_db(
-                    &mut sortdb,
-                    chainstate,
-                    &stacks_block_id,
-                    &peer_client.config.burnchain,
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                convo_client.new_getmapentry(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "unit-map".try_into().unwrap(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
+                            .unwrap(),
+                    ),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
                 )
-                .unwrap();
-                *pox_server_info.borrow_mut() = Some(pox_info);
-                convo_client.new_getpoxinfo(TipRequest::UseLatestUnconfirmedTip)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::PoxInfo(response_md, pox_data) => {
-                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                    HttpResponseType::GetMapEntry(response_md, data) => {
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
+                        );
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3977,28 +9290,37 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getneighbors() {
+    fn test_rpc_get_contract_abi() {
+        // Test /v2/contracts/interface (aka GetContractABI) endpoint.
+        // In this test, we don't set any tip parameters, and we expect that querying
+        // against the canonical Stacks tip will succeed.
         test_rpc(
-            "test_rpc_getneighbors",
-            40010,
-            40011,
-            50010,
-            50011,
+            "test_rpc_get_contract_abi",
+            40150,
+            40151,
+            50150,
+            50151,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
-             ref mut convo_server| { convo_client.new_getneighbors() },
+             ref mut convo_server| {
+                convo_client.new_getcontractabi(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                )
+            },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Neighbors(response_md, neighbor_info) => {
-                        assert_eq!(neighbor_info.sample.len(), 1);
-                        assert_eq!(neighbor_info.sample[0].port, peer_client.config.server_port); // we see ourselves as the neighbor
+                    HttpResponseType::NotFound(..) => {
+                        // not confirmed yet
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4008,85 +9330,41 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getheaders() {
-        let server_blocks_cell = RefCell::new(None);
-
+    fn test_rpc_get_contract_abi_unconfirmed() {
+        // Test /v2/contracts/interface (aka GetContractABI) endpoint.
+        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying
+        // against the unconfirmed state will succeed.
         test_rpc(
-            "test_rpc_getheaders",
-            40012,
-            40013,
-            50012,
-            50013,
+            "test_rpc_get_contract_abi_unconfirmed",
+            40152,
+            40153,
+            50152,
+            50153,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                // have "server" peer store a few continuous block to staging
-                let mut blocks: Vec<StacksBlock> = vec![];
-                let mut index_block_hashes = vec![];
-                for i in 0..25 {
-                    let mut peer_server_block = make_codec_test_block(25);
-
-                    peer_server_block.header.total_work.work = (i + 1) as u64;
-                    peer_server_block.header.total_work.burn = (i + 1) as u64;
-                    peer_server_block.header.parent_block = blocks
-                        .last()
-                        .map(|blk| blk.block_hash())
-                        .unwrap_or(BlockHeaderHash([0u8; 32]));
-
-                    let peer_server_consensus_hash = ConsensusHash([(i + 1) as u8; 20]);
-                    let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                        &peer_server_consensus_hash,
-                        &peer_server_block.block_hash(),
-                    );
-
-                    test_debug!("Store peer server index block {:?}", &index_block_hash);
-                    store_staging_block(
-                        peer_server.chainstate(),
-                        &peer_server_consensus_hash,
-                        &peer_server_block,
-                        &ConsensusHash([i as u8; 20]),
-                        456,
-                        123,
-                    );
-                    set_block_processed(
-                        peer_server.chainstate(),
-                        &peer_server_consensus_hash,
-                        &peer_server_block.block_hash(),
-                        true,
-                    );
-
-                    index_block_hashes.push(index_block_hash);
-                    blocks.push(peer_server_block);
-                }
-
-                let rev_blocks: Vec<_> = blocks.into_iter().rev().collect();
-                let rev_ibhs: Vec<_> = index_block_hashes.into_iter().rev().collect();
-
-                let tip = rev_ibhs[0].clone();
-                *server_blocks_cell.borrow_mut() = Some((rev_blocks, rev_ibhs));
-
-                // now ask for it
-                convo_client.new_getheaders(25, TipRequest::SpecificTip(tip))
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getcontractabi(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Headers(response_md, headers) => {
-                        assert_eq!(headers.len(), 25);
-                        let expected = server_blocks_cell.borrow().clone().unwrap();
-                        for (i, h) in headers.iter().enumerate() {
-                            assert_eq!(h.header, expected.0[i].header);
-                            assert_eq!(h.consensus_hash, ConsensusHash([(25 - i) as u8; 20]));
-                            if i + 1 < headers.len() {
-                                assert_eq!(h.parent_block_id, expected.1[i + 1]);
-                            }
-                        }
-                        true
-                    }
+                    HttpResponseType::GetContractABI(response_md, data) => true,
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4096,55 +9374,31 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_unconfirmed_getblock() {
-        let server_block_cell = RefCell::new(None);
-
+    fn test_rpc_get_contract_abi_use_latest_tip() {
         test_rpc(
-            "test_rpc_unconfirmed_getblock",
-            40020,
-            40021,
-            50020,
-            50021,
+            "test_rpc_get_contract_abi_use_latest_tip",
+            40154,
+            40155,
+            50154,
+            50155,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                // have "server" peer store a block to staging
-                let peer_server_block = make_codec_test_block(25);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block.block_hash(),
-                );
-
-                test_debug!("Store peer server index block {:?}", &index_block_hash);
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &peer_server_consensus_hash,
-                    &peer_server_block,
-                    &ConsensusHash([0x03; 20]),
-                    456,
-                    123,
-                );
-
-                *server_block_cell.borrow_mut() = Some(peer_server_block);
-
-                // now ask for it
-                convo_client.new_getblock(index_block_hash)
+                convo_client.new_getcontractabi(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Block(response_md, block_info) => {
-                        assert_eq!(
-                            block_info.block_hash(),
-                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
-                        );
-                        true
-                    }
+                    HttpResponseType::GetContractABI(response_md, data) => true,
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4154,61 +9408,50 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_confirmed_getblock() {
-        let server_block_cell = RefCell::new(None);
-
+    fn test_rpc_get_contract_storage() {
         test_rpc(
-            "test_rpc_confirmed_getblock",
-            40030,
-            40031,
-            50030,
-            50031,
+            "test_rpc_get_contract_storage",
+            40820,
+            40821,
+            50820,
+            50821,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                // have "server" peer store a block to staging
-                let peer_server_block = make_codec_test_block(25);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block.block_hash(),
-                );
-
-                test_debug!("Store peer server index block {:?}", &index_block_hash);
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &peer_server_consensus_hash,
-                    &peer_server_block,
-                    &ConsensusHash([0x03; 20]),
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &peer_server_consensus_hash,
-                    &peer_server_block.block_hash(),
-                    true,
-                );
-
-                *server_block_cell.borrow_mut() = Some(peer_server_block);
-
-                // now ask for it
-                convo_client.new_getblock(index_block_hash)
+                convo_client.new_getcontractstorage(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    TipRequest::UseLatestAnchoredTip,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Block(response_md, block_info) => {
+                    HttpResponseType::GetContractStorage(response_md, data) => {
                         assert_eq!(
-                            block_info.block_hash(),
-                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
+                            data.maps,
+                            vec![ContractStorageMapEntry {
+                                name: "unit-map".into(),
+                                key_type: "(tuple (account principal))".into(),
+                                value_type: "(tuple (units int))".into(),
+                            }]
+                        );
+                        assert_eq!(
+                            data.data_vars,
+                            vec![ContractStorageEntry {
+                                name: "bar".into(),
+                                type_signature: "int".into(),
+                            }]
                         );
+                        assert!(data.fungible_tokens.is_empty());
+                        assert!(data.non_fungible_tokens.is_empty());
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4218,110 +9461,61 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_indexed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_post_postconditions_check() {
         test_rpc(
-            "test_rpc_indexed_microblocks",
-            40040,
-            40041,
-            50040,
-            50041,
+            "test_rpc_post_postconditions_check",
+            40822,
+            40823,
+            50822,
+            50823,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
-                )
-                .unwrap();
-
-                let parent_block = make_codec_test_block(25);
-                let parent_consensus_hash = ConsensusHash([0x02; 20]);
-                let parent_index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &parent_consensus_hash,
-                    &parent_block.block_hash(),
-                );
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
-                mblocks.truncate(15);
-
-                let mut child_block = make_codec_test_block(25);
-                let child_consensus_hash = ConsensusHash([0x03; 20]);
-
-                child_block.header.parent_block = parent_block.block_hash();
-                child_block.header.parent_microblock =
-                    mblocks.last().as_ref().unwrap().block_hash();
-                child_block.header.parent_microblock_sequence =
-                    mblocks.last().as_ref().unwrap().header.sequence;
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block,
-                    &ConsensusHash([0x01; 20]),
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block.block_hash(),
-                    true,
-                );
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block,
-                    &parent_consensus_hash,
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    true,
-                );
-
-                let index_microblock_hash = StacksBlockHeader::make_index_block_hash(
-                    &parent_consensus_hash,
-                    &mblocks.last().as_ref().unwrap().block_hash(),
+                let privk = StacksPrivateKey::new();
+                let recipient = PrincipalData::from(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
                 );
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &parent_consensus_hash,
-                        &parent_block.block_hash(),
-                        &mblock,
-                    );
-                }
-
-                set_microblocks_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    &mblocks.last().as_ref().unwrap().block_hash(),
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::TokenTransfer(
+                        recipient.clone(),
+                        123,
+                        TokenTransferMemo([0u8; 34]),
+                    ),
                 );
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                convo_client.new_getmicroblocks_indexed(index_microblock_hash)
+                tx.post_condition_mode = TransactionPostConditionMode::Deny;
+                tx.post_conditions = vec![TransactionPostCondition::STX(
+                    PostConditionPrincipal::Origin,
+                    FungibleConditionCode::SentLe,
+                    100,
+                )];
+
+                let events = vec![StacksTransactionEvent::STXEvent(
+                    STXEventType::STXTransferEvent(STXTransferEventData {
+                        sender: PrincipalData::from(tx.origin_address()),
+                        recipient,
+                        amount: 123,
+                    }),
+                )];
+                convo_client.new_postconditionscheck(tx, events)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
-                match (*http_response).clone() {
-                    HttpResponseType::Microblocks(_, mut microblocks) => {
-                        microblocks.reverse();
-                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
-                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                match http_response {
+                    HttpResponseType::PostConditionCheck(response_md, data) => {
+                        assert!(!data.passed);
+                        let failure = data.failure.as_ref().unwrap();
+                        assert_eq!(failure["type"], "fungible_condition_not_met");
+                        assert_eq!(failure["index"], 0);
+                        assert_eq!(failure["amount_sent"], "123");
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4331,106 +9525,117 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_confirmed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_post_postconditions_check_stx_overflow() {
         test_rpc(
-            "test_rpc_confirmed_microblocks",
-            40042,
-            40043,
-            50042,
-            50043,
+            "test_rpc_post_postconditions_check_stx_overflow",
+            40824,
+            40825,
+            50824,
+            50825,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
-                )
-                .unwrap();
-
-                let parent_block = make_codec_test_block(25);
-                let parent_consensus_hash = ConsensusHash([0x02; 20]);
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
-                mblocks.truncate(15);
-
-                let mut child_block = make_codec_test_block(25);
-                let child_consensus_hash = ConsensusHash([0x03; 20]);
-
-                child_block.header.parent_block = parent_block.block_hash();
-                child_block.header.parent_microblock =
-                    mblocks.last().as_ref().unwrap().block_hash();
-                child_block.header.parent_microblock_sequence =
-                    mblocks.last().as_ref().unwrap().header.sequence;
-
-                let child_index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                );
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block,
-                    &ConsensusHash([0x01; 20]),
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block.block_hash(),
-                    true,
-                );
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block,
-                    &parent_consensus_hash,
-                    456,
-                    123,
+                let privk = StacksPrivateKey::new();
+                let recipient = PrincipalData::from(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
                 );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    true,
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::TokenTransfer(
+                        recipient.clone(),
+                        123,
+                        TokenTransferMemo([0u8; 34]),
+                    ),
                 );
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &parent_consensus_hash,
-                        &parent_block.block_hash(),
-                        &mblock,
-                    );
+                tx.post_condition_mode = TransactionPostConditionMode::Allow;
+                tx.post_conditions = vec![TransactionPostCondition::STX(
+                    PostConditionPrincipal::Origin,
+                    FungibleConditionCode::SentGt,
+                    0,
+                )];
+
+                // the transfer and the burn each fit in a u128, but their sum doesn't
+                let origin = PrincipalData::from(tx.origin_address());
+                let events = vec![
+                    StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(
+                        STXTransferEventData {
+                            sender: origin.clone(),
+                            recipient,
+                            amount: u128::MAX,
+                        },
+                    )),
+                    StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(
+                        STXBurnEventData {
+                            sender: origin,
+                            amount: 1,
+                        },
+                    )),
+                ];
+                convo_client.new_postconditionscheck(tx, events)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::PostConditionCheck(response_md, data) => {
+                        assert!(!data.passed);
+                        let failure = data.failure.as_ref().unwrap();
+                        assert_eq!(failure["type"], "stx_amount_overflow");
+                        assert_eq!(failure["index"], 0);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
                 }
+            },
+        );
+    }
 
-                set_microblocks_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    &mblocks.last().as_ref().unwrap().block_hash(),
-                );
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                convo_client.new_getmicroblocks_confirmed(child_index_block_hash)
+    #[test]
+    #[ignore]
+    fn test_rpc_call_read_only() {
+        // Test /v2/contracts/call-read (aka CallReadOnlyFunction) endpoint.
+        // In this test, we don't set any tip parameters, and we expect that querying
+        // against the canonical Stacks tip will succeed.
+        test_rpc(
+            "test_rpc_call_read_only",
+            40170,
+            40171,
+            50170,
+            50171,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "ro-test".try_into().unwrap(),
+                    vec![],
+                    TipRequest::UseLatestAnchoredTip,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
-                match (*http_response).clone() {
-                    HttpResponseType::Microblocks(_, mut microblocks) => {
-                        microblocks.reverse();
-                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
-                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                match http_response {
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.cause.is_some());
+                        assert!(data.cause.clone().unwrap().find("NoSuchContract").is_some());
+                        assert!(!data.okay);
+                        assert!(data.result.is_none());
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4440,60 +9645,48 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_unconfirmed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_call_read_only_use_latest_tip() {
+        // Test /v2/contracts/call-read (aka CallReadOnlyFunction) endpoint.
+        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying
+        // against the unconfirmed state will succeed.
         test_rpc(
-            "test_rpc_unconfirmed_microblocks",
-            40050,
-            40051,
-            50050,
-            50051,
+            "test_rpc_call_read_only_use_latest_tip",
+            40172,
+            40173,
+            50172,
+            50173,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "ro-test".try_into().unwrap(),
+                    vec![],
+                    TipRequest::UseLatestAnchoredTip,
                 )
-                .unwrap();
-
-                let consensus_hash = ConsensusHash([0x02; 20]);
-                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
-                let index_block_hash =
-                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
-                mblocks.truncate(15);
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &consensus_hash,
-                        &anchored_block_hash,
-                        &mblock,
-                    );
-                }
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                // start at seq 5
-                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 5)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Microblocks(response_md, microblocks) => {
-                        assert_eq!(microblocks.len(), 10);
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.okay);
                         assert_eq!(
-                            *microblocks,
-                            (*server_microblocks_cell.borrow())[5..].to_vec()
+                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
+                                .unwrap(),
+                            Value::okay(Value::Int(1)).unwrap()
                         );
+                        assert!(data.cause.is_none());
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4503,73 +9696,52 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_unconfirmed_transaction() {
-        let last_txid = RefCell::new(Txid([0u8; 32]));
-        let last_mblock = RefCell::new(BlockHeaderHash([0u8; 32]));
-
+    fn test_rpc_call_read_only_unconfirmed() {
         test_rpc(
-            "test_rpc_unconfirmed_transaction",
-            40052,
-            40053,
-            50052,
-            50053,
+            "test_rpc_call_read_only_unconfirmed",
+            40180,
+            40181,
+            50180,
+            50181,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "ro-test".try_into().unwrap(),
+                    vec![],
+                    TipRequest::SpecificTip(unconfirmed_tip),
                 )
-                .unwrap();
-
-                let sortdb = peer_server.sortdb.take().unwrap();
-                Relayer::setup_unconfirmed_state(peer_server.chainstate(), &sortdb).unwrap();
-                peer_server.sortdb = Some(sortdb);
-
-                assert!(peer_server.chainstate().unconfirmed_state.is_some());
-                let (txid, mblock_hash) = match peer_server.chainstate().unconfirmed_state {
-                    Some(ref unconfirmed) => {
-                        assert!(unconfirmed.mined_txs.len() > 0);
-                        let mut txid = Txid([0u8; 32]);
-                        let mut mblock_hash = BlockHeaderHash([0u8; 32]);
-                        for (next_txid, (_, mbh, ..)) in unconfirmed.mined_txs.iter() {
-                            txid = next_txid.clone();
-                            mblock_hash = mbh.clone();
-                            break;
-                        }
-                        (txid, mblock_hash)
-                    }
-                    None => {
-                        panic!("No unconfirmed state");
-                    }
-                };
-
-                *last_txid.borrow_mut() = txid.clone();
-                *last_mblock.borrow_mut() = mblock_hash.clone();
-
-                convo_client.new_gettransaction_unconfirmed(txid)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::UnconfirmedTransaction(response_md, unconfirmed_resp) => {
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.okay);
                         assert_eq!(
-                            unconfirmed_resp.status,
-                            UnconfirmedTransactionStatus::Microblock {
-                                block_hash: (*last_mblock.borrow()).clone(),
-                                seq: 0
-                            }
+                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
+                                .unwrap(),
+                            Value::okay(Value::Int(1)).unwrap()
                         );
-                        let tx = StacksTransaction::consensus_deserialize(
-                            &mut &hex_bytes(&unconfirmed_resp.tx).unwrap()[..],
-                        )
-                        .unwrap();
-                        assert_eq!(tx.txid(), *last_txid.borrow());
+                        assert!(data.cause.is_none());
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4579,34 +9751,80 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_getblock() {
+    fn test_rpc_call_read_only_batch() {
+        // Test /v2/contracts/call-read-batch (aka CallReadOnlyFunctionBatch) endpoint.
+        // Each call gets its own result, and a failing call does not fail the batch.
         test_rpc(
-            "test_rpc_missing_getblock",
-            40060,
-            40061,
-            50060,
-            50061,
+            "test_rpc_call_read_only_batch",
+            40840,
+            40841,
+            50840,
+            50841,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block_hash,
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                let contract_id = QualifiedContractIdentifier::new(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .into(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
                 );
-
-                // now ask for it
-                convo_client.new_getblock(index_block_hash)
+                convo_client.new_call_read_only_batch(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    vec![
+                        ReadOnlyCall {
+                            contract_id: contract_id.clone(),
+                            function: "ro-test".try_into().unwrap(),
+                            args: vec![],
+                        },
+                        ReadOnlyCall {
+                            contract_id: contract_id.clone(),
+                            function: "no-such-function".try_into().unwrap(),
+                            args: vec![],
+                        },
+                    ],
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
+                    HttpResponseType::CallReadOnlyFunctionBatch(response_md, data) => {
+                        let unconfirmed_tip = peer_client
+                            .chainstate()
+                            .unconfirmed_state
+                            .as_ref()
+                            .unwrap()
+                            .unconfirmed_chain_tip
+                            .clone();
+                        assert_eq!(data.tip, unconfirmed_tip.to_hex());
+                        assert_eq!(data.results.len(), 2);
+                        assert!(data.results[0].okay);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(
+                                &data.results[0].result.clone().unwrap()
+                            )
+                            .unwrap(),
+                            Value::okay(Value::Int(1)).unwrap()
+                        );
+                        assert!(!data.results[1].okay);
+                        assert!(data.results[1].result.is_none());
+                        assert!(data.results[1].cause.is_some());
+                        true
+                    }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4616,34 +9834,44 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_index_getmicroblocks() {
+    fn test_rpc_check_contract_call_args() {
+        // Test /v2/contracts/check-args (aka CheckContractCallArgs) endpoint.
+        // `set-bar` takes two ints, so a uint in either position is a mismatch.
         test_rpc(
-            "test_rpc_missing_index_getmicroblocks",
-            40070,
-            40071,
-            50070,
-            50071,
+            "test_rpc_check_contract_call_args",
+            40850,
+            40851,
+            50850,
+            50851,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block_hash,
-                );
-
-                // now ask for it
-                convo_client.new_getmicroblocks_indexed(index_block_hash)
+                convo_client.new_check_contract_call_args(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "set-bar".try_into().unwrap(),
+                    vec![Value::Int(1), Value::UInt(2)],
+                    TipRequest::UseLatestAnchoredTip,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
+                    HttpResponseType::CheckContractCallArgs(response_md, data) => {
+                        assert!(!data.okay);
+                        assert!(data.cause.is_none());
+                        assert_eq!(data.mismatches.len(), 1);
+                        assert_eq!(data.mismatches[0].index, 1);
+                        assert_eq!(data.mismatches[0].name, "y");
+                        assert_eq!(data.mismatches[0].expected, "int");
+                        assert_eq!(data.mismatches[0].found, "uint");
+                        true
+                    }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4653,36 +9881,33 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_confirmed_getmicroblocks() {
+    fn test_rpc_getattachmentsinv_limit_reached() {
         test_rpc(
-            "test_rpc_missing_confirmed_getmicroblocks",
-            40072,
-            40073,
-            50072,
-            50073,
+            "test_rpc_getattachmentsinv",
+            40190,
+            40191,
+            50190,
+            50191,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block_hash,
-                );
-
-                // now ask for it
-                convo_client.new_getmicroblocks_confirmed(index_block_hash)
+                let pages_indexes = HashSet::from_iter(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+                convo_client.new_getattachmentsinv(StacksBlockId([0x00; 32]), pages_indexes)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
+                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
-                    _ => {
-                        error!("Invalid response: {:?}", &http_response);
-                        false
+                    HttpResponseType::BadRequest(_, msg) => {
+                        assert_eq!(
+                            msg,
+                            "Number of attachment inv pages is limited by 8 per request"
+                        );
+                        true
                     }
+                    _ => false,
                 }
             },
         );
@@ -4690,55 +9915,33 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_unconfirmed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_mempool_query_txtags() {
         test_rpc(
-            "test_rpc_missing_unconfirmed_microblocks",
-            40080,
-            40081,
-            50080,
-            50081,
-            true,
+            "test_rpc_mempool_query_txtags",
+            40813,
+            40814,
+            50813,
+            50814,
+            false,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                convo_client.new_mempool_query(
+                    MemPoolSyncData::TxTags([0u8; 32], vec![]),
+                    Some(Txid([0u8; 32])),
                 )
-                .unwrap();
-
-                let consensus_hash = ConsensusHash([0x02; 20]);
-                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
-                let index_block_hash =
-                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
-                mblocks.truncate(15);
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &consensus_hash,
-                        &anchored_block_hash,
-                        &mblock,
-                    );
-                }
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                // start at seq 16 (which doesn't exist)
-                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 16)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
+                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
-                    _ => {
-                        error!("Invalid response: {:?}", &http_response);
-                        false
+                    HttpResponseType::MemPoolTxs(_, _, txs) => {
+                        // got everything
+                        assert_eq!(txs.len(), 10);
+                        true
                     }
+                    _ => false,
                 }
             },
         );
@@ -4746,42 +9949,38 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_src() {
-        // Test v2/contracts/source (aka GetContractSrc) endpoint.
-        // In this test, we don't set any tip parameters, and allow the endpoint to execute against
-        // the canonical Stacks tip.
-        // The contract source we are querying for exists in the anchored state, so we expect the
-        // query to succeed.
+    fn test_rpc_mempool_query_bloom() {
         test_rpc(
-            "test_rpc_get_contract_src",
-            40090,
-            40091,
-            50090,
-            50091,
-            true,
+            "test_rpc_mempool_query_bloom",
+            40815,
+            40816,
+            50815,
+            50816,
+            false,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractsrc(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
+                // empty bloom filter
+                convo_client.new_mempool_query(
+                    MemPoolSyncData::BloomFilter(BloomFilter::new(
+                        BLOOM_COUNTER_ERROR_RATE,
+                        MAX_BLOOM_COUNTER_TXS,
+                        BloomNodeHasher::new(&[0u8; 32]),
+                    )),
+                    Some(Txid([0u8; 32])),
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
+                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::GetContractSrc(response_md, data) => {
-                        assert_eq!(data.source, TEST_CONTRACT);
+                    HttpResponseType::MemPoolTxs(_, _, txs) => {
+                        // got everything
+                        assert_eq!(txs.len(), 10);
                         true
                     }
-                    _ => {
-                        error!("Invalid response; {:?}", &http_response);
-                        false
-                    }
+                    _ => false,
                 }
             },
         );
@@ -4789,42 +9988,37 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_src_unconfirmed_with_canonical_tip() {
-        // Test v2/contracts/source (aka GetContractSrc) endpoint.
-        // In this test, we don't set any tip parameters, and allow the endpoint to execute against
-        // the canonical Stacks tip.
-        // The contract source we are querying for only exists in the unconfirmed state, so we
-        // expect the query to fail.
+    fn test_rpc_get_mempool_transactions() {
         test_rpc(
-            "test_rpc_get_contract_src_unconfirmed_with_canonical_tip",
-            40100,
-            40101,
-            50100,
-            50101,
-            true,
+            "test_rpc_get_mempool_transactions",
+            40860,
+            40861,
+            50860,
+            50861,
+            false,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractsrc(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
-                )
+                convo_client.new_get_mempool_transactions(MemPoolTransactionsQuery {
+                    filter: MemPoolTxFilter::default(),
+                    cursor: None,
+                    limit: 4,
+                })
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
+                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::NotFound(_, error_str) => {
-                        assert_eq!(error_str, "No contract source data found");
+                    HttpResponseType::MemPoolTransactions(_, page) => {
+                        assert_eq!(page.transactions.len(), 4);
+                        assert_eq!(
+                            page.next_cursor.as_ref(),
+                            Some(&page.transactions[3].cursor)
+                        );
                         true
                     }
-                    _ => {
-                        error!("Invalid response; {:?}", &http_response);
-                        false
-                    }
+                    _ => false,
                 }
             },
         );
@@ -4832,88 +10026,66 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_src_with_unconfirmed_tip() {
-        // Test v2/contracts/source (aka GetContractSrc) endpoint.
-        // In this test, we set `tip_req` to be the unconfirmed chain tip.
-        // The contract source we are querying for exists in the unconfirmed state, so we expect
-        // the query to succeed.
+    fn test_rpc_get_mempool_transaction_unknown() {
         test_rpc(
-            "test_rpc_get_contract_src_with_unconfirmed_tip",
-            40102,
-            40103,
-            50102,
-            50103,
-            true,
+            "test_rpc_get_mempool_transaction_unknown",
+            40862,
+            40863,
+            50862,
+            50863,
+            false,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getcontractsrc(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    TipRequest::SpecificTip(unconfirmed_tip),
-                    false,
-                )
+                convo_client.new_get_mempool_transaction(Txid([0x11; 32]))
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
-                match http_response {
-                    HttpResponseType::GetContractSrc(response_md, data) => {
-                        assert_eq!(data.source, TEST_CONTRACT_UNCONFIRMED);
-                        true
-                    }
-                    _ => {
-                        error!("Invalid response; {:?}", &http_response);
-                        false
-                    }
-                }
+                println!("{:?}", http_response);
+                matches!(http_response, HttpResponseType::NotFound(_, _))
             },
         );
     }
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_src_use_latest_tip() {
-        // Test v2/contracts/source (aka GetContractSrc) endpoint.
-        // In this test, we set `tip_req` to UseLatestUnconfirmedTip.
-        // The contract source we are querying for exists in the unconfirmed state, so we expect
-        // the query to succeed.
+    fn test_rpc_get_address_transactions() {
         test_rpc(
-            "test_rpc_get_contract_src_use_latest_tip",
-            40104,
-            40105,
-            50104,
-            50105,
+            "test_rpc_get_address_transactions",
+            40870,
+            40871,
+            50870,
+            50871,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractsrc(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
-                )
+                convo_client.new_get_address_transactions(AddressTransactionsQuery {
+                    principal: StacksAddress::from_string(
+                        "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R",
+                    )
+                    .unwrap()
+                    .into(),
+                    cursor: None,
+                    limit: 1,
+                })
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetContractSrc(response_md, data) => {
-                        assert_eq!(data.source, TEST_CONTRACT_UNCONFIRMED);
+                    HttpResponseType::AddressTransactions(response_md, data) => {
+                        // the contract deploy comes after the coinbase
+                        assert_eq!(data.transactions.len(), 1);
+                        let tx = &data.transactions[0];
+                        assert_eq!(tx.receipt_index, 1);
+                        assert_eq!(tx.microblock_hash, None);
+                        assert_eq!(data.next_cursor, Some(tx.cursor.clone()));
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -4923,37 +10095,47 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_account() {
+    fn test_rpc_get_address_transactions_before_genesis() {
         test_rpc(
-            "test_rpc_get_account",
-            40110,
-            40111,
-            50110,
-            50111,
-            true,
+            "test_rpc_get_address_transactions_before_genesis",
+            40872,
+            40873,
+            50872,
+            50873,
+            false,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getaccount(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                let mut request =
+                    convo_client.new_get_address_transactions(AddressTransactionsQuery {
+                        principal: StacksAddress::from_string(
+                            "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R",
+                        )
                         .unwrap()
-                        .to_account_principal(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
-                )
+                        .into(),
+                        cursor: None,
+                        limit: 1,
+                    });
+                if let HttpRequestType::GetAddressTransactions(_, ref mut query) = request {
+                    query.cursor = Some(AddressTransactionCursor {
+                        block_height: 0,
+                        receipt_index: 0,
+                    });
+                }
+                request
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetAccount(response_md, data) => {
-                        assert_eq!(data.nonce, 2);
-                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
-                        assert_eq!(balance, 1000000000);
+                    HttpResponseType::AddressTransactions(_, data) => {
+                        // nothing comes before the genesis block
+                        assert!(data.transactions.is_empty());
+                        assert_eq!(data.next_cursor, None);
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -4961,42 +10143,37 @@ mod test {
         );
     }
 
-    /// In this test, the query parameter `tip_req` is set to UseLatestUnconfirmedTip, and so we expect the
-    /// tip used for the query to be the latest microblock.
-    /// We check that the account state matches the state in the most recent microblock.
     #[test]
     #[ignore]
-    fn test_rpc_get_account_use_latest_tip() {
+    fn test_rpc_get_address_assets() {
         test_rpc(
-            "test_rpc_get_account_use_latest_tip",
-            40112,
-            40113,
-            50112,
-            50113,
+            "test_rpc_get_address_assets",
+            40874,
+            40875,
+            50874,
+            50875,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getaccount(
+                convo_client.new_get_address_assets(
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap()
-                        .to_account_principal(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
+                        .into(),
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
-                match http_response {
-                    HttpResponseType::GetAccount(response_md, data) => {
-                        assert_eq!(data.nonce, 4);
-                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
-                        assert_eq!(balance, 999999877);
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::AddressAssets(response_md, data) => {
+                        // the test chain has no tokens
+                        assert!(data.fungible_tokens.is_empty());
+                        assert!(data.non_fungible_tokens.is_empty());
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5004,43 +10181,45 @@ mod test {
         );
     }
 
-    /// In this test, the query parameter `tip_req` is set to UseLatestUnconfirmedTip, but we did not generate
-    /// microblocks in the rpc test. Thus, we expect the tip used for the query to be the previous
-    /// anchor block (which is the latest tip).
-    /// We check that the account state matches the state in the previous anchor block.
     #[test]
     #[ignore]
-    fn test_rpc_get_account_use_latest_tip_no_microblocks() {
+    fn test_rpc_rosetta_block() {
+        // Test the Rosetta /block endpoint on the chain tip.  Every indexed transaction should
+        // carry its operations, and the block should link to its parent.
         test_rpc(
-            "test_rpc_get_account",
-            40114,
-            40115,
-            50114,
-            50115,
-            false,
+            "test_rpc_rosetta_block",
+            40888,
+            40889,
+            50888,
+            50889,
+            true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getaccount(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
+                convo_client.new_rosetta(
+                    RosettaEndpoint::Block,
+                    json!({
+                        "network_identifier": { "blockchain": "stacks", "network": "testnet" },
+                        "block_identifier": {}
+                    }),
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetAccount(response_md, data) => {
-                        assert_eq!(data.nonce, 2);
-                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
-                        assert_eq!(balance, 1000000000);
+                    HttpResponseType::Rosetta(response_md, data) => {
+                        let block = &data["block"];
+                        assert!(block["block_identifier"]["index"].as_u64().unwrap() > 0);
+                        assert!(block["parent_block_identifier"]["hash"].is_string());
+                        let txs = block["transactions"].as_array().unwrap();
+                        assert!(!txs.is_empty());
+                        for tx in txs.iter() {
+                            assert!(!tx["operations"].as_array().unwrap().is_empty());
+                        }
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5050,44 +10229,36 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_account_unconfirmed() {
+    fn test_rpc_rosetta_unknown_network() {
+        // Test a Rosetta request for a network this node does not serve.  We expect a Rosetta
+        // error object rather than data.
         test_rpc(
-            "test_rpc_get_account_unconfirmed",
-            40120,
-            40121,
-            50120,
-            50121,
+            "test_rpc_rosetta_unknown_network",
+            40890,
+            40891,
+            50890,
+            50891,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getaccount(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal(),
-                    TipRequest::SpecificTip(unconfirmed_tip),
-                    false,
+                convo_client.new_rosetta(
+                    RosettaEndpoint::NetworkStatus,
+                    json!({
+                        "network_identifier": { "blockchain": "bitcoin", "network": "mainnet" }
+                    }),
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetAccount(response_md, data) => {
-                        assert_eq!(data.nonce, 4);
-                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
-                        assert_eq!(balance, 1000000000 - 123);
+                    HttpResponseType::RosettaError(response_md, err) => {
+                        assert_eq!(err.code, ERR_NETWORK);
+                        assert!(!err.retriable);
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5097,39 +10268,26 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_data_var() {
+    fn test_rpc_get_health_live() {
         test_rpc(
-            "test_rpc_get_data_var",
-            40122,
-            40123,
-            50122,
-            50123,
+            "test_rpc_get_health_live",
+            40892,
+            40893,
+            50892,
+            50893,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
-             ref mut convo_server| {
-                convo_client.new_getdatavar(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "bar".try_into().unwrap(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
-                )
-            },
+             ref mut convo_server| { convo_client.new_get_health_live() },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetDataVar(response_md, data) => {
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::Int(0)
-                        );
+                    HttpResponseType::HealthLive(response_md, data) => {
+                        assert_eq!(data.status, "ok");
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5139,46 +10297,45 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_data_var_unconfirmed() {
+    fn test_rpc_get_health_ready() {
+        // The test peers never learn the burnchain tip height, so the node is not ready, and
+        // says so in a 503 that still carries the readiness checks.
         test_rpc(
-            "test_rpc_get_data_var_unconfirmed",
-            40124,
-            40125,
-            50124,
-            50125,
+            "test_rpc_get_health_ready",
+            40894,
+            40895,
+            50894,
+            50895,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
-             ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getdatavar(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "bar".try_into().unwrap(),
-                    TipRequest::SpecificTip(unconfirmed_tip),
-                    false,
-                )
-            },
+             ref mut convo_server| { convo_client.new_get_health_ready() },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetDataVar(response_md, data) => {
+                    HttpResponseType::HealthReady(response_md, data) => {
+                        assert!(!data.ready);
+                        assert!(!data.burnchain.ready);
+                        assert_eq!(data.burnchain.target_height, None);
                         assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::Int(1)
+                            data.burnchain.height,
+                            peer_server.network.burnchain_tip.block_height
+                        );
+                        assert_eq!(
+                            data.stacks_tip.height,
+                            peer_server
+                                .network
+                                .burnchain_tip
+                                .canonical_stacks_tip_height
+                        );
+                        assert_eq!(
+                            data.peers.min_count,
+                            peer_server.config.connection_opts.health_min_peers
                         );
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5187,37 +10344,27 @@ mod test {
     }
 
     #[test]
-    #[ignore]
-    fn test_rpc_get_data_var_nonexistant() {
+    fn test_rpc_get_peer_policy_disabled() {
+        // no admin API key is configured, so the admin endpoints are refused
         test_rpc(
-            "test_rpc_get_data_var_nonexistant",
-            40125,
-            40126,
-            50125,
-            50126,
+            "test_rpc_get_peer_policy_disabled",
+            40926,
+            40927,
+            50926,
+            50927,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
-             ref mut convo_server| {
-                convo_client.new_getdatavar(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "bar-nonexistant".try_into().unwrap(),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
-                )
-            },
+             ref mut convo_server| { convo_client.new_get_peer_policy("admin") },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(_, msg) => {
-                        assert_eq!(msg, "Data var not found");
+                    HttpResponseType::Forbidden(_, msg) => {
+                        assert_eq!(msg, "Admin endpoints are disabled");
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5227,54 +10374,56 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_map_entry() {
-        // Test v2/map_entry (aka GetMapEntry) endpoint.
-        // In this test, we don't set any tip parameters, and we expect that querying for map data
-        // against the canonical Stacks tip will succeed.
+    fn test_rpc_get_pox_cycle_current() {
+        let pox_cycle = RefCell::new(None);
         test_rpc(
-            "test_rpc_get_map_entry",
-            40130,
-            40131,
-            50130,
-            50131,
+            "test_rpc_get_pox_cycle_current",
+            40896,
+            40897,
+            50896,
+            50897,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let principal =
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal();
-                convo_client.new_getmapentry(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "unit-map".try_into().unwrap(),
-                    Value::Tuple(
-                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
-                            .unwrap(),
-                    ),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
+                let sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let stacks_block_id = {
+                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                    StacksBlockHeader::make_index_block_hash(
+                        &tip.consensus_hash,
+                        &tip.anchored_block_hash,
+                    )
+                };
+                let cycle = RPCRewardCycleData::from_db(
+                    sortdb,
+                    chainstate,
+                    &stacks_block_id,
+                    &peer_client.config.burnchain,
+                    None,
                 )
+                .unwrap();
+                assert!(cycle.is_current);
+                *pox_cycle.borrow_mut() = Some(cycle);
+                convo_client.new_get_pox_cycle(None, TipRequest::UseLatestAnchoredTip)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetMapEntry(response_md, data) => {
+                    HttpResponseType::PoxCycle(response_md, data) => {
+                        assert_eq!(Some((*data).clone()), *pox_cycle.borrow());
                         assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::some(Value::Tuple(
-                                TupleData::from_data(vec![("units".into(), Value::Int(123))])
-                                    .unwrap()
-                            ))
-                            .unwrap()
+                            data.end_block_height - data.reward_phase_start_block_height + 1,
+                            peer_client
+                                .config
+                                .burnchain
+                                .pox_constants
+                                .reward_cycle_length as u64
                         );
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5284,61 +10433,41 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_map_entry_unconfirmed() {
-        // Test v2/map_entry (aka GetMapEntry) endpoint.
-        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying for map data
-        // against the unconfirmed state will succeed.
+    fn test_rpc_get_pox_reward_set() {
+        let pox_reward_set = RefCell::new(None);
         test_rpc(
-            "test_rpc_get_map_entry_unconfirmed",
-            40140,
-            40141,
-            50140,
-            50141,
+            "test_rpc_get_pox_reward_set",
+            40898,
+            40899,
+            50898,
+            50899,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                let principal =
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal();
-                convo_client.new_getmapentry(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "unit-map".try_into().unwrap(),
-                    Value::Tuple(
-                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
-                            .unwrap(),
-                    ),
-                    TipRequest::SpecificTip(unconfirmed_tip),
-                    false,
-                )
+                let sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let burnchain = &peer_client.config.burnchain;
+                let burnchain_tip =
+                    SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).unwrap();
+                let current_cycle = burnchain
+                    .block_height_to_reward_cycle(burnchain_tip.block_height)
+                    .unwrap();
+                let reward_set =
+                    RPCRewardSetData::from_db(sortdb, chainstate, burnchain, current_cycle)
+                        .unwrap();
+                *pox_reward_set.borrow_mut() = Some(reward_set);
+                convo_client.new_get_pox_reward_set(current_cycle)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetMapEntry(response_md, data) => {
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::some(Value::Tuple(
-                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
-                                    .unwrap()
-                            ))
-                            .unwrap()
-                        );
+                    HttpResponseType::PoxRewardSet(response_md, data) => {
+                        assert_eq!(Some((*data).clone()), *pox_reward_set.borrow());
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5348,51 +10477,29 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_map_entry_use_latest_tip() {
+    fn test_rpc_get_pox_reward_set_future() {
         test_rpc(
-            "test_rpc_get_map_entry_use_latest_tip",
-            40142,
-            40143,
-            50142,
-            50143,
-            true,
-            |ref mut peer_client,
-             ref mut convo_client,
-             ref mut peer_server,
-             ref mut convo_server| {
-                let principal =
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal();
-                convo_client.new_getmapentry(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "unit-map".try_into().unwrap(),
-                    Value::Tuple(
-                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
-                            .unwrap(),
-                    ),
-                    TipRequest::UseLatestAnchoredTip,
-                    false,
-                )
+            "test_rpc_get_pox_reward_set_future",
+            40900,
+            40901,
+            50900,
+            50901,
+            true,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // this cycle's prior prepare phase hasn't happened yet
+                convo_client.new_get_pox_reward_set(1_000_000)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetMapEntry(response_md, data) => {
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::some(Value::Tuple(
-                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
-                                    .unwrap()
-                            ))
-                            .unwrap()
-                        );
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "Reward set is not known yet");
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5402,37 +10509,37 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_abi() {
-        // Test /v2/contracts/interface (aka GetContractABI) endpoint.
-        // In this test, we don't set any tip parameters, and we expect that querying
-        // against the canonical Stacks tip will succeed.
+    fn test_rpc_get_pox_stacker() {
         test_rpc(
-            "test_rpc_get_contract_abi",
-            40150,
-            40151,
-            50150,
-            50151,
+            "test_rpc_get_pox_stacker",
+            40902,
+            40903,
+            50902,
+            50903,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractabi(
+                // ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R has not stacked
+                convo_client.new_get_pox_stacker(
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
+                        .unwrap()
+                        .into(),
                     TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(..) => {
-                        // not confirmed yet
+                    HttpResponseType::PoxStacker(response_md, data) => {
+                        assert_eq!(data.locked_ustx, 0);
+                        assert_eq!(data.unlock_height, 0);
+                        assert_eq!(data.stacking, None);
+                        assert_eq!(data.delegation, None);
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5442,41 +10549,40 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_abi_unconfirmed() {
-        // Test /v2/contracts/interface (aka GetContractABI) endpoint.
-        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying
-        // against the unconfirmed state will succeed.
+    fn test_rpc_get_name() {
         test_rpc(
-            "test_rpc_get_contract_abi_unconfirmed",
-            40152,
-            40153,
-            50152,
-            50153,
+            "test_rpc_get_name",
+            40918,
+            40919,
+            50918,
+            50919,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getcontractabi(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    TipRequest::SpecificTip(unconfirmed_tip),
+                convo_client.new_get_name(
+                    "alice".to_string(),
+                    "test".to_string(),
+                    TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetContractABI(response_md, data) => true,
+                    HttpResponseType::Name(response_md, data) => {
+                        assert_eq!(data.name, "alice");
+                        assert_eq!(data.namespace, "test");
+                        assert_eq!(data.owner, "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R");
+                        assert_eq!(
+                            data.zonefile_hash,
+                            "0102030405060708091011121314151617181920"
+                        );
+                        assert_eq!(data.lease_started_at, 0);
+                        assert_eq!(data.lease_ending_at, None);
+                        true
+                    }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5486,31 +10592,29 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_abi_use_latest_tip() {
+    fn test_rpc_get_name_not_found() {
         test_rpc(
-            "test_rpc_get_contract_abi_use_latest_tip",
-            40154,
-            40155,
-            50154,
-            50155,
+            "test_rpc_get_name_not_found",
+            40920,
+            40921,
+            50920,
+            50921,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractabi(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
+                convo_client.new_get_name(
+                    "bob".to_string(),
+                    "test".to_string(),
                     TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetContractABI(response_md, data) => true,
+                    HttpResponseType::NotFound(..) => true,
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5520,96 +10624,66 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_call_read_only() {
-        // Test /v2/contracts/call-read (aka CallReadOnlyFunction) endpoint.
-        // In this test, we don't set any tip parameters, and we expect that querying
-        // against the canonical Stacks tip will succeed.
+    fn test_rpc_get_address_names() {
         test_rpc(
-            "test_rpc_call_read_only",
-            40170,
-            40171,
-            50170,
-            50171,
+            "test_rpc_get_address_names",
+            40922,
+            40923,
+            50922,
+            50923,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_callreadonlyfunction(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
+                convo_client.new_get_address_names(
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap()
-                        .to_account_principal(),
-                    "ro-test".try_into().unwrap(),
-                    vec![],
+                        .into(),
                     TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
-                        assert!(data.cause.is_some());
-                        assert!(data.cause.clone().unwrap().find("NoSuchContract").is_some());
-                        assert!(!data.okay);
-                        assert!(data.result.is_none());
+                    HttpResponseType::AddressNames(response_md, data) => {
+                        assert_eq!(data.names, vec!["alice.test".to_string()]);
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
             },
         );
-    }
 
-    #[test]
-    #[ignore]
-    fn test_rpc_call_read_only_use_latest_tip() {
-        // Test /v2/contracts/call-read (aka CallReadOnlyFunction) endpoint.
-        // In this test, we set `tip_req` to UseLatestUnconfirmedTip, and we expect that querying
-        // against the unconfirmed state will succeed.
         test_rpc(
-            "test_rpc_call_read_only_use_latest_tip",
-            40172,
-            40173,
-            50172,
-            50173,
+            "test_rpc_get_address_names_none",
+            40924,
+            40925,
+            50924,
+            50925,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_callreadonlyfunction(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                // STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW owns no names
+                convo_client.new_get_address_names(
+                    StacksAddress::from_string("STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW")
                         .unwrap()
-                        .to_account_principal(),
-                    "ro-test".try_into().unwrap(),
-                    vec![],
+                        .into(),
                     TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
-                        assert!(data.okay);
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
-                                .unwrap(),
-                            Value::okay(Value::Int(1)).unwrap()
-                        );
-                        assert!(data.cause.is_none());
+                    HttpResponseType::AddressNames(response_md, data) => {
+                        assert!(data.names.is_empty());
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5619,52 +10693,53 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_call_read_only_unconfirmed() {
+    fn test_rpc_get_block_state_diff() {
+        let expected_diff = RefCell::new(None);
         test_rpc(
-            "test_rpc_call_read_only_unconfirmed",
-            40180,
-            40181,
-            50180,
-            50181,
+            "test_rpc_get_block_state_diff",
+            40904,
+            40905,
+            50904,
+            50905,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
+                let sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let stacks_block_id = {
+                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                    StacksBlockHeader::make_index_block_hash(
+                        &tip.consensus_hash,
+                        &tip.anchored_block_hash,
+                    )
+                };
+                let diff = chainstate
+                    .get_block_state_diff(&stacks_block_id)
                     .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_callreadonlyfunction(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal(),
-                    "ro-test".try_into().unwrap(),
-                    vec![],
-                    TipRequest::SpecificTip(unconfirmed_tip),
-                )
+                    .unwrap();
+                *expected_diff.borrow_mut() = Some((stacks_block_id.clone(), diff));
+                convo_client.new_getblock_state_diff(stacks_block_id)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
+                let (stacks_block_id, diff) = expected_diff.borrow().clone().unwrap();
                 match http_response {
-                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
-                        assert!(data.okay);
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
-                                .unwrap(),
-                            Value::okay(Value::Int(1)).unwrap()
-                        );
-                        assert!(data.cause.is_none());
+                    HttpResponseType::BlockStateDiff(response_md, data) => {
+                        assert_eq!(data.index_block_hash, stacks_block_id);
+                        // the tip block mined and processed transactions
+                        assert!(!data.entries.is_empty());
+                        assert_eq!(data.entries.len(), diff.len());
+                        for (entry, expected) in data.entries.iter().zip(diff.iter()) {
+                            assert_eq!(entry.key, expected.key);
+                            assert_eq!(entry.path, expected.path);
+                            assert_eq!(entry.value, expected.value);
+                            assert_eq!(entry.prior_value, expected.prior_value);
+                        }
                         true
                     }
                     _ => {
-                        error!("Invalid response; {:?}", &http_response);
+                        error!("Invalid response: {:?}", &http_response);
                         false
                     }
                 }
@@ -5674,33 +10749,27 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getattachmentsinv_limit_reached() {
+    fn test_rpc_get_block_state_diff_unknown_block() {
         test_rpc(
-            "test_rpc_getattachmentsinv",
-            40190,
-            40191,
-            50190,
-            50191,
+            "test_rpc_get_block_state_diff_unknown_block",
+            40906,
+            40907,
+            50906,
+            50907,
             true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let pages_indexes = HashSet::from_iter(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
-                convo_client.new_getattachmentsinv(StacksBlockId([0x00; 32]), pages_indexes)
+                convo_client.new_getblock_state_diff(StacksBlockId([0x11; 32]))
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
-                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::BadRequest(_, msg) => {
-                        assert_eq!(
-                            msg,
-                            "Number of attachment inv pages is limited by 8 per request"
-                        );
-                        true
+                    HttpResponseType::NotFound(..) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
                     }
-                    _ => false,
                 }
             },
         );
@@ -5708,33 +10777,75 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_mempool_query_txtags() {
+    fn test_rpc_post_transaction_with_report() {
+        // Test v2/transactions?report=1 with an STX transfer that pays no fee and sends more than
+        // its post-condition allows.  We expect a report that says it was rejected for its fee,
+        // and that its post-condition does not hold.
         test_rpc(
-            "test_rpc_mempool_query_txtags",
-            40813,
-            40814,
-            50813,
-            50814,
-            false,
+            "test_rpc_post_transaction_with_report",
+            40908,
+            40909,
+            50908,
+            50909,
+            true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_mempool_query(
-                    MemPoolSyncData::TxTags([0u8; 32], vec![]),
-                    Some(Txid([0u8; 32])),
-                )
+                let privk = StacksPrivateKey::new();
+                let recipient = PrincipalData::from(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                );
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::TokenTransfer(
+                        recipient.clone(),
+                        123,
+                        TokenTransferMemo([0u8; 34]),
+                    ),
+                );
+                tx.post_condition_mode = TransactionPostConditionMode::Deny;
+                tx.post_conditions = vec![TransactionPostCondition::STX(
+                    PostConditionPrincipal::Origin,
+                    FungibleConditionCode::SentLe,
+                    100,
+                )];
+                tx.chain_id = 0x80000000;
+
+                let mut signer = StacksTransactionSigner::new(&tx);
+                signer.sign_origin(&privk).unwrap();
+                convo_client.new_post_transaction_with_report(signer.get_tx().unwrap())
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
-                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::MemPoolTxs(_, _, txs) => {
-                        // got everything
-                        assert_eq!(txs.len(), 10);
+                    HttpResponseType::TransactionAdmissionReport(_, report) => {
+                        assert!(!report.accepted);
+                        assert!(!report.already_in_mempool);
+                        assert_eq!(report.rejection.as_ref().unwrap()["reason"], "FeeTooLow");
+
+                        let origin_nonce = report.origin_nonce.as_ref().unwrap();
+                        assert_eq!(origin_nonce.expected, 0);
+                        assert_eq!(origin_nonce.actual, 0);
+                        assert!(report.sponsor_nonce.is_none());
+
+                        assert_eq!(report.fee.fee, 0);
+                        assert_eq!(report.fee.minimum_fee, report.fee.tx_len);
+
+                        assert_eq!(report.post_conditions.mode, "deny");
+                        assert_eq!(report.post_conditions.count, 1);
+                        assert_eq!(report.post_conditions.passed, Some(false));
+                        assert_eq!(
+                            report.post_conditions.failure.as_ref().unwrap()["type"],
+                            "fungible_condition_not_met"
+                        );
                         true
                     }
-                    _ => false,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
             },
         );
@@ -5742,38 +10853,44 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_mempool_query_bloom() {
+    fn test_rpc_post_transaction_with_report_unknown_outflows() {
+        // Test v2/transactions?report=1 with a transaction whose outflows can't be known before
+        // it runs.  We expect its post-conditions to be left unchecked.
         test_rpc(
-            "test_rpc_mempool_query_bloom",
-            40815,
-            40816,
-            50815,
-            50816,
-            false,
+            "test_rpc_post_transaction_with_report_unknown_outflows",
+            40910,
+            40911,
+            50910,
+            50911,
+            true,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                // empty bloom filter
-                convo_client.new_mempool_query(
-                    MemPoolSyncData::BloomFilter(BloomFilter::new(
-                        BLOOM_COUNTER_ERROR_RATE,
-                        MAX_BLOOM_COUNTER_TXS,
-                        BloomNodeHasher::new(&[0u8; 32]),
-                    )),
-                    Some(Txid([0u8; 32])),
-                )
+                let privk = StacksPrivateKey::new();
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::Coinbase(CoinbasePayload([0x00; 32])),
+                );
+                tx.set_tx_fee(1000);
+                convo_client.new_post_transaction_with_report(tx)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
-                println!("{:?}", http_response);
                 match http_response {
-                    HttpResponseType::MemPoolTxs(_, _, txs) => {
-                        // got everything
-                        assert_eq!(txs.len(), 10);
+                    HttpResponseType::TransactionAdmissionReport(_, report) => {
+                        assert!(!report.accepted);
+                        assert!(report.rejection.is_some());
+                        assert_eq!(report.fee.fee, 1000);
+                        assert_eq!(report.post_conditions.count, 0);
+                        assert_eq!(report.post_conditions.passed, None);
+                        assert!(report.post_conditions.failure.is_none());
                         true
                     }
-                    _ => false,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
                 }
             },
         );
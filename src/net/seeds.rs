@@ -0,0 +1,216 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! DNS seeds.  A DNS seed is a hostname whose A/AAAA records are the addresses of reachable
+//! nodes, so a new network doesn't need a hand-maintained list of bootstrap peers.  Like
+//! Bitcoin's seeds, a seed can be asked for nodes that offer particular services by prefixing
+//! its hostname with `x<service bits in hex>.` -- e.g. `x3.seed.example.com` for nodes with
+//! `RELAY` and `RPC`.  If the prefixed name doesn't resolve, we ask for the bare hostname.
+//!
+//! DNS only gives us addresses, not public keys, so the neighbor walk learns each seeded peer's
+//! key from its handshake before the peer goes into the frontier.  The configured bootstrap
+//! peers remain the fallback if no seed answers.
+
+use std::mem;
+
+use net::dns::{DNSClient, DNSRequest};
+use net::p2p::PeerNetwork;
+use net::NeighborKey;
+use net::PeerAddress;
+use util::{get_epoch_time_ms, get_epoch_time_secs};
+
+/// Split a DNS seed of the form `host[:port]` into its hostname and port.  Returns None if the
+/// hostname is empty or the port is not a number.
+pub fn parse_dns_seed(seed: &str, default_port: u16) -> Option<(String, u16)> {
+    let (host, port) = match seed.rfind(':') {
+        Some(idx) => {
+            let port = seed[idx + 1..].parse::<u16>().ok()?;
+            (&seed[..idx], port)
+        }
+        None => (seed, default_port),
+    };
+    if host.is_empty() || host.contains(':') {
+        return None;
+    }
+    Some((host.to_string(), port))
+}
+
+/// The name to look up in order to ask a DNS seed for nodes offering `services`
+pub fn dns_seed_query_host(host: &str, services: u16) -> String {
+    if services == 0 {
+        host.to_string()
+    } else {
+        format!("x{:x}.{}", services, host)
+    }
+}
+
+impl PeerNetwork {
+    /// Queue a lookup of a DNS seed's hostname
+    fn queue_dns_seed_lookup(&mut self, dns_client: &mut DNSClient, host: &str, port: u16) {
+        let deadline = get_epoch_time_ms() + self.connection_opts.dns_timeout;
+        match dns_client.queue_lookup(host, port, deadline) {
+            Ok(_) => {
+                self.dns_seed_requests
+                    .push(DNSRequest::new(host.to_string(), port, deadline));
+            }
+            Err(e) => {
+                warn!(
+                    "{:?}: Failed to queue DNS seed lookup of {}: {:?}",
+                    &self.local_peer, host, &e
+                );
+            }
+        }
+    }
+
+    /// Add the addresses a DNS seed gave us to the peers to bootstrap from
+    fn add_dns_seed_peers(&mut self, host: &str, addrs: Vec<std::net::SocketAddr>) {
+        debug!(
+            "{:?}: DNS seed {} gave us {} peers",
+            &self.local_peer,
+            host,
+            addrs.len()
+        );
+        for addr in addrs {
+            let nk = NeighborKey::from_socketaddr(
+                self.peer_version,
+                self.local_peer.network_id,
+                &PeerAddress::canonical_socketaddr(&addr),
+            );
+            if !self.dns_seed_peers.contains(&nk) {
+                self.dns_seed_peers.push_back(nk);
+            }
+        }
+    }
+
+    /// Look up our DNS seeds whenever we've run out of seeded peers to try, and collect the
+    /// answers.  Does nothing if no DNS seeds are configured.
+    pub fn resolve_dns_seeds(&mut self, dns_client_opt: &mut Option<&mut DNSClient>) {
+        if self.connection_opts.dns_seeds.is_empty() {
+            return;
+        }
+        let dns_client = match dns_client_opt {
+            Some(ref mut dns_client) => dns_client,
+            None => {
+                return;
+            }
+        };
+        if let Err(e) = dns_client.try_recv() {
+            debug!(
+                "{:?}: Failed to receive DNS seed lookups: {:?}",
+                &self.local_peer, &e
+            );
+        }
+
+        let services = self.connection_opts.dns_seed_services;
+        let requests = mem::take(&mut self.dns_seed_requests);
+        for request in requests.into_iter() {
+            let addrs = match dns_client.poll_lookup(&request.host, request.port) {
+                Ok(Some(response)) => response.result.unwrap_or_else(|msg| {
+                    debug!(
+                        "{:?}: DNS seed lookup of {} failed: {}",
+                        &self.local_peer, &request.host, &msg
+                    );
+                    vec![]
+                }),
+                Ok(None) => {
+                    // still in-flight
+                    self.dns_seed_requests.push(request);
+                    continue;
+                }
+                Err(e) => {
+                    debug!(
+                        "{:?}: DNS seed lookup of {} failed: {:?}",
+                        &self.local_peer, &request.host, &e
+                    );
+                    vec![]
+                }
+            };
+
+            // if the seed doesn't know the services convention, then ask for any nodes
+            let service_prefix = dns_seed_query_host("", services);
+            if addrs.is_empty() && services != 0 && request.host.starts_with(&service_prefix) {
+                let host = request.host[service_prefix.len()..].to_string();
+                self.queue_dns_seed_lookup(dns_client, &host, request.port);
+                continue;
+            }
+            self.add_dns_seed_peers(&request.host, addrs);
+        }
+
+        if !self.dns_seed_requests.is_empty()
+            || !self.dns_seed_peers.is_empty()
+            || self.dns_seeds_resolved_at + self.connection_opts.dns_seed_interval
+                > get_epoch_time_secs()
+        {
+            return;
+        }
+
+        debug!(
+            "{:?}: look up {} DNS seeds",
+            &self.local_peer,
+            self.connection_opts.dns_seeds.len()
+        );
+        self.dns_seeds_resolved_at = get_epoch_time_secs();
+        for seed in self.connection_opts.dns_seeds.clone().into_iter() {
+            match parse_dns_seed(&seed, self.bind_port()) {
+                Some((host, port)) => {
+                    let query_host = dns_seed_query_host(&host, services);
+                    self.queue_dns_seed_lookup(dns_client, &query_host, port);
+                }
+                None => {
+                    warn!("{:?}: Invalid DNS seed '{}'", &self.local_peer, &seed);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_dns_seed() {
+        assert_eq!(
+            parse_dns_seed("seed.example.com", 20444),
+            Some(("seed.example.com".to_string(), 20444))
+        );
+        assert_eq!(
+            parse_dns_seed("seed.example.com:30444", 20444),
+            Some(("seed.example.com".to_string(), 30444))
+        );
+        assert_eq!(parse_dns_seed("", 20444), None);
+        assert_eq!(parse_dns_seed(":30444", 20444), None);
+        assert_eq!(parse_dns_seed("seed.example.com:nope", 20444), None);
+        assert_eq!(parse_dns_seed("seed.example.com:99999", 20444), None);
+        assert_eq!(parse_dns_seed("::1:20444", 20444), None);
+    }
+
+    #[test]
+    fn test_dns_seed_query_host() {
+        assert_eq!(
+            dns_seed_query_host("seed.example.com", 0),
+            "seed.example.com"
+        );
+        assert_eq!(
+            dns_seed_query_host("seed.example.com", 0x03),
+            "x3.seed.example.com"
+        );
+        assert_eq!(
+            dns_seed_query_host("seed.example.com", 0x1d),
+            "x1d.seed.example.com"
+        );
+    }
+}
@@ -23,6 +23,7 @@ use std::io::{Read, Write};
 
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::net::Shutdown;
 
 use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::Receiver;
@@ -38,6 +39,8 @@ use net::http::*;
 use net::p2p::{PeerMap, PeerNetwork};
 use net::poll::*;
 use net::rpc::*;
+use net::tls::{HttpSocket, TlsAcceptor};
+use net::ws::{WebSocketEvent, WebSocketEventReceiver};
 use net::Error as net_error;
 use net::*;
 
@@ -53,11 +56,13 @@ use util::get_epoch_time_secs;
 
 use core::mempool::*;
 
+use monitoring::increment_connections_refused_counter;
+
 #[derive(Debug)]
 pub struct HttpPeer {
     // ongoing http conversations (either they reached out to us, or we to them)
     pub peers: HashMap<usize, ConversationHttp>,
-    pub sockets: HashMap<usize, mio_net::TcpStream>,
+    pub sockets: HashMap<usize, HttpSocket>,
 
     // outbound connections that are pending connection
     pub connecting: HashMap<
@@ -75,10 +80,41 @@ pub struct HttpPeer {
 
     // connection options
     pub connection_opts: ConnectionOptions,
+
+    // chain and mempool events to publish to WebSocket subscribers
+    pub websocket_events: Option<WebSocketEventReceiver>,
+
+    // terminates TLS on inbound connections, if configured
+    tls: Option<TlsAcceptor>,
 }
 
 impl HttpPeer {
     pub fn new(conn_opts: ConnectionOptions, server_handle: usize) -> HttpPeer {
+        let tls = match (&conn_opts.rpc_tls_cert_file, &conn_opts.rpc_tls_key_file) {
+            (Some(cert_path), Some(key_path)) => {
+                match TlsAcceptor::new(
+                    cert_path,
+                    key_path,
+                    conn_opts.rpc_tls_client_ca_file.as_deref(),
+                ) {
+                    Ok(acceptor) => Some(acceptor),
+                    Err(e) => {
+                        panic!(
+                            "FATAL: failed to set up TLS for the RPC interface: {:?}",
+                            &e
+                        );
+                    }
+                }
+            }
+            (None, None) => None,
+            (_, _) => {
+                panic!("FATAL: RPC TLS needs both a certificate file and a key file");
+            }
+        };
+        if conn_opts.rpc_tls_require_client_cert && conn_opts.rpc_tls_client_ca_file.is_none() {
+            panic!("FATAL: requiring RPC client certificates needs a client CA file");
+        }
+
         HttpPeer {
             peers: HashMap::new(),
             sockets: HashMap::new(),
@@ -87,6 +123,8 @@ impl HttpPeer {
             http_server_handle: server_handle,
 
             connection_opts: conn_opts,
+            websocket_events: None,
+            tls,
         }
     }
 
@@ -115,10 +153,7 @@ impl HttpPeer {
     pub fn get_conversation_and_socket(
         &mut self,
         event_id: usize,
-    ) -> (
-        Option<&mut ConversationHttp>,
-        Option<&mut mio::net::TcpStream>,
-    ) {
+    ) -> (Option<&mut ConversationHttp>, Option<&mut HttpSocket>) {
         (
             self.peers.get_mut(&event_id),
             self.sockets.get_mut(&event_id),
@@ -176,8 +211,14 @@ impl HttpPeer {
         peer_addr: &SocketAddr,
         outbound_url: Option<&UrlString>,
     ) -> Result<(), net_error> {
+        // WebSocket sessions are capped separately, by max_websocket_sessions
+        let num_http_peers = self
+            .peers
+            .values()
+            .filter(|convo| !convo.is_websocket())
+            .count();
         if outbound_url.is_none()
-            && (self.peers.len() as u64) + 1 > self.connection_opts.max_http_clients
+            && (num_http_peers as u64) + 1 > self.connection_opts.max_http_clients
         {
             // inbound
             debug!(
@@ -218,15 +259,15 @@ impl HttpPeer {
         mempool: &MemPoolDB,
         chainstate: &mut StacksChainState,
         event_id: usize,
-        mut socket: mio_net::TcpStream,
+        mut socket: HttpSocket,
         outbound_url: Option<UrlString>,
         initial_request: Option<HttpRequestType>,
     ) -> Result<(), net_error> {
-        let client_addr = match socket.peer_addr() {
-            Ok(addr) => addr,
+        let client_addr = match socket.stream().peer_addr() {
+            Ok(addr) => PeerAddress::canonical_socketaddr(&addr),
             Err(e) => {
                 warn!("Failed to get peer address of {:?}: {:?}", &socket, &e);
-                let _ = network_state.deregister(event_id, &socket);
+                let _ = network_state.deregister(event_id, socket.stream());
                 return Err(net_error::SocketError);
             }
         };
@@ -234,7 +275,7 @@ impl HttpPeer {
         match self.can_register_http(&client_addr, outbound_url.as_ref()) {
             Ok(_) => {}
             Err(e) => {
-                let _ = network_state.deregister(event_id, &socket);
+                let _ = network_state.deregister(event_id, socket.stream());
                 return Err(e);
             }
         }
@@ -264,7 +305,7 @@ impl HttpPeer {
             match new_convo.send_request(request) {
                 Ok(_) => {}
                 Err(e) => {
-                    let _ = network_state.deregister(event_id, &socket);
+                    let _ = network_state.deregister(event_id, socket.stream());
                     return Err(e);
                 }
             }
@@ -273,7 +314,7 @@ impl HttpPeer {
             match HttpPeer::saturate_http_socket(&mut socket, &mut new_convo, mempool, chainstate) {
                 Ok(_) => {}
                 Err(e) => {
-                    let _ = network_state.deregister(event_id, &socket);
+                    let _ = network_state.deregister(event_id, socket.stream());
                     return Err(e);
                 }
             }
@@ -290,8 +331,9 @@ impl HttpPeer {
 
         match self.sockets.remove(&event_id) {
             None => {}
-            Some(sock) => {
-                let _ = network_state.deregister(event_id, &sock);
+            Some(mut sock) => {
+                sock.close();
+                let _ = network_state.deregister(event_id, sock.stream());
             }
         }
         match self.connecting.remove(&event_id) {
@@ -314,6 +356,18 @@ impl HttpPeer {
         }
 
         for (event_id, convo) in self.peers.iter() {
+            if convo.is_websocket() {
+                // WebSocket clients answer our pings, so if we haven't heard from one in two ping
+                // intervals, it's gone.
+                if convo.get_last_request_time() + 2 * self.connection_opts.websocket_ping_interval
+                    < now
+                {
+                    debug!("Removing unresponsive WebSocket conversation {:?}", convo);
+                    to_remove.push(*event_id);
+                }
+                continue;
+            }
+
             let mut last_request_time = convo.get_last_request_time();
             if last_request_time == 0 {
                 // never got a request
@@ -343,7 +397,7 @@ impl HttpPeer {
     /// Saturate a conversation's socket -- either sends the whole request, or fills the socket
     /// buffer.
     pub fn saturate_http_socket(
-        client_sock: &mut mio::net::TcpStream,
+        client_sock: &mut HttpSocket,
         convo: &mut ConversationHttp,
         mempool: &MemPoolDB,
         chainstate: &mut StacksChainState,
@@ -375,10 +429,21 @@ impl HttpPeer {
         mempool: &MemPoolDB,
         chainstate: &mut StacksChainState,
         poll_state: &mut NetworkPollState,
+        short_on_files: bool,
     ) -> Result<Vec<usize>, net_error> {
         let mut registered = vec![];
 
         for (hint_event_id, client_sock) in poll_state.new.drain() {
+            if short_on_files {
+                debug!(
+                    "HTTP: running out of file descriptors; refusing {:?}",
+                    &client_sock
+                );
+                increment_connections_refused_counter();
+                let _ = client_sock.shutdown(Shutdown::Both);
+                continue;
+            }
+
             let event_id = match network_state.register(
                 self.http_server_handle,
                 hint_event_id,
@@ -405,6 +470,21 @@ impl HttpPeer {
                 continue;
             }
 
+            let client_sock = match self.tls {
+                Some(ref mut acceptor) => match acceptor.accept() {
+                    Ok(session) => HttpSocket::tls(client_sock, session),
+                    Err(e) => {
+                        warn!(
+                            "Failed to start TLS session on {:?}: {:?}",
+                            &client_sock, &e
+                        );
+                        let _ = network_state.deregister(event_id, &client_sock);
+                        continue;
+                    }
+                },
+                None => HttpSocket::plain(client_sock),
+            };
+
             if let Err(_e) = self.register_http(
                 network_state,
                 mempool,
@@ -420,6 +500,58 @@ impl HttpPeer {
             registered.push(event_id);
         }
 
+        #[cfg(unix)]
+        for (hint_event_id, client_sock) in poll_state.new_unix.drain() {
+            if short_on_files {
+                debug!(
+                    "HTTP: running out of file descriptors; refusing {:?}",
+                    &client_sock
+                );
+                increment_connections_refused_counter();
+                let _ = client_sock.shutdown(Shutdown::Both);
+                continue;
+            }
+
+            let event_id = match network_state.register(
+                self.http_server_handle,
+                hint_event_id,
+                &client_sock,
+            ) {
+                Ok(event_id) => event_id,
+                Err(e) => {
+                    warn!(
+                        "Failed to register HTTP connection {:?}: {:?}",
+                        &client_sock, &e
+                    );
+                    continue;
+                }
+            };
+
+            if self.peers.contains_key(&event_id) {
+                warn!(
+                    "Already have an event {}: {:?}",
+                    event_id,
+                    self.peers.get(&event_id)
+                );
+                let _ = network_state.deregister(event_id, &client_sock);
+                continue;
+            }
+
+            if let Err(_e) = self.register_http(
+                network_state,
+                mempool,
+                chainstate,
+                event_id,
+                HttpSocket::unix(client_sock),
+                None,
+                None,
+            ) {
+                // NOTE: register_http will deregister the socket for us
+                continue;
+            }
+            registered.push(event_id);
+        }
+
         Ok(registered)
     }
 
@@ -432,7 +564,7 @@ impl HttpPeer {
         chainstate: &mut StacksChainState,
         mempool: &mut MemPoolDB,
         event_id: usize,
-        client_sock: &mut mio_net::TcpStream,
+        client_sock: &mut HttpSocket,
         convo: &mut ConversationHttp,
         handler_args: &RPCHandlerArgs,
     ) -> Result<(bool, Vec<StacksMessageType>), net_error> {
@@ -498,6 +630,7 @@ impl HttpPeer {
             }
             Ok(_) => {}
         }
+        convo.set_client_authenticated(client_sock.peer_authenticated());
 
         // react to inbound messages -- do we need to send something out, or fulfill requests
         // to other threads?  Try to chat even if the recv() failed, since we'll want to at
@@ -557,7 +690,7 @@ impl HttpPeer {
                     mempool,
                     chainstate,
                     *event_id,
-                    socket,
+                    HttpSocket::plain(socket),
                     data_url.clone(),
                     initial_request_opt,
                 ) {
@@ -656,8 +789,108 @@ impl HttpPeer {
                 }
             }
             if convo.is_drained() && !convo.is_keep_alive() {
-                // did some work, but nothing more to do and we're not keep-alive
-                debug!("Close drained HTTP connection {:?}", convo);
+                // did some work, but nothing more to do and we're not keep-alive.  Wait for any
+                // buffered TLS records to go out before closing.
+                let flushed = self
+                    .sockets
+                    .get_mut(event_id)
+                    .map(|sock| sock.is_flushed())
+                    .unwrap_or(true);
+                if flushed {
+                    debug!("Close drained HTTP connection {:?}", convo);
+                    close.push(*event_id);
+                }
+            }
+        }
+
+        close
+    }
+
+    /// Finish upgrading conversations to WebSockets, publish any new chain and mempool events to
+    /// them, and keep them alive.
+    /// Return the list of conversation event IDs to close (i.e. they're broken, or the client or
+    /// we have closed the session)
+    fn run_websockets(
+        &mut self,
+        mempool: &MemPoolDB,
+        chainstate: &mut StacksChainState,
+    ) -> Vec<usize> {
+        let mut events: Vec<WebSocketEvent> = vec![];
+        if let Some(ref receiver) = self.websocket_events {
+            loop {
+                match receiver.try_recv() {
+                    Ok(event) => events.push(event),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        debug!("WebSocket event sender disconnected");
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut num_sessions = self
+            .peers
+            .values()
+            .filter(|convo| convo.is_websocket())
+            .count() as u64;
+
+        let mut close = vec![];
+        for (event_id, convo) in self.peers.iter_mut() {
+            if convo.try_upgrade_websocket() {
+                // the client may have spoken as soon as it got our 101, and since sockets are
+                // edge-triggered, we won't hear about it again.
+                if let Some(client_sock) = self.sockets.get_mut(event_id) {
+                    if let Err(e) = convo.recv(client_sock) {
+                        debug!("Failed to receive from WebSocket {:?}: {:?}", convo, &e);
+                        close.push(*event_id);
+                        continue;
+                    }
+                }
+                num_sessions += 1;
+                if num_sessions > self.connection_opts.max_websocket_sessions {
+                    debug!(
+                        "HTTP: too many WebSocket sessions (max is {}); closing {:?}",
+                        self.connection_opts.max_websocket_sessions, convo
+                    );
+                    num_sessions -= 1;
+                    if let Some(session) = convo.websocket_mut() {
+                        // 1013: try again later
+                        session.close(1013);
+                    }
+                }
+            }
+
+            let session = match convo.websocket_mut() {
+                Some(session) => session,
+                None => {
+                    continue;
+                }
+            };
+
+            for event in events.iter() {
+                session.publish(event);
+            }
+            session.ping_if_idle(self.connection_opts.websocket_ping_interval);
+
+            // sockets are edge-triggered, so push the data out now rather than waiting for the
+            // socket to become ready.
+            if let Some(client_sock) = self.sockets.get_mut(event_id) {
+                if let Err(e) =
+                    HttpPeer::saturate_http_socket(client_sock, convo, mempool, chainstate)
+                {
+                    debug!("Failed to send to WebSocket {:?}: {:?}", convo, &e);
+                    close.push(*event_id);
+                    continue;
+                }
+            }
+
+            if convo
+                .websocket_mut()
+                .map(|s| s.is_closed())
+                .unwrap_or(false)
+            {
+                debug!("Close WebSocket conversation {:?}", convo);
                 close.push(*event_id);
             }
         }
@@ -682,11 +915,25 @@ impl HttpPeer {
         handler_args: &RPCHandlerArgs,
     ) -> Result<Vec<StacksMessageType>, net_error> {
         // set up new inbound conversations
-        self.process_new_sockets(network_state, mempool, chainstate, &mut poll_state)?;
+        self.process_new_sockets(
+            network_state,
+            mempool,
+            chainstate,
+            &mut poll_state,
+            network.is_short_on_files(),
+        )?;
 
         // set up connected sockets
         self.process_connecting_sockets(network_state, mempool, chainstate, &mut poll_state);
 
+        // sockets are edge-triggered, so conversations that stopped reading pipelined requests
+        // won't be signaled again.  Revisit them.
+        for (event_id, convo) in self.peers.iter() {
+            if convo.is_recv_paused() && !poll_state.ready.contains(event_id) {
+                poll_state.ready.push(*event_id);
+            }
+        }
+
         // run existing conversations, clear out broken ones, and get back messages forwarded to us
         let (stacks_msgs, error_events) = self.process_ready_sockets(
             &mut poll_state,
@@ -708,6 +955,13 @@ impl HttpPeer {
             self.deregister_http(network_state, close_event);
         }
 
+        // service WebSocket clients
+        let close_events = self.run_websockets(mempool, chainstate);
+        for close_event in close_events {
+            debug!("Close WebSocket connection on event {}", close_event);
+            self.deregister_http(network_state, close_event);
+        }
+
         // remove timed-out requests
         for (_, convo) in self.peers.iter_mut() {
             convo.clear_timeouts();
@@ -727,6 +981,7 @@ mod test {
     use net::http::*;
     use net::rpc::*;
     use net::test::*;
+    use net::ws::*;
     use net::*;
     use std::cell::RefCell;
 
@@ -1125,6 +1380,37 @@ mod test {
         assert!(*have_error.borrow());
     }
 
+    #[test]
+    fn test_http_out_of_files() {
+        // make sure it's the configured limit below, not the process's, that refuses the clients
+        crate::util::fdlimit::raise_open_files_limit(u64::MAX).unwrap();
+
+        let mut conn_opts = ConnectionOptions::default();
+        // we already have more than this open, so every inbound client gets refused
+        conn_opts.max_open_files = 1;
+
+        test_http_server(
+            "test_http_out_of_files",
+            51200,
+            51201,
+            conn_opts,
+            5,
+            0,
+            |client_id, _| {
+                let mut request = HttpRequestType::GetInfo(HttpRequestMetadata::from_host(
+                    PeerHost::from_host_port("127.0.0.1".to_string(), 51201),
+                ));
+                request.metadata_mut().keep_alive = false;
+                StacksHttp::serialize_request(&request).unwrap()
+            },
+            |client_id, http_response_bytes_res| {
+                // should have been refused
+                test_debug!("{:?}", &http_response_bytes_res);
+                http_response_bytes_res.is_err()
+            },
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_http_slow_client() {
@@ -1216,6 +1502,7 @@ mod test {
                     )),
                     signed_contract_tx,
                     None,
+                    false,
                 );
                 request.metadata_mut().keep_alive = false;
 
@@ -1235,6 +1522,116 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_http_pipelined_requests() {
+        let conn_opts = ConnectionOptions {
+            max_http_pipelined_requests: 4,
+            ..ConnectionOptions::default()
+        };
+
+        test_http_server(
+            "test_http_pipelined_requests",
+            51212,
+            51213,
+            conn_opts,
+            1,
+            0,
+            |client_id, _| {
+                // many more requests in one write than the server will take at once.  The last
+                // one comes after a "Connection: close", and is ignored.
+                let mut request_bytes = vec![];
+                for i in 0..40 {
+                    if i % 2 == 0 {
+                        let mut request = HttpRequestType::GetInfo(HttpRequestMetadata::from_host(
+                            PeerHost::from_host_port("127.0.0.1".to_string(), 51213),
+                        ));
+                        request.metadata_mut().keep_alive = i != 38;
+                        request_bytes.append(&mut StacksHttp::serialize_request(&request).unwrap());
+                    } else {
+                        request_bytes.extend_from_slice(
+                            format!(
+                                "GET /v2/not-found/{} HTTP/1.1\r\nHost: 127.0.0.1:51213\r\n\r\n",
+                                i
+                            )
+                            .as_bytes(),
+                        );
+                    }
+                }
+                request_bytes
+            },
+            |client_id, http_response_bytes_res| {
+                let http_response_bytes = http_response_bytes_res.unwrap();
+                let txt = String::from_utf8_lossy(&http_response_bytes);
+                let statuses: Vec<&str> = txt
+                    .match_indices("HTTP/1.1 ")
+                    .map(|(i, _)| &txt[i + 9..i + 12])
+                    .collect();
+
+                // replies come back in order
+                assert_eq!(statuses.len(), 39);
+                for (i, status) in statuses.iter().enumerate() {
+                    assert_eq!(*status, if i % 2 == 0 { "200" } else { "404" });
+                }
+                true
+            },
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_http_getinfo_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!(
+            "test_http_getinfo_unix_socket.{}.sock",
+            std::process::id()
+        ));
+
+        let mut peer_config = TestPeerConfig::new("test_http_getinfo_unix_socket", 51210, 51211);
+        let p2p_addr = format!("127.0.0.1:{}", peer_config.server_port)
+            .parse::<SocketAddr>()
+            .unwrap();
+        peer_config.connection_opts.rpc_unix_socket_mode = 0o660;
+
+        // serve RPC on the unix socket instead of the TCP port
+        let mut peer = TestPeer::new(peer_config);
+        peer.network.network = None;
+        peer.network.bind_unix_http(&p2p_addr, &path).unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o660
+        );
+
+        let (http_sx, http_rx) = sync_channel(1);
+        let http_thread = thread::spawn(move || loop {
+            peer.step().unwrap();
+            if http_rx.try_recv().is_ok() {
+                break;
+            }
+        });
+
+        let mut request = HttpRequestType::GetInfo(HttpRequestMetadata::from_host(
+            PeerHost::from_host_port("localhost".to_string(), 0),
+        ));
+        request.metadata_mut().keep_alive = false;
+        let request_bytes = StacksHttp::serialize_request(&request).unwrap();
+
+        let mut sock = UnixStream::connect(&path).unwrap();
+        sock.write_all(&request_bytes).unwrap();
+
+        let mut resp = vec![];
+        sock.read_to_end(&mut resp).unwrap();
+
+        http_sx.send(true).unwrap();
+        http_thread.join().unwrap();
+
+        match StacksHttp::parse_response("/v2/info", &resp).unwrap() {
+            StacksHttpMessage::Response(HttpResponseType::PeerInfo(..)) => {}
+            x => panic!("Unexpected response: {:?}", &x),
+        }
+    }
+
     #[test]
     fn test_http_400() {
         test_http_server(
@@ -1375,4 +1772,97 @@ mod test {
             |client_id, http_response_bytes_res| true,
         );
     }
+
+    /// Read one (small, unmasked) frame from the server
+    fn read_websocket_frame(sock: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut header = [0u8; 2];
+        sock.read_exact(&mut header).unwrap();
+        let len = (header[1] & 0x7f) as usize;
+        assert!(len < 126);
+        let mut payload = vec![0u8; len];
+        sock.read_exact(&mut payload).unwrap();
+        (header[0] & 0x0f, payload)
+    }
+
+    #[test]
+    fn test_http_websocket_subscriptions() {
+        let peer_config = TestPeerConfig::new("test_http_websocket_subscriptions", 51090, 51091);
+        let mut peer = TestPeer::new(peer_config);
+
+        let (websocket_sx, websocket_rx) = websocket_event_channel();
+        peer.network.set_websocket_event_receiver(websocket_rx);
+
+        let (http_sx, http_rx) = sync_channel(1);
+        let http_thread = thread::spawn(move || loop {
+            peer.step().unwrap();
+            if http_rx.try_recv().is_ok() {
+                break;
+            }
+        });
+
+        let mut sock =
+            TcpStream::connect("127.0.0.1:51091".parse::<SocketAddr>().unwrap()).unwrap();
+        sock.set_read_timeout(Some(std::time::Duration::from_secs(60)))
+            .unwrap();
+
+        // upgrade, using the key from RFC 6455
+        sock.write_all(
+            "GET /v2/ws HTTP/1.1\r\nHost: 127.0.0.1:51091\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let mut preamble = vec![];
+        while !preamble.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8; 1];
+            sock.read_exact(&mut byte).unwrap();
+            preamble.push(byte[0]);
+        }
+        let preamble = String::from_utf8(preamble).unwrap();
+        assert!(preamble.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(preamble.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        // subscribe to the mempool
+        sock.write_all(
+            &WebSocketFrame::new(
+                WebSocketOpcode::Text,
+                br#"{"op": "subscribe", "topic": "mempool"}"#.to_vec(),
+            )
+            .encode_masked([1, 2, 3, 4]),
+        )
+        .unwrap();
+        let (opcode, payload) = read_websocket_frame(&mut sock);
+        assert_eq!(opcode, 0x1);
+        let message: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(message["op"], "subscribed");
+        assert_eq!(message["topic"], "mempool");
+
+        // events are published to the client
+        let txid = Txid([0x11; 32]);
+        websocket_send_event(
+            &websocket_sx,
+            WebSocketEvent::MempoolTxs(vec![txid.clone()]),
+        );
+        let (opcode, payload) = read_websocket_frame(&mut sock);
+        assert_eq!(opcode, 0x1);
+        let message: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(message["op"], "event");
+        assert_eq!(message["topic"], "mempool");
+        assert_eq!(message["data"]["txid"], format!("0x{}", &txid));
+
+        // closing the session closes the connection
+        sock.write_all(
+            &WebSocketFrame::new(WebSocketOpcode::Close, vec![0x03, 0xe8])
+                .encode_masked([5, 6, 7, 8]),
+        )
+        .unwrap();
+        let (opcode, _) = read_websocket_frame(&mut sock);
+        assert_eq!(opcode, 0x8);
+        let mut rest = vec![];
+        sock.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+
+        http_sx.send(true).unwrap();
+        http_thread.join().unwrap();
+    }
 }
@@ -0,0 +1,327 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Connection slots by peer class.  Inbound peers are sorted into classes -- miners (which
+//! advertise `ServiceFlags::MINER`), followers (everyone else who has handshaked with us), and
+//! unknown peers (which haven't handshaked yet) -- and each class gets its own number of slots,
+//! so a flood of one kind of inbound connection can't crowd out the others.  In addition,
+//! `reserved_outbound_slots` of the node's sockets are kept free for the outbound connections it
+//! needs to stay in sync, so inbound connections can't use up the socket pool either.
+//!
+//! Unknown peers are turned away when they connect.  Miners and followers are only known once
+//! they handshake, so if a class goes over its slots, its most recently-contacted peers are
+//! disconnected.
+
+use std::collections::HashMap;
+
+use net::chat::ConversationP2P;
+use net::connection::ConnectionOptions;
+use net::p2p::{PeerMap, PeerNetwork};
+use net::Error as net_error;
+use net::ServiceFlags;
+
+/// The class of peer a connection slot goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerClass {
+    /// a connection we made
+    Outbound,
+    /// an inbound peer that advertises itself as a miner
+    Miner,
+    /// an inbound peer that has handshaked with us, but isn't a miner
+    Follower,
+    /// an inbound peer that hasn't handshaked with us yet
+    Unknown,
+}
+
+impl PeerClass {
+    pub fn of(convo: &ConversationP2P) -> PeerClass {
+        if convo.is_outbound() {
+            PeerClass::Outbound
+        } else if !convo.is_authenticated() {
+            PeerClass::Unknown
+        } else if convo.peer_services & (ServiceFlags::MINER as u16) != 0 {
+            PeerClass::Miner
+        } else {
+            PeerClass::Follower
+        }
+    }
+
+    /// How many inbound connections of this class we'll keep.  Outbound connections aren't
+    /// limited here.
+    pub fn max_slots(&self, opts: &ConnectionOptions) -> Option<u64> {
+        match *self {
+            PeerClass::Outbound => None,
+            PeerClass::Miner => Some(opts.max_inbound_miners),
+            PeerClass::Follower => Some(opts.max_inbound_followers),
+            PeerClass::Unknown => Some(opts.max_inbound_unknown),
+        }
+    }
+}
+
+/// How many connection slots each class of peer is using
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConnectionSlots {
+    pub outbound: u64,
+    pub miners: u64,
+    pub followers: u64,
+    pub unknown: u64,
+}
+
+impl ConnectionSlots {
+    pub fn count(peers: &PeerMap) -> ConnectionSlots {
+        let mut slots = ConnectionSlots::default();
+        for (_, convo) in peers.iter() {
+            match PeerClass::of(convo) {
+                PeerClass::Outbound => slots.outbound += 1,
+                PeerClass::Miner => slots.miners += 1,
+                PeerClass::Follower => slots.followers += 1,
+                PeerClass::Unknown => slots.unknown += 1,
+            }
+        }
+        slots
+    }
+
+    pub fn num_inbound(&self) -> u64 {
+        self.miners + self.followers + self.unknown
+    }
+
+    /// Check whether or not another inbound peer can connect, given how many sockets we have
+    /// free.  It starts out as an unknown peer.
+    pub fn check_inbound(
+        &self,
+        opts: &ConnectionOptions,
+        free_sockets: u64,
+    ) -> Result<(), net_error> {
+        if self.num_inbound() >= opts.num_clients {
+            debug!("Too many inbound connections ({})", self.num_inbound());
+            return Err(net_error::TooManyPeers);
+        }
+        if self.unknown >= opts.max_inbound_unknown {
+            debug!(
+                "Too many inbound connections that haven't handshaked yet ({})",
+                self.unknown
+            );
+            return Err(net_error::TooManyPeers);
+        }
+        let reserved = opts.reserved_outbound_slots.saturating_sub(self.outbound);
+        if free_sockets <= reserved {
+            debug!(
+                "Only {} sockets free, and {} are reserved for outbound connections",
+                free_sockets, reserved
+            );
+            return Err(net_error::TooManyPeers);
+        }
+        Ok(())
+    }
+
+    /// Find the inbound miners and followers in excess of their classes' slots, so they can be
+    /// disconnected.  The most recently-contacted peers in each class go first.
+    pub fn find_excess_inbound(peers: &PeerMap, opts: &ConnectionOptions) -> Vec<usize> {
+        let mut by_class: HashMap<PeerClass, Vec<(u64, usize)>> = HashMap::new();
+        for (event_id, convo) in peers.iter() {
+            let class = PeerClass::of(convo);
+            if class == PeerClass::Miner || class == PeerClass::Follower {
+                by_class
+                    .entry(class)
+                    .or_insert_with(Vec::new)
+                    .push((convo.stats.first_contact_time, *event_id));
+            }
+        }
+
+        let mut excess = vec![];
+        for (class, mut convos) in by_class.into_iter() {
+            let max_slots = class.max_slots(opts).unwrap_or(u64::MAX) as usize;
+            if convos.len() <= max_slots {
+                continue;
+            }
+            // oldest first
+            convos.sort();
+            excess.extend(convos[max_slots..].iter().map(|(_, event_id)| *event_id));
+        }
+        excess
+    }
+}
+
+impl PeerNetwork {
+    /// How many more sockets can we register?
+    fn num_free_sockets(&self) -> u64 {
+        self.network
+            .as_ref()
+            .map(|network| network.num_free_events() as u64)
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Is there a slot for another inbound peer?
+    pub fn check_inbound_slots(&self) -> Result<(), net_error> {
+        ConnectionSlots::count(&self.peers)
+            .check_inbound(&self.connection_opts, self.num_free_sockets())
+            .map_err(|e| {
+                info!(
+                    "{:?}: No connection slot for another inbound peer",
+                    &self.local_peer
+                );
+                e
+            })
+    }
+
+    /// Disconnect inbound miners and followers that don't fit in their classes' slots.
+    pub fn prune_peer_class_slots(&mut self) {
+        let excess = ConnectionSlots::find_excess_inbound(&self.peers, &self.connection_opts);
+        for event_id in excess.into_iter() {
+            debug!(
+                "{:?}: Disconnect inbound peer on event {}: no more connection slots for its class",
+                &self.local_peer, event_id
+            );
+            self.deregister_peer(event_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use burnchains::Burnchain;
+    use chainstate::stacks::StacksPublicKey;
+    use core::StacksEpoch;
+    use util::secp256k1::Secp256k1PrivateKey;
+
+    fn make_convo(
+        conn_opts: &ConnectionOptions,
+        outbound: bool,
+        services: Option<u16>,
+        first_contact_time: u64,
+    ) -> ConversationP2P {
+        let burnchain = Burnchain::regtest(":memory:");
+        let mut convo = ConversationP2P::new(
+            123,
+            456,
+            &burnchain,
+            &"127.0.0.1:20443".parse().unwrap(),
+            conn_opts,
+            outbound,
+            0,
+            StacksEpoch::unit_test_pre_2_05(0),
+        );
+        if let Some(services) = services {
+            // handshaked
+            convo
+                .connection
+                .set_public_key(Some(StacksPublicKey::from_private(
+                    &Secp256k1PrivateKey::new(),
+                )));
+            convo.peer_services = services;
+        }
+        convo.stats.first_contact_time = first_contact_time;
+        convo
+    }
+
+    #[test]
+    fn test_connection_slots_check_inbound() {
+        let conn_opts = ConnectionOptions {
+            num_clients: 5,
+            max_inbound_unknown: 2,
+            reserved_outbound_slots: 2,
+            ..ConnectionOptions::default()
+        };
+        let miner = ServiceFlags::RELAY as u16 | ServiceFlags::MINER as u16;
+        let follower = ServiceFlags::RELAY as u16;
+
+        let mut peers = PeerMap::new();
+        peers.insert(1, make_convo(&conn_opts, false, None, 0));
+        peers.insert(2, make_convo(&conn_opts, false, Some(miner), 0));
+        peers.insert(3, make_convo(&conn_opts, false, Some(follower), 0));
+        peers.insert(4, make_convo(&conn_opts, true, Some(follower), 0));
+
+        let slots = ConnectionSlots::count(&peers);
+        assert_eq!(
+            slots,
+            ConnectionSlots {
+                outbound: 1,
+                miners: 1,
+                followers: 1,
+                unknown: 1,
+            }
+        );
+        assert_eq!(slots.num_inbound(), 3);
+
+        assert!(slots.check_inbound(&conn_opts, 100).is_ok());
+
+        // one outbound slot is still reserved
+        assert_eq!(
+            slots.check_inbound(&conn_opts, 1),
+            Err(net_error::TooManyPeers)
+        );
+        assert!(slots.check_inbound(&conn_opts, 2).is_ok());
+
+        // too many peers that haven't handshaked
+        peers.insert(5, make_convo(&conn_opts, false, None, 0));
+        let slots = ConnectionSlots::count(&peers);
+        assert_eq!(
+            slots.check_inbound(&conn_opts, 100),
+            Err(net_error::TooManyPeers)
+        );
+
+        // too many inbound peers
+        peers.remove(&5);
+        peers.insert(5, make_convo(&conn_opts, false, Some(follower), 0));
+        peers.insert(6, make_convo(&conn_opts, false, Some(follower), 0));
+        let slots = ConnectionSlots::count(&peers);
+        assert_eq!(slots.num_inbound(), 5);
+        assert_eq!(
+            slots.check_inbound(&conn_opts, 100),
+            Err(net_error::TooManyPeers)
+        );
+    }
+
+    #[test]
+    fn test_connection_slots_find_excess_inbound() {
+        let conn_opts = ConnectionOptions {
+            max_inbound_miners: 1,
+            max_inbound_followers: 2,
+            ..ConnectionOptions::default()
+        };
+        let miner = ServiceFlags::RELAY as u16 | ServiceFlags::MINER as u16;
+        let follower = ServiceFlags::RELAY as u16;
+
+        let mut peers = PeerMap::new();
+        peers.insert(1, make_convo(&conn_opts, false, Some(miner), 300));
+        peers.insert(2, make_convo(&conn_opts, false, Some(miner), 100));
+        peers.insert(3, make_convo(&conn_opts, false, Some(follower), 100));
+        peers.insert(4, make_convo(&conn_opts, false, Some(follower), 200));
+
+        // outbound and unknown peers don't count against these slots
+        peers.insert(5, make_convo(&conn_opts, true, Some(follower), 400));
+        peers.insert(6, make_convo(&conn_opts, true, Some(miner), 400));
+        peers.insert(7, make_convo(&conn_opts, false, None, 400));
+
+        // the newer miner has to go
+        assert_eq!(
+            ConnectionSlots::find_excess_inbound(&peers, &conn_opts),
+            vec![1]
+        );
+
+        peers.remove(&1);
+        assert!(ConnectionSlots::find_excess_inbound(&peers, &conn_opts).is_empty());
+
+        // the newest follower has to go
+        peers.insert(8, make_convo(&conn_opts, false, Some(follower), 150));
+        assert_eq!(
+            ConnectionSlots::find_excess_inbound(&peers, &conn_opts),
+            vec![4]
+        );
+    }
+}
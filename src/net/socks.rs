@@ -0,0 +1,586 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! SOCKS5 client (RFC 1928, with RFC 1929 username/password authentication), for reaching peers
+//! through a proxy like Tor.  A node with `socks5_proxy` set opens every outbound p2p connection
+//! to the proxy, and asks the proxy to connect it onwards before the p2p session starts.
+//!
+//! Tor hidden services don't have IP addresses, so a `.onion` peer is stored in the peer DB
+//! under a `PeerAddress` in the OnionCat range (`fd87:d87e:eb43::/48`) that is derived from its
+//! hostname, and the hostname itself is kept in the peer DB's `onion_hosts` table.  Onion peers
+//! can only be reached through a proxy.
+
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use net::Error as net_error;
+use net::PeerAddress;
+use util::hash::Sha256Sum;
+
+const SOCKS5_VERSION: u8 = 0x05;
+const SOCKS5_AUTH_NONE: u8 = 0x00;
+const SOCKS5_AUTH_PASSWORD: u8 = 0x02;
+const SOCKS5_AUTH_UNACCEPTABLE: u8 = 0xff;
+const SOCKS5_PASSWORD_AUTH_VERSION: u8 = 0x01;
+const SOCKS5_CMD_CONNECT: u8 = 0x01;
+const SOCKS5_ATYP_IPV4: u8 = 0x01;
+const SOCKS5_ATYP_DOMAIN: u8 = 0x03;
+const SOCKS5_ATYP_IPV6: u8 = 0x04;
+const SOCKS5_REPLY_SUCCEEDED: u8 = 0x00;
+
+/// `PeerAddress` prefix for onion peers
+pub const ONION_PEER_ADDRESS_PREFIX: [u8; 6] = [0xfd, 0x87, 0xd8, 0x7e, 0xeb, 0x43];
+
+impl PeerAddress {
+    /// The address under which an onion host is stored in the peer DB
+    pub fn from_onion_host(host: &str) -> PeerAddress {
+        let hash = Sha256Sum::from_data(host.to_lowercase().as_bytes());
+        let mut bytes = [0u8; 16];
+        bytes[0..6].copy_from_slice(&ONION_PEER_ADDRESS_PREFIX);
+        bytes[6..16].copy_from_slice(&hash.as_bytes()[0..10]);
+        PeerAddress(bytes)
+    }
+
+    /// Does this address stand for an onion host?
+    pub fn is_onion(&self) -> bool {
+        self.0[0..6] == ONION_PEER_ADDRESS_PREFIX
+    }
+}
+
+/// Is this hostname a Tor hidden service?
+pub fn is_onion_host(host: &str) -> bool {
+    host.to_lowercase().ends_with(".onion")
+}
+
+/// Where we want the proxy to connect us to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Socks5Target {
+    Addr(SocketAddr),
+    /// resolved by the proxy, so we don't leak DNS lookups
+    Domain(String, u16),
+}
+
+impl fmt::Display for Socks5Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Socks5Target::Addr(ref addr) => write!(f, "{}", addr),
+            Socks5Target::Domain(ref host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Socks5State {
+    /// sent our authentication methods
+    Greeting,
+    /// sent our username and password
+    Authenticating,
+    /// sent the CONNECT request
+    Connecting,
+    Connected,
+}
+
+/// Client side of a SOCKS5 CONNECT.  Bytes to send to the proxy accumulate in a buffer, and
+/// bytes from the proxy are fed in as they arrive, so this works on non-blocking sockets.
+#[derive(Debug)]
+pub struct Socks5Handshake {
+    target: Socks5Target,
+    credentials: Option<(String, String)>,
+    state: Socks5State,
+    send_buf: Vec<u8>,
+    recv_buf: Vec<u8>,
+}
+
+fn proxy_error(msg: &str) -> net_error {
+    net_error::ProxyError(msg.to_string())
+}
+
+impl Socks5Handshake {
+    pub fn new(
+        target: Socks5Target,
+        credentials: Option<(String, String)>,
+    ) -> Result<Socks5Handshake, net_error> {
+        if let Socks5Target::Domain(ref host, _) = target {
+            if host.is_empty() || host.len() > 255 {
+                return Err(proxy_error("Invalid target hostname"));
+            }
+        }
+        if let Some((ref username, ref password)) = credentials {
+            if username.is_empty()
+                || username.len() > 255
+                || password.is_empty()
+                || password.len() > 255
+            {
+                return Err(proxy_error("Invalid proxy username or password"));
+            }
+        }
+
+        let send_buf = if credentials.is_some() {
+            vec![SOCKS5_VERSION, 2, SOCKS5_AUTH_NONE, SOCKS5_AUTH_PASSWORD]
+        } else {
+            vec![SOCKS5_VERSION, 1, SOCKS5_AUTH_NONE]
+        };
+        Ok(Socks5Handshake {
+            target,
+            credentials,
+            state: Socks5State::Greeting,
+            send_buf,
+            recv_buf: vec![],
+        })
+    }
+
+    pub fn target(&self) -> &Socks5Target {
+        &self.target
+    }
+
+    /// Has the proxy connected us to the target?
+    pub fn is_connected(&self) -> bool {
+        self.state == Socks5State::Connected
+    }
+
+    /// Write out as much of what we owe the proxy as the socket will take
+    pub fn flush_to<W: Write>(&mut self, sock: &mut W) -> io::Result<()> {
+        while !self.send_buf.is_empty() {
+            let len = sock.write(&self.send_buf)?;
+            if len == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero));
+            }
+            self.send_buf.drain(0..len);
+        }
+        Ok(())
+    }
+
+    fn push_connect_request(&mut self) {
+        let mut req = vec![SOCKS5_VERSION, SOCKS5_CMD_CONNECT, 0x00];
+        let port = match self.target {
+            Socks5Target::Addr(SocketAddr::V4(ref addr)) => {
+                req.push(SOCKS5_ATYP_IPV4);
+                req.extend_from_slice(&addr.ip().octets());
+                addr.port()
+            }
+            Socks5Target::Addr(SocketAddr::V6(ref addr)) => {
+                req.push(SOCKS5_ATYP_IPV6);
+                req.extend_from_slice(&addr.ip().octets());
+                addr.port()
+            }
+            Socks5Target::Domain(ref host, port) => {
+                req.push(SOCKS5_ATYP_DOMAIN);
+                req.push(host.len() as u8);
+                req.extend_from_slice(host.as_bytes());
+                port
+            }
+        };
+        req.extend_from_slice(&port.to_be_bytes());
+        self.send_buf.append(&mut req);
+    }
+
+    /// How many bytes the proxy's CONNECT reply takes up, if we've seen enough of it to know
+    fn connect_reply_len(&self) -> Result<Option<usize>, net_error> {
+        if self.recv_buf.len() < 5 {
+            return Ok(None);
+        }
+        let addr_len = match self.recv_buf[3] {
+            SOCKS5_ATYP_IPV4 => 4,
+            SOCKS5_ATYP_IPV6 => 16,
+            SOCKS5_ATYP_DOMAIN => 1 + (self.recv_buf[4] as usize),
+            _ => {
+                return Err(proxy_error("Proxy sent an unknown address type"));
+            }
+        };
+        Ok(Some(4 + addr_len + 2))
+    }
+
+    /// Advance the handshake with what's in `recv_buf`
+    fn process_input(&mut self) -> Result<(), net_error> {
+        loop {
+            match self.state {
+                Socks5State::Greeting => {
+                    if self.recv_buf.len() < 2 {
+                        return Ok(());
+                    }
+                    if self.recv_buf[0] != SOCKS5_VERSION {
+                        return Err(proxy_error("Proxy does not speak SOCKS5"));
+                    }
+                    let method = self.recv_buf[1];
+                    self.recv_buf.drain(0..2);
+                    match method {
+                        SOCKS5_AUTH_NONE => {
+                            self.push_connect_request();
+                            self.state = Socks5State::Connecting;
+                        }
+                        SOCKS5_AUTH_PASSWORD => {
+                            let (username, password) = match self.credentials {
+                                Some((ref username, ref password)) => (username, password),
+                                None => {
+                                    return Err(proxy_error(
+                                        "Proxy asked for a password we did not offer",
+                                    ));
+                                }
+                            };
+                            self.send_buf.push(SOCKS5_PASSWORD_AUTH_VERSION);
+                            self.send_buf.push(username.len() as u8);
+                            self.send_buf.extend_from_slice(username.as_bytes());
+                            self.send_buf.push(password.len() as u8);
+                            self.send_buf.extend_from_slice(password.as_bytes());
+                            self.state = Socks5State::Authenticating;
+                        }
+                        SOCKS5_AUTH_UNACCEPTABLE => {
+                            return Err(proxy_error(
+                                "Proxy accepted none of our authentication methods",
+                            ));
+                        }
+                        _ => {
+                            return Err(proxy_error(
+                                "Proxy chose an authentication method we did not offer",
+                            ));
+                        }
+                    }
+                }
+                Socks5State::Authenticating => {
+                    if self.recv_buf.len() < 2 {
+                        return Ok(());
+                    }
+                    let status = self.recv_buf[1];
+                    self.recv_buf.drain(0..2);
+                    if status != 0 {
+                        return Err(proxy_error("Proxy rejected our username and password"));
+                    }
+                    self.push_connect_request();
+                    self.state = Socks5State::Connecting;
+                }
+                Socks5State::Connecting => {
+                    if self.recv_buf.len() < 2 {
+                        return Ok(());
+                    }
+                    if self.recv_buf[0] != SOCKS5_VERSION {
+                        return Err(proxy_error("Proxy does not speak SOCKS5"));
+                    }
+                    if self.recv_buf[1] != SOCKS5_REPLY_SUCCEEDED {
+                        return Err(net_error::ProxyError(format!(
+                            "Proxy could not connect to {} (reply {})",
+                            &self.target, self.recv_buf[1]
+                        )));
+                    }
+                    let reply_len = match self.connect_reply_len()? {
+                        Some(len) => len,
+                        None => {
+                            return Ok(());
+                        }
+                    };
+                    if self.recv_buf.len() < reply_len {
+                        return Ok(());
+                    }
+                    self.recv_buf.drain(0..reply_len);
+                    self.state = Socks5State::Connected;
+                }
+                Socks5State::Connected => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Feed in bytes from the proxy
+    pub fn recv(&mut self, bytes: &[u8]) -> Result<(), net_error> {
+        self.recv_buf.extend_from_slice(bytes);
+        self.process_input()
+    }
+
+    /// Once connected, take whatever the target sent right after the proxy's reply
+    pub fn take_leftover(&mut self) -> Vec<u8> {
+        mem::take(&mut self.recv_buf)
+    }
+}
+
+/// Open a blocking connection to `target` through the SOCKS5 proxy at `proxy`
+pub fn connect_through_proxy(
+    proxy: &SocketAddr,
+    target: Socks5Target,
+    credentials: Option<(String, String)>,
+    timeout: Duration,
+) -> Result<TcpStream, net_error> {
+    let mut handshake = Socks5Handshake::new(target, credentials)?;
+    let mut sock = TcpStream::connect_timeout(proxy, timeout).map_err(|e| {
+        net_error::ProxyError(format!("Failed to connect to proxy {}: {:?}", proxy, &e))
+    })?;
+    sock.set_read_timeout(Some(timeout))
+        .map_err(net_error::ReadError)?;
+    sock.set_write_timeout(Some(timeout))
+        .map_err(net_error::WriteError)?;
+
+    // read the replies a byte at a time, so we don't consume anything the target sends
+    while !handshake.is_connected() {
+        handshake
+            .flush_to(&mut sock)
+            .map_err(net_error::WriteError)?;
+        let mut byte = [0u8; 1];
+        let len = sock.read(&mut byte).map_err(net_error::ReadError)?;
+        if len == 0 {
+            return Err(proxy_error("Proxy closed the connection"));
+        }
+        handshake.recv(&byte)?;
+    }
+
+    sock.set_read_timeout(None).map_err(net_error::ReadError)?;
+    sock.set_write_timeout(None)
+        .map_err(net_error::WriteError)?;
+    Ok(sock)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Read the client's side of a SOCKS5 CONNECT, answer it, and return the requested target
+    pub fn serve_socks5_handshake<S: Read + Write>(
+        sock: &mut S,
+        credentials: Option<(String, String)>,
+    ) -> Socks5Target {
+        let mut greeting = [0u8; 2];
+        sock.read_exact(&mut greeting).unwrap();
+        assert_eq!(greeting[0], SOCKS5_VERSION);
+        let mut methods = vec![0u8; greeting[1] as usize];
+        sock.read_exact(&mut methods).unwrap();
+
+        if let Some((username, password)) = credentials {
+            assert!(methods.contains(&SOCKS5_AUTH_PASSWORD));
+            sock.write_all(&[SOCKS5_VERSION, SOCKS5_AUTH_PASSWORD])
+                .unwrap();
+
+            let mut hdr = [0u8; 2];
+            sock.read_exact(&mut hdr).unwrap();
+            let mut user = vec![0u8; hdr[1] as usize];
+            sock.read_exact(&mut user).unwrap();
+            let mut plen = [0u8; 1];
+            sock.read_exact(&mut plen).unwrap();
+            let mut pass = vec![0u8; plen[0] as usize];
+            sock.read_exact(&mut pass).unwrap();
+
+            let ok = user == username.as_bytes() && pass == password.as_bytes();
+            sock.write_all(&[SOCKS5_PASSWORD_AUTH_VERSION, if ok { 0 } else { 1 }])
+                .unwrap();
+            assert!(ok);
+        } else {
+            assert!(methods.contains(&SOCKS5_AUTH_NONE));
+            sock.write_all(&[SOCKS5_VERSION, SOCKS5_AUTH_NONE]).unwrap();
+        }
+
+        let mut req = [0u8; 4];
+        sock.read_exact(&mut req).unwrap();
+        assert_eq!(req[0..3], [SOCKS5_VERSION, SOCKS5_CMD_CONNECT, 0x00]);
+        let target = match req[3] {
+            SOCKS5_ATYP_IPV4 => {
+                let mut addr = [0u8; 6];
+                sock.read_exact(&mut addr).unwrap();
+                Socks5Target::Addr(SocketAddr::from((
+                    [addr[0], addr[1], addr[2], addr[3]],
+                    u16::from_be_bytes([addr[4], addr[5]]),
+                )))
+            }
+            SOCKS5_ATYP_IPV6 => {
+                let mut addr = [0u8; 18];
+                sock.read_exact(&mut addr).unwrap();
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(&addr[0..16]);
+                Socks5Target::Addr(SocketAddr::from((
+                    ip,
+                    u16::from_be_bytes([addr[16], addr[17]]),
+                )))
+            }
+            SOCKS5_ATYP_DOMAIN => {
+                let mut len = [0u8; 1];
+                sock.read_exact(&mut len).unwrap();
+                let mut host = vec![0u8; len[0] as usize];
+                sock.read_exact(&mut host).unwrap();
+                let mut port = [0u8; 2];
+                sock.read_exact(&mut port).unwrap();
+                Socks5Target::Domain(String::from_utf8(host).unwrap(), u16::from_be_bytes(port))
+            }
+            _ => panic!("bad address type"),
+        };
+
+        // bound to 0.0.0.0:0
+        sock.write_all(&[
+            SOCKS5_VERSION,
+            SOCKS5_REPLY_SUCCEEDED,
+            0x00,
+            SOCKS5_ATYP_IPV4,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ])
+        .unwrap();
+        target
+    }
+
+    /// Run a SOCKS5 proxy that accepts `num_conns` connections and splices each one to the
+    /// target it asks for.  Returns the proxy's address, and a handle that yields the targets.
+    pub fn spawn_socks5_proxy(
+        num_conns: usize,
+        credentials: Option<(String, String)>,
+    ) -> (SocketAddr, thread::JoinHandle<Vec<Socks5Target>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut targets = vec![];
+            for _ in 0..num_conns {
+                let (mut client, _) = listener.accept().unwrap();
+                let target = serve_socks5_handshake(&mut client, credentials.clone());
+                let target_addr = match target {
+                    Socks5Target::Addr(ref addr) => addr.clone(),
+                    Socks5Target::Domain(..) => panic!("cannot resolve {}", &target),
+                };
+                let server = TcpStream::connect(&target_addr).unwrap();
+
+                let mut client_r = client.try_clone().unwrap();
+                let mut server_w = server.try_clone().unwrap();
+                thread::spawn(move || {
+                    let _ = io::copy(&mut client_r, &mut server_w);
+                });
+                let mut server_r = server;
+                let mut client_w = client;
+                thread::spawn(move || {
+                    let _ = io::copy(&mut server_r, &mut client_w);
+                });
+                targets.push(target);
+            }
+            targets
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn test_onion_peer_address() {
+        let host = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+        let addr = PeerAddress::from_onion_host(host);
+        assert!(addr.is_onion());
+        assert!(!addr.is_ipv4());
+        assert_eq!(addr, PeerAddress::from_onion_host(&host.to_uppercase()));
+        assert!(addr != PeerAddress::from_onion_host("abc.onion"));
+
+        assert!(!PeerAddress::from_ipv4(127, 0, 0, 1).is_onion());
+        assert!(is_onion_host(host));
+        assert!(!is_onion_host("example.com"));
+    }
+
+    #[test]
+    fn test_socks5_handshake() {
+        let target = Socks5Target::Domain("example.onion".to_string(), 20444);
+        let mut handshake = Socks5Handshake::new(target.clone(), None).unwrap();
+
+        let mut sent = vec![];
+        handshake.flush_to(&mut sent).unwrap();
+        assert_eq!(sent, vec![SOCKS5_VERSION, 1, SOCKS5_AUTH_NONE]);
+
+        // replies can arrive in pieces
+        handshake.recv(&[SOCKS5_VERSION]).unwrap();
+        assert!(!handshake.is_connected());
+        handshake.recv(&[SOCKS5_AUTH_NONE]).unwrap();
+
+        let mut sent = vec![];
+        handshake.flush_to(&mut sent).unwrap();
+        let mut expected = vec![
+            SOCKS5_VERSION,
+            SOCKS5_CMD_CONNECT,
+            0x00,
+            SOCKS5_ATYP_DOMAIN,
+            13,
+        ];
+        expected.extend_from_slice(b"example.onion");
+        expected.extend_from_slice(&20444u16.to_be_bytes());
+        assert_eq!(sent, expected);
+
+        handshake
+            .recv(&[
+                SOCKS5_VERSION,
+                SOCKS5_REPLY_SUCCEEDED,
+                0x00,
+                SOCKS5_ATYP_IPV6,
+            ])
+            .unwrap();
+        handshake.recv(&[0u8; 16]).unwrap();
+        assert!(!handshake.is_connected());
+
+        // port, followed by the target's first bytes
+        handshake.recv(&[0x00, 0x00, 0xaa, 0xbb]).unwrap();
+        assert!(handshake.is_connected());
+        assert_eq!(handshake.take_leftover(), vec![0xaa, 0xbb]);
+
+        // proxy refuses
+        let mut handshake = Socks5Handshake::new(target.clone(), None).unwrap();
+        handshake.recv(&[SOCKS5_VERSION, SOCKS5_AUTH_NONE]).unwrap();
+        match handshake.recv(&[SOCKS5_VERSION, 0x05, 0x00, SOCKS5_ATYP_IPV4, 0]) {
+            Err(net_error::ProxyError(_)) => {}
+            x => panic!("expected a proxy error, got {:?}", &x),
+        }
+
+        // proxy wants a password we don't have
+        let mut handshake = Socks5Handshake::new(target.clone(), None).unwrap();
+        assert!(handshake
+            .recv(&[SOCKS5_VERSION, SOCKS5_AUTH_PASSWORD])
+            .is_err());
+
+        // no such thing as an empty password
+        assert!(
+            Socks5Handshake::new(target.clone(), Some(("user".to_string(), "".to_string())))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_connect_through_proxy() {
+        let credentials = Some(("alice".to_string(), "hunter2".to_string()));
+
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let server_thread = thread::spawn(move || {
+            let (mut sock, _) = server.accept().unwrap();
+            let mut buf = [0u8; 5];
+            sock.read_exact(&mut buf).unwrap();
+            sock.write_all(&buf).unwrap();
+        });
+
+        let (proxy_addr, proxy_thread) = spawn_socks5_proxy(1, credentials.clone());
+
+        let mut sock = connect_through_proxy(
+            &proxy_addr,
+            Socks5Target::Addr(server_addr.clone()),
+            credentials,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        sock.write_all(b"hello").unwrap();
+        let mut echo = [0u8; 5];
+        sock.read_exact(&mut echo).unwrap();
+        assert_eq!(&echo, b"hello");
+
+        server_thread.join().unwrap();
+        assert_eq!(
+            proxy_thread.join().unwrap(),
+            vec![Socks5Target::Addr(server_addr)]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Transaction gossip deduplication.  For each neighbor, we remember (in a rolling bloom filter)
+//! the txids it has sent us and the txids we've sent it, and we don't relay a transaction to a
+//! neighbor that is already known to have it.
+//!
+//! Nodes can also pull the transactions they're missing from a neighbor over p2p: the puller
+//! sends a `GetMempoolTxs` with a filter describing its mempool (the same filter the RPC mempool
+//! query uses), and the neighbor answers with a `MempoolTxs` of transactions that aren't in it.
+//! Pulled transactions are stored, but not relayed.
+
+use std::cmp;
+
+use rand::thread_rng;
+use rand::Rng;
+
+use burnchains::Txid;
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::stacks::db::StacksChainState;
+use core::mempool::{MemPoolDB, MAX_BLOOM_COUNTER_TXS};
+use net::capabilities::Capability;
+use net::p2p::PeerNetwork;
+use net::reputation::PeerMisbehavior;
+use net::Error as net_error;
+use net::*;
+use util::bloom::{BloomNodeHasher, RollingBloomFilter};
+use util::get_epoch_time_secs;
+
+/// False positive rate of each neighbor's tx relay filter.  A false positive means that we don't
+/// relay a transaction to a neighbor that doesn't have it; it will still get it from its other
+/// neighbors, or from a mempool pull.
+pub const TX_RELAY_FILTER_ERROR_RATE: f64 = 0.0001;
+
+impl PeerNetwork {
+    /// Remember that a neighbor has a transaction
+    pub fn mark_tx_known(&mut self, nk: &NeighborKey, txid: &Txid) {
+        let max_items = self.connection_opts.tx_relay_filter_items;
+        self.tx_relay_filters
+            .entry(nk.clone())
+            .or_insert_with(|| {
+                RollingBloomFilter::new(
+                    TX_RELAY_FILTER_ERROR_RATE,
+                    max_items,
+                    BloomNodeHasher::new_random(),
+                )
+            })
+            .insert_raw(&txid.0);
+    }
+
+    /// Does a neighbor (probably) already have a transaction?
+    pub fn is_tx_known(&self, nk: &NeighborKey, txid: &Txid) -> bool {
+        self.tx_relay_filters
+            .get(nk)
+            .map(|filter| filter.contains_raw(&txid.0))
+            .unwrap_or(false)
+    }
+
+    /// Of the given neighbors, find the ones that don't already have a transaction we're about to
+    /// broadcast, and remember that they will have it once we're done.
+    pub fn filter_tx_broadcast_peers(
+        &mut self,
+        neighbor_keys: Vec<NeighborKey>,
+        txid: &Txid,
+    ) -> Vec<NeighborKey> {
+        let mut ret = vec![];
+        for nk in neighbor_keys.into_iter() {
+            if self.is_tx_known(&nk, txid) {
+                debug!(
+                    "{:?}: Do not broadcast tx {} to {:?}: it already has it",
+                    &self.local_peer, txid, &nk
+                );
+                continue;
+            }
+            self.mark_tx_known(&nk, txid);
+            ret.push(nk);
+        }
+        ret
+    }
+
+    /// Answer a neighbor's `GetMempoolTxs` with the transactions that aren't in its filter
+    fn handle_unsolicited_GetMempoolTxs(
+        &mut self,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
+        event_id: usize,
+        nk: NeighborKey,
+        data: GetMempoolTxsData,
+    ) -> Result<(), net_error> {
+        let now = get_epoch_time_secs();
+        if let Some(last_answered) = self.mempool_txs_answered.get(&event_id) {
+            if *last_answered + self.connection_opts.mempool_txs_min_interval > now {
+                debug!(
+                    "{:?}: Throttle GetMempoolTxs from {:?}",
+                    &self.local_peer, &nk
+                );
+                let msg = self.sign_for_peer(
+                    &nk,
+                    StacksMessageType::Nack(NackData {
+                        error_code: NackErrorCodes::Throttled,
+                    }),
+                )?;
+                return self.relay_signed_message(&nk, msg);
+            }
+        }
+        self.mempool_txs_answered.insert(event_id, now);
+
+        let height = chainstate
+            .get_stacks_chain_tip(sortdb)?
+            .map(|blk| blk.height)
+            .unwrap_or(0);
+        let max_txs = cmp::min(
+            data.max_txs as u64,
+            self.connection_opts.mempool_max_tx_query,
+        );
+        let (txs, _, _) = mempool.find_next_missing_transactions(
+            &data.filter,
+            height,
+            &Txid([0u8; 32]),
+            max_txs,
+            MAX_BLOOM_COUNTER_TXS.into(),
+        )?;
+
+        debug!(
+            "{:?}: Send {} mempool transactions to {:?}",
+            &self.local_peer,
+            txs.len(),
+            &nk
+        );
+        for tx in txs.iter() {
+            self.mark_tx_known(&nk, &tx.txid());
+        }
+        let msg = self.sign_for_peer(&nk, StacksMessageType::MempoolTxs(MempoolTxsData { txs }))?;
+        self.relay_signed_message(&nk, msg)
+    }
+
+    /// Handle transaction-gossip messages from a neighbor: remember the transactions it pushes
+    /// us, answer its `GetMempoolTxs`, and accept the `MempoolTxs` we asked it for.  Returns the
+    /// message if it should continue on to the relayer.
+    pub fn handle_unsolicited_tx_gossip_message(
+        &mut self,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
+        event_id: usize,
+        message: StacksMessage,
+    ) -> Option<StacksMessage> {
+        match message.payload {
+            StacksMessageType::Transaction(ref tx) => {
+                if let Some(nk) = self
+                    .peers
+                    .get(&event_id)
+                    .map(|convo| convo.to_neighbor_key())
+                {
+                    self.mark_tx_known(&nk, &tx.txid());
+                }
+                return Some(message);
+            }
+            StacksMessageType::GetMempoolTxs(_) | StacksMessageType::MempoolTxs(_) => {}
+            _ => {
+                return Some(message);
+            }
+        }
+
+        let nk = match self.peers.get(&event_id) {
+            Some(convo) if convo.is_authenticated() => convo.to_neighbor_key(),
+            _ => {
+                // drop -- a correct peer will have authenticated before sending this message
+                test_debug!(
+                    "{:?}: Drop {} from unauthenticated event {}",
+                    &self.local_peer,
+                    message.payload.get_message_name(),
+                    event_id
+                );
+                return None;
+            }
+        };
+
+        match message.payload {
+            StacksMessageType::GetMempoolTxs(data) => {
+                if let Err(e) = self.handle_unsolicited_GetMempoolTxs(
+                    sortdb,
+                    chainstate,
+                    mempool,
+                    event_id,
+                    nk.clone(),
+                    data,
+                ) {
+                    warn!(
+                        "{:?}: Failed to answer GetMempoolTxs from {:?}: {:?}",
+                        &self.local_peer, &nk, &e
+                    );
+                }
+                None
+            }
+            StacksMessageType::MempoolTxs(ref data) => {
+                if self.pending_mempool_pulls.remove(&event_id).is_none() {
+                    debug!(
+                        "{:?}: Drop unrequested MempoolTxs from {:?}",
+                        &self.local_peer, &nk
+                    );
+                    self.record_peer_misbehavior(&nk, PeerMisbehavior::UnsolicitedSpam);
+                    return None;
+                }
+                debug!(
+                    "{:?}: Pulled {} mempool transactions from {:?}",
+                    &self.local_peer,
+                    data.txs.len(),
+                    &nk
+                );
+                for tx in data.txs.iter() {
+                    self.mark_tx_known(&nk, &tx.txid());
+                }
+                Some(message)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Every `mempool_pull_interval` seconds, ask a random outbound neighbor that answers
+    /// `GetMempoolTxs` for the mempool transactions we're missing.
+    pub fn do_mempool_pull(&mut self, mempool: &MemPoolDB, ibd: bool) -> Result<(), net_error> {
+        let interval = self.connection_opts.mempool_pull_interval;
+        let now = get_epoch_time_secs();
+        if ibd || interval == 0 || self.last_mempool_pull + interval > now {
+            return Ok(());
+        }
+        self.last_mempool_pull = now;
+
+        let candidates: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|(event_id, convo)| {
+                convo.is_outbound()
+                    && convo.is_authenticated()
+                    && convo.supports(Capability::MempoolTxs)
+                    && !self.pending_mempool_pulls.contains_key(*event_id)
+            })
+            .map(|(event_id, convo)| (*event_id, convo.to_neighbor_key()))
+            .collect();
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let (event_id, nk) = candidates[thread_rng().gen::<usize>() % candidates.len()].clone();
+
+        let filter = mempool.make_mempool_sync_data()?;
+        let max_txs = self.connection_opts.mempool_max_tx_query as u32;
+        debug!(
+            "{:?}: Pull up to {} mempool transactions from {:?}",
+            &self.local_peer, max_txs, &nk
+        );
+        let msg = self.sign_for_peer(
+            &nk,
+            StacksMessageType::GetMempoolTxs(GetMempoolTxsData { filter, max_txs }),
+        )?;
+        self.relay_signed_message(&nk, msg)?;
+        self.pending_mempool_pulls.insert(event_id, now);
+        Ok(())
+    }
+
+    /// Forget per-neighbor gossip state for neighbors we're no longer connected to, and give up
+    /// on mempool pulls that were never answered.
+    pub fn prune_tx_gossip_state(&mut self) {
+        let now = get_epoch_time_secs();
+        let timeout = self.connection_opts.timeout;
+        let events = &self.events;
+        let peers = &self.peers;
+        self.tx_relay_filters
+            .retain(|nk, _| events.contains_key(nk));
+        self.mempool_txs_answered
+            .retain(|event_id, _| peers.contains_key(event_id));
+        self.pending_mempool_pulls.retain(|event_id, asked_at| {
+            peers.contains_key(event_id) && *asked_at + timeout >= now
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use net::test::*;
+
+    #[test]
+    fn test_filter_tx_broadcast_peers() {
+        let peer_config = TestPeerConfig::new("test_filter_tx_broadcast_peers", 2243, 2244);
+        let mut peer = TestPeer::new(peer_config);
+
+        let nk_1 = NeighborKey {
+            peer_version: 0x12345678,
+            network_id: 0x9abcdef0,
+            addrbytes: PeerAddress([0x11; 16]),
+            port: 1234,
+        };
+        let mut nk_2 = nk_1.clone();
+        nk_2.port = 1235;
+
+        let txid_1 = Txid([0x01; 32]);
+        let txid_2 = Txid([0x02; 32]);
+
+        // nk_1 sent us txid_1
+        peer.network.mark_tx_known(&nk_1, &txid_1);
+        assert!(peer.network.is_tx_known(&nk_1, &txid_1));
+        assert!(!peer.network.is_tx_known(&nk_2, &txid_1));
+
+        // so only nk_2 needs it
+        let nks = peer
+            .network
+            .filter_tx_broadcast_peers(vec![nk_1.clone(), nk_2.clone()], &txid_1);
+        assert_eq!(nks, vec![nk_2.clone()]);
+
+        // and now nobody does
+        let nks = peer
+            .network
+            .filter_tx_broadcast_peers(vec![nk_1.clone(), nk_2.clone()], &txid_1);
+        assert!(nks.is_empty());
+
+        // everyone needs txid_2
+        let nks = peer
+            .network
+            .filter_tx_broadcast_peers(vec![nk_1.clone(), nk_2.clone()], &txid_2);
+        assert_eq!(nks, vec![nk_1.clone(), nk_2.clone()]);
+
+        // neither is connected, so their filters get pruned
+        peer.network.prune_tx_gossip_state();
+        assert!(!peer.network.is_tx_known(&nk_1, &txid_1));
+        assert!(peer.network.tx_relay_filters.is_empty());
+    }
+}
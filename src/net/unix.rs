@@ -0,0 +1,250 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Unix domain sockets for the RPC interface, so services on the same host can reach the node
+//! without it listening on a TCP port.  mio 0.6 only knows about TCP and UDP sockets, so these
+//! wrap the standard library's unix sockets and register their file descriptors with the poller
+//! directly.  Who may connect is decided by the socket file's permissions.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use mio::unix::EventedFd;
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+
+use net::Error as net_error;
+
+/// A listening unix domain socket.  The socket file is removed when this is dropped.
+pub struct UnixSocketListener {
+    path: PathBuf,
+    listener: UnixListener,
+}
+
+impl fmt::Debug for UnixSocketListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UnixSocketListener({})", self.path.display())
+    }
+}
+
+impl UnixSocketListener {
+    /// Bind to `path`, and give the socket file the permission bits in `mode`.  A socket file
+    /// left behind by an earlier run is replaced, but any other kind of file is left alone.
+    pub fn bind(path: &Path, mode: u32) -> Result<UnixSocketListener, net_error> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                error!(
+                    "Refusing to bind to {}: it exists and is not a socket",
+                    path.display()
+                );
+                return Err(net_error::BindError);
+            }
+            fs::remove_file(path).map_err(|e| {
+                error!("Failed to remove stale socket {}: {:?}", path.display(), &e);
+                net_error::BindError
+            })?;
+        }
+
+        let listener = UnixListener::bind(path).map_err(|e| {
+            error!("Failed to bind to {}: {:?}", path.display(), &e);
+            net_error::BindError
+        })?;
+
+        let listener = UnixSocketListener {
+            path: path.to_path_buf(),
+            listener,
+        };
+
+        listener.listener.set_nonblocking(true).map_err(|e| {
+            error!("Failed to make {} non-blocking: {:?}", path.display(), &e);
+            net_error::BindError
+        })?;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|e| {
+            error!(
+                "Failed to set permissions {:o} on {}: {:?}",
+                mode,
+                path.display(),
+                &e
+            );
+            net_error::BindError
+        })?;
+
+        Ok(listener)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accept a pending connection.  Fails with `WouldBlock` if there are none.
+    pub fn accept(&self) -> io::Result<UnixSocketStream> {
+        let (sock, _addr) = self.listener.accept()?;
+        sock.set_nonblocking(true)?;
+        Ok(UnixSocketStream { sock })
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Evented for UnixSocketListener {
+    fn register(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.listener.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.listener.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.listener.as_raw_fd()).deregister(poll)
+    }
+}
+
+/// A non-blocking connection accepted on a `UnixSocketListener`
+#[derive(Debug)]
+pub struct UnixSocketStream {
+    sock: UnixStream,
+}
+
+impl UnixSocketStream {
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.sock.shutdown(how)
+    }
+}
+
+impl Read for UnixSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.sock.read(buf)
+    }
+}
+
+impl Write for UnixSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sock.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sock.flush()
+    }
+}
+
+impl Evented for UnixSocketStream {
+    fn register(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.sock.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.sock.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.sock.as_raw_fd()).deregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_unix_socket_listener_bind() {
+        let path = env::temp_dir().join(format!(
+            "test_unix_socket_listener_bind.{}.sock",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let listener = UnixSocketListener::bind(&path, 0o600).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+        // nothing pending
+        assert_eq!(
+            listener.accept().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut server = listener.accept().unwrap();
+        client.write_all(b"hello").unwrap();
+
+        let mut buf = [0u8; 5];
+        let mut num_read = 0;
+        while num_read < buf.len() {
+            match server.read(&mut buf[num_read..]) {
+                Ok(n) => num_read += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("{:?}", &e),
+            }
+        }
+        assert_eq!(&buf, b"hello");
+
+        // a stale socket is replaced, and the new one gets the new permissions
+        drop(server);
+        std::mem::forget(listener);
+        let listener = UnixSocketListener::bind(&path, 0o660).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o660
+        );
+
+        // the socket file goes away with the listener
+        drop(listener);
+        assert!(fs::symlink_metadata(&path).is_err());
+
+        // other files are never replaced
+        fs::write(&path, b"not a socket").unwrap();
+        assert!(UnixSocketListener::bind(&path, 0o600).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
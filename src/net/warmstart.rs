@@ -0,0 +1,386 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fast restart.  The frontier already survives a restart, but a node that boots with it still
+//! has to find good neighbors one walk step at a time, which delays sync by minutes.  So every so
+//! often we score our outbound neighbors (reputation, request success rate, and round-trip time)
+//! and save the scores to the peer DB.  When we boot, we connect to the best-scoring peers from
+//! our last run all at once and handshake with them, before the first neighbor walk.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use net::chat::NeighborStats;
+use net::connection::ReplyHandleP2P;
+use net::db::PeerDB;
+use net::p2p::PeerNetwork;
+use net::Error as net_error;
+use net::*;
+use util::get_epoch_time_secs;
+
+/// Round-trip time at which a neighbor's score is halved
+pub const RTT_HALF_SCORE_MS: f64 = 1000.0;
+
+/// Score a neighbor out of `MAX_REPUTATION`: its reputation, scaled by the fraction of our
+/// requests it answered, and discounted by how long it takes to answer them.
+pub fn peer_quality_score(reputation: f64, stats: &NeighborStats) -> f64 {
+    let success_rate = stats.get_request_success_rate().unwrap_or(1.0);
+    let rtt_ms = stats.rtt_ms.unwrap_or(0.0).max(0.0);
+    reputation * success_rate * RTT_HALF_SCORE_MS / (RTT_HALF_SCORE_MS + rtt_ms)
+}
+
+/// Connections to our best peers from the last run that we're still setting up
+#[derive(Debug)]
+pub struct PeerWarmStart {
+    deadline: u64,
+    connecting: HashMap<NeighborKey, usize>,
+    handshakes: HashMap<NeighborKey, ReplyHandleP2P>,
+    /// peers that accepted our handshake
+    pub connected: HashSet<NeighborKey>,
+}
+
+impl PeerWarmStart {
+    pub fn is_done(&self) -> bool {
+        self.connecting.is_empty() && self.handshakes.is_empty()
+    }
+
+    /// Event IDs of the connections we're setting up, which the pruner must leave alone
+    pub fn events(&self) -> Vec<usize> {
+        self.connecting
+            .values()
+            .copied()
+            .chain(self.handshakes.values().map(|rh| rh.get_event_id()))
+            .collect()
+    }
+}
+
+impl PeerNetwork {
+    /// Connect to the best-scoring peers we saved on our last run
+    fn begin_peer_warm_start(&mut self) -> Result<PeerWarmStart, net_error> {
+        let now = get_epoch_time_secs();
+        let cur_epoch = self.get_current_epoch();
+        let best_peers = PeerDB::get_best_scored_peers(
+            self.peerdb.conn(),
+            self.local_peer.network_id,
+            cur_epoch.network_epoch,
+            self.connection_opts.warm_start_peers as u32,
+            self.chain_view.burn_block_height,
+            now.saturating_sub(self.connection_opts.peer_score_max_age),
+        )?;
+
+        let mut connecting = HashMap::new();
+        for neighbor in best_peers.into_iter() {
+            let nk = neighbor.addr;
+            if self.is_registered(&nk) {
+                continue;
+            }
+            if let Err(e) = self.can_register_peer(&nk, true) {
+                debug!(
+                    "{:?}: Will not warm-start {:?}: {:?}",
+                    &self.local_peer, &nk, &e
+                );
+                continue;
+            }
+            match self.connect_peer(&nk) {
+                Ok(event_id) => {
+                    debug!(
+                        "{:?}: Warm-start connecting to {:?} (event {})",
+                        &self.local_peer, &nk, event_id
+                    );
+                    connecting.insert(nk, event_id);
+                }
+                Err(e) => {
+                    debug!(
+                        "{:?}: Failed to warm-start {:?}: {:?}",
+                        &self.local_peer, &nk, &e
+                    );
+                }
+            }
+        }
+
+        if !connecting.is_empty() {
+            info!(
+                "{:?}: Reconnecting to {} peer(s) from our last run",
+                &self.local_peer,
+                connecting.len()
+            );
+        }
+
+        Ok(PeerWarmStart {
+            deadline: now + self.connection_opts.timeout,
+            connecting,
+            handshakes: HashMap::new(),
+            connected: HashSet::new(),
+        })
+    }
+
+    /// Handshake with warm-start peers that have finished connecting, and collect their replies
+    fn step_peer_warm_start(&mut self, warm_start: &mut PeerWarmStart) {
+        for (nk, event_id) in warm_start
+            .connecting
+            .drain()
+            .collect::<Vec<_>>()
+            .into_iter()
+        {
+            if self.is_connecting(event_id) {
+                warm_start.connecting.insert(nk, event_id);
+                continue;
+            }
+            if !self.peers.contains_key(&event_id) {
+                debug!(
+                    "{:?}: Failed to connect to warm-start peer {:?}",
+                    &self.local_peer, &nk
+                );
+                continue;
+            }
+
+            let handshake_data =
+                HandshakeData::from_local_peer_for(&self.local_peer, Some(&nk.addrbytes));
+            let res = self
+                .sign_for_peer(&nk, StacksMessageType::Handshake(handshake_data))
+                .and_then(|msg| self.send_message(&nk, msg, self.connection_opts.timeout));
+            match res {
+                Ok(rh) => {
+                    warm_start.handshakes.insert(nk, rh);
+                }
+                Err(e) => {
+                    debug!(
+                        "{:?}: Failed to handshake with warm-start peer {:?}: {:?}",
+                        &self.local_peer, &nk, &e
+                    );
+                }
+            }
+        }
+
+        for (nk, mut rh) in warm_start
+            .handshakes
+            .drain()
+            .collect::<Vec<_>>()
+            .into_iter()
+        {
+            if let Err(e) = self.saturate_p2p_socket(rh.get_event_id(), &mut rh) {
+                debug!(
+                    "{:?}: Failed to handshake with warm-start peer {:?}: {:?}",
+                    &self.local_peer, &nk, &e
+                );
+                continue;
+            }
+            match rh.try_send_recv() {
+                Ok(message) => match message.payload {
+                    StacksMessageType::HandshakeAccept(..) => {
+                        debug!("{:?}: Warm-started {:?}", &self.local_peer, &nk);
+                        warm_start.connected.insert(nk);
+                    }
+                    other => {
+                        debug!(
+                            "{:?}: Warm-start peer {:?} did not accept our handshake: {:?}",
+                            &self.local_peer,
+                            &nk,
+                            other.get_message_name()
+                        );
+                    }
+                },
+                Err(Ok(rh)) => {
+                    warm_start.handshakes.insert(nk, rh);
+                }
+                Err(Err(e)) => {
+                    debug!(
+                        "{:?}: Failed to handshake with warm-start peer {:?}: {:?}",
+                        &self.local_peer, &nk, &e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reconnect to our best peers from the last run, if we haven't already.  Runs until every
+    /// warm-start peer has either handshaked with us or failed to, or until the connection
+    /// timeout passes.
+    pub fn do_peer_warm_start(&mut self) {
+        if self.connection_opts.warm_start_peers == 0 {
+            return;
+        }
+        if !self.warm_start_begun {
+            self.warm_start_begun = true;
+            match self.begin_peer_warm_start() {
+                Ok(warm_start) => {
+                    self.warm_start = Some(warm_start);
+                }
+                Err(e) => {
+                    warn!(
+                        "{:?}: Failed to load warm-start peers: {:?}",
+                        &self.local_peer, &e
+                    );
+                }
+            }
+        }
+
+        let mut warm_start = match self.warm_start.take() {
+            Some(warm_start) => warm_start,
+            None => {
+                return;
+            }
+        };
+
+        self.step_peer_warm_start(&mut warm_start);
+        if warm_start.is_done() || warm_start.deadline < get_epoch_time_secs() {
+            info!(
+                "{:?}: Warm-started {} peer(s) from our last run",
+                &self.local_peer,
+                warm_start.connected.len()
+            );
+            return;
+        }
+        self.warm_start = Some(warm_start);
+    }
+
+    /// Event IDs of warm-start connections that are still being set up
+    pub fn get_warm_start_events(&self) -> Vec<usize> {
+        self.warm_start
+            .as_ref()
+            .map(|warm_start| warm_start.events())
+            .unwrap_or_default()
+    }
+
+    /// Score our authenticated outbound neighbors and save the scores to the peer DB, so we can
+    /// reconnect to the best of them when we restart
+    pub fn save_peer_scores(&mut self) -> Result<(), net_error> {
+        let now = get_epoch_time_secs();
+        let mut scores = vec![];
+        for convo in self.peers.values() {
+            if !convo.is_outbound() || !convo.is_authenticated() {
+                continue;
+            }
+            let nk = convo.to_neighbor_key();
+            let score = peer_quality_score(self.get_peer_reputation(&nk), &convo.stats);
+            scores.push((nk, score));
+        }
+
+        let max_age = self.connection_opts.peer_score_max_age;
+        let mut tx = self.peerdb.tx_begin()?;
+        for (nk, score) in scores.iter() {
+            PeerDB::set_peer_score(&mut tx, nk, *score, now)?;
+        }
+        PeerDB::prune_peer_scores(&mut tx, now.saturating_sub(max_age))?;
+        tx.commit().map_err(db_error::SqliteError)?;
+
+        debug!(
+            "{:?}: Saved scores for {} outbound neighbor(s)",
+            &self.local_peer,
+            scores.len()
+        );
+        Ok(())
+    }
+
+    /// Save our neighbors' scores, if it's been long enough since we last did
+    pub fn do_save_peer_scores(&mut self) {
+        let now = get_epoch_time_secs();
+        if self.peer_scores_saved_at + self.connection_opts.peer_score_save_interval > now {
+            return;
+        }
+        self.peer_scores_saved_at = now;
+        if let Err(e) = self.save_peer_scores() {
+            warn!(
+                "{:?}: Failed to save neighbor scores: {:?}",
+                &self.local_peer, &e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use net::reputation::MAX_REPUTATION;
+    use net::test::*;
+    use util::test::with_timeout;
+
+    #[test]
+    fn test_peer_quality_score() {
+        let mut stats = NeighborStats::new(true);
+
+        // nothing known yet
+        assert_eq!(peer_quality_score(MAX_REPUTATION, &stats), MAX_REPUTATION);
+        assert_eq!(peer_quality_score(50.0, &stats), 50.0);
+
+        // dropped requests
+        stats.replies_rx = 3;
+        stats.requests_timed_out = 1;
+        assert_eq!(peer_quality_score(MAX_REPUTATION, &stats), 75.0);
+
+        // slow replies
+        stats.rtt_ms = Some(RTT_HALF_SCORE_MS);
+        assert_eq!(peer_quality_score(MAX_REPUTATION, &stats), 37.5);
+
+        let mut fast_stats = NeighborStats::new(true);
+        fast_stats.rtt_ms = Some(10.0);
+        let mut slow_stats = NeighborStats::new(true);
+        slow_stats.rtt_ms = Some(500.0);
+        assert!(
+            peer_quality_score(MAX_REPUTATION, &fast_stats)
+                > peer_quality_score(MAX_REPUTATION, &slow_stats)
+        );
+    }
+
+    #[test]
+    fn test_peer_warm_start() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(32800);
+            let peer_2_config = TestPeerConfig::from_port(32802);
+
+            // peer 1 knows about peer 2, but won't walk to it
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+            peer_1_config.connection_opts.disable_neighbor_walk = true;
+            peer_1_config.connection_opts.warm_start_peers = 8;
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+
+            // peer 1 scored peer 2 on its last run
+            let nk_2 = peer_2.to_neighbor().addr;
+            {
+                let mut tx = peer_1.network.peerdb.tx_begin().unwrap();
+                PeerDB::set_peer_score(&mut tx, &nk_2, 90.0, get_epoch_time_secs()).unwrap();
+                tx.commit().unwrap();
+            }
+
+            let mut i = 0;
+            while peer_1
+                .network
+                .get_convo(&nk_2)
+                .map(|convo| convo.is_authenticated())
+                != Some(true)
+            {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+                i += 1;
+            }
+            debug!("Warm-started peer 2 in {} step(s)", i);
+
+            assert!(peer_1.network.warm_start_begun);
+            assert!(peer_1.network.get_convo(&nk_2).unwrap().is_outbound());
+            assert_eq!(peer_1.network.walk_total_step_count, 0);
+
+            // peer 1 scores peer 2 again
+            peer_1.network.save_peer_scores().unwrap();
+            let (score, _) = PeerDB::get_peer_score(peer_1.network.peerdb.conn(), &nk_2)
+                .unwrap()
+                .unwrap();
+            assert!(score > 0.0 && score <= MAX_REPUTATION);
+        })
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Stale-tip detection.  If the burnchain keeps advancing but our canonical Stacks tip doesn't,
+//! then we're probably stuck talking to neighbors that can't (or won't) give us new blocks.
+//! Once the tip has been stuck for `stale_tip_burn_blocks` burnchain blocks, the peer network
+//! throws away its neighbors' inventories and its block downloader state, walks the peer graph
+//! to meet new neighbors, asks its outbound neighbors for their headers above our tip (see
+//! `net::headers`), and reports the stale tip to event observers.  It tries again every
+//! `stale_tip_burn_blocks` burnchain blocks until the tip moves.
+
+use net::p2p::PeerNetwork;
+use net::RPCStaleTipData;
+
+use crate::types::chainstate::{StacksBlockHeader, StacksBlockId};
+
+/// How many burnchain blocks the Stacks tip may go without advancing before the node tries to
+/// recover, unless configured otherwise
+pub const STALE_TIP_BURN_BLOCKS: u64 = 6;
+
+/// Tracks when the canonical Stacks tip last advanced
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaleTipWatchdog {
+    stacks_tip: StacksBlockId,
+    /// burnchain height when `stacks_tip` became the tip (or when we stopped being in the
+    /// initial block download)
+    advanced_at_burn_height: u64,
+    /// burnchain height of our last recovery attempt for this tip
+    recovered_at_burn_height: Option<u64>,
+    recovery_attempts: u64,
+}
+
+impl StaleTipWatchdog {
+    pub fn new() -> StaleTipWatchdog {
+        StaleTipWatchdog {
+            stacks_tip: StacksBlockId([0u8; 32]),
+            advanced_at_burn_height: 0,
+            recovered_at_burn_height: None,
+            recovery_attempts: 0,
+        }
+    }
+
+    /// Start timing the tip anew from `burn_block_height`
+    fn restart(&mut self, stacks_tip: &StacksBlockId, burn_block_height: u64) {
+        self.stacks_tip = stacks_tip.clone();
+        self.advanced_at_burn_height = burn_block_height;
+        self.recovered_at_burn_height = None;
+        self.recovery_attempts = 0;
+    }
+
+    /// Check the Stacks tip against the burnchain tip.  Returns the alert to raise if it's time
+    /// to try to recover.  A `threshold` of 0 disables the watchdog.
+    pub fn check(
+        &mut self,
+        stacks_tip: &StacksBlockId,
+        stacks_tip_height: u64,
+        burn_block_height: u64,
+        threshold: u64,
+        ibd: bool,
+    ) -> Option<RPCStaleTipData> {
+        if ibd || *stacks_tip != self.stacks_tip {
+            // still catching up, or making progress
+            self.restart(stacks_tip, burn_block_height);
+            return None;
+        }
+        if threshold == 0 {
+            return None;
+        }
+
+        let last_checkpoint = self
+            .recovered_at_burn_height
+            .unwrap_or(self.advanced_at_burn_height);
+        if burn_block_height < last_checkpoint.saturating_add(threshold) {
+            return None;
+        }
+
+        self.recovered_at_burn_height = Some(burn_block_height);
+        self.recovery_attempts += 1;
+        Some(RPCStaleTipData {
+            stacks_tip: self.stacks_tip.clone(),
+            stacks_tip_height,
+            stale_since_burn_block_height: self.advanced_at_burn_height,
+            burn_block_height,
+            recovery_attempt: self.recovery_attempts,
+        })
+    }
+}
+
+impl PeerNetwork {
+    /// Has our Stacks tip stopped advancing?  If so, start over on finding and fetching blocks:
+    /// forget our neighbors' inventories, drop in-flight downloads, and go meet new neighbors.
+    /// Returns the alert to report, if we're recovering.
+    pub fn check_stale_tip(&mut self, ibd: bool) -> Option<RPCStaleTipData> {
+        let stacks_tip = StacksBlockHeader::make_index_block_hash(
+            &self.burnchain_tip.canonical_stacks_tip_consensus_hash,
+            &self.burnchain_tip.canonical_stacks_tip_hash,
+        );
+        let alert = self.stale_tip_watchdog.check(
+            &stacks_tip,
+            self.burnchain_tip.canonical_stacks_tip_height,
+            self.burnchain_tip.block_height,
+            self.connection_opts.stale_tip_burn_blocks,
+            ibd,
+        )?;
+
+        warn!(
+            "{:?}: Stacks tip {} (height {}) has not advanced since burn block {} (now {}); resetting block sync (attempt {})",
+            &self.local_peer,
+            &alert.stacks_tip,
+            alert.stacks_tip_height,
+            alert.stale_since_burn_block_height,
+            alert.burn_block_height,
+            alert.recovery_attempt
+        );
+
+        // re-sync inventories with whoever we're connected to once the walk finds them
+        self.inv_state = None;
+        self.block_downloader = None;
+
+        // walk the peer graph right away, and do a few walks to mix in new neighbors
+        self.walk = None;
+        self.walk_count = 0;
+        self.walk_retries = 0;
+        self.walk_deadline = 0;
+
+        // find out where our current neighbors' chains go from our tip
+        self.request_tip_headers();
+
+        Some(alert)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stale_tip_watchdog() {
+        let tip_1 = StacksBlockId([0x11; 32]);
+        let tip_2 = StacksBlockId([0x22; 32]);
+        let mut watchdog = StaleTipWatchdog::new();
+
+        // tip appears at burn block 100
+        assert!(watchdog.check(&tip_1, 10, 100, 3, false).is_none());
+        assert!(watchdog.check(&tip_1, 10, 102, 3, false).is_none());
+
+        // stuck for 3 blocks
+        let alert = watchdog.check(&tip_1, 10, 103, 3, false).unwrap();
+        assert_eq!(alert.stacks_tip, tip_1);
+        assert_eq!(alert.stacks_tip_height, 10);
+        assert_eq!(alert.stale_since_burn_block_height, 100);
+        assert_eq!(alert.burn_block_height, 103);
+        assert_eq!(alert.recovery_attempt, 1);
+
+        // doesn't fire again until another 3 blocks go by
+        assert!(watchdog.check(&tip_1, 10, 103, 3, false).is_none());
+        assert!(watchdog.check(&tip_1, 10, 105, 3, false).is_none());
+        let alert = watchdog.check(&tip_1, 10, 106, 3, false).unwrap();
+        assert_eq!(alert.stale_since_burn_block_height, 100);
+        assert_eq!(alert.recovery_attempt, 2);
+
+        // tip moves, so we start over
+        assert!(watchdog.check(&tip_2, 11, 107, 3, false).is_none());
+        assert!(watchdog.check(&tip_2, 11, 109, 3, false).is_none());
+        let alert = watchdog.check(&tip_2, 11, 110, 3, false).unwrap();
+        assert_eq!(alert.stale_since_burn_block_height, 107);
+        assert_eq!(alert.recovery_attempt, 1);
+
+        // never fires during the initial block download, or once that's over
+        let mut watchdog = StaleTipWatchdog::new();
+        assert!(watchdog.check(&tip_1, 10, 100, 3, false).is_none());
+        assert!(watchdog.check(&tip_1, 10, 200, 3, true).is_none());
+        assert!(watchdog.check(&tip_1, 10, 202, 3, false).is_none());
+        assert!(watchdog.check(&tip_1, 10, 203, 3, false).is_some());
+
+        // can be disabled
+        let mut watchdog = StaleTipWatchdog::new();
+        assert!(watchdog.check(&tip_1, 10, 100, 0, false).is_none());
+        assert!(watchdog.check(&tip_1, 10, 1000, 0, false).is_none());
+    }
+}
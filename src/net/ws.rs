@@ -0,0 +1,916 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! WebSocket subscriptions on the RPC server.  A client upgrades a `GET /v2/ws` request to a
+//! WebSocket (RFC 6455), and then sends JSON text messages to subscribe to topics:
+//!
+//! * `{"op": "subscribe", "topic": "block"}` -- newly-processed anchored blocks
+//! * `{"op": "subscribe", "topic": "microblock"}` -- newly-processed microblocks
+//! * `{"op": "subscribe", "topic": "mempool"}` -- transactions admitted to the mempool
+//! * `{"op": "subscribe", "topic": "tx", "txid": "0x..."}` -- status changes of one transaction
+//! * `{"op": "subscribe", "topic": "reorg"}` -- reorgs of the canonical Stacks chain
+//!
+//! The node feeds `WebSocketEvent`s to the RPC server through a bounded channel, and the RPC
+//! server pushes each event to the sessions subscribed to it.
+
+use std::collections::HashSet;
+use std::io;
+use std::io::{Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+
+use base64;
+use sha1::{Digest, Sha1};
+
+use burnchains::Txid;
+use chainstate::burn::ConsensusHash;
+use net::Error as net_error;
+use net::RPCChainReorgData;
+use util::get_epoch_time_secs;
+
+use crate::types::chainstate::{BlockHeaderHash, StacksBlockId};
+
+/// GUID that a server appends to the client's key when accepting a WebSocket handshake
+pub const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest message we accept from a client.  Client messages are only ever subscription
+/// requests, so this is generous.
+pub const WEBSOCKET_MAX_MESSAGE_LEN: usize = 4096;
+/// Largest amount of unsent data a session may buffer before it's considered too slow to keep
+pub const WEBSOCKET_MAX_OUTBOX_LEN: usize = 16 * 1024 * 1024;
+/// Most transactions a session can watch at once
+pub const WEBSOCKET_MAX_TX_SUBSCRIPTIONS: usize = 256;
+/// Most events the node can queue up for the RPC server before it starts dropping them
+pub const WEBSOCKET_EVENT_QUEUE_LEN: usize = 4096;
+
+pub type WebSocketEventSender = SyncSender<WebSocketEvent>;
+pub type WebSocketEventReceiver = Receiver<WebSocketEvent>;
+
+/// Make the channel through which the node feeds events to the RPC server's WebSocket sessions
+pub fn websocket_event_channel() -> (WebSocketEventSender, WebSocketEventReceiver) {
+    sync_channel(WEBSOCKET_EVENT_QUEUE_LEN)
+}
+
+/// Queue up an event for the RPC server's WebSocket sessions.  This never blocks; if the RPC
+/// server has fallen behind, the event is dropped.
+pub fn websocket_send_event(sender: &WebSocketEventSender, event: WebSocketEvent) {
+    match sender.try_send(event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            warn!("WebSocket event queue is full; dropping event");
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            debug!("WebSocket event queue is disconnected; dropping event");
+        }
+    }
+}
+
+/// Compute the `Sec-WebSocket-Accept` header value for a client's `Sec-WebSocket-Key`
+pub fn websocket_accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    base64::encode(hasher.finalize())
+}
+
+/// Something that happened on the node that WebSocket clients can subscribe to
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebSocketEvent {
+    /// An anchored block was processed and is now the canonical chain tip
+    Block {
+        index_block_hash: StacksBlockId,
+        block_hash: BlockHeaderHash,
+        consensus_hash: ConsensusHash,
+        parent_index_block_hash: StacksBlockId,
+        height: u64,
+        txids: Vec<Txid>,
+    },
+    /// A microblock was processed on top of the given anchored block
+    Microblock {
+        microblock_hash: BlockHeaderHash,
+        sequence: u16,
+        parent_index_block_hash: StacksBlockId,
+        txids: Vec<Txid>,
+    },
+    /// Transactions were admitted to the mempool
+    MempoolTxs(Vec<Txid>),
+    /// Transactions were dropped from the mempool, for the given reason
+    MempoolTxsDropped(Vec<Txid>, String),
+    /// The canonical Stacks chain tip moved to a block that does not descend from the old tip
+    Reorg(RPCChainReorgData),
+}
+
+/// What a session can subscribe to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WebSocketTopic {
+    Blocks,
+    Microblocks,
+    Mempool,
+    Transaction(Txid),
+    Reorgs,
+}
+
+impl WebSocketTopic {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebSocketTopic::Blocks => "block",
+            WebSocketTopic::Microblocks => "microblock",
+            WebSocketTopic::Mempool => "mempool",
+            WebSocketTopic::Transaction(_) => "tx",
+            WebSocketTopic::Reorgs => "reorg",
+        }
+    }
+
+    fn from_request(request: &WebSocketRequest) -> Result<WebSocketTopic, String> {
+        match request.topic.as_str() {
+            "block" => Ok(WebSocketTopic::Blocks),
+            "microblock" => Ok(WebSocketTopic::Microblocks),
+            "mempool" => Ok(WebSocketTopic::Mempool),
+            "reorg" => Ok(WebSocketTopic::Reorgs),
+            "tx" => {
+                let txid_hex = request
+                    .txid
+                    .as_ref()
+                    .ok_or_else(|| "topic `tx` requires a `txid`".to_string())?;
+                let txid_hex = if txid_hex.starts_with("0x") {
+                    &txid_hex[2..]
+                } else {
+                    txid_hex.as_str()
+                };
+                Txid::from_hex(txid_hex)
+                    .map(WebSocketTopic::Transaction)
+                    .map_err(|_| format!("invalid txid `{}`", txid_hex))
+            }
+            topic => Err(format!("unknown topic `{}`", topic)),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            WebSocketTopic::Transaction(txid) => {
+                json!({ "topic": self.name(), "txid": format!("0x{}", txid) })
+            }
+            _ => json!({ "topic": self.name() }),
+        }
+    }
+}
+
+/// A message from a client
+#[derive(Debug, Deserialize)]
+struct WebSocketRequest {
+    op: String,
+    topic: String,
+    #[serde(default)]
+    txid: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebSocketOpcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl WebSocketOpcode {
+    fn from_u8(opcode: u8) -> Option<WebSocketOpcode> {
+        match opcode {
+            0x0 => Some(WebSocketOpcode::Continuation),
+            0x1 => Some(WebSocketOpcode::Text),
+            0x2 => Some(WebSocketOpcode::Binary),
+            0x8 => Some(WebSocketOpcode::Close),
+            0x9 => Some(WebSocketOpcode::Ping),
+            0xa => Some(WebSocketOpcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn to_u8(&self) -> u8 {
+        match self {
+            WebSocketOpcode::Continuation => 0x0,
+            WebSocketOpcode::Text => 0x1,
+            WebSocketOpcode::Binary => 0x2,
+            WebSocketOpcode::Close => 0x8,
+            WebSocketOpcode::Ping => 0x9,
+            WebSocketOpcode::Pong => 0xa,
+        }
+    }
+
+    fn is_control(&self) -> bool {
+        match self {
+            WebSocketOpcode::Close | WebSocketOpcode::Ping | WebSocketOpcode::Pong => true,
+            _ => false,
+        }
+    }
+}
+
+/// A single WebSocket frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketFrame {
+    pub fin: bool,
+    pub opcode: WebSocketOpcode,
+    pub payload: Vec<u8>,
+}
+
+impl WebSocketFrame {
+    pub fn new(opcode: WebSocketOpcode, payload: Vec<u8>) -> WebSocketFrame {
+        WebSocketFrame {
+            fin: true,
+            opcode,
+            payload,
+        }
+    }
+
+    /// Decode a client frame from the front of `buf`.  Client frames must be masked, and no
+    /// bigger than WEBSOCKET_MAX_MESSAGE_LEN.
+    /// Returns the frame and the number of bytes it took up, or Ok(None) if `buf` does not yet
+    /// hold a whole frame.
+    pub fn decode(buf: &[u8]) -> Result<Option<(WebSocketFrame, usize)>, net_error> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+        if buf[0] & 0x70 != 0 {
+            return Err(net_error::DeserializeError(
+                "WebSocket frame uses reserved bits".into(),
+            ));
+        }
+        let fin = buf[0] & 0x80 != 0;
+        let opcode = WebSocketOpcode::from_u8(buf[0] & 0x0f).ok_or_else(|| {
+            net_error::DeserializeError(format!("Unknown WebSocket opcode {}", buf[0] & 0x0f))
+        })?;
+        if buf[1] & 0x80 == 0 {
+            return Err(net_error::DeserializeError(
+                "WebSocket client frame is not masked".into(),
+            ));
+        }
+
+        let (payload_len, mut offset) = match buf[1] & 0x7f {
+            126 => {
+                if buf.len() < 4 {
+                    return Ok(None);
+                }
+                ((u16::from_be_bytes([buf[2], buf[3]])) as u64, 4)
+            }
+            127 => {
+                if buf.len() < 10 {
+                    return Ok(None);
+                }
+                let mut len_bytes = [0u8; 8];
+                len_bytes.copy_from_slice(&buf[2..10]);
+                (u64::from_be_bytes(len_bytes), 10)
+            }
+            len => (len as u64, 2),
+        };
+        if payload_len > WEBSOCKET_MAX_MESSAGE_LEN as u64 {
+            return Err(net_error::OverflowError(format!(
+                "WebSocket frame is too big ({} bytes)",
+                payload_len
+            )));
+        }
+        if opcode.is_control() && (payload_len > 125 || !fin) {
+            return Err(net_error::DeserializeError(
+                "Invalid WebSocket control frame".into(),
+            ));
+        }
+
+        let payload_len = payload_len as usize;
+        if buf.len() < offset + 4 + payload_len {
+            return Ok(None);
+        }
+        let mask = [
+            buf[offset],
+            buf[offset + 1],
+            buf[offset + 2],
+            buf[offset + 3],
+        ];
+        offset += 4;
+        let payload = buf[offset..offset + payload_len]
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4])
+            .collect();
+
+        Ok(Some((
+            WebSocketFrame {
+                fin,
+                opcode,
+                payload,
+            },
+            offset + payload_len,
+        )))
+    }
+
+    /// Encode this frame as a server frame, which is never masked
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.payload.len() + 10);
+        let fin = if self.fin { 0x80 } else { 0x00 };
+        bytes.push(fin | self.opcode.to_u8());
+        if self.payload.len() < 126 {
+            bytes.push(self.payload.len() as u8);
+        } else if self.payload.len() <= u16::MAX as usize {
+            bytes.push(126);
+            bytes.extend_from_slice(&(self.payload.len() as u16).to_be_bytes());
+        } else {
+            bytes.push(127);
+            bytes.extend_from_slice(&(self.payload.len() as u64).to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Encode this frame as a client frame, masked with `mask`
+    #[cfg(test)]
+    pub fn encode_masked(&self, mask: [u8; 4]) -> Vec<u8> {
+        let mut bytes = self.encode();
+        let header_len = bytes.len() - self.payload.len();
+        bytes[1] |= 0x80;
+        let masked: Vec<u8> = self
+            .payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4])
+            .collect();
+        bytes.truncate(header_len);
+        bytes.extend_from_slice(&mask);
+        bytes.extend_from_slice(&masked);
+        bytes
+    }
+}
+
+/// The state of one client's WebSocket connection, once the HTTP upgrade has completed
+#[derive(Debug)]
+pub struct WebSocketSession {
+    inbuf: Vec<u8>,
+    outbuf: Vec<u8>,
+    /// a fragmented message we've started to receive
+    partial_message: Option<Vec<u8>>,
+    subscriptions: HashSet<WebSocketTopic>,
+    /// we've sent a close frame; once it's flushed, the session is over
+    closing: bool,
+    /// when we last received a frame, and when we last sent one
+    last_recv_time: u64,
+    last_send_time: u64,
+}
+
+impl WebSocketSession {
+    pub fn new() -> WebSocketSession {
+        let now = get_epoch_time_secs();
+        WebSocketSession {
+            inbuf: vec![],
+            outbuf: vec![],
+            partial_message: None,
+            subscriptions: HashSet::new(),
+            closing: false,
+            last_recv_time: now,
+            last_send_time: now,
+        }
+    }
+
+    pub fn is_subscribed(&self, topic: &WebSocketTopic) -> bool {
+        self.subscriptions.contains(topic)
+    }
+
+    /// Is the session over?  It is once we've sent a close frame and flushed it.
+    pub fn is_closed(&self) -> bool {
+        self.closing && self.outbuf.is_empty()
+    }
+
+    pub fn get_last_recv_time(&self) -> u64 {
+        self.last_recv_time
+    }
+
+    pub fn get_last_send_time(&self) -> u64 {
+        self.last_send_time
+    }
+
+    /// Read whatever data is available on `fd`, and act on the client's messages.
+    /// Returns the number of bytes read, or net_error::PermanentlyDrained if the client
+    /// disconnected.
+    pub fn recv<R: Read>(&mut self, fd: &mut R) -> Result<usize, net_error> {
+        let mut total_read = 0;
+        let mut socket_closed = false;
+        loop {
+            let mut buf = [0u8; 4096];
+            match fd.read(&mut buf) {
+                Ok(0) => {
+                    socket_closed = true;
+                    break;
+                }
+                Ok(count) => {
+                    total_read += count;
+                    self.inbuf.extend_from_slice(&buf[0..count]);
+                    if self.inbuf.len() > 2 * WEBSOCKET_MAX_MESSAGE_LEN {
+                        return Err(net_error::InboxOverflow);
+                    }
+                }
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || (cfg!(windows) && e.kind() == io::ErrorKind::TimedOut)
+                    {
+                        break;
+                    } else if e.kind() == io::ErrorKind::BrokenPipe
+                        || e.kind() == io::ErrorKind::ConnectionReset
+                    {
+                        socket_closed = true;
+                        break;
+                    } else {
+                        return Err(net_error::RecvError(format!("Failed to read: {:?}", &e)));
+                    }
+                }
+            }
+        }
+
+        if total_read > 0 {
+            self.last_recv_time = get_epoch_time_secs();
+        }
+        self.process_frames()?;
+
+        if socket_closed {
+            return Err(net_error::PermanentlyDrained);
+        }
+        Ok(total_read)
+    }
+
+    /// Act on each whole frame in the inbound buffer
+    fn process_frames(&mut self) -> Result<(), net_error> {
+        let mut consumed = 0;
+        while !self.closing {
+            let (frame, frame_len) = match WebSocketFrame::decode(&self.inbuf[consumed..]) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("Bad WebSocket frame: {:?}", &e);
+                    self.close(1002);
+                    break;
+                }
+            };
+            consumed += frame_len;
+            self.process_frame(frame);
+        }
+        self.inbuf.drain(0..consumed);
+        Ok(())
+    }
+
+    fn process_frame(&mut self, frame: WebSocketFrame) {
+        match frame.opcode {
+            WebSocketOpcode::Ping => {
+                self.push_frame(WebSocketFrame::new(WebSocketOpcode::Pong, frame.payload));
+            }
+            WebSocketOpcode::Pong => {}
+            WebSocketOpcode::Close => {
+                self.close(1000);
+            }
+            WebSocketOpcode::Text | WebSocketOpcode::Binary => {
+                if self.partial_message.is_some() {
+                    // a new message can't start until the fragmented one ends
+                    self.close(1002);
+                } else if frame.fin {
+                    self.handle_message(&frame.payload);
+                } else {
+                    self.partial_message = Some(frame.payload);
+                }
+            }
+            WebSocketOpcode::Continuation => match self.partial_message.take() {
+                None => self.close(1002),
+                Some(mut message) => {
+                    message.extend_from_slice(&frame.payload);
+                    if message.len() > WEBSOCKET_MAX_MESSAGE_LEN {
+                        self.close(1009);
+                    } else if frame.fin {
+                        self.handle_message(&message);
+                    } else {
+                        self.partial_message = Some(message);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Act on a (subscription) message from the client
+    fn handle_message(&mut self, message: &[u8]) {
+        let request: WebSocketRequest = match serde_json::from_slice(message) {
+            Ok(request) => request,
+            Err(e) => {
+                self.push_error(format!("failed to parse message: {}", e));
+                return;
+            }
+        };
+        let topic = match WebSocketTopic::from_request(&request) {
+            Ok(topic) => topic,
+            Err(reason) => {
+                self.push_error(reason);
+                return;
+            }
+        };
+
+        match request.op.as_str() {
+            "subscribe" => {
+                if let WebSocketTopic::Transaction(_) = topic {
+                    let num_txs = self
+                        .subscriptions
+                        .iter()
+                        .filter(|topic| match topic {
+                            WebSocketTopic::Transaction(_) => true,
+                            _ => false,
+                        })
+                        .count();
+                    if num_txs >= WEBSOCKET_MAX_TX_SUBSCRIPTIONS
+                        && !self.subscriptions.contains(&topic)
+                    {
+                        self.push_error(format!(
+                            "too many transaction subscriptions (at most {})",
+                            WEBSOCKET_MAX_TX_SUBSCRIPTIONS
+                        ));
+                        return;
+                    }
+                }
+                let mut reply = topic.to_json();
+                reply["op"] = json!("subscribed");
+                self.subscriptions.insert(topic);
+                self.push_json(&reply);
+            }
+            "unsubscribe" => {
+                let mut reply = topic.to_json();
+                reply["op"] = json!("unsubscribed");
+                self.subscriptions.remove(&topic);
+                self.push_json(&reply);
+            }
+            op => {
+                self.push_error(format!("unknown op `{}`", op));
+            }
+        }
+    }
+
+    /// Send the client the parts of `event` it's subscribed to
+    pub fn publish(&mut self, event: &WebSocketEvent) {
+        if self.closing {
+            return;
+        }
+        match event {
+            WebSocketEvent::Block {
+                index_block_hash,
+                block_hash,
+                consensus_hash,
+                parent_index_block_hash,
+                height,
+                txids,
+            } => {
+                if self.is_subscribed(&WebSocketTopic::Blocks) {
+                    self.push_event(
+                        WebSocketTopic::Blocks.name(),
+                        json!({
+                            "index_block_hash": format!("0x{}", index_block_hash),
+                            "block_hash": format!("0x{}", block_hash),
+                            "consensus_hash": format!("0x{}", consensus_hash),
+                            "parent_index_block_hash": format!("0x{}", parent_index_block_hash),
+                            "block_height": height,
+                            "txids": txids.iter().map(|txid| format!("0x{}", txid)).collect::<Vec<_>>(),
+                        }),
+                    );
+                }
+                for txid in txids.iter() {
+                    self.push_tx_status(
+                        txid,
+                        json!({
+                            "status": "anchored",
+                            "index_block_hash": format!("0x{}", index_block_hash),
+                            "block_height": height,
+                        }),
+                    );
+                }
+            }
+            WebSocketEvent::Microblock {
+                microblock_hash,
+                sequence,
+                parent_index_block_hash,
+                txids,
+            } => {
+                if self.is_subscribed(&WebSocketTopic::Microblocks) {
+                    self.push_event(
+                        WebSocketTopic::Microblocks.name(),
+                        json!({
+                            "microblock_hash": format!("0x{}", microblock_hash),
+                            "sequence": sequence,
+                            "parent_index_block_hash": format!("0x{}", parent_index_block_hash),
+                            "txids": txids.iter().map(|txid| format!("0x{}", txid)).collect::<Vec<_>>(),
+                        }),
+                    );
+                }
+                for txid in txids.iter() {
+                    self.push_tx_status(
+                        txid,
+                        json!({
+                            "status": "microblock",
+                            "microblock_hash": format!("0x{}", microblock_hash),
+                            "parent_index_block_hash": format!("0x{}", parent_index_block_hash),
+                        }),
+                    );
+                }
+            }
+            WebSocketEvent::MempoolTxs(txids) => {
+                for txid in txids.iter() {
+                    if self.is_subscribed(&WebSocketTopic::Mempool) {
+                        self.push_event(
+                            WebSocketTopic::Mempool.name(),
+                            json!({ "txid": format!("0x{}", txid) }),
+                        );
+                    }
+                    self.push_tx_status(txid, json!({ "status": "pending" }));
+                }
+            }
+            WebSocketEvent::MempoolTxsDropped(txids, reason) => {
+                for txid in txids.iter() {
+                    self.push_tx_status(txid, json!({ "status": "dropped", "reason": reason }));
+                }
+            }
+            WebSocketEvent::Reorg(reorg) => {
+                if self.is_subscribed(&WebSocketTopic::Reorgs) {
+                    self.push_event(
+                        WebSocketTopic::Reorgs.name(),
+                        json!({
+                            "orphaned_tip": format!("0x{}", reorg.orphaned_tip),
+                            "orphaned_tip_height": reorg.orphaned_tip_height,
+                            "new_tip": format!("0x{}", reorg.new_tip),
+                            "new_tip_height": reorg.new_tip_height,
+                            "common_ancestor": format!("0x{}", reorg.common_ancestor),
+                            "common_ancestor_height": reorg.common_ancestor_height,
+                            "depth": reorg.depth,
+                            "burn_block_height": reorg.burn_block_height,
+                        }),
+                    );
+                }
+            }
+        }
+
+        if self.outbuf.len() > WEBSOCKET_MAX_OUTBOX_LEN {
+            debug!("WebSocket client is too slow; closing");
+            self.outbuf.clear();
+            self.close(1008);
+        }
+    }
+
+    /// Ping the client if we haven't sent it anything in `interval` seconds, so that both sides
+    /// can tell the connection is still alive.
+    pub fn ping_if_idle(&mut self, interval: u64) {
+        if !self.closing && self.last_send_time + interval < get_epoch_time_secs() {
+            self.push_frame(WebSocketFrame::new(WebSocketOpcode::Ping, vec![]));
+        }
+    }
+
+    /// Write as much buffered data as `fd` will take.
+    /// Returns the number of bytes written.
+    pub fn send<W: Write>(&mut self, fd: &mut W) -> Result<usize, net_error> {
+        let mut total_written = 0;
+        while total_written < self.outbuf.len() {
+            match fd.write(&self.outbuf[total_written..]) {
+                Ok(0) => break,
+                Ok(count) => total_written += count,
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || (cfg!(windows) && e.kind() == io::ErrorKind::TimedOut)
+                    {
+                        break;
+                    }
+                    return Err(net_error::WriteError(e));
+                }
+            }
+        }
+        self.outbuf.drain(0..total_written);
+        if total_written > 0 {
+            self.last_send_time = get_epoch_time_secs();
+        }
+        Ok(total_written)
+    }
+
+    /// Start closing the session with the given status code.  No more frames are read or
+    /// queued after this.
+    pub fn close(&mut self, status_code: u16) {
+        if !self.closing {
+            self.push_frame(WebSocketFrame::new(
+                WebSocketOpcode::Close,
+                status_code.to_be_bytes().to_vec(),
+            ));
+            self.closing = true;
+        }
+    }
+
+    fn push_tx_status(&mut self, txid: &Txid, mut status: serde_json::Value) {
+        if self.is_subscribed(&WebSocketTopic::Transaction(txid.clone())) {
+            status["txid"] = json!(format!("0x{}", txid));
+            self.push_event("tx", status);
+        }
+    }
+
+    fn push_event(&mut self, topic: &str, data: serde_json::Value) {
+        self.push_json(&json!({ "op": "event", "topic": topic, "data": data }));
+    }
+
+    fn push_error(&mut self, reason: String) {
+        self.push_json(&json!({ "op": "error", "reason": reason }));
+    }
+
+    fn push_json(&mut self, message: &serde_json::Value) {
+        self.push_frame(WebSocketFrame::new(
+            WebSocketOpcode::Text,
+            message.to_string().into_bytes(),
+        ));
+    }
+
+    fn push_frame(&mut self, frame: WebSocketFrame) {
+        self.outbuf.extend_from_slice(&frame.encode());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn client_text(message: &str) -> Vec<u8> {
+        WebSocketFrame::new(WebSocketOpcode::Text, message.as_bytes().to_vec())
+            .encode_masked([0x12, 0x34, 0x56, 0x78])
+    }
+
+    /// Decode the server frames a session has sent, as JSON
+    fn sent_messages(session: &mut WebSocketSession) -> Vec<serde_json::Value> {
+        let mut bytes = vec![];
+        session.send(&mut bytes).unwrap();
+        let mut messages = vec![];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (payload_start, len) = match bytes[offset + 1] {
+                126 => (
+                    offset + 4,
+                    u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize,
+                ),
+                len => {
+                    assert!(len < 126);
+                    (offset + 2, len as usize)
+                }
+            };
+            let frame = WebSocketFrame {
+                fin: true,
+                opcode: WebSocketOpcode::from_u8(bytes[offset] & 0x0f).unwrap(),
+                payload: bytes[payload_start..payload_start + len].to_vec(),
+            };
+            if frame.opcode == WebSocketOpcode::Text {
+                messages.push(serde_json::from_slice(&frame.payload).unwrap());
+            } else {
+                messages.push(json!({ "opcode": frame.opcode.to_u8() }));
+            }
+            offset = payload_start + len;
+        }
+        messages
+    }
+
+    #[test]
+    fn test_websocket_accept_key() {
+        // example from RFC 6455, section 1.3
+        assert_eq!(
+            websocket_accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_websocket_frame_codec() {
+        for len in [0, 125, 126, 1000, WEBSOCKET_MAX_MESSAGE_LEN].iter() {
+            let frame = WebSocketFrame::new(WebSocketOpcode::Binary, vec![0xab; *len]);
+            let bytes = frame.encode_masked([1, 2, 3, 4]);
+            assert_eq!(
+                WebSocketFrame::decode(&bytes).unwrap(),
+                Some((frame, bytes.len()))
+            );
+            // partial frames need more data
+            assert_eq!(
+                WebSocketFrame::decode(&bytes[0..bytes.len() - 1]).unwrap(),
+                None
+            );
+        }
+
+        // unmasked client frames and oversized frames are rejected
+        let frame = WebSocketFrame::new(WebSocketOpcode::Text, vec![0x61; 10]);
+        assert!(WebSocketFrame::decode(&frame.encode()).is_err());
+        let frame = WebSocketFrame::new(WebSocketOpcode::Text, vec![0x61; 5000]);
+        assert!(WebSocketFrame::decode(&frame.encode_masked([1, 2, 3, 4])).is_err());
+    }
+
+    #[test]
+    fn test_websocket_session_subscriptions() {
+        let mut session = WebSocketSession::new();
+        let txid = Txid([0x11; 32]);
+
+        let mut input = client_text(r#"{"op": "subscribe", "topic": "block"}"#);
+        input.append(&mut client_text(&format!(
+            r#"{{"op": "subscribe", "topic": "tx", "txid": "0x{}"}}"#,
+            &txid
+        )));
+        input.append(&mut client_text(r#"{"op": "subscribe", "topic": "nope"}"#));
+        session.recv(&mut &input[..]).unwrap_err();
+
+        let messages = sent_messages(&mut session);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["op"], "subscribed");
+        assert_eq!(messages[0]["topic"], "block");
+        assert_eq!(messages[1]["op"], "subscribed");
+        assert_eq!(messages[1]["txid"], format!("0x{}", &txid));
+        assert_eq!(messages[2]["op"], "error");
+
+        // not subscribed to the mempool, but watching the transaction
+        session.publish(&WebSocketEvent::MempoolTxs(vec![
+            txid.clone(),
+            Txid([0x22; 32]),
+        ]));
+        let messages = sent_messages(&mut session);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["topic"], "tx");
+        assert_eq!(messages[0]["data"]["status"], "pending");
+
+        session.publish(&WebSocketEvent::Block {
+            index_block_hash: StacksBlockId([0x33; 32]),
+            block_hash: BlockHeaderHash([0x44; 32]),
+            consensus_hash: ConsensusHash([0x55; 20]),
+            parent_index_block_hash: StacksBlockId([0x66; 32]),
+            height: 7,
+            txids: vec![txid.clone()],
+        });
+        let messages = sent_messages(&mut session);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["topic"], "block");
+        assert_eq!(messages[0]["data"]["block_height"], 7);
+        assert_eq!(messages[1]["topic"], "tx");
+        assert_eq!(messages[1]["data"]["status"], "anchored");
+
+        // once unsubscribed, nothing more is sent
+        let input = client_text(r#"{"op": "unsubscribe", "topic": "block"}"#);
+        session.recv(&mut &input[..]).unwrap_err();
+        assert_eq!(sent_messages(&mut session)[0]["op"], "unsubscribed");
+        session.publish(&WebSocketEvent::Block {
+            index_block_hash: StacksBlockId([0x33; 32]),
+            block_hash: BlockHeaderHash([0x44; 32]),
+            consensus_hash: ConsensusHash([0x55; 20]),
+            parent_index_block_hash: StacksBlockId([0x66; 32]),
+            height: 8,
+            txids: vec![],
+        });
+        assert!(sent_messages(&mut session).is_empty());
+
+        // a close frame is answered with a close frame, and ends the session
+        let input = WebSocketFrame::new(WebSocketOpcode::Close, vec![0x03, 0xe8])
+            .encode_masked([9, 9, 9, 9]);
+        session.recv(&mut &input[..]).unwrap_err();
+        assert!(!session.is_closed());
+        assert_eq!(sent_messages(&mut session)[0]["opcode"], 0x8);
+        assert!(session.is_closed());
+    }
+
+    #[test]
+    fn test_websocket_session_reorgs() {
+        let mut session = WebSocketSession::new();
+        let reorg = RPCChainReorgData {
+            orphaned_tip: StacksBlockId([0x11; 32]),
+            orphaned_tip_height: 10,
+            new_tip: StacksBlockId([0x22; 32]),
+            new_tip_height: 11,
+            common_ancestor: StacksBlockId([0x33; 32]),
+            common_ancestor_height: 8,
+            depth: 2,
+            burn_block_height: 100,
+        };
+
+        // not subscribed yet
+        session.publish(&WebSocketEvent::Reorg(reorg.clone()));
+        assert!(sent_messages(&mut session).is_empty());
+
+        let input = client_text(r#"{"op": "subscribe", "topic": "reorg"}"#);
+        session.recv(&mut &input[..]).unwrap_err();
+        assert_eq!(sent_messages(&mut session)[0]["topic"], "reorg");
+
+        session.publish(&WebSocketEvent::Reorg(reorg));
+        let messages = sent_messages(&mut session);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["op"], "event");
+        assert_eq!(messages[0]["topic"], "reorg");
+        assert_eq!(
+            messages[0]["data"]["orphaned_tip"],
+            format!("0x{}", StacksBlockId([0x11; 32]))
+        );
+        assert_eq!(
+            messages[0]["data"]["common_ancestor"],
+            format!("0x{}", StacksBlockId([0x33; 32]))
+        );
+        assert_eq!(messages[0]["data"]["depth"], 2);
+    }
+}
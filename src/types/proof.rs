@@ -18,7 +18,7 @@ impl Default for TrieHash {
 
 pub const TRIEHASH_ENCODED_SIZE: usize = 32;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TrieMerkleProof<T: ClarityMarfTrieId>(pub Vec<TrieMerkleProofType<T>>);
 
 pub trait ClarityMarfTrieId:
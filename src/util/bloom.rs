@@ -18,6 +18,7 @@ use std::cmp;
 use std::collections::HashMap;
 use std::hash::Hasher;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::mem;
 
 use util::hash::Sha512Trunc256Sum;
 
@@ -341,6 +342,55 @@ impl StacksMessageCodec for BloomFilter<BloomNodeHasher> {
     }
 }
 
+/// In-memory bloom filter that forgets old items.  It keeps two generations of `BloomFilter`s:
+/// new items go into the current generation, and once it has taken `max_items` new items, the
+/// previous generation is discarded and the current one takes its place.  The filter therefore
+/// remembers at least the last `max_items` items (and at most the last `2 * max_items`), and its
+/// false positive rate is at most about twice the given error rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollingBloomFilter<H: BloomHash + Clone> {
+    error_rate: f64,
+    max_items: u32,
+    num_current: u32,
+    current: BloomFilter<H>,
+    previous: BloomFilter<H>,
+}
+
+impl<H: BloomHash + Clone> RollingBloomFilter<H> {
+    /// Make a new rolling bloom filter with a given error rate and generation size
+    pub fn new(error_rate: f64, max_items: u32, hasher: H) -> RollingBloomFilter<H> {
+        RollingBloomFilter {
+            error_rate,
+            max_items,
+            num_current: 0,
+            current: BloomFilter::new(error_rate, max_items, hasher.clone()),
+            previous: BloomFilter::new(error_rate, max_items, hasher),
+        }
+    }
+
+    /// Add a raw item.  Returns true if the item was (likely) already present.
+    pub fn insert_raw(&mut self, item: &[u8]) -> bool {
+        if self.current.contains_raw(item) {
+            return true;
+        }
+        let present = self.previous.contains_raw(item);
+        if self.num_current >= self.max_items {
+            let fresh =
+                BloomFilter::new(self.error_rate, self.max_items, self.current.hasher.clone());
+            self.previous = mem::replace(&mut self.current, fresh);
+            self.num_current = 0;
+        }
+        self.current.insert_raw(item);
+        self.num_current += 1;
+        present
+    }
+
+    /// Test to see if a given item is likely present in either generation
+    pub fn contains_raw(&self, item: &[u8]) -> bool {
+        self.current.contains_raw(item) || self.previous.contains_raw(item)
+    }
+}
+
 /// Disk-backed counting bloom filter with a given set of hash functions.  Uses a sqlite3 blob of
 /// 32-bit bins to count things.  Meant to work alongside an existing database, in its own table
 /// (e.g. the mempool).
@@ -675,6 +725,56 @@ pub mod test {
         assert!(calculated_error_rate <= err_rate);
     }
 
+    #[test]
+    fn test_rolling_bloom_filter_forgets_old_items() {
+        let num_items = 1024;
+        let err_rate = 0.001;
+
+        let hasher = BloomNodeHasher::new(&[0u8; 32]);
+        let mut bf = RollingBloomFilter::new(err_rate, num_items, hasher);
+
+        let mut items = vec![];
+        for _ in 0..(3 * num_items) {
+            let mut random_data = [0u8; 32];
+            thread_rng().fill(&mut random_data[..]);
+            items.push(random_data);
+        }
+
+        // first generation
+        for item in items[0..(num_items as usize)].iter() {
+            bf.insert_raw(item);
+            assert!(bf.contains_raw(item));
+        }
+        assert!(bf.insert_raw(&items[0]));
+
+        // second generation -- first is still remembered
+        for item in items[(num_items as usize)..(2 * num_items as usize)].iter() {
+            bf.insert_raw(item);
+        }
+        for item in items[0..(2 * num_items as usize)].iter() {
+            assert!(bf.contains_raw(item));
+        }
+
+        // third generation -- first is forgotten
+        for item in items[(2 * num_items as usize)..].iter() {
+            bf.insert_raw(item);
+        }
+        for item in items[(num_items as usize)..].iter() {
+            assert!(bf.contains_raw(item));
+        }
+
+        let fp_count = items[0..(num_items as usize)]
+            .iter()
+            .filter(|item| bf.contains_raw(*item))
+            .count();
+        let calculated_error_rate = (fp_count as f64) / (num_items as f64);
+        eprintln!(
+            "fp_count = {}, num_items = {}, err_rate = {}, calculated_error_rate = {}",
+            fp_count, num_items, err_rate, calculated_error_rate
+        );
+        assert!(calculated_error_rate <= 2.0 * err_rate + 0.01);
+    }
+
     #[test]
     fn test_bloom_counter_has_all_inserted_items_with_error_rate() {
         let num_items = 8192;
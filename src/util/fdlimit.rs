@@ -0,0 +1,209 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::io;
+
+/// Directory listing this process's open file descriptors, if the platform has one
+#[cfg(target_os = "linux")]
+const FD_DIR: Option<&str> = Some("/proc/self/fd");
+#[cfg(target_os = "macos")]
+const FD_DIR: Option<&str> = Some("/dev/fd");
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const FD_DIR: Option<&str> = None;
+
+/// Snapshot of this process's open file descriptors
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FileDescriptorUsage {
+    /// total number of open descriptors
+    pub total: u64,
+    /// how many are sockets
+    pub sockets: u64,
+    /// how many are SQLite databases, journals, and WALs
+    pub databases: u64,
+}
+
+impl FileDescriptorUsage {
+    /// Everything that isn't a socket or a database (log files, MARF blobs, pipes, etc.)
+    pub fn other(&self) -> u64 {
+        self.total
+            .saturating_sub(self.sockets)
+            .saturating_sub(self.databases)
+    }
+}
+
+fn is_database_path(path: &str) -> bool {
+    path.ends_with(".sqlite")
+        || path.ends_with(".sqlite-journal")
+        || path.ends_with(".sqlite-wal")
+        || path.ends_with(".sqlite-shm")
+        || path.ends_with(".db")
+        || path.ends_with(".db-journal")
+        || path.ends_with(".db-wal")
+        || path.ends_with(".db-shm")
+}
+
+/// Count and classify this process's open file descriptors.
+/// Returns None if the platform doesn't expose them.
+pub fn get_open_files() -> Option<FileDescriptorUsage> {
+    let dir = FD_DIR?;
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Failed to list open file descriptors in {}: {:?}", dir, &e);
+            return None;
+        }
+    };
+
+    let mut usage = FileDescriptorUsage::default();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => {
+                // closed while we were listing
+                continue;
+            }
+        };
+        usage.total += 1;
+
+        let target = match fs::read_link(entry.path()) {
+            Ok(target) => target,
+            Err(_) => {
+                continue;
+            }
+        };
+        let target = target.to_string_lossy();
+        if target.starts_with("socket:") {
+            usage.sockets += 1;
+        } else if is_database_path(&target) {
+            usage.databases += 1;
+        }
+    }
+
+    // the directory listing itself holds a descriptor while we read it
+    usage.total = usage.total.saturating_sub(1);
+    Some(usage)
+}
+
+/// Get this process's soft and hard limits on open file descriptors
+#[cfg(unix)]
+pub fn get_open_files_limit() -> Result<(u64, u64), io::Error> {
+    let mut rlim = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlim) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((rlim.rlim_cur, rlim.rlim_max))
+}
+
+#[cfg(not(unix))]
+pub fn get_open_files_limit() -> Result<(u64, u64), io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "open file limits are not supported on this platform",
+    ))
+}
+
+/// Raise this process's soft limit on open file descriptors to `target`, or as high as the hard
+/// limit allows.  The soft limit is never lowered.  Returns the new soft limit.
+#[cfg(unix)]
+pub fn raise_open_files_limit(target: u64) -> Result<u64, io::Error> {
+    let (soft, hard) = get_open_files_limit()?;
+    let new_soft = target.min(hard);
+    if new_soft <= soft {
+        return Ok(soft);
+    }
+
+    let rlim = libc::rlimit {
+        rlim_cur: new_soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &rlim) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(new_soft)
+}
+
+#[cfg(not(unix))]
+pub fn raise_open_files_limit(_target: u64) -> Result<u64, io::Error> {
+    get_open_files_limit().map(|(soft, _)| soft)
+}
+
+/// Did this I/O error happen because we (or the system) ran out of file descriptors?
+pub fn is_out_of_files(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(e.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use std::net::TcpListener;
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn test_get_open_files() {
+        let path = "/tmp/stacks-test-fdlimit.sqlite";
+        let _file = File::create(path).unwrap();
+        let _listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let usage = get_open_files().unwrap();
+
+        // other tests run concurrently, so only check lower bounds
+        assert!(usage.total >= 2);
+        assert!(usage.sockets >= 1);
+        if cfg!(target_os = "linux") {
+            assert!(usage.databases >= 1);
+        }
+        assert!(usage.other() <= usage.total);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_open_files_limit() {
+        let (soft, hard) = get_open_files_limit().unwrap();
+        assert!(soft <= hard);
+
+        // never lowers the limit
+        assert_eq!(raise_open_files_limit(0).unwrap(), soft);
+
+        let raised = raise_open_files_limit(hard).unwrap();
+        assert!(raised >= soft);
+        assert!(raised <= hard);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_out_of_files() {
+        assert!(is_out_of_files(&io::Error::from_raw_os_error(libc::EMFILE)));
+        assert!(is_out_of_files(&io::Error::from_raw_os_error(libc::ENFILE)));
+        assert!(!is_out_of_files(&io::Error::from_raw_os_error(
+            libc::ECONNREFUSED
+        )));
+    }
+}
@@ -23,6 +23,7 @@ pub mod db;
 
 pub mod bloom;
 pub mod boot;
+pub mod fdlimit;
 pub mod hash;
 pub mod pair;
 pub mod pipe;
@@ -16,8 +16,10 @@
 
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+use util::hash::Sha512Trunc256Sum;
 use vm::analysis::errors::{CheckError, CheckErrors, CheckResult};
 use vm::analysis::type_checker::ContractAnalysis;
+use vm::analysis::types::ContractStorageLayout;
 use vm::database::{
     ClarityBackingStore, ClarityDeserializable, ClaritySerializable, RollbackWrapper,
 };
@@ -73,7 +75,6 @@ impl<'a> AnalysisDatabase<'a> {
     //    even if the contract isn't published.
     #[cfg(test)]
     pub fn test_insert_contract_hash(&mut self, contract_identifier: &QualifiedContractIdentifier) {
-        use util::hash::Sha512Trunc256Sum;
         self.store
             .prepare_for_contract_metadata(contract_identifier, Sha512Trunc256Sum([0; 32]));
     }
@@ -95,6 +96,18 @@ impl<'a> AnalysisDatabase<'a> {
             .map(|x| ContractAnalysis::deserialize(&x))
     }
 
+    /// Hash of the stored analysis of a contract, which changes whenever anything another
+    /// contract's analysis could read from it does.
+    pub fn get_contract_analysis_hash(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+    ) -> Option<Sha512Trunc256Sum> {
+        self.store
+            .get_metadata(contract_identifier, AnalysisDatabase::storage_key())
+            .ok()?
+            .map(|x| Sha512Trunc256Sum::from_data(x.as_bytes()))
+    }
+
     pub fn insert_contract(
         &mut self,
         contract_identifier: &QualifiedContractIdentifier,
@@ -179,6 +192,16 @@ impl<'a> AnalysisDatabase<'a> {
         Ok(map_type.clone())
     }
 
+    pub fn get_storage_layout(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+    ) -> CheckResult<ContractStorageLayout> {
+        let contract = self
+            .load_contract(contract_identifier)
+            .ok_or(CheckErrors::NoSuchContract(contract_identifier.to_string()))?;
+        Ok(contract.get_storage_layout())
+    }
+
     pub fn destroy(self) -> RollbackWrapper<'a> {
         self.store
     }
@@ -172,7 +172,10 @@ impl ArithmeticOnlyChecker {
                 return Err(Error::FunctionNotPermitted(function));
             }
             Append | Concat | AsMaxLen | ContractOf | PrincipalOf | ListCons | Print
-            | AsContract | ElementAt | IndexOf | Map | Filter | Fold => {
+            | AsContract | ElementAt | IndexOf | Map | Filter | Fold | ImplementsTrait
+            | GetPublicFunctions | BuffToIntLe | BuffToIntBe | BuffToUIntLe | BuffToUIntBe
+            | IntToBuffLe | IntToBuffBe | UIntToBuffLe | UIntToBuffBe | StringToInt
+            | StringToUInt | Slice => {
                 return Err(Error::FunctionNotPermitted(function));
             }
             Sha512 | Sha512Trunc256 | Secp256k1Recover | Secp256k1Verify | Hash160 | Sha256
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use chainstate::stacks::boot::BOOT_CODE_COSTS;
+use core::StacksEpochId;
 use vm::analysis::{
     arithmetic_checker::ArithmeticOnlyChecker, arithmetic_checker::Error,
     arithmetic_checker::Error::*, mem_type_check, ContractAnalysis,
@@ -34,6 +35,7 @@ fn arithmetic_check(contract: &str) -> Result<(), Error> {
         contract_identifier,
         expressions,
         LimitedCostTracker::new_free(),
+        StacksEpochId::latest(),
     );
 
     ArithmeticOnlyChecker::run(&analysis)
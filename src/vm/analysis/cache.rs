@@ -0,0 +1,272 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A content-addressed cache of contract analyses, so that publishing the same source many times
+//! (e.g., token clones) only type-checks it once.
+//!
+//! The analysis of a contract is a function of its expanded AST, the stored analyses of the
+//! contracts it refers to, the epoch, and the cost functions charged for it.  The expanded AST is
+//! in turn a function of the source and the issuer (which `.contract` references are resolved
+//! against), as long as the contract never refers to itself.  So entries are keyed by the
+//! epoch, cost model, issuer and source, and only reused if every contract the cached analysis
+//! read still has the same stored analysis.  A reused analysis charges the cost the original
+//! analysis was charged, so the cache never changes what a block costs.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use util::hash::Sha512Trunc256Sum;
+use vm::analysis::dependency_graph::ContractDependencies;
+use vm::analysis::{run_analysis, AnalysisDatabase, CheckError, ContractAnalysis};
+use vm::costs::{CostTracker, ExecutionCost, LimitedCostTracker};
+use vm::representations::{SymbolicExpression, SymbolicExpressionType, TraitDefinition};
+use vm::types::{PrincipalData, QualifiedContractIdentifier, Value};
+
+use crate::core::StacksEpochId;
+
+struct CachedAnalysis {
+    /// the analysis, without its expressions and cost tracker
+    analysis: ContractAnalysis,
+    /// the contracts the analysis read, and the hashes of their stored analyses at the time
+    dependencies: Vec<(QualifiedContractIdentifier, Sha512Trunc256Sum)>,
+    /// the cost charged for running the analysis
+    cost: ExecutionCost,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    analyses: HashMap<Sha512Trunc256Sum, CachedAnalysis>,
+    /// keys in insertion order, for eviction
+    order: VecDeque<Sha512Trunc256Sum>,
+    hits: u64,
+}
+
+pub struct AnalysisCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+fn refers_to(expr: &SymbolicExpression, contract_identifier: &QualifiedContractIdentifier) -> bool {
+    use self::SymbolicExpressionType::*;
+    match expr.expr {
+        AtomValue(Value::Principal(PrincipalData::Contract(ref id)))
+        | LiteralValue(Value::Principal(PrincipalData::Contract(ref id))) => {
+            id == contract_identifier
+        }
+        Field(ref trait_identifier)
+        | TraitReference(_, TraitDefinition::Defined(ref trait_identifier))
+        | TraitReference(_, TraitDefinition::Imported(ref trait_identifier)) => {
+            trait_identifier.contract_identifier == *contract_identifier
+        }
+        List(ref children) => children
+            .iter()
+            .any(|child| refers_to(child, contract_identifier)),
+        AtomValue(_) | LiteralValue(_) | Atom(_) => false,
+    }
+}
+
+impl AnalysisCache {
+    /// A cache holding at most `capacity` analyses
+    pub fn new(capacity: usize) -> AnalysisCache {
+        AnalysisCache {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    /// Maximum number of analyses held
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of analyses reused so far
+    pub fn hits(&self) -> u64 {
+        self.entries
+            .lock()
+            .expect("FATAL: analysis cache lock poisoned")
+            .hits
+    }
+
+    fn cache_key(
+        contract_identifier: &QualifiedContractIdentifier,
+        source: &str,
+        cost_model: &str,
+        epoch: StacksEpochId,
+    ) -> Sha512Trunc256Sum {
+        let mut preimage = vec![];
+        preimage.extend_from_slice(&(epoch as u32).to_be_bytes());
+        for part in [cost_model, &contract_identifier.issuer.to_string(), source].iter() {
+            preimage.extend_from_slice(&(part.len() as u64).to_be_bytes());
+            preimage.extend_from_slice(part.as_bytes());
+        }
+        Sha512Trunc256Sum::from_data(&preimage)
+    }
+
+    fn dependencies_of(
+        contract_identifier: &QualifiedContractIdentifier,
+        expressions: &[SymbolicExpression],
+        analysis_db: &mut AnalysisDatabase,
+    ) -> Option<Vec<(QualifiedContractIdentifier, Sha512Trunc256Sum)>> {
+        ContractDependencies::from_expressions(contract_identifier, expressions)
+            .depends_on()
+            .into_iter()
+            .map(|dependency| {
+                let hash = analysis_db.get_contract_analysis_hash(&dependency)?;
+                Some((dependency, hash))
+            })
+            .collect()
+    }
+
+    /// Run the analysis passes on `expressions`, the expanded AST of `source`, or reuse the
+    /// analysis of an identical contract.  Like `run_analysis`, but never saves the analysis.
+    pub fn run_analysis(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+        source: &str,
+        expressions: &mut [SymbolicExpression],
+        analysis_db: &mut AnalysisDatabase,
+        mut cost_tracker: LimitedCostTracker,
+        epoch: StacksEpochId,
+    ) -> Result<ContractAnalysis, (CheckError, LimitedCostTracker)> {
+        let cost_model = match cost_tracker.default_cost_model() {
+            Some(cost_model) => cost_model,
+            None => {
+                return run_analysis(
+                    contract_identifier,
+                    expressions,
+                    analysis_db,
+                    false,
+                    cost_tracker,
+                    epoch,
+                )
+            }
+        };
+        if expressions
+            .iter()
+            .any(|expr| refers_to(expr, contract_identifier))
+        {
+            return run_analysis(
+                contract_identifier,
+                expressions,
+                analysis_db,
+                false,
+                cost_tracker,
+                epoch,
+            );
+        }
+
+        let key = AnalysisCache::cache_key(contract_identifier, source, &cost_model, epoch);
+        if let Some(mut analysis) = self.try_reuse(
+            &key,
+            contract_identifier,
+            expressions,
+            analysis_db,
+            &mut cost_tracker,
+        ) {
+            analysis.replace_contract_cost_tracker(cost_tracker);
+            return Ok(analysis);
+        }
+
+        let cost_before = cost_tracker.get_total();
+        let mut analysis = run_analysis(
+            contract_identifier,
+            expressions,
+            analysis_db,
+            false,
+            cost_tracker,
+            epoch,
+        )?;
+
+        let mut cost = analysis
+            .cost_track
+            .as_ref()
+            .expect("BUG: contract analysis lost its cost tracker")
+            .get_total();
+        let dependencies =
+            AnalysisCache::dependencies_of(contract_identifier, expressions, analysis_db);
+        if let (Ok(()), Some(dependencies)) = (cost.sub(&cost_before), dependencies) {
+            let cost_tracker = analysis.take_contract_cost_tracker();
+            let expressions = std::mem::take(&mut analysis.expressions);
+            self.insert(
+                key,
+                CachedAnalysis {
+                    analysis: analysis.clone(),
+                    dependencies,
+                    cost,
+                },
+            );
+            analysis.expressions = expressions;
+            analysis.replace_contract_cost_tracker(cost_tracker);
+        }
+        Ok(analysis)
+    }
+
+    fn try_reuse(
+        &self,
+        key: &Sha512Trunc256Sum,
+        contract_identifier: &QualifiedContractIdentifier,
+        expressions: &[SymbolicExpression],
+        analysis_db: &mut AnalysisDatabase,
+        cost_tracker: &mut LimitedCostTracker,
+    ) -> Option<ContractAnalysis> {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("FATAL: analysis cache lock poisoned");
+        let cached = entries.analyses.get(key)?;
+
+        for (dependency, hash) in cached.dependencies.iter() {
+            if analysis_db.get_contract_analysis_hash(dependency).as_ref() != Some(hash) {
+                return None;
+            }
+        }
+
+        // if the original cost does not fit, run the analysis to fail at the same point it would
+        let mut total = cost_tracker.get_total();
+        if total.add(&cached.cost).is_err() || total.exceeds(&cost_tracker.get_limit()) {
+            return None;
+        }
+        cost_tracker
+            .add_cost(cached.cost.clone())
+            .expect("BUG: failed to charge a cost that fits the budget");
+
+        let mut analysis = cached.analysis.clone();
+        analysis.contract_identifier = contract_identifier.clone();
+        analysis.expressions = expressions.to_vec();
+        entries.hits += 1;
+
+        debug!("Reused cached analysis for {}", contract_identifier);
+        Some(analysis)
+    }
+
+    fn insert(&self, key: Sha512Trunc256Sum, cached: CachedAnalysis) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("FATAL: analysis cache lock poisoned");
+        if entries.analyses.insert(key, cached).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.order.len() > self.capacity {
+            if let Some(evicted) = entries.order.pop_front() {
+                entries.analyses.remove(&evicted);
+            }
+        }
+    }
+}
@@ -39,6 +39,7 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         cost_track: _,
         contract_interface: _,
         is_cost_contract_eligible: _,
+        epoch: _,
     } = contract_analysis;
 
     contract_interface
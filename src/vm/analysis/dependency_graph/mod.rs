@@ -0,0 +1,291 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use vm::analysis::types::ContractAnalysis;
+use vm::ast::build_ast;
+use vm::database::ClarityDatabase;
+use vm::functions::define::DefineFunctionsParsed;
+use vm::functions::NativeFunctions;
+use vm::representations::{ClarityName, SymbolicExpression};
+use vm::types::{PrincipalData, QualifiedContractIdentifier, TraitIdentifier, Value};
+
+#[cfg(test)]
+mod tests;
+
+///
+/// A static-analysis pass that collects the contracts a contract refers to:
+///  the targets of its `contract-call?`s, and the contracts defining
+///  the traits it implements (`impl-trait`) or uses (`use-trait`).
+/// It never fails, and it is not run as part of contract deployment.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractDependencies {
+    pub contract_identifier: QualifiedContractIdentifier,
+    /// Functions called on other contracts by name
+    pub contract_calls: BTreeSet<ContractCall>,
+    /// Functions called through trait references.  These can reach any contract implementing
+    /// the trait, so they are not edges of the graph.
+    pub dynamic_calls: BTreeSet<ClarityName>,
+    pub implemented_traits: BTreeSet<TraitIdentifier>,
+    pub used_traits: BTreeSet<TraitIdentifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ContractCall {
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub function_name: ClarityName,
+}
+
+impl ContractDependencies {
+    pub fn from_analysis(contract_analysis: &ContractAnalysis) -> ContractDependencies {
+        ContractDependencies::from_expressions(
+            &contract_analysis.contract_identifier,
+            &contract_analysis.expressions,
+        )
+    }
+
+    /// Collect the dependencies of a contract from its expanded AST (i.e., with sugared
+    /// contract identifiers already resolved)
+    pub fn from_expressions(
+        contract_identifier: &QualifiedContractIdentifier,
+        expressions: &[SymbolicExpression],
+    ) -> ContractDependencies {
+        let mut dependencies = ContractDependencies {
+            contract_identifier: contract_identifier.clone(),
+            contract_calls: BTreeSet::new(),
+            dynamic_calls: BTreeSet::new(),
+            implemented_traits: BTreeSet::new(),
+            used_traits: BTreeSet::new(),
+        };
+        for expr in expressions.iter() {
+            match DefineFunctionsParsed::try_parse(expr) {
+                Ok(Some(DefineFunctionsParsed::ImplTrait { trait_identifier })) => {
+                    dependencies
+                        .implemented_traits
+                        .insert(trait_identifier.clone());
+                }
+                Ok(Some(DefineFunctionsParsed::UseTrait {
+                    trait_identifier, ..
+                })) => {
+                    dependencies.used_traits.insert(trait_identifier.clone());
+                }
+                _ => dependencies.collect_calls(expr),
+            }
+        }
+        dependencies
+    }
+
+    fn collect_calls(&mut self, expr: &SymbolicExpression) {
+        let list = match expr.match_list() {
+            Some(list) => list,
+            None => return,
+        };
+        if let [function, target, function_name, ..] = list {
+            let is_contract_call = function
+                .match_atom()
+                .and_then(|name| NativeFunctions::lookup_by_name(name))
+                == Some(NativeFunctions::ContractCall);
+            if let (true, Some(function_name)) = (is_contract_call, function_name.match_atom()) {
+                match target
+                    .match_literal_value()
+                    .or_else(|| target.match_atom_value())
+                {
+                    Some(Value::Principal(PrincipalData::Contract(contract_identifier))) => {
+                        self.contract_calls.insert(ContractCall {
+                            contract_identifier: contract_identifier.clone(),
+                            function_name: function_name.clone(),
+                        });
+                    }
+                    _ => {
+                        self.dynamic_calls.insert(function_name.clone());
+                    }
+                }
+            }
+        }
+        for child in list.iter() {
+            self.collect_calls(child);
+        }
+    }
+
+    /// The contracts this contract refers to, other than itself
+    pub fn depends_on(&self) -> BTreeSet<QualifiedContractIdentifier> {
+        let called = self
+            .contract_calls
+            .iter()
+            .map(|call| &call.contract_identifier);
+        let traits = self
+            .implemented_traits
+            .iter()
+            .chain(self.used_traits.iter())
+            .map(|trait_identifier| &trait_identifier.contract_identifier);
+        called
+            .chain(traits)
+            .filter(|contract_identifier| **contract_identifier != self.contract_identifier)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The dependencies of a set of contracts, and of everything they (transitively) depend on
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencyGraph {
+    pub contracts: BTreeMap<QualifiedContractIdentifier, ContractDependencies>,
+    /// Contracts that are depended on, but could not be loaded
+    pub missing: BTreeSet<QualifiedContractIdentifier>,
+}
+
+impl DependencyGraph {
+    /// Build the graph reachable from `roots`, loading each contract's dependencies with `load`
+    pub fn build<F>(roots: &[QualifiedContractIdentifier], mut load: F) -> DependencyGraph
+    where
+        F: FnMut(&QualifiedContractIdentifier) -> Option<ContractDependencies>,
+    {
+        let mut graph = DependencyGraph {
+            contracts: BTreeMap::new(),
+            missing: BTreeSet::new(),
+        };
+        let mut queue: VecDeque<QualifiedContractIdentifier> = roots.iter().cloned().collect();
+        while let Some(contract_identifier) = queue.pop_front() {
+            if graph.contracts.contains_key(&contract_identifier)
+                || graph.missing.contains(&contract_identifier)
+            {
+                continue;
+            }
+            match load(&contract_identifier) {
+                Some(dependencies) => {
+                    queue.extend(dependencies.depends_on());
+                    graph.contracts.insert(contract_identifier, dependencies);
+                }
+                None => {
+                    graph.missing.insert(contract_identifier);
+                }
+            }
+        }
+        graph
+    }
+
+    /// Build the graph reachable from `roots` out of the contracts stored in `db`
+    pub fn from_clarity_db(
+        db: &mut ClarityDatabase,
+        roots: &[QualifiedContractIdentifier],
+    ) -> DependencyGraph {
+        DependencyGraph::build(roots, |contract_identifier| {
+            let source = db.get_contract_src(contract_identifier)?;
+            let ast = build_ast(contract_identifier, &source, &mut ()).ok()?;
+            Some(ContractDependencies::from_expressions(
+                contract_identifier,
+                &ast.expressions,
+            ))
+        })
+    }
+
+    /// All contracts reachable from `contract_identifier`, not including itself unless it is
+    /// part of a cycle
+    pub fn transitive_dependencies(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+    ) -> BTreeSet<QualifiedContractIdentifier> {
+        let mut reached = BTreeSet::new();
+        let mut queue: VecDeque<QualifiedContractIdentifier> = self
+            .contracts
+            .get(contract_identifier)
+            .map(|dependencies| dependencies.depends_on().into_iter().collect())
+            .unwrap_or_default();
+        while let Some(next) = queue.pop_front() {
+            if reached.insert(next.clone()) {
+                if let Some(dependencies) = self.contracts.get(&next) {
+                    queue.extend(dependencies.depends_on());
+                }
+            }
+        }
+        reached
+    }
+
+    /// Find the cycles in the graph: every strongly-connected set of two or more contracts, in
+    /// sorted order
+    pub fn find_cycles(&self) -> Vec<Vec<QualifiedContractIdentifier>> {
+        // iterative Tarjan's algorithm, so that long dependency chains can't overflow the stack
+        let edges: BTreeMap<&QualifiedContractIdentifier, Vec<QualifiedContractIdentifier>> = self
+            .contracts
+            .iter()
+            .map(|(contract_identifier, dependencies)| {
+                let depends_on = dependencies
+                    .depends_on()
+                    .into_iter()
+                    .filter(|dependency| self.contracts.contains_key(dependency))
+                    .collect();
+                (contract_identifier, depends_on)
+            })
+            .collect();
+
+        let mut index: BTreeMap<&QualifiedContractIdentifier, (usize, usize)> = BTreeMap::new();
+        let mut stack: Vec<&QualifiedContractIdentifier> = vec![];
+        let mut on_stack: BTreeSet<&QualifiedContractIdentifier> = BTreeSet::new();
+        let mut cycles = vec![];
+
+        for root in edges.keys() {
+            if index.contains_key(root) {
+                continue;
+            }
+            // (node, position of the next edge to visit)
+            let mut work: Vec<(&QualifiedContractIdentifier, usize)> = vec![(root, 0)];
+            while let Some((node, edge)) = work.pop() {
+                if edge == 0 {
+                    let next_index = index.len();
+                    index.insert(node, (next_index, next_index));
+                    stack.push(node);
+                    on_stack.insert(node);
+                }
+                if let Some(next) = edges[node].get(edge) {
+                    work.push((node, edge + 1));
+                    if !index.contains_key(next) {
+                        work.push((next, 0));
+                    } else if on_stack.contains(next) {
+                        let next_index = index[next].0;
+                        let entry = index.get_mut(node).unwrap();
+                        entry.1 = entry.1.min(next_index);
+                    }
+                    continue;
+                }
+
+                // all of node's edges are visited
+                let (node_index, node_lowlink) = index[node];
+                if let Some((parent, _)) = work.last() {
+                    let entry = index.get_mut(parent).unwrap();
+                    entry.1 = entry.1.min(node_lowlink);
+                }
+                if node_index == node_lowlink {
+                    let mut component = vec![];
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(member);
+                        component.push(member.clone());
+                        if member == node {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 {
+                        component.sort();
+                        cycles.push(component);
+                    }
+                }
+            }
+        }
+        cycles.sort();
+        cycles
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use crate::clarity_vm::database::MemoryBackingStore;
+use vm::analysis::dependency_graph::{ContractCall, ContractDependencies, DependencyGraph};
+use vm::ast::parse;
+use vm::contexts::OwnedEnvironment;
+use vm::types::{QualifiedContractIdentifier, TraitIdentifier};
+
+const TRAIT_CONTRACT: &str =
+    "(define-trait token ((transfer (uint principal) (response bool uint))))";
+
+const IMPL_CONTRACT: &str = "(impl-trait .defs.token)
+(define-public (transfer (amount uint) (to principal)) (ok true))";
+
+const CALLER_CONTRACT: &str = "(use-trait token-trait .defs.token)
+(define-public (pay (token <token-trait>) (amount uint))
+  (let ((result (contract-call? token transfer amount tx-sender)))
+    (contract-call? .impl transfer amount tx-sender)))
+(define-read-only (other) (contract-call? 'S1G2081040G2081040G2081040G208105NK8PE5.other get-x))";
+
+fn contract(name: &str) -> QualifiedContractIdentifier {
+    QualifiedContractIdentifier::local(name).unwrap()
+}
+
+fn dependencies(name: &str, source: &str) -> ContractDependencies {
+    let contract_identifier = contract(name);
+    let expressions = parse(&contract_identifier, source).unwrap();
+    ContractDependencies::from_expressions(&contract_identifier, &expressions)
+}
+
+#[test]
+fn test_contract_dependencies() {
+    let token_trait = TraitIdentifier {
+        name: "token".into(),
+        contract_identifier: contract("defs"),
+    };
+
+    let deps = dependencies("caller", CALLER_CONTRACT);
+    assert_eq!(
+        deps.contract_calls.into_iter().collect::<Vec<_>>(),
+        vec![
+            ContractCall {
+                contract_identifier: contract("impl"),
+                function_name: "transfer".into(),
+            },
+            ContractCall {
+                contract_identifier: contract("other"),
+                function_name: "get-x".into(),
+            },
+        ]
+    );
+    assert_eq!(
+        deps.dynamic_calls.into_iter().collect::<Vec<_>>(),
+        vec!["transfer".into()]
+    );
+    assert!(deps.implemented_traits.is_empty());
+    assert_eq!(
+        deps.used_traits.into_iter().collect::<Vec<_>>(),
+        vec![token_trait.clone()]
+    );
+
+    let deps = dependencies("impl", IMPL_CONTRACT);
+    assert!(deps.contract_calls.is_empty());
+    assert_eq!(
+        deps.implemented_traits.iter().collect::<Vec<_>>(),
+        vec![&token_trait]
+    );
+    assert_eq!(
+        deps.depends_on().into_iter().collect::<Vec<_>>(),
+        vec![contract("defs")]
+    );
+
+    assert!(dependencies("defs", TRAIT_CONTRACT).depends_on().is_empty());
+}
+
+#[test]
+fn test_dependency_graph() {
+    let mut sources = HashMap::new();
+    sources.insert(contract("a"), "(define-public (f) (contract-call? .b f))");
+    sources.insert(contract("b"), "(define-public (f) (contract-call? .c f))");
+    sources.insert(
+        contract("c"),
+        "(define-public (f) (begin (try! (contract-call? .a f)) (contract-call? .e f)))",
+    );
+    sources.insert(contract("d"), "(define-public (f) (contract-call? .a f))");
+    sources.insert(
+        contract("x"),
+        "(define-public (f) (begin (try! (contract-call? .y f)) (contract-call? .x f)))",
+    );
+    sources.insert(contract("y"), "(define-public (f) (contract-call? .x f))");
+
+    let mut loaded = vec![];
+    let graph = DependencyGraph::build(&[contract("d"), contract("y")], |contract_identifier| {
+        loaded.push(contract_identifier.clone());
+        sources
+            .get(contract_identifier)
+            .map(|source| dependencies(&contract_identifier.name, source))
+    });
+
+    // every contract is loaded once
+    assert_eq!(loaded.len(), 7);
+    assert_eq!(
+        graph.contracts.keys().cloned().collect::<Vec<_>>(),
+        vec![
+            contract("a"),
+            contract("b"),
+            contract("c"),
+            contract("d"),
+            contract("x"),
+            contract("y"),
+        ]
+    );
+    assert_eq!(
+        graph.missing.iter().cloned().collect::<Vec<_>>(),
+        vec![contract("e")]
+    );
+
+    assert_eq!(
+        graph.find_cycles(),
+        vec![
+            vec![contract("a"), contract("b"), contract("c")],
+            vec![contract("x"), contract("y")],
+        ]
+    );
+    assert_eq!(
+        graph
+            .transitive_dependencies(&contract("d"))
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![contract("a"), contract("b"), contract("c"), contract("e")]
+    );
+    assert!(graph
+        .transitive_dependencies(&contract("a"))
+        .contains(&contract("a")));
+}
+
+#[test]
+fn test_dependency_graph_from_clarity_db() {
+    let mut marf = MemoryBackingStore::new();
+    {
+        let mut owned_env = OwnedEnvironment::new(marf.as_clarity_db());
+        for (name, source) in [
+            ("defs", TRAIT_CONTRACT),
+            ("impl", IMPL_CONTRACT),
+            ("caller", CALLER_CONTRACT),
+        ]
+        .iter()
+        {
+            owned_env
+                .initialize_contract(contract(name), source)
+                .unwrap();
+        }
+    }
+
+    let mut db = marf.as_clarity_db();
+    db.begin();
+    let graph = DependencyGraph::from_clarity_db(&mut db, &[contract("caller")]);
+    db.roll_back();
+
+    assert_eq!(
+        graph.contracts.keys().cloned().collect::<Vec<_>>(),
+        vec![contract("caller"), contract("defs"), contract("impl")]
+    );
+    assert_eq!(
+        graph.missing.iter().cloned().collect::<Vec<_>>(),
+        vec![contract("other")]
+    );
+    assert!(graph.find_cycles().is_empty());
+}
@@ -0,0 +1,547 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use vm::ast::build_ast;
+use vm::ast::errors::ParseResult;
+use vm::functions::define::DefineFunctionsParsed;
+use vm::functions::NativeFunctions;
+use vm::representations::{ClarityName, Span, SymbolicExpression};
+use vm::types::QualifiedContractIdentifier;
+use vm::variables::NativeVariables;
+
+#[cfg(test)]
+mod tests;
+
+define_named_enum!(LintRule {
+    UnusedLetBinding("unused-let-binding"),
+    ShadowedName("shadowed-name"),
+    UnwrapPanic("unwrap-panic"),
+    TxSenderAuthorization("tx-sender-authorization"),
+    TraitCallBeforeWrite("trait-call-before-write"),
+});
+
+impl LintRule {
+    /// The level of the rule when the config does not mention it.  Rules that are noisy on
+    /// correct code are opt-in.
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            LintRule::TraitCallBeforeWrite => LintLevel::Allow,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    /// The rule is not checked
+    Allow,
+    Warn,
+    /// Violations of the rule should fail the lint run
+    Deny,
+}
+
+/// The level of each lint rule.  Every rule defaults to `warn`, except the opt-in
+/// `trait-call-before-write`, which defaults to `allow`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintConfig {
+    levels: HashMap<LintRule, LintLevel>,
+}
+
+impl Default for LintConfig {
+    fn default() -> LintConfig {
+        LintConfig {
+            levels: LintRule::ALL
+                .iter()
+                .map(|rule| (*rule, rule.default_level()))
+                .collect(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Read a config of the form `{"unused-let-binding": "deny", "shadowed-name": "allow"}`.
+    /// Rules that are not mentioned keep their default level.
+    pub fn from_json(json: &str) -> Result<LintConfig, String> {
+        let levels: HashMap<String, LintLevel> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid lint config: {}", e))?;
+        let mut config = LintConfig::default();
+        for (rule_name, level) in levels.into_iter() {
+            let rule = LintRule::lookup_by_name(&rule_name)
+                .ok_or_else(|| format!("Unknown lint rule: {}", rule_name))?;
+            config.set_level(rule, level);
+        }
+        Ok(config)
+    }
+
+    pub fn level(&self, rule: LintRule) -> LintLevel {
+        self.levels
+            .get(&rule)
+            .cloned()
+            .unwrap_or(rule.default_level())
+    }
+
+    pub fn set_level(&mut self, rule: LintRule, level: LintLevel) {
+        self.levels.insert(rule, level);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub rule: LintRule,
+    pub level: LintLevel,
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level {
+            LintLevel::Deny => "error",
+            LintLevel::Warn | LintLevel::Allow => "warning",
+        };
+        write!(
+            f,
+            "{} (line {}, column {}): {} [{}]",
+            level, self.span.start_line, self.span.start_column, self.message, self.rule
+        )
+    }
+}
+
+/// Parse `source_code` and lint it
+pub fn lint_contract(
+    contract_identifier: &QualifiedContractIdentifier,
+    source_code: &str,
+    config: &LintConfig,
+) -> ParseResult<Vec<Lint>> {
+    let ast = build_ast(contract_identifier, source_code, &mut ())?;
+    Ok(Linter::run(&ast.expressions, config))
+}
+
+#[cfg(feature = "developer-mode")]
+fn span_of(expr: &SymbolicExpression) -> Span {
+    expr.span.clone()
+}
+
+#[cfg(not(feature = "developer-mode"))]
+fn span_of(_expr: &SymbolicExpression) -> Span {
+    Span::zero()
+}
+
+fn native_function_of(list: &[SymbolicExpression]) -> Option<NativeFunctions> {
+    list.first()
+        .and_then(|function| function.match_atom())
+        .and_then(|name| NativeFunctions::lookup_by_name(name))
+}
+
+fn binding_name_of(binding: &SymbolicExpression) -> Option<&ClarityName> {
+    binding.match_list()?.first()?.match_atom()
+}
+
+/// Whether `name` is referenced as a variable in `expr`, outside of any scope that rebinds it
+fn references(name: &str, expr: &SymbolicExpression) -> bool {
+    if let Some(atom) = expr.match_atom() {
+        return atom.as_str() == name;
+    }
+    let list = match expr.match_list() {
+        Some(list) => list,
+        None => return false,
+    };
+    let args = if list.first().and_then(|e| e.match_atom()).is_some() {
+        &list[1..]
+    } else {
+        list
+    };
+
+    match native_function_of(list) {
+        Some(NativeFunctions::Let) if !args.is_empty() => {
+            let bindings = args[0].match_list().unwrap_or(&[]);
+            let mut rebound = false;
+            for binding in bindings.iter() {
+                if let Some([_, value]) = binding.match_list() {
+                    if !rebound && references(name, value) {
+                        return true;
+                    }
+                }
+                rebound |= binding_name_of(binding).map(|n| n.as_str()) == Some(name);
+            }
+            !rebound && args[1..].iter().any(|body| references(name, body))
+        }
+        Some(NativeFunctions::Match) if args.len() == 4 || args.len() == 5 => {
+            // (match input some-name some-branch none-branch) or
+            // (match input ok-name ok-branch err-name err-branch)
+            let binds = |i: usize| args[i].match_atom().map(|n| n.as_str()) == Some(name);
+            references(name, &args[0])
+                || (!binds(1) && references(name, &args[2]))
+                || if args.len() == 4 {
+                    references(name, &args[3])
+                } else {
+                    !binds(3) && references(name, &args[4])
+                }
+        }
+        // tuple keys are names, not variables
+        Some(NativeFunctions::TupleCons) => args.iter().any(|pair| match pair.match_list() {
+            Some([_, value]) => references(name, value),
+            _ => false,
+        }),
+        Some(NativeFunctions::TupleGet) if args.len() == 2 => references(name, &args[1]),
+        _ => args.iter().any(|arg| references(name, arg)),
+    }
+}
+
+fn is_tx_sender(expr: &SymbolicExpression) -> bool {
+    expr.match_atom()
+        .and_then(|name| NativeVariables::lookup_by_name(name))
+        == Some(NativeVariables::TxSender)
+}
+
+/// Collect the `is-eq` comparisons against `tx-sender` in `expr`, skipping `as-contract`
+/// bodies, where `tx-sender` is the contract itself
+fn collect_tx_sender_checks<'a>(
+    expr: &'a SymbolicExpression,
+    checks: &mut Vec<&'a SymbolicExpression>,
+) {
+    let list = match expr.match_list() {
+        Some(list) => list,
+        None => return,
+    };
+    match native_function_of(list) {
+        Some(NativeFunctions::AsContract) => return,
+        Some(NativeFunctions::Equals) if list[1..].iter().any(is_tx_sender) => checks.push(expr),
+        _ => {}
+    }
+    for child in list.iter() {
+        collect_tx_sender_checks(child, checks);
+    }
+}
+
+/// Whether `function` writes to the calling contract's own data or tokens
+fn is_state_write(function: NativeFunctions) -> bool {
+    use vm::functions::NativeFunctions::*;
+    match function {
+        SetVar | SetEntry | InsertEntry | DeleteEntry | MintToken | MintAsset | TransferToken
+        | TransferAsset | BurnToken | BurnAsset => true,
+        _ => false,
+    }
+}
+
+/// Whether `expr` directly writes state, or calls one of the private functions in `writers`
+fn writes_state(expr: &SymbolicExpression, writers: &HashSet<&str>) -> bool {
+    let list = match expr.match_list() {
+        Some(list) => list,
+        None => return false,
+    };
+    let is_write = match native_function_of(list) {
+        Some(function) => is_state_write(function),
+        None => list
+            .first()
+            .and_then(|function| function.match_atom())
+            .map(|name| writers.contains(name.as_str()))
+            .unwrap_or(false),
+    };
+    is_write || list.iter().any(|child| writes_state(child, writers))
+}
+
+/// Walk `expr` in evaluation order (arguments before the expression itself), remembering the
+/// first `contract-call?` to a trait-typed principal, and collecting every state write that
+/// is evaluated after it.  Both branches of a conditional are treated as evaluated.
+fn collect_writes_after_trait_call<'a>(
+    expr: &'a SymbolicExpression,
+    writers: &HashSet<&str>,
+    trait_call: &mut Option<&'a SymbolicExpression>,
+    writes: &mut Vec<(&'a SymbolicExpression, &'a SymbolicExpression)>,
+) {
+    let list = match expr.match_list() {
+        Some(list) => list,
+        None => return,
+    };
+    for child in list.iter() {
+        collect_writes_after_trait_call(child, writers, trait_call, writes);
+    }
+
+    match native_function_of(list) {
+        // a literal contract principal is a static call; an atom names a trait-typed argument
+        Some(NativeFunctions::ContractCall) => {
+            if trait_call.is_none() && list.get(1).and_then(|c| c.match_atom()).is_some() {
+                *trait_call = Some(expr);
+            }
+        }
+        Some(function) if is_state_write(function) => {
+            if let Some(call) = trait_call {
+                writes.push((call, expr));
+            }
+        }
+        Some(_) => {}
+        None => {
+            let calls_writer = list
+                .first()
+                .and_then(|function| function.match_atom())
+                .map(|name| writers.contains(name.as_str()))
+                .unwrap_or(false);
+            if let (true, Some(call)) = (calls_writer, &trait_call) {
+                writes.push((call, expr));
+            }
+        }
+    }
+}
+
+///
+/// A static-analysis pass that reports code patterns which are legal, but often mistakes:
+///  let-bindings that are never used, local names shadowing top-level definitions,
+///  `unwrap-panic`s, authorization checks against `tx-sender` in functions
+///  that never look at `contract-caller`, and (opt-in) public functions that call into a
+///  trait-typed contract before they finish writing their own state.
+/// It runs on the expanded AST and is not run as part of contract deployment.
+///
+pub struct Linter<'a> {
+    config: &'a LintConfig,
+    /// Names defined at the top level of the contract, and what kind of definition each is
+    top_level_names: HashMap<&'a str, &'static str>,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn run(expressions: &'a [SymbolicExpression], config: &'a LintConfig) -> Vec<Lint> {
+        let mut linter = Linter {
+            config,
+            top_level_names: HashMap::new(),
+            lints: vec![],
+        };
+
+        let mut bodies = vec![];
+        let mut public_functions = vec![];
+        let mut private_functions = HashMap::new();
+        for expr in expressions.iter() {
+            use vm::functions::define::DefineFunctionsParsed::*;
+            match DefineFunctionsParsed::try_parse(expr) {
+                Ok(Some(Constant { name, value })) => {
+                    linter.top_level_names.insert(name, "constant");
+                    bodies.push((None, value));
+                }
+                Ok(Some(PersistedVariable { name, .. })) => {
+                    linter.top_level_names.insert(name, "data variable");
+                }
+                Ok(Some(Map { name, .. })) => {
+                    linter.top_level_names.insert(name, "map");
+                }
+                Ok(Some(BoundedFungibleToken { name, .. }))
+                | Ok(Some(UnboundedFungibleToken { name }))
+                | Ok(Some(NonFungibleToken { name, .. })) => {
+                    linter.top_level_names.insert(name, "token");
+                }
+                Ok(Some(PrivateFunction { signature, body })) => {
+                    if let Some(name) = signature.first().and_then(|name| name.match_atom()) {
+                        private_functions.insert(name.as_str(), body);
+                    }
+                    bodies.push((Some(signature), body));
+                }
+                Ok(Some(PublicFunction { signature, body })) => {
+                    public_functions.push(body);
+                    bodies.push((Some(signature), body));
+                }
+                Ok(Some(ReadOnlyFunction { signature, body })) => {
+                    bodies.push((Some(signature), body));
+                }
+                Ok(Some(Trait { .. })) | Ok(Some(UseTrait { .. })) | Ok(Some(ImplTrait { .. })) => {
+                }
+                // top-level expressions, and malformed definitions, which the analysis reports
+                Ok(None) | Err(_) => bodies.push((None, expr)),
+            }
+        }
+
+        for (signature, body) in bodies.into_iter() {
+            match signature {
+                Some(signature) => linter.check_function(signature, body),
+                None => linter.check_expression(body),
+            }
+        }
+        if linter.config.level(LintRule::TraitCallBeforeWrite) != LintLevel::Allow {
+            linter.check_trait_calls(&public_functions, &private_functions);
+        }
+        linter.lints
+    }
+
+    fn check_trait_calls(
+        &mut self,
+        public_functions: &[&SymbolicExpression],
+        private_functions: &HashMap<&str, &SymbolicExpression>,
+    ) {
+        // private functions which write state, directly or through other private functions
+        let mut writers = HashSet::new();
+        loop {
+            let new_writers: Vec<_> = private_functions
+                .iter()
+                .filter(|(name, body)| !writers.contains(*name) && writes_state(body, &writers))
+                .map(|(name, _)| *name)
+                .collect();
+            if new_writers.is_empty() {
+                break;
+            }
+            writers.extend(new_writers);
+        }
+
+        for body in public_functions.iter() {
+            let mut trait_call = None;
+            let mut writes = vec![];
+            collect_writes_after_trait_call(body, &writers, &mut trait_call, &mut writes);
+            for (call, write) in writes.into_iter() {
+                let target = call.match_list().unwrap()[1].match_atom().unwrap();
+                self.report(
+                    LintRule::TraitCallBeforeWrite,
+                    write,
+                    format!(
+                        "state is written after the `contract-call?` to trait-typed `{}` (line {}); \
+                         the called contract runs before this function finishes its own writes",
+                        target,
+                        span_of(call).start_line
+                    ),
+                );
+            }
+        }
+    }
+
+    fn report(&mut self, rule: LintRule, expr: &SymbolicExpression, message: String) {
+        let level = self.config.level(rule);
+        if level != LintLevel::Allow {
+            self.lints.push(Lint {
+                rule,
+                level,
+                message,
+                span: span_of(expr),
+            });
+        }
+    }
+
+    fn check_shadowing(&mut self, name: &str, expr: &SymbolicExpression) {
+        if let Some(kind) = self.top_level_names.get(name).cloned() {
+            self.report(
+                LintRule::ShadowedName,
+                expr,
+                format!("`{}` shadows the {} `{}`", name, kind, name),
+            );
+        }
+    }
+
+    fn check_function(&mut self, signature: &[SymbolicExpression], body: &SymbolicExpression) {
+        for argument in signature.iter().skip(1) {
+            if let Some(name) = binding_name_of(argument) {
+                self.check_shadowing(name, argument);
+            }
+        }
+
+        self.check_expression(body);
+
+        if !references(NativeVariables::ContractCaller.get_name_str(), body) {
+            let mut checks = vec![];
+            collect_tx_sender_checks(body, &mut checks);
+            for check in checks.into_iter() {
+                self.report(
+                    LintRule::TxSenderAuthorization,
+                    check,
+                    "authorization compares `tx-sender`, but the function never checks \
+                     `contract-caller`; any contract the sender calls can pass this check"
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expr: &SymbolicExpression) {
+        let list = match expr.match_list() {
+            Some(list) => list,
+            None => return,
+        };
+
+        match native_function_of(list) {
+            Some(NativeFunctions::Let) if list.len() >= 3 => return self.check_let(&list[1..]),
+            Some(NativeFunctions::Match) => {
+                // (match input some-name some-branch none-branch), or
+                // (match input ok-name ok-branch err-name err-branch)
+                let binding_positions: &[usize] = match list.len() {
+                    5 => &[2],
+                    6 => &[2, 4],
+                    _ => &[],
+                };
+                for i in binding_positions.iter() {
+                    if let Some(name) = list[*i].match_atom() {
+                        self.check_shadowing(name, &list[*i]);
+                    }
+                }
+            }
+            Some(function @ NativeFunctions::Unwrap)
+            | Some(function @ NativeFunctions::UnwrapErr) => {
+                self.report(
+                    LintRule::UnwrapPanic,
+                    expr,
+                    format!(
+                        "`{}` aborts the transaction without an error code; consider `unwrap!` or `try!`",
+                        function
+                    ),
+                );
+            }
+            _ => {}
+        }
+
+        self.check_all(list);
+    }
+
+    fn check_let(&mut self, args: &[SymbolicExpression]) {
+        let bindings = match args[0].match_list() {
+            Some(bindings) => bindings,
+            None => return self.check_all(args),
+        };
+        let body = &args[1..];
+
+        for (i, binding) in bindings.iter().enumerate() {
+            let (name, value) = match binding.match_list() {
+                Some([name, value]) => match name.match_atom() {
+                    Some(name) => (name, value),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            self.check_shadowing(name, binding);
+            self.check_expression(value);
+
+            // later bindings can refer to earlier ones
+            let used = bindings[i + 1..]
+                .iter()
+                .any(|later| match later.match_list() {
+                    Some([_, later_value]) => references(name, later_value),
+                    _ => false,
+                })
+                || body.iter().any(|e| references(name, e));
+            if !used {
+                self.report(
+                    LintRule::UnusedLetBinding,
+                    binding,
+                    format!("let-binding `{}` is never used", name),
+                );
+            }
+        }
+
+        self.check_all(body);
+    }
+
+    fn check_all(&mut self, expressions: &[SymbolicExpression]) {
+        for expr in expressions.iter() {
+            self.check_expression(expr);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use vm::analysis::linter::{lint_contract, Lint, LintConfig, LintLevel, LintRule};
+use vm::types::QualifiedContractIdentifier;
+
+fn lint_with(source: &str, config: &LintConfig) -> Vec<Lint> {
+    lint_contract(&QualifiedContractIdentifier::transient(), source, config).unwrap()
+}
+
+fn rules(source: &str) -> Vec<(LintRule, u32)> {
+    lint_with(source, &LintConfig::default())
+        .into_iter()
+        .map(|lint| (lint.rule, lint.span.start_line))
+        .collect()
+}
+
+#[test]
+fn test_unused_let_binding() {
+    let source = "(define-read-only (f (x int))
+  (let ((a (+ x 1))
+        (b (* a 2))
+        (unused 3))
+    b))";
+    assert_eq!(rules(source), vec![(LintRule::UnusedLetBinding, 4)]);
+
+    // uses as tuple keys or in a rebinding scope don't count
+    let source = "(define-read-only (g)
+  (let ((a 1) (b 2) (c (some 3)))
+    (list (tuple (a 4)) (let ((b 5)) b) (match c a a 0))))";
+    assert_eq!(
+        rules(source),
+        vec![
+            (LintRule::UnusedLetBinding, 2),
+            (LintRule::UnusedLetBinding, 2),
+        ]
+    );
+
+    let source = "(define-read-only (h (m (tuple (a int))))
+  (let ((a 1)) (+ a (get a m))))";
+    assert_eq!(rules(source), vec![]);
+}
+
+#[test]
+fn test_shadowed_names() {
+    let source = "(define-constant owner 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR)
+(define-data-var counter int 0)
+(define-map balances principal uint)
+(define-public (set (owner principal))
+  (let ((counter 1))
+    (match (map-get? balances owner) balances (ok counter) (ok 0))))";
+    assert_eq!(
+        rules(source),
+        vec![
+            (LintRule::ShadowedName, 4),
+            (LintRule::ShadowedName, 5),
+            (LintRule::ShadowedName, 6),
+        ]
+    );
+
+    let lints = lint_with(source, &LintConfig::default());
+    assert_eq!(lints[0].message, "`owner` shadows the constant `owner`");
+    assert_eq!(
+        lints[1].message,
+        "`counter` shadows the data variable `counter`"
+    );
+}
+
+#[test]
+fn test_unwrap_panic() {
+    let source = "(define-map m int int)
+(define-read-only (f) (unwrap-panic (map-get? m 1)))
+(define-read-only (g) (unwrap! (map-get? m 1) (err u1)))
+(define-read-only (h) (unwrap-err-panic (err 1)))";
+    assert_eq!(
+        rules(source),
+        vec![(LintRule::UnwrapPanic, 2), (LintRule::UnwrapPanic, 4)]
+    );
+}
+
+#[test]
+fn test_tx_sender_authorization() {
+    let source = "(define-constant owner 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR)
+(define-public (withdraw)
+  (begin
+    (asserts! (is-eq tx-sender owner) (err u1))
+    (ok true)))
+(define-public (withdraw-safe)
+  (begin
+    (asserts! (and (is-eq tx-sender owner) (is-eq contract-caller owner)) (err u1))
+    (ok true)))
+(define-public (pay)
+  (as-contract (stx-transfer? u1 tx-sender (if (is-eq tx-sender owner) owner owner))))";
+    assert_eq!(rules(source), vec![(LintRule::TxSenderAuthorization, 4)]);
+}
+
+#[test]
+fn test_lint_config() {
+    let source = "(define-read-only (f) (let ((a 1)) (unwrap-panic (some 2))))";
+
+    let mut config = LintConfig::default();
+    config.set_level(LintRule::UnwrapPanic, LintLevel::Allow);
+    config.set_level(LintRule::UnusedLetBinding, LintLevel::Deny);
+    let lints = lint_with(source, &config);
+    assert_eq!(lints.len(), 1);
+    assert_eq!(lints[0].rule, LintRule::UnusedLetBinding);
+    assert_eq!(lints[0].level, LintLevel::Deny);
+    assert_eq!(
+        lints[0].to_string(),
+        "error (line 1, column 29): let-binding `a` is never used [unused-let-binding]"
+    );
+
+    let config =
+        LintConfig::from_json(r#"{"unused-let-binding": "allow", "unwrap-panic": "deny"}"#)
+            .unwrap();
+    assert_eq!(config.level(LintRule::UnusedLetBinding), LintLevel::Allow);
+    assert_eq!(config.level(LintRule::UnwrapPanic), LintLevel::Deny);
+    assert_eq!(config.level(LintRule::ShadowedName), LintLevel::Warn);
+    let lints = lint_with(source, &config);
+    assert_eq!(lints.len(), 1);
+    assert_eq!(lints[0].rule, LintRule::UnwrapPanic);
+
+    assert!(LintConfig::from_json(r#"{"no-such-rule": "deny"}"#).is_err());
+    assert!(LintConfig::from_json(r#"{"unwrap-panic": "loud"}"#).is_err());
+}
+
+#[test]
+fn test_trait_call_before_write() {
+    let source = "(define-trait token ((transfer (uint principal) (response bool uint))))
+(define-map balances principal uint)
+(define-data-var total uint u0)
+(define-private (debit (amount uint))
+  (map-set balances tx-sender amount))
+(define-private (debit-twice (amount uint))
+  (begin (debit amount) (debit amount)))
+(define-public (withdraw (t <token>) (amount uint))
+  (begin
+    (try! (contract-call? t transfer amount tx-sender))
+    (var-set total amount)
+    (ok (debit-twice amount))))
+(define-public (withdraw-safe (t <token>) (amount uint))
+  (begin
+    (debit amount)
+    (var-set total amount)
+    (contract-call? t transfer amount tx-sender)))
+(define-public (call-static (amount uint))
+  (begin
+    (try! (contract-call? .token transfer amount tx-sender))
+    (ok (var-set total amount))))";
+
+    // opt-in
+    assert_eq!(rules(source), vec![]);
+
+    let mut config = LintConfig::default();
+    config.set_level(LintRule::TraitCallBeforeWrite, LintLevel::Warn);
+    let lints: Vec<_> = lint_with(source, &config)
+        .into_iter()
+        .filter(|lint| lint.rule == LintRule::TraitCallBeforeWrite)
+        .collect();
+    assert_eq!(
+        lints
+            .iter()
+            .map(|lint| lint.span.start_line)
+            .collect::<Vec<_>>(),
+        vec![11, 12]
+    );
+    assert_eq!(
+        lints[0].message,
+        "state is written after the `contract-call?` to trait-typed `t` (line 10); \
+         the called contract runs before this function finishes its own writes"
+    );
+}
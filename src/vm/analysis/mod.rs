@@ -16,8 +16,11 @@
 
 pub mod analysis_db;
 pub mod arithmetic_checker;
+pub mod cache;
 pub mod contract_interface_builder;
+pub mod dependency_graph;
 pub mod errors;
+pub mod linter;
 pub mod read_only_checker;
 pub mod trait_checker;
 pub mod type_checker;
@@ -25,7 +28,7 @@ pub mod types;
 
 use crate::core::StacksEpochId;
 
-pub use self::types::{AnalysisPass, ContractAnalysis};
+pub use self::types::{AnalysisPass, ContractAnalysis, ContractStorageLayout};
 use vm::costs::LimitedCostTracker;
 use vm::database::STORE_CONTRACT_SRC_INTERFACE;
 use vm::representations::SymbolicExpression;
@@ -55,6 +58,7 @@ pub fn mem_type_check(snippet: &str) -> CheckResult<(Option<TypeSignature>, Cont
         &mut analysis_db,
         false,
         cost_tracker,
+        StacksEpochId::latest(),
     ) {
         Ok(x) => {
             // return the first type result of the type checker
@@ -87,6 +91,7 @@ pub fn type_check(
         // for the type check tests, the cost tracker's epoch doesn't
         //  matter: the costs in those tests are all free anyways.
         LimitedCostTracker::new_free(),
+        StacksEpochId::latest(),
     )
     .map_err(|(e, _cost_tracker)| e)
 }
@@ -97,11 +102,13 @@ pub fn run_analysis(
     analysis_db: &mut AnalysisDatabase,
     save_contract: bool,
     cost_tracker: LimitedCostTracker,
+    epoch: StacksEpochId,
 ) -> Result<ContractAnalysis, (CheckError, LimitedCostTracker)> {
     let mut contract_analysis = ContractAnalysis::new(
         contract_identifier.clone(),
         expressions.to_vec(),
         cost_tracker,
+        epoch,
     );
     let result = analysis_db.execute(|db| {
         ReadOnlyChecker::run_pass(&mut contract_analysis, db)?;
@@ -31,6 +31,7 @@ pub use super::errors::{
     check_argument_count, check_arguments_at_least, CheckError, CheckErrors, CheckResult,
 };
 use super::AnalysisDatabase;
+use crate::core::StacksEpochId;
 
 #[cfg(test)]
 mod tests;
@@ -38,6 +39,7 @@ mod tests;
 pub struct ReadOnlyChecker<'a, 'b> {
     db: &'a mut AnalysisDatabase<'b>,
     defined_functions: HashMap<ClarityName, bool>,
+    epoch: StacksEpochId,
 }
 
 impl<'a, 'b> AnalysisPass for ReadOnlyChecker<'a, 'b> {
@@ -45,16 +47,17 @@ impl<'a, 'b> AnalysisPass for ReadOnlyChecker<'a, 'b> {
         contract_analysis: &mut ContractAnalysis,
         analysis_db: &mut AnalysisDatabase,
     ) -> CheckResult<()> {
-        let mut command = ReadOnlyChecker::new(analysis_db);
+        let mut command = ReadOnlyChecker::new(analysis_db, contract_analysis.epoch);
         command.run(contract_analysis)?;
         Ok(())
     }
 }
 
 impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
-    fn new(db: &'a mut AnalysisDatabase<'b>) -> ReadOnlyChecker<'a, 'b> {
+    fn new(db: &'a mut AnalysisDatabase<'b>, epoch: StacksEpochId) -> ReadOnlyChecker<'a, 'b> {
         Self {
             db,
+            epoch,
             defined_functions: HashMap::new(),
         }
     }
@@ -159,7 +162,7 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
         function: &str,
         args: &[SymbolicExpression],
     ) -> Option<CheckResult<bool>> {
-        NativeFunctions::lookup_by_name(function)
+        NativeFunctions::lookup_by_name_at_epoch(function, &self.epoch)
             .map(|function| self.check_native_function(&function, args))
     }
 
@@ -179,7 +182,16 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
             | IsSome | TryRet | ToUInt | ToInt | Append | Concat | AsMaxLen | ContractOf
             | PrincipalOf | ListCons | GetBlockInfo | TupleGet | TupleMerge | Len | Print
             | AsContract | Begin | FetchVar | GetStxBalance | GetTokenBalance | GetAssetOwner
-            | GetTokenSupply | ElementAt | IndexOf => self.check_all_read_only(args),
+            | GetTokenSupply | ElementAt | IndexOf | GetPublicFunctions | BuffToIntLe
+            | BuffToIntBe | BuffToUIntLe | BuffToUIntBe | IntToBuffLe | IntToBuffBe
+            | UIntToBuffLe | UIntToBuffBe | StringToInt | StringToUInt | Slice => {
+                self.check_all_read_only(args)
+            }
+            ImplementsTrait => {
+                // the second argument is a trait identifier
+                check_argument_count(2, args)?;
+                self.check_read_only(&args[0])
+            }
             AtBlock => {
                 check_argument_count(2, args)?;
 
@@ -29,7 +29,7 @@ use vm::representations::SymbolicExpression;
 use vm::tests::costs::get_simple_test;
 use vm::tests::{
     execute, symbols_from_values, with_marfed_environment, with_memory_environment,
-    TEST_BURN_STATE_DB, TEST_HEADER_DB,
+    TEST_BURN_STATE_DB, TEST_BURN_STATE_DB_21, TEST_HEADER_DB,
 };
 use vm::types::{AssetIdentifier, PrincipalData, QualifiedContractIdentifier, ResponseData, Value};
 
@@ -76,6 +76,13 @@ pub fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -
     let trait_contract_id =
         QualifiedContractIdentifier::new(p1_principal.clone(), "contract-trait".into());
 
+    // the block's Stacks epoch, which analysis runs in, comes from the burnchain
+    let burn_state_db = if epoch >= StacksEpochId::Epoch21 {
+        &TEST_BURN_STATE_DB_21
+    } else {
+        &TEST_BURN_STATE_DB
+    };
+
     clarity_instance
         .begin_test_genesis_block(
             &StacksBlockId::sentinel(),
@@ -90,12 +97,15 @@ pub fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -
             &StacksBlockId([0 as u8; 32]),
             &StacksBlockId([1 as u8; 32]),
             &TEST_HEADER_DB,
-            &TEST_BURN_STATE_DB,
+            burn_state_db,
         );
 
-        if epoch == StacksEpochId::Epoch2_05 {
+        if epoch >= StacksEpochId::Epoch2_05 {
             conn.initialize_epoch_2_05().unwrap();
         }
+        if epoch >= StacksEpochId::Epoch21 {
+            conn.initialize_epoch_2_1().unwrap();
+        }
 
         conn.commit_block();
     }
@@ -105,7 +115,7 @@ pub fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -
             &StacksBlockId([1 as u8; 32]),
             &StacksBlockId([2 as u8; 32]),
             &TEST_HEADER_DB,
-            &TEST_BURN_STATE_DB,
+            burn_state_db,
         );
 
         assert_eq!(
@@ -133,7 +143,7 @@ pub fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -
             &StacksBlockId([2 as u8; 32]),
             &StacksBlockId([3 as u8; 32]),
             &TEST_HEADER_DB,
-            &TEST_BURN_STATE_DB,
+            burn_state_db,
         );
         conn.as_transaction(|conn| {
             let (ct_ast, ct_analysis) = conn
@@ -155,7 +165,7 @@ pub fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -
             &StacksBlockId([3 as u8; 32]),
             &StacksBlockId([4 as u8; 32]),
             &TEST_HEADER_DB,
-            &TEST_BURN_STATE_DB,
+            burn_state_db,
         );
 
         conn.as_transaction(|conn| {
@@ -176,7 +186,10 @@ pub fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -
 fn test_all(use_mainnet: bool) {
     let baseline = test_tracked_costs("1", use_mainnet, StacksEpochId::Epoch20);
 
-    for f in NativeFunctions::ALL.iter() {
+    for f in NativeFunctions::ALL
+        .iter()
+        .filter(|f| f.get_min_epoch() <= StacksEpochId::Epoch20)
+    {
         let test = get_simple_test(f);
         let cost = test_tracked_costs(test, use_mainnet, StacksEpochId::Epoch20);
         assert!(cost.exceeds(&baseline));
@@ -196,7 +209,10 @@ fn test_all_testnet() {
 fn epoch_205_test_all(use_mainnet: bool) {
     let baseline = test_tracked_costs("1", use_mainnet, StacksEpochId::Epoch2_05);
 
-    for f in NativeFunctions::ALL.iter() {
+    for f in NativeFunctions::ALL
+        .iter()
+        .filter(|f| f.get_min_epoch() <= StacksEpochId::Epoch2_05)
+    {
         let test = get_simple_test(f);
         let cost = test_tracked_costs(test, use_mainnet, StacksEpochId::Epoch2_05);
         assert!(cost.exceeds(&baseline));
@@ -212,3 +228,23 @@ fn epoch_205_test_all_mainnet() {
 fn epoch_205_test_all_testnet() {
     epoch_205_test_all(false)
 }
+
+fn epoch_21_test_all(use_mainnet: bool) {
+    let baseline = test_tracked_costs("1", use_mainnet, StacksEpochId::Epoch21);
+
+    for f in NativeFunctions::ALL.iter() {
+        let test = get_simple_test(f);
+        let cost = test_tracked_costs(test, use_mainnet, StacksEpochId::Epoch21);
+        assert!(cost.exceeds(&baseline));
+    }
+}
+
+#[test]
+fn epoch_21_test_all_mainnet() {
+    epoch_21_test_all(true)
+}
+
+#[test]
+fn epoch_21_test_all_testnet() {
+    epoch_21_test_all(false)
+}
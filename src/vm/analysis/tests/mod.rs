@@ -298,3 +298,64 @@ fn test_write_attempt_in_readonly() {
     assert!(format!("{}", err.diagnostic)
         .contains("expecting read-only statements, detected a writing operation"));
 }
+
+#[test]
+fn test_storage_layout() {
+    use crate::clarity_vm::database::MemoryBackingStore;
+    use vm::types::{QualifiedContractIdentifier, TypeSignature};
+    use vm::ClarityName;
+
+    let snippet = "(define-constant owner tx-sender)
+        (define-data-var counter uint u0)
+        (define-map balances principal uint)
+        (define-fungible-token points)
+        (define-non-fungible-token badge (buff 8))
+        (define-private (f) (var-get counter))";
+    let contract_id = QualifiedContractIdentifier::local("storage").unwrap();
+    let mut expressions = parse(&contract_id, snippet).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    analysis_db
+        .execute(|db| {
+            db.test_insert_contract_hash(&contract_id);
+            type_check(&contract_id, &mut expressions, db, true)
+                .map(|_| ())
+                .unwrap();
+            Ok::<_, CheckErrors>(())
+        })
+        .unwrap();
+
+    let missing = QualifiedContractIdentifier::local("missing").unwrap();
+    let (layout, missing_err) = analysis_db
+        .execute(|db| {
+            Ok::<_, CheckErrors>((
+                db.get_storage_layout(&contract_id).unwrap(),
+                db.get_storage_layout(&missing).unwrap_err(),
+            ))
+        })
+        .unwrap();
+    assert_eq!(
+        layout.maps.get("balances"),
+        Some(&(TypeSignature::PrincipalType, TypeSignature::UIntType))
+    );
+    assert_eq!(layout.maps.len(), 1);
+    assert_eq!(
+        layout.data_vars.into_iter().collect::<Vec<_>>(),
+        vec![("counter".into(), TypeSignature::UIntType)]
+    );
+    assert_eq!(
+        layout.fungible_tokens.into_iter().collect::<Vec<_>>(),
+        vec![ClarityName::from("points")]
+    );
+    assert_eq!(
+        layout
+            .non_fungible_tokens
+            .get("badge")
+            .map(|t| t.to_string()),
+        Some("(buff 8)".to_string())
+    );
+    assert_eq!(
+        missing_err.err,
+        CheckErrors::NoSuchContract(missing.to_string())
+    );
+}
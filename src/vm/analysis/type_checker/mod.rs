@@ -39,6 +39,7 @@ use vm::variables::NativeVariables;
 
 pub use super::types::{AnalysisPass, ContractAnalysis};
 use super::AnalysisDatabase;
+use crate::core::StacksEpochId;
 
 use self::contexts::{ContractContext, TypeMap, TypingContext};
 
@@ -75,6 +76,7 @@ pub struct TypeChecker<'a, 'b> {
     function_return_tracker: Option<Option<TypeSignature>>,
     db: &'a mut AnalysisDatabase<'b>,
     pub cost_track: LimitedCostTracker,
+    epoch: StacksEpochId,
 }
 
 impl CostTracker for TypeChecker<'_, '_> {
@@ -115,7 +117,7 @@ impl AnalysisPass for TypeChecker<'_, '_> {
         analysis_db: &mut AnalysisDatabase,
     ) -> CheckResult<()> {
         let cost_track = contract_analysis.take_contract_cost_tracker();
-        let mut command = TypeChecker::new(analysis_db, cost_track);
+        let mut command = TypeChecker::new(analysis_db, cost_track, contract_analysis.epoch);
         // run the analysis, and replace the cost tracker whether or not the
         //   analysis succeeded.
         match command.run(contract_analysis) {
@@ -249,34 +251,68 @@ impl FunctionType {
         check_argument_count(expected_args.len(), func_args)?;
 
         for (expected_arg, arg) in expected_args.iter().zip(func_args.iter()).into_iter() {
-            match (&expected_arg.signature, arg) {
-                (
-                    TypeSignature::TraitReferenceType(trait_id),
-                    Value::Principal(PrincipalData::Contract(contract)),
-                ) => {
-                    let contract_to_check = db
-                        .load_contract(contract)
-                        .ok_or_else(|| CheckErrors::NoSuchContract(contract.name.to_string()))?;
-                    let trait_definition = db
-                        .get_defined_trait(&trait_id.contract_identifier, &trait_id.name)
-                        .unwrap()
-                        .ok_or(CheckErrors::NoSuchContract(
-                            trait_id.contract_identifier.to_string(),
-                        ))?;
-                    contract_to_check.check_trait_compliance(trait_id, &trait_definition)?;
-                }
-                (expected_type, value) => {
-                    if !expected_type.admits(&value) {
-                        let actual_type = TypeSignature::type_of(&value);
-                        return Err(
-                            CheckErrors::TypeError(expected_type.clone(), actual_type).into()
-                        );
-                    }
-                }
-            }
+            check_arg_by_allowing_trait_cast(db, &expected_arg.signature, arg)?;
         }
         Ok(returns.clone())
     }
+
+    /// Like `check_args_by_allowing_trait_cast`, but does not stop at the first bad argument:
+    /// returns the index and error of every argument that does not fit the signature.  A wrong
+    /// number of arguments is still reported as an error.
+    pub fn check_each_arg_by_allowing_trait_cast(
+        &self,
+        db: &mut AnalysisDatabase,
+        func_args: &[Value],
+    ) -> CheckResult<Vec<(usize, CheckErrors)>> {
+        let expected_args = match self {
+            FunctionType::Fixed(FixedFunction { args, .. }) => args,
+            _ => panic!("Unexpected function type"),
+        };
+        check_argument_count(expected_args.len(), func_args)?;
+
+        let mismatches = expected_args
+            .iter()
+            .zip(func_args.iter())
+            .enumerate()
+            .filter_map(|(index, (expected_arg, arg))| {
+                check_arg_by_allowing_trait_cast(db, &expected_arg.signature, arg)
+                    .err()
+                    .map(|e| (index, e.err))
+            })
+            .collect();
+        Ok(mismatches)
+    }
+}
+
+fn check_arg_by_allowing_trait_cast(
+    db: &mut AnalysisDatabase,
+    expected_type: &TypeSignature,
+    arg: &Value,
+) -> CheckResult<()> {
+    match (expected_type, arg) {
+        (
+            TypeSignature::TraitReferenceType(trait_id),
+            Value::Principal(PrincipalData::Contract(contract)),
+        ) => {
+            let contract_to_check = db
+                .load_contract(contract)
+                .ok_or_else(|| CheckErrors::NoSuchContract(contract.name.to_string()))?;
+            let trait_definition = db
+                .get_defined_trait(&trait_id.contract_identifier, &trait_id.name)
+                .unwrap()
+                .ok_or(CheckErrors::NoSuchContract(
+                    trait_id.contract_identifier.to_string(),
+                ))?;
+            contract_to_check.check_trait_compliance(trait_id, &trait_definition)?;
+        }
+        (expected_type, value) => {
+            if !expected_type.admits(value) {
+                let actual_type = TypeSignature::type_of(value);
+                return Err(CheckErrors::TypeError(expected_type.clone(), actual_type).into());
+            }
+        }
+    }
+    Ok(())
 }
 
 fn trait_type_size(trait_sig: &BTreeMap<ClarityName, FunctionSignature>) -> CheckResult<u64> {
@@ -315,10 +351,12 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
     fn new(
         db: &'a mut AnalysisDatabase<'b>,
         cost_track: LimitedCostTracker,
+        epoch: StacksEpochId,
     ) -> TypeChecker<'a, 'b> {
         Self {
             db,
             cost_track,
+            epoch,
             contract_context: ContractContext::new(),
             function_return_tracker: None,
             type_map: TypeMap::new(),
@@ -606,7 +644,9 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
         args: &[SymbolicExpression],
         context: &TypingContext,
     ) -> Option<TypeResult> {
-        if let Some(ref native_function) = NativeFunctions::lookup_by_name(function) {
+        if let Some(ref native_function) =
+            NativeFunctions::lookup_by_name_at_epoch(function, &self.epoch)
+        {
             let typed_function = TypedNativeFunction::type_native_function(native_function);
             Some(typed_function.type_check_appliction(self, args, context))
         } else {
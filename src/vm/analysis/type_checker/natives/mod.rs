@@ -15,15 +15,17 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::{
-    check_argument_count, check_arguments_at_least, no_type, TypeChecker, TypeResult, TypingContext,
+    check_argument_count, check_arguments_at_least, no_type, trait_type_size, TypeChecker,
+    TypeResult, TypingContext,
 };
 use std::convert::TryFrom;
 use vm::analysis::errors::{CheckError, CheckErrors, CheckResult};
 use vm::errors::{Error as InterpError, RuntimeErrorType};
-use vm::functions::{handle_binding_list, NativeFunctions};
+use vm::functions::{handle_binding_list, public_functions_list_type, NativeFunctions};
+use vm::representations::TraitDefinition;
 use vm::types::{
     BlockInfoProperty, FixedFunction, FunctionArg, FunctionSignature, FunctionType, PrincipalData,
-    TupleTypeSignature, TypeSignature, Value, BUFF_20, BUFF_32, BUFF_33, BUFF_64, BUFF_65,
+    TupleTypeSignature, TypeSignature, Value, BUFF_16, BUFF_20, BUFF_32, BUFF_33, BUFF_64, BUFF_65,
     MAX_VALUE_SIZE,
 };
 use vm::{ClarityName, SymbolicExpression, SymbolicExpressionType};
@@ -464,6 +466,73 @@ fn check_contract_of(
     Ok(TypeSignature::PrincipalType)
 }
 
+fn check_implements_trait(
+    checker: &mut TypeChecker,
+    args: &[SymbolicExpression],
+    context: &TypingContext,
+) -> TypeResult {
+    check_argument_count(2, args)?;
+
+    checker.type_check_expects(&args[0], context, &TypeSignature::PrincipalType)?;
+
+    runtime_cost(ClarityCostFunction::ContractOf, checker, 1)?;
+
+    match &args[1].expr {
+        SymbolicExpressionType::TraitReference(_, TraitDefinition::Defined(trait_identifier))
+        | SymbolicExpressionType::TraitReference(_, TraitDefinition::Imported(trait_identifier)) => {
+            // declared by this contract's define-trait or use-trait
+            checker
+                .contract_context
+                .get_trait(&trait_identifier.name)
+                .ok_or_else(|| {
+                    CheckErrors::TraitReferenceUnknown(trait_identifier.name.to_string())
+                })?;
+        }
+        SymbolicExpressionType::Field(trait_identifier) => {
+            let result = checker.db.get_defined_trait(
+                &trait_identifier.contract_identifier,
+                &trait_identifier.name,
+            )?;
+            match result {
+                Some(trait_sig) => {
+                    let type_size = trait_type_size(&trait_sig)?;
+                    runtime_cost(
+                        ClarityCostFunction::AnalysisUseTraitEntry,
+                        checker,
+                        type_size,
+                    )?;
+                }
+                None => {
+                    runtime_cost(ClarityCostFunction::AnalysisUseTraitEntry, checker, 1)?;
+                    return Err(CheckErrors::TraitReferenceUnknown(
+                        trait_identifier.name.to_string(),
+                    )
+                    .into());
+                }
+            }
+        }
+        _ => return Err(CheckErrors::ExpectedTraitIdentifier.into()),
+    }
+
+    Ok(TypeSignature::BoolType)
+}
+
+fn check_get_public_functions(
+    checker: &mut TypeChecker,
+    args: &[SymbolicExpression],
+    context: &TypingContext,
+) -> TypeResult {
+    check_argument_count(1, args)?;
+
+    checker.type_check_expects(&args[0], context, &TypeSignature::PrincipalType)?;
+
+    runtime_cost(ClarityCostFunction::ContractOf, checker, 1)?;
+
+    Ok(TypeSignature::new_option(TypeSignature::from(
+        public_functions_list_type(),
+    ))?)
+}
+
 fn check_principal_of(
     checker: &mut TypeChecker,
     args: &[SymbolicExpression],
@@ -568,6 +637,60 @@ impl TypedNativeFunction {
                 )],
                 returns: TypeSignature::IntType,
             }))),
+            BuffToIntLe | BuffToIntBe => {
+                Simple(SimpleNativeFunction(FunctionType::Fixed(FixedFunction {
+                    args: vec![FunctionArg::new(
+                        BUFF_16.clone(),
+                        ClarityName::try_from("value".to_owned())
+                            .expect("FAIL: ClarityName failed to accept default arg name"),
+                    )],
+                    returns: TypeSignature::IntType,
+                })))
+            }
+            BuffToUIntLe | BuffToUIntBe => {
+                Simple(SimpleNativeFunction(FunctionType::Fixed(FixedFunction {
+                    args: vec![FunctionArg::new(
+                        BUFF_16.clone(),
+                        ClarityName::try_from("value".to_owned())
+                            .expect("FAIL: ClarityName failed to accept default arg name"),
+                    )],
+                    returns: TypeSignature::UIntType,
+                })))
+            }
+            IntToBuffLe | IntToBuffBe => {
+                Simple(SimpleNativeFunction(FunctionType::Fixed(FixedFunction {
+                    args: vec![FunctionArg::new(
+                        TypeSignature::IntType,
+                        ClarityName::try_from("value".to_owned())
+                            .expect("FAIL: ClarityName failed to accept default arg name"),
+                    )],
+                    returns: BUFF_16.clone(),
+                })))
+            }
+            UIntToBuffLe | UIntToBuffBe => {
+                Simple(SimpleNativeFunction(FunctionType::Fixed(FixedFunction {
+                    args: vec![FunctionArg::new(
+                        TypeSignature::UIntType,
+                        ClarityName::try_from("value".to_owned())
+                            .expect("FAIL: ClarityName failed to accept default arg name"),
+                    )],
+                    returns: BUFF_16.clone(),
+                })))
+            }
+            StringToInt => Simple(SimpleNativeFunction(FunctionType::UnionArgs(
+                vec![
+                    TypeSignature::max_string_ascii(),
+                    TypeSignature::max_string_utf8(),
+                ],
+                TypeSignature::OptionalType(Box::new(TypeSignature::IntType)),
+            ))),
+            StringToUInt => Simple(SimpleNativeFunction(FunctionType::UnionArgs(
+                vec![
+                    TypeSignature::max_string_ascii(),
+                    TypeSignature::max_string_utf8(),
+                ],
+                TypeSignature::OptionalType(Box::new(TypeSignature::UIntType)),
+            ))),
             Not => Simple(SimpleNativeFunction(FunctionType::Fixed(FixedFunction {
                 args: vec![FunctionArg::new(
                     TypeSignature::BoolType,
@@ -694,6 +817,7 @@ impl TypedNativeFunction {
             Len => Special(SpecialNativeFunction(&sequences::check_special_len)),
             ElementAt => Special(SpecialNativeFunction(&sequences::check_special_element_at)),
             IndexOf => Special(SpecialNativeFunction(&sequences::check_special_index_of)),
+            Slice => Special(SpecialNativeFunction(&sequences::check_special_slice)),
             ListCons => Special(SpecialNativeFunction(&check_special_list_cons)),
             FetchEntry => Special(SpecialNativeFunction(&maps::check_special_fetch_entry)),
             SetEntry => Special(SpecialNativeFunction(&maps::check_special_set_entry)),
@@ -707,6 +831,8 @@ impl TypedNativeFunction {
             AsContract => Special(SpecialNativeFunction(&check_special_as_contract)),
             ContractCall => Special(SpecialNativeFunction(&check_contract_call)),
             ContractOf => Special(SpecialNativeFunction(&check_contract_of)),
+            ImplementsTrait => Special(SpecialNativeFunction(&check_implements_trait)),
+            GetPublicFunctions => Special(SpecialNativeFunction(&check_get_public_functions)),
             PrincipalOf => Special(SpecialNativeFunction(&check_principal_of)),
             GetBlockInfo => Special(SpecialNativeFunction(&check_get_block_info)),
             ConsSome => Special(SpecialNativeFunction(&options::check_special_some)),
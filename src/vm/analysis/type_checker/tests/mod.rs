@@ -715,6 +715,96 @@ fn test_element_at() {
     }
 }
 
+#[test]
+fn test_slice() {
+    let good = [
+        "(slice? (list 1 2 3 4 5) u1 u3)",
+        "(slice? \"abcd\" u0 (+ u1 u2))",
+        "(slice? 0xfedb u1 u2)",
+        "(slice? u\"abcd\" u1 u2)",
+    ];
+
+    let expected = [
+        "(optional (list 5 int))",
+        "(optional (string-ascii 4))",
+        "(optional (buff 2))",
+        "(optional (string-utf8 4))",
+    ];
+
+    let bad = [
+        "(slice? (list 1 2 3 4 5) 1 u3)",
+        "(slice? 3 u0 u1)",
+        "(slice? 0xfedb u1)",
+    ];
+
+    let bad_expected = [
+        CheckErrors::TypeError(TypeSignature::UIntType, TypeSignature::IntType),
+        CheckErrors::ExpectedSequence(TypeSignature::IntType),
+        CheckErrors::IncorrectArgumentCount(3, 2),
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(good_test).unwrap())
+        );
+    }
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(bad_test).unwrap_err().err);
+    }
+}
+
+#[test]
+fn test_integer_conversions() {
+    let good = [
+        "(buff-to-int-le 0x0102)",
+        "(buff-to-uint-be 0x0102030405060708090a0b0c0d0e0f10)",
+        "(int-to-buff-be -1)",
+        "(uint-to-buff-le u1)",
+        "(string-to-int? \"-12\")",
+        "(string-to-uint? u\"12\")",
+    ];
+
+    let expected = [
+        "int",
+        "uint",
+        "(buff 16)",
+        "(buff 16)",
+        "(optional int)",
+        "(optional uint)",
+    ];
+
+    let bad = [
+        "(buff-to-int-be 0x0102030405060708090a0b0c0d0e0f1011)",
+        "(int-to-buff-le u1)",
+        "(string-to-int? 0x01)",
+    ];
+
+    let bad_expected = [
+        CheckErrors::TypeError(buff_type(16), buff_type(17)),
+        CheckErrors::TypeError(TypeSignature::IntType, TypeSignature::UIntType),
+        CheckErrors::UnionTypeError(
+            vec![
+                TypeSignature::max_string_ascii(),
+                TypeSignature::max_string_utf8(),
+            ],
+            buff_type(1),
+        ),
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(good_test).unwrap())
+        );
+    }
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(bad_test).unwrap_err().err);
+    }
+}
+
 #[test]
 fn test_eqs() {
     let good = [
@@ -24,6 +24,8 @@ use vm::types::signatures::FunctionSignature;
 use vm::types::{FunctionType, QualifiedContractIdentifier, TraitIdentifier, TypeSignature};
 use vm::{ClarityName, SymbolicExpression};
 
+use crate::core::StacksEpochId;
+
 const DESERIALIZE_FAIL_MESSAGE: &str =
     "PANIC: Failed to deserialize bad database data in contract analysis.";
 const SERIALIZE_FAIL_MESSAGE: &str =
@@ -57,6 +59,21 @@ pub struct ContractAnalysis {
     pub type_map: Option<TypeMap>,
     #[serde(skip)]
     pub cost_track: Option<LimitedCostTracker>,
+    /// Epoch the contract is analyzed in, which determines the available native functions
+    #[serde(skip, default = "StacksEpochId::latest")]
+    pub epoch: StacksEpochId,
+}
+
+/// The data a contract persists in the chainstate: its data maps, data vars, and tokens,
+///  with their types.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContractStorageLayout {
+    /// the key and value types of each data map
+    pub maps: BTreeMap<ClarityName, (TypeSignature, TypeSignature)>,
+    pub data_vars: BTreeMap<ClarityName, TypeSignature>,
+    pub fungible_tokens: BTreeSet<ClarityName>,
+    /// the asset type of each non-fungible token
+    pub non_fungible_tokens: BTreeMap<ClarityName, TypeSignature>,
 }
 
 impl ContractAnalysis {
@@ -64,6 +81,7 @@ impl ContractAnalysis {
         contract_identifier: QualifiedContractIdentifier,
         expressions: Vec<SymbolicExpression>,
         cost_track: LimitedCostTracker,
+        epoch: StacksEpochId,
     ) -> ContractAnalysis {
         ContractAnalysis {
             contract_identifier,
@@ -82,6 +100,7 @@ impl ContractAnalysis {
             non_fungible_tokens: BTreeMap::new(),
             cost_track: Some(cost_track),
             is_cost_contract_eligible: false,
+            epoch,
         }
     }
 
@@ -174,6 +193,15 @@ impl ContractAnalysis {
         self.persisted_variable_types.get(name)
     }
 
+    pub fn get_storage_layout(&self) -> ContractStorageLayout {
+        ContractStorageLayout {
+            maps: self.map_types.clone(),
+            data_vars: self.persisted_variable_types.clone(),
+            fungible_tokens: self.fungible_tokens.clone(),
+            non_fungible_tokens: self.non_fungible_tokens.clone(),
+        }
+    }
+
     pub fn get_defined_trait(
         &self,
         name: &str,
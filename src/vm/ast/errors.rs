@@ -63,6 +63,7 @@ pub enum ParseErrors {
     InvalidCharactersDetected,
     InvalidEscaping,
     CostComputationFailed(String),
+    FormattingChangedProgram,
 }
 
 #[derive(Debug, PartialEq)]
@@ -238,6 +239,9 @@ impl DiagnosableError for ParseErrors {
             ParseErrors::InvalidCharactersDetected => format!("invalid characters detected"),
             ParseErrors::InvalidEscaping => format!("invalid escaping detected in string"),
             ParseErrors::CostComputationFailed(s) => format!("Cost computation failed: {}", s),
+            ParseErrors::FormattingChangedProgram => {
+                "formatting would change the parsed program".to_string()
+            }
         }
     }
 
@@ -0,0 +1,537 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A canonical pretty-printer for Clarity source.
+//!
+//! The parser drops comments, so the formatter reads the source a second time to build a layout
+//! tree that keeps them, and only ever rewrites whitespace, commas and colons: atoms, literals and
+//! comments are copied through verbatim.  The layout is a function of the program and its
+//! comments alone (plus whether there was a blank line between two items), so formatting is
+//! deterministic and formatting formatted source is a no-op:
+//!
+//! * a list or tuple stays on one line if it has no comments and fits in `MAX_LINE_WIDTH`
+//! * otherwise a list keeps its head (and, unless it is a `begin`, its first argument if that
+//!   fits) on the opening line, and puts each remaining item on its own line, indented two spaces
+//!   past the opening parenthesis.  A tuple puts each entry on its own line.
+//! * comments that followed an item on the same line still do; other comments get their own line
+//! * at most one blank line is kept between two items
+//!
+//! Before returning, the output is parsed again and checked against the original program.
+
+use vm::ast::errors::{ParseError, ParseErrors, ParseResult};
+use vm::ast::parser;
+use vm::representations::{PreSymbolicExpression, PreSymbolicExpressionType};
+
+/// Lists and tuples longer than this are broken across lines
+pub const MAX_LINE_WIDTH: usize = 80;
+
+/// Forms whose arguments all go on their own lines once the form is broken
+const BODY_FORMS: &[&str] = &["begin"];
+
+enum Token {
+    Open(char),
+    Close,
+    Separator,
+    Atom(String),
+    Comment(String),
+}
+
+enum Node {
+    List(Vec<Item>),
+    Tuple(Vec<Item>),
+    Atom(String),
+    Comment(String),
+}
+
+struct Item {
+    node: Node,
+    /// The item started a new line in the source
+    line_break_before: bool,
+    /// There was at least one blank line before the item in the source
+    blank_line_before: bool,
+}
+
+/// Split `source` into tokens, each with the number of newlines before it.  This mirrors the
+/// lexer's rules for where strings and comments start and end; anything the lexer would reject
+/// has already been rejected by the parser.
+fn tokenize(source: &str) -> Vec<(Token, usize)> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut newlines = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b'\n' => {
+                newlines += 1;
+                i += 1;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'(' | b'{' => {
+                i += 1;
+                Token::Open(bytes[start] as char)
+            }
+            b')' | b'}' => {
+                i += 1;
+                Token::Close
+            }
+            b',' | b':' => {
+                i += 1;
+                Token::Separator
+            }
+            b';' => {
+                while i < bytes.len() && (b' '..=b'~').contains(&bytes[i]) {
+                    i += 1;
+                }
+                Token::Comment(source[start..i].trim_end().to_string())
+            }
+            _ => {
+                if bytes[i] == b'u' && bytes.get(i + 1) == Some(&b'"') {
+                    i += 1;
+                }
+                if bytes[i] == b'"' {
+                    i += 1;
+                    while i < bytes.len() && bytes[i] != b'"' {
+                        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'"') {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    i = (i + 1).min(bytes.len());
+                } else {
+                    while i < bytes.len() && !b" \t\r\n(){},:;\"".contains(&bytes[i]) {
+                        i += 1;
+                    }
+                }
+                Token::Atom(source[start..i].to_string())
+            }
+        };
+        tokens.push((token, newlines));
+        newlines = 0;
+    }
+    tokens
+}
+
+/// An open list or tuple: its opening character and the newlines before it, and its items so far
+type OpenNode = (Option<(char, usize)>, Vec<Item>);
+
+fn build_tree(tokens: Vec<(Token, usize)>) -> Vec<Item> {
+    // the bottom of the stack holds the top-level items
+    let mut stack: Vec<OpenNode> = vec![(None, vec![])];
+    for (token, newlines) in tokens.into_iter() {
+        let node = match token {
+            Token::Open(c) => {
+                stack.push((Some((c, newlines)), vec![]));
+                continue;
+            }
+            Token::Separator => continue,
+            Token::Atom(text) => Node::Atom(text),
+            Token::Comment(text) => Node::Comment(text),
+            Token::Close => {
+                let (open, items) = stack.pop().expect("BUG: unbalanced source was parsed");
+                let (c, newlines) = open.expect("BUG: unbalanced source was parsed");
+                let node = if c == '(' {
+                    Node::List(items)
+                } else {
+                    Node::Tuple(items)
+                };
+                stack.last_mut().unwrap().1.push(Item {
+                    node,
+                    line_break_before: newlines > 0,
+                    blank_line_before: newlines > 1,
+                });
+                continue;
+            }
+        };
+        stack.last_mut().unwrap().1.push(Item {
+            node,
+            line_break_before: newlines > 0,
+            blank_line_before: newlines > 1,
+        });
+    }
+    assert_eq!(stack.len(), 1, "BUG: unbalanced source was parsed");
+    stack.pop().unwrap().1
+}
+
+/// The one-line rendering of `node`, if it has one
+fn flat(node: &Node) -> Option<String> {
+    match node {
+        Node::Atom(text) => Some(text.clone()),
+        Node::Comment(_) => None,
+        Node::List(items) => {
+            let parts: Option<Vec<String>> = items.iter().map(|item| flat(&item.node)).collect();
+            Some(format!("({})", parts?.join(" ")))
+        }
+        Node::Tuple(items) => {
+            if items.is_empty() {
+                return Some("{}".to_string());
+            }
+            let parts: Option<Vec<String>> = items.iter().map(|item| flat(&item.node)).collect();
+            let entries: Vec<String> = parts?.chunks(2).map(|entry| entry.join(": ")).collect();
+            Some(format!("{{ {} }}", entries.join(", ")))
+        }
+    }
+}
+
+fn indented(column: usize, text: &str) -> String {
+    format!("{}{}", " ".repeat(column), text)
+}
+
+/// Column just past the end of the rendering so far, which started at `column`
+fn end_column(lines: &[String], column: usize) -> usize {
+    match lines {
+        [line] => column + line.len(),
+        _ => lines.last().map(|line| line.len()).unwrap_or(column),
+    }
+}
+
+/// Append `rendered` (laid out starting at the end of `lines`) to `lines`
+fn append(lines: &mut Vec<String>, rendered: Vec<String>) {
+    let mut rendered = rendered.into_iter();
+    if let Some(first) = rendered.next() {
+        lines.last_mut().unwrap().push_str(&first);
+    }
+    lines.extend(rendered);
+}
+
+/// Start a new line at `column` for `item`, keeping a blank line before it if it had one
+fn push_item(lines: &mut Vec<String>, item: &Item, column: usize, trailing: usize) {
+    if item.blank_line_before {
+        lines.push(String::new());
+    }
+    lines.push(" ".repeat(column));
+    append(lines, layout(&item.node, column, trailing));
+}
+
+/// Lay out `node` starting at `column`, to be followed by `trailing` more characters (closing
+/// parentheses and commas) on its last line.  The first line carries no indentation; the others
+/// are indented absolutely.
+fn layout(node: &Node, column: usize, trailing: usize) -> Vec<String> {
+    if let Some(text) = flat(node) {
+        if column + text.len() + trailing <= MAX_LINE_WIDTH {
+            return vec![text];
+        }
+    }
+    match node {
+        Node::Atom(text) | Node::Comment(text) => vec![text.clone()],
+        Node::List(items) => layout_list(items, column, trailing),
+        Node::Tuple(items) => layout_tuple(items, column),
+    }
+}
+
+fn layout_list(items: &[Item], column: usize, trailing: usize) -> Vec<String> {
+    let head = items.iter().find_map(|item| match item.node {
+        Node::Comment(_) => None,
+        ref node => Some(node),
+    });
+    let (continuation, args_on_head_line) = match head {
+        Some(Node::Atom(name)) if BODY_FORMS.contains(&name.as_str()) => (column + 2, 0),
+        Some(Node::Atom(_)) => (column + 2, 1),
+        _ => (column + 1, 0),
+    };
+
+    // the closing parenthesis goes on the last item's line, unless a comment comes after it
+    let closes_on = match items.last() {
+        Some(Item {
+            node: Node::Comment(_),
+            ..
+        }) => None,
+        _ => Some(items.len().saturating_sub(1)),
+    };
+
+    let mut lines = vec!["(".to_string()];
+    let mut exprs = 0;
+    let mut after_comment = false;
+    for (i, item) in items.iter().enumerate() {
+        let trailing = if closes_on == Some(i) {
+            trailing + 1
+        } else {
+            0
+        };
+        if let Node::Comment(ref text) = item.node {
+            if item.line_break_before || after_comment {
+                push_item(&mut lines, item, continuation, 0);
+            } else {
+                let line = lines.last_mut().unwrap();
+                line.push(' ');
+                line.push_str(text);
+            }
+            after_comment = true;
+            continue;
+        }
+
+        let on_opening_line = !after_comment && lines.len() == 1;
+        if on_opening_line && exprs == 0 {
+            let start = end_column(&lines, column);
+            append(&mut lines, layout(&item.node, start, trailing));
+        } else if on_opening_line && exprs <= args_on_head_line {
+            let start = end_column(&lines, column) + 1;
+            match flat(&item.node) {
+                Some(text) if start + text.len() + trailing <= MAX_LINE_WIDTH => {
+                    let line = lines.last_mut().unwrap();
+                    line.push(' ');
+                    line.push_str(&text);
+                }
+                _ => push_item(&mut lines, item, continuation, trailing),
+            }
+        } else {
+            push_item(&mut lines, item, continuation, trailing);
+        }
+        exprs += 1;
+        after_comment = false;
+    }
+
+    if after_comment {
+        lines.push(indented(column, ")"));
+    } else {
+        lines.last_mut().unwrap().push(')');
+    }
+    lines
+}
+
+struct TupleEntry<'a> {
+    /// Comments on their own lines before the entry, or between its key and value
+    comments: Vec<&'a str>,
+    key: &'a Item,
+    value: Option<&'a Item>,
+    /// Comment following the entry on the same line
+    trailing: Vec<&'a str>,
+}
+
+fn layout_tuple(items: &[Item], column: usize) -> Vec<String> {
+    let continuation = column + 2;
+    let mut entries: Vec<TupleEntry> = vec![];
+    let mut comments = vec![];
+    for item in items.iter() {
+        let incomplete = entries.last().map(|entry| entry.value.is_none()) == Some(true);
+        match item.node {
+            Node::Comment(ref text) if incomplete => {
+                entries.last_mut().unwrap().comments.push(text);
+            }
+            Node::Comment(ref text) if !item.line_break_before && !entries.is_empty() => {
+                entries.last_mut().unwrap().trailing.push(text);
+            }
+            Node::Comment(ref text) => comments.push(text.as_str()),
+            _ if incomplete => entries.last_mut().unwrap().value = Some(item),
+            _ => entries.push(TupleEntry {
+                comments: std::mem::take(&mut comments),
+                key: item,
+                value: None,
+                trailing: vec![],
+            }),
+        }
+    }
+
+    let mut lines = vec!["{".to_string()];
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.key.blank_line_before && i > 0 {
+            lines.push(String::new());
+        }
+        for comment in entry.comments.iter() {
+            lines.push(indented(continuation, comment));
+        }
+        lines.push(" ".repeat(continuation));
+        let comma = if i + 1 < entries.len() { 1 } else { 0 };
+        append(&mut lines, layout(&entry.key.node, continuation, 0));
+        if let Some(value) = entry.value {
+            lines.last_mut().unwrap().push_str(": ");
+            let start = end_column(&lines, column);
+            append(&mut lines, layout(&value.node, start, comma));
+        }
+        if comma > 0 {
+            lines.last_mut().unwrap().push(',');
+        }
+        for comment in entry.trailing.iter() {
+            let line = lines.last_mut().unwrap();
+            line.push(' ');
+            line.push_str(comment);
+        }
+    }
+    for comment in comments.iter() {
+        lines.push(indented(continuation, comment));
+    }
+
+    lines.push(indented(column, "}"));
+    lines
+}
+
+fn layout_top_level(items: &[Item]) -> String {
+    let mut lines: Vec<String> = vec![];
+    for item in items.iter() {
+        match item.node {
+            Node::Comment(ref text) if !item.line_break_before && !lines.is_empty() => {
+                let line = lines.last_mut().unwrap();
+                line.push(' ');
+                line.push_str(text);
+            }
+            _ => {
+                if item.blank_line_before && !lines.is_empty() {
+                    lines.push(String::new());
+                }
+                lines.extend(layout(&item.node, 0, 0));
+            }
+        }
+    }
+
+    let mut output = String::new();
+    for line in lines.iter() {
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+fn same_program(a: &[PreSymbolicExpression], b: &[PreSymbolicExpression]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b.iter())
+            .all(|(a, b)| match (&a.pre_expr, &b.pre_expr) {
+                (PreSymbolicExpressionType::List(a), PreSymbolicExpressionType::List(b))
+                | (PreSymbolicExpressionType::Tuple(a), PreSymbolicExpressionType::Tuple(b)) => {
+                    same_program(a, b)
+                }
+                (a, b) => a == b,
+            })
+}
+
+/// Format Clarity source canonically, keeping its comments.  Fails if the source does not parse.
+pub fn format_source(source: &str) -> ParseResult<String> {
+    let program = parser::parse(source)?;
+    let formatted = layout_top_level(&build_tree(tokenize(source)));
+
+    let reparsed = parser::parse(&formatted)
+        .map_err(|_| ParseError::new(ParseErrors::FormattingChangedProgram))?;
+    if !same_program(&program, &reparsed) {
+        return Err(ParseError::new(ParseErrors::FormattingChangedProgram));
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_source() {
+        let source = "(define-data-var counter   int 0)
+(define-public (increment (amount-to-add int))
+  (begin (var-set counter (+ (var-get counter) amount-to-add)) (ok (var-get counter))))";
+        let expected = "(define-data-var counter int 0)
+(define-public (increment (amount-to-add int))
+  (begin
+    (var-set counter (+ (var-get counter) amount-to-add))
+    (ok (var-get counter))))
+";
+        assert_eq!(format_source(source).unwrap(), expected);
+
+        // short forms are joined onto one line
+        assert_eq!(
+            format_source("(define-map m\n  int\n  {a:   int,\n b: (list 10 uint)})").unwrap(),
+            "(define-map m int { a: int, b: (list 10 uint) })\n"
+        );
+
+        // long tuples put each entry on its own line
+        let source = "(define-read-only (get-info) (ok { name: \"a long enough name\", symbol: \"SYM\", decimals: u6, supply: u1000000 }))";
+        let expected = "(define-read-only (get-info)
+  (ok
+    {
+      name: \"a long enough name\",
+      symbol: \"SYM\",
+      decimals: u6,
+      supply: u1000000
+    }))
+";
+        assert_eq!(format_source(source).unwrap(), expected);
+
+        assert_eq!(format_source("").unwrap(), "");
+        assert!(format_source("(define-data-var x int 0").is_err());
+    }
+
+    #[test]
+    fn test_format_source_keeps_comments() {
+        let source = ";; a counter
+(define-data-var counter int 0) ;; starts at zero
+
+
+;; bump the counter
+(define-public (increment)
+  ;; no overflow check: \";; not a comment\"
+  (ok (var-set counter (+ (var-get counter) 1))) ;; done
+)";
+        let expected = ";; a counter
+(define-data-var counter int 0) ;; starts at zero
+
+;; bump the counter
+(define-public (increment)
+  ;; no overflow check: \";; not a comment\"
+  (ok (var-set counter (+ (var-get counter) 1))) ;; done
+)
+";
+        assert_eq!(format_source(source).unwrap(), expected);
+
+        // comments inside strings are kept as strings
+        let source = "(print \";; not a comment\") ;; a comment\n";
+        assert_eq!(format_source(source).unwrap(), source);
+
+        let source = "{ a: 1, ;; first\n  b: ;; second\n 2 }";
+        let expected = "{
+  a: 1, ;; first
+  ;; second
+  b: 2
+}
+";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_source_idempotent() {
+        let sources = [
+            "(define-data-var counter int 0)
+(define-public (increment (amount int))
+  (begin (var-set counter (+ (var-get counter) amount)) (ok (var-get counter))))",
+            "(define-map m int { a: int }) ;; trailing
+(define-private (f (x int)) (match (map-get? m x) entry (get a entry) ;; found
+  0))",
+            "(let ((a 1) (b 2) (c \"some string that is pretty long indeed\") (d { x: 1, y: 2, z: 3 })) (+ a b))",
+            "(define-constant ERR (err u1)) {a: 1,}",
+            ";; only a comment",
+        ];
+        for source in sources.iter() {
+            let formatted = format_source(source).unwrap();
+            assert_eq!(format_source(&formatted).unwrap(), formatted);
+            for line in formatted.lines() {
+                assert!(line.len() <= MAX_LINE_WIDTH, "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_boot_contracts() {
+        let sources = [
+            include_str!("../../chainstate/stacks/boot/pox.clar"),
+            include_str!("../../chainstate/stacks/boot/costs.clar"),
+            include_str!("../../chainstate/stacks/boot/bns.clar"),
+        ];
+        for source in sources.iter() {
+            let formatted = format_source(source).unwrap();
+            assert_eq!(format_source(&formatted).unwrap(), formatted);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A stable JSON encoding of Clarity ASTs, for tools that need to inspect contract source.
+//!
+//! The serde encodings derived for the AST types follow their Rust definitions, and change with
+//! them.  The encoding here is versioned instead (`AST_JSON_VERSION`), and only gains new fields
+//! within a version.  Every node is an object with a `kind`, its expression `id`, and (in
+//! `developer-mode` builds) its source `span`:
+//!
+//! * `atom`: a symbol, in `name`
+//! * `atom_value`, `literal_value`: a value, with its Clarity representation in `value` and its
+//!   type in `type`
+//! * `list`, `tuple`: the child nodes, in `children`
+//! * `sugared_contract_identifier`: `.contract`, with the contract in `contract_name`
+//! * `sugared_field_identifier`: `.contract.name`, in `contract_name` and `name`
+//! * `field_identifier`: `'SP...contract.name`, in `contract_identifier` and `name`
+//! * `trait_reference`: `<name>`, in `name`.  Once traits are resolved, `trait` holds the
+//!   referenced trait, and `defined` whether it was defined (rather than imported) by this
+//!   contract.
+
+use serde_json::{json, Map, Value as JsonValue};
+
+use vm::ast::types::ContractAST;
+#[cfg(feature = "developer-mode")]
+use vm::representations::Span;
+use vm::representations::{
+    PreSymbolicExpression, PreSymbolicExpressionType, SymbolicExpression, SymbolicExpressionType,
+    TraitDefinition,
+};
+use vm::types::{TraitIdentifier, TypeSignature, Value};
+
+pub const AST_JSON_VERSION: u32 = 1;
+
+fn make_node(kind: &str, id: u64) -> Map<String, JsonValue> {
+    let mut node = Map::new();
+    node.insert("kind".into(), json!(kind));
+    node.insert("id".into(), json!(id));
+    node
+}
+
+#[cfg(feature = "developer-mode")]
+fn add_span(node: &mut Map<String, JsonValue>, span: &Span) {
+    node.insert(
+        "span".into(),
+        json!({
+            "start_line": span.start_line,
+            "start_column": span.start_column,
+            "end_line": span.end_line,
+            "end_column": span.end_column,
+        }),
+    );
+}
+
+fn add_value(node: &mut Map<String, JsonValue>, value: &Value) {
+    node.insert("value".into(), json!(format!("{}", value)));
+    node.insert(
+        "type".into(),
+        json!(format!("{}", TypeSignature::type_of(value))),
+    );
+}
+
+fn add_trait(node: &mut Map<String, JsonValue>, trait_identifier: &TraitIdentifier) {
+    node.insert(
+        "contract_identifier".into(),
+        json!(trait_identifier.contract_identifier.to_string()),
+    );
+    node.insert("name".into(), json!(trait_identifier.name.as_str()));
+}
+
+pub fn pre_expression_to_json(expr: &PreSymbolicExpression) -> JsonValue {
+    use vm::representations::PreSymbolicExpressionType::*;
+
+    let kind = match expr.pre_expr {
+        AtomValue(_) => "atom_value",
+        Atom(_) => "atom",
+        List(_) => "list",
+        Tuple(_) => "tuple",
+        SugaredContractIdentifier(_) => "sugared_contract_identifier",
+        SugaredFieldIdentifier(..) => "sugared_field_identifier",
+        FieldIdentifier(_) => "field_identifier",
+        TraitReference(_) => "trait_reference",
+    };
+    let mut node = make_node(kind, expr.id);
+    #[cfg(feature = "developer-mode")]
+    add_span(&mut node, &expr.span);
+
+    match expr.pre_expr {
+        PreSymbolicExpressionType::AtomValue(ref value) => add_value(&mut node, value),
+        PreSymbolicExpressionType::Atom(ref name)
+        | PreSymbolicExpressionType::TraitReference(ref name) => {
+            node.insert("name".into(), json!(name.as_str()));
+        }
+        PreSymbolicExpressionType::List(ref children)
+        | PreSymbolicExpressionType::Tuple(ref children) => {
+            node.insert(
+                "children".into(),
+                JsonValue::Array(children.iter().map(pre_expression_to_json).collect()),
+            );
+        }
+        PreSymbolicExpressionType::SugaredContractIdentifier(ref contract_name) => {
+            node.insert("contract_name".into(), json!(contract_name.as_str()));
+        }
+        PreSymbolicExpressionType::SugaredFieldIdentifier(ref contract_name, ref name) => {
+            node.insert("contract_name".into(), json!(contract_name.as_str()));
+            node.insert("name".into(), json!(name.as_str()));
+        }
+        PreSymbolicExpressionType::FieldIdentifier(ref trait_identifier) => {
+            add_trait(&mut node, trait_identifier)
+        }
+    }
+    JsonValue::Object(node)
+}
+
+pub fn expression_to_json(expr: &SymbolicExpression) -> JsonValue {
+    use vm::representations::SymbolicExpressionType::*;
+
+    let kind = match expr.expr {
+        AtomValue(_) => "atom_value",
+        Atom(_) => "atom",
+        List(_) => "list",
+        LiteralValue(_) => "literal_value",
+        Field(_) => "field_identifier",
+        TraitReference(..) => "trait_reference",
+    };
+    let mut node = make_node(kind, expr.id);
+    #[cfg(feature = "developer-mode")]
+    add_span(&mut node, &expr.span);
+
+    match expr.expr {
+        SymbolicExpressionType::AtomValue(ref value)
+        | SymbolicExpressionType::LiteralValue(ref value) => add_value(&mut node, value),
+        SymbolicExpressionType::Atom(ref name) => {
+            node.insert("name".into(), json!(name.as_str()));
+        }
+        SymbolicExpressionType::List(ref children) => {
+            node.insert(
+                "children".into(),
+                JsonValue::Array(children.iter().map(expression_to_json).collect()),
+            );
+        }
+        SymbolicExpressionType::Field(ref trait_identifier) => {
+            add_trait(&mut node, trait_identifier)
+        }
+        SymbolicExpressionType::TraitReference(ref name, ref definition) => {
+            node.insert("name".into(), json!(name.as_str()));
+            node.insert("trait".into(), trait_definition_to_json(definition));
+        }
+    }
+    JsonValue::Object(node)
+}
+
+fn trait_definition_to_json(definition: &TraitDefinition) -> JsonValue {
+    let (defined, trait_identifier) = match definition {
+        TraitDefinition::Defined(trait_identifier) => (true, trait_identifier),
+        TraitDefinition::Imported(trait_identifier) => (false, trait_identifier),
+    };
+    json!({
+        "contract_identifier": trait_identifier.contract_identifier.to_string(),
+        "name": trait_identifier.name.as_str(),
+        "defined": defined,
+    })
+}
+
+/// Encode a contract's AST.  An AST which has only been parsed (see `ast::build_pre_ast()`)
+/// has its `pre_expressions`, and an AST which went through all of `ast::build_ast()` has its
+/// `expressions` and resolved traits.
+pub fn contract_ast_to_json(contract_ast: &ContractAST) -> JsonValue {
+    let mut referenced_traits: Vec<_> = contract_ast
+        .referenced_traits
+        .iter()
+        .map(|(name, definition)| {
+            json!({
+                "name": name.as_str(),
+                "trait": trait_definition_to_json(definition),
+            })
+        })
+        .collect();
+    referenced_traits.sort_by_key(|entry| entry["name"].as_str().unwrap_or("").to_string());
+
+    let mut implemented_traits: Vec<_> = contract_ast
+        .implemented_traits
+        .iter()
+        .map(|trait_identifier| trait_identifier.to_string())
+        .collect();
+    implemented_traits.sort();
+
+    json!({
+        "version": AST_JSON_VERSION,
+        "contract_identifier": contract_ast.contract_identifier.to_string(),
+        "pre_expressions": contract_ast
+            .pre_expressions
+            .iter()
+            .map(pre_expression_to_json)
+            .collect::<Vec<_>>(),
+        "expressions": contract_ast
+            .expressions
+            .iter()
+            .map(expression_to_json)
+            .collect::<Vec<_>>(),
+        "referenced_traits": referenced_traits,
+        "implemented_traits": implemented_traits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vm::ast::{build_ast, build_pre_ast};
+    use vm::types::QualifiedContractIdentifier;
+
+    const CONTRACT: &str =
+        "(use-trait token-trait 'SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.sip-010.ft-trait)
+(define-map balances principal { amount: uint })
+(define-public (pay (token <token-trait>) (amount uint))
+  (begin
+    (map-set balances tx-sender { amount: amount })
+    (ok .other)))";
+
+    #[test]
+    fn test_pre_ast_json() {
+        let contract_id = QualifiedContractIdentifier::local("json").unwrap();
+        let ast = build_pre_ast(&contract_id, CONTRACT).unwrap();
+        let json = contract_ast_to_json(&ast);
+
+        assert_eq!(json["version"], json!(AST_JSON_VERSION));
+        assert_eq!(json["contract_identifier"], json!(contract_id.to_string()));
+        assert_eq!(json["expressions"], json!([]));
+
+        let pre_exprs = json["pre_expressions"].as_array().unwrap();
+        assert_eq!(pre_exprs.len(), 3);
+
+        let use_trait = &pre_exprs[0];
+        assert_eq!(use_trait["kind"], json!("list"));
+        assert_eq!(use_trait["id"], json!(1));
+        assert_eq!(use_trait["children"][0]["kind"], json!("atom"));
+        assert_eq!(use_trait["children"][0]["name"], json!("use-trait"));
+        assert_eq!(use_trait["children"][2]["kind"], json!("field_identifier"));
+        assert_eq!(
+            use_trait["children"][2]["contract_identifier"],
+            json!("SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.sip-010")
+        );
+        assert_eq!(use_trait["children"][2]["name"], json!("ft-trait"));
+
+        // the tuple sugar and the `.other` sugar survive parsing
+        let define_map = &pre_exprs[1];
+        assert_eq!(define_map["children"][3]["kind"], json!("tuple"));
+        let pay = &pre_exprs[2];
+        assert_eq!(
+            pay["children"][1]["children"][1]["children"][1]["kind"],
+            json!("trait_reference")
+        );
+        let ok_other = &pay["children"][2]["children"][2];
+        assert_eq!(
+            ok_other["children"][1]["kind"],
+            json!("sugared_contract_identifier")
+        );
+        assert_eq!(ok_other["children"][1]["contract_name"], json!("other"));
+
+        #[cfg(feature = "developer-mode")]
+        {
+            assert_eq!(
+                pay["span"],
+                json!({"start_line": 3, "start_column": 1, "end_line": 6, "end_column": 17})
+            );
+        }
+    }
+
+    #[test]
+    fn test_ast_json() {
+        let contract_id = QualifiedContractIdentifier::local("json").unwrap();
+        let ast = build_ast(&contract_id, CONTRACT, &mut ()).unwrap();
+        let json = contract_ast_to_json(&ast);
+
+        assert_eq!(json["pre_expressions"], json!([]));
+        assert_eq!(
+            json["referenced_traits"],
+            json!([{
+                "name": "token-trait",
+                "trait": {
+                    "contract_identifier": "SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.sip-010",
+                    "name": "ft-trait",
+                    "defined": false,
+                },
+            }])
+        );
+
+        let exprs = json["expressions"].as_array().unwrap();
+        assert_eq!(exprs.len(), 3);
+
+        // sugar is expanded: `{ amount: uint }` becomes `(tuple ...)`, and `.other` a literal
+        let define_map = &exprs[1];
+        assert_eq!(
+            define_map["children"][3]["children"][0]["name"],
+            json!("tuple")
+        );
+
+        let pay = &exprs[2];
+        let token_arg = &pay["children"][1]["children"][1]["children"][1];
+        assert_eq!(token_arg["kind"], json!("trait_reference"));
+        assert_eq!(token_arg["trait"]["name"], json!("ft-trait"));
+
+        let other = &pay["children"][2]["children"][2]["children"][1];
+        assert_eq!(other["kind"], json!("literal_value"));
+        assert_eq!(
+            other["value"],
+            json!(format!("{}.other", contract_id.issuer))
+        );
+        assert_eq!(other["type"], json!("principal"));
+
+        let amount = &pay["children"][1]["children"][2]["children"][1];
+        assert_eq!(amount["kind"], json!("atom"));
+        assert_eq!(amount["name"], json!("uint"));
+    }
+
+    #[test]
+    fn test_value_json() {
+        let contract_id = QualifiedContractIdentifier::transient();
+        let program = "(+ 1 u2 \"three\")";
+
+        // the parser produces atom values, which sugar expansion turns into literals
+        let pre_ast = build_pre_ast(&contract_id, program).unwrap();
+        let pre_json = contract_ast_to_json(&pre_ast);
+        assert_eq!(
+            pre_json["pre_expressions"][0]["children"][1]["kind"],
+            json!("atom_value")
+        );
+
+        let ast = build_ast(&contract_id, program, &mut ()).unwrap();
+        let json = contract_ast_to_json(&ast);
+        let args = &json["expressions"][0]["children"];
+        assert_eq!(args[1]["kind"], json!("literal_value"));
+        assert_eq!(args[1]["value"], json!("1"));
+        assert_eq!(args[1]["type"], json!("int"));
+        assert_eq!(args[2]["value"], json!("u2"));
+        assert_eq!(args[2]["type"], json!("uint"));
+        assert_eq!(args[3]["value"], json!("\"three\""));
+        assert_eq!(args[3]["type"], json!("(string-ascii 5)"));
+    }
+}
@@ -16,6 +16,8 @@
 
 pub mod definition_sorter;
 pub mod expression_identifier;
+pub mod formatter;
+pub mod json;
 pub mod parser;
 pub mod traits_resolver;
 
@@ -69,6 +71,19 @@ pub fn build_ast<T: CostTracker>(
     Ok(contract_ast)
 }
 
+/// Parse a contract without running any of the later AST passes, so the result still has its
+/// `pre_expressions` (with sugar like `{ a: 1 }` and `.contract` intact), numbered in order.
+pub fn build_pre_ast(
+    contract_identifier: &QualifiedContractIdentifier,
+    source_code: &str,
+) -> ParseResult<ContractAST> {
+    let pre_expressions = parser::parse(source_code)?;
+    let mut contract_ast = ContractAST::new(contract_identifier.clone(), pre_expressions);
+    StackDepthChecker::run_pass(&mut contract_ast)?;
+    ExpressionIdentifier::run_pre_expression_pass(&mut contract_ast)?;
+    Ok(contract_ast)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,402 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use vm::analysis::ContractAnalysis;
+use vm::representations::ClarityName;
+use vm::types::signatures::{FunctionType, SequenceSubtype, StringSubtype};
+use vm::types::TypeSignature;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static",
+    "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// `get-balance?` => `get_balance`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            snake.push(c.to_ascii_lowercase());
+        } else if !snake.ends_with('_') {
+            snake.push('_');
+        }
+    }
+    let mut snake = snake.trim_matches('_').to_string();
+    if snake.is_empty() || snake.starts_with(|c: char| c.is_ascii_digit()) {
+        snake.insert(0, '_');
+    }
+    if RUST_KEYWORDS.contains(&snake.as_str()) {
+        snake.push('_');
+    }
+    snake
+}
+
+/// `token-a` => `TokenA`
+fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    let mut upper = true;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if upper {
+                camel.push(c.to_ascii_uppercase());
+            } else {
+                camel.push(c);
+            }
+            upper = false;
+        } else {
+            upper = true;
+        }
+    }
+    if camel.is_empty() || camel.starts_with(|c: char| c.is_ascii_digit()) {
+        camel.insert(0, 'T');
+    }
+    camel
+}
+
+/// Make `name` distinct from every name in `used` by appending a number, and reserve it
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut suffix = 2;
+    while used.contains(&candidate) {
+        candidate = format!("{}{}", name, suffix);
+        suffix += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+fn has_unknown_type(signature: &TypeSignature) -> bool {
+    match signature {
+        TypeSignature::NoType => true,
+        TypeSignature::OptionalType(inner) => has_unknown_type(inner),
+        TypeSignature::ResponseType(inner) => {
+            has_unknown_type(&inner.0) || has_unknown_type(&inner.1)
+        }
+        TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => {
+            has_unknown_type(list_type.get_list_item_type())
+        }
+        TypeSignature::TupleType(tuple_type) => {
+            tuple_type.get_type_map().values().any(has_unknown_type)
+        }
+        _ => false,
+    }
+}
+
+/// The type an argument is checked against.  Trait references are passed as contract
+/// principals.
+fn argument_type_repr(signature: &TypeSignature) -> String {
+    match signature {
+        TypeSignature::TraitReferenceType(_) => "principal".to_string(),
+        _ => signature.to_string(),
+    }
+}
+
+struct Generator {
+    /// rendered structs for tuple types
+    structs: Vec<String>,
+    struct_names: HashSet<String>,
+}
+
+impl Generator {
+    /// The Rust type standing in for a Clarity type, and an expression converting `expr` of
+    /// that type into a `Value`.  Tuple types get a struct, named after `name_hint`.
+    fn convert(
+        &mut self,
+        signature: &TypeSignature,
+        expr: &str,
+        name_hint: &str,
+        depth: usize,
+    ) -> (String, String) {
+        let var = format!("x{}", depth);
+        match signature {
+            TypeSignature::IntType => ("i128".into(), format!("Value::Int({})", expr)),
+            TypeSignature::UIntType => ("u128".into(), format!("Value::UInt({})", expr)),
+            TypeSignature::BoolType => ("bool".into(), format!("Value::Bool({})", expr)),
+            TypeSignature::PrincipalType => (
+                "PrincipalData".into(),
+                format!("Value::Principal({})", expr),
+            ),
+            TypeSignature::TraitReferenceType(_) => (
+                "QualifiedContractIdentifier".into(),
+                format!("Value::Principal(PrincipalData::Contract({}))", expr),
+            ),
+            TypeSignature::SequenceType(SequenceSubtype::BufferType(_)) => {
+                ("Vec<u8>".into(), format!("bindings::buff({})?", expr))
+            }
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(_))) => (
+                "String".into(),
+                format!("bindings::string_ascii({})?", expr),
+            ),
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(_))) => {
+                ("String".into(), format!("bindings::string_utf8({})?", expr))
+            }
+            TypeSignature::OptionalType(inner) => {
+                let (inner_type, inner_expr) = self.convert(inner, &var, name_hint, depth + 1);
+                (
+                    format!("Option<{}>", inner_type),
+                    format!(
+                        "match {} {{ Some({}) => bindings::some({})?, None => Value::none() }}",
+                        expr, var, inner_expr
+                    ),
+                )
+            }
+            TypeSignature::ResponseType(inner) => {
+                let (ok_type, ok_expr) =
+                    self.convert(&inner.0, &var, &format!("{}Ok", name_hint), depth + 1);
+                let (err_type, err_expr) =
+                    self.convert(&inner.1, &var, &format!("{}Err", name_hint), depth + 1);
+                (
+                    format!("Result<{}, {}>", ok_type, err_type),
+                    format!(
+                        "match {} {{ Ok({}) => bindings::okay({})?, Err({}) => bindings::error({})? }}",
+                        expr, var, ok_expr, var, err_expr
+                    ),
+                )
+            }
+            TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => {
+                let (item_type, item_expr) = self.convert(
+                    list_type.get_list_item_type(),
+                    &var,
+                    &format!("{}Item", name_hint),
+                    depth + 1,
+                );
+                (
+                    format!("Vec<{}>", item_type),
+                    format!(
+                        "bindings::list({}.into_iter().map(|{}| -> Result<Value, BindingError> {{ Ok({}) }}).collect::<Result<Vec<_>, _>>()?)?",
+                        expr, var, item_expr
+                    ),
+                )
+            }
+            TypeSignature::TupleType(tuple_type) => {
+                let struct_name =
+                    self.tuple_struct(tuple_type.get_type_map(), signature, name_hint);
+                (struct_name, format!("{}.into_value()?", expr))
+            }
+            TypeSignature::NoType => ("Value".into(), expr.to_string()),
+        }
+    }
+
+    fn tuple_struct(
+        &mut self,
+        fields: &BTreeMap<ClarityName, TypeSignature>,
+        signature: &TypeSignature,
+        name_hint: &str,
+    ) -> String {
+        let struct_name = unique_name(name_hint.to_string(), &mut self.struct_names);
+        let mut field_names = HashSet::new();
+        let mut declarations = String::new();
+        let mut conversions = String::new();
+        for (name, field_type) in fields.iter() {
+            let field_name = unique_name(snake_case(name), &mut field_names);
+            let (rust_type, conversion) = self.convert(
+                field_type,
+                &format!("self.{}", field_name),
+                &format!("{}{}", struct_name, camel_case(name)),
+                0,
+            );
+            writeln!(declarations, "    pub {}: {},", field_name, rust_type).unwrap();
+            writeln!(conversions, "            (\"{}\", {}),", name, conversion).unwrap();
+        }
+
+        let mut rendered = String::new();
+        writeln!(rendered, "/// `{}`", signature).unwrap();
+        writeln!(rendered, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+        writeln!(rendered, "pub struct {} {{", struct_name).unwrap();
+        rendered.push_str(&declarations);
+        writeln!(rendered, "}}\n").unwrap();
+        writeln!(rendered, "impl {} {{", struct_name).unwrap();
+        writeln!(
+            rendered,
+            "    pub fn into_value(self) -> Result<Value, BindingError> {{"
+        )
+        .unwrap();
+        writeln!(rendered, "        bindings::tuple(vec![").unwrap();
+        rendered.push_str(&conversions);
+        writeln!(rendered, "        ])").unwrap();
+        writeln!(rendered, "    }}").unwrap();
+        writeln!(rendered, "}}").unwrap();
+        self.structs.push(rendered);
+
+        struct_name
+    }
+}
+
+/// Generate Rust source for a struct with a call-builder and a result decoder for each public
+/// and read-only function of the analyzed contract.  `crate_name` is the name the generated code
+/// uses for this crate (usually `blockstack_lib`).
+pub fn generate_bindings(analysis: &ContractAnalysis, crate_name: &str) -> String {
+    let contract_struct = camel_case(&analysis.contract_identifier.name);
+    let mut generator = Generator {
+        structs: vec![],
+        struct_names: HashSet::new(),
+    };
+    generator.struct_names.insert(contract_struct.clone());
+
+    let mut method_names: HashSet<String> = ["new".to_string()].iter().cloned().collect();
+    let mut methods = vec![];
+    let functions = analysis
+        .public_function_types
+        .iter()
+        .map(|f| ("public", f))
+        .chain(
+            analysis
+                .read_only_function_types
+                .iter()
+                .map(|f| ("read-only", f)),
+        );
+    for (kind, (name, function_type)) in functions {
+        let function = match function_type {
+            FunctionType::Fixed(function) => function,
+            // user-defined functions always have fixed signatures
+            _ => continue,
+        };
+        let method_name = unique_name(snake_case(name), &mut method_names);
+        let decoder_name = unique_name(format!("decode_{}", method_name), &mut method_names);
+        let name_hint = camel_case(name);
+
+        let mut params = vec!["&self".to_string()];
+        let mut args = String::new();
+        let mut clarity_args = String::new();
+        let mut arg_names = HashSet::new();
+        for arg in function.args.iter() {
+            let arg_name = unique_name(snake_case(&arg.name), &mut arg_names);
+            let (rust_type, conversion) = generator.convert(
+                &arg.signature,
+                &arg_name,
+                &format!("{}{}", name_hint, camel_case(&arg.name)),
+                0,
+            );
+            params.push(format!("{}: {}", arg_name, rust_type));
+            write!(
+                args,
+                "\n            .arg(\"{}\", \"{}\", {})?",
+                arg.name,
+                argument_type_repr(&arg.signature),
+                conversion
+            )
+            .unwrap();
+            write!(clarity_args, " ({} {})", arg.name, arg.signature).unwrap();
+        }
+
+        let returns = if has_unknown_type(&function.returns) {
+            "None".to_string()
+        } else {
+            format!("Some(\"{}\")", function.returns)
+        };
+
+        let mut rendered = String::new();
+        writeln!(
+            rendered,
+            "    /// Call the {} function `({}{})`",
+            kind, name, clarity_args
+        )
+        .unwrap();
+        writeln!(
+            rendered,
+            "    pub fn {}({}) -> Result<ContractCall, BindingError> {{",
+            method_name,
+            params.join(", ")
+        )
+        .unwrap();
+        writeln!(
+            rendered,
+            "        Ok(ContractCall::new(&self.contract_identifier, \"{}\"){})",
+            name, args
+        )
+        .unwrap();
+        writeln!(rendered, "    }}\n").unwrap();
+        writeln!(
+            rendered,
+            "    /// Decode the result of `{}`, of type `{}`",
+            name, function.returns
+        )
+        .unwrap();
+        writeln!(
+            rendered,
+            "    pub fn {}(result_hex: &str) -> Result<Value, BindingError> {{",
+            decoder_name
+        )
+        .unwrap();
+        writeln!(
+            rendered,
+            "        bindings::decode_result(result_hex, {})",
+            returns
+        )
+        .unwrap();
+        writeln!(rendered, "    }}").unwrap();
+        methods.push(rendered);
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// Rust bindings for the Clarity contract `{}`.",
+        analysis.contract_identifier
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "// Generated by `clarity-cli bindings`; do not edit.\n"
+    )
+    .unwrap();
+    writeln!(out, "#[allow(unused_imports)]").unwrap();
+    writeln!(
+        out,
+        "use {}::vm::bindings::{{self, BindingError, ContractCall}};",
+        crate_name
+    )
+    .unwrap();
+    writeln!(out, "#[allow(unused_imports)]").unwrap();
+    writeln!(
+        out,
+        "use {}::vm::types::{{PrincipalData, QualifiedContractIdentifier}};",
+        crate_name
+    )
+    .unwrap();
+    writeln!(out, "use {}::vm::Value;\n", crate_name).unwrap();
+    for rendered in generator.structs.iter() {
+        writeln!(out, "{}", rendered).unwrap();
+    }
+    writeln!(out, "/// Calls to `{}`", analysis.contract_identifier).unwrap();
+    writeln!(out, "pub struct {} {{", contract_struct).unwrap();
+    writeln!(
+        out,
+        "    pub contract_identifier: QualifiedContractIdentifier,"
+    )
+    .unwrap();
+    writeln!(out, "}}\n").unwrap();
+    writeln!(out, "impl {} {{", contract_struct).unwrap();
+    writeln!(
+        out,
+        "    pub fn new(contract_identifier: QualifiedContractIdentifier) -> {} {{",
+        contract_struct
+    )
+    .unwrap();
+    writeln!(out, "        {} {{ contract_identifier }}", contract_struct).unwrap();
+    writeln!(out, "    }}").unwrap();
+    for rendered in methods.iter() {
+        writeln!(out, "\n{}", rendered.trim_end()).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
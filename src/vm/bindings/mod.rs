@@ -0,0 +1,184 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Support code for the Rust bindings produced by `generator::generate_bindings` (and
+//! `clarity-cli bindings`).  Generated call-builders encode their arguments into Clarity values
+//! with the helpers here, and check them against the function's declared argument types before
+//! producing a `ContractCall`.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+
+use chainstate::stacks::{TransactionContractCall, TransactionPayload};
+use types::chainstate::StacksAddress;
+use vm::ast::parse;
+use vm::database::ClaritySerializable;
+use vm::errors::RuntimeErrorType;
+use vm::representations::ClarityName;
+use vm::types::{QualifiedContractIdentifier, TupleData, TypeSignature, Value};
+
+pub mod generator;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindingError {
+    /// an argument does not fit the type the function declares for it
+    InvalidArgument { name: String, expected: String },
+    /// a Clarity value could not be constructed, e.g., because a buffer is too long
+    InvalidValue(String),
+    /// a function result could not be decoded
+    InvalidResult(String),
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BindingError::InvalidArgument { name, expected } => {
+                write!(f, "argument `{}` is not a {}", name, expected)
+            }
+            BindingError::InvalidValue(e) => write!(f, "invalid Clarity value: {}", e),
+            BindingError::InvalidResult(e) => write!(f, "invalid function result: {}", e),
+        }
+    }
+}
+
+impl error::Error for BindingError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+fn parse_type(type_repr: &str) -> Result<TypeSignature, String> {
+    let expressions =
+        parse(&QualifiedContractIdentifier::transient(), type_repr).map_err(|e| e.to_string())?;
+    match expressions.as_slice() {
+        [expression] => {
+            TypeSignature::parse_type_repr(expression, &mut ()).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("not a type: {}", type_repr)),
+    }
+}
+
+/// A call to a contract function, with its arguments encoded and type-checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractCall {
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub function_name: String,
+    pub args: Vec<Value>,
+}
+
+impl ContractCall {
+    pub fn new(contract_identifier: &QualifiedContractIdentifier, function_name: &str) -> Self {
+        ContractCall {
+            contract_identifier: contract_identifier.clone(),
+            function_name: function_name.to_string(),
+            args: vec![],
+        }
+    }
+
+    /// Append the argument `name`, which the function declares to be of type `type_repr`
+    pub fn arg(mut self, name: &str, type_repr: &str, value: Value) -> Result<Self, BindingError> {
+        let expected = parse_type(type_repr).map_err(BindingError::InvalidValue)?;
+        if !expected.admits(&value) {
+            return Err(BindingError::InvalidArgument {
+                name: name.to_string(),
+                expected: type_repr.to_string(),
+            });
+        }
+        self.args.push(value);
+        Ok(self)
+    }
+
+    /// The arguments, serialized as the RPC interface's read-only call endpoint expects them
+    pub fn hex_args(&self) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| format!("0x{}", arg.serialize()))
+            .collect()
+    }
+
+    /// The payload of a transaction making this call
+    pub fn into_payload(self) -> Result<TransactionPayload, BindingError> {
+        let function_name = ClarityName::try_from(self.function_name)
+            .map_err(|e| BindingError::InvalidValue(e.to_string()))?;
+        Ok(TransactionPayload::ContractCall(TransactionContractCall {
+            address: StacksAddress::from(self.contract_identifier.issuer),
+            contract_name: self.contract_identifier.name,
+            function_name,
+            function_args: self.args,
+        }))
+    }
+}
+
+fn value_error<E: fmt::Display>(e: E) -> BindingError {
+    BindingError::InvalidValue(e.to_string())
+}
+
+pub fn buff(data: Vec<u8>) -> Result<Value, BindingError> {
+    Value::buff_from(data).map_err(value_error)
+}
+
+pub fn string_ascii(data: String) -> Result<Value, BindingError> {
+    Value::string_ascii_from_bytes(data.into_bytes()).map_err(value_error)
+}
+
+pub fn string_utf8(data: String) -> Result<Value, BindingError> {
+    Value::string_utf8_from_bytes(data.into_bytes()).map_err(value_error)
+}
+
+pub fn some(data: Value) -> Result<Value, BindingError> {
+    Value::some(data).map_err(value_error)
+}
+
+pub fn okay(data: Value) -> Result<Value, BindingError> {
+    Value::okay(data).map_err(value_error)
+}
+
+pub fn error(data: Value) -> Result<Value, BindingError> {
+    Value::error(data).map_err(value_error)
+}
+
+pub fn list(items: Vec<Value>) -> Result<Value, BindingError> {
+    Value::list_from(items).map_err(value_error)
+}
+
+pub fn tuple(fields: Vec<(&str, Value)>) -> Result<Value, BindingError> {
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| Ok((ClarityName::try_from(name.to_string())?, value)))
+        .collect::<Result<Vec<_>, RuntimeErrorType>>()
+        .map_err(value_error)?;
+    TupleData::from_data(fields)
+        .map(Value::from)
+        .map_err(value_error)
+}
+
+/// Decode a function result, as returned (hex-encoded) by the RPC interface.  Results whose
+/// type the analysis could not fully determine (`None`) are decoded without one.
+pub fn decode_result(result_hex: &str, type_repr: Option<&str>) -> Result<Value, BindingError> {
+    let result_hex = result_hex.trim_start_matches("0x");
+    match type_repr {
+        Some(type_repr) => {
+            let expected = parse_type(type_repr).map_err(BindingError::InvalidResult)?;
+            Value::try_deserialize_hex(result_hex, &expected)
+        }
+        None => Value::try_deserialize_hex_untyped(result_hex),
+    }
+    .map_err(|e| BindingError::InvalidResult(e.to_string()))
+}
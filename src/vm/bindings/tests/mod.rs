@@ -0,0 +1,167 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::chainstate::stacks::TransactionPayload;
+use crate::clarity_vm::database::MemoryBackingStore;
+use crate::core::StacksEpochId;
+use vm::analysis::{run_analysis, ContractAnalysis};
+use vm::ast::parse;
+use vm::bindings::generator::generate_bindings;
+use vm::bindings::BindingError;
+use vm::costs::LimitedCostTracker;
+use vm::database::ClaritySerializable;
+use vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, Value};
+
+/// The bindings generated for `TOKEN_CONTRACT`, checked in so that they are compiled and
+/// exercised here.
+#[allow(clippy::all)]
+#[rustfmt::skip]
+mod token_bindings;
+
+use self::token_bindings::{ApproveManyApprovalsItem, Token};
+
+const TOKEN_CONTRACT: &str = "(define-trait transferable ((transfer (uint principal) (response bool uint))))
+(define-fungible-token token)
+(define-public (transfer! (amount uint) (recipient principal) (memo (optional (buff 34))))
+  (begin (asserts! (> amount u0) (err u1)) (ok true)))
+(define-public (approve-many (approvals (list 10 { spender: principal, amount: uint })) (note (string-ascii 20)))
+  (ok (len approvals)))
+(define-public (forward (target <transferable>) (amount uint))
+  (contract-call? target transfer amount tx-sender))
+(define-read-only (get-balance (who principal)) (ok (ft-get-balance token who)))
+(define-read-only (type (label (string-utf8 10)) (result (response int bool))) (ok label))
+(define-private (helper) true)";
+
+fn analyze(contract_identifier: &QualifiedContractIdentifier, source: &str) -> ContractAnalysis {
+    let mut expressions = parse(contract_identifier, source).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    run_analysis(
+        contract_identifier,
+        &mut expressions,
+        &mut analysis_db,
+        false,
+        LimitedCostTracker::new_free(),
+        StacksEpochId::latest(),
+    )
+    .unwrap()
+}
+
+fn token_contract_identifier() -> QualifiedContractIdentifier {
+    QualifiedContractIdentifier::parse("SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.token").unwrap()
+}
+
+#[test]
+fn test_generate_bindings() {
+    let analysis = analyze(&token_contract_identifier(), TOKEN_CONTRACT);
+    let generated = generate_bindings(&analysis, "crate");
+    assert_eq!(generated, include_str!("token_bindings.rs"));
+}
+
+#[test]
+fn test_generated_call_builders() {
+    let token = Token::new(token_contract_identifier());
+    let recipient = PrincipalData::from(StandardPrincipalData::transient());
+
+    let call = token
+        .transfer(10, recipient.clone(), Some(vec![1, 2, 3]))
+        .unwrap();
+    assert_eq!(call.function_name, "transfer!");
+    assert_eq!(
+        call.args,
+        vec![
+            Value::UInt(10),
+            Value::Principal(recipient.clone()),
+            Value::some(Value::buff_from(vec![1, 2, 3]).unwrap()).unwrap(),
+        ]
+    );
+    assert_eq!(
+        call.hex_args()[0],
+        format!("0x{}", Value::UInt(10).serialize())
+    );
+    match call.into_payload().unwrap() {
+        TransactionPayload::ContractCall(contract_call) => {
+            assert_eq!(contract_call.contract_name.as_str(), "token");
+            assert_eq!(contract_call.function_name.as_str(), "transfer!");
+            assert_eq!(contract_call.function_args.len(), 3);
+        }
+        _ => panic!("expected a contract call"),
+    }
+
+    // arguments are checked against the declared types
+    assert_eq!(
+        token.transfer(10, recipient.clone(), Some(vec![0; 35])),
+        Err(BindingError::InvalidArgument {
+            name: "memo".to_string(),
+            expected: "(optional (buff 34))".to_string(),
+        })
+    );
+    let approvals = vec![
+        ApproveManyApprovalsItem {
+            amount: 1,
+            spender: recipient.clone(),
+        };
+        11
+    ];
+    assert!(token.approve_many(approvals, "note".into()).is_err());
+    let call = token
+        .approve_many(
+            vec![ApproveManyApprovalsItem {
+                amount: 1,
+                spender: recipient.clone(),
+            }],
+            "note".into(),
+        )
+        .unwrap();
+    assert_eq!(
+        call.args[0].to_string(),
+        format!("((tuple (amount u1) (spender {})))", recipient)
+    );
+    assert!(token
+        .approve_many(vec![], "not ascii: \u{e9}".into())
+        .is_err());
+
+    let call = token
+        .forward(QualifiedContractIdentifier::local("other").unwrap(), 1)
+        .unwrap();
+    assert_eq!(
+        call.args[0],
+        Value::from(QualifiedContractIdentifier::local("other").unwrap())
+    );
+    let call = token.type_("\u{e9}t\u{e9}".into(), Err(true)).unwrap();
+    assert_eq!(call.args[1], Value::error(Value::Bool(true)).unwrap());
+}
+
+#[test]
+fn test_generated_result_decoders() {
+    let result = Value::okay(Value::Bool(true)).unwrap();
+    assert_eq!(Token::decode_transfer(&result.serialize()).unwrap(), result);
+    assert_eq!(
+        Token::decode_forward(&format!("0x{}", result.serialize())).unwrap(),
+        result
+    );
+    // a result that does not match the declared type
+    let wrong = Value::okay(Value::UInt(1)).unwrap();
+    assert!(matches!(
+        Token::decode_transfer(&wrong.serialize()),
+        Err(BindingError::InvalidResult(_))
+    ));
+    // results whose type is only partially known are decoded untyped
+    assert_eq!(
+        Token::decode_get_balance(&wrong.serialize()).unwrap(),
+        wrong
+    );
+}
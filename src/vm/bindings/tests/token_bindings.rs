@@ -0,0 +1,95 @@
+// Rust bindings for the Clarity contract `SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.token`.
+// Generated by `clarity-cli bindings`; do not edit.
+
+#[allow(unused_imports)]
+use crate::vm::bindings::{self, BindingError, ContractCall};
+#[allow(unused_imports)]
+use crate::vm::types::{PrincipalData, QualifiedContractIdentifier};
+use crate::vm::Value;
+
+/// `(tuple (amount uint) (spender principal))`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproveManyApprovalsItem {
+    pub amount: u128,
+    pub spender: PrincipalData,
+}
+
+impl ApproveManyApprovalsItem {
+    pub fn into_value(self) -> Result<Value, BindingError> {
+        bindings::tuple(vec![
+            ("amount", Value::UInt(self.amount)),
+            ("spender", Value::Principal(self.spender)),
+        ])
+    }
+}
+
+/// Calls to `SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.token`
+pub struct Token {
+    pub contract_identifier: QualifiedContractIdentifier,
+}
+
+impl Token {
+    pub fn new(contract_identifier: QualifiedContractIdentifier) -> Token {
+        Token { contract_identifier }
+    }
+
+    /// Call the public function `(approve-many (approvals (list 10 (tuple (amount uint) (spender principal)))) (note (string-ascii 20)))`
+    pub fn approve_many(&self, approvals: Vec<ApproveManyApprovalsItem>, note: String) -> Result<ContractCall, BindingError> {
+        Ok(ContractCall::new(&self.contract_identifier, "approve-many")
+            .arg("approvals", "(list 10 (tuple (amount uint) (spender principal)))", bindings::list(approvals.into_iter().map(|x0| -> Result<Value, BindingError> { Ok(x0.into_value()?) }).collect::<Result<Vec<_>, _>>()?)?)?
+            .arg("note", "(string-ascii 20)", bindings::string_ascii(note)?)?)
+    }
+
+    /// Decode the result of `approve-many`, of type `(response uint UnknownType)`
+    pub fn decode_approve_many(result_hex: &str) -> Result<Value, BindingError> {
+        bindings::decode_result(result_hex, None)
+    }
+
+    /// Call the public function `(forward (target <SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.token.transferable>) (amount uint))`
+    pub fn forward(&self, target: QualifiedContractIdentifier, amount: u128) -> Result<ContractCall, BindingError> {
+        Ok(ContractCall::new(&self.contract_identifier, "forward")
+            .arg("target", "principal", Value::Principal(PrincipalData::Contract(target)))?
+            .arg("amount", "uint", Value::UInt(amount))?)
+    }
+
+    /// Decode the result of `forward`, of type `(response bool uint)`
+    pub fn decode_forward(result_hex: &str) -> Result<Value, BindingError> {
+        bindings::decode_result(result_hex, Some("(response bool uint)"))
+    }
+
+    /// Call the public function `(transfer! (amount uint) (recipient principal) (memo (optional (buff 34))))`
+    pub fn transfer(&self, amount: u128, recipient: PrincipalData, memo: Option<Vec<u8>>) -> Result<ContractCall, BindingError> {
+        Ok(ContractCall::new(&self.contract_identifier, "transfer!")
+            .arg("amount", "uint", Value::UInt(amount))?
+            .arg("recipient", "principal", Value::Principal(recipient))?
+            .arg("memo", "(optional (buff 34))", match memo { Some(x0) => bindings::some(bindings::buff(x0)?)?, None => Value::none() })?)
+    }
+
+    /// Decode the result of `transfer!`, of type `(response bool uint)`
+    pub fn decode_transfer(result_hex: &str) -> Result<Value, BindingError> {
+        bindings::decode_result(result_hex, Some("(response bool uint)"))
+    }
+
+    /// Call the read-only function `(get-balance (who principal))`
+    pub fn get_balance(&self, who: PrincipalData) -> Result<ContractCall, BindingError> {
+        Ok(ContractCall::new(&self.contract_identifier, "get-balance")
+            .arg("who", "principal", Value::Principal(who))?)
+    }
+
+    /// Decode the result of `get-balance`, of type `(response uint UnknownType)`
+    pub fn decode_get_balance(result_hex: &str) -> Result<Value, BindingError> {
+        bindings::decode_result(result_hex, None)
+    }
+
+    /// Call the read-only function `(type (label (string-utf8 10)) (result (response int bool)))`
+    pub fn type_(&self, label: String, result: Result<i128, bool>) -> Result<ContractCall, BindingError> {
+        Ok(ContractCall::new(&self.contract_identifier, "type")
+            .arg("label", "(string-utf8 10)", bindings::string_utf8(label)?)?
+            .arg("result", "(response int bool)", match result { Ok(x0) => bindings::okay(Value::Int(x0))?, Err(x0) => bindings::error(Value::Bool(x0))? })?)
+    }
+
+    /// Decode the result of `type`, of type `(response (string-utf8 10) UnknownType)`
+    pub fn decode_type_(result_hex: &str) -> Result<Value, BindingError> {
+        bindings::decode_result(result_hex, None)
+    }
+}
@@ -79,19 +79,21 @@ pub enum NativeHandle {
 }
 
 impl NativeHandle {
-    pub fn apply(&self, mut args: Vec<Value>) -> Result<Value> {
+    /// Apply the function to `args`, leaving the vector empty.  Single- and double-argument
+    /// functions leave its buffer in place for reuse.
+    pub fn apply(&self, args: &mut Vec<Value>) -> Result<Value> {
         match self {
             Self::SingleArg(function) => {
-                check_argument_count(1, &args)?;
+                check_argument_count(1, args)?;
                 function(args.pop().unwrap())
             }
             Self::DoubleArg(function) => {
-                check_argument_count(2, &args)?;
+                check_argument_count(2, args)?;
                 let second = args.pop().unwrap();
                 let first = args.pop().unwrap();
                 function(first, second)
             }
-            Self::MoreArg(function) => function(args),
+            Self::MoreArg(function) => function(std::mem::take(args)),
         }
     }
 }
@@ -150,7 +152,6 @@ impl DefinedFunction {
             )?;
         }
 
-        let mut context = LocalContext::new();
         if args.len() != self.arguments.len() {
             Err(CheckErrors::IncorrectArgumentCount(
                 self.arguments.len(),
@@ -158,14 +159,28 @@ impl DefinedFunction {
             ))?
         }
 
-        let mut arg_iterator: Vec<_> = self
+        let mut context = LocalContext::new();
+        context.variables = env.global_context.eval_pool.take_variables();
+        let result = self.bind_and_eval(&mut context, args, env);
+        env.global_context
+            .eval_pool
+            .return_variables(context.variables);
+        result
+    }
+
+    fn bind_and_eval(
+        &self,
+        context: &mut LocalContext,
+        args: &[Value],
+        env: &mut Environment,
+    ) -> Result<Value> {
+        let arg_iterator = self
             .arguments
             .iter()
             .zip(self.arg_types.iter())
-            .zip(args.iter())
-            .collect();
+            .zip(args.iter());
 
-        for arg in arg_iterator.drain(..) {
+        for arg in arg_iterator {
             let ((name, type_sig), value) = arg;
 
             match (type_sig, value) {
@@ -194,7 +209,7 @@ impl DefinedFunction {
             }
         }
 
-        let result = eval(&self.body, env, &context);
+        let result = eval(&self.body, env, context);
 
         // if the error wasn't actually an error, but a function return,
         //    pull that out and return it.
@@ -286,4 +301,13 @@ impl FunctionIdentifier {
             identifier: identifier,
         }
     }
+
+    /// The name of the function, if this identifies a user-defined function rather than a
+    ///   native one.
+    pub fn user_function_name(&self) -> Option<&str> {
+        if self.identifier.starts_with("_native_:") {
+            return None;
+        }
+        self.identifier.rsplit(':').next()
+    }
 }
@@ -18,6 +18,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::mem::replace;
+use std::time::{Duration, Instant};
 
 use vm::ast;
 use vm::ast::ContractAST;
@@ -33,6 +34,7 @@ use vm::database::{
 };
 use vm::errors::{CheckErrors, InterpreterError, InterpreterResult as Result, RuntimeErrorType};
 use vm::functions::handle_contract_call_special_cases;
+use vm::pool::EvalPool;
 use vm::representations::{ClarityName, ContractName, SymbolicExpression};
 use vm::stx_transfer_consolidated;
 use vm::types::signatures::FunctionSignature;
@@ -40,7 +42,7 @@ use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, TraitIdentifier, TypeSignature,
     Value,
 };
-use vm::{eval, is_reserved};
+use vm::{eval, is_reserved, EvalHook};
 
 use chainstate::stacks::db::StacksChainState;
 use chainstate::stacks::events::*;
@@ -195,10 +197,39 @@ pub struct GlobalContext<'a> {
     pub cost_track: LimitedCostTracker,
     pub mainnet: bool,
     pub coverage_reporting: Option<CoverageReporter>,
+    pub eval_hooks: Option<Vec<Box<dyn EvalHook>>>,
+    execution_limiter: Option<ExecutionLimiter>,
+    pub eval_pool: EvalPool,
     /// This is the epoch of the the block that this transaction is executing within.
     epoch_id: StacksEpochId,
 }
 
+/// Hard limits on an evaluation, on top of its cost limit: how many expressions it may evaluate,
+/// and how long it may run.  Cheap expressions can still take a long time to evaluate, so these
+/// bound the time spent on untrusted input, e.g., read-only calls made over RPC.  Wall-clock time
+/// differs from node to node, so they must never be applied to transactions in blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ExecutionLimits {
+    pub max_steps: Option<u64>,
+    pub max_duration: Option<Duration>,
+}
+
+struct ExecutionLimiter {
+    limits: ExecutionLimits,
+    steps: u64,
+    started: Instant,
+}
+
+impl ExecutionLimiter {
+    fn new(limits: ExecutionLimits) -> ExecutionLimiter {
+        ExecutionLimiter {
+            limits,
+            steps: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ContractContext {
     pub contract_identifier: QualifiedContractIdentifier,
@@ -215,6 +246,10 @@ pub struct ContractContext {
     pub meta_nft: HashMap<ClarityName, NonFungibleTokenMetadata>,
     pub meta_ft: HashMap<ClarityName, FungibleTokenMetadata>,
     pub data_size: u64,
+    /// Epoch the contract was deployed in, which fixes the native functions it can call.
+    ///  Contracts stored without one were all deployed before Stacks 2.1.
+    #[serde(default = "ContractContext::legacy_epoch")]
+    pub epoch: StacksEpochId,
 }
 
 pub struct LocalContext<'a> {
@@ -535,7 +570,7 @@ impl<'a> OwnedEnvironment<'a> {
         let epoch = StacksEpochId::Epoch2_05;
         OwnedEnvironment {
             context: GlobalContext::new(false, database, LimitedCostTracker::new_free(), epoch),
-            default_contract: ContractContext::new(QualifiedContractIdentifier::transient()),
+            default_contract: ContractContext::new(QualifiedContractIdentifier::transient(), epoch),
             call_stack: CallStack::new(),
         }
     }
@@ -550,7 +585,7 @@ impl<'a> OwnedEnvironment<'a> {
             .expect("FAIL: problem instantiating cost tracking");
         OwnedEnvironment {
             context: GlobalContext::new(use_mainnet, database, cost_track, epoch),
-            default_contract: ContractContext::new(QualifiedContractIdentifier::transient()),
+            default_contract: ContractContext::new(QualifiedContractIdentifier::transient(), epoch),
             call_stack: CallStack::new(),
         }
     }
@@ -563,6 +598,31 @@ impl<'a> OwnedEnvironment<'a> {
         self.context.coverage_reporting.take()
     }
 
+    pub fn add_eval_hook(&mut self, hook: Box<dyn EvalHook>) {
+        self.context
+            .eval_hooks
+            .get_or_insert_with(Vec::new)
+            .push(hook);
+    }
+
+    pub fn take_eval_hooks(&mut self) -> Vec<Box<dyn EvalHook>> {
+        self.context.eval_hooks.take().unwrap_or_default()
+    }
+
+    /// Evaluate with the buffers in `pool`, e.g., those left over by the previous transaction
+    pub fn set_eval_pool(&mut self, mut pool: EvalPool) {
+        self.call_stack = pool.take_call_stack();
+        self.context.eval_pool = pool;
+    }
+
+    /// Take back the buffers this environment evaluated with, so the next one can reuse them
+    pub fn take_eval_pool(&mut self) -> EvalPool {
+        let mut pool = std::mem::take(&mut self.context.eval_pool);
+        let call_stack = replace(&mut self.call_stack, CallStack::new());
+        pool.return_call_stack(call_stack);
+        pool
+    }
+
     pub fn new_free(
         mainnet: bool,
         database: ClarityDatabase<'a>,
@@ -575,7 +635,10 @@ impl<'a> OwnedEnvironment<'a> {
                 LimitedCostTracker::new_free(),
                 epoch_id,
             ),
-            default_contract: ContractContext::new(QualifiedContractIdentifier::transient()),
+            default_contract: ContractContext::new(
+                QualifiedContractIdentifier::transient(),
+                epoch_id,
+            ),
             call_stack: CallStack::new(),
         }
     }
@@ -588,7 +651,10 @@ impl<'a> OwnedEnvironment<'a> {
     ) -> OwnedEnvironment<'a> {
         OwnedEnvironment {
             context: GlobalContext::new(mainnet, database, cost_tracker, epoch_id),
-            default_contract: ContractContext::new(QualifiedContractIdentifier::transient()),
+            default_contract: ContractContext::new(
+                QualifiedContractIdentifier::transient(),
+                epoch_id,
+            ),
             call_stack: CallStack::new(),
         }
     }
@@ -1006,6 +1072,26 @@ impl<'a, 'b> Environment<'a, 'b> {
         })
     }
 
+    /// Like `execute_contract`, but aborts with `StepLimitExceeded` or `TimeLimitExceeded` once
+    /// the call runs into `limits`.  Intended for untrusted calls whose result never goes into a
+    /// block.
+    pub fn execute_contract_limited(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        tx_name: &str,
+        args: &[SymbolicExpression],
+        read_only: bool,
+        limits: ExecutionLimits,
+    ) -> Result<Value> {
+        let previous = self
+            .global_context
+            .execution_limiter
+            .replace(ExecutionLimiter::new(limits));
+        let result = self.execute_contract(contract_identifier, tx_name, args, read_only);
+        self.global_context.execution_limiter = previous;
+        result
+    }
+
     pub fn execute_function_as_transaction(
         &mut self,
         function: &DefinedFunction,
@@ -1389,7 +1475,41 @@ impl<'a> GlobalContext<'a> {
             mainnet,
             epoch_id,
             coverage_reporting: None,
+            eval_hooks: None,
+            execution_limiter: None,
+            eval_pool: EvalPool::new(),
+        }
+    }
+
+    pub fn epoch(&self) -> &StacksEpochId {
+        &self.epoch_id
+    }
+
+    /// Enforce `limits` on everything evaluated from now on, until they are replaced
+    pub fn set_execution_limits(&mut self, limits: Option<ExecutionLimits>) {
+        self.execution_limiter = limits.map(ExecutionLimiter::new);
+    }
+
+    /// Count an evaluation step against the execution limits, if any
+    pub fn check_execution_limits(&mut self) -> Result<()> {
+        let limiter = match self.execution_limiter.as_mut() {
+            Some(limiter) => limiter,
+            None => return Ok(()),
+        };
+        limiter.steps += 1;
+        if let Some(max_steps) = limiter.limits.max_steps {
+            if limiter.steps > max_steps {
+                return Err(RuntimeErrorType::StepLimitExceeded(max_steps).into());
+            }
         }
+        if let Some(max_duration) = limiter.limits.max_duration {
+            if limiter.started.elapsed() > max_duration {
+                return Err(
+                    RuntimeErrorType::TimeLimitExceeded(max_duration.as_millis() as u64).into(),
+                );
+            }
+        }
+        Ok(())
     }
 
     pub fn is_top_level(&self) -> bool {
@@ -1553,9 +1673,10 @@ impl<'a> GlobalContext<'a> {
 }
 
 impl ContractContext {
-    pub fn new(contract_identifier: QualifiedContractIdentifier) -> Self {
+    pub fn new(contract_identifier: QualifiedContractIdentifier, epoch: StacksEpochId) -> Self {
         Self {
             contract_identifier,
+            epoch,
             variables: HashMap::new(),
             functions: HashMap::new(),
             defined_traits: HashMap::new(),
@@ -1569,6 +1690,10 @@ impl ContractContext {
         }
     }
 
+    fn legacy_epoch() -> StacksEpochId {
+        StacksEpochId::Epoch20
+    }
+
     pub fn lookup_variable(&self, name: &str) -> Option<&Value> {
         self.variables.get(name)
     }
@@ -1589,7 +1714,7 @@ impl ContractContext {
     }
 
     pub fn is_name_used(&self, name: &str) -> bool {
-        is_reserved(name)
+        is_reserved(name, &self.epoch)
             || self.variables.contains_key(name)
             || self.functions.contains_key(name)
             || self.persisted_names.contains(name)
@@ -1664,6 +1789,12 @@ impl CallStack {
         self.stack.len() + self.apply_depth
     }
 
+    pub fn clear(&mut self) {
+        self.stack.clear();
+        self.set.clear();
+        self.apply_depth = 0;
+    }
+
     pub fn contains(&self, function: &FunctionIdentifier) -> bool {
         self.set.contains(function)
     }
@@ -1703,6 +1834,15 @@ impl CallStack {
         }
     }
 
+    /// The name of the innermost user-defined function on the stack, if any.
+    pub fn innermost_user_function(&self) -> Option<String> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|function| function.user_function_name())
+            .map(|name| name.to_string())
+    }
+
     #[cfg(feature = "developer-mode")]
     pub fn make_stack_trace(&self) -> StackTrace {
         self.stack.clone()
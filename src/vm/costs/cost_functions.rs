@@ -130,4 +130,7 @@ define_named_enum!(ClarityCostFunction {
     NftOwner("cost_nft_owner"),
     NftBurn("cost_nft_burn"),
     PoisonMicroblock("poison_microblock"),
+    StringToInt("cost_string_to_int"),
+    StringToUInt("cost_string_to_uint"),
+    Slice("cost_slice"),
 });
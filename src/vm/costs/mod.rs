@@ -16,6 +16,7 @@
 
 use std::collections::{BTreeMap, HashMap};
 use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
 use std::{cmp, fmt};
 
 use regex::internal::Exec;
@@ -23,7 +24,7 @@ use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
 
 use crate::util::boot::boot_code_id;
-use chainstate::stacks::boot::{COSTS_1_NAME, COSTS_2_NAME};
+use chainstate::stacks::boot::{COSTS_1_NAME, COSTS_2_NAME, COSTS_3_NAME};
 use core::StacksEpochId;
 use vm::ast::ContractAST;
 use vm::contexts::{ContractContext, Environment, GlobalContext, OwnedEnvironment};
@@ -222,6 +223,10 @@ impl CostStateSummary {
     }
 }
 
+/// Constant costs which replace the results of the given cost functions, for private chains
+/// which don't follow the boot cost contracts.
+pub type CostOverrides = HashMap<ClarityCostFunction, ExecutionCost>;
+
 #[derive(Clone)]
 /// This struct holds all of the data required for non-free LimitedCostTracker instances
 pub struct TrackerData {
@@ -238,6 +243,8 @@ pub struct TrackerData {
     ///  evaluated, so no epoch identifier is necessary.
     epoch: StacksEpochId,
     mainnet: bool,
+    /// if set, cost functions in this map are not evaluated -- their cost is taken from the map
+    cost_overrides: Option<Arc<CostOverrides>>,
 }
 
 #[derive(Clone)]
@@ -628,6 +635,7 @@ impl LimitedCostTracker {
             memory: 0,
             epoch,
             mainnet,
+            cost_overrides: None,
         };
         assert!(clarity_db.is_stack_empty());
         cost_tracker.load_costs(clarity_db, true)?;
@@ -650,6 +658,7 @@ impl LimitedCostTracker {
             memory: 0,
             epoch,
             mainnet,
+            cost_overrides: None,
         };
         cost_tracker.load_costs(clarity_db, false)?;
         Ok(Self::Limited(cost_tracker))
@@ -669,6 +678,14 @@ impl LimitedCostTracker {
         Self::Free
     }
 
+    /// Charge the given constant costs for these cost functions instead of evaluating them.
+    /// Has no effect on a free tracker.
+    pub fn set_cost_overrides(&mut self, cost_overrides: Arc<CostOverrides>) {
+        if let Self::Limited(ref mut data) = self {
+            data.cost_overrides = Some(cost_overrides);
+        }
+    }
+
     fn default_cost_contract_for_epoch(epoch_id: StacksEpochId) -> String {
         match epoch_id {
             StacksEpochId::Epoch10 => {
@@ -676,6 +693,7 @@ impl LimitedCostTracker {
             }
             StacksEpochId::Epoch20 => COSTS_1_NAME.to_string(),
             StacksEpochId::Epoch2_05 => COSTS_2_NAME.to_string(),
+            StacksEpochId::Epoch21 => COSTS_3_NAME.to_string(),
         }
     }
 }
@@ -775,6 +793,39 @@ impl LimitedCostTracker {
             Self::Free => ExecutionCost::max_value(),
         }
     }
+    /// Identifies the cost functions this tracker charges with, if they all come from a single
+    /// cost contract (less any overrides), unmodified by contract-call circuits.  Two trackers
+    /// with the same cost model charge the same costs for the same work.
+    pub fn default_cost_model(&self) -> Option<String> {
+        let data = match self {
+            Self::Limited(data) => data,
+            Self::Free => return Some("free".to_string()),
+        };
+        if !data.contract_call_circuits.is_empty() {
+            return None;
+        }
+        let mut contract_id = None;
+        for (f, cost_function_ref) in data.cost_function_references.iter() {
+            if cost_function_ref.function_name != f.get_name() {
+                return None;
+            }
+            match contract_id {
+                None => contract_id = Some(&cost_function_ref.contract_id),
+                Some(id) if id == &cost_function_ref.contract_id => {}
+                Some(_) => return None,
+            }
+        }
+        let mut cost_model = contract_id.map(|id| format!("{}@{}", id, data.epoch))?;
+        if let Some(ref cost_overrides) = data.cost_overrides {
+            let mut overrides: Vec<_> = cost_overrides
+                .iter()
+                .map(|(f, cost)| format!("{}={}", f.get_name(), cost))
+                .collect();
+            overrides.sort();
+            cost_model.push_str(&format!("+{}", overrides.join(",")));
+        }
+        Some(cost_model)
+    }
 }
 
 fn parse_cost(
@@ -900,6 +951,14 @@ impl CostTracker for LimitedCostTracker {
                 return Ok(ExecutionCost::zero());
             }
             Self::Limited(ref mut data) => {
+                if let Some(cost) = data
+                    .cost_overrides
+                    .as_ref()
+                    .and_then(|overrides| overrides.get(&cost_function))
+                {
+                    return Ok(cost.clone());
+                }
+
                 let cost_function_ref = data
                     .cost_function_references
                     .get(&cost_function)
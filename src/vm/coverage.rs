@@ -5,13 +5,28 @@ use std::{
 };
 
 use serde_json::Value as JsonValue;
+use vm::costs::ExecutionCost;
+use vm::errors::InterpreterResult;
 use vm::types::QualifiedContractIdentifier;
-use vm::SymbolicExpression;
+use vm::{Environment, EvalHook, LocalContext, SymbolicExpression, Value};
 
 use super::functions::define::DefineFunctionsParsed;
+use super::functions::NativeFunctions;
 
 pub struct CoverageReporter {
     executed_lines: HashMap<QualifiedContractIdentifier, HashMap<u32, u64>>,
+    executed_exprs: HashMap<QualifiedContractIdentifier, HashMap<u64, u64>>,
+}
+
+/// One outcome of a branching expression (`if`, `match`, `asserts!`).  `block` is the id of
+/// the branching expression, and `expr_id` is the id of the expression evaluated when this
+/// outcome is taken.
+#[derive(Serialize, Deserialize, Clone)]
+struct BranchInfo {
+    line: u32,
+    block: u64,
+    branch: u32,
+    expr_id: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -19,34 +34,86 @@ struct ContractFileInfo {
     contract: String,
     src_file: String,
     executable_lines: Vec<u32>,
+    #[serde(default)]
+    branches: Vec<BranchInfo>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct CoverageFileInfo {
     coverage: HashMap<String, Vec<(u32, u64)>>,
+    #[serde(default)]
+    executed_exprs: HashMap<String, Vec<(u64, u64)>>,
 }
 
-impl CoverageReporter {
-    pub fn new() -> CoverageReporter {
-        CoverageReporter {
-            executed_lines: HashMap::new(),
+/// Coverage of one contract, summed over all coverage files
+#[derive(Serialize)]
+struct ContractCoverageSummary {
+    contract: String,
+    src_file: String,
+    lines_found: usize,
+    lines_hit: usize,
+    branches_found: usize,
+    branches_hit: usize,
+    /// (line, execution count)
+    lines: Vec<(u32, u64)>,
+    /// (line, block, branch, times taken -- or None if the block was never evaluated)
+    branches: Vec<(u32, u64, u32, Option<u64>)>,
+}
+
+impl EvalHook for CoverageReporter {
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        _context: &LocalContext,
+        expr: &SymbolicExpression,
+    ) {
+        let contract = &env.contract_context.contract_identifier;
+        self.report_eval(expr, contract);
+        if let Some(function_variable) = expr.match_list().and_then(|list| list.first()) {
+            // the function name of an application is never evaluated on its own
+            self.report_eval(function_variable, contract);
         }
     }
 
-    #[cfg(not(feature = "developer-mode"))]
-    pub fn report_eval(
+    fn did_finish_eval(
         &mut self,
+        _env: &mut Environment,
+        _context: &LocalContext,
         _expr: &SymbolicExpression,
-        _contract: &QualifiedContractIdentifier,
+        _cost: &ExecutionCost,
+        _res: &InterpreterResult<Value>,
     ) {
     }
+}
+
+impl CoverageReporter {
+    pub fn new() -> CoverageReporter {
+        CoverageReporter {
+            executed_lines: HashMap::new(),
+            executed_exprs: HashMap::new(),
+        }
+    }
 
-    #[cfg(feature = "developer-mode")]
     pub fn report_eval(
         &mut self,
         expr: &SymbolicExpression,
         contract: &QualifiedContractIdentifier,
     ) {
+        let execution_map_contract = self
+            .executed_exprs
+            .entry(contract.clone())
+            .or_insert_with(HashMap::new);
+        *execution_map_contract.entry(expr.id).or_insert(0) += 1;
+
+        self.report_line(expr, contract);
+    }
+
+    #[cfg(not(feature = "developer-mode"))]
+    fn report_line(&mut self, _expr: &SymbolicExpression, _contract: &QualifiedContractIdentifier) {
+    }
+
+    #[cfg(feature = "developer-mode")]
+    fn report_line(&mut self, expr: &SymbolicExpression, contract: &QualifiedContractIdentifier) {
         if expr.match_list().is_some() {
             // don't count the whole list expression: wait until we've eval'ed the
             //   list components
@@ -83,7 +150,21 @@ impl CoverageReporter {
             coverage.insert(contract.to_string(), executed_lines);
         }
 
-        let out = CoverageFileInfo { coverage };
+        let mut executed_exprs = HashMap::new();
+        for (contract, execution_map) in self.executed_exprs.iter() {
+            let mut exprs: Vec<_> = execution_map
+                .iter()
+                .map(|(expr_id, count)| (*expr_id, *count))
+                .collect();
+            exprs.sort_by_key(|f| f.0);
+
+            executed_exprs.insert(contract.to_string(), exprs);
+        }
+
+        let out = CoverageFileInfo {
+            coverage,
+            executed_exprs,
+        };
         if let Err(e) = serde_json::to_writer(f, &out) {
             error!(
                 "Failed to serialize JSON to coverage file {}: {}",
@@ -157,6 +238,54 @@ impl CoverageReporter {
         lines
     }
 
+    /// Find the outcomes of each branching expression.  `if` and `match` have two outcomes
+    /// each (the two branch bodies), and `asserts!` has one (the thrown value, which is only
+    /// evaluated if the assertion fails).
+    fn branches(exprs: &[SymbolicExpression]) -> Vec<BranchInfo> {
+        let mut branches = vec![];
+        let mut frontier: Vec<_> = exprs.iter().collect();
+        while let Some(cur_expr) = frontier.pop() {
+            let children = match cur_expr.match_list() {
+                Some(children) => children,
+                None => continue,
+            };
+            frontier.extend(children);
+
+            let native = match children
+                .first()
+                .and_then(|f| f.match_atom())
+                .and_then(|name| NativeFunctions::lookup_by_name(name))
+            {
+                Some(native) => native,
+                None => continue,
+            };
+
+            let branch_exprs = match (native, children.len()) {
+                // (if cond then else)
+                (NativeFunctions::If, 4) => vec![&children[2], &children[3]],
+                // (match opt some-name some-branch none-branch)
+                (NativeFunctions::Match, 5) => vec![&children[3], &children[4]],
+                // (match resp ok-name ok-branch err-name err-branch)
+                (NativeFunctions::Match, 6) => vec![&children[3], &children[5]],
+                // (asserts! cond thrown)
+                (NativeFunctions::Asserts, 3) => vec![&children[2]],
+                _ => continue,
+            };
+
+            for (branch, branch_expr) in branch_exprs.into_iter().enumerate() {
+                branches.push(BranchInfo {
+                    line: cur_expr.span.start_line,
+                    block: cur_expr.id,
+                    branch: branch as u32,
+                    expr_id: branch_expr.id,
+                });
+            }
+        }
+
+        branches.sort_by_key(|b| (b.line, b.block, b.branch));
+        branches
+    }
+
     pub fn register_src_file<P: AsRef<std::path::Path> + Copy>(
         contract: &QualifiedContractIdentifier,
         src_file_name: &str,
@@ -166,11 +295,13 @@ impl CoverageReporter {
         let f = File::create(filename)?;
 
         let executable_lines = CoverageReporter::executable_lines(ast);
+        let branches = CoverageReporter::branches(ast);
 
         let json = ContractFileInfo {
             contract: contract.to_string(),
             src_file: src_file_name.to_string(),
             executable_lines,
+            branches,
         };
 
         if let Err(e) = serde_json::to_writer(f, &json) {
@@ -184,20 +315,26 @@ impl CoverageReporter {
         Ok(())
     }
 
-    pub fn produce_lcov<P: AsRef<std::path::Path>>(
-        out_filename: &str,
+    /// Sum up the coverage recorded in `coverage_files` for each contract registered in
+    /// `register_files`.
+    fn summarize<P: AsRef<std::path::Path>>(
         register_files: &[P],
         coverage_files: &[P],
-    ) -> std::io::Result<()> {
-        let mut out = File::create(out_filename)?;
+    ) -> std::io::Result<Vec<ContractCoverageSummary>> {
+        let mut coverage_infos = vec![];
+        for coverage_filename in coverage_files.iter() {
+            let cov_reader = File::open(coverage_filename)?;
+            let coverage: CoverageFileInfo = serde_json::from_reader(cov_reader)?;
+            coverage_infos.push(coverage);
+        }
 
+        let mut summaries = vec![];
         for contract_filename in register_files.iter() {
             let reader = File::open(contract_filename)?;
             let info: ContractFileInfo = serde_json::from_reader(reader)?;
             let mut summed_coverage = BTreeMap::new();
-            for coverage_filename in coverage_files.iter() {
-                let cov_reader = File::open(coverage_filename)?;
-                let coverage: CoverageFileInfo = serde_json::from_reader(cov_reader)?;
+            let mut summed_exprs = HashMap::new();
+            for coverage in coverage_infos.iter() {
                 if let Some(contract_coverage) = coverage.coverage.get(&info.contract) {
                     for (line, count) in contract_coverage.iter() {
                         if let Some(line_count) = summed_coverage.get_mut(line) {
@@ -207,18 +344,85 @@ impl CoverageReporter {
                         }
                     }
                 }
+                if let Some(contract_exprs) = coverage.executed_exprs.get(&info.contract) {
+                    for (expr_id, count) in contract_exprs.iter() {
+                        *summed_exprs.entry(*expr_id).or_insert(0) += *count;
+                    }
+                }
             }
-            writeln!(out, "TN:{}", &info.contract)?;
-            writeln!(out, "SF:{}", &info.src_file)?;
-            for line in info.executable_lines.iter() {
-                let count = summed_coverage.get(line).cloned().unwrap_or(0);
+
+            let lines: Vec<_> = info
+                .executable_lines
+                .iter()
+                .map(|line| (*line, summed_coverage.get(line).cloned().unwrap_or(0)))
+                .collect();
+
+            let branches: Vec<_> = info
+                .branches
+                .iter()
+                .map(|b| {
+                    let taken = if summed_exprs.contains_key(&b.block) {
+                        Some(summed_exprs.get(&b.expr_id).cloned().unwrap_or(0))
+                    } else {
+                        None
+                    };
+                    (b.line, b.block, b.branch, taken)
+                })
+                .collect();
+
+            summaries.push(ContractCoverageSummary {
+                contract: info.contract,
+                src_file: info.src_file,
+                lines_found: info.executable_lines.len(),
+                lines_hit: summed_coverage.len(),
+                branches_found: branches.len(),
+                branches_hit: branches
+                    .iter()
+                    .filter(|(_, _, _, taken)| taken.unwrap_or(0) > 0)
+                    .count(),
+                lines,
+                branches,
+            });
+        }
+
+        Ok(summaries)
+    }
+
+    pub fn produce_lcov<P: AsRef<std::path::Path>>(
+        out_filename: &str,
+        register_files: &[P],
+        coverage_files: &[P],
+    ) -> std::io::Result<()> {
+        let mut out = File::create(out_filename)?;
+
+        for summary in CoverageReporter::summarize(register_files, coverage_files)?.iter() {
+            writeln!(out, "TN:{}", &summary.contract)?;
+            writeln!(out, "SF:{}", &summary.src_file)?;
+            for (line, count) in summary.lines.iter() {
                 writeln!(out, "DA:{},{}", line, count)?;
             }
-            writeln!(out, "LH:{}", summed_coverage.len())?;
-            writeln!(out, "LF:{}", &info.executable_lines.len())?;
+            writeln!(out, "LH:{}", summary.lines_hit)?;
+            writeln!(out, "LF:{}", summary.lines_found)?;
+            for (line, block, branch, taken) in summary.branches.iter() {
+                match taken {
+                    Some(count) => writeln!(out, "BRDA:{},{},{},{}", line, block, branch, count)?,
+                    None => writeln!(out, "BRDA:{},{},{},-", line, block, branch)?,
+                }
+            }
+            writeln!(out, "BRF:{}", summary.branches_found)?;
+            writeln!(out, "BRH:{}", summary.branches_hit)?;
             writeln!(out, "end_of_record")?;
         }
 
         Ok(())
     }
+
+    /// Like `produce_lcov`, but produces a JSON report
+    pub fn produce_json<P: AsRef<std::path::Path>>(
+        register_files: &[P],
+        coverage_files: &[P],
+    ) -> std::io::Result<JsonValue> {
+        let summaries = CoverageReporter::summarize(register_files, coverage_files)?;
+        Ok(serde_json::to_value(&summaries)?)
+    }
 }
@@ -463,6 +463,18 @@ impl<'a> ClarityDatabase<'a> {
             .map(|x| ContractAnalysis::deserialize(&x))
     }
 
+    /// Like `load_contract_analysis`, but also returns the size of the stored analysis, so
+    ///  that the interpreter can charge for reading it.
+    pub fn load_contract_analysis_sized(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+    ) -> Option<(ContractAnalysis, u64)> {
+        self.store
+            .get_metadata(contract_identifier, AnalysisDatabase::storage_key())
+            .ok()?
+            .map(|x| (ContractAnalysis::deserialize(&x), x.len() as u64))
+    }
+
     pub fn get_contract_size(
         &mut self,
         contract_identifier: &QualifiedContractIdentifier,
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::{clone::Clone, cmp::Eq, hash::Hash};
 
 use util::hash::Sha512Trunc256Sum;
@@ -246,6 +246,24 @@ impl<'a> RollbackWrapper<'a> {
         self.stack.len()
     }
 
+    /// Get the keys written in the current (innermost) context, in the order in which they
+    ///   were first written, along with their most recent values.
+    pub fn get_pending_writes(&self) -> Vec<(String, String)> {
+        let mut seen = HashSet::new();
+        let mut writes = vec![];
+        if let Some(current) = self.stack.last() {
+            for (key, _) in current.edits.iter() {
+                if !seen.insert(key) {
+                    continue;
+                }
+                if let Some(value) = self.lookup_map.get(key).and_then(|history| history.last()) {
+                    writes.push((key.clone(), value.clone()));
+                }
+            }
+        }
+        writes
+    }
+
     pub fn commit(&mut self) {
         let mut last_item = self
             .stack
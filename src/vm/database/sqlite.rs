@@ -155,6 +155,69 @@ impl SqliteConnection {
     pub fn has_entry(conn: &Connection, key: &str) -> bool {
         sqlite_has_entry(conn, key)
     }
+
+    /// Remember that the MARF path `path` (as hex) is the hash of `key`, so the key can be
+    /// recovered from the path later.
+    pub fn put_key_preimage(conn: &Connection, path: &str, key: &str) {
+        let params: [&dyn ToSql; 2] = [&path, &key];
+        if let Err(e) = conn.execute(
+            "INSERT OR IGNORE INTO key_preimage_table (path, key) VALUES (?, ?)",
+            &params,
+        ) {
+            error!("Failed to insert preimage ({},{}): {:?}", path, key, &e);
+            panic!("{}", SQL_FAIL_MESSAGE);
+        }
+    }
+
+    /// Find the key whose MARF path is `path` (as hex), if it was recorded.  Databases written
+    /// before key preimages were recorded have none.
+    pub fn get_key_preimage(conn: &Connection, path: &str) -> Option<String> {
+        match conn
+            .query_row(
+                "SELECT key FROM key_preimage_table WHERE path = ?",
+                &[&path],
+                |row| row.get(0),
+            )
+            .optional()
+        {
+            Ok(x) => x,
+            Err(SqliteError::SqliteFailure(_, Some(ref msg))) if msg.contains("no such table") => {
+                None
+            }
+            Err(e) => {
+                error!("Failed to query preimage of '{}': {:?}", path, &e);
+                panic!("{}", SQL_FAIL_MESSAGE);
+            }
+        }
+    }
+
+    /// Find the contracts with a metadata entry for `key`, in any fork.
+    pub fn get_contracts_with_metadata(conn: &Connection, key: &str) -> Vec<String> {
+        let prefix = "clr-meta::";
+        let suffix = format!("::{}", key);
+        let pattern = format!("{}%{}", prefix, suffix);
+
+        let contracts: std::result::Result<Vec<String>, SqliteError> = conn
+            .prepare("SELECT DISTINCT key FROM metadata_table WHERE key LIKE ?")
+            .and_then(|mut stmt| {
+                stmt.query_map(&[&pattern], |row| row.get::<_, String>(0))?
+                    .collect()
+            });
+        match contracts {
+            Ok(keys) => keys
+                .into_iter()
+                .filter_map(|key| {
+                    key.strip_prefix(prefix)?
+                        .strip_suffix(&suffix)
+                        .map(|contract| contract.to_string())
+                })
+                .collect(),
+            Err(e) => {
+                error!("Failed to query contracts with metadata {}: {:?}", key, &e);
+                panic!("{}", SQL_FAIL_MESSAGE);
+            }
+        }
+    }
 }
 
 impl SqliteConnection {
@@ -177,10 +240,25 @@ impl SqliteConnection {
         )
         .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
 
+        Self::initialize_key_preimages(conn)?;
+
         Self::check_schema(conn)?;
 
         Ok(())
     }
+
+    /// Create the table of MARF key preimages.  Also run on databases created before the table
+    /// existed.
+    pub fn initialize_key_preimages(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS key_preimage_table
+                      (path TEXT PRIMARY KEY, key TEXT NOT NULL)",
+            NO_PARAMS,
+        )
+        .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+        Ok(())
+    }
+
     pub fn memory() -> Result<Connection> {
         let contract_db = SqliteConnection::inner_open(":memory:")?;
         SqliteConnection::initialize_conn(&contract_db)?;
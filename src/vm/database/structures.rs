@@ -222,7 +222,7 @@ impl<'db, 'conn> STXBalanceSnapshot<'db, 'conn> {
             recipient_balance
                 .amount_unlocked
                 .checked_add(amount)
-                .ok_or(Error::Runtime(RuntimeErrorType::ArithmeticOverflow, None))?;
+                .ok_or(Error::from(RuntimeErrorType::ArithmeticOverflow))?;
 
         self.debit(amount);
         self.db_ref.put(&recipient_key, &recipient_balance);
@@ -167,6 +167,127 @@ const TO_INT_API: SimpleFunctionAPI = SimpleFunctionAPI {
     example: "(to-int u238) ;; Returns 238"
 };
 
+const BUFF_TO_INT_LE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(buff-to-int-le buffer)",
+    description: "Converts a buffer of at most 16 bytes to an `int`, reading it as the little-endian,
+two's complement representation of the integer. A buffer shorter than 16 bytes is padded with zero bytes at its end
+(its most significant side), so it always converts to a non-negative integer.
+
+This function is available starting with Stacks 2.1.",
+    example: "(buff-to-int-le 0x0102) ;; Returns 513
+(buff-to-int-le 0xffffffffffffffffffffffffffffffff) ;; Returns -1
+(buff-to-int-le 0x) ;; Returns 0
+"
+};
+
+const BUFF_TO_INT_BE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(buff-to-int-be buffer)",
+    description: "Converts a buffer of at most 16 bytes to an `int`, reading it as the big-endian,
+two's complement representation of the integer. A buffer shorter than 16 bytes is padded with zero bytes at its start
+(its most significant side), so it always converts to a non-negative integer.
+
+This function is available starting with Stacks 2.1.",
+    example: "(buff-to-int-be 0x0102) ;; Returns 258
+(buff-to-int-be 0xfffffffffffffffffffffffffffffffe) ;; Returns -2
+"
+};
+
+const BUFF_TO_UINT_LE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(buff-to-uint-le buffer)",
+    description: "Converts a buffer of at most 16 bytes to a `uint`, reading it as the little-endian
+representation of the integer. A buffer shorter than 16 bytes is padded with zero bytes at its end.
+
+This function is available starting with Stacks 2.1.",
+    example: "(buff-to-uint-le 0x0102) ;; Returns u513
+(buff-to-uint-le 0xffffffffffffffffffffffffffffffff) ;; Returns u340282366920938463463374607431768211455
+"
+};
+
+const BUFF_TO_UINT_BE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(buff-to-uint-be buffer)",
+    description: "Converts a buffer of at most 16 bytes to a `uint`, reading it as the big-endian
+representation of the integer. A buffer shorter than 16 bytes is padded with zero bytes at its start.
+
+This function is available starting with Stacks 2.1.",
+    example: "(buff-to-uint-be 0x0102) ;; Returns u258
+(buff-to-uint-be 0x01) ;; Returns u1
+"
+};
+
+const INT_TO_BUFF_LE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(int-to-buff-le i)",
+    description: "Converts an `int` to its 16-byte, little-endian, two's complement representation.
+
+This function is available starting with Stacks 2.1.",
+    example: "(int-to-buff-le 258) ;; Returns 0x02010000000000000000000000000000
+(int-to-buff-le -1) ;; Returns 0xffffffffffffffffffffffffffffffff
+",
+};
+
+const INT_TO_BUFF_BE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(int-to-buff-be i)",
+    description: "Converts an `int` to its 16-byte, big-endian, two's complement representation.
+
+This function is available starting with Stacks 2.1.",
+    example: "(int-to-buff-be 258) ;; Returns 0x00000000000000000000000000000102
+(int-to-buff-be -2) ;; Returns 0xfffffffffffffffffffffffffffffffe
+",
+};
+
+const UINT_TO_BUFF_LE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(uint-to-buff-le u)",
+    description: "Converts a `uint` to its 16-byte, little-endian representation.
+
+This function is available starting with Stacks 2.1.",
+    example: "(uint-to-buff-le u258) ;; Returns 0x02010000000000000000000000000000
+",
+};
+
+const UINT_TO_BUFF_BE_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(uint-to-buff-be u)",
+    description: "Converts a `uint` to its 16-byte, big-endian representation.
+
+This function is available starting with Stacks 2.1.",
+    example: "(uint-to-buff-be u258) ;; Returns 0x00000000000000000000000000000102
+",
+};
+
+const STRING_TO_INT_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(string-to-int? string)",
+    description: "Parses a `string-ascii` or `string-utf8` of decimal digits, optionally preceded by `-`,
+as an `int`. Returns `none` if the string contains any other character, or if its value does not fit in an `int`.
+
+This function is available starting with Stacks 2.1.",
+    example: "(string-to-int? \"-145\") ;; Returns (some -145)
+(string-to-int? u\"42\") ;; Returns (some 42)
+(string-to-int? \"12a\") ;; Returns none
+(string-to-int? \"170141183460469231731687303715884105728\") ;; Returns none
+"
+};
+
+const STRING_TO_UINT_API: SimpleFunctionAPI = SimpleFunctionAPI {
+    name: None,
+    signature: "(string-to-uint? string)",
+    description:
+        "Parses a `string-ascii` or `string-utf8` of decimal digits as a `uint`. Returns `none`
+if the string contains any other character, or if its value does not fit in a `uint`.
+
+This function is available starting with Stacks 2.1.",
+    example: "(string-to-uint? \"145\") ;; Returns (some u145)
+(string-to-uint? \"-1\") ;; Returns none
+(string-to-uint? \"\") ;; Returns none
+",
+};
+
 const ADD_API: SimpleFunctionAPI = SimpleFunctionAPI {
     name: Some("+ (add)"),
     signature: "(+ i1 i2...)",
@@ -607,6 +728,25 @@ for which the corresponding element types are, respectively, `A`, `(buff 1)`, `(
 "#,
 };
 
+const SLICE_API: SpecialAPI = SpecialAPI {
+    input_type: "sequence_A, uint, uint",
+    output_type: "(optional sequence_A)",
+    signature: "(slice? sequence left-position right-position)",
+    description: "The `slice?` function returns the items of `sequence` from `left-position` up to, but not
+including, `right-position`, as a sequence of the same type. If `left-position` is greater than `right-position`,
+or `right-position` is greater than the length of the sequence, it returns `none`.
+Applicable sequence types are `(list A)`, `buff`, `string-ascii` and `string-utf8`.
+
+This function is available starting with Stacks 2.1.",
+    example: r#"
+(slice? "blockstack" u5 u10) ;; Returns (some "stack")
+(slice? (list 1 2 3 4 5) u5 u9) ;; Returns none
+(slice? (list 1 2 3 4 5) u3 u4) ;; Returns (some (4))
+(slice? "abcd" u1 u3) ;; Returns (some "bc")
+(slice? 0xfb01 u0 u0) ;; Returns (some 0x)
+"#,
+};
+
 const INDEX_OF_API: SpecialAPI = SpecialAPI {
     input_type: "sequence_A, A",
     output_type: "(optional uint)",
@@ -869,6 +1009,37 @@ const CONTRACT_OF_API: SpecialAPI = SpecialAPI {
 "
 };
 
+const IMPLEMENTS_TRAIT_API: SpecialAPI = SpecialAPI {
+    input_type: "principal, TraitIdentifier",
+    output_type: "bool",
+    signature: "(implements-trait principal trait-identifier)",
+    description: "The `implements-trait` function returns `true` if `principal` is a published contract that
+implements the trait `trait-identifier`, and `false` otherwise. A contract implements a trait if it declares so
+with `impl-trait`, or if it defines every function of the trait as a public or read-only function with matching
+argument and return types. Standard principals never implement a trait.
+
+This function is available starting with Stacks 2.1.",
+    example: "
+(implements-trait .tokens 'SPAXYA5XS51713FDTQ8H94EJ4V579CXMTRNBZKSF.token-a.token-trait) ;; Returns false
+(implements-trait tx-sender 'SPAXYA5XS51713FDTQ8H94EJ4V579CXMTRNBZKSF.token-a.token-trait) ;; Returns false
+"
+};
+
+const GET_PUBLIC_FUNCTIONS_API: SpecialAPI = SpecialAPI {
+    input_type: "principal",
+    output_type: "(optional (list 1024 (string-ascii 128)))",
+    signature: "(get-public-functions? principal)",
+    description: "The `get-public-functions?` function returns the names of the public functions of the
+contract `principal`, in lexicographic order, wrapped in `some`. If `principal` is a standard principal, or
+no such contract exists, it returns `none`. Read-only and private functions are not listed.
+
+This function is available starting with Stacks 2.1.",
+    example: "
+(get-public-functions? .tokens) ;; Returns (some (\"mint!\" \"token-transfer\"))
+(get-public-functions? tx-sender) ;; Returns none
+"
+};
+
 const PRINCIPAL_OF_API: SpecialAPI = SpecialAPI {
     input_type: "(buff 33)",
     output_type: "(response principal uint)",
@@ -1623,6 +1794,16 @@ fn make_api_reference(function: &NativeFunctions) -> FunctionAPI {
         Add => make_for_simple_native(&ADD_API, &Add, name),
         ToUInt => make_for_simple_native(&TO_UINT_API, &ToUInt, name),
         ToInt => make_for_simple_native(&TO_INT_API, &ToInt, name),
+        BuffToIntLe => make_for_simple_native(&BUFF_TO_INT_LE_API, &BuffToIntLe, name),
+        BuffToIntBe => make_for_simple_native(&BUFF_TO_INT_BE_API, &BuffToIntBe, name),
+        BuffToUIntLe => make_for_simple_native(&BUFF_TO_UINT_LE_API, &BuffToUIntLe, name),
+        BuffToUIntBe => make_for_simple_native(&BUFF_TO_UINT_BE_API, &BuffToUIntBe, name),
+        IntToBuffLe => make_for_simple_native(&INT_TO_BUFF_LE_API, &IntToBuffLe, name),
+        IntToBuffBe => make_for_simple_native(&INT_TO_BUFF_BE_API, &IntToBuffBe, name),
+        UIntToBuffLe => make_for_simple_native(&UINT_TO_BUFF_LE_API, &UIntToBuffLe, name),
+        UIntToBuffBe => make_for_simple_native(&UINT_TO_BUFF_BE_API, &UIntToBuffBe, name),
+        StringToInt => make_for_simple_native(&STRING_TO_INT_API, &StringToInt, name),
+        StringToUInt => make_for_simple_native(&STRING_TO_UINT_API, &StringToUInt, name),
         Subtract => make_for_simple_native(&SUB_API, &Subtract, name),
         Multiply => make_for_simple_native(&MUL_API, &Multiply, name),
         Divide => make_for_simple_native(&DIV_API, &Divide, name),
@@ -1671,6 +1852,9 @@ fn make_api_reference(function: &NativeFunctions) -> FunctionAPI {
         Print => make_for_special(&PRINT_API, name),
         ContractCall => make_for_special(&CONTRACT_CALL_API, name),
         ContractOf => make_for_special(&CONTRACT_OF_API, name),
+        ImplementsTrait => make_for_special(&IMPLEMENTS_TRAIT_API, name),
+        GetPublicFunctions => make_for_special(&GET_PUBLIC_FUNCTIONS_API, name),
+        Slice => make_for_special(&SLICE_API, name),
         PrincipalOf => make_for_special(&PRINCIPAL_OF_API, name),
         AsContract => make_for_special(&AS_CONTRACT_API, name),
         GetBlockInfo => make_for_special(&GET_BLOCK_INFO_API, name),
@@ -1925,12 +2109,13 @@ mod test {
         }
 
         let conn = store.as_clarity_db(&DOC_HEADER_DB, &DOC_POX_STATE_DB);
-        let mut contract_context = ContractContext::new(contract_id.clone());
+        let mut contract_context =
+            ContractContext::new(contract_id.clone(), StacksEpochId::latest());
         let mut global_context = GlobalContext::new(
             false,
             conn,
             LimitedCostTracker::new_free(),
-            StacksEpochId::Epoch2_05,
+            StacksEpochId::latest(),
         );
 
         global_context
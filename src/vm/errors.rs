@@ -26,6 +26,7 @@ pub use vm::analysis::errors::{check_argument_count, check_arguments_at_least};
 use vm::ast::errors::ParseError;
 use vm::contexts::StackTrace;
 use vm::costs::CostErrors;
+use vm::representations::Span;
 use vm::types::{TypeSignature, Value};
 
 #[derive(Debug)]
@@ -40,10 +41,32 @@ pub enum Error {
     ///   trigger these errors.
     Unchecked(CheckErrors),
     Interpreter(InterpreterError),
-    Runtime(RuntimeErrorType, Option<StackTrace>),
+    Runtime(RuntimeErrorType, Option<StackTrace>, Option<ErrorLocation>),
     ShortReturn(ShortReturnType),
 }
 
+/// Where a runtime error arose: the contract and function being evaluated, and the innermost
+///   expression (whose span is only known in `developer-mode` builds).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorLocation {
+    pub contract: String,
+    pub function: Option<String>,
+    pub span: Option<Span>,
+}
+
+/// A machine-readable description of an error, for transaction receipts and event observers.
+///   `code` is stable across releases (see `Error::code`), while `message` is for humans and
+///   may change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorPayload {
+    pub code: u32,
+    pub name: String,
+    pub message: String,
+    pub contract: Option<String>,
+    pub function: Option<String>,
+    pub span: Option<Span>,
+}
+
 /// InterpreterErrors are errors that *should never* occur.
 /// Test executions may trigger these errors.
 #[derive(Debug, PartialEq)]
@@ -96,6 +119,10 @@ pub enum RuntimeErrorType {
     UnknownBlockHeaderHash(BlockHeaderHash),
     BadBlockHash(Vec<u8>),
     UnwrapFailure,
+    // an evaluation ran into its `ExecutionLimits`: the number of expressions evaluated, or the
+    // milliseconds elapsed
+    StepLimitExceeded(u64),
+    TimeLimitExceeded(u64),
 }
 
 #[derive(Debug, PartialEq)]
@@ -115,7 +142,7 @@ impl<T> PartialEq<IncomparableError<T>> for IncomparableError<T> {
 impl PartialEq<Error> for Error {
     fn eq(&self, other: &Error) -> bool {
         match (self, other) {
-            (Error::Runtime(x, _), Error::Runtime(y, _)) => x == y,
+            (Error::Runtime(x, ..), Error::Runtime(y, ..)) => x == y,
             (Error::Unchecked(x), Error::Unchecked(y)) => x == y,
             (Error::ShortReturn(x), Error::ShortReturn(y)) => x == y,
             (Error::Interpreter(x), Error::Interpreter(y)) => x == y,
@@ -127,7 +154,7 @@ impl PartialEq<Error> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::Runtime(ref err, ref stack) => {
+            Error::Runtime(ref err, ref stack, _) => {
                 match err {
                     _ => write!(f, "{}", err),
                 }?;
@@ -145,6 +172,168 @@ impl fmt::Display for Error {
     }
 }
 
+impl Error {
+    /// A numeric code for the kind of error, stable across releases so that downstream tools
+    ///   can match on it: runtime errors are 1000-1999, interpreter errors 2000-2999, short
+    ///   returns 3000-3999, and unchecked errors are all 4000.  Codes are never reused; new
+    ///   variants get new codes at the end of their range.
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::Runtime(err, ..) => err.code(),
+            Error::Interpreter(err) => err.code(),
+            Error::ShortReturn(ShortReturnType::ExpectedValue(_)) => 3000,
+            Error::ShortReturn(ShortReturnType::AssertionFailed(_)) => 3001,
+            Error::Unchecked(_) => 4000,
+        }
+    }
+
+    /// The name of the kind of error, e.g. `DivisionByZero`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Error::Runtime(err, ..) => err.name(),
+            Error::Interpreter(err) => err.name(),
+            Error::ShortReturn(ShortReturnType::ExpectedValue(_)) => "ExpectedValue",
+            Error::ShortReturn(ShortReturnType::AssertionFailed(_)) => "AssertionFailed",
+            Error::Unchecked(_) => "Unchecked",
+        }
+    }
+
+    pub fn payload(&self) -> ErrorPayload {
+        let message = match self {
+            Error::Runtime(err, ..) => format!("{}", err),
+            Error::Interpreter(err) => format!("{:?}", err),
+            Error::ShortReturn(err) => format!("{:?}", err),
+            Error::Unchecked(err) => format!("{:?}", err),
+        };
+        let location = match self {
+            Error::Runtime(_, _, Some(location)) => Some(location.clone()),
+            _ => None,
+        };
+        ErrorPayload {
+            code: self.code(),
+            name: self.name().to_string(),
+            message,
+            contract: location.as_ref().map(|l| l.contract.clone()),
+            function: location.as_ref().and_then(|l| l.function.clone()),
+            span: location.and_then(|l| l.span),
+        }
+    }
+}
+
+impl RuntimeErrorType {
+    /// See `Error::code`
+    pub fn code(&self) -> u32 {
+        use self::RuntimeErrorType::*;
+        match self {
+            Arithmetic(_) => 1000,
+            ArithmeticOverflow => 1001,
+            ArithmeticUnderflow => 1002,
+            SupplyOverflow(..) => 1003,
+            SupplyUnderflow(..) => 1004,
+            DivisionByZero => 1005,
+            ParseError(_) => 1006,
+            ASTError(_) => 1007,
+            MaxStackDepthReached => 1008,
+            MaxContextDepthReached => 1009,
+            ListDimensionTooHigh => 1010,
+            BadTypeConstruction => 1011,
+            ValueTooLarge => 1012,
+            BadBlockHeight(_) => 1013,
+            TransferNonPositiveAmount => 1014,
+            NoSuchToken => 1015,
+            NotImplemented => 1016,
+            NoSenderInContext => 1017,
+            NonPositiveTokenSupply => 1018,
+            JSONParseError(_) => 1019,
+            AttemptToFetchInTransientContext => 1020,
+            BadNameValue(..) => 1021,
+            UnknownBlockHeaderHash(_) => 1022,
+            BadBlockHash(_) => 1023,
+            UnwrapFailure => 1024,
+            StepLimitExceeded(_) => 1025,
+            TimeLimitExceeded(_) => 1026,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use self::RuntimeErrorType::*;
+        match self {
+            Arithmetic(_) => "Arithmetic",
+            ArithmeticOverflow => "ArithmeticOverflow",
+            ArithmeticUnderflow => "ArithmeticUnderflow",
+            SupplyOverflow(..) => "SupplyOverflow",
+            SupplyUnderflow(..) => "SupplyUnderflow",
+            DivisionByZero => "DivisionByZero",
+            ParseError(_) => "ParseError",
+            ASTError(_) => "ASTError",
+            MaxStackDepthReached => "MaxStackDepthReached",
+            MaxContextDepthReached => "MaxContextDepthReached",
+            ListDimensionTooHigh => "ListDimensionTooHigh",
+            BadTypeConstruction => "BadTypeConstruction",
+            ValueTooLarge => "ValueTooLarge",
+            BadBlockHeight(_) => "BadBlockHeight",
+            TransferNonPositiveAmount => "TransferNonPositiveAmount",
+            NoSuchToken => "NoSuchToken",
+            NotImplemented => "NotImplemented",
+            NoSenderInContext => "NoSenderInContext",
+            NonPositiveTokenSupply => "NonPositiveTokenSupply",
+            JSONParseError(_) => "JSONParseError",
+            AttemptToFetchInTransientContext => "AttemptToFetchInTransientContext",
+            BadNameValue(..) => "BadNameValue",
+            UnknownBlockHeaderHash(_) => "UnknownBlockHeaderHash",
+            BadBlockHash(_) => "BadBlockHash",
+            UnwrapFailure => "UnwrapFailure",
+            StepLimitExceeded(_) => "StepLimitExceeded",
+            TimeLimitExceeded(_) => "TimeLimitExceeded",
+        }
+    }
+}
+
+impl InterpreterError {
+    /// See `Error::code`
+    pub fn code(&self) -> u32 {
+        use self::InterpreterError::*;
+        match self {
+            BadSender(_) => 2000,
+            BadSymbolicRepresentation(_) => 2001,
+            InterpreterError(_) => 2002,
+            UninitializedPersistedVariable => 2003,
+            FailedToConstructAssetTable => 2004,
+            FailedToConstructEventBatch => 2005,
+            SqliteError(_) => 2006,
+            BadFileName => 2007,
+            FailedToCreateDataDirectory => 2008,
+            MarfFailure(_) => 2009,
+            FailureConstructingTupleWithType => 2010,
+            FailureConstructingListWithType => 2011,
+            InsufficientBalance => 2012,
+            CostContractLoadFailure => 2013,
+            DBError(_) => 2014,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use self::InterpreterError::*;
+        match self {
+            BadSender(_) => "BadSender",
+            BadSymbolicRepresentation(_) => "BadSymbolicRepresentation",
+            InterpreterError(_) => "InterpreterError",
+            UninitializedPersistedVariable => "UninitializedPersistedVariable",
+            FailedToConstructAssetTable => "FailedToConstructAssetTable",
+            FailedToConstructEventBatch => "FailedToConstructEventBatch",
+            SqliteError(_) => "SqliteError",
+            BadFileName => "BadFileName",
+            FailedToCreateDataDirectory => "FailedToCreateDataDirectory",
+            MarfFailure(_) => "MarfFailure",
+            FailureConstructingTupleWithType => "FailureConstructingTupleWithType",
+            FailureConstructingListWithType => "FailureConstructingListWithType",
+            InsufficientBalance => "InsufficientBalance",
+            CostContractLoadFailure => "CostContractLoadFailure",
+            DBError(_) => "DBError",
+        }
+    }
+}
+
 impl fmt::Display for RuntimeErrorType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -183,7 +372,7 @@ impl From<SerdeJSONErr> for Error {
 
 impl From<RuntimeErrorType> for Error {
     fn from(err: RuntimeErrorType) -> Self {
-        Error::Runtime(err, None)
+        Error::Runtime(err, None, None)
     }
 }
 
@@ -235,6 +424,35 @@ _native_:native_div
         assert_eq!(format!("{}", execute(t).unwrap_err()), expected);
     }
 
+    #[test]
+    fn error_payloads() {
+        let t = "(define-private (div-zero (x int))
+                   (/ x 0))
+                 (div-zero 10)";
+        let payload = execute(t).unwrap_err().payload();
+        assert_eq!(payload.code, 1005);
+        assert_eq!(payload.name, "DivisionByZero");
+        assert_eq!(payload.message, "DivisionByZero");
+        assert_eq!(payload.function.as_deref(), Some("div-zero"));
+        assert!(payload.contract.is_some());
+        #[cfg(feature = "developer-mode")]
+        assert_eq!(payload.span.unwrap().start_line, 2);
+
+        let payload = execute("(unwrap-panic none)").unwrap_err().payload();
+        assert_eq!(payload.code, 1024);
+        assert_eq!(payload.function, None);
+
+        let payload = execute("(asserts! false (err u1))").unwrap_err().payload();
+        assert_eq!(payload.code, 3001);
+        assert_eq!(payload.contract, None);
+
+        assert_eq!(
+            Error::from(RuntimeErrorType::TimeLimitExceeded(0)).code(),
+            1026
+        );
+        assert_eq!(Error::from(InterpreterError::BadFileName).code(), 2007);
+    }
+
     #[test]
     fn equality() {
         assert_eq!(
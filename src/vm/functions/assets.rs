@@ -324,7 +324,7 @@ pub fn special_mint_asset_v200(
             &asset,
             expected_asset_type,
         ) {
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => Ok(()),
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => Ok(()),
             Ok(_owner) => return clarity_ecode!(MintAssetErrorCodes::ALREADY_EXIST),
             Err(e) => Err(e),
         }?;
@@ -387,7 +387,7 @@ pub fn special_mint_asset_v205(
             &asset,
             expected_asset_type,
         ) {
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => Ok(()),
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => Ok(()),
             Ok(_owner) => return clarity_ecode!(MintAssetErrorCodes::ALREADY_EXIST),
             Err(e) => Err(e),
         }?;
@@ -457,7 +457,7 @@ pub fn special_transfer_asset_v200(
             expected_asset_type,
         ) {
             Ok(owner) => Ok(owner),
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => {
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => {
                 return clarity_ecode!(TransferAssetErrorCodes::DOES_NOT_EXIST)
             }
             Err(e) => Err(e),
@@ -543,7 +543,7 @@ pub fn special_transfer_asset_v205(
             expected_asset_type,
         ) {
             Ok(owner) => Ok(owner),
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => {
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => {
                 return clarity_ecode!(TransferAssetErrorCodes::DOES_NOT_EXIST)
             }
             Err(e) => Err(e),
@@ -759,7 +759,7 @@ pub fn special_get_owner_v200(
             Ok(Value::some(Value::Principal(owner))
                 .expect("Principal should always fit in optional."))
         }
-        Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => Ok(Value::none()),
+        Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => Ok(Value::none()),
         Err(e) => Err(e),
     }
 }
@@ -801,7 +801,7 @@ pub fn special_get_owner_v205(
             Ok(Value::some(Value::Principal(owner))
                 .expect("Principal should always fit in optional."))
         }
-        Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => Ok(Value::none()),
+        Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => Ok(Value::none()),
         Err(e) => Err(e),
     }
 }
@@ -929,7 +929,7 @@ pub fn special_burn_asset_v200(
             &asset,
             expected_asset_type,
         ) {
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => {
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => {
                 return clarity_ecode!(BurnAssetErrorCodes::DOES_NOT_EXIST)
             }
             Ok(owner) => Ok(owner),
@@ -1006,7 +1006,7 @@ pub fn special_burn_asset_v205(
             &asset,
             expected_asset_type,
         ) {
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => {
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, ..)) => {
                 return clarity_ecode!(BurnAssetErrorCodes::DOES_NOT_EXIST)
             }
             Ok(owner) => Ok(owner),
@@ -0,0 +1,158 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use vm::errors::{CheckErrors, InterpreterResult as Result};
+use vm::types::{CharType, SequenceData, TypeSignature, Value, BUFF_16};
+
+/// Longest string `string-to-int?` will try to parse: the 39 digits of `i128::MIN`, and its sign.
+const MAX_INTEGER_STRING_LEN: usize = 40;
+
+enum Endianness {
+    Little,
+    Big,
+}
+
+/// Read a buffer of at most 16 bytes as the 16 bytes of a 128-bit integer.  Shorter buffers
+///  are zero-padded on their most significant side.
+fn buff_to_bytes(value: Value, endianness: Endianness) -> Result<[u8; 16]> {
+    match value {
+        Value::Sequence(SequenceData::Buffer(ref buff)) if buff.data.len() <= 16 => {
+            let mut bytes = [0; 16];
+            match endianness {
+                Endianness::Little => bytes[..buff.data.len()].copy_from_slice(&buff.data),
+                Endianness::Big => bytes[16 - buff.data.len()..].copy_from_slice(&buff.data),
+            }
+            Ok(bytes)
+        }
+        _ => Err(CheckErrors::TypeValueError(BUFF_16.clone(), value).into()),
+    }
+}
+
+pub fn native_buff_to_int_le(value: Value) -> Result<Value> {
+    let bytes = buff_to_bytes(value, Endianness::Little)?;
+    Ok(Value::Int(i128::from_le_bytes(bytes)))
+}
+
+pub fn native_buff_to_int_be(value: Value) -> Result<Value> {
+    let bytes = buff_to_bytes(value, Endianness::Big)?;
+    Ok(Value::Int(i128::from_be_bytes(bytes)))
+}
+
+pub fn native_buff_to_uint_le(value: Value) -> Result<Value> {
+    let bytes = buff_to_bytes(value, Endianness::Little)?;
+    Ok(Value::UInt(u128::from_le_bytes(bytes)))
+}
+
+pub fn native_buff_to_uint_be(value: Value) -> Result<Value> {
+    let bytes = buff_to_bytes(value, Endianness::Big)?;
+    Ok(Value::UInt(u128::from_be_bytes(bytes)))
+}
+
+pub fn native_int_to_buff_le(value: Value) -> Result<Value> {
+    if let Value::Int(int_val) = value {
+        Value::buff_from(int_val.to_le_bytes().to_vec())
+    } else {
+        Err(CheckErrors::TypeValueError(TypeSignature::IntType, value).into())
+    }
+}
+
+pub fn native_int_to_buff_be(value: Value) -> Result<Value> {
+    if let Value::Int(int_val) = value {
+        Value::buff_from(int_val.to_be_bytes().to_vec())
+    } else {
+        Err(CheckErrors::TypeValueError(TypeSignature::IntType, value).into())
+    }
+}
+
+pub fn native_uint_to_buff_le(value: Value) -> Result<Value> {
+    if let Value::UInt(uint_val) = value {
+        Value::buff_from(uint_val.to_le_bytes().to_vec())
+    } else {
+        Err(CheckErrors::TypeValueError(TypeSignature::UIntType, value).into())
+    }
+}
+
+pub fn native_uint_to_buff_be(value: Value) -> Result<Value> {
+    if let Value::UInt(uint_val) = value {
+        Value::buff_from(uint_val.to_be_bytes().to_vec())
+    } else {
+        Err(CheckErrors::TypeValueError(TypeSignature::UIntType, value).into())
+    }
+}
+
+/// The decimal digits of a string-ascii or string-utf8 value, with an optional leading `-` if
+///  `allow_sign` is set.  Returns None if the string is not of that form, or is too long to
+///  hold a 128-bit integer.  Overlong strings are rejected before any of their characters are
+///  read.
+fn integer_string(value: Value, allow_sign: bool) -> Result<Option<String>> {
+    let chars: Vec<u8> = match value {
+        Value::Sequence(SequenceData::String(CharType::ASCII(ascii))) => {
+            if ascii.data.len() > MAX_INTEGER_STRING_LEN {
+                return Ok(None);
+            }
+            ascii.data
+        }
+        Value::Sequence(SequenceData::String(CharType::UTF8(utf8))) => {
+            if utf8.data.len() > MAX_INTEGER_STRING_LEN {
+                return Ok(None);
+            }
+            let mut chars = Vec::with_capacity(utf8.data.len());
+            for c in utf8.data.into_iter() {
+                if c.len() != 1 {
+                    return Ok(None);
+                }
+                chars.push(c[0]);
+            }
+            chars
+        }
+        _ => {
+            return Err(CheckErrors::UnionTypeValueError(
+                vec![
+                    TypeSignature::max_string_ascii(),
+                    TypeSignature::max_string_utf8(),
+                ],
+                value,
+            )
+            .into())
+        }
+    };
+    if chars.is_empty() {
+        return Ok(None);
+    }
+    let digits = if allow_sign && chars[0] == b'-' {
+        &chars[1..]
+    } else {
+        &chars[..]
+    };
+    if digits.is_empty() || !digits.iter().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(chars).ok())
+}
+
+pub fn native_string_to_int(value: Value) -> Result<Value> {
+    match integer_string(value, true)?.and_then(|s| s.parse::<i128>().ok()) {
+        Some(int_val) => Value::some(Value::Int(int_val)),
+        None => Ok(Value::none()),
+    }
+}
+
+pub fn native_string_to_uint(value: Value) -> Result<Value> {
+    match integer_string(value, false)?.and_then(|s| s.parse::<u128>().ok()) {
+        Some(uint_val) => Value::some(Value::UInt(uint_val)),
+        None => Ok(Value::none()),
+    }
+}
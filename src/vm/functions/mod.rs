@@ -14,9 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::convert::TryFrom;
+
 use address::AddressHashMode;
 use util::hash;
-use vm::callables::{CallableType, NativeHandle};
+use vm::callables::{CallableType, DefineType, NativeHandle};
 use vm::costs::cost_functions::ClarityCostFunction;
 use vm::costs::{
     constants as cost_constants, cost_functions, runtime_cost, CostTracker, MemoryConsumer,
@@ -30,12 +32,15 @@ pub use vm::functions::special::handle_contract_call_special_cases;
 use vm::is_reserved;
 use vm::representations::SymbolicExpressionType::{Atom, List};
 use vm::representations::{ClarityName, SymbolicExpression, SymbolicExpressionType};
+use vm::representations::{TraitDefinition, MAX_STRING_LEN};
+use vm::types::signatures::{BufferLength, ListTypeData, SequenceSubtype, StringSubtype};
 use vm::types::{
-    BuffData, CharType, PrincipalData, ResponseData, SequenceData, TypeSignature, Value, BUFF_32,
-    BUFF_33, BUFF_65,
+    BuffData, CharType, PrincipalData, QualifiedContractIdentifier, ResponseData, SequenceData,
+    TraitIdentifier, TypeSignature, Value, BUFF_32, BUFF_33, BUFF_65,
 };
 use vm::{eval, Environment, LocalContext};
 
+use crate::core::StacksEpochId;
 use crate::types::chainstate::StacksAddress;
 use crate::vm::callables::cost_input_sized_vararg;
 
@@ -51,7 +56,9 @@ macro_rules! switch_on_global_epoch {
                     panic!("Executing Clarity method during Epoch 1.0, before Clarity")
                 }
                 StacksEpochId::Epoch20 => $Epoch2Version(args, env, context),
-                StacksEpochId::Epoch2_05 => $Epoch205Version(args, env, context),
+                StacksEpochId::Epoch2_05 | StacksEpochId::Epoch21 => {
+                    $Epoch205Version(args, env, context)
+                }
             }
         }
     };
@@ -60,6 +67,7 @@ macro_rules! switch_on_global_epoch {
 mod arithmetic;
 mod assets;
 mod boolean;
+mod conversions;
 mod crypto;
 mod database;
 pub mod define;
@@ -151,12 +159,44 @@ define_named_enum!(NativeFunctions {
     GetStxBalance("stx-get-balance"),
     StxTransfer("stx-transfer?"),
     StxBurn("stx-burn?"),
+    ImplementsTrait("implements-trait"),
+    GetPublicFunctions("get-public-functions?"),
+    BuffToIntLe("buff-to-int-le"),
+    BuffToIntBe("buff-to-int-be"),
+    BuffToUIntLe("buff-to-uint-le"),
+    BuffToUIntBe("buff-to-uint-be"),
+    IntToBuffLe("int-to-buff-le"),
+    IntToBuffBe("int-to-buff-be"),
+    UIntToBuffLe("uint-to-buff-le"),
+    UIntToBuffBe("uint-to-buff-be"),
+    StringToInt("string-to-int?"),
+    StringToUInt("string-to-uint?"),
+    Slice("slice?"),
 });
 
-pub fn lookup_reserved_functions(name: &str) -> Option<CallableType> {
+impl NativeFunctions {
+    /// The first epoch in which this native function exists.  Before then, its name is not
+    ///  reserved, so contracts deployed earlier may use it for their own definitions.
+    pub fn get_min_epoch(&self) -> StacksEpochId {
+        use vm::functions::NativeFunctions::*;
+        match self {
+            ImplementsTrait | GetPublicFunctions | BuffToIntLe | BuffToIntBe | BuffToUIntLe
+            | BuffToUIntBe | IntToBuffLe | IntToBuffBe | UIntToBuffLe | UIntToBuffBe
+            | StringToInt | StringToUInt | Slice => StacksEpochId::Epoch21,
+            _ => StacksEpochId::Epoch20,
+        }
+    }
+
+    pub fn lookup_by_name_at_epoch(name: &str, epoch: &StacksEpochId) -> Option<NativeFunctions> {
+        NativeFunctions::lookup_by_name(name)
+            .filter(|native_function| native_function.get_min_epoch() <= *epoch)
+    }
+}
+
+pub fn lookup_reserved_functions(name: &str, epoch: &StacksEpochId) -> Option<CallableType> {
     use vm::callables::CallableType::{NativeFunction, NativeFunction205, SpecialFunction};
     use vm::functions::NativeFunctions::*;
-    if let Some(native_function) = NativeFunctions::lookup_by_name(name) {
+    if let Some(native_function) = NativeFunctions::lookup_by_name_at_epoch(name, epoch) {
         let callable = match native_function {
             Add => NativeFunction(
                 "native_add",
@@ -328,6 +368,66 @@ pub fn lookup_reserved_functions(name: &str) -> Option<CallableType> {
                 SpecialFunction("native_secp256k1-verify", &crypto::special_secp256k1_verify)
             }
             Print => SpecialFunction("special_print", &special_print),
+            ImplementsTrait => {
+                SpecialFunction("special_implements_trait", &special_implements_trait)
+            }
+            GetPublicFunctions => SpecialFunction(
+                "special_get_public_functions",
+                &special_get_public_functions,
+            ),
+            BuffToIntLe => NativeFunction(
+                "native_buff_to_int_le",
+                NativeHandle::SingleArg(&conversions::native_buff_to_int_le),
+                ClarityCostFunction::IntCast,
+            ),
+            BuffToIntBe => NativeFunction(
+                "native_buff_to_int_be",
+                NativeHandle::SingleArg(&conversions::native_buff_to_int_be),
+                ClarityCostFunction::IntCast,
+            ),
+            BuffToUIntLe => NativeFunction(
+                "native_buff_to_uint_le",
+                NativeHandle::SingleArg(&conversions::native_buff_to_uint_le),
+                ClarityCostFunction::IntCast,
+            ),
+            BuffToUIntBe => NativeFunction(
+                "native_buff_to_uint_be",
+                NativeHandle::SingleArg(&conversions::native_buff_to_uint_be),
+                ClarityCostFunction::IntCast,
+            ),
+            IntToBuffLe => NativeFunction(
+                "native_int_to_buff_le",
+                NativeHandle::SingleArg(&conversions::native_int_to_buff_le),
+                ClarityCostFunction::IntCast,
+            ),
+            IntToBuffBe => NativeFunction(
+                "native_int_to_buff_be",
+                NativeHandle::SingleArg(&conversions::native_int_to_buff_be),
+                ClarityCostFunction::IntCast,
+            ),
+            UIntToBuffLe => NativeFunction(
+                "native_uint_to_buff_le",
+                NativeHandle::SingleArg(&conversions::native_uint_to_buff_le),
+                ClarityCostFunction::IntCast,
+            ),
+            UIntToBuffBe => NativeFunction(
+                "native_uint_to_buff_be",
+                NativeHandle::SingleArg(&conversions::native_uint_to_buff_be),
+                ClarityCostFunction::IntCast,
+            ),
+            StringToInt => NativeFunction205(
+                "native_string_to_int",
+                NativeHandle::SingleArg(&conversions::native_string_to_int),
+                ClarityCostFunction::StringToInt,
+                &cost_input_sized_vararg,
+            ),
+            StringToUInt => NativeFunction205(
+                "native_string_to_uint",
+                NativeHandle::SingleArg(&conversions::native_string_to_uint),
+                ClarityCostFunction::StringToUInt,
+                &cost_input_sized_vararg,
+            ),
+            Slice => SpecialFunction("special_slice", &sequences::special_slice),
             ContractCall => {
                 SpecialFunction("special_contract-call", &database::special_contract_call)
             }
@@ -583,7 +683,7 @@ fn special_let(
 
     finally_drop_memory!( env, memory_use; {
         handle_binding_list::<_, Error>(bindings, |binding_name, var_sexp| {
-            if is_reserved(binding_name) ||
+            if is_reserved(binding_name, &env.contract_context.epoch) ||
                 env.contract_context.lookup_function(binding_name).is_some() ||
                 inner_context.lookup_variable(binding_name).is_some() {
                     return Err(CheckErrors::NameAlreadyUsed(binding_name.clone().into()).into())
@@ -662,3 +762,166 @@ fn special_contract_of(
     let contract_principal = Value::Principal(PrincipalData::Contract(contract_identifier.clone()));
     Ok(contract_principal)
 }
+
+/// `get-public-functions?` lists at most this many functions
+pub const MAX_LISTED_PUBLIC_FUNCTIONS: u32 = 1024;
+
+/// The type of the list returned by `get-public-functions?`
+pub fn public_functions_list_type() -> ListTypeData {
+    ListTypeData::new_list(
+        TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(
+            BufferLength::try_from(MAX_STRING_LEN as u32)
+                .expect("BUG: Legal Clarity names must fit in a string-ascii"),
+        ))),
+        MAX_LISTED_PUBLIC_FUNCTIONS,
+    )
+    .expect("BUG: public function list type is too large")
+}
+
+fn trait_identifier_of(expr: &SymbolicExpression) -> Result<&TraitIdentifier> {
+    match &expr.expr {
+        SymbolicExpressionType::Field(trait_identifier)
+        | SymbolicExpressionType::TraitReference(_, TraitDefinition::Defined(trait_identifier))
+        | SymbolicExpressionType::TraitReference(_, TraitDefinition::Imported(trait_identifier)) => {
+            Ok(trait_identifier)
+        }
+        _ => Err(CheckErrors::ExpectedTraitIdentifier.into()),
+    }
+}
+
+/// Evaluate `expr` to the contract it names, or None if it names a standard principal
+fn eval_contract_principal(
+    expr: &SymbolicExpression,
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Option<QualifiedContractIdentifier>> {
+    match eval(expr, env, context)? {
+        Value::Principal(PrincipalData::Contract(contract_identifier)) => {
+            Ok(Some(contract_identifier))
+        }
+        Value::Principal(PrincipalData::Standard(_)) => Ok(None),
+        x => Err(CheckErrors::TypeValueError(TypeSignature::PrincipalType, x).into()),
+    }
+}
+
+/// Charge for loading a contract, as a contract call would.  Returns false if there
+///  is no such contract.
+fn charge_contract_load(
+    env: &mut Environment,
+    contract_identifier: &QualifiedContractIdentifier,
+) -> Result<bool> {
+    match env
+        .global_context
+        .database
+        .get_contract_size(contract_identifier)
+    {
+        Ok(contract_size) => {
+            runtime_cost(ClarityCostFunction::LoadContract, env, contract_size)?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+fn special_implements_trait(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    // (implements-trait principal trait-identifier)
+    check_argument_count(2, args)?;
+
+    runtime_cost(ClarityCostFunction::ContractOf, env, 0)?;
+
+    let trait_identifier = trait_identifier_of(&args[1])?;
+    let contract_identifier = match eval_contract_principal(&args[0], env, context)? {
+        Some(contract_identifier) => contract_identifier,
+        None => return Ok(Value::Bool(false)),
+    };
+
+    if !charge_contract_load(env, &contract_identifier)? {
+        return Ok(Value::Bool(false));
+    }
+    let contract = env
+        .global_context
+        .database
+        .get_contract(&contract_identifier)?;
+    if contract
+        .contract_context
+        .is_explicitly_implementing_trait(trait_identifier)
+    {
+        return Ok(Value::Bool(true));
+    }
+
+    let trait_definition =
+        if trait_identifier.contract_identifier == env.contract_context.contract_identifier {
+            env.contract_context
+                .lookup_trait_definition(&trait_identifier.name)
+        } else {
+            charge_contract_load(env, &trait_identifier.contract_identifier)?;
+            env.global_context
+                .database
+                .get_contract(&trait_identifier.contract_identifier)
+                .map_err(|_e| {
+                    CheckErrors::NoSuchContract(trait_identifier.contract_identifier.to_string())
+                })?
+                .contract_context
+                .lookup_trait_definition(&trait_identifier.name)
+        }
+        .ok_or_else(|| CheckErrors::TraitReferenceUnknown(trait_identifier.name.to_string()))?;
+
+    // return types are only known to the analysis, which every published contract has
+    let implements = match env
+        .global_context
+        .database
+        .load_contract_analysis_sized(&contract_identifier)
+    {
+        Some((analysis, analysis_size)) => {
+            runtime_cost(ClarityCostFunction::LoadContract, env, analysis_size)?;
+            analysis
+                .check_trait_compliance(trait_identifier, &trait_definition)
+                .is_ok()
+        }
+        None => false,
+    };
+    Ok(Value::Bool(implements))
+}
+
+fn special_get_public_functions(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    // (get-public-functions? principal)
+    check_argument_count(1, args)?;
+
+    runtime_cost(ClarityCostFunction::ContractOf, env, 0)?;
+
+    let contract_identifier = match eval_contract_principal(&args[0], env, context)? {
+        Some(contract_identifier) => contract_identifier,
+        None => return Ok(Value::none()),
+    };
+    if !charge_contract_load(env, &contract_identifier)? {
+        return Ok(Value::none());
+    }
+    let contract = env
+        .global_context
+        .database
+        .get_contract(&contract_identifier)?;
+
+    let mut names: Vec<_> = contract
+        .contract_context
+        .functions
+        .iter()
+        .filter(|(_, function)| function.define_type == DefineType::Public)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    names.sort();
+
+    let names = names
+        .into_iter()
+        .take(MAX_LISTED_PUBLIC_FUNCTIONS as usize)
+        .map(|name| Value::string_ascii_from_bytes(name.as_bytes().to_vec()))
+        .collect::<Result<Vec<_>>>()?;
+    Value::some(Value::list_with_type(names, public_functions_list_type())?)
+}
@@ -343,3 +343,50 @@ pub fn native_element_at(sequence: Value, index: Value) -> Result<Value> {
         Ok(Value::none())
     }
 }
+
+pub fn special_slice(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    check_argument_count(3, args)?;
+
+    let sequence = eval(&args[0], env, context)?;
+    let left = eval(&args[1], env, context)?;
+    let right = eval(&args[2], env, context)?;
+
+    let sequence_data = match sequence {
+        Value::Sequence(sequence_data) => sequence_data,
+        _ => {
+            runtime_cost(ClarityCostFunction::Slice, env, 1)?;
+            return Err(CheckErrors::ExpectedSequence(TypeSignature::type_of(&sequence)).into());
+        }
+    };
+    let (left, right) = match (left, right) {
+        (Value::UInt(left), Value::UInt(right)) => (left, right),
+        (Value::UInt(_), right) => {
+            return Err(CheckErrors::TypeValueError(TypeSignature::UIntType, right).into())
+        }
+        (left, _) => return Err(CheckErrors::TypeValueError(TypeSignature::UIntType, left).into()),
+    };
+
+    // bounds beyond usize are necessarily out of range
+    let bounds = usize::try_from(left)
+        .ok()
+        .zip(usize::try_from(right).ok())
+        .filter(|(left, right)| left <= right && *right <= sequence_data.len());
+
+    match bounds {
+        Some((left, right)) => {
+            runtime_cost(ClarityCostFunction::Slice, env, (right - left) as u64)?;
+            match sequence_data.slice(left, right) {
+                Some(result) => Value::some(result),
+                None => Ok(Value::none()),
+            }
+        }
+        None => {
+            runtime_cost(ClarityCostFunction::Slice, env, 0)?;
+            Ok(Value::none())
+        }
+    }
+}
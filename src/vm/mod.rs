@@ -36,9 +36,13 @@ mod functions;
 mod variables;
 
 pub mod analysis;
+pub mod bindings;
 pub mod docs;
 
 pub mod coverage;
+pub mod pool;
+pub mod profiler;
+pub mod property_test;
 
 #[cfg(test)]
 pub mod tests;
@@ -47,13 +51,14 @@ use crate::clarity_vm::database::MemoryBackingStore;
 use crate::core::StacksEpochId;
 use vm::callables::CallableType;
 use vm::contexts::GlobalContext;
-pub use vm::contexts::{CallStack, ContractContext, Environment, LocalContext};
+pub use vm::contexts::{CallStack, ContractContext, Environment, ExecutionLimits, LocalContext};
 use vm::costs::{
-    cost_functions, runtime_cost, CostOverflowingMath, CostTracker, LimitedCostTracker,
-    MemoryConsumer,
+    cost_functions, runtime_cost, CostOverflowingMath, CostTracker, ExecutionCost,
+    LimitedCostTracker, MemoryConsumer,
 };
 use vm::errors::{
-    CheckErrors, Error, InterpreterError, InterpreterResult as Result, RuntimeErrorType,
+    CheckErrors, Error, ErrorLocation, InterpreterError, InterpreterResult as Result,
+    RuntimeErrorType,
 };
 use vm::functions::define::DefineResult;
 pub use vm::types::Value;
@@ -107,7 +112,7 @@ fn lookup_variable(name: &str, context: &LocalContext, env: &mut Environment) ->
 pub fn lookup_function(name: &str, env: &mut Environment) -> Result<CallableType> {
     runtime_cost(ClarityCostFunction::LookupFunction, env, 0)?;
 
-    if let Some(result) = functions::lookup_reserved_functions(name) {
+    if let Some(result) = functions::lookup_reserved_functions(name, &env.contract_context.epoch) {
         Ok(result)
     } else {
         let user_function = env
@@ -119,7 +124,7 @@ pub fn lookup_function(name: &str, env: &mut Environment) -> Result<CallableType
 }
 
 fn add_stack_trace(result: &mut Result<Value>, env: &Environment) {
-    if let Err(Error::Runtime(_, ref mut stack_trace)) = result {
+    if let Err(Error::Runtime(_, ref mut stack_trace, _)) = result {
         if stack_trace.is_none() {
             stack_trace.replace(env.call_stack.make_stack_trace());
         }
@@ -158,7 +163,7 @@ pub fn apply(
         resp
     } else {
         let mut used_memory = 0;
-        let mut evaluated_args = vec![];
+        let mut evaluated_args = env.global_context.eval_pool.take_args();
         env.call_stack.incr_apply_depth();
         for arg_x in args.iter() {
             let arg_value = match eval(arg_x, env, context) {
@@ -166,6 +171,7 @@ pub fn apply(
                 Err(e) => {
                     env.drop_memory(used_memory);
                     env.call_stack.decr_apply_depth();
+                    env.global_context.eval_pool.return_args(evaluated_args);
                     return Err(e);
                 }
             };
@@ -175,6 +181,7 @@ pub fn apply(
                 Err(e) => {
                     env.drop_memory(used_memory);
                     env.call_stack.decr_apply_depth();
+                    env.global_context.eval_pool.return_args(evaluated_args);
                     return Err(Error::from(e));
                 }
             };
@@ -188,21 +195,24 @@ pub fn apply(
             CallableType::NativeFunction(_, function, cost_function) => {
                 runtime_cost(*cost_function, env, evaluated_args.len())
                     .map_err(Error::from)
-                    .and_then(|_| function.apply(evaluated_args))
+                    .and_then(|_| function.apply(&mut evaluated_args))
             }
             CallableType::NativeFunction205(_, function, cost_function, cost_input_handle) => {
                 let cost_input = if env.epoch() >= &StacksEpochId::Epoch2_05 {
-                    cost_input_handle(evaluated_args.as_slice())?
+                    cost_input_handle(evaluated_args.as_slice())
                 } else {
-                    evaluated_args.len() as u64
+                    Ok(evaluated_args.len() as u64)
                 };
-                runtime_cost(*cost_function, env, cost_input)
-                    .map_err(Error::from)
-                    .and_then(|_| function.apply(evaluated_args))
+                cost_input
+                    .and_then(|cost_input| {
+                        runtime_cost(*cost_function, env, cost_input).map_err(Error::from)
+                    })
+                    .and_then(|_| function.apply(&mut evaluated_args))
             }
             CallableType::UserFunction(function) => function.apply(&evaluated_args, env),
             _ => panic!("Should be unreachable."),
         };
+        env.global_context.eval_pool.return_args(evaluated_args);
         add_stack_trace(&mut resp, env);
         env.drop_memory(used_memory);
         env.call_stack.remove(&identifier, track_recursion)?;
@@ -210,19 +220,108 @@ pub fn apply(
     }
 }
 
+/// A hook into expression evaluation.  Implementations are registered on the `GlobalContext`
+/// (see `OwnedEnvironment::add_eval_hook`), and are invoked before and after every expression
+/// the VM evaluates.  This lets profilers, debuggers, and tracers observe execution without
+/// modifying the interpreter.
+pub trait EvalHook {
+    /// Called before `expr` is evaluated.
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        context: &LocalContext,
+        expr: &SymbolicExpression,
+    );
+
+    /// Called after `expr` has been evaluated.  `cost` is the runtime cost consumed by
+    /// evaluating `expr` (including its sub-expressions), and `res` is the evaluation result.
+    fn did_finish_eval(
+        &mut self,
+        env: &mut Environment,
+        context: &LocalContext,
+        expr: &SymbolicExpression,
+        cost: &ExecutionCost,
+        res: &Result<Value>,
+    );
+}
+
 pub fn eval<'a>(
     exp: &SymbolicExpression,
     env: &'a mut Environment,
     context: &LocalContext,
 ) -> Result<Value> {
-    use vm::representations::SymbolicExpressionType::{
-        Atom, AtomValue, Field, List, LiteralValue, TraitReference,
+    if let Some(mut coverage_tracker) = env.global_context.coverage_reporting.take() {
+        coverage_tracker.will_begin_eval(env, context, exp);
+        env.global_context.coverage_reporting = Some(coverage_tracker);
+    }
+
+    // the hooks are taken out of the global context while they run, so they can be given
+    // the environment
+    let cost_before_eval = match env.global_context.eval_hooks.take() {
+        Some(mut eval_hooks) => {
+            for hook in eval_hooks.iter_mut() {
+                hook.will_begin_eval(env, context, exp);
+            }
+            env.global_context.eval_hooks = Some(eval_hooks);
+            Some(env.global_context.cost_track.get_total())
+        }
+        None => None,
     };
 
-    if let Some(ref mut coverage_tracker) = env.global_context.coverage_reporting {
-        coverage_tracker.report_eval(exp, &env.contract_context.contract_identifier);
+    let res = env
+        .global_context
+        .check_execution_limits()
+        .and_then(|_| eval_expression(exp, env, context))
+        .map_err(|err| add_error_location(err, exp, env));
+
+    if let Some(cost_before_eval) = cost_before_eval {
+        if let Some(mut eval_hooks) = env.global_context.eval_hooks.take() {
+            let mut cost = env.global_context.cost_track.get_total();
+            if cost.sub(&cost_before_eval).is_err() {
+                cost = ExecutionCost::zero();
+            }
+            for hook in eval_hooks.iter_mut() {
+                hook.did_finish_eval(env, context, exp, &cost, &res);
+            }
+            env.global_context.eval_hooks = Some(eval_hooks);
+        }
     }
 
+    res
+}
+
+/// Record where a runtime error was raised.  Only the innermost expression's location is kept,
+///   since enclosing `eval` calls see the error after it has been located.
+fn add_error_location(err: Error, exp: &SymbolicExpression, env: &Environment) -> Error {
+    match err {
+        Error::Runtime(err, stack_trace, None) => {
+            #[cfg(feature = "developer-mode")]
+            let span = Some(exp.span.clone());
+            #[cfg(not(feature = "developer-mode"))]
+            let span = {
+                let _ = exp;
+                None
+            };
+            let location = ErrorLocation {
+                contract: env.contract_context.contract_identifier.to_string(),
+                function: env.call_stack.innermost_user_function(),
+                span,
+            };
+            Error::Runtime(err, stack_trace, Some(location))
+        }
+        err => err,
+    }
+}
+
+fn eval_expression(
+    exp: &SymbolicExpression,
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    use vm::representations::SymbolicExpressionType::{
+        Atom, AtomValue, Field, List, LiteralValue, TraitReference,
+    };
+
     match exp.expr {
         AtomValue(ref value) | LiteralValue(ref value) => Ok(value.clone()),
         Atom(ref value) => lookup_variable(&value, context, env),
@@ -231,13 +330,6 @@ pub fn eval<'a>(
                 .split_first()
                 .ok_or(CheckErrors::NonFunctionApplication)?;
 
-            if let Some(ref mut coverage_tracker) = env.global_context.coverage_reporting {
-                coverage_tracker.report_eval(
-                    &function_variable,
-                    &env.contract_context.contract_identifier,
-                );
-            }
-
             let function_name = function_variable
                 .match_atom()
                 .ok_or(CheckErrors::BadFunctionName)?;
@@ -248,8 +340,8 @@ pub fn eval<'a>(
     }
 }
 
-pub fn is_reserved(name: &str) -> bool {
-    if let Some(_result) = functions::lookup_reserved_functions(name) {
+pub fn is_reserved(name: &str, epoch: &StacksEpochId) -> bool {
+    if let Some(_result) = functions::lookup_reserved_functions(name, epoch) {
         true
     } else if variables::is_reserved_name(name) {
         true
@@ -401,7 +493,7 @@ pub fn execute_in_epoch(
     use_mainnet: bool,
 ) -> Result<Option<Value>> {
     let contract_id = QualifiedContractIdentifier::transient();
-    let mut contract_context = ContractContext::new(contract_id.clone());
+    let mut contract_context = ContractContext::new(contract_id.clone(), epoch);
     let mut marf = MemoryBackingStore::new();
     let conn = marf.as_clarity_db();
     let mut global_context = GlobalContext::new(false, conn, LimitedCostTracker::new_free(), epoch);
@@ -456,7 +548,10 @@ mod test {
         );
 
         let context = LocalContext::new();
-        let mut contract_context = ContractContext::new(QualifiedContractIdentifier::transient());
+        let mut contract_context = ContractContext::new(
+            QualifiedContractIdentifier::transient(),
+            StacksEpochId::Epoch2_05,
+        );
 
         let mut marf = MemoryBackingStore::new();
         let mut global_context = GlobalContext::new(
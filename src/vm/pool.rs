@@ -0,0 +1,160 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Buffers the interpreter would otherwise allocate and free on every function application:
+//! evaluated-argument vectors and the variable maps of user-function calls, plus the call stack
+//! of each transaction.  An `EvalPool` lives in the `GlobalContext`, and a block connection passes
+//! it from one transaction to the next, so short contract calls in a block mostly reuse memory
+//! instead of churning the allocator.
+//!
+//! Pooled buffers are always handed out empty, so pooling never changes what is evaluated.  Only
+//! a bounded number of buffers of bounded capacity are kept, so one large transaction does not
+//! pin its memory for the rest of the block.
+
+use std::collections::HashMap;
+
+use vm::contexts::CallStack;
+use vm::representations::ClarityName;
+use vm::types::Value;
+
+/// most buffers of each kind kept for reuse
+const MAX_POOLED_BUFFERS: usize = 64;
+/// buffers which grew beyond this many items are freed instead of kept
+const MAX_POOLED_CAPACITY: usize = 256;
+
+#[derive(Default)]
+pub struct EvalPool {
+    arg_buffers: Vec<Vec<Value>>,
+    variable_maps: Vec<HashMap<ClarityName, Value>>,
+    call_stack: Option<CallStack>,
+    reused: u64,
+    allocated: u64,
+}
+
+impl EvalPool {
+    pub fn new() -> EvalPool {
+        EvalPool::default()
+    }
+
+    /// An empty vector for evaluated arguments
+    pub fn take_args(&mut self) -> Vec<Value> {
+        match self.arg_buffers.pop() {
+            Some(buffer) => {
+                self.reused += 1;
+                buffer
+            }
+            None => {
+                self.allocated += 1;
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn return_args(&mut self, mut buffer: Vec<Value>) {
+        if buffer.capacity() == 0
+            || buffer.capacity() > MAX_POOLED_CAPACITY
+            || self.arg_buffers.len() >= MAX_POOLED_BUFFERS
+        {
+            return;
+        }
+        buffer.clear();
+        self.arg_buffers.push(buffer);
+    }
+
+    /// An empty map for the variables of a function call
+    pub fn take_variables(&mut self) -> HashMap<ClarityName, Value> {
+        match self.variable_maps.pop() {
+            Some(variables) => {
+                self.reused += 1;
+                variables
+            }
+            None => {
+                self.allocated += 1;
+                HashMap::new()
+            }
+        }
+    }
+
+    pub fn return_variables(&mut self, mut variables: HashMap<ClarityName, Value>) {
+        if variables.capacity() == 0
+            || variables.capacity() > MAX_POOLED_CAPACITY
+            || self.variable_maps.len() >= MAX_POOLED_BUFFERS
+        {
+            return;
+        }
+        variables.clear();
+        self.variable_maps.push(variables);
+    }
+
+    /// An empty call stack for a transaction
+    pub fn take_call_stack(&mut self) -> CallStack {
+        match self.call_stack.take() {
+            Some(call_stack) => {
+                self.reused += 1;
+                call_stack
+            }
+            None => {
+                self.allocated += 1;
+                CallStack::new()
+            }
+        }
+    }
+
+    pub fn return_call_stack(&mut self, mut call_stack: CallStack) {
+        call_stack.clear();
+        self.call_stack = Some(call_stack);
+    }
+
+    /// How many buffers were handed out from the pool, and how many had to be allocated
+    pub fn stats(&self) -> (u64, u64) {
+        (self.reused, self.allocated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_pool_reuse() {
+        let mut pool = EvalPool::new();
+        let mut args = pool.take_args();
+        args.extend(vec![Value::Int(1), Value::Int(2)]);
+        let capacity = args.capacity();
+        pool.return_args(args);
+        let args = pool.take_args();
+        assert!(args.is_empty());
+        assert_eq!(args.capacity(), capacity);
+        assert_eq!(pool.stats(), (1, 1));
+
+        // unused and oversized buffers are not kept
+        pool.return_args(Vec::new());
+        pool.return_args(Vec::with_capacity(MAX_POOLED_CAPACITY + 1));
+        pool.take_args();
+        assert_eq!(pool.stats(), (1, 2));
+
+        let mut variables = pool.take_variables();
+        variables.insert("a".into(), Value::Bool(true));
+        pool.return_variables(variables);
+        assert!(pool.take_variables().is_empty());
+
+        let mut call_stack = pool.take_call_stack();
+        call_stack.incr_apply_depth();
+        pool.return_call_stack(call_stack);
+        assert_eq!(pool.take_call_stack().depth(), 0);
+        assert_eq!(pool.stats(), (3, 4));
+    }
+}
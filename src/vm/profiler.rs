@@ -0,0 +1,233 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Attribute execution costs to the expressions that incurred them.
+//!
+//! `CostProfiler` is an `EvalHook` which keeps a stack of the function applications being
+//! evaluated, and charges the cost consumed by each one (less the cost of the applications
+//! nested in it) to that stack.  The result can be written out in the "folded stacks" format
+//! understood by `flamegraph.pl`, `inferno-flamegraph`, and speedscope.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use vm::costs::ExecutionCost;
+use vm::errors::InterpreterResult;
+use vm::types::QualifiedContractIdentifier;
+use vm::{Environment, EvalHook, LocalContext, SymbolicExpression, Value};
+
+/// One of the dimensions of an `ExecutionCost`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CostDimension {
+    Runtime,
+    ReadCount,
+    ReadLength,
+    WriteCount,
+    WriteLength,
+}
+
+impl CostDimension {
+    pub const ALL: [CostDimension; 5] = [
+        CostDimension::Runtime,
+        CostDimension::ReadCount,
+        CostDimension::ReadLength,
+        CostDimension::WriteCount,
+        CostDimension::WriteLength,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            CostDimension::Runtime => "runtime",
+            CostDimension::ReadCount => "read_count",
+            CostDimension::ReadLength => "read_length",
+            CostDimension::WriteCount => "write_count",
+            CostDimension::WriteLength => "write_length",
+        }
+    }
+
+    pub fn of(&self, cost: &ExecutionCost) -> u64 {
+        match self {
+            CostDimension::Runtime => cost.runtime,
+            CostDimension::ReadCount => cost.read_count,
+            CostDimension::ReadLength => cost.read_length,
+            CostDimension::WriteCount => cost.write_count,
+            CostDimension::WriteLength => cost.write_length,
+        }
+    }
+}
+
+impl fmt::Display for CostDimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+impl FromStr for CostDimension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CostDimension, String> {
+        CostDimension::ALL
+            .iter()
+            .find(|dim| dim.get_name() == s)
+            .cloned()
+            .ok_or_else(|| format!("Unknown cost dimension: {}", s))
+    }
+}
+
+/// A function application that is still being evaluated
+struct ProfileFrame {
+    label: String,
+    /// total cost of the applications evaluated within this one so far
+    children_cost: ExecutionCost,
+}
+
+#[derive(Default)]
+struct ProfileState {
+    stack: Vec<ProfileFrame>,
+    /// folded stack (`a;b;c`) => cost charged to its innermost frame
+    self_costs: BTreeMap<String, ExecutionCost>,
+}
+
+/// Cost profiler.  Clones share the same profile, so one clone can be registered on an
+/// `OwnedEnvironment` with `add_eval_hook()` and another used to read the profile afterwards.
+#[derive(Clone, Default)]
+pub struct CostProfiler {
+    state: Rc<RefCell<ProfileState>>,
+}
+
+impl CostProfiler {
+    pub fn new() -> CostProfiler {
+        CostProfiler::default()
+    }
+
+    /// Label for an application of `name`.  Frame labels can't contain spaces or `;`, which
+    /// delimit folded stacks -- neither can contract identifiers or Clarity names.
+    #[cfg(feature = "developer-mode")]
+    fn frame_label(
+        contract: &QualifiedContractIdentifier,
+        name: &str,
+        expr: &SymbolicExpression,
+    ) -> String {
+        format!("{}::{}:{}", contract, name, expr.span.start_line)
+    }
+
+    #[cfg(not(feature = "developer-mode"))]
+    fn frame_label(
+        contract: &QualifiedContractIdentifier,
+        name: &str,
+        _expr: &SymbolicExpression,
+    ) -> String {
+        format!("{}::{}", contract, name)
+    }
+
+    /// The cost charged to each folded stack, in stack order
+    pub fn get_self_costs(&self) -> Vec<(String, ExecutionCost)> {
+        self.state
+            .borrow()
+            .self_costs
+            .iter()
+            .map(|(stack, cost)| (stack.clone(), cost.clone()))
+            .collect()
+    }
+
+    /// Write the profile as folded stacks, one `frame;frame;frame value` line per stack, where
+    /// the value is the given dimension of the cost charged to that stack.  Stacks which were
+    /// charged nothing in that dimension are omitted.
+    pub fn write_folded<W: Write>(&self, w: &mut W, dimension: CostDimension) -> io::Result<()> {
+        for (stack, cost) in self.state.borrow().self_costs.iter() {
+            let value = dimension.of(cost);
+            if value > 0 {
+                writeln!(w, "{} {}", stack, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EvalHook for CostProfiler {
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        _context: &LocalContext,
+        expr: &SymbolicExpression,
+    ) {
+        let name = match expr.match_list() {
+            Some(list) => list
+                .first()
+                .and_then(|f| f.match_atom())
+                .map(|name| name.as_str())
+                .unwrap_or("<unknown>"),
+            None => {
+                // atoms and values are charged to the enclosing application
+                return;
+            }
+        };
+        let label =
+            CostProfiler::frame_label(&env.contract_context.contract_identifier, name, expr);
+        self.state.borrow_mut().stack.push(ProfileFrame {
+            label,
+            children_cost: ExecutionCost::zero(),
+        });
+    }
+
+    fn did_finish_eval(
+        &mut self,
+        _env: &mut Environment,
+        _context: &LocalContext,
+        expr: &SymbolicExpression,
+        cost: &ExecutionCost,
+        _res: &InterpreterResult<Value>,
+    ) {
+        if expr.match_list().is_none() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let folded = state
+            .stack
+            .iter()
+            .map(|frame| frame.label.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let frame = match state.stack.pop() {
+            Some(frame) => frame,
+            None => {
+                // the profiler was registered part-way through an evaluation
+                return;
+            }
+        };
+
+        let mut self_cost = cost.clone();
+        if self_cost.sub(&frame.children_cost).is_err() {
+            self_cost = ExecutionCost::zero();
+        }
+
+        let _ = state
+            .self_costs
+            .entry(folded)
+            .or_insert_with(ExecutionCost::zero)
+            .add(&self_cost);
+
+        if let Some(parent) = state.stack.last_mut() {
+            let _ = parent.children_cost.add(cost);
+        }
+    }
+}
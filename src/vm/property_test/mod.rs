@@ -0,0 +1,545 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Deterministic property testing of Clarity contracts.
+//!
+//! A `PropertyTest` deploys a contract into a fresh `MemoryBackingStore` once per run, then calls
+//! randomly chosen public functions with random arguments of the types the contract's analysis
+//! gives them, from randomly chosen senders.  After deployment and after every call, it checks
+//! the test's invariants: read-only Clarity expressions, evaluated in the contract's context,
+//! which must evaluate to `true`, and Rust checks of each call's outcome.  All randomness is drawn
+//! from a ChaCha RNG seeded from the test's seed and the run number, so a failure reproduces
+//! exactly, and is reported with the sequence of calls that led to it.
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+use vm::analysis::{run_analysis, ContractAnalysis};
+use vm::ast::build_ast;
+use vm::contexts::OwnedEnvironment;
+use vm::costs::LimitedCostTracker;
+use vm::errors::Error as InterpreterError;
+use vm::representations::SymbolicExpression;
+use vm::types::signatures::FunctionType;
+use vm::types::{
+    PrincipalData, QualifiedContractIdentifier, SequenceSubtype, StandardPrincipalData,
+    StringSubtype, TupleData, TypeSignature, Value,
+};
+
+use crate::clarity_vm::database::MemoryBackingStore;
+use crate::core::StacksEpochId;
+
+#[cfg(test)]
+mod tests;
+
+/// Characters drawn for `string-utf8` values, including multi-byte ones
+const UTF8_CHARS: &[char] = &[
+    'a',
+    'Z',
+    '0',
+    ' ',
+    '\u{e9}',
+    '\u{df}',
+    '\u{4e2d}',
+    '\u{1f600}',
+];
+
+const INT_EDGE_CASES: &[i128] = &[0, 1, -1, 2, i128::MAX, i128::MIN, i128::MAX - 1];
+const UINT_EDGE_CASES: &[u128] = &[0, 1, 2, u128::MAX, u128::MAX - 1];
+
+/// What random values are drawn from
+#[derive(Debug, Clone)]
+pub struct ValueGenerator {
+    /// upper bound on the length of generated lists, buffers and strings, on top of the bound
+    /// their type declares
+    pub max_sequence_len: u32,
+    /// the principals generated `principal` values are chosen from
+    pub principals: Vec<PrincipalData>,
+}
+
+impl ValueGenerator {
+    /// A random value of type `ty`, or `None` if no value of the type can be made up (trait
+    /// references, which must name a deployed contract implementing the trait)
+    pub fn generate<R: Rng>(&self, rng: &mut R, ty: &TypeSignature) -> Option<Value> {
+        use vm::types::TypeSignature::*;
+        let value = match ty {
+            NoType | TraitReferenceType(_) => return None,
+            IntType => {
+                if rng.gen_ratio(1, 4) {
+                    Value::Int(*INT_EDGE_CASES.choose(rng)?)
+                } else if rng.gen_bool(0.5) {
+                    Value::Int(rng.gen_range(-100, 100))
+                } else {
+                    Value::Int(rng.gen())
+                }
+            }
+            UIntType => {
+                if rng.gen_ratio(1, 4) {
+                    Value::UInt(*UINT_EDGE_CASES.choose(rng)?)
+                } else if rng.gen_bool(0.5) {
+                    Value::UInt(rng.gen_range(0, 100))
+                } else {
+                    Value::UInt(rng.gen())
+                }
+            }
+            BoolType => Value::Bool(rng.gen()),
+            PrincipalType => Value::Principal(self.principals.choose(rng)?.clone()),
+            SequenceType(SequenceSubtype::BufferType(max_len)) => {
+                let len = self.sequence_len(rng, u32::from(max_len));
+                Value::buff_from((0..len).map(|_| rng.gen()).collect()).ok()?
+            }
+            SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(max_len))) => {
+                let len = self.sequence_len(rng, u32::from(max_len));
+                Value::string_ascii_from_bytes(
+                    (0..len).map(|_| rng.gen_range(0x20, 0x7f)).collect(),
+                )
+                .ok()?
+            }
+            SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(max_len))) => {
+                let len = self.sequence_len(rng, u32::from(max_len));
+                let data: String = (0..len)
+                    .map(|_| *UTF8_CHARS.choose(rng).expect("BUG: no UTF-8 characters"))
+                    .collect();
+                Value::string_utf8_from_bytes(data.into_bytes()).ok()?
+            }
+            SequenceType(SequenceSubtype::ListType(list_type)) => {
+                let len = self.sequence_len(rng, list_type.get_max_len());
+                let items = (0..len)
+                    .map(|_| self.generate(rng, list_type.get_list_item_type()))
+                    .collect::<Option<Vec<_>>>()?;
+                Value::list_with_type(items, list_type.clone()).ok()?
+            }
+            TupleType(tuple_type) => {
+                let fields = tuple_type
+                    .get_type_map()
+                    .iter()
+                    .map(|(name, field_type)| Some((name.clone(), self.generate(rng, field_type)?)))
+                    .collect::<Option<Vec<_>>>()?;
+                Value::from(TupleData::from_data(fields).ok()?)
+            }
+            OptionalType(inner) => {
+                if rng.gen_ratio(1, 4) {
+                    Value::none()
+                } else {
+                    Value::some(self.generate(rng, inner)?).ok()?
+                }
+            }
+            ResponseType(inner) => {
+                let (ok_type, err_type) = inner.as_ref();
+                let ok = match (ok_type, err_type) {
+                    (NoType, _) => false,
+                    (_, NoType) => true,
+                    _ => rng.gen(),
+                };
+                if ok {
+                    Value::okay(self.generate(rng, ok_type)?).ok()?
+                } else {
+                    Value::error(self.generate(rng, err_type)?).ok()?
+                }
+            }
+        };
+        debug_assert!(ty.admits(&value), "generated {} for {}", value, ty);
+        Some(value)
+    }
+
+    fn sequence_len<R: Rng>(&self, rng: &mut R, max_len: u32) -> u32 {
+        rng.gen_range(0, max_len.min(self.max_sequence_len) + 1)
+    }
+}
+
+/// A public function call made by a property test
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub sender: PrincipalData,
+    pub function_name: String,
+    pub args: Vec<Value>,
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ({}", self.sender, self.function_name)?;
+        for arg in self.args.iter() {
+            write!(f, " {}", arg)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// How a call ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallOutcome {
+    /// the function returned an `ok` response, and its changes were committed
+    Committed(Value),
+    /// the function returned an `err` response, and its changes were rolled back
+    RolledBack(Value),
+    /// the call failed with a runtime error, e.g., an `unwrap-panic` of `none`
+    Aborted(String),
+}
+
+type CallCheck = Box<dyn Fn(&Call, &CallOutcome) -> Result<(), String>>;
+
+enum Invariant {
+    Expression(String),
+    Call(CallCheck),
+}
+
+/// The counterexample found by a property test
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyTestFailure {
+    pub seed: u64,
+    pub run: u32,
+    /// the calls made in the failing run, the last of which broke the invariant
+    pub calls: Vec<Call>,
+    pub invariant: String,
+    pub message: String,
+}
+
+impl fmt::Display for PropertyTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invariant `{}` failed in run {} of seed {}: {}",
+            self.invariant, self.run, self.seed, self.message
+        )?;
+        if self.calls.is_empty() {
+            write!(f, "\n  (after deployment)")?;
+        }
+        for call in self.calls.iter() {
+            write!(f, "\n  {}", call)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyTestError {
+    /// the contract could not be analyzed or deployed, or has no public function that can be
+    /// called with generated arguments
+    Setup(String),
+    Failed(PropertyTestFailure),
+}
+
+impl fmt::Display for PropertyTestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropertyTestError::Setup(e) => write!(f, "property test setup failed: {}", e),
+            PropertyTestError::Failed(failure) => write!(f, "{}", failure),
+        }
+    }
+}
+
+impl error::Error for PropertyTestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+/// Summary of a passing property test
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropertyTestReport {
+    pub runs: u32,
+    pub committed: u64,
+    pub rolled_back: u64,
+    pub aborted: u64,
+    /// public functions that were never called, because some argument is a trait reference
+    pub skipped_functions: Vec<String>,
+}
+
+pub struct PropertyTest {
+    contract_identifier: QualifiedContractIdentifier,
+    source: String,
+    epoch: StacksEpochId,
+    seed: u64,
+    runs: u32,
+    calls_per_run: u32,
+    senders: Vec<PrincipalData>,
+    initial_balance: u128,
+    max_sequence_len: u32,
+    invariants: Vec<(String, Invariant)>,
+}
+
+fn default_senders() -> Vec<PrincipalData> {
+    (1..=3)
+        .map(|i| StandardPrincipalData(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, [i; 20]).into())
+        .collect()
+}
+
+impl PropertyTest {
+    pub fn new(contract_identifier: QualifiedContractIdentifier, source: &str) -> PropertyTest {
+        PropertyTest {
+            contract_identifier,
+            source: source.to_string(),
+            epoch: StacksEpochId::latest(),
+            seed: 0,
+            runs: 100,
+            calls_per_run: 20,
+            senders: default_senders(),
+            initial_balance: 1_000_000_000,
+            max_sequence_len: 8,
+            invariants: vec![],
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn runs(mut self, runs: u32) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    pub fn calls_per_run(mut self, calls_per_run: u32) -> Self {
+        self.calls_per_run = calls_per_run;
+        self
+    }
+
+    pub fn epoch(mut self, epoch: StacksEpochId) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// Make calls from `senders`, each of which starts every run with `initial_balance` uSTX
+    pub fn senders(mut self, senders: Vec<PrincipalData>, initial_balance: u128) -> Self {
+        self.senders = senders;
+        self.initial_balance = initial_balance;
+        self
+    }
+
+    pub fn max_sequence_len(mut self, max_sequence_len: u32) -> Self {
+        self.max_sequence_len = max_sequence_len;
+        self
+    }
+
+    /// Require the read-only Clarity `expression` to evaluate to `true` after deployment and
+    /// after every call
+    pub fn invariant(mut self, name: &str, expression: &str) -> Self {
+        self.invariants.push((
+            name.to_string(),
+            Invariant::Expression(expression.to_string()),
+        ));
+        self
+    }
+
+    /// Require `check` to accept the outcome of every call
+    pub fn check_calls<F>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn(&Call, &CallOutcome) -> Result<(), String> + 'static,
+    {
+        self.invariants
+            .push((name.to_string(), Invariant::Call(Box::new(check))));
+        self
+    }
+
+    /// The generator of argument values: senders and the contract itself are the principals
+    pub fn value_generator(&self) -> ValueGenerator {
+        let mut principals = self.senders.clone();
+        principals.push(self.contract_identifier.clone().into());
+        ValueGenerator {
+            max_sequence_len: self.max_sequence_len,
+            principals,
+        }
+    }
+
+    fn analyze(&self, store: &mut MemoryBackingStore) -> Result<ContractAnalysis, String> {
+        let mut ast = build_ast(&self.contract_identifier, &self.source, &mut ())
+            .map_err(|e| e.to_string())?;
+        run_analysis(
+            &self.contract_identifier,
+            &mut ast.expressions,
+            &mut store.as_analysis_db(),
+            true,
+            LimitedCostTracker::new_free(),
+            self.epoch,
+        )
+        .map_err(|(e, _)| e.to_string())
+    }
+
+    /// Deploy the contract into `store` and fund the senders
+    fn deploy<'a>(
+        &self,
+        store: &'a mut MemoryBackingStore,
+    ) -> Result<OwnedEnvironment<'a>, String> {
+        self.analyze(store)?;
+        let mut env = OwnedEnvironment::new_free(false, store.as_clarity_db(), self.epoch);
+        env.initialize_contract(self.contract_identifier.clone(), &self.source)
+            .map_err(|e| e.to_string())?;
+        for sender in self.senders.iter() {
+            let amount = self.initial_balance;
+            env.execute_in_env(sender.clone(), |env| {
+                let mut snapshot = env.global_context.database.get_stx_balance_snapshot(sender);
+                let mut balance = snapshot.balance().clone();
+                balance.amount_unlocked += amount;
+                snapshot.set_balance(balance);
+                snapshot.save();
+                env.global_context
+                    .database
+                    .increment_ustx_liquid_supply(amount)
+            })
+            .map_err(|e: InterpreterError| e.to_string())?;
+        }
+        Ok(env)
+    }
+
+    fn check(
+        &self,
+        env: &mut OwnedEnvironment,
+        last_call: Option<(&Call, &CallOutcome)>,
+    ) -> Result<(), (String, String)> {
+        for (name, invariant) in self.invariants.iter() {
+            let result = match (invariant, last_call) {
+                (Invariant::Expression(expression), _) => {
+                    match env.eval_read_only(&self.contract_identifier, expression) {
+                        Ok((Value::Bool(true), _, _)) => Ok(()),
+                        Ok((value, _, _)) => Err(format!("evaluated to {}", value)),
+                        Err(e) => Err(format!("failed to evaluate: {}", e)),
+                    }
+                }
+                (Invariant::Call(check), Some((call, outcome))) => check(call, outcome),
+                (Invariant::Call(_), None) => Ok(()),
+            };
+            result.map_err(|message| (name.clone(), message))?;
+        }
+        Ok(())
+    }
+
+    /// Run the test, returning the first counterexample found
+    pub fn run(&self) -> Result<PropertyTestReport, PropertyTestError> {
+        let analysis = self
+            .analyze(&mut MemoryBackingStore::new())
+            .map_err(PropertyTestError::Setup)?;
+        let mut functions: BTreeMap<String, Vec<TypeSignature>> = BTreeMap::new();
+        let mut report = PropertyTestReport::default();
+        for (name, function_type) in analysis.public_function_types.iter() {
+            let arg_types: Vec<_> = match function_type {
+                FunctionType::Fixed(function) => function
+                    .args
+                    .iter()
+                    .map(|arg| arg.signature.clone())
+                    .collect(),
+                _ => continue,
+            };
+            if arg_types.iter().any(|ty| {
+                matches!(
+                    ty,
+                    TypeSignature::TraitReferenceType(_) | TypeSignature::NoType
+                )
+            }) {
+                report.skipped_functions.push(name.to_string());
+            } else {
+                functions.insert(name.to_string(), arg_types);
+            }
+        }
+        if functions.is_empty() {
+            return Err(PropertyTestError::Setup(
+                "no public function can be called with generated arguments".into(),
+            ));
+        }
+        let functions: Vec<_> = functions.into_iter().collect();
+        let generator = self.value_generator();
+
+        for run in 0..self.runs {
+            let mut rng = ChaCha20Rng::seed_from_u64(self.seed.wrapping_add(run as u64));
+            let mut store = MemoryBackingStore::new();
+            let mut env = self.deploy(&mut store).map_err(PropertyTestError::Setup)?;
+            let mut calls = vec![];
+
+            let fail = |calls: Vec<Call>, (invariant, message)| {
+                PropertyTestError::Failed(PropertyTestFailure {
+                    seed: self.seed,
+                    run,
+                    calls,
+                    invariant,
+                    message,
+                })
+            };
+
+            if let Err(e) = self.check(&mut env, None) {
+                return Err(fail(calls, e));
+            }
+
+            for _ in 0..self.calls_per_run {
+                let (function_name, arg_types) = functions
+                    .choose(&mut rng)
+                    .expect("BUG: no functions to call");
+                let sender = self
+                    .senders
+                    .choose(&mut rng)
+                    .cloned()
+                    .ok_or_else(|| PropertyTestError::Setup("no senders".into()))?;
+                let args = arg_types
+                    .iter()
+                    .map(|ty| generator.generate(&mut rng, ty))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        PropertyTestError::Setup(format!(
+                            "could not generate arguments for `{}`",
+                            function_name
+                        ))
+                    })?;
+                let call = Call {
+                    sender,
+                    function_name: function_name.clone(),
+                    args,
+                };
+
+                let arg_exprs: Vec<_> = call
+                    .args
+                    .iter()
+                    .map(|arg| SymbolicExpression::atom_value(arg.clone()))
+                    .collect();
+                let outcome = match env.execute_transaction(
+                    call.sender.clone(),
+                    self.contract_identifier.clone(),
+                    &call.function_name,
+                    &arg_exprs,
+                ) {
+                    Ok((Value::Response(response), _, _)) if response.committed => {
+                        report.committed += 1;
+                        CallOutcome::Committed(*response.data)
+                    }
+                    Ok((Value::Response(response), _, _)) => {
+                        report.rolled_back += 1;
+                        CallOutcome::RolledBack(*response.data)
+                    }
+                    Ok((value, _, _)) => {
+                        report.aborted += 1;
+                        CallOutcome::Aborted(format!("returned a non-response {}", value))
+                    }
+                    Err(e) => {
+                        report.aborted += 1;
+                        CallOutcome::Aborted(e.to_string())
+                    }
+                };
+
+                let result = self.check(&mut env, Some((&call, &outcome)));
+                calls.push(call);
+                if let Err(e) = result {
+                    return Err(fail(calls, e));
+                }
+            }
+            report.runs += 1;
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use vm::ast::parse;
+use vm::property_test::{CallOutcome, PropertyTest, PropertyTestError};
+use vm::types::{QualifiedContractIdentifier, TypeSignature};
+
+const TOKEN_CONTRACT: &str = "(define-fungible-token token)
+(define-data-var minted uint u0)
+(define-public (mint (amount uint))
+  (begin
+    (asserts! (<= amount u1000) (err u1))
+    (var-set minted (+ (var-get minted) amount))
+    (ft-mint? token amount tx-sender)))
+(define-public (transfer (amount uint) (recipient principal) (memo (optional (buff 34))))
+  (ft-transfer? token amount tx-sender recipient))
+(define-public (burn-all)
+  (let ((balance (ft-get-balance token tx-sender)))
+    (var-set minted (- (var-get minted) balance))
+    (if (> balance u0) (ft-burn? token balance tx-sender) (ok true))))";
+
+fn token_test() -> PropertyTest {
+    PropertyTest::new(
+        QualifiedContractIdentifier::local("token").unwrap(),
+        TOKEN_CONTRACT,
+    )
+    .runs(10)
+    .calls_per_run(15)
+}
+
+#[test]
+fn test_invariants_hold() {
+    let report = token_test()
+        .invariant("supply", "(is-eq (var-get minted) (ft-get-supply token))")
+        .check_calls("mint limit", |call, outcome| match outcome {
+            CallOutcome::Committed(_) if call.function_name == "mint" => {
+                if call.args[0].clone().expect_u128() <= 1000 {
+                    Ok(())
+                } else {
+                    Err(format!("minted {}", call.args[0]))
+                }
+            }
+            _ => Ok(()),
+        })
+        .run()
+        .unwrap();
+    assert_eq!(report.runs, 10);
+    assert_eq!(
+        report.committed + report.rolled_back + report.aborted,
+        10 * 15
+    );
+    assert!(report.committed > 0);
+    assert!(report.rolled_back > 0);
+    assert!(report.skipped_functions.is_empty());
+}
+
+#[test]
+fn test_counterexample() {
+    let test = || {
+        token_test()
+            .seed(7)
+            .invariant("no supply", "(is-eq (ft-get-supply token) u0)")
+    };
+    let failure = match test().run() {
+        Err(PropertyTestError::Failed(failure)) => failure,
+        x => panic!("Expected a counterexample, got {:?}", x),
+    };
+    assert_eq!(failure.invariant, "no supply");
+    assert_eq!(failure.seed, 7);
+    assert!(failure.message.contains("evaluated to false"));
+    let last = failure.calls.last().unwrap();
+    assert_eq!(last.function_name, "mint");
+
+    // the same seed finds the same counterexample
+    match test().run() {
+        Err(PropertyTestError::Failed(again)) => assert_eq!(again, failure),
+        x => panic!("Expected a counterexample, got {:?}", x),
+    }
+    let display = failure.to_string();
+    assert!(display.starts_with("invariant `no supply` failed in run"));
+    assert_eq!(display.lines().count(), failure.calls.len() + 1);
+
+    // invariants are checked after deployment, too
+    match token_test()
+        .invariant("minted", "(> (var-get minted) u0)")
+        .run()
+    {
+        Err(PropertyTestError::Failed(failure)) => assert!(failure.calls.is_empty()),
+        x => panic!("Expected a counterexample, got {:?}", x),
+    }
+}
+
+#[test]
+fn test_setup_errors() {
+    let contract_id = QualifiedContractIdentifier::local("c").unwrap();
+    let trait_only = "(define-trait t ((f () (response bool bool))))
+(define-public (call (x <t>)) (contract-call? x f))";
+    match PropertyTest::new(contract_id.clone(), trait_only).run() {
+        Err(PropertyTestError::Setup(_)) => {}
+        x => panic!("Expected a setup error, got {:?}", x),
+    }
+    match PropertyTest::new(contract_id, "(define-public (f) (ok (+ 1 u1)))").run() {
+        Err(PropertyTestError::Setup(e)) => assert!(e.contains("TypeError")),
+        x => panic!("Expected a setup error, got {:?}", x),
+    }
+}
+
+#[test]
+fn test_generated_values_are_well_typed() {
+    let generator = token_test().value_generator();
+    let mut rng = ChaCha20Rng::seed_from_u64(0);
+    let type_reprs = [
+        "int",
+        "uint",
+        "bool",
+        "principal",
+        "(buff 3)",
+        "(string-ascii 20)",
+        "(string-utf8 4)",
+        "(list 50 (optional (tuple (a int) (b (list 2 (string-utf8 2))))))",
+        "(response (list 3 uint) (buff 1))",
+    ];
+    for type_repr in type_reprs.iter() {
+        let expr = parse(&QualifiedContractIdentifier::transient(), type_repr).unwrap();
+        let ty = TypeSignature::parse_type_repr(&expr[0], &mut ()).unwrap();
+        for _ in 0..50 {
+            let value = generator.generate(&mut rng, &ty).unwrap();
+            assert!(ty.admits(&value), "{} does not admit {}", type_repr, value);
+        }
+    }
+}
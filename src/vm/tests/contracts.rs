@@ -1060,7 +1060,7 @@ fn test_at_unknown_block() {
             .unwrap_err();
         eprintln!("{}", err);
         match err {
-            Error::Runtime(x, _) => assert_eq!(
+            Error::Runtime(x, ..) => assert_eq!(
                 x,
                 RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash::from(
                     vec![2 as u8; 32].as_slice()
@@ -22,19 +22,21 @@ use crate::types::proof::ClarityMarfTrieId;
 use crate::util::boot::boot_code_id;
 use chainstate::stacks::events::StacksTransactionEvent;
 use chainstate::stacks::index::storage::TrieFileStorage;
-use clarity_vm::clarity::ClarityInstance;
+use clarity_vm::clarity::{ClarityConnection, ClarityInstance};
 use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use core::FIRST_STACKS_BLOCK_HASH;
 use std::collections::HashMap;
+use std::sync::Arc;
 use util::hash::hex_bytes;
 use vm::contexts::Environment;
 use vm::contexts::{AssetMap, AssetMapEntry, GlobalContext, OwnedEnvironment};
 use vm::contracts::Contract;
 use vm::costs::cost_functions::ClarityCostFunction;
-use vm::costs::{ClarityCostFunctionReference, ExecutionCost, LimitedCostTracker};
+use vm::costs::{ClarityCostFunctionReference, CostOverrides, ExecutionCost, LimitedCostTracker};
 use vm::database::ClarityDatabase;
 use vm::errors::{CheckErrors, Error, RuntimeErrorType};
 use vm::functions::NativeFunctions;
+use vm::profiler::{CostDimension, CostProfiler};
 use vm::representations::SymbolicExpression;
 use vm::tests::{
     execute, execute_on_network, is_committed, is_err_code, symbols_from_values,
@@ -106,6 +108,19 @@ pub fn get_simple_test(function: &NativeFunctions) -> &'static str {
         Print => "(print 1)",
         ContractCall => "(contract-call? .contract-other foo-exec 1)",
         ContractOf => "(contract-of contract)",
+        ImplementsTrait => "(implements-trait .contract-other .contract-trait.trait-1)",
+        GetPublicFunctions => "(get-public-functions? .contract-other)",
+        BuffToIntLe => "(buff-to-int-le 0x0102)",
+        BuffToIntBe => "(buff-to-int-be 0x0102)",
+        BuffToUIntLe => "(buff-to-uint-le 0x0102)",
+        BuffToUIntBe => "(buff-to-uint-be 0x0102)",
+        IntToBuffLe => "(int-to-buff-le 1)",
+        IntToBuffBe => "(int-to-buff-be 1)",
+        UIntToBuffLe => "(uint-to-buff-le u1)",
+        UIntToBuffBe => "(uint-to-buff-be u1)",
+        StringToInt => "(string-to-int? \"-12\")",
+        StringToUInt => "(string-to-uint? \"12\")",
+        Slice => "(slice? \"abcd\" u1 u3)",
         PrincipalOf => "(principal-of? 0x03adb8de4bfb65db2cfd6120d55c6526ae9c52e675db7e47308636534ba7786110)",
         AsContract => "(as-contract 1)",
         GetBlockInfo => "(get-block-info? time u1)",
@@ -167,7 +182,7 @@ where
         )
         .commit_block();
 
-    let tip = if epoch == StacksEpochId::Epoch2_05 {
+    let tip = if epoch >= StacksEpochId::Epoch2_05 {
         let next_block = StacksBlockId([1 as u8; 32]);
         let mut clarity_conn = clarity_instance.begin_block(
             &first_block,
@@ -176,6 +191,9 @@ where
             &TEST_BURN_STATE_DB,
         );
         clarity_conn.initialize_epoch_2_05().unwrap();
+        if epoch >= StacksEpochId::Epoch21 {
+            clarity_conn.initialize_epoch_2_1().unwrap();
+        }
         clarity_conn.commit_block();
         next_block
     } else {
@@ -474,6 +492,62 @@ fn epoch205_concat_testnet() {
     epoch205_concat(false)
 }
 
+// `string-to-int?` and `string-to-uint?` are charged by the size of the string, even though
+//  overlong strings are rejected without being read
+fn epoch21_string_to_int_input_size(use_mainnet: bool) {
+    let long_string = "1".repeat(1000);
+    for function in ["string-to-int?", "string-to-uint?"].iter() {
+        let small_cost = exec_cost(
+            &format!(
+                "(define-public (execute) (begin ({} u\"1\") (ok 1)))",
+                function
+            ),
+            use_mainnet,
+            StacksEpochId::Epoch21,
+        )
+        .runtime
+            - exec_cost(
+                "(define-public (execute) (begin u\"1\" (ok 1)))",
+                use_mainnet,
+                StacksEpochId::Epoch21,
+            )
+            .runtime;
+        let large_cost = exec_cost(
+            &format!(
+                "(define-public (execute) (begin ({} u\"{}\") (ok 1)))",
+                function, long_string
+            ),
+            use_mainnet,
+            StacksEpochId::Epoch21,
+        )
+        .runtime
+            - exec_cost(
+                &format!(
+                    "(define-public (execute) (begin u\"{}\" (ok 1)))",
+                    long_string
+                ),
+                use_mainnet,
+                StacksEpochId::Epoch21,
+            )
+            .runtime;
+        assert!(
+            large_cost > small_cost,
+            "{} should cost more for a longer string",
+            function
+        );
+    }
+}
+
+#[test]
+fn epoch21_string_to_int_input_size_mainnet() {
+    epoch21_string_to_int_input_size(true)
+}
+
+#[test]
+fn epoch21_string_to_int_input_size_testnet() {
+    epoch21_string_to_int_input_size(false)
+}
+
 // Test the `var-get` changes in epoch 2.05. Using a dynamic input to the cost function will make the difference in runtime
 // cost larger when larger objects are fetched from the datastore.
 fn epoch205_var_get(use_mainnet: bool) {
@@ -860,7 +934,10 @@ fn test_tracked_costs(prog: &str, use_mainnet: bool, epoch: StacksEpochId) -> Ex
 fn test_all(use_mainnet: bool) {
     let baseline = test_tracked_costs("1", use_mainnet, StacksEpochId::Epoch20);
 
-    for f in NativeFunctions::ALL.iter() {
+    for f in NativeFunctions::ALL
+        .iter()
+        .filter(|f| f.get_min_epoch() <= StacksEpochId::Epoch20)
+    {
         let test = get_simple_test(f);
         let cost = test_tracked_costs(test, use_mainnet, StacksEpochId::Epoch20);
         assert!(cost.exceeds(&baseline));
@@ -882,7 +959,10 @@ fn test_all_testnet() {
 fn epoch_205_test_all(use_mainnet: bool) {
     let baseline = test_tracked_costs("1", use_mainnet, StacksEpochId::Epoch2_05);
 
-    for f in NativeFunctions::ALL.iter() {
+    for f in NativeFunctions::ALL
+        .iter()
+        .filter(|f| f.get_min_epoch() <= StacksEpochId::Epoch2_05)
+    {
         let test = get_simple_test(f);
         let cost = test_tracked_costs(test, use_mainnet, StacksEpochId::Epoch2_05);
         assert!(cost.exceeds(&baseline));
@@ -899,6 +979,28 @@ fn epoch_205_test_all_testnet() {
     epoch_205_test_all(false)
 }
 
+// test each individual cost function can be correctly invoked as
+//  Clarity code executes in Epoch 2.1, including the natives introduced there
+fn epoch_21_test_all(use_mainnet: bool) {
+    let baseline = test_tracked_costs("1", use_mainnet, StacksEpochId::Epoch21);
+
+    for f in NativeFunctions::ALL.iter() {
+        let test = get_simple_test(f);
+        let cost = test_tracked_costs(test, use_mainnet, StacksEpochId::Epoch21);
+        assert!(cost.exceeds(&baseline));
+    }
+}
+
+#[test]
+fn epoch_21_test_all_mainnet() {
+    epoch_21_test_all(true)
+}
+
+#[test]
+fn epoch_21_test_all_testnet() {
+    epoch_21_test_all(false)
+}
+
 fn test_cost_contract_short_circuits(use_mainnet: bool) {
     let marf_kv = MarfedKV::temporary();
     let mut clarity_instance = ClarityInstance::new(use_mainnet, marf_kv);
@@ -1534,3 +1636,152 @@ fn test_cost_voting_integration(use_mainnet: bool) {
 fn test_cost_voting_integration_testnet() {
     test_cost_voting_integration(false)
 }
+
+#[test]
+fn test_cost_profiler() {
+    let contract = "(define-data-var counter int 0)
+(define-private (bump)
+  (var-set counter (+ (var-get counter) 1)))
+(define-public (bump-twice)
+  (begin
+    (bump)
+    (bump)
+    (ok (var-get counter))))";
+
+    with_owned_env(StacksEpochId::Epoch20, false, |mut owned_env| {
+        let contract_id = QualifiedContractIdentifier::local("profiled").unwrap();
+        let sender = PrincipalData::parse("SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR").unwrap();
+        owned_env
+            .initialize_contract(contract_id.clone(), contract)
+            .unwrap();
+
+        let profiler = CostProfiler::new();
+        owned_env.add_eval_hook(Box::new(profiler.clone()));
+
+        let cost_before = owned_env.get_cost_total();
+        owned_env
+            .execute_transaction(sender, contract_id.clone(), "bump-twice", &[])
+            .unwrap();
+        let mut call_cost = owned_env.get_cost_total();
+        call_cost.sub(&cost_before).unwrap();
+
+        let self_costs = profiler.get_self_costs();
+
+        // each call to `bump` is its own call site: `begin` => `bump` => `var-set`
+        let var_sets: Vec<_> = self_costs
+            .iter()
+            .filter(|(stack, _)| stack.rsplit(';').next().unwrap().contains("::var-set"))
+            .collect();
+        assert_eq!(var_sets.len(), 2);
+        for (stack, cost) in var_sets.iter() {
+            let frames: Vec<_> = stack.split(';').collect();
+            assert_eq!(frames.len(), 3);
+            assert!(frames[0].starts_with(&format!("{}::begin", &contract_id)));
+            assert!(frames[1].starts_with(&format!("{}::bump", &contract_id)));
+            assert!(frames[2].starts_with(&format!("{}::var-set", &contract_id)));
+            assert_eq!(cost.write_count, 1);
+        }
+        assert_ne!(var_sets[0].0, var_sets[1].0);
+
+        // the profile accounts for everything but the cost of calling `bump-twice` itself
+        let mut profiled = ExecutionCost::zero();
+        for (_, cost) in self_costs.iter() {
+            profiled.add(cost).unwrap();
+        }
+        assert!(profiled.runtime > 0);
+        assert!(!profiled.exceeds(&call_cost));
+        assert_eq!(profiled.write_count, call_cost.write_count);
+
+        let mut folded = vec![];
+        profiler
+            .write_folded(&mut folded, CostDimension::WriteCount)
+            .unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        assert_eq!(
+            folded,
+            format!("{} 1\n{} 1\n", &var_sets[0].0, &var_sets[1].0)
+        );
+
+        assert_eq!(
+            "read_length".parse::<CostDimension>(),
+            Ok(CostDimension::ReadLength)
+        );
+        assert!("gas".parse::<CostDimension>().is_err());
+    });
+}
+
+#[test]
+fn test_cost_overrides() {
+    let marf_kv = MarfedKV::temporary();
+    let mut clarity_instance = ClarityInstance::new(false, marf_kv);
+    let first_block = StacksBlockId::new(&FIRST_BURNCHAIN_CONSENSUS_HASH, &FIRST_STACKS_BLOCK_HASH);
+    clarity_instance
+        .begin_test_genesis_block(
+            &StacksBlockId::sentinel(),
+            &first_block,
+            &TEST_HEADER_DB,
+            &TEST_BURN_STATE_DB,
+        )
+        .commit_block();
+
+    // everything is free, except for `+`
+    let mut cost_overrides: CostOverrides = ClarityCostFunction::ALL
+        .iter()
+        .map(|f| (*f, ExecutionCost::zero()))
+        .collect();
+    cost_overrides.insert(
+        ClarityCostFunction::Add,
+        ExecutionCost {
+            runtime: 1_000_000,
+            write_length: 0,
+            write_count: 0,
+            read_length: 0,
+            read_count: 0,
+        },
+    );
+    clarity_instance.set_cost_overrides(Some(Arc::new(cost_overrides)));
+
+    let contract_id = QualifiedContractIdentifier::local("overridden").unwrap();
+    let contract_src = "(define-constant three (+ 1 2))
+(define-read-only (six) (+ three three))";
+
+    let mut block_conn = clarity_instance.begin_block(
+        &first_block,
+        &StacksBlockId([1u8; 32]),
+        &TEST_HEADER_DB,
+        &TEST_BURN_STATE_DB,
+    );
+    block_conn.as_transaction(|tx| {
+        let (ast, analysis) = tx
+            .analyze_smart_contract(&contract_id, contract_src)
+            .unwrap();
+        tx.initialize_smart_contract(&contract_id, &ast, contract_src, |_, _| false)
+            .unwrap();
+        tx.save_analysis(&contract_id, &analysis).unwrap();
+    });
+    assert_eq!(block_conn.cost_so_far().runtime, 1_000_000);
+
+    // read-only calls against the block are charged the overridden costs too
+    let mut cost_track = block_conn
+        .with_clarity_db_readonly(|clarity_db| {
+            LimitedCostTracker::new_mid_block(
+                false,
+                ExecutionCost::max_value(),
+                clarity_db,
+                StacksEpochId::Epoch20,
+            )
+        })
+        .unwrap();
+    cost_track.set_cost_overrides(block_conn.cost_overrides().unwrap());
+    let sender = PrincipalData::parse("SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR").unwrap();
+    let (result, cost) = block_conn
+        .with_readonly_clarity_env(false, sender, cost_track, |env| {
+            let result = env.execute_contract(&contract_id, "six", &[], true)?;
+            Ok((result, env.global_context.cost_track.get_total()))
+        })
+        .unwrap();
+    assert_eq!(result, Value::Int(6));
+    assert_eq!(cost.runtime, 1_000_000);
+
+    block_conn.commit_block();
+}
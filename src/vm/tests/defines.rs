@@ -175,7 +175,7 @@ fn test_stack_depth() {
 
     assert_eq!(Ok(Some(Value::Int(64))), execute(&test0));
     assert!(match execute(&test1).unwrap_err() {
-        Error::Runtime(RuntimeErrorType::MaxStackDepthReached, _) => true,
+        Error::Runtime(RuntimeErrorType::MaxStackDepthReached, ..) => true,
         _ => false,
     })
 }
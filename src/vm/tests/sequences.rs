@@ -18,10 +18,11 @@ use vm::types::signatures::{ListTypeData, SequenceSubtype};
 use vm::types::TypeSignature::{BoolType, IntType, SequenceType, UIntType};
 use vm::types::{TypeSignature, Value};
 
+use core::StacksEpochId;
 use std::convert::TryInto;
 use vm::analysis::errors::CheckError;
 use vm::errors::{CheckErrors, Error, RuntimeErrorType};
-use vm::execute;
+use vm::{execute, execute_in_epoch};
 
 #[test]
 fn test_simple_list_admission() {
@@ -54,6 +55,48 @@ fn test_simple_list_admission() {
     });
 }
 
+#[test]
+fn test_slice() {
+    let good = [
+        "(slice? (list 1 2 3 4 5) u1 u3)",
+        "(slice? (list 1 2 3 4 5) u5 u5)",
+        "(slice? \"blockstack\" u5 u10)",
+        "(slice? u\"ab\\u{1F98A}c\" u1 u3)",
+        "(slice? 0xfedb01 u0 u2)",
+        "(slice? \"abcd\" u3 u2)",
+        "(slice? \"abcd\" u2 u5)",
+        "(slice? 0xfedb u0 u340282366920938463463374607431768211455)",
+    ];
+
+    let expected = [
+        "(some (2 3))",
+        "(some ())",
+        "(some \"stack\")",
+        "(some u\"b\\u{f09fa68a}\")",
+        "(some 0xfedb)",
+        "none",
+        "none",
+        "none",
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!(
+                "{}",
+                execute_in_epoch(good_test, StacksEpochId::Epoch21, false)
+                    .unwrap()
+                    .unwrap()
+            )
+        );
+    }
+
+    assert_eq!(
+        execute_in_epoch("(slice? 3 u0 u1)", StacksEpochId::Epoch21, false).unwrap_err(),
+        CheckErrors::ExpectedSequence(IntType).into()
+    );
+}
+
 #[test]
 fn test_index_of() {
     let good = [
@@ -15,7 +15,10 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use core::StacksEpochId;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
 
 use address::c32;
 use address::AddressHashMode;
@@ -25,14 +28,18 @@ use util::hash::{hex_bytes, to_hex};
 use vm::ast::parse;
 use vm::callables::DefinedFunction;
 use vm::contexts::OwnedEnvironment;
-use vm::costs::LimitedCostTracker;
+use vm::costs::{ExecutionCost, LimitedCostTracker};
 use vm::errors::{CheckErrors, Error, RuntimeErrorType, ShortReturnType};
 use vm::tests::execute;
 use vm::types::signatures::*;
 use vm::types::{BuffData, QualifiedContractIdentifier, TypeSignature};
 use vm::types::{PrincipalData, ResponseData, SequenceData, SequenceSubtype};
-use vm::{eval, execute as vm_execute};
-use vm::{CallStack, ContractContext, Environment, GlobalContext, LocalContext, Value};
+use vm::{
+    eval, execute as vm_execute, execute_in_epoch as vm_execute_in_epoch, EvalHook, ExecutionLimits,
+};
+use vm::{
+    CallStack, ContractContext, Environment, GlobalContext, LocalContext, SymbolicExpression, Value,
+};
 
 use crate::clarity_vm::database::MemoryBackingStore;
 use crate::core;
@@ -89,6 +96,117 @@ fn test_simple_let() {
     }
 }
 
+struct CountingEvalHook {
+    // (begins, finishes, results in the order they finished)
+    trace: Rc<RefCell<(u32, u32, Vec<Value>)>>,
+}
+
+impl EvalHook for CountingEvalHook {
+    fn will_begin_eval(
+        &mut self,
+        _env: &mut Environment,
+        _context: &LocalContext,
+        _expr: &SymbolicExpression,
+    ) {
+        self.trace.borrow_mut().0 += 1;
+    }
+
+    fn did_finish_eval(
+        &mut self,
+        _env: &mut Environment,
+        _context: &LocalContext,
+        _expr: &SymbolicExpression,
+        _cost: &ExecutionCost,
+        res: &Result<Value, Error>,
+    ) {
+        let mut trace = self.trace.borrow_mut();
+        trace.1 += 1;
+        trace.2.push(res.as_ref().unwrap().clone());
+    }
+}
+
+#[test]
+fn test_eval_hooks() {
+    let program = "(+ 1 (* 2 3))";
+    let contract_id = QualifiedContractIdentifier::transient();
+    let parsed_program = parse(&contract_id, program).unwrap();
+    let context = LocalContext::new();
+    let mut marf = MemoryBackingStore::new();
+    let mut env = OwnedEnvironment::new(marf.as_clarity_db());
+
+    let trace = Rc::new(RefCell::new((0, 0, vec![])));
+    env.add_eval_hook(Box::new(CountingEvalHook {
+        trace: trace.clone(),
+    }));
+
+    assert_eq!(
+        Ok(Value::Int(7)),
+        eval(
+            &parsed_program[0],
+            &mut env.get_exec_environment(None),
+            &context
+        )
+    );
+
+    // the outer list, `1`, the inner list, `2`, and `3`
+    let trace = trace.borrow();
+    assert_eq!(trace.0, 5);
+    assert_eq!(trace.1, 5);
+    assert_eq!(
+        trace.2,
+        vec![
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(3),
+            Value::Int(6),
+            Value::Int(7)
+        ]
+    );
+
+    assert_eq!(env.take_eval_hooks().len(), 1);
+}
+
+#[test]
+fn test_execution_limits() {
+    let items: Vec<_> = (0..2000).map(|i| i.to_string()).collect();
+    let contract = format!(
+        "(define-private (add (x int) (acc int)) (+ x acc))
+         (define-read-only (sum) (fold add (list {}) 0))",
+        items.join(" ")
+    );
+    let contract_id = QualifiedContractIdentifier::local("limits").unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut owned_env = OwnedEnvironment::new(marf.as_clarity_db());
+    owned_env
+        .initialize_contract(contract_id.clone(), &contract)
+        .unwrap();
+    owned_env.begin();
+    let mut env = owned_env.get_exec_environment(None);
+
+    let mut call = |limits| env.execute_contract_limited(&contract_id, "sum", &[], true, limits);
+    assert_eq!(call(ExecutionLimits::default()), Ok(Value::Int(1999000)));
+    assert_eq!(
+        call(ExecutionLimits {
+            max_steps: Some(1000),
+            max_duration: None,
+        }),
+        Err(RuntimeErrorType::StepLimitExceeded(1000).into())
+    );
+    assert_eq!(
+        call(ExecutionLimits {
+            max_steps: None,
+            max_duration: Some(Duration::from_nanos(1)),
+        }),
+        Err(RuntimeErrorType::TimeLimitExceeded(0).into())
+    );
+
+    // the limits only apply to the call they were given for
+    assert_eq!(
+        env.execute_contract(&contract_id, "sum", &[], true),
+        Ok(Value::Int(1999000))
+    );
+}
+
 #[test]
 fn test_sha256() {
     let sha256_evals = [
@@ -382,7 +500,10 @@ fn test_simple_if_functions() {
         );
 
         let context = LocalContext::new();
-        let mut contract_context = ContractContext::new(QualifiedContractIdentifier::transient());
+        let mut contract_context = ContractContext::new(
+            QualifiedContractIdentifier::transient(),
+            StacksEpochId::Epoch2_05,
+        );
         let mut marf = MemoryBackingStore::new();
         let mut global_context = GlobalContext::new(
             false,
@@ -1024,3 +1145,163 @@ fn test_asserts_short_circuit() {
             assert_eq!((*expectation), vm_execute(program).unwrap_err())
         });
 }
+
+#[test]
+fn test_buff_int_conversions() {
+    let tests = [
+        ("(buff-to-int-le 0x0102)", Value::Int(513)),
+        ("(buff-to-int-be 0x0102)", Value::Int(258)),
+        ("(buff-to-uint-le 0x0102)", Value::UInt(513)),
+        ("(buff-to-uint-be 0x0102)", Value::UInt(258)),
+        ("(buff-to-int-be 0x)", Value::Int(0)),
+        (
+            "(buff-to-int-le 0xffffffffffffffffffffffffffffffff)",
+            Value::Int(-1),
+        ),
+        (
+            "(buff-to-uint-le 0xffffffffffffffffffffffffffffffff)",
+            Value::UInt(u128::MAX),
+        ),
+        (
+            "(buff-to-int-le (int-to-buff-le -12345))",
+            Value::Int(-12345),
+        ),
+        (
+            "(buff-to-int-be (int-to-buff-be -12345))",
+            Value::Int(-12345),
+        ),
+        (
+            "(buff-to-uint-le (uint-to-buff-le u340282366920938463463374607431768211455))",
+            Value::UInt(u128::MAX),
+        ),
+        (
+            "(int-to-buff-be 258)",
+            Value::buff_from(hex_bytes("00000000000000000000000000000102").unwrap()).unwrap(),
+        ),
+        (
+            "(uint-to-buff-le u258)",
+            Value::buff_from(hex_bytes("02010000000000000000000000000000").unwrap()).unwrap(),
+        ),
+    ];
+
+    for (program, expected) in tests.iter() {
+        assert_eq!(
+            expected.clone(),
+            vm_execute_in_epoch(program, StacksEpochId::Epoch21, false)
+                .unwrap()
+                .unwrap(),
+            "{}",
+            program
+        );
+    }
+
+    // buffers longer than an integer are rejected
+    assert_eq!(
+        vm_execute_in_epoch(
+            "(buff-to-uint-be 0x0102030405060708090a0b0c0d0e0f1011)",
+            StacksEpochId::Epoch21,
+            false
+        )
+        .unwrap_err(),
+        CheckErrors::TypeValueError(
+            BUFF_16.clone(),
+            Value::buff_from(hex_bytes("0102030405060708090a0b0c0d0e0f1011").unwrap()).unwrap()
+        )
+        .into()
+    );
+}
+
+#[test]
+fn test_string_to_int() {
+    let tests = [
+        (
+            "(string-to-int? \"-145\")",
+            Value::some(Value::Int(-145)).unwrap(),
+        ),
+        (
+            "(string-to-int? u\"42\")",
+            Value::some(Value::Int(42)).unwrap(),
+        ),
+        (
+            "(string-to-int? \"-170141183460469231731687303715884105728\")",
+            Value::some(Value::Int(i128::MIN)).unwrap(),
+        ),
+        (
+            "(string-to-uint? \"340282366920938463463374607431768211455\")",
+            Value::some(Value::UInt(u128::MAX)).unwrap(),
+        ),
+        (
+            "(string-to-uint? u\"0012\")",
+            Value::some(Value::UInt(12)).unwrap(),
+        ),
+    ];
+    for (program, expected) in tests.iter() {
+        assert_eq!(
+            expected.clone(),
+            vm_execute_in_epoch(program, StacksEpochId::Epoch21, false)
+                .unwrap()
+                .unwrap(),
+            "{}",
+            program
+        );
+    }
+
+    let unparseable = [
+        "(string-to-int? \"\")",
+        "(string-to-int? \"-\")",
+        "(string-to-int? \"+1\")",
+        "(string-to-int? \" 1\")",
+        "(string-to-int? \"12a\")",
+        "(string-to-int? \"170141183460469231731687303715884105728\")",
+        "(string-to-int? \"00000000000000000000000000000000000000001\")",
+        "(string-to-uint? \"-1\")",
+        "(string-to-uint? \"340282366920938463463374607431768211456\")",
+        "(string-to-uint? u\"1\\u{0661}\")",
+    ];
+    let overlong = format!("(string-to-uint? u\"{}\")", "1".repeat(10_000));
+    for program in unparseable
+        .iter()
+        .copied()
+        .chain(std::iter::once(overlong.as_str()))
+    {
+        assert_eq!(
+            Value::none(),
+            vm_execute_in_epoch(program, StacksEpochId::Epoch21, false)
+                .unwrap()
+                .unwrap(),
+            "{}",
+            program
+        );
+    }
+}
+
+#[test]
+fn test_conversions_gated_by_epoch() {
+    let programs = [
+        "(buff-to-int-le 0x01)",
+        "(int-to-buff-be 1)",
+        "(string-to-uint? \"1\")",
+        "(slice? \"abc\" u0 u1)",
+    ];
+    for program in programs.iter() {
+        match vm_execute_in_epoch(program, StacksEpochId::Epoch2_05, false).unwrap_err() {
+            Error::Unchecked(CheckErrors::UndefinedFunction(_)) => {}
+            e => panic!(
+                "Expected an undefined function for {}, got {:?}",
+                program, e
+            ),
+        }
+    }
+
+    // before 2.1, the names are free for contracts to define
+    assert_eq!(
+        Value::Int(3),
+        vm_execute_in_epoch(
+            "(define-private (slice? (a int)) (+ a 1)) (slice? 2)",
+            StacksEpochId::Epoch2_05,
+            false
+        )
+        .unwrap()
+        .unwrap()
+    );
+}
@@ -35,8 +35,8 @@ use vm::representations::{ClarityName, ContractName, SymbolicExpression, Symboli
 pub use vm::types::signatures::{
     parse_name_type_pairs, AssetIdentifier, BufferLength, FixedFunction, FunctionArg,
     FunctionSignature, FunctionType, ListTypeData, SequenceSubtype, StringSubtype,
-    StringUTF8Length, TupleTypeSignature, TypeSignature, BUFF_1, BUFF_20, BUFF_32, BUFF_33,
-    BUFF_64, BUFF_65,
+    StringUTF8Length, TupleTypeSignature, TypeSignature, BUFF_1, BUFF_16, BUFF_20, BUFF_32,
+    BUFF_33, BUFF_64, BUFF_65,
 };
 
 pub const MAX_VALUE_SIZE: u32 = 1024 * 1024; // 1MB
@@ -263,6 +263,35 @@ impl SequenceData {
         Some(result)
     }
 
+    /// The items in `[left, right)`, as a sequence of the same type, or None if those are not
+    ///  valid bounds for this sequence.
+    pub fn slice(self, left: usize, right: usize) -> Option<Value> {
+        if left > right || right > self.len() {
+            return None;
+        }
+        let result = match self {
+            SequenceData::Buffer(data) => SequenceData::Buffer(BuffData {
+                data: data.data[left..right].to_vec(),
+            }),
+            SequenceData::List(mut data) => SequenceData::List(ListData {
+                data: data.data.drain(left..right).collect(),
+                type_signature: data.type_signature,
+            }),
+            SequenceData::String(CharType::ASCII(data)) => {
+                SequenceData::String(CharType::ASCII(ASCIIData {
+                    data: data.data[left..right].to_vec(),
+                }))
+            }
+            SequenceData::String(CharType::UTF8(mut data)) => {
+                SequenceData::String(CharType::UTF8(UTF8Data {
+                    data: data.data.drain(left..right).collect(),
+                }))
+            }
+        };
+
+        Some(Value::Sequence(result))
+    }
+
     pub fn contains(&self, to_find: Value) -> Result<Option<usize>> {
         match self {
             SequenceData::Buffer(ref data) => {
@@ -18,7 +18,7 @@ use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::io::{Read, Write};
-use std::{error, fmt, str};
+use std::{cmp, error, fmt, str};
 
 use serde_json::Value as JSONValue;
 
@@ -171,6 +171,122 @@ impl From<&Value> for TypePrefix {
     }
 }
 
+/// Limits on the values `Value::deserialize_read_limited` will decode, so that callers
+///   handling untrusted input can bound the memory a single value takes.  The defaults are
+///   the consensus limits, which `Value::deserialize_read` always uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeserializationLimits {
+    /// Maximum nesting depth of optionals, responses, lists and tuples
+    pub max_depth: u8,
+    /// Maximum number of bytes read for one value
+    pub max_bytes: u64,
+    /// Maximum number of entries in one list or tuple
+    pub max_entries: u32,
+}
+
+impl Default for DeserializationLimits {
+    fn default() -> DeserializationLimits {
+        DeserializationLimits {
+            max_depth: 16,
+            max_bytes: BOUND_VALUE_SERIALIZATION_BYTES as u64,
+            max_entries: MAX_VALUE_SIZE,
+        }
+    }
+}
+
+/// Lists and tuples only reserve space for this many entries up front, so a length prefix
+///   alone can't make the deserializer allocate more than the input's actual size.
+const MAX_PREALLOCATED_ENTRIES: u32 = 1024;
+
+/// Read exactly `len` bytes, growing the buffer as bytes arrive rather than allocating `len`
+///   bytes up front.
+fn read_exact_bounded<R: Read>(r: &mut R, len: u32) -> Result<Vec<u8>, SerializationError> {
+    let mut data = vec![];
+    r.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        )
+        .into());
+    }
+    Ok(data)
+}
+
+/// A reader that decodes a hex string as it is read, so that a hex-encoded value (e.g. from
+///   the RPC map-entry and data-var endpoints) can be deserialized without first decoding the
+///   whole string into a byte buffer.
+pub struct HexReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> HexReader<R> {
+    pub fn new(inner: R) -> HexReader<R> {
+        HexReader { inner }
+    }
+}
+
+impl<R: Read> Read for HexReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut hex = vec![0u8; buf.len() * 2];
+        let mut num_read = 0;
+        // fill whole byte pairs, so a short read never splits a byte's two digits
+        while num_read < hex.len() {
+            let n = self.inner.read(&mut hex[num_read..])?;
+            if n == 0 {
+                break;
+            }
+            num_read += n;
+            if num_read % 2 == 0 {
+                break;
+            }
+        }
+        if num_read % 2 != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Odd number of hex digits",
+            ));
+        }
+
+        let digit = |c: u8| -> std::io::Result<u8> {
+            (c as char).to_digit(16).map(|d| d as u8).ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "Bad hex digit")
+            })
+        };
+        for i in 0..(num_read / 2) {
+            buf[i] = (digit(hex[2 * i])? << 4) | digit(hex[2 * i + 1])?;
+        }
+        Ok(num_read / 2)
+    }
+}
+
+/// A writer that hex-encodes what is written to it, so a value can be serialized straight into
+///   a hex-encoded output (like an RPC response body) without an intermediate byte buffer.
+pub struct HexWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> HexWriter<W> {
+    pub fn new(inner: W) -> HexWriter<W> {
+        HexWriter { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write_all(to_hex(buf).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// Not a public trait,
 ///   this is just used to simplify serializing some types that
 ///   are repeatedly serialized or deserialized.
@@ -292,19 +408,30 @@ impl Value {
         r: &mut R,
         expected_type: Option<&TypeSignature>,
     ) -> Result<Value, SerializationError> {
-        let mut bound_reader = BoundReader::from_reader(r, BOUND_VALUE_SERIALIZATION_BYTES as u64);
-        Value::inner_deserialize_read(&mut bound_reader, expected_type, 0)
+        Value::deserialize_read_limited(r, expected_type, &DeserializationLimits::default())
+    }
+
+    /// Deserialize a value from `r`, failing if it exceeds any of the given `limits`.  Nothing
+    ///   past the end of the value is read from `r`.
+    pub fn deserialize_read_limited<R: Read>(
+        r: &mut R,
+        expected_type: Option<&TypeSignature>,
+        limits: &DeserializationLimits,
+    ) -> Result<Value, SerializationError> {
+        let mut bound_reader = BoundReader::from_reader(r, limits.max_bytes);
+        Value::inner_deserialize_read(&mut bound_reader, expected_type, 0, limits)
     }
 
     fn inner_deserialize_read<R: Read>(
         r: &mut R,
         expected_type: Option<&TypeSignature>,
         depth: u8,
+        limits: &DeserializationLimits,
     ) -> Result<Value, SerializationError> {
         use super::PrincipalData::*;
         use super::Value::*;
 
-        if depth >= 16 {
+        if depth >= limits.max_depth {
             return Err(CheckErrors::TypeSignatureTooDeep.into());
         }
 
@@ -343,9 +470,7 @@ impl Value {
                     }
                 }
 
-                let mut data = vec![0; u32::from(buffer_len) as usize];
-
-                r.read_exact(&mut data[..])?;
+                let data = read_exact_bounded(r, u32::from(buffer_len))?;
 
                 Value::buff_from(data).map_err(|_| "Bad buffer".into())
             }
@@ -382,7 +507,8 @@ impl Value {
                     }
                 };
 
-                let data = Value::inner_deserialize_read(r, expect_contained_type, depth + 1)?;
+                let data =
+                    Value::inner_deserialize_read(r, expect_contained_type, depth + 1, limits)?;
                 let value = if committed {
                     Value::okay(data)
                 } else {
@@ -412,6 +538,7 @@ impl Value {
                     r,
                     expect_contained_type,
                     depth + 1,
+                    limits,
                 )?)
                 .map_err(|_x| "Value too large")?;
 
@@ -425,6 +552,9 @@ impl Value {
                 if len > MAX_VALUE_SIZE {
                     return Err("Illegal list type".into());
                 }
+                if len > limits.max_entries {
+                    return Err("List exceeds the maximum number of entries".into());
+                }
 
                 let (list_type, entry_type) = match expected_type {
                     None => (None, None),
@@ -439,9 +569,15 @@ impl Value {
                     Some(x) => return Err(SerializationError::DeserializeExpected(x.clone())),
                 };
 
-                let mut items = Vec::with_capacity(len as usize);
+                let mut items =
+                    Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED_ENTRIES) as usize);
                 for _i in 0..len {
-                    items.push(Value::inner_deserialize_read(r, entry_type, depth + 1)?);
+                    items.push(Value::inner_deserialize_read(
+                        r,
+                        entry_type,
+                        depth + 1,
+                        limits,
+                    )?);
                 }
 
                 if let Some(list_type) = list_type {
@@ -461,6 +597,9 @@ impl Value {
                         "Illegal tuple type".to_string(),
                     ));
                 }
+                if len > limits.max_entries {
+                    return Err("Tuple exceeds the maximum number of entries".into());
+                }
 
                 let tuple_type = match expected_type {
                     None => None,
@@ -475,7 +614,8 @@ impl Value {
                     Some(x) => return Err(SerializationError::DeserializeExpected(x.clone())),
                 };
 
-                let mut items = Vec::with_capacity(len as usize);
+                let mut items =
+                    Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED_ENTRIES) as usize);
                 for _i in 0..len {
                     let key = ClarityName::deserialize_read(r)?;
 
@@ -486,7 +626,8 @@ impl Value {
                         })?),
                     };
 
-                    let value = Value::inner_deserialize_read(r, expected_field_type, depth + 1)?;
+                    let value =
+                        Value::inner_deserialize_read(r, expected_field_type, depth + 1, limits)?;
                     items.push((key, value))
                 }
 
@@ -517,9 +658,7 @@ impl Value {
                     }
                 }
 
-                let mut data = vec![0; u32::from(buffer_len) as usize];
-
-                r.read_exact(&mut data[..])?;
+                let data = read_exact_bounded(r, u32::from(buffer_len))?;
 
                 Value::string_ascii_from_bytes(data).map_err(|_| "Bad string".into())
             }
@@ -528,9 +667,7 @@ impl Value {
                 r.read_exact(&mut total_len)?;
                 let total_len = BufferLength::try_from(u32::from_be_bytes(total_len))?;
 
-                let mut data: Vec<u8> = vec![0; u32::from(total_len) as usize];
-
-                r.read_exact(&mut data[..])?;
+                let data = read_exact_bounded(r, u32::from(total_len))?;
 
                 let value = Value::string_utf8_from_bytes(data)
                     .map_err(|_| "Illegal string_utf8 type".into());
@@ -647,6 +784,21 @@ impl Value {
         Value::try_deserialize_bytes_untyped(&mut data)
     }
 
+    /// Deserialize a hex-encoded value from `r` as it is read, without decoding the whole hex
+    ///   string first.
+    pub fn deserialize_hex_read<R: Read>(
+        r: &mut R,
+        expected_type: Option<&TypeSignature>,
+        limits: &DeserializationLimits,
+    ) -> Result<Value, SerializationError> {
+        Value::deserialize_read_limited(&mut HexReader::new(r), expected_type, limits)
+    }
+
+    /// Write the hex encoding of this value's serialization to `w`
+    pub fn serialize_hex_write<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        self.serialize_write(&mut HexWriter::new(w))
+    }
+
     pub fn deserialize(hex: &str, expected: &TypeSignature) -> Self {
         Value::try_deserialize_hex(hex, expected)
             .expect("ERROR: Failed to parse Clarity hex string")
@@ -733,7 +885,8 @@ mod tests {
     use vm::types::TypeSignature::{BoolType, IntType};
 
     use super::super::*;
-    use super::SerializationError;
+    use super::{DeserializationLimits, SerializationError};
+    use crate::codec::StacksMessageCodec;
 
     fn buff_type(size: u32) -> TypeSignature {
         TypeSignature::SequenceType(SequenceSubtype::BufferType(size.try_into().unwrap())).into()
@@ -1166,4 +1319,108 @@ mod tests {
         test_bad_expectation(contract_p2, TypeSignature::BoolType);
         test_bad_expectation(standard_p, TypeSignature::BoolType);
     }
+
+    #[test]
+    fn test_deserialize_limits() {
+        let list = Value::list_from(vec![Value::list_from(vec![
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(3),
+        ])
+        .unwrap()])
+        .unwrap();
+        let bytes = list.serialize_to_vec();
+
+        assert_eq!(
+            Value::deserialize_read_limited(
+                &mut bytes.as_slice(),
+                None,
+                &DeserializationLimits::default()
+            )
+            .unwrap(),
+            list
+        );
+
+        let limits = DeserializationLimits {
+            max_depth: 2,
+            ..DeserializationLimits::default()
+        };
+        assert_eq!(
+            Value::deserialize_read_limited(&mut bytes.as_slice(), None, &limits),
+            Err(CheckErrors::TypeSignatureTooDeep.into())
+        );
+
+        let limits = DeserializationLimits {
+            max_entries: 2,
+            ..DeserializationLimits::default()
+        };
+        assert_eq!(
+            Value::deserialize_read_limited(&mut bytes.as_slice(), None, &limits),
+            Err("List exceeds the maximum number of entries".into())
+        );
+
+        let limits = DeserializationLimits {
+            max_bytes: bytes.len() as u64 - 1,
+            ..DeserializationLimits::default()
+        };
+        match Value::deserialize_read_limited(&mut bytes.as_slice(), None, &limits) {
+            Err(SerializationError::IOError(e)) => {
+                assert_eq!(e.err.kind(), std::io::ErrorKind::UnexpectedEof)
+            }
+            res => panic!("Expected an EOF error, got {:?}", res),
+        }
+
+        // a buffer whose length prefix promises more bytes than there are
+        let truncated_buffer = [&[2u8][..], &MAX_VALUE_SIZE.to_be_bytes(), &[0u8; 8]].concat();
+        match Value::deserialize_read_limited(
+            &mut truncated_buffer.as_slice(),
+            None,
+            &DeserializationLimits::default(),
+        ) {
+            Err(SerializationError::IOError(e)) => {
+                assert_eq!(e.err.kind(), std::io::ErrorKind::UnexpectedEof)
+            }
+            res => panic!("Expected an EOF error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_hex_streaming() {
+        let value = Value::from(
+            TupleData::from_data(vec![
+                ("a".into(), Value::UInt(7)),
+                (
+                    "b".into(),
+                    Value::list_from(vec![Value::buff_from(vec![0xde, 0xad]).unwrap()]).unwrap(),
+                ),
+            ])
+            .unwrap(),
+        );
+
+        let mut hex = vec![];
+        value.serialize_hex_write(&mut hex).unwrap();
+        let hex = String::from_utf8(hex).unwrap();
+        assert_eq!(hex, value.serialize());
+
+        // trailing input past the value is left unread
+        let input = format!("{}ffff", hex);
+        let mut reader = input.as_bytes();
+        assert_eq!(
+            Value::deserialize_hex_read(&mut reader, None, &DeserializationLimits::default())
+                .unwrap(),
+            value
+        );
+        assert_eq!(reader, b"ffff");
+
+        match Value::deserialize_hex_read(
+            &mut "0c0g".as_bytes(),
+            None,
+            &DeserializationLimits::default(),
+        ) {
+            Err(SerializationError::IOError(e)) => {
+                assert_eq!(e.err.kind(), std::io::ErrorKind::InvalidData)
+            }
+            res => panic!("Expected an invalid-data error, got {:?}", res),
+        }
+    }
 }
@@ -0,0 +1,8 @@
+fn main() {
+    // the gRPC server's message types and service trait are generated from its protobuf schema
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/node.proto"], &["proto"])
+        .expect("Failed to compile the gRPC protobuf schema");
+}
@@ -3,12 +3,15 @@ use async_std::io::ReadExt;
 use async_std::net::TcpStream;
 use base64::encode;
 use http_types::{Method, Request, Url};
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
 };
-use std::time::Instant;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
 use serde_json::value::RawValue;
@@ -18,9 +21,11 @@ use std::cmp;
 use super::super::operations::BurnchainOpSigner;
 use super::super::Config;
 use super::{BurnchainController, BurnchainTip, Error as BurnchainControllerError};
+use crate::syncctl::PoxSyncWatchdogComms;
 
 use stacks::burnchains::bitcoin::indexer::{
-    BitcoinIndexer, BitcoinIndexerConfig, BitcoinIndexerRuntime,
+    BitcoinEndpoint, BitcoinIndexer, BitcoinIndexerConfig, BitcoinIndexerRuntime,
+    BITCOIN_ENDPOINT_FAILBACK_INTERVAL,
 };
 use stacks::burnchains::bitcoin::spv::SpvClient;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
@@ -42,18 +47,23 @@ use stacks::chainstate::burn::operations::{
 };
 use stacks::chainstate::coordinator::comm::CoordinatorChannels;
 use stacks::codec::StacksMessageCodec;
-use stacks::core::StacksEpoch;
+use stacks::core::{StacksEpoch, StacksEpochId};
 use stacks::deps::bitcoin::blockdata::opcodes;
 use stacks::deps::bitcoin::blockdata::script::{Builder, Script};
 use stacks::deps::bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
 use stacks::deps::bitcoin::network::encodable::ConsensusEncodable;
-use stacks::deps::bitcoin::network::serialize::RawEncoder;
+use stacks::deps::bitcoin::network::serialize::{deserialize, RawEncoder};
 use stacks::deps::bitcoin::util::hash::Sha256dHash;
+use stacks::util::get_epoch_time_secs;
 use stacks::util::hash::{hex_bytes, Hash160};
 use stacks::util::secp256k1::Secp256k1PublicKey;
 use stacks::util::sleep_ms;
 
-use stacks::monitoring::{increment_btc_blocks_received_counter, increment_btc_ops_sent_counter};
+use stacks::monitoring::{
+    increment_btc_blocks_received_counter, increment_btc_ops_sent_counter,
+    update_block_commit_fee_rate,
+};
+use stacks::net::socks::{connect_through_proxy, Socks5Target};
 
 #[cfg(test)]
 use stacks::chainstate::burn::Opcodes;
@@ -64,6 +74,18 @@ use stacks::types::chainstate::BurnchainHeaderHash;
 ///  the cache is force-reset.
 const UTXO_CACHE_STALENESS_LIMIT: u64 = 6;
 const DUST_UTXO_LIMIT: u64 = 5500;
+/// Most inputs a transaction spends when consolidating the miner's UTXOs
+const MAX_CONSOLIDATION_INPUTS: usize = 10;
+/// Longest DER signature plus its sighash byte, as found in a P2PKH scriptSig
+const MAX_P2PKH_SIG_LEN: usize = 73;
+
+/// Virtual size of a transaction, which is what fee rates are charged against
+fn get_vsize(tx: &Transaction) -> u64 {
+    tx.get_weight().div_ceil(4)
+}
+/// How long each `waitfornewblock` long-poll may block inside bitcoind before returning the
+///  current tip, so the listener thread notices when the node shuts down.
+const NEW_BLOCK_WAIT_TIMEOUT_MS: u64 = 5000;
 
 pub struct BitcoinRegtestController {
     config: Config,
@@ -74,6 +96,7 @@ pub struct BitcoinRegtestController {
     use_coordinator: Option<CoordinatorChannels>,
     burnchain_config: Option<Burnchain>,
     ongoing_block_commit: Option<OngoingBlockCommit>,
+    utxo_cache: Option<UTXOCache>,
     should_keep_running: Option<Arc<AtomicBool>>,
 }
 
@@ -82,6 +105,9 @@ struct OngoingBlockCommit {
     utxos: UTXOSet,
     fees: LeaderBlockCommitFees,
     txids: Vec<Txid>,
+    /// burnchain headers height when the latest attempt was sent.  The commit is meant for the
+    /// next block.
+    sent_at_height: u64,
 }
 
 impl OngoingBlockCommit {
@@ -90,6 +116,120 @@ impl OngoingBlockCommit {
     }
 }
 
+/// The miner's UTXOs as bitcoind listed them at a burnchain height, less those we've spent
+/// since and plus the change we've made since, so we only list them once per block.
+struct UTXOCache {
+    headers_height: u64,
+    block_height: u64,
+    addresses: Vec<String>,
+    utxos: UTXOSet,
+    /// inputs and txid of each transaction sent since
+    sent_txs: Vec<(Vec<OutPoint>, Sha256dHash)>,
+}
+
+impl UTXOCache {
+    fn new(
+        headers_height: u64,
+        block_height: u64,
+        addresses: Vec<String>,
+        utxos: UTXOSet,
+    ) -> UTXOCache {
+        UTXOCache {
+            headers_height,
+            block_height,
+            addresses,
+            utxos,
+            sent_txs: vec![],
+        }
+    }
+
+    /// Can these UTXOs be used for the same query, or has a block arrived since?
+    fn is_fresh(&self, headers_height: u64, block_height: u64, addresses: &[String]) -> bool {
+        self.headers_height == headers_height
+            && self.block_height == block_height
+            && self.addresses == addresses
+    }
+
+    /// Account for a transaction we sent: its inputs are spent, and its outputs to
+    /// `own_scripts` are ours to spend.  If it replaces one we sent earlier, that one's outputs
+    /// are gone.
+    fn record_sent_tx(&mut self, tx: &Transaction, own_scripts: &[Script]) {
+        let spent: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+        let replaced: Vec<Sha256dHash> = self
+            .sent_txs
+            .iter()
+            .filter(|(inputs, _)| inputs.iter().any(|input| spent.contains(input)))
+            .map(|(_, txid)| *txid)
+            .collect();
+
+        self.sent_txs.retain(|(_, txid)| !replaced.contains(txid));
+        self.utxos.utxos.retain(|utxo| {
+            let outpoint = OutPoint {
+                txid: utxo.txid,
+                vout: utxo.vout,
+            };
+            !spent.contains(&outpoint) && !replaced.contains(&utxo.txid)
+        });
+
+        let txid = tx.txid();
+        for (vout, output) in tx.output.iter().enumerate() {
+            if own_scripts.contains(&output.script_pubkey) {
+                self.utxos.utxos.push(UTXO {
+                    txid,
+                    vout: vout as u32,
+                    script_pub_key: output.script_pubkey.clone(),
+                    amount: output.value,
+                    confirmations: 0,
+                });
+            }
+        }
+        self.sent_txs.push((spent, txid));
+    }
+}
+
+/// Pick which of `utxos`, in order of preference, to spend to cover `total_to_spend`.  One UTXO
+/// that covers it on its own is best, so block-commits keep spending their last change output;
+/// failing that, the largest are combined so there are as few inputs as possible.  UTXOs under
+/// the dust limit are left alone, unless `consolidate` is set -- then up to
+/// `MAX_CONSOLIDATION_INPUTS` inputs are spent in all, sweeping the smallest UTXOs into change.
+fn select_utxos(utxos: &[UTXO], total_to_spend: u64, consolidate: bool) -> Option<Vec<UTXO>> {
+    let mut candidates: Vec<&UTXO> = utxos
+        .iter()
+        .filter(|utxo| consolidate || utxo.amount >= DUST_UTXO_LIMIT)
+        .collect();
+
+    let mut selected = vec![];
+    if let Some(index) = candidates
+        .iter()
+        .position(|utxo| utxo.amount >= total_to_spend)
+    {
+        selected.push(candidates.remove(index));
+    } else {
+        candidates.sort_by_key(|utxo| cmp::Reverse(utxo.amount));
+        let mut total_selected = 0;
+        while total_selected < total_to_spend {
+            if candidates.is_empty() {
+                return None;
+            }
+            let utxo = candidates.remove(0);
+            total_selected += utxo.amount;
+            selected.push(utxo);
+        }
+    }
+
+    if consolidate {
+        candidates.sort_by_key(|utxo| utxo.amount);
+        for utxo in candidates.into_iter() {
+            if selected.len() >= MAX_CONSOLIDATION_INPUTS {
+                break;
+            }
+            selected.push(utxo);
+        }
+    }
+
+    Some(selected.into_iter().cloned().collect())
+}
+
 #[derive(Clone)]
 struct LeaderBlockCommitFees {
     sunset_fee: u64,
@@ -239,6 +379,7 @@ impl BitcoinRegtestController {
                 first_block: burnchain_params.first_block_height,
                 magic_bytes: burnchain_config.magic_bytes,
                 epochs: burnchain_config.epochs,
+                backup_endpoints: burnchain_config.backup_endpoints,
             }
         };
 
@@ -258,6 +399,7 @@ impl BitcoinRegtestController {
             chain_tip: None,
             burnchain_config,
             ongoing_block_commit: None,
+            utxo_cache: None,
             should_keep_running,
         }
     }
@@ -283,6 +425,7 @@ impl BitcoinRegtestController {
                 first_block: burnchain_params.first_block_height,
                 magic_bytes: burnchain_config.magic_bytes,
                 epochs: burnchain_config.epochs,
+                backup_endpoints: burnchain_config.backup_endpoints,
             }
         };
 
@@ -302,6 +445,7 @@ impl BitcoinRegtestController {
             chain_tip: None,
             burnchain_config: None,
             ongoing_block_commit: None,
+            utxo_cache: None,
             should_keep_running: None,
         }
     }
@@ -581,17 +725,23 @@ impl BitcoinRegtestController {
 
     /// Checks if there is a default wallet with the name of "".
     /// If the default wallet does not exist, this function creates a wallet with name "".
+    /// A wallet for an external signer must be set up by the operator, since only they can
+    /// connect it to the signer.
     pub fn create_wallet_if_dne(&self) -> RPCResult<()> {
         let wallets = BitcoinRPCRequest::list_wallets(&self.config)?;
 
         if !wallets.contains(&("".to_string())) {
+            if self.config.burnchain.external_signer_public_key.is_some() {
+                warn!("bitcoind has no default wallet to sign with the external signer -- create one with `bitcoin-cli createwallet \"\" true false \"\" false true true true`");
+                return Ok(());
+            }
             BitcoinRPCRequest::create_wallet(&self.config, "")?;
         }
         Ok(())
     }
 
     pub fn get_utxos(
-        &self,
+        &mut self,
         public_key: &Secp256k1PublicKey,
         total_required: u64,
         utxos_to_exclude: Option<UTXOSet>,
@@ -610,15 +760,79 @@ impl BitcoinRegtestController {
         let address =
             BitcoinAddress::from_bytes(network_id, BitcoinAddressType::PublicKeyHash, &pkh)
                 .expect("Public key incorrect");
-        let filter_addresses = vec![address.to_b58()];
+        let mut filter_addresses = vec![address.to_b58()];
+        if self.is_segwit_active(public_key) {
+            let address = BitcoinAddress::from_bytes(
+                network_id,
+                BitcoinAddressType::WitnessPublicKeyHash,
+                &pkh,
+            )
+            .expect("Public key incorrect");
+            filter_addresses.push(address.to_bech32());
+        }
 
-        let mut utxos = loop {
+        let headers_height = self.get_headers_height();
+        let cached_utxos = match self.utxo_cache {
+            Some(ref cache) if cache.is_fresh(headers_height, block_height, &filter_addresses) => {
+                debug!("Using cached UTXOs for {}", &public_key.to_hex());
+                Some(cache.utxos.clone())
+            }
+            _ => None,
+        };
+        let mut utxos = match cached_utxos {
+            Some(utxos) => utxos,
+            None => {
+                let utxos = self.list_all_utxos(public_key, &filter_addresses, block_height)?;
+                self.utxo_cache = Some(UTXOCache::new(
+                    headers_height,
+                    block_height,
+                    filter_addresses,
+                    utxos.clone(),
+                ));
+                utxos
+            }
+        };
+
+        if let Some(utxos_to_exclude) = utxos_to_exclude {
+            let txids_to_filter: Vec<_> = utxos_to_exclude
+                .utxos
+                .iter()
+                .map(|utxo| utxo.txid)
+                .collect();
+            utxos
+                .utxos
+                .retain(|utxo| !txids_to_filter.contains(&utxo.txid));
+        }
+
+        let total_unspent = utxos.total_available();
+        if total_unspent < total_required {
+            warn!(
+                "Total unspent {} < {} for {:?}",
+                total_unspent,
+                total_required,
+                &public_key.to_hex()
+            );
+            return None;
+        }
+
+        Some(utxos)
+    }
+
+    /// Ask bitcoind for all of our spendable UTXOs at `filter_addresses`, importing the miner's
+    /// key first on regtest if it has none.
+    fn list_all_utxos(
+        &self,
+        public_key: &Secp256k1PublicKey,
+        filter_addresses: &[String],
+        block_height: u64,
+    ) -> Option<UTXOSet> {
+        let utxos = loop {
             let result = BitcoinRPCRequest::list_unspent(
                 &self.config,
-                filter_addresses.clone(),
+                filter_addresses.to_vec(),
                 false,
-                total_required,
-                &utxos_to_exclude,
+                1,
+                &None,
                 block_height,
             );
 
@@ -635,7 +849,7 @@ impl BitcoinRegtestController {
             };
         };
 
-        let utxos = if utxos.is_empty() {
+        if utxos.is_empty() {
             let (_, network) = self.config.burnchain.get_bitcoin_network();
             loop {
                 if let BitcoinNetworkType::Regtest = network {
@@ -649,14 +863,14 @@ impl BitcoinRegtestController {
 
                 let result = BitcoinRPCRequest::list_unspent(
                     &self.config,
-                    filter_addresses.clone(),
+                    filter_addresses.to_vec(),
                     false,
-                    total_required,
-                    &utxos_to_exclude,
+                    1,
+                    &None,
                     block_height,
                 );
 
-                utxos = match result {
+                let utxos = match result {
                     Ok(utxos) => utxos,
                     Err(e) => {
                         error!("Bitcoin RPC failure: error listing utxos {:?}", e);
@@ -665,30 +879,29 @@ impl BitcoinRegtestController {
                     }
                 };
 
-                if utxos.is_empty() {
-                    return None;
-                } else {
-                    break utxos;
-                }
+                return if utxos.is_empty() { None } else { Some(utxos) };
             }
-        } else {
-            utxos
-        };
-
-        let total_unspent = utxos.total_available();
-        if total_unspent < total_required {
-            warn!(
-                "Total unspent {} < {} for {:?}",
-                total_unspent,
-                total_required,
-                &public_key.to_hex()
-            );
-            return None;
         }
 
         Some(utxos)
     }
 
+    /// Can we spend from and send change to the miner key's p2wpkh address?  Only if the
+    /// operator asked for it, the key is compressed, and Stacks 2.1 has begun -- the burnchain
+    /// parser ignores transactions with witness inputs or outputs before then.
+    fn is_segwit_active(&self, public_key: &Secp256k1PublicKey) -> bool {
+        if !self.config.burnchain.segwit || !public_key.compressed() {
+            return false;
+        }
+        let next_height = self.get_headers_height() + 1;
+        self.indexer
+            .get_stacks_epochs()
+            .iter()
+            .find(|epoch| epoch.epoch_id >= StacksEpochId::Epoch21)
+            .map(|epoch| epoch.start_height <= next_height)
+            .unwrap_or(false)
+    }
+
     fn build_leader_key_register_tx(
         &mut self,
         payload: LeaderKeyRegisterOp,
@@ -936,12 +1149,17 @@ impl BitcoinRegtestController {
         signer: &mut BurnchainOpSigner,
         utxos_to_include: Option<UTXOSet>,
         utxos_to_exclude: Option<UTXOSet>,
-        previous_fees: Option<LeaderBlockCommitFees>,
+        replacement_fees: Option<LeaderBlockCommitFees>,
         previous_txids: &Vec<Txid>,
     ) -> Option<Transaction> {
-        let mut estimated_fees = match previous_fees {
-            Some(fees) => fees.fees_from_previous_tx(&payload, &self.config),
-            None => LeaderBlockCommitFees::estimated_fees_from_payload(&payload, &self.config),
+        let mut estimated_fees = match replacement_fees {
+            Some(fees) => fees,
+            None => {
+                let mut fees =
+                    LeaderBlockCommitFees::estimated_fees_from_payload(&payload, &self.config);
+                fees.fee_rate = self.get_block_commit_fee_rate();
+                fees
+            }
         };
 
         let public_key = signer.get_public_key();
@@ -990,9 +1208,7 @@ impl BitcoinRegtestController {
             signer,
         )?;
 
-        let serialized_tx = SerializedTx::new(tx.clone());
-
-        let tx_size = serialized_tx.bytes.len() as u64;
+        let tx_size = get_vsize(&tx);
         estimated_fees.register_replacement(tx_size);
         let mut txid = tx.txid().as_bytes().to_vec();
         txid.reverse();
@@ -1006,6 +1222,7 @@ impl BitcoinRegtestController {
             utxos,
             fees: estimated_fees,
             txids,
+            sent_at_height: self.get_headers_height(),
         };
 
         info!(
@@ -1020,6 +1237,7 @@ impl BitcoinRegtestController {
         self.ongoing_block_commit = Some(ongoing_block_commit);
 
         increment_btc_ops_sent_counter();
+        update_block_commit_fee_rate(fee_rate as i64);
 
         Some(tx)
     }
@@ -1081,13 +1299,12 @@ impl BitcoinRegtestController {
             return res;
         }
 
-        // Stop as soon as the fee_rate is ${self.config.burnchain.max_rbf} percent higher, stop RBF
-        if ongoing_op.fees.fee_rate
-            > (self.config.burnchain.satoshis_per_byte * self.config.burnchain.max_rbf / 100)
-        {
+        // Stop as soon as the fee_rate is ${self.config.burnchain.max_rbf} percent higher (or
+        // past max_fee_rate, with fee estimation), stop RBF
+        if ongoing_op.fees.fee_rate > self.config.burnchain.get_max_block_commit_fee_rate() {
             warn!(
-                "RBF'd block commits reached {}% satoshi per byte fee rate, not resubmitting",
-                self.config.burnchain.max_rbf
+                "RBF'd block commits reached {} sats/vbyte fee rate, not resubmitting",
+                self.config.burnchain.get_max_block_commit_fee_rate()
             );
             self.ongoing_block_commit = Some(ongoing_op);
             return None;
@@ -1123,12 +1340,15 @@ impl BitcoinRegtestController {
         } else {
             // Case 2) ii): Attempt to RBF
             info!("Attempt to replace by fee an outdated leader block commit");
+            let fees = ongoing_op
+                .fees
+                .fees_from_previous_tx(&payload, &self.config);
             self.send_block_commit_operation(
                 payload,
                 signer,
                 Some(ongoing_op.utxos.clone()),
                 None,
-                Some(ongoing_op.fees.clone()),
+                Some(fees),
                 &ongoing_op.txids,
             )
         };
@@ -1140,6 +1360,122 @@ impl BitcoinRegtestController {
         res
     }
 
+    /// The fee rate for a new block-commit: `satoshis_per_byte`, or with `fee_estimation`,
+    /// bitcoind's estimate for confirming in the next block
+    fn get_block_commit_fee_rate(&self) -> u64 {
+        if !self.config.burnchain.fee_estimation {
+            return self.config.burnchain.satoshis_per_byte;
+        }
+        match BitcoinRPCRequest::estimate_smart_fee(&self.config, 1) {
+            Ok(estimated_fee_rate) => {
+                let fee_rate = self.config.burnchain.clamp_fee_rate(estimated_fee_rate);
+                info!(
+                    "Block commit fee rate: {} sats/vbyte (bitcoind estimate: {} sats/vbyte)",
+                    fee_rate, estimated_fee_rate
+                );
+                fee_rate
+            }
+            Err(e) => {
+                warn!(
+                    "No fee estimate from bitcoind, using satoshis_per_byte ({} sats/vbyte) - {:?}",
+                    self.config.burnchain.satoshis_per_byte, e
+                );
+                self.config.burnchain.satoshis_per_byte
+            }
+        }
+    }
+
+    /// If bitcoind now expects a higher fee rate than our in-flight block-commit pays to confirm
+    /// in the next block, replace it with an otherwise-identical commit (same block, burn, PoX
+    /// outputs and UTXOs) paying that rate, up to `max_rbf`.  Only done while the next block is
+    /// still the one the commit was sent for -- after that, the miner's next tenure replaces it.
+    /// Returns true if a replacement was sent.
+    pub fn bump_block_commit_fee(&mut self, signer: &mut BurnchainOpSigner) -> bool {
+        let ongoing_op = match self.ongoing_block_commit.take() {
+            Some(ongoing_op) => ongoing_op,
+            None => return false,
+        };
+
+        let res = self.try_bump_block_commit_fee(&ongoing_op, signer);
+        if res.is_none() {
+            self.ongoing_block_commit = Some(ongoing_op);
+        }
+
+        match res {
+            Some(tx) => self.submit_transaction(&tx, &signer.get_public_key()),
+            None => false,
+        }
+    }
+
+    fn try_bump_block_commit_fee(
+        &mut self,
+        ongoing_op: &OngoingBlockCommit,
+        signer: &mut BurnchainOpSigner,
+    ) -> Option<Transaction> {
+        if self.get_headers_height() > ongoing_op.sent_at_height {
+            debug!("Not bumping block commit fee: its burnchain block has passed");
+            return None;
+        }
+
+        let _ = self.sortdb_mut();
+        let burnchain_db = self.burnchain_db.as_ref().expect("BurnchainDB not opened");
+        if ongoing_op
+            .txids
+            .iter()
+            .any(|txid| burnchain_db.get_burnchain_op(txid).is_some())
+        {
+            debug!("Not bumping block commit fee: it was mined");
+            return None;
+        }
+
+        let estimated_fee_rate = match BitcoinRPCRequest::estimate_smart_fee(&self.config, 1) {
+            Ok(fee_rate) => fee_rate,
+            Err(e) => {
+                debug!("Not bumping block commit fee: no fee estimate - {:?}", e);
+                return None;
+            }
+        };
+        if estimated_fee_rate <= ongoing_op.fees.fee_rate {
+            return None;
+        }
+
+        let max_fee_rate = self.config.burnchain.get_max_block_commit_fee_rate();
+        if ongoing_op.fees.fee_rate >= max_fee_rate {
+            warn!(
+                "Block commit fee rate is already at the {} sats/vbyte limit, not bumping it to {} sats/vbyte",
+                max_fee_rate, estimated_fee_rate
+            );
+            return None;
+        }
+
+        // BIP125 replacements must raise the fee rate by at least the relay fee, which is what
+        // `rbf_fee_increment` is for
+        let mut fees = ongoing_op
+            .fees
+            .fees_from_previous_tx(&ongoing_op.payload, &self.config);
+        fees.fee_rate = cmp::min(cmp::max(fees.fee_rate, estimated_fee_rate), max_fee_rate);
+        if fees.estimated_amount_required() > ongoing_op.sum_utxos() {
+            warn!(
+                "Not enough in block commit UTXOs to bump its fee rate to {} sats/vbyte",
+                fees.fee_rate
+            );
+            return None;
+        }
+
+        info!(
+            "Bitcoin fee rate rose to {} sats/vbyte, replacing block commit paying {} sats/vbyte",
+            estimated_fee_rate, ongoing_op.fees.fee_rate
+        );
+        self.send_block_commit_operation(
+            ongoing_op.payload.clone(),
+            signer,
+            Some(ongoing_op.utxos.clone()),
+            None,
+            Some(fees),
+            &ongoing_op.txids,
+        )
+    }
+
     fn prepare_tx(
         &mut self,
         public_key: &Secp256k1PublicKey,
@@ -1198,6 +1534,7 @@ impl BitcoinRegtestController {
             }
         });
 
+        let consolidate = fee_rate <= self.config.burnchain.utxo_consolidation_fee_rate;
         let tx_size = {
             // We will be calling 2 times serialize_tx, the first time with an estimated size,
             // Second time with the actual size, computed thanks to the 1st attempt.
@@ -1213,9 +1550,9 @@ impl BitcoinRegtestController {
                 spent_in_outputs + min_tx_size * fee_rate + estimated_rbf,
                 &mut utxos_cloned,
                 signer,
+                consolidate,
             );
-            let serialized_tx = SerializedTx::new(tx_cloned);
-            cmp::max(min_tx_size, serialized_tx.bytes.len() as u64)
+            cmp::max(min_tx_size, get_vsize(&tx_cloned))
         };
 
         let rbf_fee = if spent_in_rbf == 0 {
@@ -1228,40 +1565,59 @@ impl BitcoinRegtestController {
             spent_in_outputs + tx_size * fee_rate + rbf_fee,
             utxos_set,
             signer,
+            consolidate,
         );
+        if signer.is_external() {
+            *tx = self.sign_with_wallet(tx)?;
+        }
         signer.dispose();
         Some(())
     }
 
+    /// Have bitcoind's wallet sign a transaction as a PSBT, for a miner key we don't hold.
+    /// If the wallet is backed by an external signer, bitcoind hands the PSBT off to it.
+    fn sign_with_wallet(&self, tx: &Transaction) -> Option<Transaction> {
+        let mut unsigned_tx = tx.clone();
+        for input in unsigned_tx.input.iter_mut() {
+            input.script_sig = Script::new();
+            input.witness = vec![];
+        }
+        let unsigned_tx = SerializedTx::new(unsigned_tx);
+        match BitcoinRPCRequest::sign_psbt(&self.config, unsigned_tx.to_hex()) {
+            Ok(tx) => Some(tx),
+            Err(e) => {
+                error!(
+                    "Bitcoin RPC failure: failed to sign transaction with the wallet - {:?}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
     fn serialize_tx(
         &mut self,
         tx: &mut Transaction,
         total_to_spend: u64,
         utxos_set: &mut UTXOSet,
         signer: &mut BurnchainOpSigner,
+        consolidate: bool,
     ) -> bool {
         let public_key = signer.get_public_key();
-        let mut total_consumed = 0;
-
-        // select UTXOs until we have enough to cover the cost
-        let mut available_utxos = vec![];
-        available_utxos.append(&mut utxos_set.utxos);
-        for utxo in available_utxos.into_iter() {
-            total_consumed += utxo.amount;
-            utxos_set.utxos.push(utxo);
 
-            if total_consumed >= total_to_spend {
-                break;
+        let selected_utxos = match select_utxos(&utxos_set.utxos, total_to_spend, consolidate) {
+            Some(selected_utxos) => selected_utxos,
+            None => {
+                warn!(
+                    "Total available {} is less than intended spend: {}",
+                    utxos_set.total_available(),
+                    total_to_spend
+                );
+                return false;
             }
-        }
-
-        if total_consumed < total_to_spend {
-            warn!(
-                "Consumed total {} is less than intended spend: {}",
-                total_consumed, total_to_spend
-            );
-            return false;
-        }
+        };
+        utxos_set.utxos = selected_utxos;
+        let total_consumed = utxos_set.total_available();
 
         // Append the change output
         let change_address_hash = Hash160::from_data(&public_key.to_bytes());
@@ -1271,14 +1627,18 @@ impl BitcoinRegtestController {
             value, total_consumed, total_to_spend
         );
         if value >= DUST_UTXO_LIMIT {
-            let change_output = BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, value);
+            let change_output = if self.is_segwit_active(&public_key) {
+                BitcoinAddress::to_p2wpkh_tx_out(&change_address_hash, value)
+            } else {
+                BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, value)
+            };
             tx.output.push(change_output);
         } else {
             // Instead of leaving that change to the BTC miner, we could / should bump the sortition fee
             debug!("Not enough change to clear dust limit. Not adding change address.");
         }
 
-        for (i, utxo) in utxos_set.utxos.iter().enumerate() {
+        for utxo in utxos_set.utxos.iter() {
             let input = TxIn {
                 previous_output: OutPoint {
                     txid: utxo.txid,
@@ -1289,10 +1649,35 @@ impl BitcoinRegtestController {
                 witness: vec![],
             };
             tx.input.push(input);
+        }
+
+        // every input's signature commits to all of the inputs, so sign once they're all in
+        for (i, utxo) in utxos_set.utxos.iter().enumerate() {
+            let is_witness = utxo.script_pub_key.is_v0_p2wpkh();
+
+            if signer.is_external() {
+                // bitcoind's wallet signs this later, but reserve room for the signature so we
+                // pay enough fees for the signed transaction
+                if is_witness {
+                    tx.input[i].witness = vec![vec![0u8; MAX_P2PKH_SIG_LEN], public_key.to_bytes()];
+                } else {
+                    tx.input[i].script_sig = Builder::new()
+                        .push_slice(&[0u8; MAX_P2PKH_SIG_LEN])
+                        .push_slice(&public_key.to_bytes())
+                        .into_script();
+                }
+                continue;
+            }
 
-            let script_pub_key = utxo.script_pub_key.clone();
             let sig_hash_all = 0x01;
-            let sig_hash = tx.signature_hash(i, &script_pub_key, sig_hash_all);
+            let sig_hash = if is_witness {
+                // BIP143: p2wpkh inputs sign the p2pkh script of their key hash, and commit to
+                // the amount spent
+                let script_code = BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, 0);
+                tx.segwit_signature_hash(i, &script_code.script_pubkey, utxo.amount, sig_hash_all)
+            } else {
+                tx.signature_hash(i, &utxo.script_pub_key, sig_hash_all)
+            };
 
             let sig1_der = {
                 let message = signer
@@ -1305,10 +1690,15 @@ impl BitcoinRegtestController {
                     .serialize_der()
             };
 
-            tx.input[i].script_sig = Builder::new()
-                .push_slice(&[&*sig1_der, &[sig_hash_all as u8][..]].concat())
-                .push_slice(&public_key.to_bytes())
-                .into_script();
+            let sig1 = [&*sig1_der, &[sig_hash_all as u8][..]].concat();
+            if is_witness {
+                tx.input[i].witness = vec![sig1, public_key.to_bytes()];
+            } else {
+                tx.input[i].script_sig = Builder::new()
+                    .push_slice(&sig1)
+                    .push_slice(&public_key.to_bytes())
+                    .into_script();
+            }
         }
         true
     }
@@ -1322,6 +1712,25 @@ impl BitcoinRegtestController {
         unimplemented!()
     }
 
+    /// Send a transaction spending `public_key`'s UTXOs, and keep our cache of them up to date.
+    /// If bitcoind rejects it, the cache may be wrong, so it's dropped.
+    fn submit_transaction(&mut self, tx: &Transaction, public_key: &Secp256k1PublicKey) -> bool {
+        if !self.send_transaction(SerializedTx::new(tx.clone())) {
+            self.utxo_cache = None;
+            return false;
+        }
+
+        if let Some(ref mut utxo_cache) = self.utxo_cache {
+            let address_hash = Hash160::from_data(&public_key.to_bytes());
+            let own_scripts = vec![
+                BitcoinAddress::to_p2pkh_tx_out(&address_hash, 0).script_pubkey,
+                BitcoinAddress::to_p2wpkh_tx_out(&address_hash, 0).script_pubkey,
+            ];
+            utxo_cache.record_sent_tx(tx, &own_scripts);
+        }
+        true
+    }
+
     fn send_transaction(&self, transaction: SerializedTx) -> bool {
         let result = BitcoinRPCRequest::send_raw_transaction(&self.config, transaction.to_hex());
         match result {
@@ -1445,6 +1854,36 @@ impl BitcoinRegtestController {
     }
 }
 
+/// Start a thread that long-polls bitcoind for new blocks, and tells the PoX sync watchdog
+/// about each one so it can sync the burnchain right away instead of waiting out
+/// `burnchain.poll_time_secs`.
+pub fn start_new_block_listener(config: Config, mut comms: PoxSyncWatchdogComms) -> JoinHandle<()> {
+    thread::Builder::new()
+        .name("bitcoind-new-blocks".to_string())
+        .spawn(move || {
+            let mut last_tip: Option<String> = None;
+            while comms.should_keep_running() {
+                match BitcoinRPCRequest::wait_for_new_block(&config, NEW_BLOCK_WAIT_TIMEOUT_MS) {
+                    Ok(tip) => {
+                        if last_tip.as_ref() != Some(&tip) {
+                            debug!("bitcoind has a new chain tip {}", &tip);
+                            comms.notify_new_burn_block();
+                            last_tip = Some(tip);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to wait for a new bitcoin block: {:?}", &e);
+                        let deadline = get_epoch_time_secs() + config.burnchain.poll_time_secs;
+                        while comms.should_keep_running() && get_epoch_time_secs() < deadline {
+                            sleep_ms(1000);
+                        }
+                    }
+                }
+            }
+        })
+        .expect("FATAL: failed to start bitcoind new-block listener thread")
+}
+
 impl BurnchainController for BitcoinRegtestController {
     fn sortdb_ref(&self) -> &SortitionDB {
         self.db
@@ -1570,12 +2009,10 @@ impl BurnchainController for BitcoinRegtestController {
             BlockstackOperationType::StackStx(_payload) => unimplemented!(),
         };
 
-        let transaction = match transaction {
-            Some(tx) => SerializedTx::new(tx),
-            _ => return false,
-        };
-
-        self.send_transaction(transaction)
+        match transaction {
+            Some(tx) => self.submit_transaction(&tx, &op_signer.get_public_key()),
+            _ => false,
+        }
     }
 
     #[cfg(test)]
@@ -1758,10 +2195,15 @@ pub enum RPCError {
 
 type RPCResult<T> = Result<T, RPCError>;
 
+lazy_static! {
+    /// When we last failed to reach each bitcoind's RPC interface, keyed by host and RPC port
+    static ref RPC_ENDPOINT_FAILURES: Mutex<HashMap<(String, u16), u64>> = Mutex::new(HashMap::new());
+}
+
 impl BitcoinRPCRequest {
-    fn build_rpc_request(config: &Config) -> Request {
+    fn build_rpc_request(config: &Config, endpoint: &BitcoinEndpoint) -> Request {
         let url = {
-            let url = config.burnchain.get_rpc_url();
+            let url = config.burnchain.get_rpc_url(endpoint);
             Url::parse(&url).expect(&format!("Unable to parse {} as a URL", url))
         };
         debug!(
@@ -1932,6 +2374,20 @@ impl BitcoinRPCRequest {
     }
 
     pub fn import_public_key(config: &Config, public_key: &Secp256k1PublicKey) -> RPCResult<()> {
+        let segwit = config.burnchain.segwit && public_key.compressed();
+        if config.burnchain.is_descriptor_wallet() {
+            if segwit {
+                BitcoinRPCRequest::import_descriptor(
+                    config,
+                    &format!("wpkh({})", public_key.to_hex()),
+                )?;
+            }
+            return BitcoinRPCRequest::import_descriptor(
+                config,
+                &format!("pkh({})", public_key.to_hex()),
+            );
+        }
+
         let rescan = true;
         let label = "";
 
@@ -1943,17 +2399,130 @@ impl BitcoinRPCRequest {
             BitcoinAddress::from_bytes(network_id, BitcoinAddressType::PublicKeyHash, &pkh)
                 .expect("Public key incorrect");
 
+        let mut addresses = vec![address.to_b58()];
+        if segwit {
+            let address = BitcoinAddress::from_bytes(
+                network_id,
+                BitcoinAddressType::WitnessPublicKeyHash,
+                &pkh,
+            )
+            .expect("Public key incorrect");
+            addresses.push(address.to_bech32());
+        }
+
+        for address in addresses.into_iter() {
+            let payload = BitcoinRPCRequest {
+                method: "importaddress".to_string(),
+                params: vec![address.into(), label.into(), rescan.into()],
+                id: "stacks".to_string(),
+                jsonrpc: "2.0".to_string(),
+            };
+
+            BitcoinRPCRequest::send(config, payload)?;
+        }
+        Ok(())
+    }
+
+    /// Watch a descriptor in a descriptor wallet, rescanning the whole chain for its UTXOs
+    pub fn import_descriptor(config: &Config, descriptor: &str) -> RPCResult<()> {
         let payload = BitcoinRPCRequest {
-            method: "importaddress".to_string(),
-            params: vec![address.to_b58().into(), label.into(), rescan.into()],
+            method: "getdescriptorinfo".to_string(),
+            params: vec![descriptor.into()],
             id: "stacks".to_string(),
             jsonrpc: "2.0".to_string(),
         };
 
-        BitcoinRPCRequest::send(&config, payload)?;
+        // importdescriptors only takes descriptors with their checksums
+        let res = BitcoinRPCRequest::send(config, payload)?;
+        let descriptor = match res
+            .get("result")
+            .and_then(|result| result.get("descriptor"))
+            .and_then(|descriptor| descriptor.as_str())
+        {
+            Some(descriptor) => descriptor.to_string(),
+            None => {
+                return Err(RPCError::Parsing(format!(
+                    "Bitcoin RPC: no descriptor in getdescriptorinfo response: {:?}",
+                    &res
+                )))
+            }
+        };
+
+        let payload = BitcoinRPCRequest {
+            method: "importdescriptors".to_string(),
+            params: vec![json!([{ "desc": descriptor, "timestamp": 0 }])],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+
+        BitcoinRPCRequest::send(config, payload)?;
         Ok(())
     }
 
+    /// Sign a raw transaction with the wallet by way of a PSBT, and return the finalized
+    /// transaction.
+    pub fn sign_psbt(config: &Config, tx_hex: String) -> RPCResult<Transaction> {
+        let payload = BitcoinRPCRequest {
+            method: "converttopsbt".to_string(),
+            params: vec![tx_hex.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+        let res = BitcoinRPCRequest::send(config, payload)?;
+        let psbt = BitcoinRPCRequest::get_result_str(&res, None)?;
+
+        let payload = BitcoinRPCRequest {
+            method: "walletprocesspsbt".to_string(),
+            params: vec![psbt.into(), true.into(), "ALL".into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+        let res = BitcoinRPCRequest::send(config, payload)?;
+        let psbt = BitcoinRPCRequest::get_result_str(&res, Some("psbt"))?;
+
+        let payload = BitcoinRPCRequest {
+            method: "finalizepsbt".to_string(),
+            params: vec![psbt.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+        let res = BitcoinRPCRequest::send(config, payload)?;
+        if res
+            .get("result")
+            .and_then(|result| result.get("complete"))
+            .and_then(|complete| complete.as_bool())
+            != Some(true)
+        {
+            return Err(RPCError::Bitcoind(format!(
+                "Bitcoin RPC: wallet did not fully sign the PSBT: {:?}",
+                &res
+            )));
+        }
+        let tx_hex = BitcoinRPCRequest::get_result_str(&res, Some("hex"))?;
+
+        let tx_bytes = hex_bytes(&tx_hex)
+            .map_err(|e| RPCError::Parsing(format!("Bitcoin RPC: bad transaction hex: {:?}", e)))?;
+        deserialize(&tx_bytes)
+            .map_err(|e| RPCError::Parsing(format!("Bitcoin RPC: bad transaction: {:?}", e)))
+    }
+
+    /// Get a string out of an RPC response's `result`, or out of one of its fields
+    fn get_result_str(res: &serde_json::Value, field: Option<&str>) -> RPCResult<String> {
+        let result = res.get("result");
+        let value = match field {
+            Some(field) => result.and_then(|result| result.get(field)),
+            None => result,
+        };
+        match value.and_then(|value| value.as_str()) {
+            Some(value) => Ok(value.to_string()),
+            None => Err(RPCError::Parsing(format!(
+                "Bitcoin RPC: no {} in response: {:?}",
+                field.unwrap_or("result"),
+                res
+            ))),
+        }
+    }
+
     /// Calls `listwallets` method through RPC call and returns wallet names as a vector of Strings
     pub fn list_wallets(config: &Config) -> RPCResult<Vec<String>> {
         let payload = BitcoinRPCRequest {
@@ -1992,11 +2561,79 @@ impl BitcoinRPCRequest {
         Ok(wallets)
     }
 
+    /// Get bitcoind's estimate of the fee rate, in sats per vbyte, that a transaction needs to
+    /// confirm within `conf_target` blocks.
+    pub fn estimate_smart_fee(config: &Config, conf_target: u64) -> RPCResult<u64> {
+        let payload = BitcoinRPCRequest {
+            method: "estimatesmartfee".to_string(),
+            params: vec![conf_target.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+
+        let res = BitcoinRPCRequest::send(config, payload)?;
+        match res
+            .get("result")
+            .and_then(|result| result.get("feerate"))
+            .and_then(|fee_rate| fee_rate.as_f64())
+        {
+            // reported in BTC per kvbyte
+            Some(fee_rate) => Ok((fee_rate * 100_000.0).ceil() as u64),
+            None => Err(RPCError::Parsing(format!(
+                "Bitcoin RPC: no feerate in estimatesmartfee response: {:?}",
+                &res
+            ))),
+        }
+    }
+
+    /// Block in bitcoind until it has a new chain tip or `timeout_ms` passes, and return the
+    /// hash of its chain tip.
+    pub fn wait_for_new_block(config: &Config, timeout_ms: u64) -> RPCResult<String> {
+        let payload = BitcoinRPCRequest {
+            method: "waitfornewblock".to_string(),
+            params: vec![timeout_ms.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+
+        let mut res = BitcoinRPCRequest::send(config, payload)?;
+        match res
+            .as_object_mut()
+            .and_then(|object| object.get_mut("result"))
+            .and_then(|result| result.get("hash"))
+            .and_then(|hash| hash.as_str())
+        {
+            Some(hash) => Ok(hash.to_string()),
+            None => Err(RPCError::Parsing(format!(
+                "Bitcoin RPC: no block hash in waitfornewblock response: {:?}",
+                &res
+            ))),
+        }
+    }
+
     /// Tries to create a wallet with the given name
+    /// A descriptor wallet is created watch-only, since we sign transactions ourselves.
     pub fn create_wallet(config: &Config, wallet_name: &str) -> RPCResult<()> {
+        let params = if config.burnchain.is_descriptor_wallet() {
+            let disable_private_keys = true;
+            let blank = true;
+            let passphrase = "";
+            let avoid_reuse = false;
+            let descriptors = true;
+            vec![
+                wallet_name.into(),
+                disable_private_keys.into(),
+                blank.into(),
+                passphrase.into(),
+                avoid_reuse.into(),
+                descriptors.into(),
+            ]
+        } else {
+            vec![wallet_name.into()]
+        };
         let payload = BitcoinRPCRequest {
             method: "createwallet".to_string(),
-            params: vec![wallet_name.into()],
+            params,
             id: "stacks".to_string(),
             jsonrpc: "2.0".to_string(),
         };
@@ -2005,20 +2642,91 @@ impl BitcoinRPCRequest {
         Ok(())
     }
 
+    /// Open a connection to bitcoind, through the SOCKS5 proxy if `rpc_use_proxy` is set
+    async fn connect(config: &Config, endpoint: &BitcoinEndpoint) -> std::io::Result<TcpStream> {
+        match config.connection_options.socks5_proxy {
+            Some(ref proxy) if config.burnchain.rpc_use_proxy => {
+                let target = Socks5Target::Domain(endpoint.host.clone(), endpoint.rpc_port);
+                let stream = connect_through_proxy(
+                    proxy,
+                    target,
+                    config.connection_options.socks5_credentials.clone(),
+                    Duration::from_secs(config.burnchain.timeout as u64),
+                )
+                .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
+                Ok(TcpStream::from(stream))
+            }
+            _ => TcpStream::connect(config.burnchain.get_rpc_socket_addr(endpoint)?).await,
+        }
+    }
+
+    /// Send an RPC request to the most preferred bitcoind that we can reach.  Bitcoinds that we
+    /// couldn't reach in the last `BITCOIN_ENDPOINT_FAILBACK_INTERVAL` seconds are tried last.
     fn send(config: &Config, payload: BitcoinRPCRequest) -> RPCResult<serde_json::Value> {
-        let mut request = BitcoinRPCRequest::build_rpc_request(&config);
+        let now = get_epoch_time_secs();
+        let endpoints = config.burnchain.get_endpoints();
+        let num_endpoints = endpoints.len();
+        let (up, down): (Vec<_>, Vec<_>) = {
+            let failures = RPC_ENDPOINT_FAILURES
+                .lock()
+                .expect("FATAL: RPC endpoint failures lock poisoned");
+            endpoints.into_iter().partition(|endpoint| {
+                failures
+                    .get(&(endpoint.host.clone(), endpoint.rpc_port))
+                    .map(|failed_at| failed_at + BITCOIN_ENDPOINT_FAILBACK_INTERVAL <= now)
+                    .unwrap_or(true)
+            })
+        };
+
+        let mut last_err = None;
+        for endpoint in up.iter().chain(down.iter()) {
+            let key = (endpoint.host.clone(), endpoint.rpc_port);
+            match BitcoinRPCRequest::send_to(config, endpoint, &payload) {
+                Ok(res) => {
+                    RPC_ENDPOINT_FAILURES
+                        .lock()
+                        .expect("FATAL: RPC endpoint failures lock poisoned")
+                        .remove(&key);
+                    return res;
+                }
+                Err(err) => {
+                    if num_endpoints > 1 {
+                        warn!(
+                            "Bitcoin RPC: failed to reach {}:{}: {:?}",
+                            &endpoint.host, endpoint.rpc_port, &err
+                        );
+                    }
+                    RPC_ENDPOINT_FAILURES
+                        .lock()
+                        .expect("FATAL: RPC endpoint failures lock poisoned")
+                        .insert(key, now);
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.expect("BUG: no bitcoind endpoints"))
+    }
+
+    /// Send an RPC request to one bitcoind.  Returns Err(..) if we couldn't reach it, and
+    /// Ok(..) with its answer otherwise.
+    fn send_to(
+        config: &Config,
+        endpoint: &BitcoinEndpoint,
+        payload: &BitcoinRPCRequest,
+    ) -> Result<RPCResult<serde_json::Value>, RPCError> {
+        let mut request = BitcoinRPCRequest::build_rpc_request(config, endpoint);
 
         let body = match serde_json::to_vec(&json!(payload)) {
             Ok(body) => body,
             Err(err) => {
-                return Err(RPCError::Network(format!("RPC Error: {}", err)));
+                return Ok(Err(RPCError::Network(format!("RPC Error: {}", err))));
             }
         };
         request.append_header("Content-Type", "application/json");
         request.set_body(body);
 
         let mut response = async_std::task::block_on(async move {
-            let stream = match TcpStream::connect(config.burnchain.get_rpc_socket_addr()).await {
+            let stream = match BitcoinRPCRequest::connect(config, endpoint).await {
                 Ok(stream) => stream,
                 Err(err) => {
                     return Err(RPCError::Network(format!(
@@ -2048,8 +2756,15 @@ impl BitcoinRPCRequest {
             (res, buffer)
         });
 
-        if !status.is_success() {
+        if res.is_err() {
             return Err(RPCError::Network(format!(
+                "Bitcoin RPC: unable to read body - {:?}",
+                res
+            )));
+        }
+
+        if !status.is_success() {
+            return Ok(Err(RPCError::Network(format!(
                 "Bitcoin RPC: status({}) != success, body is '{:?}'",
                 status,
                 match serde_json::from_slice::<serde_json::Value>(&buffer[..]) {
@@ -2057,18 +2772,131 @@ impl BitcoinRPCRequest {
                     Err(_e) => serde_json::from_str("\"(unparseable)\"")
                         .expect("Failed to parse JSON literal"),
                 }
-            )));
+            ))));
         }
 
-        if res.is_err() {
-            return Err(RPCError::Network(format!(
-                "Bitcoin RPC: unable to read body - {:?}",
-                res
-            )));
+        Ok(serde_json::from_slice::<serde_json::Value>(&buffer[..])
+            .map_err(|e| RPCError::Parsing(format!("Bitcoin RPC: {}", e))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_utxo(txid_byte: u8, amount: u64, confirmations: u32) -> UTXO {
+        UTXO {
+            txid: Sha256dHash([txid_byte; 32]),
+            vout: 0,
+            script_pub_key: Script::new(),
+            amount,
+            confirmations,
         }
+    }
+
+    fn amounts(utxos: &[UTXO]) -> Vec<u64> {
+        utxos.iter().map(|utxo| utxo.amount).collect()
+    }
+
+    #[test]
+    fn should_select_utxos() {
+        let utxos = vec![
+            make_utxo(1, 1_000, 0),
+            make_utxo(2, 20_000, 1),
+            make_utxo(3, 50_000, 2),
+            make_utxo(4, 30_000, 3),
+            make_utxo(5, 2_000, 4),
+        ];
+
+        // the first UTXO that covers the spend on its own
+        assert_eq!(
+            amounts(&select_utxos(&utxos, 15_000, false).unwrap()),
+            vec![20_000]
+        );
+        assert_eq!(
+            amounts(&select_utxos(&utxos, 40_000, false).unwrap()),
+            vec![50_000]
+        );
+
+        // otherwise, the largest first, leaving dust alone
+        assert_eq!(
+            amounts(&select_utxos(&utxos, 90_000, false).unwrap()),
+            vec![50_000, 30_000, 20_000]
+        );
+        assert!(select_utxos(&utxos, 101_000, false).is_none());
+
+        // consolidating spends dust too, and sweeps up everything else
+        assert_eq!(
+            amounts(&select_utxos(&utxos, 101_000, true).unwrap()),
+            vec![50_000, 30_000, 20_000, 2_000, 1_000]
+        );
+        assert_eq!(
+            amounts(&select_utxos(&utxos, 15_000, true).unwrap()),
+            vec![20_000, 1_000, 2_000, 30_000, 50_000]
+        );
+
+        let many_utxos: Vec<_> = (0..20).map(|i| make_utxo(i, 10_000, 0)).collect();
+        assert_eq!(
+            select_utxos(&many_utxos, 5_000, true).unwrap().len(),
+            MAX_CONSOLIDATION_INPUTS
+        );
+    }
+
+    #[test]
+    fn should_track_sent_txs_in_utxo_cache() {
+        let own_scripts = vec![BitcoinAddress::to_p2pkh_tx_out(&Hash160([1; 20]), 0).script_pubkey];
+        let other_script = BitcoinAddress::to_p2pkh_tx_out(&Hash160([2; 20]), 0).script_pubkey;
+
+        let funding = make_utxo(1, 100_000, 6);
+        let mut cache = UTXOCache::new(
+            100,
+            99,
+            vec!["addr".to_string()],
+            UTXOSet {
+                bhh: BurnchainHeaderHash([0; 32]),
+                utxos: vec![funding.clone(), make_utxo(2, 50_000, 6)],
+            },
+        );
+        assert!(cache.is_fresh(100, 99, &["addr".to_string()]));
+        assert!(!cache.is_fresh(101, 99, &["addr".to_string()]));
+        assert!(!cache.is_fresh(100, 98, &["addr".to_string()]));
+
+        let make_tx = |change: u64| Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: funding.txid,
+                    vout: funding.vout,
+                },
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFD,
+                witness: vec![],
+            }],
+            output: vec![
+                TxOut {
+                    value: 10_000,
+                    script_pubkey: other_script.clone(),
+                },
+                TxOut {
+                    value: change,
+                    script_pubkey: own_scripts[0].clone(),
+                },
+            ],
+        };
 
-        let payload = serde_json::from_slice::<serde_json::Value>(&buffer[..])
-            .map_err(|e| RPCError::Parsing(format!("Bitcoin RPC: {}", e)))?;
-        Ok(payload)
+        // the funding UTXO is spent, and the change is ours
+        let tx = make_tx(80_000);
+        cache.record_sent_tx(&tx, &own_scripts);
+        assert_eq!(amounts(&cache.utxos.utxos), vec![50_000, 80_000]);
+        assert_eq!(cache.utxos.utxos[1].txid, tx.txid());
+        assert_eq!(cache.utxos.utxos[1].vout, 1);
+
+        // a replacement's change supersedes the replaced transaction's
+        let replacement = make_tx(70_000);
+        cache.record_sent_tx(&replacement, &own_scripts);
+        assert_eq!(amounts(&cache.utxos.utxos), vec![50_000, 70_000]);
+        assert_eq!(cache.utxos.utxos[1].txid, replacement.txid());
+        assert_eq!(cache.sent_txs.len(), 1);
     }
 }
@@ -1,21 +1,30 @@
+use std::cmp;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fs;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use rand::RngCore;
 
+use stacks::burnchains::bitcoin::indexer::BitcoinEndpoint;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
 use stacks::burnchains::{MagicBytes, BLOCKSTACK_MAGIC_MAINNET};
+use stacks::chainstate::stacks::boot::overrides::validate_boot_contract_overrides;
+use stacks::chainstate::stacks::db::manifest::CHAINSTATE_MANIFEST_INTERVAL;
 use stacks::chainstate::stacks::miner::BlockBuilderSettings;
 use stacks::chainstate::stacks::MAX_BLOCK_LEN;
-use stacks::core::mempool::MemPoolWalkSettings;
+use stacks::core::mempool::{MemPoolDB, MemPoolWalkSettings};
 use stacks::core::StacksEpoch;
 use stacks::core::{
     CHAIN_ID_MAINNET, CHAIN_ID_TESTNET, PEER_VERSION_MAINNET, PEER_VERSION_TESTNET,
 };
 use stacks::cost_estimates::fee_medians::WeightedMedianFeeRateEstimator;
+use stacks::cost_estimates::fee_observations::FeeObservationRecorder;
+use stacks::cost_estimates::fee_percentiles::PercentileFeeRateEstimator;
 use stacks::cost_estimates::fee_rate_fuzzer::FeeRateFuzzer;
+use stacks::cost_estimates::fee_regression::RegressionFeeRateEstimator;
 use stacks::cost_estimates::fee_scalar::ScalarFeeRateEstimator;
 use stacks::cost_estimates::metrics::CostMetric;
 use stacks::cost_estimates::metrics::ProportionalDotProduct;
@@ -23,16 +32,25 @@ use stacks::cost_estimates::CostEstimator;
 use stacks::cost_estimates::FeeEstimator;
 use stacks::cost_estimates::PessimisticEstimator;
 use stacks::net::connection::ConnectionOptions;
-use stacks::net::{Neighbor, NeighborKey, PeerAddress};
+use stacks::net::portmap::PortMappingMethod;
+use stacks::net::ratelimit::RPCRateLimit;
+use stacks::net::seeds::parse_dns_seed;
+use stacks::net::socks::is_onion_host;
+use stacks::net::watchdog::STALE_TIP_BURN_BLOCKS;
+use stacks::net::{AddressFamilyPreference, Neighbor, NeighborKey, PeerAddress};
 use stacks::util::get_epoch_time_ms;
 use stacks::util::hash::hex_bytes;
 use stacks::util::secp256k1::Secp256k1PrivateKey;
 use stacks::util::secp256k1::Secp256k1PublicKey;
+use stacks::vm::costs::cost_functions::ClarityCostFunction;
+use stacks::vm::costs::{CostOverrides, ExecutionCost};
 use stacks::vm::types::{AssetIdentifier, PrincipalData, QualifiedContractIdentifier};
 
 const DEFAULT_SATS_PER_VB: u64 = 50;
 const DEFAULT_MAX_RBF_RATE: u64 = 150; // 1.5x
 const DEFAULT_RBF_FEE_RATE_INCREMENT: u64 = 5;
+const DEFAULT_MIN_FEE_RATE: u64 = 1;
+const DEFAULT_MAX_FEE_RATE: u64 = 300;
 const LEADER_KEY_TX_ESTIM_SIZE: u64 = 290;
 const BLOCK_COMMIT_TX_ESTIM_SIZE: u64 = 350;
 const INV_REWARD_CYCLES_TESTNET: u64 = 6;
@@ -56,6 +74,7 @@ pub struct LegacyMstxConfigFile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Keychain;
 
     #[test]
     fn should_load_legacy_mstx_balances_toml() {
@@ -100,6 +119,235 @@ mod tests {
             "ST2TFVBMRPS5SSNP98DQKQ5JNB2B6NZM91C4K3P7B"
         );
     }
+
+    #[test]
+    fn should_load_boot_contracts() {
+        let path = "/tmp/stacks-node-tests-boot-contract-appchain.clar";
+        fs::write(path, "(define-data-var height uint u0)").unwrap();
+
+        let config = Config::from_config_file(ConfigFile::from_str(&format!(
+            r#"
+            [burnchain]
+            mode = "mocknet"
+
+            [[node.boot_contracts]]
+            name = "appchain"
+            path = "{}"
+            "#,
+            path
+        )));
+        assert_eq!(
+            config.node.boot_contract_overrides,
+            vec![(
+                "appchain".to_string(),
+                "(define-data-var height uint u0)".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn should_load_unix_socket_rpc_bind() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [node]
+            rpc_bind = "unix:/var/run/stacks/rpc.sock"
+            p2p_bind = "0.0.0.0:20444"
+
+            [connection_options]
+            rpc_unix_socket_mode = "660"
+            "#,
+        ));
+        assert_eq!(
+            config.node.get_rpc_bind(),
+            RpcBind::Unix(PathBuf::from("/var/run/stacks/rpc.sock"))
+        );
+        assert_eq!(config.node.p2p_address, "0.0.0.0:20444");
+        assert_eq!(config.node.data_url, "");
+        assert_eq!(config.connection_options.rpc_unix_socket_mode, 0o660);
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [node]
+            rpc_bind = "127.0.0.1:20443"
+            "#,
+        ));
+        assert_eq!(
+            config.node.get_rpc_bind(),
+            RpcBind::Tcp("127.0.0.1:20443".parse().unwrap())
+        );
+        assert_eq!(config.node.data_url, "http://127.0.0.1:20443");
+        assert_eq!(config.connection_options.rpc_unix_socket_mode, 0o600);
+    }
+
+    #[test]
+    fn should_load_backup_bitcoin_endpoints() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            peer_host = "127.0.0.1"
+            peer_port = 18444
+            rpc_port = 18443
+
+            [[burnchain.backup_endpoints]]
+            peer_host = "bitcoind-2.example.com"
+
+            [[burnchain.backup_endpoints]]
+            peer_host = "bitcoind-3.example.com"
+            peer_port = 8333
+            rpc_port = 8332
+            "#,
+        ));
+        assert_eq!(
+            config.burnchain.get_endpoints(),
+            vec![
+                BitcoinEndpoint {
+                    host: "127.0.0.1".to_string(),
+                    peer_port: 18444,
+                    rpc_port: 18443,
+                },
+                BitcoinEndpoint {
+                    host: "bitcoind-2.example.com".to_string(),
+                    peer_port: 18444,
+                    rpc_port: 18443,
+                },
+                BitcoinEndpoint {
+                    host: "bitcoind-3.example.com".to_string(),
+                    peer_port: 8333,
+                    rpc_port: 8332,
+                },
+            ]
+        );
+        assert_eq!(
+            config
+                .burnchain
+                .get_rpc_url(&config.burnchain.backup_endpoints[1]),
+            "http://bitcoind-3.example.com:8332"
+        );
+
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert_eq!(config.burnchain.get_endpoints().len(), 1);
+    }
+
+    #[test]
+    fn should_load_wait_for_new_blocks() {
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert!(!config.burnchain.wait_for_new_blocks);
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            wait_for_new_blocks = true
+            "#,
+        ));
+        assert!(config.burnchain.wait_for_new_blocks);
+    }
+
+    #[test]
+    fn should_load_segwit() {
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert!(!config.burnchain.segwit);
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            segwit = true
+            "#,
+        ));
+        assert!(config.burnchain.segwit);
+    }
+
+    #[test]
+    fn should_load_auto_rbf() {
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert!(!config.burnchain.auto_rbf);
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            auto_rbf = true
+            "#,
+        ));
+        assert!(config.burnchain.auto_rbf);
+    }
+
+    #[test]
+    fn should_load_fee_estimation() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            satoshis_per_byte = 20
+            "#,
+        ));
+        assert!(!config.burnchain.fee_estimation);
+        assert_eq!(config.burnchain.get_max_block_commit_fee_rate(), 30);
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            satoshis_per_byte = 20
+            fee_estimation = true
+            min_fee_rate = 5
+            max_fee_rate = 100
+            "#,
+        ));
+        assert!(config.burnchain.fee_estimation);
+        assert_eq!(config.burnchain.clamp_fee_rate(1), 5);
+        assert_eq!(config.burnchain.clamp_fee_rate(42), 42);
+        assert_eq!(config.burnchain.clamp_fee_rate(1000), 100);
+        assert_eq!(config.burnchain.get_max_block_commit_fee_rate(), 100);
+    }
+
+    #[test]
+    fn should_load_utxo_consolidation_fee_rate() {
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert_eq!(config.burnchain.utxo_consolidation_fee_rate, 0);
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            utxo_consolidation_fee_rate = 3
+            "#,
+        ));
+        assert_eq!(config.burnchain.utxo_consolidation_fee_rate, 3);
+    }
+
+    #[test]
+    fn should_load_external_signer() {
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert!(!config.burnchain.is_descriptor_wallet());
+        assert!(config.burnchain.get_external_signer_public_key().is_none());
+        assert!(Keychain::from_config(&config)
+            .generate_op_signer()
+            .sign_message(&[0u8; 32])
+            .is_some());
+
+        let public_key = "03ef2340518b5867b23598a9cf74611f8b98064f7d55cdb8c107c67b5efcbc5c77";
+        let config = Config::from_config_file(ConfigFile::from_str(&format!(
+            r#"
+            [burnchain]
+            descriptor_wallet = true
+            external_signer_public_key = "{}"
+            "#,
+            public_key
+        )));
+        assert!(config.burnchain.is_descriptor_wallet());
+        assert_eq!(
+            config.burnchain.get_external_signer_public_key(),
+            Some(Secp256k1PublicKey::from_hex(public_key).unwrap())
+        );
+
+        let keychain = Keychain::from_config(&config);
+        let mut op_signer = keychain.generate_op_signer();
+        assert!(op_signer.is_external());
+        assert_eq!(
+            op_signer.get_public_key(),
+            Secp256k1PublicKey::from_hex(public_key).unwrap()
+        );
+        assert_eq!(
+            keychain.get_burnchain_signer().public_keys,
+            vec![Secp256k1PublicKey::from_hex(public_key).unwrap()]
+        );
+    }
 }
 
 impl ConfigFile {
@@ -306,6 +554,12 @@ lazy_static! {
         num_clients: 750,               // number of inbound p2p connections
         soft_num_neighbors: 16,         // soft-limit on the number of neighbors whose inventories we track
         soft_num_clients: 750,          // soft limit on the number of inbound p2p connections
+        max_inbound_miners: 64,         // number of inbound p2p connections from miners
+        max_inbound_followers: 750,     // number of inbound p2p connections from non-miners
+        max_inbound_unknown: 128,       // number of inbound p2p connections that haven't handshaked yet
+        reserved_outbound_slots: 16,    // number of sockets inbound connections leave free for outbound ones
+        inbound_handshake_rate_limit: Some(RPCRateLimit { rate: 1.0, burst: 10 }), // how often each IP address may connect to us
+        partial_message_timeout: 60,    // how long a p2p peer may take to send a message it has started
         max_neighbors_per_host: 1,      // maximum number of neighbors per host we permit
         max_clients_per_host: 4,        // maximum number of inbound p2p connections per host we permit
         soft_max_neighbors_per_host: 1, // soft limit on the number of neighbors per host we permit
@@ -320,6 +574,10 @@ lazy_static! {
         dns_timeout: 15_000,
         max_inflight_blocks: 6,
         max_inflight_attachments: 6,
+        mempool_sync_bootstrap_peers: 3, // how many peers to sync our mempool with right after booting
+        microblock_relay_canonical_budget: 16 * 1024 * 1024, // bytes of the canonical microblock stream we forward per interval
+        microblock_relay_stale_budget: 256 * 1024, // bytes of each stale microblock stream we forward per interval
+        warm_start_peers: 8,            // how many of our best peers from the last run to reconnect to on boot
         .. std::default::Default::default()
     };
 }
@@ -330,6 +588,8 @@ impl Config {
         let (mut node, bootstrap_node, deny_nodes) = match config_file.node {
             Some(node) => {
                 let rpc_bind = node.rpc_bind.unwrap_or(default_node_config.rpc_bind);
+                let p2p_bind = node.p2p_bind.unwrap_or(default_node_config.p2p_bind);
+                let rpc_on_unix_socket = rpc_bind.starts_with(RPC_UNIX_SOCKET_PREFIX);
                 let node_config = NodeConfig {
                     name: node.name.unwrap_or(default_node_config.name),
                     seed: match node.seed {
@@ -340,12 +600,19 @@ impl Config {
                     },
                     working_dir: node.working_dir.unwrap_or(default_node_config.working_dir),
                     rpc_bind: rpc_bind.clone(),
-                    p2p_bind: node.p2p_bind.unwrap_or(default_node_config.p2p_bind),
-                    p2p_address: node.p2p_address.unwrap_or(rpc_bind.clone()),
+                    p2p_bind: p2p_bind.clone(),
+                    p2p_address: node.p2p_address.unwrap_or(if rpc_on_unix_socket {
+                        p2p_bind.clone()
+                    } else {
+                        rpc_bind.clone()
+                    }),
                     bootstrap_node: vec![],
+                    onion_hosts: vec![],
                     deny_nodes: vec![],
                     data_url: match node.data_url {
                         Some(data_url) => data_url,
+                        // peers can't reach an RPC interface on a unix socket
+                        None if rpc_on_unix_socket => "".to_string(),
                         None => format!("http://{}", rpc_bind),
                     },
                     local_peer_seed: match node.local_peer_seed {
@@ -369,10 +636,77 @@ impl Config {
                         .wait_time_for_microblocks
                         .unwrap_or(default_node_config.wait_time_for_microblocks),
                     prometheus_bind: node.prometheus_bind,
+                    grpc_bind: node.grpc_bind,
+                    statsd_endpoint: node.statsd_endpoint,
+                    statsd_prefix: node.statsd_prefix,
+                    otlp_endpoint: node.otlp_endpoint,
+                    otlp_interval: node
+                        .otlp_interval
+                        .unwrap_or(default_node_config.otlp_interval),
                     pox_sync_sample_secs: node
                         .pox_sync_sample_secs
                         .unwrap_or(default_node_config.pox_sync_sample_secs),
                     use_test_genesis_chainstate: node.use_test_genesis_chainstate,
+                    replicate_from: node.replicate_from,
+                    telemetry_url: node.telemetry_url,
+                    telemetry_interval: node
+                        .telemetry_interval
+                        .unwrap_or(default_node_config.telemetry_interval),
+                    marf_blob_store: node.marf_blob_store,
+                    marf_blob_cache_dir: node.marf_blob_cache_dir,
+                    marf_blob_cache_size: node
+                        .marf_blob_cache_size
+                        .unwrap_or(default_node_config.marf_blob_cache_size),
+                    marf_blob_keep_local: node
+                        .marf_blob_keep_local
+                        .unwrap_or(default_node_config.marf_blob_keep_local),
+                    marf_offload_interval: node
+                        .marf_offload_interval
+                        .unwrap_or(default_node_config.marf_offload_interval),
+                    cost_overrides: node.cost_overrides.map(|overrides| {
+                        let overrides: CostOverrides = overrides
+                            .into_iter()
+                            .map(|(name, cost)| {
+                                let cost_function = ClarityCostFunction::lookup_by_name(&name)
+                                    .unwrap_or_else(|| {
+                                        panic!(
+                                            "Unknown cost function in `node.cost_overrides`: {}",
+                                            name
+                                        )
+                                    });
+                                (cost_function, cost)
+                            })
+                            .collect();
+                        Arc::new(overrides)
+                    }),
+                    chainstate_manifest_interval: node
+                        .chainstate_manifest_interval
+                        .unwrap_or(default_node_config.chainstate_manifest_interval),
+                    address_tx_index: node
+                        .address_tx_index
+                        .unwrap_or(default_node_config.address_tx_index),
+                    address_asset_index: node
+                        .address_asset_index
+                        .unwrap_or(default_node_config.address_asset_index),
+                    rosetta: node.rosetta.unwrap_or(default_node_config.rosetta),
+                    boot_contract_overrides: node
+                        .boot_contracts
+                        .map(|contracts| {
+                            contracts
+                                .into_iter()
+                                .map(|contract| {
+                                    let code =
+                                        fs::read_to_string(&contract.path).unwrap_or_else(|e| {
+                                            panic!(
+                                                "Failed to read boot contract `{}` from {}: {}",
+                                                contract.name, contract.path, e
+                                            )
+                                        });
+                                    (contract.name, code)
+                                })
+                                .collect()
+                        })
+                        .unwrap_or(default_node_config.boot_contract_overrides),
                 };
                 (node_config, node.bootstrap_node, node.deny_nodes)
             }
@@ -406,6 +740,12 @@ impl Config {
                     if node.use_test_genesis_chainstate == Some(true) {
                         panic!("Attempted to run mainnet node with `use_test_genesis_chainstate`");
                     }
+                    if node.cost_overrides.is_some() {
+                        panic!("Attempted to run mainnet node with `cost_overrides`");
+                    }
+                    if !node.boot_contract_overrides.is_empty() {
+                        panic!("Attempted to run mainnet node with `boot_contracts`");
+                    }
                     if let Some(ref balances) = config_file.ustx_balance {
                         if balances.len() > 0 {
                             panic!(
@@ -415,6 +755,14 @@ impl Config {
                     }
                 }
 
+                // backup bitcoinds use the same ports as peer_host unless they say otherwise
+                let peer_port = burnchain
+                    .peer_port
+                    .unwrap_or(default_burnchain_config.peer_port);
+                let rpc_port = burnchain
+                    .rpc_port
+                    .unwrap_or(default_burnchain_config.rpc_port);
+
                 BurnchainConfig {
                     chain: burnchain.chain.unwrap_or(default_burnchain_config.chain),
                     chain_id: if &burnchain_mode == "mainnet" {
@@ -446,15 +794,14 @@ impl Config {
                         }
                         None => default_burnchain_config.peer_host,
                     },
-                    peer_port: burnchain
-                        .peer_port
-                        .unwrap_or(default_burnchain_config.peer_port),
-                    rpc_port: burnchain
-                        .rpc_port
-                        .unwrap_or(default_burnchain_config.rpc_port),
+                    peer_port,
+                    rpc_port,
                     rpc_ssl: burnchain
                         .rpc_ssl
                         .unwrap_or(default_burnchain_config.rpc_ssl),
+                    rpc_use_proxy: burnchain
+                        .rpc_use_proxy
+                        .unwrap_or(default_burnchain_config.rpc_use_proxy),
                     username: burnchain.username,
                     password: burnchain.password,
                     timeout: burnchain
@@ -492,6 +839,39 @@ impl Config {
                         Some(epochs) => Some(epochs),
                         None => default_burnchain_config.epochs,
                     },
+                    backup_endpoints: burnchain
+                        .backup_endpoints
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|endpoint| BitcoinEndpoint {
+                            host: endpoint.peer_host,
+                            peer_port: endpoint.peer_port.unwrap_or(peer_port),
+                            rpc_port: endpoint.rpc_port.unwrap_or(rpc_port),
+                        })
+                        .collect(),
+                    wait_for_new_blocks: burnchain
+                        .wait_for_new_blocks
+                        .unwrap_or(default_burnchain_config.wait_for_new_blocks),
+                    descriptor_wallet: burnchain
+                        .descriptor_wallet
+                        .unwrap_or(default_burnchain_config.descriptor_wallet),
+                    external_signer_public_key: burnchain.external_signer_public_key,
+                    segwit: burnchain.segwit.unwrap_or(default_burnchain_config.segwit),
+                    auto_rbf: burnchain
+                        .auto_rbf
+                        .unwrap_or(default_burnchain_config.auto_rbf),
+                    fee_estimation: burnchain
+                        .fee_estimation
+                        .unwrap_or(default_burnchain_config.fee_estimation),
+                    min_fee_rate: burnchain
+                        .min_fee_rate
+                        .unwrap_or(default_burnchain_config.min_fee_rate),
+                    max_fee_rate: burnchain
+                        .max_fee_rate
+                        .unwrap_or(default_burnchain_config.max_fee_rate),
+                    utxo_consolidation_fee_rate: burnchain
+                        .utxo_consolidation_fee_rate
+                        .unwrap_or(default_burnchain_config.utxo_consolidation_fee_rate),
                 }
             }
             None => default_burnchain_config,
@@ -513,6 +893,7 @@ impl Config {
                 probability_pick_no_estimate_tx: miner
                     .probability_pick_no_estimate_tx
                     .unwrap_or(miner_default_config.probability_pick_no_estimate_tx),
+                assembly_replay_dir: miner.assembly_replay_dir.clone(),
             },
             None => miner_default_config,
         };
@@ -528,6 +909,9 @@ impl Config {
             )
         }
 
+        // fail fast on a malformed external signer key
+        burnchain.get_external_signer_public_key();
+
         if burnchain.mode == "helium" && burnchain.local_mining_public_key.is_none() {
             panic!("Config is missing the setting `burnchain.local_mining_public_key` (mandatory for helium)")
         }
@@ -547,6 +931,12 @@ impl Config {
         if let Some(deny_nodes) = deny_nodes {
             node.set_deny_nodes(deny_nodes, burnchain.chain_id, burnchain.peer_version);
         }
+        if let Err(e) = validate_boot_contract_overrides(
+            &node.boot_contract_overrides,
+            burnchain.mode == "mainnet",
+        ) {
+            panic!("Invalid `node.boot_contracts`: {}", e);
+        }
 
         let initial_balances: Vec<InitialBalance> = match config_file.ustx_balance {
             Some(balances) => balances
@@ -606,6 +996,72 @@ impl Config {
                     }
                     None => None,
                 };
+                let alt_ip_addr = opts.alt_public_ip_address.map(|alt_public_ip_address| {
+                    let addr = alt_public_ip_address
+                        .parse::<SocketAddr>()
+                        .unwrap_or_else(|_| {
+                            panic!("Invalid alt_public_ip_address '{}'", &alt_public_ip_address)
+                        });
+                    (PeerAddress::from_socketaddr(&addr), addr.port())
+                });
+                let address_family = opts
+                    .address_family
+                    .map(|address_family| {
+                        address_family
+                            .parse::<AddressFamilyPreference>()
+                            .unwrap_or_else(|e| panic!("Invalid address_family: {}", e))
+                    })
+                    .unwrap_or(HELIUM_DEFAULT_CONNECTION_OPTIONS.address_family);
+                let parse_cidrs = |cidrs: &Option<Vec<String>>| -> Vec<(PeerAddress, u32)> {
+                    cidrs
+                        .iter()
+                        .flatten()
+                        .map(|cidr| {
+                            PeerAddress::from_cidr_str(cidr)
+                                .unwrap_or_else(|| panic!("Invalid CIDR range '{}'", cidr))
+                        })
+                        .collect()
+                };
+                let parse_asns = |asns: &Option<Vec<u32>>| -> Vec<u32> {
+                    let asns = asns.clone().unwrap_or_default();
+                    if asns.contains(&0) {
+                        panic!("Invalid AS number 0");
+                    }
+                    asns
+                };
+                let allow_cidrs = parse_cidrs(&opts.allow_cidrs);
+                let deny_cidrs = parse_cidrs(&opts.deny_cidrs);
+                let allow_asns = parse_asns(&opts.allow_asns);
+                let deny_asns = parse_asns(&opts.deny_asns);
+                let dns_seeds = opts.dns_seeds.clone().unwrap_or_default();
+                for seed in dns_seeds.iter() {
+                    if parse_dns_seed(seed, 0).is_none() {
+                        panic!("Invalid DNS seed '{}': expected HOST[:PORT]", seed);
+                    }
+                }
+                let port_mapping = opts
+                    .port_mapping
+                    .map(|port_mapping| {
+                        port_mapping
+                            .parse::<PortMappingMethod>()
+                            .unwrap_or_else(|e| panic!("Invalid port_mapping: {}", e))
+                    })
+                    .unwrap_or(HELIUM_DEFAULT_CONNECTION_OPTIONS.port_mapping);
+                let port_mapping_gateway = opts.port_mapping_gateway.map(|gateway| {
+                    gateway
+                        .parse::<Ipv4Addr>()
+                        .unwrap_or_else(|_| panic!("Invalid port_mapping_gateway '{}'", &gateway))
+                });
+                let socks5_proxy = opts.socks5_proxy.map(|socks5_proxy| {
+                    socks5_proxy
+                        .parse::<SocketAddr>()
+                        .unwrap_or_else(|_| panic!("Invalid socks5_proxy '{}'", &socks5_proxy))
+                });
+                let socks5_credentials = match (opts.socks5_username, opts.socks5_password) {
+                    (Some(username), Some(password)) => Some((username, password)),
+                    (None, None) => None,
+                    _ => panic!("socks5_username and socks5_password must be set together"),
+                };
                 let mut read_only_call_limit = HELIUM_DEFAULT_CONNECTION_OPTIONS
                     .read_only_call_limit
                     .clone();
@@ -626,6 +1082,12 @@ impl Config {
                 });
                 ConnectionOptions {
                     read_only_call_limit,
+                    read_only_call_max_steps: opts.read_only_call_max_steps.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.read_only_call_max_steps
+                    }),
+                    read_only_call_timeout_ms: opts.read_only_call_timeout_ms.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.read_only_call_timeout_ms,
+                    ),
                     inbox_maxlen: opts
                         .inbox_maxlen
                         .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.inbox_maxlen.clone()),
@@ -658,6 +1120,28 @@ impl Config {
                     soft_num_clients: opts.soft_num_clients.unwrap_or_else(|| {
                         HELIUM_DEFAULT_CONNECTION_OPTIONS.soft_num_clients.clone()
                     }),
+                    max_inbound_miners: opts.max_inbound_miners.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_inbound_miners.clone()
+                    }),
+                    max_inbound_followers: opts.max_inbound_followers.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_inbound_followers.clone()
+                    }),
+                    max_inbound_unknown: opts.max_inbound_unknown.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_inbound_unknown.clone()
+                    }),
+                    reserved_outbound_slots: opts.reserved_outbound_slots.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .reserved_outbound_slots
+                            .clone()
+                    }),
+                    inbound_handshake_rate_limit: rpc_rate_limit(
+                        opts.inbound_handshake_rate_limit,
+                        opts.inbound_handshake_rate_burst,
+                    )
+                    .or(HELIUM_DEFAULT_CONNECTION_OPTIONS.inbound_handshake_rate_limit),
+                    partial_message_timeout: opts.partial_message_timeout.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.partial_message_timeout.clone()
+                    }),
                     max_neighbors_per_host: opts.max_neighbors_per_host.unwrap_or_else(|| {
                         HELIUM_DEFAULT_CONNECTION_OPTIONS
                             .max_neighbors_per_host
@@ -705,6 +1189,34 @@ impl Config {
                             .max_inflight_attachments
                             .clone()
                     }),
+                    max_inflight_blocks_per_peer: opts.max_inflight_blocks_per_peer.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .max_inflight_blocks_per_peer
+                            .clone()
+                    }),
+                    block_download_steal_timeout: opts.block_download_steal_timeout.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .block_download_steal_timeout
+                            .clone()
+                    }),
+                    download_retry_base_delay: opts.download_retry_base_delay.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.download_retry_base_delay.clone()
+                    }),
+                    download_retry_max_delay: opts.download_retry_max_delay.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.download_retry_max_delay.clone()
+                    }),
+                    download_retry_jitter: opts.download_retry_jitter.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.download_retry_jitter.clone()
+                    }),
+                    attachment_retry_base_delay: opts.attachment_retry_base_delay.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.attachment_retry_base_delay.clone()
+                    }),
+                    attachment_retry_max_delay: opts.attachment_retry_max_delay.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.attachment_retry_max_delay.clone()
+                    }),
+                    attachment_retry_jitter: opts.attachment_retry_jitter.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.attachment_retry_jitter.clone()
+                    }),
                     maximum_call_argument_size: opts.maximum_call_argument_size.unwrap_or_else(
                         || {
                             HELIUM_DEFAULT_CONNECTION_OPTIONS
@@ -729,17 +1241,244 @@ impl Config {
                         }
                     }),
                     public_ip_address: ip_addr,
+                    alt_public_ip_address: alt_ip_addr,
+                    address_family,
+                    allow_cidrs,
+                    deny_cidrs,
+                    allow_asns,
+                    deny_asns,
                     disable_inbound_walks: opts.disable_inbound_walks.unwrap_or(false),
                     disable_inbound_handshakes: opts.disable_inbound_handshakes.unwrap_or(false),
-                    disable_block_download: opts.disable_block_download.unwrap_or(false),
+                    disable_block_download: opts
+                        .disable_block_download
+                        .unwrap_or(node.replicate_from.is_some()),
                     force_disconnect_interval: opts.force_disconnect_interval,
                     max_http_clients: opts.max_http_clients.unwrap_or_else(|| {
                         HELIUM_DEFAULT_CONNECTION_OPTIONS.max_http_clients.clone()
                     }),
+                    max_http_pipelined_requests: opts.max_http_pipelined_requests.unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .max_http_pipelined_requests
+                                .clone()
+                        },
+                    ),
+                    max_websocket_sessions: opts.max_websocket_sessions.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .max_websocket_sessions
+                            .clone()
+                    }),
+                    websocket_ping_interval: opts.websocket_ping_interval.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .websocket_ping_interval
+                            .clone()
+                    }),
+                    http_response_cache_size: opts.http_response_cache_size.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .http_response_cache_size
+                            .clone()
+                    }),
+                    max_recent_reorgs: opts.max_recent_reorgs.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_recent_reorgs.clone()
+                    }),
+                    stale_tip_burn_blocks: opts
+                        .stale_tip_burn_blocks
+                        .unwrap_or(STALE_TIP_BURN_BLOCKS),
+                    rpc_cors_allowed_origins: opts.rpc_cors_allowed_origins.clone().unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .rpc_cors_allowed_origins
+                                .clone()
+                        },
+                    ),
+                    rpc_cors_allowed_methods: opts.rpc_cors_allowed_methods.clone().unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .rpc_cors_allowed_methods
+                                .clone()
+                        },
+                    ),
+                    rpc_cors_allowed_headers: opts.rpc_cors_allowed_headers.clone().unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .rpc_cors_allowed_headers
+                                .clone()
+                        },
+                    ),
+                    rpc_cors_max_age: opts.rpc_cors_max_age,
+                    rpc_tls_cert_file: opts.rpc_tls_cert_file.clone(),
+                    rpc_tls_key_file: opts.rpc_tls_key_file.clone(),
+                    rpc_tls_client_ca_file: opts.rpc_tls_client_ca_file.clone(),
+                    rpc_tls_require_client_cert: opts.rpc_tls_require_client_cert.unwrap_or(false),
+                    rpc_unix_socket_mode: opts
+                        .rpc_unix_socket_mode
+                        .as_ref()
+                        .map(|mode| {
+                            u32::from_str_radix(mode, 8).unwrap_or_else(|_| {
+                                panic!(
+                                    "rpc_unix_socket_mode must be octal permission bits, like \"660\"; got {}",
+                                    mode
+                                )
+                            })
+                        })
+                        .unwrap_or(0o600),
+                    rpc_api_keys: opts
+                        .rpc_api_keys
+                        .iter()
+                        .flatten()
+                        .map(|api_key| {
+                            (
+                                api_key.key.clone(),
+                                rpc_rate_limit(api_key.rate_limit, api_key.rate_burst),
+                            )
+                        })
+                        .collect(),
+                    rpc_require_api_key: opts.rpc_require_api_key.unwrap_or(false),
+                    rpc_ip_rate_limit: rpc_rate_limit(
+                        opts.rpc_ip_rate_limit,
+                        opts.rpc_ip_rate_burst,
+                    ),
+                    rpc_admin_api_key: opts.rpc_admin_api_key.clone(),
+                    health_max_burnchain_lag: opts.health_max_burnchain_lag.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .health_max_burnchain_lag
+                            .clone()
+                    }),
+                    health_max_tip_age: opts.health_max_tip_age.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.health_max_tip_age.clone()
+                    }),
+                    health_min_peers: opts.health_min_peers.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.health_min_peers.clone()
+                    }),
+                    reputation_half_life: opts.reputation_half_life.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_half_life.clone()
+                    }),
+                    reputation_ban_threshold: opts.reputation_ban_threshold.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_ban_threshold.clone()
+                    }),
+                    reputation_deprioritize_threshold: opts.reputation_deprioritize_threshold.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_deprioritize_threshold.clone()
+                    }),
+                    reputation_penalty_invalid_message: opts.reputation_penalty_invalid_message.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_penalty_invalid_message.clone()
+                    }),
+                    reputation_penalty_stale_inventory: opts.reputation_penalty_stale_inventory.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_penalty_stale_inventory.clone()
+                    }),
+                    reputation_penalty_slow_response: opts.reputation_penalty_slow_response.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_penalty_slow_response.clone()
+                    }),
+                    reputation_penalty_unsolicited_spam: opts.reputation_penalty_unsolicited_spam.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reputation_penalty_unsolicited_spam.clone()
+                    }),
+                    p2p_encryption: opts.p2p_encryption.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.p2p_encryption.clone()
+                    }),
+                    p2p_require_encryption: opts.p2p_require_encryption.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.p2p_require_encryption.clone()
+                    }),
+                    compact_blocks: opts.compact_blocks.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.compact_blocks.clone()
+                    }),
+                    p2p_compression: opts.p2p_compression.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.p2p_compression.clone()
+                    }),
+                    p2p_compression_min_size: opts.p2p_compression_min_size.unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .p2p_compression_min_size
+                                .clone()
+                        },
+                    ),
+                    capability_exchange: opts.capability_exchange.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.capability_exchange.clone()
+                    }),
+                    compact_block_timeout: opts.compact_block_timeout.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.compact_block_timeout.clone()
+                    }),
+                    tx_relay_filter_items: opts.tx_relay_filter_items.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.tx_relay_filter_items.clone()
+                    }),
+                    mempool_sync_bootstrap_peers: opts.mempool_sync_bootstrap_peers.unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .mempool_sync_bootstrap_peers
+                                .clone()
+                        },
+                    ),
+                    mempool_pull_interval: opts.mempool_pull_interval.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.mempool_pull_interval.clone()
+                    }),
+                    mempool_txs_min_interval: opts.mempool_txs_min_interval.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.mempool_txs_min_interval.clone()
+                    }),
+                    microblock_relay_canonical_budget: opts
+                        .microblock_relay_canonical_budget
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .microblock_relay_canonical_budget
+                                .clone()
+                        }),
+                    microblock_relay_stale_budget: opts
+                        .microblock_relay_stale_budget
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .microblock_relay_stale_budget
+                                .clone()
+                        }),
+                    microblock_relay_budget_interval: opts
+                        .microblock_relay_budget_interval
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .microblock_relay_budget_interval
+                                .clone()
+                        }),
+                    warm_start_peers: opts.warm_start_peers.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.warm_start_peers.clone()
+                    }),
+                    peer_score_save_interval: opts.peer_score_save_interval.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .peer_score_save_interval
+                            .clone()
+                    }),
+                    peer_score_max_age: opts.peer_score_max_age.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.peer_score_max_age.clone()
+                    }),
+                    socks5_proxy,
+                    socks5_credentials,
+                    peer_egress_limit: rpc_rate_limit(
+                        opts.max_peer_egress_rate,
+                        opts.max_peer_egress_burst,
+                    ),
+                    global_egress_limit: rpc_rate_limit(
+                        opts.max_egress_rate,
+                        opts.max_egress_burst,
+                    ),
+                    dns_seeds,
+                    dns_seed_services: opts.dns_seed_services.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.dns_seed_services.clone()
+                    }),
+                    dns_seed_interval: opts.dns_seed_interval.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.dns_seed_interval.clone()
+                    }),
+                    port_mapping,
+                    port_mapping_lease: opts.port_mapping_lease.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.port_mapping_lease.clone()
+                    }),
+                    port_mapping_gateway,
                     connect_timeout: opts.connect_timeout.unwrap_or(10),
-                    handshake_timeout: opts.connect_timeout.unwrap_or(5),
+                    handshake_timeout: opts.handshake_timeout.unwrap_or(5),
                     max_sockets: opts.max_sockets.unwrap_or(800) as usize,
+                    max_open_files: opts.max_open_files.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_open_files.clone()
+                    }),
+                    open_files_reserve: opts.open_files_reserve.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.open_files_reserve.clone()
+                    }),
                     antientropy_public: opts.antientropy_public.unwrap_or(true),
+                    antientropy_offers: opts.antientropy_offers.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.antientropy_offers.clone()
+                    }),
                     ..ConnectionOptions::default()
                 }
             }
@@ -889,6 +1628,11 @@ impl Config {
                 },
                 consider_no_estimate_tx_prob: self.miner.probability_pick_no_estimate_tx,
             },
+            replay_dir: if microblocks {
+                None
+            } else {
+                self.miner.assembly_replay_dir.clone()
+            },
         }
     }
 }
@@ -931,6 +1675,8 @@ pub struct BurnchainConfig {
     pub peer_port: u16,
     pub rpc_port: u16,
     pub rpc_ssl: bool,
+    /// send bitcoind RPC requests through `connection_options.socks5_proxy`
+    pub rpc_use_proxy: bool,
     pub username: Option<String>,
     pub password: Option<String>,
     pub timeout: u32,
@@ -946,6 +1692,37 @@ pub struct BurnchainConfig {
     /// Custom override for the definitions of the epochs. This will only be applied for testnet and
     /// regtest nodes.
     pub epochs: Option<Vec<StacksEpoch>>,
+    /// bitcoinds to fail over to if `peer_host` goes down, in order of preference
+    pub backup_endpoints: Vec<BitcoinEndpoint>,
+    /// long-poll bitcoind's `waitfornewblock` RPC, and sync the burnchain as soon as it reports
+    /// a new block instead of waiting out `poll_time_secs`
+    pub wait_for_new_blocks: bool,
+    /// create bitcoind's wallet as a descriptor wallet, and watch the miner's key with
+    /// `importdescriptors` instead of `importaddress`.  Implied by `external_signer_public_key`.
+    pub descriptor_wallet: bool,
+    /// hex public key of a miner BTC key held by bitcoind's wallet (e.g. on a hardware signer
+    /// attached with `-signer`) instead of derived from `node.seed`.  Burnchain transactions are
+    /// handed to the wallet to sign as PSBTs.
+    pub external_signer_public_key: Option<String>,
+    /// once Stacks 2.1 begins, fund burnchain transactions from, and send their change to, the
+    /// miner key's native p2wpkh address as well as its p2pkh address
+    pub segwit: bool,
+    /// while waiting to mine, replace our in-flight block-commit at a higher fee rate whenever
+    /// bitcoind's `estimatesmartfee` says it no longer pays enough to make the next block.
+    /// Bounded by `max_rbf` (or `max_fee_rate`, with `fee_estimation`).
+    pub auto_rbf: bool,
+    /// pay block-commits the fee rate bitcoind's `estimatesmartfee` says will confirm them in
+    /// the next block, clamped to `min_fee_rate`..`max_fee_rate`, instead of `satoshis_per_byte`.
+    /// `satoshis_per_byte` is still used if bitcoind has no estimate.
+    pub fee_estimation: bool,
+    /// lowest estimated fee rate a block-commit will pay, in sats/vbyte
+    pub min_fee_rate: u64,
+    /// highest fee rate a block-commit will pay with `fee_estimation`, in sats/vbyte, including
+    /// replacements
+    pub max_fee_rate: u64,
+    /// when sending a transaction at or below this fee rate (sats/vbyte), also spend up to 10 of
+    /// the miner's small UTXOs into its change output, while it's cheap to.  0 disables this.
+    pub utxo_consolidation_fee_rate: u64,
 }
 
 impl BurnchainConfig {
@@ -961,6 +1738,7 @@ impl BurnchainConfig {
             peer_port: 8333,
             rpc_port: 8332,
             rpc_ssl: false,
+            rpc_use_proxy: false,
             username: None,
             password: None,
             timeout: 300,
@@ -974,23 +1752,75 @@ impl BurnchainConfig {
             block_commit_tx_estimated_size: BLOCK_COMMIT_TX_ESTIM_SIZE,
             rbf_fee_increment: DEFAULT_RBF_FEE_RATE_INCREMENT,
             epochs: None,
+            backup_endpoints: vec![],
+            wait_for_new_blocks: false,
+            descriptor_wallet: false,
+            external_signer_public_key: None,
+            segwit: false,
+            auto_rbf: false,
+            fee_estimation: false,
+            min_fee_rate: DEFAULT_MIN_FEE_RATE,
+            max_fee_rate: DEFAULT_MAX_FEE_RATE,
+            utxo_consolidation_fee_rate: 0,
         }
     }
 
-    pub fn get_rpc_url(&self) -> String {
+    /// Clamp a fee rate estimate to `min_fee_rate`..`max_fee_rate`
+    pub fn clamp_fee_rate(&self, fee_rate: u64) -> u64 {
+        cmp::min(cmp::max(fee_rate, self.min_fee_rate), self.max_fee_rate)
+    }
+
+    /// The highest fee rate a block-commit may pay, replacements included
+    pub fn get_max_block_commit_fee_rate(&self) -> u64 {
+        if self.fee_estimation {
+            self.max_fee_rate
+        } else {
+            self.satoshis_per_byte * self.max_rbf / 100
+        }
+    }
+
+    /// Is bitcoind's wallet a descriptor wallet?  External signer wallets always are.
+    pub fn is_descriptor_wallet(&self) -> bool {
+        self.descriptor_wallet || self.external_signer_public_key.is_some()
+    }
+
+    /// The miner's BTC key, if it is held by bitcoind's wallet instead of derived from our seed
+    pub fn get_external_signer_public_key(&self) -> Option<Secp256k1PublicKey> {
+        self.external_signer_public_key.as_ref().map(|public_key| {
+            Secp256k1PublicKey::from_hex(public_key)
+                .expect("Config field burnchain.external_signer_public_key is not a public key")
+        })
+    }
+
+    /// Our bitcoinds, in order of preference: `peer_host`, then its backups
+    pub fn get_endpoints(&self) -> Vec<BitcoinEndpoint> {
+        let mut endpoints = vec![BitcoinEndpoint {
+            host: self.peer_host.clone(),
+            peer_port: self.peer_port,
+            rpc_port: self.rpc_port,
+        }];
+        endpoints.extend(self.backup_endpoints.iter().cloned());
+        endpoints
+    }
+
+    pub fn get_rpc_url(&self, endpoint: &BitcoinEndpoint) -> String {
         let scheme = match self.rpc_ssl {
             true => "https://",
             false => "http://",
         };
-        format!("{}{}:{}", scheme, self.peer_host, self.rpc_port)
+        format!("{}{}:{}", scheme, endpoint.host, endpoint.rpc_port)
     }
 
-    pub fn get_rpc_socket_addr(&self) -> SocketAddr {
-        let mut addrs_iter = format!("{}:{}", self.peer_host, self.rpc_port)
-            .to_socket_addrs()
-            .unwrap();
-        let sock_addr = addrs_iter.next().unwrap();
-        sock_addr
+    pub fn get_rpc_socket_addr(&self, endpoint: &BitcoinEndpoint) -> std::io::Result<SocketAddr> {
+        format!("{}:{}", endpoint.host, endpoint.rpc_port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Could not resolve {}", &endpoint.host),
+                )
+            })
     }
 
     pub fn get_bitcoin_network(&self) -> (String, BitcoinNetworkType) {
@@ -1015,6 +1845,7 @@ pub struct BurnchainConfigFile {
     pub peer_port: Option<u16>,
     pub rpc_port: Option<u16>,
     pub rpc_ssl: Option<bool>,
+    pub rpc_use_proxy: Option<bool>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub timeout: Option<u32>,
@@ -1028,6 +1859,24 @@ pub struct BurnchainConfigFile {
     pub rbf_fee_increment: Option<u64>,
     pub max_rbf: Option<u64>,
     pub epochs: Option<Vec<StacksEpoch>>,
+    pub backup_endpoints: Option<Vec<BitcoinEndpointConfigFile>>,
+    pub wait_for_new_blocks: Option<bool>,
+    pub descriptor_wallet: Option<bool>,
+    pub external_signer_public_key: Option<String>,
+    pub segwit: Option<bool>,
+    pub auto_rbf: Option<bool>,
+    pub fee_estimation: Option<bool>,
+    pub min_fee_rate: Option<u64>,
+    pub max_fee_rate: Option<u64>,
+    pub utxo_consolidation_fee_rate: Option<u64>,
+}
+
+/// A bitcoind to fail over to.  Its ports default to `peer_host`'s.
+#[derive(Clone, Deserialize, Default)]
+pub struct BitcoinEndpointConfigFile {
+    pub peer_host: String,
+    pub peer_port: Option<u16>,
+    pub rpc_port: Option<u16>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1041,6 +1890,8 @@ pub struct NodeConfig {
     pub p2p_address: String,
     pub local_peer_seed: Vec<u8>,
     pub bootstrap_node: Vec<Neighbor>,
+    /// hostnames of the `.onion` bootstrap nodes, which are only reachable through a proxy
+    pub onion_hosts: Vec<String>,
     pub deny_nodes: Vec<Neighbor>,
     pub miner: bool,
     pub mock_mining: bool,
@@ -1049,8 +1900,53 @@ pub struct NodeConfig {
     pub max_microblocks: u64,
     pub wait_time_for_microblocks: u64,
     pub prometheus_bind: Option<String>,
+    /// address to serve the gRPC interface on (requires the `grpc` feature)
+    pub grpc_bind: Option<String>,
+    /// statsd agent (`host:port`) to send metrics to over UDP
+    pub statsd_endpoint: Option<String>,
+    /// prefix for all metric names sent to statsd
+    pub statsd_prefix: Option<String>,
+    /// OpenTelemetry collector URL to push OTLP/HTTP JSON metrics to (requires the
+    /// `monitoring_otlp` feature)
+    pub otlp_endpoint: Option<String>,
+    /// how often, in seconds, to push metrics to `otlp_endpoint`
+    pub otlp_interval: u64,
     pub pox_sync_sample_secs: u64,
     pub use_test_genesis_chainstate: Option<bool>,
+    pub replicate_from: Option<String>,
+    pub telemetry_url: Option<String>,
+    pub telemetry_interval: u64,
+    /// URL of the object store to move buried Clarity state tries to (`file:///path` or
+    /// `s3+https://host:port/bucket/prefix?region=...&ca_file=...`).  Tries stay local if unset.
+    pub marf_blob_store: Option<String>,
+    /// Directory for the local cache of tries fetched from `marf_blob_store`
+    pub marf_blob_cache_dir: Option<String>,
+    pub marf_blob_cache_size: u64,
+    /// Number of most recent tries which are never offloaded
+    pub marf_blob_keep_local: u32,
+    pub marf_offload_interval: u64,
+    /// Constant costs to charge in place of the boot cost contracts' cost functions (private
+    /// chains only).  Every node on the chain must use the same overrides.
+    pub cost_overrides: Option<Arc<CostOverrides>>,
+    /// Number of Stacks blocks between the chainstate checkpoints which are logged as they are
+    /// reached.  0 disables logging.
+    pub chainstate_manifest_interval: u64,
+    /// Whether to index the transactions involving each principal as blocks are processed, to
+    /// serve `/v2/addresses/{principal}/transactions`.  Only blocks processed while this is set
+    /// are indexed.
+    pub address_tx_index: bool,
+    /// Whether to track the fungible and non-fungible tokens each principal holds as blocks are
+    /// processed, to serve `/v2/addresses/{principal}/assets`.  Holdings are only complete if
+    /// this was set while the node processed the whole chain.
+    pub address_asset_index: bool,
+    /// Whether to index each transaction's STX balance changes as blocks are processed, and serve
+    /// the Rosetta Data and Construction APIs under `/rosetta/v1`.  Only blocks processed while
+    /// this is set are indexed.
+    pub rosetta: bool,
+    /// (name, code) of boot contracts to install at genesis in place of, or in addition to, the
+    /// stock boot contracts (private chains only).  Every node on the chain must use the same
+    /// boot contracts.
+    pub boot_contract_overrides: Vec<(String, String)>,
 }
 
 #[derive(Clone, Debug)]
@@ -1062,6 +1958,9 @@ pub enum CostEstimatorName {
 pub enum FeeEstimatorName {
     ScalarFeeRate,
     FuzzedWeightedMedianFeeRate,
+    WeightedMedianFeeRate,
+    RegressionFeeRate,
+    PercentileFeeRate,
 }
 
 #[derive(Clone, Debug)]
@@ -1106,6 +2005,12 @@ impl FeeEstimatorName {
             FeeEstimatorName::ScalarFeeRate
         } else if &s.to_lowercase() == "fuzzed_weighted_median_fee_rate" {
             FeeEstimatorName::FuzzedWeightedMedianFeeRate
+        } else if &s.to_lowercase() == "weighted_median_fee_rate" {
+            FeeEstimatorName::WeightedMedianFeeRate
+        } else if &s.to_lowercase() == "regression_fee_rate" {
+            FeeEstimatorName::RegressionFeeRate
+        } else if &s.to_lowercase() == "percentile_fee_rate" {
+            FeeEstimatorName::PercentileFeeRate
         } else {
             panic!(
                 "Bad fee estimator name supplied in configuration file: {}",
@@ -1134,9 +2039,12 @@ pub struct FeeEstimationConfig {
     /// If using FeeRateFuzzer, the amount of random noise, as a percentage of the base value (in
     /// [0, 1]) to add for fuzz. See comments on FeeRateFuzzer.
     pub fee_rate_fuzzer_fraction: f64,
-    /// If using WeightedMedianFeeRateEstimator, the window size to use. See comments on
-    /// WeightedMedianFeeRateEstimator.
+    /// If using WeightedMedianFeeRateEstimator, RegressionFeeRateEstimator or
+    /// PercentileFeeRateEstimator, the window size to use. See comments on those estimators.
     pub fee_rate_window_size: u64,
+    /// If true, record each confirmed transaction's cost, fee and confirmation delay for
+    /// offline evaluation of fee estimators. See comments on FeeObservationRecorder.
+    pub record_observations: bool,
 }
 
 impl Default for FeeEstimationConfig {
@@ -1148,6 +2056,7 @@ impl Default for FeeEstimationConfig {
             log_error: false,
             fee_rate_fuzzer_fraction: 0.1f64,
             fee_rate_window_size: 5u64,
+            record_observations: false,
         }
     }
 }
@@ -1162,6 +2071,7 @@ impl From<FeeEstimationConfigFile> for FeeEstimationConfig {
                 log_error: false,
                 fee_rate_fuzzer_fraction: 0f64,
                 fee_rate_window_size: 0u64,
+                record_observations: false,
             };
         }
         let cost_estimator = f
@@ -1184,6 +2094,7 @@ impl From<FeeEstimationConfigFile> for FeeEstimationConfig {
             log_error,
             fee_rate_fuzzer_fraction: f.fee_rate_fuzzer_fraction.unwrap_or(0.1f64),
             fee_rate_window_size: f.fee_rate_window_size.unwrap_or(5u64),
+            record_observations: f.record_observations.unwrap_or(false),
         }
     }
 }
@@ -1220,8 +2131,34 @@ impl Config {
             FeeEstimatorName::FuzzedWeightedMedianFeeRate => self
                 .estimation
                 .make_fuzzed_weighted_median_fee_estimator(self.get_estimates_path(), metric),
+            FeeEstimatorName::WeightedMedianFeeRate => self
+                .estimation
+                .make_weighted_median_fee_estimator(self.get_estimates_path(), metric),
+            FeeEstimatorName::RegressionFeeRate => self
+                .estimation
+                .make_regression_fee_estimator(self.get_estimates_path(), metric),
+            FeeEstimatorName::PercentileFeeRate => self
+                .estimation
+                .make_percentile_fee_estimator(self.get_estimates_path(), metric),
         };
 
+        if self.estimation.record_observations {
+            let mut observations_path = self.get_estimates_path();
+            observations_path.push("fee_observations.sqlite");
+            let mempool_path = PathBuf::from(
+                MemPoolDB::db_path(&self.get_chainstate_path_str())
+                    .expect("Unable to produce mempool path"),
+            );
+            let recorder = FeeObservationRecorder::open(
+                &observations_path,
+                fee_estimator,
+                self.make_cost_metric()?,
+                Some(mempool_path),
+            )
+            .expect("Error opening fee observation recorder");
+            return Some(Box::new(recorder));
+        }
+
         Some(fee_estimator)
     }
 }
@@ -1281,9 +2218,98 @@ impl FeeEstimationConfig {
             panic!("BUG: Expected to configure a weighted median fee estimator");
         }
     }
+
+    pub fn make_weighted_median_fee_estimator<CM: 'static + CostMetric>(
+        &self,
+        mut estimates_path: PathBuf,
+        metric: CM,
+    ) -> Box<dyn FeeEstimator> {
+        if let Some(FeeEstimatorName::WeightedMedianFeeRate) = self.fee_estimator.as_ref() {
+            estimates_path.push("fee_weighted_median.sqlite");
+            Box::new(
+                WeightedMedianFeeRateEstimator::open(
+                    &estimates_path,
+                    metric,
+                    self.fee_rate_window_size
+                        .try_into()
+                        .expect("Configured fee rate window size out of bounds."),
+                )
+                .expect("Error opening fee estimator"),
+            )
+        } else {
+            panic!("BUG: Expected to configure a weighted median fee estimator");
+        }
+    }
+
+    pub fn make_regression_fee_estimator<CM: 'static + CostMetric>(
+        &self,
+        mut estimates_path: PathBuf,
+        metric: CM,
+    ) -> Box<dyn FeeEstimator> {
+        if let Some(FeeEstimatorName::RegressionFeeRate) = self.fee_estimator.as_ref() {
+            estimates_path.push("fee_estimator_regression.sqlite");
+            Box::new(
+                RegressionFeeRateEstimator::open(
+                    &estimates_path,
+                    metric,
+                    self.fee_rate_window_size
+                        .try_into()
+                        .expect("Configured fee rate window size out of bounds."),
+                )
+                .expect("Error opening fee estimator"),
+            )
+        } else {
+            panic!("BUG: Expected to configure a regression fee estimator");
+        }
+    }
+
+    pub fn make_percentile_fee_estimator<CM: 'static + CostMetric>(
+        &self,
+        mut estimates_path: PathBuf,
+        metric: CM,
+    ) -> Box<dyn FeeEstimator> {
+        if let Some(FeeEstimatorName::PercentileFeeRate) = self.fee_estimator.as_ref() {
+            estimates_path.push("fee_estimator_percentile.sqlite");
+            Box::new(
+                PercentileFeeRateEstimator::open(
+                    &estimates_path,
+                    metric,
+                    self.fee_rate_window_size
+                        .try_into()
+                        .expect("Configured fee rate window size out of bounds."),
+                )
+                .expect("Error opening fee estimator"),
+            )
+        } else {
+            panic!("BUG: Expected to configure a percentile fee estimator");
+        }
+    }
+}
+
+/// Where the node serves its RPC interface
+#[derive(Clone, Debug, PartialEq)]
+pub enum RpcBind {
+    Tcp(SocketAddr),
+    /// a unix domain socket, given in `rpc_bind` as `unix:<path>`
+    Unix(PathBuf),
 }
 
+pub const RPC_UNIX_SOCKET_PREFIX: &str = "unix:";
+
 impl NodeConfig {
+    /// Parse `rpc_bind`, which is either a socket address or `unix:` followed by the path of a
+    /// unix domain socket.
+    pub fn get_rpc_bind(&self) -> RpcBind {
+        match self.rpc_bind.strip_prefix(RPC_UNIX_SOCKET_PREFIX) {
+            Some(path) => RpcBind::Unix(PathBuf::from(path)),
+            None => RpcBind::Tcp(
+                self.rpc_bind
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Failed to parse socket: {}", &self.rpc_bind)),
+            ),
+        }
+    }
+
     fn default() -> NodeConfig {
         let mut rng = rand::thread_rng();
         let mut buf = [0u8; 8];
@@ -1311,6 +2337,7 @@ impl NodeConfig {
             data_url: format!("http://127.0.0.1:{}", rpc_port),
             p2p_address: format!("127.0.0.1:{}", rpc_port),
             bootstrap_node: vec![],
+            onion_hosts: vec![],
             deny_nodes: vec![],
             local_peer_seed: local_peer_seed.to_vec(),
             miner: false,
@@ -1320,8 +2347,27 @@ impl NodeConfig {
             max_microblocks: u16::MAX as u64,
             wait_time_for_microblocks: 30_000,
             prometheus_bind: None,
+            grpc_bind: None,
+            statsd_endpoint: None,
+            statsd_prefix: None,
+            otlp_endpoint: None,
+            otlp_interval: 60,
             pox_sync_sample_secs: 30,
             use_test_genesis_chainstate: None,
+            replicate_from: None,
+            telemetry_url: None,
+            telemetry_interval: 3600,
+            marf_blob_store: None,
+            marf_blob_cache_dir: None,
+            marf_blob_cache_size: 4 * 1024 * 1024 * 1024,
+            marf_blob_keep_local: 1000,
+            marf_offload_interval: 600,
+            cost_overrides: None,
+            chainstate_manifest_interval: CHAINSTATE_MANIFEST_INTERVAL,
+            address_tx_index: false,
+            address_asset_index: false,
+            rosetta: false,
+            boot_contract_overrides: vec![],
         }
     }
 
@@ -1361,7 +2407,17 @@ impl NodeConfig {
         let (pubkey_str, hostport) = (parts[0], parts[1]);
         let pubkey = Secp256k1PublicKey::from_hex(pubkey_str)
             .expect(&format!("Invalid public key '{}'", pubkey_str));
-        let sockaddr = hostport.to_socket_addrs().unwrap().next().unwrap();
+        let sockaddr = match hostport.rsplitn(2, ':').collect::<Vec<_>>()[..] {
+            [port, host] if is_onion_host(host) => {
+                // can't be resolved; the proxy connects to it by name
+                let port = port
+                    .parse::<u16>()
+                    .unwrap_or_else(|_| panic!("Invalid port in '{}'", hostport));
+                self.onion_hosts.push(host.to_lowercase());
+                PeerAddress::from_onion_host(host).to_socketaddr(port)
+            }
+            _ => hostport.to_socket_addrs().unwrap().next().unwrap(),
+        };
         let neighbor = NodeConfig::default_neighbor(sockaddr, pubkey, chain_id, peer_version);
         self.bootstrap_node.push(neighbor);
     }
@@ -1408,6 +2464,7 @@ pub struct MinerConfig {
     pub subsequent_attempt_time_ms: u64,
     pub microblock_attempt_time_ms: u64,
     pub probability_pick_no_estimate_tx: u8,
+    pub assembly_replay_dir: Option<String>,
 }
 
 impl MinerConfig {
@@ -1418,6 +2475,7 @@ impl MinerConfig {
             subsequent_attempt_time_ms: 180_000,
             microblock_attempt_time_ms: 30_000,
             probability_pick_no_estimate_tx: 5,
+            assembly_replay_dir: None,
         }
     }
 }
@@ -1434,7 +2492,87 @@ pub struct ConnectionOptionsFile {
     pub private_key_lifetime: Option<u64>,
     pub num_neighbors: Option<u64>,
     pub num_clients: Option<u64>,
+    pub max_inbound_miners: Option<u64>,
+    pub max_inbound_followers: Option<u64>,
+    pub max_inbound_unknown: Option<u64>,
+    pub reserved_outbound_slots: Option<u64>,
+    /// inbound p2p connections per second we accept from each IP address
+    pub inbound_handshake_rate_limit: Option<f64>,
+    /// inbound p2p connections we accept from an IP address at once; defaults to one second's
+    /// worth
+    pub inbound_handshake_rate_burst: Option<u64>,
+    pub partial_message_timeout: Option<u64>,
     pub max_http_clients: Option<u64>,
+    pub max_http_pipelined_requests: Option<u64>,
+    pub max_websocket_sessions: Option<u64>,
+    pub websocket_ping_interval: Option<u64>,
+    pub http_response_cache_size: Option<u64>,
+    pub max_recent_reorgs: Option<u64>,
+    pub stale_tip_burn_blocks: Option<u64>,
+    pub rpc_cors_allowed_origins: Option<Vec<String>>,
+    pub rpc_cors_allowed_methods: Option<Vec<String>>,
+    pub rpc_cors_allowed_headers: Option<Vec<String>>,
+    pub rpc_cors_max_age: Option<u64>,
+    pub rpc_tls_cert_file: Option<String>,
+    pub rpc_tls_key_file: Option<String>,
+    pub rpc_tls_client_ca_file: Option<String>,
+    pub rpc_tls_require_client_cert: Option<bool>,
+    pub rpc_unix_socket_mode: Option<String>,
+    pub rpc_api_keys: Option<Vec<RPCApiKeyConfigFile>>,
+    pub rpc_require_api_key: Option<bool>,
+    pub rpc_ip_rate_limit: Option<f64>,
+    pub rpc_ip_rate_burst: Option<u64>,
+    pub rpc_admin_api_key: Option<String>,
+    pub health_max_burnchain_lag: Option<u64>,
+    pub health_max_tip_age: Option<u64>,
+    pub health_min_peers: Option<u64>,
+    pub reputation_half_life: Option<u64>,
+    pub reputation_ban_threshold: Option<f64>,
+    pub reputation_deprioritize_threshold: Option<f64>,
+    pub reputation_penalty_invalid_message: Option<f64>,
+    pub reputation_penalty_stale_inventory: Option<f64>,
+    pub reputation_penalty_slow_response: Option<f64>,
+    pub reputation_penalty_unsolicited_spam: Option<f64>,
+    pub p2p_encryption: Option<bool>,
+    pub p2p_require_encryption: Option<bool>,
+    pub compact_blocks: Option<bool>,
+    pub p2p_compression: Option<bool>,
+    pub p2p_compression_min_size: Option<u64>,
+    pub capability_exchange: Option<bool>,
+    pub compact_block_timeout: Option<u64>,
+    pub tx_relay_filter_items: Option<u32>,
+    pub mempool_sync_bootstrap_peers: Option<u64>,
+    pub mempool_pull_interval: Option<u64>,
+    pub mempool_txs_min_interval: Option<u64>,
+    /// bytes of the canonical tip's microblock stream we forward per interval
+    pub microblock_relay_canonical_budget: Option<u64>,
+    /// bytes of each stale microblock stream we forward per interval
+    pub microblock_relay_stale_budget: Option<u64>,
+    pub microblock_relay_budget_interval: Option<u64>,
+    /// how many of our best peers from the last run to reconnect to on boot; 0 disables this
+    pub warm_start_peers: Option<u64>,
+    pub peer_score_save_interval: Option<u64>,
+    pub peer_score_max_age: Option<u64>,
+    pub socks5_proxy: Option<String>,
+    pub socks5_username: Option<String>,
+    pub socks5_password: Option<String>,
+    /// bytes per second we may push to each p2p neighbor; unlimited if not given
+    pub max_peer_egress_rate: Option<f64>,
+    /// bytes we may push to a p2p neighbor at once; defaults to one second's worth
+    pub max_peer_egress_burst: Option<u64>,
+    /// bytes per second we may push to all p2p neighbors together; unlimited if not given
+    pub max_egress_rate: Option<f64>,
+    /// bytes we may push to all p2p neighbors at once; defaults to one second's worth
+    pub max_egress_burst: Option<u64>,
+    /// hostnames (`HOST[:PORT]`) whose addresses are bootstrap peers
+    pub dns_seeds: Option<Vec<String>>,
+    /// only ask DNS seeds for peers with these service bits
+    pub dns_seed_services: Option<u16>,
+    pub dns_seed_interval: Option<u64>,
+    /// one of "none", "upnp", "natpmp", or "any"
+    pub port_mapping: Option<String>,
+    pub port_mapping_lease: Option<u64>,
+    pub port_mapping_gateway: Option<String>,
     pub soft_num_neighbors: Option<u64>,
     pub soft_num_clients: Option<u64>,
     pub max_neighbors_per_host: Option<u64>,
@@ -1443,26 +2581,63 @@ pub struct ConnectionOptionsFile {
     pub soft_max_neighbors_per_org: Option<u64>,
     pub soft_max_clients_per_host: Option<u64>,
     pub max_sockets: Option<u64>,
+    pub max_open_files: Option<u64>,
+    pub open_files_reserve: Option<u64>,
     pub walk_interval: Option<u64>,
     pub dns_timeout: Option<u64>,
     pub max_inflight_blocks: Option<u64>,
     pub max_inflight_attachments: Option<u64>,
+    pub max_inflight_blocks_per_peer: Option<u64>,
+    pub block_download_steal_timeout: Option<u64>,
+    pub download_retry_base_delay: Option<u64>,
+    pub download_retry_max_delay: Option<u64>,
+    pub download_retry_jitter: Option<f64>,
+    pub attachment_retry_base_delay: Option<u64>,
+    pub attachment_retry_max_delay: Option<u64>,
+    pub attachment_retry_jitter: Option<f64>,
     pub read_only_call_limit_write_length: Option<u64>,
     pub read_only_call_limit_read_length: Option<u64>,
     pub read_only_call_limit_write_count: Option<u64>,
     pub read_only_call_limit_read_count: Option<u64>,
     pub read_only_call_limit_runtime: Option<u64>,
+    pub read_only_call_max_steps: Option<u64>,
+    pub read_only_call_timeout_ms: Option<u64>,
     pub maximum_call_argument_size: Option<u32>,
     pub download_interval: Option<u64>,
     pub inv_sync_interval: Option<u64>,
     pub full_inv_sync_interval: Option<u64>,
     pub inv_reward_cycles: Option<u64>,
     pub public_ip_address: Option<String>,
+    pub alt_public_ip_address: Option<String>,
+    /// one of "any", "prefer_ipv4", "prefer_ipv6", "ipv4_only", or "ipv6_only"
+    pub address_family: Option<String>,
+    pub allow_cidrs: Option<Vec<String>>,
+    pub deny_cidrs: Option<Vec<String>>,
+    pub allow_asns: Option<Vec<u32>>,
+    pub deny_asns: Option<Vec<u32>>,
     pub disable_inbound_walks: Option<bool>,
     pub disable_inbound_handshakes: Option<bool>,
     pub disable_block_download: Option<bool>,
     pub force_disconnect_interval: Option<u64>,
     pub antientropy_public: Option<bool>,
+    pub antientropy_offers: Option<bool>,
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct RPCApiKeyConfigFile {
+    pub key: String,
+    /// requests per second; unlimited if not given
+    pub rate_limit: Option<f64>,
+    /// requests allowed at once; defaults to one second's worth
+    pub rate_burst: Option<u64>,
+}
+
+/// Rate limit from a requests-per-second (or bytes-per-second) rate and an optional burst size
+fn rpc_rate_limit(rate: Option<f64>, burst: Option<u64>) -> Option<RPCRateLimit> {
+    rate.map(|rate| RPCRateLimit {
+        rate,
+        burst: burst.unwrap_or_else(|| (rate.ceil() as u64).max(1)),
+    })
 }
 
 #[derive(Clone, Deserialize, Default)]
@@ -1484,8 +2659,36 @@ pub struct NodeConfigFile {
     pub max_microblocks: Option<u64>,
     pub wait_time_for_microblocks: Option<u64>,
     pub prometheus_bind: Option<String>,
+    pub grpc_bind: Option<String>,
+    pub statsd_endpoint: Option<String>,
+    pub statsd_prefix: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub otlp_interval: Option<u64>,
     pub pox_sync_sample_secs: Option<u64>,
     pub use_test_genesis_chainstate: Option<bool>,
+    pub replicate_from: Option<String>,
+    pub telemetry_url: Option<String>,
+    pub telemetry_interval: Option<u64>,
+    pub marf_blob_store: Option<String>,
+    pub marf_blob_cache_dir: Option<String>,
+    pub marf_blob_cache_size: Option<u64>,
+    pub marf_blob_keep_local: Option<u32>,
+    pub marf_offload_interval: Option<u64>,
+    /// cost function name (e.g. `cost_add`) => the constant cost to charge for it
+    pub cost_overrides: Option<HashMap<String, ExecutionCost>>,
+    pub chainstate_manifest_interval: Option<u64>,
+    pub address_tx_index: Option<bool>,
+    pub address_asset_index: Option<bool>,
+    pub rosetta: Option<bool>,
+    pub boot_contracts: Option<Vec<BootContractFile>>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct BootContractFile {
+    /// contract name, e.g. `pox` to replace the stock PoX contract
+    pub name: String,
+    /// path to the contract's Clarity source
+    pub path: String,
 }
 
 #[derive(Clone, Deserialize)]
@@ -1497,6 +2700,7 @@ pub struct FeeEstimationConfigFile {
     pub log_error: Option<bool>,
     pub fee_rate_fuzzer_fraction: Option<f64>,
     pub fee_rate_window_size: Option<u64>,
+    pub record_observations: Option<bool>,
 }
 
 impl Default for FeeEstimationConfigFile {
@@ -1509,6 +2713,7 @@ impl Default for FeeEstimationConfigFile {
             log_error: None,
             fee_rate_fuzzer_fraction: None,
             fee_rate_window_size: None,
+            record_observations: None,
         }
     }
 }
@@ -1520,6 +2725,7 @@ pub struct MinerConfigFile {
     pub subsequent_attempt_time_ms: Option<u64>,
     pub microblock_attempt_time_ms: Option<u64>,
     pub probability_pick_no_estimate_tx: Option<u8>,
+    pub assembly_replay_dir: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Default)]
@@ -1545,6 +2751,8 @@ pub enum EventKeyType {
     BurnchainBlocks,
     MinedBlocks,
     MinedMicroblocks,
+    Reorgs,
+    StaleTip,
 }
 
 impl EventKeyType {
@@ -1569,6 +2777,14 @@ impl EventKeyType {
             return Some(EventKeyType::Microblocks);
         }
 
+        if raw_key == "reorgs" {
+            return Some(EventKeyType::Reorgs);
+        }
+
+        if raw_key == "stale_tip" {
+            return Some(EventKeyType::StaleTip);
+        }
+
         let comps: Vec<_> = raw_key.split("::").collect();
         if comps.len() == 1 {
             let split: Vec<_> = comps[0].split(".").collect();
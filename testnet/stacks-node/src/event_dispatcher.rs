@@ -28,6 +28,9 @@ use stacks::chainstate::stacks::{StacksBlock, StacksMicroblock};
 use stacks::codec::StacksMessageCodec;
 use stacks::core::mempool::{MemPoolDropReason, MemPoolEventDispatcher};
 use stacks::net::atlas::{Attachment, AttachmentInstance};
+use stacks::net::ws::{websocket_send_event, WebSocketEvent, WebSocketEventSender};
+use stacks::net::RPCChainReorgData;
+use stacks::net::RPCStaleTipData;
 use stacks::types::chainstate::{
     BlockHeaderHash, BurnchainHeaderHash, StacksAddress, StacksBlockId,
 };
@@ -69,6 +72,8 @@ pub const PATH_MINED_MICROBLOCK: &str = "mined_microblock";
 pub const PATH_BURN_BLOCK_SUBMIT: &str = "new_burn_block";
 pub const PATH_BLOCK_PROCESSED: &str = "new_block";
 pub const PATH_ATTACHMENT_PROCESSED: &str = "attachments/new";
+pub const PATH_REORG: &str = "reorg";
+pub const PATH_STALE_TIP: &str = "stale_tip";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MinedBlockEvent {
@@ -261,6 +266,7 @@ impl EventObserver {
             "microblock_sequence": receipt.microblock_header.as_ref().map(|x| x.sequence),
             "microblock_hash": receipt.microblock_header.as_ref().map(|x| format!("0x{}", x.block_hash())),
             "microblock_parent_hash": receipt.microblock_header.as_ref().map(|x| format!("0x{}", x.prev_block)),
+            "vm_error": receipt.vm_error,
         })
     }
 
@@ -287,6 +293,37 @@ impl EventObserver {
         self.send_payload(payload, PATH_MEMPOOL_TX_SUBMIT);
     }
 
+    fn make_reorg_payload(reorg: &RPCChainReorgData) -> serde_json::Value {
+        json!({
+            "orphaned_tip": format!("0x{}", reorg.orphaned_tip),
+            "orphaned_tip_height": reorg.orphaned_tip_height,
+            "new_tip": format!("0x{}", reorg.new_tip),
+            "new_tip_height": reorg.new_tip_height,
+            "common_ancestor": format!("0x{}", reorg.common_ancestor),
+            "common_ancestor_height": reorg.common_ancestor_height,
+            "depth": reorg.depth,
+            "burn_block_height": reorg.burn_block_height,
+        })
+    }
+
+    fn send_reorg(&self, payload: &serde_json::Value) {
+        self.send_payload(payload, PATH_REORG);
+    }
+
+    fn make_stale_tip_payload(stale_tip: &RPCStaleTipData) -> serde_json::Value {
+        json!({
+            "stacks_tip": format!("0x{}", stale_tip.stacks_tip),
+            "stacks_tip_height": stale_tip.stacks_tip_height,
+            "stale_since_burn_block_height": stale_tip.stale_since_burn_block_height,
+            "burn_block_height": stale_tip.burn_block_height,
+            "recovery_attempt": stale_tip.recovery_attempt,
+        })
+    }
+
+    fn send_stale_tip(&self, payload: &serde_json::Value) {
+        self.send_payload(payload, PATH_STALE_TIP);
+    }
+
     /// Serializes new microblocks data into a JSON payload and sends it off to the correct path
     fn send_new_microblocks(
         &self,
@@ -404,7 +441,10 @@ pub struct EventDispatcher {
     any_event_observers_lookup: HashSet<u16>,
     miner_observers_lookup: HashSet<u16>,
     mined_microblocks_observers_lookup: HashSet<u16>,
+    reorg_observers_lookup: HashSet<u16>,
+    stale_tip_observers_lookup: HashSet<u16>,
     boot_receipts: Arc<Mutex<Option<Vec<StacksTransactionReceipt>>>>,
+    websocket_events: Option<WebSocketEventSender>,
 }
 
 impl MemPoolEventDispatcher for EventDispatcher {
@@ -520,9 +560,17 @@ impl EventDispatcher {
             boot_receipts: Arc::new(Mutex::new(None)),
             miner_observers_lookup: HashSet::new(),
             mined_microblocks_observers_lookup: HashSet::new(),
+            reorg_observers_lookup: HashSet::new(),
+            stale_tip_observers_lookup: HashSet::new(),
+            websocket_events: None,
         }
     }
 
+    /// Also send block, microblock, and mempool events to the RPC server's WebSocket clients
+    pub fn register_websocket_sender(&mut self, sender: WebSocketEventSender) {
+        self.websocket_events = Some(sender);
+    }
+
     pub fn process_burn_block(
         &self,
         burn_block: &BurnchainHeaderHash,
@@ -670,6 +718,20 @@ impl EventDispatcher {
         anchored_consumed: &ExecutionCost,
         mblock_confirmed_consumed: &ExecutionCost,
     ) {
+        if let Some(ref sender) = self.websocket_events {
+            websocket_send_event(
+                sender,
+                WebSocketEvent::Block {
+                    index_block_hash: chain_tip.metadata.index_block_hash(),
+                    block_hash: chain_tip.block.block_hash(),
+                    consensus_hash: chain_tip.metadata.consensus_hash.clone(),
+                    parent_index_block_hash: parent_index_hash.clone(),
+                    height: chain_tip.metadata.block_height,
+                    txids: chain_tip.block.txs.iter().map(|tx| tx.txid()).collect(),
+                },
+            );
+        }
+
         let boot_receipts = if chain_tip.metadata.block_height == 1 {
             let mut boot_receipts_result = self
                 .boot_receipts
@@ -746,6 +808,23 @@ impl EventDispatcher {
         parent_index_block_hash: StacksBlockId,
         processed_unconfirmed_state: ProcessedUnconfirmedState,
     ) {
+        if let Some(ref sender) = self.websocket_events {
+            for (sequence, header, receipts) in processed_unconfirmed_state.receipts.iter() {
+                websocket_send_event(
+                    sender,
+                    WebSocketEvent::Microblock {
+                        microblock_hash: header.block_hash(),
+                        sequence: *sequence,
+                        parent_index_block_hash: parent_index_block_hash.clone(),
+                        txids: receipts
+                            .iter()
+                            .map(|receipt| receipt.transaction.txid())
+                            .collect(),
+                    },
+                );
+            }
+        }
+
         // lazily assemble payload only if we have observers
         let interested_observers: Vec<_> = self
             .registered_observers
@@ -799,6 +878,15 @@ impl EventDispatcher {
     }
 
     pub fn process_new_mempool_txs(&self, txs: Vec<StacksTransaction>) {
+        if let Some(ref sender) = self.websocket_events {
+            if !txs.is_empty() {
+                websocket_send_event(
+                    sender,
+                    WebSocketEvent::MempoolTxs(txs.iter().map(|tx| tx.txid()).collect()),
+                );
+            }
+        }
+
         // lazily assemble payload only if we have observers
         let interested_observers: Vec<_> = self
             .registered_observers
@@ -820,6 +908,56 @@ impl EventDispatcher {
         }
     }
 
+    pub fn process_reorgs(&self, reorgs: &[RPCChainReorgData]) {
+        if let Some(ref sender) = self.websocket_events {
+            for reorg in reorgs.iter() {
+                websocket_send_event(sender, WebSocketEvent::Reorg(reorg.clone()));
+            }
+        }
+
+        // lazily assemble payload only if we have observers
+        let interested_observers: Vec<_> = self
+            .registered_observers
+            .iter()
+            .enumerate()
+            .filter(|(obs_id, _observer)| {
+                self.reorg_observers_lookup.contains(&(*obs_id as u16))
+                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+            })
+            .collect();
+        if interested_observers.is_empty() {
+            return;
+        }
+
+        for reorg in reorgs.iter() {
+            let payload = EventObserver::make_reorg_payload(reorg);
+            for (_, observer) in interested_observers.iter() {
+                observer.send_reorg(&payload);
+            }
+        }
+    }
+
+    pub fn process_stale_tip(&self, stale_tip: &RPCStaleTipData) {
+        // lazily assemble payload only if we have observers
+        let interested_observers: Vec<_> = self
+            .registered_observers
+            .iter()
+            .enumerate()
+            .filter(|(obs_id, _observer)| {
+                self.stale_tip_observers_lookup.contains(&(*obs_id as u16))
+                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+            })
+            .collect();
+        if interested_observers.is_empty() {
+            return;
+        }
+
+        let payload = EventObserver::make_stale_tip_payload(stale_tip);
+        for (_, observer) in interested_observers.iter() {
+            observer.send_stale_tip(&payload);
+        }
+    }
+
     pub fn process_mined_block_event(
         &self,
         target_burn_height: u64,
@@ -890,6 +1028,13 @@ impl EventDispatcher {
     }
 
     pub fn process_dropped_mempool_txs(&self, txs: Vec<Txid>, reason: MemPoolDropReason) {
+        if let Some(ref sender) = self.websocket_events {
+            websocket_send_event(
+                sender,
+                WebSocketEvent::MempoolTxsDropped(txs.clone(), reason.to_string()),
+            );
+        }
+
         // lazily assemble payload only if we have observers
         let interested_observers: Vec<_> = self
             .registered_observers
@@ -1017,6 +1162,12 @@ impl EventDispatcher {
                     self.mined_microblocks_observers_lookup
                         .insert(observer_index);
                 }
+                EventKeyType::Reorgs => {
+                    self.reorg_observers_lookup.insert(observer_index);
+                }
+                EventKeyType::StaleTip => {
+                    self.stale_tip_observers_lookup.insert(observer_index);
+                }
             }
         }
 
@@ -0,0 +1,570 @@
+//! Optional gRPC interface to the node (built with the `grpc` feature).  Each call is translated
+//! into the equivalent HTTP RPC request and sent to the node's own RPC listener over loopback, so
+//! gRPC clients get exactly the answers (and API-key checks, rate limits, and transaction relaying)
+//! that HTTP clients do.  The protobuf schema is in `proto/node.proto`.
+
+use std::convert::TryFrom;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use stacks::burnchains::{Address, Txid};
+use stacks::chainstate::stacks::StacksTransaction;
+use stacks::codec::StacksMessageCodec;
+use stacks::net::{
+    HttpRequestMetadata, HttpRequestType, HttpResponseType, PeerHost, ProtocolFamily, StacksHttp,
+    StacksHttpMessage, StacksHttpPreamble, TipRequest, UnconfirmedTransactionStatus,
+};
+use stacks::types::chainstate::{StacksAddress, StacksBlockId};
+use stacks::util::hash::hex_bytes;
+use stacks::vm::types::PrincipalData;
+use stacks::vm::{ClarityName, ContractName, Value};
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataMap;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+pub mod proto {
+    tonic::include_proto!("stacks.node.v1");
+}
+
+use self::proto::node_server::{Node, NodeServer};
+
+/// How long to wait for the RPC listener to answer a forwarded request
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many read-only call results to buffer per stream before waiting on the client
+const CALL_STREAM_BUFFER: usize = 16;
+
+/// The metadata key gRPC clients pass their RPC API key in
+const API_KEY_METADATA: &str = "x-api-key";
+
+/// Forwards gRPC calls to the node's RPC listener
+#[derive(Debug, Clone)]
+pub struct NodeService {
+    rpc_addr: SocketAddr,
+}
+
+impl NodeService {
+    /// Forward calls to the RPC listener bound to `rpc_bind`.  Wildcard binds are reached over
+    /// loopback.
+    pub fn new(rpc_bind: &SocketAddr) -> NodeService {
+        let mut rpc_addr = *rpc_bind;
+        if rpc_addr.ip().is_unspecified() {
+            rpc_addr.set_ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        }
+        NodeService { rpc_addr }
+    }
+
+    fn request_metadata(&self, metadata: &MetadataMap) -> HttpRequestMetadata {
+        let mut md = HttpRequestMetadata::from_host(PeerHost::from_socketaddr(&self.rpc_addr));
+        md.keep_alive = false;
+        md.api_key = metadata
+            .get(API_KEY_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        md
+    }
+
+    /// Send a request to the RPC listener and read its response, without blocking the runtime
+    async fn forward(&self, request: HttpRequestType) -> Result<HttpResponseType, Status> {
+        let rpc_addr = self.rpc_addr;
+        tokio::task::spawn_blocking(move || send_rpc_request(&rpc_addr, request))
+            .await
+            .map_err(|e| Status::internal(format!("RPC request failed: {:?}", &e)))?
+    }
+
+    async fn call_read_only_inner(
+        &self,
+        metadata: &MetadataMap,
+        call: proto::CallReadOnlyRequest,
+    ) -> Result<proto::CallReadOnlyResult, Status> {
+        let contract_address = StacksAddress::from_string(&call.contract_address)
+            .ok_or_else(|| Status::invalid_argument("Invalid contract address"))?;
+        let contract_name = ContractName::try_from(call.contract_name)
+            .map_err(|_| Status::invalid_argument("Invalid contract name"))?;
+        let function_name = ClarityName::try_from(call.function_name)
+            .map_err(|_| Status::invalid_argument("Invalid function name"))?;
+        let sender = parse_principal(&call.sender)?;
+        let mut arguments = Vec::with_capacity(call.arguments.len());
+        for argument in call.arguments.iter() {
+            arguments.push(
+                Value::try_deserialize_bytes_untyped(argument)
+                    .map_err(|_| Status::invalid_argument("Invalid Clarity value argument"))?,
+            );
+        }
+
+        let request = HttpRequestType::CallReadOnlyFunction(
+            self.request_metadata(metadata),
+            contract_address,
+            contract_name,
+            sender,
+            function_name,
+            arguments,
+            parse_tip(&call.tip)?,
+        );
+        match self.forward(request).await? {
+            HttpResponseType::CallReadOnlyFunction(_, data) => Ok(proto::CallReadOnlyResult {
+                okay: data.okay,
+                result: match data.result {
+                    Some(ref result) => decode_hex(result)?,
+                    None => vec![],
+                },
+                cause: data.cause.unwrap_or_default(),
+            }),
+            other => Err(error_status(other)),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Node for NodeService {
+    async fn get_info(
+        &self,
+        request: Request<proto::GetInfoRequest>,
+    ) -> Result<Response<proto::NodeInfo>, Status> {
+        let md = self.request_metadata(request.metadata());
+        match self.forward(HttpRequestType::GetInfo(md)).await? {
+            HttpResponseType::PeerInfo(_, info) => Ok(Response::new(proto::NodeInfo {
+                peer_version: info.peer_version,
+                pox_consensus: info.pox_consensus.as_bytes().to_vec(),
+                burn_block_height: info.burn_block_height,
+                stable_pox_consensus: info.stable_pox_consensus.as_bytes().to_vec(),
+                stable_burn_block_height: info.stable_burn_block_height,
+                server_version: info.server_version,
+                network_id: info.network_id,
+                parent_network_id: info.parent_network_id,
+                stacks_tip_height: info.stacks_tip_height,
+                stacks_tip: info.stacks_tip.as_bytes().to_vec(),
+                stacks_tip_consensus_hash: info.stacks_tip_consensus_hash.as_bytes().to_vec(),
+                unanchored_tip: info
+                    .unanchored_tip
+                    .map(|tip| tip.as_bytes().to_vec())
+                    .unwrap_or_default(),
+                unanchored_seq: info.unanchored_seq.unwrap_or(0) as u32,
+            })),
+            other => Err(error_status(other)),
+        }
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<proto::GetAccountRequest>,
+    ) -> Result<Response<proto::Account>, Status> {
+        let md = self.request_metadata(request.metadata());
+        let request = request.into_inner();
+        let principal = parse_principal(&request.principal)?;
+        let tip = parse_tip(&request.tip)?;
+        match self
+            .forward(HttpRequestType::GetAccount(md, principal, tip, false))
+            .await?
+        {
+            HttpResponseType::GetAccount(_, account) => Ok(Response::new(proto::Account {
+                balance: decode_amount(&account.balance)?,
+                locked: decode_amount(&account.locked)?,
+                unlock_height: account.unlock_height,
+                nonce: account.nonce,
+            })),
+            other => Err(error_status(other)),
+        }
+    }
+
+    async fn get_block(
+        &self,
+        request: Request<proto::GetBlockRequest>,
+    ) -> Result<Response<proto::Block>, Status> {
+        let md = self.request_metadata(request.metadata());
+        let index_block_hash = StacksBlockId::from_bytes(&request.get_ref().index_block_hash)
+            .ok_or_else(|| Status::invalid_argument("Invalid index block hash"))?;
+        match self
+            .forward(HttpRequestType::GetBlock(md, index_block_hash))
+            .await?
+        {
+            HttpResponseType::Block(_, block) => Ok(Response::new(proto::Block {
+                block: block.serialize_to_vec(),
+            })),
+            other => Err(error_status(other)),
+        }
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<proto::GetTransactionRequest>,
+    ) -> Result<Response<proto::UnconfirmedTransaction>, Status> {
+        let md = self.request_metadata(request.metadata());
+        let txid = Txid::from_bytes(&request.get_ref().txid)
+            .ok_or_else(|| Status::invalid_argument("Invalid txid"))?;
+        match self
+            .forward(HttpRequestType::GetTransactionUnconfirmed(md, txid))
+            .await?
+        {
+            HttpResponseType::UnconfirmedTransaction(_, data) => {
+                let status = match data.status {
+                    UnconfirmedTransactionStatus::Mempool => {
+                        proto::unconfirmed_transaction::Status::Mempool(proto::MempoolStatus {})
+                    }
+                    UnconfirmedTransactionStatus::Microblock { block_hash, seq } => {
+                        proto::unconfirmed_transaction::Status::Microblock(
+                            proto::MicroblockStatus {
+                                block_hash: block_hash.as_bytes().to_vec(),
+                                seq: seq as u32,
+                            },
+                        )
+                    }
+                };
+                Ok(Response::new(proto::UnconfirmedTransaction {
+                    transaction: decode_hex(&data.tx)?,
+                    status: Some(status),
+                }))
+            }
+            other => Err(error_status(other)),
+        }
+    }
+
+    async fn call_read_only(
+        &self,
+        request: Request<proto::CallReadOnlyRequest>,
+    ) -> Result<Response<proto::CallReadOnlyResult>, Status> {
+        let metadata = request.metadata().clone();
+        self.call_read_only_inner(&metadata, request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    type CallReadOnlyStreamStream = ReceiverStream<Result<proto::CallReadOnlyResult, Status>>;
+
+    async fn call_read_only_stream(
+        &self,
+        request: Request<Streaming<proto::CallReadOnlyRequest>>,
+    ) -> Result<Response<Self::CallReadOnlyStreamStream>, Status> {
+        let metadata = request.metadata().clone();
+        let mut calls = request.into_inner();
+        let (results_tx, results_rx) = mpsc::channel(CALL_STREAM_BUFFER);
+        let service = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let result = match calls.message().await {
+                    Ok(Some(call)) => service.call_read_only_inner(&metadata, call).await,
+                    Ok(None) => break,
+                    Err(status) => Err(status),
+                };
+                // a bad call fails only its own result, but a broken stream ends the stream
+                if results_tx.send(result).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(results_rx)))
+    }
+
+    async fn broadcast_transaction(
+        &self,
+        request: Request<proto::BroadcastTransactionRequest>,
+    ) -> Result<Response<proto::BroadcastTransactionResult>, Status> {
+        let md = self.request_metadata(request.metadata());
+        let tx = StacksTransaction::consensus_deserialize(&mut &request.get_ref().transaction[..])
+            .map_err(|e| Status::invalid_argument(format!("Invalid transaction: {:?}", &e)))?;
+        match self
+            .forward(HttpRequestType::PostTransaction(md, tx, None, false))
+            .await?
+        {
+            HttpResponseType::TransactionID(_, txid) => {
+                Ok(Response::new(proto::BroadcastTransactionResult {
+                    txid: txid.as_bytes().to_vec(),
+                }))
+            }
+            other => Err(error_status(other)),
+        }
+    }
+}
+
+/// Parse a `tip` field the way the HTTP RPC interface parses its `tip` query parameter, except
+/// that malformed tips are rejected instead of ignored
+fn parse_tip(tip: &str) -> Result<TipRequest, Status> {
+    match tip {
+        "" => Ok(TipRequest::UseLatestAnchoredTip),
+        "latest" => Ok(TipRequest::UseLatestUnconfirmedTip),
+        hex => StacksBlockId::from_hex(hex)
+            .map(TipRequest::SpecificTip)
+            .map_err(|_| Status::invalid_argument("Invalid tip")),
+    }
+}
+
+fn parse_principal(principal: &str) -> Result<PrincipalData, Status> {
+    PrincipalData::parse(principal).map_err(|_| Status::invalid_argument("Invalid principal"))
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, Status> {
+    hex_bytes(hex.strip_prefix("0x").unwrap_or(hex))
+        .map_err(|_| Status::internal("RPC response has invalid hex"))
+}
+
+/// Convert an RPC balance (a big-endian hex u128) to a decimal string
+fn decode_amount(hex: &str) -> Result<String, Status> {
+    let bytes = decode_hex(hex)?;
+    let bytes = <[u8; 16]>::try_from(bytes.as_slice())
+        .map_err(|_| Status::internal("RPC response has an invalid amount"))?;
+    Ok(u128::from_be_bytes(bytes).to_string())
+}
+
+/// The gRPC status for an RPC response that isn't the expected data
+fn error_status(response: HttpResponseType) -> Status {
+    match response {
+        HttpResponseType::BadRequest(_, msg) => Status::invalid_argument(msg),
+        HttpResponseType::BadRequestJSON(_, json) => Status::invalid_argument(json.to_string()),
+        HttpResponseType::Unauthorized(_, msg) => Status::unauthenticated(msg),
+        HttpResponseType::PaymentRequired(_, msg) | HttpResponseType::Forbidden(_, msg) => {
+            Status::permission_denied(msg)
+        }
+        HttpResponseType::NotFound(_, msg) => Status::not_found(msg),
+        HttpResponseType::TooManyRequests(_, msg) => Status::resource_exhausted(msg),
+        HttpResponseType::ServiceUnavailable(_, msg) => Status::unavailable(msg),
+        HttpResponseType::ServerError(_, msg) => Status::internal(msg),
+        other => Status::internal(format!("Unexpected RPC response: {:?}", &other)),
+    }
+}
+
+/// Send one request to the RPC listener at `rpc_addr` and read back its response.  The request
+/// asks the listener to close the connection once it has answered.
+fn send_rpc_request(
+    rpc_addr: &SocketAddr,
+    request: HttpRequestType,
+) -> Result<HttpResponseType, Status> {
+    let unavailable = |e: std::io::Error| {
+        Status::unavailable(format!("Failed to reach the RPC listener: {:?}", &e))
+    };
+    let mut socket = TcpStream::connect_timeout(rpc_addr, RPC_TIMEOUT).map_err(unavailable)?;
+    socket
+        .set_read_timeout(Some(RPC_TIMEOUT))
+        .map_err(unavailable)?;
+    socket
+        .set_write_timeout(Some(RPC_TIMEOUT))
+        .map_err(unavailable)?;
+
+    let mut http = StacksHttp::new(*rpc_addr);
+    http.write_message(&mut socket, &StacksHttpMessage::Request(request))
+        .map_err(|e| Status::unavailable(format!("Failed to send RPC request: {:?}", &e)))?;
+    let mut buf = vec![];
+    socket.read_to_end(&mut buf).map_err(unavailable)?;
+
+    match parse_rpc_response(&mut http, &buf)? {
+        StacksHttpMessage::Response(response) => Ok(response),
+        StacksHttpMessage::Request(_) => Err(Status::internal("RPC listener sent a request")),
+    }
+}
+
+fn parse_rpc_response(http: &mut StacksHttp, buf: &[u8]) -> Result<StacksHttpMessage, Status> {
+    let invalid = |e| Status::internal(format!("Invalid RPC response: {:?}", &e));
+    let (preamble, offset) = http.read_preamble(buf).map_err(invalid)?;
+    if let StacksHttpPreamble::Request(_) = preamble {
+        return Err(Status::internal("RPC listener sent a request"));
+    }
+    let body = &buf[offset..];
+    match http.payload_len(&preamble) {
+        Some(len) if len <= body.len() => http
+            .read_payload(&preamble, &body[..len])
+            .map(|(message, _)| message)
+            .map_err(invalid),
+        Some(_) => Err(Status::internal("Truncated RPC response")),
+        None => match http.stream_payload(&preamble, &mut &body[..]) {
+            Ok((Some((message, _)), _)) => Ok(message),
+            Ok((None, _)) => Err(Status::internal("Truncated RPC response")),
+            Err(e) => Err(invalid(e)),
+        },
+    }
+}
+
+/// Serve the gRPC interface on `bind`, forwarding calls to the RPC listener bound to `rpc_bind`.
+/// Runs on its own thread and Tokio runtime.
+pub fn start_grpc_server(bind: SocketAddr, rpc_bind: SocketAddr) -> Result<(), String> {
+    let service = NodeService::new(&rpc_bind);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("grpc")
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to start the gRPC runtime: {:?}", &e))?;
+    thread::Builder::new()
+        .name("grpc".to_string())
+        .spawn(move || {
+            info!("Start gRPC server on: {}", &bind);
+            let result = runtime.block_on(
+                Server::builder()
+                    .add_service(NodeServer::new(service))
+                    .serve(bind),
+            );
+            if let Err(e) = result {
+                error!("gRPC server on {} stopped: {:?}", &bind, &e);
+            }
+        })
+        .map_err(|e| format!("Failed to start the gRPC thread: {:?}", &e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use stacks::net::{HttpResponseMetadata, HttpVersion};
+    use stacks::vm::types::QualifiedContractIdentifier;
+
+    #[test]
+    fn test_parse_tip() {
+        assert_eq!(parse_tip("").unwrap(), TipRequest::UseLatestAnchoredTip);
+        assert_eq!(
+            parse_tip("latest").unwrap(),
+            TipRequest::UseLatestUnconfirmedTip
+        );
+        assert_eq!(
+            parse_tip(&"11".repeat(32)).unwrap(),
+            TipRequest::SpecificTip(StacksBlockId([0x11; 32]))
+        );
+        assert_eq!(
+            parse_tip("not-a-tip").unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_decode_amount() {
+        assert_eq!(
+            decode_amount("0x0000000000000000000000000000000a").unwrap(),
+            "10"
+        );
+        assert_eq!(
+            decode_amount(&format!("0x{}", "ff".repeat(16))).unwrap(),
+            u128::MAX.to_string()
+        );
+        assert_eq!(
+            decode_amount("0x0a").unwrap_err().code(),
+            tonic::Code::Internal
+        );
+    }
+
+    #[test]
+    fn test_error_status() {
+        let md = || HttpResponseMetadata::new(HttpVersion::Http11, 1, None, false);
+        let cases = vec![
+            (
+                HttpResponseType::NotFound(md(), "no".into()),
+                tonic::Code::NotFound,
+            ),
+            (
+                HttpResponseType::BadRequestJSON(md(), json!({"error": "bad"})),
+                tonic::Code::InvalidArgument,
+            ),
+            (
+                HttpResponseType::Unauthorized(md(), "key".into()),
+                tonic::Code::Unauthenticated,
+            ),
+            (
+                HttpResponseType::TooManyRequests(md(), "slow down".into()),
+                tonic::Code::ResourceExhausted,
+            ),
+            (
+                HttpResponseType::ServerError(md(), "oops".into()),
+                tonic::Code::Internal,
+            ),
+        ];
+        for (response, code) in cases {
+            assert_eq!(error_status(response).code(), code);
+        }
+    }
+
+    #[test]
+    fn test_parse_rpc_response() {
+        let rpc_addr: SocketAddr = "127.0.0.1:20443".parse().unwrap();
+        let response = HttpResponseType::CallReadOnlyFunction(
+            HttpResponseMetadata::new(HttpVersion::Http11, 1, None, false),
+            stacks::net::CallReadOnlyResponse {
+                okay: true,
+                result: Some("0x0100000000000000000000000000000001".to_string()),
+                cause: None,
+            },
+        );
+        let contract =
+            QualifiedContractIdentifier::parse("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.foo")
+                .unwrap();
+        let request = HttpRequestType::CallReadOnlyFunction(
+            HttpRequestMetadata::from_host(PeerHost::from_socketaddr(&rpc_addr)),
+            StacksAddress::from(contract.issuer.clone()),
+            contract.name.clone(),
+            PrincipalData::from(contract.clone()),
+            ClarityName::try_from("bar").unwrap(),
+            vec![],
+            TipRequest::UseLatestAnchoredTip,
+        );
+
+        // the client side of the exchange has a request outstanding for the response's path
+        let mut server = StacksHttp::new(rpc_addr);
+        let mut bytes = vec![];
+        server.begin_request(HttpVersion::Http11, request.request_path());
+        server
+            .write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let client = |request: &HttpRequestType| {
+            let mut client = StacksHttp::new(rpc_addr);
+            client
+                .write_message(&mut vec![], &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+            client
+        };
+        assert_eq!(
+            parse_rpc_response(&mut client(&request), &bytes).unwrap(),
+            StacksHttpMessage::Response(response)
+        );
+        assert_eq!(
+            parse_rpc_response(&mut client(&request), &bytes[..bytes.len() - 1])
+                .unwrap_err()
+                .code(),
+            tonic::Code::Internal
+        );
+    }
+
+    #[test]
+    fn test_send_rpc_request() {
+        // a stand-in RPC listener that checks the forwarded request and refuses it
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let rpc_addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let nread = socket.read(&mut buf).unwrap();
+                assert!(nread > 0);
+                request.extend_from_slice(&buf[..nread]);
+            }
+            let response =
+                "HTTP/1.1 401 Unauthorized\r\nContent-Type: text/plain\r\nContent-Length: 7\r\nConnection: close\r\n\r\nbad key";
+            std::io::Write::write_all(&mut socket, response.as_bytes()).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert(API_KEY_METADATA, "secret".parse().unwrap());
+        let service = NodeService::new(&rpc_addr);
+        let request = HttpRequestType::GetInfo(service.request_metadata(&metadata));
+        match send_rpc_request(&service.rpc_addr, request).unwrap() {
+            HttpResponseType::Unauthorized(_, msg) => assert_eq!(msg, "bad key"),
+            other => panic!("Unexpected response: {:?}", &other),
+        }
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /v2/info HTTP/1.1\r\n"));
+        assert!(request.contains("X-API-Key: secret\r\n"));
+        assert!(request.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_service_rpc_addr() {
+        let service = NodeService::new(&"0.0.0.0:20443".parse().unwrap());
+        assert_eq!(service.rpc_addr, "127.0.0.1:20443".parse().unwrap());
+        let service = NodeService::new(&"10.0.0.1:20443".parse().unwrap());
+        assert_eq!(service.rpc_addr, "10.0.0.1:20443".parse().unwrap());
+    }
+}
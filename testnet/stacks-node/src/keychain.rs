@@ -10,6 +10,7 @@ use stacks::util::hash::{Hash160, Sha256Sum};
 use stacks::util::vrf::{VRFPrivateKey, VRFProof, VRFPublicKey, VRF};
 
 use super::operations::BurnchainOpSigner;
+use super::Config;
 
 #[derive(Clone)]
 pub struct Keychain {
@@ -21,6 +22,8 @@ pub struct Keychain {
     vrf_secret_keys: Vec<VRFPrivateKey>,
     vrf_map: HashMap<VRFPublicKey, VRFPrivateKey>,
     rotations: u64,
+    /// burnchain key held outside the node, if the miner's BTC key is on an external signer
+    external_burnchain_key: Option<StacksPublicKey>,
 }
 
 impl Keychain {
@@ -49,6 +52,7 @@ impl Keychain {
             rotations: 0,
             vrf_secret_keys: vec![],
             vrf_map: HashMap::new(),
+            external_burnchain_key: None,
         }
     }
 
@@ -71,6 +75,14 @@ impl Keychain {
         Keychain::new(vec![secret_key], threshold, hash_mode)
     }
 
+    /// The keychain for this node: derived from `node.seed`, but with its burnchain operations
+    /// signed by `burnchain.external_signer_public_key`'s holder if set.
+    pub fn from_config(config: &Config) -> Keychain {
+        let mut keychain = Keychain::default(config.node.seed.clone());
+        keychain.external_burnchain_key = config.burnchain.get_external_signer_public_key();
+        keychain
+    }
+
     pub fn rotate_vrf_keypair(&mut self, block_height: u64) -> VRFPublicKey {
         let mut seed = {
             let mut secret_state = self.hashed_secret_state.to_bytes().to_vec();
@@ -201,6 +213,13 @@ impl Keychain {
     }
 
     pub fn get_burnchain_signer(&self) -> BurnchainSigner {
+        if let Some(ref public_key) = self.external_burnchain_key {
+            return BurnchainSigner {
+                hash_mode: AddressHashMode::SerializeP2PKH,
+                num_sigs: 1,
+                public_keys: vec![public_key.clone()],
+            };
+        }
         let public_keys = self
             .secret_keys
             .iter()
@@ -241,6 +260,9 @@ impl Keychain {
     }
 
     pub fn generate_op_signer(&self) -> BurnchainOpSigner {
-        BurnchainOpSigner::new(self.secret_keys[0], false)
+        match self.external_burnchain_key {
+            Some(ref public_key) => BurnchainOpSigner::new_external(public_key.clone()),
+            None => BurnchainOpSigner::new(self.secret_keys[0], false),
+        }
     }
 }
@@ -15,6 +15,10 @@ extern crate stacks;
 #[macro_use(o, slog_log, slog_trace, slog_debug, slog_info, slog_warn, slog_error)]
 extern crate slog;
 
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::chainstate::stacks::miner::BlockAssemblyReplay;
+use stacks::chainstate::stacks::StacksBlockBuilder;
 pub use stacks::util;
 use stacks::util::hash::hex_bytes;
 
@@ -24,12 +28,16 @@ pub mod burnchains;
 pub mod config;
 pub mod event_dispatcher;
 pub mod genesis_data;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod keychain;
 pub mod neon_node;
 pub mod node;
 pub mod operations;
 pub mod run_loop;
+pub mod scenario;
 pub mod syncctl;
+pub mod telemetry;
 pub mod tenure;
 
 pub use self::burnchains::{
@@ -40,6 +48,7 @@ pub use self::event_dispatcher::EventDispatcher;
 pub use self::keychain::Keychain;
 pub use self::node::{ChainTip, Node};
 pub use self::run_loop::{helium, neon};
+pub use self::scenario::Scenario;
 pub use self::tenure::Tenure;
 
 use pico_args::Arguments;
@@ -143,6 +152,40 @@ fn main() {
             );
             return;
         }
+        "replay-assembly" => {
+            let config_path: String = args.value_from_str("--config").unwrap();
+            let free_args = args.free().unwrap();
+            let replay_path = free_args.first().expect(
+                "`replay-assembly` must be passed the path to a block assembly replay file",
+            );
+            let conf = Config::from_config_file(ConfigFile::from_path(&config_path));
+            if let Err(e) = replay_assembly(&conf, replay_path) {
+                eprintln!("Failed to replay block assembly: {}", &e);
+                process::exit(1);
+            }
+            return;
+        }
+        "test-scenario" => {
+            let free_args = args.free().unwrap();
+            let scenario_path = free_args
+                .first()
+                .expect("`test-scenario` must be passed the path to a scenario file");
+            let report = Scenario::from_path(scenario_path)
+                .and_then(|scenario| scenario.run(Config::from_config_file(ConfigFile::mocknet())));
+            match report {
+                Ok(report) => {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                    if !report.passed {
+                        process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to run scenario: {}", &e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
         _ => {
             print_help();
             return;
@@ -174,6 +217,53 @@ fn main() {
     }
 }
 
+/// Re-run a miner's recorded anchored block assembly attempt against this node's chainstate,
+/// and report whether or not the same block was produced, and which decisions differed.
+fn replay_assembly(conf: &Config, replay_path: &str) -> Result<(), String> {
+    let replay = BlockAssemblyReplay::from_path(replay_path).map_err(|e| e.to_string())?;
+    let recorded_block = replay.get_block().map_err(|e| e.to_string())?;
+
+    let sortdb = SortitionDB::open(&conf.get_burn_db_file_path(), false)
+        .map_err(|e| format!("Failed to open sortition DB: {:?}", &e))?;
+    let (mut chainstate, _) = StacksChainState::open(
+        conf.is_mainnet(),
+        conf.burnchain.chain_id,
+        &conf.get_chainstate_path_str(),
+    )
+    .map_err(|e| format!("Failed to open chainstate: {:?}", &e))?;
+    chainstate.set_cost_overrides(conf.node.cost_overrides.clone());
+
+    let (block, decisions) =
+        StacksBlockBuilder::replay_anchored_block(&chainstate, &sortdb.index_conn(), &replay)
+            .map_err(|e| e.to_string())?;
+
+    let mismatches: Vec<_> = replay
+        .candidates
+        .iter()
+        .zip(decisions.iter())
+        .filter(|(candidate, decision)| candidate.decision != **decision)
+        .map(|(candidate, decision)| {
+            json!({
+                "txid": candidate.txid.to_hex(),
+                "recorded": candidate.decision,
+                "replayed": decision,
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "recorded_block_hash": recorded_block.as_ref().map(|b| b.block_hash().to_hex()),
+        "replayed_block_hash": block.block_hash().to_hex(),
+        "block_matches": recorded_block.as_ref() == Some(&block),
+        "recorded_error": replay.error,
+        "num_candidates": replay.candidates.len(),
+        "num_txs": block.txs.len(),
+        "mismatched_decisions": mismatches,
+    });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    Ok(())
+}
+
 fn version() -> String {
     stacks::version_string(
         "stacks-node",
@@ -223,6 +313,20 @@ key-for-seed\tOutput the associated secret key for a burnchain signer created wi
 \t\tCan be passed a config file for the seed via the `--config=<file>` option *or* by supplying the hex seed on
 \t\tthe command line directly.
 
+replay-assembly\tRe-run a miner's recorded block assembly attempt against this node's chainstate, and report
+\t\twhether or not it produces the same block.  Miners record attempts when `assembly_replay_dir` is set
+\t\tin the `[miner]` section of their config.  The node should not be running.
+\t\tArguments:
+\t\t  --config: path of the node's config.
+\t\tExample:
+\t\t  stacks-node replay-assembly --config=/path/to/config.toml /path/to/replay.json
+
+test-scenario\tRun an end-to-end test scenario against a fresh mocknet node: deploy contracts, submit transactions,
+\t\tmine blocks, and check transaction results, read-only evaluations and printed events, as described in
+\t\ta TOML (or JSON) scenario file.  Outputs a JSON report, and exits with 1 if any check failed.
+\t\tExample:
+\t\t  stacks-node test-scenario /path/to/scenario.toml
+
 help\t\tDisplay this help.
 
 OPTIONAL ARGUMENTS:
@@ -1,7 +1,12 @@
 #![allow(unused_variables)]
 
+use std::sync::Arc;
+
 pub use stacks::monitoring::{increment_errors_emitted_counter, increment_warning_emitted_counter};
+use stacks::monitoring::{register_metrics_backend, statsd::StatsdBackend};
 
+#[cfg(feature = "monitoring_otlp")]
+mod otlp;
 #[cfg(feature = "monitoring_prom")]
 mod prometheus;
 
@@ -10,3 +15,28 @@ pub fn start_serving_monitoring_metrics(bind_address: String) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::start_serving_prometheus_metrics(bind_address);
 }
+
+/// Send metrics to the statsd agent at `endpoint`, with each metric name prefixed by `prefix`
+pub fn start_statsd_metrics(endpoint: &str, prefix: Option<&str>) -> Result<(), String> {
+    let backend = StatsdBackend::new(endpoint, prefix)
+        .map_err(|e| format!("Failed to connect to statsd agent {}: {:?}", endpoint, &e))?;
+    register_metrics_backend(Arc::new(backend));
+    info!("Start sending metrics to statsd agent {}", endpoint);
+    Ok(())
+}
+
+/// Push metrics to the OpenTelemetry collector at `collector_url` every `interval_secs`
+pub fn start_otlp_metrics(collector_url: &str, interval_secs: u64) -> Result<(), String> {
+    #[cfg(feature = "monitoring_otlp")]
+    {
+        let exporter = otlp::OtlpExporter::spawn(collector_url, interval_secs)?;
+        register_metrics_backend(exporter);
+        info!(
+            "Start exporting metrics to OTLP collector {}",
+            collector_url
+        );
+        Ok(())
+    }
+    #[cfg(not(feature = "monitoring_otlp"))]
+    Err("OTLP export requires building with the `monitoring_otlp` feature".to_string())
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use async_h1::client;
+use async_std::net::TcpStream;
+use http_types::{Method, Request, Url};
+
+use stacks::monitoring::MetricsBackend;
+use stacks::util::get_epoch_time_ms;
+
+/// Upper bounds of the histogram buckets, matching the Prometheus client's defaults
+const HISTOGRAM_BOUNDS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Default)]
+struct Histogram {
+    count: u64,
+    sum: f64,
+    /// one more bucket than there are bounds, for samples above the last bound
+    bucket_counts: Vec<u64>,
+}
+
+#[derive(Default)]
+struct Aggregates {
+    counters: BTreeMap<(String, Labels), u64>,
+    gauges: BTreeMap<String, f64>,
+    histograms: BTreeMap<(String, Labels), Histogram>,
+}
+
+/// Metrics backend that aggregates metrics in memory and periodically pushes them to an
+/// OpenTelemetry collector as OTLP/HTTP JSON.  Counters and histograms are cumulative since
+/// node start-up.
+pub struct OtlpExporter {
+    aggregates: Mutex<Aggregates>,
+    start_time_ms: u128,
+}
+
+fn owned_labels(labels: &[(&str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn attributes_json(labels: &Labels) -> serde_json::Value {
+    labels
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+fn nanos(time_ms: u128) -> String {
+    (time_ms * 1_000_000).to_string()
+}
+
+impl OtlpExporter {
+    fn new() -> OtlpExporter {
+        OtlpExporter {
+            aggregates: Mutex::new(Aggregates::default()),
+            start_time_ms: get_epoch_time_ms(),
+        }
+    }
+
+    /// Start up a thread that pushes this exporter's metrics to the collector at
+    /// `collector_url` (e.g. `http://127.0.0.1:4318/v1/metrics`) every `interval_secs`.
+    pub fn spawn(collector_url: &str, interval_secs: u64) -> Result<Arc<OtlpExporter>, String> {
+        let url = Url::parse(collector_url)
+            .map_err(|e| format!("Invalid OTLP URL {}: {:?}", collector_url, &e))?;
+        if url.scheme() != "http" {
+            return Err(format!(
+                "Invalid OTLP URL {}: only http:// is supported",
+                collector_url
+            ));
+        }
+
+        let exporter = Arc::new(OtlpExporter::new());
+        let thread_exporter = exporter.clone();
+        thread::Builder::new()
+            .name("otlp-exporter".to_string())
+            .spawn(move || loop {
+                thread::sleep(Duration::from_secs(interval_secs));
+                thread_exporter.export(&url);
+            })
+            .map_err(|e| format!("Failed to start OTLP exporter thread: {:?}", &e))?;
+
+        Ok(exporter)
+    }
+
+    /// Encode the current state of all metrics as an OTLP `ExportMetricsServiceRequest`
+    pub fn to_json(&self) -> serde_json::Value {
+        let start_time = nanos(self.start_time_ms);
+        let now = nanos(get_epoch_time_ms());
+        let aggregates = self.aggregates.lock().unwrap();
+
+        let mut sums: BTreeMap<&str, Vec<serde_json::Value>> = BTreeMap::new();
+        for ((name, labels), value) in aggregates.counters.iter() {
+            sums.entry(name).or_default().push(json!({
+                "attributes": attributes_json(labels),
+                "startTimeUnixNano": start_time,
+                "timeUnixNano": now,
+                "asInt": value.to_string(),
+            }));
+        }
+
+        let mut histograms: BTreeMap<&str, Vec<serde_json::Value>> = BTreeMap::new();
+        for ((name, labels), histogram) in aggregates.histograms.iter() {
+            histograms.entry(name).or_default().push(json!({
+                "attributes": attributes_json(labels),
+                "startTimeUnixNano": start_time,
+                "timeUnixNano": now,
+                "count": histogram.count.to_string(),
+                "sum": histogram.sum,
+                "bucketCounts": histogram
+                    .bucket_counts
+                    .iter()
+                    .map(|count| count.to_string())
+                    .collect::<Vec<_>>(),
+                "explicitBounds": HISTOGRAM_BOUNDS.to_vec(),
+            }));
+        }
+
+        let mut metrics = vec![];
+        for (name, data_points) in sums.into_iter() {
+            metrics.push(json!({
+                "name": name,
+                "sum": {
+                    "dataPoints": data_points,
+                    // cumulative
+                    "aggregationTemporality": 2,
+                    "isMonotonic": true,
+                },
+            }));
+        }
+        for (name, value) in aggregates.gauges.iter() {
+            metrics.push(json!({
+                "name": name,
+                "gauge": {
+                    "dataPoints": [{ "timeUnixNano": now, "asDouble": value }],
+                },
+            }));
+        }
+        for (name, data_points) in histograms.into_iter() {
+            metrics.push(json!({
+                "name": name,
+                "histogram": {
+                    "dataPoints": data_points,
+                    "aggregationTemporality": 2,
+                },
+            }));
+        }
+
+        json!({
+            "resourceMetrics": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": { "stringValue": "stacks-node" } },
+                    ],
+                },
+                "scopeMetrics": [{
+                    "scope": { "name": "stacks-node" },
+                    "metrics": metrics,
+                }],
+            }],
+        })
+    }
+
+    fn export(&self, url: &Url) {
+        let body = match serde_json::to_vec(&self.to_json()) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("OTLP: failed to serialize metrics: {:?}", &e);
+                return;
+            }
+        };
+
+        let addr = match url.socket_addrs(|| Some(80)) {
+            Ok(addrs) if !addrs.is_empty() => addrs[0],
+            Ok(_) | Err(_) => {
+                warn!("OTLP: failed to resolve {}", url);
+                return;
+            }
+        };
+
+        let mut req = Request::new(Method::Post, url.clone());
+        req.append_header("Content-Type", "application/json");
+        req.set_body(body);
+
+        let response = async_std::task::block_on(async {
+            let stream = match TcpStream::connect(addr).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("OTLP: connection to {} failed - {:?}", url, err);
+                    return None;
+                }
+            };
+
+            match client::connect(stream, req).await {
+                Ok(response) => Some(response),
+                Err(err) => {
+                    warn!("OTLP: POST to {} failed - {:?}", url, err);
+                    None
+                }
+            }
+        });
+
+        if let Some(response) = response {
+            if !response.status().is_success() {
+                warn!(
+                    "OTLP: POST to {} failed with status {}",
+                    url,
+                    response.status()
+                );
+            }
+        }
+    }
+}
+
+impl MetricsBackend for OtlpExporter {
+    fn counter_add(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let mut aggregates = self.aggregates.lock().unwrap();
+        *aggregates
+            .counters
+            .entry((name.to_string(), owned_labels(labels)))
+            .or_insert(0) += value;
+    }
+
+    fn gauge_set(&self, name: &str, value: f64) {
+        let mut aggregates = self.aggregates.lock().unwrap();
+        aggregates.gauges.insert(name.to_string(), value);
+    }
+
+    fn gauge_add(&self, name: &str, delta: f64) {
+        let mut aggregates = self.aggregates.lock().unwrap();
+        *aggregates.gauges.entry(name.to_string()).or_insert(0.0) += delta;
+    }
+
+    fn histogram_observe(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let mut aggregates = self.aggregates.lock().unwrap();
+        let histogram = aggregates
+            .histograms
+            .entry((name.to_string(), owned_labels(labels)))
+            .or_insert_with(|| Histogram {
+                bucket_counts: vec![0; HISTOGRAM_BOUNDS.len() + 1],
+                ..Histogram::default()
+            });
+        histogram.count += 1;
+        histogram.sum += value;
+        let bucket = HISTOGRAM_BOUNDS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(HISTOGRAM_BOUNDS.len());
+        histogram.bucket_counts[bucket] += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_otlp_aggregation() {
+        let exporter = OtlpExporter::new();
+        exporter.counter_add("requests", &[("path", "/v2/info")], 1);
+        exporter.counter_add("requests", &[("path", "/v2/info")], 2);
+        exporter.counter_add("requests", &[("path", "/v2/fees")], 1);
+        exporter.gauge_set("height", 10.0);
+        exporter.gauge_add("outstanding", 2.0);
+        exporter.gauge_add("outstanding", -1.0);
+        exporter.histogram_observe("latency", &[], 0.02);
+        exporter.histogram_observe("latency", &[], 20.0);
+
+        let exported = exporter.to_json();
+        let metrics = exported["resourceMetrics"][0]["scopeMetrics"][0]["metrics"]
+            .as_array()
+            .unwrap()
+            .clone();
+        assert_eq!(metrics.len(), 4);
+
+        assert_eq!(metrics[0]["name"], "requests");
+        let points = metrics[0]["sum"]["dataPoints"].as_array().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            points[0]["attributes"][0]["value"]["stringValue"],
+            "/v2/fees"
+        );
+        assert_eq!(points[0]["asInt"], "1");
+        assert_eq!(points[1]["asInt"], "3");
+
+        assert_eq!(metrics[1]["name"], "height");
+        assert_eq!(metrics[1]["gauge"]["dataPoints"][0]["asDouble"], 10.0);
+        assert_eq!(metrics[2]["name"], "outstanding");
+        assert_eq!(metrics[2]["gauge"]["dataPoints"][0]["asDouble"], 1.0);
+
+        assert_eq!(metrics[3]["name"], "latency");
+        let point = &metrics[3]["histogram"]["dataPoints"][0];
+        assert_eq!(point["count"], "2");
+        let bucket_counts = point["bucketCounts"].as_array().unwrap();
+        assert_eq!(bucket_counts.len(), HISTOGRAM_BOUNDS.len() + 1);
+        assert_eq!(bucket_counts[2], "1");
+        assert_eq!(bucket_counts[HISTOGRAM_BOUNDS.len()], "1");
+    }
+}
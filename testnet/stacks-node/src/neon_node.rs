@@ -3,9 +3,11 @@ use std::collections::HashMap;
 use std::collections::{HashSet, VecDeque};
 use std::convert::{TryFrom, TryInto};
 use std::default::Default;
-use std::net::SocketAddr;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
 use std::sync::{atomic::Ordering, Arc, Mutex};
+use std::time::Duration;
 use std::{thread, thread::JoinHandle};
 
 use stacks::burnchains::{Burnchain, BurnchainParameters, Txid};
@@ -27,7 +29,8 @@ use stacks::chainstate::stacks::{
 };
 use stacks::chainstate::stacks::{
     CoinbasePayload, StacksBlock, StacksMicroblock, StacksTransaction, StacksTransactionSigner,
-    TransactionAnchorMode, TransactionPayload, TransactionVersion,
+    TransactionAnchorMode, TransactionPayload, TransactionVersion, MAX_BLOCK_LEN,
+    MAX_MICROBLOCK_SIZE,
 };
 use stacks::codec::StacksMessageCodec;
 use stacks::core::mempool::MemPoolDB;
@@ -40,14 +43,18 @@ use stacks::net::{
     atlas::{AtlasConfig, AtlasDB, AttachmentInstance},
     db::{LocalPeer, PeerDB},
     dns::DNSResolver,
+    http::StacksHttp,
     p2p::PeerNetwork,
     relay::Relayer,
     rpc::RPCHandlerArgs,
-    Error as NetError, NetworkResult, PeerAddress, ServiceFlags,
+    Error as NetError, ExportedBlockData, HttpRequestMetadata, HttpRequestType, HttpResponseType,
+    NetworkResult, PeerAddress, PeerHost, ServiceFlags, StacksHttpMessage,
+    HTTP_PREAMBLE_MAX_ENCODED_SIZE, MAX_BLOCK_EXPORT, MAX_MICROBLOCKS_UNCONFIRMED,
 };
 use stacks::types::chainstate::{
     BlockHeaderHash, BurnchainHeaderHash, SortitionId, StacksAddress, StacksBlockHeader, VRFSeed,
 };
+use stacks::util::fdlimit;
 use stacks::util::get_epoch_time_ms;
 use stacks::util::get_epoch_time_secs;
 use stacks::util::hash::{to_hex, Hash160, Sha256Sum};
@@ -61,14 +68,26 @@ use crate::burnchains::bitcoin_regtest_controller::BitcoinRegtestController;
 use crate::run_loop::neon::Counters;
 use crate::run_loop::neon::RunLoop;
 use crate::run_loop::RegisteredKey;
+use crate::telemetry::TelemetryBeacon;
 use crate::ChainTip;
 
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain};
+use crate::config::RpcBind;
 use crate::stacks::vm::database::BurnStateDB;
 use stacks::monitoring;
 
 pub const RELAYER_MAX_BUFFER: usize = 100;
 
+/// How often to poll the upstream node for new blocks, when replicating over HTTP
+const REPLICATE_INTERVAL_MS: u128 = 5_000;
+/// How long to wait on the upstream node before giving up on a block export request
+const REPLICATE_TIMEOUT_SECS: u64 = 30;
+/// Most bytes to read from the upstream node for one block export: a largest-possible anchored
+/// block and parent microblock stream for each exported block, plus the HTTP preamble
+const MAX_BLOCK_EXPORT_RESPONSE_LEN: u64 = (MAX_BLOCK_EXPORT as u64)
+    * (MAX_BLOCK_LEN as u64 + (MAX_MICROBLOCKS_UNCONFIRMED as u64) * (MAX_MICROBLOCK_SIZE as u64))
+    + (HTTP_PREAMBLE_MAX_ENCODED_SIZE as u64);
+
 struct AssembledAnchorBlock {
     parent_consensus_hash: ConsensusHash,
     my_burn_hash: BurnchainHeaderHash,
@@ -615,11 +634,123 @@ fn recv_unconfirmed_txs(
     }
 }
 
+/// Fetch up to MAX_BLOCK_EXPORT canonical blocks (and their parent microblocks), starting at
+/// `start_height`, from the block export endpoint of the upstream node at `upstream`.
+fn fetch_block_export(
+    upstream: &str,
+    start_height: u64,
+) -> Result<Vec<ExportedBlockData>, NetError> {
+    let addr = upstream
+        .to_socket_addrs()
+        .map_err(|e| NetError::LookupError(format!("{}: {:?}", upstream, &e)))?
+        .next()
+        .ok_or(NetError::LookupError(format!(
+            "{}: no addresses found",
+            upstream
+        )))?;
+
+    let mut md = HttpRequestMetadata::from_host(PeerHost::from_socketaddr(&addr));
+    md.keep_alive = false;
+    let request = HttpRequestType::GetBlockExport(md, start_height, MAX_BLOCK_EXPORT);
+    let request_path = request.request_path();
+
+    let timeout = Duration::from_secs(REPLICATE_TIMEOUT_SECS);
+    let mut sock = TcpStream::connect_timeout(&addr, timeout).map_err(|e| {
+        warn!("Replicate: failed to connect to {}: {:?}", upstream, &e);
+        NetError::ConnectionError
+    })?;
+    sock.set_read_timeout(Some(timeout))
+        .map_err(NetError::ReadError)?;
+    sock.set_write_timeout(Some(timeout))
+        .map_err(NetError::WriteError)?;
+
+    let request_bytes = StacksHttp::serialize_request(&request)?;
+    sock.write_all(&request_bytes)
+        .map_err(NetError::WriteError)?;
+
+    // read one byte past the limit, to tell a response that's too big from one that's just big
+    let mut resp = vec![];
+    sock.take(MAX_BLOCK_EXPORT_RESPONSE_LEN + 1)
+        .read_to_end(&mut resp)
+        .map_err(NetError::ReadError)?;
+    if resp.len() as u64 > MAX_BLOCK_EXPORT_RESPONSE_LEN {
+        return Err(NetError::RecvError(format!(
+            "Block export from {} is longer than {} bytes",
+            upstream, MAX_BLOCK_EXPORT_RESPONSE_LEN
+        )));
+    }
+
+    match StacksHttp::parse_response(&request_path, &resp)? {
+        StacksHttpMessage::Response(HttpResponseType::BlockExport(_, blocks)) => Ok(blocks),
+        StacksHttpMessage::Response(HttpResponseType::NotFound(..)) => Ok(vec![]),
+        StacksHttpMessage::Response(resp) => Err(NetError::RecvError(format!(
+            "Unexpected response from {}: {:?}",
+            upstream, &resp
+        ))),
+        _ => Err(NetError::RecvError(format!(
+            "Did not receive a response from {}",
+            upstream
+        ))),
+    }
+}
+
+/// Pull the next run of blocks above our canonical Stacks chain tip from the upstream node, and
+/// add them to the network result so the relayer will store and process them as though they
+/// had been downloaded over p2p.
+fn replicate_from_upstream(
+    upstream: &str,
+    sortdb: &SortitionDB,
+    chainstate: &mut StacksChainState,
+    network_result: &mut NetworkResult,
+) {
+    let start_height = match chainstate.get_stacks_chain_tip(sortdb) {
+        Ok(Some(tip)) => tip.height + 1,
+        Ok(None) => 1,
+        Err(e) => {
+            warn!("Replicate: failed to load Stacks chain tip: {:?}", &e);
+            return;
+        }
+    };
+
+    let fetch_start = get_epoch_time_ms();
+    let blocks = match fetch_block_export(upstream, start_height) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            warn!(
+                "Replicate: failed to fetch blocks from {} at height {}: {:?}",
+                upstream, start_height, &e
+            );
+            return;
+        }
+    };
+    let download_time = (get_epoch_time_ms() - fetch_start) as u64;
+
+    debug!(
+        "Replicate: fetched {} block(s) from {} at height {}",
+        blocks.len(),
+        upstream,
+        start_height
+    );
+
+    for exported in blocks.into_iter() {
+        if exported.parent_microblocks.len() > 0 {
+            network_result.confirmed_microblocks.push((
+                exported.parent_consensus_hash,
+                exported.parent_microblocks,
+                download_time,
+            ));
+        }
+        network_result
+            .blocks
+            .push((exported.consensus_hash, exported.block, download_time));
+    }
+}
+
 fn spawn_peer(
     runloop: &RunLoop,
     mut this: PeerNetwork,
     p2p_sock: &SocketAddr,
-    rpc_sock: &SocketAddr,
+    rpc_bind: &RpcBind,
     poll_timeout: u64,
     relay_channel: SyncSender<RelayerDirective>,
     attachments_rx: Receiver<HashSet<AttachmentInstance>>,
@@ -635,7 +766,27 @@ fn spawn_peer(
     let stacks_chainstate_path = config.get_chainstate_path_str();
     let exit_at_block_height = config.burnchain.process_exit_at_block_height;
 
-    this.bind(p2p_sock, rpc_sock).unwrap();
+    // raise our open file limit as far as we're allowed, so the network can shed connections
+    // well before we run out
+    let open_files_target = match this.connection_opts.max_open_files {
+        0 => u64::MAX,
+        max_open_files => max_open_files,
+    };
+    match fdlimit::raise_open_files_limit(open_files_target) {
+        Ok(limit) => info!("Open file descriptor limit is {}", limit),
+        Err(e) => warn!("Failed to raise open file descriptor limit: {:?}", &e),
+    }
+
+    match rpc_bind {
+        RpcBind::Tcp(rpc_sock) => this.bind(p2p_sock, rpc_sock),
+        #[cfg(unix)]
+        RpcBind::Unix(rpc_path) => this.bind_unix_http(p2p_sock, rpc_path),
+        #[cfg(not(unix))]
+        RpcBind::Unix(_) => {
+            panic!("RPC over a unix domain socket is not supported on this platform")
+        }
+    }
+    .unwrap();
     let (mut dns_resolver, mut dns_client) = DNSResolver::new(10);
     let sortdb = SortitionDB::open(&burn_db_path, false).map_err(NetError::DBError)?;
 
@@ -645,6 +796,11 @@ fn spawn_peer(
         &stacks_chainstate_path,
     )
     .map_err(|e| NetError::ChainstateError(e.to_string()))?;
+    chainstate.address_tx_index = config.node.address_tx_index;
+    chainstate.address_asset_index = config.node.address_asset_index;
+    chainstate.rosetta_index = config.node.rosetta;
+    chainstate.set_clarity_state_blobs(runloop.get_marf_blobs());
+    chainstate.set_cost_overrides(config.node.cost_overrides.clone());
 
     // buffer up blocks to store without stalling the p2p thread
     let mut results_with_data = VecDeque::new();
@@ -661,6 +817,13 @@ fn spawn_peer(
                 .unwrap_or_else(|| Box::new(UnitMetric));
             let fee_estimator = config.make_fee_estimator();
 
+            // opt-in telemetry, signed with a key that only lives as long as this process
+            let telemetry_key = Secp256k1PrivateKey::new();
+            let mut telemetry_beacon = config.node.telemetry_url.as_ref().map(|url| {
+                TelemetryBeacon::spawn(url, config.node.telemetry_interval)
+                    .expect("FATAL: failed to start telemetry beacon")
+            });
+
             let mut mem_pool = MemPoolDB::open(
                 is_mainnet,
                 config.burnchain.chain_id,
@@ -677,7 +840,7 @@ fn spawn_peer(
                 .make_cost_metric()
                 .unwrap_or_else(|| Box::new(UnitMetric));
 
-            let handler_args = RPCHandlerArgs {
+            let mut handler_args = RPCHandlerArgs {
                 exit_at_block_height: exit_at_block_height.as_ref(),
                 genesis_chainstate_hash: Sha256Sum::from_hex(stx_genesis::GENESIS_CHAINSTATE_HASH)
                     .unwrap(),
@@ -685,6 +848,7 @@ fn spawn_peer(
                 cost_estimator: Some(cost_estimator.as_ref()),
                 cost_metric: Some(metric.as_ref()),
                 fee_estimator: fee_estimator.as_ref().map(|x| x.as_ref()),
+                telemetry_key: telemetry_beacon.as_ref().map(|_| &telemetry_key),
                 ..RPCHandlerArgs::default()
             };
 
@@ -692,10 +856,15 @@ fn spawn_peer(
             let mut num_inv_sync_passes = 0;
             let mut num_download_passes = 0;
             let mut mblock_deadline = 0;
+            let mut replicate_deadline = 0;
 
             while should_keep_running.load(Ordering::SeqCst) {
                 // initial block download?
                 let ibd = sync_comms.get_ibd();
+                handler_args.burnchain_target_height = match sync_comms.get_burnchain_tip_height() {
+                    0 => None,
+                    height => Some(height),
+                };
                 let download_backpressure = results_with_data.len() > 0;
                 let poll_ms = if !download_backpressure && this.has_more_downloads() {
                     // keep getting those blocks -- drive the downloader state-machine
@@ -731,7 +900,23 @@ fn spawn_peer(
                     &handler_args,
                     &mut expected_attachments,
                 ) {
-                    Ok(network_result) => {
+                    Ok(mut network_result) => {
+                        if let Some(ref upstream) = config.node.replicate_from {
+                            if !download_backpressure && replicate_deadline < get_epoch_time_ms() {
+                                replicate_from_upstream(
+                                    upstream,
+                                    &sortdb,
+                                    &mut chainstate,
+                                    &mut network_result,
+                                );
+                                replicate_deadline = get_epoch_time_ms() + REPLICATE_INTERVAL_MS;
+                            }
+                        }
+
+                        if let Some(ref mut telemetry_beacon) = telemetry_beacon {
+                            telemetry_beacon.run(&this, &telemetry_key);
+                        }
+
                         if num_p2p_state_machine_passes < network_result.num_state_machine_passes {
                             // p2p state-machine did a full pass. Notify anyone listening.
                             sync_comms.notify_p2p_state_pass();
@@ -878,6 +1063,8 @@ fn spawn_miner_relayer(
 
     let (mut chainstate, _) = StacksChainState::open(is_mainnet, chain_id, &stacks_chainstate_path)
         .map_err(|e| NetError::ChainstateError(e.to_string()))?;
+    chainstate.set_clarity_state_blobs(runloop.get_marf_blobs());
+    chainstate.set_cost_overrides(config.node.cost_overrides.clone());
 
     let mut last_mined_blocks: HashMap<
         BurnchainHeaderHash,
@@ -937,6 +1124,14 @@ fn spawn_miner_relayer(
                         event_dispatcher.process_new_attachments(&net_result.attachments);
                     }
 
+                    if net_result.has_reorgs() {
+                        event_dispatcher.process_reorgs(&net_result.reorgs);
+                    }
+
+                    if let Some(ref stale_tip) = net_result.stale_tip {
+                        event_dispatcher.process_stale_tip(stale_tip);
+                    }
+
                     // synchronize unconfirmed tx index to p2p thread
                     send_unconfirmed_txs(&chainstate, unconfirmed_txs.clone());
                 }
@@ -1083,6 +1278,11 @@ fn spawn_miner_relayer(
                         // to give microblocks a chance to collect
                         if issue_timestamp_ms < last_tenure_issue_time + (config.node.wait_time_for_microblocks as u128) {
                             debug!("Relayer: will NOT run tenure since issuance at {} is too fresh (wait until {} + {} = {})", issue_timestamp_ms / 1000, last_tenure_issue_time / 1000, config.node.wait_time_for_microblocks / 1000, (last_tenure_issue_time + (config.node.wait_time_for_microblocks as u128)) / 1000);
+                            if config.burnchain.auto_rbf && !config.node.mock_mining {
+                                // make sure our last block-commit still pays enough to get into the next block
+                                let mut op_signer = keychain.generate_op_signer();
+                                bitcoin_controller.bump_block_commit_fee(&mut op_signer);
+                            }
                             continue;
                         }
                     }
@@ -1222,7 +1422,7 @@ impl StacksNode {
         let miner = runloop.is_miner();
         let burnchain = runloop.get_burnchain();
         let atlas_config = AtlasConfig::default(config.is_mainnet());
-        let mut keychain = Keychain::default(config.node.seed.clone());
+        let mut keychain = Keychain::from_config(&config);
 
         // we can call _open_ here rather than _connect_, since connect is first called in
         //   make_genesis_block
@@ -1254,10 +1454,7 @@ impl StacksNode {
             "Failed to parse socket: {}",
             &config.node.p2p_bind
         ));
-        let rpc_sock = config.node.rpc_bind.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &config.node.rpc_bind
-        ));
+        let rpc_bind = config.node.get_rpc_bind();
         let p2p_addr: SocketAddr = config.node.p2p_address.parse().expect(&format!(
             "Failed to parse socket: {}",
             &config.node.p2p_address
@@ -1313,6 +1510,9 @@ impl StacksNode {
                 )
                 .unwrap();
             }
+            for onion_host in config.node.onion_hosts.iter() {
+                PeerDB::set_onion_host(&mut tx, onion_host).unwrap();
+            }
             tx.commit().unwrap();
         }
 
@@ -1332,17 +1532,30 @@ impl StacksNode {
                 )
                 .unwrap();
             }
+            let opts = &config.connection_options;
+            for (prefix, mask) in opts.allow_cidrs.iter() {
+                PeerDB::add_allow_cidr(&mut tx, prefix, *mask).unwrap();
+            }
+            for (prefix, mask) in opts.deny_cidrs.iter() {
+                PeerDB::add_deny_cidr(&mut tx, prefix, *mask).unwrap();
+            }
+            for asn in opts.allow_asns.iter() {
+                PeerDB::add_allow_asn(&mut tx, *asn).unwrap();
+            }
+            for asn in opts.deny_asns.iter() {
+                PeerDB::add_deny_asn(&mut tx, *asn).unwrap();
+            }
             tx.commit().unwrap();
         }
 
-        // update services to indicate we can support mempool sync
+        // update services to indicate we can support mempool sync, and whether or not we mine
         {
+            let mut services = (ServiceFlags::RPC as u16) | (ServiceFlags::RELAY as u16);
+            if config.node.miner {
+                services |= ServiceFlags::MINER as u16;
+            }
             let mut tx = peerdb.tx_begin().unwrap();
-            PeerDB::set_local_services(
-                &mut tx,
-                (ServiceFlags::RPC as u16) | (ServiceFlags::RELAY as u16),
-            )
-            .unwrap();
+            PeerDB::set_local_services(&mut tx, services).unwrap();
             tx.commit().unwrap();
         }
 
@@ -1382,6 +1595,9 @@ impl StacksNode {
             config.connection_options.clone(),
             epochs,
         );
+        if let Some(websocket_events) = runloop.take_websocket_event_receiver() {
+            p2p_net.set_websocket_event_receiver(websocket_events);
+        }
 
         // setup the relayer channel
         let (relay_send, relay_recv) = sync_channel(RELAYER_MAX_BUFFER);
@@ -1428,7 +1644,7 @@ impl StacksNode {
             runloop,
             p2p_net,
             &p2p_sock,
-            &rpc_sock,
+            &rpc_bind,
             5000,
             relay_send.clone(),
             attachments_rx,
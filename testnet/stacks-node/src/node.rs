@@ -53,6 +53,7 @@ use stacks::{
 use crate::{genesis_data::USE_TEST_GENESIS_CHAINSTATE, run_loop::RegisteredKey};
 
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain, Tenure};
+use crate::config::RpcBind;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
 use stacks::burnchains::PoxConstants;
 use stacks::vm::database::BurnStateDB;
@@ -161,7 +162,7 @@ fn spawn_peer(
     chain_id: u32,
     mut this: PeerNetwork,
     p2p_sock: &SocketAddr,
-    rpc_sock: &SocketAddr,
+    rpc_bind: &RpcBind,
     burn_db_path: String,
     stacks_chainstate_path: String,
     event_dispatcher: EventDispatcher,
@@ -171,7 +172,16 @@ fn spawn_peer(
     attachments_rx: Receiver<HashSet<AttachmentInstance>>,
     config: Config,
 ) -> Result<JoinHandle<()>, NetError> {
-    this.bind(p2p_sock, rpc_sock).unwrap();
+    match rpc_bind {
+        RpcBind::Tcp(rpc_sock) => this.bind(p2p_sock, rpc_sock),
+        #[cfg(unix)]
+        RpcBind::Unix(rpc_path) => this.bind_unix_http(p2p_sock, rpc_path),
+        #[cfg(not(unix))]
+        RpcBind::Unix(_) => {
+            panic!("RPC over a unix domain socket is not supported on this platform")
+        }
+    }
+    .unwrap();
     let server_thread = thread::spawn(move || {
         // create estimators, metric instances for RPC handler
         let cost_estimator = config
@@ -209,6 +219,7 @@ fn spawn_peer(
                         continue;
                     }
                 };
+            chainstate.set_cost_overrides(config.node.cost_overrides.clone());
 
             let estimator = Box::new(UnitEstimator);
             let metric = Box::new(UnitMetric);
@@ -256,6 +267,12 @@ fn spawn_peer(
             if net_result.has_attachments() {
                 event_dispatcher.process_new_attachments(&net_result.attachments);
             }
+            if net_result.has_reorgs() {
+                event_dispatcher.process_reorgs(&net_result.reorgs);
+            }
+            if let Some(ref stale_tip) = net_result.stale_tip {
+                event_dispatcher.process_stale_tip(stale_tip);
+            }
         }
     });
     Ok(server_thread)
@@ -286,7 +303,7 @@ impl Node {
             USE_TEST_GENESIS_CHAINSTATE
         };
 
-        let keychain = Keychain::default(config.node.seed.clone());
+        let keychain = Keychain::from_config(&config);
 
         let initial_balances = config
             .initial_balances
@@ -316,6 +333,7 @@ impl Node {
                 get_namespaces(use_test_genesis_data)
             })),
             get_bulk_initial_names: Some(Box::new(move || get_names(use_test_genesis_data))),
+            boot_contract_overrides: config.node.boot_contract_overrides.clone(),
         };
 
         let chain_state_result = StacksChainState::open_and_exec(
@@ -325,7 +343,7 @@ impl Node {
             Some(&mut boot_data),
         );
 
-        let (chain_state, receipts) = match chain_state_result {
+        let (mut chain_state, receipts) = match chain_state_result {
             Ok(res) => res,
             Err(err) => panic!(
                 "Error while opening chain state at path {}: {:?}",
@@ -333,6 +351,7 @@ impl Node {
                 err
             ),
         };
+        chain_state.set_cost_overrides(config.node.cost_overrides.clone());
 
         let estimator = Box::new(UnitEstimator);
         let metric = Box::new(UnitMetric);
@@ -376,7 +395,7 @@ impl Node {
     ) -> Node {
         let burnchain_tip = burnchain_controller.get_chain_tip();
 
-        let keychain = Keychain::default(config.node.seed.clone());
+        let keychain = Keychain::from_config(&config);
 
         let mut event_dispatcher = EventDispatcher::new();
 
@@ -387,7 +406,7 @@ impl Node {
         let chainstate_path = config.get_chainstate_path_str();
         let sortdb_path = config.get_burn_db_file_path();
 
-        let (chain_state, _) = match StacksChainState::open(
+        let (mut chain_state, _) = match StacksChainState::open(
             config.is_mainnet(),
             config.burnchain.chain_id,
             &chainstate_path,
@@ -395,6 +414,7 @@ impl Node {
             Ok(x) => x,
             Err(_e) => panic!(),
         };
+        chain_state.set_cost_overrides(config.node.cost_overrides.clone());
 
         let (attachments_tx, attachments_rx) = sync_channel(1);
         let mut node = Node {
@@ -458,10 +478,7 @@ impl Node {
 
         println!("BOOTSTRAP WITH {:?}", initial_neighbors);
 
-        let rpc_sock: SocketAddr = self.config.node.rpc_bind.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &self.config.node.rpc_bind
-        ));
+        let rpc_bind = self.config.node.get_rpc_bind();
         let p2p_sock: SocketAddr = self.config.node.p2p_bind.parse().expect(&format!(
             "Failed to parse socket: {}",
             &self.config.node.p2p_bind
@@ -513,6 +530,22 @@ impl Node {
                 )
                 .unwrap();
             }
+            for onion_host in self.config.node.onion_hosts.iter() {
+                PeerDB::set_onion_host(&mut tx, onion_host).unwrap();
+            }
+            let opts = &self.config.connection_options;
+            for (prefix, mask) in opts.allow_cidrs.iter() {
+                PeerDB::add_allow_cidr(&mut tx, prefix, *mask).unwrap();
+            }
+            for (prefix, mask) in opts.deny_cidrs.iter() {
+                PeerDB::add_deny_cidr(&mut tx, prefix, *mask).unwrap();
+            }
+            for asn in opts.allow_asns.iter() {
+                PeerDB::add_allow_asn(&mut tx, *asn).unwrap();
+            }
+            for asn in opts.deny_asns.iter() {
+                PeerDB::add_deny_asn(&mut tx, *asn).unwrap();
+            }
             tx.commit().unwrap();
         }
         let atlas_config = AtlasConfig::default(false);
@@ -542,7 +575,7 @@ impl Node {
             self.config.burnchain.chain_id,
             p2p_net,
             &p2p_sock,
-            &rpc_sock,
+            &rpc_bind,
             self.config.get_burn_db_file_path(),
             self.config.get_chainstate_path_str(),
             event_dispatcher,
@@ -2,7 +2,9 @@ use stacks::util::secp256k1::{MessageSignature, Secp256k1PrivateKey, Secp256k1Pu
 use stacks::{burnchains::PrivateKey, util::hash::hex_bytes};
 
 pub struct BurnchainOpSigner {
-    secret_key: Secp256k1PrivateKey,
+    /// None if the key is held outside the node, and bitcoind signs for us
+    secret_key: Option<Secp256k1PrivateKey>,
+    public_key: Secp256k1PublicKey,
     is_one_off: bool,
     is_disposed: bool,
     usages: u8,
@@ -11,27 +13,50 @@ pub struct BurnchainOpSigner {
 impl BurnchainOpSigner {
     pub fn new(secret_key: Secp256k1PrivateKey, is_one_off: bool) -> BurnchainOpSigner {
         BurnchainOpSigner {
-            secret_key,
+            public_key: Secp256k1PublicKey::from_private(&secret_key),
+            secret_key: Some(secret_key),
             usages: 0,
             is_one_off,
             is_disposed: false,
         }
     }
 
+    /// A signer for a key we don't have, such as one on a hardware signer attached to bitcoind.
+    /// Transactions for it must be signed by bitcoind's wallet as PSBTs.
+    pub fn new_external(public_key: Secp256k1PublicKey) -> BurnchainOpSigner {
+        BurnchainOpSigner {
+            secret_key: None,
+            public_key,
+            usages: 0,
+            is_one_off: false,
+            is_disposed: false,
+        }
+    }
+
+    /// Is the key held outside the node?
+    pub fn is_external(&self) -> bool {
+        self.secret_key.is_none()
+    }
+
+    fn get_secret_key(&self) -> &Secp256k1PrivateKey {
+        self.secret_key
+            .as_ref()
+            .expect("BUG: burnchain op signer has no secret key")
+    }
+
     pub fn get_sk_as_wif(&self) -> String {
-        let hex_encoded = self.secret_key.to_hex();
+        let hex_encoded = self.get_secret_key().to_hex();
         let mut as_bytes = hex_bytes(&hex_encoded).unwrap();
         as_bytes.insert(0, 0x80);
         stacks::address::b58::check_encode_slice(&as_bytes)
     }
 
     pub fn get_sk_as_hex(&self) -> String {
-        self.secret_key.to_hex()
+        self.get_secret_key().to_hex()
     }
 
     pub fn get_public_key(&mut self) -> Secp256k1PublicKey {
-        let public_key = Secp256k1PublicKey::from_private(&self.secret_key);
-        public_key
+        self.public_key.clone()
     }
 
     pub fn sign_message(&mut self, hash: &[u8]) -> Option<MessageSignature> {
@@ -39,7 +64,7 @@ impl BurnchainOpSigner {
             return None;
         }
 
-        let signature = match self.secret_key.sign(hash) {
+        let signature = match self.secret_key.as_ref()?.sign(hash) {
             Ok(r) => r,
             _ => return None,
         };
@@ -59,7 +84,7 @@ impl BurnchainOpSigner {
 
 #[cfg(test)]
 mod test {
-    use stacks::util::secp256k1::Secp256k1PrivateKey;
+    use stacks::util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
 
     use super::BurnchainOpSigner;
 
@@ -75,4 +100,20 @@ mod test {
             assert_eq!(expected_wif, &op_signer.get_sk_as_wif());
         }
     }
+
+    #[test]
+    fn test_external_signer() {
+        let secp_k = Secp256k1PrivateKey::new();
+        let public_key = Secp256k1PublicKey::from_private(&secp_k);
+
+        let mut op_signer = BurnchainOpSigner::new(secp_k, false);
+        assert!(!op_signer.is_external());
+        assert_eq!(op_signer.get_public_key(), public_key);
+        assert!(op_signer.sign_message(&[0u8; 32]).is_some());
+
+        let mut op_signer = BurnchainOpSigner::new_external(public_key.clone());
+        assert!(op_signer.is_external());
+        assert_eq!(op_signer.get_public_key(), public_key);
+        assert!(op_signer.sign_message(&[0u8; 32]).is_none());
+    }
 }
@@ -30,12 +30,17 @@ macro_rules! info_green {
     })
 }
 
+type BurnChainInitializedCallback = Box<dyn Fn(&mut Box<dyn BurnchainController>) + Send>;
+type NewBurnChainStateCallback = Box<dyn Fn(u64, &BurnchainTip, &ChainTip) + Send>;
+type NewStacksChainStateCallback =
+    Box<dyn Fn(u64, &BurnchainTip, &ChainTip, &mut StacksChainState, &dyn BurnStateDB) + Send>;
+type NewTenureCallback = Box<dyn Fn(u64, &BurnchainTip, &ChainTip, &mut Tenure) + Send>;
+
 pub struct RunLoopCallbacks {
-    on_burn_chain_initialized: Option<fn(&mut Box<dyn BurnchainController>)>,
-    on_new_burn_chain_state: Option<fn(u64, &BurnchainTip, &ChainTip)>,
-    on_new_stacks_chain_state:
-        Option<fn(u64, &BurnchainTip, &ChainTip, &mut StacksChainState, &dyn BurnStateDB)>,
-    on_new_tenure: Option<fn(u64, &BurnchainTip, &ChainTip, &mut Tenure)>,
+    on_burn_chain_initialized: Option<BurnChainInitializedCallback>,
+    on_new_burn_chain_state: Option<NewBurnChainStateCallback>,
+    on_new_stacks_chain_state: Option<NewStacksChainStateCallback>,
+    on_new_tenure: Option<NewTenureCallback>,
 }
 
 impl RunLoopCallbacks {
@@ -48,27 +53,38 @@ impl RunLoopCallbacks {
         }
     }
 
-    pub fn on_burn_chain_initialized(&mut self, callback: fn(&mut Box<dyn BurnchainController>)) {
-        self.on_burn_chain_initialized = Some(callback);
+    pub fn on_burn_chain_initialized<F>(&mut self, callback: F)
+    where
+        F: Fn(&mut Box<dyn BurnchainController>) + Send + 'static,
+    {
+        self.on_burn_chain_initialized = Some(Box::new(callback));
     }
 
-    pub fn on_new_burn_chain_state(&mut self, callback: fn(u64, &BurnchainTip, &ChainTip)) {
-        self.on_new_burn_chain_state = Some(callback);
+    pub fn on_new_burn_chain_state<F>(&mut self, callback: F)
+    where
+        F: Fn(u64, &BurnchainTip, &ChainTip) + Send + 'static,
+    {
+        self.on_new_burn_chain_state = Some(Box::new(callback));
     }
 
-    pub fn on_new_stacks_chain_state(
-        &mut self,
-        callback: fn(u64, &BurnchainTip, &ChainTip, &mut StacksChainState, &dyn BurnStateDB),
-    ) {
-        self.on_new_stacks_chain_state = Some(callback);
+    pub fn on_new_stacks_chain_state<F>(&mut self, callback: F)
+    where
+        F: Fn(u64, &BurnchainTip, &ChainTip, &mut StacksChainState, &dyn BurnStateDB)
+            + Send
+            + 'static,
+    {
+        self.on_new_stacks_chain_state = Some(Box::new(callback));
     }
 
-    pub fn on_new_tenure(&mut self, callback: fn(u64, &BurnchainTip, &ChainTip, &mut Tenure)) {
-        self.on_new_tenure = Some(callback);
+    pub fn on_new_tenure<F>(&mut self, callback: F)
+    where
+        F: Fn(u64, &BurnchainTip, &ChainTip, &mut Tenure) + Send + 'static,
+    {
+        self.on_new_tenure = Some(Box::new(callback));
     }
 
     pub fn invoke_burn_chain_initialized(&self, burnchain: &mut Box<dyn BurnchainController>) {
-        if let Some(cb) = self.on_burn_chain_initialized {
+        if let Some(cb) = &self.on_burn_chain_initialized {
             cb(burnchain);
         }
     }
@@ -86,7 +102,7 @@ impl RunLoopCallbacks {
             burnchain_tip.block_snapshot.sortition_hash
         );
 
-        if let Some(cb) = self.on_new_burn_chain_state {
+        if let Some(cb) = &self.on_new_burn_chain_state {
             cb(round, burnchain_tip, chain_tip);
         }
     }
@@ -123,7 +139,7 @@ impl RunLoopCallbacks {
             }
         }
 
-        if let Some(cb) = self.on_new_stacks_chain_state {
+        if let Some(cb) = &self.on_new_stacks_chain_state {
             cb(round, burnchain_tip, chain_tip, chain_state, burn_dbconn);
         }
     }
@@ -135,7 +151,7 @@ impl RunLoopCallbacks {
         chain_tip: &ChainTip,
         tenure: &mut Tenure,
     ) {
-        if let Some(cb) = self.on_new_tenure {
+        if let Some(cb) = &self.on_new_tenure {
             cb(round, burnchain_tip, chain_tip, tenure);
         }
     }
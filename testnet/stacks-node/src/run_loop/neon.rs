@@ -7,6 +7,7 @@ use std::sync::atomic::AtomicU64;
 use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::Receiver;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::thread;
 use std::thread::JoinHandle;
 
@@ -24,10 +25,16 @@ use stacks::chainstate::coordinator::{
     check_chainstate_db_versions, BlockEventDispatcher, ChainsCoordinator, CoordinatorCommunication,
 };
 use stacks::chainstate::stacks::db::{ChainStateBootData, StacksChainState};
+use stacks::chainstate::stacks::index::blobs::{open_blob_store, ExternalTrieBlobs, TrieBlobCache};
 use stacks::net::atlas::{AtlasConfig, Attachment, AttachmentInstance};
+use stacks::net::ws::{websocket_event_channel, WebSocketEventReceiver};
+use stacks::util::get_epoch_time_secs;
 use stx_genesis::GenesisData;
 
-use crate::monitoring::start_serving_monitoring_metrics;
+use crate::burnchains::bitcoin_regtest_controller::start_new_block_listener;
+use crate::monitoring::{
+    start_otlp_metrics, start_serving_monitoring_metrics, start_statsd_metrics,
+};
 use crate::neon_node::StacksNode;
 use crate::node::use_test_genesis_chainstate;
 use crate::syncctl::{PoxSyncWatchdog, PoxSyncWatchdogComms};
@@ -127,9 +134,11 @@ pub struct RunLoop {
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
     should_keep_running: Arc<AtomicBool>,
     event_dispatcher: EventDispatcher,
+    websocket_events: Mutex<Option<WebSocketEventReceiver>>, // taken by the p2p thread
     pox_watchdog: Option<PoxSyncWatchdog>, // can't be instantiated until .start() is called
     is_miner: Option<bool>,                // not known until .start() is called
     burnchain: Option<Burnchain>,          // not known until .start() is called
+    marf_blobs: Option<Arc<ExternalTrieBlobs>>, // not known until .start() is called
 }
 
 /// Write to stderr in an async-safe manner.
@@ -160,6 +169,8 @@ impl RunLoop {
         for observer in config.events_observers.iter() {
             event_dispatcher.register_observer(observer, should_keep_running.clone());
         }
+        let (websocket_send, websocket_recv) = websocket_event_channel();
+        event_dispatcher.register_websocket_sender(websocket_send);
 
         Self {
             config,
@@ -168,9 +179,11 @@ impl RunLoop {
             counters: Counters::new(),
             should_keep_running: should_keep_running,
             event_dispatcher,
+            websocket_events: Mutex::new(Some(websocket_recv)),
             pox_watchdog: None,
             is_miner: None,
             burnchain: None,
+            marf_blobs: None,
         }
     }
 
@@ -210,6 +223,21 @@ impl RunLoop {
         self.event_dispatcher.clone()
     }
 
+    /// Take the receiving end of the event dispatcher's WebSocket event channel, so the RPC
+    /// server can publish events to its WebSocket clients.  Only the first caller gets it.
+    pub fn take_websocket_event_receiver(&self) -> Option<WebSocketEventReceiver> {
+        self.websocket_events
+            .lock()
+            .expect("FATAL: websocket event receiver lock poisoned")
+            .take()
+    }
+
+    /// Where the Clarity state MARF's offloaded tries live, if the node offloads them.  Every
+    /// chainstate the node opens must use these (see `StacksChainState::set_clarity_state_blobs`).
+    pub fn get_marf_blobs(&self) -> Option<Arc<ExternalTrieBlobs>> {
+        self.marf_blobs.clone()
+    }
+
     pub fn is_miner(&self) -> bool {
         self.is_miner.unwrap_or(false)
     }
@@ -269,7 +297,7 @@ impl RunLoop {
     /// If there's a network error, then assume that we're not a miner.
     fn check_is_miner(&mut self, burnchain: &mut BitcoinRegtestController) -> bool {
         if self.config.node.miner {
-            let keychain = Keychain::default(self.config.node.seed.clone());
+            let keychain = Keychain::from_config(&self.config);
             let node_address = Keychain::address_from_burnchain_signer(
                 &keychain.get_burnchain_signer(),
                 self.config.is_mainnet(),
@@ -422,15 +450,21 @@ impl RunLoop {
                 get_namespaces(use_test_genesis_data)
             })),
             get_bulk_initial_names: Some(Box::new(move || get_names(use_test_genesis_data))),
+            boot_contract_overrides: self.config.node.boot_contract_overrides.clone(),
         };
 
-        let (chain_state_db, receipts) = StacksChainState::open_and_exec(
+        let (mut chain_state_db, receipts) = StacksChainState::open_and_exec(
             self.config.is_mainnet(),
             self.config.burnchain.chain_id,
             &self.config.get_chainstate_path_str(),
             Some(&mut boot_data),
         )
         .unwrap();
+        chain_state_db.address_tx_index = self.config.node.address_tx_index;
+        chain_state_db.address_asset_index = self.config.node.address_asset_index;
+        chain_state_db.rosetta_index = self.config.node.rosetta;
+        chain_state_db.set_clarity_state_blobs(self.get_marf_blobs());
+        chain_state_db.set_cost_overrides(self.config.node.cost_overrides.clone());
         self.event_dispatcher.dispatch_boot_receipts(receipts);
 
         // NOTE: re-instantiate AtlasConfig so we don't have to keep the genesis attachments around
@@ -478,6 +512,148 @@ impl RunLoop {
         self.pox_watchdog = Some(pox_watchdog);
     }
 
+    /// If the node is configured to, long-poll bitcoind for new blocks so the PoX watchdog can
+    /// sync the burnchain as soon as one arrives.
+    fn start_new_block_listener(&mut self) {
+        if !self.config.burnchain.wait_for_new_blocks {
+            return;
+        }
+        info!("Waiting for new blocks from bitcoind instead of polling");
+        start_new_block_listener(
+            self.config.clone(),
+            self.get_pox_watchdog().make_comms_handle(),
+        );
+    }
+
+    /// If the node is configured with a MARF blob store, open it for the Clarity state MARF
+    /// (which must happen before the chainstate is first opened), and start a thread which
+    /// periodically moves buried tries out to it.
+    fn start_marf_offloading(&mut self) {
+        let store_url = match self.config.node.marf_blob_store.as_ref() {
+            Some(url) => url.clone(),
+            None => return,
+        };
+
+        let chainstate_path = self.config.get_chainstate_path();
+        let cache_dir = match self.config.node.marf_blob_cache_dir.as_ref() {
+            Some(dir) => dir.into(),
+            None => {
+                let mut dir = chainstate_path;
+                dir.push("marf-blob-cache");
+                dir
+            }
+        };
+
+        let store = open_blob_store(&store_url).expect(&format!(
+            "FATAL: failed to open MARF blob store {}",
+            &store_url
+        ));
+        let cache =
+            TrieBlobCache::new(&cache_dir, self.config.node.marf_blob_cache_size).expect(&format!(
+                "FATAL: failed to open MARF blob cache in {}",
+                cache_dir.to_string_lossy()
+            ));
+        let marf_blobs = Arc::new(ExternalTrieBlobs::new(store, cache));
+        self.marf_blobs = Some(marf_blobs.clone());
+        info!("Offloading buried Clarity state tries to {}", &store_url);
+
+        let config = self.config.clone();
+        let keep_running = self.should_keep_running.clone();
+        thread::Builder::new()
+            .name("marf-offload".to_string())
+            .spawn(move || {
+                let interval = cmp::max(config.node.marf_offload_interval, 1);
+                let mut next_offload = get_epoch_time_secs() + interval;
+                while keep_running.load(Ordering::SeqCst) {
+                    if get_epoch_time_secs() < next_offload {
+                        thread::sleep(std::time::Duration::from_secs(1));
+                        continue;
+                    }
+                    next_offload = get_epoch_time_secs() + interval;
+
+                    let mut chainstate = match StacksChainState::open(
+                        config.is_mainnet(),
+                        config.burnchain.chain_id,
+                        &config.get_chainstate_path_str(),
+                    ) {
+                        Ok((chainstate, _)) => chainstate,
+                        Err(e) => {
+                            warn!("Failed to open chainstate to offload tries: {:?}", &e);
+                            continue;
+                        }
+                    };
+                    chainstate.set_clarity_state_blobs(Some(marf_blobs.clone()));
+                    match chainstate.offload_clarity_state_tries(config.node.marf_blob_keep_local) {
+                        Ok(0) => {}
+                        Ok(num_offloaded) => {
+                            info!("Offloaded {} Clarity state tries", num_offloaded)
+                        }
+                        Err(e) => warn!("Failed to offload Clarity state tries: {:?}", &e),
+                    }
+                }
+            })
+            .expect("FATAL: failed to start MARF offload thread");
+    }
+
+    /// Log each chainstate checkpoint (see `StacksChainState::get_chainstate_manifest`) as the
+    /// canonical Stacks chain reaches it, so operators can compare logs across nodes.
+    fn start_chainstate_manifest_logging(&mut self) {
+        let interval = self.config.node.chainstate_manifest_interval;
+        if interval == 0 {
+            return;
+        }
+
+        let config = self.config.clone();
+        let keep_running = self.should_keep_running.clone();
+        let marf_blobs = self.get_marf_blobs();
+        thread::Builder::new()
+            .name("chainstate-manifest".to_string())
+            .spawn(move || {
+                let (mut chainstate, _) = StacksChainState::open(
+                    config.is_mainnet(),
+                    config.burnchain.chain_id,
+                    &config.get_chainstate_path_str(),
+                )
+                .expect("FATAL: failed to open chainstate DB");
+                chainstate.set_clarity_state_blobs(marf_blobs);
+                let sortdb = SortitionDB::open(&config.get_burn_db_file_path(), false)
+                    .expect("FATAL: failed to open sortition DB");
+
+                let mut last_logged_height = 0;
+                let mut next_check = 0;
+                while keep_running.load(Ordering::SeqCst) {
+                    if get_epoch_time_secs() < next_check {
+                        thread::sleep(std::time::Duration::from_secs(1));
+                        continue;
+                    }
+                    next_check = get_epoch_time_secs() + 60;
+
+                    let manifest = match chainstate.get_chainstate_manifest(&sortdb, interval, 1) {
+                        Ok(manifest) => manifest,
+                        Err(e) => {
+                            debug!("Failed to compute chainstate manifest: {:?}", &e);
+                            continue;
+                        }
+                    };
+                    if let Some(checkpoint) = manifest.checkpoints.last() {
+                        if checkpoint.block_height <= last_logged_height {
+                            continue;
+                        }
+                        last_logged_height = checkpoint.block_height;
+                        info!("Chainstate checkpoint";
+                              "block_height" => checkpoint.block_height,
+                              "index_block_hash" => %checkpoint.index_block_hash,
+                              "index_root" => %checkpoint.index_root,
+                              "state_index_root" => %checkpoint.state_index_root,
+                              "consensus_hash" => %checkpoint.consensus_hash,
+                              "sortition_root" => %checkpoint.sortition_root,
+                              "digest" => %checkpoint.digest);
+                    }
+                }
+            })
+            .expect("FATAL: failed to start chainstate manifest thread");
+    }
+
     /// Start Prometheus logging
     fn start_prometheus(&mut self) {
         let prometheus_bind = self.config.node.prometheus_bind.clone();
@@ -491,6 +667,56 @@ impl RunLoop {
         }
     }
 
+    /// Start the gRPC interface, if configured.  It forwards calls to the plaintext RPC listener,
+    /// so it can't be used alongside RPC TLS.
+    fn start_grpc(&mut self) {
+        let grpc_bind = match self.config.node.grpc_bind.as_ref() {
+            Some(grpc_bind) => grpc_bind,
+            None => return,
+        };
+        if self.config.connection_options.rpc_tls_cert_file.is_some() {
+            error!("The gRPC interface is not available when the RPC interface uses TLS");
+            return;
+        }
+        #[cfg(feature = "grpc")]
+        {
+            let bind = grpc_bind
+                .parse()
+                .expect(&format!("Failed to parse gRPC bind address {}", grpc_bind));
+            let rpc_bind = match self.config.node.get_rpc_bind() {
+                crate::config::RpcBind::Tcp(rpc_bind) => rpc_bind,
+                crate::config::RpcBind::Unix(_) => {
+                    error!("The gRPC interface is not available when the RPC interface is on a unix socket");
+                    return;
+                }
+            };
+            if let Err(e) = crate::grpc::start_grpc_server(bind, rpc_bind) {
+                error!("{}", e);
+            }
+        }
+        #[cfg(not(feature = "grpc"))]
+        error!(
+            "Cannot serve gRPC on {}: requires building with the `grpc` feature",
+            grpc_bind
+        );
+    }
+
+    /// Start sending metrics to any configured statsd agent or OTLP collector
+    fn start_metrics_backends(&mut self) {
+        if let Some(endpoint) = self.config.node.statsd_endpoint.as_ref() {
+            if let Err(e) =
+                start_statsd_metrics(endpoint, self.config.node.statsd_prefix.as_deref())
+            {
+                error!("{}", e);
+            }
+        }
+        if let Some(endpoint) = self.config.node.otlp_endpoint.as_ref() {
+            if let Err(e) = start_otlp_metrics(endpoint, self.config.node.otlp_interval) {
+                error!("{}", e);
+            }
+        }
+    }
+
     /// Get the sortition DB's highest block height
     fn get_sortition_db_height(sortdb: &SortitionDB, burnchain_config: &Burnchain) -> u64 {
         let sortition_db_height = {
@@ -529,6 +755,7 @@ impl RunLoop {
             .expect("Run loop already started, can only start once after initialization.");
 
         self.setup_termination_handler();
+        self.start_marf_offloading();
         let mut burnchain =
             self.instantiate_burnchain_state(burnchain_opt, coordinator_senders.clone());
 
@@ -542,6 +769,8 @@ impl RunLoop {
         let (coordinator_thread_handle, attachments_rx) =
             self.spawn_chains_coordinator(&burnchain_config, coordinator_receivers);
         self.instantiate_pox_watchdog();
+        self.start_new_block_listener();
+        self.start_chainstate_manifest_logging();
 
         // We announce a new burn block so that the chains coordinator
         // can resume prior work and handle eventual unprocessed sortitions
@@ -567,6 +796,8 @@ impl RunLoop {
         // Start the runloop
         debug!("Begin run loop");
         self.start_prometheus();
+        self.start_grpc();
+        self.start_metrics_backends();
         self.counters.bump_blocks_processed();
 
         let mut burnchain_height = sortition_db_height;
@@ -0,0 +1,630 @@
+//! End-to-end test scenarios, run with `stacks-node test-scenario <file>`.
+//!
+//! A scenario file (TOML, or JSON if its name ends in `.json`) lists a set of funded accounts
+//! and a sequence of steps.  The runner boots a mocknet node, and for each step it submits the
+//! step's transactions, mines the step's blocks, and then checks the step's expectations:
+//!
+//! ```toml
+//! [[accounts]]
+//! name = "alice"
+//! secret_key = "b8d99fd45da58038d630d9855d3ca2466e8e0f89d3894c4724f0efc9ff4b51f001"
+//! balance = 100000000
+//!
+//! [[steps]]
+//! [[steps.deploy]]
+//! sender = "alice"
+//! name = "counter"
+//! path = "contracts/counter.clar"
+//!
+//! [[steps]]
+//! blocks = 2
+//! [[steps.call]]
+//! sender = "alice"
+//! contract = "alice.counter"
+//! function = "increment"
+//! args = ["u1"]
+//! expect = "(ok u1)"
+//! [[steps.read]]
+//! contract = "alice.counter"
+//! expression = "(get-count)"
+//! expect = "u1"
+//! [[steps.event]]
+//! contract = "alice.counter"
+//! value = "{ event: \"increment\", count: u1 }"
+//! ```
+//!
+//! Contracts are named `<account name>.<contract name>` (or by their full identifier), and
+//! arguments and expected values are Clarity expressions.  Values are compared by their Clarity
+//! representation.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use stacks::address::AddressHashMode;
+use stacks::burnchains::Txid;
+use stacks::chainstate::stacks::db::blocks::MINIMUM_TX_FEE_RATE_PER_BYTE;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::chainstate::stacks::events::{SmartContractEventData, StacksTransactionEvent};
+use stacks::chainstate::stacks::{
+    StacksPrivateKey, StacksPublicKey, StacksTransaction, StacksTransactionSigner,
+    TokenTransferMemo, TransactionAnchorMode, TransactionAuth, TransactionContractCall,
+    TransactionPayload, TransactionPostConditionMode, TransactionSmartContract,
+    TransactionSpendingCondition, TransactionVersion, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use stacks::clarity::vm_execute;
+use stacks::codec::StacksMessageCodec;
+use stacks::core::{StacksEpochId, CHAIN_ID_TESTNET};
+use stacks::types::chainstate::StacksAddress;
+use stacks::util::strings::StacksString;
+use stacks::vm::costs::ExecutionCost;
+use stacks::vm::database::BurnStateDB;
+use stacks::vm::types::{PrincipalData, QualifiedContractIdentifier};
+use stacks::vm::{ContractName, Value};
+
+use crate::{helium, ChainTip, Config, Tenure};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub accounts: Vec<ScenarioAccount>,
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioAccount {
+    pub name: String,
+    /// Hex-encoded secret key
+    pub secret_key: String,
+    /// Initial balance, in microSTX
+    pub balance: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioStep {
+    /// Number of blocks to mine in this step.  Transactions are mined in the first one, and reads
+    /// and events are checked after the last one.
+    #[serde(default = "default_step_blocks")]
+    pub blocks: u64,
+    #[serde(default)]
+    pub deploy: Vec<DeployContract>,
+    #[serde(default)]
+    pub call: Vec<CallContract>,
+    #[serde(default)]
+    pub transfer: Vec<TransferStx>,
+    #[serde(default)]
+    pub read: Vec<ReadExpectation>,
+    #[serde(default)]
+    pub event: Vec<EventExpectation>,
+}
+
+fn default_step_blocks() -> u64 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeployContract {
+    pub sender: String,
+    pub name: String,
+    /// The contract's source, or the path of a file with the contract's source (relative to the
+    /// scenario file)
+    pub source: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallContract {
+    pub sender: String,
+    pub contract: String,
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The expected result of the call
+    pub expect: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferStx {
+    pub sender: String,
+    /// An account name, or a principal
+    pub recipient: String,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadExpectation {
+    pub contract: String,
+    /// A read-only expression, evaluated in the context of `contract`
+    pub expression: String,
+    pub expect: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventExpectation {
+    pub contract: String,
+    /// The value that `contract` is expected to `print` during the step
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioFailure {
+    /// Index of the step, starting at 0
+    pub step: usize,
+    pub check: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioReport {
+    pub passed: bool,
+    pub blocks: u64,
+    /// The address of each account
+    pub accounts: BTreeMap<String, String>,
+    pub failures: Vec<ScenarioFailure>,
+}
+
+/// A submitted transaction whose receipt has to be checked
+struct PendingTransaction {
+    txid: Txid,
+    check: String,
+    expect: Option<Value>,
+}
+
+/// A transaction with the name of its check and its expected result, or the name of the check
+/// and the reason the transaction could not be built
+type BuiltTransaction = Result<(String, StacksTransaction, Option<Value>), (String, String)>;
+
+/// State shared by the run loop callbacks while a scenario runs
+struct ScenarioRun {
+    steps: Vec<ScenarioStep>,
+    keys: HashMap<String, StacksPrivateKey>,
+    addresses: HashMap<String, StacksAddress>,
+    nonces: HashMap<String, u64>,
+    /// The first and last round of each step
+    rounds: Vec<(u64, u64)>,
+    pending: Vec<PendingTransaction>,
+    events: Vec<SmartContractEventData>,
+    failures: Vec<ScenarioFailure>,
+}
+
+impl Scenario {
+    /// Load a scenario, and the contract sources it refers to
+    pub fn from_path(path: &str) -> Result<Scenario, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scenario {}: {}", path, e))?;
+        let mut scenario: Scenario = if path.ends_with(".json") {
+            serde_json::from_str(&content).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&content).map_err(|e| e.to_string())?
+        };
+        scenario.validate()?;
+
+        let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+        for deploy in scenario
+            .steps
+            .iter_mut()
+            .flat_map(|step| step.deploy.iter_mut())
+        {
+            if let (None, Some(contract_path)) = (&deploy.source, &deploy.path) {
+                let contract_path = base_dir.join(contract_path);
+                let source = fs::read_to_string(&contract_path).map_err(|e| {
+                    format!("Failed to read contract {}: {}", contract_path.display(), e)
+                })?;
+                deploy.source = Some(source);
+            }
+        }
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut names = HashMap::new();
+        for account in self.accounts.iter() {
+            StacksPrivateKey::from_hex(&account.secret_key)
+                .map_err(|_| format!("Invalid secret key for account {}", account.name))?;
+            if names.insert(account.name.as_str(), ()).is_some() {
+                return Err(format!("Duplicate account {}", account.name));
+            }
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.blocks == 0 {
+                return Err(format!("Step {} must mine at least one block", i));
+            }
+            let senders = step
+                .deploy
+                .iter()
+                .map(|deploy| &deploy.sender)
+                .chain(step.call.iter().map(|call| &call.sender))
+                .chain(step.transfer.iter().map(|transfer| &transfer.sender));
+            for sender in senders {
+                if !names.contains_key(sender.as_str()) {
+                    return Err(format!("Step {} refers to unknown account {}", i, sender));
+                }
+            }
+            for deploy in step.deploy.iter() {
+                if deploy.source.is_some() == deploy.path.is_some() {
+                    return Err(format!(
+                        "Step {}: deployment of {} must have exactly one of `source` or `path`",
+                        i, deploy.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the scenario on a mocknet node configured by `conf`, and report the failed checks.
+    /// The scenario's accounts are funded in addition to `conf`'s initial balances.
+    pub fn run(self, mut conf: Config) -> Result<ScenarioReport, String> {
+        let mut keys = HashMap::new();
+        let mut addresses = HashMap::new();
+        for account in self.accounts.iter() {
+            let secret_key = StacksPrivateKey::from_hex(&account.secret_key)
+                .map_err(|_| format!("Invalid secret key for account {}", account.name))?;
+            let address = StacksAddress::from_public_keys(
+                C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                &AddressHashMode::SerializeP2PKH,
+                1,
+                &vec![StacksPublicKey::from_private(&secret_key)],
+            )
+            .expect("Failed to make address from public key");
+            conf.add_initial_balance(address.to_string(), account.balance);
+            keys.insert(account.name.clone(), secret_key);
+            addresses.insert(account.name.clone(), address);
+        }
+
+        // round 0 mines the first block on top of the boot block, so steps start at round 1
+        let mut rounds = vec![];
+        let mut next_round = 1;
+        for step in self.steps.iter() {
+            rounds.push((next_round, next_round + step.blocks - 1));
+            next_round += step.blocks;
+        }
+
+        let state = Arc::new(Mutex::new(ScenarioRun {
+            steps: self.steps,
+            keys,
+            addresses,
+            nonces: HashMap::new(),
+            rounds,
+            pending: vec![],
+            events: vec![],
+            failures: vec![],
+        }));
+
+        let mut run_loop = helium::RunLoop::new(conf);
+        let tenure_state = state.clone();
+        run_loop
+            .callbacks
+            .on_new_tenure(move |round, _burnchain_tip, chain_tip, tenure| {
+                tenure_state
+                    .lock()
+                    .unwrap()
+                    .submit_transactions(round, chain_tip, tenure)
+            });
+        let chain_state = state.clone();
+        run_loop.callbacks.on_new_stacks_chain_state(
+            move |round, _burnchain_tip, chain_tip, chainstate, burn_dbconn| {
+                chain_state
+                    .lock()
+                    .unwrap()
+                    .check_block(round, chain_tip, chainstate, burn_dbconn)
+            },
+        );
+        run_loop
+            .start(next_round)
+            .map_err(|e| format!("Run loop exited: {}", e))?;
+        drop(run_loop);
+
+        let state = Arc::try_unwrap(state)
+            .ok()
+            .expect("Scenario state is still shared")
+            .into_inner()
+            .unwrap();
+        let accounts = state
+            .addresses
+            .iter()
+            .map(|(name, address)| (name.clone(), address.to_string()))
+            .collect();
+        Ok(ScenarioReport {
+            passed: state.failures.is_empty(),
+            blocks: next_round - 1,
+            accounts,
+            failures: state.failures,
+        })
+    }
+}
+
+impl ScenarioRun {
+    fn fail(&mut self, step: usize, check: &str, message: String) {
+        self.failures.push(ScenarioFailure {
+            step,
+            check: check.to_string(),
+            message,
+        });
+    }
+
+    /// Resolve `<account name>.<contract name>`, or a full contract identifier
+    fn resolve_contract(&self, contract: &str) -> Result<QualifiedContractIdentifier, String> {
+        let mut parts = contract.splitn(2, '.');
+        let contract = match (parts.next(), parts.next()) {
+            (Some(account), Some(name)) => match self.addresses.get(account) {
+                Some(address) => format!("{}.{}", address, name),
+                None => contract.to_string(),
+            },
+            _ => contract.to_string(),
+        };
+        QualifiedContractIdentifier::parse(&contract)
+            .map_err(|_| format!("Invalid contract identifier {}", contract))
+    }
+
+    /// Resolve an account name, or a principal
+    fn resolve_principal(&self, principal: &str) -> Result<PrincipalData, String> {
+        match self.addresses.get(principal) {
+            Some(address) => Ok((*address).into()),
+            None => PrincipalData::parse(principal)
+                .map_err(|_| format!("Invalid principal {}", principal)),
+        }
+    }
+
+    fn make_transaction(&mut self, sender: &str, payload: TransactionPayload) -> StacksTransaction {
+        let secret_key = &self.keys[sender];
+        let nonce = self.nonces.entry(sender.to_string()).or_insert(0);
+
+        let mut spending_condition = TransactionSpendingCondition::new_singlesig_p2pkh(
+            StacksPublicKey::from_private(secret_key),
+        )
+        .expect("Failed to create p2pkh spending condition from public key.");
+        spending_condition.set_nonce(*nonce);
+        *nonce += 1;
+
+        let mut unsigned_tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::Standard(spending_condition),
+            payload,
+        );
+        unsigned_tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
+        unsigned_tx.post_condition_mode = TransactionPostConditionMode::Allow;
+        unsigned_tx.chain_id = CHAIN_ID_TESTNET;
+        // the signature is already accounted for in the unsigned transaction's length
+        let tx_len = unsigned_tx.serialize_to_vec().len() as u64;
+        unsigned_tx.set_tx_fee(tx_len * MINIMUM_TX_FEE_RATE_PER_BYTE);
+
+        let mut tx_signer = StacksTransactionSigner::new(&unsigned_tx);
+        tx_signer.sign_origin(secret_key).unwrap();
+        tx_signer.get_tx().unwrap()
+    }
+
+    fn build_transactions(&mut self, step: &ScenarioStep) -> Vec<BuiltTransaction> {
+        let mut transactions = vec![];
+        for deploy in step.deploy.iter() {
+            let check = format!("deploy {}", deploy.name);
+            let payload = ContractName::try_from(deploy.name.clone())
+                .map_err(|_| format!("Invalid contract name {}", deploy.name))
+                .and_then(|name| {
+                    let code_body = StacksString::from_string(deploy.source.as_ref().unwrap())
+                        .ok_or_else(|| "Contract source is not valid ASCII".to_string())?;
+                    Ok(TransactionPayload::SmartContract(
+                        TransactionSmartContract { name, code_body },
+                    ))
+                });
+            transactions.push(match payload {
+                Ok(payload) => Ok((
+                    check,
+                    self.make_transaction(&deploy.sender, payload),
+                    Some(Value::okay_true()),
+                )),
+                Err(e) => Err((check, e)),
+            });
+        }
+        for call in step.call.iter() {
+            let check = format!("call {} {}", call.contract, call.function);
+            let payload = self.resolve_contract(&call.contract).and_then(|contract| {
+                let function_args = call
+                    .args
+                    .iter()
+                    .map(|arg| eval_value(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(TransactionPayload::ContractCall(TransactionContractCall {
+                    address: contract.issuer.into(),
+                    contract_name: contract.name,
+                    function_name: call
+                        .function
+                        .clone()
+                        .try_into()
+                        .map_err(|_| format!("Invalid function name {}", call.function))?,
+                    function_args,
+                }))
+            });
+            let expect = match call.expect.as_ref().map(|expect| eval_value(expect)) {
+                Some(Err(e)) => Err(e),
+                Some(Ok(expect)) => Ok(Some(expect)),
+                None => Ok(None),
+            };
+            transactions.push(match (payload, expect) {
+                (Ok(payload), Ok(expect)) => {
+                    Ok((check, self.make_transaction(&call.sender, payload), expect))
+                }
+                (Err(e), _) | (_, Err(e)) => Err((check, e)),
+            });
+        }
+        for transfer in step.transfer.iter() {
+            let check = format!("transfer {} to {}", transfer.amount, transfer.recipient);
+            transactions.push(match self.resolve_principal(&transfer.recipient) {
+                Ok(recipient) => {
+                    let payload = TransactionPayload::TokenTransfer(
+                        recipient,
+                        transfer.amount,
+                        TokenTransferMemo([0; 34]),
+                    );
+                    Ok((
+                        check,
+                        self.make_transaction(&transfer.sender, payload),
+                        Some(Value::okay_true()),
+                    ))
+                }
+                Err(e) => Err((check, e)),
+            });
+        }
+        transactions
+    }
+
+    fn submit_transactions(&mut self, round: u64, chain_tip: &ChainTip, tenure: &mut Tenure) {
+        let step_index = match self.rounds.iter().position(|(first, _)| *first == round) {
+            Some(step_index) => step_index,
+            None => return,
+        };
+        let step = self.steps[step_index].clone();
+        let transactions = self.build_transactions(&step);
+        if transactions.is_empty() {
+            return;
+        }
+
+        let mut chainstate = tenure.open_chainstate();
+        let consensus_hash = chain_tip.metadata.consensus_hash;
+        let block_hash = chain_tip.block.block_hash();
+        for transaction in transactions {
+            let (check, tx, expect) = match transaction {
+                Ok(transaction) => transaction,
+                Err((check, message)) => {
+                    self.fail(step_index, &check, message);
+                    continue;
+                }
+            };
+            let txid = tx.txid();
+            match tenure.mem_pool.submit_raw(
+                &mut chainstate,
+                &consensus_hash,
+                &block_hash,
+                tx.serialize_to_vec(),
+                &ExecutionCost::max_value(),
+                &StacksEpochId::Epoch20,
+            ) {
+                Ok(()) => self.pending.push(PendingTransaction {
+                    txid,
+                    check,
+                    expect,
+                }),
+                Err(e) => self.fail(
+                    step_index,
+                    &check,
+                    format!("Transaction rejected by the mempool: {:?}", e),
+                ),
+            }
+        }
+    }
+
+    fn check_block(
+        &mut self,
+        round: u64,
+        chain_tip: &ChainTip,
+        chainstate: &mut StacksChainState,
+        burn_dbconn: &dyn BurnStateDB,
+    ) {
+        let step_index = match self
+            .rounds
+            .iter()
+            .position(|(first, last)| *first <= round && round <= *last)
+        {
+            Some(step_index) => step_index,
+            None => return,
+        };
+        let (first_round, last_round) = self.rounds[step_index];
+
+        for receipt in chain_tip.receipts.iter() {
+            for event in receipt.events.iter() {
+                if let StacksTransactionEvent::SmartContractEvent(event) = event {
+                    self.events.push(event.clone());
+                }
+            }
+        }
+
+        if round == first_round {
+            let pending = std::mem::take(&mut self.pending);
+            for tx in pending {
+                let receipt = chain_tip
+                    .receipts
+                    .iter()
+                    .find(|receipt| receipt.transaction.txid() == tx.txid);
+                match (receipt, tx.expect) {
+                    (None, _) => self.fail(
+                        step_index,
+                        &tx.check,
+                        format!("Transaction {} was not mined", tx.txid),
+                    ),
+                    (Some(receipt), Some(expect)) if !values_match(&expect, &receipt.result) => {
+                        self.fail(
+                            step_index,
+                            &tx.check,
+                            format!("Expected {}, got {}", expect, receipt.result),
+                        )
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if round != last_round {
+            return;
+        }
+
+        let tip = chain_tip.metadata.index_block_hash();
+        let step = self.steps[step_index].clone();
+        for read in step.read.iter() {
+            let check = format!("read {} {}", read.contract, read.expression);
+            let result = self.resolve_contract(&read.contract).and_then(|contract| {
+                let expect = eval_value(&read.expect)?;
+                let value = chainstate
+                    .clarity_eval_read_only_checked(burn_dbconn, &tip, &contract, &read.expression)
+                    .map_err(|e| format!("Failed to evaluate: {}", e))?;
+                if values_match(&expect, &value) {
+                    Ok(())
+                } else {
+                    Err(format!("Expected {}, got {}", expect, value))
+                }
+            });
+            if let Err(message) = result {
+                self.fail(step_index, &check, message);
+            }
+        }
+
+        let events = std::mem::take(&mut self.events);
+        for expected_event in step.event.iter() {
+            let check = format!("event {} {}", expected_event.contract, expected_event.value);
+            let result = self
+                .resolve_contract(&expected_event.contract)
+                .and_then(|contract| {
+                    let expect = eval_value(&expected_event.value)?;
+                    let printed = events.iter().any(|event| {
+                        event.key.0 == contract
+                            && event.key.1 == "print"
+                            && values_match(&expect, &event.value)
+                    });
+                    if printed {
+                        Ok(())
+                    } else {
+                        Err(format!("{} did not print {}", contract, expect))
+                    }
+                });
+            if let Err(message) = result {
+                self.fail(step_index, &check, message);
+            }
+        }
+    }
+}
+
+/// Evaluate a Clarity expression (without any chain state) to a value
+fn eval_value(expression: &str) -> Result<Value, String> {
+    match vm_execute(expression) {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Err(format!("`{}` does not evaluate to a value", expression)),
+        Err(e) => Err(format!("Failed to evaluate `{}`: {}", expression, e)),
+    }
+}
+
+/// Values are compared by their representation, so that e.g. a list literal matches a list
+/// returned by a contract even though their types' maximum lengths differ.
+fn values_match(expected: &Value, actual: &Value) -> bool {
+    expected.to_string() == actual.to_string()
+}
@@ -18,6 +18,9 @@ use std::sync::{
 // network if your node is actualy waiting a day in-between reward cycles).
 const SYNC_WAIT_SECS: u64 = 24 * 3600;
 
+// how often to check for a new burnchain block notification while waiting to re-sync the burnchain
+const NEW_BURN_BLOCK_POLL_MS: u64 = 100;
+
 #[derive(Clone)]
 pub struct PoxSyncWatchdogComms {
     /// how many passes in the p2p state machine have taken place since startup?
@@ -32,6 +35,8 @@ pub struct PoxSyncWatchdogComms {
     last_ibd: Arc<AtomicBool>,
     /// Should keep running?
     should_keep_running: Arc<AtomicBool>,
+    /// how many new burnchain blocks has the burnchain node told us about?
+    new_burn_blocks: Arc<AtomicU64>,
 }
 
 impl PoxSyncWatchdogComms {
@@ -43,6 +48,7 @@ impl PoxSyncWatchdogComms {
             burnchain_tip_height: Arc::new(AtomicU64::new(0)),
             last_ibd: Arc::new(AtomicBool::new(true)),
             should_keep_running,
+            new_burn_blocks: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -58,10 +64,20 @@ impl PoxSyncWatchdogComms {
         self.download_passes.load(Ordering::SeqCst)
     }
 
+    /// Height of the burnchain tip, as reported by the burnchain node (0 if not known yet)
+    pub fn get_burnchain_tip_height(&self) -> u64 {
+        self.burnchain_tip_height.load(Ordering::SeqCst)
+    }
+
     pub fn get_ibd(&self) -> bool {
         self.last_ibd.load(Ordering::SeqCst)
     }
 
+    /// Number of new-block notifications received from the burnchain node since startup
+    pub fn get_new_burn_blocks(&self) -> u64 {
+        self.new_burn_blocks.load(Ordering::SeqCst)
+    }
+
     /// Wait for at least one inv-sync state-machine passes
     pub fn wait_for_inv_sync_pass(&self, timeout: u64) -> Result<bool, burnchain_error> {
         let current = self.get_inv_sync_passes();
@@ -89,6 +105,22 @@ impl PoxSyncWatchdogComms {
         Ok(())
     }
 
+    /// Sleep for up to `secs` seconds, but wake up early if the burnchain node has told us about
+    /// a new block since we last saw `seen` notifications.  Returns true if we were woken up.
+    pub fn wait_for_new_burn_block(&self, seen: u64, secs: u64) -> Result<bool, burnchain_error> {
+        let deadline = get_epoch_time_secs() + secs;
+        while get_epoch_time_secs() < deadline {
+            if self.get_new_burn_blocks() != seen {
+                return Ok(true);
+            }
+            sleep_ms(NEW_BURN_BLOCK_POLL_MS);
+            if !self.should_keep_running() {
+                return Err(burnchain_error::CoordinatorClosed);
+            }
+        }
+        Ok(self.get_new_burn_blocks() != seen)
+    }
+
     pub fn wait_for_download_pass(&self, timeout: u64) -> Result<bool, burnchain_error> {
         let current = self.get_download_passes();
 
@@ -120,9 +152,18 @@ impl PoxSyncWatchdogComms {
         self.download_passes.fetch_add(1, Ordering::SeqCst);
     }
 
+    pub fn set_burnchain_tip_height(&mut self, value: u64) {
+        self.burnchain_tip_height.store(value, Ordering::SeqCst);
+    }
+
     pub fn set_ibd(&mut self, value: bool) {
         self.last_ibd.store(value, Ordering::SeqCst);
     }
+
+    /// Called when the burnchain node tells us it has a new block
+    pub fn notify_new_burn_block(&mut self) {
+        self.new_burn_blocks.fetch_add(1, Ordering::SeqCst);
+    }
 }
 
 /// Monitor the state of the Stacks blockchain as the peer network and relay threads download and
@@ -163,6 +204,8 @@ pub struct PoxSyncWatchdog {
     relayer_comms: PoxSyncWatchdogComms,
     /// should this sync watchdog always download? used in integration tests.
     unconditionally_download: bool,
+    /// how many new burnchain block notifications have we acted on?
+    burn_block_notifications_seen: u64,
 }
 
 const PER_SAMPLE_WAIT_MS: u64 = 1000;
@@ -204,6 +247,7 @@ impl PoxSyncWatchdog {
             steady_state_resync_ts: 0,
             chainstate: chainstate,
             relayer_comms: PoxSyncWatchdogComms::new(should_keep_running),
+            burn_block_notifications_seen: 0,
         })
     }
 
@@ -211,6 +255,19 @@ impl PoxSyncWatchdog {
         self.relayer_comms.clone()
     }
 
+    /// Wait out the steady-state burnchain sync interval, unless the burnchain node tells us
+    /// about a new block first.
+    fn wait_for_burnchain_resync(&mut self) -> Result<(), burnchain_error> {
+        if self.relayer_comms.wait_for_new_burn_block(
+            self.burn_block_notifications_seen,
+            self.steady_state_burnchain_sync_interval,
+        )? {
+            debug!("PoX watchdog: burnchain node announced a new block -- sync immediately");
+        }
+        self.burn_block_notifications_seen = self.relayer_comms.get_new_burn_blocks();
+        Ok(())
+    }
+
     /// How many recently-added Stacks blocks are in an attachable state, up to $max_staging?
     fn count_attachable_stacks_blocks(&mut self) -> Result<u64, String> {
         // number of staging blocks that have arrived since the last sortition
@@ -444,6 +501,8 @@ impl PoxSyncWatchdog {
                 return Ok(true);
             }
         };
+        self.relayer_comms
+            .set_burnchain_tip_height(burnchain_height);
 
         if self.watch_start_ts == 0 {
             self.watch_start_ts = get_epoch_time_secs();
@@ -466,8 +525,7 @@ impl PoxSyncWatchdog {
             debug!("PoX watchdog in first reward cycle -- sync immediately");
             self.relayer_comms.set_ibd(ibbd);
 
-            self.relayer_comms
-                .interruptable_sleep(self.steady_state_burnchain_sync_interval)?;
+            self.wait_for_burnchain_resync()?;
 
             return Ok(ibbd);
         }
@@ -511,8 +569,7 @@ impl PoxSyncWatchdog {
                 );
                 self.relayer_comms.set_ibd(ibbd);
 
-                self.relayer_comms
-                    .interruptable_sleep(self.steady_state_burnchain_sync_interval)?;
+                self.wait_for_burnchain_resync()?;
             } else {
                 debug!("PoX watchdog in last reward cycle -- sync immediately");
                 self.relayer_comms.set_ibd(ibbd);
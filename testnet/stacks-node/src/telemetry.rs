@@ -0,0 +1,136 @@
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread;
+
+use async_h1::client;
+use async_std::net::TcpStream;
+use http_types::{Method, Request, Url};
+
+use stacks::net::p2p::PeerNetwork;
+use stacks::net::RPCTelemetryData;
+use stacks::util::get_epoch_time_ms;
+use stacks::util::secp256k1::Secp256k1PrivateKey;
+
+// how many beacons can be queued up while the collector is unreachable
+const TELEMETRY_QUEUE_LEN: usize = 4;
+
+/// Opt-in beacon that periodically submits signed, anonymized node statistics to a collector.
+/// The payload is built by the p2p thread (which owns the `PeerNetwork`), and submitted by a
+/// background thread, so a slow or unreachable collector never stalls the node.
+pub struct TelemetryBeacon {
+    /// how often to send a beacon
+    interval_ms: u128,
+    /// when to send the next beacon
+    next_beacon_ms: u128,
+    /// queue to the submitter thread
+    sender: SyncSender<RPCTelemetryData>,
+}
+
+impl TelemetryBeacon {
+    /// Start up the submitter thread for the given collector URL.  The first beacon is sent on
+    /// the first call to `run()`.
+    pub fn spawn(collector_url: &str, interval_secs: u64) -> Result<TelemetryBeacon, String> {
+        let url = Url::parse(collector_url)
+            .map_err(|e| format!("Invalid telemetry URL {}: {:?}", collector_url, &e))?;
+        if url.scheme() != "http" {
+            return Err(format!(
+                "Invalid telemetry URL {}: only http:// is supported",
+                collector_url
+            ));
+        }
+
+        let (sender, receiver) = sync_channel(TELEMETRY_QUEUE_LEN);
+        thread::Builder::new()
+            .name("telemetry".to_string())
+            .spawn(move || TelemetryBeacon::submitter_main(url, receiver))
+            .map_err(|e| format!("Failed to start telemetry thread: {:?}", &e))?;
+
+        Ok(TelemetryBeacon {
+            interval_ms: (interval_secs as u128) * 1000,
+            next_beacon_ms: 0,
+            sender,
+        })
+    }
+
+    /// If it's time, build a beacon from the network's current view and queue it for submission
+    pub fn run(&mut self, network: &PeerNetwork, telemetry_key: &Secp256k1PrivateKey) {
+        if self.next_beacon_ms > get_epoch_time_ms() {
+            return;
+        }
+        self.next_beacon_ms = get_epoch_time_ms() + self.interval_ms;
+
+        let telemetry_data = match RPCTelemetryData::from_network(network, telemetry_key) {
+            Ok(telemetry_data) => telemetry_data,
+            Err(e) => {
+                warn!("Telemetry: failed to build beacon: {:?}", &e);
+                return;
+            }
+        };
+
+        match self.sender.try_send(telemetry_data) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!("Telemetry: submission queue is full; dropping beacon");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("Telemetry: submitter thread has exited");
+            }
+        }
+    }
+
+    fn submitter_main(url: Url, receiver: Receiver<RPCTelemetryData>) {
+        while let Ok(telemetry_data) = receiver.recv() {
+            TelemetryBeacon::submit(&url, &telemetry_data);
+        }
+        debug!("Telemetry: submitter thread exiting");
+    }
+
+    fn submit(url: &Url, telemetry_data: &RPCTelemetryData) {
+        let body = match serde_json::to_vec(telemetry_data) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Telemetry: failed to serialize beacon: {:?}", &e);
+                return;
+            }
+        };
+
+        let addr = match url.socket_addrs(|| Some(80)) {
+            Ok(addrs) if !addrs.is_empty() => addrs[0],
+            Ok(_) | Err(_) => {
+                warn!("Telemetry: failed to resolve {}", url);
+                return;
+            }
+        };
+
+        let mut req = Request::new(Method::Post, url.clone());
+        req.append_header("Content-Type", "application/json");
+        req.set_body(body);
+
+        let response = async_std::task::block_on(async {
+            let stream = match TcpStream::connect(addr).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("Telemetry: connection to {} failed - {:?}", url, err);
+                    return None;
+                }
+            };
+
+            match client::connect(stream, req).await {
+                Ok(response) => Some(response),
+                Err(err) => {
+                    warn!("Telemetry: POST to {} failed - {:?}", url, err);
+                    None
+                }
+            }
+        });
+
+        if let Some(response) = response {
+            if !response.status().is_success() {
+                warn!(
+                    "Telemetry: POST to {} failed with status {}",
+                    url,
+                    response.status()
+                );
+            }
+        }
+    }
+}
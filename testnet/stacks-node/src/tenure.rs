@@ -82,6 +82,7 @@ impl<'a> Tenure {
             &self.config.get_chainstate_path_str(),
         )
         .unwrap();
+        chain_state.set_cost_overrides(self.config.node.cost_overrides.clone());
 
         let (anchored_block, _, _) = StacksBlockBuilder::build_anchored_block(
             &mut chain_state,
@@ -108,16 +109,14 @@ impl<'a> Tenure {
         Some(artifact)
     }
 
-    #[cfg(test)]
     pub fn open_chainstate(&self) -> StacksChainState {
-        use stacks::core::CHAIN_ID_TESTNET;
-
-        let (chain_state, _) = StacksChainState::open(
-            false,
-            CHAIN_ID_TESTNET,
+        let (mut chain_state, _) = StacksChainState::open(
+            self.config.is_mainnet(),
+            self.config.burnchain.chain_id,
             &self.config.get_chainstate_path_str(),
         )
         .unwrap();
+        chain_state.set_cost_overrides(self.config.node.cost_overrides.clone());
         chain_state
     }
 }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fmt::Write;
+use std::fs;
 use std::sync::Mutex;
 
 use reqwest;
@@ -31,6 +32,7 @@ use stacks::vm::{
 
 use crate::config::InitialBalance;
 use crate::helium::RunLoop;
+use crate::scenario::Scenario;
 use crate::tests::make_sponsored_stacks_transfer_on_testnet;
 use stacks::core::StacksEpoch;
 use stacks::core::StacksEpochId;
@@ -2213,3 +2215,114 @@ fn mempool_errors() {
 
     run_loop.start(num_rounds).unwrap();
 }
+
+const SCENARIO_CONTRACT: &'static str = "
+  (define-data-var count uint u0)
+  (define-public (increment (by uint))
+    (begin
+      (var-set count (+ (var-get count) by))
+      (print { event: \"increment\", count: (var-get count) })
+      (ok (var-get count))))
+  (define-read-only (get-count)
+    (var-get count))
+";
+
+#[test]
+fn scenario_runner() {
+    let mut conf = super::new_test_conf();
+    conf.burnchain.commit_anchor_block_within = 5000;
+
+    let scenario_dir = format!("{}-scenario", &conf.node.working_dir);
+    fs::create_dir_all(&scenario_dir).unwrap();
+    fs::write(format!("{}/counter.clar", &scenario_dir), SCENARIO_CONTRACT).unwrap();
+
+    let scenario_path = format!("{}/scenario.toml", &scenario_dir);
+    let scenario = format!(
+        r#"
+[[accounts]]
+name = "alice"
+secret_key = "{}"
+balance = 100000000
+
+[[accounts]]
+name = "bob"
+secret_key = "{}"
+balance = 0
+
+[[steps]]
+[[steps.deploy]]
+sender = "alice"
+name = "counter"
+path = "counter.clar"
+
+[[steps]]
+blocks = 2
+[[steps.call]]
+sender = "alice"
+contract = "alice.counter"
+function = "increment"
+args = ["u2"]
+expect = "(ok u2)"
+[[steps.transfer]]
+sender = "alice"
+recipient = "bob"
+amount = 1000
+[[steps.read]]
+contract = "alice.counter"
+expression = "(get-count)"
+expect = "u2"
+[[steps.read]]
+contract = "alice.counter"
+expression = "(stx-get-balance '{})"
+expect = "u1000"
+[[steps.event]]
+contract = "alice.counter"
+value = "{{ event: \"increment\", count: u2 }}"
+
+[[steps]]
+[[steps.call]]
+sender = "alice"
+contract = "alice.counter"
+function = "increment"
+args = ["u1"]
+expect = "(ok u4)"
+[[steps.read]]
+contract = "alice.counter"
+expression = "(get-count)"
+expect = "u3"
+[[steps.event]]
+contract = "alice.counter"
+value = "{{ event: \"increment\", count: u2 }}"
+"#,
+        SK_1,
+        SK_2,
+        to_addr(&StacksPrivateKey::from_hex(SK_2).unwrap())
+    );
+    fs::write(&scenario_path, scenario).unwrap();
+
+    let report = Scenario::from_path(&scenario_path)
+        .unwrap()
+        .run(conf)
+        .unwrap();
+    eprintln!("{}", serde_json::to_string_pretty(&report).unwrap());
+
+    assert_eq!(report.blocks, 4);
+    assert_eq!(
+        report.accounts["alice"],
+        to_addr(&StacksPrivateKey::from_hex(SK_1).unwrap()).to_string()
+    );
+    // only the wrong expectations of the last step fail
+    assert!(!report.passed);
+    assert_eq!(
+        report
+            .failures
+            .iter()
+            .map(|failure| (failure.step, failure.check.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (2, "call alice.counter increment"),
+            (2, "event alice.counter { event: \"increment\", count: u2 }"),
+        ]
+    );
+    assert_eq!(report.failures[0].message, "Expected (ok u4), got (ok u3)");
+}